@@ -0,0 +1,121 @@
+use agent_runtime::llm::types::{FunctionCall, ToolCall};
+use agent_runtime::workflow::{StepStatus, WorkflowRun, WorkflowStepRecord};
+use agent_runtime::{persist, ChatMessage, PersistFormat, WorkflowContext, WorkflowState};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use serde_json::json;
+
+/// A long conversation with tool traffic, like a research agent's context
+fn context_300_messages() -> WorkflowContext {
+    let mut context = WorkflowContext::new();
+    let mut history = vec![ChatMessage::system("You are a careful research assistant.")];
+    for i in 0..299 {
+        let message = match i % 3 {
+            0 => ChatMessage::user(format!(
+                "Question {}: summarize what is known about topic {} in a paragraph.",
+                i, i
+            )),
+            1 => ChatMessage::assistant_with_tool_calls(
+                "",
+                vec![ToolCall {
+                    id: format!("call_{}", i),
+                    r#type: "function".to_string(),
+                    function: FunctionCall {
+                        name: "search".to_string(),
+                        arguments: json!({ "query": format!("topic {}", i), "limit": 5 })
+                            .to_string(),
+                    },
+                }],
+            ),
+            _ => {
+                ChatMessage::tool_result(format!("call_{}", i - 1), "Found 5 results. ".repeat(12))
+            }
+        };
+        history.push(message.with_provenance("researcher", "bench"));
+    }
+    context.set_history(history);
+    context
+}
+
+/// A completed run whose steps pass structured data along
+fn run_50_steps() -> WorkflowRun {
+    let steps = (0..50)
+        .map(|i| WorkflowStepRecord {
+            step_index: i,
+            step_name: format!("step_{}", i),
+            step_type: "Transform".to_string(),
+            input: json!({ "items": (0..20).collect::<Vec<_>>(), "note": "x".repeat(64) }),
+            output: Some(json!({ "score": i as f64 * 0.5, "tags": ["a", "b", "c"], "ok": true })),
+            execution_time_ms: Some(i as u64 * 3),
+            replayed: false,
+            critic: None,
+            status: StepStatus::Succeeded,
+            error: None,
+            handoffs: None,
+            started_at: None,
+            finished_at: None,
+            artifacts: Vec::new(),
+        })
+        .collect();
+    WorkflowRun {
+        workflow_id: "bench".to_string(),
+        run_id: "run_bench".to_string(),
+        state: WorkflowState::Completed,
+        steps,
+        final_output: Some(json!({ "done": true })),
+        parent_workflow_id: None,
+        artifacts: Vec::new(),
+        pii_findings: None,
+        trace: None,
+        usage: Default::default(),
+        usage_breakdown: Default::default(),
+        rerun_of: None,
+        failure: None,
+        approvals: Vec::new(),
+        sub_workflows: Vec::new(),
+    }
+}
+
+const FORMATS: [PersistFormat; 4] = [
+    PersistFormat::Json,
+    PersistFormat::JsonGzip,
+    PersistFormat::Cbor,
+    PersistFormat::MessagePack,
+];
+
+fn bench_context_formats(c: &mut Criterion) {
+    let context = context_300_messages();
+    let mut group = c.benchmark_group("persist_context_300_messages");
+    for format in FORMATS {
+        let bytes = context.export(format).unwrap();
+        println!("context, 300 messages, {}: {} bytes", format, bytes.len());
+
+        group.bench_with_input(BenchmarkId::new("encode", format), &format, |b, f| {
+            b.iter(|| black_box(context.export(*f).unwrap()))
+        });
+        group.bench_with_input(BenchmarkId::new("decode", format), &bytes, |b, bytes| {
+            b.iter(|| black_box(WorkflowContext::import(bytes).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+fn bench_run_formats(c: &mut Criterion) {
+    let run = run_50_steps();
+    let mut group = c.benchmark_group("persist_run_50_steps");
+    for format in FORMATS {
+        let bytes = run.export(format).unwrap();
+        println!("run, 50 steps, {}: {} bytes", format, bytes.len());
+
+        group.bench_with_input(BenchmarkId::new("encode", format), &format, |b, f| {
+            b.iter(|| black_box(run.export(*f).unwrap()))
+        });
+        group.bench_with_input(BenchmarkId::new("decode", format), &bytes, |b, bytes| {
+            b.iter(|| black_box(persist::decode::<WorkflowRun>(bytes).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_context_formats, bench_run_formats);
+
+criterion_main!(benches);
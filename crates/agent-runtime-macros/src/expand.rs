@@ -0,0 +1,411 @@
+//! Parsing, validation and expansion for `workflow!`.
+
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, quote_spanned};
+use std::collections::HashMap;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{braced, bracketed, Expr, Ident, LitStr, Token};
+
+/// Parse `input`, validate it and expand it to builder calls
+pub(crate) fn expand(input: TokenStream) -> syn::Result<TokenStream> {
+    let def: WorkflowDef = syn::parse2(input)?;
+    def.validate()?;
+    Ok(def.to_tokens())
+}
+
+/// `error` as `compile_error!`s in a block, so that several of them still
+/// expand to a single expression
+pub(crate) fn compile_errors(error: syn::Error) -> TokenStream {
+    let errors = error.into_compile_error();
+    quote! {{ #errors }}
+}
+
+struct WorkflowDef {
+    name: Option<LitStr>,
+    input: Option<Expr>,
+    labels: Vec<LitStr>,
+    steps: Vec<StepDef>,
+}
+
+enum StepKind {
+    Agent(Expr),
+    Transform(Expr),
+    SubWorkflow(Expr),
+    Conditional {
+        when: Expr,
+        then: Box<StepDef>,
+        otherwise: Box<StepDef>,
+    },
+}
+
+struct StepDef {
+    kind_span: Span,
+    name: LitStr,
+    kind: StepKind,
+}
+
+impl Parse for WorkflowDef {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut name = None;
+        let mut workflow_input = None;
+        let mut labels = Vec::new();
+        let mut steps = None;
+
+        while !input.is_empty() {
+            let key: Ident = input.parse()?;
+            input.parse::<Token![:]>()?;
+            match key.to_string().as_str() {
+                "name" => name = Some(input.parse()?),
+                "input" => workflow_input = Some(input.parse()?),
+                "labels" => {
+                    let content;
+                    bracketed!(content in input);
+                    labels = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?
+                        .into_iter()
+                        .collect();
+                }
+                "steps" => {
+                    let content;
+                    let brackets = bracketed!(content in input);
+                    let parsed: Vec<StepDef> =
+                        Punctuated::<StepDef, Token![,]>::parse_terminated(&content)?
+                            .into_iter()
+                            .collect();
+                    if parsed.is_empty() {
+                        return Err(syn::Error::new(
+                            brackets.span.join(),
+                            "workflow has no steps",
+                        ));
+                    }
+                    steps = Some(parsed);
+                }
+                other => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        format!(
+                            "unknown workflow field `{}`; expected `name`, `input`, `labels` or `steps`",
+                            other
+                        ),
+                    ))
+                }
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        let steps = steps.ok_or_else(|| {
+            syn::Error::new(Span::call_site(), "workflow is missing `steps: [...]`")
+        })?;
+        Ok(Self {
+            name,
+            input: workflow_input,
+            labels,
+            steps,
+        })
+    }
+}
+
+impl Parse for StepDef {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let kind_ident: Ident = input.parse()?;
+        let name: LitStr = input.parse()?;
+        if name.value().is_empty() {
+            return Err(syn::Error::new(name.span(), "step name must not be empty"));
+        }
+
+        let kind = match kind_ident.to_string().as_str() {
+            "agent" | "transform" | "subworkflow" => {
+                input.parse::<Token![=>]>()?;
+                let expr: Expr = input.parse()?;
+                match kind_ident.to_string().as_str() {
+                    "agent" => StepKind::Agent(expr),
+                    "transform" => StepKind::Transform(expr),
+                    _ => StepKind::SubWorkflow(expr),
+                }
+            }
+            "conditional" => parse_conditional(input, &name)?,
+            other => {
+                return Err(syn::Error::new(
+                    kind_ident.span(),
+                    format!(
+                        "unknown step kind `{}`; expected `agent`, `transform`, `subworkflow` or `conditional`",
+                        other
+                    ),
+                ))
+            }
+        };
+
+        Ok(Self {
+            kind_span: kind_ident.span(),
+            name,
+            kind,
+        })
+    }
+}
+
+/// `conditional "name" { when: <expr>, then: <step>, else: <step> }`
+fn parse_conditional(input: ParseStream, name: &LitStr) -> syn::Result<StepKind> {
+    let content;
+    braced!(content in input);
+
+    let mut when = None;
+    let mut then = None;
+    let mut otherwise = None;
+    while !content.is_empty() {
+        // `else` is a keyword, so it can't be parsed as a plain identifier
+        let key_span = content.span();
+        let key = if content.peek(Token![else]) {
+            content.parse::<Token![else]>()?;
+            "else".to_string()
+        } else {
+            content.parse::<Ident>()?.to_string()
+        };
+        content.parse::<Token![:]>()?;
+        match key.as_str() {
+            "when" => when = Some(content.parse::<Expr>()?),
+            "then" => then = Some(Box::new(content.parse::<StepDef>()?)),
+            "else" => otherwise = Some(Box::new(content.parse::<StepDef>()?)),
+            other => {
+                return Err(syn::Error::new(
+                    key_span,
+                    format!(
+                        "unknown conditional field `{}`; expected `when`, `then` or `else`",
+                        other
+                    ),
+                ))
+            }
+        }
+        if !content.is_empty() {
+            content.parse::<Token![,]>()?;
+        }
+    }
+
+    let missing = |what: &str| {
+        syn::Error::new(
+            name.span(),
+            format!(
+                "conditional step `{}` is missing its `{}` {}",
+                name.value(),
+                what,
+                if what == "when" {
+                    "condition"
+                } else {
+                    "branch"
+                }
+            ),
+        )
+    };
+    Ok(StepKind::Conditional {
+        when: when.ok_or_else(|| missing("when"))?,
+        then: then.ok_or_else(|| missing("then"))?,
+        otherwise: otherwise.ok_or_else(|| missing("else"))?,
+    })
+}
+
+impl WorkflowDef {
+    /// Step names, including conditional branches, must be unique
+    fn validate(&self) -> syn::Result<()> {
+        fn visit<'a>(
+            step: &'a StepDef,
+            seen: &mut HashMap<String, &'a LitStr>,
+            errors: &mut Option<syn::Error>,
+        ) {
+            let value = step.name.value();
+            if let Some(first) = seen.get(&value) {
+                let mut error =
+                    syn::Error::new(step.name.span(), format!("duplicate step name `{}`", value));
+                error.combine(syn::Error::new(
+                    first.span(),
+                    format!("step `{}` first defined here", value),
+                ));
+                match errors {
+                    Some(errors) => errors.combine(error),
+                    None => *errors = Some(error),
+                }
+            } else {
+                seen.insert(value, &step.name);
+            }
+            if let StepKind::Conditional {
+                then, otherwise, ..
+            } = &step.kind
+            {
+                visit(then, seen, errors);
+                visit(otherwise, seen, errors);
+            }
+        }
+
+        let mut seen = HashMap::new();
+        let mut errors = None;
+        for step in &self.steps {
+            visit(step, &mut seen, &mut errors);
+        }
+        errors.map_or(Ok(()), Err)
+    }
+
+    fn to_tokens(&self) -> TokenStream {
+        let name = self
+            .name
+            .as_ref()
+            .map(|name| quote! { .name(#name.to_string()) });
+        let labels = self.labels.iter().map(|label| quote! { .label(#label) });
+        let steps = self.steps.iter().map(|step| {
+            let step = step.to_tokens();
+            quote! { .add_step(#step) }
+        });
+        let input = self
+            .input
+            .as_ref()
+            .map(|input| quote! { .initial_input(#input) });
+
+        quote! {
+            ::agent_runtime::Workflow::builder()
+                #name
+                #(#labels)*
+                #(#steps)*
+                #input
+                .build()
+        }
+    }
+}
+
+impl StepDef {
+    /// Boxed step, spanned to its kind keyword so type errors point there
+    fn to_tokens(&self) -> TokenStream {
+        let name = &self.name;
+        let span = self.kind_span;
+        match &self.kind {
+            StepKind::Agent(agent) => quote_spanned! {span=>
+                ::std::boxed::Box::new(::agent_runtime::AgentStep::from_agent(
+                    #agent,
+                    #name.to_string(),
+                ))
+            },
+            StepKind::Transform(transform) => quote_spanned! {span=>
+                ::std::boxed::Box::new(::agent_runtime::TransformStep::new(
+                    #name.to_string(),
+                    #transform,
+                ))
+            },
+            StepKind::SubWorkflow(builder) => quote_spanned! {span=>
+                ::std::boxed::Box::new(::agent_runtime::SubWorkflowStep::new(
+                    #name.to_string(),
+                    #builder,
+                ))
+            },
+            StepKind::Conditional {
+                when,
+                then,
+                otherwise,
+            } => {
+                let then = then.to_tokens();
+                let otherwise = otherwise.to_tokens();
+                quote_spanned! {span=>
+                    ::std::boxed::Box::new(::agent_runtime::ConditionalStep::new(
+                        #name.to_string(),
+                        #when,
+                        #then,
+                        #otherwise,
+                    ))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(input: TokenStream) -> String {
+        match expand(input) {
+            Ok(tokens) => panic!("expected an error, got {}", tokens),
+            Err(e) => e
+                .into_iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+                .join("; "),
+        }
+    }
+
+    #[test]
+    fn test_expands_to_builder_chain() {
+        let expanded = expand(quote! {
+            name: "triage",
+            labels: ["tenant:acme"],
+            input: json!("server down"),
+            steps: [
+                agent "classifier" => classifier,
+                conditional "route" {
+                    when: |data| data["urgent"] == true,
+                    then: agent "pager" => pager,
+                    else: transform "ticket" => |data| data,
+                },
+                subworkflow "follow_up" => follow_up,
+            ],
+        })
+        .unwrap();
+
+        let hand_written = quote! {
+            ::agent_runtime::Workflow::builder()
+                .name("triage".to_string())
+                .label("tenant:acme")
+                .add_step(::std::boxed::Box::new(::agent_runtime::AgentStep::from_agent(
+                    classifier,
+                    "classifier".to_string(),
+                )))
+                .add_step(::std::boxed::Box::new(::agent_runtime::ConditionalStep::new(
+                    "route".to_string(),
+                    |data| data["urgent"] == true,
+                    ::std::boxed::Box::new(::agent_runtime::AgentStep::from_agent(
+                        pager,
+                        "pager".to_string(),
+                    )),
+                    ::std::boxed::Box::new(::agent_runtime::TransformStep::new(
+                        "ticket".to_string(),
+                        |data| data,
+                    )),
+                )))
+                .add_step(::std::boxed::Box::new(::agent_runtime::SubWorkflowStep::new(
+                    "follow_up".to_string(),
+                    follow_up,
+                )))
+                .initial_input(json!("server down"))
+                .build()
+        };
+        assert_eq!(expanded.to_string(), hand_written.to_string());
+    }
+
+    #[test]
+    fn test_rejects_invalid_definitions() {
+        assert_eq!(
+            error(quote! { steps: [agent "a" => x, transform "b" => f, agent "a" => y] }),
+            "duplicate step name `a`; step `a` first defined here"
+        );
+        assert_eq!(
+            error(quote! { steps: [conditional "c" {
+                when: |_| true,
+                then: agent "c" => x,
+                else: agent "d" => y,
+            }] }),
+            "duplicate step name `c`; step `c` first defined here"
+        );
+        assert_eq!(
+            error(quote! { steps: [conditional "c" { when: |_| true, then: agent "a" => x }] }),
+            "conditional step `c` is missing its `else` branch"
+        );
+        assert_eq!(error(quote! { steps: [] }), "workflow has no steps");
+        assert_eq!(
+            error(quote! { name: "x" }),
+            "workflow is missing `steps: [...]`"
+        );
+        assert_eq!(
+            error(quote! { steps: [agent "" => x] }),
+            "step name must not be empty"
+        );
+        assert!(
+            error(quote! { steps: [router "r" => x] }).starts_with("unknown step kind `router`")
+        );
+    }
+}
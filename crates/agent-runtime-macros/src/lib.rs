@@ -0,0 +1,44 @@
+//! Compile-time checked workflow definitions for `agent-runtime`.
+//!
+//! Use through `agent_runtime::workflow!` (the `macros` feature). The macro
+//! expands to plain `WorkflowBuilder` calls, so the generated code only uses
+//! the public API. The compile errors are covered by `tests/ui` in
+//! `agent-runtime`.
+
+use proc_macro::TokenStream;
+
+mod expand;
+
+/// Declare a workflow whose steps are checked at compile time.
+///
+/// Expands to the equivalent `Workflow::builder()` chain. Step names,
+/// including those inside conditional branches, must be unique; a workflow
+/// needs at least one step; a `conditional` needs `when`, `then` and `else`.
+/// Errors point at the offending step name.
+///
+/// ```ignore
+/// use agent_runtime::{workflow, Agent, AgentConfig};
+/// use serde_json::json;
+///
+/// let agent = |name: &str| Agent::new(AgentConfig::builder(name).build());
+/// let wf = workflow! {
+///     name: "triage",
+///     labels: ["tenant:acme"],
+///     input: json!("server down"),
+///     steps: [
+///         agent "classifier" => agent("classifier"),
+///         conditional "route" {
+///             when: |data| data["urgent"] == true,
+///             then: agent "pager" => agent("pager"),
+///             else: transform "ticket" => |data| data,
+///         },
+///     ],
+/// };
+/// assert_eq!(wf.steps.len(), 2);
+/// ```
+#[proc_macro]
+pub fn workflow(input: TokenStream) -> TokenStream {
+    expand::expand(input.into())
+        .unwrap_or_else(expand::compile_errors)
+        .into()
+}
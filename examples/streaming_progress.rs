@@ -0,0 +1,79 @@
+//! Live progress for a workflow run, the way a terminal UI would show it.
+//!
+//! Runs offline against a scripted LLM:
+//!
+//! ```text
+//! cargo run --example streaming_progress --features workflow
+//! ```
+
+use std::io::Write;
+use std::sync::Arc;
+
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::*;
+use futures::StreamExt;
+use serde_json::json;
+
+#[tokio::main]
+async fn main() {
+    let mut registry = ToolRegistry::new();
+    registry.register(NativeTool::new(
+        "search",
+        "Search the crate index",
+        json!({"type": "object", "properties": {"q": {"type": "string"}}}),
+        |_params| async move {
+            Ok(ToolResult::success(
+                json!({"crates": ["tokio", "serde", "reqwest"]}),
+                1.0,
+            ))
+        },
+    ));
+    let llm = MockLlmClient::new()
+        .with_tool_call("search", json!({"q": "popular rust crates"}))
+        .with_response("The most popular crates are tokio, serde and reqwest.");
+    let researcher = Agent::new(
+        AgentConfig::builder("researcher")
+            .system_prompt("You research Rust crates.")
+            .tools(Arc::new(registry))
+            .build(),
+    )
+    .with_client(Arc::new(llm));
+
+    let workflow = Workflow::builder()
+        .name("crate_report".to_string())
+        .step(Box::new(AgentStep::from_agent(
+            researcher,
+            "research".to_string(),
+        )))
+        .step(Box::new(TransformStep::new(
+            "headline".to_string(),
+            |v| json!({ "headline": v["response"] }),
+        )))
+        .initial_input(json!("Which Rust crates are most popular?"))
+        .build();
+
+    let runtime = Runtime::new();
+    let mut updates = runtime.execute_streaming(workflow);
+    while let Some(update) = updates.next().await {
+        match update {
+            WorkflowUpdate::StepStarted {
+                step_index,
+                step_name,
+            } => println!("▶ step {} `{}`", step_index + 1, step_name),
+            WorkflowUpdate::LlmChunk { text, .. } => {
+                print!("{}", text);
+                let _ = std::io::stdout().flush();
+            }
+            WorkflowUpdate::ToolCall { name, args } => println!("  ⚙ {}({})", name, args),
+            WorkflowUpdate::StepCompleted { step_name, .. } => {
+                println!("\n✔ `{}` done", step_name)
+            }
+            WorkflowUpdate::WorkflowCompleted { run } => println!(
+                "\nFinished in {} steps: {}",
+                run.steps.len(),
+                run.final_output.unwrap_or_default()
+            ),
+            WorkflowUpdate::WorkflowFailed { error, .. } => println!("\n✘ {}", error),
+        }
+    }
+}
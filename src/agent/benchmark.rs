@@ -0,0 +1,669 @@
+//! Compare models on a suite of graded tasks.
+//!
+//! [`ModelBenchmark`] runs every task against every named client, with a
+//! fresh agent built from the same [`AgentConfig`] template for each cell
+//! (model × task × repetition), and summarizes the results as a
+//! [`BenchmarkReport`]: accuracy, latency percentiles, token usage, cost and
+//! refusal/parse-failure counts per model, plus a per-task breakdown.
+//!
+//! With a checkpoint file, each finished cell is appended as a JSON line and
+//! cells already in the file are skipped, so an interrupted benchmark picks
+//! up where it stopped.
+
+use crate::agent::{Agent, AgentConfig};
+use crate::llm::types::{ChatResponse, Usage};
+use crate::llm::{
+    AppliedEffort, ChatMessage, ChatRequest, Effort, GenericChatClient, LlmClient, LlmResult,
+};
+use crate::types::AgentInput;
+use crate::usage::CostPerMToken;
+use async_trait::async_trait;
+use futures::StreamExt;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// How a task's response is graded
+#[derive(Clone)]
+pub enum Grader {
+    /// Trimmed response equals the expected text
+    ExactMatch(String),
+    /// Response matches the pattern anywhere
+    Regex(Regex),
+    /// Response parses as JSON and the value at `pointer` equals `expected`
+    JsonField {
+        pointer: String,
+        expected: JsonValue,
+    },
+    /// A judge model answers PASS or FAIL against `rubric`
+    Judge { client: LlmClient, rubric: String },
+}
+
+impl Grader {
+    pub fn exact(expected: impl Into<String>) -> Self {
+        Grader::ExactMatch(expected.into())
+    }
+
+    /// Panics if `pattern` is not a valid regex
+    pub fn regex(pattern: &str) -> Self {
+        Grader::Regex(Regex::new(pattern).expect("invalid grader regex"))
+    }
+
+    pub fn json_field(pointer: impl Into<String>, expected: JsonValue) -> Self {
+        Grader::JsonField {
+            pointer: pointer.into(),
+            expected,
+        }
+    }
+
+    pub fn judge(client: LlmClient, rubric: impl Into<String>) -> Self {
+        Grader::Judge {
+            client,
+            rubric: rubric.into(),
+        }
+    }
+
+    async fn grade(&self, input: &JsonValue, response: &str) -> Grade {
+        match self {
+            Grader::ExactMatch(expected) => Grade::pass_if(response.trim() == expected.trim()),
+            Grader::Regex(pattern) => Grade::pass_if(pattern.is_match(response)),
+            Grader::JsonField { pointer, expected } => match parse_json(response) {
+                Some(value) => Grade::pass_if(value.pointer(pointer) == Some(expected)),
+                None => Grade {
+                    correct: false,
+                    parse_failure: true,
+                    error: None,
+                },
+            },
+            Grader::Judge { client, rubric } => {
+                let input = match input {
+                    JsonValue::String(text) => text.clone(),
+                    other => other.to_string(),
+                };
+                let request = ChatRequest::new(vec![
+                    ChatMessage::system(JUDGE_PROMPT),
+                    ChatMessage::user(format!(
+                        "Task:\n{}\n\nRubric:\n{}\n\nResponse:\n{}",
+                        input, rubric, response
+                    )),
+                ])
+                .with_temperature(0.0)
+                .with_max_tokens(8);
+                match client.chat(request).await {
+                    Ok(verdict) => Grade::pass_if(
+                        verdict
+                            .content
+                            .trim_start()
+                            .to_ascii_uppercase()
+                            .starts_with("PASS"),
+                    ),
+                    Err(e) => Grade {
+                        correct: false,
+                        parse_failure: false,
+                        error: Some(format!("judge failed: {}", e)),
+                    },
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Grader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Grader::ExactMatch(expected) => f.debug_tuple("ExactMatch").field(expected).finish(),
+            Grader::Regex(pattern) => f.debug_tuple("Regex").field(&pattern.as_str()).finish(),
+            Grader::JsonField { pointer, expected } => f
+                .debug_struct("JsonField")
+                .field("pointer", pointer)
+                .field("expected", expected)
+                .finish(),
+            Grader::Judge { rubric, .. } => {
+                f.debug_struct("Judge").field("rubric", rubric).finish()
+            }
+        }
+    }
+}
+
+const JUDGE_PROMPT: &str = "You grade responses to tasks. Decide whether the response \
+satisfies the rubric. Reply with exactly PASS or FAIL.";
+
+struct Grade {
+    correct: bool,
+    parse_failure: bool,
+    error: Option<String>,
+}
+
+impl Grade {
+    fn pass_if(correct: bool) -> Self {
+        Self {
+            correct,
+            parse_failure: false,
+            error: None,
+        }
+    }
+}
+
+/// Parse a JSON response, tolerating code fences and surrounding prose
+pub(super) fn parse_json(response: &str) -> Option<JsonValue> {
+    let trimmed = response.trim();
+    serde_json::from_str(trimmed).ok().or_else(|| {
+        let start = trimmed.find(['{', '['])?;
+        let end = trimmed.rfind(['}', ']'])?;
+        (start < end)
+            .then(|| serde_json::from_str(&trimmed[start..=end]).ok())
+            .flatten()
+    })
+}
+
+const REFUSAL_PREFIXES: &[&str] = &[
+    "i can't",
+    "i cannot",
+    "i can not",
+    "i won't",
+    "i'm sorry",
+    "i am sorry",
+    "i'm unable",
+    "i am unable",
+    "sorry, i",
+];
+
+fn is_refusal(response: &str) -> bool {
+    let start = response.trim_start().to_lowercase().replace('’', "'");
+    REFUSAL_PREFIXES
+        .iter()
+        .any(|prefix| start.starts_with(prefix))
+}
+
+/// One task in the suite
+#[derive(Debug, Clone)]
+pub struct BenchmarkTask {
+    pub id: String,
+    pub input: JsonValue,
+    pub grader: Grader,
+}
+
+impl BenchmarkTask {
+    pub fn new(id: impl Into<String>, input: impl Into<JsonValue>, grader: Grader) -> Self {
+        Self {
+            id: id.into(),
+            input: input.into(),
+            grader,
+        }
+    }
+}
+
+/// Result of one model × task × repetition
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CellResult {
+    pub model: String,
+    pub task_id: String,
+    pub repetition: usize,
+    pub correct: bool,
+    pub refusal: bool,
+    pub parse_failure: bool,
+
+    /// Agent (or judge) error; the cell counts as incorrect
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    pub latency_ms: f64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+
+    /// `None` when the model has no price
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+
+    pub response: String,
+}
+
+impl CellResult {
+    fn key(&self) -> (String, String, usize) {
+        (self.model.clone(), self.task_id.clone(), self.repetition)
+    }
+}
+
+/// Per-model summary
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelSummary {
+    pub model: String,
+    pub runs: usize,
+    pub correct: usize,
+    /// `correct / runs`
+    pub accuracy: f64,
+    pub latency_p50_ms: f64,
+    pub latency_p90_ms: f64,
+    pub latency_p99_ms: f64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// `None` when the model has no price
+    pub cost_usd: Option<f64>,
+    pub refusals: usize,
+    pub parse_failures: usize,
+    pub errors: usize,
+}
+
+/// How each model did on one task
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskBreakdown {
+    pub task_id: String,
+    /// `(model, correct, runs)`, in model order
+    pub scores: Vec<(String, usize, usize)>,
+}
+
+/// Benchmark results
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    pub models: Vec<ModelSummary>,
+    pub tasks: Vec<TaskBreakdown>,
+    /// Every cell, ordered by model, task and repetition
+    pub cells: Vec<CellResult>,
+    /// Cells loaded from the checkpoint instead of run
+    pub resumed_cells: usize,
+}
+
+impl BenchmarkReport {
+    pub fn model(&self, name: &str) -> Option<&ModelSummary> {
+        self.models.iter().find(|m| m.model == name)
+    }
+
+    /// Comparison table per model, then correct/runs per task
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from(
+            "| Model | Accuracy | p50 ms | p90 ms | p99 ms | Tokens | Cost | Refusals | Parse failures | Errors |\n\
+             |---|---|---|---|---|---|---|---|---|---|\n",
+        );
+        for m in &self.models {
+            out.push_str(&format!(
+                "| {} | {:.1}% ({}/{}) | {:.0} | {:.0} | {:.0} | {} | {} | {} | {} | {} |\n",
+                m.model,
+                m.accuracy * 100.0,
+                m.correct,
+                m.runs,
+                m.latency_p50_ms,
+                m.latency_p90_ms,
+                m.latency_p99_ms,
+                m.prompt_tokens + m.completion_tokens,
+                m.cost_usd
+                    .map_or_else(|| "n/a".to_string(), |c| format!("${:.4}", c)),
+                m.refusals,
+                m.parse_failures,
+                m.errors,
+            ));
+        }
+
+        out.push_str("\n| Task |");
+        for m in &self.models {
+            out.push_str(&format!(" {} |", m.model));
+        }
+        out.push_str("\n|---|");
+        out.push_str(&"---|".repeat(self.models.len()));
+        out.push('\n');
+        for task in &self.tasks {
+            out.push_str(&format!("| {} |", task.task_id));
+            for (_, correct, runs) in &task.scores {
+                out.push_str(&format!(" {}/{} |", correct, runs));
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Runs a task suite against several models
+pub struct ModelBenchmark {
+    template: AgentConfig,
+    tasks: Vec<BenchmarkTask>,
+    models: Vec<(String, LlmClient)>,
+    prices: HashMap<String, CostPerMToken>,
+    repetitions: usize,
+    concurrency: usize,
+    seed: Option<u64>,
+    checkpoint: Option<PathBuf>,
+}
+
+impl ModelBenchmark {
+    pub fn new(agent_template: AgentConfig, tasks: Vec<BenchmarkTask>) -> Self {
+        Self {
+            template: agent_template,
+            tasks,
+            models: Vec::new(),
+            prices: HashMap::new(),
+            repetitions: 1,
+            concurrency: 1,
+            seed: None,
+            checkpoint: None,
+        }
+    }
+
+    /// Add a model under a display name
+    pub fn model(mut self, name: impl Into<String>, client: LlmClient) -> Self {
+        self.models.push((name.into(), client));
+        self
+    }
+
+    /// Price the model registered as `name`
+    pub fn price(mut self, name: impl Into<String>, price: CostPerMToken) -> Self {
+        self.prices.insert(name.into(), price);
+        self
+    }
+
+    /// Run each task this many times per model (default: 1)
+    pub fn repetitions(mut self, repetitions: usize) -> Self {
+        self.repetitions = repetitions.max(1);
+        self
+    }
+
+    /// Run up to this many cells at once (default: 1)
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Send a fixed sampling seed, for providers that support one
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Append finished cells to `path` (JSON lines) and skip cells already
+    /// recorded there
+    pub fn checkpoint_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.checkpoint = Some(path.into());
+        self
+    }
+
+    /// Run every pending cell and summarize all of them
+    pub async fn run(&self) -> std::io::Result<BenchmarkReport> {
+        let resumed = self.load_checkpoint()?;
+        let done: HashSet<_> = resumed.iter().map(CellResult::key).collect();
+        let resumed_cells = resumed.len();
+
+        let mut pending = Vec::new();
+        for (model, client) in &self.models {
+            for task in &self.tasks {
+                for repetition in 0..self.repetitions {
+                    if !done.contains(&(model.clone(), task.id.clone(), repetition)) {
+                        pending.push((model, client, task, repetition));
+                    }
+                }
+            }
+        }
+
+        let writer = match &self.checkpoint {
+            Some(path) => {
+                let mut file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)?;
+                // Terminate a line cut short by an interruption so the next
+                // cell doesn't get glued onto it
+                if std::fs::read(path)?.last().is_some_and(|b| *b != b'\n') {
+                    writeln!(file)?;
+                }
+                Some(Mutex::new(file))
+            }
+            None => None,
+        };
+
+        let mut results = futures::stream::iter(pending)
+            .map(|(model, client, task, repetition)| self.run_cell(model, client, task, repetition))
+            .buffer_unordered(self.concurrency);
+
+        let mut cells = resumed;
+        while let Some(cell) = results.next().await {
+            if let Some(writer) = &writer {
+                let line = serde_json::to_string(&cell).map_err(std::io::Error::other)?;
+                writeln!(writer.lock().unwrap(), "{}", line)?;
+            }
+            cells.push(cell);
+        }
+
+        Ok(self.report(cells, resumed_cells))
+    }
+
+    fn load_checkpoint(&self) -> std::io::Result<Vec<CellResult>> {
+        let Some(path) = &self.checkpoint else {
+            return Ok(Vec::new());
+        };
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        // A line cut short by an interruption is simply run again
+        Ok(contents
+            .lines()
+            .filter_map(|line| serde_json::from_str::<CellResult>(line).ok())
+            .filter(|cell| {
+                self.models.iter().any(|(name, _)| *name == cell.model)
+                    && self.tasks.iter().any(|t| t.id == cell.task_id)
+                    && cell.repetition < self.repetitions
+            })
+            .collect())
+    }
+
+    async fn run_cell(
+        &self,
+        model: &str,
+        client: &LlmClient,
+        task: &BenchmarkTask,
+        repetition: usize,
+    ) -> CellResult {
+        let metered = Arc::new(MeteredClient {
+            inner: client.clone(),
+            usage: Mutex::default(),
+        });
+        let mut config = self.template.clone();
+        if self.seed.is_some() {
+            config.sampling.seed = self.seed;
+        }
+        let agent = Agent::new(config).with_client(metered.clone());
+
+        let started = Instant::now();
+        let result = agent
+            .execute(&AgentInput::from_value(task.input.clone()))
+            .await;
+        let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+        let (prompt_tokens, completion_tokens) = *metered.usage.lock().unwrap();
+
+        let (response, grade) = match result {
+            Ok(output) => {
+                let response = match &output.data["response"] {
+                    JsonValue::String(text) => text.clone(),
+                    other => other.to_string(),
+                };
+                let grade = task.grader.grade(&task.input, &response).await;
+                (response, grade)
+            }
+            Err(e) => (
+                String::new(),
+                Grade {
+                    correct: false,
+                    parse_failure: false,
+                    error: Some(e.to_string()),
+                },
+            ),
+        };
+
+        CellResult {
+            model: model.to_string(),
+            task_id: task.id.clone(),
+            repetition,
+            correct: grade.correct,
+            refusal: !grade.correct && is_refusal(&response),
+            parse_failure: grade.parse_failure,
+            error: grade.error,
+            latency_ms,
+            prompt_tokens,
+            completion_tokens,
+            cost_usd: self
+                .prices
+                .get(model)
+                .map(|price| price.cost(prompt_tokens, completion_tokens)),
+            response,
+        }
+    }
+
+    fn report(&self, mut cells: Vec<CellResult>, resumed_cells: usize) -> BenchmarkReport {
+        let model_index = |name: &str| self.models.iter().position(|(m, _)| m == name);
+        let task_index = |id: &str| self.tasks.iter().position(|t| t.id == id);
+        cells.sort_by_key(|c| (model_index(&c.model), task_index(&c.task_id), c.repetition));
+
+        let models = self
+            .models
+            .iter()
+            .map(|(name, _)| {
+                let mine: Vec<&CellResult> = cells.iter().filter(|c| c.model == *name).collect();
+                let mut latencies: Vec<f64> = mine.iter().map(|c| c.latency_ms).collect();
+                latencies.sort_by(|a, b| a.total_cmp(b));
+                let correct = mine.iter().filter(|c| c.correct).count();
+                ModelSummary {
+                    model: name.clone(),
+                    runs: mine.len(),
+                    correct,
+                    accuracy: if mine.is_empty() {
+                        0.0
+                    } else {
+                        correct as f64 / mine.len() as f64
+                    },
+                    latency_p50_ms: percentile(&latencies, 0.50),
+                    latency_p90_ms: percentile(&latencies, 0.90),
+                    latency_p99_ms: percentile(&latencies, 0.99),
+                    prompt_tokens: mine.iter().map(|c| c.prompt_tokens).sum(),
+                    completion_tokens: mine.iter().map(|c| c.completion_tokens).sum(),
+                    cost_usd: self
+                        .prices
+                        .contains_key(name)
+                        .then(|| mine.iter().filter_map(|c| c.cost_usd).sum()),
+                    refusals: mine.iter().filter(|c| c.refusal).count(),
+                    parse_failures: mine.iter().filter(|c| c.parse_failure).count(),
+                    errors: mine.iter().filter(|c| c.error.is_some()).count(),
+                }
+            })
+            .collect();
+
+        let tasks = self
+            .tasks
+            .iter()
+            .map(|task| TaskBreakdown {
+                task_id: task.id.clone(),
+                scores: self
+                    .models
+                    .iter()
+                    .map(|(name, _)| {
+                        let runs = cells
+                            .iter()
+                            .filter(|c| c.model == *name && c.task_id == task.id);
+                        let (correct, total) = runs.fold((0, 0), |(correct, total), c| {
+                            (correct + c.correct as usize, total + 1)
+                        });
+                        (name.clone(), correct, total)
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        BenchmarkReport {
+            models,
+            tasks,
+            cells,
+            resumed_cells,
+        }
+    }
+}
+
+/// Nearest-rank percentile of sorted values; 0 when empty
+pub(super) fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (sorted.len() as f64 * p).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Counts the tokens one cell's agent spends
+struct MeteredClient {
+    inner: LlmClient,
+    usage: Mutex<(u64, u64)>,
+}
+
+impl MeteredClient {
+    fn record(&self, usage: Option<&Usage>) {
+        if let Some(usage) = usage {
+            let mut totals = self.usage.lock().unwrap();
+            totals.0 += usage.prompt_tokens as u64;
+            totals.1 += usage.completion_tokens as u64;
+        }
+    }
+}
+
+#[async_trait]
+impl GenericChatClient for MeteredClient {
+    async fn chat(&self, request: ChatRequest) -> LlmResult<ChatResponse> {
+        let response = self.inner.chat(request).await?;
+        self.record(response.usage.as_ref());
+        Ok(response)
+    }
+
+    async fn chat_stream(
+        &self,
+        request: ChatRequest,
+        tx: mpsc::Sender<String>,
+    ) -> LlmResult<ChatResponse> {
+        let response = self.inner.chat_stream(request, tx).await?;
+        self.record(response.usage.as_ref());
+        Ok(response)
+    }
+
+    fn apply_effort(&self, request: &mut ChatRequest, effort: &Effort) -> AppliedEffort {
+        self.inner.apply_effort(request, effort)
+    }
+
+    fn provider_name(&self) -> &str {
+        self.inner.provider_name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_graders() {
+        let input = JsonValue::Null;
+        assert!(Grader::exact("42").grade(&input, " 42\n").await.correct);
+        assert!(
+            Grader::regex(r"\b42\b")
+                .grade(&input, "it is 42.")
+                .await
+                .correct
+        );
+
+        let field = Grader::json_field("/answer", serde_json::json!(42));
+        assert!(
+            field
+                .grade(&input, "```json\n{\"answer\": 42}\n```")
+                .await
+                .correct
+        );
+        let bad = field.grade(&input, "forty-two").await;
+        assert!(!bad.correct && bad.parse_failure);
+    }
+
+    #[test]
+    fn test_refusal_and_percentile() {
+        assert!(is_refusal("I’m sorry, I can't help with that"));
+        assert!(!is_refusal("Sorry to hear that; the answer is 4"));
+        assert_eq!(percentile(&[1.0, 2.0, 3.0, 4.0], 0.5), 2.0);
+        assert_eq!(percentile(&[1.0, 2.0, 3.0, 4.0], 0.99), 4.0);
+        assert_eq!(percentile(&[], 0.5), 0.0);
+    }
+}
@@ -0,0 +1,349 @@
+//! Soft budget signals: telling the agent it is running out of room before
+//! a hard limit cuts it off.
+//!
+//! With [`BudgetSignals`] configured, the agent measures three budgets before
+//! each LLM call: wall-clock time against `deadline`, iterations against
+//! `max_tool_iterations`, and tokens against an optional token budget. When
+//! the most-used of them crosses a threshold, a short notice describing
+//! what is left ("about 30 seconds, 2 model calls") goes to the model ahead
+//! of the call, a `system:budget` event is emitted and the signal is
+//! recorded in `AgentOutputMetadata::budget_signals`. Each threshold fires at
+//! most once; a jump past several fires only the highest.
+//!
+//! At the final threshold the agent can also degrade: tool calls are ruled
+//! out with `ToolChoice::None` and `max_tokens` is lowered to what is left
+//! of the token budget, so the next reply is a complete answer rather than
+//! another tool call. The tool definitions stay in the request, keeping the
+//! prefix providers cache.
+//!
+//! Notices are appended as user messages at the end of the conversation,
+//! never folded into the system prompt, so the prefix providers cache stays
+//! unchanged. They are dropped from the returned chat history.
+
+use crate::llm::types::{ChatMessage, Role, Usage};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Thresholds used unless configured otherwise
+pub const DEFAULT_THRESHOLDS: [f64; 2] = [0.7, 0.9];
+
+/// Smallest `max_tokens` a degraded request is given, so a nearly spent
+/// token budget still leaves room for an answer
+pub const MIN_DEGRADED_MAX_TOKENS: u32 = 256;
+
+/// Prefix of every notice, so the model (and readers of logs) can tell
+/// them apart from user turns
+const NOTICE_PREFIX: &str = "[Budget notice]";
+
+/// When and how to warn an agent about its remaining budget
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BudgetSignals {
+    /// Fractions of a budget, ascending, at which the agent is told what is
+    /// left
+    pub thresholds: Vec<f64>,
+
+    /// At the final threshold, rule out tool calls and fit `max_tokens` to
+    /// the remaining token budget. Default: true.
+    pub degrade_at_final: bool,
+
+    /// Tokens (prompt plus completion, over all LLM calls) the execution
+    /// should fit in. Only used for signaling; nothing is cut off.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_budget: Option<u32>,
+}
+
+impl Default for BudgetSignals {
+    fn default() -> Self {
+        Self {
+            thresholds: DEFAULT_THRESHOLDS.to_vec(),
+            degrade_at_final: true,
+            token_budget: None,
+        }
+    }
+}
+
+impl BudgetSignals {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Warn at these fractions instead; values outside (0, 1] are dropped
+    pub fn with_thresholds(mut self, thresholds: impl IntoIterator<Item = f64>) -> Self {
+        let mut thresholds: Vec<f64> = thresholds
+            .into_iter()
+            .filter(|t| *t > 0.0 && *t <= 1.0)
+            .collect();
+        thresholds.sort_by(f64::total_cmp);
+        thresholds.dedup();
+        self.thresholds = thresholds;
+        self
+    }
+
+    /// Also measure tokens against `tokens`
+    pub fn with_token_budget(mut self, tokens: u32) -> Self {
+        self.token_budget = Some(tokens);
+        self
+    }
+
+    /// Keep tools and `max_tokens` at the final threshold; only warn
+    pub fn without_degradation(mut self) -> Self {
+        self.degrade_at_final = false;
+        self
+    }
+}
+
+/// A budget the agent is measured against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetKind {
+    Time,
+    Iterations,
+    Tokens,
+}
+
+/// What was left when a signal fired
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BudgetRemaining {
+    /// Time until the deadline, if one is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_ms: Option<u64>,
+
+    /// LLM calls left, counting the one the notice precedes
+    pub iterations: usize,
+
+    /// Tokens left, if a token budget is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<u32>,
+}
+
+/// A budget notice delivered to the agent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BudgetSignal {
+    pub threshold: f64,
+
+    /// Iteration whose LLM call the notice was sent with
+    pub iteration: usize,
+
+    /// The budget closest to running out, and the fraction of it used
+    pub budget: BudgetKind,
+    pub used: f64,
+
+    pub remaining: BudgetRemaining,
+
+    /// Whether tool calls were ruled out from this point on
+    pub degraded: bool,
+
+    /// The notice as the model saw it
+    pub message: String,
+}
+
+/// Measures an execution against its budgets
+pub(crate) struct BudgetTracker<'a> {
+    config: &'a BudgetSignals,
+    deadline: Option<(Instant, Duration)>,
+    max_iterations: usize,
+    tokens_used: u32,
+    /// Index of the lowest threshold not yet crossed
+    next: usize,
+    degraded: bool,
+    signals: Vec<BudgetSignal>,
+}
+
+impl<'a> BudgetTracker<'a> {
+    /// `deadline` is the instant the execution must finish by and the
+    /// budget it was computed from
+    pub(crate) fn new(
+        config: &'a BudgetSignals,
+        deadline: Option<(Instant, Duration)>,
+        max_iterations: usize,
+    ) -> Self {
+        Self {
+            config,
+            deadline,
+            max_iterations,
+            tokens_used: 0,
+            next: 0,
+            degraded: false,
+            signals: Vec::new(),
+        }
+    }
+
+    pub(crate) fn record_usage(&mut self, usage: Option<&Usage>) {
+        if let Some(usage) = usage {
+            self.tokens_used = self.tokens_used.saturating_add(usage.total_tokens);
+        }
+    }
+
+    /// Measure before `iteration`'s LLM call, returning the signal to
+    /// deliver if a new threshold was crossed
+    pub(crate) fn check(&mut self, iteration: usize) -> Option<BudgetSignal> {
+        let used_iterations = iteration.saturating_sub(1);
+        let mut usage = vec![(
+            BudgetKind::Iterations,
+            used_iterations as f64 / self.max_iterations.max(1) as f64,
+        )];
+        let time_left = self.deadline.map(|(at, total)| {
+            let left = at.saturating_duration_since(Instant::now());
+            if !total.is_zero() {
+                usage.push((
+                    BudgetKind::Time,
+                    1.0 - left.as_secs_f64() / total.as_secs_f64(),
+                ));
+            }
+            left
+        });
+        let tokens_left = self.config.token_budget.map(|budget| {
+            usage.push((
+                BudgetKind::Tokens,
+                self.tokens_used as f64 / budget.max(1) as f64,
+            ));
+            budget.saturating_sub(self.tokens_used)
+        });
+
+        let (budget, used) = usage
+            .into_iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .expect("iterations are always measured");
+        let crossed = self.config.thresholds[self.next..]
+            .iter()
+            .rposition(|t| used >= *t)?
+            + self.next;
+        self.next = crossed + 1;
+
+        let is_final = crossed + 1 == self.config.thresholds.len();
+        self.degraded = is_final && self.config.degrade_at_final;
+        let remaining = BudgetRemaining {
+            time_ms: time_left.map(|d| d.as_millis() as u64),
+            iterations: self.max_iterations.saturating_sub(used_iterations),
+            tokens: tokens_left,
+        };
+        let signal = BudgetSignal {
+            threshold: self.config.thresholds[crossed],
+            iteration,
+            budget,
+            used,
+            message: notice(&remaining, is_final, self.degraded),
+            remaining,
+            degraded: self.degraded,
+        };
+        self.signals.push(signal.clone());
+        Some(signal)
+    }
+
+    /// Whether the final threshold has ruled out tool calls
+    pub(crate) fn degraded(&self) -> bool {
+        self.degraded
+    }
+
+    /// `max_tokens` for a degraded request: the current limit, lowered to
+    /// the remaining token budget if there is one
+    pub(crate) fn max_tokens(&self, current: Option<u32>) -> Option<u32> {
+        let Some(budget) = self.config.token_budget else {
+            return current;
+        };
+        let left = budget
+            .saturating_sub(self.tokens_used)
+            .max(MIN_DEGRADED_MAX_TOKENS);
+        Some(current.map_or(left, |c| c.min(left)))
+    }
+
+    /// Remove delivered notices from a conversation
+    pub(crate) fn strip_notices(&self, messages: &mut Vec<ChatMessage>) {
+        if self.signals.is_empty() {
+            return;
+        }
+        messages.retain(|m| {
+            !(m.role == Role::User
+                && m.content.text().starts_with(NOTICE_PREFIX)
+                && self.signals.iter().any(|s| m.content == s.message))
+        });
+    }
+
+    pub(crate) fn into_signals(self) -> Vec<BudgetSignal> {
+        self.signals
+    }
+}
+
+/// The notice for a crossing, e.g. "You have about 30 seconds and 2 model
+/// calls left. Start wrapping up ..."
+fn notice(remaining: &BudgetRemaining, is_final: bool, degraded: bool) -> String {
+    let mut parts = Vec::new();
+    if let Some(ms) = remaining.time_ms {
+        parts.push(match ms.div_ceil(1000) {
+            0 => "no time".to_string(),
+            1 => "about 1 second".to_string(),
+            secs => format!("about {} seconds", secs),
+        });
+    }
+    parts.push(match remaining.iterations {
+        1 => "1 model call".to_string(),
+        n => format!("{} model calls", n),
+    });
+    if let Some(tokens) = remaining.tokens {
+        parts.push(format!("about {} tokens", tokens));
+    }
+    let left = match parts.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+        None => unreachable!("model calls are always described"),
+    };
+
+    let advice = if degraded {
+        "Tools are no longer available; give your final answer now."
+    } else if is_final {
+        "Give your final answer now."
+    } else {
+        "Start wrapping up so your answer is complete in time."
+    };
+    format!("{} You have {} left. {}", NOTICE_PREFIX, left, advice)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_iteration_crossings_fire_once() {
+        let config = BudgetSignals::new();
+        let mut tracker = BudgetTracker::new(&config, None, 10);
+
+        let fired: Vec<usize> = (1..=10)
+            .filter(|&iteration| tracker.check(iteration).is_some())
+            .collect();
+        assert_eq!(fired, vec![8, 10]);
+        assert!(tracker.degraded());
+
+        let signals = tracker.into_signals();
+        assert_eq!(
+            signals[0].message,
+            "[Budget notice] You have 3 model calls left. \
+             Start wrapping up so your answer is complete in time."
+        );
+        assert_eq!(signals[1].remaining.iterations, 1);
+    }
+
+    #[test]
+    fn test_jump_fires_highest_threshold_only() {
+        let config = BudgetSignals::new().with_token_budget(1000);
+        let mut tracker = BudgetTracker::new(&config, None, 10);
+
+        assert!(tracker.check(1).is_none());
+        tracker.record_usage(Some(&Usage {
+            prompt_tokens: 900,
+            completion_tokens: 50,
+            total_tokens: 950,
+            reasoning_tokens: None,
+        }));
+        let signal = tracker.check(2).unwrap();
+        assert_eq!(signal.threshold, 0.9);
+        assert_eq!(signal.budget, BudgetKind::Tokens);
+        assert_eq!(signal.remaining.tokens, Some(50));
+        assert!(tracker.check(3).is_none());
+
+        assert_eq!(
+            tracker.max_tokens(Some(8192)),
+            Some(MIN_DEGRADED_MAX_TOKENS)
+        );
+    }
+}
@@ -0,0 +1,602 @@
+//! Score an agent on a fixed set of cases, and compare runs.
+//!
+//! [`EvalSuite::run`] executes each [`EvalCase`] against one agent,
+//! optionally several times to average out sampling, scores every output
+//! against the case's [`Expectation`], and summarizes the lot as an
+//! [`EvalReport`]: pass/fail per case, accuracy, latency percentiles and
+//! token usage. Reports serialize to JSON, so CI can keep one as a baseline
+//! and [`compare`](EvalReport::compare) each new run against it.
+
+use crate::agent::benchmark::{parse_json, percentile};
+use crate::agent::Agent;
+use crate::llm::{ChatMessage, ChatRequest, LlmClient};
+use crate::types::{AgentInput, AgentOutput};
+use futures::StreamExt;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::time::Instant;
+
+/// How well one output met its case's expectation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Score {
+    /// From 0 (wrong) to 1 (right)
+    pub value: f64,
+    pub passed: bool,
+
+    /// Why, when the scorer says
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl Score {
+    pub fn pass() -> Self {
+        Self {
+            value: 1.0,
+            passed: true,
+            reason: None,
+        }
+    }
+
+    pub fn fail(reason: impl Into<String>) -> Self {
+        Self {
+            value: 0.0,
+            passed: false,
+            reason: Some(reason.into()),
+        }
+    }
+
+    /// `value`, passing at `pass_at` or above
+    pub fn graded(value: f64, pass_at: f64) -> Self {
+        let value = value.clamp(0.0, 1.0);
+        Self {
+            value,
+            passed: value >= pass_at,
+            reason: None,
+        }
+    }
+
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    fn pass_if(passed: bool, reason: impl FnOnce() -> String) -> Self {
+        if passed {
+            Self::pass()
+        } else {
+            Self::fail(reason())
+        }
+    }
+}
+
+/// Scores an output in code
+pub type ScoreFn = Arc<dyn Fn(&AgentOutput) -> Score + Send + Sync>;
+
+/// What a case's output should be
+///
+/// The text ones check the agent's `response`; a structured response is
+/// checked as its JSON.
+#[derive(Clone)]
+pub enum Expectation {
+    /// Trimmed response equals the expected text
+    ExactMatch(String),
+    /// Response contains the text
+    Contains(String),
+    /// Response parses as JSON and holds every field of the expected value;
+    /// each expected array element must match some actual element
+    JsonSubset(JsonValue),
+    /// Response matches the pattern anywhere
+    Regex(Regex),
+    Custom(ScoreFn),
+    /// A judge model scores the response from 0 to 1 against `rubric`,
+    /// passing at `pass_at`
+    Judge {
+        client: LlmClient,
+        rubric: String,
+        pass_at: f64,
+    },
+}
+
+impl Expectation {
+    pub fn exact(expected: impl Into<String>) -> Self {
+        Expectation::ExactMatch(expected.into())
+    }
+
+    pub fn contains(expected: impl Into<String>) -> Self {
+        Expectation::Contains(expected.into())
+    }
+
+    pub fn json_subset(expected: JsonValue) -> Self {
+        Expectation::JsonSubset(expected)
+    }
+
+    /// Panics if `pattern` is not a valid regex
+    pub fn regex(pattern: &str) -> Self {
+        Expectation::Regex(Regex::new(pattern).expect("invalid expectation regex"))
+    }
+
+    pub fn custom(score: impl Fn(&AgentOutput) -> Score + Send + Sync + 'static) -> Self {
+        Expectation::Custom(Arc::new(score))
+    }
+
+    /// A judge passing scores of 0.5 and above
+    pub fn judge(client: LlmClient, rubric: impl Into<String>) -> Self {
+        Expectation::Judge {
+            client,
+            rubric: rubric.into(),
+            pass_at: 0.5,
+        }
+    }
+
+    /// Score `output`; `Err` when the judge fails to
+    async fn score(&self, input: &AgentInput, output: &AgentOutput) -> Result<Score, String> {
+        let response = response_text(output);
+        Ok(match self {
+            Expectation::ExactMatch(expected) => {
+                Score::pass_if(response.trim() == expected.trim(), || {
+                    format!("expected {:?}", expected)
+                })
+            }
+            Expectation::Contains(expected) => {
+                Score::pass_if(response.contains(expected.as_str()), || {
+                    format!("missing {:?}", expected)
+                })
+            }
+            Expectation::JsonSubset(expected) => {
+                let actual = match &output.data["response"] {
+                    JsonValue::String(text) => parse_json(text),
+                    other => Some(other.clone()),
+                };
+                match actual {
+                    Some(actual) => Score::pass_if(is_subset(expected, &actual), || {
+                        format!("{} does not contain {}", actual, expected)
+                    }),
+                    None => Score::fail("response is not JSON"),
+                }
+            }
+            Expectation::Regex(pattern) => Score::pass_if(pattern.is_match(&response), || {
+                format!("no match for /{}/", pattern.as_str())
+            }),
+            Expectation::Custom(score) => score(output),
+            Expectation::Judge {
+                client,
+                rubric,
+                pass_at,
+            } => {
+                let task = match &input.data {
+                    JsonValue::String(text) => text.clone(),
+                    other => other.to_string(),
+                };
+                let request = ChatRequest::new(vec![
+                    ChatMessage::system(JUDGE_PROMPT),
+                    ChatMessage::user(format!(
+                        "Task:\n{}\n\nRubric:\n{}\n\nResponse:\n{}",
+                        task, rubric, response
+                    )),
+                ])
+                .with_temperature(0.0)
+                .with_max_tokens(200);
+                let verdict = client
+                    .chat(request)
+                    .await
+                    .map_err(|e| format!("judge failed: {}", e))?;
+                let (value, reason) = parse_judgement(&verdict.content).ok_or_else(|| {
+                    format!("judge gave no score from 0 to 1: {:?}", verdict.content)
+                })?;
+                let score = Score::graded(value, *pass_at);
+                match reason {
+                    Some(reason) => score.with_reason(reason),
+                    None => score,
+                }
+            }
+        })
+    }
+}
+
+impl std::fmt::Debug for Expectation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expectation::ExactMatch(expected) => {
+                f.debug_tuple("ExactMatch").field(expected).finish()
+            }
+            Expectation::Contains(expected) => f.debug_tuple("Contains").field(expected).finish(),
+            Expectation::JsonSubset(expected) => {
+                f.debug_tuple("JsonSubset").field(expected).finish()
+            }
+            Expectation::Regex(pattern) => f.debug_tuple("Regex").field(&pattern.as_str()).finish(),
+            Expectation::Custom(_) => f.write_str("Custom"),
+            Expectation::Judge {
+                rubric, pass_at, ..
+            } => f
+                .debug_struct("Judge")
+                .field("rubric", rubric)
+                .field("pass_at", pass_at)
+                .finish(),
+        }
+    }
+}
+
+const JUDGE_PROMPT: &str = "You grade responses to tasks against a rubric. Reply with a \
+score from 0 (fails the rubric) to 1 (fully satisfies it) on the first line, then one \
+sentence saying why.";
+
+/// The first number in a judge's reply, if it's from 0 to 1, and the rest
+/// of the reply as the reason
+fn parse_judgement(reply: &str) -> Option<(f64, Option<String>)> {
+    let number = Regex::new(r"\d+(?:\.\d+)?").expect("valid regex");
+    let found = number.find(reply)?;
+    let value: f64 = found.as_str().parse().ok()?;
+    if !(0.0..=1.0).contains(&value) {
+        return None;
+    }
+    let reason = reply[found.end()..]
+        .trim_start_matches(|c: char| c.is_whitespace() || "-:.,".contains(c))
+        .trim();
+    Some((value, (!reason.is_empty()).then(|| reason.to_string())))
+}
+
+/// Whether `actual` holds everything in `expected`
+fn is_subset(expected: &JsonValue, actual: &JsonValue) -> bool {
+    match (expected, actual) {
+        (JsonValue::Object(expected), JsonValue::Object(actual)) => {
+            expected.iter().all(|(key, value)| {
+                actual
+                    .get(key)
+                    .is_some_and(|actual| is_subset(value, actual))
+            })
+        }
+        (JsonValue::Array(expected), JsonValue::Array(actual)) => expected
+            .iter()
+            .all(|value| actual.iter().any(|actual| is_subset(value, actual))),
+        _ => expected == actual,
+    }
+}
+
+fn response_text(output: &AgentOutput) -> String {
+    match &output.data["response"] {
+        JsonValue::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// One input and what the agent should make of it
+#[derive(Debug, Clone)]
+pub struct EvalCase {
+    pub name: String,
+    pub input: AgentInput,
+    pub expected: Expectation,
+}
+
+impl EvalCase {
+    pub fn new(
+        name: impl Into<String>,
+        input: impl Into<JsonValue>,
+        expected: Expectation,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            input: AgentInput::from_value(input.into()),
+            expected,
+        }
+    }
+}
+
+/// How a suite runs
+#[derive(Debug, Clone, Copy)]
+pub struct EvalConfig {
+    /// Cases run at once (default: 1)
+    pub concurrency: usize,
+    /// Runs per case, for agents that don't answer the same way twice
+    /// (default: 1)
+    pub repeats: usize,
+}
+
+impl Default for EvalConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 1,
+            repeats: 1,
+        }
+    }
+}
+
+/// One execution of a case
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalRun {
+    pub repeat: usize,
+    pub score: Score,
+
+    /// Agent (or judge) error; the run fails
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    pub latency_ms: f64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub response: String,
+}
+
+/// How a case did over its runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaseReport {
+    pub name: String,
+    /// Every run passed
+    pub passed: bool,
+    /// Share of runs that passed
+    pub pass_rate: f64,
+    pub mean_score: f64,
+    /// In repeat order
+    pub runs: Vec<EvalRun>,
+}
+
+/// Suite results
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalReport {
+    /// In the order the cases were given
+    pub cases: Vec<CaseReport>,
+    pub runs: usize,
+    pub passed_runs: usize,
+    /// `passed_runs / runs`
+    pub accuracy: f64,
+    pub latency_p50_ms: f64,
+    pub latency_p90_ms: f64,
+    pub latency_p99_ms: f64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub errors: usize,
+}
+
+impl EvalReport {
+    pub fn case(&self, name: &str) -> Option<&CaseReport> {
+        self.cases.iter().find(|c| c.name == name)
+    }
+
+    /// Cases that passed
+    pub fn passed(&self) -> usize {
+        self.cases.iter().filter(|c| c.passed).count()
+    }
+
+    /// What changed since `baseline`
+    ///
+    /// A case regressed if it passed in the baseline and fails now, or its
+    /// pass rate fell; it improved the other way round.
+    pub fn compare(&self, baseline: &EvalReport) -> EvalDiff {
+        let mut diff = EvalDiff {
+            accuracy_delta: self.accuracy - baseline.accuracy,
+            latency_p50_delta_ms: self.latency_p50_ms - baseline.latency_p50_ms,
+            latency_p90_delta_ms: self.latency_p90_ms - baseline.latency_p90_ms,
+            tokens_delta: (self.prompt_tokens + self.completion_tokens) as i64
+                - (baseline.prompt_tokens + baseline.completion_tokens) as i64,
+            ..Default::default()
+        };
+        let before: HashMap<&str, &CaseReport> = baseline
+            .cases
+            .iter()
+            .map(|c| (c.name.as_str(), c))
+            .collect();
+        for case in &self.cases {
+            let Some(old) = before.get(case.name.as_str()) else {
+                diff.added.push(case.name.clone());
+                continue;
+            };
+            let change = CaseChange {
+                name: case.name.clone(),
+                was_passed: old.passed,
+                passed: case.passed,
+                baseline_pass_rate: old.pass_rate,
+                pass_rate: case.pass_rate,
+                baseline_score: old.mean_score,
+                score: case.mean_score,
+            };
+            if (old.passed && !case.passed) || case.pass_rate < old.pass_rate {
+                diff.regressions.push(change);
+            } else if (!old.passed && case.passed) || case.pass_rate > old.pass_rate {
+                diff.improvements.push(change);
+            }
+        }
+        diff.removed = baseline
+            .cases
+            .iter()
+            .filter(|old| self.case(&old.name).is_none())
+            .map(|old| old.name.clone())
+            .collect();
+        diff
+    }
+}
+
+/// A case whose result changed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaseChange {
+    pub name: String,
+    pub was_passed: bool,
+    pub passed: bool,
+    pub baseline_pass_rate: f64,
+    pub pass_rate: f64,
+    pub baseline_score: f64,
+    pub score: f64,
+}
+
+/// A report compared with its baseline; deltas are new minus baseline
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EvalDiff {
+    pub accuracy_delta: f64,
+    pub latency_p50_delta_ms: f64,
+    pub latency_p90_delta_ms: f64,
+    pub tokens_delta: i64,
+    pub regressions: Vec<CaseChange>,
+    pub improvements: Vec<CaseChange>,
+    /// Cases not in the baseline
+    pub added: Vec<String>,
+    /// Baseline cases no longer run
+    pub removed: Vec<String>,
+}
+
+impl EvalDiff {
+    pub fn has_regressions(&self) -> bool {
+        !self.regressions.is_empty()
+    }
+
+    /// Regressions first, then improvements, as a markdown list
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!(
+            "Accuracy {:+.1} points, p50 latency {:+.0} ms, tokens {:+}\n",
+            self.accuracy_delta * 100.0,
+            self.latency_p50_delta_ms,
+            self.tokens_delta
+        );
+        for (label, changes) in [
+            ("Regressed", &self.regressions),
+            ("Improved", &self.improvements),
+        ] {
+            for change in changes {
+                out.push_str(&format!(
+                    "- {}: {} (pass rate {:.0}% -> {:.0}%, score {:.2} -> {:.2})\n",
+                    label,
+                    change.name,
+                    change.baseline_pass_rate * 100.0,
+                    change.pass_rate * 100.0,
+                    change.baseline_score,
+                    change.score
+                ));
+            }
+        }
+        for name in &self.added {
+            out.push_str(&format!("- Added: {}\n", name));
+        }
+        for name in &self.removed {
+            out.push_str(&format!("- Removed: {}\n", name));
+        }
+        out
+    }
+}
+
+/// Runs eval cases against an agent
+pub struct EvalSuite;
+
+impl EvalSuite {
+    /// Run every case `config.repeats` times and score each run
+    pub async fn run(agent: &Agent, cases: Vec<EvalCase>, config: EvalConfig) -> EvalReport {
+        let repeats = config.repeats.max(1);
+        let pending = cases
+            .iter()
+            .enumerate()
+            .flat_map(|(i, case)| (0..repeats).map(move |repeat| (i, case, repeat)));
+        let mut results: Vec<(usize, EvalRun)> = futures::stream::iter(pending)
+            .map(|(i, case, repeat)| async move { (i, run_case(agent, case, repeat).await) })
+            .buffer_unordered(config.concurrency.max(1))
+            .collect()
+            .await;
+        results.sort_by_key(|(i, run)| (*i, run.repeat));
+
+        let mut reports: Vec<CaseReport> = cases
+            .iter()
+            .map(|case| CaseReport {
+                name: case.name.clone(),
+                passed: false,
+                pass_rate: 0.0,
+                mean_score: 0.0,
+                runs: Vec::with_capacity(repeats),
+            })
+            .collect();
+        for (i, run) in results {
+            reports[i].runs.push(run);
+        }
+        for report in &mut reports {
+            let runs = report.runs.len().max(1) as f64;
+            let passed = report.runs.iter().filter(|r| r.score.passed).count();
+            report.passed = passed == report.runs.len();
+            report.pass_rate = passed as f64 / runs;
+            report.mean_score = report.runs.iter().map(|r| r.score.value).sum::<f64>() / runs;
+        }
+
+        let all: Vec<&EvalRun> = reports.iter().flat_map(|c| &c.runs).collect();
+        let mut latencies: Vec<f64> = all.iter().map(|r| r.latency_ms).collect();
+        latencies.sort_by(|a, b| a.total_cmp(b));
+        let passed_runs = all.iter().filter(|r| r.score.passed).count();
+        EvalReport {
+            runs: all.len(),
+            passed_runs,
+            accuracy: if all.is_empty() {
+                0.0
+            } else {
+                passed_runs as f64 / all.len() as f64
+            },
+            latency_p50_ms: percentile(&latencies, 0.50),
+            latency_p90_ms: percentile(&latencies, 0.90),
+            latency_p99_ms: percentile(&latencies, 0.99),
+            prompt_tokens: all.iter().map(|r| r.prompt_tokens).sum(),
+            completion_tokens: all.iter().map(|r| r.completion_tokens).sum(),
+            errors: all.iter().filter(|r| r.error.is_some()).count(),
+            cases: reports,
+        }
+    }
+}
+
+async fn run_case(agent: &Agent, case: &EvalCase, repeat: usize) -> EvalRun {
+    let started = Instant::now();
+    let result = agent.execute(&case.input).await;
+    let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    let output = match result {
+        Ok(output) => output,
+        Err(e) => {
+            return EvalRun {
+                repeat,
+                score: Score::fail("agent failed"),
+                error: Some(e.to_string()),
+                latency_ms,
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                response: String::new(),
+            }
+        }
+    };
+    let (score, error) = match case.expected.score(&case.input, &output).await {
+        Ok(score) => (score, None),
+        Err(e) => (Score::fail("not scored"), Some(e)),
+    };
+    EvalRun {
+        repeat,
+        score,
+        error,
+        latency_ms,
+        prompt_tokens: output.metadata.usage.prompt_tokens,
+        completion_tokens: output.metadata.usage.completion_tokens,
+        response: response_text(&output),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_subset() {
+        let actual = json!({"order": {"id": 42, "items": [{"sku": "A1", "qty": 2}, {"sku": "B7"}]}, "ok": true});
+        assert!(is_subset(&json!({"ok": true}), &actual));
+        assert!(is_subset(
+            &json!({"order": {"items": [{"sku": "B7"}]}}),
+            &actual
+        ));
+        assert!(!is_subset(&json!({"order": {"id": 43}}), &actual));
+        assert!(!is_subset(&json!({"missing": null}), &actual));
+    }
+
+    #[test]
+    fn test_judgement_parsing() {
+        assert_eq!(
+            parse_judgement("0.8\nCovers both points."),
+            Some((0.8, Some("Covers both points.".to_string())))
+        );
+        assert_eq!(
+            parse_judgement("Score: 1 - exact"),
+            Some((1.0, Some("exact".to_string())))
+        );
+        assert_eq!(parse_judgement("7/10"), None);
+        assert_eq!(parse_judgement("Looks fine"), None);
+    }
+}
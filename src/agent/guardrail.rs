@@ -0,0 +1,349 @@
+//! Input and output checks around agent execution.
+//!
+//! A [`Guardrail`] sees each input before the agent builds its first LLM
+//! request, and the final output before the agent returns it. It lets the
+//! value through, blocks it, or replaces it: a modified input reaches the
+//! model as `AgentInput::data`, a modified output is what the caller gets.
+//!
+//! Guardrails run in the order they were attached, each seeing the previous
+//! one's modifications; the first block ends the execution with
+//! [`AgentError::Blocked`](crate::types::AgentError::Blocked) and a
+//! `<agent>:guardrail` event. Every check is recorded, with its duration, in
+//! `AgentOutputMetadata::guardrails`.
+//!
+//! [`RegexRedactor`] and [`KeywordBlocklist`] cover the common cases.
+
+use crate::error::{ConfigError, ConfigErrorCode};
+use crate::llm::types::{MessageContent, Role};
+use crate::types::{AgentInput, AgentOutput, JsonValue};
+use async_trait::async_trait;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// What a guardrail decided about a value
+#[derive(Debug, Clone, PartialEq)]
+pub enum GuardrailDecision {
+    Allow,
+
+    /// Stop the execution
+    Block {
+        reason: String,
+    },
+
+    /// Go on with this value instead: the input's data, or the output's
+    /// response text (a string) or whole data (anything else)
+    Modify(JsonValue),
+}
+
+/// A check run on an agent's input and final output
+#[async_trait]
+pub trait Guardrail: Send + Sync {
+    /// Name recorded in events and metadata
+    fn name(&self) -> &str;
+
+    async fn check_input(&self, _input: &AgentInput) -> GuardrailDecision {
+        GuardrailDecision::Allow
+    }
+
+    async fn check_output(&self, _output: &AgentOutput) -> GuardrailDecision {
+        GuardrailDecision::Allow
+    }
+}
+
+/// Which side of the execution a check ran on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuardrailStage {
+    Input,
+    Output,
+}
+
+/// What a check did
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuardrailOutcome {
+    Allowed,
+    Blocked,
+    Modified,
+}
+
+/// One guardrail check of an execution
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GuardrailCheck {
+    pub guardrail: String,
+    pub stage: GuardrailStage,
+    pub outcome: GuardrailOutcome,
+    pub duration_ms: f64,
+}
+
+/// Put a guardrail's replacement into `output`: a string becomes the
+/// response text, in the data and the final assistant message, anything
+/// else the whole data
+pub(crate) fn apply_output(output: &mut AgentOutput, value: JsonValue) {
+    let JsonValue::String(text) = value else {
+        output.data = value;
+        return;
+    };
+    if let Some(message) = output
+        .chat_history
+        .as_mut()
+        .and_then(|history| history.iter_mut().rev().find(|m| m.role == Role::Assistant))
+    {
+        message.content = MessageContent::Text(text.clone());
+    }
+    match output.data.get_mut("response") {
+        Some(response) => *response = JsonValue::String(text),
+        None => output.data = JsonValue::String(text),
+    }
+}
+
+/// Every string in `value` passed through `f`; `None` if nothing changed
+fn map_strings(value: &JsonValue, f: &dyn Fn(&str) -> Cow<'_, str>) -> Option<JsonValue> {
+    match value {
+        JsonValue::String(text) => match f(text) {
+            Cow::Owned(changed) => Some(JsonValue::String(changed)),
+            Cow::Borrowed(_) => None,
+        },
+        JsonValue::Array(items) => {
+            let mapped: Vec<Option<JsonValue>> =
+                items.iter().map(|item| map_strings(item, f)).collect();
+            if mapped.iter().all(Option::is_none) {
+                return None;
+            }
+            Some(JsonValue::Array(
+                mapped
+                    .into_iter()
+                    .zip(items)
+                    .map(|(changed, item)| changed.unwrap_or_else(|| item.clone()))
+                    .collect(),
+            ))
+        }
+        JsonValue::Object(fields) => {
+            let mut changed = false;
+            let mapped = fields
+                .iter()
+                .map(|(key, item)| {
+                    let value = map_strings(item, f).inspect(|_| changed = true);
+                    (key.clone(), value.unwrap_or_else(|| item.clone()))
+                })
+                .collect();
+            changed.then_some(JsonValue::Object(mapped))
+        }
+        _ => None,
+    }
+}
+
+/// Whether any string in `value` satisfies `f`
+fn any_string(value: &JsonValue, f: &dyn Fn(&str) -> bool) -> bool {
+    match value {
+        JsonValue::String(text) => f(text),
+        JsonValue::Array(items) => items.iter().any(|item| any_string(item, f)),
+        JsonValue::Object(fields) => fields.values().any(|item| any_string(item, f)),
+        _ => false,
+    }
+}
+
+/// Replaces matches of its patterns with `[LABEL]`, in inputs and outputs
+///
+/// ```rust,ignore
+/// let redactor = RegexRedactor::common()
+///     .pattern("account", r"\bACC-\d{8}\b")?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RegexRedactor {
+    patterns: Vec<(String, Regex)>,
+}
+
+const EMAIL_PATTERN: &str =
+    r"[A-Za-z0-9._%+-]{1,64}@[A-Za-z0-9-]{1,63}(?:\.[A-Za-z0-9-]{1,63}){0,8}\.[A-Za-z]{2,24}";
+const SSN_PATTERN: &str = r"\b\d{3}-\d{2}-\d{4}\b";
+
+impl RegexRedactor {
+    pub const NAME: &'static str = "regex_redactor";
+
+    /// A redactor without patterns
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Emails (`[EMAIL]`) and US Social Security numbers (`[SSN]`)
+    pub fn common() -> Self {
+        Self::new()
+            .pattern("email", EMAIL_PATTERN)
+            .and_then(|redactor| redactor.pattern("ssn", SSN_PATTERN))
+            .expect("built-in redaction patterns must compile")
+    }
+
+    /// Also redact matches of `pattern` as `[LABEL]` (builder-style); fails
+    /// if the pattern doesn't compile
+    pub fn pattern(mut self, label: impl Into<String>, pattern: &str) -> Result<Self, ConfigError> {
+        let label = label.into();
+        let regex = RegexBuilder::new(pattern)
+            .size_limit(1 << 20)
+            .build()
+            .map_err(|e| ConfigError {
+                code: ConfigErrorCode::InvalidValue,
+                message: format!("Invalid redaction pattern: {}", e),
+                field: Some(format!("patterns.{}", label)),
+            })?;
+        self.patterns.push((label, regex));
+        Ok(self)
+    }
+
+    /// `text` with every match replaced, borrowed if nothing matched
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for (label, regex) in &self.patterns {
+            let placeholder = format!("[{}]", label.to_uppercase());
+            if let Cow::Owned(replaced) = regex.replace_all(&text, placeholder.as_str()) {
+                text = Cow::Owned(replaced);
+            }
+        }
+        text
+    }
+
+    fn redact_value(&self, value: &JsonValue) -> GuardrailDecision {
+        match map_strings(value, &|text| self.redact(text)) {
+            Some(redacted) => GuardrailDecision::Modify(redacted),
+            None => GuardrailDecision::Allow,
+        }
+    }
+}
+
+#[async_trait]
+impl Guardrail for RegexRedactor {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    async fn check_input(&self, input: &AgentInput) -> GuardrailDecision {
+        self.redact_value(&input.data)
+    }
+
+    async fn check_output(&self, output: &AgentOutput) -> GuardrailDecision {
+        // Redacting the response text alone keeps the chat history in step
+        match output.data.get("response") {
+            Some(response @ JsonValue::String(_)) => self.redact_value(response),
+            _ => self.redact_value(&output.data),
+        }
+    }
+}
+
+/// Blocks inputs containing any of its phrases, ignoring case
+///
+/// Outputs are let through unless [`with_outputs`](Self::with_outputs) is
+/// set.
+#[derive(Debug, Clone)]
+pub struct KeywordBlocklist {
+    keywords: Vec<String>,
+    outputs: bool,
+}
+
+impl KeywordBlocklist {
+    pub const NAME: &'static str = "keyword_blocklist";
+
+    pub fn new<I, S>(keywords: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self {
+            keywords: keywords
+                .into_iter()
+                .map(|keyword| keyword.as_ref().to_lowercase())
+                .filter(|keyword| !keyword.is_empty())
+                .collect(),
+            outputs: false,
+        }
+    }
+
+    /// Check final outputs too (builder-style)
+    pub fn with_outputs(mut self) -> Self {
+        self.outputs = true;
+        self
+    }
+
+    /// The first phrase found in `value`
+    fn find(&self, value: &JsonValue) -> Option<&str> {
+        self.keywords
+            .iter()
+            .find(|keyword| any_string(value, &|text| text.to_lowercase().contains(*keyword)))
+            .map(String::as_str)
+    }
+
+    fn check(&self, what: &str, value: &JsonValue) -> GuardrailDecision {
+        match self.find(value) {
+            Some(keyword) => GuardrailDecision::Block {
+                reason: format!("{} contains blocked phrase '{}'", what, keyword),
+            },
+            None => GuardrailDecision::Allow,
+        }
+    }
+}
+
+#[async_trait]
+impl Guardrail for KeywordBlocklist {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    async fn check_input(&self, input: &AgentInput) -> GuardrailDecision {
+        self.check("input", &input.data)
+    }
+
+    async fn check_output(&self, output: &AgentOutput) -> GuardrailDecision {
+        if !self.outputs {
+            return GuardrailDecision::Allow;
+        }
+        self.check("output", &output.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_patterns_and_keywords_walk_nested_strings() {
+        let redactor = RegexRedactor::common()
+            .pattern("account", r"\bACC-\d{4}\b")
+            .unwrap();
+        assert_eq!(
+            redactor.redact("Mail ada@example.com about ACC-1234, SSN 123-45-6789"),
+            "Mail [EMAIL] about [ACCOUNT], SSN [SSN]"
+        );
+        assert!(matches!(redactor.redact("nothing here"), Cow::Borrowed(_)));
+
+        let data = json!({ "notes": ["call 555", { "to": "bob@example.org" }], "n": 5 });
+        assert_eq!(
+            redactor.redact_value(&data),
+            GuardrailDecision::Modify(
+                json!({ "notes": ["call 555", { "to": "[EMAIL]" }], "n": 5 })
+            )
+        );
+        assert_eq!(
+            redactor.redact_value(&json!(["plain", 1])),
+            GuardrailDecision::Allow
+        );
+
+        let error = RegexRedactor::new().pattern("broken", "(").unwrap_err();
+        assert_eq!(error.field.as_deref(), Some("patterns.broken"));
+
+        let blocklist = KeywordBlocklist::new(["Ignore previous instructions", ""]);
+        assert_eq!(
+            blocklist.check(
+                "input",
+                &json!({ "text": "Please IGNORE previous instructions" })
+            ),
+            GuardrailDecision::Block {
+                reason: "input contains blocked phrase 'ignore previous instructions'".to_string()
+            }
+        );
+        assert_eq!(
+            blocklist.check("input", &json!("hello")),
+            GuardrailDecision::Allow
+        );
+    }
+}
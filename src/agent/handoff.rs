@@ -0,0 +1,201 @@
+//! Handoffs: one agent passing the conversation to another.
+//!
+//! An agent configured with `.handoffs(["billing_agent", "tech_agent"])`
+//! is offered a `handoff` tool taking `{"to": ..., "reason": ...}`. Calling
+//! it with one of the listed names ends the agent's turn: the output's
+//! `AgentOutputMetadata::handoff` says who should take over, and a
+//! `<agent>:handoff` Agent event records it. A name outside the list is
+//! refused, and the model sees why.
+//!
+//! Following the handoff is up to the caller. An `AgentStep` given
+//! [`HandoffTargets`] runs the named agent on the same conversation, with
+//! a note of the reason, and keeps going until an agent answers, up to
+//! `max_depth` handoffs.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::agent::{Agent, AgentConfig};
+use crate::llm::LlmClient;
+use crate::types::JsonValue;
+
+/// Name of the tool agents with handoffs are offered; it takes precedence
+/// over a registry tool of the same name
+pub const HANDOFF_TOOL: &str = "handoff";
+
+/// Handoffs followed unless [`HandoffTargets::with_max_depth`] sets another
+/// limit
+pub const DEFAULT_MAX_HANDOFF_DEPTH: usize = 4;
+
+/// One agent handing the conversation to another
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Handoff {
+    pub from: String,
+    pub to: String,
+
+    /// Why, as the model gave it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl fmt::Display for Handoff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}", self.from, self.to)
+    }
+}
+
+impl Handoff {
+    /// What the agent taking over is told, before the conversation goes on
+    pub fn note(&self) -> String {
+        match &self.reason {
+            Some(reason) => format!(
+                "Agent {} handed this conversation to you. Reason: {}",
+                self.from, reason
+            ),
+            None => format!("Agent {} handed this conversation to you.", self.from),
+        }
+    }
+}
+
+/// The `handoff` tool's schema, offering `targets`
+pub(crate) fn tool_schema(targets: &[String]) -> JsonValue {
+    serde_json::json!({
+        "type": "function",
+        "function": {
+            "name": HANDOFF_TOOL,
+            "description": "Hand the conversation to an agent better suited to it. \
+                            Your turn ends; that agent answers the user.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "to": {
+                        "type": "string",
+                        "enum": targets,
+                        "description": "The agent to take over"
+                    },
+                    "reason": {
+                        "type": "string",
+                        "description": "Why, for the agent taking over"
+                    }
+                },
+                "required": ["to"]
+            }
+        }
+    })
+}
+
+/// The handoff `from` asked for with `arguments`, or what to tell the
+/// model when it named an agent it may not hand off to
+pub(crate) fn parse(from: &str, targets: &[String], arguments: &str) -> Result<Handoff, String> {
+    #[derive(Deserialize)]
+    struct Arguments {
+        to: String,
+        #[serde(default)]
+        reason: Option<String>,
+    }
+
+    let arguments: Arguments =
+        serde_json::from_str(arguments).map_err(|e| format!("Invalid handoff arguments: {}", e))?;
+    if !targets.contains(&arguments.to) {
+        return Err(format!(
+            "Cannot hand off to '{}'. Agents you can hand off to: {}",
+            arguments.to,
+            targets.join(", ")
+        ));
+    }
+    Ok(Handoff {
+        from: from.to_string(),
+        to: arguments.to,
+        reason: arguments.reason.filter(|r| !r.trim().is_empty()),
+    })
+}
+
+/// The agents a handoff can reach, by name
+///
+/// ```rust,ignore
+/// let targets = HandoffTargets::new()
+///     .with_client(client.clone())
+///     .with_config(AgentConfig::builder("billing_agent").system_prompt("You handle billing").build())
+///     .with_agent(tech_agent);
+/// let step = AgentStep::from_agent(triage, "support".to_string()).with_handoffs(targets);
+/// ```
+#[derive(Clone)]
+pub struct HandoffTargets {
+    agents: HashMap<String, Arc<Agent>>,
+    client: Option<LlmClient>,
+    max_depth: usize,
+}
+
+impl Default for HandoffTargets {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HandoffTargets {
+    pub fn new() -> Self {
+        Self {
+            agents: HashMap::new(),
+            client: None,
+            max_depth: DEFAULT_MAX_HANDOFF_DEPTH,
+        }
+    }
+
+    /// The client for agents added with [`with_config`](Self::with_config)
+    /// from here on
+    pub fn with_client(mut self, client: LlmClient) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Fail once a conversation would be handed off more than `depth` times,
+    /// e.g. two agents passing it back and forth
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Reach `agent` by its name
+    pub fn with_agent(mut self, agent: Agent) -> Self {
+        self.agents
+            .insert(agent.name().to_string(), Arc::new(agent));
+        self
+    }
+
+    /// Reach an agent built from `config`, using the shared client
+    pub fn with_config(self, config: AgentConfig) -> Self {
+        let agent = match &self.client {
+            Some(client) => Agent::new(config).with_client(client.clone()),
+            None => Agent::new(config),
+        };
+        self.with_agent(agent)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Agent> {
+        self.agents.get(name).map(Arc::as_ref)
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Names of the agents, sorted
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.agents.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+impl fmt::Debug for HandoffTargets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HandoffTargets")
+            .field("agents", &self.names())
+            .field("client", &self.client.as_ref().map(|c| c.provider_name()))
+            .field("max_depth", &self.max_depth)
+            .finish()
+    }
+}
@@ -0,0 +1,669 @@
+//! Per-turn latency breakdown and SLO tracking.
+//!
+//! Every agent turn records a cheap [`TurnLatency`] breakdown: queue wait,
+//! each LLM call (time to first chunk and total), each tool call, rate
+//! limiter waits, retries and the remaining framework overhead. With a
+//! [`LatencySlo`] configured, turns slower than the target produce a
+//! [`SlowTurnReport`] (emitted as a `system:slow_turn` event and optionally
+//! saved to a [`SlowTurnStore`]) and every turn updates the rolling
+//! [`SloAttainment`] stats.
+//!
+//! Components below the agent (rate limiters, retrying or failover clients)
+//! report into the current turn with [`record_rate_limit_wait`] and
+//! [`record_retry`]; both are no-ops outside a turn.
+
+use crate::llm::types::{ChatMessage, Role, DEFAULT_IMAGE_TOKENS};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Default number of turns in the rolling attainment window
+pub const DEFAULT_SLO_WINDOW: usize = 100;
+
+/// Number of heaviest messages included in a slow-turn report
+const HEAVIEST_MESSAGES: usize = 5;
+
+/// Maximum characters kept in a message preview
+const PREVIEW_CHARS: usize = 80;
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+/// Timing of one LLM call within a turn
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LlmCallTiming {
+    pub iteration: usize,
+
+    /// Time until the first streamed chunk arrived, if any were streamed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_to_first_chunk_ms: Option<f64>,
+
+    pub total_ms: f64,
+
+    /// Portion of `total_ms` spent waiting on a rate limiter
+    #[serde(default)]
+    pub rate_limit_wait_ms: f64,
+
+    pub success: bool,
+}
+
+/// Timing of one tool call within a turn
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCallTiming {
+    pub tool: String,
+    pub tool_call_id: String,
+    pub duration_ms: f64,
+}
+
+/// A retry or failover that happened during a turn
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetryOccurrence {
+    /// Component that retried (e.g. "llm", "tool:search", a provider name)
+    pub component: String,
+    pub reason: String,
+    /// Offset from the start of the turn
+    pub at_ms: f64,
+}
+
+/// Where the time in a turn went
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "component", rename_all = "snake_case")]
+pub enum LatencyComponent {
+    QueueWait,
+    /// An LLM call, net of rate limiter waits
+    Llm {
+        iteration: usize,
+    },
+    Tool {
+        name: String,
+    },
+    RateLimitWait,
+    Overhead,
+}
+
+/// Latency breakdown for a single turn
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TurnLatency {
+    pub turn_id: String,
+    pub agent_name: String,
+    pub total_ms: f64,
+
+    /// Time spent waiting for a turn slot (see [`super::Agent::with_turn_limit`])
+    pub queue_wait_ms: f64,
+
+    pub llm_calls: Vec<LlmCallTiming>,
+    pub tool_calls: Vec<ToolCallTiming>,
+
+    /// Total rate limiter wait, including waits outside LLM calls
+    pub rate_limit_wait_ms: f64,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retries: Vec<RetryOccurrence>,
+
+    /// Time not attributed to any of the above
+    pub overhead_ms: f64,
+}
+
+impl TurnLatency {
+    pub fn llm_ms(&self) -> f64 {
+        self.llm_calls.iter().map(|c| c.total_ms).sum()
+    }
+
+    pub fn tool_ms(&self) -> f64 {
+        self.tool_calls.iter().map(|c| c.duration_ms).sum()
+    }
+
+    /// Every component with the time attributed to it, largest first.
+    /// Tool time is summed per tool name.
+    pub fn components(&self) -> Vec<(LatencyComponent, f64)> {
+        let mut components = vec![
+            (LatencyComponent::QueueWait, self.queue_wait_ms),
+            (LatencyComponent::RateLimitWait, self.rate_limit_wait_ms),
+            (LatencyComponent::Overhead, self.overhead_ms),
+        ];
+        for call in &self.llm_calls {
+            components.push((
+                LatencyComponent::Llm {
+                    iteration: call.iteration,
+                },
+                (call.total_ms - call.rate_limit_wait_ms).max(0.0),
+            ));
+        }
+        let mut per_tool: Vec<(String, f64)> = Vec::new();
+        for call in &self.tool_calls {
+            match per_tool.iter_mut().find(|(name, _)| name == &call.tool) {
+                Some((_, ms)) => *ms += call.duration_ms,
+                None => per_tool.push((call.tool.clone(), call.duration_ms)),
+            }
+        }
+        components.extend(
+            per_tool
+                .into_iter()
+                .map(|(name, ms)| (LatencyComponent::Tool { name }, ms)),
+        );
+        components.sort_by(|a, b| b.1.total_cmp(&a.1));
+        components
+    }
+
+    /// The component that took the most time
+    pub fn dominant_component(&self) -> LatencyComponent {
+        self.components()
+            .into_iter()
+            .next()
+            .map(|(component, _)| component)
+            .unwrap_or(LatencyComponent::Overhead)
+    }
+}
+
+/// A message that occupied a large share of the context
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HeavyMessage {
+    pub index: usize,
+    pub role: Role,
+    pub tokens: usize,
+    pub preview: String,
+}
+
+/// Context utilization at the end of a turn
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContextSnapshot {
+    pub message_count: usize,
+
+    /// Estimated with the ~4 chars per token heuristic
+    pub estimated_tokens: usize,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_input_tokens: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub utilization: Option<f64>,
+
+    pub heaviest_messages: Vec<HeavyMessage>,
+}
+
+impl ContextSnapshot {
+    pub fn from_messages(messages: &[ChatMessage], max_input_tokens: Option<usize>) -> Self {
+        let estimate = |m: &ChatMessage| {
+            m.content.text().len().div_ceil(4) + m.content.image_count() * DEFAULT_IMAGE_TOKENS
+        };
+        let estimated_tokens = messages.iter().map(estimate).sum();
+
+        let mut heaviest: Vec<HeavyMessage> = messages
+            .iter()
+            .enumerate()
+            .map(|(index, m)| HeavyMessage {
+                index,
+                role: m.role.clone(),
+                tokens: estimate(m),
+                preview: m.content.text().chars().take(PREVIEW_CHARS).collect(),
+            })
+            .collect();
+        heaviest.sort_by(|a, b| b.tokens.cmp(&a.tokens).then(a.index.cmp(&b.index)));
+        heaviest.truncate(HEAVIEST_MESSAGES);
+
+        Self {
+            message_count: messages.len(),
+            estimated_tokens,
+            max_input_tokens,
+            utilization: max_input_tokens
+                .filter(|max| *max > 0)
+                .map(|max| estimated_tokens as f64 / max as f64),
+            heaviest_messages: heaviest,
+        }
+    }
+}
+
+/// Diagnostics bundle for a turn that exceeded its SLO
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlowTurnReport {
+    pub turn_id: String,
+    pub agent_name: String,
+    pub target_ms: f64,
+    pub latency: TurnLatency,
+    pub dominant: LatencyComponent,
+    pub context: ContextSnapshot,
+
+    /// Set when the turn failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+impl SlowTurnReport {
+    /// One-line summary, e.g. "turn_x took 2400ms (SLO 1000ms), mostly tool `search`"
+    pub fn summary(&self) -> String {
+        let culprit = match &self.dominant {
+            LatencyComponent::QueueWait => "queue wait".to_string(),
+            LatencyComponent::Llm { iteration } => format!("LLM call #{}", iteration),
+            LatencyComponent::Tool { name } => format!("tool `{}`", name),
+            LatencyComponent::RateLimitWait => "rate limiter wait".to_string(),
+            LatencyComponent::Overhead => "framework overhead".to_string(),
+        };
+        format!(
+            "{} took {:.0}ms (SLO {:.0}ms), mostly {}",
+            self.turn_id, self.latency.total_ms, self.target_ms, culprit
+        )
+    }
+}
+
+/// Persistence for slow-turn reports, keyed by turn ID
+#[async_trait]
+pub trait SlowTurnStore: Send + Sync {
+    async fn save(&self, report: &SlowTurnReport) -> Result<(), String>;
+
+    async fn load(&self, turn_id: &str) -> Result<Option<SlowTurnReport>, String>;
+}
+
+/// In-memory [`SlowTurnStore`]
+#[derive(Debug, Default)]
+pub struct InMemorySlowTurnStore {
+    reports: Mutex<HashMap<String, SlowTurnReport>>,
+}
+
+impl InMemorySlowTurnStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.reports.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl SlowTurnStore for InMemorySlowTurnStore {
+    async fn save(&self, report: &SlowTurnReport) -> Result<(), String> {
+        self.reports
+            .lock()
+            .unwrap()
+            .insert(report.turn_id.clone(), report.clone());
+        Ok(())
+    }
+
+    async fn load(&self, turn_id: &str) -> Result<Option<SlowTurnReport>, String> {
+        Ok(self.reports.lock().unwrap().get(turn_id).cloned())
+    }
+}
+
+/// Rolling SLO attainment stats
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SloAttainment {
+    pub target_ms: f64,
+    pub window: usize,
+
+    /// Turns currently in the window
+    pub turns: usize,
+    pub within_slo: usize,
+
+    /// `within_slo / turns`, 1.0 when no turns have been recorded
+    pub attainment: f64,
+
+    /// P95 latency over the window
+    pub p95_ms: f64,
+
+    /// Lifetime counters
+    pub total_turns: u64,
+    pub total_slow_turns: u64,
+}
+
+/// Tracks SLO attainment over the last `window` turns
+#[derive(Debug)]
+pub struct SloTracker {
+    target: Duration,
+    window: usize,
+    state: Mutex<TrackerState>,
+}
+
+#[derive(Debug, Default)]
+struct TrackerState {
+    recent: VecDeque<f64>,
+    total_turns: u64,
+    total_slow_turns: u64,
+}
+
+impl SloTracker {
+    pub fn new(target: Duration, window: usize) -> Self {
+        Self {
+            target,
+            window: window.max(1),
+            state: Mutex::new(TrackerState::default()),
+        }
+    }
+
+    pub fn target(&self) -> Duration {
+        self.target
+    }
+
+    /// Record a turn; returns true if it met the SLO
+    pub fn record(&self, total_ms: f64) -> bool {
+        let within = total_ms <= millis(self.target);
+        let mut state = self.state.lock().unwrap();
+        if state.recent.len() == self.window {
+            state.recent.pop_front();
+        }
+        state.recent.push_back(total_ms);
+        state.total_turns += 1;
+        if !within {
+            state.total_slow_turns += 1;
+        }
+        within
+    }
+
+    pub fn attainment(&self) -> SloAttainment {
+        let state = self.state.lock().unwrap();
+        let target_ms = millis(self.target);
+        let turns = state.recent.len();
+        let within_slo = state.recent.iter().filter(|ms| **ms <= target_ms).count();
+
+        let mut sorted: Vec<f64> = state.recent.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let p95_ms = if sorted.is_empty() {
+            0.0
+        } else {
+            let rank = ((sorted.len() as f64) * 0.95).ceil() as usize;
+            sorted[rank.clamp(1, sorted.len()) - 1]
+        };
+
+        SloAttainment {
+            target_ms,
+            window: self.window,
+            turns,
+            within_slo,
+            attainment: if turns == 0 {
+                1.0
+            } else {
+                within_slo as f64 / turns as f64
+            },
+            p95_ms,
+            total_turns: state.total_turns,
+            total_slow_turns: state.total_slow_turns,
+        }
+    }
+}
+
+/// Latency SLO for an agent's turns
+#[derive(Clone)]
+pub struct LatencySlo {
+    tracker: Arc<SloTracker>,
+    store: Option<Arc<dyn SlowTurnStore>>,
+    max_input_tokens: Option<usize>,
+}
+
+impl LatencySlo {
+    pub fn new(target: Duration) -> Self {
+        Self {
+            tracker: Arc::new(SloTracker::new(target, DEFAULT_SLO_WINDOW)),
+            store: None,
+            max_input_tokens: None,
+        }
+    }
+
+    /// Size of the rolling attainment window (default 100 turns)
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.tracker = Arc::new(SloTracker::new(self.tracker.target(), window));
+        self
+    }
+
+    /// Persist slow-turn reports
+    pub fn with_store(mut self, store: Arc<dyn SlowTurnStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Context budget used for the utilization figure in reports
+    pub fn with_max_input_tokens(mut self, tokens: usize) -> Self {
+        self.max_input_tokens = Some(tokens);
+        self
+    }
+
+    pub fn target(&self) -> Duration {
+        self.tracker.target()
+    }
+
+    /// Shared tracker, e.g. for exporting attainment as metrics
+    pub fn tracker(&self) -> Arc<SloTracker> {
+        self.tracker.clone()
+    }
+
+    pub fn attainment(&self) -> SloAttainment {
+        self.tracker.attainment()
+    }
+
+    pub fn store(&self) -> Option<&Arc<dyn SlowTurnStore>> {
+        self.store.as_ref()
+    }
+
+    pub(crate) fn max_input_tokens(&self) -> Option<usize> {
+        self.max_input_tokens
+    }
+}
+
+impl std::fmt::Debug for LatencySlo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LatencySlo")
+            .field("target", &self.tracker.target())
+            .field("window", &self.tracker.window)
+            .field("store", &self.store.is_some())
+            .field("max_input_tokens", &self.max_input_tokens)
+            .finish()
+    }
+}
+
+// --- Recording --------------------------------------------------------------
+
+#[derive(Debug)]
+struct RecorderState {
+    started: Instant,
+    queue_wait: Duration,
+    llm_calls: Vec<LlmCallTiming>,
+    tool_calls: Vec<ToolCallTiming>,
+    rate_limit_wait: Duration,
+    /// Rate limiter wait recorded since the current LLM call started
+    pending_rate_limit_wait: Duration,
+    retries: Vec<RetryOccurrence>,
+}
+
+/// Collects timings for one turn. Cloning shares the same recording.
+#[derive(Debug, Clone)]
+pub(crate) struct TurnRecorder {
+    turn_id: String,
+    agent_name: String,
+    state: Arc<Mutex<RecorderState>>,
+}
+
+tokio::task_local! {
+    static CURRENT_TURN: TurnRecorder;
+}
+
+impl TurnRecorder {
+    pub(crate) fn new(agent_name: &str, submitted_at: Instant) -> Self {
+        Self {
+            turn_id: format!("turn_{}", uuid::Uuid::new_v4()),
+            agent_name: agent_name.to_string(),
+            state: Arc::new(Mutex::new(RecorderState {
+                started: submitted_at,
+                queue_wait: Duration::ZERO,
+                llm_calls: Vec::new(),
+                tool_calls: Vec::new(),
+                rate_limit_wait: Duration::ZERO,
+                pending_rate_limit_wait: Duration::ZERO,
+                retries: Vec::new(),
+            })),
+        }
+    }
+
+    /// Run `fut` with this recorder as the current turn
+    pub(crate) async fn scope<F: std::future::Future>(&self, fut: F) -> F::Output {
+        CURRENT_TURN.scope(self.clone(), fut).await
+    }
+
+    pub(crate) fn record_queue_wait(&self, wait: Duration) {
+        self.state.lock().unwrap().queue_wait = wait;
+    }
+
+    pub(crate) fn start_llm_call(&self) {
+        self.state.lock().unwrap().pending_rate_limit_wait = Duration::ZERO;
+    }
+
+    pub(crate) fn record_llm_call(
+        &self,
+        iteration: usize,
+        started: Instant,
+        first_chunk: Option<Instant>,
+        success: bool,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        let rate_limit_wait = std::mem::take(&mut state.pending_rate_limit_wait);
+        state.llm_calls.push(LlmCallTiming {
+            iteration,
+            time_to_first_chunk_ms: first_chunk.map(|t| millis(t.duration_since(started))),
+            total_ms: millis(started.elapsed()),
+            rate_limit_wait_ms: millis(rate_limit_wait),
+            success,
+        });
+    }
+
+    pub(crate) fn record_tool_call(&self, tool: &str, tool_call_id: &str, started: Instant) {
+        self.state.lock().unwrap().tool_calls.push(ToolCallTiming {
+            tool: tool.to_string(),
+            tool_call_id: tool_call_id.to_string(),
+            duration_ms: millis(started.elapsed()),
+        });
+    }
+
+    /// Finish the turn and compute the breakdown
+    pub(crate) fn finish(&self) -> TurnLatency {
+        let state = self.state.lock().unwrap();
+        let total = state.started.elapsed();
+        let llm: Duration = state
+            .llm_calls
+            .iter()
+            .map(|c| Duration::from_secs_f64(c.total_ms / 1000.0))
+            .sum();
+        let tools: Duration = state
+            .tool_calls
+            .iter()
+            .map(|c| Duration::from_secs_f64(c.duration_ms / 1000.0))
+            .sum();
+        let in_call_rate_limit: f64 = state.llm_calls.iter().map(|c| c.rate_limit_wait_ms).sum();
+        let outside_calls_rate_limit =
+            (millis(state.rate_limit_wait) - in_call_rate_limit).max(0.0);
+        let overhead = millis(total)
+            - millis(state.queue_wait)
+            - millis(llm)
+            - millis(tools)
+            - outside_calls_rate_limit;
+
+        TurnLatency {
+            turn_id: self.turn_id.clone(),
+            agent_name: self.agent_name.clone(),
+            total_ms: millis(total),
+            queue_wait_ms: millis(state.queue_wait),
+            llm_calls: state.llm_calls.clone(),
+            tool_calls: state.tool_calls.clone(),
+            rate_limit_wait_ms: millis(state.rate_limit_wait),
+            retries: state.retries.clone(),
+            overhead_ms: overhead.max(0.0),
+        }
+    }
+}
+
+/// Report time spent waiting on a rate limiter to the current turn
+pub fn record_rate_limit_wait(wait: Duration) {
+    let _ = CURRENT_TURN.try_with(|turn| {
+        let mut state = turn.state.lock().unwrap();
+        state.rate_limit_wait += wait;
+        state.pending_rate_limit_wait += wait;
+    });
+}
+
+/// Report a retry or failover to the current turn
+pub fn record_retry(component: impl Into<String>, reason: impl Into<String>) {
+    let component = component.into();
+    let reason = reason.into();
+    let _ = CURRENT_TURN.try_with(|turn| {
+        let mut state = turn.state.lock().unwrap();
+        let at_ms = millis(state.started.elapsed());
+        state.retries.push(RetryOccurrence {
+            component,
+            reason,
+            at_ms,
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attainment_window_rolls() {
+        let tracker = SloTracker::new(Duration::from_millis(100), 4);
+        for ms in [150.0, 50.0, 80.0, 90.0, 60.0] {
+            tracker.record(ms);
+        }
+
+        // The slow first turn has rolled out of the window
+        let stats = tracker.attainment();
+        assert_eq!(stats.turns, 4);
+        assert_eq!(stats.within_slo, 4);
+        assert_eq!(stats.attainment, 1.0);
+        assert_eq!(stats.p95_ms, 90.0);
+        assert_eq!(stats.total_turns, 5);
+        assert_eq!(stats.total_slow_turns, 1);
+    }
+
+    #[test]
+    fn test_components_sum_tools_by_name() {
+        let latency = TurnLatency {
+            turn_id: "t".into(),
+            agent_name: "a".into(),
+            total_ms: 100.0,
+            queue_wait_ms: 5.0,
+            llm_calls: vec![LlmCallTiming {
+                iteration: 1,
+                time_to_first_chunk_ms: None,
+                total_ms: 40.0,
+                rate_limit_wait_ms: 30.0,
+                success: true,
+            }],
+            tool_calls: vec![
+                ToolCallTiming {
+                    tool: "search".into(),
+                    tool_call_id: "1".into(),
+                    duration_ms: 20.0,
+                },
+                ToolCallTiming {
+                    tool: "search".into(),
+                    tool_call_id: "2".into(),
+                    duration_ms: 20.0,
+                },
+            ],
+            rate_limit_wait_ms: 30.0,
+            retries: Vec::new(),
+            overhead_ms: 15.0,
+        };
+
+        assert_eq!(
+            latency.dominant_component(),
+            LatencyComponent::Tool {
+                name: "search".into()
+            }
+        );
+        let llm = latency
+            .components()
+            .into_iter()
+            .find(|(c, _)| matches!(c, LatencyComponent::Llm { .. }))
+            .unwrap();
+        assert_eq!(llm.1, 10.0);
+    }
+}
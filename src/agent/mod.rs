@@ -1,14 +1,65 @@
+use crate::artifact::{ArtifactRef, ArtifactStore};
+use crate::config::LlmConfig;
 use crate::event::EventStream;
-use crate::llm::types::ToolCall;
-use crate::llm::{ChatMessage, ChatRequest, LlmClient};
-use crate::tools::{ToolCallTracker, ToolLoopDetectionConfig, ToolRegistry};
-use crate::types::{AgentError, AgentInput, AgentOutput, AgentOutputMetadata, AgentResult};
+use crate::limits::{LimitEvent, LimitExceeded};
+use crate::llm::types::{ContentPart, MessageContent, ToolCall, ToolChoice};
+use crate::llm::{batch, rate_limit};
+use crate::llm::{
+    validate_history, AppliedEffort, ChatMessage, ChatRequest, ChatResponse, Effort,
+    GenericChatClient, LlmClient, LlmError, LlmResult, SamplingParams,
+};
+use crate::runtime::retry::RetryPolicy;
+use crate::runtime::timeout::TimeoutConfig;
+use crate::tools::truncation::{self, DEFAULT_MAX_TOOL_RESULT_BYTES, MAX_EVENT_RESULT_BYTES};
+use crate::tools::{
+    CancellationToken, ToolCallTracker, ToolLoopDetectionConfig, ToolRegistry,
+    ToolResultTransformer, ToolRunContext,
+};
+use crate::types::{
+    AgentError, AgentInput, AgentOutput, AgentOutputMetadata, AgentResult, ToolError,
+    ToolErrorDetail, ToolResult, ToolStatus,
+};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
-
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::Instrument;
+
+pub mod benchmark;
+pub mod budget;
+pub mod eval;
+pub mod guardrail;
+pub mod handoff;
+pub mod latency;
+pub mod prompt;
+pub mod reflection;
+pub mod speculation;
+mod stop;
 #[cfg(test)]
 mod tests;
+pub mod tool_prompting;
+
+pub use benchmark::{BenchmarkReport, BenchmarkTask, Grader, ModelBenchmark};
+use budget::BudgetTracker;
+pub use budget::{BudgetKind, BudgetRemaining, BudgetSignal, BudgetSignals};
+pub use eval::{EvalCase, EvalConfig, EvalDiff, EvalReport, EvalSuite, Expectation, Score};
+pub use guardrail::{
+    Guardrail, GuardrailCheck, GuardrailDecision, GuardrailOutcome, GuardrailStage,
+    KeywordBlocklist, RegexRedactor,
+};
+pub use handoff::{Handoff, HandoffTargets};
+use latency::TurnRecorder;
+pub use latency::{LatencySlo, SloAttainment, SlowTurnReport, TurnLatency};
+pub use prompt::{PromptTemplate, PromptVars};
+pub use reflection::{ReflectionConfig, ReflectionReport, ReflectionVerdict};
+use speculation::Speculation;
+pub use speculation::{
+    LearnedPrefetch, PredictedCall, PrefetchRule, SpeculationStats, SpeculativePrefetcher,
+};
+pub use tool_prompting::ToolCallingMode;
 
 /// Agent configuration
 #[derive(Clone, Serialize, Deserialize)]
@@ -16,11 +67,34 @@ pub struct AgentConfig {
     pub name: String,
     pub system_prompt: String,
 
+    /// Renders the system prompt for each execution; `system_prompt` then
+    /// holds its text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt_template: Option<PromptTemplate>,
+
     #[serde(skip)]
     pub tools: Option<Arc<ToolRegistry>>,
 
+    /// Names of the tools in `tools` this agent may see and call; `None`
+    /// allows all of them. Lets agents share one registry. A call to any
+    /// other tool is refused with `ToolError::NotPermitted`, which the LLM
+    /// sees.
+    #[serde(default)]
+    pub allowed_tools: Option<Vec<String>>,
+
     pub max_tool_iterations: usize,
 
+    /// What to do when the agent still wants tools after
+    /// `max_tool_iterations` LLM calls. Default: one last call without tools
+    /// for a final answer.
+    #[serde(default)]
+    pub on_max_iterations: MaxIterationsBehavior,
+
+    /// What to do with earlier agents' system prompts in a borrowed
+    /// history. Default: drop them.
+    #[serde(default)]
+    pub system_prompt_policy: SystemPromptPolicy,
+
     /// Strip <think>...</think> reasoning blocks from responses before
     /// storing in chat history or returning as output. Default: true.
     pub strip_think_blocks: bool,
@@ -28,6 +102,134 @@ pub struct AgentConfig {
     /// Tool loop detection configuration
     #[serde(skip)]
     pub tool_loop_detection: Option<ToolLoopDetectionConfig>,
+
+    /// Reasoning effort, mapped by the client onto its native mechanism
+    #[serde(default)]
+    pub effort: Option<Effort>,
+
+    /// Sampling parameters sent with every LLM request. Unset ones fall
+    /// back to [`DEFAULT_TEMPERATURE`] and [`DEFAULT_MAX_TOKENS`], or are
+    /// left to the provider.
+    #[serde(default)]
+    pub sampling: SamplingParams,
+
+    /// How to retry tools' transient failures, unless the tool sets its
+    /// own policy. Default: [`RetryPolicy::transient_tool`].
+    #[serde(skip, default = "RetryPolicy::transient_tool")]
+    pub tool_retry: RetryPolicy,
+
+    /// How to retry LLM requests that fail transiently (network errors,
+    /// rate limits, 5xx responses). Default: no retries.
+    #[serde(skip)]
+    pub retry_policy: Option<RetryPolicy>,
+
+    /// Longest one tool call may run, unless the tool sets its own
+    /// timeout. A call that runs over is abandoned and the LLM is told it
+    /// timed out. Default: unbounded.
+    #[serde(default)]
+    pub default_tool_timeout: Option<Duration>,
+
+    /// Longest tool result sent back to the LLM, in bytes of JSON; longer
+    /// ones are compacted, see [`crate::tools::truncation`]. `None` sends
+    /// results whole. Default: [`DEFAULT_MAX_TOOL_RESULT_BYTES`].
+    #[serde(default = "default_max_tool_result_bytes")]
+    pub max_tool_result_bytes: Option<usize>,
+
+    /// Compacts oversized tool results instead of
+    /// [`truncate_tool_result`](crate::tools::truncate_tool_result)
+    #[serde(skip)]
+    pub tool_result_transformer: Option<Arc<dyn ToolResultTransformer>>,
+
+    /// Wall-clock budget for one execution. Tool retries are not scheduled
+    /// past it, and tools see it as `ToolRunContext::deadline`.
+    #[serde(default)]
+    pub deadline: Option<Duration>,
+
+    /// Limits on each streamed LLM request: `first_response` bounds the
+    /// wait for the first chunk, `total` the whole request. A request that
+    /// runs over is dropped and fails as a retryable network error.
+    /// Default: unbounded.
+    #[serde(skip)]
+    pub timeouts: Option<TimeoutConfig>,
+
+    /// Warn the agent as its time, iterations or tokens run low; see
+    /// [`budget`]. Default: off.
+    #[serde(default)]
+    pub budget_signals: Option<BudgetSignals>,
+
+    /// Start predictable, side-effect-free first tool calls alongside the
+    /// first LLM call; see [`speculation`]. Default: off.
+    #[serde(skip)]
+    pub speculative_prefetch: Option<SpeculativePrefetcher>,
+
+    /// Critique the final answer and revise it if the critique finds
+    /// issues; see [`reflection`]. Default: off.
+    #[serde(default)]
+    pub reflection: Option<ReflectionConfig>,
+
+    /// Checks on each input and final output, run in order; see
+    /// [`guardrail`]. Default: none.
+    #[serde(skip)]
+    pub guardrails: Vec<Arc<dyn Guardrail>>,
+
+    /// Agents this one may hand the conversation to through the `handoff`
+    /// tool; see [`handoff`]. Default: none, and no tool.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub handoffs: Vec<String>,
+
+    /// Whether tools go to the provider as schemas or are described in the
+    /// prompt; see [`tool_prompting`]. Default: native.
+    #[serde(default)]
+    pub tool_calling: ToolCallingMode,
+}
+
+/// What an agent does when it reaches `max_tool_iterations`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaxIterationsBehavior {
+    /// Fail the execution with `AgentError::ExecutionError`
+    Error,
+
+    /// Make one more LLM call without tools, telling the model its tool
+    /// budget is spent, and return that answer with
+    /// `AgentOutputMetadata::iterations_exhausted` set
+    #[default]
+    FinalAnswer,
+}
+
+/// What an agent does with other agents' system prompts in the history it
+/// is given, e.g. a workflow's shared history
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SystemPromptPolicy {
+    /// Drop them; the model sees only this agent's system prompt
+    #[default]
+    Strip,
+
+    /// Replace each with a short user note, "Previously, agent X was
+    /// instructed to: ...", so the model knows why earlier turns read as
+    /// they do without taking the instructions as its own
+    Annotate,
+
+    /// Send them unchanged, after this agent's system prompt
+    Keep,
+}
+
+/// Temperature of agent requests whose sampling parameters don't set one
+pub const DEFAULT_TEMPERATURE: f32 = 0.7;
+
+/// `max_tokens` of agent requests whose sampling parameters don't set one
+pub const DEFAULT_MAX_TOKENS: u32 = 8192;
+
+/// Longest system prompt quoted in full by [`SystemPromptPolicy::Annotate`]
+const ANNOTATED_PROMPT_MAX_CHARS: usize = 400;
+
+/// System note for the tool-less call after `max_tool_iterations`
+pub(crate) const ITERATIONS_EXHAUSTED_NOTE: &str =
+    "Tool budget exhausted — answer with what you have. Do not call any more tools.";
+
+fn default_max_tool_result_bytes() -> Option<usize> {
+    Some(DEFAULT_MAX_TOOL_RESULT_BYTES)
 }
 
 impl std::fmt::Debug for AgentConfig {
@@ -35,16 +237,47 @@ impl std::fmt::Debug for AgentConfig {
         f.debug_struct("AgentConfig")
             .field("name", &self.name)
             .field("system_prompt", &self.system_prompt)
+            .field(
+                "system_prompt_template",
+                &self
+                    .system_prompt_template
+                    .as_ref()
+                    .map(|t| t.has_placeholders()),
+            )
             .field(
                 "tools",
                 &self.tools.as_ref().map(|t| format!("{} tools", t.len())),
             )
+            .field("allowed_tools", &self.allowed_tools)
             .field("max_tool_iterations", &self.max_tool_iterations)
+            .field("on_max_iterations", &self.on_max_iterations)
+            .field("system_prompt_policy", &self.system_prompt_policy)
             .field("strip_think_blocks", &self.strip_think_blocks)
             .field(
                 "tool_loop_detection",
                 &self.tool_loop_detection.as_ref().map(|c| c.enabled),
             )
+            .field("effort", &self.effort)
+            .field("sampling", &self.sampling)
+            .field("tool_retry", &self.tool_retry)
+            .field("retry_policy", &self.retry_policy)
+            .field("default_tool_timeout", &self.default_tool_timeout)
+            .field("max_tool_result_bytes", &self.max_tool_result_bytes)
+            .field(
+                "tool_result_transformer",
+                &self.tool_result_transformer.as_ref().map(|_| "custom"),
+            )
+            .field("deadline", &self.deadline)
+            .field("timeouts", &self.timeouts)
+            .field("budget_signals", &self.budget_signals)
+            .field("speculative_prefetch", &self.speculative_prefetch)
+            .field("reflection", &self.reflection)
+            .field(
+                "guardrails",
+                &self.guardrails.iter().map(|g| g.name()).collect::<Vec<_>>(),
+            )
+            .field("handoffs", &self.handoffs)
+            .field("tool_calling", &self.tool_calling)
             .finish()
     }
 }
@@ -54,27 +287,105 @@ impl AgentConfig {
         AgentConfigBuilder {
             name: name.into(),
             system_prompt: String::new(),
+            system_prompt_template: None,
             tools: None,
+            allowed_tools: None,
             max_tool_iterations: 10,
+            on_max_iterations: MaxIterationsBehavior::default(),
+            system_prompt_policy: SystemPromptPolicy::default(),
             strip_think_blocks: false,
             tool_loop_detection: Some(ToolLoopDetectionConfig::default()),
+            effort: None,
+            sampling: SamplingParams::default(),
+            llm_defaults: SamplingParams::default(),
+            tool_retry: RetryPolicy::transient_tool(),
+            retry_policy: None,
+            default_tool_timeout: None,
+            max_tool_result_bytes: default_max_tool_result_bytes(),
+            tool_result_transformer: None,
+            deadline: None,
+            timeouts: None,
+            budget_signals: None,
+            speculative_prefetch: None,
+            reflection: None,
+            guardrails: Vec::new(),
+            handoffs: Vec::new(),
+            tool_calling: ToolCallingMode::default(),
         }
     }
+
+    /// Whether `allowed_tools` lets this agent use tool `name`
+    pub fn allows_tool(&self, name: &str) -> bool {
+        self.allowed_tools
+            .as_ref()
+            .is_none_or(|allowed| allowed.iter().any(|a| a == name))
+    }
+
+    /// The schemas of the tools this agent may use, for the LLM
+    pub(crate) fn tool_schemas(&self) -> Vec<serde_json::Value> {
+        let mut schemas = match &self.tools {
+            Some(registry) => registry.list_tools(),
+            None => Vec::new(),
+        };
+        let handoffs = !self.handoffs.is_empty();
+        if self.allowed_tools.is_some() || handoffs {
+            schemas.retain(|schema| {
+                schema["function"]["name"].as_str().is_some_and(|name| {
+                    self.allows_tool(name) && !(handoffs && name == handoff::HANDOFF_TOOL)
+                })
+            });
+        }
+        if handoffs {
+            schemas.push(handoff::tool_schema(&self.handoffs));
+        }
+        schemas
+    }
 }
 
 /// Builder for AgentConfig
 pub struct AgentConfigBuilder {
     name: String,
     system_prompt: String,
+    system_prompt_template: Option<PromptTemplate>,
     tools: Option<Arc<ToolRegistry>>,
+    allowed_tools: Option<Vec<String>>,
     max_tool_iterations: usize,
+    on_max_iterations: MaxIterationsBehavior,
+    system_prompt_policy: SystemPromptPolicy,
     strip_think_blocks: bool,
     tool_loop_detection: Option<ToolLoopDetectionConfig>,
+    effort: Option<Effort>,
+    sampling: SamplingParams,
+    llm_defaults: SamplingParams,
+    tool_retry: RetryPolicy,
+    retry_policy: Option<RetryPolicy>,
+    default_tool_timeout: Option<Duration>,
+    max_tool_result_bytes: Option<usize>,
+    tool_result_transformer: Option<Arc<dyn ToolResultTransformer>>,
+    deadline: Option<Duration>,
+    timeouts: Option<TimeoutConfig>,
+    budget_signals: Option<BudgetSignals>,
+    speculative_prefetch: Option<SpeculativePrefetcher>,
+    reflection: Option<ReflectionConfig>,
+    guardrails: Vec<Arc<dyn Guardrail>>,
+    handoffs: Vec<String>,
+    tool_calling: ToolCallingMode,
 }
 
 impl AgentConfigBuilder {
     pub fn system_prompt(mut self, prompt: impl Into<String>) -> Self {
         self.system_prompt = prompt.into();
+        self.system_prompt_template = None;
+        self
+    }
+
+    /// A system prompt with `{{placeholders}}`, filled in at each execution
+    /// from the input's variables, workflow memory and the built-ins (see
+    /// [`prompt`])
+    pub fn system_prompt_template(mut self, template: impl Into<PromptTemplate>) -> Self {
+        let template = template.into();
+        self.system_prompt = template.text().to_string();
+        self.system_prompt_template = Some(template);
         self
     }
 
@@ -83,11 +394,34 @@ impl AgentConfigBuilder {
         self
     }
 
+    /// Only let this agent see and call the named tools of its registry
+    pub fn allowed_tools<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allowed_tools = Some(names.into_iter().map(Into::into).collect());
+        self
+    }
+
     pub fn max_tool_iterations(mut self, max: usize) -> Self {
         self.max_tool_iterations = max;
         self
     }
 
+    /// Fail, or answer without tools, once `max_tool_iterations` is reached
+    pub fn on_max_iterations(mut self, behavior: MaxIterationsBehavior) -> Self {
+        self.on_max_iterations = behavior;
+        self
+    }
+
+    /// Drop, annotate or keep other agents' system prompts found in the
+    /// history this agent is given
+    pub fn system_prompt_policy(mut self, policy: SystemPromptPolicy) -> Self {
+        self.system_prompt_policy = policy;
+        self
+    }
+
     pub fn tool_loop_detection(mut self, config: ToolLoopDetectionConfig) -> Self {
         self.tool_loop_detection = Some(config);
         self
@@ -104,22 +438,187 @@ impl AgentConfigBuilder {
         self
     }
 
+    /// Ask the model to think harder (or less). Each client maps this onto
+    /// its native mechanism; see [`crate::llm::effort`].
+    pub fn effort(mut self, effort: Effort) -> Self {
+        self.effort = Some(effort);
+        self
+    }
+
+    /// Send a fixed sampling seed with every request, for reproducible
+    /// output where the provider supports it
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.sampling.seed = Some(seed);
+        self
+    }
+
+    /// End responses at any of `stop`: sent to the provider, and enforced
+    /// on the stream for providers that ignore it
+    pub fn stop_sequences<I, S>(mut self, stop: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.sampling.stop = Some(stop.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Cut responses after `max` characters, dropping the request
+    pub fn max_response_chars(mut self, max: usize) -> Self {
+        self.sampling.max_response_chars = Some(max);
+        self
+    }
+
+    /// Sampling parameters for every request, replacing any set before
+    pub fn sampling(mut self, sampling: SamplingParams) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    /// Fall back to `config`'s `default_temperature` and
+    /// `default_max_tokens` for parameters [`sampling`](Self::sampling)
+    /// leaves unset
+    pub fn llm_defaults(mut self, config: &LlmConfig) -> Self {
+        self.llm_defaults = SamplingParams::from_config(config);
+        self
+    }
+
+    /// Retry policy for tools' transient failures; `RetryPolicy::no_retry()`
+    /// turns automatic retries off
+    pub fn tool_retry(mut self, policy: RetryPolicy) -> Self {
+        self.tool_retry = policy;
+        self
+    }
+
+    /// Retry transient LLM request failures with this policy
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Abandon tool calls that run longer than `timeout`, for tools that
+    /// don't set their own
+    pub fn default_tool_timeout(mut self, timeout: Duration) -> Self {
+        self.default_tool_timeout = Some(timeout);
+        self
+    }
+
+    /// Compact tool results longer than `bytes` before the LLM sees them
+    pub fn max_tool_result_bytes(mut self, bytes: usize) -> Self {
+        self.max_tool_result_bytes = Some(bytes);
+        self
+    }
+
+    /// Send tool results to the LLM whatever their size
+    pub fn unlimited_tool_results(mut self) -> Self {
+        self.max_tool_result_bytes = None;
+        self
+    }
+
+    /// Compact oversized tool results with `transformer`
+    pub fn tool_result_transformer(mut self, transformer: Arc<dyn ToolResultTransformer>) -> Self {
+        self.tool_result_transformer = Some(transformer);
+        self
+    }
+
+    /// Time budget for one execution
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Time out streamed LLM requests that don't start or finish in time
+    pub fn timeouts(mut self, timeouts: TimeoutConfig) -> Self {
+        self.timeouts = Some(timeouts);
+        self
+    }
+
+    /// Tell the agent what budget is left as it crosses `signals`'
+    /// thresholds
+    pub fn budget_signals(mut self, signals: BudgetSignals) -> Self {
+        self.budget_signals = Some(signals);
+        self
+    }
+
+    /// Speculatively run the first tool calls `prefetcher` predicts
+    pub fn speculative_prefetch(mut self, prefetcher: SpeculativePrefetcher) -> Self {
+        self.speculative_prefetch = Some(prefetcher);
+        self
+    }
+
+    /// Critique the final answer before returning it, revising per
+    /// `config`
+    pub fn reflection(mut self, config: ReflectionConfig) -> Self {
+        self.reflection = Some(config);
+        self
+    }
+
+    /// Check each input before the first LLM call and the final output
+    /// before it's returned, with `guardrails` in order
+    pub fn guardrails(mut self, guardrails: Vec<Arc<dyn Guardrail>>) -> Self {
+        self.guardrails = guardrails;
+        self
+    }
+
+    /// Offer the `handoff` tool, naming the agents this one may hand the
+    /// conversation to
+    pub fn handoffs<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.handoffs = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Offer tools natively, in the prompt for models without function
+    /// calling, or natively until that fails
+    pub fn tool_calling(mut self, mode: ToolCallingMode) -> Self {
+        self.tool_calling = mode;
+        self
+    }
+
     pub fn build(self) -> AgentConfig {
         AgentConfig {
             name: self.name,
             system_prompt: self.system_prompt,
+            system_prompt_template: self.system_prompt_template,
             tools: self.tools,
+            allowed_tools: self.allowed_tools,
             max_tool_iterations: self.max_tool_iterations,
+            on_max_iterations: self.on_max_iterations,
+            system_prompt_policy: self.system_prompt_policy,
             strip_think_blocks: self.strip_think_blocks,
             tool_loop_detection: self.tool_loop_detection,
+            effort: self.effort,
+            sampling: self.sampling.or(&self.llm_defaults),
+            tool_retry: self.tool_retry,
+            retry_policy: self.retry_policy,
+            default_tool_timeout: self.default_tool_timeout,
+            max_tool_result_bytes: self.max_tool_result_bytes,
+            tool_result_transformer: self.tool_result_transformer,
+            deadline: self.deadline,
+            timeouts: self.timeouts,
+            budget_signals: self.budget_signals,
+            speculative_prefetch: self.speculative_prefetch,
+            reflection: self.reflection,
+            guardrails: self.guardrails,
+            handoffs: self.handoffs,
+            tool_calling: self.tool_calling,
         }
     }
 }
 
 /// Agent execution unit
+///
+/// Clones share the LLM client, tools and latency tracking.
+#[derive(Clone)]
 pub struct Agent {
     config: AgentConfig,
     llm_client: Option<LlmClient>,
+    artifact_store: Option<ArtifactStore>,
+    latency_slo: Option<LatencySlo>,
+    turn_slots: Option<Arc<tokio::sync::Semaphore>>,
 }
 
 impl Agent {
@@ -127,6 +626,9 @@ impl Agent {
         Self {
             config,
             llm_client: None,
+            artifact_store: None,
+            latency_slo: None,
+            turn_slots: None,
         }
     }
 
@@ -135,6 +637,63 @@ impl Agent {
         self
     }
 
+    /// Store artifacts produced by tools here when running outside a workflow.
+    /// Inside a workflow the runtime's run-scoped store takes precedence.
+    pub fn with_artifact_store(mut self, store: ArtifactStore) -> Self {
+        self.artifact_store = Some(store);
+        self
+    }
+
+    /// Track turns against a latency target and report slow ones
+    pub fn with_latency_slo(mut self, slo: LatencySlo) -> Self {
+        self.latency_slo = Some(slo);
+        self
+    }
+
+    /// Run at most `max_concurrent` turns at once; further turns wait in a
+    /// queue and the wait shows up as `queue_wait_ms` in their latency
+    pub fn with_turn_limit(mut self, max_concurrent: usize) -> Self {
+        self.turn_slots = Some(Arc::new(tokio::sync::Semaphore::new(max_concurrent.max(1))));
+        self
+    }
+
+    /// This agent with `tools` in place of its own
+    #[cfg(feature = "workflow")]
+    pub(crate) fn with_tool_view(&self, tools: Arc<ToolRegistry>) -> Agent {
+        // Tools the view adds, e.g. the workflow's memory tools, aren't
+        // restricted by `allowed_tools`
+        let allowed_tools = self.config.allowed_tools.as_ref().map(|allowed| {
+            let own = self.config.tools.as_deref();
+            let mut allowed = allowed.clone();
+            allowed.extend(
+                tools
+                    .list_names()
+                    .into_iter()
+                    .filter(|name| !own.is_some_and(|own| own.has_tool(name))),
+            );
+            allowed
+        });
+        Agent {
+            config: AgentConfig {
+                tools: Some(tools),
+                allowed_tools,
+                ..self.config.clone()
+            },
+            llm_client: self.llm_client.clone(),
+            artifact_store: self.artifact_store.clone(),
+            latency_slo: self.latency_slo.clone(),
+            turn_slots: self.turn_slots.clone(),
+        }
+    }
+
+    pub fn artifact_store(&self) -> Option<&ArtifactStore> {
+        self.artifact_store.as_ref()
+    }
+
+    pub fn latency_slo(&self) -> Option<&LatencySlo> {
+        self.latency_slo.as_ref()
+    }
+
     pub fn name(&self) -> &str {
         &self.config.name
     }
@@ -143,6 +702,21 @@ impl Agent {
         &self.config
     }
 
+    /// The system prompt this agent sends for `input`: its template
+    /// rendered, or `system_prompt` as is
+    pub fn system_prompt_for(&self, input: &AgentInput) -> Result<Cow<'_, str>, AgentError> {
+        let Some(template) = &self.config.system_prompt_template else {
+            return Ok(Cow::Borrowed(&self.config.system_prompt));
+        };
+        template
+            .render_with(|| {
+                input
+                    .vars
+                    .resolve(prompt::builtins(&self.config.name, &input.metadata))
+            })
+            .map_err(|e| AgentError::InvalidInput(e.to_string()))
+    }
+
     /// Execute the agent with the given input
     pub async fn execute(&self, input: &AgentInput) -> AgentResult {
         self.execute_with_events(input.clone(), None).await
@@ -153,14 +727,321 @@ impl Agent {
         &self,
         input: AgentInput,
         event_stream: Option<&EventStream>,
+    ) -> AgentResult {
+        self.execute_with_artifacts(input, event_stream, self.artifact_store.as_ref())
+            .await
+    }
+
+    /// Execute the agent, storing tool-produced artifacts in `artifacts`
+    pub async fn execute_with_artifacts(
+        &self,
+        input: AgentInput,
+        event_stream: Option<&EventStream>,
+        artifacts: Option<&ArtifactStore>,
+    ) -> AgentResult {
+        self.execute_with_cancellation(input, event_stream, artifacts, CancellationToken::new())
+            .await
+    }
+
+    /// Execute the agent, handing `cancellation` to every tool it calls.
+    /// A tool that returns `ToolError::Canceled` ends the turn with
+    /// [`AgentError::Canceled`].
+    pub async fn execute_with_cancellation(
+        &self,
+        input: AgentInput,
+        event_stream: Option<&EventStream>,
+        artifacts: Option<&ArtifactStore>,
+        cancellation: CancellationToken,
+    ) -> AgentResult {
+        let recorder = TurnRecorder::new(&self.config.name, Instant::now());
+        let _slot = match &self.turn_slots {
+            Some(slots) => {
+                let wait_started = Instant::now();
+                let permit = slots.clone().acquire_owned().await.ok();
+                recorder.record_queue_wait(wait_started.elapsed());
+                permit
+            }
+            None => None,
+        };
+
+        let workflow_id = input
+            .metadata
+            .workflow_id
+            .clone()
+            .or_else(|| input.metadata.previous_agent.clone())
+            .unwrap_or_else(|| "workflow".to_string());
+        let input_history = self.latency_slo.as_ref().and(input.chat_history.clone());
+
+        let result = recorder
+            .scope(self.guarded_turn(
+                input,
+                event_stream,
+                artifacts,
+                cancellation,
+                &recorder,
+                &workflow_id,
+            ))
+            .await;
+        let latency = recorder.finish();
+        crate::metrics::agent_finished(
+            &self.config.name,
+            result.is_ok(),
+            Duration::from_secs_f64(latency.total_ms / 1000.0),
+        );
+
+        if let Some(slo) = &self.latency_slo {
+            let messages = match &result {
+                Ok(output) => output.chat_history.as_deref(),
+                Err(_) => None,
+            }
+            .or(input_history.as_deref())
+            .unwrap_or_default();
+            self.check_latency_slo(
+                slo,
+                &latency,
+                messages,
+                result.as_ref().err().map(|e| e.to_string()),
+                workflow_id,
+                event_stream,
+            )
+            .await;
+        }
+
+        result.map(|mut output| {
+            output.metadata.latency = Some(latency);
+            output
+        })
+    }
+
+    /// Run the turn between the configured guardrails' input and output
+    /// checks
+    async fn guarded_turn(
+        &self,
+        mut input: AgentInput,
+        event_stream: Option<&EventStream>,
+        artifacts: Option<&ArtifactStore>,
+        cancellation: CancellationToken,
+        recorder: &TurnRecorder,
+        workflow_id: &str,
+    ) -> AgentResult {
+        let mut checks = Vec::new();
+        for guardrail in &self.config.guardrails {
+            let started = std::time::Instant::now();
+            let decision = guardrail.check_input(&input).await;
+            if let Some(data) = self.record_guardrail(
+                guardrail.as_ref(),
+                GuardrailStage::Input,
+                decision,
+                started,
+                &mut checks,
+                workflow_id,
+                event_stream,
+            )? {
+                input.data = data;
+            }
+        }
+
+        // The turn's state machine is large; boxing it keeps every caller
+        // of the agent from growing by another copy
+        let mut output =
+            Box::pin(self.run_turn(input, event_stream, artifacts, cancellation, recorder)).await?;
+        for guardrail in &self.config.guardrails {
+            let started = std::time::Instant::now();
+            let decision = guardrail.check_output(&output).await;
+            if let Some(value) = self.record_guardrail(
+                guardrail.as_ref(),
+                GuardrailStage::Output,
+                decision,
+                started,
+                &mut checks,
+                workflow_id,
+                event_stream,
+            )? {
+                guardrail::apply_output(&mut output, value);
+            }
+        }
+        output.metadata.guardrails = checks;
+        Ok(output)
+    }
+
+    /// Record a guardrail's decision, emitting an event unless it allowed
+    /// the value. Returns the replacement value of a modification; a block
+    /// fails the turn with [`AgentError::Blocked`].
+    #[allow(clippy::too_many_arguments)]
+    fn record_guardrail(
+        &self,
+        guardrail: &dyn Guardrail,
+        stage: GuardrailStage,
+        decision: GuardrailDecision,
+        started: std::time::Instant,
+        checks: &mut Vec<GuardrailCheck>,
+        workflow_id: &str,
+        event_stream: Option<&EventStream>,
+    ) -> Result<Option<serde_json::Value>, AgentError> {
+        let outcome = match &decision {
+            GuardrailDecision::Allow => GuardrailOutcome::Allowed,
+            GuardrailDecision::Block { .. } => GuardrailOutcome::Blocked,
+            GuardrailDecision::Modify(_) => GuardrailOutcome::Modified,
+        };
+        checks.push(GuardrailCheck {
+            guardrail: guardrail.name().to_string(),
+            stage,
+            outcome,
+            duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+        });
+
+        match decision {
+            GuardrailDecision::Allow => Ok(None),
+            GuardrailDecision::Modify(value) => {
+                if let Some(stream) = event_stream {
+                    stream.guardrail_modified(
+                        &self.config.name,
+                        workflow_id.to_string(),
+                        serde_json::json!({
+                            "guardrail": guardrail.name(),
+                            "stage": stage,
+                            "value": value,
+                        }),
+                    );
+                }
+                Ok(Some(value))
+            }
+            GuardrailDecision::Block { reason } => {
+                if let Some(stream) = event_stream {
+                    stream.guardrail_blocked(
+                        &self.config.name,
+                        workflow_id.to_string(),
+                        &reason,
+                        serde_json::json!({
+                            "guardrail": guardrail.name(),
+                            "stage": stage,
+                            "checks": checks,
+                        }),
+                    );
+                }
+                Err(AgentError::Blocked {
+                    guardrail: guardrail.name().to_string(),
+                    reason,
+                })
+            }
+        }
+    }
+
+    /// Record the turn against the SLO; build, emit and store a
+    /// [`SlowTurnReport`] if it was too slow
+    async fn check_latency_slo(
+        &self,
+        slo: &LatencySlo,
+        latency: &TurnLatency,
+        messages: &[ChatMessage],
+        error: Option<String>,
+        workflow_id: String,
+        event_stream: Option<&EventStream>,
+    ) {
+        if slo.tracker().record(latency.total_ms) {
+            return;
+        }
+
+        let report = SlowTurnReport {
+            turn_id: latency.turn_id.clone(),
+            agent_name: self.config.name.clone(),
+            target_ms: slo.target().as_secs_f64() * 1000.0,
+            latency: latency.clone(),
+            dominant: latency.dominant_component(),
+            context: latency::ContextSnapshot::from_messages(messages, slo.max_input_tokens()),
+            error,
+            timestamp: chrono::Utc::now(),
+        };
+
+        if let Some(stream) = event_stream {
+            stream.append(
+                crate::event::EventScope::System,
+                crate::event::EventType::Progress,
+                "system:slow_turn".to_string(),
+                crate::event::ComponentStatus::Running,
+                workflow_id,
+                Some(report.summary()),
+                serde_json::json!({
+                    "report": report,
+                    "attainment": slo.attainment(),
+                }),
+            );
+        }
+
+        // The event above already carries the report, so a failed save
+        // doesn't lose it
+        if let Some(store) = slo.store() {
+            let _ = store.save(&report).await;
+        }
+    }
+
+    /// Record the outcome of a conversation cap check, emitting an event for
+    /// each cap hit. A rejection fails the turn with
+    /// [`AgentError::LimitReached`].
+    fn apply_limits(
+        &self,
+        outcome: Result<Vec<LimitEvent>, LimitExceeded>,
+        limit_events: &mut Vec<LimitEvent>,
+        workflow_id: &str,
+        event_stream: Option<&EventStream>,
+    ) -> Result<(), AgentError> {
+        match outcome {
+            Ok(events) => {
+                if let Some(stream) = event_stream {
+                    for event in &events {
+                        event.emit(stream, workflow_id.to_string(), Some(&self.config.name));
+                    }
+                }
+                limit_events.extend(events);
+                Ok(())
+            }
+            Err(exceeded) => {
+                if let Some(stream) = event_stream {
+                    exceeded
+                        .event()
+                        .emit(stream, workflow_id.to_string(), Some(&self.config.name));
+                    stream.agent_failed(
+                        &self.config.name,
+                        workflow_id.to_string(),
+                        &exceeded.to_string(),
+                        serde_json::json!({
+                            "limit": exceeded,
+                            "user_message": exceeded.user_message(),
+                        }),
+                    );
+                }
+                Err(AgentError::LimitReached(exceeded))
+            }
+        }
+    }
+
+    async fn run_turn(
+        &self,
+        input: AgentInput,
+        event_stream: Option<&EventStream>,
+        artifacts: Option<&ArtifactStore>,
+        cancellation: CancellationToken,
+        recorder: &TurnRecorder,
     ) -> AgentResult {
         let start = std::time::Instant::now();
+        let tool_ctx = ToolRunContext {
+            agent_name: Some(self.config.name.clone()),
+            tool_call_id: None,
+            artifacts: artifacts.cloned(),
+            cancellation,
+            deadline: self.config.deadline.map(|d| Instant::now() + d),
+        };
+        let mut produced_artifacts: Vec<ArtifactRef> = Vec::new();
+        // Set when a runtime with a heartbeat runs this agent
+        let activity = crate::runtime::heartbeat::current();
 
         let workflow_id = input
             .metadata
-            .previous_agent
+            .workflow_id
             .clone()
+            .or_else(|| input.metadata.previous_agent.clone())
             .unwrap_or_else(|| "workflow".to_string());
+        let system_prompt = self.system_prompt_for(&input)?;
 
         // Emit Agent::Started event
         if let Some(stream) = event_stream {
@@ -175,79 +1056,155 @@ impl Agent {
 
         // If we have an LLM client, use it
         if let Some(client) = &self.llm_client {
-            // Build messages from chat_history OR from input data
+            // Build messages from chat_history OR from input data. Notes
+            // standing in for other agents' prompts are for this request only.
+            let mut annotations = Vec::new();
             let messages = if let Some(history) = &input.chat_history {
-                // Always strip any existing system message and prepend this
-                // agent's own system prompt so each agent in a chain operates
-                // under its own persona regardless of what previous agents left.
-                let non_system: Vec<_> = history
-                    .iter()
-                    .filter(|m| m.role != crate::llm::types::Role::System)
-                    .cloned()
-                    .collect();
-
-                let mut msgs: Vec<ChatMessage> = Vec::with_capacity(non_system.len() + 1);
-                if !self.config.system_prompt.is_empty() {
-                    msgs.push(ChatMessage::system(&self.config.system_prompt));
+                // This agent's own system prompt always comes first, so each
+                // agent in a chain operates under its own persona regardless
+                // of what previous agents left
+                let (borrowed, notes) = self.borrowed_history(history, &system_prompt);
+                annotations = notes;
+                let mut msgs: Vec<ChatMessage> = Vec::with_capacity(borrowed.len() + 1);
+                if !system_prompt.is_empty() {
+                    msgs.push(ChatMessage::system(system_prompt.as_ref()));
                 }
-                msgs.extend(non_system);
+                msgs.extend(borrowed);
 
                 // If the history ends with a non-user message the LLM has no new
                 // prompt to respond to. Append a user turn derived from input.data
-                // so the chained agent knows what to act on.
+                // so the chained agent knows what to act on. Notes don't count.
                 let needs_user_turn = msgs
-                    .last()
+                    .iter()
+                    .rfind(|m| !annotations.contains(m))
                     .map(|m| m.role != crate::llm::types::Role::User)
                     .unwrap_or(true);
 
                 if needs_user_turn && !input.data.is_null() {
                     // If data is a step-output JSON with a "response" key, use that;
                     // otherwise serialise the whole value.
-                    let user_text = input
+                    let user_content = input
                         .data
                         .get("response")
                         .and_then(|v| v.as_str())
-                        .map(|s| s.to_string())
-                        .unwrap_or_else(|| {
-                            if let Some(s) = input.data.as_str() {
-                                s.to_string()
-                            } else {
-                                serde_json::to_string_pretty(&input.data).unwrap_or_default()
-                            }
-                        });
-                    msgs.push(ChatMessage::user(user_text));
+                        .map(MessageContent::from)
+                        .unwrap_or_else(|| user_content(&input.data));
+                    msgs.push(ChatMessage::user(user_content));
                 }
 
                 msgs
             } else {
                 // Build messages from scratch (legacy behavior)
-                let user_message = if let Some(s) = input.data.as_str() {
-                    s.to_string()
-                } else {
-                    serde_json::to_string_pretty(&input.data).unwrap_or_default()
-                };
-
                 vec![
-                    ChatMessage::system(&self.config.system_prompt),
-                    ChatMessage::user(&user_message),
+                    ChatMessage::system(system_prompt.as_ref()),
+                    ChatMessage::user(user_content(&input.data)),
                 ]
             };
 
-            let mut request = ChatRequest::new(messages.clone())
-                .with_temperature(0.7)
-                .with_max_tokens(8192);
+            // Conversation caps apply to the history plus the new user turn
+            let limits = input.limits.clone().unwrap_or_default();
+            let mut limit_events: Vec<LimitEvent> = Vec::new();
+            let mut messages = messages;
+            self.apply_limits(
+                limits.enforce(&mut messages),
+                &mut limit_events,
+                &workflow_id,
+                event_stream,
+            )?;
+            let mut tool_exchanges = std::collections::VecDeque::new();
+
+            let sampling = self.config.sampling.clone().or(&SamplingParams {
+                temperature: Some(DEFAULT_TEMPERATURE),
+                max_tokens: Some(DEFAULT_MAX_TOKENS),
+                ..SamplingParams::default()
+            });
+            let mut request = ChatRequest::new(messages.clone()).with_sampling(&sampling);
 
-            // Get tool schemas if available
-            let tool_schemas = self
+            // Map the effort knob onto the client's native mechanism
+            let applied_effort: Option<AppliedEffort> = self
                 .config
-                .tools
+                .effort
                 .as_ref()
-                .map(|registry| registry.list_tools())
-                .filter(|tools| !tools.is_empty());
+                .map(|effort| client.apply_effort(&mut request, effort));
+            if let (Some(stream), Some(warning)) = (
+                event_stream,
+                applied_effort.as_ref().and_then(|a| a.warning.as_ref()),
+            ) {
+                stream.append(
+                    crate::event::EventScope::System,
+                    crate::event::EventType::Progress,
+                    "system:effort".to_string(),
+                    crate::event::ComponentStatus::Running,
+                    workflow_id.clone(),
+                    Some(warning.clone()),
+                    serde_json::json!({
+                        "agent": self.config.name,
+                        "effort": applied_effort,
+                    }),
+                );
+            }
+
+            // Get tool schemas if available, re-read whenever the registry
+            // changes mid-run
+            let mut tools_generation = self.config.tools.as_ref().map(|r| r.generation());
+            let mut tool_schemas =
+                Some(self.config.tool_schemas()).filter(|tools| !tools.is_empty());
+
+            let mut budget = self.config.budget_signals.as_ref().map(|signals| {
+                BudgetTracker::new(
+                    signals,
+                    tool_ctx.deadline.zip(self.config.deadline),
+                    self.config.max_tool_iterations,
+                )
+            });
+
+            // Start predictable first tool calls so they overlap the first
+            // LLM call
+            let speculation_input = request
+                .messages
+                .iter()
+                .rev()
+                .find(|m| m.role == crate::llm::types::Role::User)
+                .map(|m| m.content.text().into_owned());
+            // Only tools the agent may call are run early
+            let speculation_tools =
+                self.config
+                    .tools
+                    .as_ref()
+                    .map(|registry| match &self.config.allowed_tools {
+                        Some(allowed) => Arc::new(registry.subset(allowed)),
+                        None => registry.clone(),
+                    });
+            let mut speculation = match (
+                &self.config.speculative_prefetch,
+                &speculation_tools,
+                &speculation_input,
+            ) {
+                (Some(prefetcher), Some(registry), Some(input)) => Some(Speculation::start(
+                    prefetcher,
+                    input,
+                    registry,
+                    &tool_ctx,
+                    self.config.default_tool_timeout,
+                    &self.config.name,
+                    &workflow_id,
+                    event_stream,
+                ))
+                .filter(|s| !s.is_idle()),
+                _ => None,
+            };
+            let mut first_tool_call: Option<(String, serde_json::Value)> = None;
+            // Set by a call to the handoff tool; ends the turn
+            let mut handoff: Option<Handoff> = None;
+            // Whether tools are described in the prompt, and the message
+            // describing them while they're offered
+            let mut prompted = self.config.tool_calling == ToolCallingMode::Prompted;
+            let mut tool_prompt: Option<ChatMessage> = None;
 
             // Tool calling loop
             let mut iteration = 0;
             let mut total_tool_calls = 0;
+            let mut usage = crate::usage::UsageTotals::default();
 
             // Initialize tool call tracker for loop detection
             let mut tool_tracker = if self.config.tool_loop_detection.is_some() {
@@ -255,23 +1212,188 @@ impl Agent {
             } else {
                 None
             };
+            // Set for the one tool-less call after the iteration cap
+            let mut iterations_exhausted = false;
+            // Set for the tool-less call that spends the run's budget
+            let mut last_call = false;
+            let mut reflection = self
+                .config
+                .reflection
+                .as_ref()
+                .filter(|c| c.enabled)
+                .map(|_| ReflectionReport::default());
 
             loop {
                 iteration += 1;
+                if let Some(activity) = &activity {
+                    activity.record_iteration(iteration);
+                }
+
+                // Don't start another model call once the run is ending
+                if tool_ctx.is_canceled() {
+                    let reason = "canceled before the next LLM request";
+                    if let Some(stream) = event_stream {
+                        stream.agent_canceled(
+                            &self.config.name,
+                            workflow_id.clone(),
+                            reason,
+                            serde_json::json!({ "iteration": iteration }),
+                        );
+                    }
+                    return Err(AgentError::Canceled(reason.to_string()));
+                }
+
+                // Nor one the run's budget can't pay for; the last call it
+                // can must answer
+                match crate::usage::check_budget() {
+                    crate::usage::BudgetCheck::Exceeded(exceeded) => {
+                        if let Some(stream) = event_stream {
+                            stream.agent_failed(
+                                &self.config.name,
+                                workflow_id.clone(),
+                                &exceeded.to_string(),
+                                serde_json::json!({
+                                    "iteration": iteration,
+                                    "budget": exceeded,
+                                }),
+                            );
+                        }
+                        return Err(AgentError::BudgetExceeded(exceeded));
+                    }
+                    crate::usage::BudgetCheck::LastCall
+                        if !last_call && !iterations_exhausted && tool_schemas.is_some() =>
+                    {
+                        last_call = true;
+                        request
+                            .messages
+                            .push(ChatMessage::system(ITERATIONS_EXHAUSTED_NOTE));
+                        if let Some(stream) = event_stream {
+                            stream.append(
+                                crate::event::EventScope::System,
+                                crate::event::EventType::Progress,
+                                "system:run_budget".to_string(),
+                                crate::event::ComponentStatus::Running,
+                                workflow_id.clone(),
+                                Some(
+                                    "Run budget allows one more LLM call; asking for a final answer"
+                                        .to_string(),
+                                ),
+                                serde_json::json!({
+                                    "agent": self.config.name,
+                                    "iteration": iteration,
+                                }),
+                            );
+                        }
+                    }
+                    _ => {}
+                }
 
                 // Check iteration limit
-                if iteration > self.config.max_tool_iterations {
-                    return Err(AgentError::ExecutionError(format!(
-                        "Maximum tool iterations ({}) exceeded",
-                        self.config.max_tool_iterations
-                    )));
+                if iteration > self.config.max_tool_iterations && !iterations_exhausted {
+                    if self.config.on_max_iterations == MaxIterationsBehavior::Error {
+                        return Err(AgentError::ExecutionError(format!(
+                            "Maximum tool iterations ({}) exceeded",
+                            self.config.max_tool_iterations
+                        )));
+                    }
+                    iterations_exhausted = true;
+                    request
+                        .messages
+                        .push(ChatMessage::system(ITERATIONS_EXHAUSTED_NOTE));
+                    if let Some(stream) = event_stream {
+                        stream.append(
+                            crate::event::EventScope::System,
+                            crate::event::EventType::Progress,
+                            "system:iterations_exhausted".to_string(),
+                            crate::event::ComponentStatus::Running,
+                            workflow_id.clone(),
+                            Some(format!(
+                                "Tool budget of {} iterations exhausted; asking for a final answer",
+                                self.config.max_tool_iterations
+                            )),
+                            serde_json::json!({
+                                "agent": self.config.name,
+                                "max_tool_iterations": self.config.max_tool_iterations,
+                                "tool_calls": total_tool_calls,
+                            }),
+                        );
+                    }
+                }
+
+                // Tell the agent how much budget is left once it runs low
+                if let Some(signal) = budget.as_mut().and_then(|b| b.check(iteration)) {
+                    request.messages.push(ChatMessage::user(&signal.message));
+                    if let Some(stream) = event_stream {
+                        stream.append(
+                            crate::event::EventScope::System,
+                            crate::event::EventType::Progress,
+                            "system:budget".to_string(),
+                            crate::event::ComponentStatus::Running,
+                            workflow_id.clone(),
+                            Some(format!(
+                                "{:?} budget {:.0}% used",
+                                signal.budget,
+                                signal.used * 100.0
+                            )),
+                            serde_json::json!({
+                                "agent": self.config.name,
+                                "signal": signal,
+                            }),
+                        );
+                    }
                 }
 
-                // Add tools to request if available
-                if let Some(ref schemas) = tool_schemas {
-                    request = request.with_tools(schemas.clone());
+                // Offer tools registered since the last request, and stop
+                // offering removed ones
+                let generation = self.config.tools.as_ref().map(|r| r.generation());
+                if generation != tools_generation {
+                    tools_generation = generation;
+                    let schemas = Some(self.config.tool_schemas()).filter(|t| !t.is_empty());
+                    if let Some(stream) = event_stream {
+                        self.emit_tool_changes(
+                            stream,
+                            &workflow_id,
+                            tool_schemas.as_deref(),
+                            schemas.as_deref(),
+                            iteration,
+                        );
+                    }
+                    tool_schemas = schemas;
                 }
 
+                // Add tools to request if available; a degraded request
+                // keeps them, so the cached prefix stays the same, but must
+                // answer instead
+                let (offered, tool_choice) = match &budget {
+                    _ if iterations_exhausted || last_call => (None, None),
+                    Some(budget) if budget.degraded() => {
+                        request.max_tokens = budget.max_tokens(request.max_tokens);
+                        (tool_schemas.clone(), Some(ToolChoice::None))
+                    }
+                    _ => (tool_schemas.clone(), None),
+                };
+                if prompted {
+                    // Tools in the prompt can only be left out
+                    let offered = offered.filter(|_| tool_choice.is_none());
+                    tool_prompting::offer(
+                        &mut request.messages,
+                        &mut tool_prompt,
+                        offered.as_deref(),
+                    );
+                    request.tools = None;
+                    request.tool_choice = None;
+                } else {
+                    request.tool_choice = tool_choice.filter(|_| offered.is_some());
+                    request.tools = offered;
+                }
+
+                // Say which call this is to wrapping clients, e.g. logging
+                request.metadata = HashMap::from([
+                    ("workflow_id".to_string(), workflow_id.clone()),
+                    ("agent".to_string(), self.config.name.clone()),
+                    ("iteration".to_string(), iteration.to_string()),
+                ]);
+
                 // Emit LlmRequest::Started event
                 if let Some(stream) = event_stream {
                     stream.llm_started(
@@ -280,37 +1402,190 @@ impl Agent {
                         workflow_id.clone(),
                         serde_json::json!({
                             "messages": request.messages.len(),
+                            "effort": applied_effort,
+                            "sampling": sampling,
                         }),
                     );
                 }
 
-                // Call LLM with streaming + full response (for tool calls)
-                let event_stream_for_streaming = event_stream.cloned();
-                let agent_name = self.config.name.clone();
-                let workflow_id_for_streaming = workflow_id.clone();
-
-                // Create channel for streaming chunks
-                let (chunk_tx, mut chunk_rx) = tokio::sync::mpsc::channel(100);
-
-                // Spawn task to receive chunks and emit events
-                let chunk_event_task = tokio::spawn(async move {
-                    while let Some(chunk) = chunk_rx.recv().await {
-                        if let Some(stream) = &event_stream_for_streaming {
-                            stream.llm_progress(
-                                &agent_name,
-                                iteration,
-                                workflow_id_for_streaming.clone(),
-                                chunk,
-                            );
+                // Call LLM with streaming + full response (for tool calls),
+                // retrying transient failures under the configured policy
+                let retry_started = Instant::now();
+                let llm_span = crate::telemetry::llm_span(
+                    &self.config.name,
+                    client.provider_name(),
+                    iteration,
+                );
+                let stops =
+                    stop::StopConditions::new(&request, sampling.max_response_chars, prompted);
+                let mut attempts = 1;
+                let (result, llm_started, first_chunk, timed_out) = loop {
+                    let event_stream_for_streaming = event_stream.cloned();
+                    let agent_name = self.config.name.clone();
+                    let workflow_id_for_streaming = workflow_id.clone();
+                    let activity_for_streaming = activity.clone();
+                    let tools_offered = request.offers_tools();
+
+                    // Create channel for streaming chunks
+                    let (chunk_tx, mut chunk_rx) = tokio::sync::mpsc::channel(100);
+
+                    // Spawn task to receive chunks and emit events
+                    let chunk_event_task =
+                        tokio::spawn(crate::event::sampling::propagate(async move {
+                            let mut first_chunk = None;
+                            while let Some(chunk) = chunk_rx.recv().await {
+                                first_chunk.get_or_insert_with(Instant::now);
+                                if let Some(activity) = &activity_for_streaming {
+                                    activity.record_chunk();
+                                }
+                                if let Some(stream) = &event_stream_for_streaming {
+                                    stream.llm_progress(
+                                        &agent_name,
+                                        iteration,
+                                        workflow_id_for_streaming.clone(),
+                                        chunk,
+                                        tools_offered,
+                                    );
+                                }
+                            }
+                            first_chunk
+                        }));
+
+                    recorder.start_llm_call();
+                    let llm_started = Instant::now();
+                    // Cancellation and timeouts drop the request, closing the
+                    // provider's stream mid-response
+                    let call = async {
+                        // Batched runs are answered by the batch, not the client
+                        if let Some(result) = batch::intercept(&request).await {
+                            return Ok(result);
+                        }
+                        let permit =
+                            rate_limit::acquire_current(client.provider_name(), &request).await;
+                        let result = chat_stream_within(
+                            client.as_ref(),
+                            request.clone(),
+                            chunk_tx,
+                            self.config.timeouts.as_ref(),
+                            &stops,
+                        )
+                        .await;
+                        if let (Some(permit), Ok(Ok(response))) = (permit, &result) {
+                            permit.settle(response.usage.as_ref());
+                        }
+                        result
+                    }
+                    .instrument(llm_span.clone());
+                    let result = tokio::select! {
+                        result = call => result,
+                        _ = tool_ctx.cancellation.cancelled() => {
+                            let first_chunk = chunk_event_task.await.ok().flatten();
+                            recorder.record_llm_call(iteration, llm_started, first_chunk, false);
+                            let reason = "canceled during the LLM request";
+                            crate::telemetry::finish(&llm_span, retry_started.elapsed(), Some(reason));
+                            if let Some(stream) = event_stream {
+                                stream.llm_canceled(
+                                    &self.config.name,
+                                    iteration,
+                                    workflow_id.clone(),
+                                    reason,
+                                );
+                                stream.agent_canceled(
+                                    &self.config.name,
+                                    workflow_id.clone(),
+                                    reason,
+                                    serde_json::json!({ "iteration": iteration }),
+                                );
+                            }
+                            return Err(AgentError::Canceled(reason.to_string()));
+                        }
+                    };
+                    // Wait for chunk event task to finish processing all chunks
+                    // This ensures all Progress events are emitted before Completed
+                    let first_chunk = chunk_event_task.await.ok().flatten();
+
+                    let (result, timed_out) = match result {
+                        Ok(result) => (result, None),
+                        Err(timeout) => (Err(timeout.error()), Some(timeout.kind())),
+                    };
+                    let error = match &result {
+                        Err(e) if e.is_retryable() => e,
+                        _ => break (result, llm_started, first_chunk, timed_out),
+                    };
+                    let Some(delay) = self.config.retry_policy.as_ref().and_then(|policy| {
+                        policy.next_delay(
+                            attempts - 1,
+                            retry_started.elapsed(),
+                            tool_ctx.time_left(),
+                        )
+                    }) else {
+                        break (result, llm_started, first_chunk, timed_out);
+                    };
+
+                    recorder.record_llm_call(iteration, llm_started, first_chunk, false);
+                    latency::record_retry("llm", error.to_string());
+                    crate::metrics::retry("llm");
+                    if let Some(stream) = event_stream {
+                        stream.llm_attempt_failed(
+                            &self.config.name,
+                            iteration,
+                            workflow_id.clone(),
+                            attempts,
+                            &error.to_string(),
+                            timeout_data(
+                                serde_json::json!({ "delay_ms": delay.as_millis() as u64 }),
+                                timed_out,
+                            ),
+                        );
+                    }
+                    attempts += 1;
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {}
+                        _ = tool_ctx.cancellation.cancelled() => {
+                            let reason = "canceled while waiting to retry the LLM request";
+                            if let Some(stream) = event_stream {
+                                stream.agent_canceled(
+                                    &self.config.name,
+                                    workflow_id.clone(),
+                                    reason,
+                                    serde_json::json!({ "iteration": iteration }),
+                                );
+                            }
+                            return Err(AgentError::Canceled(reason.to_string()));
                         }
                     }
-                });
+                };
 
-                match client.chat_stream(request.clone(), chunk_tx).await {
-                    Ok(response) => {
-                        // Wait for chunk event task to finish processing all chunks
-                        // This ensures all Progress events are emitted before Completed
-                        let _ = chunk_event_task.await;
+                match result {
+                    Ok(mut response) => {
+                        recorder.record_llm_call(iteration, llm_started, first_chunk, true);
+                        if let Some(provider) = &response.provider {
+                            llm_span.record("provider", provider.as_str());
+                        }
+                        llm_span.record("model", response.model.as_str());
+                        llm_span.record("attempts", attempts);
+                        if let Some(reason) = &response.finish_reason {
+                            llm_span.record("finish_reason", reason.as_str());
+                        }
+                        crate::telemetry::record_usage(&llm_span, response.usage.as_ref());
+                        crate::telemetry::finish(&llm_span, retry_started.elapsed(), None);
+                        crate::metrics::llm_request(
+                            response
+                                .provider
+                                .as_deref()
+                                .unwrap_or_else(|| client.provider_name()),
+                            &response.model,
+                            response.usage.as_ref(),
+                            llm_started.elapsed(),
+                        );
+                        if let Some(budget) = &mut budget {
+                            budget.record_usage(response.usage.as_ref());
+                        }
+                        usage.add(&crate::usage::record_llm_call(
+                            &self.config.name,
+                            &response.model,
+                            response.usage.as_ref(),
+                        ));
 
                         // Emit LlmRequest::Completed event
                         if let Some(stream) = event_stream {
@@ -321,54 +1596,176 @@ impl Agent {
                                 serde_json::json!({
                                     "content": response.content.chars().take(100).collect::<String>(),
                                     "has_tool_calls": response.tool_calls.is_some(),
+                                    "finish_reason": response.finish_reason,
+                                    "usage": response.usage,
                                 }),
                             );
+
+                            // Surface repairs made to a malformed provider response
+                            if !response.warnings.is_empty() {
+                                stream.append(
+                                    crate::event::EventScope::System,
+                                    crate::event::EventType::Progress,
+                                    "system:response_validation".to_string(),
+                                    crate::event::ComponentStatus::Running,
+                                    workflow_id.clone(),
+                                    Some(format!(
+                                        "Repaired provider response: {}",
+                                        response.warnings.join("; ")
+                                    )),
+                                    serde_json::json!({
+                                        "agent": self.config.name,
+                                        "iteration": iteration,
+                                        "warnings": response.warnings,
+                                    }),
+                                );
+                            }
+
+                            // A fallback chain had to skip a failing provider
+                            if !response.failovers.is_empty() {
+                                stream.append(
+                                    crate::event::EventScope::System,
+                                    crate::event::EventType::Progress,
+                                    "system:llm_failover".to_string(),
+                                    crate::event::ComponentStatus::Running,
+                                    workflow_id.clone(),
+                                    Some(format!(
+                                        "Served by {} after {} provider(s) failed",
+                                        response.provider.as_deref().unwrap_or("unknown"),
+                                        response.failovers.len()
+                                    )),
+                                    serde_json::json!({
+                                        "agent": self.config.name,
+                                        "iteration": iteration,
+                                        "provider": response.provider,
+                                        "failovers": response.failovers,
+                                    }),
+                                );
+                            }
+                        }
+
+                        // Read tool calls written as text when prompted, and
+                        // watch for them while trying native calls
+                        let mut correction = None;
+                        let offered = match prompted {
+                            true => tool_prompt.is_some(),
+                            false => {
+                                request.offers_tools()
+                                    && self.config.tool_calling == ToolCallingMode::Auto
+                            }
+                        };
+                        if offered && response.tool_calls.as_ref().is_none_or(Vec::is_empty) {
+                            let written = tool_prompting::parse(&response.content, iteration);
+                            if !prompted && !written.is_empty() {
+                                prompted = true;
+                                self.switch_to_prompted(
+                                    "the model wrote a tool call as text",
+                                    iteration,
+                                    &workflow_id,
+                                    event_stream,
+                                );
+                            }
+                            if prompted {
+                                correction = written.correction();
+                                response.tool_calls =
+                                    Some(written.calls).filter(|calls| !calls.is_empty());
+                            }
+                        }
+                        // A call that couldn't be read gets another try
+                        if let (Some(message), None) = (&correction, &response.tool_calls) {
+                            request
+                                .messages
+                                .push(ChatMessage::assistant(response.content.clone()));
+                            request.messages.push(message.clone());
+                            self.apply_limits(
+                                limits.enforce(&mut request.messages),
+                                &mut limit_events,
+                                &workflow_id,
+                                event_stream,
+                            )?;
+                            continue;
                         }
 
-                        // Check if we have tool calls (and they're not empty)
-                        if let Some(tool_calls) = response.tool_calls.clone() {
+                        // Check if we have tool calls (and they're not empty);
+                        // past the cap, any the model still makes are ignored
+                        if let Some(tool_calls) = response
+                            .tool_calls
+                            .clone()
+                            .filter(|_| !iterations_exhausted && !last_call)
+                        {
                             if tool_calls.is_empty() {
                                 // Empty tool calls array - treat as final response
                             } else {
                                 total_tool_calls += tool_calls.len();
-
-                                // Add assistant message with tool calls to conversation
-                                let assistant_msg = ChatMessage::assistant_with_tool_calls(
-                                    response.content.clone(),
-                                    tool_calls.clone(),
+                                crate::usage::record_tool_calls(
+                                    &self.config.name,
+                                    tool_calls.len(),
                                 );
+                                if first_tool_call.is_none() {
+                                    first_tool_call = tool_calls.first().map(|call| {
+                                        (
+                                            call.function.name.clone(),
+                                            serde_json::from_str(&call.function.arguments)
+                                                .unwrap_or(serde_json::json!({})),
+                                        )
+                                    });
+                                }
+
+                                // Add assistant message with tool calls to
+                                // conversation; written calls are in its text
+                                let assistant_msg = if prompted {
+                                    ChatMessage::assistant(response.content.clone())
+                                } else {
+                                    ChatMessage::assistant_with_tool_calls(
+                                        response.content.clone(),
+                                        tool_calls.clone(),
+                                    )
+                                };
                                 request.messages.push(assistant_msg);
+                                let call_ids: Vec<String> =
+                                    tool_calls.iter().map(|c| c.id.clone()).collect();
+                                let tool_message = |call: &ToolCall, result: &str| {
+                                    if prompted {
+                                        tool_prompting::observation(call, result)
+                                    } else {
+                                        ChatMessage::tool_result(&call.id, result)
+                                    }
+                                };
 
                                 // Execute each tool call
                                 for tool_call in tool_calls {
+                                    if tool_call.function.name == handoff::HANDOFF_TOOL
+                                        && !self.config.handoffs.is_empty()
+                                    {
+                                        let result = self.request_handoff(
+                                            &tool_call,
+                                            &mut handoff,
+                                            iteration,
+                                            &workflow_id,
+                                            event_stream,
+                                        );
+                                        request.messages.push(tool_message(&tool_call, &result));
+                                        continue;
+                                    }
+
                                     // Check for duplicate tool call (loop detection)
                                     if let (Some(tracker), Some(loop_config)) =
                                         (&tool_tracker, &self.config.tool_loop_detection)
                                     {
                                         if loop_config.enabled {
-                                            // Parse tool arguments from JSON string
-                                            let args_value: serde_json::Value =
-                                                serde_json::from_str(&tool_call.function.arguments)
-                                                    .unwrap_or(serde_json::json!({}));
-
-                                            // Convert to HashMap for comparison
-                                            let args_map: HashMap<String, serde_json::Value> =
-                                                args_value
-                                                    .as_object()
-                                                    .map(|obj| {
-                                                        obj.iter()
-                                                            .map(|(k, v)| (k.clone(), v.clone()))
-                                                            .collect()
-                                                    })
-                                                    .unwrap_or_default();
-
-                                            if let Some(previous_result) = tracker
-                                                .check_for_loop(&tool_call.function.name, &args_map)
-                                            {
+                                            if let Some(detected) = tracker.detect(
+                                                loop_config,
+                                                &tool_call.function.name,
+                                                &tool_call.function.arguments,
+                                            ) {
                                                 // Loop detected! Inject message instead of calling tool
-                                                let loop_message = loop_config.get_message(
+                                                crate::metrics::tool_loop_detected(
+                                                    &self.config.name,
+                                                    &tool_call.function.name,
+                                                );
+                                                let loop_message = loop_config.loop_message(
                                                     &tool_call.function.name,
-                                                    &previous_result,
+                                                    &detected,
                                                 );
 
                                                 // Emit tool loop detected event (System scope)
@@ -386,17 +1783,16 @@ impl Agent {
                                                         serde_json::json!({
                                                             "agent": self.config.name,
                                                             "tool": tool_call.function.name,
+                                                            "rule": detected.rule.name(),
                                                             "message": loop_message,
                                                         }),
                                                     );
                                                 }
 
                                                 // Add system message explaining the loop
-                                                let tool_msg = ChatMessage::tool_result(
-                                                    &tool_call.id,
-                                                    &loop_message,
-                                                );
-                                                request.messages.push(tool_msg);
+                                                request
+                                                    .messages
+                                                    .push(tool_message(&tool_call, &loop_message));
 
                                                 // Skip actual tool execution
                                                 continue;
@@ -405,53 +1801,109 @@ impl Agent {
                                     }
 
                                     // No loop detected - execute the tool normally
-                                    let tool_result = self
+                                    let tool_started = Instant::now();
+                                    let tool_span = crate::telemetry::tool_span(
+                                        &self.config.name,
+                                        &tool_call.function.name,
+                                        &tool_call.id,
+                                    );
+                                    let outcome = self
                                         .execute_tool_call(
                                             &tool_call,
-                                            &input
-                                                .metadata
-                                                .previous_agent
-                                                .clone()
-                                                .unwrap_or_else(|| "workflow".to_string()),
+                                            &workflow_id,
                                             event_stream,
+                                            &tool_ctx,
+                                            &mut produced_artifacts,
+                                            speculation.as_mut(),
                                         )
+                                        .instrument(tool_span.clone())
                                         .await;
+                                    let tool_error = match &outcome {
+                                        Ok((_, result)) => result.error.as_ref().map(|error| {
+                                            format!("{}: {}", error.code, error.message)
+                                        }),
+                                        Err(e) => Some(e.to_string()),
+                                    };
+                                    crate::telemetry::finish(
+                                        &tool_span,
+                                        tool_started.elapsed(),
+                                        tool_error.as_deref(),
+                                    );
+                                    recorder.record_tool_call(
+                                        &tool_call.function.name,
+                                        &tool_call.id,
+                                        tool_started,
+                                    );
+                                    if let Some(activity) = &activity {
+                                        activity.record_progress();
+                                    }
+
+                                    // A canceled tool ends the turn; the LLM
+                                    // never sees the result
+                                    let (tool_result, structured) = match outcome {
+                                        Ok(outcome) => outcome,
+                                        Err(e) => {
+                                            if let Some(stream) = event_stream {
+                                                stream.agent_canceled(
+                                                    &self.config.name,
+                                                    workflow_id.clone(),
+                                                    &e.to_string(),
+                                                    serde_json::json!({
+                                                        "tool": tool_call.function.name,
+                                                        "tool_call_id": tool_call.id,
+                                                        "iteration": iteration,
+                                                    }),
+                                                );
+                                            }
+                                            return Err(e);
+                                        }
+                                    };
 
                                     // Record this call in the tracker
                                     if let Some(tracker) = &mut tool_tracker {
-                                        // Parse tool arguments from JSON string
-                                        let args_value: serde_json::Value =
-                                            serde_json::from_str(&tool_call.function.arguments)
-                                                .unwrap_or(serde_json::json!({}));
-
-                                        // Convert to HashMap
-                                        let args_map: HashMap<String, serde_json::Value> =
-                                            args_value
-                                                .as_object()
-                                                .map(|obj| {
-                                                    obj.iter()
-                                                        .map(|(k, v)| (k.clone(), v.clone()))
-                                                        .collect()
-                                                })
-                                                .unwrap_or_default();
-
-                                        let result_json = serde_json::to_value(&tool_result)
-                                            .unwrap_or(serde_json::json!({}));
-                                        tracker.record_call(
+                                        tracker.record_raw_call(
                                             &tool_call.function.name,
-                                            &args_map,
-                                            &result_json,
+                                            &tool_call.function.arguments,
+                                            &structured,
                                         );
                                     }
 
                                     // Add tool result to conversation
-                                    let tool_msg =
-                                        ChatMessage::tool_result(&tool_call.id, &tool_result);
-                                    request.messages.push(tool_msg);
+                                    request
+                                        .messages
+                                        .push(tool_message(&tool_call, &tool_result));
                                 }
+                                request.messages.extend(correction);
 
-                                // Continue loop to get next response
-                                continue;
+                                // Speculation only covers the first request
+                                if let Some(speculation) = &mut speculation {
+                                    speculation.settle(event_stream);
+                                }
+
+                                tool_exchanges.push_back(call_ids);
+                                self.apply_limits(
+                                    limits
+                                        .enforce_tool_messages(
+                                            &mut request.messages,
+                                            &mut tool_exchanges,
+                                        )
+                                        .map(Vec::from_iter),
+                                    &mut limit_events,
+                                    &workflow_id,
+                                    event_stream,
+                                )?;
+                                self.apply_limits(
+                                    limits.enforce(&mut request.messages),
+                                    &mut limit_events,
+                                    &workflow_id,
+                                    event_stream,
+                                )?;
+
+                                // Continue loop to get next response, unless
+                                // another agent takes over
+                                if handoff.is_none() {
+                                    continue;
+                                }
                             }
                         }
 
@@ -463,22 +1915,82 @@ impl Agent {
                             raw_response.to_string()
                         };
 
+                        // Critique the answer; a rejection with revisions left
+                        // sends the issues back instead of returning
+                        if let (Some(config), Some(report), None) = (
+                            self.config.reflection.as_ref().filter(|c| c.enabled),
+                            reflection.as_mut(),
+                            &handoff,
+                        ) {
+                            let verdict = self
+                                .critique(
+                                    client,
+                                    config,
+                                    &request.messages,
+                                    &response_text,
+                                    report.verdicts.len() as u32,
+                                    &mut usage,
+                                    &workflow_id,
+                                    event_stream,
+                                )
+                                .await?;
+                            let revise =
+                                verdict.rejects() && report.revisions < config.max_revisions;
+                            if revise {
+                                request.messages.push(
+                                    ChatMessage::assistant(&response_text)
+                                        .with_provenance(&self.config.name, &workflow_id),
+                                );
+                                request.messages.push(ChatMessage::user(
+                                    reflection::revision_request(&verdict.issues),
+                                ));
+                                report.revisions += 1;
+                            }
+                            report.verdicts.push(verdict);
+                            if revise {
+                                self.apply_limits(
+                                    limits.enforce(&mut request.messages),
+                                    &mut limit_events,
+                                    &workflow_id,
+                                    event_stream,
+                                )?;
+                                continue;
+                            }
+                        }
+
                         let token_count = response
                             .usage
                             .map(|u| u.total_tokens)
                             .unwrap_or_else(|| (response_text.len() as f32 / 4.0).ceil() as u32);
 
-                        let output_data = serde_json::json!({
+                        let mut output_data = serde_json::json!({
                             "response": response_text,
                             "content_type": "text/plain",
                             "token_count": token_count,
                         });
+                        if !produced_artifacts.is_empty() {
+                            output_data["artifacts"] =
+                                serde_json::to_value(&produced_artifacts).unwrap_or_default();
+                        }
+                        if let Some(handoff) = &handoff {
+                            output_data["handoff"] =
+                                serde_json::to_value(handoff).unwrap_or_default();
+                        }
 
-                        // Add final assistant response with provenance to chat history
-                        request.messages.push(
-                            ChatMessage::assistant(&response_text)
-                                .with_provenance(&self.config.name, &workflow_id),
-                        );
+                        // Add final assistant response with provenance to chat
+                        // history; a handoff's is already there, with its call
+                        if handoff.is_none() {
+                            request.messages.push(
+                                ChatMessage::assistant(&response_text)
+                                    .with_provenance(&self.config.name, &workflow_id),
+                            );
+                        }
+                        self.apply_limits(
+                            limits.enforce(&mut request.messages),
+                            &mut limit_events,
+                            &workflow_id,
+                            event_stream,
+                        )?;
 
                         // Emit Agent::Completed event
                         if let Some(stream) = event_stream {
@@ -493,21 +2005,99 @@ impl Agent {
                                     "execution_time_ms": start.elapsed().as_millis() as u64,
                                     "tool_calls": total_tool_calls,
                                     "iterations": iteration,
+                                    "handoff": handoff,
                                 }),
                             );
                         }
 
+                        let speculation = speculation.map(|s| s.finish(event_stream));
+                        if let (Some(prefetcher), Some(input)) =
+                            (&self.config.speculative_prefetch, &speculation_input)
+                        {
+                            prefetcher.observe(
+                                input,
+                                first_tool_call
+                                    .as_ref()
+                                    .map(|(tool, args)| (tool.as_str(), args)),
+                            );
+                        }
+
+                        // Notices and tool descriptions were for this
+                        // execution only
+                        request.messages.retain(|m| {
+                            !annotations.contains(m) && Some(m) != tool_prompt.as_ref()
+                        });
+                        let budget_signals = match budget {
+                            Some(budget) => {
+                                budget.strip_notices(&mut request.messages);
+                                budget.into_signals()
+                            }
+                            None => Vec::new(),
+                        };
+
                         return Ok(AgentOutput {
                             data: output_data,
                             metadata: AgentOutputMetadata {
                                 agent_name: self.config.name.clone(),
                                 execution_time_ms: start.elapsed().as_millis() as u64,
                                 tool_calls_count: total_tool_calls,
+                                effort: applied_effort,
+                                latency: None,
+                                limit_events,
+                                budget_signals,
+                                speculation,
+                                usage,
+                                iterations_exhausted,
+                                reflection,
+                                guardrails: Vec::new(),
+                                handoff,
                             },
                             chat_history: Some(request.messages),
                         });
                     }
+                    // A provider without tool calling gets them in the prompt
+                    Err(e)
+                        if !prompted
+                            && request.tools.is_some()
+                            && self.config.tool_calling == ToolCallingMode::Auto
+                            && tool_prompting::rejects_tools(&e) =>
+                    {
+                        recorder.record_llm_call(iteration, llm_started, first_chunk, false);
+                        crate::metrics::llm_error(client.provider_name());
+                        llm_span.record("attempts", attempts);
+                        crate::telemetry::finish(
+                            &llm_span,
+                            retry_started.elapsed(),
+                            Some(&e.to_string()),
+                        );
+                        if let Some(stream) = event_stream {
+                            stream.llm_failed(
+                                &self.config.name,
+                                iteration,
+                                workflow_id.clone(),
+                                &e.to_string(),
+                                serde_json::json!({}),
+                            );
+                        }
+                        prompted = true;
+                        self.switch_to_prompted(
+                            &format!("the provider rejected the tools: {}", e),
+                            iteration,
+                            &workflow_id,
+                            event_stream,
+                        );
+                        continue;
+                    }
                     Err(e) => {
+                        recorder.record_llm_call(iteration, llm_started, first_chunk, false);
+                        crate::metrics::llm_error(client.provider_name());
+                        llm_span.record("attempts", attempts);
+                        crate::telemetry::finish(
+                            &llm_span,
+                            retry_started.elapsed(),
+                            Some(&e.to_string()),
+                        );
+
                         // Emit LlmRequest::Failed event
                         if let Some(stream) = event_stream {
                             stream.llm_failed(
@@ -515,6 +2105,7 @@ impl Agent {
                                 iteration,
                                 workflow_id.clone(),
                                 &e.to_string(),
+                                timeout_data(serde_json::json!({}), timed_out),
                             );
                         }
 
@@ -528,10 +2119,11 @@ impl Agent {
                             );
                         }
 
-                        return Err(AgentError::ExecutionError(format!(
-                            "LLM call failed: {}",
-                            e
-                        )));
+                        return Err(AgentError::ExecutionError(if attempts > 1 {
+                            format!("LLM call failed after {} attempts: {}", attempts, e)
+                        } else {
+                            format!("LLM call failed: {}", e)
+                        }));
                     }
                 }
             }
@@ -540,7 +2132,7 @@ impl Agent {
             let output_data = serde_json::json!({
                 "agent": self.config.name,
                 "processed": input.data,
-                "system_prompt": self.config.system_prompt,
+                "system_prompt": system_prompt,
                 "note": "Mock execution - no LLM client configured"
             });
 
@@ -562,6 +2154,16 @@ impl Agent {
                     agent_name: self.config.name.clone(),
                     execution_time_ms: start.elapsed().as_millis() as u64,
                     tool_calls_count: 0,
+                    effort: None,
+                    latency: None,
+                    limit_events: Vec::new(),
+                    budget_signals: Vec::new(),
+                    speculation: None,
+                    usage: Default::default(),
+                    iterations_exhausted: false,
+                    reflection: None,
+                    guardrails: Vec::new(),
+                    handoff: None,
                 },
                 chat_history: None, // No LLM client means no chat history
             })
@@ -574,6 +2176,145 @@ impl Agent {
 /// Some reasoning models (e.g. Qwen-thinking, DeepSeek-R1) wrap their
 /// chain-of-thought in these tags. We remove them from chat history and
 /// output so downstream agents and callers only see the final answer.
+/// The [`TimeoutConfig`] limit a streamed LLM request ran into
+#[derive(Debug, Clone, Copy)]
+enum StreamTimeout {
+    FirstResponse(Duration),
+    Total(Duration),
+}
+
+impl StreamTimeout {
+    fn limit(self) -> Duration {
+        match self {
+            StreamTimeout::FirstResponse(limit) | StreamTimeout::Total(limit) => limit,
+        }
+    }
+
+    /// `timeout_kind` in the request's failure event
+    fn kind(self) -> &'static str {
+        match self {
+            StreamTimeout::FirstResponse(_) => "first_response",
+            StreamTimeout::Total(_) => "total",
+        }
+    }
+
+    /// Reported as a network error, so the retry policy retries it
+    fn error(self) -> LlmError {
+        LlmError::NetworkError(match self {
+            StreamTimeout::FirstResponse(limit) => {
+                format!("no response within {}ms", limit.as_millis())
+            }
+            StreamTimeout::Total(limit) => {
+                format!("response not finished within {}ms", limit.as_millis())
+            }
+        })
+    }
+}
+
+/// Stream `request`, forwarding chunks to `tx`, and drop it if it runs
+/// past `timeouts` or meets one of `stops`
+///
+/// A client that doesn't stream sends its whole response as the first
+/// chunk, so `first_response` bounds all of it.
+async fn chat_stream_within(
+    client: &dyn GenericChatClient,
+    request: ChatRequest,
+    tx: mpsc::Sender<String>,
+    timeouts: Option<&TimeoutConfig>,
+    stops: &stop::StopConditions,
+) -> Result<LlmResult<ChatResponse>, StreamTimeout> {
+    debug_assert_eq!(
+        validate_history(&request.messages),
+        Ok(()),
+        "request history has broken tool-call links"
+    );
+    if timeouts.is_none() && stops.is_empty() {
+        return Ok(client.chat_stream(request, tx).await);
+    }
+    let started = Instant::now();
+    let (provider_tx, mut provider_rx) = mpsc::channel(tx.max_capacity());
+    let call = client.chat_stream(request, provider_tx);
+    tokio::pin!(call);
+    let mut scanner = stop::StopScanner::new(stops);
+    let mut first_chunk = false;
+    let mut receiving = true;
+    loop {
+        // The nearest limit that still applies
+        let limit = timeouts.and_then(|timeouts| {
+            [
+                timeouts
+                    .first_response
+                    .filter(|_| !first_chunk)
+                    .map(StreamTimeout::FirstResponse),
+                timeouts.total.map(StreamTimeout::Total),
+            ]
+            .into_iter()
+            .flatten()
+            .min_by_key(|timeout| timeout.limit())
+        });
+        let expired = async {
+            match limit {
+                Some(timeout) => {
+                    tokio::time::sleep_until(started + timeout.limit()).await;
+                    timeout
+                }
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            result = &mut call => {
+                // Pass on what the provider sent before it returned
+                while let Ok(chunk) = provider_rx.try_recv() {
+                    if let Some(text) = scanner.push(&chunk) {
+                        let _ = tx.send(text).await;
+                    }
+                }
+                if let Some(text) = scanner.finish() {
+                    let _ = tx.send(text).await;
+                }
+                return Ok(result.map(|response| stops.apply(response)));
+            }
+            chunk = provider_rx.recv(), if receiving => match chunk {
+                Some(chunk) => {
+                    first_chunk = true;
+                    if let Some(text) = scanner.push(&chunk) {
+                        let _ = tx.send(text).await;
+                    }
+                    if scanner.stopped() && !stops.keep_streaming() {
+                        return Ok(Ok(scanner.response()));
+                    }
+                }
+                None => receiving = false,
+            },
+            timeout = expired => return Err(timeout),
+        }
+    }
+}
+
+/// The user turn for `data`: a string as is, content parts (see
+/// [`AgentInput::from_text_and_images`]) as text and images, anything else
+/// as pretty-printed JSON
+fn user_content(data: &serde_json::Value) -> MessageContent {
+    if let Some(text) = data.as_str() {
+        return MessageContent::from(text);
+    }
+    if data.as_array().is_some_and(|parts| !parts.is_empty()) {
+        if let Ok(parts) = serde_json::from_value::<Vec<ContentPart>>(data.clone()) {
+            return MessageContent::Parts(parts);
+        }
+    }
+    MessageContent::Text(serde_json::to_string_pretty(data).unwrap_or_default())
+}
+
+/// `data` with the `timeout_kind` of a request that timed out
+fn timeout_data(mut data: serde_json::Value, timed_out: Option<&str>) -> serde_json::Value {
+    if let Some(kind) = timed_out {
+        data["timeout_kind"] = kind.into();
+    }
+    data
+}
+
 fn strip_think_blocks(text: &str) -> String {
     let mut result = String::with_capacity(text.len());
     let mut remaining = text;
@@ -599,24 +2340,289 @@ fn strip_think_blocks(text: &str) -> String {
 }
 
 impl Agent {
+    /// `history` without this agent's own system prompt, `own_prompt` as
+    /// rendered for this execution, and with other agents' handled per
+    /// [`SystemPromptPolicy`]; also returns the notes that replaced them
+    fn borrowed_history(
+        &self,
+        history: &[ChatMessage],
+        own_prompt: &str,
+    ) -> (Vec<ChatMessage>, Vec<ChatMessage>) {
+        let policy = self.config.system_prompt_policy;
+        let mut annotations = Vec::new();
+        let borrowed = history
+            .iter()
+            .filter_map(|message| {
+                if message.role != crate::llm::types::Role::System {
+                    return Some(message.clone());
+                }
+                let prompt = message.content.text();
+                let own = message.agent_id.as_deref() == Some(self.config.name.as_str())
+                    || prompt == own_prompt;
+                match policy {
+                    _ if own => None,
+                    SystemPromptPolicy::Strip => None,
+                    SystemPromptPolicy::Keep => Some(message.clone()),
+                    SystemPromptPolicy::Annotate => {
+                        let agent = match &message.agent_id {
+                            Some(agent) => format!("agent {}", agent),
+                            None => "another agent".to_string(),
+                        };
+                        let mut prompt = prompt.trim().to_string();
+                        if let Some((cut, _)) =
+                            prompt.char_indices().nth(ANNOTATED_PROMPT_MAX_CHARS)
+                        {
+                            prompt.truncate(cut);
+                            prompt.push('…');
+                        }
+                        let note = ChatMessage {
+                            agent_id: message.agent_id.clone(),
+                            workflow_id: message.workflow_id.clone(),
+                            ..ChatMessage::user(format!(
+                                "Previously, {} was instructed to: {}",
+                                agent, prompt
+                            ))
+                        };
+                        annotations.push(note.clone());
+                        Some(note)
+                    }
+                }
+            })
+            .collect();
+        (borrowed, annotations)
+    }
+
+    /// Handle a call to the handoff tool, returning the result the model
+    /// sees. The first valid call of the turn sets `handoff`; later ones
+    /// are refused.
+    fn request_handoff(
+        &self,
+        tool_call: &ToolCall,
+        handoff: &mut Option<Handoff>,
+        iteration: usize,
+        workflow_id: &str,
+        event_stream: Option<&EventStream>,
+    ) -> String {
+        if let Some(handoff) = handoff {
+            return format!("Already handing off to {}", handoff.to);
+        }
+        let requested = match handoff::parse(
+            &self.config.name,
+            &self.config.handoffs,
+            &tool_call.function.arguments,
+        ) {
+            Ok(requested) => requested,
+            Err(refusal) => return refusal,
+        };
+        if let Some(stream) = event_stream {
+            stream.agent_handoff(
+                &self.config.name,
+                workflow_id.to_string(),
+                format!("Handing off to {}", requested.to),
+                serde_json::json!({
+                    "handoff": requested,
+                    "iteration": iteration,
+                }),
+            );
+        }
+        let result = format!("Handed off to {}", requested.to);
+        *handoff = Some(requested);
+        result
+    }
+
+    /// Emit a `Tool` event for each tool in `after` but not `before`, and
+    /// for each in `before` but not `after`
+    fn emit_tool_changes(
+        &self,
+        stream: &EventStream,
+        workflow_id: &str,
+        before: Option<&[serde_json::Value]>,
+        after: Option<&[serde_json::Value]>,
+        iteration: usize,
+    ) {
+        let names = |schemas: Option<&[serde_json::Value]>| -> BTreeSet<String> {
+            schemas
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|schema| schema["function"]["name"].as_str().map(str::to_string))
+                .collect()
+        };
+        let (before, after) = (names(before), names(after));
+        let data = serde_json::json!({ "agent": self.config.name, "iteration": iteration });
+        for name in after.difference(&before) {
+            stream.tool_registered(name, workflow_id.to_string(), data.clone());
+        }
+        for name in before.difference(&after) {
+            stream.tool_unregistered(name, workflow_id.to_string(), data.clone());
+        }
+    }
+
+    /// Note that this execution's tools are described in the prompt from
+    /// now on, and why
+    fn switch_to_prompted(
+        &self,
+        reason: &str,
+        iteration: usize,
+        workflow_id: &str,
+        event_stream: Option<&EventStream>,
+    ) {
+        if let Some(stream) = event_stream {
+            stream.append(
+                crate::event::EventScope::System,
+                crate::event::EventType::Progress,
+                "system:tool_calling".to_string(),
+                crate::event::ComponentStatus::Running,
+                workflow_id.to_string(),
+                Some(format!("Describing tools in the prompt: {}", reason)),
+                serde_json::json!({
+                    "agent": self.config.name,
+                    "iteration": iteration,
+                    "mode": ToolCallingMode::Prompted,
+                    "reason": reason,
+                }),
+            );
+        }
+    }
+
+    /// Ask the model to critique `answer`, counting the call in `usage`
+    #[allow(clippy::too_many_arguments)]
+    async fn critique(
+        &self,
+        client: &LlmClient,
+        config: &ReflectionConfig,
+        conversation: &[ChatMessage],
+        answer: &str,
+        round: u32,
+        usage: &mut crate::usage::UsageTotals,
+        workflow_id: &str,
+        event_stream: Option<&EventStream>,
+    ) -> Result<ReflectionVerdict, AgentError> {
+        if let crate::usage::BudgetCheck::Exceeded(exceeded) = crate::usage::check_budget() {
+            if let Some(stream) = event_stream {
+                stream.agent_failed(
+                    &self.config.name,
+                    workflow_id.to_string(),
+                    &exceeded.to_string(),
+                    serde_json::json!({ "round": round, "budget": exceeded }),
+                );
+            }
+            return Err(AgentError::BudgetExceeded(exceeded));
+        }
+
+        if let Some(stream) = event_stream {
+            stream.reflection_started(
+                &self.config.name,
+                workflow_id.to_string(),
+                serde_json::json!({ "round": round }),
+            );
+        }
+
+        let mut request =
+            ChatRequest::new(reflection::critique_messages(conversation, answer, config))
+                .with_temperature(0.0)
+                .with_metadata("workflow_id", workflow_id.to_string())
+                .with_metadata("agent", self.config.name.clone())
+                .with_metadata("reflection_round", round.to_string());
+        request.seed = self.config.sampling.seed;
+        debug_assert_eq!(
+            validate_history(&request.messages),
+            Ok(()),
+            "critique history has broken tool-call links"
+        );
+        let response = match client.chat(request).await {
+            Ok(response) => response,
+            Err(e) => {
+                let message = format!("Reflection request failed: {}", e);
+                if let Some(stream) = event_stream {
+                    stream.reflection_failed(
+                        &self.config.name,
+                        workflow_id.to_string(),
+                        &message,
+                        serde_json::json!({ "round": round }),
+                    );
+                    stream.agent_failed(
+                        &self.config.name,
+                        workflow_id.to_string(),
+                        &message,
+                        serde_json::json!({}),
+                    );
+                }
+                return Err(AgentError::ExecutionError(message));
+            }
+        };
+        usage.add(&crate::usage::record_llm_call_as(
+            "reflection",
+            &self.config.name,
+            &response.model,
+            response.usage.as_ref(),
+        ));
+
+        let verdict = ReflectionVerdict::parse(&response.content);
+        if let Some(stream) = event_stream {
+            stream.reflection_completed(
+                &self.config.name,
+                workflow_id.to_string(),
+                serde_json::json!({
+                    "round": round,
+                    "accepted": !verdict.rejects(),
+                    "verdict": &verdict,
+                    "usage": response.usage,
+                }),
+            );
+        }
+        Ok(verdict)
+    }
+
     /// Execute a single tool call
     async fn execute_tool_call(
         &self,
         tool_call: &ToolCall,
-        previous_agent: &str,
+        workflow_id: &str,
         event_stream: Option<&EventStream>,
-    ) -> String {
+        tool_ctx: &ToolRunContext,
+        produced_artifacts: &mut Vec<ArtifactRef>,
+        speculation: Option<&mut Speculation>,
+    ) -> Result<(String, ToolResult), AgentError> {
         let tool_name = &tool_call.function.name;
 
-        // Emit Tool::Started event
+        // Don't start new work once the run is ending
+        if tool_ctx.is_canceled() {
+            let reason = format!("run canceled before '{}' started", tool_name);
+            if let Some(stream) = event_stream {
+                stream.tool_canceled(
+                    tool_name,
+                    workflow_id.to_string(),
+                    &reason,
+                    serde_json::json!({
+                        "agent": self.config.name,
+                        "tool_call_id": tool_call.id,
+                        "duration_ms": 0,
+                    }),
+                );
+            }
+            return Err(AgentError::Canceled(reason));
+        }
+
+        // Emit Tool::Started event, with any secrets the tool withholds
         if let Some(stream) = event_stream {
+            let arguments = self
+                .config
+                .tools
+                .as_ref()
+                .and_then(|registry| registry.get(tool_name))
+                .and_then(|tool| {
+                    let parsed = serde_json::from_str(&tool_call.function.arguments).ok()?;
+                    tool.redact_arguments(&parsed)
+                })
+                .map(|redacted| redacted.to_string())
+                .unwrap_or_else(|| tool_call.function.arguments.clone());
             stream.tool_started(
                 tool_name,
-                previous_agent.to_string(),
+                workflow_id.to_string(),
                 serde_json::json!({
                     "agent": self.config.name,
                     "tool_call_id": tool_call.id,
-                    "arguments": tool_call.function.arguments,
+                    "arguments": arguments,
                 }),
             );
         }
@@ -626,81 +2632,349 @@ impl Agent {
             Some(reg) => reg,
             None => {
                 let error_msg = "No tool registry configured".to_string();
+                let detail = ToolErrorDetail::new("no_registry", &error_msg);
+                crate::metrics::tool_rejected(tool_name);
                 if let Some(stream) = event_stream {
                     stream.tool_failed(
                         tool_name,
-                        previous_agent.to_string(),
+                        workflow_id.to_string(),
                         &error_msg,
                         serde_json::json!({
                             "agent": self.config.name,
                             "tool_call_id": tool_call.id,
                             "duration_ms": 0,
+                            "status": ToolStatus::Error,
+                            "error": detail,
                         }),
                     );
                 }
-                return format!("Error: {}", error_msg);
+                return Ok((
+                    format!("Error: {}", error_msg),
+                    ToolResult::failed(detail, 0.0),
+                ));
             }
         };
 
+        // Tools outside `allowed_tools` are refused without running; the
+        // LLM is told which it may use
+        if !self.config.allows_tool(tool_name) {
+            let allowed = self.config.allowed_tools.as_deref().unwrap_or_default();
+            let error = ToolError::NotPermitted(format!(
+                "agent '{}' may not call '{}'; its tools are: {}",
+                self.config.name,
+                tool_name,
+                if allowed.is_empty() {
+                    "none".to_string()
+                } else {
+                    allowed.join(", ")
+                }
+            ));
+            let error_msg = format!("Tool execution failed: {}", error);
+            let detail = ToolErrorDetail::from(&error);
+            crate::metrics::tool_rejected(tool_name);
+            if let Some(stream) = event_stream {
+                stream.tool_failed(
+                    tool_name,
+                    workflow_id.to_string(),
+                    &error_msg,
+                    serde_json::json!({
+                        "agent": self.config.name,
+                        "tool_call_id": tool_call.id,
+                        "duration_ms": 0,
+                        "retryable": false,
+                        "status": ToolStatus::Error,
+                        "error": detail,
+                    }),
+                );
+            }
+            return Ok((
+                format!("Error: {}", error_msg),
+                ToolResult::failed(detail, 0.0),
+            ));
+        }
+
         // Parse arguments from JSON string
         let params: HashMap<String, serde_json::Value> =
             match serde_json::from_str(&tool_call.function.arguments) {
                 Ok(p) => p,
                 Err(e) => {
                     let error_msg = format!("Failed to parse tool arguments: {}", e);
+                    let detail = ToolErrorDetail::new("invalid_arguments", &error_msg);
+                    crate::metrics::tool_rejected(tool_name);
                     if let Some(stream) = event_stream {
                         stream.tool_failed(
                             tool_name,
-                            previous_agent.to_string(),
+                            workflow_id.to_string(),
                             &error_msg,
                             serde_json::json!({
                                 "agent": self.config.name,
                                 "tool_call_id": tool_call.id,
                                 "duration_ms": 0,
-                                "duration_ms": 0,
+                                "status": ToolStatus::Error,
+                                "error": detail,
                             }),
                         );
                     }
-                    return format!("Error: {}", error_msg);
+                    return Ok((
+                        format!("Error: {}", error_msg),
+                        ToolResult::failed(detail, 0.0),
+                    ));
                 }
             };
 
+        // Arguments that break the schema go back to the LLM to correct,
+        // without running the tool
+        if let Err(violations) = registry.validate_arguments(tool_name, &params) {
+            let error_msg = format!(
+                "Tool execution failed: {}",
+                crate::tools::registry::invalid_arguments(&violations)
+            );
+            let detail = ToolErrorDetail::new("invalid_arguments", &error_msg);
+            crate::metrics::tool_rejected(tool_name);
+            if let Some(stream) = event_stream {
+                stream.tool_failed(
+                    tool_name,
+                    workflow_id.to_string(),
+                    &error_msg,
+                    serde_json::json!({
+                        "agent": self.config.name,
+                        "tool_call_id": tool_call.id,
+                        "duration_ms": 0,
+                        "retryable": false,
+                        "validation_errors": violations,
+                        "status": ToolStatus::Error,
+                        "error": detail,
+                    }),
+                );
+            }
+            return Ok((
+                format!("Error: {}", error_msg),
+                ToolResult::failed(detail, 0.0),
+            ));
+        }
+
         // Execute the tool
         let start_time = std::time::Instant::now();
-        match registry.call_tool(tool_name, params.clone()).await {
-            Ok(result) => {
-                // Emit Tool::Completed event
+        // Each call gets its own child token, canceled if this future is
+        // dropped (e.g. by a timeout) so spawned work doesn't outlive it
+        let call_token = tool_ctx.cancellation.child_token();
+        let _abandon_guard = call_token.clone().drop_guard();
+        let call_ctx = ToolRunContext {
+            tool_call_id: Some(tool_call.id.clone()),
+            cancellation: call_token,
+            ..tool_ctx.clone()
+        };
+
+        // Retry transient failures in place, so loop detection and the LLM
+        // see one logical call
+        let tool = registry.get(tool_name);
+        let policy = tool
+            .as_ref()
+            .and_then(|tool| tool.retry_policy())
+            .unwrap_or_else(|| self.config.tool_retry.clone());
+        let timeout = tool
+            .as_ref()
+            .and_then(|tool| tool.timeout())
+            .or(self.config.default_tool_timeout);
+        let mut attempts = 1;
+        // A matching speculative result stands in for the call
+        let prefetched = match speculation {
+            Some(speculation) => speculation.take(tool_name, &params, event_stream).await,
+            None => None,
+        };
+        let speculative = prefetched.is_some();
+        let outcome = match prefetched {
+            Some(result) => Ok(result),
+            None => loop {
+                let call = registry.call_tool_with_context(tool_name, params.clone(), &call_ctx);
+                // Anything a timed-out call spawned is canceled by the drop
+                // guard once this call returns
+                let result = match timeout {
+                    Some(limit) => tokio::time::timeout(limit, call)
+                        .await
+                        .unwrap_or(Err(ToolError::TimedOut(limit.as_millis() as u64))),
+                    None => call.await,
+                };
+                let error = match result {
+                    Err(e) if e.is_retryable() => e,
+                    other => break other,
+                };
+                let Some(delay) =
+                    policy.next_delay(attempts - 1, start_time.elapsed(), tool_ctx.time_left())
+                else {
+                    break Err(error);
+                };
+                attempts += 1;
+                crate::metrics::retry("tool");
                 if let Some(stream) = event_stream {
-                    stream.tool_completed(
+                    stream.tool_retrying(
                         tool_name,
-                        previous_agent.to_string(),
+                        workflow_id.to_string(),
+                        attempts,
+                        &error.to_string(),
                         serde_json::json!({
                             "agent": self.config.name,
                             "tool_call_id": tool_call.id,
-                            "result": result.output,
-                            "duration_ms": (result.duration_ms * 1000.0).round() / 1000.0,
+                            "delay_ms": delay.as_millis() as u64,
                         }),
                     );
                 }
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = call_ctx.cancellation.cancelled() => {
+                        break Err(ToolError::Canceled(format!(
+                            "canceled while waiting to retry '{}'",
+                            tool_name
+                        )));
+                    }
+                }
+            },
+        };
+
+        // A canceled call is neither a success nor a tool error
+        if !matches!(outcome, Err(ToolError::Canceled(_))) {
+            let succeeded = outcome
+                .as_ref()
+                .is_ok_and(|result| result.status != ToolStatus::Error);
+            crate::metrics::tool_call(tool_name, succeeded, start_time.elapsed());
+        }
+        match outcome {
+            Ok(mut result) => {
+                // Move artifact bytes into the store; the LLM only sees handles
+                let mut artifact_lines = Vec::new();
+                let mut stored = Vec::new();
+                for artifact in std::mem::take(&mut result.artifacts) {
+                    match &tool_ctx.artifacts {
+                        Some(store) => {
+                            let reference = store.put(artifact);
+                            artifact_lines.push(reference.summary());
+                            stored.push(reference);
+                        }
+                        None => artifact_lines.push(format!(
+                            "[artifact {} discarded: no artifact store configured]",
+                            artifact.name
+                        )),
+                    }
+                }
+
+                // Convert result to string for LLM, compacting successful
+                // output if too long
+                let full = result.to_llm_content();
+                let mut content = match self.config.max_tool_result_bytes {
+                    Some(max) if full.len() > max && result.status == ToolStatus::Success => {
+                        let compacted = match &self.config.tool_result_transformer {
+                            Some(transformer) => {
+                                transformer.compact(tool_name, &result.output, max).await
+                            }
+                            None => truncation::truncate_tool_result(&result.output, max),
+                        };
+                        truncation::cut_text(&compacted, max)
+                    }
+                    Some(max) if full.len() > max => truncation::cut_text(&full, max),
+                    _ => full.clone(),
+                };
+
+                // Emit Tool::Completed event, or Tool::Failed for an error
+                // result
+                if let Some(stream) = event_stream {
+                    let mut data = serde_json::json!({
+                        "agent": self.config.name,
+                        "tool_call_id": tool_call.id,
+                        "duration_ms": (result.duration_ms * 1000.0).round() / 1000.0,
+                        "attempts": attempts,
+                        "status": result.status,
+                    });
+                    // Too large even for the event: reference it by hash
+                    if full.len() > MAX_EVENT_RESULT_BYTES {
+                        data["result_sha256"] = crate::artifact::sha256_hex(full.as_bytes()).into();
+                        data["result_bytes"] = full.len().into();
+                    } else {
+                        data["result"] = result.output.clone();
+                    }
+                    if let Some(message) = &result.message {
+                        data["message"] = message.as_str().into();
+                    }
+                    if let Some(error) = &result.error {
+                        data["error"] = serde_json::to_value(error).unwrap_or_default();
+                        data["retryable"] = error.retryable.into();
+                    }
+                    if content.len() < full.len() {
+                        data["truncated"] = serde_json::json!({
+                            "original_bytes": full.len(),
+                            "sent_bytes": content.len(),
+                        });
+                    }
+                    if !stored.is_empty() {
+                        data["artifacts"] = serde_json::to_value(&stored).unwrap_or_default();
+                    }
+                    if speculative {
+                        data["speculative"] = true.into();
+                    }
+                    if result.cached {
+                        data["cached"] = true.into();
+                    }
+                    if result.status == ToolStatus::Error {
+                        let message = result.message.clone().unwrap_or_default();
+                        stream.tool_failed(tool_name, workflow_id.to_string(), &message, data);
+                    } else {
+                        stream.tool_completed(tool_name, workflow_id.to_string(), data);
+                    }
+                }
+                produced_artifacts.extend(stored);
 
-                // Convert result to string for LLM
-                serde_json::to_string(&result.output).unwrap_or_else(|_| result.output.to_string())
+                // Loop detection quotes back what the LLM saw
+                if content.len() < full.len() {
+                    result.output = serde_json::Value::String(content.clone());
+                }
+                for line in artifact_lines {
+                    content.push('\n');
+                    content.push_str(&line);
+                }
+                Ok((content, result))
+            }
+            Err(ToolError::Canceled(reason)) => {
+                if let Some(stream) = event_stream {
+                    stream.tool_canceled(
+                        tool_name,
+                        workflow_id.to_string(),
+                        &reason,
+                        serde_json::json!({
+                            "agent": self.config.name,
+                            "tool_call_id": tool_call.id,
+                            "duration_ms": start_time.elapsed().as_secs_f64() * 1000.0,
+                            "attempts": attempts,
+                        }),
+                    );
+                }
+                Err(AgentError::Canceled(format!("{}: {}", tool_name, reason)))
             }
             Err(e) => {
-                let error_msg = format!("Tool execution failed: {}", e);
+                let mut error_msg = format!("Tool execution failed: {}", e);
+                if attempts > 1 {
+                    error_msg.push_str(&format!(" (after {} attempts)", attempts));
+                }
+                let elapsed_ms = start_time.elapsed().as_secs_f64() * 1000.0;
+                let detail = ToolErrorDetail::from(&e);
                 if let Some(stream) = event_stream {
                     stream.tool_failed(
                         tool_name,
-                        previous_agent.to_string(),
+                        workflow_id.to_string(),
                         &error_msg,
                         serde_json::json!({
                             "agent": self.config.name,
                             "tool_call_id": tool_call.id,
-                            "duration_ms": start_time.elapsed().as_secs_f64() * 1000.0,
+                            "duration_ms": elapsed_ms,
+                            "attempts": attempts,
+                            "retryable": e.is_retryable(),
+                            "status": ToolStatus::Error,
+                            "error": detail,
                         }),
                     );
                 }
-                format!("Error: {}", error_msg)
+                Ok((
+                    format!("Error: {}", error_msg),
+                    ToolResult::failed(detail, elapsed_ms),
+                ))
             }
         }
     }
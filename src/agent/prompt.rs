@@ -0,0 +1,206 @@
+//! Prompt templates filled in when the agent executes.
+//!
+//! A [`PromptTemplate`] is a [`Template`] whose placeholders are resolved
+//! per execution, from, in priority order:
+//! 1. the input's own variables ([`AgentInput::with_vars`](crate::AgentInput::with_vars))
+//! 2. the workflow's shared memory, when the agent runs as a workflow step
+//! 3. the built-ins `today` (`YYYY-MM-DD`, UTC), `agent_name`, `step_index`
+//!    and `workflow_id` (when the input has one)
+//!
+//! A prompt without placeholders is never re-rendered.
+
+use crate::template::{MissingVariables, Template, TemplateError};
+use crate::types::{AgentInputMetadata, JsonValue};
+use serde::{Deserialize, Serialize};
+use serde_json::Map;
+use std::borrow::Cow;
+use std::sync::Arc;
+
+/// A prompt with `{{placeholders}}`, see the [module docs](self)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "TemplateSpec", into = "TemplateSpec")]
+pub struct PromptTemplate {
+    text: String,
+    on_missing: MissingVariables,
+    compiled: Result<Compiled, TemplateError>,
+}
+
+#[derive(Debug, Clone)]
+enum Compiled {
+    Static(String),
+    Template(Arc<Template>),
+}
+
+#[derive(Serialize, Deserialize)]
+struct TemplateSpec {
+    text: String,
+    #[serde(default)]
+    on_missing: MissingVariables,
+}
+
+impl From<TemplateSpec> for PromptTemplate {
+    fn from(spec: TemplateSpec) -> Self {
+        Self::new(spec.text).on_missing(spec.on_missing)
+    }
+}
+
+impl From<PromptTemplate> for TemplateSpec {
+    fn from(template: PromptTemplate) -> Self {
+        Self {
+            text: template.text,
+            on_missing: template.on_missing,
+        }
+    }
+}
+
+impl PromptTemplate {
+    /// Parse `text`. A syntax error is reported by every render, so a bad
+    /// template fails the agent at execution rather than when it is built.
+    pub fn new(text: impl Into<String>) -> Self {
+        let text = text.into();
+        let compiled =
+            Template::new("prompt", &text).map(|template| match template.static_text() {
+                Some(text) => Compiled::Static(text.to_string()),
+                None => Compiled::Template(Arc::new(template)),
+            });
+        Self {
+            text,
+            on_missing: MissingVariables::default(),
+            compiled,
+        }
+    }
+
+    /// Fail on placeholders no variable fills (the default), or leave them
+    /// in the prompt as written
+    pub fn on_missing(mut self, on_missing: MissingVariables) -> Self {
+        self.on_missing = on_missing;
+        if let Ok(Compiled::Template(template)) = &mut self.compiled {
+            *template = Arc::new(template.as_ref().clone().on_missing(on_missing));
+        }
+        self
+    }
+
+    /// The template as written
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Whether the template has placeholders to fill
+    pub fn has_placeholders(&self) -> bool {
+        !matches!(self.compiled, Ok(Compiled::Static(_)))
+    }
+
+    /// Render with the variables `vars` builds; `vars` isn't called for a
+    /// template without placeholders
+    pub fn render_with(
+        &self,
+        vars: impl FnOnce() -> JsonValue,
+    ) -> Result<Cow<'_, str>, TemplateError> {
+        match &self.compiled {
+            Ok(Compiled::Static(text)) => Ok(Cow::Borrowed(text)),
+            Ok(Compiled::Template(template)) => template.render(&vars()).map(Cow::Owned),
+            Err(e) => Err(e.clone()),
+        }
+    }
+}
+
+impl From<&str> for PromptTemplate {
+    fn from(text: &str) -> Self {
+        Self::new(text)
+    }
+}
+
+impl From<String> for PromptTemplate {
+    fn from(text: String) -> Self {
+        Self::new(text)
+    }
+}
+
+/// Variables for an input's prompt templates, besides the built-ins
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PromptVars {
+    /// Set by the caller; these win
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub input: Map<String, JsonValue>,
+
+    /// The workflow's shared memory
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub memory: Map<String, JsonValue>,
+}
+
+impl PromptVars {
+    pub fn is_empty(&self) -> bool {
+        self.input.is_empty() && self.memory.is_empty()
+    }
+
+    /// One object of variable roots: `builtins`, shadowed by memory, shadowed
+    /// by the input's variables
+    pub(crate) fn resolve(&self, mut builtins: Map<String, JsonValue>) -> JsonValue {
+        for (key, value) in self.memory.iter().chain(&self.input) {
+            builtins.insert(key.clone(), value.clone());
+        }
+        JsonValue::Object(builtins)
+    }
+}
+
+/// The built-in variables of one execution of `agent_name`
+pub(crate) fn builtins(agent_name: &str, metadata: &AgentInputMetadata) -> Map<String, JsonValue> {
+    let mut vars = Map::new();
+    vars.insert(
+        "today".to_string(),
+        chrono::Utc::now().format("%Y-%m-%d").to_string().into(),
+    );
+    vars.insert("agent_name".to_string(), agent_name.into());
+    vars.insert("step_index".to_string(), metadata.step_index.into());
+    if let Some(workflow_id) = &metadata.workflow_id {
+        vars.insert("workflow_id".to_string(), workflow_id.as_str().into());
+    }
+    vars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_precedence_and_static_prompts() {
+        let vars = PromptVars {
+            input: Map::from_iter([("customer".to_string(), json!("Ada"))]),
+            memory: Map::from_iter([
+                ("customer".to_string(), json!("Grace")),
+                ("plan".to_string(), json!("gold")),
+            ]),
+        };
+        let builtins = Map::from_iter([
+            ("plan".to_string(), json!("free")),
+            ("today".to_string(), json!("2026-10-14")),
+        ]);
+        let template = PromptTemplate::new("{{customer}} / {{plan}} / {{today}}");
+        assert!(template.has_placeholders());
+        assert_eq!(
+            template
+                .render_with(|| vars.resolve(builtins.clone()))
+                .unwrap(),
+            "Ada / gold / 2026-10-14"
+        );
+
+        let plain = PromptTemplate::new(r"Reply with \{{json}}.");
+        assert!(!plain.has_placeholders());
+        let rendered = plain
+            .render_with(|| unreachable!("static prompts need no variables"))
+            .unwrap();
+        assert!(matches!(rendered, Cow::Borrowed("Reply with {{json}}.")));
+
+        let spec = serde_json::to_value(template.on_missing(MissingVariables::Literal)).unwrap();
+        assert_eq!(
+            spec,
+            json!({ "text": "{{customer}} / {{plan}} / {{today}}", "on_missing": "literal" })
+        );
+        let restored: PromptTemplate = serde_json::from_value(spec).unwrap();
+        assert_eq!(
+            restored.render_with(|| json!({})).unwrap(),
+            "{{customer}} / {{plan}} / {{today}}"
+        );
+    }
+}
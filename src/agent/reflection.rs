@@ -0,0 +1,126 @@
+//! Self-reflection: one critique pass over the agent's final answer.
+//!
+//! With [`ReflectionConfig`] enabled, an answer the agent would return is
+//! first sent back to the model with the conversation so far and
+//! `critique_prompt`, asking for a verdict. A verdict listing issues goes to
+//! the agent as a user message and the tool loop resumes for a revised
+//! answer, at most `max_revisions` times. A reply that isn't a verdict
+//! accepts the answer. Critique calls emit `<agent>:reflection` events, count
+//! in `AgentOutputMetadata::usage` and are recorded with role `reflection`.
+
+use crate::llm::types::ChatMessage;
+use serde::{Deserialize, Serialize};
+
+/// Prompt used unless the config sets its own
+pub const DEFAULT_CRITIQUE_PROMPT: &str = "Check your answer above against the user's request and the tool results. List any problems: missing parts of the request, claims the tool results don't support, mistakes.
+
+Reply with only a JSON object: {\"acceptable\": true or false, \"issues\": [\"one problem per entry\"]}";
+
+/// A critique pass run before the agent returns its answer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReflectionConfig {
+    pub enabled: bool,
+
+    /// Sent after the candidate answer; should ask for a JSON verdict of the
+    /// form `{"acceptable": bool, "issues": [...]}`
+    pub critique_prompt: String,
+
+    /// Revisions allowed after rejected answers; `0` only critiques
+    pub max_revisions: u32,
+}
+
+impl Default for ReflectionConfig {
+    /// Enabled, with the default prompt and one revision
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            critique_prompt: DEFAULT_CRITIQUE_PROMPT.to_string(),
+            max_revisions: 1,
+        }
+    }
+}
+
+/// The model's judgement of one answer
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReflectionVerdict {
+    pub acceptable: bool,
+
+    #[serde(default)]
+    pub issues: Vec<String>,
+}
+
+impl ReflectionVerdict {
+    /// Parse a critique reply, tolerating prose or code fences around the
+    /// JSON object. Anything else accepts the answer: a critique that can't
+    /// be read shouldn't hold it back.
+    pub fn parse(reply: &str) -> Self {
+        let accepted = Self {
+            acceptable: true,
+            issues: Vec::new(),
+        };
+        let (Some(start), Some(end)) = (reply.find('{'), reply.rfind('}')) else {
+            return accepted;
+        };
+        reply
+            .get(start..=end)
+            .and_then(|json| serde_json::from_str(json).ok())
+            .unwrap_or(accepted)
+    }
+
+    /// Whether the answer goes back for revision: rejected, with issues to
+    /// fix
+    pub fn rejects(&self) -> bool {
+        !self.acceptable && !self.issues.is_empty()
+    }
+}
+
+/// Every critique of one execution
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReflectionReport {
+    /// Revised answers the agent gave
+    pub revisions: u32,
+
+    /// One per critique, in order
+    pub verdicts: Vec<ReflectionVerdict>,
+}
+
+/// The conversation the critique request sends: everything so far, the
+/// candidate answer, then the critique prompt
+pub(crate) fn critique_messages(
+    conversation: &[ChatMessage],
+    answer: &str,
+    config: &ReflectionConfig,
+) -> Vec<ChatMessage> {
+    let mut messages = conversation.to_vec();
+    messages.push(ChatMessage::assistant(answer));
+    messages.push(ChatMessage::user(&config.critique_prompt));
+    messages
+}
+
+/// The message that sends a rejected answer's issues back to the agent
+pub(crate) fn revision_request(issues: &[String]) -> String {
+    let issues: Vec<String> = issues.iter().map(|issue| format!("- {}", issue)).collect();
+    format!(
+        "Your answer has these problems:\n{}\n\nRevise your answer to fix them.",
+        issues.join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_verdict() {
+        let verdict = ReflectionVerdict::parse(
+            "```json\n{\"acceptable\": false, \"issues\": [\"no source for the price\"]}\n```",
+        );
+        assert!(verdict.rejects());
+        assert_eq!(verdict.issues, vec!["no source for the price"]);
+
+        assert!(!ReflectionVerdict::parse("{\"acceptable\": true}").rejects());
+        assert!(!ReflectionVerdict::parse("{\"acceptable\": false, \"issues\": []}").rejects());
+        assert!(ReflectionVerdict::parse("Looks fine to me.").acceptable);
+        assert!(ReflectionVerdict::parse("{not json}").acceptable);
+    }
+}
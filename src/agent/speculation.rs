@@ -0,0 +1,611 @@
+//! Speculative tool prefetching: starting a likely first tool call while the
+//! first LLM call is still in flight.
+//!
+//! An interactive turn is usually a serial chain: the model asks for
+//! `read_file`, the tool runs, the model continues. When the first call is
+//! predictable from the user's message, a [`SpeculativePrefetcher`] starts it
+//! alongside the first LLM call. If the model then requests exactly that
+//! call (same tool, same arguments), the already running result is served
+//! instead of executing it again; otherwise it is discarded.
+//!
+//! Predictions come from [`PrefetchRule`]s, and optionally from a learned
+//! mode that counts which first call followed which kind of input over the
+//! prefetcher's recent executions (see [`LearnedPrefetch`]).
+//!
+//! Only tools that declare themselves free of side effects
+//! ([`Tool::side_effecting`](crate::tools::Tool::side_effecting) returning
+//! false) are ever speculated. Each execution reports its hits, misses and
+//! wasted executions in `AgentOutputMetadata::speculation` and as
+//! `system:speculation` events.
+
+use crate::event::{ComponentStatus, EventScope, EventStream, EventType};
+use crate::tools::cache::call_key;
+use crate::tools::{ToolRegistry, ToolRunContext};
+use crate::types::{ToolError, ToolExecutionResult, ToolResult};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// How long a speculative result stays servable
+pub const DEFAULT_SPECULATION_TTL: Duration = Duration::from_secs(30);
+
+/// Most calls speculated per execution
+pub const DEFAULT_MAX_SPECULATIONS: usize = 2;
+
+/// A tool call expected to come first
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PredictedCall {
+    pub tool: String,
+
+    /// Arguments as the model would send them; must be an object
+    pub arguments: JsonValue,
+}
+
+impl PredictedCall {
+    pub fn new(tool: impl Into<String>, arguments: JsonValue) -> Self {
+        Self {
+            tool: tool.into(),
+            arguments,
+        }
+    }
+
+    /// Cache key: the tool name and its arguments with object keys sorted
+    fn key(&self) -> String {
+        call_key(&self.tool, &self.arguments)
+    }
+}
+
+type Predictor = Arc<dyn Fn(&str) -> Option<PredictedCall> + Send + Sync>;
+
+/// Predicts a first tool call from the user's message
+#[derive(Clone)]
+pub struct PrefetchRule {
+    name: String,
+    predict: Predictor,
+}
+
+impl PrefetchRule {
+    /// A rule that predicts with `predict`, given the latest user message
+    pub fn new(
+        name: impl Into<String>,
+        predict: impl Fn(&str) -> Option<PredictedCall> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            predict: Arc::new(predict),
+        }
+    }
+
+    /// Call `tool` with `{argument: path}` when the message mentions a file
+    /// path (`src/main.rs`, `./notes.md`)
+    pub fn file_path(tool: impl Into<String>, argument: impl Into<String>) -> Self {
+        let tool = tool.into();
+        let argument = argument.into();
+        Self::new(format!("{} mentioned file", tool), move |input| {
+            let path = find_path(input)?;
+            Some(PredictedCall::new(
+                tool.clone(),
+                JsonValue::Object([(argument.clone(), path.into())].into_iter().collect()),
+            ))
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl std::fmt::Debug for PrefetchRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrefetchRule")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+/// Predict from the agent's own recent executions
+///
+/// Each finished execution records its first tool call, with any argument
+/// equal to the file path in the user's message generalized to "the path
+/// mentioned". A call is predicted once it followed at least `min_samples`
+/// of the last `window` comparable inputs (those that did, or did not,
+/// mention a path) and at least `confidence` of them.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LearnedPrefetch {
+    pub confidence: f64,
+    pub min_samples: usize,
+    pub window: usize,
+}
+
+impl Default for LearnedPrefetch {
+    fn default() -> Self {
+        Self {
+            confidence: 0.8,
+            min_samples: 3,
+            window: 50,
+        }
+    }
+}
+
+/// Speculation counts for one execution, or totals across executions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpeculationStats {
+    /// Speculative executions started
+    pub started: usize,
+
+    /// Speculative results served to a matching call
+    pub hits: usize,
+
+    /// Calls in the model's first tool request that no speculation covered
+    pub misses: usize,
+
+    /// Speculative executions whose result was discarded: never requested,
+    /// expired, or failed
+    pub wasted: usize,
+
+    /// Predictions not run because the tool has side effects or is unknown
+    pub refused: usize,
+}
+
+impl SpeculationStats {
+    fn add(&mut self, other: &SpeculationStats) {
+        self.started += other.started;
+        self.hits += other.hits;
+        self.misses += other.misses;
+        self.wasted += other.wasted;
+        self.refused += other.refused;
+    }
+}
+
+/// A recorded first call, generalized for learning
+#[derive(Debug, Clone, PartialEq)]
+struct Pattern {
+    tool: String,
+    /// Arguments with the path fields nulled
+    arguments: JsonValue,
+    /// Top-level arguments that took the path from the message
+    path_fields: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Observation {
+    had_path: bool,
+    first_call: Option<Pattern>,
+}
+
+/// Opt-in speculative execution of predictable first tool calls
+///
+/// Clones share learned history and totals, so a prefetcher set on an
+/// `AgentConfig` learns across every execution of that agent.
+#[derive(Clone)]
+pub struct SpeculativePrefetcher {
+    rules: Vec<PrefetchRule>,
+    learned: Option<LearnedPrefetch>,
+    ttl: Duration,
+    max_speculations: usize,
+    history: Arc<Mutex<VecDeque<Observation>>>,
+    totals: Arc<Mutex<SpeculationStats>>,
+}
+
+impl Default for SpeculativePrefetcher {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            learned: None,
+            ttl: DEFAULT_SPECULATION_TTL,
+            max_speculations: DEFAULT_MAX_SPECULATIONS,
+            history: Arc::new(Mutex::new(VecDeque::new())),
+            totals: Arc::new(Mutex::new(SpeculationStats::default())),
+        }
+    }
+}
+
+impl SpeculativePrefetcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a rule; rules are consulted in order
+    pub fn rule(mut self, rule: PrefetchRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Also predict from recent executions
+    pub fn learned(mut self, learned: LearnedPrefetch) -> Self {
+        self.learned = Some(learned);
+        self
+    }
+
+    /// Discard speculative results older than `ttl` instead of serving them
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Speculate at most `max` calls per execution
+    pub fn with_max_speculations(mut self, max: usize) -> Self {
+        self.max_speculations = max;
+        self
+    }
+
+    /// Record that `input` led to `first_call` (or to no tool call), e.g.
+    /// to seed the learned mode from stored runs. Executions of an agent
+    /// with this prefetcher are recorded automatically.
+    pub fn observe(&self, input: &str, first_call: Option<(&str, &JsonValue)>) {
+        let Some(learned) = self.learned else {
+            return;
+        };
+        let path = find_path(input);
+        let first_call = first_call.map(|(tool, arguments)| {
+            let mut arguments = arguments.clone();
+            let mut path_fields = Vec::new();
+            if let (Some(path), Some(fields)) = (path, arguments.as_object_mut()) {
+                for (name, value) in fields.iter_mut() {
+                    if value.as_str() == Some(path) {
+                        path_fields.push(name.clone());
+                        *value = JsonValue::Null;
+                    }
+                }
+            }
+            Pattern {
+                tool: tool.to_string(),
+                arguments,
+                path_fields,
+            }
+        });
+
+        let mut history = self.history.lock().unwrap();
+        history.push_back(Observation {
+            had_path: path.is_some(),
+            first_call,
+        });
+        while history.len() > learned.window {
+            history.pop_front();
+        }
+    }
+
+    /// Calls to speculate for `input`, rules first, without duplicates
+    pub fn predict(&self, input: &str) -> Vec<PredictedCall> {
+        let mut predictions: Vec<PredictedCall> = Vec::new();
+        let candidates = self
+            .rules
+            .iter()
+            .filter_map(|rule| (rule.predict)(input))
+            .chain(self.learned_prediction(input));
+        for candidate in candidates {
+            if predictions.len() == self.max_speculations {
+                break;
+            }
+            if !predictions.iter().any(|p| p.key() == candidate.key()) {
+                predictions.push(candidate);
+            }
+        }
+        predictions
+    }
+
+    /// Totals over every execution that used this prefetcher
+    pub fn stats(&self) -> SpeculationStats {
+        *self.totals.lock().unwrap()
+    }
+
+    fn learned_prediction(&self, input: &str) -> Option<PredictedCall> {
+        let learned = self.learned?;
+        let path = find_path(input);
+        let history = self.history.lock().unwrap();
+
+        let comparable: Vec<&Observation> = history
+            .iter()
+            .filter(|o| o.had_path == path.is_some())
+            .collect();
+        let mut counts: Vec<(&Pattern, usize)> = Vec::new();
+        for pattern in comparable.iter().filter_map(|o| o.first_call.as_ref()) {
+            match counts.iter_mut().find(|(p, _)| *p == pattern) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((pattern, 1)),
+            }
+        }
+        let (pattern, count) = counts.into_iter().max_by_key(|(_, count)| *count)?;
+        if count < learned.min_samples
+            || (count as f64) < learned.confidence * comparable.len() as f64
+        {
+            return None;
+        }
+
+        let mut arguments = pattern.arguments.clone();
+        if let (Some(path), Some(fields)) = (path, arguments.as_object_mut()) {
+            for name in &pattern.path_fields {
+                fields.insert(name.clone(), path.into());
+            }
+        }
+        Some(PredictedCall::new(pattern.tool.clone(), arguments))
+    }
+}
+
+impl std::fmt::Debug for SpeculativePrefetcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpeculativePrefetcher")
+            .field("rules", &self.rules)
+            .field("learned", &self.learned)
+            .field("ttl", &self.ttl)
+            .field("max_speculations", &self.max_speculations)
+            .finish()
+    }
+}
+
+/// A speculative execution in flight or finished
+struct Pending {
+    tool: String,
+    started: Instant,
+    cancel: CancellationToken,
+    handle: JoinHandle<ToolExecutionResult>,
+}
+
+/// Speculative executions for one agent execution
+///
+/// Only the model's first tool request is matched; once it has been
+/// handled, whatever is left is discarded. Dropping this aborts anything
+/// still running.
+pub(crate) struct Speculation {
+    prefetcher: SpeculativePrefetcher,
+    pending: HashMap<String, Pending>,
+    stats: SpeculationStats,
+    settled: bool,
+    agent: String,
+    workflow_id: String,
+}
+
+impl Speculation {
+    /// Start the predicted calls for `input` that are safe to run early
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn start(
+        prefetcher: &SpeculativePrefetcher,
+        input: &str,
+        registry: &Arc<ToolRegistry>,
+        ctx: &ToolRunContext,
+        default_timeout: Option<Duration>,
+        agent: &str,
+        workflow_id: &str,
+        event_stream: Option<&EventStream>,
+    ) -> Self {
+        let mut speculation = Self {
+            prefetcher: prefetcher.clone(),
+            pending: HashMap::new(),
+            stats: SpeculationStats::default(),
+            settled: false,
+            agent: agent.to_string(),
+            workflow_id: workflow_id.to_string(),
+        };
+
+        for call in prefetcher.predict(input) {
+            let tool = registry.get(&call.tool);
+            let refusal = match (&tool, call.arguments.as_object()) {
+                (None, _) => Some("unknown tool"),
+                (Some(tool), _) if tool.side_effecting() => Some("tool has side effects"),
+                (_, None) => Some("arguments are not an object"),
+                _ => None,
+            };
+            if let Some(reason) = refusal {
+                speculation.stats.refused += 1;
+                speculation.emit(
+                    event_stream,
+                    format!("Not speculating '{}': {}", call.tool, reason),
+                    serde_json::json!({ "outcome": "refused", "tool": call.tool, "reason": reason }),
+                );
+                continue;
+            }
+            let timeout = tool.and_then(|t| t.timeout()).or(default_timeout);
+            let params: HashMap<String, JsonValue> = call
+                .arguments
+                .as_object()
+                .map(|args| args.clone().into_iter().collect())
+                .unwrap_or_default();
+
+            let cancel = ctx.cancellation.child_token();
+            let call_ctx = ToolRunContext {
+                tool_call_id: None,
+                cancellation: cancel.clone(),
+                ..ctx.clone()
+            };
+            let registry = registry.clone();
+            let name = call.tool.clone();
+            let handle = tokio::spawn(async move {
+                let run = registry.call_tool_with_context(&name, params, &call_ctx);
+                match timeout {
+                    Some(limit) => tokio::time::timeout(limit, run)
+                        .await
+                        .unwrap_or(Err(ToolError::TimedOut(limit.as_millis() as u64))),
+                    None => run.await,
+                }
+            });
+
+            speculation.stats.started += 1;
+            speculation.emit(
+                event_stream,
+                format!("Speculating '{}'", call.tool),
+                serde_json::json!({ "outcome": "started", "call": call }),
+            );
+            speculation.pending.insert(
+                call.key(),
+                Pending {
+                    tool: call.tool,
+                    started: Instant::now(),
+                    cancel,
+                    handle,
+                },
+            );
+        }
+        speculation
+    }
+
+    /// Whether nothing was started or refused, so there's nothing to report
+    pub(crate) fn is_idle(&self) -> bool {
+        self.stats == SpeculationStats::default()
+    }
+
+    /// The speculative result for a call the model made, if one matches
+    pub(crate) async fn take(
+        &mut self,
+        tool: &str,
+        params: &HashMap<String, JsonValue>,
+        event_stream: Option<&EventStream>,
+    ) -> Option<ToolResult> {
+        if self.settled {
+            return None;
+        }
+        let arguments = JsonValue::Object(params.clone().into_iter().collect());
+        let Some(pending) = self.pending.remove(&call_key(tool, &arguments)) else {
+            self.stats.misses += 1;
+            return None;
+        };
+        if pending.started.elapsed() > self.prefetcher.ttl {
+            self.discard(pending, "expired", event_stream);
+            return None;
+        }
+
+        let started = pending.started;
+        match pending.handle.await {
+            Ok(Ok(result)) => {
+                self.stats.hits += 1;
+                self.emit(
+                    event_stream,
+                    format!("Speculation hit for '{}'", tool),
+                    serde_json::json!({
+                        "outcome": "hit",
+                        "tool": tool,
+                        "age_ms": started.elapsed().as_millis() as u64,
+                    }),
+                );
+                Some(result)
+            }
+            _ => {
+                self.stats.wasted += 1;
+                self.emit(
+                    event_stream,
+                    format!("Speculative '{}' failed; running it again", tool),
+                    serde_json::json!({ "outcome": "wasted", "tool": tool, "reason": "failed" }),
+                );
+                None
+            }
+        }
+    }
+
+    /// The first tool request has been handled; discard the rest
+    pub(crate) fn settle(&mut self, event_stream: Option<&EventStream>) {
+        if self.settled {
+            return;
+        }
+        self.settled = true;
+        for (_, pending) in std::mem::take(&mut self.pending) {
+            self.discard(pending, "not requested", event_stream);
+        }
+    }
+
+    /// Settle, add this execution to the prefetcher's totals and report it
+    pub(crate) fn finish(mut self, event_stream: Option<&EventStream>) -> SpeculationStats {
+        self.settle(event_stream);
+        self.prefetcher.totals.lock().unwrap().add(&self.stats);
+        let stats = self.stats;
+        self.emit(
+            event_stream,
+            format!(
+                "Speculation: {} hits, {} misses, {} wasted",
+                stats.hits, stats.misses, stats.wasted
+            ),
+            serde_json::json!({ "outcome": "summary", "stats": stats }),
+        );
+        stats
+    }
+
+    fn discard(&mut self, pending: Pending, reason: &str, event_stream: Option<&EventStream>) {
+        pending.cancel.cancel();
+        pending.handle.abort();
+        self.stats.wasted += 1;
+        self.emit(
+            event_stream,
+            format!("Discarded speculative '{}': {}", pending.tool, reason),
+            serde_json::json!({ "outcome": "wasted", "tool": pending.tool, "reason": reason }),
+        );
+    }
+
+    fn emit(&self, event_stream: Option<&EventStream>, message: String, mut data: JsonValue) {
+        if let Some(stream) = event_stream {
+            data["agent"] = self.agent.clone().into();
+            stream.append(
+                EventScope::System,
+                EventType::Progress,
+                "system:speculation".to_string(),
+                ComponentStatus::Running,
+                self.workflow_id.clone(),
+                Some(message),
+                data,
+            );
+        }
+    }
+}
+
+impl Drop for Speculation {
+    fn drop(&mut self) {
+        for pending in self.pending.values() {
+            pending.cancel.cancel();
+            pending.handle.abort();
+        }
+    }
+}
+
+/// The first thing in `input` that looks like a file path
+fn find_path(input: &str) -> Option<&str> {
+    static PATH: OnceLock<Regex> = OnceLock::new();
+    PATH.get_or_init(|| {
+        Regex::new(r"(?:\.{1,2}/|/)?(?:[\w.-]+/)*[\w-]+\.[A-Za-z][A-Za-z0-9]{0,7}\b")
+            .expect("valid path pattern")
+    })
+    .find(input)
+    .map(|m| m.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_call_key_ignores_argument_order() {
+        let a = serde_json::json!({ "path": "a.md", "opts": { "x": 1, "y": [1, 2] } });
+        let b = serde_json::json!({ "opts": { "y": [1, 2], "x": 1 }, "path": "a.md" });
+        assert_eq!(call_key("read_file", &a), call_key("read_file", &b));
+        assert_ne!(call_key("read_file", &a), call_key("stat", &a));
+    }
+
+    #[test]
+    fn test_learned_mode_generalizes_paths() {
+        let prefetcher = SpeculativePrefetcher::new().learned(LearnedPrefetch::default());
+        for path in ["src/lib.rs", "README.md", "docs/guide.md"] {
+            let args = serde_json::json!({ "path": path, "lines": 200 });
+            prefetcher.observe(
+                &format!("What does {} do?", path),
+                Some(("read_file", &args)),
+            );
+        }
+        // Inputs without a path are a separate population
+        prefetcher.observe("Hello there", None);
+
+        assert_eq!(
+            prefetcher.predict("Summarize src/agent/mod.rs please"),
+            vec![PredictedCall::new(
+                "read_file",
+                serde_json::json!({ "path": "src/agent/mod.rs", "lines": 200 })
+            )]
+        );
+        assert!(prefetcher.predict("How are you?").is_empty());
+
+        // A different first call drops confidence below the threshold
+        prefetcher.observe("Open notes.txt", Some(("stat", &serde_json::json!({}))));
+        assert!(prefetcher.predict("Read main.rs").is_empty());
+    }
+}
@@ -0,0 +1,250 @@
+//! Client-side stop conditions on a streamed response.
+//!
+//! Providers that honor `stop` end the response at a stop sequence
+//! themselves; this catches the ones that don't, and enforces
+//! [`SamplingParams::max_response_chars`](crate::llm::SamplingParams::max_response_chars),
+//! which no provider knows about. Chunks are held back while they could be
+//! the start of a stop sequence, so one split across chunks is never
+//! streamed. Once a condition is met the response is cut there and the
+//! request dropped, closing the provider's stream.
+
+use crate::llm::types::{ChatRequest, ChatResponse};
+
+/// `finish_reason` of a response cut at a stop sequence
+pub(crate) const STOP_SEQUENCE: &str = "stop_sequence";
+/// `finish_reason` of a response cut at `max_response_chars`
+pub(crate) const LENGTH: &str = "length";
+
+const FENCE: &str = "```";
+
+/// What ends a streamed response early
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StopConditions {
+    sequences: Vec<String>,
+    max_chars: Option<usize>,
+
+    /// Let the stream finish after a match, so native tool calls that
+    /// follow the text aren't lost
+    keep_streaming: bool,
+
+    /// Ignore matches inside an open ``` block, where prompted tool calls
+    /// are written
+    skip_fenced: bool,
+}
+
+impl StopConditions {
+    /// The stop sequences of `request` and `max_chars`
+    pub(crate) fn new(request: &ChatRequest, max_chars: Option<usize>, prompted: bool) -> Self {
+        let sequences = request
+            .stop
+            .iter()
+            .flatten()
+            .filter(|s| !s.is_empty())
+            .cloned()
+            .collect();
+        Self {
+            sequences,
+            max_chars,
+            keep_streaming: request.offers_tools(),
+            skip_fenced: prompted || request.offers_tools(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.sequences.is_empty() && self.max_chars.is_none()
+    }
+
+    pub(crate) fn keep_streaming(&self) -> bool {
+        self.keep_streaming
+    }
+
+    /// Where `text` should end, and the `finish_reason` for it
+    fn cut(&self, text: &str, from: usize) -> Option<(usize, &'static str)> {
+        let matched = self
+            .sequences
+            .iter()
+            .filter_map(|sequence| {
+                text[from..]
+                    .match_indices(sequence.as_str())
+                    .map(|(at, _)| from + at)
+                    .find(|&at| !(self.skip_fenced && in_fence(&text[..at])))
+            })
+            .min()
+            .map(|at| (at, STOP_SEQUENCE));
+        let capped = self
+            .max_chars
+            .and_then(|max| text.char_indices().nth(max))
+            .map(|(at, _)| (at, LENGTH));
+        match (matched, capped) {
+            (Some(m), Some(c)) => Some(if c.0 < m.0 { c } else { m }),
+            (m, c) => m.or(c),
+        }
+    }
+
+    /// `response` cut where a condition is met
+    ///
+    /// Tool calls are kept, along with the provider's `finish_reason` for
+    /// them.
+    pub(crate) fn apply(&self, mut response: ChatResponse) -> ChatResponse {
+        if let Some((at, reason)) = self.cut(&response.content, 0) {
+            response.content.truncate(at);
+            if response.tool_calls.is_none() {
+                response.finish_reason = Some(reason.to_string());
+            }
+        }
+        response
+    }
+}
+
+/// Streamed text checked against [`StopConditions`]
+pub(crate) struct StopScanner<'a> {
+    conditions: &'a StopConditions,
+    text: String,
+    /// How much of `text` has been passed on
+    forwarded: usize,
+    stopped: Option<&'static str>,
+}
+
+impl<'a> StopScanner<'a> {
+    pub(crate) fn new(conditions: &'a StopConditions) -> Self {
+        Self {
+            conditions,
+            text: String::new(),
+            forwarded: 0,
+            stopped: None,
+        }
+    }
+
+    /// Add a chunk, returning the text that's safe to pass on
+    pub(crate) fn push(&mut self, chunk: &str) -> Option<String> {
+        if self.stopped.is_some() {
+            return None;
+        }
+        self.text.push_str(chunk);
+        let end = match self.conditions.cut(&self.text, self.forwarded) {
+            Some((at, reason)) => {
+                self.stopped = Some(reason);
+                self.text.truncate(at);
+                at
+            }
+            None => self.text.len() - self.partial_match(),
+        };
+        self.forward(end)
+    }
+
+    /// The text held back when the stream ends without a match
+    pub(crate) fn finish(&mut self) -> Option<String> {
+        self.forward(self.text.len())
+    }
+
+    /// Whether a condition was met
+    pub(crate) fn stopped(&self) -> bool {
+        self.stopped.is_some()
+    }
+
+    /// The response so far, for a stream that was dropped at a condition
+    pub(crate) fn response(self) -> ChatResponse {
+        ChatResponse {
+            content: self.text,
+            model: String::new(),
+            usage: None,
+            finish_reason: self.stopped.map(str::to_string),
+            tool_calls: None,
+            warnings: Vec::new(),
+            provider: None,
+            failovers: Vec::new(),
+        }
+    }
+
+    fn forward(&mut self, end: usize) -> Option<String> {
+        let end = end.max(self.forwarded);
+        let text = self.text[self.forwarded..end].to_string();
+        self.forwarded = end;
+        Some(text).filter(|t| !t.is_empty())
+    }
+
+    /// Length of the longest tail of the text that starts a stop sequence
+    fn partial_match(&self) -> usize {
+        self.conditions
+            .sequences
+            .iter()
+            .flat_map(|sequence| {
+                sequence
+                    .char_indices()
+                    .skip(1)
+                    .map(|(at, _)| &sequence[..at])
+                    .filter(|prefix| self.text.ends_with(prefix))
+                    .map(str::len)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Whether `text` ends inside an unclosed ``` block
+fn in_fence(text: &str) -> bool {
+    text.matches(FENCE).count() % 2 == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::types::ChatMessage;
+
+    fn conditions(stop: &[&str], max_chars: Option<usize>) -> StopConditions {
+        let request = ChatRequest::new(vec![ChatMessage::user("Hi")])
+            .with_stop(stop.iter().map(|s| s.to_string()).collect());
+        StopConditions::new(&request, max_chars, false)
+    }
+
+    #[test]
+    fn test_partial_match_is_held_back() {
+        let conditions = conditions(&["\nObservation:"], None);
+        let mut scanner = StopScanner::new(&conditions);
+        assert_eq!(
+            scanner.push("Thought: look it up\nObs").as_deref(),
+            Some("Thought: look it up")
+        );
+        assert_eq!(scanner.push("ervation: 42"), None);
+        assert!(scanner.stopped());
+        assert_eq!(scanner.push("more"), None);
+        assert_eq!(scanner.response().content, "Thought: look it up");
+    }
+
+    #[test]
+    fn test_held_text_is_released_without_a_match() {
+        let conditions = conditions(&["END"], None);
+        let mut scanner = StopScanner::new(&conditions);
+        assert_eq!(scanner.push("THE EN").as_deref(), Some("THE "));
+        assert_eq!(scanner.push("D").as_deref(), None);
+        assert!(scanner.stopped());
+
+        let mut scanner = StopScanner::new(&conditions);
+        assert_eq!(scanner.push("THE EN").as_deref(), Some("THE "));
+        assert_eq!(scanner.push("TRY").as_deref(), Some("ENTRY"));
+        assert_eq!(scanner.finish(), None);
+        assert!(!scanner.stopped());
+    }
+
+    #[test]
+    fn test_char_cap_counts_characters() {
+        let conditions = conditions(&[], Some(4));
+        let mut scanner = StopScanner::new(&conditions);
+        assert_eq!(scanner.push("né").as_deref(), Some("né"));
+        assert_eq!(scanner.push("ée!!").as_deref(), Some("ée"));
+        let response = scanner.response();
+        assert_eq!(response.content, "néée");
+        assert_eq!(response.finish_reason.as_deref(), Some(LENGTH));
+    }
+
+    #[test]
+    fn test_fenced_matches_are_skipped_for_tool_calls() {
+        let request = ChatRequest::new(vec![ChatMessage::user("Hi")]).with_stop(vec!["##".into()]);
+        let conditions = StopConditions::new(&request, None, true);
+        let text =
+            "```json\n{\"tool\": \"grep\", \"arguments\": {\"pattern\": \"##\"}}\n```\n## Done";
+        let (at, reason) = conditions.cut(text, 0).unwrap();
+        assert_eq!(&text[at..], "## Done");
+        assert_eq!(reason, STOP_SEQUENCE);
+    }
+}
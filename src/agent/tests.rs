@@ -38,8 +38,11 @@ async fn test_agent_execute_without_llm() {
         metadata: AgentInputMetadata {
             step_index: 0,
             previous_agent: None,
+            workflow_id: None,
         },
         chat_history: None,
+        limits: None,
+        vars: Default::default(),
     };
 
     let result = agent.execute(&input).await;
@@ -64,3 +67,1000 @@ fn test_agent_config_debug() {
     assert!(debug_str.contains("debug_agent"));
     assert!(debug_str.contains("None"));
 }
+
+#[tokio::test]
+async fn test_agent_effort_falls_back_to_scratchpad_prompt() {
+    use crate::llm::effort::SCRATCHPAD_INSTRUCTION;
+    use crate::llm::{Effort, EffortMapping, MockLlmClient};
+    use std::sync::Arc;
+
+    let client = Arc::new(MockLlmClient::with_responses_vec(vec!["done"]));
+    let config = AgentConfig::builder("effort_agent")
+        .system_prompt("Be precise")
+        .effort(Effort::High)
+        .build();
+    let agent = Agent::new(config).with_client(client.clone());
+
+    let input = AgentInput::from_value(json!("question"));
+    let output = agent.execute(&input).await.unwrap();
+
+    let request = client.last_call().unwrap();
+    assert!(request.messages[0]
+        .content
+        .text()
+        .contains(SCRATCHPAD_INSTRUCTION));
+    assert_eq!(request.max_tokens, Some(16384));
+    assert!(request.reasoning_effort.is_none());
+
+    let applied = output.metadata.effort.unwrap();
+    assert_eq!(applied.effort, Effort::High);
+    assert!(matches!(
+        applied.mapping,
+        EffortMapping::PromptFallback {
+            scratchpad: true,
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn test_agent_emits_response_validation_warnings() {
+    use crate::event::EventStream;
+    use crate::llm::{MockLlmClient, MockResponse};
+    use std::sync::Arc;
+
+    let client = Arc::new(MockLlmClient::from_mock_responses(vec![
+        MockResponse::text("done")
+            .with_warnings(vec!["tool call 0 has an empty function name; dropped"]),
+    ]));
+    let agent = Agent::new(
+        AgentConfig::builder("validated")
+            .system_prompt("You help")
+            .build(),
+    )
+    .with_client(client);
+    let stream = EventStream::new();
+
+    agent
+        .execute_with_events(AgentInput::from_value(json!("hi")), Some(&stream))
+        .await
+        .unwrap();
+
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let events = stream.all();
+    let event = events
+        .iter()
+        .find(|e| e.component_id == "system:response_validation")
+        .unwrap();
+    assert_eq!(event.data["agent"], "validated");
+    assert_eq!(event.data["warnings"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_abandoned_tool_call_cancels_its_token() {
+    use crate::llm::MockLlmClient;
+    use crate::tools::{CancellationToken, NativeTool, ToolRegistry};
+    use crate::types::ToolResult;
+    use std::sync::{Arc, Mutex};
+
+    let seen: Arc<Mutex<Option<CancellationToken>>> = Arc::default();
+    let mut registry = ToolRegistry::new();
+    let slot = seen.clone();
+    registry.register(NativeTool::cancellable(
+        "wait",
+        "Ignores cancellation",
+        json!({"type": "object"}),
+        move |_params, token| {
+            *slot.lock().unwrap() = Some(token);
+            async move {
+                tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+                Ok(ToolResult::success(json!({}), 0.0))
+            }
+        },
+    ));
+    let agent = Agent::new(
+        AgentConfig::builder("impatient")
+            .tools(Arc::new(registry))
+            .build(),
+    )
+    .with_client(Arc::new(
+        MockLlmClient::new().with_tool_call("wait", json!({})),
+    ));
+
+    let run_token = CancellationToken::new();
+    let outcome = tokio::time::timeout(
+        std::time::Duration::from_millis(50),
+        agent.execute_with_cancellation(
+            AgentInput::from_value(json!("go")),
+            None,
+            None,
+            run_token.clone(),
+        ),
+    )
+    .await;
+
+    assert!(outcome.is_err());
+    assert!(seen.lock().unwrap().as_ref().unwrap().is_cancelled());
+    // Only the abandoned call is canceled, not the whole run
+    assert!(!run_token.is_cancelled());
+}
+
+/// Tool that fails with `error` for the first `failures` calls, then
+/// succeeds; returns the registry and the call counter
+fn flaky_tool(
+    failures: usize,
+    error: crate::types::ToolError,
+) -> (
+    crate::tools::ToolRegistry,
+    std::sync::Arc<std::sync::atomic::AtomicUsize>,
+) {
+    use crate::tools::{NativeTool, ToolRegistry};
+    use crate::types::ToolResult;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let mut registry = ToolRegistry::new();
+    registry.register(NativeTool::new(
+        "lookup",
+        "Flaky lookup",
+        json!({"type": "object"}),
+        move |_params| {
+            let call = counter.fetch_add(1, Ordering::SeqCst);
+            let error = error.clone();
+            async move {
+                if call < failures {
+                    Err(error)
+                } else {
+                    Ok(ToolResult::success(json!({"answer": 42}), 0.0))
+                }
+            }
+        },
+    ));
+    (registry, calls)
+}
+
+fn quick_retry(max_attempts: u32, initial_ms: u64) -> crate::RetryPolicy {
+    crate::RetryPolicy {
+        max_attempts,
+        initial_delay: std::time::Duration::from_millis(initial_ms),
+        max_delay: std::time::Duration::from_secs(1),
+        backoff_multiplier: 2.0,
+        jitter_factor: 0.0,
+        max_total_duration: None,
+    }
+}
+
+#[tokio::test]
+async fn test_transient_tool_failures_are_retried_transparently() {
+    use crate::event::{EventScope, EventStream, EventType};
+    use crate::llm::MockLlmClient;
+    use crate::types::ToolError;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    let (registry, calls) = flaky_tool(2, ToolError::transient("connection reset"));
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("lookup", json!({}))
+            .with_response("The answer is 42"),
+    );
+    let agent = Agent::new(
+        AgentConfig::builder("patient")
+            .tools(Arc::new(registry))
+            .tool_retry(quick_retry(3, 5))
+            .build(),
+    )
+    .with_client(client.clone());
+
+    let stream = EventStream::new();
+    let output = agent
+        .execute_with_events(AgentInput::from_value(json!("go")), Some(&stream))
+        .await
+        .unwrap();
+    assert_eq!(output.data["response"], "The answer is 42");
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    // The LLM only ever sees the successful result
+    let tool_message = client.get_calls()[1]
+        .messages
+        .last()
+        .unwrap()
+        .content
+        .clone();
+    assert_eq!(tool_message, r#"{"answer":42}"#);
+
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let tool_events: Vec<_> = stream
+        .all()
+        .into_iter()
+        .filter(|e| e.scope == EventScope::Tool)
+        .collect();
+    let retries: Vec<_> = tool_events
+        .iter()
+        .filter(|e| e.event_type == EventType::Progress)
+        .map(|e| e.data["attempt"].as_u64().unwrap())
+        .collect();
+    assert_eq!(retries, vec![2, 3]);
+    let completed = tool_events
+        .iter()
+        .find(|e| e.event_type == EventType::Completed)
+        .unwrap();
+    assert_eq!(completed.data["attempts"], 3);
+}
+
+#[tokio::test]
+async fn test_permanent_tool_failure_is_not_retried() {
+    use crate::event::{EventScope, EventStream, EventType};
+    use crate::llm::MockLlmClient;
+    use crate::types::ToolError;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    let (registry, calls) = flaky_tool(1, ToolError::ExecutionFailed("404 not found".into()));
+    let agent = Agent::new(
+        AgentConfig::builder("patient")
+            .tools(Arc::new(registry))
+            .tool_retry(quick_retry(3, 5))
+            .build(),
+    )
+    .with_client(Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("lookup", json!({}))
+            .with_response("Not found"),
+    ));
+
+    let stream = EventStream::new();
+    agent
+        .execute_with_events(AgentInput::from_value(json!("go")), Some(&stream))
+        .await
+        .unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let failed = stream
+        .all()
+        .into_iter()
+        .find(|e| e.scope == EventScope::Tool && e.event_type == EventType::Failed)
+        .unwrap();
+    assert_eq!(failed.data["attempts"], 1);
+    assert_eq!(failed.data["retryable"], false);
+}
+
+#[tokio::test]
+async fn test_deadline_truncates_retry_backoff() {
+    use crate::llm::MockLlmClient;
+    use crate::types::ToolError;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    // Always failing; backoff 100ms, 200ms, 400ms, ... but only 250ms to spend
+    let (registry, calls) = flaky_tool(usize::MAX, ToolError::transient("503"));
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("lookup", json!({}))
+            .with_response("Gave up"),
+    );
+    let agent = Agent::new(
+        AgentConfig::builder("hurried")
+            .tools(Arc::new(registry))
+            .tool_retry(quick_retry(5, 100))
+            .deadline(Duration::from_millis(250))
+            .build(),
+    )
+    .with_client(client.clone());
+
+    let started = std::time::Instant::now();
+    agent
+        .execute(&AgentInput::from_value(json!("go")))
+        .await
+        .unwrap();
+    assert!(started.elapsed() < Duration::from_millis(250));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    let tool_message = client.get_calls()[1]
+        .messages
+        .last()
+        .unwrap()
+        .content
+        .to_string();
+    assert!(tool_message.contains("Transient failure: 503 (after 2 attempts)"));
+}
+
+#[tokio::test]
+async fn test_transient_llm_failure_is_retried() {
+    use crate::event::{EventScope, EventStream, EventType};
+    use crate::llm::MockLlmClient;
+    use std::sync::Arc;
+
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_response("Recovered")
+            .error_on_call(0),
+    );
+    let agent = Agent::new(
+        AgentConfig::builder("resilient")
+            .retry_policy(quick_retry(2, 5))
+            .build(),
+    )
+    .with_client(client.clone());
+
+    let stream = EventStream::new();
+    let output = agent
+        .execute_with_events(AgentInput::from_value(json!("go")), Some(&stream))
+        .await
+        .unwrap();
+    assert_eq!(output.data["response"], "Recovered");
+    assert_eq!(client.call_count(), 2);
+
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let llm_events: Vec<_> = stream
+        .all()
+        .into_iter()
+        .filter(|e| e.scope == EventScope::LlmRequest && e.event_type != EventType::Progress)
+        .collect();
+    let types: Vec<_> = llm_events.iter().map(|e| e.event_type.clone()).collect();
+    assert_eq!(
+        types,
+        vec![EventType::Started, EventType::Failed, EventType::Completed]
+    );
+    assert_eq!(llm_events[1].data["attempt"], 1);
+    assert_eq!(llm_events[1].data["will_retry"], true);
+}
+
+#[tokio::test]
+async fn test_llm_retries_exhaust() {
+    use crate::llm::MockLlmClient;
+    use crate::types::AgentError;
+    use std::sync::Arc;
+
+    let client = Arc::new(MockLlmClient::new().error_on_calls(0..3));
+    let agent = Agent::new(
+        AgentConfig::builder("unlucky")
+            .retry_policy(quick_retry(2, 5))
+            .build(),
+    )
+    .with_client(client.clone());
+
+    let error = agent
+        .execute(&AgentInput::from_value(json!("go")))
+        .await
+        .unwrap_err();
+    assert_eq!(client.call_count(), 3);
+    assert!(matches!(
+        &error,
+        AgentError::ExecutionError(m)
+            if m == "LLM call failed after 3 attempts: Network error: Mock network error"
+    ));
+
+    // Without a policy the first failure is final
+    let client = Arc::new(MockLlmClient::new().error_on_call(0));
+    let agent = Agent::new(AgentConfig::builder("fragile").build()).with_client(client.clone());
+    assert!(agent
+        .execute(&AgentInput::from_value(json!("go")))
+        .await
+        .is_err());
+    assert_eq!(client.call_count(), 1);
+}
+
+/// Answers from `inner`, but the first `stalls` streamed requests send
+/// nothing for 200ms first
+struct StallingClient {
+    inner: crate::llm::MockLlmClient,
+    stalls: usize,
+    calls: std::sync::atomic::AtomicUsize,
+}
+
+impl StallingClient {
+    fn new(stalls: usize, inner: crate::llm::MockLlmClient) -> Self {
+        Self {
+            inner,
+            stalls,
+            calls: std::sync::atomic::AtomicUsize::new(0),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl crate::llm::GenericChatClient for StallingClient {
+    async fn chat(
+        &self,
+        request: crate::llm::ChatRequest,
+    ) -> crate::llm::LlmResult<crate::llm::ChatResponse> {
+        self.inner.chat(request).await
+    }
+
+    async fn chat_stream(
+        &self,
+        request: crate::llm::ChatRequest,
+        tx: tokio::sync::mpsc::Sender<String>,
+    ) -> crate::llm::LlmResult<crate::llm::ChatResponse> {
+        let call = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        if call < self.stalls {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        }
+        self.inner.chat_stream(request, tx).await
+    }
+}
+
+fn llm_failures(stream: &crate::event::EventStream) -> Vec<crate::event::Event> {
+    stream
+        .all()
+        .into_iter()
+        .filter(|e| {
+            e.scope == crate::event::EventScope::LlmRequest
+                && e.event_type == crate::event::EventType::Failed
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_first_response_timeout_fails_the_request() {
+    use crate::event::EventStream;
+    use crate::llm::MockLlmClient;
+    use crate::types::AgentError;
+    use crate::TimeoutConfig;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let client = Arc::new(StallingClient::new(
+        1,
+        MockLlmClient::new().with_response("Too late"),
+    ));
+    let agent = Agent::new(
+        AgentConfig::builder("impatient")
+            .timeouts(TimeoutConfig {
+                total: None,
+                first_response: Some(Duration::from_millis(50)),
+            })
+            .build(),
+    )
+    .with_client(client);
+
+    let stream = EventStream::new();
+    let started = std::time::Instant::now();
+    let error = agent
+        .execute_with_events(AgentInput::from_value(json!("go")), Some(&stream))
+        .await
+        .unwrap_err();
+    assert!(started.elapsed() < Duration::from_millis(200));
+    assert!(matches!(
+        &error,
+        AgentError::ExecutionError(m)
+            if m == "LLM call failed: Network error: no response within 50ms"
+    ));
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let failures = llm_failures(&stream);
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].data["timeout_kind"], "first_response");
+}
+
+#[tokio::test]
+async fn test_first_response_timeout_is_retried() {
+    use crate::event::EventStream;
+    use crate::llm::MockLlmClient;
+    use crate::TimeoutConfig;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let client = Arc::new(StallingClient::new(
+        1,
+        MockLlmClient::new().with_response("Recovered"),
+    ));
+    let agent = Agent::new(
+        AgentConfig::builder("impatient")
+            .timeouts(TimeoutConfig {
+                total: None,
+                first_response: Some(Duration::from_millis(50)),
+            })
+            .retry_policy(quick_retry(2, 5))
+            .build(),
+    )
+    .with_client(client.clone());
+
+    let stream = EventStream::new();
+    let output = agent
+        .execute_with_events(AgentInput::from_value(json!("go")), Some(&stream))
+        .await
+        .unwrap();
+    assert_eq!(output.data["response"], "Recovered");
+    assert_eq!(client.calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let failures = llm_failures(&stream);
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].data["timeout_kind"], "first_response");
+    assert_eq!(failures[0].data["will_retry"], true);
+}
+
+#[tokio::test]
+async fn test_total_timeout_bounds_the_whole_request() {
+    use crate::event::EventStream;
+    use crate::llm::MockLlmClient;
+    use crate::TimeoutConfig;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let client = Arc::new(StallingClient::new(
+        1,
+        MockLlmClient::new().with_response("Too late"),
+    ));
+    let agent = Agent::new(
+        AgentConfig::builder("impatient")
+            .timeouts(TimeoutConfig::custom(Duration::from_millis(80), None))
+            .build(),
+    )
+    .with_client(client);
+
+    let stream = EventStream::new();
+    let error = agent
+        .execute_with_events(AgentInput::from_value(json!("go")), Some(&stream))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("not finished within 80ms"));
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let failures = llm_failures(&stream);
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].data["timeout_kind"], "total");
+}
+
+fn sleepy_tool(name: &str, sleep_ms: u64) -> crate::NativeTool {
+    crate::NativeTool::new(
+        name,
+        "Takes its time",
+        json!({ "type": "object", "properties": {} }),
+        move |_params| async move {
+            tokio::time::sleep(std::time::Duration::from_millis(sleep_ms)).await;
+            Ok(crate::types::ToolResult::success(json!("finally"), 0.0))
+        },
+    )
+}
+
+#[tokio::test]
+async fn test_tool_timeout_is_reported_and_the_loop_continues() {
+    use crate::event::{EventScope, EventStream, EventType};
+    use crate::llm::MockLlmClient;
+    use crate::tools::ToolRegistry;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let mut registry = ToolRegistry::new();
+    registry.register(sleepy_tool("slow", 500).with_timeout(Duration::from_millis(50)));
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("slow", json!({}))
+            .with_response("Moved on without it"),
+    );
+    let agent = Agent::new(
+        AgentConfig::builder("impatient")
+            .tools(Arc::new(registry))
+            .build(),
+    )
+    .with_client(client.clone());
+
+    let stream = EventStream::new();
+    let started = std::time::Instant::now();
+    let output = agent
+        .execute_with_events(AgentInput::from_value(json!("go")), Some(&stream))
+        .await
+        .unwrap();
+    assert!(started.elapsed() < Duration::from_millis(400));
+    assert_eq!(output.data["response"], "Moved on without it");
+
+    let tool_message = client.get_calls()[1]
+        .messages
+        .last()
+        .unwrap()
+        .content
+        .clone();
+    assert_eq!(
+        tool_message,
+        "Error: Tool execution failed: timed out after 50ms"
+    );
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let failed = stream
+        .all()
+        .into_iter()
+        .find(|e| e.scope == EventScope::Tool && e.event_type == EventType::Failed)
+        .unwrap();
+    assert!(failed.message.unwrap().contains("timed out after 50ms"));
+    assert_eq!(failed.data["attempts"], 1);
+}
+
+#[tokio::test]
+async fn test_default_tool_timeout_applies_to_tools_without_one() {
+    use crate::llm::MockLlmClient;
+    use crate::tools::ToolRegistry;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let mut registry = ToolRegistry::new();
+    registry.register(sleepy_tool("slow", 500));
+    registry.register(sleepy_tool("patient", 20).with_timeout(Duration::from_millis(200)));
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("slow", json!({}))
+            .with_tool_call("patient", json!({}))
+            .with_response("Done"),
+    );
+    let agent = Agent::new(
+        AgentConfig::builder("bounded")
+            .tools(Arc::new(registry))
+            .default_tool_timeout(Duration::from_millis(10))
+            .build(),
+    )
+    .with_client(client.clone());
+
+    agent
+        .execute(&AgentInput::from_value(json!("go")))
+        .await
+        .unwrap();
+    let calls = client.get_calls();
+    let result = |call: usize| calls[call].messages.last().unwrap().content.clone();
+    assert_eq!(
+        result(1),
+        "Error: Tool execution failed: timed out after 10ms"
+    );
+    // The tool's own timeout wins over the shorter default
+    assert_eq!(result(2), "\"finally\"");
+}
+
+/// A tool returning `count` records
+fn bulky_tool(count: usize) -> crate::NativeTool {
+    crate::NativeTool::new(
+        "list_files",
+        "Lists every file",
+        json!({ "type": "object", "properties": {} }),
+        move |_params| async move {
+            let files: Vec<_> = (0..count)
+                .map(|i| json!({ "path": format!("/data/file-{}.txt", i), "size": i }))
+                .collect();
+            Ok(crate::types::ToolResult::success(json!(files), 0.0))
+        },
+    )
+}
+
+fn tool_completed(stream: &crate::event::EventStream) -> serde_json::Value {
+    stream
+        .all()
+        .into_iter()
+        .find(|e| {
+            e.scope == crate::event::EventScope::Tool
+                && e.event_type == crate::event::EventType::Completed
+        })
+        .unwrap()
+        .data
+}
+
+#[tokio::test]
+async fn test_oversized_tool_results_are_truncated_for_the_llm() {
+    use crate::event::EventStream;
+    use crate::llm::MockLlmClient;
+    use crate::tools::truncation::DEFAULT_MAX_TOOL_RESULT_BYTES;
+    use crate::tools::ToolRegistry;
+    use std::sync::Arc;
+
+    let mut registry = ToolRegistry::new();
+    registry.register(bulky_tool(100_000));
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("list_files", json!({}))
+            .with_response("Lots of files"),
+    );
+    let agent = Agent::new(
+        AgentConfig::builder("lister")
+            .tools(Arc::new(registry))
+            .build(),
+    )
+    .with_client(client.clone());
+
+    let stream = EventStream::new();
+    agent
+        .execute_with_events(AgentInput::from_value(json!("go")), Some(&stream))
+        .await
+        .unwrap();
+
+    let request = &client.get_calls()[1];
+    let tool_message = request.messages.last().unwrap();
+    assert!(tool_message.content.text().len() <= DEFAULT_MAX_TOOL_RESULT_BYTES);
+    // Escaping the JSON inside the message adds some on the wire
+    assert!(serde_json::to_vec(request).unwrap().len() < 2 * DEFAULT_MAX_TOOL_RESULT_BYTES);
+    let text = tool_message.content.text();
+    let (kept, note) = text.rsplit_once('\n').unwrap();
+    let kept: Vec<serde_json::Value> = serde_json::from_str(kept).unwrap();
+    assert_eq!(kept[0]["path"], "/data/file-0.txt");
+    assert_eq!(
+        kept.last().unwrap(),
+        &json!(format!(
+            "...truncated, {} more items",
+            100_000 - (kept.len() - 1)
+        ))
+    );
+    assert!(note.starts_with("[truncated; the full result was "));
+
+    // Too large to carry in the event too, so it is referenced by hash
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let data = tool_completed(&stream);
+    assert!(data.get("result").is_none());
+    assert_eq!(data["result_sha256"].as_str().unwrap().len(), 64);
+    let original = data["result_bytes"].as_u64().unwrap();
+    assert!(original > 1024 * 1024);
+    assert_eq!(data["truncated"]["original_bytes"], original);
+    assert_eq!(
+        data["truncated"]["sent_bytes"],
+        tool_message.content.text().len()
+    );
+}
+
+struct CountingTransformer;
+
+#[async_trait::async_trait]
+impl crate::tools::ToolResultTransformer for CountingTransformer {
+    async fn compact(&self, tool_name: &str, output: &serde_json::Value, _max: usize) -> String {
+        format!(
+            "{} returned {} items",
+            tool_name,
+            output.as_array().unwrap().len()
+        )
+    }
+}
+
+#[tokio::test]
+async fn test_tool_result_transformer_replaces_truncation() {
+    use crate::event::EventStream;
+    use crate::llm::MockLlmClient;
+    use crate::tools::ToolRegistry;
+    use std::sync::Arc;
+
+    let mut registry = ToolRegistry::new();
+    registry.register(bulky_tool(1_000));
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("list_files", json!({}))
+            .with_response("A thousand files"),
+    );
+    let agent = Agent::new(
+        AgentConfig::builder("lister")
+            .tools(Arc::new(registry))
+            .max_tool_result_bytes(1_000)
+            .tool_result_transformer(Arc::new(CountingTransformer))
+            .build(),
+    )
+    .with_client(client.clone());
+
+    let stream = EventStream::new();
+    agent
+        .execute_with_events(AgentInput::from_value(json!("go")), Some(&stream))
+        .await
+        .unwrap();
+    let calls = client.get_calls();
+    assert_eq!(
+        calls[1].messages.last().unwrap().content,
+        "list_files returned 1000 items"
+    );
+
+    // The event still has the whole result
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let data = tool_completed(&stream);
+    assert_eq!(data["result"].as_array().unwrap().len(), 1_000);
+    assert!(data.get("result_sha256").is_none());
+
+    // Without a limit the result goes through whole
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("list_files", json!({}))
+            .with_response("A thousand files"),
+    );
+    let mut registry = ToolRegistry::new();
+    registry.register(bulky_tool(1_000));
+    let agent = Agent::new(
+        AgentConfig::builder("lister")
+            .tools(Arc::new(registry))
+            .unlimited_tool_results()
+            .build(),
+    )
+    .with_client(client.clone());
+    agent
+        .execute(&AgentInput::from_value(json!("go")))
+        .await
+        .unwrap();
+    let content = client.get_calls()[1]
+        .messages
+        .last()
+        .unwrap()
+        .content
+        .to_string();
+    let files: Vec<serde_json::Value> = serde_json::from_str(&content).unwrap();
+    assert_eq!(files.len(), 1_000);
+}
+
+#[tokio::test]
+async fn test_budget_signals_fire_once_and_degrade_at_the_end() {
+    use crate::agent::{BudgetKind, BudgetSignals};
+    use crate::llm::{MockLlmClient, ToolChoice};
+    use crate::tools::ToolRegistry;
+    use std::sync::Arc;
+
+    let mut registry = ToolRegistry::new();
+    registry.register(sleepy_tool("work", 0));
+    let client = (0..8).fold(MockLlmClient::new(), |client, _| {
+        client.with_tool_call("work", json!({}))
+    });
+    let client = Arc::new(client.with_response("Here is what I found"));
+    let agent = Agent::new(
+        AgentConfig::builder("diligent")
+            .tools(Arc::new(registry))
+            .max_tool_iterations(10)
+            .disable_tool_loop_detection()
+            .budget_signals(BudgetSignals::new().with_thresholds([0.5, 0.8]))
+            .build(),
+    )
+    .with_client(client.clone());
+
+    let output = agent
+        .execute(&AgentInput::from_value(json!("go")))
+        .await
+        .unwrap();
+    assert_eq!(output.data["response"], "Here is what I found");
+
+    let signals = &output.metadata.budget_signals;
+    let fired: Vec<(f64, usize, bool)> = signals
+        .iter()
+        .map(|s| (s.threshold, s.iteration, s.degraded))
+        .collect();
+    assert_eq!(fired, vec![(0.5, 6, false), (0.8, 9, true)]);
+    assert_eq!(signals[0].budget, BudgetKind::Iterations);
+    assert_eq!(
+        signals[1].message,
+        "[Budget notice] You have 2 model calls left. \
+         Tools are no longer available; give your final answer now."
+    );
+
+    // Each notice reached the model once, at the end of the conversation
+    let calls = client.get_calls();
+    for (call, request) in calls.iter().enumerate() {
+        let notices: Vec<_> = request
+            .messages
+            .iter()
+            .filter(|m| m.content.text().starts_with("[Budget notice]"))
+            .map(|m| m.content.text())
+            .collect();
+        assert_eq!(notices.len(), [0, 0, 0, 0, 0, 1, 1, 1, 2][call]);
+    }
+    assert_eq!(
+        calls[5].messages.last().unwrap().content,
+        signals[0].message
+    );
+    assert_eq!(calls[0].messages[0], calls[8].messages[0]);
+
+    // The final call kept its tools but couldn't call them
+    assert!(calls[7].offers_tools());
+    assert_eq!(calls[8].tools, calls[7].tools);
+    assert_eq!(calls[8].tool_choice, Some(ToolChoice::None));
+
+    // Downstream agents don't inherit the notices
+    assert!(output
+        .chat_history
+        .unwrap()
+        .iter()
+        .all(|m| !m.content.text().starts_with("[Budget notice]")));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_time_budget_signal_describes_the_time_left() {
+    use crate::agent::{BudgetKind, BudgetSignals};
+    use crate::event::EventStream;
+    use crate::llm::MockLlmClient;
+    use crate::tools::ToolRegistry;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let mut registry = ToolRegistry::new();
+    registry.register(sleepy_tool("crawl", 4000));
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("crawl", json!({ "page": 1 }))
+            .with_tool_call("crawl", json!({ "page": 2 }))
+            .with_response("Summary of two pages"),
+    );
+    let agent = Agent::new(
+        AgentConfig::builder("crawler")
+            .tools(Arc::new(registry))
+            .deadline(Duration::from_secs(10))
+            .budget_signals(BudgetSignals::new())
+            .build(),
+    )
+    .with_client(client.clone());
+
+    let stream = EventStream::new();
+    let output = agent
+        .execute_with_events(AgentInput::from_value(json!("go")), Some(&stream))
+        .await
+        .unwrap();
+
+    let signals = &output.metadata.budget_signals;
+    assert_eq!(signals.len(), 1);
+    assert_eq!(signals[0].threshold, 0.7);
+    assert_eq!(signals[0].iteration, 3);
+    assert_eq!(signals[0].budget, BudgetKind::Time);
+    assert_eq!(signals[0].remaining.time_ms, Some(2000));
+    assert_eq!(
+        signals[0].message,
+        "[Budget notice] You have about 2 seconds and 8 model calls left. \
+         Start wrapping up so your answer is complete in time."
+    );
+    // Only the final threshold rules out tool calls
+    assert!(client.last_call().unwrap().offers_tools());
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let events: Vec<_> = stream
+        .all()
+        .into_iter()
+        .filter(|e| e.component_id == "system:budget")
+        .collect();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].data["signal"]["threshold"], 0.7);
+    assert_eq!(events[0].data["agent"], "crawler");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_agent_events_keep_emission_order() {
+    use crate::event::{EventStream, EventType};
+    use crate::llm::MockLlmClient;
+    use crate::tools::{NativeTool, ToolRegistry};
+    use crate::types::ToolResult;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    let mut registry = ToolRegistry::new();
+    registry.register(NativeTool::new(
+        "lookup",
+        "Look it up",
+        json!({"type": "object"}),
+        |_params| async move { Ok(ToolResult::success(json!({"found": true}), 0.0)) },
+    ));
+    let registry = Arc::new(registry);
+    let stream = EventStream::new();
+
+    // Fast runs emit Started and Completed back to back; repeat to give a
+    // reordering the chance to show
+    for _ in 0..20 {
+        let client = Arc::new(
+            MockLlmClient::new()
+                .with_tool_call("lookup", json!({}))
+                .with_response("It exists"),
+        );
+        let agent = Agent::new(AgentConfig::builder("fast").tools(registry.clone()).build())
+            .with_client(client);
+        agent
+            .execute_with_events(AgentInput::from_text("Does it exist?"), Some(&stream))
+            .await
+            .unwrap();
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+    let mut started: HashMap<String, u64> = HashMap::new();
+    for event in stream.snapshot().iter() {
+        match event.event_type {
+            EventType::Started => {
+                started.insert(event.component_id.clone(), event.offset);
+            }
+            EventType::Completed => {
+                let start = started.remove(&event.component_id);
+                assert!(
+                    start.is_some_and(|start| start < event.offset),
+                    "{} completed at {} before it started",
+                    event.component_id,
+                    event.offset
+                );
+            }
+            _ => {}
+        }
+    }
+    assert!(started.is_empty(), "never completed: {:?}", started);
+}
@@ -0,0 +1,227 @@
+//! Tool calling for models without native function calling.
+//!
+//! An agent in [`ToolCallingMode::Prompted`] sends no tool schemas. A
+//! system message after its system prompt describes the tools instead, and
+//! asks for each call as a fenced JSON block,
+//! `{"tool": "get_weather", "arguments": {"city": "Oslo"}}`. Every block
+//! found in a response's text runs like a native tool call, with the same
+//! loop detection and Tool events, and its result goes back as a user
+//! message starting "Observation". A response without blocks is the
+//! answer. A block that looks like a call but doesn't parse is answered
+//! with a user message saying what's wrong, so the model can try again.
+//!
+//! [`ToolCallingMode::Auto`] starts native, and switches to prompted for
+//! the rest of the execution once the provider rejects the tools or the
+//! model writes a call as text.
+
+use serde::{Deserialize, Serialize};
+
+use crate::llm::types::{FunctionCall, Role, ToolCall};
+use crate::llm::{ChatMessage, LlmError};
+use crate::types::JsonValue;
+
+/// How an agent offers its tools to the model
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCallingMode {
+    /// Send tool schemas with each request and run the calls the provider
+    /// returns
+    #[default]
+    Native,
+
+    /// Describe the tools in the prompt and run the calls the model writes
+    /// in its text
+    Prompted,
+
+    /// Native until the provider rejects the tools or the model writes a
+    /// call as text, then prompted
+    Auto,
+}
+
+/// Tool calls found in a response's text
+#[derive(Debug, Default)]
+pub(crate) struct PromptedCalls {
+    pub calls: Vec<ToolCall>,
+
+    /// Why blocks that look like calls couldn't be read
+    pub errors: Vec<String>,
+}
+
+impl PromptedCalls {
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty() && self.errors.is_empty()
+    }
+
+    /// The user message telling the model what was wrong with its blocks
+    pub fn correction(&self) -> Option<ChatMessage> {
+        if self.errors.is_empty() {
+            return None;
+        }
+        Some(ChatMessage::user(format!(
+            "Your tool call could not be read: {}. Write each call as a fenced JSON block \
+             like {{\"tool\": \"<name>\", \"arguments\": {{...}}}}, or answer without one.",
+            self.errors.join("; ")
+        )))
+    }
+}
+
+/// The system message describing `schemas` and how to call them
+pub(crate) fn instructions(schemas: &[JsonValue]) -> ChatMessage {
+    let mut text = String::from("You can use these tools:\n");
+    for schema in schemas {
+        let function = &schema["function"];
+        text.push_str(&format!(
+            "\n- {}: {}\n  Arguments (JSON Schema): {}\n",
+            function["name"].as_str().unwrap_or_default(),
+            function["description"].as_str().unwrap_or_default(),
+            function["parameters"]
+        ));
+    }
+    text.push_str(
+        "\nTo call a tool, write a fenced JSON block naming it:\n\n\
+         ```json\n{\"tool\": \"<name>\", \"arguments\": {...}}\n```\n\n\
+         Use one block per call and stop after your blocks. Each result comes back \
+         in a message starting with \"Observation\". Once you can answer, answer \
+         without a block.",
+    );
+    ChatMessage::system(text)
+}
+
+/// Offer `schemas` in `messages`, replacing the `current` instructions;
+/// `None` withdraws them
+pub(crate) fn offer(
+    messages: &mut Vec<ChatMessage>,
+    current: &mut Option<ChatMessage>,
+    schemas: Option<&[JsonValue]>,
+) {
+    let wanted = schemas.map(instructions);
+    if wanted == *current {
+        return;
+    }
+    if let Some(previous) = current.take() {
+        messages.retain(|m| *m != previous);
+    }
+    if let Some(message) = &wanted {
+        let at = usize::from(messages.first().is_some_and(|m| m.role == Role::System));
+        messages.insert(at, message.clone());
+    }
+    *current = wanted;
+}
+
+/// The user message carrying a call's result back to the model
+pub(crate) fn observation(call: &ToolCall, result: &str) -> ChatMessage {
+    ChatMessage::user(format!(
+        "Observation from {}: {}",
+        call.function.name, result
+    ))
+}
+
+/// Whether `error` is the provider refusing a request for its tools
+pub(crate) fn rejects_tools(error: &LlmError) -> bool {
+    let message = match error {
+        LlmError::InvalidRequest(message) => message,
+        LlmError::ApiError(message) if message.starts_with("Status 400") => message,
+        _ => return false,
+    };
+    let message = message.to_lowercase();
+    message.contains("tool") || message.contains("function")
+}
+
+/// The tool calls written in `text` as fenced JSON blocks, or as bare
+/// JSON making up the whole text; ids are numbered by `iteration`
+pub(crate) fn parse(text: &str, iteration: usize) -> PromptedCalls {
+    let mut found = PromptedCalls::default();
+    let mut blocks = fenced_blocks(text);
+    // Some models skip the fences when the call is all they write
+    let bare = text.trim();
+    if blocks.is_empty() && (bare.starts_with('{') || bare.starts_with('[')) {
+        blocks.push(bare);
+    }
+    for block in blocks {
+        match serde_json::from_str::<JsonValue>(block) {
+            Ok(JsonValue::Array(items)) => {
+                for item in &items {
+                    read_call(item, iteration, &mut found);
+                }
+            }
+            Ok(value) => read_call(&value, iteration, &mut found),
+            // Only a block that means to be a call is worth correcting
+            Err(e) if block.contains("\"tool\"") => {
+                found.errors.push(format!("invalid JSON, {}", e))
+            }
+            Err(_) => {}
+        }
+    }
+    found
+}
+
+/// The contents of each ``` block, without its info string; an unclosed
+/// block runs to the end
+fn fenced_blocks(text: &str) -> Vec<&str> {
+    let mut blocks = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("```") {
+        let after = &rest[start + 3..];
+        // The info string, e.g. `json`, ends the opening line
+        let body = match after.find('\n') {
+            Some(newline) if !after[..newline].contains('{') => &after[newline + 1..],
+            _ => after,
+        };
+        let (block, next) = match body.find("```") {
+            Some(end) => (&body[..end], &body[end + 3..]),
+            None => (body, ""),
+        };
+        blocks.push(block.trim());
+        rest = next;
+    }
+    blocks
+}
+
+/// Add `value` to `found` if it's a call: `tool` names it, or `name` does
+/// alongside `arguments`
+fn read_call(value: &JsonValue, iteration: usize, found: &mut PromptedCalls) {
+    let Some(fields) = value.as_object() else {
+        return;
+    };
+    let arguments = fields.get("arguments").or_else(|| fields.get("parameters"));
+    let name = match (fields.get("tool"), fields.get("name")) {
+        (Some(tool), _) => tool,
+        (None, Some(name)) if arguments.is_some() => name,
+        _ => return,
+    };
+    let Some(name) = name.as_str().filter(|name| !name.is_empty()) else {
+        found
+            .errors
+            .push(format!("\"tool\" must be a tool's name, not {}", name));
+        return;
+    };
+    let arguments = match arguments {
+        None | Some(JsonValue::Null) => JsonValue::Object(Default::default()),
+        // Some models send the arguments JSON-encoded, as providers do
+        Some(JsonValue::String(encoded)) => match serde_json::from_str(encoded) {
+            Ok(arguments) => arguments,
+            Err(e) => {
+                found
+                    .errors
+                    .push(format!("the arguments of {} are invalid JSON, {}", name, e));
+                return;
+            }
+        },
+        Some(arguments) => arguments.clone(),
+    };
+    if !arguments.is_object() {
+        found.errors.push(format!(
+            "the arguments of {} must be a JSON object, not {}",
+            name, arguments
+        ));
+        return;
+    }
+    found.calls.push(ToolCall {
+        id: format!("prompted_{}_{}", iteration, found.calls.len()),
+        r#type: "function".to_string(),
+        function: FunctionCall {
+            name: name.to_string(),
+            arguments: arguments.to_string(),
+        },
+    });
+}
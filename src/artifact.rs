@@ -0,0 +1,369 @@
+//! Binary artifact storage for passing files between tools and steps.
+//!
+//! Tools hand bytes to the runtime by attaching [`NewArtifact`]s to their
+//! `ToolResult`. The runtime stores them in an [`ArtifactStore`] and only the
+//! short handle plus a description ever reaches the LLM conversation; later
+//! tools resolve the handle through their `ToolRunContext`.
+//!
+//! Artifacts are scoped to the workflow run that produced them and are
+//! garbage-collected when that run finishes unless they were exported. A
+//! sub-workflow's artifacts pass to its parent's run instead.
+
+use crate::paths::{PathError, Sandbox};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// Prefix used for artifact handles shown to the LLM
+pub const HANDLE_PREFIX: &str = "artifact://";
+
+/// Reference to a stored artifact - safe to embed in JSON and step outputs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtifactRef {
+    /// Globally unique ID
+    pub id: String,
+
+    /// Short handle the LLM and tools use (`artifact://N`)
+    pub handle: String,
+
+    /// File name (e.g. `chart.png` or `charts/q3.png`), always with `/`
+    /// separators
+    pub name: String,
+
+    pub mime_type: String,
+
+    /// Size in bytes
+    pub size: u64,
+
+    /// Hex-encoded SHA-256 of the content
+    pub sha256: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl ArtifactRef {
+    /// One-line summary suitable for the LLM conversation
+    pub fn summary(&self) -> String {
+        let mut line = format!(
+            "[artifact {}: {} ({}, {} bytes)",
+            self.handle, self.name, self.mime_type, self.size
+        );
+        if let Some(description) = &self.description {
+            line.push_str(&format!(" - {}", description));
+        }
+        line.push(']');
+        line
+    }
+}
+
+/// Artifact content produced by a tool, not yet stored
+#[derive(Debug, Clone)]
+pub struct NewArtifact {
+    pub name: String,
+    pub mime_type: String,
+    pub description: Option<String>,
+    pub data: Vec<u8>,
+}
+
+impl NewArtifact {
+    pub fn new(name: impl Into<String>, mime_type: impl Into<String>, data: Vec<u8>) -> Self {
+        Self {
+            name: name.into(),
+            mime_type: mime_type.into(),
+            description: None,
+            data,
+        }
+    }
+
+    /// Describe the artifact for the LLM (shown next to the handle)
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+}
+
+/// A stored artifact with its content
+#[derive(Debug, Clone)]
+pub struct Artifact {
+    pub reference: ArtifactRef,
+    pub data: Arc<Vec<u8>>,
+}
+
+impl Artifact {
+    /// Verify the content still matches the recorded hash
+    pub fn verify(&self) -> bool {
+        sha256_hex(&self.data) == self.reference.sha256
+    }
+}
+
+struct StoredArtifact {
+    artifact: Artifact,
+    scope: Option<String>,
+    exported: bool,
+}
+
+/// In-memory artifact store shared between the runtime and tools
+///
+/// Cloning is cheap and clones share the same storage. [`ArtifactStore::scoped`]
+/// returns a view that tags every new artifact with a run ID so it can be
+/// collected when that run completes.
+#[derive(Clone, Default)]
+pub struct ArtifactStore {
+    entries: Arc<RwLock<HashMap<String, StoredArtifact>>>,
+    next_handle: Arc<AtomicU64>,
+    scope: Option<String>,
+}
+
+impl ArtifactStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// View of this store that tags new artifacts with `scope` (usually a run ID)
+    pub fn scoped(&self, scope: impl Into<String>) -> Self {
+        Self {
+            entries: self.entries.clone(),
+            next_handle: self.next_handle.clone(),
+            scope: Some(scope.into()),
+        }
+    }
+
+    /// Scope new artifacts are tagged with
+    pub fn scope(&self) -> Option<&str> {
+        self.scope.as_deref()
+    }
+
+    /// Store artifact content and return its reference
+    pub fn put(&self, artifact: NewArtifact) -> ArtifactRef {
+        let n = self.next_handle.fetch_add(1, Ordering::SeqCst) + 1;
+        let reference = ArtifactRef {
+            id: format!("art_{}", uuid::Uuid::new_v4()),
+            handle: format!("{}{}", HANDLE_PREFIX, n),
+            name: crate::paths::portable_name(&artifact.name),
+            mime_type: artifact.mime_type,
+            size: artifact.data.len() as u64,
+            sha256: sha256_hex(&artifact.data),
+            description: artifact.description,
+        };
+
+        self.entries.write().unwrap().insert(
+            reference.id.clone(),
+            StoredArtifact {
+                artifact: Artifact {
+                    reference: reference.clone(),
+                    data: Arc::new(artifact.data),
+                },
+                scope: self.scope.clone(),
+                exported: false,
+            },
+        );
+
+        reference
+    }
+
+    /// Resolve an artifact by ID or handle
+    pub fn get(&self, id_or_handle: &str) -> Option<Artifact> {
+        let entries = self.entries.read().unwrap();
+        entries
+            .get(id_or_handle)
+            .or_else(|| {
+                entries
+                    .values()
+                    .find(|e| e.artifact.reference.handle == id_or_handle)
+            })
+            .map(|e| e.artifact.clone())
+    }
+
+    /// Mark an artifact as exported so it outlives the run that produced it
+    ///
+    /// Returns false if no artifact matches.
+    pub fn export(&self, id_or_handle: &str) -> bool {
+        let mut entries = self.entries.write().unwrap();
+        match entries.values_mut().find(|e| {
+            e.artifact.reference.id == id_or_handle || e.artifact.reference.handle == id_or_handle
+        }) {
+            Some(entry) => {
+                entry.exported = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Write an artifact's content to its name inside `directory`, creating
+    /// subdirectories as needed, and return the written path
+    ///
+    /// Names are untrusted tool output, so they are resolved through a
+    /// [`Sandbox`] and refused if they would land
+    /// outside `directory`.
+    pub fn save_to(
+        &self,
+        id_or_handle: &str,
+        directory: impl AsRef<std::path::Path>,
+    ) -> Result<std::path::PathBuf, PathError> {
+        let artifact = self.get(id_or_handle).ok_or_else(|| PathError::Io {
+            path: id_or_handle.to_string(),
+            message: "no such artifact".to_string(),
+        })?;
+        let directory = directory.as_ref();
+        let path = Sandbox::new(directory)?.resolve(&artifact.reference.name)?;
+        let write = || -> std::io::Result<()> {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, artifact.data.as_slice())
+        };
+        write().map_err(|e| PathError::Io {
+            path: path.display().to_string(),
+            message: e.to_string(),
+        })?;
+        Ok(path)
+    }
+
+    /// Remove an artifact regardless of scope or export state
+    pub fn remove(&self, id_or_handle: &str) -> Option<Artifact> {
+        let id = self.get(id_or_handle)?.reference.id;
+        self.entries
+            .write()
+            .unwrap()
+            .remove(&id)
+            .map(|e| e.artifact)
+    }
+
+    /// References of all artifacts produced under `scope`
+    pub fn list_scope(&self, scope: &str) -> Vec<ArtifactRef> {
+        let entries = self.entries.read().unwrap();
+        let mut refs: Vec<ArtifactRef> = entries
+            .values()
+            .filter(|e| e.scope.as_deref() == Some(scope))
+            .map(|e| e.artifact.reference.clone())
+            .collect();
+        refs.sort_by_key(|r| handle_number(&r.handle));
+        refs
+    }
+
+    /// Drop every unexported artifact produced under `scope`
+    ///
+    /// Returns the number of artifacts removed.
+    pub fn collect_scope(&self, scope: &str) -> usize {
+        let mut entries = self.entries.write().unwrap();
+        let before = entries.len();
+        entries.retain(|_, e| e.exported || e.scope.as_deref() != Some(scope));
+        before - entries.len()
+    }
+
+    /// Move every artifact produced under `from` to the scope `to`
+    ///
+    /// Returns the number of artifacts moved.
+    pub fn rescope(&self, from: &str, to: &str) -> usize {
+        let mut entries = self.entries.write().unwrap();
+        let mut moved = 0;
+        for entry in entries.values_mut() {
+            if entry.scope.as_deref() == Some(from) {
+                entry.scope = Some(to.to_string());
+                moved += 1;
+            }
+        }
+        moved
+    }
+
+    /// Number of stored artifacts (all scopes)
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.read().unwrap().is_empty()
+    }
+}
+
+impl std::fmt::Debug for ArtifactStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArtifactStore")
+            .field("artifacts", &self.len())
+            .field("scope", &self.scope)
+            .finish()
+    }
+}
+
+/// Hex-encoded SHA-256 digest
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn handle_number(handle: &str) -> u64 {
+    handle
+        .strip_prefix(HANDLE_PREFIX)
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_put_and_resolve_by_handle() {
+        let store = ArtifactStore::new();
+        let reference = store.put(
+            NewArtifact::new("chart.png", "image/png", vec![1, 2, 3]).with_description("Sales"),
+        );
+
+        assert_eq!(reference.handle, "artifact://1");
+        assert_eq!(reference.size, 3);
+        assert_eq!(reference.sha256, sha256_hex(&[1, 2, 3]));
+
+        let artifact = store.get("artifact://1").unwrap();
+        assert_eq!(*artifact.data, vec![1, 2, 3]);
+        assert!(artifact.verify());
+        assert!(store.get(&reference.id).is_some());
+        assert!(reference.summary().contains("artifact://1: chart.png"));
+    }
+
+    #[test]
+    fn test_collect_scope_keeps_exported_and_other_scopes() {
+        let store = ArtifactStore::new();
+        let run_a = store.scoped("run_a");
+        let run_b = store.scoped("run_b");
+
+        let keep = run_a.put(NewArtifact::new("keep.zip", "application/zip", vec![0]));
+        run_a.put(NewArtifact::new("tmp.zip", "application/zip", vec![1]));
+        run_b.put(NewArtifact::new("other.zip", "application/zip", vec![2]));
+
+        assert!(store.export(&keep.handle));
+        assert_eq!(store.list_scope("run_a").len(), 2);
+        assert_eq!(store.collect_scope("run_a"), 1);
+
+        assert_eq!(store.len(), 2);
+        assert!(store.get(&keep.handle).is_some());
+        assert_eq!(store.list_scope("run_b").len(), 1);
+    }
+
+    #[test]
+    fn test_save_to_stays_inside_directory() {
+        let dir =
+            std::env::temp_dir().join(format!("agent-runtime-artifacts-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = ArtifactStore::new();
+
+        let chart = store.put(NewArtifact::new(r"charts\q3.png", "image/png", vec![7]));
+        assert_eq!(chart.name, "charts/q3.png");
+        let path = store.save_to(&chart.handle, &dir).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), vec![7]);
+        assert!(path.ends_with(std::path::Path::new("charts").join("q3.png")));
+
+        let device = store.put(NewArtifact::new("nul.txt", "text/plain", vec![]));
+        assert!(matches!(
+            store.save_to(&device.handle, &dir),
+            Err(PathError::Reserved { .. })
+        ));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,7 +1,12 @@
 use crate::error::{ConfigError, ConfigErrorCode};
+use crate::event::EventFilter;
+use crate::llm::RateLimits;
+use crate::pii::{NationalIdLocale, PiiAction};
 use crate::runtime::retry::RetryPolicy;
 use crate::runtime::timeout::TimeoutConfig;
+use crate::usage::CostPerMToken;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 use std::time::Duration;
 
@@ -27,6 +32,14 @@ pub struct RuntimeConfig {
     /// Workflow configuration
     #[serde(default)]
     pub workflow: WorkflowConfig,
+
+    /// Event webhook subscriptions
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+
+    /// Usage accounting
+    #[serde(default)]
+    pub usage: UsageConfig,
 }
 
 impl RuntimeConfig {
@@ -67,10 +80,10 @@ impl RuntimeConfig {
         let path = path.as_ref();
         let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("");
 
-        match extension {
-            "toml" => Self::from_toml_file(path),
-            "yaml" | "yml" => Self::from_yaml_file(path),
-            _ => Err(ConfigError {
+        match file_format(path) {
+            Some(config::FileFormat::Toml) => Self::from_toml_file(path),
+            Some(_) => Self::from_yaml_file(path),
+            None => Err(ConfigError {
                 code: ConfigErrorCode::ParseError,
                 message: format!(
                     "Unsupported file extension '{}'. Use .toml, .yaml, or .yml",
@@ -112,8 +125,16 @@ impl RuntimeConfig {
 
         // Add file if provided
         if let Some(path) = file_path {
-            let path_str = path.as_ref().display().to_string();
-            settings = settings.add_source(config::File::with_name(&path_str).required(false));
+            let path = path.as_ref();
+            // Use the extension's format, in any case, as `from_file` does
+            settings = match file_format(path) {
+                Some(format) => {
+                    settings.add_source(config::File::from(path).format(format).required(false))
+                }
+                None => settings.add_source(
+                    config::File::with_name(&path.display().to_string()).required(false),
+                ),
+            };
         }
 
         // Add environment variables (highest priority)
@@ -144,10 +165,28 @@ impl RuntimeConfig {
         // Validate timeout config
         self.timeout.validate()?;
 
+        for (index, webhook) in self.webhooks.iter().enumerate() {
+            webhook.validate(index)?;
+        }
+
+        self.usage.validate()?;
+
         Ok(())
     }
 }
 
+/// Config file format from a path's extension, ignoring case
+fn file_format(path: &Path) -> Option<config::FileFormat> {
+    if crate::paths::has_extension(path, "toml") {
+        Some(config::FileFormat::Toml)
+    } else if crate::paths::has_extension(path, "yaml") || crate::paths::has_extension(path, "yml")
+    {
+        Some(config::FileFormat::Yaml)
+    } else {
+        None
+    }
+}
+
 /// LLM provider configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LlmConfig {
@@ -160,6 +199,15 @@ pub struct LlmConfig {
     /// Llama.cpp configuration
     pub llama: Option<LlamaConfig>,
 
+    /// Anthropic configuration
+    pub anthropic: Option<AnthropicConfig>,
+
+    /// Gemini configuration
+    pub gemini: Option<GeminiConfig>,
+
+    /// Ollama configuration
+    pub ollama: Option<OllamaConfig>,
+
     /// Default model name
     pub default_model: Option<String>,
 
@@ -169,8 +217,48 @@ pub struct LlmConfig {
 
     /// Default max tokens
     pub default_max_tokens: Option<u32>,
+
+    /// Providers to try in order, for `FallbackChatClient::from_config`,
+    /// e.g. `["llama", "openai"]`
+    #[serde(default)]
+    pub fallback: Vec<String>,
+
+    /// How long a failed provider is skipped in the fallback chain
+    pub fallback_cooldown_ms: Option<u64>,
+
+    /// Embedding model, for `llm::embeddings::from_config`
+    pub embedding: Option<EmbeddingConfig>,
+}
+
+/// Embedding model configuration
+///
+/// Connection settings come from the provider's own section.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingConfig {
+    /// `openai` or `llama`
+    pub provider: String,
+
+    pub model: String,
+
+    /// Vector length to ask for (OpenAI `text-embedding-3-*` models only)
+    pub dimensions: Option<usize>,
+
+    /// Inputs sent per request; larger batches are split (default: 2048
+    /// for OpenAI, 32 for llama.cpp)
+    pub max_inputs_per_request: Option<usize>,
+
+    /// llama.cpp only: use the native `/embedding` endpoint instead of
+    /// `/v1/embeddings`
+    #[serde(default)]
+    pub native_endpoint: bool,
 }
 
+/// Provider names accepted in `llm.embedding.provider`
+pub const EMBEDDING_PROVIDERS: &[&str] = &["openai", "llama"];
+
+/// Provider names accepted in `llm.fallback`
+pub const FALLBACK_PROVIDERS: &[&str] = &["openai", "llama", "anthropic", "gemini", "ollama"];
+
 fn default_temperature() -> f32 {
     0.7
 }
@@ -181,9 +269,15 @@ impl Default for LlmConfig {
             default_provider: None,
             openai: None,
             llama: None,
+            anthropic: None,
+            gemini: None,
+            ollama: None,
             default_model: None,
             default_temperature: 0.7,
             default_max_tokens: None,
+            fallback: Vec::new(),
+            fallback_cooldown_ms: None,
+            embedding: None,
         }
     }
 }
@@ -199,6 +293,107 @@ impl LlmConfig {
                 });
             }
         }
+        for provider in FALLBACK_PROVIDERS {
+            if let Some(limits) = self.rate_limits(provider) {
+                limits.validate(&format!("llm.{}.rate_limit", provider))?;
+            }
+        }
+        self.validate_fallback()?;
+        self.validate_embedding()
+    }
+
+    /// The `rate_limit` of a provider's section (`openai`, `llama`,
+    /// `anthropic`, `gemini` or `ollama`), if it has one
+    pub fn rate_limits(&self, provider: &str) -> Option<&RateLimits> {
+        match provider {
+            "openai" => self.openai.as_ref()?.rate_limit.as_ref(),
+            "llama" => self.llama.as_ref()?.rate_limit.as_ref(),
+            "anthropic" => self.anthropic.as_ref()?.rate_limit.as_ref(),
+            "gemini" => self.gemini.as_ref()?.rate_limit.as_ref(),
+            "ollama" => self.ollama.as_ref()?.rate_limit.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Every provider in `fallback` is known, listed once and configured
+    pub(crate) fn validate_fallback(&self) -> Result<(), ConfigError> {
+        for (i, name) in self.fallback.iter().enumerate() {
+            let field = Some(format!("llm.fallback[{}]", i));
+            if !FALLBACK_PROVIDERS.contains(&name.as_str()) {
+                return Err(ConfigError {
+                    code: ConfigErrorCode::InvalidValue,
+                    message: format!(
+                        "Unknown provider '{}', expected one of {}",
+                        name,
+                        FALLBACK_PROVIDERS.join(", ")
+                    ),
+                    field,
+                });
+            }
+            if self.fallback[..i].contains(name) {
+                return Err(ConfigError {
+                    code: ConfigErrorCode::InvalidValue,
+                    message: format!("Provider '{}' is listed twice", name),
+                    field,
+                });
+            }
+            let configured = match name.as_str() {
+                "openai" => self.openai.is_some(),
+                "llama" => self.llama.is_some(),
+                "anthropic" => self.anthropic.is_some(),
+                "gemini" => self.gemini.is_some(),
+                _ => self.ollama.is_some(),
+            };
+            if !configured {
+                return Err(ConfigError {
+                    code: ConfigErrorCode::MissingRequiredField,
+                    message: format!("Provider '{}' has no [llm.{}] section", name, name),
+                    field,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// `embedding`, if set, names a known, configured provider and a
+    /// positive batch size
+    pub(crate) fn validate_embedding(&self) -> Result<(), ConfigError> {
+        let Some(embedding) = &self.embedding else {
+            return Ok(());
+        };
+        let field = Some("llm.embedding.provider".to_string());
+        let configured = match embedding.provider.as_str() {
+            "openai" => self.openai.is_some(),
+            "llama" => self.llama.is_some(),
+            name => {
+                return Err(ConfigError {
+                    code: ConfigErrorCode::InvalidValue,
+                    message: format!(
+                        "Unknown embedding provider '{}', expected one of {}",
+                        name,
+                        EMBEDDING_PROVIDERS.join(", ")
+                    ),
+                    field,
+                })
+            }
+        };
+        if !configured {
+            return Err(ConfigError {
+                code: ConfigErrorCode::MissingRequiredField,
+                message: format!(
+                    "Provider '{}' has no [llm.{}] section",
+                    embedding.provider, embedding.provider
+                ),
+                field,
+            });
+        }
+        if embedding.max_inputs_per_request == Some(0) {
+            return Err(ConfigError {
+                code: ConfigErrorCode::InvalidValue,
+                message: "max_inputs_per_request must be greater than 0".to_string(),
+                field: Some("llm.embedding.max_inputs_per_request".to_string()),
+            });
+        }
         Ok(())
     }
 }
@@ -209,6 +404,47 @@ pub struct OpenAIConfig {
     pub api_key: Option<String>,
     pub api_base: Option<String>,
     pub organization: Option<String>,
+    /// Client-side limits, applied by `FallbackChatClient::from_config`
+    /// or a `RateLimitedChatClient`
+    pub rate_limit: Option<RateLimits>,
+    /// Headers sent with every request, e.g. for a gateway in front of
+    /// the provider
+    #[serde(default)]
+    pub default_headers: HashMap<String, String>,
+    /// Proxy for every request, e.g. `http://proxy.internal:3128`
+    pub proxy: Option<String>,
+    /// How long to wait for a connection (default: no limit)
+    pub connect_timeout_ms: Option<u64>,
+    /// How long a whole request may take, streamed body included
+    /// (default: no limit)
+    pub request_timeout_ms: Option<u64>,
+}
+
+/// Anthropic-specific configuration
+///
+/// `api_key` falls back to the `ANTHROPIC_API_KEY` environment variable.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnthropicConfig {
+    pub api_key: Option<String>,
+    pub api_base: Option<String>,
+    pub model: Option<String>,
+    pub max_tokens: Option<u32>,
+    /// Client-side limits, applied by `FallbackChatClient::from_config`
+    /// or a `RateLimitedChatClient`
+    pub rate_limit: Option<RateLimits>,
+}
+
+/// Gemini-specific configuration
+///
+/// `api_key` falls back to the `GEMINI_API_KEY` environment variable.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GeminiConfig {
+    pub api_key: Option<String>,
+    pub api_base: Option<String>,
+    pub model: Option<String>,
+    /// Client-side limits, applied by `FallbackChatClient::from_config`
+    /// or a `RateLimitedChatClient`
+    pub rate_limit: Option<RateLimits>,
 }
 
 /// Llama.cpp-specific configuration
@@ -216,6 +452,36 @@ pub struct OpenAIConfig {
 pub struct LlamaConfig {
     pub base_url: String,
     pub insecure: bool,
+    /// Client-side limits, applied by `FallbackChatClient::from_config`
+    /// or a `RateLimitedChatClient`
+    pub rate_limit: Option<RateLimits>,
+    /// Headers sent with every request, e.g. for a gateway in front of
+    /// the provider
+    #[serde(default)]
+    pub default_headers: HashMap<String, String>,
+    /// Proxy for every request, e.g. `http://proxy.internal:3128`
+    pub proxy: Option<String>,
+    /// How long to wait for a connection (default: no limit)
+    pub connect_timeout_ms: Option<u64>,
+    /// How long a whole request may take, streamed body included
+    /// (default: no limit)
+    pub request_timeout_ms: Option<u64>,
+}
+
+/// Ollama-specific configuration, for the native `/api/chat` endpoint
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OllamaConfig {
+    /// Server address (default: `http://localhost:11434`)
+    pub base_url: Option<String>,
+    pub model: Option<String>,
+    /// How long the model stays loaded, e.g. `"10m"`, or seconds
+    pub keep_alive: Option<serde_json::Value>,
+    /// Model options sent with every request, e.g. `num_ctx`
+    #[serde(default)]
+    pub options: serde_json::Map<String, serde_json::Value>,
+    /// Client-side limits, applied by `FallbackChatClient::from_config`
+    /// or a `RateLimitedChatClient`
+    pub rate_limit: Option<RateLimits>,
 }
 
 /// Retry policy configuration
@@ -381,21 +647,166 @@ pub struct WorkflowConfig {
     /// Maximum tool iterations per agent
     #[serde(default = "default_max_tool_iterations")]
     pub max_tool_iterations: u32,
+
+    /// PII scanning of workflow outputs
+    #[serde(default)]
+    pub pii: PiiConfig,
+
+    /// How deep sub-workflows may nest; a sub-workflow step past it fails
+    /// instead of starting its workflow
+    #[serde(default = "default_max_subworkflow_depth")]
+    pub max_subworkflow_depth: usize,
 }
 
 fn default_max_tool_iterations() -> u32 {
     5
 }
 
+/// Default for [`WorkflowConfig::max_subworkflow_depth`]
+pub const DEFAULT_MAX_SUBWORKFLOW_DEPTH: usize = 8;
+
+fn default_max_subworkflow_depth() -> usize {
+    DEFAULT_MAX_SUBWORKFLOW_DEPTH
+}
+
 impl Default for WorkflowConfig {
     fn default() -> Self {
         Self {
             max_concurrent: None,
             max_tool_iterations: 5,
+            pii: PiiConfig::default(),
+            max_subworkflow_depth: DEFAULT_MAX_SUBWORKFLOW_DEPTH,
         }
     }
 }
 
+/// PII scanning configuration (see [`crate::pii::PiiScanner::from_config`])
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PiiConfig {
+    /// Scan outputs before they leave the runtime
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// What to do with findings
+    #[serde(default)]
+    pub action: PiiAction,
+
+    /// Also scan each step output, not just the final output
+    #[serde(default)]
+    pub scan_step_outputs: bool,
+
+    /// National ID formats to detect
+    #[serde(default = "default_pii_locales")]
+    pub locales: Vec<NationalIdLocale>,
+
+    /// Ignore matches below this confidence
+    #[serde(default)]
+    pub min_confidence: f32,
+}
+
+fn default_pii_locales() -> Vec<NationalIdLocale> {
+    vec![NationalIdLocale::Us]
+}
+
+impl Default for PiiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            action: PiiAction::ReportOnly,
+            scan_step_outputs: false,
+            locales: default_pii_locales(),
+            min_confidence: 0.0,
+        }
+    }
+}
+
+/// Webhook subscription configuration (see
+/// [`crate::event::webhook::WebhookSubscription::from_config`])
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    /// Target URL (http or https)
+    pub url: String,
+
+    /// Signs each body with HMAC-SHA256 when set
+    pub secret: Option<String>,
+
+    /// Which events to deliver (default: all)
+    #[serde(default)]
+    pub filter: EventFilter,
+
+    /// How long a batch stays open after its first event
+    #[serde(default = "default_batch_window_ms")]
+    pub batch_window_ms: u64,
+
+    #[serde(default = "default_max_batch_size")]
+    pub max_batch_size: usize,
+
+    /// Retries for failed deliveries
+    #[serde(default)]
+    pub retry: RetryConfig,
+
+    /// Batches that may wait for delivery before the oldest is dead-lettered
+    #[serde(default = "default_webhook_queue_capacity")]
+    pub queue_capacity: usize,
+
+    pub max_requests_per_second: Option<u32>,
+}
+
+fn default_batch_window_ms() -> u64 {
+    1000
+}
+fn default_max_batch_size() -> usize {
+    100
+}
+fn default_webhook_queue_capacity() -> usize {
+    16
+}
+
+impl WebhookConfig {
+    fn validate(&self, index: usize) -> Result<(), ConfigError> {
+        if !(self.url.starts_with("http://") || self.url.starts_with("https://")) {
+            return Err(ConfigError {
+                code: ConfigErrorCode::InvalidValue,
+                message: format!("Webhook URL must be http or https, got '{}'", self.url),
+                field: Some(format!("webhooks[{}].url", index)),
+            });
+        }
+        if self.max_batch_size == 0 {
+            return Err(ConfigError {
+                code: ConfigErrorCode::InvalidValue,
+                message: "Webhook max_batch_size must be at least 1".to_string(),
+                field: Some(format!("webhooks[{}].max_batch_size", index)),
+            });
+        }
+        self.retry.validate()
+    }
+}
+
+/// Usage accounting configuration (see
+/// [`crate::usage::UsageLedger::from_config`])
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageConfig {
+    /// Price per model name, in USD per million tokens; calls to other
+    /// models are counted but not priced
+    #[serde(default)]
+    pub prices: BTreeMap<String, CostPerMToken>,
+}
+
+impl UsageConfig {
+    fn validate(&self) -> Result<(), ConfigError> {
+        for (model, price) in &self.prices {
+            if !(price.prompt >= 0.0 && price.completion >= 0.0) {
+                return Err(ConfigError {
+                    code: ConfigErrorCode::InvalidValue,
+                    message: format!("Prices for '{}' must not be negative", model),
+                    field: Some(format!("usage.prices.{}", model)),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -406,6 +817,7 @@ mod tests {
         assert_eq!(config.retry.max_attempts, 3);
         assert_eq!(config.logging.level, "info");
         assert_eq!(config.workflow.max_tool_iterations, 5);
+        assert_eq!(config.workflow.max_subworkflow_depth, 8);
     }
 
     #[test]
@@ -432,6 +844,51 @@ mod tests {
         assert_eq!(config.logging.level, "debug");
     }
 
+    #[test]
+    fn test_webhook_deserialization() {
+        let toml_str = r#"
+            [[webhooks]]
+            url = "https://hooks.example.com/agents"
+            secret = "s3cret"
+            batch_window_ms = 250
+            filter = { scopes = ["tool"], event_types = ["failed"], workflows = ["prod-*"] }
+
+            [webhooks.retry]
+            max_attempts = 5
+        "#;
+
+        let config: RuntimeConfig = toml::from_str(toml_str).unwrap();
+        let webhook = &config.webhooks[0];
+        assert_eq!(webhook.batch_window_ms, 250);
+        assert_eq!(webhook.max_batch_size, 100);
+        assert_eq!(webhook.retry.max_attempts, 5);
+        assert_eq!(webhook.filter.workflows, vec!["prod-*".to_string()]);
+        assert!(config.validate().is_ok());
+
+        let mut bad = config.clone();
+        bad.webhooks[0].url = "ftp://example.com".to_string();
+        let err = bad.validate().unwrap_err();
+        assert_eq!(err.field.as_deref(), Some("webhooks[0].url"));
+    }
+
+    #[test]
+    fn test_usage_prices_deserialization() {
+        let toml_str = r#"
+            [usage.prices.gpt-4o]
+            prompt = 2.5
+            completion = 10.0
+        "#;
+
+        let config: RuntimeConfig = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.usage.prices["gpt-4o"], CostPerMToken::new(2.5, 10.0));
+        assert!(config.validate().is_ok());
+
+        let mut bad = config.clone();
+        bad.usage.prices.get_mut("gpt-4o").unwrap().prompt = -1.0;
+        let err = bad.validate().unwrap_err();
+        assert_eq!(err.field.as_deref(), Some("usage.prices.gpt-4o"));
+    }
+
     #[test]
     fn test_yaml_serialization() {
         let config = RuntimeConfig::default();
@@ -505,4 +962,25 @@ logging:
         assert_eq!(timeout.total, Some(Duration::from_millis(5000)));
         assert_eq!(timeout.first_response, Some(Duration::from_millis(1000)));
     }
+
+    #[test]
+    fn test_uppercase_extension_loads() {
+        let dir = std::env::temp_dir().join(format!("agent-runtime-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Runtime.TOML");
+        std::fs::write(&path, "[retry]\nmax_attempts = 7\n").unwrap();
+
+        assert_eq!(
+            RuntimeConfig::from_file(&path).unwrap().retry.max_attempts,
+            7
+        );
+        assert_eq!(
+            RuntimeConfig::from_sources(Some(&path))
+                .unwrap()
+                .retry
+                .max_attempts,
+            7
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
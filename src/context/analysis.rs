@@ -0,0 +1,501 @@
+//! Token attribution analysis for chat histories.
+//!
+//! `analyze_context` breaks a history down per message, per role and per
+//! provenance so it's obvious which messages are eating the context budget.
+
+use crate::llm::types::{ChatMessage, Role};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::strategies::estimate_tokens_simple;
+
+/// Number of heaviest messages kept in [`ContextReport::top_messages`]
+pub const DEFAULT_TOP_N: usize = 5;
+
+/// Maximum characters kept in a message preview
+const PREVIEW_CHARS: usize = 80;
+
+/// Width of the bar drawn by [`ContextReport::render_text`]
+const BAR_WIDTH: usize = 30;
+
+/// Estimates how many tokens a message occupies in the prompt
+pub trait TokenEstimator: Send + Sync {
+    /// Estimate the tokens for a single message
+    fn estimate_message(&self, message: &ChatMessage) -> usize;
+
+    /// Estimate the tokens for a slice of messages
+    fn estimate(&self, messages: &[ChatMessage]) -> usize {
+        messages.iter().map(|m| self.estimate_message(m)).sum()
+    }
+
+    /// Get the name of this estimator
+    fn name(&self) -> &str;
+}
+
+/// Character-based estimator (~4 chars per token), the same heuristic used
+/// by the built-in context strategies
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SimpleTokenEstimator;
+
+impl TokenEstimator for SimpleTokenEstimator {
+    fn estimate_message(&self, message: &ChatMessage) -> usize {
+        estimate_tokens_simple(std::slice::from_ref(message))
+    }
+
+    fn name(&self) -> &str {
+        "simple"
+    }
+}
+
+/// Which part of the prompt a message belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextSection {
+    /// System prompts
+    System,
+    /// Assistant tool calls and tool results
+    Tools,
+    /// Everything before the latest user turn
+    History,
+    /// The latest user message and whatever followed it
+    LatestTurn,
+}
+
+/// Token attribution for a single message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageTokens {
+    /// Position in the analyzed history
+    pub index: usize,
+    pub role: Role,
+    pub section: ContextSection,
+    /// Provenance key (`agent:<id>`, `tool:<name>` or `unattributed`)
+    pub provenance: String,
+    pub tokens: usize,
+    /// Fraction of the total tokens (0.0 - 1.0)
+    pub share: f64,
+    /// First characters of the content
+    pub preview: String,
+}
+
+/// Aggregated tokens for a group of messages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenGroup {
+    pub key: String,
+    pub messages: usize,
+    pub tokens: usize,
+    pub share: f64,
+}
+
+/// Result of [`analyze_context`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextReport {
+    /// Name of the estimator used
+    pub estimator: String,
+    pub total_tokens: usize,
+    /// Per-message attribution, in history order
+    pub messages: Vec<MessageTokens>,
+    /// Grouped by role, heaviest first
+    pub by_role: Vec<TokenGroup>,
+    /// Grouped by provenance, heaviest first
+    pub by_provenance: Vec<TokenGroup>,
+    /// Grouped by prompt section, heaviest first
+    pub by_section: Vec<TokenGroup>,
+    /// Heaviest messages, heaviest first
+    pub top_messages: Vec<MessageTokens>,
+}
+
+/// Analyze a chat history and attribute tokens to each message
+pub fn analyze_context(history: &[ChatMessage], estimator: &dyn TokenEstimator) -> ContextReport {
+    analyze_context_top_n(history, estimator, DEFAULT_TOP_N)
+}
+
+/// Same as [`analyze_context`] with a custom number of top messages
+pub fn analyze_context_top_n(
+    history: &[ChatMessage],
+    estimator: &dyn TokenEstimator,
+    top_n: usize,
+) -> ContextReport {
+    // Map tool_call_id -> tool name so results can be attributed to the tool
+    let tool_names: HashMap<&str, &str> = history
+        .iter()
+        .filter_map(|m| m.tool_calls.as_ref())
+        .flatten()
+        .map(|call| (call.id.as_str(), call.function.name.as_str()))
+        .collect();
+
+    let latest_user = history.iter().rposition(|m| m.role == Role::User);
+
+    let counts: Vec<usize> = history
+        .iter()
+        .map(|m| estimator.estimate_message(m))
+        .collect();
+    let total_tokens: usize = counts.iter().sum();
+
+    let messages: Vec<MessageTokens> = history
+        .iter()
+        .zip(counts)
+        .enumerate()
+        .map(|(index, (msg, tokens))| {
+            let section = if msg.role == Role::System {
+                ContextSection::System
+            } else if msg.role == Role::Tool || msg.tool_calls.is_some() {
+                ContextSection::Tools
+            } else if latest_user.is_some_and(|latest| index >= latest) {
+                ContextSection::LatestTurn
+            } else {
+                ContextSection::History
+            };
+
+            MessageTokens {
+                index,
+                role: msg.role.clone(),
+                section,
+                provenance: provenance_key(msg, &tool_names),
+                tokens,
+                share: share_of(tokens, total_tokens),
+                preview: preview(&msg.content.text()),
+            }
+        })
+        .collect();
+
+    let by_role = group_by(&messages, total_tokens, |m| role_name(&m.role).to_string());
+    let by_provenance = group_by(&messages, total_tokens, |m| m.provenance.clone());
+    let by_section = group_by(&messages, total_tokens, |m| {
+        section_name(m.section).to_string()
+    });
+
+    let mut top_messages = messages.clone();
+    // Stable sort keeps history order for ties
+    top_messages.sort_by(|a, b| b.tokens.cmp(&a.tokens));
+    top_messages.truncate(top_n);
+
+    ContextReport {
+        estimator: estimator.name().to_string(),
+        total_tokens,
+        messages,
+        by_role,
+        by_provenance,
+        by_section,
+        top_messages,
+    }
+}
+
+impl ContextReport {
+    /// Tokens attributed to a prompt section
+    pub fn section_tokens(&self, section: ContextSection) -> usize {
+        self.messages
+            .iter()
+            .filter(|m| m.section == section)
+            .map(|m| m.tokens)
+            .sum()
+    }
+
+    /// Fraction of `max_tokens` used by this history
+    pub fn utilization(&self, max_tokens: usize) -> f64 {
+        share_of(self.total_tokens, max_tokens)
+    }
+
+    /// Render a plain-text heatmap, heaviest entries first
+    pub fn render_text(&self) -> String {
+        let mut out = format!(
+            "Context: {} tokens across {} messages (estimator: {})\n",
+            self.total_tokens,
+            self.messages.len(),
+            self.estimator
+        );
+
+        for (title, groups) in [
+            ("By section", &self.by_section),
+            ("By role", &self.by_role),
+            ("By provenance", &self.by_provenance),
+        ] {
+            out.push_str(&format!("\n{}:\n", title));
+            for group in groups {
+                out.push_str(&format!(
+                    "  {:<24} {} {:>7} tokens {:>5.1}%\n",
+                    group.key,
+                    bar(group.share),
+                    group.tokens,
+                    group.share * 100.0
+                ));
+            }
+        }
+
+        out.push_str("\nHeaviest messages:\n");
+        for msg in &self.top_messages {
+            out.push_str(&format!(
+                "  #{:<4} {:<9} {} {:>7} tokens {:>5.1}%  {}\n",
+                msg.index,
+                role_name(&msg.role),
+                bar(msg.share),
+                msg.tokens,
+                msg.share * 100.0,
+                msg.preview
+            ));
+        }
+
+        out
+    }
+
+    /// Render the report as Markdown tables, heaviest entries first
+    pub fn render_markdown(&self) -> String {
+        let mut out = format!(
+            "## Context analysis\n\n**{} tokens** across {} messages (estimator: `{}`)\n",
+            self.total_tokens,
+            self.messages.len(),
+            self.estimator
+        );
+
+        for (title, groups) in [
+            ("By section", &self.by_section),
+            ("By role", &self.by_role),
+            ("By provenance", &self.by_provenance),
+        ] {
+            out.push_str(&format!(
+                "\n### {}\n\n| Group | Messages | Tokens | Share | |\n|---|---:|---:|---:|---|\n",
+                title
+            ));
+            for group in groups {
+                out.push_str(&format!(
+                    "| {} | {} | {} | {:.1}% | `{}` |\n",
+                    group.key,
+                    group.messages,
+                    group.tokens,
+                    group.share * 100.0,
+                    bar(group.share)
+                ));
+            }
+        }
+
+        out.push_str(
+            "\n### Heaviest messages\n\n| # | Role | Tokens | Share | Preview |\n|---:|---|---:|---:|---|\n",
+        );
+        for msg in &self.top_messages {
+            out.push_str(&format!(
+                "| {} | {} | {} | {:.1}% | {} |\n",
+                msg.index,
+                role_name(&msg.role),
+                msg.tokens,
+                msg.share * 100.0,
+                msg.preview.replace('|', "\\|")
+            ));
+        }
+
+        out
+    }
+}
+
+/// Opt-in diagnostic: emit a context report once utilization crosses a threshold
+///
+/// Attach with `WorkflowBuilder::with_context_diagnostics`. After each step the
+/// runtime analyzes the workflow context and, when the history uses at least
+/// `threshold` of the input budget, emits a `System` progress event with
+/// component id `system:context_analysis` carrying the report. The event fires
+/// once per crossing; dropping back below the threshold (e.g. after pruning)
+/// re-arms it.
+#[derive(Clone)]
+pub struct ContextDiagnostics {
+    /// Utilization (0.0 - 1.0) of `max_input_tokens` that triggers the event
+    pub threshold: f64,
+    pub estimator: Arc<dyn TokenEstimator>,
+}
+
+impl ContextDiagnostics {
+    pub fn new(threshold: f64, estimator: Arc<dyn TokenEstimator>) -> Self {
+        Self {
+            threshold,
+            estimator,
+        }
+    }
+}
+
+impl std::fmt::Debug for ContextDiagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContextDiagnostics")
+            .field("threshold", &self.threshold)
+            .field("estimator", &self.estimator.name())
+            .finish()
+    }
+}
+
+fn provenance_key(msg: &ChatMessage, tool_names: &HashMap<&str, &str>) -> String {
+    if let Some(call_id) = msg.tool_call_id.as_deref() {
+        let name = tool_names.get(call_id).copied().unwrap_or(call_id);
+        return format!("tool:{}", name);
+    }
+    match msg.agent_id.as_deref() {
+        Some(agent) => format!("agent:{}", agent),
+        None => "unattributed".to_string(),
+    }
+}
+
+fn group_by<F>(messages: &[MessageTokens], total: usize, key_fn: F) -> Vec<TokenGroup>
+where
+    F: Fn(&MessageTokens) -> String,
+{
+    let mut groups: Vec<TokenGroup> = Vec::new();
+    for msg in messages {
+        let key = key_fn(msg);
+        match groups.iter_mut().find(|g| g.key == key) {
+            Some(group) => {
+                group.messages += 1;
+                group.tokens += msg.tokens;
+            }
+            None => groups.push(TokenGroup {
+                key,
+                messages: 1,
+                tokens: msg.tokens,
+                share: 0.0,
+            }),
+        }
+    }
+    for group in &mut groups {
+        group.share = share_of(group.tokens, total);
+    }
+    // Stable sort keeps first-seen order for ties
+    groups.sort_by(|a, b| b.tokens.cmp(&a.tokens));
+    groups
+}
+
+fn share_of(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64
+    }
+}
+
+fn preview(content: &str) -> String {
+    let flat = content.split_whitespace().collect::<Vec<_>>().join(" ");
+    if flat.chars().count() > PREVIEW_CHARS {
+        let truncated: String = flat.chars().take(PREVIEW_CHARS).collect();
+        format!("{}…", truncated)
+    } else {
+        flat
+    }
+}
+
+fn bar(share: f64) -> String {
+    let filled = ((share * BAR_WIDTH as f64).round() as usize).min(BAR_WIDTH);
+    format!("{}{}", "█".repeat(filled), "░".repeat(BAR_WIDTH - filled))
+}
+
+fn role_name(role: &Role) -> &'static str {
+    match role {
+        Role::System => "system",
+        Role::User => "user",
+        Role::Assistant => "assistant",
+        Role::Tool => "tool",
+    }
+}
+
+fn section_name(section: ContextSection) -> &'static str {
+    match section {
+        ContextSection::System => "system",
+        ContextSection::Tools => "tools",
+        ContextSection::History => "history",
+        ContextSection::LatestTurn => "latest_turn",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::types::{FunctionCall, ToolCall};
+
+    /// Counts one token per character so the math is easy to pin
+    struct CharEstimator;
+
+    impl TokenEstimator for CharEstimator {
+        fn estimate_message(&self, message: &ChatMessage) -> usize {
+            message.content.text().len()
+        }
+
+        fn name(&self) -> &str {
+            "chars"
+        }
+    }
+
+    fn call(id: &str, name: &str) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            r#type: "function".to_string(),
+            function: FunctionCall {
+                name: name.to_string(),
+                arguments: "{}".to_string(),
+            },
+        }
+    }
+
+    fn fixture() -> Vec<ChatMessage> {
+        vec![
+            ChatMessage::system("s".repeat(10)),
+            ChatMessage::user("u".repeat(5)),
+            ChatMessage::assistant("a".repeat(20)).with_provenance("researcher", "wf"),
+            ChatMessage::user("q".repeat(4)),
+            ChatMessage::assistant_with_tool_calls("c".repeat(2), vec![call("call_0", "search")]),
+            ChatMessage::tool_result("call_0", "r".repeat(50)),
+            ChatMessage::assistant("f".repeat(9)).with_provenance("researcher", "wf"),
+        ]
+    }
+
+    #[test]
+    fn test_grouping_math() {
+        let report = analyze_context(&fixture(), &CharEstimator);
+        assert_eq!(report.total_tokens, 100);
+        assert_eq!(report.estimator, "chars");
+
+        assert_eq!(report.section_tokens(ContextSection::System), 10);
+        assert_eq!(report.section_tokens(ContextSection::Tools), 52);
+        assert_eq!(report.section_tokens(ContextSection::History), 25);
+        assert_eq!(report.section_tokens(ContextSection::LatestTurn), 13);
+
+        let tool_role = report.by_role.iter().find(|g| g.key == "tool").unwrap();
+        assert_eq!(tool_role.tokens, 50);
+        assert!((tool_role.share - 0.5).abs() < f64::EPSILON);
+        // Heaviest group first
+        assert_eq!(report.by_role[0].key, "tool");
+
+        let agent = report
+            .by_provenance
+            .iter()
+            .find(|g| g.key == "agent:researcher")
+            .unwrap();
+        assert_eq!(agent.messages, 2);
+        assert_eq!(agent.tokens, 29);
+        assert!(report.by_provenance.iter().any(|g| g.key == "tool:search"));
+    }
+
+    #[test]
+    fn test_top_n_selection() {
+        let report = analyze_context(&fixture(), &CharEstimator);
+        let indices: Vec<usize> = report.top_messages.iter().map(|m| m.index).collect();
+        assert_eq!(indices, vec![5, 2, 0, 6, 1]);
+
+        let report = analyze_context_top_n(&fixture(), &CharEstimator, 2);
+        assert_eq!(report.top_messages.len(), 2);
+        assert_eq!(report.top_messages[0].tokens, 50);
+    }
+
+    #[test]
+    fn test_render_sorted_by_weight() {
+        let report = analyze_context(&fixture(), &CharEstimator);
+        let text = report.render_text();
+        let tools = text.find("tools").unwrap();
+        let system = text.find("  system").unwrap();
+        assert!(tools < system);
+        assert!(text.contains("100 tokens across 7 messages"));
+
+        let md = report.render_markdown();
+        assert!(md.contains("| tool:search | 1 | 50 | 50.0% |"));
+    }
+
+    #[test]
+    fn test_empty_history() {
+        let report = analyze_context(&[], &SimpleTokenEstimator);
+        assert_eq!(report.total_tokens, 0);
+        assert!(report.top_messages.is_empty());
+        assert_eq!(report.utilization(1000), 0.0);
+    }
+}
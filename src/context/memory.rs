@@ -0,0 +1,173 @@
+//! Tools giving agents the workflow's memory scratchpad.
+//!
+//! `AgentStep` adds both to an agent's tools whenever the workflow has a
+//! context, so agents in later steps can read what earlier ones stored.
+//! An agent's own tools win over these if the names clash.
+
+use super::WorkflowContext;
+use crate::tools::registry::{Tool, ToolRegistry};
+use crate::types::{ToolError, ToolExecutionResult, ToolResult};
+use async_trait::async_trait;
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+fn key_param(params: &HashMap<String, JsonValue>) -> Result<&str, ToolError> {
+    params
+        .get("key")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| ToolError::InvalidParameters("missing 'key' parameter".into()))
+}
+
+/// Reads a value from the workflow's memory
+pub struct MemoryGetTool {
+    context: Arc<RwLock<WorkflowContext>>,
+}
+
+impl MemoryGetTool {
+    pub fn new(context: Arc<RwLock<WorkflowContext>>) -> Self {
+        Self { context }
+    }
+}
+
+#[async_trait]
+impl Tool for MemoryGetTool {
+    fn name(&self) -> &str {
+        "memory_get"
+    }
+
+    fn description(&self) -> &str {
+        "Reads a value that this or an earlier step stored with memory_set"
+    }
+
+    fn input_schema(&self) -> JsonValue {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "key": {
+                    "type": "string",
+                    "description": "The key the value was stored under"
+                }
+            },
+            "required": ["key"]
+        })
+    }
+
+    async fn execute(&self, params: HashMap<String, JsonValue>) -> ToolExecutionResult {
+        let start = std::time::Instant::now();
+        let key = key_param(&params)?;
+
+        let context = self.context.read().unwrap();
+        let output = match context.memory_get(key) {
+            Some(value) => serde_json::json!({ "key": key, "found": true, "value": value }),
+            None => serde_json::json!({
+                "key": key,
+                "found": false,
+                "keys": sorted_keys(&context),
+            }),
+        };
+
+        Ok(ToolResult::success(
+            output,
+            start.elapsed().as_secs_f64() * 1000.0,
+        ))
+    }
+
+    fn side_effecting(&self) -> bool {
+        false
+    }
+}
+
+/// Stores a value in the workflow's memory, or appends it to a list
+pub struct MemorySetTool {
+    context: Arc<RwLock<WorkflowContext>>,
+}
+
+impl MemorySetTool {
+    pub fn new(context: Arc<RwLock<WorkflowContext>>) -> Self {
+        Self { context }
+    }
+}
+
+#[async_trait]
+impl Tool for MemorySetTool {
+    fn name(&self) -> &str {
+        "memory_set"
+    }
+
+    fn description(&self) -> &str {
+        "Stores a value for later steps to read with memory_get"
+    }
+
+    fn input_schema(&self) -> JsonValue {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "key": {
+                    "type": "string",
+                    "description": "Where to store the value"
+                },
+                "value": {
+                    "description": "Any JSON value"
+                },
+                "append": {
+                    "type": "boolean",
+                    "description": "Add the value to the list under the key instead of replacing it"
+                }
+            },
+            "required": ["key", "value"]
+        })
+    }
+
+    async fn execute(&self, params: HashMap<String, JsonValue>) -> ToolExecutionResult {
+        let start = std::time::Instant::now();
+        let key = key_param(&params)?;
+        let value = params
+            .get("value")
+            .cloned()
+            .ok_or_else(|| ToolError::InvalidParameters("missing 'value' parameter".into()))?;
+        let append = params
+            .get("append")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        // One guard for the whole update, so parallel branches can't
+        // interleave inside it
+        let mut context = self.context.write().unwrap();
+        let output = if append {
+            let length = context.memory_append(key, value);
+            serde_json::json!({ "key": key, "stored": true, "length": length })
+        } else {
+            let replaced = context.memory_set(key, value).is_some();
+            serde_json::json!({ "key": key, "stored": true, "replaced": replaced })
+        };
+
+        Ok(ToolResult::success(
+            output,
+            start.elapsed().as_secs_f64() * 1000.0,
+        ))
+    }
+}
+
+fn sorted_keys(context: &WorkflowContext) -> Vec<&str> {
+    let mut keys: Vec<&str> = context.memory.keys().map(String::as_str).collect();
+    keys.sort_unstable();
+    keys
+}
+
+/// `tools` plus the memory tools for `context`, tracking changes to
+/// `tools`
+pub(crate) fn with_memory_tools(
+    tools: Option<&Arc<ToolRegistry>>,
+    context: &Arc<RwLock<WorkflowContext>>,
+) -> ToolRegistry {
+    let mut registry = ToolRegistry::new();
+    registry
+        .register(MemoryGetTool::new(context.clone()))
+        .register(MemorySetTool::new(context.clone()));
+    match tools {
+        // Tools registered with the agent mid-run show up in the view too
+        Some(tools) => registry.beneath(tools.clone()),
+        None => registry,
+    }
+}
@@ -1,16 +1,42 @@
-use crate::llm::types::ChatMessage;
+use crate::limits::{ConversationLimits, LimitEvent, LimitExceeded, LimitStats};
+use crate::llm::types::{ChatMessage, DEFAULT_IMAGE_TOKENS};
+use crate::persist::{PersistError, PersistFormat};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-
+use snapshot::{Commit, SnapshotPublisher};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+pub mod analysis;
+pub mod memory;
+pub mod snapshot;
+pub mod store;
 pub mod strategies;
 
+#[cfg(feature = "tiktoken")]
+pub use crate::llm::tokens::{Encoding, TiktokenCounter};
+pub use crate::llm::tokens::{HeuristicCounter, TokenCounter};
+pub use analysis::{
+    analyze_context, ContextDiagnostics, ContextReport, ContextSection, SimpleTokenEstimator,
+    TokenEstimator,
+};
+pub use memory::{MemoryGetTool, MemorySetTool};
+pub use snapshot::{ContextMonitor, ContextSnapshot};
+#[cfg(feature = "sqlite")]
+pub use store::SqliteContextStore;
+pub use store::{
+    ContextRecord, ContextStore, ContextStoreError, FileContextStore, MemoryContextStore,
+    StoredContext,
+};
 pub use strategies::{
-    MessageTypeManager, SlidingWindowManager, SummarizationManager, TokenBudgetManager,
+    CompositeContextManager, MessageTypeManager, SlidingWindowManager, SummarizationManager,
+    TokenBudgetManager,
 };
 
 /// Central workflow context that manages conversation history across steps
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "CheckpointedContext")]
 pub struct WorkflowContext {
     /// Conversation history shared across workflow steps
     pub chat_history: Vec<ChatMessage>,
@@ -23,6 +49,92 @@ pub struct WorkflowContext {
 
     /// Input to output token ratio (e.g., 3.0 means 3:1 ratio)
     pub input_output_ratio: f64,
+
+    /// Caps on turns and messages, checkpointed with the history
+    pub limits: ConversationLimits,
+
+    /// Every time a cap was hit over the life of this context
+    pub limit_stats: LimitStats,
+
+    /// Scratchpad shared by every step, checkpointed with the history.
+    /// Agents reach it through the `memory_get` and `memory_set` tools.
+    ///
+    /// The context sits behind one `RwLock` for the whole run, so a write
+    /// can't interleave with another; but a read followed by a write under
+    /// separate guards can lose a concurrent update. Change a value in place
+    /// with one `write()` guard, or use
+    /// [`memory_append`](Self::memory_append). A parallel step's branches
+    /// each write to their own copy, merged when they finish.
+    pub memory: HashMap<String, serde_json::Value>,
+
+    /// System prompts of the agents that wrote to the history, in the order
+    /// they first ran. The history holds only user, assistant and tool
+    /// turns; agent steps hand these to later agents, which drop, annotate
+    /// or keep them per their
+    /// [`SystemPromptPolicy`](crate::agent::SystemPromptPolicy).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub system_prompts: Vec<AgentSystemPrompt>,
+
+    #[serde(skip)]
+    snapshots: SnapshotPublisher,
+
+    /// Prunes the history before each agent step; not checkpointed
+    #[serde(skip)]
+    manager: AttachedManager,
+}
+
+/// An agent's system prompt, kept beside the shared history
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentSystemPrompt {
+    pub agent: String,
+    pub prompt: String,
+}
+
+/// The checkpointed fields of a [`WorkflowContext`]; loading them publishes
+/// the context's first snapshot
+#[derive(Deserialize)]
+struct CheckpointedContext {
+    chat_history: Vec<ChatMessage>,
+    metadata: WorkflowMetadata,
+    max_context_tokens: usize,
+    input_output_ratio: f64,
+    #[serde(default)]
+    limits: ConversationLimits,
+    #[serde(default)]
+    limit_stats: LimitStats,
+    #[serde(default)]
+    memory: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    system_prompts: Vec<AgentSystemPrompt>,
+}
+
+impl From<CheckpointedContext> for WorkflowContext {
+    fn from(stored: CheckpointedContext) -> Self {
+        Self {
+            chat_history: stored.chat_history,
+            metadata: stored.metadata,
+            max_context_tokens: stored.max_context_tokens,
+            input_output_ratio: stored.input_output_ratio,
+            limits: stored.limits,
+            limit_stats: stored.limit_stats,
+            memory: stored.memory,
+            system_prompts: stored.system_prompts,
+            snapshots: SnapshotPublisher::unpublished(),
+            manager: AttachedManager::default(),
+        }
+        .published()
+    }
+}
+
+#[derive(Clone, Default)]
+struct AttachedManager(Option<Arc<dyn ContextManager>>);
+
+impl std::fmt::Debug for AttachedManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AttachedManager")
+            .field(&self.0.as_ref().map(|m| m.name()))
+            .finish()
+    }
 }
 
 impl WorkflowContext {
@@ -33,7 +145,14 @@ impl WorkflowContext {
             metadata: WorkflowMetadata::default(),
             max_context_tokens: 128_000, // Default to 128k
             input_output_ratio: 4.0,     // Default 4:1 ratio
+            limits: ConversationLimits::default(),
+            limit_stats: LimitStats::default(),
+            memory: HashMap::new(),
+            system_prompts: Vec::new(),
+            snapshots: SnapshotPublisher::unpublished(),
+            manager: AttachedManager::default(),
         }
+        .published()
     }
 
     /// Create a workflow context with specific token limits
@@ -43,7 +162,20 @@ impl WorkflowContext {
             metadata: WorkflowMetadata::default(),
             max_context_tokens: max_tokens,
             input_output_ratio,
+            limits: ConversationLimits::default(),
+            limit_stats: LimitStats::default(),
+            memory: HashMap::new(),
+            system_prompts: Vec::new(),
+            snapshots: SnapshotPublisher::unpublished(),
+            manager: AttachedManager::default(),
         }
+        .published()
+    }
+
+    /// Publish the first snapshot of a new or loaded context
+    fn published(self) -> Self {
+        self.snapshots.reset(&self);
+        self
     }
 
     /// Calculate the maximum tokens available for input
@@ -62,16 +194,69 @@ impl WorkflowContext {
         (total / (ratio + 1.0)) as usize
     }
 
-    /// Add messages to the chat history
+    /// Set caps on turns and messages, checked by [`try_append_messages`](Self::try_append_messages)
+    /// and [`try_set_history`](Self::try_set_history)
+    pub fn with_limits(mut self, limits: ConversationLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Prune the history with `manager` before each agent step (see
+    /// [`prune_history`])
+    pub fn set_manager(&mut self, manager: Arc<dyn ContextManager>) {
+        self.manager = AttachedManager(Some(manager));
+    }
+
+    pub fn manager(&self) -> Option<&Arc<dyn ContextManager>> {
+        self.manager.0.as_ref()
+    }
+
+    /// Add messages to the chat history without checking [`limits`](Self::limits)
     pub fn append_messages(&mut self, messages: Vec<ChatMessage>) {
+        let start = self.chat_history.len();
         self.chat_history.extend(messages);
         self.metadata.last_updated = Utc::now();
+        self.snapshots
+            .commit(self, Commit::Appended(&self.chat_history[start..]));
     }
 
-    /// Replace the entire chat history
+    /// Replace the entire chat history without checking [`limits`](Self::limits)
     pub fn set_history(&mut self, history: Vec<ChatMessage>) {
         self.chat_history = history;
         self.metadata.last_updated = Utc::now();
+        self.snapshots.commit(self, Commit::Replaced);
+    }
+
+    /// Add messages, applying the turn and total-message caps. On rejection
+    /// the history is unchanged. Every cap hit is recorded in
+    /// [`limit_stats`](Self::limit_stats).
+    pub fn try_append_messages(
+        &mut self,
+        messages: Vec<ChatMessage>,
+    ) -> Result<Vec<LimitEvent>, LimitExceeded> {
+        let mut history = self.chat_history.clone();
+        history.extend(messages);
+        self.try_set_history(history)
+    }
+
+    /// Replace the history, applying the turn and total-message caps
+    pub fn try_set_history(
+        &mut self,
+        mut history: Vec<ChatMessage>,
+    ) -> Result<Vec<LimitEvent>, LimitExceeded> {
+        match self.limits.enforce(&mut history) {
+            Ok(events) => {
+                for event in &events {
+                    self.limit_stats.record(event.clone());
+                }
+                self.set_history(history);
+                Ok(events)
+            }
+            Err(exceeded) => {
+                self.limit_stats.record(exceeded.event());
+                Err(exceeded)
+            }
+        }
     }
 
     /// Get the current chat history
@@ -79,6 +264,153 @@ impl WorkflowContext {
         &self.chat_history
     }
 
+    /// Remember `agent`'s system prompt for later agents, replacing the one
+    /// it had before
+    pub fn record_system_prompt(&mut self, agent: &str, prompt: &str) {
+        match self.system_prompts.iter_mut().find(|p| p.agent == agent) {
+            Some(existing) => existing.prompt = prompt.to_string(),
+            None => self.system_prompts.push(AgentSystemPrompt {
+                agent: agent.to_string(),
+                prompt: prompt.to_string(),
+            }),
+        }
+    }
+
+    /// The other agents' system prompts, as system messages attributed to
+    /// them, for `agent` to read with the history
+    pub fn system_prompts_for(&self, agent: &str) -> Vec<ChatMessage> {
+        self.system_prompts
+            .iter()
+            .filter(|p| p.agent != agent)
+            .map(|p| {
+                ChatMessage::system(p.prompt.as_str())
+                    .with_provenance(&p.agent, &self.metadata.workflow_id)
+            })
+            .collect()
+    }
+
+    /// The scratchpad value stored under `key`
+    pub fn memory_get(&self, key: &str) -> Option<&serde_json::Value> {
+        self.memory.get(key)
+    }
+
+    /// Store `value` under `key`, returning the value it replaced
+    pub fn memory_set(
+        &mut self,
+        key: impl Into<String>,
+        value: serde_json::Value,
+    ) -> Option<serde_json::Value> {
+        self.metadata.last_updated = Utc::now();
+        self.memory.insert(key.into(), value)
+    }
+
+    /// Add `value` to the list under `key`, returning the list's new length.
+    /// A missing key starts a list; a value that isn't a list becomes the
+    /// list's first element.
+    pub fn memory_append(&mut self, key: impl Into<String>, value: serde_json::Value) -> usize {
+        self.metadata.last_updated = Utc::now();
+        let entry = self
+            .memory
+            .entry(key.into())
+            .or_insert_with(|| serde_json::Value::Array(Vec::new()));
+        match entry {
+            serde_json::Value::Array(list) => {
+                list.push(value);
+                list.len()
+            }
+            other => {
+                *other = serde_json::Value::Array(vec![other.take(), value]);
+                2
+            }
+        }
+    }
+
+    /// The snapshot published by the last commit, read from the snapshot
+    /// channel without looking at the context itself. Monitors running
+    /// alongside a workflow should keep a [`monitor`](Self::monitor), which
+    /// doesn't need the context at all.
+    pub fn latest_snapshot(&self) -> Arc<ContextSnapshot> {
+        self.snapshots.latest()
+    }
+
+    /// A handle that reads snapshots without locking this context
+    pub fn monitor(&self) -> ContextMonitor {
+        self.snapshots.monitor()
+    }
+
+    /// Publish a snapshot after editing [`chat_history`](Self::chat_history)
+    /// directly (the mutation methods publish on their own)
+    pub fn refresh_snapshot(&mut self) {
+        self.snapshots.commit(self, Commit::Replaced);
+    }
+
+    /// Attribute the current history's tokens per message, role and provenance
+    pub fn analyze(&self, estimator: &dyn TokenEstimator) -> ContextReport {
+        analyze_context(&self.chat_history, estimator)
+    }
+
+    /// A copy for one branch of a parallel step to write to, until
+    /// [`merge_branches`](Self::merge_branches) folds it back in
+    pub(crate) fn branch(&self) -> Self {
+        Self {
+            limit_stats: LimitStats::default(),
+            ..self.clone()
+        }
+    }
+
+    /// Fold in the [`branch`](Self::branch)es of this context, which hasn't
+    /// changed since they were taken, in branch order: each branch's new
+    /// turns are appended to the history after the previous branch's (a
+    /// branch that rewrote the history it started from, e.g. by pruning,
+    /// replaces it), and its memory writes are applied, with lists it
+    /// appended to extended rather than replaced. The merged history is
+    /// held to the caps like any other.
+    pub(crate) fn merge_branches(
+        &mut self,
+        branches: Vec<WorkflowContext>,
+    ) -> Result<Vec<LimitEvent>, LimitExceeded> {
+        let mut history = self.chat_history.clone();
+        let mut memory = self.memory.clone();
+        for branch in branches {
+            match branch
+                .chat_history
+                .strip_prefix(self.chat_history.as_slice())
+            {
+                Some(turns) => history.extend_from_slice(turns),
+                None => history = branch.chat_history,
+            }
+            for (key, value) in branch.memory {
+                let before = self.memory.get(&key);
+                if before == Some(&value) {
+                    continue;
+                }
+                let appended = match (before, &value) {
+                    (None, serde_json::Value::Array(after)) => Some(&after[..]),
+                    (Some(serde_json::Value::Array(before)), serde_json::Value::Array(after)) => {
+                        after.strip_prefix(before.as_slice())
+                    }
+                    _ => None,
+                };
+                match (appended, memory.get_mut(&key)) {
+                    (Some(items), Some(serde_json::Value::Array(list))) => {
+                        list.extend_from_slice(items)
+                    }
+                    _ => {
+                        memory.insert(key, value);
+                    }
+                }
+            }
+            for prompt in branch.system_prompts {
+                self.record_system_prompt(&prompt.agent, &prompt.prompt);
+            }
+            for event in branch.limit_stats.events {
+                self.limit_stats.record(event);
+            }
+        }
+        self.memory = memory;
+        self.try_set_history(history)
+    }
+
     /// Create a fork of this context for sub-workflows (isolated copy)
     pub fn fork(&self) -> Self {
         Self {
@@ -91,7 +423,25 @@ impl WorkflowContext {
             },
             max_context_tokens: self.max_context_tokens,
             input_output_ratio: self.input_output_ratio,
+            limits: self.limits.clone(),
+            limit_stats: LimitStats::default(),
+            memory: self.memory.clone(),
+            system_prompts: self.system_prompts.clone(),
+            snapshots: SnapshotPublisher::unpublished(),
+            manager: self.manager.clone(),
         }
+        .published()
+    }
+
+    /// Serialize this context as a checkpoint in `format`
+    pub fn export(&self, format: PersistFormat) -> Result<Vec<u8>, PersistError> {
+        format.encode(self)
+    }
+
+    /// Load a checkpoint written by [`export`](Self::export) in any format,
+    /// or as plain JSON
+    pub fn import(bytes: &[u8]) -> Result<Self, PersistError> {
+        crate::persist::decode(bytes)
     }
 }
 
@@ -140,6 +490,114 @@ pub trait ContextManager: Send + Sync {
 
     /// Get the name of this strategy
     fn name(&self) -> &str;
+
+    /// What the latest `prune` did stage by stage, for managers chaining
+    /// several strategies
+    fn last_report(&self) -> Option<PruneReport> {
+        None
+    }
+}
+
+/// What a context manager did to a history
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PruneOutcome {
+    /// `ContextManager::name` of the strategy
+    pub strategy: String,
+    pub messages_before: usize,
+    pub messages_after: usize,
+    /// Estimated, by the manager, before pruning
+    pub tokens_before: usize,
+    pub tokens_freed: usize,
+
+    /// What each strategy of a chain did, per
+    /// [`ContextManager::last_report`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stages: Vec<PruneStage>,
+}
+
+/// What one strategy of a [`CompositeContextManager`] did
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PruneStage {
+    /// `ContextManager::name` of the strategy
+    pub strategy: String,
+    /// Whether it still asked to prune; a stage that didn't was skipped
+    pub ran: bool,
+    pub messages_before: usize,
+    pub messages_after: usize,
+    /// Estimated, by the chain, before this stage
+    pub tokens_before: usize,
+    pub tokens_freed: usize,
+}
+
+/// What each strategy of a chain did in one prune, in order
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PruneReport {
+    pub stages: Vec<PruneStage>,
+}
+
+impl PruneReport {
+    /// Tokens freed by all stages together
+    pub fn tokens_freed(&self) -> usize {
+        self.stages.iter().map(|stage| stage.tokens_freed).sum()
+    }
+
+    /// The stage run by strategy `name`
+    pub fn stage(&self, name: &str) -> Option<&PruneStage> {
+        self.stages.iter().find(|stage| stage.strategy == name)
+    }
+}
+
+/// Let `manager` prune `history` if it asks to, returning the pruned
+/// history and what it did
+pub async fn prune_messages(
+    manager: &dyn ContextManager,
+    history: &[ChatMessage],
+) -> Result<Option<(Vec<ChatMessage>, PruneOutcome)>, ContextError> {
+    let tokens_before = manager.estimate_tokens(history);
+    if !manager.should_prune(history, tokens_before).await {
+        return Ok(None);
+    }
+    let (pruned, tokens_freed) = manager.prune(history.to_vec()).await?;
+    let outcome = PruneOutcome {
+        strategy: manager.name().to_string(),
+        messages_before: history.len(),
+        messages_after: pruned.len(),
+        tokens_before,
+        tokens_freed,
+        stages: manager
+            .last_report()
+            .map(|report| report.stages)
+            .unwrap_or_default(),
+    };
+    Ok(Some((pruned, outcome)))
+}
+
+/// Let `context`'s manager prune the history if it asks to, returning what
+/// it did
+///
+/// The lock isn't held while the manager runs, since summarizing strategies
+/// call an LLM. If the history changed in the meantime, e.g. by a parallel
+/// branch, the pruned copy is discarded.
+pub async fn prune_history(
+    context: &RwLock<WorkflowContext>,
+) -> Result<Option<PruneOutcome>, ContextError> {
+    let (manager, history) = {
+        let context = context.read().unwrap();
+        match context.manager() {
+            Some(manager) => (manager.clone(), context.chat_history.clone()),
+            None => return Ok(None),
+        }
+    };
+
+    let Some((pruned, outcome)) = prune_messages(manager.as_ref(), &history).await? else {
+        return Ok(None);
+    };
+    let mut context = context.write().unwrap();
+    if context.chat_history != history {
+        return Ok(None);
+    }
+    context.set_history(pruned);
+    Ok(Some(outcome))
 }
 
 /// Errors that can occur during context management
@@ -191,7 +649,9 @@ impl ContextManager for NoOpManager {
         // Simple approximation: ~4 characters per token
         messages
             .iter()
-            .map(|msg| msg.content.len() / 4)
+            .map(|msg| {
+                msg.content.text().len() / 4 + msg.content.image_count() * DEFAULT_IMAGE_TOKENS
+            })
             .sum::<usize>()
     }
 
@@ -269,6 +729,22 @@ mod tests {
         assert!(forked.metadata.workflow_id.contains("fork"));
     }
 
+    #[test]
+    fn test_memory_append_builds_a_list() {
+        let mut ctx = WorkflowContext::new();
+        assert_eq!(ctx.memory_append("sources", serde_json::json!("wiki")), 1);
+        assert_eq!(ctx.memory_append("sources", serde_json::json!("atlas")), 2);
+        assert_eq!(
+            ctx.memory_get("sources"),
+            Some(&serde_json::json!(["wiki", "atlas"]))
+        );
+
+        // A scalar becomes the first element
+        ctx.memory_set("answer", serde_json::json!(42));
+        assert_eq!(ctx.memory_append("answer", serde_json::json!(43)), 2);
+        assert_eq!(ctx.memory_get("answer"), Some(&serde_json::json!([42, 43])));
+    }
+
     #[tokio::test]
     async fn test_noop_manager_never_prunes() {
         let manager = NoOpManager::new();
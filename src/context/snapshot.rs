@@ -0,0 +1,334 @@
+//! Lock-free views of a [`WorkflowContext`] for monitoring.
+//!
+//! A running workflow holds its context behind an `RwLock` that the executing
+//! agent needs for writes. Dashboards that read (and serialize) the history
+//! through that lock can stall the run. Instead, every mutation publishes an
+//! immutable [`ContextSnapshot`] on a watch channel; a [`ContextMonitor`]
+//! reads the latest one without touching the context lock at all.
+//!
+//! Snapshots share structure: the history is stored as a list of `Arc`
+//! segments, so an append only allocates the new messages and a history
+//! replacement reuses every segment that is still a prefix of the new history.
+
+use super::analysis::{SimpleTokenEstimator, TokenEstimator};
+use super::WorkflowContext;
+use crate::llm::types::ChatMessage;
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Serializer};
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Segments are merged into one once there are more than this many
+const MAX_SEGMENTS: usize = 32;
+
+/// An immutable, consistent view of a context at one commit
+#[derive(Debug, Clone, Serialize)]
+pub struct ContextSnapshot {
+    pub workflow_id: String,
+
+    /// Incremented on every commit to the context
+    pub version: u64,
+
+    #[serde(rename = "messages", serialize_with = "serialize_segments")]
+    segments: Vec<Segment>,
+
+    pub message_count: usize,
+    pub utilization: ContextUtilization,
+    pub stats: ContextLifetimeStats,
+}
+
+/// How full the context is relative to its input budget
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ContextUtilization {
+    /// Estimated with [`SimpleTokenEstimator`]
+    pub estimated_tokens: usize,
+    pub max_input_tokens: usize,
+    /// `estimated_tokens / max_input_tokens`
+    pub ratio: f64,
+}
+
+/// Counters over the life of the context
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ContextLifetimeStats {
+    pub messages_appended: u64,
+    pub history_replacements: u64,
+    pub limit_hits: usize,
+    pub created_at: DateTime<Utc>,
+    pub last_updated: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+struct Segment {
+    messages: Arc<[ChatMessage]>,
+    tokens: usize,
+}
+
+impl Segment {
+    fn new(messages: &[ChatMessage]) -> Self {
+        Self {
+            tokens: SimpleTokenEstimator.estimate(messages),
+            messages: Arc::from(messages),
+        }
+    }
+}
+
+fn serialize_segments<S: Serializer>(
+    segments: &[Segment],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(segments.iter().flat_map(|s| s.messages.iter()))
+}
+
+impl ContextSnapshot {
+    /// Iterate over the history without copying it
+    pub fn messages(&self) -> impl Iterator<Item = &ChatMessage> {
+        self.segments.iter().flat_map(|s| s.messages.iter())
+    }
+
+    /// Copy the history out
+    pub fn to_vec(&self) -> Vec<ChatMessage> {
+        self.messages().cloned().collect()
+    }
+
+    /// The shared segments backing the history, oldest first
+    pub fn segments(&self) -> impl Iterator<Item = &Arc<[ChatMessage]>> {
+        self.segments.iter().map(|s| &s.messages)
+    }
+
+    fn build(
+        ctx: &WorkflowContext,
+        version: u64,
+        segments: Vec<Segment>,
+        stats: ContextLifetimeStats,
+    ) -> Self {
+        let estimated_tokens = segments.iter().map(|s| s.tokens).sum();
+        let max_input_tokens = ctx.max_input_tokens();
+        Self {
+            workflow_id: ctx.metadata.workflow_id.clone(),
+            version,
+            message_count: segments.iter().map(|s| s.messages.len()).sum(),
+            utilization: ContextUtilization {
+                estimated_tokens,
+                max_input_tokens,
+                ratio: if max_input_tokens == 0 {
+                    0.0
+                } else {
+                    estimated_tokens as f64 / max_input_tokens as f64
+                },
+            },
+            stats: ContextLifetimeStats {
+                limit_hits: ctx.limit_stats.total(),
+                created_at: ctx.metadata.created_at,
+                last_updated: ctx.metadata.last_updated,
+                ..stats
+            },
+            segments,
+        }
+    }
+
+    fn full(ctx: &WorkflowContext, version: u64, stats: ContextLifetimeStats) -> Self {
+        let segments = if ctx.chat_history.is_empty() {
+            Vec::new()
+        } else {
+            vec![Segment::new(&ctx.chat_history)]
+        };
+        Self::build(ctx, version, segments, stats)
+    }
+}
+
+/// Read-only handle on a context's snapshots. Cheap to clone; never takes
+/// the context lock.
+#[derive(Debug, Clone)]
+pub struct ContextMonitor {
+    rx: watch::Receiver<Arc<ContextSnapshot>>,
+}
+
+impl ContextMonitor {
+    /// The most recently committed snapshot
+    pub fn latest(&self) -> Arc<ContextSnapshot> {
+        self.rx.borrow().clone()
+    }
+
+    /// A receiver notified on every commit (notifications coalesce if the
+    /// receiver falls behind; the value is always the latest snapshot)
+    pub fn subscribe(&self) -> watch::Receiver<Arc<ContextSnapshot>> {
+        self.rx.clone()
+    }
+}
+
+/// What a commit changed, so the snapshot can be updated incrementally
+pub(crate) enum Commit<'a> {
+    Appended(&'a [ChatMessage]),
+    Replaced,
+}
+
+/// Publishes snapshots for a context. The first is published when the
+/// context is created or loaded, so reading one never builds it.
+pub(crate) struct SnapshotPublisher {
+    tx: watch::Sender<Arc<ContextSnapshot>>,
+}
+
+impl SnapshotPublisher {
+    /// Publishes nothing useful until [`reset`](Self::reset)
+    pub(crate) fn unpublished() -> Self {
+        let now = Utc::now();
+        let empty = ContextSnapshot {
+            workflow_id: String::new(),
+            version: 0,
+            segments: Vec::new(),
+            message_count: 0,
+            utilization: ContextUtilization {
+                estimated_tokens: 0,
+                max_input_tokens: 0,
+                ratio: 0.0,
+            },
+            stats: ContextLifetimeStats {
+                messages_appended: 0,
+                history_replacements: 0,
+                limit_hits: 0,
+                created_at: now,
+                last_updated: now,
+            },
+        };
+        Self {
+            tx: watch::Sender::new(Arc::new(empty)),
+        }
+    }
+
+    /// Start over from a full snapshot of `ctx`
+    pub(crate) fn reset(&self, ctx: &WorkflowContext) {
+        let stats = ContextLifetimeStats {
+            messages_appended: ctx.chat_history.len() as u64,
+            history_replacements: 0,
+            limit_hits: 0,
+            created_at: ctx.metadata.created_at,
+            last_updated: ctx.metadata.last_updated,
+        };
+        self.tx
+            .send_replace(Arc::new(ContextSnapshot::full(ctx, 0, stats)));
+    }
+
+    pub(crate) fn latest(&self) -> Arc<ContextSnapshot> {
+        self.tx.borrow().clone()
+    }
+
+    pub(crate) fn monitor(&self) -> ContextMonitor {
+        ContextMonitor {
+            rx: self.tx.subscribe(),
+        }
+    }
+
+    /// Publish the state after a mutation of `ctx`
+    pub(crate) fn commit(&self, ctx: &WorkflowContext, change: Commit<'_>) {
+        let previous = self.latest();
+        let mut stats = previous.stats.clone();
+        let mut segments = match change {
+            Commit::Appended(messages) => {
+                stats.messages_appended += messages.len() as u64;
+                let mut segments = previous.segments.clone();
+                if !messages.is_empty() {
+                    segments.push(Segment::new(messages));
+                }
+                segments
+            }
+            Commit::Replaced => {
+                stats.history_replacements += 1;
+                let (mut segments, shared) = shared_prefix(&previous.segments, &ctx.chat_history);
+                let tail = &ctx.chat_history[shared..];
+                stats.messages_appended += tail.len() as u64;
+                if !tail.is_empty() {
+                    segments.push(Segment::new(tail));
+                }
+                segments
+            }
+        };
+        if segments.len() > MAX_SEGMENTS {
+            segments = vec![Segment::new(&ctx.chat_history)];
+        }
+        let snapshot = ContextSnapshot::build(ctx, previous.version + 1, segments, stats);
+        self.tx.send_replace(Arc::new(snapshot));
+    }
+}
+
+/// The leading segments of `segments` that `history` still starts with, and
+/// how many messages they cover
+fn shared_prefix(segments: &[Segment], history: &[ChatMessage]) -> (Vec<Segment>, usize) {
+    let mut kept = Vec::new();
+    let mut covered = 0;
+    for segment in segments {
+        let end = covered + segment.messages.len();
+        if end > history.len() || history[covered..end] != segment.messages[..] {
+            break;
+        }
+        kept.push(segment.clone());
+        covered = end;
+    }
+    (kept, covered)
+}
+
+impl Clone for SnapshotPublisher {
+    /// A cloned context is a separate context; it publishes its own
+    /// snapshots, starting from this one's latest
+    fn clone(&self) -> Self {
+        Self {
+            tx: watch::Sender::new(self.latest()),
+        }
+    }
+}
+
+impl std::fmt::Debug for SnapshotPublisher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SnapshotPublisher")
+            .field("version", &self.tx.borrow().version)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replacing_history_reuses_shared_segments() {
+        let mut ctx = WorkflowContext::new();
+        ctx.append_messages(vec![ChatMessage::system("sys"), ChatMessage::user("a")]);
+        let first = ctx.latest_snapshot();
+        assert_eq!(first.version, 1);
+
+        ctx.append_messages(vec![ChatMessage::assistant("b")]);
+        let mut history = ctx.history().to_vec();
+        history.push(ChatMessage::user("c"));
+        ctx.set_history(history);
+
+        let snapshot = ctx.latest_snapshot();
+        assert_eq!(snapshot.version, 3);
+        assert_eq!(snapshot.message_count, 4);
+        let segments: Vec<_> = snapshot.segments().collect();
+        assert_eq!(segments.len(), 3);
+        assert!(Arc::ptr_eq(segments[0], first.segments().next().unwrap()));
+        assert_eq!(snapshot.stats.history_replacements, 1);
+        assert_eq!(snapshot.stats.messages_appended, 4);
+
+        // A rewrite of the start shares nothing
+        ctx.set_history(vec![ChatMessage::system("other")]);
+        let snapshot = ctx.latest_snapshot();
+        assert_eq!(snapshot.segments().count(), 1);
+        assert_eq!(snapshot.to_vec(), ctx.history());
+    }
+
+    #[test]
+    fn test_segments_are_compacted() {
+        let mut ctx = WorkflowContext::new();
+        for i in 0..(MAX_SEGMENTS + 1) {
+            ctx.append_messages(vec![ChatMessage::user(format!("m{}", i))]);
+        }
+
+        let snapshot = ctx.latest_snapshot();
+        assert_eq!(snapshot.segments().count(), 1);
+        assert_eq!(snapshot.message_count, MAX_SEGMENTS + 1);
+        assert_eq!(
+            snapshot.utilization.estimated_tokens,
+            SimpleTokenEstimator.estimate(ctx.history())
+        );
+    }
+}
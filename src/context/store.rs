@@ -0,0 +1,496 @@
+//! Keeping a [`WorkflowContext`] between runs.
+//!
+//! A workflow built with
+//! [`with_context_store`](crate::workflow::WorkflowBuilder::with_context_store)
+//! loads the context saved under its key when it starts executing, and saves
+//! it again after every step that completes. Runs sharing a key continue
+//! one conversation.
+//!
+//! Every save bumps the key's version. [`ContextStore::save`] overwrites
+//! whatever is stored: the last write wins. [`ContextStore::save_versioned`]
+//! only writes over the version the caller read, and fails with
+//! [`ContextStoreError::VersionConflict`] if another writer got there first;
+//! the runtime saves this way, so two runs on one key can't silently clobber
+//! each other's conversation.
+//!
+//! [`MemoryContextStore`] suits tests and [`FileContextStore`] a single
+//! process; `SqliteContextStore` (the `sqlite` feature) keeps every context
+//! in one database file.
+
+use super::WorkflowContext;
+use crate::persist::PersistFormat;
+use crate::workflow::report::file_safe;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Why a context could not be saved or loaded
+#[derive(Debug, thiserror::Error)]
+pub enum ContextStoreError {
+    /// The stored version isn't the one the save expected to replace
+    #[error("context '{key}' is at version {found}, expected {expected}")]
+    VersionConflict {
+        key: String,
+        expected: u64,
+        found: u64,
+    },
+
+    #[error("context store I/O failed: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("corrupt stored context: {0}")]
+    Corrupt(String),
+
+    #[cfg(feature = "sqlite")]
+    #[error("context store database failed: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+/// What a store knows about a stored context, without loading it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContextRecord {
+    pub key: String,
+
+    /// Saves so far; the first save writes version 1
+    pub version: u64,
+
+    pub updated_at: DateTime<Utc>,
+    pub message_count: usize,
+
+    /// Estimated tokens in the chat history
+    pub token_estimate: usize,
+}
+
+impl ContextRecord {
+    fn new(key: &str, version: u64, context: &WorkflowContext) -> Self {
+        Self {
+            key: key.to_string(),
+            version,
+            updated_at: Utc::now(),
+            message_count: context.chat_history.len(),
+            token_estimate: context.latest_snapshot().utilization.estimated_tokens,
+        }
+    }
+}
+
+/// A stored context with its record
+#[derive(Debug, Clone)]
+pub struct StoredContext {
+    pub record: ContextRecord,
+    pub context: WorkflowContext,
+}
+
+/// Persistence for workflow contexts, keyed by name
+#[async_trait]
+pub trait ContextStore: Send + Sync {
+    /// Save `context` under `key` and return its new version. With
+    /// `expected_version`, fail with
+    /// [`VersionConflict`](ContextStoreError::VersionConflict) unless `key`
+    /// is stored at that version; `Some(0)` means not stored at all.
+    async fn save_versioned(
+        &self,
+        key: &str,
+        context: &WorkflowContext,
+        expected_version: Option<u64>,
+    ) -> Result<u64, ContextStoreError>;
+
+    async fn load(&self, key: &str) -> Result<Option<StoredContext>, ContextStoreError>;
+
+    /// Records of every stored context, by key
+    async fn list(&self) -> Result<Vec<ContextRecord>, ContextStoreError>;
+
+    /// Forget `key`; deleting a missing one is not an error
+    async fn delete(&self, key: &str) -> Result<(), ContextStoreError>;
+
+    /// Save `context` under `key` over whatever is stored
+    async fn save(&self, key: &str, context: &WorkflowContext) -> Result<u64, ContextStoreError> {
+        self.save_versioned(key, context, None).await
+    }
+}
+
+fn check_version(
+    key: &str,
+    stored: Option<u64>,
+    expected: Option<u64>,
+) -> Result<u64, ContextStoreError> {
+    let found = stored.unwrap_or(0);
+    match expected {
+        Some(expected) if expected != found => Err(ContextStoreError::VersionConflict {
+            key: key.to_string(),
+            expected,
+            found,
+        }),
+        _ => Ok(found + 1),
+    }
+}
+
+/// In-memory [`ContextStore`]
+#[derive(Debug, Default)]
+pub struct MemoryContextStore {
+    contexts: Mutex<HashMap<String, StoredContext>>,
+}
+
+impl MemoryContextStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.contexts.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl ContextStore for MemoryContextStore {
+    async fn save_versioned(
+        &self,
+        key: &str,
+        context: &WorkflowContext,
+        expected_version: Option<u64>,
+    ) -> Result<u64, ContextStoreError> {
+        let mut contexts = self.contexts.lock().unwrap();
+        let stored = contexts.get(key).map(|stored| stored.record.version);
+        let version = check_version(key, stored, expected_version)?;
+        contexts.insert(
+            key.to_string(),
+            StoredContext {
+                record: ContextRecord::new(key, version, context),
+                context: context.clone(),
+            },
+        );
+        Ok(version)
+    }
+
+    async fn load(&self, key: &str) -> Result<Option<StoredContext>, ContextStoreError> {
+        Ok(self.contexts.lock().unwrap().get(key).cloned())
+    }
+
+    async fn list(&self) -> Result<Vec<ContextRecord>, ContextStoreError> {
+        let mut records: Vec<ContextRecord> = self
+            .contexts
+            .lock()
+            .unwrap()
+            .values()
+            .map(|stored| stored.record.clone())
+            .collect();
+        records.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(records)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), ContextStoreError> {
+        self.contexts.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+/// [`ContextStore`] keeping each context in a directory, as a record file
+/// and a context file in `format`
+///
+/// Saves write temporary files and rename them over the old ones, so a
+/// crash mid-save leaves the previous context intact. Versions are checked
+/// under a lock held by this store, so writers sharing a directory should
+/// share the store.
+#[derive(Debug)]
+pub struct FileContextStore {
+    dir: PathBuf,
+    format: PersistFormat,
+    writes: tokio::sync::Mutex<()>,
+}
+
+impl FileContextStore {
+    /// Store contexts in `dir` as JSON; the directory is created on the
+    /// first save
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            format: PersistFormat::Json,
+            writes: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Write contexts in `format`; either format is read
+    pub fn with_format(mut self, format: PersistFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn record_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.record.json", file_safe(key)))
+    }
+
+    fn context_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.context", file_safe(key)))
+    }
+
+    async fn read_record(&self, path: &Path) -> Result<Option<ContextRecord>, ContextStoreError> {
+        match tokio::fs::read(path).await {
+            Ok(data) => serde_json::from_slice(&data)
+                .map(Some)
+                .map_err(|e| ContextStoreError::Corrupt(e.to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+async fn replace(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    tokio::fs::write(&temp, data).await?;
+    tokio::fs::rename(&temp, path).await
+}
+
+#[async_trait]
+impl ContextStore for FileContextStore {
+    async fn save_versioned(
+        &self,
+        key: &str,
+        context: &WorkflowContext,
+        expected_version: Option<u64>,
+    ) -> Result<u64, ContextStoreError> {
+        let data = context
+            .export(self.format)
+            .map_err(|e| ContextStoreError::Corrupt(e.to_string()))?;
+        let _write = self.writes.lock().await;
+        let record_path = self.record_path(key);
+        let stored = self.read_record(&record_path).await?;
+        let version = check_version(key, stored.map(|r| r.version), expected_version)?;
+        let record = serde_json::to_vec_pretty(&ContextRecord::new(key, version, context))
+            .map_err(|e| ContextStoreError::Corrupt(e.to_string()))?;
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        // The record goes last: a crash in between leaves the old version
+        // number, so the next versioned save is refused rather than lost
+        replace(&self.context_path(key), &data).await?;
+        replace(&record_path, &record).await?;
+        Ok(version)
+    }
+
+    async fn load(&self, key: &str) -> Result<Option<StoredContext>, ContextStoreError> {
+        let Some(record) = self.read_record(&self.record_path(key)).await? else {
+            return Ok(None);
+        };
+        let data = tokio::fs::read(self.context_path(key)).await?;
+        let context = WorkflowContext::import(&data)
+            .map_err(|e| ContextStoreError::Corrupt(e.to_string()))?;
+        Ok(Some(StoredContext { record, context }))
+    }
+
+    async fn list(&self) -> Result<Vec<ContextRecord>, ContextStoreError> {
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut records = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            if entry
+                .file_name()
+                .to_string_lossy()
+                .ends_with(".record.json")
+            {
+                records.extend(self.read_record(&entry.path()).await?);
+            }
+        }
+        records.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(records)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), ContextStoreError> {
+        let _write = self.writes.lock().await;
+        for path in [self.record_path(key), self.context_path(key)] {
+            match tokio::fs::remove_file(path).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteContextStore;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use super::*;
+    use rusqlite::types::Type;
+    use rusqlite::{params, Connection, OptionalExtension, Row, TransactionBehavior};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS contexts (
+        key TEXT PRIMARY KEY,
+        version INTEGER NOT NULL,
+        updated_at TEXT NOT NULL,
+        message_count INTEGER NOT NULL,
+        token_estimate INTEGER NOT NULL,
+        context BLOB NOT NULL
+    )";
+
+    const RECORD_COLUMNS: &str = "key, version, updated_at, message_count, token_estimate";
+
+    /// [`ContextStore`] keeping contexts in a SQLite database, one row per
+    /// key: the record's fields as columns beside the context in `format`
+    ///
+    /// Versions are checked inside an immediate transaction, so writers in
+    /// other processes sharing the database file are caught too.
+    #[derive(Debug, Clone)]
+    pub struct SqliteContextStore {
+        connection: Arc<Mutex<Connection>>,
+        format: PersistFormat,
+    }
+
+    impl SqliteContextStore {
+        /// Open the database at `path`, creating it and its table if needed;
+        /// contexts are stored as JSON
+        pub fn open(path: impl AsRef<Path>) -> Result<Self, ContextStoreError> {
+            Self::with_connection(Connection::open(path)?)
+        }
+
+        /// A database that lives as long as the store and its clones
+        pub fn open_in_memory() -> Result<Self, ContextStoreError> {
+            Self::with_connection(Connection::open_in_memory()?)
+        }
+
+        fn with_connection(connection: Connection) -> Result<Self, ContextStoreError> {
+            connection.busy_timeout(Duration::from_secs(5))?;
+            connection.execute_batch(SCHEMA)?;
+            Ok(Self {
+                connection: Arc::new(Mutex::new(connection)),
+                format: PersistFormat::Json,
+            })
+        }
+
+        /// Write contexts in `format`; any format is read
+        pub fn with_format(mut self, format: PersistFormat) -> Self {
+            self.format = format;
+            self
+        }
+
+        /// Run `f` on the connection off the async runtime
+        async fn call<T, F>(&self, f: F) -> Result<T, ContextStoreError>
+        where
+            T: Send + 'static,
+            F: FnOnce(&mut Connection) -> Result<T, ContextStoreError> + Send + 'static,
+        {
+            let connection = self.connection.clone();
+            tokio::task::spawn_blocking(move || f(&mut connection.lock().unwrap()))
+                .await
+                .map_err(|e| ContextStoreError::Io(std::io::Error::other(e)))?
+        }
+    }
+
+    fn record(row: &Row<'_>) -> rusqlite::Result<ContextRecord> {
+        let updated_at: String = row.get(2)?;
+        let updated_at = DateTime::parse_from_rfc3339(&updated_at)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(2, Type::Text, Box::new(e)))?;
+        Ok(ContextRecord {
+            key: row.get(0)?,
+            version: row.get(1)?,
+            updated_at: updated_at.with_timezone(&Utc),
+            message_count: row.get(3)?,
+            token_estimate: row.get(4)?,
+        })
+    }
+
+    #[async_trait]
+    impl ContextStore for SqliteContextStore {
+        async fn save_versioned(
+            &self,
+            key: &str,
+            context: &WorkflowContext,
+            expected_version: Option<u64>,
+        ) -> Result<u64, ContextStoreError> {
+            let data = context
+                .export(self.format)
+                .map_err(|e| ContextStoreError::Corrupt(e.to_string()))?;
+            let record = ContextRecord::new(key, 0, context);
+            self.call(move |connection| {
+                let tx = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                let stored: Option<u64> = tx
+                    .query_row(
+                        "SELECT version FROM contexts WHERE key = ?1",
+                        [&record.key],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                let version = check_version(&record.key, stored, expected_version)?;
+                tx.execute(
+                    "INSERT INTO contexts (key, version, updated_at, message_count, token_estimate, context)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                     ON CONFLICT (key) DO UPDATE SET
+                         version = excluded.version,
+                         updated_at = excluded.updated_at,
+                         message_count = excluded.message_count,
+                         token_estimate = excluded.token_estimate,
+                         context = excluded.context",
+                    params![
+                        record.key,
+                        version,
+                        record.updated_at.to_rfc3339(),
+                        record.message_count,
+                        record.token_estimate,
+                        data,
+                    ],
+                )?;
+                tx.commit()?;
+                Ok(version)
+            })
+            .await
+        }
+
+        async fn load(&self, key: &str) -> Result<Option<StoredContext>, ContextStoreError> {
+            let key = key.to_string();
+            let row = self
+                .call(move |connection| {
+                    let sql = format!(
+                        "SELECT {}, context FROM contexts WHERE key = ?1",
+                        RECORD_COLUMNS
+                    );
+                    Ok(connection
+                        .query_row(&sql, [&key], |row| {
+                            Ok((record(row)?, row.get::<_, Vec<u8>>(5)?))
+                        })
+                        .optional()?)
+                })
+                .await?;
+            let Some((record, data)) = row else {
+                return Ok(None);
+            };
+            let context = WorkflowContext::import(&data)
+                .map_err(|e| ContextStoreError::Corrupt(e.to_string()))?;
+            Ok(Some(StoredContext { record, context }))
+        }
+
+        async fn list(&self) -> Result<Vec<ContextRecord>, ContextStoreError> {
+            self.call(|connection| {
+                let sql = format!("SELECT {} FROM contexts ORDER BY key", RECORD_COLUMNS);
+                let mut statement = connection.prepare(&sql)?;
+                let records = statement.query_map([], record)?;
+                Ok(records.collect::<rusqlite::Result<Vec<_>>>()?)
+            })
+            .await
+        }
+
+        async fn delete(&self, key: &str) -> Result<(), ContextStoreError> {
+            let key = key.to_string();
+            self.call(move |connection| {
+                connection.execute("DELETE FROM contexts WHERE key = ?1", [&key])?;
+                Ok(())
+            })
+            .await
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use crate::context::{ContextError, ContextManager, PruneReport, PruneStage};
+use crate::llm::tokens::TokenCounter;
+use crate::llm::types::ChatMessage;
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+
+/// Chains strategies, each pruning what the ones before it left
+///
+/// A stage runs only if its strategy still asks to, given the history so
+/// far and its running token estimate. So "drop tool output, then
+/// summarize if still over, then truncate if still over" is three
+/// strategies in that order, and pruning stops early once the history fits.
+/// What each stage freed is in [`last_report`](ContextManager::last_report).
+pub struct CompositeContextManager {
+    stages: Vec<Arc<dyn ContextManager>>,
+
+    /// Counts tokens between stages; without one, the last stage estimates
+    token_counter: Option<Arc<dyn TokenCounter>>,
+
+    name: String,
+    last_report: Mutex<Option<PruneReport>>,
+}
+
+impl CompositeContextManager {
+    /// Run `stages` in order
+    ///
+    /// # Examples
+    /// ```
+    /// use agent_runtime::context::CompositeContextManager;
+    /// use agent_runtime::context_strategies::{
+    ///     MessageTypeManager, SummarizationManager, TokenBudgetManager,
+    /// };
+    /// use std::sync::Arc;
+    ///
+    /// let manager = CompositeContextManager::new(vec![
+    ///     Arc::new(MessageTypeManager::new(40, 10)),
+    ///     Arc::new(SummarizationManager::new(18_000, 12_000, 500, 10)),
+    ///     Arc::new(TokenBudgetManager::new(24_000, 3.0)),
+    /// ]);
+    /// ```
+    pub fn new(stages: Vec<Arc<dyn ContextManager>>) -> Self {
+        let names: Vec<&str> = stages.iter().map(|stage| stage.name()).collect();
+        let name = format!("Composite({})", names.join(" > "));
+        Self {
+            stages,
+            token_counter: None,
+            name,
+            last_report: Mutex::new(None),
+        }
+    }
+
+    /// Count tokens with `counter` instead of asking the last stage
+    pub fn with_token_counter(mut self, counter: Arc<dyn TokenCounter>) -> Self {
+        self.token_counter = Some(counter);
+        self
+    }
+}
+
+#[async_trait]
+impl ContextManager for CompositeContextManager {
+    async fn should_prune(&self, history: &[ChatMessage], current_tokens: usize) -> bool {
+        for stage in &self.stages {
+            if stage.should_prune(history, current_tokens).await {
+                return true;
+            }
+        }
+        false
+    }
+
+    async fn prune(
+        &self,
+        history: Vec<ChatMessage>,
+    ) -> Result<(Vec<ChatMessage>, usize), ContextError> {
+        let tokens_before = self.estimate_tokens(&history);
+        let mut history = history;
+        let mut tokens = tokens_before;
+        let mut report = PruneReport::default();
+        for stage in &self.stages {
+            let messages_before = history.len();
+            let ran = stage.should_prune(&history, tokens).await;
+            let tokens_stage_started = tokens;
+            if ran {
+                history = stage.prune(history).await?.0;
+                tokens = self.estimate_tokens(&history);
+            }
+            report.stages.push(PruneStage {
+                strategy: stage.name().to_string(),
+                ran,
+                messages_before,
+                messages_after: history.len(),
+                tokens_before: tokens_stage_started,
+                tokens_freed: tokens_stage_started.saturating_sub(tokens),
+            });
+        }
+        *self.last_report.lock().unwrap() = Some(report);
+        // Stages from outside this crate may leave tool messages cut loose
+        let history = super::repair_tool_linkage(history);
+        let tokens = self.estimate_tokens(&history);
+        Ok((history, tokens_before.saturating_sub(tokens)))
+    }
+
+    fn estimate_tokens(&self, messages: &[ChatMessage]) -> usize {
+        match (&self.token_counter, self.stages.last()) {
+            (Some(counter), _) => counter.count_messages(messages),
+            (None, Some(stage)) => stage.estimate_tokens(messages),
+            (None, None) => super::estimate_tokens_simple(messages),
+        }
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn last_report(&self) -> Option<PruneReport> {
+        self.last_report.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::strategies::{
+        MessageTypeManager, SummarizationManager, TokenBudgetManager,
+    };
+    use crate::context::HeuristicCounter;
+    use crate::llm::types::{FunctionCall, Role, ToolCall};
+
+    /// A research session: each round a question, a search, its long
+    /// result and an answer
+    fn session(rounds: usize) -> Vec<ChatMessage> {
+        let mut history = vec![ChatMessage::system("You research questions.")];
+        for i in 0..rounds {
+            let call = ToolCall {
+                id: format!("call_{}", i),
+                r#type: "function".to_string(),
+                function: FunctionCall {
+                    name: "search".to_string(),
+                    arguments: format!("{{\"query\": \"topic {}\"}}", i),
+                },
+            };
+            history.push(ChatMessage::user(format!(
+                "Question {}: {}",
+                i,
+                "what changed? ".repeat(28)
+            )));
+            history.push(ChatMessage::assistant_with_tool_calls("", vec![call]));
+            history.push(ChatMessage::tool_result(
+                format!("call_{}", i),
+                "search hit ".repeat(200),
+            ));
+            history.push(ChatMessage::assistant(format!(
+                "Answer {}: {}",
+                i,
+                "it grew. ".repeat(44)
+            )));
+        }
+        history
+    }
+
+    fn layered() -> CompositeContextManager {
+        CompositeContextManager::new(vec![
+            Arc::new(MessageTypeManager::new(40, 10)),
+            Arc::new(SummarizationManager::new(5_000, 1_000, 200, 6)),
+            Arc::new(TokenBudgetManager::new(1_000, 1.0)),
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_each_stage_prunes_what_is_left() {
+        let manager = layered();
+        let history = session(30);
+        let tokens = manager.estimate_tokens(&history);
+        assert!(manager.should_prune(&history, tokens).await);
+
+        let (pruned, freed) = manager.prune(history.clone()).await.unwrap();
+        let report = manager.last_report().unwrap();
+        let names: Vec<&str> = report.stages.iter().map(|s| s.strategy.as_str()).collect();
+        assert_eq!(names, ["MessageType", "Summarization", "TokenBudget"]);
+        assert!(report.stages.iter().all(|s| s.ran && s.tokens_freed > 0));
+
+        // Tool output and old rounds go first, leaving ten question/answer pairs
+        let dropped = &report.stages[0];
+        assert_eq!(dropped.messages_before, 121);
+        assert_eq!(dropped.messages_after, 21);
+        assert!(pruned.iter().all(|m| m.role != Role::Tool));
+
+        // Then all but six of those are summarized, and the oldest of the
+        // rest cut until the budget fits
+        let summarized = &report.stages[1];
+        assert_eq!(
+            summarized.tokens_before,
+            dropped.tokens_before - dropped.tokens_freed
+        );
+        assert_eq!(summarized.messages_after, 8);
+        assert!(SummarizationManager::is_summary(&pruned[1]));
+        let truncated = &report.stages[2];
+        assert!(truncated.messages_after < 8);
+
+        assert_eq!(freed, report.tokens_freed());
+        assert_eq!(tokens - freed, manager.estimate_tokens(&pruned));
+        assert!(manager.estimate_tokens(&pruned) <= 500);
+        assert_eq!(
+            manager.name(),
+            "Composite(MessageType > Summarization > TokenBudget)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stages_no_longer_needed_are_skipped() {
+        let manager = layered();
+        let mut history = vec![ChatMessage::system("You chat.")];
+        for i in 0..25 {
+            history.push(ChatMessage::user(format!("Question {}", i)));
+            history.push(ChatMessage::assistant(format!("Answer {}", i)));
+        }
+
+        // Too many messages, but few tokens once the oldest are dropped
+        let (pruned, freed) = manager.prune(history).await.unwrap();
+        let report = manager.last_report().unwrap();
+        assert!(report.stages[0].ran);
+        assert_eq!(pruned.len(), 21);
+        assert!(!report.stages[1].ran);
+        assert!(!report.stages[2].ran);
+        assert_eq!(report.stages[2].messages_before, 21);
+        assert_eq!(report.stages[2].tokens_freed, 0);
+        assert_eq!(freed, report.stages[0].tokens_freed);
+    }
+
+    #[test]
+    fn test_estimates_come_from_the_injected_counter() {
+        struct Words;
+        impl TokenCounter for Words {
+            fn count_text(&self, text: &str) -> usize {
+                text.split_whitespace().count()
+            }
+            fn name(&self) -> &str {
+                "words"
+            }
+        }
+
+        let history = vec![ChatMessage::user("one two three four")];
+        let manager = layered();
+        assert_eq!(
+            manager.estimate_tokens(&history),
+            HeuristicCounter::default().count_messages(&history)
+        );
+        let manager = layered().with_token_counter(Arc::new(Words));
+        assert_eq!(
+            manager.estimate_tokens(&history),
+            Words.count_messages(&history)
+        );
+    }
+}
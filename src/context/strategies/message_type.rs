@@ -1,16 +1,24 @@
-use super::estimate_tokens_simple;
 use crate::context::{ContextError, ContextManager};
+use crate::llm::tokens::{default_counter, TokenCounter};
 use crate::llm::types::{ChatMessage, Role};
 use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 /// Message type-based context manager that prioritizes messages by type
 /// Keeps system messages, recent user/assistant pairs, and prunes old tool calls
+///
+/// An assistant message's tool calls and their results are kept or pruned
+/// as one unit.
 pub struct MessageTypeManager {
     /// Maximum messages to keep
     pub(super) max_messages: usize,
 
     /// Number of recent user/assistant pairs to always keep
     pub(super) keep_recent_pairs: usize,
+
+    /// Counts tokens for `estimate_tokens`; pruning is by message count
+    pub(super) token_counter: Arc<dyn TokenCounter>,
 }
 
 impl MessageTypeManager {
@@ -31,9 +39,16 @@ impl MessageTypeManager {
         Self {
             max_messages,
             keep_recent_pairs,
+            token_counter: default_counter(),
         }
     }
 
+    /// Count tokens with `counter` instead of the character heuristic
+    pub fn with_token_counter(mut self, counter: Arc<dyn TokenCounter>) -> Self {
+        self.token_counter = counter;
+        self
+    }
+
     /// Classify messages into priority tiers for pruning
     fn classify_message(msg: &ChatMessage) -> MessagePriority {
         match msg.role {
@@ -71,9 +86,29 @@ impl MessageTypeManager {
         pair_indices.sort_unstable();
         pair_indices
     }
+
+    /// The unit each message belongs to, as the index of its first message:
+    /// a tool result is in the unit of the assistant message that called it
+    fn tool_units(history: &[ChatMessage]) -> Vec<usize> {
+        let mut callers: HashMap<&str, usize> = HashMap::new();
+        history
+            .iter()
+            .enumerate()
+            .map(|(i, msg)| {
+                for call in msg.tool_calls.iter().flatten() {
+                    callers.insert(call.id.as_str(), i);
+                }
+                let caller = msg.tool_call_id.as_deref().and_then(|id| callers.get(id));
+                match (&msg.role, caller) {
+                    (Role::Tool, Some(&caller)) => caller,
+                    _ => i,
+                }
+            })
+            .collect()
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 enum MessagePriority {
     Critical = 0, // System messages
     High = 1,     // User/Assistant
@@ -95,40 +130,60 @@ impl ContextManager for MessageTypeManager {
         }
 
         let original_len = history.len();
+        let units = Self::tool_units(&history);
 
-        let system_indices: Vec<usize> = history
+        let system_indices = history
             .iter()
             .enumerate()
             .filter(|(_, msg)| msg.role == Role::System)
-            .map(|(i, _)| i)
-            .collect();
-
+            .map(|(i, _)| i);
         let recent_pair_indices = Self::extract_recent_pairs(&history, self.keep_recent_pairs);
 
-        let mut protected: std::collections::HashSet<usize> = system_indices.into_iter().collect();
-        protected.extend(recent_pair_indices);
-
-        let mut protected_vec: Vec<usize> = protected.iter().copied().collect();
-        protected_vec.sort_unstable();
-
-        let mut new_history = Vec::new();
-        for &idx in &protected_vec {
-            if idx < history.len() {
-                new_history.push(history[idx].clone());
+        // Protecting any message of a unit protects all of it
+        let protected: HashSet<usize> = system_indices
+            .chain(recent_pair_indices)
+            .map(|i| units[i])
+            .collect();
+        let mut keep: Vec<bool> = units.iter().map(|u| protected.contains(u)).collect();
+
+        // Still over: drop whole units, lowest priority and oldest first
+        let mut kept = keep.iter().filter(|k| **k).count();
+        if kept > self.max_messages {
+            let mut priorities: HashMap<usize, MessagePriority> = HashMap::new();
+            for (i, &unit) in units.iter().enumerate().filter(|(i, _)| keep[*i]) {
+                let priority = Self::classify_message(&history[i]);
+                let entry = priorities.entry(unit).or_insert(MessagePriority::Low);
+                if priority < *entry {
+                    *entry = priority;
+                }
+            }
+            let mut candidates: Vec<(MessagePriority, usize)> =
+                priorities.into_iter().map(|(unit, p)| (p, unit)).collect();
+            candidates.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+            for (_, unit) in candidates {
+                if kept <= self.max_messages {
+                    break;
+                }
+                for (i, _) in units.iter().enumerate().filter(|(_, &u)| u == unit) {
+                    keep[i] = false;
+                    kept -= 1;
+                }
             }
         }
 
-        if new_history.len() > self.max_messages {
-            new_history.sort_by_key(Self::classify_message);
-            new_history.truncate(self.max_messages);
-        }
+        let new_history: Vec<ChatMessage> = history
+            .into_iter()
+            .zip(keep)
+            .filter_map(|(msg, keep)| keep.then_some(msg))
+            .collect();
+        let new_history = super::repair_tool_linkage(new_history);
 
         let removed = original_len - new_history.len();
         Ok((new_history, removed))
     }
 
     fn estimate_tokens(&self, messages: &[ChatMessage]) -> usize {
-        estimate_tokens_simple(messages)
+        self.token_counter.count_messages(messages)
     }
 
     fn name(&self) -> &str {
@@ -1,30 +1,57 @@
 //! Context management strategies for keeping chat history within token budgets.
 
+mod composite;
 mod message_type;
 mod sliding_window;
 mod summarization;
 mod token_budget;
 
-use crate::llm::types::ChatMessage;
+use crate::llm::tokens::{HeuristicCounter, TokenCounter};
+use crate::llm::types::{ChatMessage, Role};
+use std::collections::HashSet;
 
+pub use composite::CompositeContextManager;
 pub use message_type::MessageTypeManager;
 pub use sliding_window::SlidingWindowManager;
 pub use summarization::SummarizationManager;
 pub use token_budget::TokenBudgetManager;
 
-/// Simple token estimation helper used by all strategies
+/// The strategies' default estimate, also behind `SimpleTokenEstimator`
 pub(crate) fn estimate_tokens_simple(messages: &[ChatMessage]) -> usize {
-    messages
-        .iter()
-        .map(|msg| {
-            let content_tokens = msg.content.len() / 4; // ~4 chars per token
-            let role_token = 1; // Role field
-            let tool_tokens = msg
-                .tool_calls
-                .as_ref()
-                .map(|calls| calls.len() * 20)
-                .unwrap_or(0);
-            content_tokens + role_token + tool_tokens
+    HeuristicCounter::default().count_messages(messages)
+}
+
+/// `history` without the tool messages pruning cut loose
+///
+/// Every strategy passes its result through this, so a removed message
+/// takes its dependents with it: a tool result whose call is gone is
+/// dropped, calls whose results are gone are taken off their assistant
+/// message, and an assistant message left with none of its calls is dropped
+/// too.
+pub(crate) fn repair_tool_linkage(history: Vec<ChatMessage>) -> Vec<ChatMessage> {
+    let mut offered = HashSet::new();
+    let mut answered = HashSet::new();
+    let mut linked = Vec::with_capacity(history.len());
+    for message in history {
+        offered.extend(message.tool_calls.iter().flatten().map(|c| c.id.clone()));
+        if message.role == Role::Tool {
+            match &message.tool_call_id {
+                Some(id) if offered.contains(id) => answered.insert(id.clone()),
+                _ => continue,
+            };
+        }
+        linked.push(message);
+    }
+    linked
+        .into_iter()
+        .filter_map(|mut message| {
+            if let Some(calls) = message.tool_calls.as_mut().filter(|c| !c.is_empty()) {
+                calls.retain(|c| answered.contains(&c.id));
+                if calls.is_empty() {
+                    return None;
+                }
+            }
+            Some(message)
         })
-        .sum()
+        .collect()
 }
@@ -1,6 +1,8 @@
 use crate::context::{ContextError, ContextManager};
+use crate::llm::tokens::TokenCounter;
 use crate::llm::types::{ChatMessage, Role};
 use async_trait::async_trait;
+use std::sync::Arc;
 
 /// Sliding window context manager that keeps last N messages
 pub struct SlidingWindowManager {
@@ -9,6 +11,10 @@ pub struct SlidingWindowManager {
 
     /// Minimum messages to keep (typically system + 1 pair)
     pub(super) min_messages: usize,
+
+    /// Counts tokens for `estimate_tokens` instead of ~4 bytes of text per
+    /// token; pruning is by message count
+    pub(super) token_counter: Option<Arc<dyn TokenCounter>>,
 }
 
 impl SlidingWindowManager {
@@ -20,6 +26,7 @@ impl SlidingWindowManager {
         Self {
             max_messages,
             min_messages: 3, // System + 1 user/assistant pair
+            token_counter: None,
         }
     }
 
@@ -28,6 +35,12 @@ impl SlidingWindowManager {
         self.min_messages = min;
         self
     }
+
+    /// Count tokens with `counter` instead of the character heuristic
+    pub fn with_token_counter(mut self, counter: Arc<dyn TokenCounter>) -> Self {
+        self.token_counter = Some(counter);
+        self
+    }
 }
 
 #[async_trait]
@@ -61,6 +74,7 @@ impl ContextManager for SlidingWindowManager {
 
         let mut pruned = system_messages;
         pruned.append(&mut kept_messages);
+        let pruned = super::repair_tool_linkage(pruned);
 
         let removed_count = initial_count - pruned.len();
 
@@ -68,10 +82,13 @@ impl ContextManager for SlidingWindowManager {
     }
 
     fn estimate_tokens(&self, messages: &[ChatMessage]) -> usize {
-        messages
-            .iter()
-            .map(|msg| msg.content.len() / 4)
-            .sum::<usize>()
+        match &self.token_counter {
+            Some(counter) => counter.count_messages(messages),
+            None => messages
+                .iter()
+                .map(|msg| msg.content.text().len() / 4)
+                .sum::<usize>(),
+        }
     }
 
     fn name(&self) -> &str {
@@ -127,4 +144,18 @@ mod tests {
         assert_eq!(pruned[pruned.len() - 1].content, "Recent resp");
         assert_eq!(removed, 3);
     }
+
+    #[test]
+    fn test_sliding_window_estimate_counts_text_only() {
+        let manager = SlidingWindowManager::new(4);
+        let history = vec![
+            ChatMessage::user("12345678"),
+            ChatMessage::assistant("1234"),
+        ];
+
+        // No role or tool-call tokens on top
+        assert_eq!(manager.estimate_tokens(&history), 3);
+        let counted = manager.with_token_counter(crate::llm::tokens::default_counter());
+        assert_eq!(counted.estimate_tokens(&history), 5);
+    }
 }
@@ -1,7 +1,17 @@
-use super::estimate_tokens_simple;
 use crate::context::{ContextError, ContextManager};
-use crate::llm::types::{ChatMessage, Role};
+use crate::llm::tokens::{default_counter, TokenCounter};
+use crate::llm::types::{ChatMessage, ChatRequest, Role};
+use crate::llm::LlmClient;
 use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Every summary message starts with this, so later prunes can fold it in
+const SUMMARY_PREFIX: &str = "Summary of previous conversation";
+
+const DEFAULT_PROMPT: &str = "You compress chat history for an AI assistant that will continue \
+     the conversation. Summarize the transcript you are given in at most {max_tokens} tokens. \
+     Keep facts, decisions, names, numbers, tool results and open questions that later turns \
+     may rely on; drop pleasantries and repetition. Write plain prose without preamble.";
 
 /// Summarization-based context manager that compresses old history using an LLM
 /// This strategy calls an LLM to create compressed summaries of old messages
@@ -9,14 +19,24 @@ pub struct SummarizationManager {
     /// Token threshold that triggers summarization
     pub(super) summarization_threshold: usize,
 
-    /// Target token count for summaries (reserved for future use)
-    pub(super) _summary_token_target: usize,
+    /// Target token count for summaries
+    pub(super) summary_token_target: usize,
 
     /// Maximum input tokens allowed
     pub(super) max_input_tokens: usize,
 
     /// Number of recent messages to never summarize
     pub(super) keep_recent_count: usize,
+
+    /// Counts tokens for pruning decisions
+    pub(super) token_counter: Arc<dyn TokenCounter>,
+
+    /// Writes the summaries; without one, a heuristic digest is used
+    pub(super) llm: Option<LlmClient>,
+
+    /// System prompt for the summarizer, with `{max_tokens}` replaced by
+    /// the summary target
+    pub(super) prompt: Option<String>,
 }
 
 impl SummarizationManager {
@@ -44,17 +64,73 @@ impl SummarizationManager {
     ) -> Self {
         Self {
             summarization_threshold,
-            _summary_token_target: summary_token_target,
+            summary_token_target,
             max_input_tokens,
             keep_recent_count,
+            token_counter: default_counter(),
+            llm: None,
+            prompt: None,
         }
     }
 
-    /// Create a summary message from a slice of history
-    /// Note: This is a placeholder implementation. In production, you would
-    /// call an actual LLM to generate the summary.
+    /// Count tokens with `counter` instead of the character heuristic
+    pub fn with_token_counter(mut self, counter: Arc<dyn TokenCounter>) -> Self {
+        self.token_counter = counter;
+        self
+    }
+
+    /// Summarize with `llm`
+    ///
+    /// If a request fails or comes back empty, the heuristic summary is
+    /// used instead, so pruning never fails the workflow.
+    pub fn with_llm(mut self, llm: LlmClient) -> Self {
+        self.llm = Some(llm);
+        self
+    }
+
+    /// Replace the summarizer's system prompt; `{max_tokens}` is replaced
+    /// by the summary target
+    pub fn with_summary_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = Some(prompt.into());
+        self
+    }
+
+    /// Whether `message` is a summary written by this manager
+    pub fn is_summary(message: &ChatMessage) -> bool {
+        message.role == Role::System && message.content.text().starts_with(SUMMARY_PREFIX)
+    }
+
+    /// Summarize `messages`, including any earlier summaries among them
+    async fn summarize(&self, messages: &[ChatMessage]) -> ChatMessage {
+        if let Some(llm) = &self.llm {
+            let prompt = self
+                .prompt
+                .as_deref()
+                .unwrap_or(DEFAULT_PROMPT)
+                .replace("{max_tokens}", &self.summary_token_target.to_string());
+            let request = ChatRequest::new(vec![
+                ChatMessage::system(prompt),
+                ChatMessage::user(transcript(messages)),
+            ])
+            .with_max_tokens(u32::try_from(self.summary_token_target).unwrap_or(u32::MAX));
+            if let Ok(response) = llm.chat(request).await {
+                let summary = response.content.trim();
+                if !summary.is_empty() {
+                    return ChatMessage::system(format!(
+                        "{} (LLM-generated):\n\n{}",
+                        SUMMARY_PREFIX, summary
+                    ));
+                }
+            }
+        }
+        Self::create_summary(messages)
+    }
+
+    /// Create a summary message from a slice of history without an LLM:
+    /// message counts and previews of where the conversation started and
+    /// where it got to
     fn create_summary(messages: &[ChatMessage]) -> ChatMessage {
-        let mut summary_content = String::from("Summary of previous conversation:\n\n");
+        let mut summary_content = format!("{} (heuristic):\n\n", SUMMARY_PREFIX);
 
         let user_messages: Vec<_> = messages.iter().filter(|m| m.role == Role::User).collect();
 
@@ -69,27 +145,83 @@ impl SummarizationManager {
             assistant_messages.len()
         ));
 
+        if let Some(earlier) = messages.iter().find(|m| Self::is_summary(m)) {
+            let text = earlier.content.text();
+            let body = summary_body(&text);
+            let preview = body.chars().take(100).collect::<String>();
+            summary_content.push_str(&format!("- Earlier summary: {}\n", preview));
+        }
+
         if let Some(first_user) = user_messages.first() {
-            let preview = first_user.content.chars().take(100).collect::<String>();
+            let preview = first_user
+                .content
+                .text()
+                .chars()
+                .take(100)
+                .collect::<String>();
             summary_content.push_str(&format!("- Initial topic: {}\n", preview));
         }
 
         if let Some(last_assistant) = assistant_messages.last() {
-            let preview = last_assistant.content.chars().take(100).collect::<String>();
+            let preview = last_assistant
+                .content
+                .text()
+                .chars()
+                .take(100)
+                .collect::<String>();
             summary_content.push_str(&format!("- Latest response: {}\n", preview));
         }
 
         summary_content.push_str("\n[This is a compressed summary. Original messages were removed to save context space.]");
 
-        ChatMessage {
-            role: Role::System,
-            content: summary_content,
-            tool_calls: None,
-            tool_call_id: None,
-            agent_id: None,
-            workflow_id: None,
+        ChatMessage::system(summary_content)
+    }
+}
+
+/// A summary's text without its header line
+fn summary_body(content: &str) -> &str {
+    content
+        .split_once("\n\n")
+        .map_or(content, |(_, body)| body)
+        .trim()
+}
+
+/// The messages as a plain transcript for the summarizer
+///
+/// Rendered as text rather than passed as messages, so tool results whose
+/// calls were kept (or dropped) earlier don't make an invalid request.
+fn transcript(messages: &[ChatMessage]) -> String {
+    let mut out = String::new();
+    for message in messages {
+        if SummarizationManager::is_summary(message) {
+            out.push_str("Summary of everything before this point:\n");
+            out.push_str(summary_body(&message.content.text()));
+            out.push_str("\n\n");
+            continue;
         }
+        let role = match message.role {
+            Role::System => "System",
+            Role::User => "User",
+            Role::Assistant => "Assistant",
+            Role::Tool => "Tool result",
+        };
+        out.push_str(role);
+        out.push_str(": ");
+        out.push_str(&message.content.text());
+        match message.content.image_count() {
+            0 => {}
+            1 => out.push_str(" [1 image]"),
+            n => out.push_str(&format!(" [{} images]", n)),
+        }
+        for call in message.tool_calls.iter().flatten() {
+            out.push_str(&format!(
+                "\n(calls {}({}))",
+                call.function.name, call.function.arguments
+            ));
+        }
+        out.push_str("\n\n");
     }
+    out.trim_end().to_string()
 }
 
 #[async_trait]
@@ -109,7 +241,15 @@ impl ContextManager for SummarizationManager {
         }
 
         let keep_from_end = self.keep_recent_count.min(history.len());
-        let summarize_count = history.len().saturating_sub(keep_from_end);
+        let mut summarize_count = history.len().saturating_sub(keep_from_end);
+
+        // Keep tool results with their call
+        while summarize_count > 0
+            && summarize_count < history.len()
+            && history[summarize_count].role == Role::Tool
+        {
+            summarize_count -= 1;
+        }
 
         if summarize_count == 0 {
             return Ok((history, 0));
@@ -119,15 +259,16 @@ impl ContextManager for SummarizationManager {
 
         let (to_summarize, keep_recent) = history.split_at(summarize_count);
 
+        // Earlier summaries are folded into the new one instead of kept
         let system_messages: Vec<ChatMessage> = to_summarize
             .iter()
-            .filter(|msg| msg.role == Role::System)
+            .filter(|msg| msg.role == Role::System && !Self::is_summary(msg))
             .cloned()
             .collect();
 
         let non_system_to_summarize: Vec<ChatMessage> = to_summarize
             .iter()
-            .filter(|msg| msg.role != Role::System)
+            .filter(|msg| msg.role != Role::System || Self::is_summary(msg))
             .cloned()
             .collect();
 
@@ -135,7 +276,7 @@ impl ContextManager for SummarizationManager {
         new_history.extend(system_messages);
 
         if !non_system_to_summarize.is_empty() {
-            new_history.push(Self::create_summary(&non_system_to_summarize));
+            new_history.push(self.summarize(&non_system_to_summarize).await);
         }
 
         new_history.extend_from_slice(keep_recent);
@@ -151,12 +292,13 @@ impl ContextManager for SummarizationManager {
             }
         }
 
-        let removed = original_len - new_history.len();
+        let new_history = super::repair_tool_linkage(new_history);
+        let removed = original_len.saturating_sub(new_history.len());
         Ok((new_history, removed))
     }
 
     fn estimate_tokens(&self, messages: &[ChatMessage]) -> usize {
-        estimate_tokens_simple(messages)
+        self.token_counter.count_messages(messages)
     }
 
     fn name(&self) -> &str {
@@ -167,13 +309,14 @@ impl ContextManager for SummarizationManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm::MockLlmClient;
 
     #[tokio::test]
     async fn test_summarization_manager_creation() {
         let manager = SummarizationManager::new(18_000, 15_000, 500, 10);
         assert_eq!(manager.max_input_tokens, 18_000);
         assert_eq!(manager.summarization_threshold, 15_000);
-        assert_eq!(manager._summary_token_target, 500);
+        assert_eq!(manager.summary_token_target, 500);
         assert_eq!(manager.keep_recent_count, 10);
     }
 
@@ -217,9 +360,10 @@ mod tests {
         assert!(pruned.iter().any(|m| m.content == "Recent message 2"));
 
         if removed > 0 {
-            assert!(pruned
-                .iter()
-                .any(|m| m.content.contains("Summary of previous conversation")));
+            assert!(pruned.iter().any(|m| m
+                .content
+                .text()
+                .contains("Summary of previous conversation")));
         }
     }
 
@@ -243,4 +387,107 @@ mod tests {
         let system_count = pruned.iter().filter(|m| m.role == Role::System).count();
         assert!(system_count >= 2, "System messages should be preserved");
     }
+
+    fn long_history() -> Vec<ChatMessage> {
+        vec![
+            ChatMessage::system("System prompt"),
+            ChatMessage::user("Plan a trip to Lisbon in May"),
+            ChatMessage::assistant("Flights from Berlin are cheapest on Tuesdays"),
+            ChatMessage::user("Book the Tuesday flight"),
+            ChatMessage::assistant("Booked flight TP 537"),
+            ChatMessage::user("Recent message"),
+            ChatMessage::assistant("Recent response"),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_llm_summary_replaces_old_messages() {
+        let mock = Arc::new(MockLlmClient::new().with_response(
+            "User is going to Lisbon in May; flight TP 537 on a Tuesday is booked.",
+        ));
+        let manager = SummarizationManager::new(18_000, 10, 120, 2).with_llm(mock.clone());
+
+        let (pruned, removed) = manager.prune(long_history()).await.unwrap();
+
+        let request = mock.last_call().unwrap();
+        assert_eq!(request.max_tokens, Some(120));
+        assert!(request.messages[0]
+            .content
+            .text()
+            .contains("at most 120 tokens"));
+        let transcript = request.messages[1].content.text();
+        assert!(transcript.contains("User: Plan a trip to Lisbon in May"));
+        assert!(transcript.contains("Assistant: Booked flight TP 537"));
+        assert!(!transcript.contains("Recent message"));
+        assert!(!transcript.contains("System prompt"));
+
+        assert_eq!(removed, 3);
+        assert_eq!(pruned[0].content, "System prompt");
+        assert!(SummarizationManager::is_summary(&pruned[1]));
+        assert!(pruned[1].content.text().contains("(LLM-generated)"));
+        assert!(pruned[1]
+            .content
+            .text()
+            .contains("flight TP 537 on a Tuesday is booked"));
+        assert_eq!(pruned[2].content, "Recent message");
+    }
+
+    #[tokio::test]
+    async fn test_llm_failure_falls_back_to_heuristic() {
+        let mock = Arc::new(MockLlmClient::new().error_on_call(0));
+        let manager = SummarizationManager::new(18_000, 10, 120, 2).with_llm(mock.clone());
+
+        let (pruned, _) = manager.prune(long_history()).await.unwrap();
+
+        assert_eq!(mock.call_count(), 1);
+        let summary = pruned.iter().find(|m| SummarizationManager::is_summary(m));
+        let summary = summary.unwrap();
+        assert!(summary.content.text().contains("(heuristic)"));
+        assert!(summary
+            .content
+            .text()
+            .contains("Initial topic: Plan a trip to Lisbon"));
+    }
+
+    #[tokio::test]
+    async fn test_earlier_summaries_are_folded_in() {
+        let mock = Arc::new(
+            MockLlmClient::new()
+                .with_response("First summary")
+                .with_response("Second summary"),
+        );
+        let manager = SummarizationManager::new(18_000, 10, 120, 2).with_llm(mock.clone());
+
+        let (mut history, _) = manager.prune(long_history()).await.unwrap();
+        history.push(ChatMessage::user("Find a hotel near the Alfama"));
+        history.push(ChatMessage::assistant("Hotel Convento is available"));
+        let (pruned, _) = manager.prune(history).await.unwrap();
+
+        let transcript = mock.last_call().unwrap().messages[1].content.to_string();
+        assert!(transcript.starts_with("Summary of everything before this point:\nFirst summary"));
+        assert!(transcript.contains("User: Recent message"));
+
+        let summaries: Vec<_> = pruned
+            .iter()
+            .filter(|m| SummarizationManager::is_summary(m))
+            .collect();
+        assert_eq!(summaries.len(), 1);
+        assert!(summaries[0].content.text().ends_with("Second summary"));
+        assert_eq!(pruned[0].content, "System prompt");
+    }
+
+    #[tokio::test]
+    async fn test_custom_summary_prompt() {
+        let mock = Arc::new(MockLlmClient::new().with_response("Short"));
+        let manager = SummarizationManager::new(18_000, 10, 64, 2)
+            .with_llm(mock.clone())
+            .with_summary_prompt("Bullet points only, under {max_tokens} tokens.");
+
+        manager.prune(long_history()).await.unwrap();
+
+        assert_eq!(
+            mock.last_call().unwrap().messages[0].content,
+            "Bullet points only, under 64 tokens."
+        );
+    }
 }
@@ -1,6 +1,8 @@
 use crate::context::{ContextError, ContextManager};
+use crate::llm::tokens::{default_counter, TokenCounter};
 use crate::llm::types::{ChatMessage, Role};
 use async_trait::async_trait;
+use std::sync::Arc;
 
 /// Token budget-based context manager that maintains a configurable input budget
 /// Supports any context size and input/output ratio
@@ -13,6 +15,9 @@ pub struct TokenBudgetManager {
 
     /// Safety buffer tokens (pruning triggers this many tokens before limit)
     pub(super) safety_buffer: usize,
+
+    /// Counts tokens for pruning decisions
+    pub(super) token_counter: Arc<dyn TokenCounter>,
 }
 
 impl TokenBudgetManager {
@@ -40,6 +45,7 @@ impl TokenBudgetManager {
             max_input_tokens: max_input,
             min_messages_to_keep: 3,       // System + 1 user/assistant pair
             safety_buffer: max_input / 10, // 10% safety buffer
+            token_counter: default_counter(),
         }
     }
 
@@ -55,6 +61,12 @@ impl TokenBudgetManager {
         self
     }
 
+    /// Count tokens with `counter` instead of the character heuristic
+    pub fn with_token_counter(mut self, counter: Arc<dyn TokenCounter>) -> Self {
+        self.token_counter = counter;
+        self
+    }
+
     /// Get the effective pruning threshold (max - safety buffer)
     pub fn pruning_threshold(&self) -> usize {
         self.max_input_tokens.saturating_sub(self.safety_buffer)
@@ -104,6 +116,7 @@ impl ContextManager for TokenBudgetManager {
         // Reconstruct: system messages + remaining messages
         let mut pruned = system_messages;
         pruned.extend(remaining);
+        let pruned = super::repair_tool_linkage(pruned);
 
         let final_tokens = self.estimate_tokens(&pruned);
         let tokens_freed = initial_tokens.saturating_sub(final_tokens);
@@ -112,19 +125,7 @@ impl ContextManager for TokenBudgetManager {
     }
 
     fn estimate_tokens(&self, messages: &[ChatMessage]) -> usize {
-        messages
-            .iter()
-            .map(|msg| {
-                let content_tokens = msg.content.len() / 4; // ~4 chars per token
-                let role_tokens = 1; // Role marker
-                let tool_tokens = msg
-                    .tool_calls
-                    .as_ref()
-                    .map(|calls| calls.len() * 20) // ~20 tokens per tool call
-                    .unwrap_or(0);
-                content_tokens + role_tokens + tool_tokens
-            })
-            .sum()
+        self.token_counter.count_messages(messages)
     }
 
     fn name(&self) -> &str {
@@ -198,4 +199,61 @@ mod tests {
         let tokens = manager.estimate_tokens(&messages);
         assert_eq!(tokens, 5);
     }
+
+    #[tokio::test]
+    async fn test_images_count_toward_the_budget() {
+        use crate::llm::types::{ContentPart, DEFAULT_IMAGE_TOKENS};
+
+        let photo = || ContentPart::image_url("https://example.com/otter.jpg");
+        let history = vec![
+            ChatMessage::system("Describe"),
+            ChatMessage::user_with_images("a", [photo(), photo()]),
+            ChatMessage::assistant("b"),
+            ChatMessage::user("c"),
+            ChatMessage::assistant("d"),
+        ];
+        let manager = TokenBudgetManager::new(2_000, 1.0).with_min_messages(2);
+
+        // Short text, yet the two images push it over the threshold
+        let estimated = manager.estimate_tokens(&history);
+        assert!(estimated >= 2 * DEFAULT_IMAGE_TOKENS);
+        assert!(manager.should_prune(&history, estimated).await);
+
+        let (kept, freed) = manager.prune(history).await.unwrap();
+        assert_eq!(kept.len(), 4);
+        assert!(kept.iter().all(|m| !m.content.has_images()));
+        assert!(freed >= 2 * DEFAULT_IMAGE_TOKENS);
+    }
+
+    #[cfg(feature = "tiktoken")]
+    #[tokio::test]
+    async fn test_real_counts_move_the_pruning_point() {
+        use crate::llm::tokens::{TiktokenCounter, CODE_HEAVY};
+
+        let history: Vec<ChatMessage> = std::iter::once(ChatMessage::system("Review code"))
+            .chain((0..4).flat_map(|_| {
+                [
+                    ChatMessage::user(CODE_HEAVY),
+                    ChatMessage::assistant("Looks fine."),
+                ]
+            }))
+            .collect();
+        let heuristic = TokenBudgetManager::new(400, 1.0);
+        let tiktoken =
+            TokenBudgetManager::new(400, 1.0).with_token_counter(Arc::new(TiktokenCounter::new()));
+
+        // The same history sits on either side of the threshold
+        let estimated = heuristic.estimate_tokens(&history);
+        let counted = tiktoken.estimate_tokens(&history);
+        assert!(estimated < heuristic.pruning_threshold());
+        assert!(counted > tiktoken.pruning_threshold());
+        assert!(!heuristic.should_prune(&history, estimated).await);
+        assert!(tiktoken.should_prune(&history, counted).await);
+
+        let (kept, _) = heuristic.prune(history.clone()).await.unwrap();
+        assert_eq!(kept.len(), history.len());
+        let (kept, freed) = tiktoken.prune(history.clone()).await.unwrap();
+        assert!(kept.len() < history.len());
+        assert!(freed > 0);
+    }
 }
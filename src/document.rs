@@ -0,0 +1,391 @@
+//! A markdown document a workflow writes and revises while it runs.
+//!
+//! A [`LiveDocument`] is an ordered list of named sections. Agents edit it
+//! through the tools from [`LiveDocument::register_tools`]; steps such as a
+//! `TransformStep` closure call the same methods directly. Every edit is
+//! recorded as a [`Revision`] and, once the document is attached to a run,
+//! emitted as a `system:document` event carrying a [`DocumentPatch`], so a
+//! UI can render the document as it changes. When the run completes the
+//! assembled markdown is exported as an artifact and inlined into the final
+//! output.
+//!
+//! Edits are serialized by the document, so concurrent writes to a section
+//! (e.g. from parallel branches) land one after the other: the last writer
+//! wins, and the history shows every write in the order it was applied.
+
+use crate::artifact::sha256_hex;
+use crate::event::{ComponentStatus, EventScope, EventStream, EventType};
+use crate::tools::{NativeTool, ToolRegistry, ToolRunContext};
+use crate::types::{ToolError, ToolResult};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// Key the document is inlined under in the final output by default
+pub const DEFAULT_OUTPUT_KEY: &str = "document";
+
+/// How a section was changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EditKind {
+    Write,
+    Append,
+}
+
+/// What one edit changed; the payload of document events
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentPatch {
+    pub section: String,
+    pub kind: EditKind,
+
+    /// Revision number of the edit, counting from 1 across the document
+    pub revision: u64,
+
+    /// Hash of the section before the edit; `None` if it was created
+    pub old_hash: Option<String>,
+
+    /// Hex-encoded SHA-256 of the section's new content
+    pub new_hash: String,
+
+    /// The section's full new content
+    pub content: String,
+}
+
+/// An entry in the document's history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Revision {
+    #[serde(flatten)]
+    pub patch: DocumentPatch,
+
+    /// Agent that made the edit, when it came through a tool
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+
+    pub at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A section as it currently reads
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Section {
+    pub name: String,
+    pub content: String,
+
+    /// Edits made to this section
+    pub revisions: u32,
+}
+
+#[derive(Default)]
+struct Inner {
+    sections: Vec<Section>,
+    history: Vec<Revision>,
+    events: Option<Attachment>,
+}
+
+/// The run a document was attached to
+#[cfg_attr(not(feature = "workflow"), allow(dead_code))]
+struct Attachment {
+    stream: EventStream,
+    workflow_id: String,
+    run_id: String,
+}
+
+/// Shared handle on a live document; clones edit the same document
+#[derive(Clone)]
+pub struct LiveDocument {
+    title: String,
+    output_key: String,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl LiveDocument {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            output_key: DEFAULT_OUTPUT_KEY.to_string(),
+            inner: Arc::default(),
+        }
+    }
+
+    /// Inline the finished document under `key` of the final output; the
+    /// exported artifact is named `<key>.md`
+    pub fn with_output_key(mut self, key: impl Into<String>) -> Self {
+        self.output_key = key.into();
+        self
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn output_key(&self) -> &str {
+        &self.output_key
+    }
+
+    /// Replace a section's content, adding the section at the end if new
+    pub fn write_section(&self, name: &str, content: impl Into<String>) -> DocumentPatch {
+        self.edit(name, EditKind::Write, content.into(), None)
+    }
+
+    /// Append to a section's content, adding the section if new
+    pub fn append_to_section(&self, name: &str, content: impl Into<String>) -> DocumentPatch {
+        self.edit(name, EditKind::Append, content.into(), None)
+    }
+
+    /// Current content of a section
+    pub fn section(&self, name: &str) -> Option<String> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .sections
+            .iter()
+            .find(|s| s.name == name)
+            .map(|s| s.content.clone())
+    }
+
+    /// All sections in document order
+    pub fn sections(&self) -> Vec<Section> {
+        self.inner.lock().unwrap().sections.clone()
+    }
+
+    /// Every edit in the order it was applied
+    pub fn history(&self) -> Vec<Revision> {
+        self.inner.lock().unwrap().history.clone()
+    }
+
+    /// Edits to one section, oldest first
+    pub fn section_history(&self, name: &str) -> Vec<Revision> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .history
+            .iter()
+            .filter(|r| r.patch.section == name)
+            .cloned()
+            .collect()
+    }
+
+    /// The document as markdown: the title, then each section as a `##`
+    /// heading followed by its content
+    pub fn to_markdown(&self) -> String {
+        let inner = self.inner.lock().unwrap();
+        let mut markdown = format!("# {}\n", self.title);
+        for section in &inner.sections {
+            markdown.push_str(&format!("\n## {}\n\n", section.name));
+            let content = section.content.trim_end();
+            if !content.is_empty() {
+                markdown.push_str(content);
+                markdown.push('\n');
+            }
+        }
+        markdown
+    }
+
+    /// Register `write_section`, `append_to_section` and `read_document`
+    /// tools that edit this document
+    pub fn register_tools(&self, registry: &mut ToolRegistry) {
+        let section_schema = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "description": "Section heading" },
+                "content": { "type": "string", "description": "Markdown content" }
+            },
+            "required": ["name", "content"]
+        });
+
+        for kind in [EditKind::Write, EditKind::Append] {
+            let (tool_name, description) = match kind {
+                EditKind::Write => (
+                    "write_section",
+                    "Replace a section of the shared document, creating it if needed",
+                ),
+                EditKind::Append => (
+                    "append_to_section",
+                    "Append markdown to a section of the shared document, creating it if needed",
+                ),
+            };
+            let document = self.clone();
+            registry.register(NativeTool::with_context(
+                tool_name,
+                description,
+                section_schema.clone(),
+                move |params, ctx: ToolRunContext| {
+                    let document = document.clone();
+                    async move {
+                        let start = std::time::Instant::now();
+                        let field = |key: &str| {
+                            params
+                                .get(key)
+                                .and_then(|v| v.as_str())
+                                .map(str::to_string)
+                                .ok_or_else(|| {
+                                    ToolError::InvalidParameters(format!(
+                                        "missing '{}' parameter",
+                                        key
+                                    ))
+                                })
+                        };
+                        let name = field("name")?;
+                        let patch = document.edit(&name, kind, field("content")?, ctx.agent_name);
+                        Ok(ToolResult::success(
+                            serde_json::json!({
+                                "section": patch.section,
+                                "revision": patch.revision,
+                            }),
+                            start.elapsed().as_secs_f64() * 1000.0,
+                        ))
+                    }
+                },
+            ));
+        }
+
+        let document = self.clone();
+        registry.register(NativeTool::new(
+            "read_document",
+            "Read the shared document as markdown",
+            serde_json::json!({ "type": "object", "properties": {} }),
+            move |_params| {
+                let document = document.clone();
+                async move {
+                    let start = std::time::Instant::now();
+                    Ok(ToolResult::success(
+                        serde_json::json!({ "markdown": document.to_markdown() }),
+                        start.elapsed().as_secs_f64() * 1000.0,
+                    ))
+                }
+            },
+        ));
+    }
+
+    /// Emit edits to `stream` under `workflow_id` for the run `run_id`
+    ///
+    /// A document belongs to the first run it's attached to: that run may
+    /// attach it again (when resumed), and its sub-workflows (`nested`)
+    /// report their edits under it. Any other run is refused with the
+    /// `run_id` the document belongs to.
+    #[cfg(feature = "workflow")]
+    pub(crate) fn attach(
+        &self,
+        stream: &EventStream,
+        workflow_id: &str,
+        run_id: &str,
+        nested: bool,
+    ) -> Result<(), String> {
+        let mut inner = self.inner.lock().unwrap();
+        match &inner.events {
+            None => {
+                inner.events = Some(Attachment {
+                    stream: stream.clone(),
+                    workflow_id: workflow_id.to_string(),
+                    run_id: run_id.to_string(),
+                });
+                Ok(())
+            }
+            Some(attached) if nested || attached.run_id == run_id => Ok(()),
+            Some(attached) => Err(attached.run_id.clone()),
+        }
+    }
+
+    fn edit(
+        &self,
+        name: &str,
+        kind: EditKind,
+        content: String,
+        author: Option<String>,
+    ) -> DocumentPatch {
+        let mut inner = self.inner.lock().unwrap();
+        let revision = inner.history.len() as u64 + 1;
+
+        let index = match inner.sections.iter().position(|s| s.name == name) {
+            Some(index) => index,
+            None => {
+                inner.sections.push(Section {
+                    name: name.to_string(),
+                    content: String::new(),
+                    revisions: 0,
+                });
+                inner.sections.len() - 1
+            }
+        };
+        let section = &mut inner.sections[index];
+        let old_hash = (section.revisions > 0).then(|| sha256_hex(section.content.as_bytes()));
+        match kind {
+            EditKind::Write => section.content = content,
+            EditKind::Append => section.content.push_str(&content),
+        }
+        section.revisions += 1;
+
+        let patch = DocumentPatch {
+            section: name.to_string(),
+            kind,
+            revision,
+            old_hash,
+            new_hash: sha256_hex(section.content.as_bytes()),
+            content: section.content.clone(),
+        };
+
+        // Emitted under the lock so events arrive in revision order
+        if let Some(Attachment {
+            stream,
+            workflow_id,
+            ..
+        }) = &inner.events
+        {
+            let mut data = serde_json::to_value(&patch).unwrap_or_default();
+            data["document"] = self.title.clone().into();
+            if let Some(author) = &author {
+                data["author"] = author.clone().into();
+            }
+            stream.append(
+                EventScope::System,
+                EventType::Progress,
+                "system:document".to_string(),
+                ComponentStatus::Running,
+                workflow_id.clone(),
+                Some(format!("Document section '{}' changed", name)),
+                data,
+            );
+        }
+
+        inner.history.push(Revision {
+            patch: patch.clone(),
+            author,
+            at: chrono::Utc::now(),
+        });
+        patch
+    }
+}
+
+impl std::fmt::Debug for LiveDocument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let inner = self.inner.lock().unwrap();
+        f.debug_struct("LiveDocument")
+            .field("title", &self.title)
+            .field("output_key", &self.output_key)
+            .field("sections", &inner.sections.len())
+            .field("revisions", &inner.history.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edits_and_markdown() {
+        let document = LiveDocument::new("Q3 Report");
+        let first = document.write_section("Summary", "Revenue grew.");
+        document.write_section("Risks", "Supply chain.");
+        let appended = document.append_to_section("Summary", " Costs fell.");
+
+        assert_eq!(first.old_hash, None);
+        assert_eq!(appended.old_hash, Some(first.new_hash));
+        assert_eq!(appended.revision, 3);
+        assert_eq!(
+            document.section("Summary").as_deref(),
+            Some("Revenue grew. Costs fell.")
+        );
+        assert_eq!(
+            document.to_markdown(),
+            "# Q3 Report\n\n## Summary\n\nRevenue grew. Costs fell.\n\n## Risks\n\nSupply chain.\n"
+        );
+        assert_eq!(document.section_history("Summary").len(), 2);
+    }
+}
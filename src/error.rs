@@ -27,14 +27,61 @@ pub enum RuntimeError {
 
     /// Operation timed out
     Timeout { operation: String, duration_ms: u64 },
+
+    /// A workflow's input failed its schema; nothing was executed
+    InvalidInput {
+        workflow: String,
+        violations: Vec<InputViolation>,
+    },
+
+    /// Several parts of one operation failed
+    Aggregate(AggregateError<RuntimeError>),
 }
 
+/// One way an input failed its schema
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct InputViolation {
+    /// JSON pointer to the offending value ("" for the input itself)
+    pub pointer: String,
+    pub message: String,
+}
+
+/// Failures of several parts of one operation (parallel branches, batch
+/// items), each labeled with the part it came from
+///
+/// Displays as "2 of 3 parallel branches failed: facts: ...; style: ...",
+/// detailing the first few failures and counting the rest.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AggregateError<E> {
+    /// What the parts are, plural ("parallel branches")
+    pub operation: String,
+
+    /// Parts attempted, failed or not
+    pub total: usize,
+
+    /// Failures in the order they were recorded
+    pub failures: Vec<FailedItem<E>>,
+}
+
+/// One failure within an [`AggregateError`]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FailedItem<E> {
+    /// Which part failed: a branch name, an item index
+    pub label: String,
+    pub error: E,
+}
+
+/// Failures an [`AggregateError`] details before counting the remainder
+pub const AGGREGATE_DETAILED_FAILURES: usize = 5;
+
 /// Workflow-specific errors
 #[derive(Debug, Clone)]
 pub struct WorkflowError {
     pub code: WorkflowErrorCode,
     pub message: String,
     pub step_id: Option<String>,
+    /// Position of the offending step, when there is one
+    pub step_index: Option<usize>,
     pub context: Option<String>,
 }
 
@@ -45,6 +92,18 @@ pub enum WorkflowErrorCode {
     CycleDetected,
     MaxIterationsExceeded,
     ConditionalEvaluationFailed,
+    /// The workflow has no steps
+    EmptyWorkflow,
+    /// Two steps share a name
+    DuplicateStepName,
+    /// Chat history options were set without a context manager
+    MissingContextManager,
+    /// The context token budget or input/output ratio is unusable
+    InvalidContextBudget,
+    /// A step's output schema can never match the next step's input schema
+    IncompatibleStepContracts,
+    /// A step can't be copied for another run (see `Step::clone_step`)
+    StepNotCloneable,
 }
 
 /// Agent-specific errors
@@ -156,6 +215,66 @@ impl fmt::Display for RuntimeError {
                     operation, duration_ms
                 )
             }
+            RuntimeError::InvalidInput {
+                workflow,
+                violations,
+            } => {
+                write!(f, "Invalid input for workflow '{}':", workflow)?;
+                for violation in violations {
+                    write!(f, " {};", violation)?;
+                }
+                Ok(())
+            }
+            RuntimeError::Aggregate(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl fmt::Display for InputViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.pointer.is_empty() {
+            write!(f, "input {}", self.message)
+        } else {
+            write!(f, "{} {}", self.pointer, self.message)
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for AggregateError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} {} failed:",
+            self.failures.len(),
+            self.total,
+            self.operation
+        )?;
+        for (i, failure) in self
+            .failures
+            .iter()
+            .take(AGGREGATE_DETAILED_FAILURES)
+            .enumerate()
+        {
+            let separator = if i == 0 { " " } else { "; " };
+            write!(f, "{}{}", separator, failure)?;
+        }
+        if self.failures.len() > AGGREGATE_DETAILED_FAILURES {
+            write!(
+                f,
+                "; and {} more",
+                self.failures.len() - AGGREGATE_DETAILED_FAILURES
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for FailedItem<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.label.is_empty() {
+            write!(f, "{}", self.error)
+        } else {
+            write!(f, "{}: {}", self.label, self.error)
         }
     }
 }
@@ -163,8 +282,11 @@ impl fmt::Display for RuntimeError {
 impl fmt::Display for WorkflowError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "[{:?}] {}", self.code, self.message)?;
-        if let Some(step_id) = &self.step_id {
-            write!(f, " (step: {})", step_id)?;
+        match (&self.step_id, self.step_index) {
+            (Some(step_id), Some(index)) => write!(f, " (step {}: {})", index, step_id)?,
+            (Some(step_id), None) => write!(f, " (step: {})", step_id)?,
+            (None, Some(index)) => write!(f, " (step {})", index)?,
+            (None, None) => {}
         }
         if let Some(context) = &self.context {
             write!(f, " - {}", context)?;
@@ -226,13 +348,160 @@ impl fmt::Display for ConfigError {
 }
 
 // Implement std::error::Error
-impl std::error::Error for RuntimeError {}
+impl std::error::Error for RuntimeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RuntimeError::Aggregate(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// The source is the first failure
+impl<E: std::error::Error + 'static> std::error::Error for AggregateError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.first()
+            .map(|e| e as &(dyn std::error::Error + 'static))
+    }
+}
 impl std::error::Error for WorkflowError {}
 impl std::error::Error for AgentError {}
 impl std::error::Error for LlmError {}
 impl std::error::Error for ToolError {}
 impl std::error::Error for ConfigError {}
 
+impl<E> AggregateError<E> {
+    /// An empty aggregate for `total` parts of `operation`
+    pub fn new(operation: impl Into<String>, total: usize) -> Self {
+        Self {
+            operation: operation.into(),
+            total,
+            failures: Vec::new(),
+        }
+    }
+
+    /// Gather the failures of labeled results, returning the successes in
+    /// order if there were none
+    pub fn collect<T, L: Into<String>>(
+        operation: impl Into<String>,
+        results: impl IntoIterator<Item = (L, Result<T, E>)>,
+    ) -> Result<Vec<T>, Self> {
+        let mut aggregate = Self::new(operation, 0);
+        let mut successes = Vec::new();
+        for (label, result) in results {
+            aggregate.total += 1;
+            match result {
+                Ok(value) => successes.push(value),
+                Err(error) => aggregate.push(label, error),
+            }
+        }
+        aggregate.into_result().map(|_| successes)
+    }
+
+    /// Record a failure of the part labeled `label`
+    pub fn push(&mut self, label: impl Into<String>, error: E) {
+        self.failures.push(FailedItem {
+            label: label.into(),
+            error,
+        });
+    }
+
+    /// Number of failures
+    pub fn len(&self) -> usize {
+        self.failures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// The first failure recorded
+    pub fn first(&self) -> Option<&E> {
+        self.failures.first().map(|f| &f.error)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &FailedItem<E>> {
+        self.failures.iter()
+    }
+
+    /// `Ok` if nothing failed, otherwise the aggregate as the error
+    pub fn into_result(self) -> Result<(), Self> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+
+    /// The failures as labeled results, e.g. for reporting per part
+    pub fn into_results<T>(self) -> Vec<(String, Result<T, E>)> {
+        self.failures
+            .into_iter()
+            .map(|f| (f.label, Err(f.error)))
+            .collect()
+    }
+
+    /// Split into the failures matching `predicate` (say, retryable ones)
+    /// and the rest; both keep the operation and total
+    pub fn partition(self, predicate: impl Fn(&E) -> bool) -> (Self, Self) {
+        let (matching, rest) = self.failures.into_iter().partition(|f| predicate(&f.error));
+        (
+            Self {
+                operation: self.operation.clone(),
+                total: self.total,
+                failures: matching,
+            },
+            Self {
+                operation: self.operation,
+                total: self.total,
+                failures: rest,
+            },
+        )
+    }
+
+    /// Convert every error, keeping labels and counts
+    pub fn map<F>(self, mut f: impl FnMut(E) -> F) -> AggregateError<F> {
+        AggregateError {
+            operation: self.operation,
+            total: self.total,
+            failures: self
+                .failures
+                .into_iter()
+                .map(|item| FailedItem {
+                    label: item.label,
+                    error: f(item.error),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<AggregateError<RuntimeError>> for RuntimeError {
+    fn from(e: AggregateError<RuntimeError>) -> Self {
+        RuntimeError::Aggregate(e)
+    }
+}
+
+impl WorkflowError {
+    /// An error about the workflow as a whole
+    pub fn new(code: WorkflowErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            step_id: None,
+            step_index: None,
+            context: None,
+        }
+    }
+
+    /// Point the error at the step at `index` named `name`
+    pub fn at_step(mut self, index: usize, name: impl Into<String>) -> Self {
+        self.step_index = Some(index);
+        self.step_id = Some(name.into());
+        self
+    }
+}
+
 // Helper methods for LlmError
 impl LlmError {
     /// Check if this error is retryable (network issues, rate limits, server errors)
@@ -298,6 +567,34 @@ impl From<LlmError> for RuntimeError {
     }
 }
 
+impl From<crate::llm::LlmError> for LlmError {
+    fn from(e: crate::llm::LlmError) -> Self {
+        use crate::llm::LlmError as ClientError;
+        let code = match &e {
+            ClientError::ApiError(_) if e.is_retryable() => LlmErrorCode::ServerError,
+            ClientError::ApiError(_) => LlmErrorCode::InvalidResponse,
+            ClientError::NetworkError(_) => LlmErrorCode::NetworkError,
+            ClientError::InvalidRequest(_) => LlmErrorCode::InvalidRequest,
+            ClientError::RateLimitExceeded => LlmErrorCode::RateLimitExceeded,
+            ClientError::AuthenticationFailed(_) => LlmErrorCode::AuthenticationFailed,
+            ClientError::ParseError(_) => LlmErrorCode::ParseError,
+        };
+        Self {
+            code,
+            message: e.to_string(),
+            provider: None,
+            model: None,
+            retryable: e.is_retryable(),
+        }
+    }
+}
+
+impl From<crate::llm::LlmError> for RuntimeError {
+    fn from(e: crate::llm::LlmError) -> Self {
+        RuntimeError::Llm(e.into())
+    }
+}
+
 impl From<ToolError> for RuntimeError {
     fn from(e: ToolError) -> Self {
         RuntimeError::Tool(e)
@@ -309,3 +606,92 @@ impl From<ConfigError> for RuntimeError {
         RuntimeError::Config(e)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aggregate(failures: usize) -> AggregateError<String> {
+        let mut aggregate = AggregateError::new("items", failures + 1);
+        for i in 0..failures {
+            aggregate.push(format!("item {}", i), format!("broke {}", i));
+        }
+        aggregate
+    }
+
+    #[test]
+    fn test_display_truncates_after_first_failures() {
+        assert_eq!(
+            aggregate(2).to_string(),
+            "2 of 3 items failed: item 0: broke 0; item 1: broke 1"
+        );
+        assert_eq!(
+            aggregate(8).to_string(),
+            "8 of 9 items failed: item 0: broke 0; item 1: broke 1; item 2: broke 2; \
+             item 3: broke 3; item 4: broke 4; and 3 more"
+        );
+
+        // Errors are converted, labels and counts kept
+        let wrapped = RuntimeError::Aggregate(aggregate(1).map(|_| RuntimeError::Timeout {
+            operation: "fetch".to_string(),
+            duration_ms: 10,
+        }));
+        assert_eq!(
+            wrapped.to_string(),
+            "1 of 2 items failed: item 0: Operation 'fetch' timed out after 10ms"
+        );
+    }
+
+    #[test]
+    fn test_source_is_first_failure() {
+        let mut aggregate = AggregateError::new("calls", 2);
+        aggregate.push("a", RuntimeError::from(LlmError::network("reset")));
+        aggregate.push("b", RuntimeError::from(LlmError::rate_limit("slow down")));
+        let error = RuntimeError::from(aggregate);
+
+        let aggregate = std::error::Error::source(&error).unwrap();
+        let first = aggregate.source().unwrap();
+        assert_eq!(
+            first.to_string(),
+            "LLM error: [NetworkError] reset (retryable)"
+        );
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let original = aggregate(2);
+        let json = serde_json::to_value(&original).unwrap();
+        assert_eq!(json["failures"][1]["label"], "item 1");
+        let restored: AggregateError<String> = serde_json::from_value(json).unwrap();
+        assert_eq!(restored, original);
+    }
+
+    #[test]
+    fn test_partition_and_collect() {
+        let mut aggregate = AggregateError::new("calls", 4);
+        aggregate.push("a", LlmError::network("reset"));
+        aggregate.push("b", LlmError::server_error("500"));
+        aggregate.push(
+            "c",
+            LlmError {
+                code: LlmErrorCode::AuthenticationFailed,
+                message: "bad key".to_string(),
+                provider: None,
+                model: None,
+                retryable: false,
+            },
+        );
+        let (retryable, fatal) = aggregate.partition(|e| e.retryable);
+        assert_eq!(retryable.len(), 2);
+        assert_eq!(fatal.iter().next().unwrap().label, "c");
+        assert_eq!(fatal.total, 4);
+
+        let all_ok = AggregateError::<String>::collect("items", [("x", Ok(1)), ("y", Ok(2))]);
+        assert_eq!(all_ok, Ok(vec![1, 2]));
+        let failed = AggregateError::collect("items", [("x", Ok(1)), ("y", Err("no"))]);
+        assert_eq!(
+            failed.unwrap_err().to_string(),
+            "1 of 2 items failed: y: no"
+        );
+    }
+}
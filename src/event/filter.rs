@@ -0,0 +1,169 @@
+//! Declarative event matching.
+//!
+//! An [`EventFilter`] with no criteria matches everything. Each non-empty
+//! list narrows the match; within a list any entry may match. Workflow and
+//! component patterns accept `*` as a wildcard (`prod-*`, `*:step:0`).
+
+use super::{Event, EventScope, EventType};
+use serde::{Deserialize, Serialize};
+
+/// Which events a subscriber wants
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventFilter {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<EventScope>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub event_types: Vec<EventType>,
+
+    /// Patterns for the workflow id; a nested run's events also match on
+    /// their parent workflow
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub workflows: Vec<String>,
+
+    /// Patterns for the component id
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<String>,
+}
+
+impl EventFilter {
+    /// A filter that matches every event
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn scope(mut self, scope: EventScope) -> Self {
+        self.scopes.push(scope);
+        self
+    }
+
+    pub fn event_type(mut self, event_type: EventType) -> Self {
+        self.event_types.push(event_type);
+        self
+    }
+
+    pub fn workflow(mut self, pattern: impl Into<String>) -> Self {
+        self.workflows.push(pattern.into());
+        self
+    }
+
+    pub fn component(mut self, pattern: impl Into<String>) -> Self {
+        self.components.push(pattern.into());
+        self
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        let workflow_matches = |pattern: &String| {
+            glob_match(pattern, &event.workflow_id)
+                || event
+                    .parent_workflow_id
+                    .as_deref()
+                    .is_some_and(|parent| glob_match(pattern, parent))
+        };
+
+        (self.scopes.is_empty() || self.scopes.contains(&event.scope))
+            && (self.event_types.is_empty() || self.event_types.contains(&event.event_type))
+            && (self.workflows.is_empty() || self.workflows.iter().any(workflow_matches))
+            && (self.components.is_empty()
+                || self
+                    .components
+                    .iter()
+                    .any(|pattern| glob_match(pattern, &event.component_id)))
+    }
+}
+
+/// Whole-string match where `*` stands for any run of characters
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard: the prefix must be the whole text
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::ComponentStatus;
+    use serde_json::json;
+
+    fn event(scope: EventScope, event_type: EventType, component: &str, workflow: &str) -> Event {
+        Event::new(
+            0,
+            scope,
+            event_type,
+            component.to_string(),
+            ComponentStatus::Running,
+            workflow.to_string(),
+            None,
+            json!({}),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("prod-*", "prod-billing"));
+        assert!(glob_match("prod-*", "prod-"));
+        assert!(!glob_match("prod-*", "staging-prod-billing"));
+        assert!(glob_match("*-billing", "prod-billing"));
+        assert!(glob_match("a*b*c", "a-x-b-y-c"));
+        assert!(!glob_match("a*b*c", "a-x-c-y-b"));
+        assert!(!glob_match("ab*ba", "aba"));
+        assert!(glob_match("exact", "exact"));
+        assert!(!glob_match("exact", "exactly"));
+        assert!(glob_match("*", ""));
+    }
+
+    #[test]
+    fn test_filter_criteria_combine() {
+        let filter = EventFilter::new()
+            .scope(EventScope::Tool)
+            .event_type(EventType::Failed)
+            .workflow("prod-*");
+
+        assert!(filter.matches(&event(
+            EventScope::Tool,
+            EventType::Failed,
+            "search",
+            "prod-a"
+        )));
+        assert!(!filter.matches(&event(
+            EventScope::Tool,
+            EventType::Failed,
+            "search",
+            "dev-a"
+        )));
+        assert!(!filter.matches(&event(
+            EventScope::Tool,
+            EventType::Completed,
+            "search",
+            "prod-a"
+        )));
+        assert!(!filter.matches(&event(
+            EventScope::Agent,
+            EventType::Failed,
+            "bot",
+            "prod-a"
+        )));
+
+        let mut nested = event(EventScope::Tool, EventType::Failed, "search", "child");
+        nested.parent_workflow_id = Some("prod-a".to_string());
+        assert!(filter.matches(&nested));
+
+        assert!(EventFilter::new().matches(&nested));
+        assert!(!EventFilter::new().component("fetch*").matches(&nested));
+    }
+}
@@ -1,14 +1,28 @@
 use crate::types::{EventId, EventOffset, JsonValue, WorkflowId};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 use tokio::sync::broadcast;
 
+pub mod filter;
+pub mod query;
+pub mod redaction;
+pub mod sampling;
+pub mod subscription;
 #[cfg(test)]
 mod tests;
+pub mod webhook;
+
+pub use filter::EventFilter;
+use query::History;
+pub use query::{EventQuery, EventStats};
+pub use redaction::RedactionRules;
+use sampling::{Admission, EventMetrics, SamplingPolicy, TraceDecision, TraceSampler};
+pub use subscription::EventSubscription;
 
 /// Event scope - which component is emitting the event
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum EventScope {
     Workflow,
@@ -20,7 +34,7 @@ pub enum EventScope {
 }
 
 /// Event type - standard lifecycle events
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum EventType {
     Started,
@@ -68,6 +82,11 @@ pub struct Event {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub parent_workflow_id: Option<WorkflowId>,
 
+    /// The workflows `workflow_id` runs inside, outermost first; empty for
+    /// a top-level run. Set by the stream for sub-workflows of a runtime.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub workflow_path: Vec<WorkflowId>,
+
     /// Optional human-readable message
     #[serde(skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
@@ -101,6 +120,7 @@ impl Event {
             status,
             workflow_id,
             parent_workflow_id: None,
+            workflow_path: Vec::new(),
             message,
             data,
         })
@@ -131,11 +151,22 @@ impl Event {
             status,
             workflow_id,
             parent_workflow_id,
+            workflow_path: Vec::new(),
             message,
             data,
         })
     }
 
+    /// How deep in sub-workflows the event's workflow runs; 0 at the top
+    pub fn depth(&self) -> usize {
+        self.workflow_path.len()
+    }
+
+    /// The top-level run the event belongs to
+    pub fn root_workflow_id(&self) -> &WorkflowId {
+        self.workflow_path.first().unwrap_or(&self.workflow_id)
+    }
+
     /// Validate component_id follows the required format for the scope
     fn validate_component_id(scope: &EventScope, component_id: &str) -> Result<(), String> {
         if component_id.is_empty() {
@@ -148,16 +179,28 @@ impl Event {
                 Ok(())
             }
             EventScope::WorkflowStep => {
-                // Must match: name:step:N
+                // Must match: name:step:N, name:step:N.M for a branch of a
+                // parallel step, or name:step:N:item:I for an item of a
+                // for-each step
                 let parts: Vec<&str> = component_id.split(':').collect();
-                if parts.len() != 3 || parts[1] != "step" {
+                let item = match parts.as_slice() {
+                    [_, "step", _] => None,
+                    [_, "step", _, "item", item] => Some(*item),
+                    _ => {
+                        return Err(format!(
+                            "WorkflowStep component_id must be 'workflow_name:step:N', got '{}'",
+                            component_id
+                        ))
+                    }
+                };
+                if item.is_some_and(|i| i.parse::<usize>().is_err()) {
                     return Err(format!(
-                        "WorkflowStep component_id must be 'workflow_name:step:N', got '{}'",
+                        "WorkflowStep item index must be a number, got '{}'",
                         component_id
                     ));
                 }
-                // Validate N is a number
-                if parts[2].parse::<usize>().is_err() {
+                // Validate N (and each branch index) is a number
+                if parts[2].split('.').any(|n| n.parse::<usize>().is_err()) {
                     return Err(format!(
                         "WorkflowStep index must be a number, got '{}'",
                         parts[2]
@@ -206,16 +249,26 @@ impl Event {
     }
 }
 
+/// A run's workflow and the workflows it runs inside, outermost first
+type Lineage = (WorkflowId, Vec<WorkflowId>);
+
 /// Event stream with broadcast capability for real-time subscribers
 pub struct EventStream {
     /// Broadcast sender for real-time event streaming
     sender: broadcast::Sender<Event>,
 
-    /// Historical events for replay (thread-safe)
-    history: Arc<RwLock<Vec<Event>>>,
+    /// Historical events for replay and queries (thread-safe)
+    history: Arc<RwLock<History>>,
 
-    /// Next offset to assign
-    next_offset: Arc<RwLock<EventOffset>>,
+    /// Trace sampling, if enabled
+    sampler: Option<Arc<TraceSampler>>,
+
+    /// Applied to every event before it is stored or broadcast
+    redaction: Option<Arc<RedactionRules>>,
+
+    /// The workflow of each running run, by run ID, with the workflows it
+    /// runs inside
+    lineage: Arc<RwLock<HashMap<String, Lineage>>>,
 }
 
 impl EventStream {
@@ -230,16 +283,113 @@ impl EventStream {
 
         Self {
             sender,
-            history: Arc::new(RwLock::new(Vec::new())),
-            next_offset: Arc::new(RwLock::new(0)),
+            history: Arc::new(RwLock::new(History::default())),
+            sampler: None,
+            redaction: None,
+            lineage: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Only emit full detail for the runs `policy` samples; see [`sampling`]
+    pub fn with_sampling(mut self, policy: SamplingPolicy) -> Self {
+        self.sampler = Some(Arc::new(TraceSampler::new(policy)));
+        self
+    }
+
+    /// Redact every event with `rules` before it is stored or broadcast
+    pub fn with_redaction(mut self, rules: RedactionRules) -> Self {
+        self.redaction = Some(Arc::new(rules));
+        self
+    }
+
+    /// Event counts from before sampling; `None` without a sampling policy
+    pub fn metrics(&self) -> Option<EventMetrics> {
+        self.sampler.as_ref().map(|s| s.metrics())
+    }
+
+    /// Make the sampling decision for the run `run_id` of `workflow_id`,
+    /// which is starting. Events from the run are attributed to it via
+    /// [`sampling::in_run`].
+    pub fn begin_trace(
+        &self,
+        run_id: &str,
+        workflow_id: &str,
+        labels: &[String],
+    ) -> Option<TraceDecision> {
+        self.sampler
+            .as_ref()
+            .map(|s| s.begin(run_id, workflow_id, labels))
+    }
+
+    /// Settle the finished run `run_id`'s sampling decision, delivering its
+    /// buffered detail if tail sampling upgrades it
+    pub fn finish_trace(&self, run_id: &str, failed: bool) -> Option<TraceDecision> {
+        let (decision, buffered) = self.sampler.as_ref()?.finish(run_id, failed)?;
+        for event in buffered {
+            self.publish(event);
         }
+        Some(decision)
+    }
+
+    /// Start attributing the events of the run `run_id` of `workflow_id` to
+    /// its parent workflow, whose run is `parent_run_id` when it's running
+    /// here, and the parent's ancestors; returns the new run's path,
+    /// outermost first
+    #[cfg(feature = "workflow")]
+    pub(crate) fn enter_workflow(
+        &self,
+        run_id: &str,
+        workflow_id: &str,
+        parent_workflow_id: Option<&str>,
+        parent_run_id: Option<&str>,
+    ) -> Vec<WorkflowId> {
+        let mut path = parent_run_id
+            .map(|parent| self.workflow_path(parent))
+            .unwrap_or_default();
+        path.extend(parent_workflow_id.map(str::to_string));
+        self.lineage
+            .write()
+            .unwrap()
+            .insert(run_id.to_string(), (workflow_id.to_string(), path.clone()));
+        path
+    }
+
+    /// Undo the [`enter_workflow`](Self::enter_workflow) of `run_id`
+    #[cfg(feature = "workflow")]
+    pub(crate) fn leave_workflow(&self, run_id: &str) {
+        self.lineage.write().unwrap().remove(run_id);
+    }
+
+    /// The workflows the running run `run_id` runs inside, outermost first;
+    /// empty for a top-level or finished run
+    pub fn workflow_path(&self, run_id: &str) -> Vec<WorkflowId> {
+        self.lineage
+            .read()
+            .unwrap()
+            .get(run_id)
+            .map(|(_, path)| path.clone())
+            .unwrap_or_default()
+    }
+
+    /// The path of the run an event of `workflow_id` comes from: the current
+    /// task's run, or else a running run of that workflow
+    fn event_path(&self, workflow_id: &str) -> Vec<WorkflowId> {
+        let lineage = self.lineage.read().unwrap();
+        sampling::current_run()
+            .and_then(|run_id| lineage.get(&run_id))
+            .filter(|(id, _)| id == workflow_id)
+            .or_else(|| lineage.values().find(|(id, _)| id == workflow_id))
+            .map(|(_, path)| path.clone())
+            .unwrap_or_default()
     }
 
     /// Append a new event and broadcast to all subscribers
     ///
-    /// Events are emitted asynchronously in a spawned task to avoid blocking
-    /// agent execution. Returns a JoinHandle that can be awaited if the caller
-    /// needs to ensure the event was processed or needs the Event object.
+    /// The event is assigned its offset, stored and broadcast before this
+    /// returns, so events appended one after another keep that order in
+    /// history and for subscribers, and a dropped handle loses nothing.
+    /// Returns a JoinHandle that can be awaited if the caller needs the
+    /// Event object.
     ///
     /// # Examples
     /// ```no_run
@@ -297,8 +447,9 @@ impl EventStream {
 
     /// Append event with optional parent workflow ID
     ///
-    /// Events are emitted asynchronously to avoid blocking execution.
-    /// Returns a JoinHandle that resolves to the created Event.
+    /// The event is stored and broadcast before this returns, so offsets
+    /// follow the order of the calls emitting them. Returns a JoinHandle
+    /// that resolves to the created Event.
     #[allow(clippy::too_many_arguments)]
     pub fn append_with_parent(
         &self,
@@ -311,40 +462,58 @@ impl EventStream {
         message: Option<String>,
         data: JsonValue,
     ) -> tokio::task::JoinHandle<Result<Event, String>> {
-        let sender = self.sender.clone();
-        let history = self.history.clone();
-        let next_offset = self.next_offset.clone();
-
-        // Spawn async task - never blocks the caller
-        tokio::spawn(async move {
-            // Get and increment offset atomically
-            let offset = {
-                let mut next_offset = next_offset.write().unwrap();
-                let current = *next_offset;
-                *next_offset += 1;
-                current
-            };
-
-            let event = Event::with_parent(
-                offset,
-                scope,
-                event_type,
-                component_id,
-                status,
-                workflow_id,
-                parent_workflow_id,
-                message,
-                data,
-            )?;
-
-            // Store in history
-            history.write().unwrap().push(event.clone());
-
-            // Broadcast to subscribers (ignore if no active receivers)
-            let _ = sender.send(event.clone());
-
-            Ok(event)
-        })
+        let workflow_path = self.event_path(&workflow_id);
+        let parent_workflow_id = parent_workflow_id.or_else(|| workflow_path.last().cloned());
+        if let Some(sampler) = &self.sampler {
+            match sampler.admit(&workflow_id, &scope, &event_type) {
+                Admission::Deliver => {}
+                Admission::Buffer(run_id) => {
+                    // Offset is assigned if and when the event is delivered
+                    let result = Event::with_parent(
+                        0,
+                        scope,
+                        event_type,
+                        component_id,
+                        status,
+                        workflow_id,
+                        parent_workflow_id,
+                        message,
+                        data,
+                    )
+                    .map(|mut event| {
+                        event.workflow_path = workflow_path;
+                        sampler.buffer(&run_id, event)
+                    })
+                    .and(Err("Event buffered by trace sampling".to_string()));
+                    return tokio::spawn(async move { result });
+                }
+                Admission::Drop(run_id) => {
+                    sampler.drop_event(&run_id);
+                    return tokio::spawn(async {
+                        Err("Event dropped by trace sampling".to_string())
+                    });
+                }
+            }
+        }
+
+        // An invalid event takes no offset, leaving no gap in history
+        let mut event = match Event::with_parent(
+            0,
+            scope,
+            event_type,
+            component_id,
+            status,
+            workflow_id,
+            parent_workflow_id,
+            message,
+            data,
+        ) {
+            Ok(event) => event,
+            Err(e) => return tokio::spawn(async move { Err(e) }),
+        };
+        event.workflow_path = workflow_path;
+        let event = self.publish(event);
+        tokio::spawn(async move { Ok(event) })
     }
 
     // Helper methods for common event patterns
@@ -405,6 +574,196 @@ impl EventStream {
         )
     }
 
+    /// Emit Agent::Started event for a critic reviewing `agent_name`'s
+    /// output; the component id is `agent_name:critic`
+    pub fn critic_started(
+        &self,
+        agent_name: &str,
+        workflow_id: WorkflowId,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::Agent,
+            EventType::Started,
+            format!("{}:critic", agent_name),
+            ComponentStatus::Running,
+            workflow_id,
+            None,
+            data,
+        )
+    }
+
+    /// Emit Agent::Completed event carrying a critic's verdict
+    pub fn critic_completed(
+        &self,
+        agent_name: &str,
+        workflow_id: WorkflowId,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::Agent,
+            EventType::Completed,
+            format!("{}:critic", agent_name),
+            ComponentStatus::Completed,
+            workflow_id,
+            None,
+            data,
+        )
+    }
+
+    /// Emit Agent::Failed event for a critic that gave no verdict
+    pub fn critic_failed(
+        &self,
+        agent_name: &str,
+        workflow_id: WorkflowId,
+        error: &str,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::Agent,
+            EventType::Failed,
+            format!("{}:critic", agent_name),
+            ComponentStatus::Failed,
+            workflow_id,
+            Some(error.to_string()),
+            data,
+        )
+    }
+
+    /// Emit Agent::Started event for an agent critiquing its own answer
+    pub fn reflection_started(
+        &self,
+        agent_name: &str,
+        workflow_id: WorkflowId,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::Agent,
+            EventType::Started,
+            format!("{}:reflection", agent_name),
+            ComponentStatus::Running,
+            workflow_id,
+            None,
+            data,
+        )
+    }
+
+    /// Emit Agent::Completed event carrying a self-critique's verdict
+    pub fn reflection_completed(
+        &self,
+        agent_name: &str,
+        workflow_id: WorkflowId,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::Agent,
+            EventType::Completed,
+            format!("{}:reflection", agent_name),
+            ComponentStatus::Completed,
+            workflow_id,
+            None,
+            data,
+        )
+    }
+
+    /// Emit Agent::Failed event for a self-critique request that failed
+    pub fn reflection_failed(
+        &self,
+        agent_name: &str,
+        workflow_id: WorkflowId,
+        error: &str,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::Agent,
+            EventType::Failed,
+            format!("{}:reflection", agent_name),
+            ComponentStatus::Failed,
+            workflow_id,
+            Some(error.to_string()),
+            data,
+        )
+    }
+
+    /// Emit Agent::Progress event for a guardrail that replaced the input
+    /// or output
+    pub fn guardrail_modified(
+        &self,
+        agent_name: &str,
+        workflow_id: WorkflowId,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::Agent,
+            EventType::Progress,
+            format!("{}:guardrail", agent_name),
+            ComponentStatus::Running,
+            workflow_id,
+            None,
+            data,
+        )
+    }
+
+    /// Emit Agent::Failed event for a guardrail that blocked the input or
+    /// output
+    pub fn guardrail_blocked(
+        &self,
+        agent_name: &str,
+        workflow_id: WorkflowId,
+        reason: &str,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::Agent,
+            EventType::Failed,
+            format!("{}:guardrail", agent_name),
+            ComponentStatus::Failed,
+            workflow_id,
+            Some(reason.to_string()),
+            data,
+        )
+    }
+
+    /// Emit Agent::Progress event for `agent_name` handing the conversation
+    /// off, or for it taking a conversation handed to it; the component id
+    /// is `agent_name:handoff`
+    pub fn agent_handoff(
+        &self,
+        agent_name: &str,
+        workflow_id: WorkflowId,
+        message: String,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::Agent,
+            EventType::Progress,
+            format!("{}:handoff", agent_name),
+            ComponentStatus::Running,
+            workflow_id,
+            Some(message),
+            data,
+        )
+    }
+
+    /// Emit Agent::Canceled event
+    pub fn agent_canceled(
+        &self,
+        agent_name: &str,
+        workflow_id: WorkflowId,
+        reason: &str,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::Agent,
+            EventType::Canceled,
+            agent_name.to_string(),
+            ComponentStatus::Canceled,
+            workflow_id,
+            Some(reason.to_string()),
+            data,
+        )
+    }
+
     /// Emit LlmRequest::Started event
     pub fn llm_started(
         &self,
@@ -425,12 +784,17 @@ impl EventStream {
     }
 
     /// Emit LlmRequest::Progress event (streaming chunk)
+    ///
+    /// `tools_offered` tells whether the request offered tools: if not, the
+    /// response is the agent's answer; if so, `has_tool_calls` on the
+    /// Completed event tells.
     pub fn llm_progress(
         &self,
         agent_name: &str,
         iteration: usize,
         workflow_id: WorkflowId,
         chunk: String,
+        tools_offered: bool,
     ) -> tokio::task::JoinHandle<Result<Event, String>> {
         self.append(
             EventScope::LlmRequest,
@@ -439,7 +803,11 @@ impl EventStream {
             ComponentStatus::Running,
             workflow_id,
             None,
-            serde_json::json!({ "chunk": chunk }),
+            serde_json::json!({
+                "chunk": chunk,
+                "iteration": iteration,
+                "tools_offered": tools_offered,
+            }),
         )
     }
 
@@ -469,6 +837,7 @@ impl EventStream {
         iteration: usize,
         workflow_id: WorkflowId,
         error: &str,
+        data: JsonValue,
     ) -> tokio::task::JoinHandle<Result<Event, String>> {
         self.append(
             EventScope::LlmRequest,
@@ -477,10 +846,52 @@ impl EventStream {
             ComponentStatus::Failed,
             workflow_id,
             Some(error.to_string()),
+            data,
+        )
+    }
+
+    /// Emit LlmRequest::Canceled for a request abandoned mid-stream
+    pub fn llm_canceled(
+        &self,
+        agent_name: &str,
+        iteration: usize,
+        workflow_id: WorkflowId,
+        reason: &str,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::LlmRequest,
+            EventType::Canceled,
+            format!("{}:llm:{}", agent_name, iteration),
+            ComponentStatus::Canceled,
+            workflow_id,
+            Some(reason.to_string()),
             serde_json::json!({}),
         )
     }
 
+    /// Emit LlmRequest::Failed for an attempt that will be retried
+    pub fn llm_attempt_failed(
+        &self,
+        agent_name: &str,
+        iteration: usize,
+        workflow_id: WorkflowId,
+        attempt: u32,
+        error: &str,
+        mut data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        data["attempt"] = attempt.into();
+        data["will_retry"] = true.into();
+        self.append(
+            EventScope::LlmRequest,
+            EventType::Failed,
+            format!("{}:llm:{}", agent_name, iteration),
+            ComponentStatus::Running,
+            workflow_id,
+            Some(format!("Attempt {} failed, retrying: {}", attempt, error)),
+            data,
+        )
+    }
+
     /// Emit Tool::Started event
     pub fn tool_started(
         &self,
@@ -518,6 +929,30 @@ impl EventStream {
         )
     }
 
+    /// Emit Tool::Progress event announcing an automatic retry
+    ///
+    /// `attempt` is the attempt about to start (2 for the first retry).
+    pub fn tool_retrying(
+        &self,
+        tool_name: &str,
+        workflow_id: WorkflowId,
+        attempt: u32,
+        error: &str,
+        mut data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        data["attempt"] = attempt.into();
+        data["error"] = error.into();
+        self.append(
+            EventScope::Tool,
+            EventType::Progress,
+            tool_name.to_string(),
+            ComponentStatus::Running,
+            workflow_id,
+            Some(format!("Retrying (attempt {}) after: {}", attempt, error)),
+            data,
+        )
+    }
+
     /// Emit Tool::Completed event
     pub fn tool_completed(
         &self,
@@ -555,6 +990,63 @@ impl EventStream {
         )
     }
 
+    /// Emit Tool::Canceled event
+    pub fn tool_canceled(
+        &self,
+        tool_name: &str,
+        workflow_id: WorkflowId,
+        reason: &str,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::Tool,
+            EventType::Canceled,
+            tool_name.to_string(),
+            ComponentStatus::Canceled,
+            workflow_id,
+            Some(reason.to_string()),
+            data,
+        )
+    }
+
+    /// Emit Tool::Progress event for a tool an agent starts offering the
+    /// LLM mid-run
+    pub fn tool_registered(
+        &self,
+        tool_name: &str,
+        workflow_id: WorkflowId,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::Tool,
+            EventType::Progress,
+            tool_name.to_string(),
+            ComponentStatus::Pending,
+            workflow_id,
+            Some(format!("Tool '{}' registered", tool_name)),
+            data,
+        )
+    }
+
+    /// Emit Tool::Progress event for a tool an agent stops offering the
+    /// LLM mid-run
+    pub fn tool_unregistered(
+        &self,
+        tool_name: &str,
+        workflow_id: WorkflowId,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::Tool,
+            EventType::Progress,
+            tool_name.to_string(),
+            ComponentStatus::Completed,
+            workflow_id,
+            Some(format!("Tool '{}' unregistered", tool_name)),
+            data,
+        )
+    }
+
     /// Emit Workflow::Started event
     pub fn workflow_started(
         &self,
@@ -607,6 +1099,24 @@ impl EventStream {
         )
     }
 
+    /// Emit Workflow::Canceled event
+    pub fn workflow_canceled(
+        &self,
+        workflow_name: &str,
+        reason: &str,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::Workflow,
+            EventType::Canceled,
+            workflow_name.to_string(),
+            ComponentStatus::Canceled,
+            workflow_name.to_string(),
+            Some(reason.to_string()),
+            data,
+        )
+    }
+
     /// Emit WorkflowStep::Started event
     pub fn step_started(
         &self,
@@ -643,6 +1153,45 @@ impl EventStream {
         )
     }
 
+    /// Emit WorkflowStep::Progress event, e.g. a loop step's iteration
+    pub fn step_progress(
+        &self,
+        workflow_name: &str,
+        step_index: usize,
+        message: &str,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::WorkflowStep,
+            EventType::Progress,
+            format!("{}:step:{}", workflow_name, step_index),
+            ComponentStatus::Running,
+            workflow_name.to_string(),
+            Some(message.to_string()),
+            data,
+        )
+    }
+
+    /// Emit WorkflowStep::Progress event with `Pending` status, for a step
+    /// waiting on something outside the run, e.g. an approval
+    pub fn step_pending(
+        &self,
+        workflow_name: &str,
+        step_index: usize,
+        message: &str,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::WorkflowStep,
+            EventType::Progress,
+            format!("{}:step:{}", workflow_name, step_index),
+            ComponentStatus::Pending,
+            workflow_name.to_string(),
+            Some(message.to_string()),
+            data,
+        )
+    }
+
     /// Emit WorkflowStep::Failed event
     pub fn step_failed(
         &self,
@@ -662,39 +1211,212 @@ impl EventStream {
         )
     }
 
+    /// Emit WorkflowStep::Started event for one branch of a parallel step
+    pub fn branch_started(
+        &self,
+        workflow_name: &str,
+        step_index: usize,
+        branch_index: usize,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::WorkflowStep,
+            EventType::Started,
+            format!("{}:step:{}.{}", workflow_name, step_index, branch_index),
+            ComponentStatus::Running,
+            workflow_name.to_string(),
+            None,
+            data,
+        )
+    }
+
+    /// Emit WorkflowStep::Completed event for one branch of a parallel step
+    pub fn branch_completed(
+        &self,
+        workflow_name: &str,
+        step_index: usize,
+        branch_index: usize,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::WorkflowStep,
+            EventType::Completed,
+            format!("{}:step:{}.{}", workflow_name, step_index, branch_index),
+            ComponentStatus::Completed,
+            workflow_name.to_string(),
+            None,
+            data,
+        )
+    }
+
+    /// Emit WorkflowStep::Failed event for one branch of a parallel step
+    pub fn branch_failed(
+        &self,
+        workflow_name: &str,
+        step_index: usize,
+        branch_index: usize,
+        error: &str,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::WorkflowStep,
+            EventType::Failed,
+            format!("{}:step:{}.{}", workflow_name, step_index, branch_index),
+            ComponentStatus::Failed,
+            workflow_name.to_string(),
+            Some(error.to_string()),
+            data,
+        )
+    }
+
+    /// Emit WorkflowStep::Started event for one item of a for-each step
+    pub fn item_started(
+        &self,
+        workflow_name: &str,
+        step_index: usize,
+        item_index: usize,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::WorkflowStep,
+            EventType::Started,
+            format!("{}:step:{}:item:{}", workflow_name, step_index, item_index),
+            ComponentStatus::Running,
+            workflow_name.to_string(),
+            None,
+            data,
+        )
+    }
+
+    /// Emit WorkflowStep::Completed event for one item of a for-each step
+    pub fn item_completed(
+        &self,
+        workflow_name: &str,
+        step_index: usize,
+        item_index: usize,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::WorkflowStep,
+            EventType::Completed,
+            format!("{}:step:{}:item:{}", workflow_name, step_index, item_index),
+            ComponentStatus::Completed,
+            workflow_name.to_string(),
+            None,
+            data,
+        )
+    }
+
+    /// Emit WorkflowStep::Failed event for one item of a for-each step
+    pub fn item_failed(
+        &self,
+        workflow_name: &str,
+        step_index: usize,
+        item_index: usize,
+        error: &str,
+        data: JsonValue,
+    ) -> tokio::task::JoinHandle<Result<Event, String>> {
+        self.append(
+            EventScope::WorkflowStep,
+            EventType::Failed,
+            format!("{}:step:{}:item:{}", workflow_name, step_index, item_index),
+            ComponentStatus::Failed,
+            workflow_name.to_string(),
+            Some(error.to_string()),
+            data,
+        )
+    }
+
     /// Subscribe to real-time event stream
     /// Returns a receiver that will get all future events
     pub fn subscribe(&self) -> broadcast::Receiver<Event> {
         self.sender.subscribe()
     }
 
+    /// Subscribe to every event from `offset` on, including ones already in
+    /// history, without losing any if the subscriber falls behind
+    pub fn subscribe_from(&self, offset: EventOffset) -> EventSubscription {
+        EventSubscription::new(self.clone(), offset)
+    }
+
+    /// Live events matching `query`, without losing any if the subscriber
+    /// falls behind
+    ///
+    /// Starts with the next event published, or replays history first if
+    /// the query sets a first offset. Ends once the query's offset range or
+    /// limit is used up.
+    pub fn subscribe_filtered(
+        &self,
+        query: EventQuery,
+    ) -> impl futures::Stream<Item = Event> + Send + 'static {
+        let from = query.start().unwrap_or_else(|| self.current_offset());
+        query::follow(self.subscribe_from(from), query)
+    }
+
+    /// Search the history; see [`EventQuery`]
+    pub fn query(&self) -> EventQuery {
+        EventQuery::new(self.clone())
+    }
+
+    /// Counts of the events published so far, by scope and type
+    pub fn stats(&self) -> EventStats {
+        self.history.read().unwrap().stats()
+    }
+
     /// Get events from a specific offset (for replay)
     pub fn get_from_offset(&self, offset: EventOffset) -> Vec<Event> {
         let history = self.history.read().unwrap();
-        history
+        let start = (offset as usize).min(history.events.len());
+        history.events[start..]
             .iter()
-            .filter(|e| e.offset >= offset)
-            .cloned()
+            .map(|event| Event::clone(event))
             .collect()
     }
 
     /// Get all events
+    ///
+    /// Each event is copied; on a large history, [`snapshot`](Self::snapshot)
+    /// is cheaper.
     pub fn all(&self) -> Vec<Event> {
-        self.history.read().unwrap().clone()
+        self.snapshot()
+            .iter()
+            .map(|event| Event::clone(event))
+            .collect()
+    }
+
+    /// All events, shared with the history rather than copied
+    pub fn snapshot(&self) -> Vec<Arc<Event>> {
+        self.history.read().unwrap().events.clone()
     }
 
     /// Get event count
     pub fn len(&self) -> usize {
-        self.history.read().unwrap().len()
+        self.history.read().unwrap().events.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.history.read().unwrap().is_empty()
+        self.history.read().unwrap().events.is_empty()
     }
 
     /// Get the current offset (next event will have this offset)
     pub fn current_offset(&self) -> EventOffset {
-        *self.next_offset.read().unwrap()
+        self.history.read().unwrap().events.len() as EventOffset
+    }
+
+    /// Redact, store and broadcast `event`, assigning its offset
+    fn publish(&self, mut event: Event) -> Event {
+        if let Some(rules) = &self.redaction {
+            rules.redact_event(&mut event);
+        }
+
+        // Take the offset and broadcast under the history lock, so
+        // subscribers get events in the order history does and nothing
+        // falls between a subscriber's backfill and its receiver (ignore
+        // if no active receivers)
+        let mut history = self.history.write().unwrap();
+        let stored = history.insert(event);
+        let _ = self.sender.send(Event::clone(&stored));
+        Event::clone(&stored)
     }
 }
 
@@ -709,7 +1431,9 @@ impl Clone for EventStream {
         Self {
             sender: self.sender.clone(),
             history: Arc::clone(&self.history),
-            next_offset: Arc::clone(&self.next_offset),
+            sampler: self.sampler.clone(),
+            redaction: self.redaction.clone(),
+            lineage: Arc::clone(&self.lineage),
         }
     }
 }
@@ -0,0 +1,279 @@
+//! Querying an [`EventStream`]'s history, and following it live.
+//!
+//! [`EventStream::query`] starts an [`EventQuery`]; every criterion set on it
+//! narrows the match. Queries on one workflow (or one parent workflow) use
+//! the history's per-workflow index, so they don't scan events from other
+//! runs sharing the stream. [`EventStream::subscribe_filtered`] applies a
+//! query to events as they are published.
+
+use super::{Event, EventScope, EventStream, EventSubscription, EventType};
+use crate::types::{EventOffset, WorkflowId};
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+
+/// Stored events, in offset order, with the indexes queries use
+#[derive(Debug, Default)]
+pub(super) struct History {
+    /// An event's offset is its index here
+    pub(super) events: Vec<Arc<Event>>,
+    by_workflow: HashMap<WorkflowId, Vec<usize>>,
+    by_parent: HashMap<WorkflowId, Vec<usize>>,
+    stats: EventStats,
+}
+
+impl History {
+    /// Store `event` at the next offset, which it takes
+    pub(super) fn insert(&mut self, mut event: Event) -> Arc<Event> {
+        let index = self.events.len();
+        event.offset = index as EventOffset;
+        self.by_workflow
+            .entry(event.workflow_id.clone())
+            .or_default()
+            .push(index);
+        if let Some(parent) = &event.parent_workflow_id {
+            self.by_parent
+                .entry(parent.clone())
+                .or_default()
+                .push(index);
+        }
+        self.stats.record(&event);
+        let event = Arc::new(event);
+        self.events.push(event.clone());
+        event
+    }
+
+    pub(super) fn stats(&self) -> EventStats {
+        self.stats.clone()
+    }
+}
+
+/// Event counts by scope and type, kept up to date as events are published
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct EventStats {
+    pub total: usize,
+    pub by_scope: HashMap<EventScope, usize>,
+    pub by_type: HashMap<EventType, usize>,
+    pub by_scope_and_type: HashMap<EventScope, HashMap<EventType, usize>>,
+}
+
+impl EventStats {
+    /// Events of `scope` and `event_type`
+    pub fn count(&self, scope: &EventScope, event_type: &EventType) -> usize {
+        self.by_scope_and_type
+            .get(scope)
+            .and_then(|types| types.get(event_type))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn record(&mut self, event: &Event) {
+        self.total += 1;
+        *self.by_scope.entry(event.scope.clone()).or_default() += 1;
+        *self.by_type.entry(event.event_type.clone()).or_default() += 1;
+        *self
+            .by_scope_and_type
+            .entry(event.scope.clone())
+            .or_default()
+            .entry(event.event_type.clone())
+            .or_default() += 1;
+    }
+}
+
+/// Criteria for events in an [`EventStream`]
+///
+/// ```rust,ignore
+/// let failures = stream
+///     .query()
+///     .workflow("wf-42")
+///     .scope(EventScope::Tool)
+///     .event_type(EventType::Failed)
+///     .events();
+/// ```
+#[derive(Clone)]
+pub struct EventQuery {
+    stream: EventStream,
+    workflow_id: Option<WorkflowId>,
+    parent_workflow_id: Option<WorkflowId>,
+    scopes: Vec<EventScope>,
+    event_types: Vec<EventType>,
+    component_prefix: Option<String>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    from_offset: Option<EventOffset>,
+    /// Exclusive
+    to_offset: Option<EventOffset>,
+    limit: Option<usize>,
+}
+
+impl EventQuery {
+    pub(super) fn new(stream: EventStream) -> Self {
+        Self {
+            stream,
+            workflow_id: None,
+            parent_workflow_id: None,
+            scopes: Vec::new(),
+            event_types: Vec::new(),
+            component_prefix: None,
+            since: None,
+            until: None,
+            from_offset: None,
+            to_offset: None,
+            limit: None,
+        }
+    }
+
+    /// Only events of this workflow
+    pub fn workflow(mut self, workflow_id: impl Into<WorkflowId>) -> Self {
+        self.workflow_id = Some(workflow_id.into());
+        self
+    }
+
+    /// Only events of runs nested in this workflow
+    pub fn parent_workflow(mut self, workflow_id: impl Into<WorkflowId>) -> Self {
+        self.parent_workflow_id = Some(workflow_id.into());
+        self
+    }
+
+    /// Only events of this scope; calling it again allows another scope
+    pub fn scope(mut self, scope: EventScope) -> Self {
+        self.scopes.push(scope);
+        self
+    }
+
+    /// Only events of this type; calling it again allows another type
+    pub fn event_type(mut self, event_type: EventType) -> Self {
+        self.event_types.push(event_type);
+        self
+    }
+
+    /// Only events whose component id starts with `prefix`
+    pub fn component_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.component_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Only events timestamped at or after `time`
+    pub fn since(mut self, time: DateTime<Utc>) -> Self {
+        self.since = Some(time);
+        self
+    }
+
+    /// Only events timestamped before `time`
+    pub fn until(mut self, time: DateTime<Utc>) -> Self {
+        self.until = Some(time);
+        self
+    }
+
+    /// Only events with offsets in `range`
+    pub fn offsets(mut self, range: impl RangeBounds<EventOffset>) -> Self {
+        self.from_offset = match range.start_bound() {
+            Bound::Included(&start) => Some(start),
+            Bound::Excluded(&start) => Some(start + 1),
+            Bound::Unbounded => None,
+        };
+        self.to_offset = match range.end_bound() {
+            Bound::Included(&end) => Some(end + 1),
+            Bound::Excluded(&end) => Some(end),
+            Bound::Unbounded => None,
+        };
+        self
+    }
+
+    /// At most `limit` events, the earliest ones
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Where a live subscription for this query starts, if the query sets
+    /// a first offset
+    pub(super) fn start(&self) -> Option<EventOffset> {
+        self.from_offset
+    }
+
+    /// Whether `event` meets every criterion
+    pub fn matches(&self, event: &Event) -> bool {
+        self.workflow_id
+            .as_ref()
+            .is_none_or(|id| event.workflow_id == *id)
+            && self
+                .parent_workflow_id
+                .as_ref()
+                .is_none_or(|id| event.parent_workflow_id.as_ref() == Some(id))
+            && (self.scopes.is_empty() || self.scopes.contains(&event.scope))
+            && (self.event_types.is_empty() || self.event_types.contains(&event.event_type))
+            && self
+                .component_prefix
+                .as_ref()
+                .is_none_or(|prefix| event.component_id.starts_with(prefix.as_str()))
+            && self.since.is_none_or(|since| event.timestamp >= since)
+            && self.until.is_none_or(|until| event.timestamp < until)
+            && self.from_offset.is_none_or(|from| event.offset >= from)
+            && self.to_offset.is_none_or(|to| event.offset < to)
+    }
+
+    /// The matching events in history, in offset order
+    pub fn events(&self) -> Vec<Event> {
+        let history = self.stream.history.read().unwrap();
+        let end = self.to_offset.map_or(history.events.len(), |to| {
+            (to as usize).min(history.events.len())
+        });
+        let start = self.from_offset.map_or(0, |from| (from as usize).min(end));
+        let limit = self.limit.unwrap_or(usize::MAX);
+
+        let indexed = match (&self.workflow_id, &self.parent_workflow_id) {
+            (Some(id), _) => Some(history.by_workflow.get(id)),
+            (None, Some(parent)) => Some(history.by_parent.get(parent)),
+            (None, None) => None,
+        };
+        match indexed {
+            Some(indices) => {
+                let indices = indices.map(Vec::as_slice).unwrap_or_default();
+                let first = indices.partition_point(|&i| i < start);
+                indices[first..]
+                    .iter()
+                    .take_while(|&&i| i < end)
+                    .map(|&i| &history.events[i])
+                    .filter(|event| self.matches(event))
+                    .take(limit)
+                    .map(|event| Event::clone(event))
+                    .collect()
+            }
+            None => history.events[start..end]
+                .iter()
+                .filter(|event| self.matches(event))
+                .take(limit)
+                .map(|event| Event::clone(event))
+                .collect(),
+        }
+    }
+}
+
+/// Live events matching `query`; see [`EventStream::subscribe_filtered`]
+pub(super) fn follow(
+    subscription: EventSubscription,
+    query: EventQuery,
+) -> impl Stream<Item = Event> + Send + 'static {
+    let remaining = query.limit.unwrap_or(usize::MAX);
+    futures::stream::unfold(
+        (subscription, query, remaining),
+        |(mut subscription, query, remaining)| async move {
+            loop {
+                let past_end = query
+                    .to_offset
+                    .is_some_and(|to| subscription.next_offset() >= to);
+                if remaining == 0 || past_end {
+                    return None;
+                }
+                let event = subscription.recv().await;
+                if query.matches(&event) {
+                    return Some((event, (subscription, query, remaining - 1)));
+                }
+            }
+        },
+    )
+}
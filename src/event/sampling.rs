@@ -0,0 +1,488 @@
+//! Trace sampling for high-throughput deployments.
+//!
+//! With a [`SamplingPolicy`] installed on an [`EventStream`](super::EventStream),
+//! each workflow run gets a head decision when it starts:
+//!
+//! - runs carrying one of the policy's `always_sample_labels` are sampled
+//! - otherwise a seeded hash of the run id is compared against `ratio`, so
+//!   runs of one workflow are sampled at `ratio` and a run (e.g. one resumed
+//!   from a checkpoint) always gets the same decision
+//!
+//! Sampled runs emit every event. Unsampled runs emit only lifecycle events
+//! (workflow and step started/completed/failed/canceled); everything else is
+//! dropped, or, with [`TailSampling`], buffered until the run ends. A run that
+//! fails or exceeds the duration threshold is upgraded to full detail and its
+//! buffered events are delivered after its terminal event, keeping their
+//! original timestamps. The buffer is bounded per run; the oldest detail is
+//! evicted first.
+//!
+//! [`EventMetrics`] count every event before the sampling decision, so they
+//! stay accurate whatever is dropped.
+
+use super::{Event, EventScope, EventType};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+tokio::task_local! {
+    static CURRENT_RUN: String;
+}
+
+/// `run_id` of the workflow run the current task belongs to, if any
+pub fn current_run() -> Option<String> {
+    CURRENT_RUN.try_with(|id| id.clone()).ok()
+}
+
+/// Attribute events emitted by `fut` to the run `run_id`
+pub async fn in_run<F: Future>(run_id: String, fut: F) -> F::Output {
+    CURRENT_RUN.scope(run_id, fut).await
+}
+
+/// Carry the current run into a future that will be spawned on another task
+pub fn propagate<F: Future>(fut: F) -> impl Future<Output = F::Output> {
+    let run = current_run();
+    async move {
+        match run {
+            Some(id) => CURRENT_RUN.scope(id, fut).await,
+            None => fut.await,
+        }
+    }
+}
+
+/// Which runs emit full detail
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SamplingPolicy {
+    /// Fraction of runs sampled up front, 0.0 to 1.0
+    pub ratio: f64,
+
+    /// Seed for the run id hash; change it to sample a different set
+    #[serde(default)]
+    pub seed: u64,
+
+    /// Runs with any of these labels are always sampled
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub always_sample_labels: Vec<String>,
+
+    /// Retroactively upgrade unsampled runs that turn out interesting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tail: Option<TailSampling>,
+}
+
+impl SamplingPolicy {
+    /// Sample `ratio` of runs (clamped to 0.0..=1.0)
+    pub fn ratio(ratio: f64) -> Self {
+        Self {
+            ratio: ratio.clamp(0.0, 1.0),
+            seed: 0,
+            always_sample_labels: Vec::new(),
+            tail: None,
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn always_sample_label(mut self, label: impl Into<String>) -> Self {
+        self.always_sample_labels.push(label.into());
+        self
+    }
+
+    pub fn with_tail(mut self, tail: TailSampling) -> Self {
+        self.tail = Some(tail);
+        self
+    }
+
+    /// Head decision for a run; deterministic in `run_id` and the seed
+    pub fn decide(&self, run_id: &str, labels: &[String]) -> SamplingReason {
+        if let Some(label) = labels
+            .iter()
+            .find(|l| self.always_sample_labels.contains(l))
+        {
+            return SamplingReason::Label {
+                label: label.clone(),
+            };
+        }
+        let bucket = stable_hash(self.seed, run_id) as f64 / u64::MAX as f64;
+        if bucket < self.ratio {
+            SamplingReason::Ratio
+        } else {
+            SamplingReason::NotSampled
+        }
+    }
+}
+
+/// Tail-based upgrade of unsampled runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TailSampling {
+    /// Upgrade runs that fail
+    pub on_failure: bool,
+
+    /// Upgrade runs that take longer than this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slow_run_ms: Option<u64>,
+
+    /// Approximate bytes of detail buffered per run before the oldest is evicted
+    pub max_buffered_bytes: usize,
+}
+
+impl TailSampling {
+    /// Upgrade failed runs, buffering up to 1 MiB of detail per run
+    pub fn new() -> Self {
+        Self {
+            on_failure: true,
+            slow_run_ms: None,
+            max_buffered_bytes: 1024 * 1024,
+        }
+    }
+
+    pub fn with_slow_run(mut self, threshold: Duration) -> Self {
+        self.slow_run_ms = Some(threshold.as_millis() as u64);
+        self
+    }
+
+    pub fn with_max_buffered_bytes(mut self, bytes: usize) -> Self {
+        self.max_buffered_bytes = bytes;
+        self
+    }
+}
+
+impl Default for TailSampling {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Why a run was, or wasn't, traced in full
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum SamplingReason {
+    /// Fell within the sampling ratio
+    Ratio,
+    /// Carried an always-sampled label
+    Label { label: String },
+    /// Lifecycle events only
+    NotSampled,
+    /// Upgraded because the run failed
+    Failed,
+    /// Upgraded because the run was slow
+    SlowRun { duration_ms: u64, threshold_ms: u64 },
+}
+
+/// The sampling decision for a run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceDecision {
+    /// Whether full detail was (or will be) delivered
+    pub sampled: bool,
+    pub reason: SamplingReason,
+    pub policy: SamplingPolicy,
+
+    /// Detail events delivered retroactively by a tail upgrade
+    #[serde(default)]
+    pub buffered_events: usize,
+
+    /// Detail events evicted from the buffer by the memory cap
+    #[serde(default)]
+    pub evicted_events: usize,
+
+    /// Detail events not delivered
+    #[serde(default)]
+    pub dropped_events: usize,
+}
+
+/// Event counts taken before sampling drops anything
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EventMetrics {
+    /// Every event emitted, sampled or not
+    pub total: u64,
+
+    /// Counts keyed by `scope.type`, e.g. `llm_request.progress`
+    pub by_kind: BTreeMap<String, u64>,
+
+    /// Detail events dropped from unsampled runs
+    pub dropped: u64,
+
+    /// Detail events evicted from tail buffers
+    pub evicted: u64,
+
+    /// Runs upgraded to full detail by tail sampling
+    pub upgraded_runs: u64,
+}
+
+impl EventMetrics {
+    pub fn count(&self, scope: &EventScope, event_type: &EventType) -> u64 {
+        self.by_kind
+            .get(&kind_key(scope, event_type))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// What to do with an event
+pub(crate) enum Admission {
+    Deliver,
+    Buffer(String),
+    Drop(String),
+}
+
+struct RunTrace {
+    workflow_id: String,
+    decision: TraceDecision,
+    started: Instant,
+    buffer: VecDeque<(Event, usize)>,
+    buffered_bytes: usize,
+}
+
+/// Per-stream sampling state
+pub(crate) struct TraceSampler {
+    policy: SamplingPolicy,
+    /// Traces of the runs in flight, by run ID
+    runs: Mutex<HashMap<String, RunTrace>>,
+    metrics: Mutex<EventMetrics>,
+}
+
+impl TraceSampler {
+    pub(crate) fn new(policy: SamplingPolicy) -> Self {
+        Self {
+            policy,
+            runs: Mutex::new(HashMap::new()),
+            metrics: Mutex::new(EventMetrics::default()),
+        }
+    }
+
+    pub(crate) fn metrics(&self) -> EventMetrics {
+        self.metrics.lock().unwrap().clone()
+    }
+
+    pub(crate) fn begin(
+        &self,
+        run_id: &str,
+        workflow_id: &str,
+        labels: &[String],
+    ) -> TraceDecision {
+        let reason = self.policy.decide(run_id, labels);
+        let decision = TraceDecision {
+            sampled: reason != SamplingReason::NotSampled,
+            reason,
+            policy: self.policy.clone(),
+            buffered_events: 0,
+            evicted_events: 0,
+            dropped_events: 0,
+        };
+        self.runs.lock().unwrap().insert(
+            run_id.to_string(),
+            RunTrace {
+                workflow_id: workflow_id.to_string(),
+                decision: decision.clone(),
+                started: Instant::now(),
+                buffer: VecDeque::new(),
+                buffered_bytes: 0,
+            },
+        );
+        decision
+    }
+
+    /// Count the event and decide whether it's delivered now. An event from
+    /// outside a run's task goes with a run of its workflow; events outside
+    /// any traced run are always delivered.
+    pub(crate) fn admit(
+        &self,
+        workflow_id: &str,
+        scope: &EventScope,
+        event_type: &EventType,
+    ) -> Admission {
+        {
+            let mut metrics = self.metrics.lock().unwrap();
+            metrics.total += 1;
+            *metrics
+                .by_kind
+                .entry(kind_key(scope, event_type))
+                .or_default() += 1;
+        }
+
+        if is_lifecycle(scope, event_type) {
+            return Admission::Deliver;
+        }
+        let runs = self.runs.lock().unwrap();
+        let traced = match current_run() {
+            Some(run_id) => runs.get_key_value(&run_id),
+            None => runs.iter().find(|(_, run)| run.workflow_id == workflow_id),
+        };
+        match traced {
+            Some((run_id, run)) if !run.decision.sampled => {
+                if self.policy.tail.is_some() {
+                    Admission::Buffer(run_id.clone())
+                } else {
+                    Admission::Drop(run_id.clone())
+                }
+            }
+            _ => Admission::Deliver,
+        }
+    }
+
+    pub(crate) fn drop_event(&self, run_id: &str) {
+        self.metrics.lock().unwrap().dropped += 1;
+        if let Some(run) = self.runs.lock().unwrap().get_mut(run_id) {
+            run.decision.dropped_events += 1;
+        }
+    }
+
+    /// Hold an unsampled run's detail event until the run's outcome is known
+    pub(crate) fn buffer(&self, run_id: &str, event: Event) {
+        let max_bytes = self
+            .policy
+            .tail
+            .as_ref()
+            .map(|t| t.max_buffered_bytes)
+            .unwrap_or_default();
+        let size = approximate_size(&event);
+
+        let mut runs = self.runs.lock().unwrap();
+        let Some(run) = runs.get_mut(run_id) else {
+            return;
+        };
+        run.buffer.push_back((event, size));
+        run.buffered_bytes += size;
+
+        let mut evicted = 0;
+        while run.buffered_bytes > max_bytes {
+            let Some((_, size)) = run.buffer.pop_front() else {
+                break;
+            };
+            run.buffered_bytes -= size;
+            evicted += 1;
+        }
+        run.decision.evicted_events += evicted;
+        drop(runs);
+        if evicted > 0 {
+            self.metrics.lock().unwrap().evicted += evicted as u64;
+        }
+    }
+
+    /// Settle the run's decision; returns the buffered events to deliver if
+    /// the run was upgraded
+    pub(crate) fn finish(&self, run_id: &str, failed: bool) -> Option<(TraceDecision, Vec<Event>)> {
+        let run = self.runs.lock().unwrap().remove(run_id)?;
+        let mut decision = run.decision;
+        if decision.sampled {
+            return Some((decision, Vec::new()));
+        }
+
+        let tail = self.policy.tail.as_ref();
+        let duration_ms = run.started.elapsed().as_millis() as u64;
+        let upgrade = if failed && tail.is_some_and(|t| t.on_failure) {
+            Some(SamplingReason::Failed)
+        } else {
+            tail.and_then(|t| t.slow_run_ms)
+                .filter(|threshold| duration_ms > *threshold)
+                .map(|threshold_ms| SamplingReason::SlowRun {
+                    duration_ms,
+                    threshold_ms,
+                })
+        };
+
+        let events: Vec<Event> = run.buffer.into_iter().map(|(event, _)| event).collect();
+        let mut metrics = self.metrics.lock().unwrap();
+        match upgrade {
+            Some(reason) => {
+                metrics.upgraded_runs += 1;
+                decision.sampled = true;
+                decision.reason = reason;
+                decision.buffered_events = events.len();
+                Some((decision, events))
+            }
+            None => {
+                metrics.dropped += events.len() as u64;
+                decision.dropped_events += events.len();
+                Some((decision, Vec::new()))
+            }
+        }
+    }
+}
+
+/// Workflow and step lifecycle events, emitted whatever the sampling decision
+pub fn is_lifecycle(scope: &EventScope, event_type: &EventType) -> bool {
+    matches!(scope, EventScope::Workflow | EventScope::WorkflowStep)
+        && !matches!(event_type, EventType::Progress)
+}
+
+fn kind_key(scope: &EventScope, event_type: &EventType) -> String {
+    let name = |value: serde_json::Value| value.as_str().unwrap_or_default().to_string();
+    format!(
+        "{}.{}",
+        name(serde_json::json!(scope)),
+        name(serde_json::json!(event_type))
+    )
+}
+
+fn approximate_size(event: &Event) -> usize {
+    // Fixed overhead covers ids, timestamp and enums
+    128 + event.component_id.len()
+        + event.message.as_ref().map(String::len).unwrap_or_default()
+        + event.data.to_string().len()
+}
+
+/// FNV-1a with a splitmix64 finalizer so similar ids spread evenly; stable
+/// across Rust versions, unlike `DefaultHasher`
+fn stable_hash(seed: u64, value: &str) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64 ^ seed;
+    for byte in value.as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash ^= hash >> 30;
+    hash = hash.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash ^= hash >> 27;
+    hash = hash.wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ratio_decision_is_deterministic_by_run_id() {
+        let policy = SamplingPolicy::ratio(0.3).with_seed(7);
+        let ids: Vec<String> = (0..1000)
+            .map(|_| format!("run_{}", uuid::Uuid::new_v4()))
+            .collect();
+
+        let first: Vec<_> = ids.iter().map(|id| policy.decide(id, &[])).collect();
+        let second: Vec<_> = ids.iter().map(|id| policy.decide(id, &[])).collect();
+        assert_eq!(first, second);
+
+        let sampled = first
+            .iter()
+            .filter(|r| **r == SamplingReason::Ratio)
+            .count();
+        assert!((200..400).contains(&sampled), "sampled {}", sampled);
+
+        // A different seed samples a different set
+        let reseeded = SamplingPolicy::ratio(0.3).with_seed(8);
+        assert!(ids
+            .iter()
+            .zip(&first)
+            .any(|(id, reason)| reseeded.decide(id, &[]) != *reason));
+    }
+
+    #[test]
+    fn test_labels_override_ratio() {
+        let policy = SamplingPolicy::ratio(0.0).always_sample_label("canary");
+        assert_eq!(policy.decide("wf", &[]), SamplingReason::NotSampled);
+        assert_eq!(
+            policy.decide("wf", &["prod".into(), "canary".into()]),
+            SamplingReason::Label {
+                label: "canary".into()
+            }
+        );
+        assert_eq!(
+            SamplingPolicy::ratio(1.0).decide("wf", &[]),
+            SamplingReason::Ratio
+        );
+    }
+}
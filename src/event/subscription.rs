@@ -0,0 +1,77 @@
+//! Subscriptions that survive lagging.
+//!
+//! A plain [`EventStream::subscribe`] receiver loses events once it falls
+//! more than the channel capacity behind. [`EventSubscription`] refills from
+//! the stream's history when that happens, and reorders events published
+//! concurrently, so it yields every event exactly once in offset order.
+
+use super::{Event, EventStream};
+use crate::types::EventOffset;
+use std::collections::BTreeMap;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+/// A lossless, ordered view of an [`EventStream`] from some offset on
+pub struct EventSubscription {
+    stream: EventStream,
+    receiver: Receiver<Event>,
+    next_offset: EventOffset,
+    pending: BTreeMap<EventOffset, Event>,
+    recovered: u64,
+}
+
+impl EventSubscription {
+    pub(super) fn new(stream: EventStream, from: EventOffset) -> Self {
+        // Subscribe before reading history so nothing falls in between
+        let receiver = stream.subscribe();
+        let mut subscription = Self {
+            stream,
+            receiver,
+            next_offset: from,
+            pending: BTreeMap::new(),
+            recovered: 0,
+        };
+        subscription.backfill();
+        subscription.recovered = 0;
+        subscription
+    }
+
+    /// The next event, waiting until one is published
+    pub async fn recv(&mut self) -> Event {
+        loop {
+            if let Some(event) = self.pending.remove(&self.next_offset) {
+                self.next_offset += 1;
+                return event;
+            }
+            match self.receiver.recv().await {
+                Ok(event) if event.offset >= self.next_offset => {
+                    self.pending.insert(event.offset, event);
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(_)) => self.backfill(),
+                // The stream holds a sender, so the channel never closes
+                Err(RecvError::Closed) => std::future::pending::<()>().await,
+            }
+        }
+    }
+
+    /// Offset of the next event `recv` returns
+    pub fn next_offset(&self) -> EventOffset {
+        self.next_offset
+    }
+
+    /// Events read from history because the receiver lagged
+    pub fn recovered(&self) -> u64 {
+        self.recovered
+    }
+
+    fn backfill(&mut self) {
+        for event in self.stream.get_from_offset(self.next_offset) {
+            if let std::collections::btree_map::Entry::Vacant(slot) =
+                self.pending.entry(event.offset)
+            {
+                slot.insert(event);
+                self.recovered += 1;
+            }
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use crate::event::{ComponentStatus, EventScope, EventStream, EventType};
+use crate::event::{ComponentStatus, EventScope, EventStream, EventType, RedactionRules};
+use futures::StreamExt;
 use serde_json::json;
 
 #[test]
@@ -60,6 +61,213 @@ async fn test_event_stream_all() {
     assert_eq!(all_events.len(), 2);
 }
 
+#[tokio::test]
+async fn test_subscription_recovers_after_lagging() {
+    let stream = EventStream::with_capacity(2);
+    stream
+        .workflow_started("wf_early", json!({}))
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Starts from history, then keeps up past the channel capacity
+    let mut subscription = stream.subscribe_from(0);
+    for step in 0..10 {
+        stream
+            .step_started("wf_123", step, json!({}))
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    let mut offsets = Vec::new();
+    for _ in 0..11 {
+        offsets.push(subscription.recv().await.offset);
+    }
+    assert_eq!(offsets, (0..11).collect::<Vec<_>>());
+    assert!(subscription.recovered() > 0);
+    assert_eq!(subscription.next_offset(), 11);
+}
+
+#[tokio::test]
+async fn test_stream_redaction_applies_before_storage() {
+    let stream = EventStream::new().with_redaction(RedactionRules::default());
+    let event = stream
+        .tool_failed(
+            "http_get",
+            "wf_123".to_string(),
+            "401 for Bearer abc.def",
+            json!({"headers": {"Authorization": "Bearer abc.def"}, "status": 401}),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(event.message.as_deref(), Some("401 for [REDACTED]"));
+    assert_eq!(
+        stream.all()[0].data,
+        json!({"headers": {"Authorization": "[REDACTED]"}, "status": 401})
+    );
+}
+
+/// Steps and tool calls from three runs, interleaved; `wf_c` is nested in
+/// `wf_a`
+async fn interleaved(stream: &EventStream, rounds: usize) {
+    for round in 0..rounds {
+        for workflow in ["wf_a", "wf_b"] {
+            stream
+                .step_started(workflow, round, json!({}))
+                .await
+                .unwrap()
+                .unwrap();
+        }
+        stream
+            .append_with_parent(
+                EventScope::Tool,
+                EventType::Completed,
+                "search".to_string(),
+                ComponentStatus::Completed,
+                "wf_c".to_string(),
+                Some("wf_a".to_string()),
+                None,
+                json!({ "round": round }),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        if round % 2 == 1 {
+            stream
+                .tool_failed("fetch", "wf_b".to_string(), "timed out", json!({}))
+                .await
+                .unwrap()
+                .unwrap();
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_query_filters_history() {
+    let stream = EventStream::new();
+    interleaved(&stream, 4).await;
+    assert_eq!(stream.len(), 14);
+
+    let offsets = |events: Vec<crate::event::Event>| -> Vec<u64> {
+        events.iter().map(|e| e.offset).collect()
+    };
+    assert_eq!(
+        offsets(stream.query().workflow("wf_b").events()),
+        vec![1, 4, 6, 8, 11, 13]
+    );
+    assert_eq!(
+        offsets(stream.query().parent_workflow("wf_a").events()),
+        vec![2, 5, 9, 12]
+    );
+    assert_eq!(
+        offsets(
+            stream
+                .query()
+                .workflow("wf_b")
+                .scope(EventScope::Tool)
+                .event_type(EventType::Failed)
+                .events()
+        ),
+        vec![6, 13]
+    );
+    assert_eq!(
+        offsets(stream.query().component_prefix("wf_a:step:").events()),
+        vec![0, 3, 7, 10]
+    );
+    assert_eq!(
+        offsets(
+            stream
+                .query()
+                .workflow("wf_b")
+                .offsets(4..11)
+                .limit(3)
+                .events()
+        ),
+        vec![4, 6, 8]
+    );
+    assert_eq!(offsets(stream.query().offsets(12..).events()), vec![12, 13]);
+
+    let cutoff = stream.all()[10].timestamp;
+    assert!(stream
+        .query()
+        .since(cutoff)
+        .events()
+        .iter()
+        .all(|e| e.timestamp >= cutoff));
+    assert!(stream.query().until(cutoff).events().len() >= 10);
+    assert!(stream.query().workflow("wf_missing").events().is_empty());
+
+    let stats = stream.stats();
+    assert_eq!(stats.total, 14);
+    assert_eq!(stats.by_scope[&EventScope::WorkflowStep], 8);
+    assert_eq!(stats.by_type[&EventType::Completed], 4);
+    assert_eq!(stats.count(&EventScope::Tool, &EventType::Failed), 2);
+    assert_eq!(stats.count(&EventScope::Agent, &EventType::Failed), 0);
+}
+
+#[tokio::test]
+async fn test_filtered_subscription_follows_live_events() {
+    let stream = EventStream::new();
+    interleaved(&stream, 1).await;
+
+    // Only events published from now on
+    let mut live = Box::pin(
+        stream.subscribe_filtered(stream.query().workflow("wf_b").scope(EventScope::Tool)),
+    );
+    interleaved(&stream, 4).await;
+
+    let mut failures = Vec::new();
+    for _ in 0..2 {
+        failures.push(live.next().await.unwrap());
+    }
+    assert!(failures
+        .iter()
+        .all(|e| e.workflow_id == "wf_b" && e.event_type == EventType::Failed));
+    assert!(failures[0].offset < failures[1].offset);
+
+    // An offset range replays history and ends with the range
+    let bounded: Vec<_> = stream
+        .subscribe_filtered(stream.query().workflow("wf_c").offsets(0..9))
+        .collect()
+        .await;
+    assert_eq!(
+        bounded.iter().map(|e| e.offset).collect::<Vec<_>>(),
+        vec![2, 5, 8]
+    );
+}
+
+#[tokio::test]
+async fn test_filtered_subscription_recovers_after_lagging() {
+    let stream = EventStream::with_capacity(2);
+    let live = stream.subscribe_filtered(stream.query().workflow("wf_a").limit(6));
+
+    // Publish far more than the channel holds before reading anything
+    interleaved(&stream, 6).await;
+    let events: Vec<_> = live.collect().await;
+    assert_eq!(
+        events
+            .iter()
+            .map(|e| e.component_id.as_str())
+            .collect::<Vec<_>>(),
+        (0..6)
+            .map(|round| format!("wf_a:step:{}", round))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        events.iter().map(|e| e.offset).collect::<Vec<_>>(),
+        stream
+            .query()
+            .workflow("wf_a")
+            .events()
+            .iter()
+            .map(|e| e.offset)
+            .collect::<Vec<_>>()
+    );
+}
+
 #[test]
 fn test_event_type_serialization() {
     let event_type = EventType::Started;
@@ -74,3 +282,105 @@ fn test_event_type_serialization() {
     let json = serde_json::to_string(&status).unwrap();
     assert_eq!(json, "\"running\"");
 }
+
+#[tokio::test]
+async fn test_aborted_append_handles_leave_no_gap() {
+    let stream = EventStream::new();
+
+    stream.workflow_started("wf_123", json!({})).abort();
+    stream.step_started("wf_123", 0, json!({})).abort();
+    let mut live = stream.subscribe_from(stream.current_offset());
+    stream.step_completed("wf_123", 0, json!({})).abort();
+
+    // Stored before the handles could run
+    assert_eq!(stream.len(), 3);
+    assert_eq!(stream.current_offset(), 3);
+    let offsets: Vec<u64> = stream.snapshot().iter().map(|e| e.offset).collect();
+    assert_eq!(offsets, vec![0, 1, 2]);
+    assert_eq!(live.recv().await.event_type, EventType::Completed);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_appends_get_dense_ordered_offsets() {
+    let stream = EventStream::new();
+    let tasks: Vec<_> = (0..8)
+        .map(|task| {
+            let stream = stream.clone();
+            tokio::spawn(async move {
+                let handles: Vec<_> = (0..1250)
+                    .map(|seq| {
+                        stream.append(
+                            EventScope::System,
+                            EventType::Progress,
+                            "system:stress".to_string(),
+                            ComponentStatus::Running,
+                            format!("wf_{}", task),
+                            None,
+                            json!({ "seq": seq }),
+                        )
+                    })
+                    .collect();
+                for handle in handles {
+                    handle.await.unwrap().unwrap();
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let events = stream.snapshot();
+    assert_eq!(events.len(), 10_000);
+    assert_eq!(stream.current_offset(), 10_000);
+    for (index, event) in events.iter().enumerate() {
+        assert_eq!(event.offset, index as u64);
+    }
+
+    // Each task's events keep the order it emitted them in
+    for task in 0..8 {
+        let seqs: Vec<u64> = stream
+            .query()
+            .workflow(format!("wf_{}", task))
+            .events()
+            .iter()
+            .map(|e| e.data["seq"].as_u64().unwrap())
+            .collect();
+        assert_eq!(seqs, (0..1250).collect::<Vec<u64>>());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_subscribers_receive_in_offset_order() {
+    let stream = EventStream::new();
+    let mut receiver = stream.subscribe();
+    for seq in 0..500 {
+        stream.step_started("wf_1", seq, json!({}));
+    }
+    for offset in 0..500 {
+        assert_eq!(receiver.recv().await.unwrap().offset, offset);
+    }
+}
+
+#[tokio::test]
+async fn test_invalid_event_takes_no_offset() {
+    let stream = EventStream::new();
+    let invalid = stream.append(
+        EventScope::System,
+        EventType::Progress,
+        "not_system".to_string(),
+        ComponentStatus::Running,
+        "wf_1".to_string(),
+        None,
+        json!({}),
+    );
+    assert!(invalid.await.unwrap().is_err());
+
+    let event = stream
+        .workflow_started("wf_1", json!({}))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(event.offset, 0);
+    assert_eq!(stream.current_offset(), 1);
+}
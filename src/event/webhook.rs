@@ -0,0 +1,602 @@
+//! Webhook delivery of selected events.
+//!
+//! A [`WebhookSubscriber`] holds [`WebhookSubscription`]s. Once attached to
+//! an [`EventStream`] (`Runtime::with_webhooks` does this), each
+//! subscription reads the stream through an [`EventSubscription`], so a slow
+//! target never loses events. It keeps the events its [`EventFilter`]
+//! matches, redacts them and POSTs them in batches:
+//!
+//! ```json
+//! { "id": "whb_…", "subscription": "wh_1", "events": [ … ] }
+//! ```
+//!
+//! Every request carries `Idempotency-Key` (the batch id, unchanged across
+//! retries) and, when the subscription has a secret, `X-Webhook-Signature:
+//! sha256=<hex>`, an HMAC-SHA256 of the body that receivers check with
+//! [`verify_signature`].
+//!
+//! A batch is sent once it holds `max_batch_size` events or `batch_window`
+//! after its first event. Batches wait for delivery in a small bounded
+//! queue. A batch moves to the dead-letter list when it runs out of retries,
+//! gets a non-retryable response (a 4xx other than 408 or 429), or is
+//! pushed out of a full queue. [`WebhookSubscriber::status`] shows recent
+//! outcomes and the dead letters.
+
+use super::{Event, EventFilter, EventStream, EventSubscription, RedactionRules};
+use crate::config::WebhookConfig;
+use crate::runtime::retry::RetryPolicy;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// Delivery attempts kept per subscription for inspection
+const MAX_OUTCOMES: usize = 100;
+
+/// Dead letters kept per subscription; the oldest are dropped first
+const MAX_DEAD_LETTERS: usize = 100;
+
+/// Where and how to deliver a set of events
+#[derive(Debug, Clone)]
+pub struct WebhookSubscription {
+    pub url: String,
+
+    /// Signs each body when set
+    pub secret: Option<String>,
+
+    pub filter: EventFilter,
+
+    /// How long a batch stays open after its first event
+    pub batch_window: Duration,
+
+    pub max_batch_size: usize,
+
+    /// Retries for failed deliveries
+    pub retry: RetryPolicy,
+
+    /// Batches that may wait for delivery before the oldest is dead-lettered
+    pub queue_capacity: usize,
+
+    /// Cap on requests (retries included) sent to the target
+    pub max_requests_per_second: Option<u32>,
+
+    /// Per-request timeout
+    pub timeout: Duration,
+}
+
+impl WebhookSubscription {
+    /// Every event to `url`, in batches of up to 100 sent within a second
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            secret: None,
+            filter: EventFilter::default(),
+            batch_window: Duration::from_secs(1),
+            max_batch_size: 100,
+            retry: RetryPolicy::default(),
+            queue_capacity: 16,
+            max_requests_per_second: None,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    pub fn from_config(config: &WebhookConfig) -> Self {
+        Self {
+            url: config.url.clone(),
+            secret: config.secret.clone(),
+            filter: config.filter.clone(),
+            batch_window: Duration::from_millis(config.batch_window_ms),
+            max_batch_size: config.max_batch_size.max(1),
+            retry: config.retry.to_policy(),
+            queue_capacity: config.queue_capacity.max(1),
+            max_requests_per_second: config.max_requests_per_second,
+            ..Self::new(String::new())
+        }
+    }
+
+    pub fn with_secret(mut self, secret: impl Into<String>) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    pub fn with_filter(mut self, filter: EventFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Send a batch `window` after its first event or once it holds
+    /// `max_size` events, whichever comes first
+    pub fn with_batching(mut self, window: Duration, max_size: usize) -> Self {
+        self.batch_window = window;
+        self.max_batch_size = max_size.max(1);
+        self
+    }
+
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    pub fn with_queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue_capacity = capacity.max(1);
+        self
+    }
+
+    pub fn with_rate_limit(mut self, requests_per_second: u32) -> Self {
+        self.max_requests_per_second = Some(requests_per_second.max(1));
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// The body of one delivery
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookBatch {
+    /// Also sent as `Idempotency-Key`
+    pub id: String,
+    pub subscription: String,
+    pub events: Vec<Event>,
+}
+
+/// The result of one delivery attempt
+#[derive(Debug, Clone, Serialize)]
+pub struct DeliveryOutcome {
+    pub batch_id: String,
+    pub events: usize,
+
+    /// 1 for the first attempt
+    pub attempt: u32,
+
+    /// HTTP status, if the target answered
+    pub status: Option<u16>,
+
+    pub error: Option<String>,
+    pub delivered: bool,
+    pub at: DateTime<Utc>,
+}
+
+/// A batch that will not be delivered
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetter {
+    pub batch: WebhookBatch,
+    pub attempts: u32,
+    pub reason: String,
+    pub at: DateTime<Utc>,
+}
+
+/// A snapshot of one subscription's delivery state
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookStatus {
+    pub subscription: String,
+    pub url: String,
+
+    /// Batches waiting for delivery or being delivered
+    pub queued: usize,
+
+    pub delivered_batches: u64,
+
+    /// The most recent attempts, oldest first
+    pub deliveries: Vec<DeliveryOutcome>,
+
+    pub dead_letters: Vec<DeadLetter>,
+}
+
+/// Delivers events to webhook subscriptions
+///
+/// Clones share subscriptions. Delivery stops when the last clone is
+/// dropped. [`attach`](Self::attach) spawns tasks, so it needs a Tokio
+/// runtime.
+#[derive(Clone, Default)]
+pub struct WebhookSubscriber {
+    inner: Arc<SubscriberInner>,
+}
+
+#[derive(Default)]
+struct SubscriberInner {
+    http: reqwest::Client,
+    redaction: Mutex<Arc<RedactionRules>>,
+    stream: Mutex<Option<EventStream>>,
+    subscriptions: Mutex<Vec<Arc<Subscription>>>,
+    next_id: AtomicU64,
+}
+
+impl Drop for SubscriberInner {
+    fn drop(&mut self) {
+        for subscription in self.subscriptions.get_mut().unwrap().iter() {
+            subscription.cancel.cancel();
+        }
+    }
+}
+
+impl WebhookSubscriber {
+    /// A subscriber with no subscriptions that redacts with
+    /// `RedactionRules::default()`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A subscriber with the subscriptions from `[[webhooks]]`
+    pub fn from_config(configs: &[WebhookConfig]) -> Self {
+        let subscriber = Self::new();
+        for config in configs {
+            subscriber.subscribe(WebhookSubscription::from_config(config));
+        }
+        subscriber
+    }
+
+    /// Redact payloads with `rules` instead of the defaults. Events from a
+    /// stream created `with_redaction` have already had that stream's rules
+    /// applied.
+    pub fn with_redaction(self, rules: RedactionRules) -> Self {
+        *self.inner.redaction.lock().unwrap() = Arc::new(rules);
+        self
+    }
+
+    /// Start delivering events from `stream`, beginning with the next event
+    /// appended. Only the first stream attached is used.
+    pub fn attach(&self, stream: &EventStream) {
+        let mut attached = self.inner.stream.lock().unwrap();
+        if attached.is_some() {
+            return;
+        }
+        *attached = Some(stream.clone());
+        for subscription in self.inner.subscriptions.lock().unwrap().iter() {
+            self.start(subscription, stream);
+        }
+    }
+
+    /// Add a subscription, starting it right away if attached; returns its id
+    pub fn subscribe(&self, config: WebhookSubscription) -> String {
+        let id = format!(
+            "wh_{}",
+            self.inner.next_id.fetch_add(1, Ordering::Relaxed) + 1
+        );
+        let subscription = Arc::new(Subscription {
+            id: id.clone(),
+            config,
+            queue: Mutex::new(VecDeque::new()),
+            in_flight: AtomicBool::new(false),
+            outcomes: Mutex::new(VecDeque::new()),
+            dead_letters: Mutex::new(VecDeque::new()),
+            delivered: AtomicU64::new(0),
+            ready: Notify::new(),
+            cancel: CancellationToken::new(),
+        });
+        if let Some(stream) = self.inner.stream.lock().unwrap().as_ref() {
+            self.start(&subscription, stream);
+        }
+        self.inner.subscriptions.lock().unwrap().push(subscription);
+        id
+    }
+
+    /// Stop and remove a subscription; queued batches are discarded
+    pub fn unsubscribe(&self, id: &str) -> bool {
+        let mut subscriptions = self.inner.subscriptions.lock().unwrap();
+        match subscriptions.iter().position(|s| s.id == id) {
+            Some(index) => {
+                subscriptions.remove(index).cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Ids of the current subscriptions, in the order they were added
+    pub fn subscriptions(&self) -> Vec<String> {
+        let subscriptions = self.inner.subscriptions.lock().unwrap();
+        subscriptions.iter().map(|s| s.id.clone()).collect()
+    }
+
+    pub fn status(&self, id: &str) -> Option<WebhookStatus> {
+        let subscription = {
+            let subscriptions = self.inner.subscriptions.lock().unwrap();
+            subscriptions.iter().find(|s| s.id == id)?.clone()
+        };
+        let queued = subscription.queue.lock().unwrap().len()
+            + usize::from(subscription.in_flight.load(Ordering::SeqCst));
+        let deliveries = subscription
+            .outcomes
+            .lock()
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect();
+        let dead_letters = subscription
+            .dead_letters
+            .lock()
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect();
+        Some(WebhookStatus {
+            subscription: subscription.id.clone(),
+            url: subscription.config.url.clone(),
+            queued,
+            delivered_batches: subscription.delivered.load(Ordering::SeqCst),
+            deliveries,
+            dead_letters,
+        })
+    }
+
+    fn start(&self, subscription: &Arc<Subscription>, stream: &EventStream) {
+        let events = stream.subscribe_from(stream.current_offset());
+        let redaction = self.inner.redaction.lock().unwrap().clone();
+        tokio::spawn(collect(subscription.clone(), events, redaction));
+        tokio::spawn(deliver(subscription.clone(), self.inner.http.clone()));
+    }
+}
+
+impl std::fmt::Debug for WebhookSubscriber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebhookSubscriber")
+            .field("subscriptions", &self.subscriptions())
+            .finish()
+    }
+}
+
+struct Subscription {
+    id: String,
+    config: WebhookSubscription,
+    queue: Mutex<VecDeque<WebhookBatch>>,
+    in_flight: AtomicBool,
+    outcomes: Mutex<VecDeque<DeliveryOutcome>>,
+    dead_letters: Mutex<VecDeque<DeadLetter>>,
+    delivered: AtomicU64,
+    ready: Notify,
+    cancel: CancellationToken,
+}
+
+impl Subscription {
+    fn enqueue(&self, events: Vec<Event>) {
+        let batch = WebhookBatch {
+            id: format!("whb_{}", uuid::Uuid::new_v4()),
+            subscription: self.id.clone(),
+            events,
+        };
+        let evicted = {
+            let mut queue = self.queue.lock().unwrap();
+            let evicted = (queue.len() >= self.config.queue_capacity)
+                .then(|| queue.pop_front())
+                .flatten();
+            queue.push_back(batch);
+            evicted
+        };
+        if let Some(oldest) = evicted {
+            self.dead_letter(oldest, 0, "delivery queue full".to_string());
+        }
+        self.ready.notify_one();
+    }
+
+    fn record(&self, outcome: DeliveryOutcome) {
+        let mut outcomes = self.outcomes.lock().unwrap();
+        if outcomes.len() == MAX_OUTCOMES {
+            outcomes.pop_front();
+        }
+        outcomes.push_back(outcome);
+    }
+
+    fn dead_letter(&self, batch: WebhookBatch, attempts: u32, reason: String) {
+        let mut dead_letters = self.dead_letters.lock().unwrap();
+        if dead_letters.len() == MAX_DEAD_LETTERS {
+            dead_letters.pop_front();
+        }
+        dead_letters.push_back(DeadLetter {
+            batch,
+            attempts,
+            reason,
+            at: Utc::now(),
+        });
+    }
+}
+
+/// Filter, redact and batch events for one subscription
+async fn collect(
+    subscription: Arc<Subscription>,
+    mut events: EventSubscription,
+    redaction: Arc<RedactionRules>,
+) {
+    let config = &subscription.config;
+    let mut batch = Vec::new();
+    let mut deadline = Instant::now();
+    loop {
+        tokio::select! {
+            _ = subscription.cancel.cancelled() => return,
+            mut event = events.recv() => {
+                if !config.filter.matches(&event) {
+                    continue;
+                }
+                redaction.redact_event(&mut event);
+                if batch.is_empty() {
+                    deadline = Instant::now() + config.batch_window;
+                }
+                batch.push(event);
+                if batch.len() >= config.max_batch_size {
+                    subscription.enqueue(std::mem::take(&mut batch));
+                }
+            }
+            _ = tokio::time::sleep_until(deadline), if !batch.is_empty() => {
+                subscription.enqueue(std::mem::take(&mut batch));
+            }
+        }
+    }
+}
+
+/// Send queued batches for one subscription, one at a time
+async fn deliver(subscription: Arc<Subscription>, http: reqwest::Client) {
+    let mut next_request = Instant::now();
+    loop {
+        let batch = {
+            let mut queue = subscription.queue.lock().unwrap();
+            let batch = queue.pop_front();
+            subscription
+                .in_flight
+                .store(batch.is_some(), Ordering::SeqCst);
+            batch
+        };
+        let Some(batch) = batch else {
+            tokio::select! {
+                _ = subscription.cancel.cancelled() => return,
+                _ = subscription.ready.notified() => continue,
+            }
+        };
+
+        let delivered = tokio::select! {
+            _ = subscription.cancel.cancelled() => return,
+            delivered = deliver_batch(&subscription, &http, batch, &mut next_request) => delivered,
+        };
+        if delivered {
+            subscription.delivered.fetch_add(1, Ordering::SeqCst);
+        }
+        subscription.in_flight.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Attempt a batch until it is delivered or dead-lettered
+async fn deliver_batch(
+    subscription: &Subscription,
+    http: &reqwest::Client,
+    batch: WebhookBatch,
+    next_request: &mut Instant,
+) -> bool {
+    let config = &subscription.config;
+    let body = serde_json::to_vec(&batch).unwrap_or_default();
+    let signature = config.secret.as_deref().map(|secret| sign(secret, &body));
+    let started = Instant::now();
+
+    for attempt in 1.. {
+        // Rate limit every request, retries included
+        if let Some(limit) = config.max_requests_per_second {
+            tokio::time::sleep_until(*next_request).await;
+            *next_request = Instant::now() + Duration::from_secs(1) / limit.max(1);
+        }
+
+        let mut request = http
+            .post(&config.url)
+            .timeout(config.timeout)
+            .header("Content-Type", "application/json")
+            .header("Idempotency-Key", &batch.id)
+            .header("X-Webhook-Subscription", &subscription.id)
+            .body(body.clone());
+        if let Some(signature) = &signature {
+            request = request.header("X-Webhook-Signature", signature);
+        }
+
+        let (status, error, retryable) = match request.send().await {
+            Ok(response) if response.status().is_success() => {
+                (Some(response.status().as_u16()), None, false)
+            }
+            Ok(response) => {
+                let status = response.status().as_u16();
+                let retryable = status >= 500 || status == 408 || status == 429;
+                (Some(status), Some(format!("HTTP {}", status)), retryable)
+            }
+            Err(e) => (None, Some(e.to_string()), true),
+        };
+        subscription.record(DeliveryOutcome {
+            batch_id: batch.id.clone(),
+            events: batch.events.len(),
+            attempt,
+            status,
+            error: error.clone(),
+            delivered: error.is_none(),
+            at: Utc::now(),
+        });
+
+        let Some(error) = error else {
+            return true;
+        };
+        if !retryable {
+            subscription.dead_letter(batch, attempt, format!("rejected: {}", error));
+            return false;
+        }
+        match config
+            .retry
+            .next_delay(attempt - 1, started.elapsed(), None)
+        {
+            Some(delay) => tokio::time::sleep(delay).await,
+            None => {
+                let reason = format!("gave up after {} attempts: {}", attempt, error);
+                subscription.dead_letter(batch, attempt, reason);
+                return false;
+            }
+        }
+    }
+    unreachable!("attempts are unbounded")
+}
+
+/// The `X-Webhook-Signature` value for `body`: `sha256=` and the hex
+/// HMAC-SHA256 keyed with `secret`
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mac = hmac_sha256(secret.as_bytes(), body);
+    let hex: String = mac.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256={}", hex)
+}
+
+/// Check an `X-Webhook-Signature` value in constant time
+pub fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let expected = sign(secret, body);
+    expected.len() == signature.len()
+        && expected
+            .bytes()
+            .zip(signature.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// HMAC (RFC 2104) over SHA-256
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(block.map(|b| b ^ 0x36));
+    inner.update(message);
+    let mut outer = Sha256::new();
+    outer.update(block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmac_matches_rfc_4231() {
+        // Test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        // Test case 6: a key longer than the block size is hashed first
+        let mac = hmac_sha256(
+            &[0xaa; 131],
+            b"Test Using Larger Than Block-Size Key - Hash Key First",
+        );
+        let hex: String = mac.iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(
+            hex,
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+
+        let signature = sign("s3cret", b"{}");
+        assert!(verify_signature("s3cret", b"{}", &signature));
+        assert!(!verify_signature("s3cret", b"{ }", &signature));
+        assert!(!verify_signature("other", b"{}", &signature));
+    }
+}
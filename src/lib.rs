@@ -1,13 +1,26 @@
 // Core modules
 pub mod agent;
+pub mod artifact;
 pub mod config;
+pub mod document;
 pub mod error;
 pub mod event;
+pub mod limits;
 pub mod llm;
 pub mod logging;
+pub mod metrics;
+pub mod paths;
+pub mod persist;
+pub mod pii;
 pub mod runtime;
+pub mod schema;
+#[cfg(feature = "workflow")]
+pub mod session;
+mod telemetry;
+pub mod template;
 pub mod tools;
 pub mod types;
+pub mod usage;
 
 // Workflow runtime — opt-in via the `workflow` feature.
 #[cfg(feature = "workflow")]
@@ -32,41 +45,98 @@ pub use workflow::step;
 pub use workflow::steps as step_impls;
 
 // Re-exports for convenience
-pub use agent::{Agent, AgentConfig};
+pub use agent::{
+    Agent, AgentConfig, BudgetSignal, BudgetSignals, Guardrail, GuardrailDecision, Handoff,
+    HandoffTargets, KeywordBlocklist, LatencySlo, LearnedPrefetch, MaxIterationsBehavior,
+    PredictedCall, PrefetchRule, PromptTemplate, PromptVars, ReflectionConfig, ReflectionReport,
+    ReflectionVerdict, RegexRedactor, SloAttainment, SlowTurnReport, SpeculationStats,
+    SpeculativePrefetcher, SystemPromptPolicy, ToolCallingMode, TurnLatency,
+};
+#[cfg(feature = "macros")]
+pub use agent_runtime_macros::workflow;
+pub use artifact::{Artifact, ArtifactRef, ArtifactStore, NewArtifact};
 pub use config::{
-    LlamaConfig, LlmConfig, LoggingConfig, OpenAIConfig, RetryConfig, RuntimeConfig,
-    TimeoutConfigSettings, WorkflowConfig,
+    AnthropicConfig, EmbeddingConfig, GeminiConfig, LlamaConfig, LlmConfig, LoggingConfig,
+    OllamaConfig, OpenAIConfig, PiiConfig, RetryConfig, RuntimeConfig, TimeoutConfigSettings,
+    UsageConfig, WebhookConfig, WorkflowConfig,
 };
+#[cfg(all(feature = "workflow", feature = "sqlite"))]
+pub use context::SqliteContextStore;
 #[cfg(feature = "workflow")]
 pub use context::{
-    ContextError, ContextManager, MergeStrategy, NoOpManager, WorkflowContext, WorkflowMetadata,
+    analyze_context, ContextDiagnostics, ContextError, ContextManager, ContextMonitor,
+    ContextRecord, ContextReport, ContextSnapshot, ContextStore, ContextStoreError,
+    FileContextStore, HeuristicCounter, MemoryContextStore, MergeStrategy, NoOpManager,
+    PruneOutcome, PruneReport, PruneStage, SimpleTokenEstimator, StoredContext, TokenCounter,
+    TokenEstimator, WorkflowContext, WorkflowMetadata,
 };
 #[cfg(feature = "workflow")]
 pub use context_strategies::{
-    MessageTypeManager, SlidingWindowManager, SummarizationManager, TokenBudgetManager,
+    CompositeContextManager, MessageTypeManager, SlidingWindowManager, SummarizationManager,
+    TokenBudgetManager,
 };
+pub use document::{DocumentPatch, LiveDocument, Revision};
 pub use error::{
-    AgentError, AgentErrorCode, ConfigError, ConfigErrorCode, LlmError, LlmErrorCode, RuntimeError,
-    ToolError, ToolErrorCode, WorkflowError, WorkflowErrorCode,
+    AgentError, AgentErrorCode, AggregateError, ConfigError, ConfigErrorCode, FailedItem,
+    InputViolation, LlmError, LlmErrorCode, RuntimeError, ToolError, ToolErrorCode, WorkflowError,
+    WorkflowErrorCode,
+};
+pub use event::webhook::{WebhookSubscriber, WebhookSubscription};
+pub use event::{
+    ComponentStatus, Event, EventFilter, EventQuery, EventScope, EventStats, EventStream,
+    EventSubscription, EventType, RedactionRules,
 };
-pub use event::{ComponentStatus, Event, EventScope, EventStream, EventType};
-pub use llm::{ChatMessage, ChatRequest, ChatResponse, LlmClient, Role};
-pub use logging::FileLogger;
+pub use limits::{ConversationLimits, LimitAction, LimitExceeded};
+#[cfg(feature = "tiktoken")]
+pub use llm::TiktokenCounter;
+pub use llm::{
+    ChatMessage, ChatRequest, ChatResponse, EmbeddingClient, LlmClient, Role, SamplingParams,
+};
+pub use logging::{init_tracing, FileLogger, TraceSubscriber, TRACE_FILE_NAME};
+pub use paths::{PathError, Sandbox};
+pub use persist::{PersistError, PersistFormat};
+pub use pii::{PiiAction, PiiDetector, PiiFinding, PiiFindings, PiiScanner, PiiType};
 pub use retry::RetryPolicy;
 #[cfg(feature = "workflow")]
-pub use runtime::Runtime;
+pub use runtime::{
+    CancellationHandle, CheckpointStore, FileCheckpointStore, RerunOptions, RunCheckpoint, Runtime,
+    WorkflowBatch, WorkflowStream, WorkflowUpdate,
+};
+pub use schema::InputSchema;
+#[cfg(feature = "workflow")]
+pub use session::{Session, SessionSnapshot};
 #[cfg(feature = "workflow")]
 pub use step::{ExecutionContext, Step, StepError, StepInput, StepOutput, StepResult, StepType};
+pub use template::{
+    validate_template, MissingVariables, Template, TemplateError, TemplateIssue, TemplateLimits,
+};
 pub use timeout::{with_timeout, TimeoutConfig};
 pub use tools::{
-    McpClient, McpTool, McpToolInfo, NativeTool, Tool, ToolCallTracker, ToolLoopDetectionConfig,
-    ToolRegistry,
+    CancellationToken, FsTools, HttpEndpoint, HttpTool, HttpToolBuilder, LoopRule, McpClient,
+    McpPrompt, McpPromptArgument, McpPromptInfo, McpResourceContents, McpResourceInfo,
+    McpResourceTool, McpTool, McpToolInfo, McpTransport, NativeTool, SimilarityConfig, SpecImport,
+    Tool, ToolBinder, ToolCacheConfig, ToolCallTracker, ToolLoopDetectionConfig, ToolRegistry,
+    ToolResultTransformer, ToolRunContext, ToolSpec, ToolSpecError, UnboundPolicy,
+    VectorSearchTool, VectorStore,
 };
 pub use types::*;
+pub use usage::{
+    Budget, BudgetDimension, BudgetExceeded, StepUsage, UsageLedger, UsageSummary, UsageTotals,
+    WorkflowUsage,
+};
 #[cfg(feature = "workflow")]
-pub use workflow::steps::{AgentStep, ConditionalStep, SubWorkflowStep, TransformStep};
+pub use workflow::steps::{
+    AgentStep, ApprovalQueue, ApprovalRecord, ApprovalStep, ConditionalStep, Decision,
+    ForEachFailureMode, ForEachStep, LoopExhaustedMode, LoopStep, OnError, ParallelFailureMode,
+    ParallelOutput, ParallelStep, PendingApproval, PolicyStep, StepPolicy, StepStatus,
+    SubWorkflowStep, SwitchStep, TransformStep, TryTransformStep, WaitStep,
+};
 #[cfg(feature = "workflow")]
-pub use workflow::{Workflow, WorkflowBuilder, WorkflowState};
+pub use workflow::{
+    CriticConfig, CriticReport, CriticVerdict, ReportFiles, ReportOptions, StepDefinition,
+    StepFailure, SubWorkflowSummary, Workflow, WorkflowBuilder, WorkflowDefinition,
+    WorkflowFactory, WorkflowState,
+};
 
 // Prelude module for convenient imports in tests and examples
 pub mod prelude {
@@ -75,10 +145,16 @@ pub mod prelude {
     pub use crate::llm::{ChatMessage, ChatRequest, ChatResponse, LlmClient, Role};
     pub use crate::tools::{NativeTool, Tool, ToolRegistry};
     pub use crate::types::{
-        AgentInput, AgentOutput, ToolError as TypesToolError, ToolResult, ToolStatus,
+        AgentInput, AgentOutput, ToolError as TypesToolError, ToolErrorDetail, ToolResult,
+        ToolStatus,
     };
     #[cfg(feature = "workflow")]
-    pub use crate::workflow::steps::{AgentStep, ConditionalStep, SubWorkflowStep, TransformStep};
+    pub use crate::workflow::steps::{
+        AgentStep, ApprovalStep, ConditionalStep, Decision, ForEachFailureMode, ForEachStep,
+        LoopExhaustedMode, LoopStep, OnError, ParallelFailureMode, ParallelOutput, ParallelStep,
+        PolicyStep, StepPolicy, StepStatus, SubWorkflowStep, SwitchStep, TransformStep,
+        TryTransformStep, WaitStep,
+    };
     #[cfg(feature = "workflow")]
     pub use crate::workflow::Workflow;
 
@@ -0,0 +1,456 @@
+//! Hard caps on conversation size.
+//!
+//! Token budgets bound how much context a model sees; [`ConversationLimits`]
+//! bound how much conversation there is at all, which keeps long-lived
+//! sessions predictable and limits abuse:
+//!
+//! - `max_turns`: user messages in the history
+//! - `max_total_messages`: messages of any role in the history
+//! - `max_tool_messages_per_execution`: tool results produced by a single
+//!   agent execution
+//!
+//! Caps are checked whenever messages are appended. Exceeding one triggers
+//! the configured [`LimitAction`]: reject the append with a [`LimitExceeded`]
+//! error, prune aggressively (down to half the cap), or drop just enough of
+//! the oldest messages to fit. System messages are never dropped, and
+//! dropping an assistant tool call also drops its tool results.
+
+use crate::event::{ComponentStatus, EventScope, EventStream, EventType};
+use crate::llm::types::{ChatMessage, Role};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+
+/// Caps on conversation size; `None` means unlimited
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConversationLimits {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_turns: Option<usize>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_total_messages: Option<usize>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tool_messages_per_execution: Option<usize>,
+
+    /// What to do when a cap is exceeded
+    #[serde(default)]
+    pub action: LimitAction,
+}
+
+/// What happens when an append would exceed a cap
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitAction {
+    /// Refuse the append and surface a [`LimitExceeded`] error
+    #[default]
+    Reject,
+    /// Drop the oldest messages until the conversation is at half the cap
+    Prune,
+    /// Drop just enough of the oldest messages to fit the cap
+    DropOldest,
+}
+
+/// Which cap was hit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitKind {
+    Turns,
+    TotalMessages,
+    ToolMessagesPerExecution,
+}
+
+impl LimitKind {
+    fn describe(&self) -> &'static str {
+        match self {
+            LimitKind::Turns => "turns",
+            LimitKind::TotalMessages => "messages",
+            LimitKind::ToolMessagesPerExecution => "tool messages per execution",
+        }
+    }
+}
+
+/// A rejected append
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, thiserror::Error)]
+#[error("Conversation limit reached: {attempted} {} exceeds the limit of {limit}", .kind.describe())]
+pub struct LimitExceeded {
+    pub kind: LimitKind,
+    pub limit: usize,
+    /// Size the conversation would have had
+    pub attempted: usize,
+}
+
+impl LimitExceeded {
+    /// Message suitable for showing to the end user
+    pub fn user_message(&self) -> String {
+        match self.kind {
+            LimitKind::Turns => format!(
+                "This conversation has reached its limit of {} turns. Please start a new conversation.",
+                self.limit
+            ),
+            LimitKind::TotalMessages => format!(
+                "This conversation has reached its limit of {} messages. Please start a new conversation.",
+                self.limit
+            ),
+            LimitKind::ToolMessagesPerExecution => format!(
+                "The request needed more than {} tool results to answer and was stopped.",
+                self.limit
+            ),
+        }
+    }
+
+    /// The rejection as a [`LimitEvent`]
+    pub fn event(&self) -> LimitEvent {
+        LimitEvent {
+            kind: self.kind,
+            limit: self.limit,
+            attempted: self.attempted,
+            action: LimitAction::Reject,
+            removed: 0,
+            at: Utc::now(),
+        }
+    }
+}
+
+/// A cap being hit, and what was done about it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LimitEvent {
+    pub kind: LimitKind,
+    pub limit: usize,
+    pub attempted: usize,
+    pub action: LimitAction,
+    /// Messages dropped to get back under the cap
+    pub removed: usize,
+    pub at: DateTime<Utc>,
+}
+
+impl LimitEvent {
+    /// Emit as a `system:conversation_limit` event
+    pub fn emit(&self, stream: &EventStream, workflow_id: String, agent: Option<&str>) {
+        let message = match self.action {
+            LimitAction::Reject => format!(
+                "Conversation limit reached: {} {} exceeds {}",
+                self.attempted,
+                self.kind.describe(),
+                self.limit
+            ),
+            _ => format!(
+                "Conversation limit on {} hit ({} > {}); dropped {} oldest messages",
+                self.kind.describe(),
+                self.attempted,
+                self.limit,
+                self.removed
+            ),
+        };
+        stream.append(
+            EventScope::System,
+            EventType::Progress,
+            "system:conversation_limit".to_string(),
+            ComponentStatus::Running,
+            workflow_id,
+            Some(message),
+            serde_json::json!({
+                "agent": agent,
+                "event": self,
+            }),
+        );
+    }
+}
+
+/// Lifetime record of caps being hit
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LimitStats {
+    pub rejected: usize,
+    pub pruned: usize,
+    pub dropped: usize,
+    pub events: Vec<LimitEvent>,
+}
+
+impl LimitStats {
+    pub fn record(&mut self, event: LimitEvent) {
+        match event.action {
+            LimitAction::Reject => self.rejected += 1,
+            LimitAction::Prune => self.pruned += 1,
+            LimitAction::DropOldest => self.dropped += 1,
+        }
+        self.events.push(event);
+    }
+
+    pub fn total(&self) -> usize {
+        self.events.len()
+    }
+}
+
+impl ConversationLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_turns(mut self, max: usize) -> Self {
+        self.max_turns = Some(max);
+        self
+    }
+
+    pub fn with_max_total_messages(mut self, max: usize) -> Self {
+        self.max_total_messages = Some(max);
+        self
+    }
+
+    pub fn with_max_tool_messages_per_execution(mut self, max: usize) -> Self {
+        self.max_tool_messages_per_execution = Some(max);
+        self
+    }
+
+    pub fn with_action(mut self, action: LimitAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Whether no cap is set
+    pub fn is_unlimited(&self) -> bool {
+        self.max_turns.is_none()
+            && self.max_total_messages.is_none()
+            && self.max_tool_messages_per_execution.is_none()
+    }
+
+    /// Size to shrink back to once `limit` is exceeded
+    fn target(&self, limit: usize) -> usize {
+        match self.action {
+            LimitAction::Prune => (limit / 2).max(1),
+            _ => limit,
+        }
+    }
+
+    /// Check the turn and total-message caps against `messages`, applying
+    /// the configured action. On rejection `messages` is left untouched.
+    pub fn enforce(
+        &self,
+        messages: &mut Vec<ChatMessage>,
+    ) -> Result<Vec<LimitEvent>, LimitExceeded> {
+        let turns = count_turns(messages);
+        let total = messages.len();
+        if self.action == LimitAction::Reject {
+            if let Some(limit) = self.max_turns.filter(|limit| turns > *limit) {
+                return Err(LimitExceeded {
+                    kind: LimitKind::Turns,
+                    limit,
+                    attempted: turns,
+                });
+            }
+            if let Some(limit) = self.max_total_messages.filter(|limit| total > *limit) {
+                return Err(LimitExceeded {
+                    kind: LimitKind::TotalMessages,
+                    limit,
+                    attempted: total,
+                });
+            }
+            return Ok(Vec::new());
+        }
+
+        let mut events = Vec::new();
+        if let Some(limit) = self.max_turns.filter(|limit| turns > *limit) {
+            let target = self.target(limit);
+            let before = messages.len();
+            while count_turns(messages) > target && drop_oldest_turn(messages) {}
+            events.push(self.event(LimitKind::Turns, limit, turns, before - messages.len()));
+        }
+
+        let total = messages.len();
+        if let Some(limit) = self.max_total_messages.filter(|limit| total > *limit) {
+            let target = self.target(limit);
+            while messages.len() > target && drop_oldest_message(messages) {}
+            events.push(self.event(
+                LimitKind::TotalMessages,
+                limit,
+                total,
+                total - messages.len(),
+            ));
+        }
+        Ok(events)
+    }
+
+    /// Check the per-execution tool cap. `exchanges` holds the tool call ids
+    /// of each tool-calling assistant message of the current execution, oldest
+    /// first; the newest one is never dropped, so if it alone exceeds the cap
+    /// the append is rejected whatever the action.
+    pub fn enforce_tool_messages(
+        &self,
+        messages: &mut Vec<ChatMessage>,
+        exchanges: &mut VecDeque<Vec<String>>,
+    ) -> Result<Option<LimitEvent>, LimitExceeded> {
+        let Some(limit) = self.max_tool_messages_per_execution else {
+            return Ok(None);
+        };
+        let count: usize = exchanges.iter().map(Vec::len).sum();
+        if count <= limit {
+            return Ok(None);
+        }
+        let exceeded = LimitExceeded {
+            kind: LimitKind::ToolMessagesPerExecution,
+            limit,
+            attempted: count,
+        };
+        let newest = exchanges.back().map(Vec::len).unwrap_or_default();
+        if self.action == LimitAction::Reject || newest > limit {
+            return Err(exceeded);
+        }
+
+        let target = self.target(limit).max(newest);
+        let before = messages.len();
+        let mut remaining = count;
+        while remaining > target && exchanges.len() > 1 {
+            let ids = exchanges.pop_front().unwrap_or_default();
+            remaining -= ids.len();
+            drop_tool_exchange(messages, &ids);
+        }
+        Ok(Some(self.event(
+            LimitKind::ToolMessagesPerExecution,
+            limit,
+            count,
+            before - messages.len(),
+        )))
+    }
+
+    fn event(&self, kind: LimitKind, limit: usize, attempted: usize, removed: usize) -> LimitEvent {
+        LimitEvent {
+            kind,
+            limit,
+            attempted,
+            action: self.action,
+            removed,
+            at: Utc::now(),
+        }
+    }
+}
+
+/// Drop the first assistant message calling exactly `ids`, and the tool
+/// results that follow it. Matching by position as well as id keeps repeated
+/// ids (some local models reuse `call_0`) from taking out later exchanges.
+fn drop_tool_exchange(messages: &mut Vec<ChatMessage>, ids: &[String]) {
+    let Some(call) = messages.iter().position(|m| {
+        m.tool_calls
+            .as_ref()
+            .is_some_and(|calls| calls.iter().map(|c| &c.id).eq(ids.iter()))
+    }) else {
+        return;
+    };
+    let mut end = call + 1;
+    while end < messages.len()
+        && messages[end].role == Role::Tool
+        && messages[end]
+            .tool_call_id
+            .as_ref()
+            .is_some_and(|id| ids.contains(id))
+    {
+        end += 1;
+    }
+    messages.drain(call..end);
+}
+
+fn count_turns(messages: &[ChatMessage]) -> usize {
+    messages.iter().filter(|m| m.role == Role::User).count()
+}
+
+/// Drop everything before the second user message except system messages.
+/// Returns false once only one turn is left.
+fn drop_oldest_turn(messages: &mut Vec<ChatMessage>) -> bool {
+    let mut users = messages
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| m.role == Role::User)
+        .map(|(i, _)| i);
+    let (Some(_), Some(next)) = (users.next(), users.next()) else {
+        return false;
+    };
+    let mut index = 0;
+    messages.retain(|m| {
+        index += 1;
+        index > next || m.role == Role::System
+    });
+    true
+}
+
+/// Drop the oldest non-system message along with any tool results it
+/// leaves orphaned. The newest user message is never dropped.
+fn drop_oldest_message(messages: &mut Vec<ChatMessage>) -> bool {
+    let last_user = messages.iter().rposition(|m| m.role == Role::User);
+    let Some(oldest) = messages
+        .iter()
+        .position(|m| m.role != Role::System)
+        .filter(|i| Some(*i) != last_user)
+    else {
+        return false;
+    };
+    messages.remove(oldest);
+
+    let known: HashSet<String> = messages
+        .iter()
+        .filter_map(|m| m.tool_calls.as_ref())
+        .flatten()
+        .map(|c| c.id.clone())
+        .collect();
+    messages.retain(|m| {
+        m.role != Role::Tool || m.tool_call_id.as_ref().is_some_and(|id| known.contains(id))
+    });
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::types::{FunctionCall, ToolCall};
+
+    fn tool_call(id: &str) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            r#type: "function".to_string(),
+            function: FunctionCall {
+                name: "search".to_string(),
+                arguments: "{}".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_drop_oldest_turn_keeps_system_prompt() {
+        let mut messages = vec![
+            ChatMessage::system("sys"),
+            ChatMessage::user("one"),
+            ChatMessage::assistant("1"),
+            ChatMessage::user("two"),
+            ChatMessage::assistant("2"),
+            ChatMessage::user("three"),
+        ];
+        let limits = ConversationLimits::new()
+            .with_max_turns(2)
+            .with_action(LimitAction::DropOldest);
+
+        let events = limits.enforce(&mut messages).unwrap();
+
+        assert_eq!(events[0].removed, 2);
+        assert_eq!(messages[0].role, Role::System);
+        assert_eq!(messages[1].content, "two");
+        assert_eq!(count_turns(&messages), 2);
+    }
+
+    #[test]
+    fn test_dropping_tool_call_drops_its_results() {
+        let mut messages = vec![
+            ChatMessage::user("q"),
+            ChatMessage::assistant_with_tool_calls("", vec![tool_call("a")]),
+            ChatMessage::tool_result("a", "hit"),
+            ChatMessage::assistant("answer"),
+            ChatMessage::user("q2"),
+        ];
+        let limits = ConversationLimits::new()
+            .with_max_total_messages(3)
+            .with_action(LimitAction::DropOldest);
+
+        let events = limits.enforce(&mut messages).unwrap();
+
+        // Dropping "q" and the tool call leaves the result orphaned
+        assert_eq!(events[0].removed, 3);
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|m| m.role != Role::Tool));
+    }
+}
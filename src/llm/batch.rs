@@ -0,0 +1,194 @@
+//! Provider batch APIs, for requests that can wait.
+//!
+//! A [`BatchChatClient`] submits many chat requests as one batch job, which
+//! the provider runs within its completion window (24 hours for OpenAI) at
+//! a discount. Submitting returns a [`BatchHandle`]; it serializes, so one
+//! process can submit a batch and a later one poll it and collect the
+//! results.
+//!
+//! `Runtime::execute_batch` runs a single-agent workflow over many inputs
+//! this way.
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::llm::{ChatRequest, ChatResponse, LlmError, LlmResult};
+
+/// A chat client that can run requests as a provider batch
+#[async_trait]
+pub trait BatchChatClient: Send + Sync {
+    /// Upload `requests` and start a batch over them
+    async fn submit_batch(&self, requests: Vec<ChatRequest>) -> LlmResult<BatchHandle>;
+
+    /// Where the batch is at
+    async fn poll(&self, handle: &BatchHandle) -> LlmResult<BatchStatus>;
+
+    /// One result per submitted request, in submission order. Requests the
+    /// batch didn't complete (it failed, expired or was cancelled) are
+    /// errors.
+    async fn results(&self, handle: &BatchHandle) -> LlmResult<Vec<LlmResult<ChatResponse>>>;
+}
+
+/// A submitted batch; keep it to poll and collect the batch later
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchHandle {
+    /// Provider name, e.g. `openai`
+    pub provider: String,
+
+    /// The provider's batch ID
+    pub batch_id: String,
+
+    /// The ID each request was submitted under, in submission order
+    pub custom_ids: Vec<String>,
+
+    pub submitted_at: DateTime<Utc>,
+}
+
+impl BatchHandle {
+    /// Requests in the batch
+    pub fn len(&self) -> usize {
+        self.custom_ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.custom_ids.is_empty()
+    }
+}
+
+/// The lifecycle of a batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchState {
+    Validating,
+    InProgress,
+    Finalizing,
+    Completed,
+    Failed,
+    Expired,
+    Cancelling,
+    Cancelled,
+}
+
+impl BatchState {
+    /// Whether the batch is done running; its results can be collected
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            BatchState::Completed
+                | BatchState::Failed
+                | BatchState::Expired
+                | BatchState::Cancelled
+        )
+    }
+}
+
+/// A batch's state and request counts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchStatus {
+    pub state: BatchState,
+    pub total: usize,
+    pub completed: usize,
+    pub failed: usize,
+}
+
+impl BatchStatus {
+    pub fn is_finished(&self) -> bool {
+        self.state.is_finished()
+    }
+}
+
+/// The custom ID of the request at `index`
+pub(crate) fn custom_id(index: usize) -> String {
+    format!("request-{}", index)
+}
+
+/// Order results keyed by custom ID as the handle's requests were
+/// submitted; a request without a result is an error
+pub(crate) fn correlate(
+    handle: &BatchHandle,
+    mut results: HashMap<String, LlmResult<ChatResponse>>,
+) -> Vec<LlmResult<ChatResponse>> {
+    handle
+        .custom_ids
+        .iter()
+        .map(|id| {
+            results.remove(id).unwrap_or_else(|| {
+                Err(LlmError::ApiError(format!(
+                    "batch {} has no result for {}",
+                    handle.batch_id, id
+                )))
+            })
+        })
+        .collect()
+}
+
+#[cfg(feature = "workflow")]
+pub(crate) use scope::{intercept, scoped, BatchTurn};
+#[cfg(not(feature = "workflow"))]
+pub(crate) async fn intercept(_request: &ChatRequest) -> Option<LlmResult<ChatResponse>> {
+    None
+}
+
+/// Answering the agent requests of one batched run in place of its client
+#[cfg(feature = "workflow")]
+mod scope {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::sync::oneshot;
+
+    use crate::llm::{ChatRequest, ChatResponse, LlmError, LlmResult};
+
+    /// What agent requests do inside [`scoped`]
+    pub(crate) enum BatchTurn {
+        /// Hand the first request over and leave it unanswered
+        Capture(Mutex<Option<oneshot::Sender<ChatRequest>>>),
+
+        /// Answer the first request with the batch's result for the run
+        Replay(Mutex<Option<LlmResult<ChatResponse>>>),
+    }
+
+    impl BatchTurn {
+        /// A turn capturing the run's request, and where it arrives
+        pub(crate) fn capture() -> (Arc<Self>, oneshot::Receiver<ChatRequest>) {
+            let (tx, rx) = oneshot::channel();
+            (Arc::new(BatchTurn::Capture(Mutex::new(Some(tx)))), rx)
+        }
+
+        pub(crate) fn replay(result: LlmResult<ChatResponse>) -> Arc<Self> {
+            Arc::new(BatchTurn::Replay(Mutex::new(Some(result))))
+        }
+    }
+
+    tokio::task_local! {
+        static CURRENT_TURN: Arc<BatchTurn>;
+    }
+
+    /// Run `fut` with its agent requests answered by `turn`
+    pub(crate) async fn scoped<F: std::future::Future>(turn: Arc<BatchTurn>, fut: F) -> F::Output {
+        CURRENT_TURN.scope(turn, fut).await
+    }
+
+    /// Answer `request` from the current [`BatchTurn`]; `None` outside one.
+    /// A captured request never completes: the run is dropped once it has
+    /// been handed over.
+    pub(crate) async fn intercept(request: &ChatRequest) -> Option<LlmResult<ChatResponse>> {
+        let turn = CURRENT_TURN.try_with(Arc::clone).ok()?;
+        match turn.as_ref() {
+            BatchTurn::Capture(sender) => {
+                let sender = sender.lock().unwrap().take();
+                if let Some(sender) = sender {
+                    let _ = sender.send(request.clone());
+                }
+                std::future::pending().await
+            }
+            BatchTurn::Replay(result) => Some(result.lock().unwrap().take().unwrap_or_else(|| {
+                Err(LlmError::InvalidRequest(
+                    "a batched run gets one LLM response; this agent asked again".to_string(),
+                ))
+            })),
+        }
+    }
+}
@@ -0,0 +1,397 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use crate::llm::types::{ToolCall, ToolChoice};
+use crate::llm::{
+    AppliedEffort, ChatMessage, ChatRequest, ChatResponse, Effort, GenericChatClient, LlmClient,
+    LlmResult, MessageContent, Role,
+};
+
+/// Where [`CachedChatClient`] keeps responses, by request hash
+///
+/// Lookups and stores never fail: an entry that can't be read is a miss,
+/// and one that can't be written is dropped.
+pub trait LlmCache: Send + Sync {
+    /// The response stored under `key`, unless it is missing or expired
+    fn get(&self, key: &str) -> Option<ChatResponse>;
+
+    fn put(&self, key: &str, response: ChatResponse);
+}
+
+/// Lookups a [`CachedChatClient`] has made so far
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    /// Requests answered from the cache
+    pub hits: u64,
+
+    /// Requests passed on to the provider, then stored
+    pub misses: u64,
+
+    /// Requests with `no_cache` set
+    pub bypassed: u64,
+}
+
+struct MemoryEntry {
+    response: ChatResponse,
+    stored: Instant,
+    /// Position in `MemoryState::recency`
+    used: u64,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    entries: HashMap<String, MemoryEntry>,
+    /// Keys from least to most recently used
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+/// An in-process cache holding at most `capacity` responses, evicting the
+/// least recently used one first
+pub struct MemoryLlmCache {
+    capacity: usize,
+    ttl: Option<Duration>,
+    state: Mutex<MemoryState>,
+}
+
+impl MemoryLlmCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ttl: None,
+            state: Mutex::new(MemoryState::default()),
+        }
+    }
+
+    /// Treat entries older than `ttl` as missing (default: keep them until
+    /// evicted)
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl LlmCache for MemoryLlmCache {
+    fn get(&self, key: &str) -> Option<ChatResponse> {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        let entry = state.entries.get_mut(key)?;
+        if self.ttl.is_some_and(|ttl| entry.stored.elapsed() >= ttl) {
+            state.recency.remove(&entry.used);
+            state.entries.remove(key);
+            return None;
+        }
+        state.recency.remove(&entry.used);
+        state.tick += 1;
+        entry.used = state.tick;
+        state.recency.insert(entry.used, key.to_string());
+        Some(entry.response.clone())
+    }
+
+    fn put(&self, key: &str, response: ChatResponse) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let used = state.tick;
+        let entry = MemoryEntry {
+            response,
+            stored: Instant::now(),
+            used,
+        };
+        if let Some(replaced) = state.entries.insert(key.to_string(), entry) {
+            state.recency.remove(&replaced.used);
+        }
+        state.recency.insert(used, key.to_string());
+        while state.entries.len() > self.capacity {
+            let Some((_, oldest)) = state.recency.pop_first() else {
+                break;
+            };
+            state.entries.remove(&oldest);
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct DiskEntry {
+    /// Milliseconds since the Unix epoch
+    stored_at_ms: u64,
+    response: ChatResponse,
+}
+
+/// A cache of JSON files, one per request hash, under a directory
+///
+/// Entries outlive the process, so an eval loop re-run tomorrow still hits.
+/// The directory is created on the first store.
+pub struct DiskLlmCache {
+    dir: PathBuf,
+    ttl: Option<Duration>,
+}
+
+impl DiskLlmCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            ttl: None,
+        }
+    }
+
+    /// Treat files older than `ttl` as missing, and delete them when found
+    /// (default: keep them forever)
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
+
+impl LlmCache for DiskLlmCache {
+    fn get(&self, key: &str) -> Option<ChatResponse> {
+        let path = self.path(key);
+        let content = std::fs::read_to_string(&path).ok()?;
+        let entry: DiskEntry = serde_json::from_str(&content).ok()?;
+        if let Some(ttl) = self.ttl {
+            if now_ms().saturating_sub(entry.stored_at_ms) >= ttl.as_millis() as u64 {
+                let _ = std::fs::remove_file(&path);
+                return None;
+            }
+        }
+        Some(entry.response)
+    }
+
+    fn put(&self, key: &str, response: ChatResponse) {
+        let entry = DiskEntry {
+            stored_at_ms: now_ms(),
+            response,
+        };
+        // Write then rename, so a concurrent reader never sees half a file
+        let written = std::fs::create_dir_all(&self.dir)
+            .and_then(|()| serde_json::to_string(&entry).map_err(std::io::Error::other))
+            .and_then(|json| {
+                let partial = self
+                    .dir
+                    .join(format!("{}.json.{}.tmp", key, uuid::Uuid::new_v4()));
+                std::fs::write(&partial, json)?;
+                std::fs::rename(&partial, self.path(key))
+            });
+        if let Err(e) = written {
+            tracing::warn!(key, error = %e, "failed to write LLM cache entry");
+        }
+    }
+}
+
+/// A message as it counts for the cache key: provenance is left out, so
+/// the same conversation hits whichever agent or workflow sends it
+#[derive(Serialize)]
+struct KeyMessage<'a> {
+    role: &'a Role,
+    content: &'a MessageContent,
+    tool_calls: &'a Option<Vec<ToolCall>>,
+    tool_call_id: &'a Option<String>,
+}
+
+#[derive(Serialize)]
+struct Key<'a> {
+    provider: &'a str,
+    model: Option<&'a str>,
+    messages: Vec<KeyMessage<'a>>,
+    tools: &'a Option<Vec<JsonValue>>,
+    // Skipped when unset, so keys from before it existed still match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<ToolChoice>,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    max_tokens: Option<u32>,
+    seed: Option<u64>,
+    stop: &'a Option<Vec<String>>,
+    frequency_penalty: Option<f32>,
+    presence_penalty: Option<f32>,
+    reasoning_effort: &'a Option<String>,
+}
+
+/// A client that answers repeated requests from a cache instead of the
+/// provider
+///
+/// Requests are keyed by a SHA-256 of the provider name, the model set
+/// with [`with_model`](Self::with_model), the messages, the tools, the
+/// tool choice and every sampling parameter. Responses with tool calls are
+/// cached like any other; errors are not. A request with `no_cache` set
+/// goes straight to the provider and is not stored.
+///
+/// A streamed hit sends the cached content through `tx` in word-sized
+/// chunks, so the agent sees what it would have seen from the provider. A
+/// streamed miss is forwarded as it arrives and stored once complete.
+///
+/// ```rust,ignore
+/// let cache = Arc::new(DiskLlmCache::new(".llm-cache").with_ttl(Duration::from_secs(86_400)));
+/// let client = CachedChatClient::new(Arc::new(OpenAIClient::new(api_key)), cache)
+///     .with_model("gpt-4o");
+/// ```
+pub struct CachedChatClient {
+    inner: LlmClient,
+    cache: Arc<dyn LlmCache>,
+    model: Option<String>,
+    stats: Mutex<CacheStats>,
+}
+
+impl CachedChatClient {
+    pub fn new(inner: LlmClient, cache: Arc<dyn LlmCache>) -> Self {
+        Self {
+            inner,
+            cache,
+            model: None,
+            stats: Mutex::new(CacheStats::default()),
+        }
+    }
+
+    /// Include `model` in the key, to share one cache between clients of
+    /// the same provider that use different models
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// The hex SHA-256 `request` is cached under
+    pub fn key(&self, request: &ChatRequest) -> String {
+        let key = Key {
+            provider: self.inner.provider_name(),
+            model: self.model.as_deref(),
+            messages: request.messages.iter().map(key_message).collect(),
+            tools: &request.tools,
+            tool_choice: request.tool_choice,
+            temperature: request.temperature,
+            top_p: request.top_p,
+            max_tokens: request.max_tokens,
+            seed: request.seed,
+            stop: &request.stop,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            reasoning_effort: &request.reasoning_effort,
+        };
+        let json = serde_json::to_vec(&key).expect("cache key serializes");
+        format!("{:x}", Sha256::digest(json))
+    }
+
+    /// The key for `request` and its cached response, if any, counting the
+    /// lookup; `None` when the request bypasses the cache
+    fn lookup(&self, request: &ChatRequest) -> Option<(String, Option<ChatResponse>)> {
+        if request.no_cache {
+            self.stats.lock().unwrap().bypassed += 1;
+            return None;
+        }
+        let key = self.key(request);
+        let cached = self.cache.get(&key);
+        let hit = cached.is_some();
+        {
+            let mut stats = self.stats.lock().unwrap();
+            if hit {
+                stats.hits += 1;
+            } else {
+                stats.misses += 1;
+            }
+        }
+        crate::metrics::llm_cache_lookup(self.inner.provider_name(), hit);
+        Some((key, cached))
+    }
+}
+
+fn key_message(message: &ChatMessage) -> KeyMessage<'_> {
+    KeyMessage {
+        role: &message.role,
+        content: &message.content,
+        tool_calls: &message.tool_calls,
+        tool_call_id: &message.tool_call_id,
+    }
+}
+
+#[async_trait]
+impl GenericChatClient for CachedChatClient {
+    async fn chat(&self, request: ChatRequest) -> LlmResult<ChatResponse> {
+        let key = match self.lookup(&request) {
+            Some((_, Some(response))) => return Ok(response),
+            Some((key, None)) => Some(key),
+            None => None,
+        };
+        let response = self.inner.chat(request).await?;
+        if let Some(key) = key {
+            self.cache.put(&key, response.clone());
+        }
+        Ok(response)
+    }
+
+    async fn chat_stream(
+        &self,
+        request: ChatRequest,
+        tx: mpsc::Sender<String>,
+    ) -> LlmResult<ChatResponse> {
+        let key = match self.lookup(&request) {
+            Some((_, Some(response))) => {
+                for chunk in response.content.split_inclusive(char::is_whitespace) {
+                    let _ = tx.send(chunk.to_string()).await;
+                }
+                return Ok(response);
+            }
+            Some((key, None)) => Some(key),
+            None => None,
+        };
+        let Some(key) = key else {
+            return self.inner.chat_stream(request, tx).await;
+        };
+
+        let (inner_tx, mut inner_rx) = mpsc::channel::<String>(tx.max_capacity());
+        let mut streamed = String::new();
+        let forward = async {
+            while let Some(chunk) = inner_rx.recv().await {
+                streamed.push_str(&chunk);
+                let _ = tx.send(chunk).await;
+            }
+        };
+        let (result, ()) = tokio::join!(self.inner.chat_stream(request, inner_tx), forward);
+        let response = result?;
+        let mut cached = response.clone();
+        if cached.content.is_empty() {
+            cached.content = streamed;
+        }
+        self.cache.put(&key, cached);
+        Ok(response)
+    }
+
+    fn apply_effort(&self, request: &mut ChatRequest, effort: &Effort) -> AppliedEffort {
+        self.inner.apply_effort(request, effort)
+    }
+
+    fn provider_name(&self) -> &str {
+        self.inner.provider_name()
+    }
+}
@@ -0,0 +1,210 @@
+//! Provider-agnostic "effort" knob.
+//!
+//! An [`Effort`] level is mapped by each chat client onto its native
+//! mechanism via [`GenericChatClient::apply_effort`](super::GenericChatClient::apply_effort):
+//!
+//! | Provider / model              | Mechanism                                  |
+//! |-------------------------------|--------------------------------------------|
+//! | OpenAI reasoning (o1/o3/o4/gpt-5) | `reasoning_effort` on the request      |
+//! | Extended-thinking providers   | thinking budget (`thinking_budget_tokens`) |
+//! | Everything else               | scratchpad instruction + larger `max_tokens` |
+//!
+//! The fallback injects [`SCRATCHPAD_INSTRUCTION`] into the system prompt and
+//! multiplies `max_tokens` by [`Effort::max_tokens_factor`] so the model has
+//! room to reason before answering. Combinations a client can't honour degrade
+//! to the fallback and set [`AppliedEffort::warning`] instead of failing.
+
+use super::types::{ChatMessage, ChatRequest, Role};
+use serde::{Deserialize, Serialize};
+
+/// Instruction appended to the system prompt by the prompt fallback
+pub const SCRATCHPAD_INSTRUCTION: &str = "Before answering, think through the problem step \
+by step inside <scratchpad>...</scratchpad> tags. Then give your final answer after the \
+closing tag.";
+
+/// How hard the model should think before answering
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Effort {
+    Low,
+    Medium,
+    High,
+    /// Explicit values for every mechanism
+    Custom {
+        /// Value sent as `reasoning_effort` (e.g. "medium")
+        reasoning_effort: String,
+        /// Thinking budget for extended-thinking providers
+        thinking_budget_tokens: u32,
+        /// `max_tokens` multiplier for the prompt fallback
+        max_tokens_factor: f32,
+    },
+}
+
+impl Effort {
+    /// Value for OpenAI-style `reasoning_effort`
+    pub fn reasoning_effort(&self) -> &str {
+        match self {
+            Effort::Low => "low",
+            Effort::Medium => "medium",
+            Effort::High => "high",
+            Effort::Custom {
+                reasoning_effort, ..
+            } => reasoning_effort,
+        }
+    }
+
+    /// Token budget for extended-thinking providers
+    pub fn thinking_budget_tokens(&self) -> u32 {
+        match self {
+            Effort::Low => 1_024,
+            Effort::Medium => 4_096,
+            Effort::High => 16_384,
+            Effort::Custom {
+                thinking_budget_tokens,
+                ..
+            } => *thinking_budget_tokens,
+        }
+    }
+
+    /// `max_tokens` multiplier used by the prompt fallback
+    pub fn max_tokens_factor(&self) -> f32 {
+        match self {
+            Effort::Low => 1.0,
+            Effort::Medium => 1.5,
+            Effort::High => 2.0,
+            Effort::Custom {
+                max_tokens_factor, ..
+            } => *max_tokens_factor,
+        }
+    }
+
+    /// Whether the prompt fallback injects the scratchpad instruction
+    pub fn uses_scratchpad(&self) -> bool {
+        !matches!(self, Effort::Low)
+    }
+}
+
+/// The native mechanism an effort level was mapped to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mechanism", rename_all = "snake_case")]
+pub enum EffortMapping {
+    /// `reasoning_effort` set on the request
+    ReasoningEffort { effort: String },
+    /// Provider-side thinking budget
+    ThinkingBudget { budget_tokens: u32 },
+    /// Scratchpad instruction and/or raised `max_tokens`
+    PromptFallback {
+        scratchpad: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        max_tokens: Option<u32>,
+    },
+}
+
+/// Record of how an effort level was applied to a request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppliedEffort {
+    pub effort: Effort,
+    pub mapping: EffortMapping,
+    /// Set when the requested effort couldn't be honoured natively
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+/// Apply the documented fallback for models without native reasoning controls
+pub fn apply_prompt_fallback(
+    request: &mut ChatRequest,
+    effort: &Effort,
+    warning: Option<String>,
+) -> AppliedEffort {
+    let scratchpad = effort.uses_scratchpad();
+    if scratchpad {
+        match request.messages.first_mut() {
+            Some(first) if first.role == Role::System => {
+                if !first.content.text().contains(SCRATCHPAD_INSTRUCTION) {
+                    if !first.content.is_empty() {
+                        first.content.push_str("\n\n");
+                    }
+                    first.content.push_str(SCRATCHPAD_INSTRUCTION);
+                }
+            }
+            _ => request
+                .messages
+                .insert(0, ChatMessage::system(SCRATCHPAD_INSTRUCTION)),
+        }
+    }
+
+    let factor = effort.max_tokens_factor().max(1.0);
+    if let Some(max_tokens) = request.max_tokens.as_mut() {
+        *max_tokens = (*max_tokens as f32 * factor).round() as u32;
+    }
+
+    AppliedEffort {
+        effort: effort.clone(),
+        mapping: EffortMapping::PromptFallback {
+            scratchpad,
+            max_tokens: request.max_tokens,
+        },
+        warning,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fallback_appends_to_existing_system_prompt() {
+        let mut request = ChatRequest::new(vec![
+            ChatMessage::system("You are helpful"),
+            ChatMessage::user("hi"),
+        ])
+        .with_max_tokens(1000);
+
+        let applied = apply_prompt_fallback(&mut request, &Effort::High, None);
+
+        assert_eq!(request.messages.len(), 2);
+        assert!(request.messages[0]
+            .content
+            .text()
+            .starts_with("You are helpful\n\n"));
+        assert!(request.messages[0]
+            .content
+            .text()
+            .ends_with(SCRATCHPAD_INSTRUCTION));
+        assert_eq!(request.max_tokens, Some(2000));
+        assert_eq!(
+            applied.mapping,
+            EffortMapping::PromptFallback {
+                scratchpad: true,
+                max_tokens: Some(2000)
+            }
+        );
+    }
+
+    #[test]
+    fn test_fallback_inserts_system_prompt_and_is_idempotent() {
+        let mut request = ChatRequest::new(vec![ChatMessage::user("hi")]);
+        apply_prompt_fallback(&mut request, &Effort::Medium, None);
+        apply_prompt_fallback(&mut request, &Effort::Medium, None);
+
+        assert_eq!(request.messages[0].role, Role::System);
+        assert_eq!(
+            request.messages[0]
+                .content
+                .text()
+                .matches(SCRATCHPAD_INSTRUCTION)
+                .count(),
+            1
+        );
+        assert_eq!(request.max_tokens, None);
+    }
+
+    #[test]
+    fn test_low_effort_leaves_prompt_alone() {
+        let mut request = ChatRequest::new(vec![ChatMessage::user("hi")]).with_max_tokens(100);
+        apply_prompt_fallback(&mut request, &Effort::Low, None);
+
+        assert_eq!(request.messages.len(), 1);
+        assert_eq!(request.max_tokens, Some(100));
+    }
+}
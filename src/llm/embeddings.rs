@@ -0,0 +1,529 @@
+//! Embedding clients, for retrieval.
+//!
+//! An [`EmbeddingClient`] turns texts into vectors, one per input and in
+//! input order. Large batches are split into requests of at most
+//! `max_inputs_per_request` texts and the results put back together, so
+//! callers can pass a whole corpus at once.
+//!
+//! [`OpenAIEmbeddings`] talks to `/embeddings` on OpenAI or a compatible
+//! server; [`LlamaEmbeddings`] to a llama.cpp server, through its
+//! OpenAI-compatible `/v1/embeddings` or its native `/embedding` endpoint.
+
+use std::future::Future;
+use std::sync::{Arc, OnceLock};
+
+use async_trait::async_trait;
+use reqwest::Client as HttpClient;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::config::LlmConfig;
+use crate::error::{ConfigError, ConfigErrorCode};
+use crate::llm::provider::openai::status_error;
+use crate::llm::{LlmError, LlmResult};
+
+const OPENAI_API_URL: &str = "https://api.openai.com/v1";
+
+/// OpenAI accepts up to 2048 inputs per request
+pub const OPENAI_MAX_INPUTS_PER_REQUEST: usize = 2048;
+
+/// Keeps a llama.cpp server's batch within its default context
+pub const LLAMA_MAX_INPUTS_PER_REQUEST: usize = 32;
+
+/// Tokens spent on an embedding call
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmbeddingUsage {
+    pub prompt_tokens: u32,
+    pub total_tokens: u32,
+}
+
+impl EmbeddingUsage {
+    /// Add another request's usage to this one
+    pub fn add(&mut self, other: EmbeddingUsage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
+
+/// Vectors for a batch of inputs, with what they cost
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EmbeddingResponse {
+    /// One per input, in input order
+    pub embeddings: Vec<Vec<f32>>,
+    pub usage: EmbeddingUsage,
+}
+
+/// Turns texts into embedding vectors
+#[async_trait]
+pub trait EmbeddingClient: Send + Sync {
+    /// One vector per input, in input order, with the tokens spent
+    async fn embed_with_usage(&self, inputs: Vec<String>) -> LlmResult<EmbeddingResponse>;
+
+    /// One vector per input, in input order
+    async fn embed(&self, inputs: Vec<String>) -> LlmResult<Vec<Vec<f32>>> {
+        Ok(self.embed_with_usage(inputs).await?.embeddings)
+    }
+
+    /// Length of the vectors, if known: configured, or learned from the
+    /// first response
+    fn dimensions(&self) -> Option<usize>;
+
+    /// Model the vectors come from
+    fn model(&self) -> &str;
+}
+
+/// Send `inputs` `max` at a time through `request`, reassembling the
+/// vectors in input order and summing usage
+async fn embed_in_chunks<F, Fut>(
+    inputs: Vec<String>,
+    max: usize,
+    mut request: F,
+) -> LlmResult<EmbeddingResponse>
+where
+    F: FnMut(Vec<String>) -> Fut,
+    Fut: Future<Output = LlmResult<EmbeddingResponse>>,
+{
+    let mut total = EmbeddingResponse {
+        embeddings: Vec::with_capacity(inputs.len()),
+        usage: EmbeddingUsage::default(),
+    };
+    let mut inputs = inputs.into_iter().peekable();
+    while inputs.peek().is_some() {
+        let chunk: Vec<String> = inputs.by_ref().take(max.max(1)).collect();
+        let sent = chunk.len();
+        let response = request(chunk).await?;
+        if response.embeddings.len() != sent {
+            return Err(LlmError::ParseError(format!(
+                "Expected {} embeddings, got {}",
+                sent,
+                response.embeddings.len()
+            )));
+        }
+        total.embeddings.extend(response.embeddings);
+        total.usage.add(response.usage);
+    }
+    Ok(total)
+}
+
+/// Remember the vector length of the first response
+fn learn_dimensions(learned: &OnceLock<usize>, response: &EmbeddingResponse) {
+    if let Some(first) = response.embeddings.first() {
+        let _ = learned.set(first.len());
+    }
+}
+
+/// An OpenAI-style `/embeddings` response
+#[derive(Debug, Deserialize)]
+struct OpenAIEmbeddingResponse {
+    data: Vec<OpenAIEmbedding>,
+    #[serde(default)]
+    usage: EmbeddingUsage,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIEmbedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+/// Post an OpenAI-style embeddings body and return its vectors in input
+/// order
+async fn post_openai_style(
+    request: reqwest::RequestBuilder,
+    body: &Value,
+) -> LlmResult<EmbeddingResponse> {
+    let response = request
+        .header("Content-Type", "application/json")
+        .json(body)
+        .send()
+        .await
+        .map_err(|e| LlmError::NetworkError(e.to_string()))?;
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(status_error(status.as_u16(), error_text));
+    }
+    let mut parsed: OpenAIEmbeddingResponse = response
+        .json()
+        .await
+        .map_err(|e| LlmError::ParseError(e.to_string()))?;
+    // The data isn't guaranteed to come back in input order
+    parsed.data.sort_by_key(|item| item.index);
+    Ok(EmbeddingResponse {
+        embeddings: parsed.data.into_iter().map(|item| item.embedding).collect(),
+        usage: parsed.usage,
+    })
+}
+
+/// OpenAI embedding client, for `/embeddings`
+pub struct OpenAIEmbeddings {
+    api_key: String,
+    model: String,
+    base_url: String,
+    dimensions: Option<usize>,
+    max_inputs_per_request: usize,
+    http_client: HttpClient,
+    learned_dimensions: OnceLock<usize>,
+}
+
+impl OpenAIEmbeddings {
+    pub fn new(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            model: model.into(),
+            base_url: OPENAI_API_URL.to_string(),
+            dimensions: None,
+            max_inputs_per_request: OPENAI_MAX_INPUTS_PER_REQUEST,
+            http_client: HttpClient::new(),
+            learned_dimensions: OnceLock::new(),
+        }
+    }
+
+    /// Point at a different endpoint, e.g. a proxy or a compatible server
+    /// (default: `https://api.openai.com/v1`)
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Ask for vectors of this length (`text-embedding-3-*` models only)
+    pub fn with_dimensions(mut self, dimensions: usize) -> Self {
+        self.dimensions = Some(dimensions);
+        self
+    }
+
+    /// Split larger batches into requests of at most `max` inputs
+    /// (default: 2048)
+    pub fn with_max_inputs_per_request(mut self, max: usize) -> Self {
+        self.max_inputs_per_request = max;
+        self
+    }
+
+    async fn request(&self, inputs: Vec<String>) -> LlmResult<EmbeddingResponse> {
+        let mut body = json!({
+            "model": self.model,
+            "input": inputs,
+            "encoding_format": "float",
+        });
+        if let Some(dimensions) = self.dimensions {
+            body["dimensions"] = json!(dimensions);
+        }
+        post_openai_style(
+            self.http_client
+                .post(format!("{}/embeddings", self.base_url))
+                .header("Authorization", format!("Bearer {}", self.api_key)),
+            &body,
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl EmbeddingClient for OpenAIEmbeddings {
+    async fn embed_with_usage(&self, inputs: Vec<String>) -> LlmResult<EmbeddingResponse> {
+        let response = embed_in_chunks(inputs, self.max_inputs_per_request, |chunk| {
+            self.request(chunk)
+        })
+        .await?;
+        learn_dimensions(&self.learned_dimensions, &response);
+        Ok(response)
+    }
+
+    fn dimensions(&self) -> Option<usize> {
+        self.dimensions
+            .or_else(|| self.learned_dimensions.get().copied())
+            .or_else(|| known_openai_dimensions(&self.model))
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+/// Default vector length of OpenAI's embedding models
+fn known_openai_dimensions(model: &str) -> Option<usize> {
+    match model {
+        "text-embedding-3-small" | "text-embedding-ada-002" => Some(1536),
+        "text-embedding-3-large" => Some(3072),
+        _ => None,
+    }
+}
+
+/// Which llama.cpp endpoint an embedding client talks to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LlamaEmbeddingApi {
+    /// `/v1/embeddings`, reporting usage
+    #[default]
+    OpenAICompatible,
+
+    /// `/embedding`, for servers without the OpenAI-compatible routes; no
+    /// usage is reported
+    Native,
+}
+
+/// A native `/embedding` response: one object for a single input, or an
+/// array of them
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum NativeResponse {
+    Many(Vec<NativeEmbedding>),
+    One(NativeEmbedding),
+}
+
+#[derive(Debug, Deserialize)]
+struct NativeEmbedding {
+    #[serde(default)]
+    index: usize,
+    embedding: NativeVector,
+}
+
+/// Newer servers nest the pooled vector in a one-element array, and return
+/// one vector per token when pooling is off
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum NativeVector {
+    Flat(Vec<f32>),
+    Nested(Vec<Vec<f32>>),
+}
+
+impl NativeVector {
+    fn pooled(self) -> LlmResult<Vec<f32>> {
+        match self {
+            NativeVector::Flat(vector) => Ok(vector),
+            NativeVector::Nested(mut vectors) if vectors.len() == 1 => Ok(vectors.remove(0)),
+            NativeVector::Nested(_) => Err(LlmError::ParseError(
+                "Server returned per-token embeddings; start it with --pooling mean".to_string(),
+            )),
+        }
+    }
+}
+
+/// Llama.cpp server embedding client (started with `--embedding`)
+pub struct LlamaEmbeddings {
+    base_url: String,
+    model: String,
+    api: LlamaEmbeddingApi,
+    max_inputs_per_request: usize,
+    http_client: HttpClient,
+    learned_dimensions: OnceLock<usize>,
+}
+
+impl LlamaEmbeddings {
+    /// Create a new llama.cpp embedding client
+    ///
+    /// # Arguments
+    /// * `base_url` - Base URL of llama.cpp server (e.g., "http://localhost:8080")
+    /// * `model` - Model name (optional, llama.cpp usually ignores this)
+    pub fn new(base_url: impl Into<String>, model: impl Into<String>) -> Self {
+        Self::with_http_client(base_url, model, HttpClient::new())
+    }
+
+    /// Create a new llama.cpp embedding client with custom HTTP client
+    pub fn with_http_client(
+        base_url: impl Into<String>,
+        model: impl Into<String>,
+        http_client: HttpClient,
+    ) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            model: model.into(),
+            api: LlamaEmbeddingApi::default(),
+            max_inputs_per_request: LLAMA_MAX_INPUTS_PER_REQUEST,
+            http_client,
+            learned_dimensions: OnceLock::new(),
+        }
+    }
+
+    /// Create a client with insecure HTTPS (accepts self-signed certificates)
+    pub fn insecure(base_url: impl Into<String>, model: impl Into<String>) -> Self {
+        let http_client = HttpClient::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .expect("Failed to build HTTP client");
+
+        Self::with_http_client(base_url, model, http_client)
+    }
+
+    /// Choose the endpoint (default: `/v1/embeddings`)
+    pub fn with_api(mut self, api: LlamaEmbeddingApi) -> Self {
+        self.api = api;
+        self
+    }
+
+    /// Split larger batches into requests of at most `max` inputs
+    /// (default: 32)
+    pub fn with_max_inputs_per_request(mut self, max: usize) -> Self {
+        self.max_inputs_per_request = max;
+        self
+    }
+
+    async fn request(&self, inputs: Vec<String>) -> LlmResult<EmbeddingResponse> {
+        match self.api {
+            LlamaEmbeddingApi::OpenAICompatible => {
+                post_openai_style(
+                    self.http_client
+                        .post(format!("{}/v1/embeddings", self.base_url)),
+                    &json!({ "model": self.model, "input": inputs }),
+                )
+                .await
+            }
+            LlamaEmbeddingApi::Native => self.request_native(inputs).await,
+        }
+    }
+
+    async fn request_native(&self, inputs: Vec<String>) -> LlmResult<EmbeddingResponse> {
+        let response = self
+            .http_client
+            .post(format!("{}/embedding", self.base_url))
+            .header("Content-Type", "application/json")
+            .json(&json!({ "content": inputs }))
+            .send()
+            .await
+            .map_err(|e| LlmError::NetworkError(e.to_string()))?;
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(LlmError::ApiError(format!(
+                "Status {}: {}",
+                status, error_text
+            )));
+        }
+        let parsed: NativeResponse = response
+            .json()
+            .await
+            .map_err(|e| LlmError::ParseError(e.to_string()))?;
+        let mut items = match parsed {
+            NativeResponse::Many(items) => items,
+            NativeResponse::One(item) => vec![item],
+        };
+        items.sort_by_key(|item| item.index);
+        Ok(EmbeddingResponse {
+            embeddings: items
+                .into_iter()
+                .map(|item| item.embedding.pooled())
+                .collect::<LlmResult<_>>()?,
+            usage: EmbeddingUsage::default(),
+        })
+    }
+}
+
+#[async_trait]
+impl EmbeddingClient for LlamaEmbeddings {
+    async fn embed_with_usage(&self, inputs: Vec<String>) -> LlmResult<EmbeddingResponse> {
+        let response = embed_in_chunks(inputs, self.max_inputs_per_request, |chunk| {
+            self.request(chunk)
+        })
+        .await?;
+        learn_dimensions(&self.learned_dimensions, &response);
+        Ok(response)
+    }
+
+    fn dimensions(&self) -> Option<usize> {
+        self.learned_dimensions.get().copied()
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+/// The embedding client described by `config.embedding`
+///
+/// Connection settings come from the provider's own section; the OpenAI key
+/// falls back to the `OPENAI_API_KEY` environment variable.
+pub fn from_config(config: &LlmConfig) -> Result<Arc<dyn EmbeddingClient>, ConfigError> {
+    config.validate_embedding()?;
+    let embedding = config.embedding.as_ref().ok_or_else(|| ConfigError {
+        code: ConfigErrorCode::MissingRequiredField,
+        message: "No [llm.embedding] section".to_string(),
+        field: Some("llm.embedding".to_string()),
+    })?;
+
+    let client: Arc<dyn EmbeddingClient> = match embedding.provider.as_str() {
+        "openai" => {
+            let openai = config.openai.as_ref().expect("validated");
+            let api_key = openai
+                .api_key
+                .clone()
+                .or_else(|| std::env::var("OPENAI_API_KEY").ok())
+                .ok_or_else(|| ConfigError {
+                    code: ConfigErrorCode::MissingRequiredField,
+                    message: "No OpenAI API key in config or OPENAI_API_KEY".to_string(),
+                    field: Some("llm.openai.api_key".to_string()),
+                })?;
+            let mut client = OpenAIEmbeddings::new(api_key, &embedding.model);
+            if let Some(base_url) = &openai.api_base {
+                client = client.with_base_url(base_url);
+            }
+            if let Some(dimensions) = embedding.dimensions {
+                client = client.with_dimensions(dimensions);
+            }
+            if let Some(max) = embedding.max_inputs_per_request {
+                client = client.with_max_inputs_per_request(max);
+            }
+            Arc::new(client)
+        }
+        _ => {
+            let llama = config.llama.as_ref().expect("validated");
+            let mut client = if llama.insecure {
+                LlamaEmbeddings::insecure(&llama.base_url, &embedding.model)
+            } else {
+                LlamaEmbeddings::new(&llama.base_url, &embedding.model)
+            };
+            if embedding.native_endpoint {
+                client = client.with_api(LlamaEmbeddingApi::Native);
+            }
+            if let Some(max) = embedding.max_inputs_per_request {
+                client = client.with_max_inputs_per_request(max);
+            }
+            Arc::new(client)
+        }
+    };
+    Ok(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_chunks_are_reassembled_in_order() {
+        let sizes = Mutex::new(Vec::new());
+        let inputs: Vec<String> = (0..5).map(|i| i.to_string()).collect();
+        let response = embed_in_chunks(inputs, 2, |chunk| {
+            sizes.lock().unwrap().push(chunk.len());
+            async move {
+                Ok(EmbeddingResponse {
+                    embeddings: chunk
+                        .iter()
+                        .map(|text| vec![text.parse::<f32>().unwrap()])
+                        .collect(),
+                    usage: EmbeddingUsage {
+                        prompt_tokens: chunk.len() as u32,
+                        total_tokens: chunk.len() as u32,
+                    },
+                })
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(*sizes.lock().unwrap(), vec![2, 2, 1]);
+        assert_eq!(
+            response.embeddings,
+            vec![vec![0.0], vec![1.0], vec![2.0], vec![3.0], vec![4.0]]
+        );
+        assert_eq!(response.usage.total_tokens, 5);
+
+        let short = embed_in_chunks(vec!["a".to_string()], 2, |_| async {
+            Ok(EmbeddingResponse::default())
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(
+            short.to_string(),
+            "Response parsing error: Expected 1 embeddings, got 0"
+        );
+    }
+}
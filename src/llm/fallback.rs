@@ -0,0 +1,290 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use crate::config::{LlmConfig, FALLBACK_PROVIDERS};
+use crate::error::{ConfigError, ConfigErrorCode};
+use crate::llm::{ChatRequest, ChatResponse, GenericChatClient, LlmClient, LlmError, LlmResult};
+use crate::llm::{
+    ClaudeClient, GeminiClient, LlamaClient, OllamaClient, OpenAIClient, RateLimitedChatClient,
+};
+
+/// A provider that failed with a retryable error before another one answered
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Failover {
+    pub provider: String,
+    pub error: String,
+}
+
+/// What a [`FallbackChatClient`] has done so far
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FallbackStats {
+    /// Requests answered, per provider
+    pub served: BTreeMap<String, u64>,
+
+    /// Retryable failures that moved a request on to the next provider
+    pub failovers: u64,
+
+    /// Times a provider was passed over because it was cooling down
+    pub skipped: u64,
+
+    /// Requests that no provider could serve
+    pub exhausted: u64,
+}
+
+struct Provider {
+    name: String,
+    client: LlmClient,
+    cooling_until: Mutex<Option<Instant>>,
+}
+
+/// A client that tries an ordered chain of providers until one answers
+///
+/// Retryable errors (see [`LlmError::is_retryable`]) move the request on to
+/// the next provider; any other error is returned at once, since another
+/// provider would most likely reject the request too. When every provider
+/// fails, the last error is returned.
+///
+/// The response's `provider` names the provider that served it and
+/// `failovers` lists the ones that failed first. The agent reports those
+/// as a `system:llm_failover` event.
+///
+/// With a cooldown, a provider that failed is skipped for that long, so
+/// later requests don't wait on a server that is down. If every provider is
+/// cooling down, all of them are tried anyway.
+///
+/// A streamed request only fails over if the failing provider hadn't sent
+/// any text yet; otherwise the caller would get two answers spliced
+/// together. Effort is applied with the prompt fallback, since the request
+/// may end up at any provider in the chain.
+///
+/// ```rust,ignore
+/// let client = FallbackChatClient::new()
+///     .provider("llama", Arc::new(LlamaClient::localhost()))
+///     .provider("openai", Arc::new(OpenAIClient::new(api_key)))
+///     .with_cooldown(Duration::from_secs(30));
+/// ```
+pub struct FallbackChatClient {
+    providers: Vec<Provider>,
+    cooldown: Option<Duration>,
+    stats: Mutex<FallbackStats>,
+}
+
+impl Default for FallbackChatClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FallbackChatClient {
+    pub fn new() -> Self {
+        Self {
+            providers: Vec::new(),
+            cooldown: None,
+            stats: Mutex::new(FallbackStats::default()),
+        }
+    }
+
+    /// Append a provider to the chain (builder-style)
+    pub fn provider(mut self, name: impl Into<String>, client: LlmClient) -> Self {
+        self.providers.push(Provider {
+            name: name.into(),
+            client,
+            cooling_until: Mutex::new(None),
+        });
+        self
+    }
+
+    /// Skip a provider for `cooldown` after it fails (default: never skip)
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = Some(cooldown);
+        self
+    }
+
+    /// Build the chain named by `llm.fallback`, from the provider sections
+    /// of the same config
+    ///
+    /// `default_model` is used for OpenAI and llama.cpp; Anthropic, Gemini
+    /// and Ollama use their own section's `model`. The OpenAI key falls back to the
+    /// `OPENAI_API_KEY` environment variable. The OpenAI and llama.cpp
+    /// sections' headers, proxy and timeouts apply to every request their
+    /// client sends. A section's `rate_limit` wraps its provider in a
+    /// [`RateLimitedChatClient`].
+    pub fn from_config(config: &LlmConfig) -> Result<Self, ConfigError> {
+        config.validate_fallback()?;
+        if config.fallback.is_empty() {
+            return Err(ConfigError {
+                code: ConfigErrorCode::MissingRequiredField,
+                message: "No providers listed in llm.fallback".to_string(),
+                field: Some("llm.fallback".to_string()),
+            });
+        }
+
+        let mut chain = Self::new();
+        for name in &config.fallback {
+            let client: LlmClient = match name.as_str() {
+                "openai" => Arc::new(OpenAIClient::from_config(
+                    config.openai.as_ref().expect("validated"),
+                    config.default_model.as_deref(),
+                )?),
+                "llama" => Arc::new(LlamaClient::from_config(
+                    config.llama.as_ref().expect("validated"),
+                    config.default_model.as_deref().unwrap_or("llama"),
+                )?),
+                "anthropic" => Arc::new(ClaudeClient::from_config(
+                    config.anthropic.as_ref().expect("validated"),
+                )?),
+                "gemini" => Arc::new(GeminiClient::from_config(
+                    config.gemini.as_ref().expect("validated"),
+                )?),
+                "ollama" => Arc::new(OllamaClient::from_config(
+                    config.ollama.as_ref().expect("validated"),
+                )),
+                _ => unreachable!("validated against {:?}", FALLBACK_PROVIDERS),
+            };
+            let client = match config.rate_limits(name) {
+                Some(limits) => Arc::new(RateLimitedChatClient::new(client, limits.clone())),
+                None => client,
+            };
+            chain = chain.provider(name.clone(), client);
+        }
+        if let Some(ms) = config.fallback_cooldown_ms {
+            chain = chain.with_cooldown(Duration::from_millis(ms));
+        }
+        Ok(chain)
+    }
+
+    /// Provider names, in the order they are tried
+    pub fn providers(&self) -> Vec<&str> {
+        self.providers.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    pub fn stats(&self) -> FallbackStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Indices of the providers to try: those not cooling down, or all of
+    /// them if every one is
+    fn candidates(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let ready: Vec<usize> = (0..self.providers.len())
+            .filter(|&i| {
+                let until = *self.providers[i].cooling_until.lock().unwrap();
+                until.is_none_or(|until| until <= now)
+            })
+            .collect();
+        if ready.is_empty() {
+            return (0..self.providers.len()).collect();
+        }
+        self.stats.lock().unwrap().skipped += (self.providers.len() - ready.len()) as u64;
+        ready
+    }
+
+    fn cool_down(&self, index: usize) {
+        if let Some(cooldown) = self.cooldown {
+            *self.providers[index].cooling_until.lock().unwrap() = Some(Instant::now() + cooldown);
+        }
+    }
+
+    fn failed(&self, index: usize, error: &LlmError, failovers: &mut Vec<Failover>) {
+        self.cool_down(index);
+        self.stats.lock().unwrap().failovers += 1;
+        failovers.push(Failover {
+            provider: self.providers[index].name.clone(),
+            error: error.to_string(),
+        });
+    }
+
+    fn served(
+        &self,
+        index: usize,
+        mut response: ChatResponse,
+        failovers: Vec<Failover>,
+    ) -> ChatResponse {
+        let provider = &self.providers[index];
+        *provider.cooling_until.lock().unwrap() = None;
+        *self
+            .stats
+            .lock()
+            .unwrap()
+            .served
+            .entry(provider.name.clone())
+            .or_default() += 1;
+        response.provider = Some(provider.name.clone());
+        response.failovers = failovers;
+        response
+    }
+
+    fn exhausted(&self, last_error: Option<LlmError>) -> LlmError {
+        self.stats.lock().unwrap().exhausted += 1;
+        last_error
+            .unwrap_or_else(|| LlmError::InvalidRequest("fallback chain has no providers".into()))
+    }
+}
+
+#[async_trait]
+impl GenericChatClient for FallbackChatClient {
+    async fn chat(&self, request: ChatRequest) -> LlmResult<ChatResponse> {
+        let mut failovers = Vec::new();
+        let mut last_error = None;
+        for index in self.candidates() {
+            match self.providers[index].client.chat(request.clone()).await {
+                Ok(response) => return Ok(self.served(index, response, failovers)),
+                Err(e) if e.is_retryable() => {
+                    self.failed(index, &e, &mut failovers);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(self.exhausted(last_error))
+    }
+
+    async fn chat_stream(
+        &self,
+        request: ChatRequest,
+        tx: mpsc::Sender<String>,
+    ) -> LlmResult<ChatResponse> {
+        let mut failovers = Vec::new();
+        let mut last_error = None;
+        for index in self.candidates() {
+            let (provider_tx, mut provider_rx) = mpsc::channel(tx.max_capacity());
+            let mut streamed = false;
+            let forward = async {
+                while let Some(chunk) = provider_rx.recv().await {
+                    streamed = true;
+                    let _ = tx.send(chunk).await;
+                }
+            };
+            let (result, ()) = tokio::join!(
+                self.providers[index]
+                    .client
+                    .chat_stream(request.clone(), provider_tx),
+                forward
+            );
+            match result {
+                Ok(response) => return Ok(self.served(index, response, failovers)),
+                Err(e) if e.is_retryable() && !streamed => {
+                    self.failed(index, &e, &mut failovers);
+                    last_error = Some(e);
+                }
+                Err(e) => {
+                    if e.is_retryable() {
+                        self.cool_down(index);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Err(self.exhausted(last_error))
+    }
+
+    fn provider_name(&self) -> &str {
+        "fallback"
+    }
+}
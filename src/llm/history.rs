@@ -0,0 +1,120 @@
+//! Checks on the tool-call linkage of a conversation.
+//!
+//! Providers reject a request whose history has a tool result with no
+//! matching call before it, or an assistant message whose tool calls aren't
+//! all answered. Pruning and hand-built histories are where those creep in;
+//! [`validate_history`] finds them, and the agent asserts it in debug
+//! builds before every LLM call.
+
+use super::types::{ChatMessage, Role};
+use std::collections::{HashMap, HashSet};
+
+/// A break in a history's tool-call linkage
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum HistoryError {
+    #[error("tool result at {index} answers '{tool_call_id}', which no earlier message calls")]
+    OrphanedToolResult { index: usize, tool_call_id: String },
+
+    #[error("tool result at {index} has no tool_call_id")]
+    MissingToolCallId { index: usize },
+
+    #[error("tool call '{tool_call_id}' at {index} has no result")]
+    UnansweredToolCall { index: usize, tool_call_id: String },
+}
+
+/// Check that every tool result answers an earlier call, and every call is
+/// answered
+pub fn validate_history(messages: &[ChatMessage]) -> Result<(), HistoryError> {
+    let mut offered = HashSet::new();
+    let mut pending: HashMap<&str, usize> = HashMap::new();
+    for (index, message) in messages.iter().enumerate() {
+        for call in message.tool_calls.iter().flatten() {
+            offered.insert(call.id.as_str());
+            pending.insert(call.id.as_str(), index);
+        }
+        if message.role != Role::Tool {
+            continue;
+        }
+        let id = message
+            .tool_call_id
+            .as_deref()
+            .ok_or(HistoryError::MissingToolCallId { index })?;
+        if !offered.contains(id) {
+            return Err(HistoryError::OrphanedToolResult {
+                index,
+                tool_call_id: id.to_string(),
+            });
+        }
+        pending.remove(id);
+    }
+    match pending.into_iter().min_by_key(|&(_, index)| index) {
+        Some((id, index)) => Err(HistoryError::UnansweredToolCall {
+            index,
+            tool_call_id: id.to_string(),
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::types::{FunctionCall, ToolCall};
+
+    fn call(id: &str) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            r#type: "function".to_string(),
+            function: FunctionCall {
+                name: "lookup".to_string(),
+                arguments: "{}".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_answered_calls_are_valid() {
+        let history = vec![
+            ChatMessage::user("Look up both"),
+            ChatMessage::assistant_with_tool_calls("", vec![call("a"), call("b")]),
+            ChatMessage::tool_result("a", "1"),
+            ChatMessage::tool_result("b", "2"),
+            ChatMessage::assistant("1 and 2"),
+        ];
+        assert_eq!(validate_history(&history), Ok(()));
+    }
+
+    #[test]
+    fn test_broken_links_are_reported() {
+        let orphan = vec![ChatMessage::user("Hi"), ChatMessage::tool_result("a", "1")];
+        assert_eq!(
+            validate_history(&orphan),
+            Err(HistoryError::OrphanedToolResult {
+                index: 1,
+                tool_call_id: "a".to_string()
+            })
+        );
+
+        let unanswered = vec![
+            ChatMessage::assistant_with_tool_calls("", vec![call("a"), call("b")]),
+            ChatMessage::tool_result("a", "1"),
+        ];
+        assert_eq!(
+            validate_history(&unanswered),
+            Err(HistoryError::UnansweredToolCall {
+                index: 0,
+                tool_call_id: "b".to_string()
+            })
+        );
+
+        // A result can't come before its call
+        let early = vec![
+            ChatMessage::tool_result("a", "1"),
+            ChatMessage::assistant_with_tool_calls("", vec![call("a")]),
+        ];
+        assert!(matches!(
+            validate_history(&early),
+            Err(HistoryError::OrphanedToolResult { index: 0, .. })
+        ));
+    }
+}
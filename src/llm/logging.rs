@@ -0,0 +1,468 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::config::LoggingConfig;
+use crate::llm::types::{ToolCall, Usage};
+use crate::llm::{
+    AppliedEffort, ChatMessage, ChatRequest, ChatResponse, Effort, GenericChatClient, LlmClient,
+    LlmResult, Role, SamplingParams,
+};
+
+/// Name of the file [`LlmLogSink::Jsonl`] appends to in its directory
+pub const LLM_LOG_FILE: &str = "llm.jsonl";
+
+/// Where [`LoggingChatClient`] writes its entries
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum LlmLogSink {
+    /// One `tracing` event per entry, at `info` on target
+    /// `agent_runtime::llm`, with the entry as JSON in `entry`
+    #[default]
+    Tracing,
+
+    /// One JSON line per entry, appended to [`LLM_LOG_FILE`] in this
+    /// directory
+    Jsonl(PathBuf),
+}
+
+/// What [`LoggingChatClient`] logs, where, and what it hides
+#[derive(Debug, Clone)]
+pub struct LlmLogConfig {
+    pub sink: LlmLogSink,
+
+    /// Log message and response text and tool call arguments; off, entries
+    /// keep only sizes, parameters, tool names, usage and timings
+    pub log_bodies: bool,
+
+    /// Cut each logged text to this many characters
+    pub max_content_chars: Option<usize>,
+
+    /// Patterns replaced by `[REDACTED]` in logged text, on top of the
+    /// API key shapes that are always masked
+    pub masks: Vec<Regex>,
+}
+
+impl Default for LlmLogConfig {
+    fn default() -> Self {
+        Self {
+            sink: LlmLogSink::Tracing,
+            log_bodies: true,
+            max_content_chars: None,
+            masks: Vec::new(),
+        }
+    }
+}
+
+impl LlmLogConfig {
+    /// Log through `tracing`
+    pub fn tracing() -> Self {
+        Self::default()
+    }
+
+    /// Append JSON lines to [`LLM_LOG_FILE`] in `directory`
+    pub fn jsonl(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            sink: LlmLogSink::Jsonl(directory.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Append JSON lines to [`LLM_LOG_FILE`] in the configured log
+    /// directory
+    pub fn from_logging_config(logging: &LoggingConfig) -> Self {
+        Self::jsonl(&logging.directory)
+    }
+
+    /// Leave message and response text out, e.g. in production
+    pub fn without_bodies(mut self) -> Self {
+        self.log_bodies = false;
+        self
+    }
+
+    /// Cut each logged text to `chars` characters
+    pub fn with_max_content_chars(mut self, chars: usize) -> Self {
+        self.max_content_chars = Some(chars);
+        self
+    }
+
+    /// Replace matches of `pattern` with `[REDACTED]`
+    pub fn with_mask(mut self, pattern: Regex) -> Self {
+        self.masks.push(pattern);
+        self
+    }
+
+    /// Mask email addresses
+    pub fn mask_emails(self) -> Self {
+        self.with_mask(
+            Regex::new(r"[A-Za-z0-9._%+-]{1,64}@[A-Za-z0-9-]{1,63}(?:\.[A-Za-z0-9-]{1,63})*\.[A-Za-z]{2,24}")
+                .expect("valid email pattern"),
+        )
+    }
+
+    /// Mask runs of 13 to 19 digits, with optional spaces or dashes, as
+    /// card numbers are written
+    pub fn mask_card_numbers(self) -> Self {
+        self.with_mask(Regex::new(r"\b\d(?:[ -]?\d){12,18}\b").expect("valid card pattern"))
+    }
+
+    /// `text` with secrets and masked patterns replaced, cut to
+    /// `max_content_chars`
+    fn clean(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        let masks = self.masks.iter().map(|pattern| (pattern, REDACTED));
+        for (pattern, replacement) in secret_patterns()
+            .iter()
+            .map(|(pattern, replacement)| (pattern, *replacement))
+            .chain(masks)
+        {
+            if pattern.is_match(&text) {
+                text = pattern.replace_all(&text, replacement).into_owned();
+            }
+        }
+        match self.max_content_chars {
+            Some(max) if text.chars().count() > max => {
+                let mut cut: String = text.chars().take(max).collect();
+                cut.push_str(&format!("...[{} chars]", text.chars().count()));
+                cut
+            }
+            _ => text,
+        }
+    }
+
+    fn body(&self, text: &str) -> Option<String> {
+        self.log_bodies.then(|| self.clean(text))
+    }
+}
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Shapes of API keys and credentials, masked whatever the config says,
+/// with what replaces them
+fn secret_patterns() -> &'static [(Regex, &'static str)] {
+    static PATTERNS: OnceLock<Vec<(Regex, &'static str)>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        [
+            // `api_key=...`, `"authorization": "..."`; the name stays
+            (
+                r#"(?i)\b(api[_-]?key|x-api-key|authorization)(["']?\s*[:=]\s*["']?)[^\s"',}]+"#,
+                "${1}${2}[REDACTED]",
+            ),
+            // OpenAI and Anthropic keys
+            (r"\bsk-[A-Za-z0-9_-]{16,}", REDACTED),
+            // Google API keys
+            (r"\bAIza[0-9A-Za-z_-]{30,}", REDACTED),
+            (r"(?i)\bbearer\s+[A-Za-z0-9._~+/=-]{16,}", REDACTED),
+        ]
+        .into_iter()
+        .map(|(pattern, replacement)| {
+            (
+                Regex::new(pattern).expect("valid secret pattern"),
+                replacement,
+            )
+        })
+        .collect()
+    })
+}
+
+/// One request to the provider and what came back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmLogEntry {
+    pub timestamp: DateTime<Utc>,
+    pub provider: String,
+
+    /// Correlation fields the caller put on the request, e.g. `workflow_id`,
+    /// `agent` and `iteration`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+
+    pub request: LoggedRequest,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<LoggedResponse>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    pub latency_ms: u64,
+
+    /// Whether the response was streamed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub streamed: bool,
+
+    /// Chunks the stream delivered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunks: Option<usize>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_to_first_token_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggedRequest {
+    pub message_count: usize,
+
+    /// Present when bodies are logged
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub messages: Option<Vec<LoggedMessage>>,
+
+    /// Names of the tools offered
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<String>,
+
+    pub params: SamplingParams,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggedMessage {
+    pub role: Role,
+    pub content: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<LoggedToolCall>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggedToolCall {
+    pub name: String,
+
+    /// Present when bodies are logged
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggedResponse {
+    pub model: String,
+
+    /// Present when bodies are logged; a stream's assembled text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<LoggedToolCall>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+}
+
+/// A client that logs every request to another one and its response, with
+/// secrets masked
+///
+/// API key shapes (`sk-...`, `AIza...`, bearer tokens, `api_key=...`) are
+/// always masked in logged text and errors; [`LlmLogConfig`] adds patterns,
+/// truncation, and a switch to leave bodies out. A stream is logged once,
+/// when it ends, with its assembled response, chunk count and time to the
+/// first chunk. Writing the log never fails the request: write errors are
+/// reported through `tracing`.
+///
+/// Entries carry the request's [`ChatRequest::metadata`]; agents set
+/// `workflow_id`, `agent` and `iteration` there.
+///
+/// ```rust,ignore
+/// let config = LlmLogConfig::jsonl("logs")
+///     .with_max_content_chars(500)
+///     .mask_emails()
+///     .mask_card_numbers();
+/// let client = LoggingChatClient::new(Arc::new(OpenAIClient::new(key)), config);
+/// ```
+pub struct LoggingChatClient {
+    inner: LlmClient,
+    config: LlmLogConfig,
+    /// The JSONL file, opened on the first entry
+    file: Mutex<Option<File>>,
+}
+
+impl LoggingChatClient {
+    pub fn new(inner: LlmClient, config: LlmLogConfig) -> Self {
+        Self {
+            inner,
+            config,
+            file: Mutex::new(None),
+        }
+    }
+
+    fn logged_calls(&self, calls: &Option<Vec<ToolCall>>) -> Vec<LoggedToolCall> {
+        calls
+            .iter()
+            .flatten()
+            .map(|call| LoggedToolCall {
+                name: call.function.name.clone(),
+                arguments: self.config.body(&call.function.arguments),
+            })
+            .collect()
+    }
+
+    fn logged_message(&self, message: &ChatMessage) -> LoggedMessage {
+        LoggedMessage {
+            role: message.role.clone(),
+            content: self.config.clean(&message.content.text()),
+            tool_calls: self.logged_calls(&message.tool_calls),
+            tool_call_id: message.tool_call_id.clone(),
+        }
+    }
+
+    /// The entry for `request`, to be completed once it is answered
+    fn start(&self, request: &ChatRequest, streamed: bool) -> LlmLogEntry {
+        let tools = request
+            .tools
+            .iter()
+            .flatten()
+            .filter_map(|tool| tool["function"]["name"].as_str().map(str::to_string))
+            .collect();
+        LlmLogEntry {
+            timestamp: Utc::now(),
+            provider: self.inner.provider_name().to_string(),
+            metadata: request.metadata.clone(),
+            request: LoggedRequest {
+                message_count: request.messages.len(),
+                messages: self.config.log_bodies.then(|| {
+                    request
+                        .messages
+                        .iter()
+                        .map(|message| self.logged_message(message))
+                        .collect()
+                }),
+                tools,
+                params: SamplingParams {
+                    temperature: request.temperature,
+                    top_p: request.top_p,
+                    max_tokens: request.max_tokens,
+                    seed: request.seed,
+                    stop: request.stop.clone(),
+                    frequency_penalty: request.frequency_penalty,
+                    presence_penalty: request.presence_penalty,
+                    max_response_chars: None,
+                },
+                reasoning_effort: request.reasoning_effort.clone(),
+            },
+            response: None,
+            error: None,
+            latency_ms: 0,
+            streamed,
+            chunks: None,
+            time_to_first_token_ms: None,
+        }
+    }
+
+    fn finish(&self, mut entry: LlmLogEntry, started: Instant, result: &LlmResult<ChatResponse>) {
+        entry.latency_ms = started.elapsed().as_millis() as u64;
+        match result {
+            Ok(response) => {
+                entry.response = Some(LoggedResponse {
+                    model: response.model.clone(),
+                    content: self.config.body(&response.content),
+                    tool_calls: self.logged_calls(&response.tool_calls),
+                    finish_reason: response.finish_reason.clone(),
+                    usage: response.usage.clone(),
+                })
+            }
+            Err(e) => entry.error = Some(self.config.clean(&e.to_string())),
+        }
+        self.write(&entry);
+    }
+
+    fn write(&self, entry: &LlmLogEntry) {
+        let json = match serde_json::to_string(entry) {
+            Ok(json) => json,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to serialize LLM log entry");
+                return;
+            }
+        };
+        let directory = match &self.config.sink {
+            LlmLogSink::Tracing => {
+                tracing::info!(
+                    target: "agent_runtime::llm",
+                    provider = %entry.provider,
+                    latency_ms = entry.latency_ms,
+                    entry = %json,
+                    "LLM request"
+                );
+                return;
+            }
+            LlmLogSink::Jsonl(directory) => directory,
+        };
+        let mut file = self.file.lock().unwrap();
+        if file.is_none() {
+            let opened = std::fs::create_dir_all(directory).and_then(|()| {
+                File::options()
+                    .create(true)
+                    .append(true)
+                    .open(directory.join(LLM_LOG_FILE))
+            });
+            match opened {
+                Ok(opened) => *file = Some(opened),
+                Err(e) => {
+                    tracing::warn!(directory = %directory.display(), error = %e, "failed to open LLM log");
+                    return;
+                }
+            }
+        }
+        if let Some(file) = file.as_mut() {
+            if let Err(e) = writeln!(file, "{}", json) {
+                tracing::warn!(error = %e, "failed to write LLM log entry");
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl GenericChatClient for LoggingChatClient {
+    async fn chat(&self, request: ChatRequest) -> LlmResult<ChatResponse> {
+        let entry = self.start(&request, false);
+        let started = Instant::now();
+        let result = self.inner.chat(request).await;
+        self.finish(entry, started, &result);
+        result
+    }
+
+    async fn chat_stream(
+        &self,
+        request: ChatRequest,
+        tx: mpsc::Sender<String>,
+    ) -> LlmResult<ChatResponse> {
+        let mut entry = self.start(&request, true);
+        let started = Instant::now();
+        let (inner_tx, mut inner_rx) = mpsc::channel::<String>(tx.max_capacity());
+        let mut chunks = 0;
+        let mut first_chunk = None;
+        let forward = async {
+            while let Some(chunk) = inner_rx.recv().await {
+                chunks += 1;
+                first_chunk.get_or_insert_with(|| started.elapsed());
+                let _ = tx.send(chunk).await;
+            }
+        };
+        let (result, ()) = tokio::join!(self.inner.chat_stream(request, inner_tx), forward);
+        entry.chunks = Some(chunks);
+        entry.time_to_first_token_ms = first_chunk.map(|elapsed| elapsed.as_millis() as u64);
+        self.finish(entry, started, &result);
+        result
+    }
+
+    fn apply_effort(&self, request: &mut ChatRequest, effort: &Effort) -> AppliedEffort {
+        self.inner.apply_effort(request, effort)
+    }
+
+    fn provider_name(&self) -> &str {
+        self.inner.provider_name()
+    }
+}
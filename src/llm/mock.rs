@@ -19,7 +19,7 @@ use tokio::sync::mpsc;
 pub struct MockLlmClient {
     responses: Arc<Mutex<Vec<MockResponse>>>,
     calls: Arc<Mutex<Vec<ChatRequest>>>,
-    error_on_call: Arc<Mutex<Option<usize>>>, // Fail on nth call
+    error_on_call: Arc<Mutex<Vec<usize>>>, // Fail on these calls
 }
 
 /// Mock response configuration
@@ -28,6 +28,8 @@ pub struct MockResponse {
     pub content: String,
     pub tool_calls: Vec<MockToolCall>,
     pub finish_reason: String,
+    /// Validation warnings to report, as a repaired provider response would
+    pub warnings: Vec<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -48,7 +50,7 @@ impl MockLlmClient {
         Self {
             responses: Arc::new(Mutex::new(Vec::new())),
             calls: Arc::new(Mutex::new(Vec::new())),
-            error_on_call: Arc::new(Mutex::new(None)),
+            error_on_call: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
@@ -59,7 +61,7 @@ impl MockLlmClient {
                 responses.iter().map(|r| MockResponse::text(r)).collect(),
             )),
             calls: Arc::new(Mutex::new(Vec::new())),
-            error_on_call: Arc::new(Mutex::new(None)),
+            error_on_call: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
@@ -68,7 +70,7 @@ impl MockLlmClient {
         Self {
             responses: Arc::new(Mutex::new(responses)),
             calls: Arc::new(Mutex::new(Vec::new())),
-            error_on_call: Arc::new(Mutex::new(None)),
+            error_on_call: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
@@ -106,13 +108,19 @@ impl MockLlmClient {
 
     /// Set the client to error on a specific call index
     pub fn error_on_call(self, call_index: usize) -> Self {
-        *self.error_on_call.lock().unwrap() = Some(call_index);
+        *self.error_on_call.lock().unwrap() = vec![call_index];
+        self
+    }
+
+    /// Set the client to error on each of these call indices
+    pub fn error_on_calls(self, call_indices: impl IntoIterator<Item = usize>) -> Self {
+        *self.error_on_call.lock().unwrap() = call_indices.into_iter().collect();
         self
     }
 
     /// Set the client to fail on the nth call (0-indexed)
     pub fn fail_on_call(&self, call_index: usize) {
-        *self.error_on_call.lock().unwrap() = Some(call_index);
+        *self.error_on_call.lock().unwrap() = vec![call_index];
     }
 
     /// Get the number of calls made
@@ -143,6 +151,7 @@ impl MockResponse {
             content: content.to_string(),
             tool_calls: vec![],
             finish_reason: "stop".to_string(),
+            warnings: vec![],
         }
     }
 
@@ -155,6 +164,7 @@ impl MockResponse {
                 arguments,
             }],
             finish_reason: "tool_calls".to_string(),
+            warnings: vec![],
         }
     }
 
@@ -170,8 +180,15 @@ impl MockResponse {
                 })
                 .collect(),
             finish_reason: "tool_calls".to_string(),
+            warnings: vec![],
         }
     }
+
+    /// Attach validation warnings to this response
+    pub fn with_warnings(mut self, warnings: Vec<&str>) -> Self {
+        self.warnings = warnings.into_iter().map(String::from).collect();
+        self
+    }
 }
 
 #[async_trait]
@@ -182,10 +199,8 @@ impl GenericChatClient for MockLlmClient {
 
         // Check if we should fail on this call
         let call_index = self.calls.lock().unwrap().len() - 1;
-        if let Some(fail_index) = *self.error_on_call.lock().unwrap() {
-            if call_index == fail_index {
-                return Err(LlmError::NetworkError("Mock network error".to_string()));
-            }
+        if self.error_on_call.lock().unwrap().contains(&call_index) {
+            return Err(LlmError::NetworkError("Mock network error".to_string()));
         }
 
         // Get the next response
@@ -200,7 +215,11 @@ impl GenericChatClient for MockLlmClient {
                     prompt_tokens: 10,
                     completion_tokens: 5,
                     total_tokens: 15,
+                    reasoning_tokens: None,
                 }),
+                warnings: vec![],
+                provider: None,
+                failovers: Vec::new(),
             });
         }
 
@@ -236,7 +255,11 @@ impl GenericChatClient for MockLlmClient {
                 prompt_tokens: 10,
                 completion_tokens: 5,
                 total_tokens: 15,
+                reasoning_tokens: None,
             }),
+            warnings: mock_response.warnings,
+            provider: None,
+            failovers: Vec::new(),
         })
     }
 
@@ -255,6 +278,10 @@ impl GenericChatClient for MockLlmClient {
 
         Ok(response)
     }
+
+    fn provider_name(&self) -> &str {
+        "mock"
+    }
 }
 
 #[cfg(test)]
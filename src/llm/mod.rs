@@ -3,13 +3,52 @@ use std::sync::Arc;
 use async_trait::async_trait;
 use tokio::sync::mpsc;
 
+pub mod batch;
+pub mod cache;
+pub mod effort;
+pub mod embeddings;
+pub mod fallback;
+pub mod history;
+pub mod logging;
 pub mod mock;
 pub mod provider;
+pub mod rate_limit;
+pub mod record_replay;
+pub mod tokens;
 pub mod types; // Always available for testing
+pub mod validation;
 
+pub use batch::{BatchChatClient, BatchHandle, BatchState, BatchStatus};
+pub use cache::{CacheStats, CachedChatClient, DiskLlmCache, LlmCache, MemoryLlmCache};
+pub use effort::{AppliedEffort, Effort, EffortMapping};
+pub use embeddings::{
+    EmbeddingClient, EmbeddingResponse, EmbeddingUsage, LlamaEmbeddingApi, LlamaEmbeddings,
+    OpenAIEmbeddings,
+};
+pub use fallback::{Failover, FallbackChatClient, FallbackStats};
+pub use history::{validate_history, HistoryError};
+pub use logging::{
+    LlmLogConfig, LlmLogEntry, LlmLogSink, LoggedMessage, LoggedRequest, LoggedResponse,
+    LoggedToolCall, LoggingChatClient, LLM_LOG_FILE,
+};
 pub use mock::{MockLlmClient, MockResponse, MockToolCall};
-pub use provider::{LlamaClient, OpenAIClient};
-pub use types::{ChatMessage, ChatRequest, ChatResponse, Role};
+pub use provider::{
+    ClaudeClient, GeminiClient, HeaderProvider, LlamaClient, OllamaClient, OpenAIApi, OpenAIClient,
+};
+pub use rate_limit::{
+    RateLimitPermit, RateLimitStats, RateLimitedChatClient, RateLimiter, RateLimits,
+};
+pub use record_replay::{
+    Cassette, Interaction, RecordingChatClient, ReplayChatClient, ReplayMatch,
+};
+#[cfg(feature = "tiktoken")]
+pub use tokens::{Encoding, TiktokenCounter};
+pub use tokens::{HeuristicCounter, TokenCounter};
+pub use types::{
+    ChatMessage, ChatRequest, ChatResponse, ContentPart, MessageContent, Role, SamplingParams,
+    ToolChoice,
+};
+pub use validation::{FinishReason, ResponseValidator, Strictness};
 
 /// Result type for LLM operations
 pub type LlmResult<T> = Result<T, LlmError>;
@@ -36,6 +75,18 @@ pub enum LlmError {
     ParseError(String),
 }
 
+impl LlmError {
+    /// Whether retrying the same request may succeed: network failures,
+    /// rate limits and 5xx server errors
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            LlmError::NetworkError(_) | LlmError::RateLimitExceeded => true,
+            LlmError::ApiError(message) => message.starts_with("Status 5"),
+            _ => false,
+        }
+    }
+}
+
 /// Generic trait for LLM chat clients
 #[async_trait]
 pub trait GenericChatClient: Send + Sync {
@@ -48,6 +99,20 @@ pub trait GenericChatClient: Send + Sync {
         request: ChatRequest,
         tx: mpsc::Sender<String>,
     ) -> LlmResult<ChatResponse>;
+
+    /// Map a provider-agnostic effort level onto this client's native
+    /// mechanism, mutating `request` accordingly.
+    ///
+    /// The default is the prompt fallback (scratchpad instruction plus a
+    /// larger `max_tokens`); clients with native reasoning controls override it.
+    fn apply_effort(&self, request: &mut ChatRequest, effort: &Effort) -> AppliedEffort {
+        effort::apply_prompt_fallback(request, effort, None)
+    }
+
+    /// Provider name for metrics labels, e.g. `openai`
+    fn provider_name(&self) -> &str {
+        "unknown"
+    }
 }
 
 /// Type alias for Arc-wrapped LLM client trait objects
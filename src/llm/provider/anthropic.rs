@@ -0,0 +1,741 @@
+use async_trait::async_trait;
+use futures::stream::StreamExt;
+use reqwest::Client as HttpClient;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use super::text_only;
+use crate::config::AnthropicConfig;
+use crate::error::{ConfigError, ConfigErrorCode};
+use crate::llm::types::{ChatMessage, ContentPart, Role, ToolChoice, Usage};
+use crate::llm::validation::{RawFunctionCall, RawToolCall, ResponseValidator, Strictness};
+use crate::llm::{ChatRequest, ChatResponse, GenericChatClient, LlmError, LlmResult};
+
+const ANTHROPIC_API_URL: &str = "https://api.anthropic.com/v1";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const DEFAULT_MODEL: &str = "claude-3-5-sonnet-latest";
+
+/// The Messages API requires `max_tokens` on every request
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+/// Anthropic Messages API client
+///
+/// Translates our OpenAI-shaped messages and tool schemas: system messages
+/// become the top-level `system` field, tool calls become `tool_use` blocks
+/// and tool results become `tool_result` blocks in a user turn.
+pub struct ClaudeClient {
+    api_key: String,
+    model: String,
+    base_url: String,
+    max_tokens: u32,
+    http_client: HttpClient,
+    validator: ResponseValidator,
+}
+
+impl ClaudeClient {
+    /// Create a new Claude client
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self::with_model(api_key, DEFAULT_MODEL)
+    }
+
+    /// Create a new Claude client with specific model
+    pub fn with_model(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            model: model.into(),
+            base_url: ANTHROPIC_API_URL.to_string(),
+            max_tokens: DEFAULT_MAX_TOKENS,
+            http_client: HttpClient::new(),
+            validator: ResponseValidator::default(),
+        }
+    }
+
+    /// Build a client from `[llm.anthropic]`, falling back to the
+    /// `ANTHROPIC_API_KEY` environment variable for the key
+    pub fn from_config(config: &AnthropicConfig) -> Result<Self, ConfigError> {
+        let api_key = config
+            .api_key
+            .clone()
+            .or_else(|| std::env::var("ANTHROPIC_API_KEY").ok())
+            .ok_or_else(|| ConfigError {
+                code: ConfigErrorCode::MissingRequiredField,
+                message: "No Anthropic API key in config or ANTHROPIC_API_KEY".to_string(),
+                field: Some("llm.anthropic.api_key".to_string()),
+            })?;
+
+        let mut client = Self::with_model(
+            api_key,
+            config.model.clone().unwrap_or_else(|| DEFAULT_MODEL.into()),
+        );
+        if let Some(base_url) = &config.api_base {
+            client = client.with_base_url(base_url.clone());
+        }
+        if let Some(max_tokens) = config.max_tokens {
+            client.max_tokens = max_tokens;
+        }
+        Ok(client)
+    }
+
+    /// Point at a different endpoint, e.g. a proxy (default:
+    /// `https://api.anthropic.com/v1`)
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// `max_tokens` sent when the request doesn't set one
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Set how malformed tool calls are handled (default: lenient)
+    pub fn with_validation(mut self, strictness: Strictness) -> Self {
+        self.validator = ResponseValidator::new(strictness);
+        self
+    }
+
+    /// Get the model name
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Get the provider name
+    pub fn provider(&self) -> &str {
+        "anthropic"
+    }
+
+    fn build_request(&self, request: ChatRequest, stream: bool) -> LlmResult<AnthropicRequest> {
+        let (system, messages) = translate_messages(request.messages)?;
+        Ok(AnthropicRequest {
+            model: self.model.clone(),
+            system,
+            messages,
+            max_tokens: request.max_tokens.unwrap_or(self.max_tokens),
+            temperature: request.temperature,
+            top_p: request.top_p,
+            stop_sequences: request.stop,
+            tools: request
+                .tools
+                .map(|tools| tools.into_iter().map(translate_tool).collect()),
+            tool_choice: request.tool_choice.map(|choice| match choice {
+                ToolChoice::Auto => serde_json::json!({ "type": "auto" }),
+                ToolChoice::None => serde_json::json!({ "type": "none" }),
+            }),
+            stream,
+        })
+    }
+
+    async fn send(&self, body: &AnthropicRequest) -> LlmResult<reqwest::Response> {
+        let response = self
+            .http_client
+            .post(format!("{}/messages", self.base_url))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .header("Content-Type", "application/json")
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| LlmError::NetworkError(e.to_string()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(match status.as_u16() {
+                401 | 403 => LlmError::AuthenticationFailed(error_text),
+                429 => LlmError::RateLimitExceeded,
+                _ => LlmError::ApiError(format!("Status {}: {}", status, error_text)),
+            });
+        }
+        Ok(response)
+    }
+
+    fn to_chat_response(&self, response: AnthropicResponse) -> LlmResult<ChatResponse> {
+        let mut content = String::new();
+        let mut tool_calls = Vec::new();
+        for block in response.content {
+            match block {
+                ContentBlock::Text { text } => content.push_str(&text),
+                ContentBlock::ToolUse { id, name, input } => {
+                    tool_calls.push(raw_tool_call(id, name, input))
+                }
+                ContentBlock::Other => {}
+            }
+        }
+
+        let normalized = self
+            .validator
+            .normalize(Some(tool_calls), response.stop_reason.as_deref())?;
+
+        Ok(ChatResponse {
+            content,
+            model: response.model,
+            usage: response.usage.map(UsageInfo::into_usage),
+            finish_reason: normalized.finish_reason,
+            tool_calls: normalized.tool_calls,
+            warnings: normalized.warnings,
+            provider: None,
+            failovers: Vec::new(),
+        })
+    }
+}
+
+#[async_trait]
+impl GenericChatClient for ClaudeClient {
+    async fn chat(&self, request: ChatRequest) -> LlmResult<ChatResponse> {
+        let body = self.build_request(request, false)?;
+        let response: AnthropicResponse = self
+            .send(&body)
+            .await?
+            .json()
+            .await
+            .map_err(|e| LlmError::ParseError(e.to_string()))?;
+
+        self.to_chat_response(response)
+    }
+
+    async fn chat_stream(
+        &self,
+        request: ChatRequest,
+        tx: mpsc::Sender<String>,
+    ) -> LlmResult<ChatResponse> {
+        let body = self.build_request(request, true)?;
+        let response = self.send(&body).await?;
+
+        let mut state = StreamState::default();
+        let mut buffer = Vec::new();
+        let mut stream = response.bytes_stream();
+        while let Some(chunk_result) = stream.next().await {
+            let bytes = chunk_result.map_err(|e| LlmError::NetworkError(e.to_string()))?;
+            buffer.extend_from_slice(&bytes);
+
+            // Events (and UTF-8 characters) can be split across chunks;
+            // only handle complete lines
+            while let Some(newline) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=newline).collect();
+                let line = String::from_utf8_lossy(&line);
+                let Some(data) = line.trim_end().strip_prefix("data:") else {
+                    continue;
+                };
+                let Ok(event) = serde_json::from_str::<Value>(data.trim()) else {
+                    continue;
+                };
+                if let Some(text) = state.apply(&event)? {
+                    let _ = tx.send(text).await;
+                }
+            }
+        }
+
+        let normalized = self
+            .validator
+            .normalize(Some(state.tool_calls()), state.stop_reason.as_deref())?;
+
+        Ok(ChatResponse {
+            content: state.content,
+            model: state.model.unwrap_or_else(|| self.model.clone()),
+            usage: state.usage.map(UsageInfo::into_usage),
+            finish_reason: normalized.finish_reason,
+            tool_calls: normalized.tool_calls,
+            warnings: normalized.warnings,
+            provider: None,
+            failovers: Vec::new(),
+        })
+    }
+
+    fn provider_name(&self) -> &str {
+        "anthropic"
+    }
+}
+
+/// Split out the system prompt and group the rest into alternating turns
+///
+/// Fails if a message other than a user turn carries an image.
+fn translate_messages(
+    messages: Vec<ChatMessage>,
+) -> LlmResult<(Option<String>, Vec<AnthropicMessage>)> {
+    let mut system = Vec::new();
+    let mut turns: Vec<AnthropicMessage> = Vec::new();
+
+    for message in messages {
+        let (role, blocks) = match message.role {
+            Role::System => {
+                system.push(text_only("anthropic", &message)?.into_owned());
+                continue;
+            }
+            Role::User => (
+                "user",
+                message.content.parts().iter().map(content_block).collect(),
+            ),
+            Role::Tool => (
+                "user",
+                vec![serde_json::json!({
+                    "type": "tool_result",
+                    "tool_use_id": message.tool_call_id.clone().unwrap_or_default(),
+                    "content": text_only("anthropic", &message)?,
+                })],
+            ),
+            Role::Assistant => {
+                let mut blocks = Vec::new();
+                let text = text_only("anthropic", &message)?;
+                if !text.is_empty() {
+                    blocks.push(text_block(text.into_owned()));
+                }
+                for call in message.tool_calls.unwrap_or_default() {
+                    let input = serde_json::from_str::<Value>(&call.function.arguments)
+                        .ok()
+                        .filter(Value::is_object)
+                        .unwrap_or_else(|| serde_json::json!({}));
+                    blocks.push(serde_json::json!({
+                        "type": "tool_use",
+                        "id": call.id,
+                        "name": call.function.name,
+                        "input": input,
+                    }));
+                }
+                ("assistant", blocks)
+            }
+        };
+
+        // Consecutive same-role messages (e.g. several tool results) share a turn
+        match turns.last_mut() {
+            Some(turn) if turn.role == role => turn.content.extend(blocks),
+            _ => turns.push(AnthropicMessage {
+                role,
+                content: blocks,
+            }),
+        }
+    }
+
+    let system = (!system.is_empty()).then(|| system.join("\n\n"));
+    Ok((system, turns))
+}
+
+fn text_block(text: String) -> Value {
+    serde_json::json!({ "type": "text", "text": text })
+}
+
+fn content_block(part: &ContentPart) -> Value {
+    match part {
+        ContentPart::Text { text } => text_block(text.clone()),
+        ContentPart::ImageUrl { url } => serde_json::json!({
+            "type": "image",
+            "source": { "type": "url", "url": url },
+        }),
+        ContentPart::ImageBase64 { mime_type, data } => serde_json::json!({
+            "type": "image",
+            "source": { "type": "base64", "media_type": mime_type, "data": data },
+        }),
+    }
+}
+
+/// OpenAI function schemas become `{name, description, input_schema}`;
+/// schemas already in Anthropic's shape pass through
+fn translate_tool(tool: Value) -> Value {
+    let Some(function) = tool.get("function") else {
+        return tool;
+    };
+    let mut translated = serde_json::json!({
+        "name": function.get("name").cloned().unwrap_or_default(),
+        "input_schema": function
+            .get("parameters")
+            .cloned()
+            .unwrap_or_else(|| serde_json::json!({ "type": "object" })),
+    });
+    if let Some(description) = function.get("description") {
+        translated["description"] = description.clone();
+    }
+    translated
+}
+
+fn raw_tool_call(id: String, name: String, input: Value) -> RawToolCall {
+    RawToolCall {
+        id: Some(id),
+        r#type: Some("function".to_string()),
+        function: Some(RawFunctionCall {
+            name: Some(name),
+            arguments: Some(input),
+        }),
+    }
+}
+
+/// A content block assembled from stream events
+#[derive(Debug)]
+enum StreamBlock {
+    Text,
+    ToolUse {
+        id: String,
+        name: String,
+        initial_input: Value,
+        partial_json: String,
+    },
+}
+
+/// Accumulates a streamed message from its SSE events
+#[derive(Debug, Default)]
+struct StreamState {
+    content: String,
+    blocks: Vec<(u64, StreamBlock)>,
+    model: Option<String>,
+    stop_reason: Option<String>,
+    usage: Option<UsageInfo>,
+}
+
+impl StreamState {
+    /// Apply one event, returning text to forward to the caller
+    fn apply(&mut self, event: &Value) -> LlmResult<Option<String>> {
+        let index = event.get("index").and_then(Value::as_u64).unwrap_or(0);
+        match event
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+        {
+            "message_start" => {
+                let message = &event["message"];
+                self.model = message["model"].as_str().map(str::to_string);
+                self.usage = serde_json::from_value(message["usage"].clone()).ok();
+            }
+            "content_block_start" => {
+                let block = &event["content_block"];
+                let block = match block["type"].as_str() {
+                    Some("tool_use") => StreamBlock::ToolUse {
+                        id: block["id"].as_str().unwrap_or_default().to_string(),
+                        name: block["name"].as_str().unwrap_or_default().to_string(),
+                        initial_input: block["input"].clone(),
+                        partial_json: String::new(),
+                    },
+                    _ => StreamBlock::Text,
+                };
+                self.blocks.push((index, block));
+            }
+            "content_block_delta" => {
+                let delta = &event["delta"];
+                match delta["type"].as_str() {
+                    Some("text_delta") => {
+                        let text = delta["text"].as_str().unwrap_or_default();
+                        self.content.push_str(text);
+                        return Ok((!text.is_empty()).then(|| text.to_string()));
+                    }
+                    Some("input_json_delta") => {
+                        let block = self.blocks.iter_mut().find(|(i, _)| *i == index);
+                        if let Some((_, StreamBlock::ToolUse { partial_json, .. })) = block {
+                            partial_json.push_str(delta["partial_json"].as_str().unwrap_or(""));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "message_delta" => {
+                if let Some(reason) = event["delta"]["stop_reason"].as_str() {
+                    self.stop_reason = Some(reason.to_string());
+                }
+                if let Some(output) = event["usage"]["output_tokens"].as_u64() {
+                    self.usage
+                        .get_or_insert_with(UsageInfo::default)
+                        .output_tokens = output as u32;
+                }
+            }
+            "error" => {
+                let error = &event["error"];
+                let message = error["message"].as_str().unwrap_or("unknown error");
+                return Err(match error["type"].as_str() {
+                    Some("rate_limit_error") => LlmError::RateLimitExceeded,
+                    Some("authentication_error") => {
+                        LlmError::AuthenticationFailed(message.to_string())
+                    }
+                    _ => LlmError::ApiError(format!("Stream error: {}", message)),
+                });
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn tool_calls(&mut self) -> Vec<RawToolCall> {
+        std::mem::take(&mut self.blocks)
+            .into_iter()
+            .filter_map(|(_, block)| match block {
+                StreamBlock::ToolUse {
+                    id,
+                    name,
+                    initial_input,
+                    partial_json,
+                } => {
+                    // Arguments arrive as JSON fragments; without any, the
+                    // start event's input is complete
+                    let arguments = if partial_json.is_empty() {
+                        initial_input
+                    } else {
+                        // Unparseable fragments are left for the validator to repair
+                        serde_json::from_str(&partial_json).unwrap_or(Value::String(partial_json))
+                    };
+                    Some(raw_tool_call(id, name, arguments))
+                }
+                StreamBlock::Text => None,
+            })
+            .collect()
+    }
+}
+
+// Anthropic-specific request/response types
+
+#[derive(Debug, Serialize)]
+struct AnthropicRequest {
+    model: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+
+    messages: Vec<AnthropicMessage>,
+    max_tokens: u32,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop_sequences: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<Value>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<Value>,
+
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct AnthropicMessage {
+    role: &'static str,
+    content: Vec<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicResponse {
+    model: String,
+    content: Vec<ContentBlock>,
+    stop_reason: Option<String>,
+    usage: Option<UsageInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentBlock {
+    Text {
+        text: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: Value,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct UsageInfo {
+    #[serde(default)]
+    input_tokens: u32,
+    #[serde(default)]
+    output_tokens: u32,
+}
+
+impl UsageInfo {
+    fn into_usage(self) -> Usage {
+        Usage {
+            prompt_tokens: self.input_tokens,
+            completion_tokens: self.output_tokens,
+            total_tokens: self.input_tokens + self.output_tokens,
+            reasoning_tokens: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::types::{FunctionCall, ToolCall};
+    use serde_json::json;
+
+    #[test]
+    fn test_request_translates_roles_and_tools() {
+        let call = ToolCall {
+            id: "toolu_1".to_string(),
+            r#type: "function".to_string(),
+            function: FunctionCall {
+                name: "search".to_string(),
+                arguments: "{\"q\":\"otters\"}".to_string(),
+            },
+        };
+        let request = ChatRequest::new(vec![
+            ChatMessage::system("Be brief"),
+            ChatMessage::user("Find otters"),
+            ChatMessage::assistant_with_tool_calls("Searching", vec![call.clone()]),
+            ChatMessage::tool_result("toolu_1", "3 results"),
+            ChatMessage::tool_result("toolu_2", "none"),
+        ])
+        .with_tools(vec![json!({
+            "type": "function",
+            "function": {
+                "name": "search",
+                "description": "Search the web",
+                "parameters": { "type": "object", "properties": { "q": { "type": "string" } } }
+            }
+        })]);
+
+        let body = serde_json::to_value(
+            ClaudeClient::new("key")
+                .build_request(request, false)
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(body["system"], "Be brief");
+        assert_eq!(body["max_tokens"], 4096);
+        assert!(body.get("stream").is_none());
+        assert_eq!(
+            body["messages"],
+            json!([
+                { "role": "user", "content": [{ "type": "text", "text": "Find otters" }] },
+                { "role": "assistant", "content": [
+                    { "type": "text", "text": "Searching" },
+                    { "type": "tool_use", "id": "toolu_1", "name": "search", "input": { "q": "otters" } }
+                ]},
+                { "role": "user", "content": [
+                    { "type": "tool_result", "tool_use_id": "toolu_1", "content": "3 results" },
+                    { "type": "tool_result", "tool_use_id": "toolu_2", "content": "none" }
+                ]}
+            ])
+        );
+        assert_eq!(
+            body["tools"],
+            json!([{
+                "name": "search",
+                "description": "Search the web",
+                "input_schema": { "type": "object", "properties": { "q": { "type": "string" } } }
+            }])
+        );
+    }
+
+    #[test]
+    fn test_tool_choice_none_keeps_the_tools() {
+        let tool = json!({
+            "type": "function",
+            "function": { "name": "search", "parameters": { "type": "object" } }
+        });
+        let request = ChatRequest::new(vec![ChatMessage::user("Sum up")])
+            .with_tools(vec![tool])
+            .with_tool_choice(ToolChoice::None);
+
+        let body = serde_json::to_value(
+            ClaudeClient::new("key")
+                .build_request(request, false)
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(body["tools"][0]["name"], "search");
+        assert_eq!(body["tool_choice"], json!({ "type": "none" }));
+
+        // Left to the provider by default
+        let request = ChatRequest::new(vec![ChatMessage::user("Sum up")]);
+        let body = serde_json::to_value(
+            ClaudeClient::new("key")
+                .build_request(request, false)
+                .unwrap(),
+        )
+        .unwrap();
+        assert!(body.get("tool_choice").is_none());
+    }
+
+    #[test]
+    fn test_user_images_become_image_blocks() {
+        let request = ChatRequest::new(vec![ChatMessage::user_with_images(
+            "What is this?",
+            [
+                ContentPart::image_url("https://example.com/otter.jpg"),
+                ContentPart::image_base64("image/png", "iVBORw0KGgo="),
+            ],
+        )]);
+
+        let body = serde_json::to_value(
+            ClaudeClient::new("key")
+                .build_request(request, false)
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            body["messages"][0]["content"],
+            json!([
+                { "type": "text", "text": "What is this?" },
+                { "type": "image", "source": { "type": "url", "url": "https://example.com/otter.jpg" } },
+                { "type": "image", "source": {
+                    "type": "base64", "media_type": "image/png", "data": "iVBORw0KGgo="
+                }}
+            ])
+        );
+    }
+
+    #[test]
+    fn test_tool_use_blocks_become_tool_calls() {
+        let body: AnthropicResponse = serde_json::from_value(json!({
+            "model": "claude-test",
+            "content": [
+                { "type": "thinking", "thinking": "hmm" },
+                { "type": "text", "text": "Let me check." },
+                { "type": "tool_use", "id": "toolu_1", "name": "search", "input": { "q": "otters" } }
+            ],
+            "stop_reason": "tool_use",
+            "usage": { "input_tokens": 12, "output_tokens": 7 }
+        }))
+        .unwrap();
+        let response = ClaudeClient::new("key").to_chat_response(body).unwrap();
+
+        assert_eq!(response.content, "Let me check.");
+        assert_eq!(response.finish_reason.as_deref(), Some("tool_calls"));
+        let calls = response.tool_calls.unwrap();
+        assert_eq!(calls[0].id, "toolu_1");
+        assert_eq!(calls[0].function.arguments, "{\"q\":\"otters\"}");
+        assert_eq!(response.usage.unwrap().total_tokens, 19);
+    }
+
+    #[test]
+    fn test_stream_events_accumulate() {
+        let mut state = StreamState::default();
+        let events = [
+            json!({"type": "message_start", "message": {"model": "claude-test", "usage": {"input_tokens": 20, "output_tokens": 1}}}),
+            json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "On it"}}),
+            json!({"type": "content_block_start", "index": 1, "content_block": {"type": "tool_use", "id": "toolu_9", "name": "search", "input": {}}}),
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "{\"q\": "}}),
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "\"otters\"}"}}),
+            json!({"type": "message_delta", "delta": {"stop_reason": "tool_use"}, "usage": {"output_tokens": 15}}),
+            json!({"type": "message_stop"}),
+        ];
+        let forwarded: Vec<String> = events
+            .iter()
+            .filter_map(|e| state.apply(e).unwrap())
+            .collect();
+        assert_eq!(forwarded, vec!["On it"]);
+        assert_eq!(state.stop_reason.as_deref(), Some("tool_use"));
+
+        let calls = ClaudeClient::new("key")
+            .validator
+            .normalize(Some(state.tool_calls()), Some("tool_use"))
+            .unwrap()
+            .tool_calls
+            .unwrap();
+        assert_eq!(calls[0].function.name, "search");
+        assert_eq!(calls[0].function.arguments, "{\"q\":\"otters\"}");
+        let usage = state.usage.unwrap().into_usage();
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (20, 15));
+
+        let err = StreamState::default()
+            .apply(&json!({"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}))
+            .unwrap_err();
+        assert!(matches!(err, LlmError::ApiError(m) if m.contains("Overloaded")));
+    }
+}
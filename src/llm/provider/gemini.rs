@@ -0,0 +1,821 @@
+use async_trait::async_trait;
+use futures::stream::StreamExt;
+use reqwest::Client as HttpClient;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+
+use super::text_only;
+use crate::config::GeminiConfig;
+use crate::error::{ConfigError, ConfigErrorCode};
+use crate::llm::types::{ChatMessage, ContentPart, Role, ToolChoice, Usage};
+use crate::llm::validation::{RawFunctionCall, RawToolCall, ResponseValidator, Strictness};
+use crate::llm::{ChatRequest, ChatResponse, GenericChatClient, LlmError, LlmResult};
+
+const GEMINI_API_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+const DEFAULT_MODEL: &str = "gemini-2.0-flash";
+
+/// Finish and block reasons meaning a filter stopped the output
+const BLOCKED_REASONS: &[&str] = &[
+    "SAFETY",
+    "RECITATION",
+    "BLOCKLIST",
+    "PROHIBITED_CONTENT",
+    "SPII",
+    "IMAGE_SAFETY",
+];
+
+/// Google Generative Language API (Gemini) client
+///
+/// Translates our OpenAI-shaped messages and tool schemas: system messages
+/// become `systemInstruction`, assistant turns become `model` turns with
+/// `functionCall` parts, and tool results become `functionResponse` parts in
+/// a user turn. Gemini doesn't identify function calls, so the client gives
+/// each one an id.
+pub struct GeminiClient {
+    api_key: String,
+    model: String,
+    base_url: String,
+    http_client: HttpClient,
+    validator: ResponseValidator,
+}
+
+impl GeminiClient {
+    /// Create a new Gemini client
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self::with_model(api_key, DEFAULT_MODEL)
+    }
+
+    /// Create a new Gemini client with specific model
+    pub fn with_model(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            model: model.into(),
+            base_url: GEMINI_API_URL.to_string(),
+            http_client: HttpClient::new(),
+            validator: ResponseValidator::default(),
+        }
+    }
+
+    /// Build a client from `[llm.gemini]`, falling back to the
+    /// `GEMINI_API_KEY` environment variable for the key
+    pub fn from_config(config: &GeminiConfig) -> Result<Self, ConfigError> {
+        let api_key = config
+            .api_key
+            .clone()
+            .or_else(|| std::env::var("GEMINI_API_KEY").ok())
+            .ok_or_else(|| ConfigError {
+                code: ConfigErrorCode::MissingRequiredField,
+                message: "No Gemini API key in config or GEMINI_API_KEY".to_string(),
+                field: Some("llm.gemini.api_key".to_string()),
+            })?;
+
+        let mut client = Self::with_model(
+            api_key,
+            config.model.clone().unwrap_or_else(|| DEFAULT_MODEL.into()),
+        );
+        if let Some(base_url) = &config.api_base {
+            client = client.with_base_url(base_url.clone());
+        }
+        Ok(client)
+    }
+
+    /// Point at a different endpoint, e.g. a proxy (default:
+    /// `https://generativelanguage.googleapis.com/v1beta`)
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Set how malformed tool calls are handled (default: lenient)
+    pub fn with_validation(mut self, strictness: Strictness) -> Self {
+        self.validator = ResponseValidator::new(strictness);
+        self
+    }
+
+    /// Get the model name
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Get the provider name
+    pub fn provider(&self) -> &str {
+        "gemini"
+    }
+
+    fn build_request(&self, request: ChatRequest) -> LlmResult<GeminiRequest> {
+        let (system, contents) = translate_messages(request.messages)?;
+        let generation_config = GenerationConfig {
+            temperature: request.temperature,
+            top_p: request.top_p,
+            max_output_tokens: request.max_tokens,
+            seed: request.seed,
+            stop_sequences: request.stop,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+        };
+        Ok(GeminiRequest {
+            contents,
+            system_instruction: system.map(|text| Content {
+                role: None,
+                parts: vec![serde_json::json!({ "text": text })],
+            }),
+            tools: request.tools.map(|tools| {
+                vec![serde_json::json!({
+                    "functionDeclarations": tools.into_iter().map(translate_tool).collect::<Vec<_>>(),
+                })]
+            }),
+            tool_config: request.tool_choice.map(|choice| {
+                let mode = match choice {
+                    ToolChoice::Auto => "AUTO",
+                    ToolChoice::None => "NONE",
+                };
+                serde_json::json!({ "functionCallingConfig": { "mode": mode } })
+            }),
+            generation_config: (!generation_config.is_empty()).then_some(generation_config),
+        })
+    }
+
+    async fn send(&self, method: &str, body: &GeminiRequest) -> LlmResult<reqwest::Response> {
+        let response = self
+            .http_client
+            .post(format!(
+                "{}/models/{}:{}",
+                self.base_url, self.model, method
+            ))
+            .header("x-goog-api-key", &self.api_key)
+            .header("Content-Type", "application/json")
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| LlmError::NetworkError(e.to_string()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(match status.as_u16() {
+                401 | 403 => LlmError::AuthenticationFailed(error_text),
+                429 => LlmError::RateLimitExceeded,
+                _ => LlmError::ApiError(format!("Status {}: {}", status, error_text)),
+            });
+        }
+        Ok(response)
+    }
+
+    fn to_chat_response(&self, response: GeminiResponse) -> LlmResult<ChatResponse> {
+        let mut state = StreamState::default();
+        state.apply(response)?;
+        self.finish(state)
+    }
+
+    /// Validate what was received and describe any block
+    fn finish(&self, mut state: StreamState) -> LlmResult<ChatResponse> {
+        let tool_calls = std::mem::take(&mut state.tool_calls);
+        let finish_reason = state.finish_reason(!tool_calls.is_empty());
+        let mut normalized = self.validator.normalize(Some(tool_calls), finish_reason)?;
+        normalized.warnings.extend(state.blocked);
+
+        Ok(ChatResponse {
+            content: state.content,
+            model: state.model.unwrap_or_else(|| self.model.clone()),
+            usage: state.usage.map(UsageMetadata::into_usage),
+            finish_reason: normalized.finish_reason,
+            tool_calls: normalized.tool_calls,
+            warnings: normalized.warnings,
+            provider: None,
+            failovers: Vec::new(),
+        })
+    }
+}
+
+#[async_trait]
+impl GenericChatClient for GeminiClient {
+    async fn chat(&self, request: ChatRequest) -> LlmResult<ChatResponse> {
+        let body = self.build_request(request)?;
+        let response: GeminiResponse = self
+            .send("generateContent", &body)
+            .await?
+            .json()
+            .await
+            .map_err(|e| LlmError::ParseError(e.to_string()))?;
+
+        self.to_chat_response(response)
+    }
+
+    async fn chat_stream(
+        &self,
+        request: ChatRequest,
+        tx: mpsc::Sender<String>,
+    ) -> LlmResult<ChatResponse> {
+        let body = self.build_request(request)?;
+        let response = self.send("streamGenerateContent", &body).await?;
+
+        let mut state = StreamState::default();
+        let mut splitter = ObjectSplitter::default();
+        let mut stream = response.bytes_stream();
+        while let Some(chunk_result) = stream.next().await {
+            let bytes = chunk_result.map_err(|e| LlmError::NetworkError(e.to_string()))?;
+            for object in splitter.push(&bytes) {
+                let chunk: GeminiResponse = serde_json::from_slice(&object)
+                    .map_err(|e| LlmError::ParseError(e.to_string()))?;
+                if let Some(text) = state.apply(chunk)? {
+                    let _ = tx.send(text).await;
+                }
+            }
+        }
+
+        self.finish(state)
+    }
+
+    fn provider_name(&self) -> &str {
+        "gemini"
+    }
+}
+
+/// Split out the system prompt and group the rest into alternating
+/// `user`/`model` turns
+///
+/// Fails on image URLs, which Gemini doesn't fetch, and on images outside
+/// user turns.
+fn translate_messages(messages: Vec<ChatMessage>) -> LlmResult<(Option<String>, Vec<Content>)> {
+    let mut system = Vec::new();
+    let mut turns: Vec<Content> = Vec::new();
+    // `functionResponse` names the function, but tool results only carry
+    // the call id
+    let mut call_names: HashMap<String, String> = HashMap::new();
+
+    for message in messages {
+        let (role, parts) = match message.role {
+            Role::System => {
+                system.push(text_only("gemini", &message)?.into_owned());
+                continue;
+            }
+            Role::User => (
+                "user",
+                message
+                    .content
+                    .parts()
+                    .iter()
+                    .map(content_part)
+                    .collect::<LlmResult<_>>()?,
+            ),
+            Role::Tool => {
+                let content = text_only("gemini", &message)?;
+                let id = message.tool_call_id.clone().unwrap_or_default();
+                let name = call_names.get(&id).cloned().unwrap_or(id);
+                // The response must be an object
+                let response = match serde_json::from_str::<Value>(&content) {
+                    Ok(value @ Value::Object(_)) => value,
+                    Ok(value) => serde_json::json!({ "content": value }),
+                    Err(_) => serde_json::json!({ "content": content }),
+                };
+                (
+                    "user",
+                    vec![serde_json::json!({
+                        "functionResponse": { "name": name, "response": response },
+                    })],
+                )
+            }
+            Role::Assistant => {
+                let mut parts = Vec::new();
+                let text = text_only("gemini", &message)?;
+                if !text.is_empty() {
+                    parts.push(serde_json::json!({ "text": text }));
+                }
+                for call in message.tool_calls.unwrap_or_default() {
+                    let args = serde_json::from_str::<Value>(&call.function.arguments)
+                        .ok()
+                        .filter(Value::is_object)
+                        .unwrap_or_else(|| serde_json::json!({}));
+                    parts.push(serde_json::json!({
+                        "functionCall": { "name": call.function.name, "args": args },
+                    }));
+                    call_names.insert(call.id, call.function.name);
+                }
+                ("model", parts)
+            }
+        };
+
+        // Consecutive same-role messages (e.g. several tool results) share a turn
+        match turns.last_mut() {
+            Some(turn) if turn.role == Some(role) => turn.parts.extend(parts),
+            _ => turns.push(Content {
+                role: Some(role),
+                parts,
+            }),
+        }
+    }
+
+    let system = (!system.is_empty()).then(|| system.join("\n\n"));
+    Ok((system, turns))
+}
+
+fn content_part(part: &ContentPart) -> LlmResult<Value> {
+    match part {
+        ContentPart::Text { text } => Ok(serde_json::json!({ "text": text })),
+        ContentPart::ImageBase64 { mime_type, data } => Ok(serde_json::json!({
+            "inlineData": { "mimeType": mime_type, "data": data },
+        })),
+        ContentPart::ImageUrl { url } => Err(LlmError::InvalidRequest(format!(
+            "gemini does not fetch image URLs; send {} inline with ContentPart::image_base64",
+            url
+        ))),
+    }
+}
+
+/// OpenAI function schemas become `{name, description, parameters}`;
+/// declarations already in Gemini's shape pass through
+fn translate_tool(tool: Value) -> Value {
+    let Some(function) = tool.get("function") else {
+        return tool;
+    };
+    let mut translated = serde_json::json!({
+        "name": function.get("name").cloned().unwrap_or_default(),
+    });
+    if let Some(description) = function.get("description") {
+        translated["description"] = description.clone();
+    }
+    if let Some(parameters) = function.get("parameters") {
+        translated["parameters"] = parameters.clone();
+    }
+    translated
+}
+
+/// Splits a stream of JSON objects, newline-delimited or framed as one
+/// array (`[{...},\n{...}]`), into complete objects as their bytes arrive
+#[derive(Debug, Default)]
+struct ObjectSplitter {
+    buffer: Vec<u8>,
+    /// Where the current object started, once its `{` is seen
+    start: Option<usize>,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    /// Bytes already scanned
+    scanned: usize,
+}
+
+impl ObjectSplitter {
+    fn push(&mut self, bytes: &[u8]) -> Vec<Vec<u8>> {
+        self.buffer.extend_from_slice(bytes);
+        let mut objects = Vec::new();
+        let mut consumed = 0;
+
+        for i in self.scanned..self.buffer.len() {
+            let byte = self.buffer[i];
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match byte {
+                b'"' if self.start.is_some() => self.in_string = true,
+                b'{' => {
+                    if self.depth == 0 {
+                        self.start = Some(i);
+                    }
+                    self.depth += 1;
+                }
+                b'}' if self.depth > 0 => {
+                    self.depth -= 1;
+                    if self.depth == 0 {
+                        let start = self.start.take().unwrap_or(i);
+                        objects.push(self.buffer[start..=i].to_vec());
+                        consumed = i + 1;
+                    }
+                }
+                // Array brackets, commas and whitespace between objects
+                _ => {}
+            }
+        }
+
+        // Keep only the object still being received
+        let keep_from = self.start.unwrap_or(self.buffer.len()).max(consumed);
+        self.buffer.drain(..keep_from);
+        if let Some(start) = self.start.as_mut() {
+            *start -= keep_from;
+        }
+        self.scanned = self.buffer.len();
+        objects
+    }
+}
+
+/// Accumulates a response from one or more `GenerateContentResponse`s
+#[derive(Debug, Default)]
+struct StreamState {
+    content: String,
+    tool_calls: Vec<RawToolCall>,
+    model: Option<String>,
+    finish_reason: Option<String>,
+    usage: Option<UsageMetadata>,
+    /// Why a filter stopped the output, as warnings
+    blocked: Vec<String>,
+}
+
+impl StreamState {
+    /// Apply one response, returning text to forward to the caller
+    fn apply(&mut self, response: GeminiResponse) -> LlmResult<Option<String>> {
+        if let Some(error) = response.error {
+            let message = error.message.unwrap_or_else(|| "unknown error".to_string());
+            return Err(match error.status.as_deref() {
+                Some("RESOURCE_EXHAUSTED") => LlmError::RateLimitExceeded,
+                Some("UNAUTHENTICATED") | Some("PERMISSION_DENIED") => {
+                    LlmError::AuthenticationFailed(message)
+                }
+                _ => LlmError::ApiError(format!("Stream error: {}", message)),
+            });
+        }
+        if let Some(model) = response.model_version {
+            self.model = Some(model);
+        }
+        if let Some(usage) = response.usage_metadata {
+            self.usage = Some(usage);
+        }
+        if let Some(reason) = response.prompt_feedback.and_then(|f| f.block_reason) {
+            self.blocked
+                .push(format!("prompt blocked by Gemini: {}", reason));
+            self.finish_reason = Some(reason);
+        }
+
+        let mut text = String::new();
+        // Only the first candidate is used; we never ask for more
+        if let Some(candidate) = response.candidates.into_iter().next() {
+            for part in candidate.content.map(|c| c.parts).unwrap_or_default() {
+                if let Some(call) = part.function_call {
+                    self.tool_calls.push(RawToolCall {
+                        id: Some(
+                            call.id.unwrap_or_else(|| {
+                                format!("call_{}", uuid::Uuid::new_v4().simple())
+                            }),
+                        ),
+                        r#type: Some("function".to_string()),
+                        function: Some(RawFunctionCall {
+                            name: Some(call.name),
+                            // `args` is an object; the validator expects
+                            // the string OpenAI sends
+                            arguments: Some(Value::String(
+                                call.args
+                                    .unwrap_or_else(|| serde_json::json!({}))
+                                    .to_string(),
+                            )),
+                        }),
+                    });
+                } else if let (Some(part_text), false) = (part.text, part.thought) {
+                    text.push_str(&part_text);
+                }
+            }
+            if let Some(reason) = candidate.finish_reason {
+                if BLOCKED_REASONS.contains(&reason.as_str()) {
+                    let categories: Vec<String> = candidate
+                        .safety_ratings
+                        .iter()
+                        .filter(|r| r.blocked)
+                        .map(|r| r.category.clone())
+                        .collect();
+                    self.blocked.push(if categories.is_empty() {
+                        format!("response blocked by Gemini: {}", reason)
+                    } else {
+                        format!(
+                            "response blocked by Gemini: {} ({})",
+                            reason,
+                            categories.join(", ")
+                        )
+                    });
+                }
+                self.finish_reason = Some(reason);
+            }
+        }
+
+        self.content.push_str(&text);
+        Ok((!text.is_empty()).then_some(text))
+    }
+
+    /// Gemini's finish reason in the validator's terms; it says `STOP` even
+    /// when it calls functions
+    fn finish_reason(&self, has_tool_calls: bool) -> Option<&'static str> {
+        let reason = self.finish_reason.as_deref()?;
+        Some(match reason {
+            "STOP" if has_tool_calls => "tool_calls",
+            "STOP" => "stop",
+            "MAX_TOKENS" => "length",
+            "MALFORMED_FUNCTION_CALL" => "tool_calls",
+            _ if BLOCKED_REASONS.contains(&reason) => "content_filter",
+            // A blocked prompt's reason, e.g. `OTHER`
+            _ if !self.blocked.is_empty() => "content_filter",
+            _ => "other",
+        })
+    }
+}
+
+// Gemini-specific request/response types
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiRequest {
+    contents: Vec<Content>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<Content>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<Value>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_config: Option<Value>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    generation_config: Option<GenerationConfig>,
+}
+
+#[derive(Debug, Serialize)]
+struct Content {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    role: Option<&'static str>,
+    parts: Vec<Value>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop_sequences: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+}
+
+impl GenerationConfig {
+    fn is_empty(&self) -> bool {
+        self.temperature.is_none()
+            && self.top_p.is_none()
+            && self.max_output_tokens.is_none()
+            && self.seed.is_none()
+            && self.stop_sequences.is_none()
+            && self.frequency_penalty.is_none()
+            && self.presence_penalty.is_none()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiResponse {
+    #[serde(default)]
+    candidates: Vec<Candidate>,
+    prompt_feedback: Option<PromptFeedback>,
+    usage_metadata: Option<UsageMetadata>,
+    model_version: Option<String>,
+    /// Sent in place of a chunk when a stream fails part way
+    error: Option<ApiErrorBody>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Candidate {
+    content: Option<CandidateContent>,
+    finish_reason: Option<String>,
+    #[serde(default)]
+    safety_ratings: Vec<SafetyRating>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CandidateContent {
+    #[serde(default)]
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Part {
+    text: Option<String>,
+    /// Thought summaries, which aren't part of the answer
+    #[serde(default)]
+    thought: bool,
+    function_call: Option<GeminiFunctionCall>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiFunctionCall {
+    /// Only some models send one
+    id: Option<String>,
+    name: String,
+    args: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PromptFeedback {
+    block_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SafetyRating {
+    category: String,
+    #[serde(default)]
+    blocked: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiErrorBody {
+    message: Option<String>,
+    status: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageMetadata {
+    #[serde(default)]
+    prompt_token_count: u32,
+    #[serde(default)]
+    candidates_token_count: u32,
+    #[serde(default)]
+    thoughts_token_count: Option<u32>,
+    #[serde(default)]
+    total_token_count: u32,
+}
+
+impl UsageMetadata {
+    fn into_usage(self) -> Usage {
+        // Thinking tokens are counted apart from the candidates'
+        let reasoning = self.thoughts_token_count.unwrap_or(0);
+        let completion = self.candidates_token_count + reasoning;
+        Usage {
+            prompt_tokens: self.prompt_token_count,
+            completion_tokens: completion,
+            total_tokens: self
+                .total_token_count
+                .max(self.prompt_token_count + completion),
+            reasoning_tokens: self.thoughts_token_count,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::types::{FunctionCall, ToolCall};
+    use serde_json::json;
+
+    #[test]
+    fn test_request_translates_roles_and_tools() {
+        let call = ToolCall {
+            id: "call_1".to_string(),
+            r#type: "function".to_string(),
+            function: FunctionCall {
+                name: "search".to_string(),
+                arguments: "{\"q\":\"otters\"}".to_string(),
+            },
+        };
+        let request = ChatRequest::new(vec![
+            ChatMessage::system("Be brief"),
+            ChatMessage::user("Find otters"),
+            ChatMessage::assistant_with_tool_calls("Searching", vec![call]),
+            ChatMessage::tool_result("call_1", "{\"count\":3}"),
+            ChatMessage::tool_result("call_2", "none"),
+        ])
+        .with_tools(vec![json!({
+            "type": "function",
+            "function": {
+                "name": "search",
+                "description": "Search the web",
+                "parameters": { "type": "object", "properties": { "q": { "type": "string" } } }
+            }
+        })])
+        .with_temperature(0.2);
+
+        let body =
+            serde_json::to_value(GeminiClient::new("key").build_request(request).unwrap()).unwrap();
+        assert_eq!(
+            body["systemInstruction"],
+            json!({ "parts": [{ "text": "Be brief" }] })
+        );
+        assert_eq!(body["generationConfig"], json!({ "temperature": 0.2f32 }));
+        assert_eq!(
+            body["contents"],
+            json!([
+                { "role": "user", "parts": [{ "text": "Find otters" }] },
+                { "role": "model", "parts": [
+                    { "text": "Searching" },
+                    { "functionCall": { "name": "search", "args": { "q": "otters" } } }
+                ]},
+                { "role": "user", "parts": [
+                    { "functionResponse": { "name": "search", "response": { "count": 3 } } },
+                    { "functionResponse": { "name": "call_2", "response": { "content": "none" } } }
+                ]}
+            ])
+        );
+        assert_eq!(
+            body["tools"],
+            json!([{ "functionDeclarations": [{
+                "name": "search",
+                "description": "Search the web",
+                "parameters": { "type": "object", "properties": { "q": { "type": "string" } } }
+            }]}])
+        );
+    }
+
+    #[test]
+    fn test_inline_images_become_inline_data() {
+        let request = ChatRequest::new(vec![ChatMessage::user_with_images(
+            "What is this?",
+            [ContentPart::image_base64("image/png", "iVBORw0KGgo=")],
+        )]);
+
+        let body =
+            serde_json::to_value(GeminiClient::new("key").build_request(request).unwrap()).unwrap();
+        assert_eq!(
+            body["contents"][0]["parts"],
+            json!([
+                { "text": "What is this?" },
+                { "inlineData": { "mimeType": "image/png", "data": "iVBORw0KGgo=" } }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_function_calls_get_ids_and_usage_counts_thoughts() {
+        let body: GeminiResponse = serde_json::from_value(json!({
+            "candidates": [{
+                "content": { "role": "model", "parts": [
+                    { "text": "Considering otters", "thought": true },
+                    { "functionCall": { "name": "search", "args": { "q": "otters" } } },
+                    { "functionCall": { "name": "search", "args": { "q": "beavers" } } }
+                ]},
+                "finishReason": "STOP"
+            }],
+            "usageMetadata": {
+                "promptTokenCount": 12,
+                "candidatesTokenCount": 7,
+                "thoughtsTokenCount": 5,
+                "totalTokenCount": 24
+            },
+            "modelVersion": "gemini-test"
+        }))
+        .unwrap();
+        let response = GeminiClient::new("key").to_chat_response(body).unwrap();
+
+        assert_eq!(response.content, "");
+        assert_eq!(response.model, "gemini-test");
+        assert_eq!(response.finish_reason.as_deref(), Some("tool_calls"));
+        assert!(response.warnings.is_empty());
+        let calls = response.tool_calls.unwrap();
+        assert_eq!(calls.len(), 2);
+        assert!(calls[0].id.starts_with("call_"));
+        assert_ne!(calls[0].id, calls[1].id);
+        assert_eq!(calls[1].function.arguments, "{\"q\":\"beavers\"}");
+
+        let usage = response.usage.unwrap();
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (12, 12));
+        assert_eq!((usage.total_tokens, usage.reasoning_tokens), (24, Some(5)));
+    }
+
+    #[test]
+    fn test_blocked_prompt_is_a_content_filter_stop() {
+        let body: GeminiResponse = serde_json::from_value(json!({
+            "promptFeedback": { "blockReason": "OTHER" }
+        }))
+        .unwrap();
+        let response = GeminiClient::new("key").to_chat_response(body).unwrap();
+
+        assert_eq!(response.finish_reason.as_deref(), Some("content_filter"));
+        assert_eq!(response.warnings, vec!["prompt blocked by Gemini: OTHER"]);
+    }
+
+    #[test]
+    fn test_splitter_handles_arrays_and_split_objects() {
+        let mut splitter = ObjectSplitter::default();
+        let mut objects = Vec::new();
+        let stream = "[{\"a\": \"}{\\\"\"},\r\n{\"b\": {\"c\": 1}}\n]";
+        for piece in stream.as_bytes().chunks(3) {
+            objects.extend(splitter.push(piece));
+        }
+        let objects: Vec<Value> = objects
+            .iter()
+            .map(|o| serde_json::from_slice(o).unwrap())
+            .collect();
+        assert_eq!(objects, vec![json!({"a": "}{\""}), json!({"b": {"c": 1}})]);
+
+        // Newline-delimited
+        let objects = ObjectSplitter::default().push(b"{\"x\":1}\n{\"y\":2}\n{\"z\"");
+        assert_eq!(objects.len(), 2);
+    }
+}
@@ -0,0 +1,114 @@
+//! Headers, proxies and timeouts shared by the HTTP providers
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client as HttpClient, RequestBuilder};
+
+use crate::error::{ConfigError, ConfigErrorCode};
+use crate::llm::{LlmError, LlmResult};
+
+/// Headers computed for each request, e.g. a gateway token that expires
+///
+/// Called before every request a client sends, streaming ones included.
+/// Its headers go after a client's default headers and replace any with
+/// the same name, `Authorization` included.
+///
+/// # Examples
+/// ```
+/// use agent_runtime::llm::{HeaderProvider, LlamaClient};
+/// use std::collections::HashMap;
+/// use std::sync::Arc;
+///
+/// let token = || HashMap::from([("X-Gateway-Token".to_string(), "t0k3n".to_string())]);
+/// let client = LlamaClient::new("http://gateway.internal:8080", "llama")
+///     .with_header_provider(Arc::new(token));
+/// ```
+pub trait HeaderProvider: Send + Sync {
+    fn headers(&self) -> HashMap<String, String>;
+}
+
+impl<F> HeaderProvider for F
+where
+    F: Fn() -> HashMap<String, String> + Send + Sync,
+{
+    fn headers(&self) -> HashMap<String, String> {
+        self()
+    }
+}
+
+/// The extra headers a client sends with every request
+#[derive(Clone, Default)]
+pub(crate) struct RequestHeaders {
+    pub fixed: HashMap<String, String>,
+    pub provider: Option<Arc<dyn HeaderProvider>>,
+}
+
+impl RequestHeaders {
+    /// Add the headers to `request`, replacing those it already has
+    pub fn apply(&self, request: RequestBuilder) -> LlmResult<RequestBuilder> {
+        if self.fixed.is_empty() && self.provider.is_none() {
+            return Ok(request);
+        }
+        let mut map = header_map(&self.fixed).map_err(LlmError::InvalidRequest)?;
+        if let Some(provider) = &self.provider {
+            map.extend(header_map(&provider.headers()).map_err(LlmError::InvalidRequest)?);
+        }
+        Ok(request.headers(map))
+    }
+}
+
+/// `headers` as a `HeaderMap`; the error names the header but not its value,
+/// which may be a secret
+pub(crate) fn header_map(headers: &HashMap<String, String>) -> Result<HeaderMap, String> {
+    let mut map = HeaderMap::with_capacity(headers.len());
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("invalid header name {:?}", name))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|_| format!("invalid value for header {}", name))?;
+        map.insert(name, value);
+    }
+    Ok(map)
+}
+
+/// Connection settings from a provider's config section
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Transport<'a> {
+    pub proxy: Option<&'a str>,
+    pub connect_timeout_ms: Option<u64>,
+    pub request_timeout_ms: Option<u64>,
+    pub insecure: bool,
+}
+
+impl Transport<'_> {
+    /// The HTTP client for these settings; errors name fields under `section`,
+    /// e.g. `llm.llama`
+    pub fn client(&self, section: &str) -> Result<HttpClient, ConfigError> {
+        let invalid = |field: &str, message: String| ConfigError {
+            code: ConfigErrorCode::InvalidValue,
+            message,
+            field: Some(format!("{}.{}", section, field)),
+        };
+        let mut builder = HttpClient::builder();
+        if let Some(proxy) = self.proxy {
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|e| invalid("proxy", format!("Invalid proxy URL: {}", e)))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(ms) = self.connect_timeout_ms {
+            builder = builder.connect_timeout(Duration::from_millis(ms));
+        }
+        if let Some(ms) = self.request_timeout_ms {
+            builder = builder.timeout(Duration::from_millis(ms));
+        }
+        if self.insecure {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        builder
+            .build()
+            .map_err(|e| invalid("proxy", format!("Failed to build HTTP client: {}", e)))
+    }
+}
@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
 use async_trait::async_trait;
 use futures::stream::StreamExt;
 use reqwest::Client as HttpClient;
@@ -5,6 +8,14 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tokio::sync::mpsc;
 
+use super::chat_completions_message;
+use super::http::{self, HeaderProvider, RequestHeaders, Transport};
+use crate::config::LlamaConfig;
+use crate::error::{ConfigError, ConfigErrorCode};
+use crate::llm::types::{ChatMessage, ToolChoice};
+use crate::llm::validation::{
+    NormalizedResponse, RawFunctionCall, RawToolCall, ResponseValidator, Strictness,
+};
 use crate::llm::{ChatRequest, ChatResponse, GenericChatClient, LlmError, LlmResult};
 
 /// Llama.cpp server client (local or remote)
@@ -15,6 +26,8 @@ pub struct LlamaClient {
     base_url: String,
     model: String,
     http_client: HttpClient,
+    headers: RequestHeaders,
+    validator: ResponseValidator,
 }
 
 impl LlamaClient {
@@ -28,6 +41,8 @@ impl LlamaClient {
             base_url: base_url.into(),
             model: model.into(),
             http_client: HttpClient::new(),
+            headers: RequestHeaders::default(),
+            validator: ResponseValidator::default(),
         }
     }
 
@@ -42,6 +57,8 @@ impl LlamaClient {
             base_url: base_url.into(),
             model: model.into(),
             http_client,
+            headers: RequestHeaders::default(),
+            validator: ResponseValidator::default(),
         }
     }
 
@@ -71,6 +88,53 @@ impl LlamaClient {
         Self::insecure(format!("https://localhost:{}", port), "llama")
     }
 
+    /// Create a client from the `[llm.llama]` section, with its headers,
+    /// proxy and timeouts
+    pub fn from_config(
+        config: &LlamaConfig,
+        model: impl Into<String>,
+    ) -> Result<Self, ConfigError> {
+        http::header_map(&config.default_headers).map_err(|message| ConfigError {
+            code: ConfigErrorCode::InvalidValue,
+            message,
+            field: Some("llm.llama.default_headers".to_string()),
+        })?;
+        let transport = Transport {
+            proxy: config.proxy.as_deref(),
+            connect_timeout_ms: config.connect_timeout_ms,
+            request_timeout_ms: config.request_timeout_ms,
+            insecure: config.insecure,
+        };
+        Ok(
+            Self::with_http_client(&config.base_url, model, transport.client("llm.llama")?)
+                .with_default_headers(config.default_headers.clone()),
+        )
+    }
+
+    /// Send `headers` with every request
+    pub fn with_default_headers(mut self, headers: HashMap<String, String>) -> Self {
+        self.headers.fixed.extend(headers);
+        self
+    }
+
+    /// Send a header with every request
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.fixed.insert(name.into(), value.into());
+        self
+    }
+
+    /// Ask `provider` for more headers before each request
+    pub fn with_header_provider(mut self, provider: Arc<dyn HeaderProvider>) -> Self {
+        self.headers.provider = Some(provider);
+        self
+    }
+
+    /// Set how malformed tool calls are handled (default: lenient)
+    pub fn with_validation(mut self, strictness: Strictness) -> Self {
+        self.validator = ResponseValidator::new(strictness);
+        self
+    }
+
     /// Get the model name
     pub fn model(&self) -> &str {
         &self.model
@@ -88,21 +152,17 @@ impl GenericChatClient for LlamaClient {
         let url = format!("{}/chat/completions", self.base_url);
 
         // Build llama.cpp-compatible request
-        let llama_request = LlamaChatRequest {
-            model: self.model.clone(),
-            messages: request.messages,
-            temperature: request.temperature,
-            max_tokens: request.max_tokens,
-            top_p: request.top_p,
-            tools: request.tools,
-        };
+        let llama_request = self.build_request(request, false)?;
 
         // Send request
-        let response = self
+        let request = self
             .http_client
             .post(&url)
             .header("Content-Type", "application/json")
-            .json(&llama_request)
+            .json(&llama_request);
+        let response = self
+            .headers
+            .apply(request)?
             .send()
             .await
             .map_err(|e| LlmError::NetworkError(e.to_string()))?;
@@ -123,38 +183,7 @@ impl GenericChatClient for LlamaClient {
             .await
             .map_err(|e| LlmError::ParseError(e.to_string()))?;
 
-        // Extract first choice
-        let choice = llama_response
-            .choices
-            .first()
-            .ok_or_else(|| LlmError::ParseError("No choices in response".to_string()))?;
-
-        // Convert llama.cpp tool_calls to our ToolCall type
-        let tool_calls = choice.message.tool_calls.as_ref().map(|calls| {
-            calls
-                .iter()
-                .map(|tc| super::super::types::ToolCall {
-                    id: tc.id.clone(),
-                    r#type: tc.r#type.clone(),
-                    function: super::super::types::FunctionCall {
-                        name: tc.function.name.clone(),
-                        arguments: tc.function.arguments.clone(),
-                    },
-                })
-                .collect()
-        });
-
-        Ok(ChatResponse {
-            content: choice.message.content.clone(),
-            model: llama_response.model.unwrap_or_else(|| self.model.clone()),
-            usage: llama_response.usage.map(|u| super::super::types::Usage {
-                prompt_tokens: u.prompt_tokens,
-                completion_tokens: u.completion_tokens,
-                total_tokens: u.total_tokens,
-            }),
-            finish_reason: choice.finish_reason.clone(),
-            tool_calls,
-        })
+        self.to_chat_response(llama_response)
     }
 
     async fn chat_stream(
@@ -165,30 +194,18 @@ impl GenericChatClient for LlamaClient {
         let url = format!("{}/v1/chat/completions", self.base_url);
 
         // Build llama.cpp-compatible request with streaming enabled
-        let llama_request = LlamaChatRequest {
-            model: self.model.clone(),
-            messages: request.messages.clone(),
-            temperature: request.temperature,
-            max_tokens: request.max_tokens,
-            top_p: request.top_p,
-            tools: request.tools.clone(),
-        };
+        let llama_request = self.build_request(request, true)?;
 
         // Send request with streaming
-        let response = self
+        let request = self
             .http_client
             .post(&url)
             .header("Content-Type", "application/json")
             .header("Accept", "text/event-stream")
-            .json(&serde_json::json!({
-                "model": llama_request.model,
-                "messages": llama_request.messages,
-                "temperature": llama_request.temperature,
-                "max_tokens": llama_request.max_tokens,
-                "top_p": llama_request.top_p,
-                "tools": llama_request.tools,
-                "stream": true,
-            }))
+            .json(&llama_request);
+        let response = self
+            .headers
+            .apply(request)?
             .send()
             .await
             .map_err(|e| LlmError::NetworkError(e.to_string()))?;
@@ -273,23 +290,7 @@ impl GenericChatClient for LlamaClient {
         }
 
         // Build response from accumulated streaming data
-        let tool_calls = if accumulated_tool_calls.is_empty() {
-            None
-        } else {
-            Some(
-                accumulated_tool_calls
-                    .iter()
-                    .map(|tc| super::super::types::ToolCall {
-                        id: tc.id.clone(),
-                        r#type: tc.r#type.clone(),
-                        function: super::super::types::FunctionCall {
-                            name: tc.function.name.clone(),
-                            arguments: tc.function.arguments.clone(),
-                        },
-                    })
-                    .collect(),
-            )
-        };
+        let normalized = self.normalize_stream(accumulated_tool_calls, finish_reason.as_deref())?;
 
         Ok(ChatResponse {
             content: full_content,
@@ -298,11 +299,76 @@ impl GenericChatClient for LlamaClient {
                 prompt_tokens: u.prompt_tokens,
                 completion_tokens: u.completion_tokens,
                 total_tokens: u.total_tokens,
+                reasoning_tokens: None,
+            }),
+            finish_reason: normalized.finish_reason,
+            tool_calls: normalized.tool_calls,
+            warnings: normalized.warnings,
+            provider: None,
+            failovers: Vec::new(),
+        })
+    }
+
+    fn provider_name(&self) -> &str {
+        "llama"
+    }
+}
+
+impl LlamaClient {
+    fn build_request(&self, request: ChatRequest, stream: bool) -> LlmResult<LlamaChatRequest> {
+        Ok(LlamaChatRequest {
+            model: self.model.clone(),
+            messages: wire_messages(&request.messages)?,
+            temperature: request.temperature,
+            max_tokens: request.max_tokens,
+            top_p: request.top_p,
+            tools: request.tools,
+            tool_choice: request.tool_choice,
+            seed: request.seed,
+            stop: request.stop,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            stream,
+        })
+    }
+
+    fn to_chat_response(&self, response: LlamaChatResponse) -> LlmResult<ChatResponse> {
+        // Extract first choice
+        let choice = response
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| LlmError::ParseError("No choices in response".to_string()))?;
+
+        let normalized = self
+            .validator
+            .normalize(choice.message.tool_calls, choice.finish_reason.as_deref())?;
+
+        Ok(ChatResponse {
+            content: choice.message.content.unwrap_or_default(),
+            model: response.model.unwrap_or_else(|| self.model.clone()),
+            usage: response.usage.map(|u| super::super::types::Usage {
+                prompt_tokens: u.prompt_tokens,
+                completion_tokens: u.completion_tokens,
+                total_tokens: u.total_tokens,
+                reasoning_tokens: None,
             }),
-            finish_reason,
-            tool_calls,
+            finish_reason: normalized.finish_reason,
+            tool_calls: normalized.tool_calls,
+            warnings: normalized.warnings,
+            provider: None,
+            failovers: Vec::new(),
         })
     }
+
+    fn normalize_stream(
+        &self,
+        tool_calls: Vec<LlamaToolCall>,
+        finish_reason: Option<&str>,
+    ) -> LlmResult<NormalizedResponse> {
+        let raw = tool_calls.into_iter().map(RawToolCall::from).collect();
+        self.validator.normalize(Some(raw), finish_reason)
+    }
 }
 
 fn accumulate_stream_tool_call(accumulated_tool_calls: &mut Vec<LlamaToolCall>, tool_call: &Value) {
@@ -329,12 +395,21 @@ fn accumulate_stream_tool_call(accumulated_tool_calls: &mut Vec<LlamaToolCall>,
     }
 }
 
+/// Messages in chat completions form; llama.cpp takes images for
+/// multimodal models loaded with a projector
+fn wire_messages(messages: &[ChatMessage]) -> LlmResult<Vec<Value>> {
+    messages
+        .iter()
+        .map(|message| chat_completions_message("llama", message))
+        .collect()
+}
+
 // llama.cpp request/response types (OpenAI-compatible)
 
 #[derive(Debug, Serialize)]
 struct LlamaChatRequest {
     model: String,
-    messages: Vec<super::super::types::ChatMessage>,
+    messages: Vec<Value>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     temperature: Option<f32>,
@@ -347,6 +422,24 @@ struct LlamaChatRequest {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     tools: Option<Vec<serde_json::Value>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<ToolChoice>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -365,12 +458,13 @@ struct Choice {
 #[derive(Debug, Deserialize)]
 struct Message {
     #[serde(default)]
-    content: String,
+    content: Option<String>,
 
-    #[serde(skip_serializing_if = "Option::is_none")]
-    tool_calls: Option<Vec<LlamaToolCall>>,
+    #[serde(default)]
+    tool_calls: Option<Vec<RawToolCall>>,
 }
 
+/// A streamed tool call being accumulated from deltas
 #[derive(Debug, Deserialize)]
 struct LlamaToolCall {
     id: String,
@@ -384,6 +478,19 @@ struct LlamaFunction {
     arguments: String,
 }
 
+impl From<LlamaToolCall> for RawToolCall {
+    fn from(call: LlamaToolCall) -> Self {
+        RawToolCall {
+            id: Some(call.id),
+            r#type: Some(call.r#type),
+            function: Some(RawFunctionCall {
+                name: Some(call.function.name),
+                arguments: Some(Value::String(call.function.arguments)),
+            }),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct UsageInfo {
     prompt_tokens: u32,
@@ -445,4 +552,90 @@ mod tests {
         accumulate_stream_tool_call(&mut calls, &delta_only);
         assert!(calls.is_empty());
     }
+
+    #[test]
+    fn chat_response_is_validated() {
+        let body: LlamaChatResponse = serde_json::from_value(serde_json::json!({
+            "choices": [{
+                "message": {
+                    "content": null,
+                    "tool_calls": [
+                        {"id": "call_1", "type": "function",
+                         "function": {"name": "", "arguments": "{}"}},
+                        {"id": "call_1", "type": "function",
+                         "function": {"name": "search_items", "arguments": "{\"query\": "}},
+                    ]
+                },
+                "finish_reason": "tool_calls"
+            }]
+        }))
+        .unwrap();
+
+        let strict = LlamaClient::localhost().with_validation(Strictness::Strict);
+        let err = strict.to_chat_response(body).unwrap_err();
+        assert!(matches!(err, LlmError::ParseError(ref d) if d.contains("empty function name")));
+
+        let body: LlamaChatResponse = serde_json::from_value(serde_json::json!({
+            "choices": [{
+                "message": {"tool_calls": [
+                    {"function": {"name": "search_items", "arguments": "{\"query\": \"key\"}"}},
+                    {"id": "x", "function": {"name": "search_items", "arguments": "not json"}},
+                ]},
+                "finish_reason": null
+            }]
+        }))
+        .unwrap();
+        let response = LlamaClient::localhost().to_chat_response(body).unwrap();
+        let calls = response.tool_calls.unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id, "call_0");
+        assert_eq!(calls[0].r#type, "function");
+        assert_eq!(response.finish_reason.as_deref(), Some("tool_calls"));
+        assert_eq!(response.warnings.len(), 2);
+    }
+
+    #[test]
+    fn streamed_tool_calls_are_validated() {
+        // The stream ended before the first call's arguments were closed
+        let truncated = || {
+            let mut calls = Vec::<LlamaToolCall>::new();
+            for delta in [
+                serde_json::json!({"index": 0, "id": "call_1", "type": "function",
+                    "function": {"name": "search_items", "arguments": "{\"query\""}}),
+                serde_json::json!({"index": 0, "function": {"arguments": ": \"key\""}}),
+                serde_json::json!({"index": 1, "id": "call_2", "type": "function",
+                    "function": {"name": "fetch", "arguments": ""}}),
+            ] {
+                accumulate_stream_tool_call(&mut calls, &delta);
+            }
+            calls
+        };
+
+        let strict = LlamaClient::localhost().with_validation(Strictness::Strict);
+        let err = strict
+            .normalize_stream(truncated(), Some("tool_calls"))
+            .unwrap_err();
+        let LlmError::ParseError(details) = err else {
+            panic!("expected ParseError");
+        };
+        assert!(details.contains("not valid JSON"));
+        assert!(details.contains("empty arguments"));
+
+        // Lenient drops the truncated call and keeps the rest
+        let normalized = LlamaClient::localhost()
+            .normalize_stream(truncated(), Some("tool_calls"))
+            .unwrap();
+        let calls = normalized.tool_calls.unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id, "call_2");
+        assert_eq!(calls[0].function.arguments, "{}");
+        assert_eq!(normalized.warnings.len(), 2);
+
+        // No calls and a tool_calls finish collapses to a plain stop
+        let normalized = LlamaClient::localhost()
+            .normalize_stream(Vec::new(), Some("tool_calls"))
+            .unwrap();
+        assert!(normalized.tool_calls.is_none());
+        assert_eq!(normalized.finish_reason.as_deref(), Some("stop"));
+    }
 }
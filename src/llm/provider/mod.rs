@@ -1,5 +1,62 @@
+pub mod anthropic;
+pub mod gemini;
+mod http;
 pub mod llama;
+pub mod ollama;
 pub mod openai;
 
+pub use anthropic::ClaudeClient;
+pub use gemini::GeminiClient;
+pub use http::HeaderProvider;
 pub use llama::LlamaClient;
-pub use openai::OpenAIClient;
+pub use ollama::OllamaClient;
+pub use openai::{OpenAIApi, OpenAIClient};
+
+use super::types::{ChatMessage, ContentPart, MessageContent, Role};
+use super::{LlmError, LlmResult};
+use serde_json::Value;
+use std::borrow::Cow;
+
+/// The text of a message that may not carry images; providers only take
+/// images in user turns
+fn text_only<'m>(provider: &str, message: &'m ChatMessage) -> LlmResult<Cow<'m, str>> {
+    if message.content.has_images() {
+        let role = match message.role {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+            Role::Tool => "tool",
+        };
+        return Err(LlmError::InvalidRequest(format!(
+            "{} does not accept images in {} messages",
+            provider, role
+        )));
+    }
+    Ok(message.content.text())
+}
+
+/// `message` for an OpenAI-style chat completions body, with images as
+/// `image_url` content blocks
+fn chat_completions_message(provider: &str, message: &ChatMessage) -> LlmResult<Value> {
+    let mut value =
+        serde_json::to_value(message).map_err(|e| LlmError::InvalidRequest(e.to_string()))?;
+    if let MessageContent::Parts(parts) = &message.content {
+        value["content"] = if message.role == Role::User && message.content.has_images() {
+            parts
+                .iter()
+                .map(|part| match part {
+                    ContentPart::Text { text } => {
+                        serde_json::json!({ "type": "text", "text": text })
+                    }
+                    image => serde_json::json!({
+                        "type": "image_url",
+                        "image_url": { "url": image.image_data_url() },
+                    }),
+                })
+                .collect()
+        } else {
+            Value::String(text_only(provider, message)?.into_owned())
+        };
+    }
+    Ok(value)
+}
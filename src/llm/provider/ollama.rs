@@ -0,0 +1,580 @@
+use async_trait::async_trait;
+use futures::stream::StreamExt;
+use reqwest::Client as HttpClient;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+
+use super::text_only;
+use crate::config::OllamaConfig;
+use crate::llm::types::{ChatMessage, ContentPart, Role, Usage};
+use crate::llm::validation::{RawFunctionCall, RawToolCall, ResponseValidator, Strictness};
+use crate::llm::{ChatRequest, ChatResponse, GenericChatClient, LlmError, LlmResult};
+
+/// Default address of a local Ollama server
+pub const OLLAMA_DEFAULT_URL: &str = "http://localhost:11434";
+const DEFAULT_MODEL: &str = "llama3.2";
+
+/// Ollama client using the native `/api/chat` endpoint
+///
+/// Unlike the OpenAI shim, the native API takes model settings such as
+/// `num_ctx` in `options`, keeps the model loaded for `keep_alive`, and
+/// streams newline-delimited JSON. Images are sent base64-encoded in the
+/// message's `images`; Ollama doesn't fetch URLs. Tool calls carry their
+/// arguments as an object and no id, so the client gives each one an id.
+pub struct OllamaClient {
+    base_url: String,
+    model: String,
+    keep_alive: Option<Value>,
+    options: Map<String, Value>,
+    http_client: HttpClient,
+    validator: ResponseValidator,
+}
+
+impl OllamaClient {
+    /// Create a new Ollama client
+    ///
+    /// # Arguments
+    /// * `base_url` - Base URL of the Ollama server (e.g., "http://localhost:11434")
+    /// * `model` - Model to run, as pulled (e.g., "llama3.2" or "qwen2.5:7b")
+    pub fn new(base_url: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            model: model.into(),
+            keep_alive: None,
+            options: Map::new(),
+            http_client: HttpClient::new(),
+            validator: ResponseValidator::default(),
+        }
+    }
+
+    /// Create a client pointing to localhost:11434 (default Ollama port)
+    pub fn localhost(model: impl Into<String>) -> Self {
+        Self::new(OLLAMA_DEFAULT_URL, model)
+    }
+
+    /// Build a client from `[llm.ollama]`
+    pub fn from_config(config: &OllamaConfig) -> Self {
+        let mut client = Self::new(
+            config.base_url.as_deref().unwrap_or(OLLAMA_DEFAULT_URL),
+            config.model.as_deref().unwrap_or(DEFAULT_MODEL),
+        )
+        .with_options(config.options.clone());
+        if let Some(keep_alive) = &config.keep_alive {
+            client = client.with_keep_alive(keep_alive.clone());
+        }
+        client
+    }
+
+    /// Use a custom HTTP client, e.g. for TLS or timeouts
+    pub fn with_http_client(mut self, http_client: HttpClient) -> Self {
+        self.http_client = http_client;
+        self
+    }
+
+    /// How long the model stays loaded after a request, as a duration
+    /// (`"10m"`) or seconds; negative keeps it loaded, `0` unloads it
+    pub fn with_keep_alive(mut self, keep_alive: impl Into<Value>) -> Self {
+        self.keep_alive = Some(keep_alive.into());
+        self
+    }
+
+    /// Set a model option sent with every request, e.g. `num_ctx`
+    ///
+    /// A request's own sampling parameters, e.g. temperature or
+    /// max_tokens (`num_predict`), take precedence.
+    pub fn with_option(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.options.insert(key.into(), value.into());
+        self
+    }
+
+    /// Add several model options; see [`with_option`](Self::with_option)
+    pub fn with_options(mut self, options: Map<String, Value>) -> Self {
+        self.options.extend(options);
+        self
+    }
+
+    /// Set how malformed tool calls are handled (default: lenient)
+    pub fn with_validation(mut self, strictness: Strictness) -> Self {
+        self.validator = ResponseValidator::new(strictness);
+        self
+    }
+
+    /// Get the model name
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Get the provider name
+    pub fn provider(&self) -> &str {
+        "ollama"
+    }
+
+    fn build_request(&self, request: ChatRequest, stream: bool) -> LlmResult<OllamaRequest> {
+        // Ollama has no tool_choice; a request that rules out calls offers
+        // no tools instead
+        let offers_tools = request.offers_tools();
+        let mut options = self.options.clone();
+        let overrides = [
+            ("temperature", request.temperature.map(Value::from)),
+            ("top_p", request.top_p.map(Value::from)),
+            ("num_predict", request.max_tokens.map(Value::from)),
+            ("seed", request.seed.map(Value::from)),
+            ("stop", request.stop.map(Value::from)),
+            (
+                "frequency_penalty",
+                request.frequency_penalty.map(Value::from),
+            ),
+            (
+                "presence_penalty",
+                request.presence_penalty.map(Value::from),
+            ),
+        ];
+        for (key, value) in overrides {
+            if let Some(value) = value {
+                options.insert(key.to_string(), value);
+            }
+        }
+
+        Ok(OllamaRequest {
+            model: self.model.clone(),
+            messages: translate_messages(request.messages)?,
+            tools: request.tools.filter(|_| offers_tools),
+            stream,
+            options: (!options.is_empty()).then_some(options),
+            keep_alive: self.keep_alive.clone(),
+        })
+    }
+
+    async fn send(&self, body: &OllamaRequest) -> LlmResult<reqwest::Response> {
+        let response = self
+            .http_client
+            .post(format!("{}/api/chat", self.base_url))
+            .header("Content-Type", "application/json")
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| LlmError::NetworkError(e.to_string()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            // Ollama answers `{"error": "..."}`
+            let message = serde_json::from_str::<Value>(&error_text)
+                .ok()
+                .and_then(|v| v.get("error")?.as_str().map(str::to_string))
+                .unwrap_or(error_text);
+            return Err(match status.as_u16() {
+                429 => LlmError::RateLimitExceeded,
+                _ => LlmError::ApiError(format!("Status {}: {}", status, message)),
+            });
+        }
+        Ok(response)
+    }
+
+    /// Validate what was received
+    fn finish(&self, state: StreamState) -> LlmResult<ChatResponse> {
+        let finish_reason = state.finish_reason();
+        let normalized = self
+            .validator
+            .normalize(Some(state.tool_calls), finish_reason)?;
+
+        Ok(ChatResponse {
+            content: state.content,
+            model: state.model.unwrap_or_else(|| self.model.clone()),
+            usage: state.usage,
+            finish_reason: normalized.finish_reason,
+            tool_calls: normalized.tool_calls,
+            warnings: normalized.warnings,
+            provider: None,
+            failovers: Vec::new(),
+        })
+    }
+}
+
+#[async_trait]
+impl GenericChatClient for OllamaClient {
+    async fn chat(&self, request: ChatRequest) -> LlmResult<ChatResponse> {
+        let body = self.build_request(request, false)?;
+        let response: OllamaChunk = self
+            .send(&body)
+            .await?
+            .json()
+            .await
+            .map_err(|e| LlmError::ParseError(e.to_string()))?;
+
+        let mut state = StreamState::default();
+        state.apply(response)?;
+        self.finish(state)
+    }
+
+    async fn chat_stream(
+        &self,
+        request: ChatRequest,
+        tx: mpsc::Sender<String>,
+    ) -> LlmResult<ChatResponse> {
+        let body = self.build_request(request, true)?;
+        let response = self.send(&body).await?;
+
+        let mut state = StreamState::default();
+        let mut lines = LineSplitter::default();
+        let mut stream = response.bytes_stream();
+        loop {
+            let chunk = stream.next().await;
+            let complete = match &chunk {
+                Some(bytes) => {
+                    let bytes = bytes
+                        .as_ref()
+                        .map_err(|e| LlmError::NetworkError(e.to_string()))?;
+                    lines.push(bytes)
+                }
+                // The last line may lack its newline
+                None => lines.finish(),
+            };
+            for line in complete {
+                let chunk: OllamaChunk = serde_json::from_slice(&line)
+                    .map_err(|e| LlmError::ParseError(e.to_string()))?;
+                if let Some(text) = state.apply(chunk)? {
+                    let _ = tx.send(text).await;
+                }
+            }
+            if chunk.is_none() {
+                break;
+            }
+        }
+
+        if !state.done {
+            return Err(LlmError::NetworkError(
+                "Ollama stream ended before the final chunk".to_string(),
+            ));
+        }
+        self.finish(state)
+    }
+
+    fn provider_name(&self) -> &str {
+        "ollama"
+    }
+}
+
+/// Messages in Ollama's shape: text `content`, base64 `images`, tool calls
+/// with object arguments, and tool results naming their function
+///
+/// Fails on image URLs, which Ollama doesn't fetch, and on images outside
+/// user turns.
+fn translate_messages(messages: Vec<ChatMessage>) -> LlmResult<Vec<Value>> {
+    // Tool results only carry the call id; Ollama wants the function name
+    let mut call_names: HashMap<String, String> = HashMap::new();
+    let mut translated = Vec::with_capacity(messages.len());
+
+    for message in messages {
+        let value = match message.role {
+            Role::System => serde_json::json!({
+                "role": "system",
+                "content": text_only("ollama", &message)?,
+            }),
+            Role::User => {
+                let mut images = Vec::new();
+                for part in message.content.parts().iter() {
+                    match part {
+                        ContentPart::Text { .. } => {}
+                        ContentPart::ImageBase64 { data, .. } => images.push(data.clone()),
+                        ContentPart::ImageUrl { url } => {
+                            return Err(LlmError::InvalidRequest(format!(
+                                "ollama does not fetch image URLs; send {} inline with ContentPart::image_base64",
+                                url
+                            )))
+                        }
+                    }
+                }
+                let mut value = serde_json::json!({
+                    "role": "user",
+                    "content": message.content.text(),
+                });
+                if !images.is_empty() {
+                    value["images"] = images.into();
+                }
+                value
+            }
+            Role::Assistant => {
+                let mut value = serde_json::json!({
+                    "role": "assistant",
+                    "content": text_only("ollama", &message)?,
+                });
+                if let Some(calls) = &message.tool_calls {
+                    value["tool_calls"] = calls
+                        .iter()
+                        .map(|call| {
+                            call_names.insert(call.id.clone(), call.function.name.clone());
+                            let arguments = serde_json::from_str::<Value>(&call.function.arguments)
+                                .ok()
+                                .filter(Value::is_object)
+                                .unwrap_or_else(|| serde_json::json!({}));
+                            serde_json::json!({
+                                "function": { "name": call.function.name, "arguments": arguments },
+                            })
+                        })
+                        .collect();
+                }
+                value
+            }
+            Role::Tool => {
+                let mut value = serde_json::json!({
+                    "role": "tool",
+                    "content": text_only("ollama", &message)?,
+                });
+                if let Some(name) = message
+                    .tool_call_id
+                    .as_ref()
+                    .and_then(|id| call_names.get(id))
+                {
+                    value["tool_name"] = Value::String(name.clone());
+                }
+                value
+            }
+        };
+        translated.push(value);
+    }
+
+    Ok(translated)
+}
+
+/// Splits a newline-delimited stream into complete lines as their bytes
+/// arrive
+#[derive(Debug, Default)]
+struct LineSplitter {
+    buffer: Vec<u8>,
+}
+
+impl LineSplitter {
+    fn push(&mut self, bytes: &[u8]) -> Vec<Vec<u8>> {
+        self.buffer.extend_from_slice(bytes);
+        let Some(end) = self.buffer.iter().rposition(|&b| b == b'\n') else {
+            return Vec::new();
+        };
+        let complete: Vec<u8> = self.buffer.drain(..=end).collect();
+        split_lines(&complete)
+    }
+
+    /// Whatever is left once the stream ends
+    fn finish(&mut self) -> Vec<Vec<u8>> {
+        split_lines(&std::mem::take(&mut self.buffer))
+    }
+}
+
+fn split_lines(bytes: &[u8]) -> Vec<Vec<u8>> {
+    bytes
+        .split(|&b| b == b'\n')
+        .filter(|line| !line.trim_ascii().is_empty())
+        .map(<[u8]>::to_vec)
+        .collect()
+}
+
+/// Accumulates a response from one or more `/api/chat` chunks
+#[derive(Debug, Default)]
+struct StreamState {
+    content: String,
+    tool_calls: Vec<RawToolCall>,
+    model: Option<String>,
+    done: bool,
+    done_reason: Option<String>,
+    usage: Option<Usage>,
+}
+
+impl StreamState {
+    /// Apply one chunk, returning text to forward to the caller
+    fn apply(&mut self, chunk: OllamaChunk) -> LlmResult<Option<String>> {
+        if let Some(error) = chunk.error {
+            return Err(LlmError::ApiError(format!("Stream error: {}", error)));
+        }
+        if let Some(model) = chunk.model {
+            self.model = Some(model);
+        }
+
+        let mut text = String::new();
+        if let Some(message) = chunk.message {
+            text = message.content;
+            for call in message.tool_calls {
+                let function = call.function.unwrap_or_default();
+                self.tool_calls.push(RawToolCall {
+                    id: Some(
+                        call.id
+                            .unwrap_or_else(|| format!("call_{}", uuid::Uuid::new_v4().simple())),
+                    ),
+                    r#type: Some("function".to_string()),
+                    function: Some(RawFunctionCall {
+                        name: function.name,
+                        // Ollama sends an object; the validator expects the
+                        // string OpenAI sends
+                        arguments: Some(match function.arguments {
+                            Some(Value::String(arguments)) => Value::String(arguments),
+                            arguments => Value::String(
+                                arguments
+                                    .unwrap_or_else(|| serde_json::json!({}))
+                                    .to_string(),
+                            ),
+                        }),
+                    }),
+                });
+            }
+        }
+
+        if chunk.done {
+            self.done = true;
+            self.done_reason = chunk.done_reason;
+            if chunk.prompt_eval_count.is_some() || chunk.eval_count.is_some() {
+                let prompt_tokens = chunk.prompt_eval_count.unwrap_or(0);
+                let completion_tokens = chunk.eval_count.unwrap_or(0);
+                self.usage = Some(Usage {
+                    prompt_tokens,
+                    completion_tokens,
+                    total_tokens: prompt_tokens + completion_tokens,
+                    reasoning_tokens: None,
+                });
+            }
+        }
+
+        self.content.push_str(&text);
+        Ok((!text.is_empty()).then_some(text))
+    }
+
+    /// Ollama's `done_reason` in the validator's terms; it says `stop` even
+    /// when it calls tools
+    fn finish_reason(&self) -> Option<&'static str> {
+        let reason = self.done_reason.as_deref()?;
+        Some(match reason {
+            "stop" if !self.tool_calls.is_empty() => "tool_calls",
+            "stop" => "stop",
+            "length" => "length",
+            // `load` and `unload` answer requests without messages
+            _ => "other",
+        })
+    }
+}
+
+// Ollama-specific request/response types
+
+#[derive(Debug, Serialize)]
+struct OllamaRequest {
+    model: String,
+    messages: Vec<Value>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<Value>>,
+
+    stream: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<Map<String, Value>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<Value>,
+}
+
+/// A non-streaming response, or one line of a stream
+#[derive(Debug, Deserialize)]
+struct OllamaChunk {
+    #[serde(default)]
+    model: Option<String>,
+
+    #[serde(default)]
+    message: Option<OllamaMessage>,
+
+    #[serde(default)]
+    done: bool,
+
+    #[serde(default)]
+    done_reason: Option<String>,
+
+    #[serde(default)]
+    prompt_eval_count: Option<u32>,
+
+    #[serde(default)]
+    eval_count: Option<u32>,
+
+    #[serde(default)]
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaMessage {
+    #[serde(default)]
+    content: String,
+
+    #[serde(default)]
+    tool_calls: Vec<OllamaToolCall>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaToolCall {
+    #[serde(default)]
+    id: Option<String>,
+
+    #[serde(default)]
+    function: Option<RawFunctionCall>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::types::{FunctionCall, ToolCall};
+
+    #[test]
+    fn lines_split_across_chunks() {
+        let mut lines = LineSplitter::default();
+        assert!(lines.push(b"{\"a\":").is_empty());
+        assert_eq!(
+            lines.push(b"1}\n\n{\"b\":2}\n{\"c\""),
+            vec![b"{\"a\":1}".to_vec(), b"{\"b\":2}".to_vec()]
+        );
+        assert!(lines.push(b":3}").is_empty());
+        assert_eq!(lines.finish(), vec![b"{\"c\":3}".to_vec()]);
+        assert!(lines.finish().is_empty());
+    }
+
+    #[test]
+    fn tool_turns_are_translated() {
+        let call = ToolCall {
+            id: "call_1".to_string(),
+            r#type: "function".to_string(),
+            function: FunctionCall {
+                name: "get_weather".to_string(),
+                arguments: "{\"city\":\"Oslo\"}".to_string(),
+            },
+        };
+        let messages = translate_messages(vec![
+            ChatMessage::user("Weather?"),
+            ChatMessage::assistant_with_tool_calls("", vec![call]),
+            ChatMessage::tool_result("call_1", "{\"temp\":3}"),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            messages[1]["tool_calls"],
+            serde_json::json!([
+                { "function": { "name": "get_weather", "arguments": { "city": "Oslo" } } }
+            ])
+        );
+        assert_eq!(
+            messages[2],
+            serde_json::json!({ "role": "tool", "content": "{\"temp\":3}", "tool_name": "get_weather" })
+        );
+    }
+
+    #[test]
+    fn request_options_override_configured_ones() {
+        let client = OllamaClient::localhost("llama3.2")
+            .with_option("num_ctx", 8192)
+            .with_option("temperature", 0.1);
+        let request = ChatRequest::new(vec![ChatMessage::user("Hi")])
+            .with_temperature(0.5)
+            .with_max_tokens(64);
+
+        let body = client.build_request(request, false).unwrap();
+        let options = body.options.unwrap();
+        assert_eq!(options["num_ctx"], 8192);
+        assert_eq!(options["temperature"], 0.5);
+        assert_eq!(options["num_predict"], 64);
+        assert!(body.keep_alive.is_none());
+    }
+}
@@ -1,19 +1,48 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
 use async_trait::async_trait;
+use futures::stream::StreamExt;
 use reqwest::Client as HttpClient;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use tokio::sync::mpsc;
 
+use crate::config::OpenAIConfig;
+use crate::error::{ConfigError, ConfigErrorCode};
+use crate::llm::batch::{self, BatchChatClient, BatchHandle, BatchStatus};
+use crate::llm::effort::{self, AppliedEffort, Effort, EffortMapping};
+use crate::llm::types::{self, ChatMessage, MessageContent, Role, Usage};
+use crate::llm::validation::{RawFunctionCall, RawToolCall, ResponseValidator, Strictness};
 use crate::llm::GenericChatClient;
 
 use super::super::{ChatRequest, ChatResponse, LlmError, LlmResult};
+use super::http::{self, HeaderProvider, RequestHeaders, Transport};
+use super::{chat_completions_message, text_only};
+
+const OPENAI_API_URL: &str = "https://api.openai.com/v1";
+
+/// Which OpenAI endpoint a client talks to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OpenAIApi {
+    /// `/chat/completions`
+    #[default]
+    ChatCompletions,
 
-const OPENAI_API_URL: &str = "https://api.openai.com/v1/chat/completions";
+    /// `/responses`: system messages become `instructions`, tool calls and
+    /// results become `function_call`/`function_call_output` input items
+    Responses,
+}
 
 /// OpenAI chat client
 pub struct OpenAIClient {
     api_key: String,
     model: String,
+    base_url: String,
+    api: OpenAIApi,
     http_client: HttpClient,
+    headers: RequestHeaders,
+    validator: ResponseValidator,
 }
 
 impl OpenAIClient {
@@ -27,8 +56,104 @@ impl OpenAIClient {
         Self {
             api_key: api_key.into(),
             model: model.into(),
+            base_url: OPENAI_API_URL.to_string(),
+            api: OpenAIApi::default(),
             http_client: HttpClient::new(),
+            headers: RequestHeaders::default(),
+            validator: ResponseValidator::default(),
+        }
+    }
+
+    /// Create a client for the Responses API
+    pub fn responses_api(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self::with_model(api_key, model).with_api(OpenAIApi::Responses)
+    }
+
+    /// Create a client from the `[llm.openai]` section, with its headers,
+    /// proxy and timeouts
+    ///
+    /// `api_key` falls back to the `OPENAI_API_KEY` environment variable,
+    /// and `organization` is sent as the `OpenAI-Organization` header.
+    pub fn from_config(config: &OpenAIConfig, model: Option<&str>) -> Result<Self, ConfigError> {
+        let api_key = config
+            .api_key
+            .clone()
+            .or_else(|| std::env::var("OPENAI_API_KEY").ok())
+            .ok_or_else(|| ConfigError {
+                code: ConfigErrorCode::MissingRequiredField,
+                message: "No OpenAI API key in config or OPENAI_API_KEY".to_string(),
+                field: Some("llm.openai.api_key".to_string()),
+            })?;
+        http::header_map(&config.default_headers).map_err(|message| ConfigError {
+            code: ConfigErrorCode::InvalidValue,
+            message,
+            field: Some("llm.openai.default_headers".to_string()),
+        })?;
+        let transport = Transport {
+            proxy: config.proxy.as_deref(),
+            connect_timeout_ms: config.connect_timeout_ms,
+            request_timeout_ms: config.request_timeout_ms,
+            insecure: false,
+        };
+
+        let mut client = match model {
+            Some(model) => Self::with_model(api_key, model),
+            None => Self::new(api_key),
         }
+        .with_http_client(transport.client("llm.openai")?);
+        if let Some(base_url) = &config.api_base {
+            client = client.with_base_url(base_url);
+        }
+        if let Some(organization) = &config.organization {
+            client = client.with_header("OpenAI-Organization", organization);
+        }
+        Ok(client.with_default_headers(config.default_headers.clone()))
+    }
+
+    /// Send requests through `http_client`, e.g. one with a proxy or TLS
+    /// settings
+    pub fn with_http_client(mut self, http_client: HttpClient) -> Self {
+        self.http_client = http_client;
+        self
+    }
+
+    /// Send `headers` with every request
+    pub fn with_default_headers(mut self, headers: HashMap<String, String>) -> Self {
+        self.headers.fixed.extend(headers);
+        self
+    }
+
+    /// Send a header with every request, replacing the `Authorization`
+    /// bearer if named so
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.fixed.insert(name.into(), value.into());
+        self
+    }
+
+    /// Ask `provider` for more headers before each request, e.g. a
+    /// refreshed token
+    pub fn with_header_provider(mut self, provider: Arc<dyn HeaderProvider>) -> Self {
+        self.headers.provider = Some(provider);
+        self
+    }
+
+    /// Choose the endpoint (default: chat completions)
+    pub fn with_api(mut self, api: OpenAIApi) -> Self {
+        self.api = api;
+        self
+    }
+
+    /// Point at a different endpoint, e.g. a proxy or an Azure deployment
+    /// (default: `https://api.openai.com/v1`)
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Set how malformed tool calls are handled (default: lenient)
+    pub fn with_validation(mut self, strictness: Strictness) -> Self {
+        self.validator = ResponseValidator::new(strictness);
+        self
     }
 
     /// Get the model name
@@ -40,28 +165,170 @@ impl OpenAIClient {
     pub fn provider(&self) -> &str {
         "openai"
     }
-}
 
-#[async_trait]
-impl GenericChatClient for OpenAIClient {
-    async fn chat(&self, request: ChatRequest) -> LlmResult<ChatResponse> {
-        // Build OpenAI API request
-        let openai_request = OpenAIChatRequest {
+    pub fn api(&self) -> OpenAIApi {
+        self.api
+    }
+
+    /// Whether the configured model is a reasoning model: it accepts
+    /// `reasoning_effort` and rejects sampling parameters
+    pub fn is_reasoning_model(&self) -> bool {
+        let model = self.model.to_ascii_lowercase();
+        ["o1", "o3", "o4", "gpt-5"]
+            .iter()
+            .any(|prefix| model.starts_with(prefix))
+    }
+
+    fn build_request(&self, request: ChatRequest) -> LlmResult<OpenAIChatRequest> {
+        // Reasoning models reject `temperature`/`top_p`, penalties and stop
+        // sequences, and take `max_completion_tokens` instead of `max_tokens`
+        let reasoning = self.is_reasoning_model();
+        let messages = request
+            .messages
+            .iter()
+            .map(|message| chat_completions_message("openai", message))
+            .collect::<LlmResult<_>>()?;
+        Ok(OpenAIChatRequest {
             model: self.model.clone(),
-            messages: request.messages,
-            temperature: request.temperature,
-            max_tokens: request.max_tokens,
-            top_p: request.top_p,
+            messages,
+            temperature: request.temperature.filter(|_| !reasoning),
+            max_tokens: request.max_tokens.filter(|_| !reasoning),
+            max_completion_tokens: request.max_tokens.filter(|_| reasoning),
+            top_p: request.top_p.filter(|_| !reasoning),
             tools: request.tools,
+            tool_choice: request.tool_choice,
+            reasoning_effort: request.reasoning_effort.filter(|_| reasoning),
+            seed: request.seed,
+            stop: request.stop.filter(|_| !reasoning),
+            frequency_penalty: request.frequency_penalty.filter(|_| !reasoning),
+            presence_penalty: request.presence_penalty.filter(|_| !reasoning),
+            stream: false,
+            stream_options: None,
+        })
+    }
+
+    fn to_chat_response(&self, response: OpenAIChatResponse) -> LlmResult<ChatResponse> {
+        // Extract first choice
+        let choice = response
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| LlmError::ParseError("No choices in response".to_string()))?;
+
+        let normalized = self
+            .validator
+            .normalize(choice.message.tool_calls, choice.finish_reason.as_deref())?;
+
+        Ok(ChatResponse {
+            content: choice.message.content.unwrap_or_default(),
+            model: response.model,
+            usage: response.usage.map(UsageInfo::into_usage),
+            finish_reason: normalized.finish_reason,
+            tool_calls: normalized.tool_calls,
+            warnings: normalized.warnings,
+            provider: None,
+            failovers: Vec::new(),
+        })
+    }
+
+    fn build_responses_request(&self, request: ChatRequest) -> LlmResult<ResponsesRequest> {
+        let reasoning = self.is_reasoning_model();
+        let (instructions, input) = translate_messages(request.messages)?;
+        Ok(ResponsesRequest {
+            model: self.model.clone(),
+            instructions,
+            input,
+            temperature: request.temperature.filter(|_| !reasoning),
+            top_p: request.top_p.filter(|_| !reasoning),
+            max_output_tokens: request.max_tokens,
+            tools: request
+                .tools
+                .map(|tools| tools.into_iter().map(translate_tool).collect()),
+            tool_choice: request.tool_choice,
+            reasoning: request
+                .reasoning_effort
+                .filter(|_| reasoning)
+                .map(|effort| ReasoningParams { effort }),
+        })
+    }
+
+    fn responses_to_chat_response(&self, response: ResponsesResponse) -> LlmResult<ChatResponse> {
+        if response.status.as_deref() == Some("failed") {
+            return Err(LlmError::ApiError(format!(
+                "Response failed: {}",
+                response.error.unwrap_or_default()
+            )));
+        }
+
+        let mut content = String::new();
+        let mut tool_calls = Vec::new();
+        for item in response.output {
+            match item {
+                OutputItem::Message { content: parts } => {
+                    for part in parts {
+                        match part {
+                            ContentPart::OutputText { text } => content.push_str(&text),
+                            ContentPart::Refusal { refusal } => content.push_str(&refusal),
+                            ContentPart::Other => {}
+                        }
+                    }
+                }
+                OutputItem::FunctionCall {
+                    call_id,
+                    name,
+                    arguments,
+                } => tool_calls.push(RawToolCall {
+                    id: call_id,
+                    r#type: Some("function".to_string()),
+                    function: Some(RawFunctionCall { name, arguments }),
+                }),
+                OutputItem::Other => {}
+            }
+        }
+
+        let incomplete = response.incomplete_details.and_then(|d| d.reason);
+        let finish_reason = match incomplete.as_deref() {
+            Some("max_output_tokens") => "length",
+            Some("content_filter") => "content_filter",
+            _ if !tool_calls.is_empty() => "tool_calls",
+            _ => "stop",
         };
+        let tool_calls = (!tool_calls.is_empty()).then_some(tool_calls);
+        let normalized = self.validator.normalize(tool_calls, Some(finish_reason))?;
+
+        Ok(ChatResponse {
+            content,
+            model: response.model,
+            usage: response.usage.map(|u| Usage {
+                prompt_tokens: u.input_tokens,
+                completion_tokens: u.output_tokens,
+                total_tokens: u.total_tokens,
+                reasoning_tokens: u.output_tokens_details.and_then(|d| d.reasoning_tokens),
+            }),
+            finish_reason: normalized.finish_reason,
+            tool_calls: normalized.tool_calls,
+            warnings: normalized.warnings,
+            provider: None,
+            failovers: Vec::new(),
+        })
+    }
 
-        // Send request
+    async fn send<T: Serialize>(&self, path: &str, body: &T) -> LlmResult<reqwest::Response> {
+        self.dispatch(
+            self.http_client
+                .post(format!("{}/{}", self.base_url, path))
+                .header("Content-Type", "application/json")
+                .json(body),
+        )
+        .await
+    }
+
+    /// Send `request` authenticated, failing on an error status
+    async fn dispatch(&self, request: reqwest::RequestBuilder) -> LlmResult<reqwest::Response> {
+        let request = request.header("Authorization", format!("Bearer {}", self.api_key));
         let response = self
-            .http_client
-            .post(OPENAI_API_URL)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&openai_request)
+            .headers
+            .apply(request)?
             .send()
             .await
             .map_err(|e| LlmError::NetworkError(e.to_string()))?;
@@ -70,63 +337,393 @@ impl GenericChatClient for OpenAIClient {
         let status = response.status();
         if !status.is_success() {
             let error_text = response.text().await.unwrap_or_default();
-            return Err(match status.as_u16() {
-                401 => LlmError::AuthenticationFailed(error_text),
-                429 => LlmError::RateLimitExceeded,
-                _ => LlmError::ApiError(format!("Status {}: {}", status, error_text)),
-            });
+            return Err(status_error(status.as_u16(), error_text));
+        }
+        Ok(response)
+    }
+
+    async fn get(&self, path: &str) -> LlmResult<reqwest::Response> {
+        self.dispatch(self.http_client.get(format!("{}/{}", self.base_url, path)))
+            .await
+    }
+
+    /// The endpoint batch requests go to
+    fn batch_endpoint(&self) -> &'static str {
+        match self.api {
+            OpenAIApi::ChatCompletions => "/v1/chat/completions",
+            OpenAIApi::Responses => "/v1/responses",
         }
+    }
+
+    /// Upload `jsonl` as a batch input file
+    async fn upload_batch_file(&self, jsonl: String) -> LlmResult<FileObject> {
+        let boundary = format!("batch-{}", uuid::Uuid::new_v4().simple());
+        let body = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"purpose\"\r\n\r\nbatch\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"batch.jsonl\"\r\n\
+             Content-Type: application/jsonl\r\n\r\n{jsonl}\r\n--{b}--\r\n",
+            b = boundary,
+            jsonl = jsonl
+        );
+        self.dispatch(
+            self.http_client
+                .post(format!("{}/files", self.base_url))
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={}", boundary),
+                )
+                .body(body),
+        )
+        .await?
+        .json()
+        .await
+        .map_err(|e| LlmError::ParseError(e.to_string()))
+    }
+
+    async fn retrieve_batch(&self, handle: &BatchHandle) -> LlmResult<BatchObject> {
+        self.get(&format!("batches/{}", handle.batch_id))
+            .await?
+            .json()
+            .await
+            .map_err(|e| LlmError::ParseError(e.to_string()))
+    }
+
+    /// Read the result lines of an output or error file by custom ID
+    async fn read_batch_file(
+        &self,
+        file_id: &str,
+        results: &mut HashMap<String, LlmResult<ChatResponse>>,
+    ) -> LlmResult<()> {
+        let content = self
+            .get(&format!("files/{}/content", file_id))
+            .await?
+            .text()
+            .await
+            .map_err(|e| LlmError::NetworkError(e.to_string()))?;
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            let line: BatchOutputLine =
+                serde_json::from_str(line).map_err(|e| LlmError::ParseError(e.to_string()))?;
+            let result = match (line.error, line.response) {
+                (Some(error), _) => Err(LlmError::ApiError(match error.code {
+                    Some(code) => format!("{}: {}", code, error.message),
+                    None => error.message,
+                })),
+                (None, Some(response)) if response.status_code == 200 => {
+                    self.batch_response(response.body)
+                }
+                (None, Some(response)) => Err(status_error(
+                    response.status_code,
+                    response.body.to_string(),
+                )),
+                (None, None) => Err(LlmError::ParseError(format!(
+                    "batch result for {} has neither a response nor an error",
+                    line.custom_id
+                ))),
+            };
+            results.insert(line.custom_id, result);
+        }
+        Ok(())
+    }
+
+    fn batch_response(&self, body: Value) -> LlmResult<ChatResponse> {
+        let parse = |e: serde_json::Error| LlmError::ParseError(e.to_string());
+        match self.api {
+            OpenAIApi::ChatCompletions => {
+                self.to_chat_response(serde_json::from_value(body).map_err(parse)?)
+            }
+            OpenAIApi::Responses => {
+                self.responses_to_chat_response(serde_json::from_value(body).map_err(parse)?)
+            }
+        }
+    }
+}
+
+/// The error for a response with `status`
+pub(crate) fn status_error(status: u16, body: String) -> LlmError {
+    match status {
+        401 => LlmError::AuthenticationFailed(body),
+        429 => LlmError::RateLimitExceeded,
+        _ => LlmError::ApiError(format!(
+            "Status {}: {}",
+            reqwest::StatusCode::from_u16(status)
+                .map(|status| status.to_string())
+                .unwrap_or_else(|_| status.to_string()),
+            body
+        )),
+    }
+}
+
+/// Split system messages into `instructions` and turn the rest into
+/// Responses API input items
+///
+/// Fails if a message other than a user turn carries an image.
+fn translate_messages(messages: Vec<ChatMessage>) -> LlmResult<(Option<String>, Vec<Value>)> {
+    let mut instructions = Vec::new();
+    let mut input = Vec::new();
+
+    for message in messages {
+        match message.role {
+            Role::System => instructions.push(text_only("openai", &message)?.into_owned()),
+            Role::User => {
+                input.push(json!({ "role": "user", "content": input_content(&message.content) }))
+            }
+            Role::Tool => input.push(json!({
+                "type": "function_call_output",
+                "call_id": message.tool_call_id.clone().unwrap_or_default(),
+                "output": text_only("openai", &message)?,
+            })),
+            Role::Assistant => {
+                let text = text_only("openai", &message)?;
+                if !text.is_empty() {
+                    input.push(json!({ "role": "assistant", "content": text }));
+                }
+                for call in message.tool_calls.unwrap_or_default() {
+                    input.push(json!({
+                        "type": "function_call",
+                        "call_id": call.id,
+                        "name": call.function.name,
+                        "arguments": call.function.arguments,
+                    }));
+                }
+            }
+        }
+    }
+
+    let instructions = (!instructions.is_empty()).then(|| instructions.join("\n\n"));
+    Ok((instructions, input))
+}
+
+/// A user turn's content: its text, or `input_text`/`input_image` items if
+/// it has images
+fn input_content(content: &MessageContent) -> Value {
+    if !content.has_images() {
+        return Value::String(content.text().into_owned());
+    }
+    content
+        .parts()
+        .iter()
+        .map(|part| match part {
+            types::ContentPart::Text { text } => json!({ "type": "input_text", "text": text }),
+            image => json!({ "type": "input_image", "image_url": image.image_data_url() }),
+        })
+        .collect()
+}
+
+/// Chat-completions tool definitions nest the function; the Responses API
+/// doesn't
+fn translate_tool(tool: Value) -> Value {
+    let Some(function) = tool.get("function") else {
+        return tool;
+    };
+    let mut translated = json!({
+        "type": "function",
+        "name": function.get("name").cloned().unwrap_or_default(),
+        "parameters": function
+            .get("parameters")
+            .cloned()
+            .unwrap_or_else(|| json!({ "type": "object" })),
+    });
+    if let Some(description) = function.get("description") {
+        translated["description"] = description.clone();
+    }
+    translated
+}
+
+#[async_trait]
+impl GenericChatClient for OpenAIClient {
+    async fn chat(&self, request: ChatRequest) -> LlmResult<ChatResponse> {
+        if self.api == OpenAIApi::Responses {
+            let body = self.build_responses_request(request)?;
+            let response: ResponsesResponse = self
+                .send("responses", &body)
+                .await?
+                .json()
+                .await
+                .map_err(|e| LlmError::ParseError(e.to_string()))?;
+            return self.responses_to_chat_response(response);
+        }
+
+        // Build OpenAI API request
+        let openai_request = self.build_request(request)?;
 
         // Parse response
-        let openai_response: OpenAIChatResponse = response
+        let openai_response: OpenAIChatResponse = self
+            .send("chat/completions", &openai_request)
+            .await?
             .json()
             .await
             .map_err(|e| LlmError::ParseError(e.to_string()))?;
 
-        // Extract first choice
-        let choice = openai_response
-            .choices
-            .first()
-            .ok_or_else(|| LlmError::ParseError("No choices in response".to_string()))?;
+        self.to_chat_response(openai_response)
+    }
 
-        // Convert OpenAI tool_calls to our ToolCall type
-        let tool_calls = choice.message.tool_calls.as_ref().map(|calls| {
-            calls
-                .iter()
-                .map(|tc| super::super::types::ToolCall {
-                    id: tc.id.clone(),
-                    r#type: tc.r#type.clone(),
-                    function: super::super::types::FunctionCall {
-                        name: tc.function.name.clone(),
-                        arguments: tc.function.arguments.clone(),
-                    },
-                })
-                .collect()
+    async fn chat_stream(
+        &self,
+        request: ChatRequest,
+        tx: mpsc::Sender<String>,
+    ) -> LlmResult<ChatResponse> {
+        // Responses API events aren't parsed yet; the whole reply is sent as
+        // one chunk
+        if self.api == OpenAIApi::Responses {
+            let response = self.chat(request).await?;
+            if !response.content.is_empty() {
+                let _ = tx.send(response.content.clone()).await;
+            }
+            return Ok(response);
+        }
+
+        let mut body = self.build_request(request)?;
+        body.stream = true;
+        body.stream_options = Some(StreamOptions {
+            include_usage: true,
         });
+        let response = self.send("chat/completions", &body).await?;
+
+        let mut state = StreamState::default();
+        let mut buffer = Vec::new();
+        let mut stream = response.bytes_stream();
+        while let Some(chunk_result) = stream.next().await {
+            let bytes = chunk_result.map_err(|e| LlmError::NetworkError(e.to_string()))?;
+            buffer.extend_from_slice(&bytes);
+
+            // Chunks (and UTF-8 characters) can be split across reads; only
+            // handle complete lines
+            while let Some(newline) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=newline).collect();
+                let line = String::from_utf8_lossy(&line);
+                let Some(data) = line.trim_end().strip_prefix("data:") else {
+                    continue;
+                };
+                let Ok(chunk) = serde_json::from_str::<Value>(data.trim()) else {
+                    continue; // `[DONE]`
+                };
+                if let Some(text) = state.apply(&chunk)? {
+                    let _ = tx.send(text).await;
+                }
+            }
+        }
+
+        let normalized = self
+            .validator
+            .normalize(state.tool_calls(), state.finish_reason.as_deref())?;
 
         Ok(ChatResponse {
-            content: choice.message.content.clone(),
-            model: openai_response.model,
-            usage: openai_response.usage.map(|u| super::super::types::Usage {
-                prompt_tokens: u.prompt_tokens,
-                completion_tokens: u.completion_tokens,
-                total_tokens: u.total_tokens,
-            }),
-            finish_reason: choice.finish_reason.clone(),
-            tool_calls,
+            content: state.content,
+            model: state.model.unwrap_or_else(|| self.model.clone()),
+            usage: state.usage.map(UsageInfo::into_usage),
+            finish_reason: normalized.finish_reason,
+            tool_calls: normalized.tool_calls,
+            warnings: normalized.warnings,
+            provider: None,
+            failovers: Vec::new(),
         })
     }
 
-    async fn chat_stream(
-        &self,
-        _request: ChatRequest,
-        _tx: mpsc::Sender<String>,
-    ) -> LlmResult<ChatResponse> {
-        // Simple non-streaming fallback for OpenAI - full implementation would use SSE
-        // For now, return error suggesting to use llama.cpp for streaming
-        Err(LlmError::ApiError(
-            "Streaming not yet implemented for OpenAI - use LlamaClient".to_string(),
-        ))
+    fn apply_effort(&self, request: &mut ChatRequest, effort: &Effort) -> AppliedEffort {
+        if !self.is_reasoning_model() {
+            return effort::apply_prompt_fallback(
+                request,
+                effort,
+                Some(format!(
+                    "model '{}' does not support reasoning_effort; using prompt fallback",
+                    self.model
+                )),
+            );
+        }
+
+        let value = effort.reasoning_effort().to_string();
+        request.reasoning_effort = Some(value.clone());
+        AppliedEffort {
+            effort: effort.clone(),
+            mapping: EffortMapping::ReasoningEffort { effort: value },
+            warning: None,
+        }
+    }
+
+    fn provider_name(&self) -> &str {
+        "openai"
+    }
+}
+
+/// Batches through the Batch API: the requests are uploaded as a JSONL
+/// file, run against the client's endpoint within 24 hours, and read back
+/// from the batch's output and error files
+#[async_trait]
+impl BatchChatClient for OpenAIClient {
+    async fn submit_batch(&self, requests: Vec<ChatRequest>) -> LlmResult<BatchHandle> {
+        if requests.is_empty() {
+            return Err(LlmError::InvalidRequest(
+                "a batch needs at least one request".to_string(),
+            ));
+        }
+        let endpoint = self.batch_endpoint();
+        let mut custom_ids = Vec::with_capacity(requests.len());
+        let mut jsonl = String::new();
+        for (index, request) in requests.into_iter().enumerate() {
+            let body = match self.api {
+                OpenAIApi::ChatCompletions => serde_json::to_value(self.build_request(request)?),
+                OpenAIApi::Responses => {
+                    serde_json::to_value(self.build_responses_request(request)?)
+                }
+            }
+            .map_err(|e| LlmError::InvalidRequest(e.to_string()))?;
+            let custom_id = batch::custom_id(index);
+            jsonl.push_str(
+                &json!({
+                    "custom_id": custom_id,
+                    "method": "POST",
+                    "url": endpoint,
+                    "body": body,
+                })
+                .to_string(),
+            );
+            jsonl.push('\n');
+            custom_ids.push(custom_id);
+        }
+
+        let file = self.upload_batch_file(jsonl).await?;
+        let created: BatchObject = self
+            .send(
+                "batches",
+                &json!({
+                    "input_file_id": file.id,
+                    "endpoint": endpoint,
+                    "completion_window": "24h",
+                }),
+            )
+            .await?
+            .json()
+            .await
+            .map_err(|e| LlmError::ParseError(e.to_string()))?;
+        Ok(BatchHandle {
+            provider: self.provider().to_string(),
+            batch_id: created.id,
+            custom_ids,
+            submitted_at: chrono::Utc::now(),
+        })
+    }
+
+    async fn poll(&self, handle: &BatchHandle) -> LlmResult<BatchStatus> {
+        self.retrieve_batch(handle).await?.status(handle)
+    }
+
+    async fn results(&self, handle: &BatchHandle) -> LlmResult<Vec<LlmResult<ChatResponse>>> {
+        let current = self.retrieve_batch(handle).await?;
+        let status = current.status(handle)?;
+        if !status.is_finished() {
+            return Err(LlmError::InvalidRequest(format!(
+                "batch {} isn't finished: {}",
+                handle.batch_id, current.status
+            )));
+        }
+        let mut results = HashMap::new();
+        for file_id in [&current.output_file_id, &current.error_file_id]
+            .into_iter()
+            .flatten()
+        {
+            self.read_batch_file(file_id, &mut results).await?;
+        }
+        Ok(batch::correlate(handle, results))
     }
 }
 
@@ -135,7 +732,7 @@ impl GenericChatClient for OpenAIClient {
 #[derive(Debug, Serialize)]
 struct OpenAIChatRequest {
     model: String,
-    messages: Vec<super::super::types::ChatMessage>,
+    messages: Vec<Value>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     temperature: Option<f32>,
@@ -143,11 +740,44 @@ struct OpenAIChatRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     max_tokens: Option<u32>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_completion_tokens: Option<u32>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     top_p: Option<f32>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     tools: Option<Vec<serde_json::Value>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<types::ToolChoice>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasoning_effort: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+}
+
+#[derive(Debug, Serialize)]
+struct StreamOptions {
+    /// Ask for a final chunk carrying the usage
+    include_usage: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -165,29 +795,536 @@ struct Choice {
 
 #[derive(Debug, Deserialize)]
 struct Message {
+    // `null` when the model only calls tools
     #[serde(default)]
+    content: Option<String>,
+
+    #[serde(default)]
+    tool_calls: Option<Vec<RawToolCall>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UsageInfo {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    total_tokens: u32,
+    #[serde(default)]
+    completion_tokens_details: Option<CompletionTokensDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionTokensDetails {
+    #[serde(default)]
+    reasoning_tokens: Option<u32>,
+}
+
+impl UsageInfo {
+    fn into_usage(self) -> Usage {
+        Usage {
+            prompt_tokens: self.prompt_tokens,
+            completion_tokens: self.completion_tokens,
+            total_tokens: self.total_tokens,
+            reasoning_tokens: self
+                .completion_tokens_details
+                .and_then(|d| d.reasoning_tokens),
+        }
+    }
+}
+
+/// Accumulates a streamed chat completion from its chunks
+#[derive(Debug, Default)]
+struct StreamState {
     content: String,
+    /// By the `index` of their deltas
+    tool_calls: Vec<(u64, StreamToolCall)>,
+    model: Option<String>,
+    finish_reason: Option<String>,
+    usage: Option<UsageInfo>,
+}
+
+/// The id and name come in the first delta of a call, the arguments in
+/// pieces across all of them
+#[derive(Debug, Default)]
+struct StreamToolCall {
+    id: Option<String>,
+    name: Option<String>,
+    arguments: String,
+}
+
+impl StreamState {
+    /// Apply one chunk, returning text to forward to the caller
+    fn apply(&mut self, chunk: &Value) -> LlmResult<Option<String>> {
+        if let Some(error) = chunk.get("error") {
+            let message = error["message"].as_str().unwrap_or("unknown error");
+            return Err(LlmError::ApiError(format!("Stream error: {}", message)));
+        }
+        if self.model.is_none() {
+            self.model = chunk["model"].as_str().map(str::to_string);
+        }
+        // With `include_usage`, the last chunk has usage and no choices
+        if chunk["usage"].is_object() {
+            self.usage = serde_json::from_value(chunk["usage"].clone()).ok();
+        }
+
+        let Some(choice) = chunk["choices"].as_array().and_then(|c| c.first()) else {
+            return Ok(None);
+        };
+        if let Some(reason) = choice["finish_reason"].as_str() {
+            self.finish_reason = Some(reason.to_string());
+        }
+        let delta = &choice["delta"];
+        for call in delta["tool_calls"].as_array().into_iter().flatten() {
+            let index = call["index"].as_u64().unwrap_or(0);
+            let position = match self.tool_calls.iter().position(|(i, _)| *i == index) {
+                Some(position) => position,
+                None => {
+                    self.tool_calls.push((index, StreamToolCall::default()));
+                    self.tool_calls.len() - 1
+                }
+            };
+            let accumulated = &mut self.tool_calls[position].1;
+            if let Some(id) = call["id"].as_str().filter(|id| !id.is_empty()) {
+                accumulated.id = Some(id.to_string());
+            }
+            if let Some(name) = call["function"]["name"].as_str().filter(|n| !n.is_empty()) {
+                accumulated.name = Some(name.to_string());
+            }
+            if let Some(arguments) = call["function"]["arguments"].as_str() {
+                accumulated.arguments.push_str(arguments);
+            }
+        }
+
+        let text = delta["content"].as_str().unwrap_or_default();
+        self.content.push_str(text);
+        Ok((!text.is_empty()).then(|| text.to_string()))
+    }
+
+    /// The calls as a non-streamed response would have listed them
+    fn tool_calls(&mut self) -> Option<Vec<RawToolCall>> {
+        let mut calls = std::mem::take(&mut self.tool_calls);
+        calls.sort_by_key(|(index, _)| *index);
+        let calls: Vec<RawToolCall> = calls
+            .into_iter()
+            .map(|(_, call)| RawToolCall {
+                id: call.id,
+                r#type: Some("function".to_string()),
+                function: Some(RawFunctionCall {
+                    name: call.name,
+                    arguments: Some(Value::String(call.arguments)),
+                }),
+            })
+            .collect();
+        (!calls.is_empty()).then_some(calls)
+    }
+}
+
+// Responses API request/response types
+
+#[derive(Debug, Serialize)]
+struct ResponsesRequest {
+    model: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instructions: Option<String>,
+
+    input: Vec<Value>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    tool_calls: Option<Vec<OpenAIToolCall>>,
+    max_output_tokens: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<Value>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<types::ToolChoice>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasoning: Option<ReasoningParams>,
+}
+
+#[derive(Debug, Serialize)]
+struct ReasoningParams {
+    effort: String,
 }
 
 #[derive(Debug, Deserialize)]
-struct OpenAIToolCall {
-    id: String,
-    r#type: String,
-    function: OpenAIFunction,
+struct ResponsesResponse {
+    model: String,
+
+    #[serde(default)]
+    status: Option<String>,
+
+    #[serde(default)]
+    output: Vec<OutputItem>,
+
+    #[serde(default)]
+    incomplete_details: Option<IncompleteDetails>,
+
+    #[serde(default)]
+    error: Option<Value>,
+
+    usage: Option<ResponsesUsage>,
 }
 
 #[derive(Debug, Deserialize)]
-struct OpenAIFunction {
-    name: String,
-    arguments: String,
+#[serde(tag = "type", rename_all = "snake_case")]
+enum OutputItem {
+    Message {
+        #[serde(default)]
+        content: Vec<ContentPart>,
+    },
+    FunctionCall {
+        #[serde(default)]
+        call_id: Option<String>,
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(default)]
+        arguments: Option<Value>,
+    },
+    /// Reasoning summaries, built-in tool calls and anything newer
+    #[serde(other)]
+    Other,
 }
 
 #[derive(Debug, Deserialize)]
-struct UsageInfo {
-    prompt_tokens: u32,
-    completion_tokens: u32,
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentPart {
+    OutputText {
+        text: String,
+    },
+    Refusal {
+        refusal: String,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct IncompleteDetails {
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponsesUsage {
+    input_tokens: u32,
+    output_tokens: u32,
     total_tokens: u32,
+    #[serde(default)]
+    output_tokens_details: Option<OutputTokensDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OutputTokensDetails {
+    #[serde(default)]
+    reasoning_tokens: Option<u32>,
+}
+
+// Batch API types
+
+#[derive(Debug, Deserialize)]
+struct FileObject {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchObject {
+    id: String,
+    status: String,
+    #[serde(default)]
+    output_file_id: Option<String>,
+    #[serde(default)]
+    error_file_id: Option<String>,
+    #[serde(default)]
+    request_counts: Option<RequestCounts>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RequestCounts {
+    total: usize,
+    completed: usize,
+    failed: usize,
+}
+
+impl BatchObject {
+    fn status(&self, handle: &BatchHandle) -> LlmResult<BatchStatus> {
+        let state = serde_json::from_value(Value::String(self.status.clone()))
+            .map_err(|_| LlmError::ParseError(format!("unknown batch status '{}'", self.status)))?;
+        let counts = self.request_counts.as_ref();
+        Ok(BatchStatus {
+            state,
+            total: counts.map_or(handle.len(), |c| c.total),
+            completed: counts.map_or(0, |c| c.completed),
+            failed: counts.map_or(0, |c| c.failed),
+        })
+    }
+}
+
+/// A line of a batch's output or error file
+#[derive(Debug, Deserialize)]
+struct BatchOutputLine {
+    custom_id: String,
+    #[serde(default)]
+    response: Option<BatchLineResponse>,
+    #[serde(default)]
+    error: Option<BatchLineError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchLineResponse {
+    status_code: u16,
+    body: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchLineError {
+    #[serde(default)]
+    code: Option<String>,
+    message: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> ChatRequest {
+        ChatRequest::new(vec![
+            ChatMessage::system("Be precise"),
+            ChatMessage::user("Prove it"),
+        ])
+        .with_temperature(0.7)
+        .with_max_tokens(1000)
+    }
+
+    #[test]
+    fn test_reasoning_model_maps_effort_to_reasoning_effort() {
+        let client = OpenAIClient::with_model("key", "o3-mini");
+        let mut req = request();
+        let applied = client.apply_effort(&mut req, &Effort::High);
+
+        assert_eq!(
+            applied.mapping,
+            EffortMapping::ReasoningEffort {
+                effort: "high".to_string()
+            }
+        );
+        assert!(applied.warning.is_none());
+
+        let body = serde_json::to_value(client.build_request(req).unwrap()).unwrap();
+        assert_eq!(body["reasoning_effort"], "high");
+        assert_eq!(body["max_completion_tokens"], 1000);
+        assert!(body.get("max_tokens").is_none());
+        assert!(body.get("temperature").is_none());
+        // System prompt untouched
+        assert_eq!(body["messages"][0]["content"], "Be precise");
+    }
+
+    #[test]
+    fn test_plain_model_degrades_to_prompt_fallback_with_warning() {
+        let client = OpenAIClient::with_model("key", "gpt-4o");
+        let mut req = request();
+        let applied = client.apply_effort(&mut req, &Effort::Medium);
+
+        assert!(matches!(
+            applied.mapping,
+            EffortMapping::PromptFallback {
+                scratchpad: true,
+                max_tokens: Some(1500)
+            }
+        ));
+        assert!(applied.warning.unwrap().contains("gpt-4o"));
+
+        let body = serde_json::to_value(client.build_request(req).unwrap()).unwrap();
+        assert!(body.get("reasoning_effort").is_none());
+        assert_eq!(body["max_tokens"], 1500);
+        assert!(body["messages"][0]["content"]
+            .as_str()
+            .unwrap()
+            .contains(effort::SCRATCHPAD_INSTRUCTION));
+    }
+
+    #[test]
+    fn test_reasoning_model_drops_sampling_params_without_effort() {
+        let client = OpenAIClient::with_model("key", "o4-mini");
+        let body = serde_json::to_value(client.build_request(request()).unwrap()).unwrap();
+        assert!(body.get("temperature").is_none());
+        assert!(body.get("reasoning_effort").is_none());
+        assert_eq!(body["max_completion_tokens"], 1000);
+
+        let body = serde_json::to_value(
+            OpenAIClient::responses_api("key", "o4-mini")
+                .build_responses_request(request())
+                .unwrap(),
+        )
+        .unwrap();
+        assert!(body.get("temperature").is_none());
+        assert_eq!(body["max_output_tokens"], 1000);
+    }
+
+    #[test]
+    fn test_responses_input_items() {
+        let calls = vec![crate::llm::types::ToolCall {
+            id: "call_1".to_string(),
+            r#type: "function".to_string(),
+            function: crate::llm::types::FunctionCall {
+                name: "search".to_string(),
+                arguments: "{\"q\":\"rust\"}".to_string(),
+            },
+        }];
+        let (instructions, input) = translate_messages(vec![
+            ChatMessage::system("Be brief"),
+            ChatMessage::user("Find rust"),
+            ChatMessage::assistant_with_tool_calls("", calls),
+            ChatMessage::tool_result("call_1", "found"),
+        ])
+        .unwrap();
+
+        assert_eq!(instructions.as_deref(), Some("Be brief"));
+        assert_eq!(
+            input,
+            vec![
+                json!({"role": "user", "content": "Find rust"}),
+                json!({"type": "function_call", "call_id": "call_1", "name": "search", "arguments": "{\"q\":\"rust\"}"}),
+                json!({"type": "function_call_output", "call_id": "call_1", "output": "found"}),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reasoning_tokens_parsed_from_usage() {
+        let usage: UsageInfo = serde_json::from_value(serde_json::json!({
+            "prompt_tokens": 10,
+            "completion_tokens": 50,
+            "total_tokens": 60,
+            "completion_tokens_details": {"reasoning_tokens": 32}
+        }))
+        .unwrap();
+        assert_eq!(
+            usage
+                .completion_tokens_details
+                .and_then(|d| d.reasoning_tokens),
+            Some(32)
+        );
+    }
+
+    fn parse(
+        strictness: Strictness,
+        message: serde_json::Value,
+        finish_reason: serde_json::Value,
+    ) -> LlmResult<ChatResponse> {
+        let body: OpenAIChatResponse = serde_json::from_value(serde_json::json!({
+            "model": "gpt-4o",
+            "choices": [{"message": message, "finish_reason": finish_reason}],
+        }))
+        .unwrap();
+        OpenAIClient::new("key")
+            .with_validation(strictness)
+            .to_chat_response(body)
+    }
+
+    fn call(id: &str, name: &str, arguments: &str) -> serde_json::Value {
+        serde_json::json!({"id": id, "type": "function",
+            "function": {"name": name, "arguments": arguments}})
+    }
+
+    #[test]
+    fn test_malformed_tool_calls_fail_in_strict_mode() {
+        let fixtures = [
+            (
+                serde_json::json!({"content": null, "tool_calls": [call("a", "", "{}")]}),
+                "empty function name",
+            ),
+            (
+                serde_json::json!({"tool_calls": [call("a", "search", "{\"q\": ")]}),
+                "not valid JSON",
+            ),
+            (
+                serde_json::json!({"tool_calls": [
+                    call("a", "search", "{}"),
+                    call("a", "fetch", "{}"),
+                ]}),
+                "reuses id `a`",
+            ),
+            (
+                serde_json::json!({"content": "", "tool_calls": null}),
+                "finish_reason is tool_calls",
+            ),
+        ];
+        for (message, expected) in fixtures {
+            let err = parse(Strictness::Strict, message, "tool_calls".into()).unwrap_err();
+            let LlmError::ParseError(details) = err else {
+                panic!("expected ParseError");
+            };
+            assert!(details.contains(expected), "{}", details);
+        }
+    }
+
+    #[test]
+    fn test_lenient_mode_repairs_tool_calls() {
+        let message = serde_json::json!({
+            "content": null,
+            "tool_calls": [
+                call("a", "", "{}"),
+                call("a", "search", "```json\n{\"q\": \"rust\"}\n```"),
+                call("a", "fetch", ""),
+                {"function": {"name": "lookup", "arguments": {"id": 7}}},
+                call("b", "broken", "{\"q\": "),
+            ],
+        });
+        let response = parse(Strictness::Lenient, message, "function_call".into()).unwrap();
+
+        let calls = response.tool_calls.unwrap();
+        let summary: Vec<_> = calls
+            .iter()
+            .map(|c| {
+                (
+                    c.id.as_str(),
+                    c.function.name.as_str(),
+                    c.function.arguments.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("a", "search", "{\"q\":\"rust\"}"),
+                ("a_1", "fetch", "{}"),
+                ("call_3", "lookup", "{\"id\":7}"),
+            ]
+        );
+        assert!(calls.iter().all(|c| c.r#type == "function"));
+        assert_eq!(response.finish_reason.as_deref(), Some("tool_calls"));
+        assert_eq!(response.content, "");
+        // One warning per repair or dropped entry
+        assert_eq!(response.warnings.len(), 7);
+    }
+
+    #[test]
+    fn test_lenient_mode_downgrades_empty_tool_calls_finish() {
+        let response = parse(
+            Strictness::Lenient,
+            serde_json::json!({"content": "All done", "tool_calls": null}),
+            "tool_calls".into(),
+        )
+        .unwrap();
+        assert!(response.tool_calls.is_none());
+        assert_eq!(response.finish_reason.as_deref(), Some("stop"));
+        assert_eq!(response.warnings.len(), 1);
+
+        // Clean responses carry no warnings
+        let clean = parse(
+            Strictness::Strict,
+            serde_json::json!({"content": "hi"}),
+            "end_turn".into(),
+        )
+        .unwrap();
+        assert!(clean.warnings.is_empty());
+        assert_eq!(clean.finish_reason.as_deref(), Some("stop"));
+    }
 }
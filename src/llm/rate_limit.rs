@@ -0,0 +1,515 @@
+//! Client-side rate limiting for LLM requests.
+//!
+//! A [`RateLimiter`] is a pair of token buckets, one for requests and one
+//! for tokens per minute, shared by every clone. Each request reserves one
+//! request and its estimated tokens (prompt plus `max_tokens`) before it is
+//! sent, waiting in FIFO order until both buckets allow it. Once the
+//! response reports its [`Usage`], the unused part of the reservation is
+//! refunded (or the overrun charged).
+//!
+//! Attach a limiter to one client with [`RateLimitedChatClient`], or to
+//! every agent request in a runtime's runs with
+//! `Runtime::with_rate_limiter`. Time spent waiting counts as rate limiter
+//! wait in the turn's latency breakdown; waits longer than the threshold
+//! are logged as a `tracing` warning.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use crate::error::{ConfigError, ConfigErrorCode};
+use crate::llm::types::{ChatMessage, Usage, DEFAULT_IMAGE_TOKENS};
+use crate::llm::{
+    AppliedEffort, ChatRequest, ChatResponse, Effort, GenericChatClient, LlmClient, LlmResult,
+};
+
+/// Waits at least this long are logged by default
+pub const DEFAULT_WAIT_THRESHOLD: Duration = Duration::from_secs(5);
+
+/// Requests and tokens a provider allows per minute; an unset limit is
+/// not enforced
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimits {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+
+    /// Prompt and completion tokens together
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_per_minute: Option<u32>,
+}
+
+impl RateLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_requests_per_minute(mut self, requests: u32) -> Self {
+        self.requests_per_minute = Some(requests);
+        self
+    }
+
+    pub fn with_tokens_per_minute(mut self, tokens: u32) -> Self {
+        self.tokens_per_minute = Some(tokens);
+        self
+    }
+
+    /// Both limits, when set, are at least 1
+    pub(crate) fn validate(&self, field: &str) -> Result<(), ConfigError> {
+        for (name, limit) in [
+            ("requests_per_minute", self.requests_per_minute),
+            ("tokens_per_minute", self.tokens_per_minute),
+        ] {
+            if limit == Some(0) {
+                return Err(ConfigError {
+                    code: ConfigErrorCode::InvalidValue,
+                    message: format!("Rate limit {} must be at least 1", name),
+                    field: Some(format!("{}.{}", field, name)),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What a [`RateLimiter`] has done so far
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitStats {
+    /// Requests let through
+    pub requests: u64,
+
+    /// Requests that had to wait
+    pub delayed: u64,
+
+    /// Total time spent waiting, in milliseconds
+    pub wait_ms: f64,
+
+    /// Tokens reserved but not used, given back after responses
+    pub refunded_tokens: u64,
+}
+
+/// A bucket holding up to a minute's allowance, refilled continuously
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    level: f64,
+}
+
+impl Bucket {
+    fn new(per_minute: Option<u32>) -> Option<Self> {
+        per_minute.map(|limit| Self {
+            capacity: limit.max(1) as f64,
+            level: limit.max(1) as f64,
+        })
+    }
+
+    fn refill(&mut self, elapsed: Duration) {
+        let refilled = self.capacity * elapsed.as_secs_f64() / 60.0;
+        self.level = (self.level + refilled).min(self.capacity);
+    }
+
+    /// How long until `amount` is available; never more than the capacity
+    /// is asked for, so a huge request waits for a full bucket
+    fn wait_for(&self, amount: f64) -> Duration {
+        let missing = amount.min(self.capacity) - self.level;
+        if missing <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing * 60.0 / self.capacity)
+        }
+    }
+
+    fn take(&mut self, amount: f64) {
+        self.level -= amount.min(self.capacity);
+    }
+
+    fn give_back(&mut self, amount: f64) {
+        self.level = (self.level + amount).min(self.capacity);
+    }
+}
+
+#[derive(Debug)]
+struct Buckets {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+    refilled_at: Instant,
+}
+
+impl Buckets {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled_at);
+        self.refilled_at = now;
+        for bucket in [&mut self.requests, &mut self.tokens].into_iter().flatten() {
+            bucket.refill(elapsed);
+        }
+    }
+}
+
+struct Shared {
+    buckets: Mutex<Buckets>,
+    /// Held while a request waits for its permits, so requests are let
+    /// through in the order they arrived
+    queue: tokio::sync::Mutex<()>,
+    stats: Mutex<RateLimitStats>,
+}
+
+type PromptCounter = Arc<dyn Fn(&[ChatMessage]) -> usize + Send + Sync>;
+
+/// Token-bucket limiter for requests and tokens per minute
+///
+/// Clones share the same buckets, so one limiter can be handed to every
+/// client that draws on the same provider quota.
+///
+/// ```rust,ignore
+/// let limiter = RateLimiter::new(
+///     RateLimits::new()
+///         .with_requests_per_minute(60)
+///         .with_tokens_per_minute(90_000),
+/// );
+/// let openai = Arc::new(RateLimitedChatClient::with_limiter(
+///     Arc::new(OpenAIClient::new(api_key)),
+///     limiter.clone(),
+/// ));
+/// ```
+#[derive(Clone)]
+pub struct RateLimiter {
+    limits: RateLimits,
+    shared: Arc<Shared>,
+    wait_threshold: Duration,
+    count_prompt: PromptCounter,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                buckets: Mutex::new(Buckets {
+                    requests: Bucket::new(limits.requests_per_minute),
+                    tokens: Bucket::new(limits.tokens_per_minute),
+                    refilled_at: Instant::now(),
+                }),
+                queue: tokio::sync::Mutex::new(()),
+                stats: Mutex::new(RateLimitStats::default()),
+            }),
+            limits,
+            wait_threshold: DEFAULT_WAIT_THRESHOLD,
+            count_prompt: Arc::new(heuristic_prompt_tokens),
+        }
+    }
+
+    /// Log waits of at least `threshold` (default: [`DEFAULT_WAIT_THRESHOLD`])
+    pub fn with_wait_threshold(mut self, threshold: Duration) -> Self {
+        self.wait_threshold = threshold;
+        self
+    }
+
+    /// Estimate prompt tokens with `counter` instead of the character
+    /// heuristic, e.g. the one the agent's context strategy prunes with
+    #[cfg(feature = "workflow")]
+    pub fn with_token_counter(mut self, counter: Arc<dyn crate::context::TokenCounter>) -> Self {
+        self.count_prompt = Arc::new(move |messages| counter.count_messages(messages));
+        self
+    }
+
+    pub fn limits(&self) -> &RateLimits {
+        &self.limits
+    }
+
+    pub fn stats(&self) -> RateLimitStats {
+        self.shared.stats.lock().unwrap().clone()
+    }
+
+    /// Tokens reserved for `request`: the estimated prompt plus its
+    /// `max_tokens`
+    pub fn estimate_tokens(&self, request: &ChatRequest) -> u32 {
+        let prompt = (self.count_prompt)(&request.messages);
+        let completion = request.max_tokens.unwrap_or(0) as usize;
+        (prompt + completion).min(u32::MAX as usize) as u32
+    }
+
+    /// Wait until `request` may be sent, behind any requests already waiting
+    pub async fn acquire(&self, request: &ChatRequest) -> RateLimitPermit {
+        let tokens = self.estimate_tokens(request);
+        let started = Instant::now();
+        let _turn = self.shared.queue.lock().await;
+        loop {
+            let wait = {
+                let mut buckets = self.shared.buckets.lock().unwrap();
+                buckets.refill();
+                let wait = [
+                    buckets.requests.as_ref().map(|b| b.wait_for(1.0)),
+                    buckets.tokens.as_ref().map(|b| b.wait_for(tokens as f64)),
+                ]
+                .into_iter()
+                .flatten()
+                .max()
+                .unwrap_or_default();
+                if wait.is_zero() {
+                    if let Some(bucket) = &mut buckets.requests {
+                        bucket.take(1.0);
+                    }
+                    if let Some(bucket) = &mut buckets.tokens {
+                        bucket.take(tokens as f64);
+                    }
+                    break;
+                }
+                wait
+            };
+            tokio::time::sleep(wait).await;
+        }
+
+        let waited = started.elapsed();
+        let mut stats = self.shared.stats.lock().unwrap();
+        stats.requests += 1;
+        if !waited.is_zero() {
+            stats.delayed += 1;
+            stats.wait_ms += crate::telemetry::millis(waited);
+        }
+        RateLimitPermit {
+            shared: self.shared.clone(),
+            tokens,
+            waited,
+        }
+    }
+
+    /// Acquire for a request to `provider`, reporting the wait to the
+    /// current turn and logging it if it ran long
+    pub(crate) async fn acquire_for(
+        &self,
+        provider: &str,
+        request: &ChatRequest,
+    ) -> RateLimitPermit {
+        let permit = self.acquire(request).await;
+        if !permit.waited.is_zero() {
+            crate::agent::latency::record_rate_limit_wait(permit.waited);
+        }
+        if permit.waited >= self.wait_threshold {
+            tracing::warn!(
+                provider,
+                wait_ms = crate::telemetry::millis(permit.waited),
+                tokens = permit.tokens,
+                "LLM request waited {:.1}s for the rate limiter",
+                permit.waited.as_secs_f64()
+            );
+        }
+        permit
+    }
+}
+
+impl std::fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("limits", &self.limits)
+            .field("wait_threshold", &self.wait_threshold)
+            .finish()
+    }
+}
+
+/// A request's reservation, from [`RateLimiter::acquire`]
+///
+/// Settle it with the response's usage to refund what wasn't used.
+/// Dropping it unsettled, e.g. after a failed request, keeps the full
+/// reservation.
+pub struct RateLimitPermit {
+    shared: Arc<Shared>,
+    tokens: u32,
+    waited: Duration,
+}
+
+impl RateLimitPermit {
+    /// Tokens reserved for the request
+    pub fn tokens(&self) -> u32 {
+        self.tokens
+    }
+
+    /// How long the request waited for its permits
+    pub fn waited(&self) -> Duration {
+        self.waited
+    }
+
+    /// Correct the reservation to the tokens `usage` reports
+    pub fn settle(self, usage: Option<&Usage>) {
+        let Some(usage) = usage else {
+            return;
+        };
+        let mut buckets = self.shared.buckets.lock().unwrap();
+        if let Some(bucket) = &mut buckets.tokens {
+            let reserved = (self.tokens as f64).min(bucket.capacity);
+            let unused = reserved - usage.total_tokens as f64;
+            bucket.give_back(unused);
+            if unused > 0.0 {
+                self.shared.stats.lock().unwrap().refunded_tokens += unused as u64;
+            }
+        }
+    }
+}
+
+/// The character heuristic `HeuristicCounter` uses: about 4 bytes per
+/// token, 1 per message, 20 per tool call and a flat cost per image
+fn heuristic_prompt_tokens(messages: &[ChatMessage]) -> usize {
+    messages
+        .iter()
+        .map(|m| {
+            m.content.text().len() / 4
+                + 1
+                + m.tool_calls.as_ref().map_or(0, |calls| calls.len() * 20)
+                + m.content.image_count() * DEFAULT_IMAGE_TOKENS
+        })
+        .sum()
+}
+
+/// A client that waits on a [`RateLimiter`] before each request
+///
+/// ```rust,ignore
+/// let limits = RateLimits::new().with_requests_per_minute(60);
+/// let client: LlmClient = Arc::new(RateLimitedChatClient::new(
+///     Arc::new(OpenAIClient::new(api_key)),
+///     limits,
+/// ));
+/// // Share `client` between agents to share the limit
+/// ```
+pub struct RateLimitedChatClient {
+    inner: LlmClient,
+    limiter: RateLimiter,
+}
+
+impl RateLimitedChatClient {
+    pub fn new(inner: LlmClient, limits: RateLimits) -> Self {
+        Self::with_limiter(inner, RateLimiter::new(limits))
+    }
+
+    /// Limit `inner` with a limiter that may be shared with other clients
+    pub fn with_limiter(inner: LlmClient, limiter: RateLimiter) -> Self {
+        Self { inner, limiter }
+    }
+
+    pub fn limiter(&self) -> &RateLimiter {
+        &self.limiter
+    }
+}
+
+#[async_trait]
+impl GenericChatClient for RateLimitedChatClient {
+    async fn chat(&self, request: ChatRequest) -> LlmResult<ChatResponse> {
+        let permit = self
+            .limiter
+            .acquire_for(self.inner.provider_name(), &request)
+            .await;
+        let response = self.inner.chat(request).await?;
+        permit.settle(response.usage.as_ref());
+        Ok(response)
+    }
+
+    async fn chat_stream(
+        &self,
+        request: ChatRequest,
+        tx: mpsc::Sender<String>,
+    ) -> LlmResult<ChatResponse> {
+        let permit = self
+            .limiter
+            .acquire_for(self.inner.provider_name(), &request)
+            .await;
+        let response = self.inner.chat_stream(request, tx).await?;
+        permit.settle(response.usage.as_ref());
+        Ok(response)
+    }
+
+    fn apply_effort(&self, request: &mut ChatRequest, effort: &Effort) -> AppliedEffort {
+        self.inner.apply_effort(request, effort)
+    }
+
+    fn provider_name(&self) -> &str {
+        self.inner.provider_name()
+    }
+}
+
+tokio::task_local! {
+    static CURRENT_LIMITER: Option<RateLimiter>;
+}
+
+/// Limit agent LLM requests made by `fut` with `limiter`, if any
+#[cfg(feature = "workflow")]
+pub(crate) async fn limited<F: std::future::Future>(
+    limiter: Option<RateLimiter>,
+    fut: F,
+) -> F::Output {
+    CURRENT_LIMITER.scope(limiter, fut).await
+}
+
+/// Wait on the current runtime's limiter, if there is one
+pub(crate) async fn acquire_current(
+    provider: &str,
+    request: &ChatRequest,
+) -> Option<RateLimitPermit> {
+    let limiter = CURRENT_LIMITER.try_with(|limiter| limiter.clone()).ok()??;
+    Some(limiter.acquire_for(provider, request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(total_tokens: u32) -> Usage {
+        Usage {
+            prompt_tokens: total_tokens,
+            completion_tokens: 0,
+            total_tokens,
+            reasoning_tokens: None,
+        }
+    }
+
+    #[test]
+    fn test_estimate_counts_prompt_and_max_tokens() {
+        let limiter = RateLimiter::new(RateLimits::new().with_tokens_per_minute(1_000));
+        let request =
+            ChatRequest::new(vec![ChatMessage::user("x".repeat(40))]).with_max_tokens(100);
+        assert_eq!(limiter.estimate_tokens(&request), 40 / 4 + 1 + 100);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unused_tokens_are_refunded() {
+        let limiter = RateLimiter::new(RateLimits::new().with_tokens_per_minute(1_000));
+        let request = ChatRequest::new(vec![ChatMessage::user("hi")]).with_max_tokens(599);
+
+        // 600 reserved, 100 used: the next 600 fit without waiting
+        limiter.acquire(&request).await.settle(Some(&usage(100)));
+        let started = Instant::now();
+        limiter.acquire(&request).await;
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        assert_eq!(limiter.stats().refunded_tokens, 500);
+
+        // Unsettled, the second reservation is kept: 300 left, 300 missing
+        let permit = limiter.acquire(&request).await;
+        assert_eq!(permit.waited(), Duration::from_secs(18));
+        assert_eq!(limiter.stats().delayed, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_oversized_requests_wait_for_a_full_bucket() {
+        let limiter = RateLimiter::new(RateLimits::new().with_tokens_per_minute(100));
+        let request = ChatRequest::new(vec![]).with_max_tokens(10_000);
+
+        assert_eq!(limiter.acquire(&request).await.waited(), Duration::ZERO);
+        assert_eq!(
+            limiter.acquire(&request).await.waited(),
+            Duration::from_secs(60)
+        );
+    }
+
+    #[test]
+    fn test_zero_limits_are_rejected() {
+        let err = RateLimits::new()
+            .with_requests_per_minute(0)
+            .validate("llm.openai.rate_limit")
+            .unwrap_err();
+        assert_eq!(
+            err.field.as_deref(),
+            Some("llm.openai.rate_limit.requests_per_minute")
+        );
+        assert!(RateLimits::new().validate("llm.openai.rate_limit").is_ok());
+    }
+}
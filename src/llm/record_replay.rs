@@ -0,0 +1,349 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::error::{ConfigError, ConfigErrorCode};
+use crate::llm::{
+    AppliedEffort, ChatMessage, ChatRequest, ChatResponse, Effort, GenericChatClient, LlmClient,
+    LlmError, LlmResult,
+};
+
+/// One recorded request and the response it got
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: ChatRequest,
+
+    /// Text sent over the stream, in order; empty for `chat`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<String>,
+
+    pub response: ChatResponse,
+}
+
+/// The interactions of a recording, in the order they happened
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| ConfigError {
+            code: ConfigErrorCode::FileNotFound,
+            message: format!("Failed to read cassette: {}", e),
+            field: Some(path.display().to_string()),
+        })?;
+        serde_json::from_str(&content).map_err(|e| ConfigError {
+            code: ConfigErrorCode::ParseError,
+            message: format!("Failed to parse cassette: {}", e),
+            field: Some(path.display().to_string()),
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(path, json)
+    }
+}
+
+/// A client that passes requests to another one and records each
+/// successful exchange to a cassette file
+///
+/// The file is rewritten after every interaction, so a test that fails
+/// halfway still leaves what it recorded. Failed requests are passed on
+/// and not recorded.
+///
+/// ```rust,ignore
+/// let client = RecordingChatClient::new(Arc::new(OpenAIClient::new(key)), "triage.json");
+/// // ... run the agent once against the real provider, then:
+/// let client = ReplayChatClient::from_file("triage.json")?;
+/// ```
+pub struct RecordingChatClient {
+    inner: LlmClient,
+    path: PathBuf,
+    cassette: Mutex<Cassette>,
+}
+
+impl RecordingChatClient {
+    /// Record to `path`, replacing what it holds
+    pub fn new(inner: LlmClient, path: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            path: path.into(),
+            cassette: Mutex::new(Cassette::default()),
+        }
+    }
+
+    /// What has been recorded so far
+    pub fn cassette(&self) -> Cassette {
+        self.cassette.lock().unwrap().clone()
+    }
+
+    fn record(&self, interaction: Interaction) -> LlmResult<()> {
+        let mut cassette = self.cassette.lock().unwrap();
+        cassette.interactions.push(interaction);
+        cassette.save(&self.path).map_err(|e| {
+            LlmError::ApiError(format!(
+                "Failed to write cassette {}: {}",
+                self.path.display(),
+                e
+            ))
+        })
+    }
+}
+
+#[async_trait]
+impl GenericChatClient for RecordingChatClient {
+    async fn chat(&self, request: ChatRequest) -> LlmResult<ChatResponse> {
+        let response = self.inner.chat(request.clone()).await?;
+        self.record(Interaction {
+            request,
+            chunks: Vec::new(),
+            response: response.clone(),
+        })?;
+        Ok(response)
+    }
+
+    async fn chat_stream(
+        &self,
+        request: ChatRequest,
+        tx: mpsc::Sender<String>,
+    ) -> LlmResult<ChatResponse> {
+        let (inner_tx, mut inner_rx) = mpsc::channel::<String>(tx.max_capacity());
+        let mut chunks = Vec::new();
+        let forward = async {
+            while let Some(chunk) = inner_rx.recv().await {
+                chunks.push(chunk.clone());
+                let _ = tx.send(chunk).await;
+            }
+        };
+        let (result, ()) = tokio::join!(self.inner.chat_stream(request.clone(), inner_tx), forward);
+        let response = result?;
+        self.record(Interaction {
+            request,
+            chunks,
+            response: response.clone(),
+        })?;
+        Ok(response)
+    }
+
+    fn apply_effort(&self, request: &mut ChatRequest, effort: &Effort) -> AppliedEffort {
+        self.inner.apply_effort(request, effort)
+    }
+
+    fn provider_name(&self) -> &str {
+        self.inner.provider_name()
+    }
+}
+
+/// How [`ReplayChatClient`] finds the recorded interaction for a request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReplayMatch {
+    /// Every message matches in role, content and tool calls
+    #[default]
+    Exact,
+
+    /// The last user message matches, e.g. when system prompts change
+    /// between runs
+    LastUserMessage,
+}
+
+/// A client that answers from a cassette instead of a provider
+///
+/// Each interaction is served once, so repeated requests get the answers
+/// they got when recorded, in order. A request that matches none of the
+/// unused interactions fails with `LlmError::InvalidRequest` describing
+/// where it diverges from the next one. Streams replay their recorded
+/// chunks at once, or with [`with_chunk_delay`](Self::with_chunk_delay)
+/// between them.
+pub struct ReplayChatClient {
+    interactions: Vec<Interaction>,
+    used: Mutex<Vec<bool>>,
+    matching: ReplayMatch,
+    chunk_delay: Option<Duration>,
+}
+
+impl ReplayChatClient {
+    pub fn new(cassette: Cassette) -> Self {
+        Self {
+            used: Mutex::new(vec![false; cassette.interactions.len()]),
+            interactions: cassette.interactions,
+            matching: ReplayMatch::default(),
+            chunk_delay: None,
+        }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        Cassette::load(path).map(Self::new)
+    }
+
+    /// Match requests this way (default: [`ReplayMatch::Exact`])
+    pub fn with_matching(mut self, matching: ReplayMatch) -> Self {
+        self.matching = matching;
+        self
+    }
+
+    /// Wait this long before each replayed chunk, e.g. to watch a UI
+    /// render a stream
+    pub fn with_chunk_delay(mut self, delay: Duration) -> Self {
+        self.chunk_delay = Some(delay);
+        self
+    }
+
+    /// Interactions not served yet
+    pub fn remaining(&self) -> usize {
+        self.used
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|used| !**used)
+            .count()
+    }
+
+    /// Take the first unused interaction matching `request`
+    fn take(&self, request: &ChatRequest) -> LlmResult<&Interaction> {
+        let mut used = self.used.lock().unwrap();
+        let mut unused = (0..self.interactions.len()).filter(|&i| !used[i]);
+        let Some(next) = unused.clone().next() else {
+            return Err(LlmError::InvalidRequest(format!(
+                "Cassette has no unused interactions left (recorded {})",
+                self.interactions.len()
+            )));
+        };
+        let Some(index) = unused.find(|&i| self.matches(&self.interactions[i].request, request))
+        else {
+            return Err(LlmError::InvalidRequest(format!(
+                "Request matches no recorded interaction; against interaction {}, {}",
+                next,
+                self.divergence(&self.interactions[next].request, request)
+            )));
+        };
+        used[index] = true;
+        Ok(&self.interactions[index])
+    }
+
+    fn matches(&self, recorded: &ChatRequest, request: &ChatRequest) -> bool {
+        match self.matching {
+            ReplayMatch::Exact => {
+                recorded.messages.len() == request.messages.len()
+                    && recorded
+                        .messages
+                        .iter()
+                        .zip(&request.messages)
+                        .all(|(a, b)| same_message(a, b))
+            }
+            ReplayMatch::LastUserMessage => match (last_user(recorded), last_user(request)) {
+                (Some((_, a)), Some((_, b))) => a.content == b.content,
+                _ => false,
+            },
+        }
+    }
+
+    /// Where `request` first differs from `recorded`
+    fn divergence(&self, recorded: &ChatRequest, request: &ChatRequest) -> String {
+        if self.matching == ReplayMatch::LastUserMessage {
+            return match (last_user(recorded), last_user(request)) {
+                (Some((_, a)), Some((index, b))) => format!(
+                    "the last user message (message {}) differs: recorded {}, got {}",
+                    index,
+                    describe(a),
+                    describe(b)
+                ),
+                (_, None) => "the request has no user message".to_string(),
+                (None, _) => "the recorded request has no user message".to_string(),
+            };
+        }
+        let count = recorded.messages.len().max(request.messages.len());
+        for index in 0..count {
+            let (a, b) = (recorded.messages.get(index), request.messages.get(index));
+            if let (Some(a), Some(b)) = (a, b) {
+                if same_message(a, b) {
+                    continue;
+                }
+            }
+            return format!(
+                "message {} differs: recorded {}, got {}",
+                index,
+                a.map_or("nothing".to_string(), describe),
+                b.map_or("nothing".to_string(), describe)
+            );
+        }
+        unreachable!("only called for requests that don't match")
+    }
+}
+
+#[async_trait]
+impl GenericChatClient for ReplayChatClient {
+    async fn chat(&self, request: ChatRequest) -> LlmResult<ChatResponse> {
+        Ok(self.take(&request)?.response.clone())
+    }
+
+    async fn chat_stream(
+        &self,
+        request: ChatRequest,
+        tx: mpsc::Sender<String>,
+    ) -> LlmResult<ChatResponse> {
+        let interaction = self.take(&request)?;
+
+        // Recorded with `chat`: the whole response is one chunk
+        let content = [interaction.response.content.clone()];
+        let chunks = match interaction.chunks.as_slice() {
+            [] if interaction.response.content.is_empty() => &[][..],
+            [] => &content[..],
+            chunks => chunks,
+        };
+        for chunk in chunks {
+            if let Some(delay) = self.chunk_delay {
+                tokio::time::sleep(delay).await;
+            }
+            let _ = tx.send(chunk.clone()).await;
+        }
+        Ok(interaction.response.clone())
+    }
+
+    fn provider_name(&self) -> &str {
+        "replay"
+    }
+}
+
+/// Role, content and tool calls match; provenance is ignored
+fn same_message(a: &ChatMessage, b: &ChatMessage) -> bool {
+    a.role == b.role
+        && a.content == b.content
+        && a.tool_calls == b.tool_calls
+        && a.tool_call_id == b.tool_call_id
+}
+
+fn last_user(request: &ChatRequest) -> Option<(usize, &ChatMessage)> {
+    request
+        .messages
+        .iter()
+        .enumerate()
+        .rev()
+        .find(|(_, message)| message.role == crate::llm::Role::User)
+}
+
+/// e.g. `user "How many moons does Mars ha..."`
+fn describe(message: &ChatMessage) -> String {
+    const MAX_CHARS: usize = 60;
+    let role = serde_json::to_value(&message.role)
+        .ok()
+        .and_then(|role| role.as_str().map(str::to_string))
+        .unwrap_or_default();
+    let text = message.content.text();
+    let mut content: String = text.chars().take(MAX_CHARS).collect();
+    if text.chars().count() > MAX_CHARS {
+        content.push_str("...");
+    }
+    match &message.tool_calls {
+        Some(calls) if !calls.is_empty() => {
+            format!("{} {:?} with {} tool call(s)", role, content, calls.len())
+        }
+        _ => format!("{} {:?}", role, content),
+    }
+}
@@ -0,0 +1,318 @@
+//! Token counting for context strategies.
+//!
+//! Strategies decide when and how much to prune from a [`TokenCounter`].
+//! [`HeuristicCounter`] (the default) is the long-standing ~4 characters per
+//! token estimate with a flat cost per tool call; it is fast but badly off
+//! for code and CJK text. With the `tiktoken` feature, [`TiktokenCounter`]
+//! runs OpenAI's byte-pair encoding through `tiktoken-rs`, counting
+//! tool-call names and JSON arguments token by token. Both charge each
+//! image a fixed, configurable number of tokens.
+
+use crate::llm::types::ChatMessage;
+pub use crate::llm::types::DEFAULT_IMAGE_TOKENS;
+
+/// Tokens added per message for role and separators, beyond its content
+pub const MESSAGE_OVERHEAD: usize = 3;
+
+/// Tokens added per tool call for its framing, beyond name and arguments
+pub const TOOL_CALL_OVERHEAD: usize = 3;
+
+/// Counts the tokens text and messages occupy in a prompt
+pub trait TokenCounter: Send + Sync {
+    /// Tokens in a piece of text
+    fn count_text(&self, text: &str) -> usize;
+
+    /// Tokens charged for each image in a message
+    fn image_tokens(&self) -> usize {
+        DEFAULT_IMAGE_TOKENS
+    }
+
+    /// Tokens in a message: its text and images, its tool calls' names and
+    /// arguments, and fixed per-message and per-call overhead
+    fn count_message(&self, message: &ChatMessage) -> usize {
+        let tool_tokens: usize = message
+            .tool_calls
+            .iter()
+            .flatten()
+            .map(|call| {
+                self.count_text(&call.function.name)
+                    + self.count_text(&call.function.arguments)
+                    + TOOL_CALL_OVERHEAD
+            })
+            .sum();
+        self.count_text(&message.content.text())
+            + message.content.image_count() * self.image_tokens()
+            + MESSAGE_OVERHEAD
+            + tool_tokens
+    }
+
+    /// Tokens in a slice of messages
+    fn count_messages(&self, messages: &[ChatMessage]) -> usize {
+        messages.iter().map(|m| self.count_message(m)).sum()
+    }
+
+    /// Get the name of this counter
+    fn name(&self) -> &str;
+}
+
+/// ~4 bytes of content per token, 1 token per role, 20 per tool call and
+/// [`DEFAULT_IMAGE_TOKENS`] per image unless set otherwise
+#[derive(Debug, Clone, Copy)]
+pub struct HeuristicCounter {
+    image_tokens: usize,
+}
+
+impl HeuristicCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Charge `tokens` for each image (builder-style)
+    pub fn with_image_tokens(mut self, tokens: usize) -> Self {
+        self.image_tokens = tokens;
+        self
+    }
+}
+
+impl Default for HeuristicCounter {
+    fn default() -> Self {
+        Self {
+            image_tokens: DEFAULT_IMAGE_TOKENS,
+        }
+    }
+}
+
+impl TokenCounter for HeuristicCounter {
+    fn count_text(&self, text: &str) -> usize {
+        text.len() / 4
+    }
+
+    fn image_tokens(&self) -> usize {
+        self.image_tokens
+    }
+
+    fn count_message(&self, message: &ChatMessage) -> usize {
+        let role_tokens = 1;
+        let tool_tokens = message
+            .tool_calls
+            .as_ref()
+            .map_or(0, |calls| calls.len() * 20);
+        self.count_text(&message.content.text())
+            + message.content.image_count() * self.image_tokens
+            + role_tokens
+            + tool_tokens
+    }
+
+    fn name(&self) -> &str {
+        "heuristic"
+    }
+}
+
+/// The counter strategies use unless given another
+#[cfg(feature = "workflow")]
+pub(crate) fn default_counter() -> std::sync::Arc<dyn TokenCounter> {
+    std::sync::Arc::new(HeuristicCounter::default())
+}
+
+#[cfg(feature = "tiktoken")]
+pub use tiktoken::{Encoding, TiktokenCounter};
+
+#[cfg(feature = "tiktoken")]
+mod tiktoken {
+    use super::TokenCounter;
+    use tiktoken_rs::CoreBPE;
+
+    /// A tiktoken encoding
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub enum Encoding {
+        /// GPT-3.5 and GPT-4
+        #[default]
+        Cl100kBase,
+        /// GPT-4o and later
+        O200kBase,
+    }
+
+    impl Encoding {
+        pub fn name(&self) -> &'static str {
+            match self {
+                Encoding::Cl100kBase => "cl100k_base",
+                Encoding::O200kBase => "o200k_base",
+            }
+        }
+
+        fn bpe(&self) -> &'static CoreBPE {
+            match self {
+                Encoding::Cl100kBase => tiktoken_rs::cl100k_base_singleton(),
+                Encoding::O200kBase => tiktoken_rs::o200k_base_singleton(),
+            }
+        }
+    }
+
+    /// Byte-pair encoding with OpenAI's published ranks, bundled by
+    /// `tiktoken-rs`
+    ///
+    /// Special tokens are not recognized; text that spells one is counted
+    /// as ordinary text. An encoding's ranks are loaded once per process,
+    /// the first time a counter for it is created.
+    pub struct TiktokenCounter {
+        encoding: Encoding,
+        bpe: &'static CoreBPE,
+        image_tokens: usize,
+    }
+
+    impl TiktokenCounter {
+        /// Count with `cl100k_base`
+        pub fn new() -> Self {
+            Self::with_encoding(Encoding::default())
+        }
+
+        /// Count with `encoding`
+        pub fn with_encoding(encoding: Encoding) -> Self {
+            Self {
+                encoding,
+                bpe: encoding.bpe(),
+                image_tokens: super::DEFAULT_IMAGE_TOKENS,
+            }
+        }
+
+        /// Charge `tokens` for each image (builder-style)
+        pub fn with_image_tokens(mut self, tokens: usize) -> Self {
+            self.image_tokens = tokens;
+            self
+        }
+
+        pub fn encoding(&self) -> Encoding {
+            self.encoding
+        }
+
+        /// Token ranks for `text`
+        pub fn encode(&self, text: &str) -> Vec<u32> {
+            self.bpe.encode_ordinary(text)
+        }
+    }
+
+    impl Default for TiktokenCounter {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl TokenCounter for TiktokenCounter {
+        fn count_text(&self, text: &str) -> usize {
+            self.encode(text).len()
+        }
+
+        fn image_tokens(&self) -> usize {
+            self.image_tokens
+        }
+
+        fn name(&self) -> &str {
+            self.encoding.name()
+        }
+    }
+
+    impl std::fmt::Debug for TiktokenCounter {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("TiktokenCounter")
+                .field("encoding", &self.encoding)
+                .field("image_tokens", &self.image_tokens)
+                .finish()
+        }
+    }
+}
+
+/// Markdown with a fenced code block
+#[cfg(all(test, feature = "tiktoken"))]
+pub(crate) const CODE_HEAVY: &str = "Here is the fix:\n\n```rust\nfn main() {\n    let v: Vec<u8> = (0..=9).map(|i| i * 2).collect();\n    println!(\"{:?}\", &v[1..]);\n}\n```\n";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::types::{FunctionCall, ToolCall};
+
+    fn call(arguments: &str) -> ToolCall {
+        ToolCall {
+            id: "call_1".to_string(),
+            r#type: "function".to_string(),
+            function: FunctionCall {
+                name: "search".to_string(),
+                arguments: arguments.to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_heuristic_matches_legacy_estimate() {
+        let message = ChatMessage::assistant_with_tool_calls("hello world", vec![call("{}")]);
+        assert_eq!(
+            HeuristicCounter::default().count_message(&message),
+            11 / 4 + 1 + 20
+        );
+    }
+
+    #[test]
+    fn test_images_cost_a_flat_amount() {
+        use crate::llm::types::ContentPart;
+
+        let message = ChatMessage::user_with_images(
+            "hello world",
+            [ContentPart::image_base64("image/png", "iVBORw0KGgo=")],
+        );
+        assert_eq!(
+            HeuristicCounter::default().count_message(&message),
+            11 / 4 + 1 + DEFAULT_IMAGE_TOKENS
+        );
+        assert_eq!(
+            HeuristicCounter::new()
+                .with_image_tokens(85)
+                .count_message(&message),
+            11 / 4 + 1 + 85
+        );
+    }
+
+    #[cfg(feature = "tiktoken")]
+    #[test]
+    fn test_tiktoken_matches_known_counts() {
+        let counter = TiktokenCounter::new();
+        assert_eq!(counter.name(), "cl100k_base");
+        assert_eq!(counter.encode("hello world"), vec![15339, 1917]);
+        assert_eq!(
+            counter.encode("tiktoken is great!"),
+            vec![83, 1609, 5963, 374, 2294, 0]
+        );
+
+        let counter = TiktokenCounter::with_encoding(Encoding::O200kBase);
+        assert_eq!(counter.name(), "o200k_base");
+        assert_eq!(counter.count_text("hello world"), 2);
+    }
+
+    #[cfg(feature = "tiktoken")]
+    #[test]
+    fn test_tool_call_arguments_are_tokenized() {
+        let counter = TiktokenCounter::new();
+        let arguments = r#"{"query": "rust async cancellation", "limit": 25}"#;
+        let small = ChatMessage::assistant_with_tool_calls("", vec![call("{}")]);
+        let large = ChatMessage::assistant_with_tool_calls("", vec![call(arguments)]);
+        assert_eq!(counter.count_text("{}"), 1);
+        assert_eq!(counter.count_text(arguments), 14);
+        assert_eq!(
+            counter.count_message(&large),
+            counter.count_message(&small) + 13
+        );
+        // The heuristic charges both the same
+        assert_eq!(
+            HeuristicCounter::default().count_message(&small),
+            HeuristicCounter::default().count_message(&large)
+        );
+    }
+
+    #[cfg(feature = "tiktoken")]
+    #[test]
+    fn test_code_counts_higher_than_heuristic() {
+        let counter = TiktokenCounter::new();
+        let heuristic = HeuristicCounter::default().count_text(CODE_HEAVY);
+        let real = counter.count_text(CODE_HEAVY);
+        assert_eq!(real, 52);
+        assert_eq!(heuristic, 32);
+    }
+}
@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
 
 #[cfg(test)]
 #[path = "types_test.rs"]
@@ -15,11 +18,215 @@ pub enum Role {
     Tool,
 }
 
+/// Whether the model may call the tools a request offers
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolChoice {
+    /// The model decides
+    Auto,
+    /// The model must answer without calling a tool
+    None,
+}
+
+/// Prompt tokens an image is estimated at: OpenAI's cost for a 1024x1024
+/// image at high detail, in the same range as other vision models
+pub const DEFAULT_IMAGE_TOKENS: usize = 765;
+
+/// One piece of a multimodal message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text {
+        text: String,
+    },
+    /// An image the provider fetches itself
+    ImageUrl {
+        url: String,
+    },
+    /// An image sent inline, base64-encoded
+    ImageBase64 {
+        /// e.g. `image/png`
+        mime_type: String,
+        data: String,
+    },
+}
+
+impl ContentPart {
+    pub fn text(text: impl Into<String>) -> Self {
+        ContentPart::Text { text: text.into() }
+    }
+
+    pub fn image_url(url: impl Into<String>) -> Self {
+        ContentPart::ImageUrl { url: url.into() }
+    }
+
+    pub fn image_base64(mime_type: impl Into<String>, data: impl Into<String>) -> Self {
+        ContentPart::ImageBase64 {
+            mime_type: mime_type.into(),
+            data: data.into(),
+        }
+    }
+
+    pub fn is_image(&self) -> bool {
+        !matches!(self, ContentPart::Text { .. })
+    }
+
+    /// The image's URL, with inline images as a `data:` URL
+    pub fn image_data_url(&self) -> Option<Cow<'_, str>> {
+        match self {
+            ContentPart::Text { .. } => None,
+            ContentPart::ImageUrl { url } => Some(Cow::Borrowed(url)),
+            ContentPart::ImageBase64 { mime_type, data } => {
+                Some(Cow::Owned(format!("data:{};base64,{}", mime_type, data)))
+            }
+        }
+    }
+}
+
+/// What a message says: plain text, or text and images
+///
+/// Plain text serializes as a bare string, as `content` always has, so
+/// older transcripts and checkpoints still load.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+impl MessageContent {
+    /// The message's text; the text parts joined by newlines if it has parts
+    pub fn text(&self) -> Cow<'_, str> {
+        match self {
+            MessageContent::Text(text) => Cow::Borrowed(text),
+            MessageContent::Parts(parts) => {
+                let mut texts = parts.iter().filter_map(|part| match part {
+                    ContentPart::Text { text } => Some(text.as_str()),
+                    _ => None,
+                });
+                match (texts.next(), texts.next()) {
+                    (None, _) => Cow::Borrowed(""),
+                    (Some(only), None) => Cow::Borrowed(only),
+                    (Some(first), Some(second)) => {
+                        let mut joined = format!("{}\n{}", first, second);
+                        for text in texts {
+                            joined.push('\n');
+                            joined.push_str(text);
+                        }
+                        Cow::Owned(joined)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether there is neither text nor an image
+    pub fn is_empty(&self) -> bool {
+        match self {
+            MessageContent::Text(text) => text.is_empty(),
+            MessageContent::Parts(parts) => parts.iter().all(|part| match part {
+                ContentPart::Text { text } => text.is_empty(),
+                _ => false,
+            }),
+        }
+    }
+
+    /// The parts, with plain text as a single text part
+    pub fn parts(&self) -> Cow<'_, [ContentPart]> {
+        match self {
+            MessageContent::Text(text) => Cow::Owned(vec![ContentPart::text(text.clone())]),
+            MessageContent::Parts(parts) => Cow::Borrowed(parts),
+        }
+    }
+
+    pub fn images(&self) -> impl Iterator<Item = &ContentPart> {
+        let parts = match self {
+            MessageContent::Text(_) => &[][..],
+            MessageContent::Parts(parts) => parts.as_slice(),
+        };
+        parts.iter().filter(|part| part.is_image())
+    }
+
+    pub fn image_count(&self) -> usize {
+        self.images().count()
+    }
+
+    pub fn has_images(&self) -> bool {
+        self.images().next().is_some()
+    }
+
+    /// Append text, to the last text part if the content has parts
+    pub fn push_str(&mut self, more: &str) {
+        match self {
+            MessageContent::Text(text) => text.push_str(more),
+            MessageContent::Parts(parts) => match parts.last_mut() {
+                Some(ContentPart::Text { text }) => text.push_str(more),
+                _ => parts.push(ContentPart::text(more)),
+            },
+        }
+    }
+}
+
+impl Default for MessageContent {
+    fn default() -> Self {
+        MessageContent::Text(String::new())
+    }
+}
+
+impl fmt::Display for MessageContent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text())
+    }
+}
+
+impl From<String> for MessageContent {
+    fn from(text: String) -> Self {
+        MessageContent::Text(text)
+    }
+}
+
+impl From<&String> for MessageContent {
+    fn from(text: &String) -> Self {
+        MessageContent::Text(text.clone())
+    }
+}
+
+impl From<&str> for MessageContent {
+    fn from(text: &str) -> Self {
+        MessageContent::Text(text.to_string())
+    }
+}
+
+impl From<Vec<ContentPart>> for MessageContent {
+    fn from(parts: Vec<ContentPart>) -> Self {
+        MessageContent::Parts(parts)
+    }
+}
+
+/// Text-only content equals its text
+impl PartialEq<str> for MessageContent {
+    fn eq(&self, other: &str) -> bool {
+        !self.has_images() && self.text() == other
+    }
+}
+
+impl PartialEq<&str> for MessageContent {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl PartialEq<String> for MessageContent {
+    fn eq(&self, other: &String) -> bool {
+        self == other.as_str()
+    }
+}
+
 /// A single message in a chat conversation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub role: Role,
-    pub content: String,
+    pub content: MessageContent,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<ToolCall>>,
@@ -38,7 +245,7 @@ pub struct ChatMessage {
 }
 
 impl ChatMessage {
-    pub fn system(content: impl Into<String>) -> Self {
+    pub fn system(content: impl Into<MessageContent>) -> Self {
         Self {
             role: Role::System,
             content: content.into(),
@@ -49,7 +256,7 @@ impl ChatMessage {
         }
     }
 
-    pub fn user(content: impl Into<String>) -> Self {
+    pub fn user(content: impl Into<MessageContent>) -> Self {
         Self {
             role: Role::User,
             content: content.into(),
@@ -60,7 +267,17 @@ impl ChatMessage {
         }
     }
 
-    pub fn assistant(content: impl Into<String>) -> Self {
+    /// A user turn of `text` followed by `images`
+    pub fn user_with_images(
+        text: impl Into<String>,
+        images: impl IntoIterator<Item = ContentPart>,
+    ) -> Self {
+        let mut parts = vec![ContentPart::text(text)];
+        parts.extend(images);
+        Self::user(parts)
+    }
+
+    pub fn assistant(content: impl Into<MessageContent>) -> Self {
         Self {
             role: Role::Assistant,
             content: content.into(),
@@ -72,7 +289,7 @@ impl ChatMessage {
     }
 
     pub fn assistant_with_tool_calls(
-        content: impl Into<String>,
+        content: impl Into<MessageContent>,
         tool_calls: Vec<ToolCall>,
     ) -> Self {
         Self {
@@ -85,7 +302,10 @@ impl ChatMessage {
         }
     }
 
-    pub fn tool_result(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
+    pub fn tool_result(
+        tool_call_id: impl Into<String>,
+        content: impl Into<MessageContent>,
+    ) -> Self {
         Self {
             role: Role::Tool,
             content: content.into(),
@@ -124,6 +344,41 @@ pub struct ChatRequest {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<JsonValue>>,
+
+    /// Whether the model may call `tools`; the provider's default (auto)
+    /// when unset. `ToolChoice::None` keeps the definitions in the request,
+    /// and so in the prompt the provider caches, while ruling out calls.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+
+    /// Reasoning effort for models that support it (e.g. OpenAI o-series).
+    /// Usually set through `GenericChatClient::apply_effort`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<String>,
+    /// Sampling seed for providers that support reproducible output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+
+    /// Sequences that end the response when generated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+
+    /// Skip [`super::CachedChatClient`]'s cache: always ask the provider,
+    /// and don't store the answer
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_cache: bool,
+
+    /// Who sent the request, e.g. `workflow_id`, `agent` and `iteration`,
+    /// for wrapping clients such as [`super::LoggingChatClient`]; never
+    /// sent to the provider
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
 }
 
 impl ChatRequest {
@@ -134,6 +389,14 @@ impl ChatRequest {
             max_tokens: None,
             top_p: None,
             tools: None,
+            tool_choice: None,
+            reasoning_effort: None,
+            seed: None,
+            stop: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            no_cache: false,
+            metadata: HashMap::new(),
         }
     }
 
@@ -156,6 +419,166 @@ impl ChatRequest {
         self.tools = Some(tools);
         self
     }
+
+    pub fn with_tool_choice(mut self, choice: ToolChoice) -> Self {
+        self.tool_choice = Some(choice);
+        self
+    }
+
+    /// Whether the model may call a tool: some are offered and
+    /// `tool_choice` isn't `None`
+    pub fn offers_tools(&self) -> bool {
+        self.tools.is_some() && self.tool_choice != Some(ToolChoice::None)
+    }
+
+    pub fn with_reasoning_effort(mut self, effort: impl Into<String>) -> Self {
+        self.reasoning_effort = Some(effort.into());
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn with_stop(mut self, stop: Vec<String>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// Bypass any response cache for this request
+    pub fn with_no_cache(mut self) -> Self {
+        self.no_cache = true;
+        self
+    }
+
+    /// Tag the request with `key` = `value` (see [`metadata`](Self::metadata))
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Set every parameter `sampling` sets, leaving the others
+    pub fn with_sampling(mut self, sampling: &SamplingParams) -> Self {
+        let sampling = sampling.clone();
+        self.temperature = sampling.temperature.or(self.temperature);
+        self.top_p = sampling.top_p.or(self.top_p);
+        self.max_tokens = sampling.max_tokens.or(self.max_tokens);
+        self.seed = sampling.seed.or(self.seed);
+        self.stop = sampling.stop.or(self.stop);
+        self.frequency_penalty = sampling.frequency_penalty.or(self.frequency_penalty);
+        self.presence_penalty = sampling.presence_penalty.or(self.presence_penalty);
+        self
+    }
+}
+
+/// Sampling parameters for an agent's LLM requests; unset ones are left to
+/// the provider
+///
+/// Providers skip the parameters their API doesn't take, see
+/// `docs/LLM_MODULE.md`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SamplingParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+
+    /// Fixed seed, for reproducible output where the provider supports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+
+    /// Cut streamed responses after this many characters, dropping the
+    /// request; never sent to the provider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_response_chars: Option<usize>,
+}
+
+impl SamplingParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `llm.default_temperature` and `llm.default_max_tokens`
+    pub fn from_config(config: &crate::config::LlmConfig) -> Self {
+        Self {
+            temperature: Some(config.default_temperature),
+            max_tokens: config.default_max_tokens,
+            ..Self::default()
+        }
+    }
+
+    /// These parameters, with `defaults`' for the ones left unset
+    pub fn or(self, defaults: &SamplingParams) -> Self {
+        let defaults = defaults.clone();
+        Self {
+            temperature: self.temperature.or(defaults.temperature),
+            top_p: self.top_p.or(defaults.top_p),
+            max_tokens: self.max_tokens.or(defaults.max_tokens),
+            seed: self.seed.or(defaults.seed),
+            stop: self.stop.or(defaults.stop),
+            frequency_penalty: self.frequency_penalty.or(defaults.frequency_penalty),
+            presence_penalty: self.presence_penalty.or(defaults.presence_penalty),
+            max_response_chars: self.max_response_chars.or(defaults.max_response_chars),
+        }
+    }
+
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    pub fn max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn stop<I, S>(mut self, stop: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.stop = Some(stop.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn frequency_penalty(mut self, penalty: f32) -> Self {
+        self.frequency_penalty = Some(penalty);
+        self
+    }
+
+    pub fn presence_penalty(mut self, penalty: f32) -> Self {
+        self.presence_penalty = Some(penalty);
+        self
+    }
+
+    pub fn max_response_chars(mut self, max: usize) -> Self {
+        self.max_response_chars = Some(max);
+        self
+    }
 }
 
 /// Response from chat completion
@@ -172,10 +595,23 @@ pub struct ChatResponse {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<ToolCall>>,
+
+    /// Repairs made by response validation (see [`super::validation`])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+
+    /// Which provider served the request, set by
+    /// [`super::FallbackChatClient`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+
+    /// Providers that failed before this one answered
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failovers: Vec<super::fallback::Failover>,
 }
 
 /// A tool call request from the LLM
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ToolCall {
     pub id: String,
     pub r#type: String, // Usually "function"
@@ -183,7 +619,7 @@ pub struct ToolCall {
 }
 
 /// Function call details
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FunctionCall {
     pub name: String,
     pub arguments: String, // JSON string
@@ -195,4 +631,9 @@ pub struct Usage {
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
     pub total_tokens: u32,
+
+    /// Portion of `completion_tokens` spent on hidden reasoning/thinking,
+    /// when the provider reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_tokens: Option<u32>,
 }
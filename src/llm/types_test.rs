@@ -1,6 +1,9 @@
 #[cfg(test)]
 mod tests {
-    use crate::llm::types::{ChatMessage, ChatRequest, ChatResponse, Role, Usage};
+    use crate::llm::types::{
+        ChatMessage, ChatRequest, ChatResponse, ContentPart, MessageContent, Role, Usage,
+    };
+    use serde_json::json;
 
     #[test]
     fn test_chat_message_creation() {
@@ -48,9 +51,13 @@ mod tests {
                 prompt_tokens: 10,
                 completion_tokens: 20,
                 total_tokens: 30,
+                reasoning_tokens: None,
             }),
             finish_reason: Some("stop".to_string()),
             tool_calls: None,
+            warnings: vec![],
+            provider: None,
+            failovers: Vec::new(),
         };
 
         assert_eq!(response.content, "Test response");
@@ -86,6 +93,58 @@ mod tests {
         assert!(json.contains("Hello"));
     }
 
+    #[test]
+    fn test_text_content_serializes_as_a_plain_string() {
+        let msg: ChatMessage =
+            serde_json::from_value(json!({ "role": "user", "content": "Hello" })).unwrap();
+        assert_eq!(msg.content, MessageContent::Text("Hello".to_string()));
+        assert_eq!(serde_json::to_value(&msg).unwrap()["content"], "Hello");
+    }
+
+    #[test]
+    fn test_image_parts_round_trip() {
+        let msg = ChatMessage::user_with_images(
+            "What is this?",
+            [
+                ContentPart::image_url("https://example.com/otter.jpg"),
+                ContentPart::image_base64("image/png", "iVBORw0KGgo="),
+            ],
+        );
+        let value = serde_json::to_value(&msg).unwrap();
+        assert_eq!(
+            value["content"],
+            json!([
+                { "type": "text", "text": "What is this?" },
+                { "type": "image_url", "url": "https://example.com/otter.jpg" },
+                { "type": "image_base64", "mime_type": "image/png", "data": "iVBORw0KGgo=" },
+            ])
+        );
+
+        let loaded: ChatMessage = serde_json::from_value(value).unwrap();
+        assert_eq!(loaded.content, msg.content);
+        assert_eq!(loaded.content.text(), "What is this?");
+        assert_eq!(loaded.content.image_count(), 2);
+        // Only text-only content compares equal to a string
+        assert_ne!(loaded.content, "What is this?");
+    }
+
+    #[test]
+    fn test_image_data_url() {
+        assert_eq!(
+            ContentPart::image_base64("image/png", "iVBORw0KGgo=")
+                .image_data_url()
+                .unwrap(),
+            "data:image/png;base64,iVBORw0KGgo="
+        );
+        assert_eq!(
+            ContentPart::image_url("https://example.com/a.jpg")
+                .image_data_url()
+                .unwrap(),
+            "https://example.com/a.jpg"
+        );
+        assert!(ContentPart::text("hi").image_data_url().is_none());
+    }
+
     #[test]
     fn test_request_serialization() {
         let messages = vec![ChatMessage::user("Test")];
@@ -102,6 +161,7 @@ mod tests {
             prompt_tokens: 15,
             completion_tokens: 25,
             total_tokens: 40,
+            reasoning_tokens: None,
         };
 
         assert_eq!(usage.total_tokens, 40);
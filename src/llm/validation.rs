@@ -0,0 +1,317 @@
+//! Validation and normalization of provider responses.
+//!
+//! OpenAI-compatible servers occasionally return malformed `tool_calls`:
+//! empty function names, arguments that aren't valid JSON, duplicate or
+//! missing ids, or `finish_reason: "tool_calls"` with no calls at all.
+//! Providers deserialize tool calls into the permissive [`RawToolCall`] and
+//! pass them through [`ResponseValidator::normalize`], which guarantees:
+//!
+//! - `tool_calls` is either `None` or non-empty
+//! - every call has a non-empty name and a unique id
+//! - every `arguments` string is valid JSON
+//! - `finish_reason` is one of the [`FinishReason`] values
+//!
+//! [`Strictness::Strict`] rejects a malformed response with
+//! [`LlmError::ParseError`]; [`Strictness::Lenient`] repairs what it can,
+//! drops what it can't, and describes each change in
+//! [`ChatResponse::warnings`](super::ChatResponse::warnings).
+
+use super::types::{FunctionCall, ToolCall};
+use super::{LlmError, LlmResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// How malformed responses are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strictness {
+    /// Fail with [`LlmError::ParseError`] describing every problem
+    Strict,
+    /// Repair or drop malformed entries and record warnings
+    #[default]
+    Lenient,
+}
+
+/// Canonical finish reasons
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    Stop,
+    Length,
+    ToolCalls,
+    ContentFilter,
+    Other,
+}
+
+impl FinishReason {
+    /// Map a provider's finish reason onto the canonical set
+    pub fn parse(reason: &str) -> Self {
+        match reason.trim().to_ascii_lowercase().as_str() {
+            "stop" | "eos" | "end_turn" | "stop_sequence" | "complete" => FinishReason::Stop,
+            "length" | "max_tokens" | "max_output_tokens" => FinishReason::Length,
+            "tool_calls" | "tool_call" | "function_call" | "tool_use" => FinishReason::ToolCalls,
+            "content_filter" | "safety" | "recitation" => FinishReason::ContentFilter,
+            _ => FinishReason::Other,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FinishReason::Stop => "stop",
+            FinishReason::Length => "length",
+            FinishReason::ToolCalls => "tool_calls",
+            FinishReason::ContentFilter => "content_filter",
+            FinishReason::Other => "other",
+        }
+    }
+}
+
+/// A tool call as a provider sent it, before validation
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RawToolCall {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub r#type: Option<String>,
+    #[serde(default)]
+    pub function: Option<RawFunctionCall>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RawFunctionCall {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Usually a JSON-encoded string; some servers send an object
+    #[serde(default)]
+    pub arguments: Option<Value>,
+}
+
+/// Tool calls and finish reason after validation
+#[derive(Debug, Clone, Default)]
+pub struct NormalizedResponse {
+    pub tool_calls: Option<Vec<ToolCall>>,
+    pub finish_reason: Option<String>,
+    pub warnings: Vec<String>,
+}
+
+/// Shared validation pass used by every provider after parsing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResponseValidator {
+    pub strictness: Strictness,
+}
+
+impl ResponseValidator {
+    pub fn new(strictness: Strictness) -> Self {
+        Self { strictness }
+    }
+
+    pub fn normalize(
+        &self,
+        tool_calls: Option<Vec<RawToolCall>>,
+        finish_reason: Option<&str>,
+    ) -> LlmResult<NormalizedResponse> {
+        let mut problems = Vec::new();
+        let mut calls = Vec::new();
+        let mut seen_ids = HashSet::new();
+
+        for (index, raw) in tool_calls.unwrap_or_default().into_iter().enumerate() {
+            let function = raw.function.unwrap_or_default();
+            let name = function.name.unwrap_or_default().trim().to_string();
+            if name.is_empty() {
+                problems.push(format!(
+                    "tool call {} has an empty function name; dropped",
+                    index
+                ));
+                continue;
+            }
+
+            let arguments = match repair_arguments(function.arguments) {
+                Ok((arguments, None)) => arguments,
+                Ok((arguments, Some(repair))) => {
+                    problems.push(format!("tool call {} (`{}`): {}", index, name, repair));
+                    arguments
+                }
+                Err(reason) => {
+                    problems.push(format!(
+                        "tool call {} (`{}`): {}; dropped",
+                        index, name, reason
+                    ));
+                    continue;
+                }
+            };
+
+            let id = match raw
+                .id
+                .map(|id| id.trim().to_string())
+                .filter(|id| !id.is_empty())
+            {
+                Some(id) if seen_ids.contains(&id) => {
+                    let unique = unique_id(&id, &seen_ids);
+                    problems.push(format!(
+                        "tool call {} (`{}`) reuses id `{}`; renamed to `{}`",
+                        index, name, id, unique
+                    ));
+                    unique
+                }
+                Some(id) => id,
+                None => {
+                    let synthesized = unique_id(&format!("call_{}", index), &seen_ids);
+                    problems.push(format!(
+                        "tool call {} (`{}`) has no id; assigned `{}`",
+                        index, name, synthesized
+                    ));
+                    synthesized
+                }
+            };
+            seen_ids.insert(id.clone());
+
+            calls.push(ToolCall {
+                id,
+                r#type: raw
+                    .r#type
+                    .filter(|t| !t.is_empty())
+                    .unwrap_or_else(|| "function".to_string()),
+                function: FunctionCall { name, arguments },
+            });
+        }
+
+        let mut reason = finish_reason.map(FinishReason::parse);
+        match (reason, calls.is_empty()) {
+            (Some(FinishReason::ToolCalls), true) => {
+                problems.push("finish_reason is tool_calls but no usable tool calls were returned; treated as stop".to_string());
+                reason = Some(FinishReason::Stop);
+            }
+            (None, false) => reason = Some(FinishReason::ToolCalls),
+            _ => {}
+        }
+
+        if self.strictness == Strictness::Strict && !problems.is_empty() {
+            return Err(LlmError::ParseError(format!(
+                "malformed response: {}",
+                problems.join("; ")
+            )));
+        }
+
+        Ok(NormalizedResponse {
+            tool_calls: (!calls.is_empty()).then_some(calls),
+            finish_reason: reason.map(|r| r.as_str().to_string()),
+            warnings: problems,
+        })
+    }
+}
+
+/// Turn `arguments` into a valid JSON string. Returns the repair made, if
+/// any, or why the arguments couldn't be salvaged.
+fn repair_arguments(arguments: Option<Value>) -> Result<(String, Option<String>), String> {
+    let text = match arguments {
+        None | Some(Value::Null) => {
+            return Ok((
+                "{}".to_string(),
+                Some("missing arguments; assumed {}".to_string()),
+            ))
+        }
+        Some(Value::String(text)) => text,
+        Some(other) => {
+            return Ok((
+                other.to_string(),
+                Some("arguments sent as JSON rather than a string; re-encoded".to_string()),
+            ))
+        }
+    };
+
+    if text.trim().is_empty() {
+        return Ok((
+            "{}".to_string(),
+            Some("empty arguments; assumed {}".to_string()),
+        ));
+    }
+    if serde_json::from_str::<Value>(&text).is_ok() {
+        return Ok((text, None));
+    }
+
+    // Common damage: code fences, or chatter around a single object
+    let unfenced = text
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim();
+    let candidate = match (unfenced.find('{'), unfenced.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &unfenced[start..=end],
+        _ => unfenced,
+    };
+    match serde_json::from_str::<Value>(candidate) {
+        Ok(value) => Ok((
+            value.to_string(),
+            Some("arguments were not valid JSON; extracted the enclosed object".to_string()),
+        )),
+        Err(e) => Err(format!("arguments are not valid JSON ({})", e)),
+    }
+}
+
+fn unique_id(base: &str, taken: &HashSet<String>) -> String {
+    if !taken.contains(base) {
+        return base.to_string();
+    }
+    (1..)
+        .map(|n| format!("{}_{}", base, n))
+        .find(|id| !taken.contains(id))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn raw(calls: Value) -> Option<Vec<RawToolCall>> {
+        Some(serde_json::from_value(calls).unwrap())
+    }
+
+    #[test]
+    fn test_well_formed_calls_pass_untouched() {
+        let normalized = ResponseValidator::new(Strictness::Strict)
+            .normalize(
+                raw(json!([{"id": "a", "type": "function",
+                    "function": {"name": "search", "arguments": "{\"q\":1}"}}])),
+                Some("tool_calls"),
+            )
+            .unwrap();
+
+        assert!(normalized.warnings.is_empty());
+        let calls = normalized.tool_calls.unwrap();
+        assert_eq!(calls[0].function.arguments, "{\"q\":1}");
+        assert_eq!(normalized.finish_reason.as_deref(), Some("tool_calls"));
+    }
+
+    #[test]
+    fn test_argument_repairs() {
+        let cases = [
+            (json!(""), Some("{}")),
+            (json!(null), Some("{}")),
+            (json!({"q": 1}), Some("{\"q\":1}")),
+            (json!("```json\n{\"q\": 1}\n```"), Some("{\"q\":1}")),
+            (json!("Sure! {\"q\": 1} hope that helps"), Some("{\"q\":1}")),
+            (json!("{\"q\": "), None),
+        ];
+        for (arguments, expected) in cases {
+            let result = repair_arguments(Some(arguments.clone()));
+            match expected {
+                Some(expected) => assert_eq!(result.unwrap().0, expected, "{}", arguments),
+                None => assert!(result.is_err(), "{}", arguments),
+            }
+        }
+    }
+
+    #[test]
+    fn test_finish_reasons_are_canonical() {
+        assert_eq!(FinishReason::parse("end_turn"), FinishReason::Stop);
+        assert_eq!(FinishReason::parse("MAX_TOKENS"), FinishReason::Length);
+        assert_eq!(
+            FinishReason::parse("function_call"),
+            FinishReason::ToolCalls
+        );
+        assert_eq!(FinishReason::parse("weird"), FinishReason::Other);
+    }
+}
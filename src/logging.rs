@@ -1,6 +1,11 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::level_filters::LevelFilter;
 
 /// Simple file logger for workflow debugging
 pub struct FileLogger {
@@ -17,6 +22,31 @@ impl FileLogger {
         })
     }
 
+    /// Create a file logger for `file_name` inside `directory`, creating the
+    /// directory if needed
+    ///
+    /// The name may use either separator but must stay inside the
+    /// directory; see [`Sandbox`](crate::paths::Sandbox).
+    pub fn in_directory(
+        directory: impl AsRef<std::path::Path>,
+        file_name: &str,
+    ) -> std::io::Result<Self> {
+        std::fs::create_dir_all(&directory)?;
+        let path = crate::paths::Sandbox::new(directory)?.resolve(file_name)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::new(path)
+    }
+
+    /// Create a file logger for `file_name` in the configured log directory
+    pub fn from_config(
+        config: &crate::config::LoggingConfig,
+        file_name: &str,
+    ) -> std::io::Result<Self> {
+        Self::in_directory(&config.directory, file_name)
+    }
+
     /// Log a message
     pub fn log(&self, message: impl AsRef<str>) {
         if let Ok(mut file) = self.file.lock() {
@@ -41,3 +71,397 @@ impl Clone for FileLogger {
         }
     }
 }
+
+/// File [`init_tracing`] writes to inside the configured log directory
+pub const TRACE_FILE_NAME: &str = "trace.log";
+
+/// Install a [`TraceSubscriber`] built from `config` as the global `tracing`
+/// subscriber, writing to [`TRACE_FILE_NAME`] in `config.directory`, or to
+/// stderr if the directory is empty
+///
+/// `RUST_LOG` overrides `config.level` when set. Fails if the file can't be
+/// opened or a global subscriber is already installed. Library code never
+/// calls this: without a subscriber, the crate's spans and events do nothing.
+pub fn init_tracing(config: &crate::config::LoggingConfig) -> std::io::Result<()> {
+    let mut subscriber = TraceSubscriber::from_config(config);
+    if !config.directory.is_empty() {
+        std::fs::create_dir_all(&config.directory)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(std::path::Path::new(&config.directory).join(TRACE_FILE_NAME))?;
+        subscriber = subscriber.with_writer(file);
+    }
+    tracing::subscriber::set_global_default(subscriber)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::AlreadyExists, e))
+}
+
+/// A small `tracing` subscriber: one line per event and per closed span, as
+/// text or JSON, written to stderr unless told otherwise
+///
+/// Filtering takes `RUST_LOG`-style directives: a default level and
+/// `target=level` overrides, e.g. `warn,agent_runtime=debug`. The most
+/// specific matching target wins. A closed span's line carries its recorded
+/// fields and how long it was open (`elapsed_ms`).
+///
+/// For OpenTelemetry export, use `tracing-opentelemetry` instead; this is
+/// for local runs and tests.
+pub struct TraceSubscriber {
+    filter: TraceFilter,
+    json: bool,
+    writer: Mutex<Box<dyn Write + Send>>,
+    spans: Mutex<HashMap<u64, SpanRecord>>,
+    next_id: AtomicU64,
+}
+
+struct SpanRecord {
+    metadata: &'static tracing::Metadata<'static>,
+    parent: Option<u64>,
+    fields: serde_json::Map<String, serde_json::Value>,
+    opened: Instant,
+    /// Handles still open; the span closes when the last one is dropped
+    refs: usize,
+}
+
+thread_local! {
+    /// Entered spans on this thread, innermost last, by subscriber address
+    static ENTERED: RefCell<Vec<(usize, u64)>> = const { RefCell::new(Vec::new()) };
+}
+
+impl TraceSubscriber {
+    /// Filter with `directives`, e.g. `info` or `warn,agent_runtime=debug`,
+    /// writing text to stderr. Unparseable directives are ignored.
+    pub fn new(directives: &str) -> Self {
+        Self {
+            filter: TraceFilter::parse(directives),
+            json: false,
+            writer: Mutex::new(Box::new(std::io::stderr())),
+            spans: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Filter with `RUST_LOG`, or `config.level` when it isn't set; write
+    /// JSON if `config.json_format`
+    pub fn from_config(config: &crate::config::LoggingConfig) -> Self {
+        let directives = std::env::var("RUST_LOG").unwrap_or_else(|_| config.level.clone());
+        Self::new(&directives).with_json(config.json_format)
+    }
+
+    /// Write one JSON object per line instead of text (builder-style)
+    pub fn with_json(mut self, json: bool) -> Self {
+        self.json = json;
+        self
+    }
+
+    /// Write to `writer` instead of stderr (builder-style)
+    pub fn with_writer(mut self, writer: impl Write + Send + 'static) -> Self {
+        self.writer = Mutex::new(Box::new(writer));
+        self
+    }
+
+    fn key(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    fn current(&self) -> Option<u64> {
+        let key = self.key();
+        ENTERED.with(|entered| {
+            entered
+                .borrow()
+                .iter()
+                .rev()
+                .find(|(owner, _)| *owner == key)
+                .map(|(_, id)| *id)
+        })
+    }
+
+    /// `(name, fields)` of `id` and its ancestors, outermost first
+    fn scope(
+        spans: &HashMap<u64, SpanRecord>,
+        mut id: Option<u64>,
+    ) -> Vec<(&'static str, &serde_json::Map<String, serde_json::Value>)> {
+        let mut scope = Vec::new();
+        while let Some(record) = id.and_then(|id| spans.get(&id)) {
+            scope.push((record.metadata.name(), &record.fields));
+            id = record.parent;
+        }
+        scope.reverse();
+        scope
+    }
+
+    fn write_line(
+        &self,
+        metadata: &tracing::Metadata<'_>,
+        scope: &[(&'static str, &serde_json::Map<String, serde_json::Value>)],
+        fields: &serde_json::Map<String, serde_json::Value>,
+    ) {
+        let timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+        let line = if self.json {
+            let span_json = |(name, fields): &(&str, &serde_json::Map<_, _>)| {
+                let mut span = (*fields).clone();
+                span.insert("name".to_string(), (*name).into());
+                serde_json::Value::Object(span)
+            };
+            let mut line = serde_json::json!({
+                "timestamp": timestamp,
+                "level": metadata.level().as_str(),
+                "target": metadata.target(),
+                "fields": fields,
+            });
+            if let Some(innermost) = scope.last() {
+                line["span"] = span_json(innermost);
+                line["spans"] = scope.iter().map(span_json).collect();
+            }
+            line.to_string()
+        } else {
+            let mut line = format!(
+                "{} {:>5} {}:",
+                timestamp,
+                metadata.level(),
+                metadata.target()
+            );
+            for (name, fields) in scope {
+                line.push_str(&format!(" {}{{{}}}:", name, text_fields(fields)));
+            }
+            let message = fields.get("message").and_then(|m| m.as_str());
+            if let Some(message) = message {
+                line.push(' ');
+                line.push_str(message);
+            }
+            let rest: serde_json::Map<String, serde_json::Value> = fields
+                .iter()
+                .filter(|(key, _)| *key != "message")
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+            if !rest.is_empty() {
+                line.push(' ');
+                line.push_str(&text_fields(&rest));
+            }
+            line
+        };
+        if let Ok(mut writer) = self.writer.lock() {
+            let _ = writeln!(writer, "{}", line);
+        }
+    }
+}
+
+fn text_fields(fields: &serde_json::Map<String, serde_json::Value>) -> String {
+    fields
+        .iter()
+        .map(|(key, value)| match value {
+            serde_json::Value::String(text) if !text.contains(char::is_whitespace) => {
+                format!("{}={}", key, text)
+            }
+            other => format!("{}={}", key, other),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl tracing::Subscriber for TraceSubscriber {
+    fn register_callsite(
+        &self,
+        metadata: &'static tracing::Metadata<'static>,
+    ) -> tracing::subscriber::Interest {
+        // Other subscribers on other threads may want what this one doesn't
+        if self.filter.enabled(metadata) {
+            tracing::subscriber::Interest::always()
+        } else {
+            tracing::subscriber::Interest::sometimes()
+        }
+    }
+
+    fn enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(self.filter.max_level())
+    }
+
+    fn new_span(&self, attributes: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let parent = if attributes.is_root() {
+            None
+        } else if attributes.is_contextual() {
+            self.current()
+        } else {
+            attributes.parent().map(tracing::span::Id::into_u64)
+        };
+        let mut fields = FieldVisitor::default();
+        attributes.record(&mut fields);
+        self.spans.lock().unwrap().insert(
+            id,
+            SpanRecord {
+                metadata: attributes.metadata(),
+                parent,
+                fields: fields.0,
+                opened: Instant::now(),
+                refs: 1,
+            },
+        );
+        tracing::span::Id::from_u64(id)
+    }
+
+    fn record(&self, span: &tracing::span::Id, values: &tracing::span::Record<'_>) {
+        if let Some(record) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+            let mut fields = FieldVisitor(std::mem::take(&mut record.fields));
+            values.record(&mut fields);
+            record.fields = fields.0;
+        }
+    }
+
+    fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+    fn event(&self, event: &tracing::Event<'_>) {
+        let parent = if event.is_root() {
+            None
+        } else if event.is_contextual() {
+            self.current()
+        } else {
+            event.parent().map(tracing::span::Id::into_u64)
+        };
+        let mut fields = FieldVisitor::default();
+        event.record(&mut fields);
+        let spans = self.spans.lock().unwrap();
+        let scope = Self::scope(&spans, parent);
+        self.write_line(event.metadata(), &scope, &fields.0);
+    }
+
+    fn enter(&self, span: &tracing::span::Id) {
+        let entry = (self.key(), span.into_u64());
+        ENTERED.with(|entered| entered.borrow_mut().push(entry));
+    }
+
+    fn exit(&self, span: &tracing::span::Id) {
+        let entry = (self.key(), span.into_u64());
+        ENTERED.with(|entered| {
+            let mut entered = entered.borrow_mut();
+            if let Some(position) = entered.iter().rposition(|e| *e == entry) {
+                entered.remove(position);
+            }
+        });
+    }
+
+    fn clone_span(&self, span: &tracing::span::Id) -> tracing::span::Id {
+        if let Some(record) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+            record.refs += 1;
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: tracing::span::Id) -> bool {
+        let mut spans = self.spans.lock().unwrap();
+        let id = span.into_u64();
+        let Some(record) = spans.get_mut(&id) else {
+            return false;
+        };
+        record.refs -= 1;
+        if record.refs > 0 {
+            return false;
+        }
+
+        let metadata = record.metadata;
+        let mut fields = serde_json::Map::new();
+        fields.insert("message".to_string(), "close".into());
+        fields.insert(
+            "elapsed_ms".to_string(),
+            crate::telemetry::millis(record.opened.elapsed()).into(),
+        );
+        let scope = Self::scope(&spans, Some(id));
+        self.write_line(metadata, &scope, &fields);
+        spans.remove(&id);
+        true
+    }
+}
+
+/// Collects field values as JSON
+#[derive(Default)]
+struct FieldVisitor(serde_json::Map<String, serde_json::Value>);
+
+impl tracing::field::Visit for FieldVisitor {
+    fn record_f64(&mut self, field: &tracing::field::Field, value: f64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &tracing::field::Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value).into());
+    }
+}
+
+/// A default level plus per-target levels
+#[derive(Debug, Clone)]
+struct TraceFilter {
+    default: LevelFilter,
+    /// `(target prefix, level)`, longest prefix first
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl TraceFilter {
+    fn parse(directives: &str) -> Self {
+        let mut filter = Self {
+            default: LevelFilter::ERROR,
+            targets: Vec::new(),
+        };
+        for directive in directives.split(',').map(str::trim) {
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    if let Ok(level) = level.trim().parse() {
+                        filter.targets.push((target.trim().to_string(), level));
+                    }
+                }
+                None => {
+                    if let Ok(level) = directive.parse() {
+                        filter.default = level;
+                    }
+                }
+            }
+        }
+        filter
+            .targets
+            .sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+        filter
+    }
+
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .find(|(prefix, _)| {
+                target == prefix
+                    || target
+                        .strip_prefix(prefix.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default)
+    }
+
+    fn enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+        metadata.level() <= &self.level_for(metadata.target())
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, std::cmp::max)
+    }
+}
@@ -0,0 +1,482 @@
+//! Prometheus metrics for runtimes, agents and tools
+//!
+//! With the `metrics` feature, `Runtime::execute`, agent turns, LLM requests
+//! and tool calls record into a process-wide registry as they run. Nothing
+//! needs configuring: render the registry with [`render_prometheus`] and
+//! serve it from your own HTTP endpoint.
+//!
+//! ```rust,ignore
+//! async fn metrics() -> ([(&'static str, &'static str); 1], String) {
+//!     ([("content-type", agent_runtime::metrics::CONTENT_TYPE)], agent_runtime::metrics::render_prometheus())
+//! }
+//! ```
+//!
+//! | Family | Type | Labels |
+//! |--------|------|--------|
+//! | `agent_runtime_workflow_duration_seconds` | histogram | `outcome` |
+//! | `agent_runtime_step_duration_seconds` | histogram | `step_type`, `step`, `outcome` |
+//! | `agent_runtime_agent_duration_seconds` | histogram | `agent`, `outcome` |
+//! | `agent_runtime_llm_request_duration_seconds` | histogram | `provider`, `model` |
+//! | `agent_runtime_llm_tokens_total` | counter | `provider`, `model`, `kind` |
+//! | `agent_runtime_llm_errors_total` | counter | `provider` |
+//! | `agent_runtime_llm_cache_lookups_total` | counter | `provider`, `result` |
+//! | `agent_runtime_tool_call_duration_seconds` | histogram | `tool` |
+//! | `agent_runtime_tool_errors_total` | counter | `tool` |
+//! | `agent_runtime_retries_total` | counter | `component` |
+//! | `agent_runtime_tool_loop_detections_total` | counter | `agent`, `tool` |
+//!
+//! Without the feature every recording call is an empty inline function, so
+//! instrumented code costs nothing.
+
+use std::time::Duration;
+
+use crate::llm::types::Usage;
+
+/// `Content-Type` of [`render_prometheus`]'s output
+#[cfg(feature = "metrics")]
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Every metric recorded so far, in the Prometheus text exposition format
+#[cfg(feature = "metrics")]
+pub fn render_prometheus() -> String {
+    imp::registry().render()
+}
+
+/// `Runtime::execute` finished a workflow run
+#[cfg(feature = "workflow")]
+pub(crate) fn workflow_finished(state: &crate::workflow::WorkflowState, elapsed: Duration) {
+    #[cfg(feature = "metrics")]
+    imp::observe(&imp::WORKFLOW_DURATION, &[outcome_label(state)], elapsed);
+    #[cfg(not(feature = "metrics"))]
+    let _ = (state, elapsed);
+}
+
+/// The runtime finished running a step
+#[cfg(feature = "workflow")]
+pub(crate) fn step_finished(
+    step_type: &crate::workflow::StepType,
+    step: &str,
+    succeeded: bool,
+    elapsed: Duration,
+) {
+    #[cfg(feature = "metrics")]
+    {
+        let step_type = match step_type {
+            crate::workflow::StepType::Custom(name) => name.clone(),
+            other => serde_json::to_value(other)
+                .ok()
+                .and_then(|value| value.as_str().map(str::to_string))
+                .unwrap_or_default(),
+        };
+        imp::observe(
+            &imp::STEP_DURATION,
+            &[&step_type, step, succeeded_label(succeeded)],
+            elapsed,
+        );
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (step_type, step, succeeded, elapsed);
+}
+
+/// An agent finished a turn
+pub(crate) fn agent_finished(agent: &str, succeeded: bool, elapsed: Duration) {
+    #[cfg(feature = "metrics")]
+    imp::observe(
+        &imp::AGENT_DURATION,
+        &[agent, succeeded_label(succeeded)],
+        elapsed,
+    );
+    #[cfg(not(feature = "metrics"))]
+    let _ = (agent, succeeded, elapsed);
+}
+
+/// An LLM request got its response
+pub(crate) fn llm_request(provider: &str, model: &str, usage: Option<&Usage>, elapsed: Duration) {
+    #[cfg(feature = "metrics")]
+    {
+        imp::observe(&imp::LLM_DURATION, &[provider, model], elapsed);
+        if let Some(usage) = usage {
+            let tokens = [
+                ("prompt", usage.prompt_tokens),
+                ("completion", usage.completion_tokens),
+            ];
+            for (kind, count) in tokens {
+                imp::add(&imp::LLM_TOKENS, &[provider, model, kind], count as f64);
+            }
+        }
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (provider, model, usage, elapsed);
+}
+
+/// An LLM request failed for good, after any retries
+pub(crate) fn llm_error(provider: &str) {
+    #[cfg(feature = "metrics")]
+    imp::add(&imp::LLM_ERRORS, &[provider], 1.0);
+    #[cfg(not(feature = "metrics"))]
+    let _ = provider;
+}
+
+/// `CachedChatClient` looked a request up in its cache
+pub(crate) fn llm_cache_lookup(provider: &str, hit: bool) {
+    #[cfg(feature = "metrics")]
+    imp::add(
+        &imp::LLM_CACHE,
+        &[provider, if hit { "hit" } else { "miss" }],
+        1.0,
+    );
+    #[cfg(not(feature = "metrics"))]
+    let _ = (provider, hit);
+}
+
+/// A tool ran; `elapsed` covers all its attempts
+pub(crate) fn tool_call(tool: &str, succeeded: bool, elapsed: Duration) {
+    #[cfg(feature = "metrics")]
+    {
+        imp::observe(&imp::TOOL_DURATION, &[tool], elapsed);
+        if !succeeded {
+            imp::add(&imp::TOOL_ERRORS, &[tool], 1.0);
+        }
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (tool, succeeded, elapsed);
+}
+
+/// A tool call was rejected before it ran, e.g. for invalid arguments
+pub(crate) fn tool_rejected(tool: &str) {
+    #[cfg(feature = "metrics")]
+    imp::add(&imp::TOOL_ERRORS, &[tool], 1.0);
+    #[cfg(not(feature = "metrics"))]
+    let _ = tool;
+}
+
+/// An LLM request or tool call is being retried (`component` is `llm` or
+/// `tool`)
+pub(crate) fn retry(component: &str) {
+    #[cfg(feature = "metrics")]
+    imp::add(&imp::RETRIES, &[component], 1.0);
+    #[cfg(not(feature = "metrics"))]
+    let _ = component;
+}
+
+/// Loop detection stopped a repeated tool call
+pub(crate) fn tool_loop_detected(agent: &str, tool: &str) {
+    #[cfg(feature = "metrics")]
+    imp::add(&imp::TOOL_LOOPS, &[agent, tool], 1.0);
+    #[cfg(not(feature = "metrics"))]
+    let _ = (agent, tool);
+}
+
+#[cfg(all(feature = "metrics", feature = "workflow"))]
+fn outcome_label(state: &crate::workflow::WorkflowState) -> &'static str {
+    use crate::workflow::WorkflowState;
+    match state {
+        WorkflowState::Completed => "completed",
+        WorkflowState::Canceled => "canceled",
+        WorkflowState::Failed => "failed",
+        WorkflowState::BudgetExceeded => "budget_exceeded",
+        _ => "other",
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn succeeded_label(succeeded: bool) -> &'static str {
+    if succeeded {
+        "ok"
+    } else {
+        "error"
+    }
+}
+
+#[cfg(feature = "metrics")]
+mod imp {
+    use std::collections::BTreeMap;
+    use std::fmt::Write;
+    use std::sync::OnceLock;
+    use std::time::Duration;
+
+    use parking_lot::Mutex;
+
+    /// Upper bounds in seconds, from a fast tool call to a long workflow
+    const DURATION_BUCKETS: &[f64] = &[
+        0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0,
+    ];
+
+    pub(super) struct Family {
+        name: &'static str,
+        help: &'static str,
+        labels: &'static [&'static str],
+        histogram: bool,
+    }
+
+    const fn histogram(
+        name: &'static str,
+        help: &'static str,
+        labels: &'static [&'static str],
+    ) -> Family {
+        Family {
+            name,
+            help,
+            labels,
+            histogram: true,
+        }
+    }
+
+    const fn counter(
+        name: &'static str,
+        help: &'static str,
+        labels: &'static [&'static str],
+    ) -> Family {
+        Family {
+            name,
+            help,
+            labels,
+            histogram: false,
+        }
+    }
+
+    pub(super) static WORKFLOW_DURATION: Family = histogram(
+        "agent_runtime_workflow_duration_seconds",
+        "Duration of workflow runs",
+        &["outcome"],
+    );
+    pub(super) static STEP_DURATION: Family = histogram(
+        "agent_runtime_step_duration_seconds",
+        "Duration of workflow steps",
+        &["step_type", "step", "outcome"],
+    );
+    pub(super) static AGENT_DURATION: Family = histogram(
+        "agent_runtime_agent_duration_seconds",
+        "Duration of agent turns",
+        &["agent", "outcome"],
+    );
+    pub(super) static LLM_DURATION: Family = histogram(
+        "agent_runtime_llm_request_duration_seconds",
+        "Latency of successful LLM requests",
+        &["provider", "model"],
+    );
+    pub(super) static LLM_TOKENS: Family = counter(
+        "agent_runtime_llm_tokens_total",
+        "Tokens reported by LLM providers",
+        &["provider", "model", "kind"],
+    );
+    pub(super) static LLM_ERRORS: Family = counter(
+        "agent_runtime_llm_errors_total",
+        "LLM requests that failed after any retries",
+        &["provider"],
+    );
+    pub(super) static LLM_CACHE: Family = counter(
+        "agent_runtime_llm_cache_lookups_total",
+        "LLM requests looked up in a response cache",
+        &["provider", "result"],
+    );
+    pub(super) static TOOL_DURATION: Family = histogram(
+        "agent_runtime_tool_call_duration_seconds",
+        "Duration of tool calls, including retries",
+        &["tool"],
+    );
+    pub(super) static TOOL_ERRORS: Family = counter(
+        "agent_runtime_tool_errors_total",
+        "Tool calls that failed or were rejected",
+        &["tool"],
+    );
+    pub(super) static RETRIES: Family = counter(
+        "agent_runtime_retries_total",
+        "Retried LLM requests and tool calls",
+        &["component"],
+    );
+    pub(super) static TOOL_LOOPS: Family = counter(
+        "agent_runtime_tool_loop_detections_total",
+        "Tool calls stopped by loop detection",
+        &["agent", "tool"],
+    );
+
+    /// Rendering order
+    static FAMILIES: [&Family; 11] = [
+        &WORKFLOW_DURATION,
+        &STEP_DURATION,
+        &AGENT_DURATION,
+        &LLM_DURATION,
+        &LLM_TOKENS,
+        &LLM_ERRORS,
+        &LLM_CACHE,
+        &TOOL_DURATION,
+        &TOOL_ERRORS,
+        &RETRIES,
+        &TOOL_LOOPS,
+    ];
+
+    enum Series {
+        Counter(f64),
+        Histogram {
+            /// Per bucket, not cumulative
+            buckets: Vec<u64>,
+            sum: f64,
+            count: u64,
+        },
+    }
+
+    /// Series keyed by family name, then label values
+    #[derive(Default)]
+    pub(super) struct Registry {
+        series: Mutex<BTreeMap<&'static str, BTreeMap<Vec<String>, Series>>>,
+    }
+
+    pub(super) fn registry() -> &'static Registry {
+        static REGISTRY: OnceLock<Registry> = OnceLock::new();
+        REGISTRY.get_or_init(Registry::default)
+    }
+
+    pub(super) fn observe(family: &'static Family, labels: &[&str], elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let mut series = registry().series.lock();
+        let entry = series
+            .entry(family.name)
+            .or_default()
+            .entry(labels.iter().map(|l| l.to_string()).collect())
+            .or_insert_with(|| Series::Histogram {
+                buckets: vec![0; DURATION_BUCKETS.len()],
+                sum: 0.0,
+                count: 0,
+            });
+        if let Series::Histogram {
+            buckets,
+            sum,
+            count,
+        } = entry
+        {
+            if let Some(bucket) = DURATION_BUCKETS.iter().position(|le| seconds <= *le) {
+                buckets[bucket] += 1;
+            }
+            *sum += seconds;
+            *count += 1;
+        }
+    }
+
+    pub(super) fn add(family: &'static Family, labels: &[&str], amount: f64) {
+        let mut series = registry().series.lock();
+        let entry = series
+            .entry(family.name)
+            .or_default()
+            .entry(labels.iter().map(|l| l.to_string()).collect())
+            .or_insert(Series::Counter(0.0));
+        if let Series::Counter(total) = entry {
+            *total += amount;
+        }
+    }
+
+    impl Registry {
+        pub(super) fn render(&self) -> String {
+            let series = self.series.lock();
+            let mut out = String::new();
+            for family in FAMILIES {
+                let kind = if family.histogram {
+                    "histogram"
+                } else {
+                    "counter"
+                };
+                let _ = writeln!(out, "# HELP {} {}", family.name, family.help);
+                let _ = writeln!(out, "# TYPE {} {}", family.name, kind);
+                let Some(all) = series.get(family.name) else {
+                    continue;
+                };
+                for (values, sample) in all {
+                    let labels = label_pairs(family.labels, values);
+                    match sample {
+                        Series::Counter(total) => {
+                            let _ = writeln!(out, "{}{{{}}} {}", family.name, labels, total);
+                        }
+                        Series::Histogram {
+                            buckets,
+                            sum,
+                            count,
+                        } => {
+                            let mut cumulative = 0;
+                            for (le, n) in DURATION_BUCKETS.iter().zip(buckets) {
+                                cumulative += n;
+                                let _ = writeln!(
+                                    out,
+                                    "{}_bucket{{{},le=\"{}\"}} {}",
+                                    family.name, labels, le, cumulative
+                                );
+                            }
+                            let _ = writeln!(
+                                out,
+                                "{}_bucket{{{},le=\"+Inf\"}} {}",
+                                family.name, labels, count
+                            );
+                            let _ = writeln!(out, "{}_sum{{{}}} {}", family.name, labels, sum);
+                            let _ = writeln!(out, "{}_count{{{}}} {}", family.name, labels, count);
+                        }
+                    }
+                }
+            }
+            out
+        }
+    }
+
+    /// `name="value",...` with values escaped
+    fn label_pairs(names: &[&str], values: &[String]) -> String {
+        names
+            .iter()
+            .zip(values)
+            .map(|(name, value)| {
+                let value = value
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('\n', "\\n");
+                format!("{}=\"{}\"", name, value)
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_histogram_buckets_are_cumulative() {
+            let registry = Registry::default();
+            {
+                let mut series = registry.series.lock();
+                series.entry(TOOL_DURATION.name).or_default().insert(
+                    vec!["fetch".to_string()],
+                    Series::Histogram {
+                        buckets: {
+                            let mut buckets = vec![0; DURATION_BUCKETS.len()];
+                            buckets[0] = 1; // 0.005
+                            buckets[7] = 2; // 1.0
+                            buckets
+                        },
+                        sum: 1.503,
+                        count: 4,
+                    },
+                );
+            }
+            let text = registry.render();
+            assert!(text.contains("# TYPE agent_runtime_tool_call_duration_seconds histogram\n"));
+            assert!(text.contains(
+                "agent_runtime_tool_call_duration_seconds_bucket{tool=\"fetch\",le=\"0.005\"} 1\n"
+            ));
+            assert!(text.contains(
+                "agent_runtime_tool_call_duration_seconds_bucket{tool=\"fetch\",le=\"1\"} 3\n"
+            ));
+            // One observation was above every bound
+            assert!(text.contains(
+                "agent_runtime_tool_call_duration_seconds_bucket{tool=\"fetch\",le=\"+Inf\"} 4\n"
+            ));
+            assert!(
+                text.contains("agent_runtime_tool_call_duration_seconds_count{tool=\"fetch\"} 4\n")
+            );
+        }
+
+        #[test]
+        fn test_label_values_are_escaped() {
+            assert_eq!(
+                label_pairs(&["tool"], &["say \"hi\"\\\n".to_string()]),
+                "tool=\"say \\\"hi\\\"\\\\\\n\""
+            );
+        }
+    }
+}
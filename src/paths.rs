@@ -0,0 +1,470 @@
+//! Portable handling of untrusted paths for file-based components.
+//!
+//! Paths from configuration, tools or model output may be written for either
+//! path family, so `a\b` and `a/b` mean the same thing on every platform and
+//! `C:\x`, `\\server\share\x` and `\\?\C:\x` are always absolute. A
+//! [`Sandbox`] resolves such paths under a root directory and rejects
+//! anything that would leave it lexically or through a symlink, uses a name
+//! Windows reserves (`CON`, `nul.txt`), ends in a dot or space (which
+//! Windows silently strips), or exceeds the platform's length limits.
+//! [`portable_name`] gives the `/`-separated form used in records and URIs.
+
+use std::path::{Component, Path, PathBuf, Prefix};
+
+/// Longest full path a sandbox resolves by default, in bytes: `MAX_PATH`
+/// on Windows, `PATH_MAX` elsewhere
+pub const MAX_PATH_LEN: usize = if cfg!(windows) { 260 } else { 4096 };
+
+/// Longest single path component on NTFS and common Unix filesystems
+pub const MAX_COMPONENT_LEN: usize = 255;
+
+/// Device names Windows reserves in every directory, with any extension
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Characters Windows forbids in file names; `:` also opens alternate
+/// data streams
+const FORBIDDEN_CHARS: &[char] = &['<', '>', ':', '"', '|', '?', '*'];
+
+/// Why a path was refused
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PathError {
+    #[error("path is empty")]
+    Empty,
+
+    #[error("'{path}' is absolute; expected a path relative to the sandbox")]
+    Absolute { path: String },
+
+    #[error("'{path}' escapes the sandbox root")]
+    Escapes { path: String },
+
+    #[error("'{component}' in '{path}' is a reserved device name")]
+    Reserved { path: String, component: String },
+
+    #[error("'{component}' in '{path}' ends with a dot or space")]
+    TrailingDotOrSpace { path: String, component: String },
+
+    #[error("'{path}' contains the forbidden character {character:?}")]
+    ForbiddenCharacter { path: String, character: char },
+
+    #[error("'{path}' is {len} bytes long; the limit is {limit}")]
+    TooLong {
+        path: String,
+        len: usize,
+        limit: usize,
+    },
+
+    #[error("I/O error on '{path}': {message}")]
+    Io { path: String, message: String },
+}
+
+impl PathError {
+    fn io(path: &Path, error: std::io::Error) -> Self {
+        PathError::Io {
+            path: path.display().to_string(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<PathError> for std::io::Error {
+    fn from(error: PathError) -> Self {
+        let kind = match &error {
+            PathError::Io { .. } => std::io::ErrorKind::Other,
+            _ => std::io::ErrorKind::InvalidInput,
+        };
+        std::io::Error::new(kind, error)
+    }
+}
+
+/// Whether `path` has the extension `ext`, ignoring ASCII case
+pub fn has_extension(path: &Path, ext: &str) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case(ext))
+}
+
+/// `path` with `/` separators and without empty, `.` or `..` segments,
+/// whichever family it was written for
+pub fn portable_name(path: &str) -> String {
+    path.split(['/', '\\'])
+        .filter(|segment| !matches!(*segment, "" | "." | ".."))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Whether `path` is absolute in either path family
+pub fn is_absolute_anywhere(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    path.starts_with(['/', '\\'])
+        || (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':')
+}
+
+/// Check one component for names that break or alias on Windows
+fn check_component(path: &str, component: &str) -> Result<(), PathError> {
+    if let Some(character) = component
+        .chars()
+        .find(|c| FORBIDDEN_CHARS.contains(c) || c.is_control())
+    {
+        return Err(PathError::ForbiddenCharacter {
+            path: path.to_string(),
+            character,
+        });
+    }
+    if component.ends_with(['.', ' ']) {
+        return Err(PathError::TrailingDotOrSpace {
+            path: path.to_string(),
+            component: component.to_string(),
+        });
+    }
+    let stem = component.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+    {
+        return Err(PathError::Reserved {
+            path: path.to_string(),
+            component: component.to_string(),
+        });
+    }
+    if component.len() > MAX_COMPONENT_LEN {
+        return Err(PathError::TooLong {
+            path: path.to_string(),
+            len: component.len(),
+            limit: MAX_COMPONENT_LEN,
+        });
+    }
+    Ok(())
+}
+
+/// A path split into comparable parts: verbatim (`\\?\`) and plain prefixes
+/// compare equal, and on Windows so do names differing only in case
+fn comparable(path: &Path) -> Vec<String> {
+    let fold = |s: &std::ffi::OsStr| {
+        let s = s.to_string_lossy();
+        if cfg!(windows) {
+            s.to_lowercase()
+        } else {
+            s.into_owned()
+        }
+    };
+    path.components()
+        .filter_map(|component| match component {
+            Component::Prefix(prefix) => Some(match prefix.kind() {
+                Prefix::Disk(d) | Prefix::VerbatimDisk(d) => {
+                    format!("{}:", (d as char).to_ascii_lowercase())
+                }
+                Prefix::UNC(server, share) | Prefix::VerbatimUNC(server, share) => {
+                    format!(r"\\{}\{}", fold(server), fold(share))
+                }
+                Prefix::Verbatim(name) | Prefix::DeviceNS(name) => fold(name),
+            }),
+            Component::RootDir => Some("/".to_string()),
+            Component::Normal(name) => Some(fold(name)),
+            Component::CurDir | Component::ParentDir => None,
+        })
+        .collect()
+}
+
+/// A directory that untrusted relative paths are resolved inside of
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    root: PathBuf,
+    max_len: usize,
+}
+
+impl Sandbox {
+    /// Sandbox an existing directory
+    pub fn new(root: impl AsRef<Path>) -> Result<Self, PathError> {
+        let root = root.as_ref();
+        let root = root.canonicalize().map_err(|e| PathError::io(root, e))?;
+        Ok(Self {
+            root,
+            max_len: MAX_PATH_LEN,
+        })
+    }
+
+    /// Refuse resolved paths longer than `max_len` bytes
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// The canonical root
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolve `path`, written with either separator, to a location inside
+    /// the root. `..` may step back out of a subdirectory but never above
+    /// the root, and an existing symlink may not lead outside it.
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, PathError> {
+        if path.trim().is_empty() {
+            return Err(PathError::Empty);
+        }
+        if is_absolute_anywhere(path) {
+            return Err(PathError::Absolute {
+                path: path.to_string(),
+            });
+        }
+
+        let mut parts: Vec<&str> = Vec::new();
+        for component in path.split(['/', '\\']) {
+            match component {
+                "" | "." => {}
+                ".." => {
+                    if parts.pop().is_none() {
+                        return Err(PathError::Escapes {
+                            path: path.to_string(),
+                        });
+                    }
+                }
+                name => {
+                    check_component(path, name)?;
+                    parts.push(name);
+                }
+            }
+        }
+        if parts.is_empty() {
+            return Err(PathError::Empty);
+        }
+
+        let mut resolved = self.root.clone();
+        resolved.extend(&parts);
+        let len = resolved.as_os_str().len();
+        if len > self.max_len {
+            return Err(PathError::TooLong {
+                path: path.to_string(),
+                len,
+                limit: self.max_len,
+            });
+        }
+
+        // The deepest existing ancestor decides where symlinks lead
+        let existing = resolved
+            .ancestors()
+            .find(|ancestor| ancestor.exists())
+            .unwrap_or(&self.root);
+        let real = existing
+            .canonicalize()
+            .map_err(|e| PathError::io(existing, e))?;
+        if !self.contains(&real) {
+            return Err(PathError::Escapes {
+                path: path.to_string(),
+            });
+        }
+        Ok(resolved)
+    }
+
+    /// Whether an already canonical `path` lies inside the root
+    pub fn contains(&self, path: &Path) -> bool {
+        comparable(path).starts_with(&comparable(&self.root))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh, empty directory under the system temp dir
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "agent-runtime-paths-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_resolve_accepts_both_separators() {
+        let dir = temp_dir("separators");
+        let sandbox = Sandbox::new(&dir).unwrap();
+        let expected = sandbox.root().join("reports").join("q3.md");
+
+        assert_eq!(sandbox.resolve("reports/q3.md").unwrap(), expected);
+        assert_eq!(sandbox.resolve(r"reports\q3.md").unwrap(), expected);
+        assert_eq!(sandbox.resolve(r".\reports/./q3.md").unwrap(), expected);
+        assert_eq!(
+            sandbox.resolve(r"reports\drafts\..\q3.md").unwrap(),
+            expected
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_rejects_escapes_and_absolute_paths() {
+        let dir = temp_dir("escapes");
+        let sandbox = Sandbox::new(&dir).unwrap();
+
+        for path in ["..", r"..\secrets", "a/../../b", r"a\..\..\b", r"a/..\../b"] {
+            assert_eq!(
+                sandbox.resolve(path),
+                Err(PathError::Escapes { path: path.into() }),
+                "{}",
+                path
+            );
+        }
+        for path in [
+            "/etc/passwd",
+            r"\Windows\System32",
+            r"C:\Windows",
+            "c:relative",
+            r"\\server\share\file",
+            r"\\?\C:\Windows",
+            "//server/share",
+        ] {
+            assert_eq!(
+                sandbox.resolve(path),
+                Err(PathError::Absolute { path: path.into() }),
+                "{}",
+                path
+            );
+        }
+        assert_eq!(sandbox.resolve("  "), Err(PathError::Empty));
+        assert_eq!(sandbox.resolve("a/.."), Err(PathError::Empty));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_rejects_names_windows_would_alias() {
+        let dir = temp_dir("aliases");
+        let sandbox = Sandbox::new(&dir).unwrap();
+
+        for (path, component) in [
+            ("CON", "CON"),
+            ("logs/nul.txt", "nul.txt"),
+            (r"out\Com1.log", "Com1.log"),
+            ("lpt9", "lpt9"),
+        ] {
+            assert_eq!(
+                sandbox.resolve(path),
+                Err(PathError::Reserved {
+                    path: path.into(),
+                    component: component.into()
+                })
+            );
+        }
+        assert!(sandbox.resolve("console.log").is_ok());
+        assert!(sandbox.resolve("connect/nullable.txt").is_ok());
+
+        for (path, component) in [("notes.", "notes."), ("dir /x", "dir "), ("a/b. ", "b. ")] {
+            assert_eq!(
+                sandbox.resolve(path),
+                Err(PathError::TrailingDotOrSpace {
+                    path: path.into(),
+                    component: component.into()
+                })
+            );
+        }
+        assert_eq!(
+            sandbox.resolve("file.txt:stream"),
+            Err(PathError::ForbiddenCharacter {
+                path: "file.txt:stream".into(),
+                character: ':'
+            })
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_enforces_length_limits() {
+        let dir = temp_dir("length");
+        let sandbox = Sandbox::new(&dir).unwrap().with_max_len(200);
+
+        let long_name = "a".repeat(MAX_COMPONENT_LEN + 1);
+        assert!(matches!(
+            sandbox.resolve(&long_name),
+            Err(PathError::TooLong { limit, .. }) if limit == MAX_COMPONENT_LEN
+        ));
+
+        let deep = vec!["segment"; 40].join("/");
+        let error = sandbox.resolve(&deep).unwrap_err();
+        assert!(matches!(error, PathError::TooLong { limit: 200, .. }));
+        assert!(error.to_string().contains("the limit is 200"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_rejects_symlinks_out_of_the_root() {
+        let dir = temp_dir("symlink");
+        let outside = temp_dir("symlink-target");
+        std::os::unix::fs::symlink(&outside, dir.join("link")).unwrap();
+        let sandbox = Sandbox::new(&dir).unwrap();
+
+        assert_eq!(
+            sandbox.resolve("link/file.txt"),
+            Err(PathError::Escapes {
+                path: "link/file.txt".into()
+            })
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+        std::fs::remove_dir_all(outside).unwrap();
+    }
+
+    #[test]
+    fn test_comparable_prefixes() {
+        // Only Windows parses prefixes, but the comparison is the same code
+        let root = Sandbox {
+            root: PathBuf::from("/srv/data"),
+            max_len: MAX_PATH_LEN,
+        };
+        assert!(root.contains(Path::new("/srv/data/x")));
+        assert!(!root.contains(Path::new("/srv/database")));
+        assert!(!root.contains(Path::new("/srv")));
+    }
+
+    #[test]
+    fn test_portable_helpers() {
+        assert_eq!(
+            portable_name(r"reports\q3\chart.png"),
+            "reports/q3/chart.png"
+        );
+        assert_eq!(portable_name(r"..\..\chart.png"), "chart.png");
+        assert_eq!(portable_name("./a//b/"), "a/b");
+        assert!(has_extension(Path::new("agent-runtime.TOML"), "toml"));
+        assert!(has_extension(Path::new("agent-runtime.Yml"), "yml"));
+        assert!(!has_extension(Path::new("toml"), "toml"));
+    }
+}
+
+#[cfg(all(test, windows))]
+mod windows_tests {
+    use super::*;
+
+    #[test]
+    fn test_verbatim_and_case_insensitive_containment() {
+        let sandbox = Sandbox {
+            root: PathBuf::from(r"\\?\C:\Data\Runs"),
+            max_len: MAX_PATH_LEN,
+        };
+        assert!(sandbox.contains(Path::new(r"C:\data\runs\a.txt")));
+        assert!(sandbox.contains(Path::new(r"\\?\c:\DATA\RUNS\b")));
+        assert!(!sandbox.contains(Path::new(r"D:\Data\Runs\a.txt")));
+        assert!(!sandbox.contains(Path::new(r"C:\Data\Runsheet")));
+
+        let unc = Sandbox {
+            root: PathBuf::from(r"\\?\UNC\Server\Share\root"),
+            max_len: MAX_PATH_LEN,
+        };
+        assert!(unc.contains(Path::new(r"\\server\share\ROOT\x")));
+        assert!(!unc.contains(Path::new(r"\\server\other\root\x")));
+    }
+
+    #[test]
+    fn test_resolved_paths_use_native_separators() {
+        let dir = std::env::temp_dir().join(format!("agent-runtime-win-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sandbox = Sandbox::new(&dir).unwrap();
+
+        let resolved = sandbox.resolve("a/b/c.txt").unwrap();
+        assert!(!resolved.to_string_lossy().contains('/'));
+        assert!(sandbox.contains(&resolved));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
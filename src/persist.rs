@@ -0,0 +1,291 @@
+//! Serialization formats for checkpoints and stored runs.
+//!
+//! [`PersistFormat::encode`] prefixes the payload with a 6-byte header (magic,
+//! version, format tag), so [`decode`] can tell the formats apart and a store
+//! holding a mix of them keeps working. Headerless JSON, as written before
+//! formats were pluggable, is still read as JSON.
+//!
+//! JSON is always available. The others each need a feature, since each
+//! brings in a dependency: gzipped JSON needs `gzip`, CBOR needs `cbor` and
+//! MessagePack needs `msgpack`.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+const MAGIC: &[u8; 4] = b"ARPF";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = MAGIC.len() + 2;
+
+/// Encoding used for persisted data
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum PersistFormat {
+    /// Readable, largest on disk
+    #[default]
+    Json,
+    /// JSON compressed with gzip, smallest for long histories; needs the
+    /// `gzip` feature
+    JsonGzip,
+    /// Compact binary (RFC 8949); needs the `cbor` feature
+    Cbor,
+    /// Compact binary, with field names kept; needs the `msgpack` feature
+    MessagePack,
+}
+
+impl std::fmt::Display for PersistFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            PersistFormat::Json => "JSON",
+            PersistFormat::JsonGzip => "gzipped JSON",
+            PersistFormat::Cbor => "CBOR",
+            PersistFormat::MessagePack => "MessagePack",
+        })
+    }
+}
+
+/// Why persisted data could not be written or read
+#[derive(Debug, thiserror::Error)]
+pub enum PersistError {
+    #[error("expected {expected} data but found {detected}")]
+    FormatMismatch {
+        expected: PersistFormat,
+        detected: PersistFormat,
+    },
+
+    #[error("unrecognized persisted data (starts with {0:02x?})")]
+    UnknownFormat(Vec<u8>),
+
+    #[error("{format} support is not compiled in; enable the `{feature}` feature")]
+    Unsupported {
+        format: PersistFormat,
+        feature: &'static str,
+    },
+
+    #[error("corrupt {format} data: {message}")]
+    Corrupt {
+        format: PersistFormat,
+        message: String,
+    },
+
+    #[error("failed to encode as {format}: {message}")]
+    Encode {
+        format: PersistFormat,
+        message: String,
+    },
+}
+
+impl PersistFormat {
+    fn tag(self) -> u8 {
+        match self {
+            PersistFormat::Json => b'J',
+            PersistFormat::JsonGzip => b'G',
+            PersistFormat::Cbor => b'C',
+            PersistFormat::MessagePack => b'M',
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            b'J' => Some(PersistFormat::Json),
+            b'G' => Some(PersistFormat::JsonGzip),
+            b'C' => Some(PersistFormat::Cbor),
+            b'M' => Some(PersistFormat::MessagePack),
+            _ => None,
+        }
+    }
+
+    /// Serialize `value` with a format header
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, PersistError> {
+        let mut out = Vec::with_capacity(HEADER_LEN + 256);
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.push(self.tag());
+
+        let encode_error = |message: String| PersistError::Encode {
+            format: self,
+            message,
+        };
+        match self {
+            PersistFormat::Json => {
+                serde_json::to_writer(&mut out, value).map_err(|e| encode_error(e.to_string()))?
+            }
+            #[cfg(feature = "gzip")]
+            PersistFormat::JsonGzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(&mut out, flate2::Compression::default());
+                serde_json::to_writer(&mut encoder, value)
+                    .map_err(|e| encode_error(e.to_string()))?;
+                encoder.finish().map_err(|e| encode_error(e.to_string()))?;
+            }
+            #[cfg(feature = "cbor")]
+            PersistFormat::Cbor => {
+                ciborium::into_writer(value, &mut out).map_err(|e| encode_error(e.to_string()))?
+            }
+            #[cfg(feature = "msgpack")]
+            PersistFormat::MessagePack => rmp_serde::encode::write_named(&mut out, value)
+                .map_err(|e| encode_error(e.to_string()))?,
+            #[allow(unreachable_patterns)]
+            _ => return Err(self.unsupported()),
+        }
+        Ok(out)
+    }
+
+    /// Work out which format `bytes` were written in
+    pub fn detect(bytes: &[u8]) -> Result<Self, PersistError> {
+        Self::split(bytes).map(|(format, _)| format)
+    }
+
+    /// Deserialize data that must be in this format
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, PersistError> {
+        let (detected, payload) = Self::split(bytes)?;
+        if detected != self {
+            return Err(PersistError::FormatMismatch {
+                expected: self,
+                detected,
+            });
+        }
+        decode_payload(detected, payload)
+    }
+
+    /// The format and payload of `bytes`
+    fn split(bytes: &[u8]) -> Result<(Self, &[u8]), PersistError> {
+        if let Some(header) = bytes.strip_prefix(MAGIC.as_slice()) {
+            return match header {
+                [VERSION, tag, payload @ ..] => Self::from_tag(*tag)
+                    .map(|format| (format, payload))
+                    .ok_or_else(|| PersistError::UnknownFormat(bytes[..HEADER_LEN].to_vec())),
+                _ => Err(PersistError::UnknownFormat(
+                    bytes[..bytes.len().min(HEADER_LEN)].to_vec(),
+                )),
+            };
+        }
+
+        // Headerless JSON from before formats were recorded
+        match bytes.iter().find(|b| !b.is_ascii_whitespace()) {
+            Some(b'{' | b'[') => Ok((PersistFormat::Json, bytes)),
+            _ => Err(PersistError::UnknownFormat(
+                bytes[..bytes.len().min(HEADER_LEN)].to_vec(),
+            )),
+        }
+    }
+
+    /// The error for a format whose feature is off
+    fn unsupported(self) -> PersistError {
+        let feature = match self {
+            PersistFormat::Json => unreachable!("JSON is always available"),
+            PersistFormat::JsonGzip => "gzip",
+            PersistFormat::Cbor => "cbor",
+            PersistFormat::MessagePack => "msgpack",
+        };
+        PersistError::Unsupported {
+            format: self,
+            feature,
+        }
+    }
+}
+
+/// Deserialize data in whichever format it was written
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, PersistError> {
+    let (format, payload) = PersistFormat::split(bytes)?;
+    decode_payload(format, payload)
+}
+
+fn decode_payload<T: DeserializeOwned>(
+    format: PersistFormat,
+    payload: &[u8],
+) -> Result<T, PersistError> {
+    let corrupt = |message: String| PersistError::Corrupt { format, message };
+    match format {
+        PersistFormat::Json => serde_json::from_slice(payload).map_err(|e| corrupt(e.to_string())),
+        #[cfg(feature = "gzip")]
+        PersistFormat::JsonGzip => serde_json::from_reader(flate2::read::GzDecoder::new(payload))
+            .map_err(|e| corrupt(e.to_string())),
+        #[cfg(feature = "cbor")]
+        PersistFormat::Cbor => {
+            let mut reader = payload;
+            let value = ciborium::from_reader(&mut reader).map_err(|e| corrupt(e.to_string()))?;
+            if !reader.is_empty() {
+                return Err(corrupt(format!("{} trailing bytes", reader.len())));
+            }
+            Ok(value)
+        }
+        #[cfg(feature = "msgpack")]
+        PersistFormat::MessagePack => {
+            let mut reader = payload;
+            let value = rmp_serde::from_read(&mut reader).map_err(|e| corrupt(e.to_string()))?;
+            if !reader.is_empty() {
+                return Err(corrupt(format!("{} trailing bytes", reader.len())));
+            }
+            Ok(value)
+        }
+        #[allow(unreachable_patterns)]
+        _ => Err(format.unsupported()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_round_trip_and_legacy_detection() {
+        let value = json!({"a": [1, 2.5, "x"], "b": null});
+        let bytes = PersistFormat::Json.encode(&value).unwrap();
+        assert_eq!(&bytes[..4], b"ARPF");
+        assert_eq!(PersistFormat::detect(&bytes).unwrap(), PersistFormat::Json);
+        assert_eq!(decode::<serde_json::Value>(&bytes).unwrap(), value);
+
+        let legacy = serde_json::to_vec_pretty(&value).unwrap();
+        assert_eq!(PersistFormat::detect(&legacy).unwrap(), PersistFormat::Json);
+        assert_eq!(decode::<serde_json::Value>(&legacy).unwrap(), value);
+    }
+
+    #[test]
+    fn test_rejects_unknown_and_mismatched_data() {
+        let err = decode::<serde_json::Value>(b"\x00\x01garbage").unwrap_err();
+        assert!(matches!(err, PersistError::UnknownFormat(_)));
+
+        let err = decode::<serde_json::Value>(b"ARPF\x01Zxx").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unrecognized persisted data (starts with [41, 52, 50, 46, 01, 5a])"
+        );
+
+        let bytes = PersistFormat::Json.encode(&json!(1)).unwrap();
+        let err = PersistFormat::Cbor
+            .decode::<serde_json::Value>(&bytes)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "expected CBOR data but found JSON");
+    }
+
+    #[test]
+    fn test_formats_need_their_features() {
+        let formats = [
+            (
+                PersistFormat::JsonGzip,
+                cfg!(feature = "gzip"),
+                "gzipped JSON",
+                "gzip",
+            ),
+            (PersistFormat::Cbor, cfg!(feature = "cbor"), "CBOR", "cbor"),
+            (
+                PersistFormat::MessagePack,
+                cfg!(feature = "msgpack"),
+                "MessagePack",
+                "msgpack",
+            ),
+        ];
+        for (format, enabled, name, feature) in formats {
+            if enabled {
+                continue;
+            }
+            let err = format.encode(&json!(1)).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("{name} support is not compiled in; enable the `{feature}` feature")
+            );
+        }
+    }
+}
@@ -0,0 +1,820 @@
+//! PII scanning for workflow outputs.
+//!
+//! A [`PiiScanner`] walks a JSON value, runs each string through its
+//! [`PiiDetector`]s and reports what it found as [`PiiFindings`]: the type,
+//! the JSON pointer of the string, the byte span within it and a confidence.
+//! Matched text is never copied into the findings.
+//!
+//! The built-in detector covers emails, phone numbers, credit cards (Luhn
+//! checked), IBANs (mod-97 checked) and national ID numbers for the
+//! configured [`NationalIdLocale`]s. [`LlmPiiDetector`] can be added for
+//! free-form PII such as names and addresses.
+//!
+//! Only string values are scanned; numbers, booleans and object keys are left
+//! alone. Redaction replaces matched spans with typed placeholders
+//! (`[EMAIL]`, `[CREDIT_CARD]`, ...) so the value stays valid JSON and
+//! non-matching content is untouched.
+
+use crate::llm::{ChatMessage, ChatRequest, LlmClient};
+use async_trait::async_trait;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock};
+
+/// Kind of personally identifiable information
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PiiType {
+    Email,
+    Phone,
+    CreditCard,
+    Iban,
+    NationalId,
+    /// Anything else a custom or LLM-based detector reports (e.g. "name")
+    Other(String),
+}
+
+impl PiiType {
+    /// Stable label used in counts and placeholders
+    pub fn label(&self) -> String {
+        match self {
+            PiiType::Email => "email".to_string(),
+            PiiType::Phone => "phone".to_string(),
+            PiiType::CreditCard => "credit_card".to_string(),
+            PiiType::Iban => "iban".to_string(),
+            PiiType::NationalId => "national_id".to_string(),
+            PiiType::Other(kind) => kind.to_lowercase(),
+        }
+    }
+
+    /// Placeholder substituted for redacted matches
+    pub fn placeholder(&self) -> String {
+        format!("[{}]", self.label().to_uppercase())
+    }
+
+    fn from_label(label: &str) -> Self {
+        match label
+            .trim()
+            .to_lowercase()
+            .replace([' ', '-'], "_")
+            .as_str()
+        {
+            "email" => PiiType::Email,
+            "phone" | "phone_number" => PiiType::Phone,
+            "credit_card" | "card" => PiiType::CreditCard,
+            "iban" => PiiType::Iban,
+            "national_id" | "ssn" => PiiType::NationalId,
+            other => PiiType::Other(other.to_string()),
+        }
+    }
+}
+
+/// Locales whose national ID formats the built-in detector recognises
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NationalIdLocale {
+    /// US Social Security Number (`123-45-6789`)
+    Us,
+    /// UK National Insurance number (`AB 12 34 56 C`)
+    Gb,
+    /// Canadian Social Insurance Number (`046 454 286`, Luhn checked)
+    Ca,
+}
+
+/// What the runtime does when PII is found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PiiAction {
+    /// Attach findings to the run, leave outputs unchanged
+    #[default]
+    ReportOnly,
+    /// Replace matches with typed placeholders in the output
+    Redact,
+    /// Fail the run and withhold the final output
+    Fail,
+}
+
+/// A match within a single string, as reported by a detector
+#[derive(Debug, Clone, PartialEq)]
+pub struct PiiMatch {
+    pub pii_type: PiiType,
+    /// Byte offset of the match start
+    pub start: usize,
+    /// Byte offset one past the match end
+    pub end: usize,
+    /// 0.0 - 1.0
+    pub confidence: f32,
+}
+
+/// Something that finds PII in text
+#[async_trait]
+pub trait PiiDetector: Send + Sync {
+    /// Detector name recorded on findings
+    fn name(&self) -> &str;
+
+    /// Find PII spans in `text`
+    async fn detect(&self, text: &str) -> Result<Vec<PiiMatch>, String>;
+}
+
+/// A single finding in a scanned value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PiiFinding {
+    pub pii_type: PiiType,
+
+    /// JSON pointer (RFC 6901) of the string containing the match
+    pub pointer: String,
+
+    /// Step whose output contained the match; `None` for the final output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step_index: Option<usize>,
+
+    /// Byte span within the original (unredacted) string
+    pub start: usize,
+    pub end: usize,
+
+    pub confidence: f32,
+    pub detector: String,
+}
+
+/// Findings report attached to a workflow run
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct PiiFindings {
+    pub action: PiiAction,
+
+    pub findings: Vec<PiiFinding>,
+
+    /// Detector failures (e.g. an LLM detector that couldn't be reached)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+impl PiiFindings {
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.findings.len()
+    }
+
+    /// Number of findings per type label
+    pub fn counts(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for finding in &self.findings {
+            *counts.entry(finding.pii_type.label()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Findings of a single type
+    pub fn of_type(&self, pii_type: &PiiType) -> Vec<&PiiFinding> {
+        self.findings
+            .iter()
+            .filter(|f| &f.pii_type == pii_type)
+            .collect()
+    }
+
+    /// Merge another report into this one
+    pub fn extend(&mut self, other: PiiFindings) {
+        self.findings.extend(other.findings);
+        self.errors.extend(other.errors);
+    }
+
+    /// One-line summary, e.g. "3 PII findings (email: 2, phone: 1)"
+    pub fn summary(&self) -> String {
+        let counts = self
+            .counts()
+            .into_iter()
+            .map(|(label, n)| format!("{}: {}", label, n))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("{} PII findings ({})", self.len(), counts)
+    }
+}
+
+/// Scans JSON values for PII and optionally redacts them
+#[derive(Clone)]
+pub struct PiiScanner {
+    detectors: Vec<Arc<dyn PiiDetector>>,
+    action: PiiAction,
+    scan_step_outputs: bool,
+    min_confidence: f32,
+}
+
+impl PiiScanner {
+    /// Scanner with the built-in detector for US national IDs, report-only
+    pub fn new() -> Self {
+        Self {
+            detectors: vec![Arc::new(BuiltinPiiDetector::new(vec![
+                NationalIdLocale::Us,
+            ]))],
+            action: PiiAction::ReportOnly,
+            scan_step_outputs: false,
+            min_confidence: 0.0,
+        }
+    }
+
+    /// Build a scanner from configuration; `None` if scanning is disabled
+    pub fn from_config(config: &crate::config::PiiConfig) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        Some(
+            Self::new()
+                .with_locales(config.locales.clone())
+                .with_action(config.action)
+                .with_step_outputs(config.scan_step_outputs)
+                .with_min_confidence(config.min_confidence),
+        )
+    }
+
+    /// Replace the built-in detector's national ID locales
+    pub fn with_locales(mut self, locales: Vec<NationalIdLocale>) -> Self {
+        self.detectors
+            .retain(|d| d.name() != BuiltinPiiDetector::NAME);
+        self.detectors
+            .insert(0, Arc::new(BuiltinPiiDetector::new(locales)));
+        self
+    }
+
+    /// Add a detector (e.g. [`LlmPiiDetector`])
+    pub fn with_detector(mut self, detector: Arc<dyn PiiDetector>) -> Self {
+        self.detectors.push(detector);
+        self
+    }
+
+    pub fn with_action(mut self, action: PiiAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Also scan every step output, not just the final output
+    ///
+    /// With [`PiiAction::Redact`] the redacted output is what the run records
+    /// as the next step's input and what checkpoints keep; the next step
+    /// itself still receives the original.
+    pub fn with_step_outputs(mut self, enabled: bool) -> Self {
+        self.scan_step_outputs = enabled;
+        self
+    }
+
+    /// Ignore matches below this confidence
+    pub fn with_min_confidence(mut self, min_confidence: f32) -> Self {
+        self.min_confidence = min_confidence;
+        self
+    }
+
+    pub fn action(&self) -> PiiAction {
+        self.action
+    }
+
+    pub fn scans_step_outputs(&self) -> bool {
+        self.scan_step_outputs
+    }
+
+    /// Find PII in a single string, overlapping matches resolved in favour of
+    /// the earlier, more confident one
+    pub async fn scan_text(&self, text: &str) -> (Vec<(PiiMatch, String)>, Vec<String>) {
+        let mut matches = Vec::new();
+        let mut errors = Vec::new();
+        for detector in &self.detectors {
+            match detector.detect(text).await {
+                Ok(found) => matches.extend(
+                    found
+                        .into_iter()
+                        .filter(|m| {
+                            m.confidence >= self.min_confidence
+                                && m.start < m.end
+                                && m.end <= text.len()
+                                && text.is_char_boundary(m.start)
+                                && text.is_char_boundary(m.end)
+                        })
+                        .map(|m| (m, detector.name().to_string())),
+                ),
+                Err(e) => errors.push(format!("{}: {}", detector.name(), e)),
+            }
+        }
+
+        matches.sort_by(|(a, _), (b, _)| {
+            a.start
+                .cmp(&b.start)
+                .then(b.confidence.total_cmp(&a.confidence))
+                .then(b.end.cmp(&a.end))
+        });
+        let mut resolved: Vec<(PiiMatch, String)> = Vec::with_capacity(matches.len());
+        for (m, detector) in matches {
+            match resolved.last_mut() {
+                Some((last, last_detector)) if m.start < last.end => {
+                    if m.confidence > last.confidence {
+                        *last = m;
+                        *last_detector = detector;
+                    }
+                }
+                _ => resolved.push((m, detector)),
+            }
+        }
+        (resolved, errors)
+    }
+
+    /// Scan a value without modifying it
+    pub async fn scan(&self, value: &JsonValue, step_index: Option<usize>) -> PiiFindings {
+        let mut report = PiiFindings {
+            action: self.action,
+            ..Default::default()
+        };
+        for (pointer, text) in string_leaves(value) {
+            let (matches, errors) = self.scan_text(text).await;
+            report.errors.extend(errors);
+            report
+                .findings
+                .extend(to_findings(&pointer, step_index, matches));
+        }
+        report
+    }
+
+    /// Scan a value and replace every match with its type placeholder
+    pub async fn redact(&self, value: &mut JsonValue, step_index: Option<usize>) -> PiiFindings {
+        let mut report = PiiFindings {
+            action: self.action,
+            ..Default::default()
+        };
+        let mut replacements = Vec::new();
+        for (pointer, text) in string_leaves(value) {
+            let (matches, errors) = self.scan_text(text).await;
+            report.errors.extend(errors);
+            if matches.is_empty() {
+                continue;
+            }
+            replacements.push((pointer.clone(), redact_text(text, &matches)));
+            report
+                .findings
+                .extend(to_findings(&pointer, step_index, matches));
+        }
+
+        for (pointer, redacted) in replacements {
+            if let Some(slot) = value.pointer_mut(&pointer) {
+                *slot = JsonValue::String(redacted);
+            }
+        }
+        report
+    }
+
+    /// Scan and, when the action is [`PiiAction::Redact`], redact in place
+    pub async fn apply(&self, value: &mut JsonValue, step_index: Option<usize>) -> PiiFindings {
+        match self.action {
+            PiiAction::Redact => self.redact(value, step_index).await,
+            PiiAction::ReportOnly | PiiAction::Fail => self.scan(value, step_index).await,
+        }
+    }
+}
+
+impl Default for PiiScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for PiiScanner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PiiScanner")
+            .field(
+                "detectors",
+                &self.detectors.iter().map(|d| d.name()).collect::<Vec<_>>(),
+            )
+            .field("action", &self.action)
+            .field("scan_step_outputs", &self.scan_step_outputs)
+            .field("min_confidence", &self.min_confidence)
+            .finish()
+    }
+}
+
+fn to_findings(
+    pointer: &str,
+    step_index: Option<usize>,
+    matches: Vec<(PiiMatch, String)>,
+) -> impl Iterator<Item = PiiFinding> + '_ {
+    matches.into_iter().map(move |(m, detector)| PiiFinding {
+        pii_type: m.pii_type,
+        pointer: pointer.to_string(),
+        step_index,
+        start: m.start,
+        end: m.end,
+        confidence: m.confidence,
+        detector,
+    })
+}
+
+/// Splice placeholders into `text`. Matches must be sorted and non-overlapping.
+fn redact_text(text: &str, matches: &[(PiiMatch, String)]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut cursor = 0;
+    for (m, _) in matches {
+        out.push_str(&text[cursor..m.start]);
+        out.push_str(&m.pii_type.placeholder());
+        cursor = m.end;
+    }
+    out.push_str(&text[cursor..]);
+    out
+}
+
+/// Every string in `value` with its JSON pointer, depth-first
+fn string_leaves(value: &JsonValue) -> Vec<(String, &str)> {
+    let mut leaves = Vec::new();
+    let mut stack = vec![(String::new(), value)];
+    while let Some((pointer, value)) = stack.pop() {
+        match value {
+            JsonValue::String(s) => leaves.push((pointer, s.as_str())),
+            JsonValue::Array(items) => {
+                for (i, item) in items.iter().enumerate().rev() {
+                    stack.push((format!("{}/{}", pointer, i), item));
+                }
+            }
+            JsonValue::Object(map) => {
+                let mut entries: Vec<_> = map.iter().collect();
+                entries.reverse();
+                for (key, item) in entries {
+                    stack.push((format!("{}/{}", pointer, escape_pointer(key)), item));
+                }
+            }
+            _ => {}
+        }
+    }
+    leaves
+}
+
+fn escape_pointer(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+// --- Built-in detector ------------------------------------------------------
+
+type Validator = fn(&str, &str, usize, usize) -> bool;
+
+struct Pattern {
+    pii_type: PiiType,
+    regex: &'static Regex,
+    confidence: f32,
+    validate: Validator,
+}
+
+/// Regex + checksum detector for common structured PII
+pub struct BuiltinPiiDetector {
+    patterns: Vec<Pattern>,
+}
+
+/// Compile a bounded regex once. All patterns use bounded repetition and the
+/// `regex` crate guarantees linear-time matching, so large inputs are safe.
+fn compiled(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| {
+        RegexBuilder::new(pattern)
+            .size_limit(1 << 20)
+            .build()
+            .expect("built-in PII pattern must compile")
+    })
+}
+
+static EMAIL: OnceLock<Regex> = OnceLock::new();
+static PHONE: OnceLock<Regex> = OnceLock::new();
+static CARD: OnceLock<Regex> = OnceLock::new();
+static IBAN: OnceLock<Regex> = OnceLock::new();
+static US_SSN: OnceLock<Regex> = OnceLock::new();
+static GB_NINO: OnceLock<Regex> = OnceLock::new();
+static CA_SIN: OnceLock<Regex> = OnceLock::new();
+
+impl BuiltinPiiDetector {
+    pub const NAME: &'static str = "builtin";
+
+    pub fn new(locales: Vec<NationalIdLocale>) -> Self {
+        let mut patterns = vec![
+            Pattern {
+                pii_type: PiiType::Email,
+                regex: compiled(
+                    &EMAIL,
+                    r"[A-Za-z0-9._%+-]{1,64}@[A-Za-z0-9-]{1,63}(?:\.[A-Za-z0-9-]{1,63}){0,8}\.[A-Za-z]{2,24}",
+                ),
+                confidence: 0.95,
+                validate: |_, _, _, _| true,
+            },
+            Pattern {
+                pii_type: PiiType::CreditCard,
+                regex: compiled(&CARD, r"\b\d(?:[ -]?\d){12,18}\b"),
+                confidence: 0.95,
+                validate: |_, m, _, _| {
+                    let digits = digits_of(m);
+                    (13..=19).contains(&digits.len()) && luhn_valid(&digits)
+                },
+            },
+            Pattern {
+                pii_type: PiiType::Iban,
+                regex: compiled(
+                    &IBAN,
+                    r"\b[A-Z]{2}\d{2}(?: ?[A-Z0-9]{4}){2,7}(?: ?[A-Z0-9]{1,3})?\b",
+                ),
+                confidence: 0.95,
+                validate: |_, m, _, _| iban_valid(m),
+            },
+            Pattern {
+                pii_type: PiiType::Phone,
+                regex: compiled(
+                    &PHONE,
+                    r"(?:\+\d{1,3}[ .-]?)?(?:\(\d{2,4}\)|\d{2,4})[ .-]?\d{3,4}[ .-]?\d{3,4}",
+                ),
+                confidence: 0.6,
+                validate: |text, m, start, end| {
+                    (10..=15).contains(&digits_of(m).len()) && !embedded_in_number(text, start, end)
+                },
+            },
+        ];
+
+        for locale in locales {
+            patterns.push(match locale {
+                NationalIdLocale::Us => Pattern {
+                    pii_type: PiiType::NationalId,
+                    regex: compiled(&US_SSN, r"\b\d{3}-\d{2}-\d{4}\b"),
+                    confidence: 0.85,
+                    validate: |text, m, start, end| {
+                        let area = &m[0..3];
+                        area != "000"
+                            && area != "666"
+                            && !area.starts_with('9')
+                            && &m[4..6] != "00"
+                            && &m[7..11] != "0000"
+                            && !embedded_in_number(text, start, end)
+                    },
+                },
+                NationalIdLocale::Gb => Pattern {
+                    pii_type: PiiType::NationalId,
+                    regex: compiled(
+                        &GB_NINO,
+                        r"\b[A-CEGHJ-PR-TWXYZ][A-CEGHJ-NPR-TWXYZ] ?\d{2} ?\d{2} ?\d{2} ?[A-D]\b",
+                    ),
+                    confidence: 0.8,
+                    validate: |_, m, _, _| {
+                        let prefix = &m[0..2];
+                        !matches!(prefix, "BG" | "GB" | "NK" | "KN" | "TN" | "NT" | "ZZ")
+                    },
+                },
+                NationalIdLocale::Ca => Pattern {
+                    pii_type: PiiType::NationalId,
+                    regex: compiled(&CA_SIN, r"\b\d{3}[ -]?\d{3}[ -]?\d{3}\b"),
+                    confidence: 0.8,
+                    validate: |text, m, start, end| {
+                        luhn_valid(&digits_of(m)) && !embedded_in_number(text, start, end)
+                    },
+                },
+            });
+        }
+
+        Self { patterns }
+    }
+
+    fn detect_sync(&self, text: &str) -> Vec<PiiMatch> {
+        let has_digit = text.bytes().any(|b| b.is_ascii_digit());
+        let has_at = text.contains('@');
+        if !has_digit && !has_at {
+            return Vec::new();
+        }
+
+        let mut matches = Vec::new();
+        for pattern in &self.patterns {
+            let needs = if pattern.pii_type == PiiType::Email {
+                has_at
+            } else {
+                has_digit
+            };
+            if !needs {
+                continue;
+            }
+            for m in pattern.regex.find_iter(text) {
+                if (pattern.validate)(text, m.as_str(), m.start(), m.end()) {
+                    matches.push(PiiMatch {
+                        pii_type: pattern.pii_type.clone(),
+                        start: m.start(),
+                        end: m.end(),
+                        confidence: pattern.confidence,
+                    });
+                }
+            }
+        }
+        matches
+    }
+}
+
+#[async_trait]
+impl PiiDetector for BuiltinPiiDetector {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    async fn detect(&self, text: &str) -> Result<Vec<PiiMatch>, String> {
+        Ok(self.detect_sync(text))
+    }
+}
+
+fn digits_of(s: &str) -> String {
+    s.chars().filter(|c| c.is_ascii_digit()).collect()
+}
+
+/// Luhn checksum over a string of ASCII digits
+pub fn luhn_valid(digits: &str) -> bool {
+    if digits.is_empty() {
+        return false;
+    }
+    let mut sum = 0;
+    for (i, c) in digits.bytes().rev().enumerate() {
+        let mut d = (c - b'0') as u32;
+        if i % 2 == 1 {
+            d *= 2;
+            if d > 9 {
+                d -= 9;
+            }
+        }
+        sum += d;
+    }
+    sum % 10 == 0
+}
+
+/// ISO 13616 mod-97 check
+fn iban_valid(candidate: &str) -> bool {
+    let compact: String = candidate.chars().filter(|c| *c != ' ').collect();
+    if !(15..=34).contains(&compact.len()) {
+        return false;
+    }
+    let rearranged = compact[4..].chars().chain(compact[..4].chars());
+    let mut remainder: u32 = 0;
+    for c in rearranged {
+        let value = match c.to_digit(36) {
+            Some(v) => v,
+            None => return false,
+        };
+        remainder = if value >= 10 {
+            (remainder * 100 + value) % 97
+        } else {
+            (remainder * 10 + value) % 97
+        };
+    }
+    remainder == 1
+}
+
+/// True if the span is part of a longer run of digit groups
+/// (e.g. a chunk of a card number that failed its checksum)
+fn embedded_in_number(text: &str, start: usize, end: usize) -> bool {
+    let bytes = text.as_bytes();
+    let is_sep = |b: u8| matches!(b, b' ' | b'-' | b'.');
+    let before = start.checked_sub(1).map(|i| bytes[i]);
+    match before {
+        Some(b) if b.is_ascii_digit() => return true,
+        Some(b) if is_sep(b) && start >= 2 && bytes[start - 2].is_ascii_digit() => return true,
+        _ => {}
+    }
+    match bytes.get(end) {
+        Some(b) if b.is_ascii_digit() => true,
+        Some(b) if is_sep(*b) => bytes.get(end + 1).is_some_and(|n| n.is_ascii_digit()),
+        _ => false,
+    }
+}
+
+// --- LLM detector -----------------------------------------------------------
+
+const LLM_DETECTOR_PROMPT: &str = "You find personally identifiable information (PII) in text: \
+names of people, postal addresses, dates of birth, and any other personal identifiers. \
+Respond with only a JSON array of objects like {\"type\": \"name\", \"text\": \"<exact substring>\"}. \
+Respond with [] if there is none.";
+
+/// Detector that asks an LLM to find free-form PII
+///
+/// Text is sent in chunks of at most `max_chars` characters. The model
+/// returns the exact substrings it considers PII and each occurrence is
+/// reported with the detector's confidence.
+pub struct LlmPiiDetector {
+    client: LlmClient,
+    max_chars: usize,
+    confidence: f32,
+}
+
+impl LlmPiiDetector {
+    pub fn new(client: LlmClient) -> Self {
+        Self {
+            client,
+            max_chars: 8_000,
+            confidence: 0.5,
+        }
+    }
+
+    pub fn with_max_chars(mut self, max_chars: usize) -> Self {
+        self.max_chars = max_chars.max(1);
+        self
+    }
+
+    pub fn with_confidence(mut self, confidence: f32) -> Self {
+        self.confidence = confidence;
+        self
+    }
+}
+
+#[derive(Deserialize)]
+struct LlmPiiEntity {
+    #[serde(rename = "type")]
+    pii_type: String,
+    text: String,
+}
+
+#[async_trait]
+impl PiiDetector for LlmPiiDetector {
+    fn name(&self) -> &str {
+        "llm"
+    }
+
+    async fn detect(&self, text: &str) -> Result<Vec<PiiMatch>, String> {
+        let mut matches = Vec::new();
+        for (offset, chunk) in chunks(text, self.max_chars) {
+            if chunk.trim().is_empty() {
+                continue;
+            }
+            let request = ChatRequest::new(vec![
+                ChatMessage::system(LLM_DETECTOR_PROMPT),
+                ChatMessage::user(chunk),
+            ])
+            .with_temperature(0.0);
+            let response = self.client.chat(request).await.map_err(|e| e.to_string())?;
+
+            let body = response.content.trim();
+            let json = body
+                .find('[')
+                .zip(body.rfind(']'))
+                .map(|(s, e)| &body[s..=e])
+                .unwrap_or(body);
+            let entities: Vec<LlmPiiEntity> = serde_json::from_str(json)
+                .map_err(|e| format!("unparseable detector response: {}", e))?;
+
+            for entity in entities {
+                if entity.text.is_empty() {
+                    continue;
+                }
+                let pii_type = PiiType::from_label(&entity.pii_type);
+                for (start, found) in chunk.match_indices(entity.text.as_str()) {
+                    matches.push(PiiMatch {
+                        pii_type: pii_type.clone(),
+                        start: offset + start,
+                        end: offset + start + found.len(),
+                        confidence: self.confidence,
+                    });
+                }
+            }
+        }
+        Ok(matches)
+    }
+}
+
+/// Split `text` into chunks of at most `max_chars` characters with their byte offsets
+fn chunks(text: &str, max_chars: usize) -> Vec<(usize, &str)> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    while start < text.len() {
+        let end = text[start..]
+            .char_indices()
+            .nth(max_chars)
+            .map(|(i, _)| start + i)
+            .unwrap_or(text.len());
+        out.push((start, &text[start..end]));
+        start = end;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_luhn() {
+        assert!(luhn_valid("4111111111111111"));
+        assert!(luhn_valid("5500005555555559"));
+        assert!(!luhn_valid("4111111111111112"));
+    }
+
+    #[test]
+    fn test_iban_checksum() {
+        assert!(iban_valid("GB82 WEST 1234 5698 7654 32"));
+        assert!(iban_valid("DE89370400440532013000"));
+        assert!(!iban_valid("DE89370400440532013001"));
+    }
+
+    #[test]
+    fn test_pointer_escaping() {
+        let value = serde_json::json!({"a/b": {"c~d": ["x"]}});
+        let leaves = string_leaves(&value);
+        assert_eq!(leaves, vec![("/a~1b/c~0d/0".to_string(), "x")]);
+        assert!(value.pointer(&leaves[0].0).is_some());
+    }
+
+    #[test]
+    fn test_chunks_respect_char_boundaries() {
+        let parts = chunks("héllo wörld", 4);
+        let joined: String = parts.iter().map(|(_, s)| *s).collect();
+        assert_eq!(joined, "héllo wörld");
+        assert_eq!(parts[1].0, "héll".len());
+    }
+}
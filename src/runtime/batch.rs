@@ -0,0 +1,114 @@
+//! Running a single-agent workflow over many inputs as one provider batch.
+//!
+//! [`Runtime::submit_batch`](crate::runtime::Runtime::submit_batch) runs
+//! the workflow on each input up to its agent's LLM request, away from the
+//! runtime's events and usage, and submits those requests through the
+//! runtime's [`BatchChatClient`](crate::llm::BatchChatClient). The
+//! [`WorkflowBatch`] it returns serializes, so a later process can pick it
+//! up: once the batch is finished,
+//! [`Runtime::collect_batch`](crate::runtime::Runtime::collect_batch)
+//! executes each run for real, with the batch's response in place of the
+//! LLM call. The runs' events, usage and outputs are those of ordinary
+//! runs.
+//!
+//! Each input gets exactly one response, so a batched workflow is a single
+//! agent step without tools or reflection. A critic, or anything else that
+//! asks the model again, fails the run.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ConfigError, ConfigErrorCode};
+use crate::llm::{BatchHandle, ChatRequest};
+use crate::types::JsonValue;
+use crate::workflow::Workflow;
+
+/// How often `Runtime::execute_batch` polls a batch by default
+pub const DEFAULT_BATCH_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Runs submitted as a provider batch, waiting to be collected
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkflowBatch {
+    pub handle: BatchHandle,
+
+    /// The input of each run, in the order of the batch's requests
+    pub inputs: Vec<JsonValue>,
+}
+
+fn unsupported(field: &str, message: String) -> ConfigError {
+    ConfigError {
+        code: ConfigErrorCode::ValidationFailed,
+        message,
+        field: Some(field.to_string()),
+    }
+}
+
+fn with_tools(agent: &str) -> ConfigError {
+    unsupported(
+        "steps[0]",
+        format!(
+            "agent '{}' has tools; a batch answers each input with one response, so tool \
+             results could never reach the model. Run workflows with tools with \
+             Runtime::execute",
+            agent
+        ),
+    )
+}
+
+/// The workflow `template` builds, starting from `input` and checked to
+/// need one LLM response, and the name of its agent
+pub(crate) fn instantiate(
+    template: &dyn Fn() -> Workflow,
+    input: &JsonValue,
+) -> Result<(Workflow, String), ConfigError> {
+    let mut workflow = template();
+    let [step] = workflow.steps.as_slice() else {
+        return Err(unsupported(
+            "steps",
+            format!(
+                "a batched workflow is a single agent step; this one has {} steps",
+                workflow.steps.len()
+            ),
+        ));
+    };
+    let Some(agent) = step.get_agent() else {
+        return Err(unsupported(
+            "steps[0]",
+            format!(
+                "a batched workflow is a single agent step; '{}' isn't one",
+                step.name()
+            ),
+        ));
+    };
+    if !agent.config().tool_schemas().is_empty() {
+        return Err(with_tools(agent.name()));
+    }
+    if agent
+        .config()
+        .reflection
+        .as_ref()
+        .is_some_and(|r| r.enabled)
+    {
+        return Err(unsupported(
+            "steps[0]",
+            format!(
+                "agent '{}' reflects on its answers, which takes a second request; a batch \
+                 answers each input once",
+                agent.name()
+            ),
+        ));
+    }
+    let agent = agent.name().to_string();
+    workflow.initial_input = input.clone();
+    Ok((workflow, agent))
+}
+
+/// Check the request captured from `agent`; tools can come from the
+/// workflow too, e.g. a context's memory tools
+pub(crate) fn check_request(agent: &str, request: &ChatRequest) -> Result<(), ConfigError> {
+    match &request.tools {
+        Some(tools) if !tools.is_empty() => Err(with_tools(agent)),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,218 @@
+//! Saving a run's progress after every step, so it can resume elsewhere.
+//!
+//! [`Runtime::execute_resumable`](crate::Runtime::execute_resumable) saves a
+//! [`RunCheckpoint`] to a [`CheckpointStore`] after each completed step and
+//! removes it once the run completes. If the process dies, or a step fails,
+//! [`Runtime::resume`](crate::Runtime::resume) picks the run up from the
+//! last checkpoint. Completed steps are restored from their records, never
+//! executed again, so agents aren't asked twice.
+
+use crate::context::WorkflowContext;
+use crate::runtime::rerun::{RerunError, RerunPlan};
+use crate::types::JsonValue;
+use crate::workflow::report::file_safe;
+use crate::workflow::{Workflow, WorkflowStepRecord};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Version of the checkpoint layout, bumped on incompatible changes
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+/// Where a run stood after its last completed step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunCheckpoint {
+    pub version: u32,
+
+    pub workflow_id: String,
+
+    /// The checkpointed run, which a resume continues; the store's key
+    #[serde(default)]
+    pub run_id: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_workflow_id: Option<String>,
+
+    /// Index of the step to run next
+    pub next_step: usize,
+
+    /// Records of the completed steps, in order
+    pub steps: Vec<WorkflowStepRecord>,
+
+    /// Output of the last completed step, which the next step receives
+    pub data: JsonValue,
+
+    /// The workflow context after the last completed step, if the workflow
+    /// has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<WorkflowContext>,
+
+    pub saved_at: DateTime<Utc>,
+}
+
+impl RunCheckpoint {
+    /// Check that `workflow` starts with the checkpointed steps, and turn
+    /// the checkpoint into a plan that skips them
+    pub(crate) fn plan(self, workflow: &Workflow) -> Result<RerunPlan, RerunError> {
+        if self.version > CHECKPOINT_VERSION {
+            return Err(RerunError::UnsupportedCheckpoint {
+                found: self.version,
+                supported: CHECKPOINT_VERSION,
+            });
+        }
+        if self.next_step > workflow.steps.len() || self.next_step != self.steps.len() {
+            return Err(RerunError::StartOutOfRange {
+                index: self.next_step,
+                steps: workflow.steps.len(),
+                recorded: self.steps.len(),
+            });
+        }
+        for (index, (step, record)) in workflow.steps.iter().zip(&self.steps).enumerate() {
+            let step_type = format!("{:?}", step.step_type());
+            let reason = if step.name() != record.step_name {
+                format!("the checkpoint recorded '{}' here", record.step_name)
+            } else if step_type != record.step_type {
+                format!(
+                    "it is a {} step but the checkpoint recorded a {} step",
+                    step_type, record.step_type
+                )
+            } else {
+                continue;
+            };
+            return Err(RerunError::StepMismatch {
+                index,
+                name: step.name().to_string(),
+                reason,
+            });
+        }
+
+        Ok(RerunPlan {
+            rerun_of: None,
+            start: self.next_step,
+            input: self.data,
+            replayed: self.steps,
+            context: self.context,
+        })
+    }
+}
+
+/// Why a checkpoint could not be saved or loaded
+#[derive(Debug, thiserror::Error)]
+pub enum CheckpointError {
+    #[error("checkpoint I/O failed: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("corrupt checkpoint: {0}")]
+    Corrupt(String),
+}
+
+/// Persistence for run checkpoints, keyed by run ID; a save replaces the
+/// run's previous checkpoint
+#[async_trait]
+pub trait CheckpointStore: Send + Sync {
+    async fn save(&self, checkpoint: &RunCheckpoint) -> Result<(), CheckpointError>;
+
+    async fn load(&self, run_id: &str) -> Result<Option<RunCheckpoint>, CheckpointError>;
+
+    /// Forget the run's checkpoint; removing a missing one is not an error
+    async fn remove(&self, run_id: &str) -> Result<(), CheckpointError>;
+}
+
+/// [`CheckpointStore`] keeping one JSON file per run in a directory
+///
+/// Saves write a temporary file and rename it over the old one, so a crash
+/// mid-save leaves the previous checkpoint intact.
+#[derive(Debug, Clone)]
+pub struct FileCheckpointStore {
+    dir: PathBuf,
+}
+
+impl FileCheckpointStore {
+    /// Store checkpoints in `dir`, which is created on the first save
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// File holding the checkpoint of the run `run_id`
+    pub fn path(&self, run_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", file_safe(run_id)))
+    }
+}
+
+#[async_trait]
+impl CheckpointStore for FileCheckpointStore {
+    async fn save(&self, checkpoint: &RunCheckpoint) -> Result<(), CheckpointError> {
+        let data = serde_json::to_vec_pretty(checkpoint)
+            .map_err(|e| CheckpointError::Corrupt(e.to_string()))?;
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let path = self.path(&checkpoint.run_id);
+        let temp = path.with_extension("json.tmp");
+        tokio::fs::write(&temp, data).await?;
+        tokio::fs::rename(&temp, &path).await?;
+        Ok(())
+    }
+
+    async fn load(&self, run_id: &str) -> Result<Option<RunCheckpoint>, CheckpointError> {
+        match tokio::fs::read(self.path(run_id)).await {
+            Ok(data) => serde_json::from_slice(&data)
+                .map(Some)
+                .map_err(|e| CheckpointError::Corrupt(e.to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn remove(&self, run_id: &str) -> Result<(), CheckpointError> {
+        match tokio::fs::remove_file(self.path(run_id)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// In-memory [`CheckpointStore`]
+#[derive(Debug, Default)]
+pub struct InMemoryCheckpointStore {
+    checkpoints: Mutex<HashMap<String, RunCheckpoint>>,
+}
+
+impl InMemoryCheckpointStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.checkpoints.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl CheckpointStore for InMemoryCheckpointStore {
+    async fn save(&self, checkpoint: &RunCheckpoint) -> Result<(), CheckpointError> {
+        self.checkpoints
+            .lock()
+            .unwrap()
+            .insert(checkpoint.run_id.clone(), checkpoint.clone());
+        Ok(())
+    }
+
+    async fn load(&self, run_id: &str) -> Result<Option<RunCheckpoint>, CheckpointError> {
+        Ok(self.checkpoints.lock().unwrap().get(run_id).cloned())
+    }
+
+    async fn remove(&self, run_id: &str) -> Result<(), CheckpointError> {
+        self.checkpoints.lock().unwrap().remove(run_id);
+        Ok(())
+    }
+}
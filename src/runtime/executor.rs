@@ -1,7 +1,44 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::StreamExt;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+
 use crate::{
-    event::{Event, EventStream},
+    artifact::{ArtifactRef, ArtifactStore, NewArtifact},
+    config::DEFAULT_MAX_SUBWORKFLOW_DEPTH,
+    context::{ContextMonitor, ContextStoreError},
+    error::{ConfigError, ConfigErrorCode, RuntimeError, WorkflowError},
+    event::{
+        sampling::{self, SamplingPolicy, TraceDecision},
+        webhook::WebhookSubscriber,
+        ComponentStatus, Event, EventScope, EventStream, EventType, RedactionRules,
+    },
+    llm::{
+        batch::{self as llm_batch, BatchTurn},
+        rate_limit::{self, RateLimiter},
+        BatchChatClient, BatchStatus,
+    },
+    pii::{PiiAction, PiiFindings, PiiScanner},
+    runtime::batch::{self, WorkflowBatch},
+    runtime::checkpoint::{CheckpointStore, RunCheckpoint, CHECKPOINT_VERSION},
+    runtime::explain::{self, ExplainOptions, RunExplanation},
+    runtime::heartbeat::{self, HeartbeatConfig, RunActivity},
+    runtime::rerun::{self, RerunError, RerunOptions, RerunPlan},
+    runtime::streaming::{FlushPolicy, OutputForwarder, WorkflowStream},
+    types::JsonValue,
+    usage::{self, RunMeter, UsageLedger, UsageTotals, WorkflowUsage},
     workflow::{
-        step::StepInputMetadata, steps::SubWorkflowStep, ExecutionContext, StepInput, StepType,
+        contract,
+        step::{StepError, StepInputMetadata, StepResult},
+        steps::{
+            execute_with_policy, ApprovalQueue, Decision, PendingApproval, StepStatus,
+            SubWorkflowStep,
+        },
+        ExecutionContext, ReportFiles, StepFailure, StepInput, StepType, SubWorkflowSummary,
         Workflow, WorkflowRun, WorkflowState, WorkflowStepRecord,
     },
 };
@@ -9,60 +46,928 @@ use crate::{
 /// Runtime for executing workflows
 pub struct Runtime {
     event_stream: EventStream,
+    artifacts: ArtifactStore,
+    pii_scanner: Option<PiiScanner>,
+    context_monitors: Mutex<HashMap<String, ContextMonitor>>,
+    shutdown: CancellationToken,
+    run_tokens: Mutex<HashMap<String, CancellationToken>>,
+    usage: UsageLedger,
+    record_context: bool,
+    webhooks: Option<WebhookSubscriber>,
+    approvals: ApprovalQueue,
+    rate_limiter: Option<RateLimiter>,
+    batch_client: Option<Arc<dyn BatchChatClient>>,
+    batch_poll_interval: Duration,
+    max_subworkflow_depth: usize,
+    sub_workflows: Mutex<HashMap<String, Vec<SubWorkflowSummary>>>,
+    heartbeat: Option<HeartbeatConfig>,
 }
 
 impl Runtime {
     pub fn new() -> Self {
         Self {
             event_stream: EventStream::new(),
+            artifacts: ArtifactStore::new(),
+            pii_scanner: None,
+            context_monitors: Mutex::new(HashMap::new()),
+            shutdown: CancellationToken::new(),
+            run_tokens: Mutex::new(HashMap::new()),
+            usage: UsageLedger::new(),
+            record_context: false,
+            webhooks: None,
+            approvals: ApprovalQueue::new(),
+            rate_limiter: None,
+            batch_client: None,
+            batch_poll_interval: batch::DEFAULT_BATCH_POLL_INTERVAL,
+            max_subworkflow_depth: DEFAULT_MAX_SUBWORKFLOW_DEPTH,
+            sub_workflows: Mutex::new(HashMap::new()),
+            heartbeat: None,
         }
     }
 
+    /// Use a shared artifact store (e.g. to read exported artifacts afterwards)
+    pub fn with_artifact_store(mut self, store: ArtifactStore) -> Self {
+        self.artifacts = store;
+        self
+    }
+
+    /// Scan outputs for PII before they leave the runtime
+    pub fn with_pii_scanner(mut self, scanner: PiiScanner) -> Self {
+        self.pii_scanner = Some(scanner);
+        self
+    }
+
+    /// Record LLM usage in `ledger` (e.g. one configured with prices and
+    /// retention, or shared between runtimes)
+    pub fn with_usage_ledger(mut self, ledger: UsageLedger) -> Self {
+        self.usage = ledger;
+        self
+    }
+
+    /// Make every agent LLM request in this runtime's runs wait on
+    /// `limiter`, on top of any limits of the agents' own clients
+    pub fn with_rate_limiter(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Submit [`submit_batch`](Self::submit_batch) runs through `client`
+    pub fn with_batch_client(mut self, client: Arc<dyn BatchChatClient>) -> Self {
+        self.batch_client = Some(client);
+        self
+    }
+
+    /// Poll batches this often in [`execute_batch`](Self::execute_batch)
+    /// (default: 30 seconds)
+    pub fn with_batch_poll_interval(mut self, interval: Duration) -> Self {
+        self.batch_poll_interval = interval;
+        self
+    }
+
+    /// Let sub-workflows nest `depth` levels below a top-level run (default
+    /// 8, see `WorkflowConfig::max_subworkflow_depth`)
+    pub fn with_max_subworkflow_depth(mut self, depth: usize) -> Self {
+        self.max_subworkflow_depth = depth;
+        self
+    }
+
+    /// Emit a heartbeat for every run, and report runs that stop making
+    /// progress (see [`heartbeat`](crate::runtime::heartbeat))
+    pub fn with_heartbeat(mut self, config: HeartbeatConfig) -> Self {
+        self.heartbeat = Some(config);
+        self
+    }
+
+    pub(crate) fn max_subworkflow_depth(&self) -> usize {
+        self.max_subworkflow_depth
+    }
+
+    /// File a finished sub-workflow under the run `parent_run_id`
+    pub(crate) fn record_sub_workflow(&self, parent_run_id: &str, summary: SubWorkflowSummary) {
+        self.sub_workflows
+            .lock()
+            .unwrap()
+            .entry(parent_run_id.to_string())
+            .or_default()
+            .push(summary);
+    }
+
+    /// Store the workflow context after every step as an exported artifact
+    /// (`context-step-N.json`), so a rerun can pick up from it
+    pub fn with_context_recording(mut self) -> Self {
+        self.record_context = true;
+        self
+    }
+
+    /// Name whoever decides through [`resolve_approval`](Self::resolve_approval),
+    /// e.g. from the request being handled; recorded in `WorkflowRun::approvals`
+    pub fn with_approval_decider<F>(mut self, decider: F) -> Self
+    where
+        F: Fn(&PendingApproval) -> String + Send + Sync + 'static,
+    {
+        self.approvals = self.approvals.with_decider(decider);
+        self
+    }
+
+    /// Approval steps waiting for a decision, oldest first
+    pub fn pending_approvals(&self) -> Vec<PendingApproval> {
+        self.approvals.pending()
+    }
+
+    /// Decide a pending approval; returns `false` if no such approval is
+    /// waiting (e.g. it was already decided or timed out)
+    pub fn resolve_approval(&self, approval_id: &str, decision: Decision) -> bool {
+        self.approvals.resolve(approval_id, decision)
+    }
+
+    /// Decide a pending approval on behalf of `decided_by`
+    pub fn resolve_approval_as(
+        &self,
+        approval_id: &str,
+        decision: Decision,
+        decided_by: impl Into<String>,
+    ) -> bool {
+        self.approvals.resolve_as(approval_id, decision, decided_by)
+    }
+
+    /// Usage of every LLM call made in this runtime's runs
+    pub fn usage_ledger(&self) -> &UsageLedger {
+        &self.usage
+    }
+
     /// Get a reference to the event stream for subscribing to events
     pub fn event_stream(&self) -> &EventStream {
         &self.event_stream
     }
 
+    /// Get the artifact store tools write to during runs
+    pub fn artifact_store(&self) -> &ArtifactStore {
+        &self.artifacts
+    }
+
+    /// Watch the context of the in-flight run `run_id` (see
+    /// `Workflow::run_id`). Returns `None` once the run has finished or if
+    /// the workflow has no context; monitors handed out earlier keep working
+    /// after the run ends.
+    pub fn context_monitor(&self, run_id: &str) -> Option<ContextMonitor> {
+        self.context_monitors.lock().unwrap().get(run_id).cloned()
+    }
+
+    /// Cancel the in-flight run `run_id` (and its sub-workflows); the ID is
+    /// the workflow's `run_id`, also on the returned `WorkflowRun`. Tools
+    /// observing their `ToolRunContext::cancellation` stop early; returns
+    /// `false` if no such run is in flight.
+    pub fn cancel(&self, run_id: &str) -> bool {
+        match self.run_tokens.lock().unwrap().get(run_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancel every in-flight run and any started later
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// Summarize a finished run in plain language. `events` should be the
+    /// run's events (e.g. `events_from_offset` from before it started); the
+    /// narrative comes from a template unless `options` supplies an LLM.
+    pub async fn explain_run(
+        run: &WorkflowRun,
+        events: &[Event],
+        options: ExplainOptions,
+    ) -> RunExplanation {
+        explain::explain_run(run, events, &options).await
+    }
+
     /// Execute a workflow and return the run with complete history
     pub async fn execute(&self, workflow: Workflow) -> WorkflowRun {
         self.execute_with_parent(workflow, None).await
     }
 
+    /// Run `template` once per input, at most `concurrency` runs at a time,
+    /// returning the runs in input order
+    ///
+    /// Each run is a copy from [`Workflow::instantiate`], with its own
+    /// workflow id and context. Fails before anything runs if a step of the
+    /// template can't be copied.
+    pub async fn execute_many(
+        &self,
+        template: &Workflow,
+        inputs: Vec<JsonValue>,
+        concurrency: usize,
+    ) -> Result<Vec<WorkflowRun>, WorkflowError> {
+        let workflows = inputs
+            .into_iter()
+            .map(|input| {
+                let mut workflow = template.instantiate()?;
+                workflow.initial_input = input;
+                Ok(workflow)
+            })
+            .collect::<Result<Vec<_>, WorkflowError>>()?;
+        Ok(futures::stream::iter(workflows)
+            .map(|workflow| self.execute(workflow))
+            .buffered(concurrency.max(1))
+            .collect()
+            .await)
+    }
+
+    /// Execute a workflow and write its JSON and HTML reports to `out_dir`
+    ///
+    /// The run is returned even if its reports could not be written.
+    pub async fn execute_and_report(
+        &self,
+        workflow: Workflow,
+        out_dir: impl AsRef<Path>,
+    ) -> (WorkflowRun, std::io::Result<ReportFiles>) {
+        let run = self.execute(workflow).await;
+        let files = run.write_reports(out_dir);
+        (run, files)
+    }
+
+    /// Execute a workflow, following it as a stream of typed updates
+    ///
+    /// The stream sees every event of the run from its first one on, even
+    /// if polling starts late, and ends after `WorkflowCompleted` or
+    /// `WorkflowFailed`, which carry the finished run.
+    ///
+    /// ```rust,ignore
+    /// let mut updates = runtime.execute_streaming(workflow);
+    /// while let Some(update) = updates.next().await {
+    ///     if let WorkflowUpdate::LlmChunk { text, .. } = update {
+    ///         print!("{}", text);
+    ///     }
+    /// }
+    /// ```
+    pub fn execute_streaming(&self, workflow: Workflow) -> WorkflowStream<'_> {
+        // Subscribe before the run can emit anything
+        let subscription = self
+            .event_stream
+            .subscribe_from(self.event_stream.current_offset());
+        WorkflowStream::new(workflow.id.clone(), subscription, self.execute(workflow))
+    }
+
+    /// Execute a workflow, streaming its answer to the caller as it is
+    /// generated, e.g. into a chat UI's HTTP response
+    ///
+    /// The stream yields the text chunks of the last agent step's final
+    /// response, in order, and ends once the run has. Chunks of responses
+    /// that call tools are never passed on. The future drives the run and
+    /// must be polled alongside the stream:
+    ///
+    /// ```rust,ignore
+    /// let (chunks, run) = runtime.execute_with_output_stream(workflow);
+    /// let (_, run) = tokio::join!(chunks.for_each(|chunk| send(chunk)), run);
+    /// ```
+    pub fn execute_with_output_stream(
+        &self,
+        workflow: Workflow,
+    ) -> (
+        impl futures::Stream<Item = String> + Send + 'static,
+        impl std::future::Future<Output = WorkflowRun> + '_,
+    ) {
+        self.execute_with_flush_policy(workflow, FlushPolicy::default())
+    }
+
+    /// [`execute_with_output_stream`](Self::execute_with_output_stream),
+    /// passing on the chunks `policy` selects
+    pub fn execute_with_flush_policy(
+        &self,
+        workflow: Workflow,
+        policy: FlushPolicy,
+    ) -> (
+        impl futures::Stream<Item = String> + Send + 'static,
+        impl std::future::Future<Output = WorkflowRun> + '_,
+    ) {
+        let (tx, rx) = futures::channel::mpsc::unbounded();
+        let mut forwarder = OutputForwarder::new(&workflow, policy, tx);
+        // Subscribe before the run can emit anything
+        let mut subscription = self
+            .event_stream
+            .subscribe_from(self.event_stream.current_offset());
+        let run = async move {
+            let execution = self.execute(workflow);
+            tokio::pin!(execution);
+            let mut finished = None;
+            // The run returns before its terminal event is published, and
+            // the event can come first too; the stream ends with the event
+            loop {
+                tokio::select! {
+                    run = &mut execution, if finished.is_none() => finished = Some(run),
+                    event = subscription.recv() => {
+                        if forwarder.observe(event) {
+                            break;
+                        }
+                    }
+                }
+            }
+            drop(forwarder);
+            match finished {
+                Some(run) => run,
+                None => execution.await,
+            }
+        };
+        (rx, run)
+    }
+
+    /// Start a run that can be canceled through the returned handle, e.g.
+    /// when the client that requested it disconnects
+    ///
+    /// Canceling stops the run between steps, between an agent's tool
+    /// iterations and during LLM requests. The future resolves to a
+    /// `WorkflowState::Canceled` run holding the steps that completed.
+    /// Dropping the future cancels the run too.
+    pub fn execute_cancellable(
+        &self,
+        workflow: Workflow,
+    ) -> (
+        CancellationHandle,
+        impl std::future::Future<Output = WorkflowRun> + '_,
+    ) {
+        let token = self.shutdown.child_token();
+        let handle = CancellationHandle {
+            workflow_id: workflow.id.clone(),
+            run_id: workflow.run_id.clone(),
+            token: token.clone(),
+        };
+        (
+            handle,
+            self.execute_planned(workflow, None, None, None, Some(token), None),
+        )
+    }
+
+    /// Re-execute `workflow` from a later step, replaying the recorded
+    /// outputs of `run`'s earlier steps instead of running them again
+    ///
+    /// The replayed steps must match the recording by name and type, and
+    /// the new workflow's transforms and conditions must still produce
+    /// compatible data from the recorded inputs. Steps from the starting
+    /// point on are executed normally; the returned run links back through
+    /// `rerun_of` and marks the copied steps as `replayed`.
+    pub async fn rerun_from(
+        &self,
+        run: &WorkflowRun,
+        mut workflow: Workflow,
+        options: RerunOptions,
+    ) -> Result<WorkflowRun, RerunError> {
+        let mut plan = rerun::plan(run, &workflow, &options, &self.artifacts).await?;
+        if let Some(context) = plan.context.take() {
+            workflow.restore_context(context);
+        }
+        Ok(self
+            .execute_planned(
+                workflow,
+                run.parent_workflow_id.clone(),
+                None,
+                Some(plan),
+                None,
+                None,
+            )
+            .await)
+    }
+
+    /// Execute `workflow`, saving a [`RunCheckpoint`] to `store` after every
+    /// completed step
+    ///
+    /// Checkpoints are saved under the workflow's `run_id`, which the
+    /// returned run carries too. The checkpoint is removed once the run
+    /// completes. A run that fails,
+    /// is canceled or never finishes (e.g. the process died) keeps its last
+    /// checkpoint for [`resume`](Self::resume). A checkpoint that can't be
+    /// saved is reported as a `system:checkpoint_failed` event; the run
+    /// carries on.
+    pub async fn execute_resumable(
+        &self,
+        workflow: Workflow,
+        store: &dyn CheckpointStore,
+    ) -> WorkflowRun {
+        self.execute_planned(workflow, None, None, None, None, Some(store))
+            .await
+    }
+
+    /// Continue the run saved in `checkpoint` with `workflow`, built again
+    /// from the same definition or builder
+    ///
+    /// The checkpointed steps must match the workflow's first steps by name
+    /// and type. They are restored from their records, not executed again;
+    /// the context is restored and the next step gets the last step's
+    /// output. The run keeps the checkpoint's workflow and run IDs, and its
+    /// `Workflow::Started` event has `resumed: true` and
+    /// `resumed_from_step`. Usage covers the resumed steps only.
+    pub async fn resume(
+        &self,
+        workflow: Workflow,
+        checkpoint: RunCheckpoint,
+    ) -> Result<WorkflowRun, RerunError> {
+        self.resume_with(workflow, checkpoint, None).await
+    }
+
+    /// [`resume`](Self::resume), saving checkpoints to `store` as the run
+    /// goes on, like [`execute_resumable`](Self::execute_resumable)
+    pub async fn resume_resumable(
+        &self,
+        workflow: Workflow,
+        checkpoint: RunCheckpoint,
+        store: &dyn CheckpointStore,
+    ) -> Result<WorkflowRun, RerunError> {
+        self.resume_with(workflow, checkpoint, Some(store)).await
+    }
+
+    async fn resume_with(
+        &self,
+        mut workflow: Workflow,
+        checkpoint: RunCheckpoint,
+        store: Option<&dyn CheckpointStore>,
+    ) -> Result<WorkflowRun, RerunError> {
+        let parent_workflow_id = checkpoint.parent_workflow_id.clone();
+        workflow.id = checkpoint.workflow_id.clone();
+        if !checkpoint.run_id.is_empty() {
+            workflow.run_id = checkpoint.run_id.clone();
+        }
+        let mut plan = checkpoint.plan(&workflow)?;
+        if let Some(context) = plan.context.take() {
+            workflow.restore_context(context);
+        }
+        Ok(self
+            .execute_planned(workflow, parent_workflow_id, None, Some(plan), None, store)
+            .await)
+    }
+
+    /// Submit a run of the workflow `template` builds for each of `inputs`
+    /// as one provider batch, see [`batch`](crate::runtime::batch)
+    ///
+    /// Each run is taken as far as its LLM request without the runtime
+    /// seeing it. Nothing is submitted unless every input gets there and
+    /// the workflow is a single agent step without tools or reflection;
+    /// otherwise this fails with a `ConfigError` naming the problem.
+    pub async fn submit_batch<F>(
+        &self,
+        template: F,
+        inputs: Vec<JsonValue>,
+    ) -> Result<WorkflowBatch, RuntimeError>
+    where
+        F: Fn() -> Workflow,
+    {
+        let client = self.batch_client()?;
+        let mut scratch = Runtime::new();
+        scratch.pii_scanner = self.pii_scanner.clone();
+
+        let mut requests = Vec::with_capacity(inputs.len());
+        for (index, input) in inputs.iter().enumerate() {
+            let (workflow, agent) = batch::instantiate(&template, input)?;
+            let (turn, captured) = BatchTurn::capture();
+            let request = tokio::select! {
+                Ok(request) = captured => request,
+                run = llm_batch::scoped(turn, scratch.execute(workflow)) => {
+                    let reason = run.failure.map_or_else(
+                        || "it made no LLM request".to_string(),
+                        |failure| failure.error.to_string(),
+                    );
+                    return Err(ConfigError {
+                        code: ConfigErrorCode::ValidationFailed,
+                        message: format!("input {} doesn't get to the LLM request: {}", index, reason),
+                        field: Some(format!("inputs[{}]", index)),
+                    }
+                    .into());
+                }
+            };
+            batch::check_request(&agent, &request)?;
+            requests.push(request);
+        }
+
+        let handle = client.submit_batch(requests).await?;
+        Ok(WorkflowBatch { handle, inputs })
+    }
+
+    /// Where a submitted batch is at
+    pub async fn poll_batch(&self, batch: &WorkflowBatch) -> Result<BatchStatus, RuntimeError> {
+        Ok(self.batch_client()?.poll(&batch.handle).await?)
+    }
+
+    /// Execute the runs of a finished batch, each answered by the batch's
+    /// result for its input; `None` while the batch is still running
+    ///
+    /// `template` should build the workflow the batch was submitted with.
+    /// A request the batch failed fails its run with the provider's error.
+    pub async fn collect_batch<F>(
+        &self,
+        template: F,
+        batch: &WorkflowBatch,
+    ) -> Result<Option<Vec<WorkflowRun>>, RuntimeError>
+    where
+        F: Fn() -> Workflow,
+    {
+        let client = self.batch_client()?;
+        if !client.poll(&batch.handle).await?.is_finished() {
+            return Ok(None);
+        }
+        let results = client.results(&batch.handle).await?;
+        if results.len() != batch.inputs.len() {
+            return Err(RuntimeError::Llm(
+                crate::llm::LlmError::ParseError(format!(
+                    "batch {} returned {} results for {} inputs",
+                    batch.handle.batch_id,
+                    results.len(),
+                    batch.inputs.len()
+                ))
+                .into(),
+            ));
+        }
+
+        let mut runs = Vec::with_capacity(results.len());
+        for (input, result) in batch.inputs.iter().zip(results) {
+            let (workflow, _) = batch::instantiate(&template, input)?;
+            let run = llm_batch::scoped(BatchTurn::replay(result), self.execute(workflow)).await;
+            runs.push(run);
+        }
+        Ok(Some(runs))
+    }
+
+    /// [`submit_batch`](Self::submit_batch), then poll the batch until it
+    /// finishes and [`collect_batch`](Self::collect_batch) its runs, in
+    /// input order
+    pub async fn execute_batch<F>(
+        &self,
+        template: F,
+        inputs: Vec<JsonValue>,
+    ) -> Result<Vec<WorkflowRun>, RuntimeError>
+    where
+        F: Fn() -> Workflow,
+    {
+        let batch = self.submit_batch(&template, inputs).await?;
+        loop {
+            if let Some(runs) = self.collect_batch(&template, &batch).await? {
+                return Ok(runs);
+            }
+            tokio::time::sleep(self.batch_poll_interval).await;
+        }
+    }
+
+    fn batch_client(&self) -> Result<&dyn BatchChatClient, ConfigError> {
+        self.batch_client.as_deref().ok_or_else(|| ConfigError {
+            code: ConfigErrorCode::MissingRequiredField,
+            message: "batch runs need a batch client, see Runtime::with_batch_client".to_string(),
+            field: Some("batch_client".to_string()),
+        })
+    }
+
+    /// Only emit full event detail for the runs `policy` samples
+    pub fn with_trace_sampling(mut self, policy: SamplingPolicy) -> Self {
+        self.event_stream = self.event_stream.with_sampling(policy);
+        self
+    }
+
+    /// Redact every event with `rules` before it is stored or broadcast
+    pub fn with_event_redaction(mut self, rules: RedactionRules) -> Self {
+        self.event_stream = self.event_stream.with_redaction(rules);
+        self
+    }
+
+    /// Deliver this runtime's events to `subscriber`'s webhooks
+    pub fn with_webhooks(mut self, subscriber: WebhookSubscriber) -> Self {
+        subscriber.attach(&self.event_stream);
+        self.webhooks = Some(subscriber);
+        self
+    }
+
+    /// The attached webhook subscriber, for adding subscriptions or
+    /// inspecting deliveries
+    pub fn webhooks(&self) -> Option<&WebhookSubscriber> {
+        self.webhooks.as_ref()
+    }
+
     /// Execute a workflow with optional parent workflow context
     pub async fn execute_with_parent(
+        &self,
+        workflow: Workflow,
+        parent_workflow_id: Option<String>,
+    ) -> WorkflowRun {
+        self.execute_planned(workflow, parent_workflow_id, None, None, None, None)
+            .await
+    }
+
+    /// Execute `workflow` as a sub-workflow of the in-flight run
+    /// `parent_run_id`, which cancels it along with its own
+    pub(crate) async fn execute_sub_workflow(
+        &self,
+        workflow: Workflow,
+        parent_workflow_id: String,
+        parent_run_id: &str,
+    ) -> WorkflowRun {
+        self.execute_planned(
+            workflow,
+            Some(parent_workflow_id),
+            Some(parent_run_id),
+            None,
+            None,
+            None,
+        )
+        .await
+    }
+
+    async fn execute_planned(
+        &self,
+        workflow: Workflow,
+        parent_workflow_id: Option<String>,
+        parent_run_id: Option<&str>,
+        rerun: Option<RerunPlan>,
+        token: Option<CancellationToken>,
+        checkpoints: Option<&dyn CheckpointStore>,
+    ) -> WorkflowRun {
+        let started = std::time::Instant::now();
+        let workflow_id = workflow.id.clone();
+        let run_id = workflow.run_id.clone();
+        let span = crate::telemetry::workflow_span(&workflow_id, parent_workflow_id.as_deref());
+        self.event_stream.enter_workflow(
+            &run_id,
+            &workflow_id,
+            parent_workflow_id.as_deref(),
+            parent_run_id,
+        );
+        let trace = self
+            .event_stream
+            .begin_trace(&run_id, &workflow_id, &workflow.labels);
+        if let Some(monitor) = workflow.context_monitor() {
+            self.context_monitors
+                .lock()
+                .unwrap()
+                .insert(run_id.clone(), monitor);
+        }
+
+        // Sub-workflows are canceled with their parent; dropping this future
+        // (e.g. on a deadline) cancels the run's tools too
+        let cancellation = {
+            let mut tokens = self.run_tokens.lock().unwrap();
+            let token = token.unwrap_or_else(|| {
+                parent_run_id
+                    .and_then(|parent| tokens.get(parent))
+                    .unwrap_or(&self.shutdown)
+                    .child_token()
+            });
+            tokens.insert(run_id.clone(), token.clone());
+            token
+        };
+        let _abandon_guard = cancellation.clone().drop_guard();
+
+        let meter = RunMeter::new(
+            self.usage.clone(),
+            workflow_id.clone(),
+            workflow.labels.clone(),
+            workflow.budget.clone(),
+        );
+        // Boxed, so nesting sub-workflows doesn't copy the run's state
+        // machine onto the stack at every level
+        let run = usage::metered(
+            meter.clone(),
+            Box::pin(self.run_workflow(
+                workflow,
+                parent_workflow_id,
+                trace,
+                &cancellation,
+                rerun,
+                checkpoints,
+            )),
+        );
+        let run = rate_limit::limited(self.rate_limiter.clone(), run);
+        let mut run = sampling::in_run(run_id.clone(), run)
+            .instrument(span.clone())
+            .await;
+        self.finish_artifacts(&mut run, parent_run_id);
+        run.approvals = self.approvals.take_records(&run_id);
+        run.sub_workflows = self
+            .sub_workflows
+            .lock()
+            .unwrap()
+            .remove(&run_id)
+            .unwrap_or_default();
+        run.usage = meter.totals();
+        run.usage_breakdown = meter.breakdown();
+        // A sub-workflow's usage counts toward the step that started it
+        usage::roll_up(&run.usage_breakdown);
+
+        // Tail sampling delivers an upgraded run's detail after its terminal event
+        if let Some(trace) = self.event_stream.finish_trace(
+            &run_id,
+            matches!(
+                run.state,
+                WorkflowState::Failed | WorkflowState::Canceled | WorkflowState::BudgetExceeded
+            ),
+        ) {
+            run.trace = Some(trace);
+        }
+        self.context_monitors.lock().unwrap().remove(&run_id);
+        self.run_tokens.lock().unwrap().remove(&run_id);
+        self.event_stream.leave_workflow(&run_id);
+        crate::metrics::workflow_finished(&run.state, started.elapsed());
+        span.record("state", format!("{:?}", run.state));
+        span.record("prompt_tokens", run.usage.prompt_tokens);
+        span.record("completion_tokens", run.usage.completion_tokens);
+        span.record("total_tokens", run.usage.total_tokens);
+        let error = match (&run.state, &run.failure) {
+            (_, Some(failure)) => Some(failure.error.to_string()),
+            (WorkflowState::Failed, None) => Some("workflow failed".to_string()),
+            _ => None,
+        };
+        crate::telemetry::finish(&span, started.elapsed(), error.as_deref());
+        run
+    }
+
+    async fn run_workflow(
         &self,
         mut workflow: Workflow,
         parent_workflow_id: Option<String>,
+        trace: Option<TraceDecision>,
+        cancellation: &CancellationToken,
+        rerun: Option<RerunPlan>,
+        checkpoints: Option<&dyn CheckpointStore>,
     ) -> WorkflowRun {
         let workflow_id = workflow.id.clone();
 
         // Emit Workflow::Started event
-        self.event_stream.workflow_started(
-            &workflow_id,
-            serde_json::json!({
-                "step_count": workflow.steps.len(),
-                "parent_workflow_id": parent_workflow_id,
-            }),
-        );
+        let mut started = serde_json::json!({
+            "run_id": &workflow.run_id,
+            "step_count": workflow.steps.len(),
+            "parent_workflow_id": parent_workflow_id,
+            "trace": trace,
+        });
+        match &rerun {
+            Some(RerunPlan {
+                rerun_of: Some(rerun_of),
+                start,
+                ..
+            }) => {
+                started["rerun_of"] = rerun_of.clone().into();
+                started["replayed_steps"] = (*start).into();
+            }
+            Some(plan) => {
+                started["resumed"] = true.into();
+                started["resumed_from_step"] = plan.start.into();
+            }
+            None => {}
+        }
+        self.event_stream.workflow_started(&workflow_id, started);
 
         workflow.state = WorkflowState::Running;
 
         let mut run = WorkflowRun {
             workflow_id: workflow_id.clone(),
+            run_id: workflow.run_id.clone(),
             state: WorkflowState::Running,
             steps: Vec::new(),
             final_output: None,
             parent_workflow_id: parent_workflow_id.clone(),
+            artifacts: Vec::new(),
+            pii_findings: None,
+            trace,
+            usage: UsageTotals::default(),
+            usage_breakdown: WorkflowUsage::default(),
+            rerun_of: None,
+            failure: None,
+            approvals: Vec::new(),
+            sub_workflows: Vec::new(),
+        };
+
+        // Artifacts produced by this run are tagged with its ID
+        let run_artifacts = self.artifacts.scoped(&run.run_id);
+        if let Some(document) = &workflow.document {
+            let nested = parent_workflow_id.is_some();
+            if let Err(owner) =
+                document.attach(&self.event_stream, &workflow_id, &run.run_id, nested)
+            {
+                let message = format!(
+                    "Live document '{}' already belongs to run '{}'",
+                    document.title(),
+                    owner
+                );
+                self.event_stream.workflow_failed(
+                    &workflow_id,
+                    &message,
+                    serde_json::json!({ "document_run_id": owner }),
+                );
+                workflow.state = WorkflowState::Failed;
+                run.state = WorkflowState::Failed;
+                return run;
+            }
+        }
+
+        let mut first_step = 0;
+        let planned = rerun.is_some();
+        let mut current_data = if let Some(plan) = rerun {
+            run.rerun_of = plan.rerun_of;
+            run.steps = plan.replayed;
+            first_step = plan.start;
+            plan.input
+        } else {
+            // Reject bad input before any step can have side effects
+            match workflow.validate_input() {
+                Ok(input) => input,
+                Err(e) => {
+                    let violations = match &e {
+                        RuntimeError::InvalidInput { violations, .. } => violations.clone(),
+                        _ => Vec::new(),
+                    };
+                    self.event_stream.workflow_failed(
+                        &workflow_id,
+                        &e.to_string(),
+                        serde_json::json!({
+                            "invalid_input": true,
+                            "violations": violations,
+                        }),
+                    );
+                    workflow.state = WorkflowState::Failed;
+                    run.state = WorkflowState::Failed;
+                    return run;
+                }
+            }
         };
+        // Continue the conversation kept in the context store; a resumed or
+        // rerun run already has its context from the plan
+        let mut context_version = None;
+        if let Some((store, key)) = workflow.context_store.clone() {
+            match store.load(&key).await {
+                Ok(stored) => {
+                    context_version = Some(stored.as_ref().map_or(0, |s| s.record.version));
+                    if let Some(stored) = stored.filter(|_| !planned) {
+                        workflow.restore_context(stored.context);
+                    }
+                }
+                Err(e) => {
+                    let message = format!("Context '{}' not loaded: {}", key, e);
+                    self.event_stream.workflow_failed(
+                        &workflow_id,
+                        &message,
+                        serde_json::json!({ "context_key": key }),
+                    );
+                    workflow.state = WorkflowState::Failed;
+                    run.state = WorkflowState::Failed;
+                    return run;
+                }
+            }
+        }
 
-        let mut current_data = workflow.initial_input.clone();
+        let mut context_over_threshold = false;
+        let mut pii_findings = self.pii_scanner.as_ref().map(|scanner| PiiFindings {
+            action: scanner.action(),
+            ..Default::default()
+        });
+        // What steps and checkpoints record of `current_data`: the last
+        // step's output as scanned, so redacted PII isn't kept as the next
+        // step's input
+        let mut recorded_data = current_data.clone();
+
+        // A sub-workflow reports progress to the heartbeat of its parent run
+        let parent_activity = heartbeat::current();
+        let nested = parent_activity.is_some();
+        let activity =
+            parent_activity.or_else(|| self.heartbeat.as_ref().map(|_| RunActivity::new()));
+        let _heartbeat = match (&self.heartbeat, &activity) {
+            (Some(config), Some(activity)) if !nested => Some(heartbeat::start(
+                config.clone(),
+                self.event_stream.clone(),
+                workflow_id.clone(),
+                workflow
+                    .steps
+                    .iter()
+                    .map(|s| s.name().to_string())
+                    .collect(),
+                activity.clone(),
+            )),
+            _ => None,
+        };
 
         // Execute each step in sequence
-        for (step_index, step) in workflow.steps.iter().enumerate() {
+        for (step_index, step) in workflow.steps.iter().enumerate().skip(first_step) {
             let step_name = step.name().to_string();
             let step_type_enum = step.step_type();
             let step_type = format!("{:?}", step_type_enum);
 
+            // A run canceled between steps starts no more of them
+            if cancellation.is_cancelled() {
+                self.event_stream.workflow_canceled(
+                    &workflow_id,
+                    &format!("canceled before step '{}' started", step_name),
+                    serde_json::json!({
+                        "next_step": step_index,
+                        "next_step_name": &step_name,
+                        "steps_completed": run.steps.len(),
+                    }),
+                );
+                workflow.state = WorkflowState::Canceled;
+                run.state = WorkflowState::Canceled;
+                self.finish_pii(&mut run, pii_findings);
+                return run;
+            }
+
+            usage::enter_step(step_index, &step_name);
+            match &activity {
+                Some(activity) if nested => activity.record_progress(),
+                Some(activity) => activity.record_step(step_index),
+                None => {}
+            }
+
             // Emit WorkflowStep::Started event
             self.event_stream.step_started(
                 &workflow_id,
@@ -84,53 +989,170 @@ impl Runtime {
                         None
                     },
                     workflow_id: workflow_id.clone(),
+                    run_id: workflow.run_id.clone(),
+                    item_index: None,
                 },
                 workflow_context: workflow.context.clone(),
             };
 
-            // Execute step - special handling for SubWorkflowStep
-            let result = if step_type_enum == StepType::SubWorkflow {
-                // Cast to SubWorkflowStep and execute with this runtime
-                // to share the event stream
-                let sub_step = unsafe {
-                    // SAFETY: We just checked step_type is SubWorkflow
-                    let ptr =
-                        step.as_ref() as *const dyn crate::workflow::Step as *const SubWorkflowStep;
-                    &*ptr
-                };
-                sub_step.execute_with_runtime(input.clone(), self).await
-            } else {
-                // Execute with event stream context
-                let ctx = ExecutionContext::with_event_stream(&self.event_stream);
-                step.execute_with_context(input.clone(), ctx).await
+            // A policy's retries run here rather than in `PolicyStep`, so a
+            // wrapped sub-workflow still shares this runtime
+            let (target, policy) = match step.get_policy() {
+                Some((inner, policy)) => (inner, Some(policy)),
+                None => (step.as_ref(), None),
+            };
+            let mut ctx = ExecutionContext::with_event_stream(&self.event_stream)
+                .with_artifacts(&run_artifacts)
+                .with_cancellation(cancellation)
+                .with_approvals(&self.approvals);
+            if let Some(activity) = &activity {
+                ctx = ctx.with_activity(activity);
+            }
+            let attempt = || self.execute_step(target, input.clone(), ctx);
+            let step_started = std::time::Instant::now();
+            let started_at = chrono::Utc::now();
+            let artifacts_before = run_artifacts.list_scope(&run.run_id);
+            let step_span = crate::telemetry::step_span(&step_name, &step_type, step_index);
+            let execution = async {
+                match policy {
+                    Some(policy) => execute_with_policy(policy, target, &input, ctx, attempt).await,
+                    None => attempt().await,
+                }
             };
+            let result = heartbeat::scope(activity.clone(), execution)
+                .instrument(step_span.clone())
+                .await;
+            // A changed output shape fails here, at the step that produced it
+            let result = match result {
+                Ok(output) if workflow.check_contracts => {
+                    let next = workflow.steps.get(step_index + 1).map(|next| next.as_ref());
+                    contract::check_output(step.as_ref(), next, &output.data)
+                        .map(|()| output)
+                        .map_err(StepError::ContractViolation)
+                }
+                result => result,
+            };
+            crate::metrics::step_finished(
+                &step_type_enum,
+                &step_name,
+                result.is_ok(),
+                step_started.elapsed(),
+            );
+            let step_error = result.as_ref().err().map(|e| e.to_string());
+            crate::telemetry::finish(&step_span, step_started.elapsed(), step_error.as_deref());
 
             match result {
                 Ok(output) => {
-                    // Emit WorkflowStep::Completed event
-                    self.event_stream.step_completed(
-                        &workflow_id,
-                        step_index,
-                        serde_json::json!({
-                            "step_name": &step_name,
-                            "execution_time_ms": output.metadata.execution_time_ms,
-                        }),
-                    );
+                    let (status, error) = match &output.metadata.policy {
+                        Some(outcome) => (outcome.status, outcome.error.clone()),
+                        None => (StepStatus::Succeeded, None),
+                    };
+
+                    // Scan the recorded copy; the next step still sees the
+                    // original output
+                    let mut recorded_output = output.data.clone();
+                    let mut pii_blocked = false;
+                    if let (Some(scanner), Some(report)) = (
+                        self.pii_scanner
+                            .as_ref()
+                            .filter(|scanner| scanner.scans_step_outputs()),
+                        pii_findings.as_mut(),
+                    ) {
+                        let findings = scanner.apply(&mut recorded_output, Some(step_index)).await;
+                        pii_blocked = scanner.action() == PiiAction::Fail && !findings.is_empty();
+                        report.extend(findings);
+                    }
+
+                    // Emit WorkflowStep::Completed event, with the output as
+                    // it is recorded
+                    let mut completed = serde_json::json!({
+                        "step_name": &step_name,
+                        "execution_time_ms": output.metadata.execution_time_ms,
+                        "status": status,
+                    });
+                    if let Some(outcome) = &output.metadata.policy {
+                        completed["attempts"] = outcome.attempts.into();
+                    }
+                    if let Some(error) = &error {
+                        completed["error"] = error.to_string().into();
+                    }
+                    if !pii_blocked {
+                        completed["output"] = recorded_output.clone();
+                    }
+                    self.event_stream
+                        .step_completed(&workflow_id, step_index, completed);
 
                     // Record step
                     run.steps.push(WorkflowStepRecord {
                         step_index,
                         step_name: step_name.clone(),
                         step_type: step_type.clone(),
-                        input: input.data,
-                        output: Some(output.data.clone()),
+                        input: recorded_data.clone(),
+                        output: (!pii_blocked).then_some(recorded_output.clone()),
                         execution_time_ms: Some(output.metadata.execution_time_ms),
+                        replayed: false,
+                        critic: output.metadata.critic.clone(),
+                        status,
+                        error,
+                        handoffs: output.metadata.handoffs.clone(),
+                        started_at: Some(started_at),
+                        finished_at: Some(chrono::Utc::now()),
+                        artifacts: Self::new_artifacts(&run_artifacts, &artifacts_before),
                     });
 
-                    // Pass output to next step
+                    if pii_blocked {
+                        let message = format!("PII detected in output of step '{}'", step_name);
+                        self.finish_pii(&mut run, pii_findings);
+                        self.event_stream.workflow_failed(
+                            &workflow_id,
+                            &message,
+                            serde_json::json!({
+                                "failed_step": step_index,
+                                "failed_step_name": &step_name,
+                            }),
+                        );
+                        workflow.state = WorkflowState::Failed;
+                        run.state = WorkflowState::Failed;
+                        return run;
+                    }
+
+                    self.check_context_utilization(&workflow, &mut context_over_threshold);
+                    if self.record_context {
+                        Self::record_context(&workflow, step_index, &run_artifacts);
+                    }
+
+                    // Pass output to next step; a checkpoint keeps the
+                    // recorded copy
                     current_data = output.data;
+                    recorded_data = recorded_output;
+                    if let Some(store) = checkpoints {
+                        self.save_checkpoint(store, &workflow, &run, &recorded_data)
+                            .await;
+                    }
+                    self.save_context(&workflow, &mut context_version).await;
                 }
                 Err(e) => {
+                    // The step a run stops at is recorded too, with its error
+                    run.steps.push(WorkflowStepRecord {
+                        step_index,
+                        step_name: step_name.clone(),
+                        step_type: step_type.clone(),
+                        input: recorded_data.clone(),
+                        output: None,
+                        execution_time_ms: Some(step_started.elapsed().as_millis() as u64),
+                        replayed: false,
+                        critic: None,
+                        status: match &e {
+                            StepError::Canceled(_) => StepStatus::Canceled,
+                            _ => StepStatus::Failed,
+                        },
+                        error: Some(e.clone()),
+                        handoffs: None,
+                        started_at: Some(started_at),
+                        finished_at: Some(chrono::Utc::now()),
+                        artifacts: Self::new_artifacts(&run_artifacts, &artifacts_before),
+                    });
+
                     // Emit WorkflowStep::Failed event
                     self.event_stream.step_failed(
                         &workflow_id,
@@ -141,27 +1163,110 @@ impl Runtime {
                         }),
                     );
 
-                    // Emit Workflow::Failed event
-                    self.event_stream.workflow_failed(
-                        &workflow_id,
-                        &e.to_string(),
-                        serde_json::json!({
-                            "failed_step": step_index,
-                            "failed_step_name": &step_name,
-                        }),
-                    );
+                    // Emit Workflow::Failed event; a conversation cap is a final
+                    // outcome with a message meant for the end user
+                    let mut failure = serde_json::json!({
+                        "failed_step": step_index,
+                        "failed_step_name": &step_name,
+                    });
+                    if let StepError::LimitReached(exceeded) = &e {
+                        failure["limit"] = serde_json::json!(exceeded);
+                        failure["user_message"] = serde_json::json!(exceeded.user_message());
+                    }
+                    if let StepError::Aggregate(errors) = &e {
+                        failure["errors"] = serde_json::json!(errors);
+                    }
+                    if let StepError::ContractViolation(violation) = &e {
+                        failure["contract"] = serde_json::json!(violation);
+                    }
+                    // A step that failed for want of budget, e.g. a parallel
+                    // branch, still ends the run as out of budget
+                    let budget = match &e {
+                        StepError::BudgetExceeded(exceeded) => Some(exceeded.clone()),
+                        _ => usage::budget_exceeded(),
+                    };
+                    let state = if let StepError::Canceled(_) = &e {
+                        self.event_stream
+                            .workflow_canceled(&workflow_id, &e.to_string(), failure);
+                        WorkflowState::Canceled
+                    } else if let Some(exceeded) = budget {
+                        failure["budget"] = serde_json::json!(exceeded);
+                        self.event_stream.workflow_failed(
+                            &workflow_id,
+                            &exceeded.to_string(),
+                            failure,
+                        );
+                        WorkflowState::BudgetExceeded
+                    } else {
+                        self.event_stream
+                            .workflow_failed(&workflow_id, &e.to_string(), failure);
+                        WorkflowState::Failed
+                    };
 
-                    workflow.state = WorkflowState::Failed;
-                    run.state = WorkflowState::Failed;
+                    workflow.state = state.clone();
+                    run.state = state;
+                    run.failure = Some(StepFailure {
+                        step_index,
+                        step_name,
+                        error: e,
+                    });
+                    self.finish_pii(&mut run, pii_findings);
                     return run;
                 }
             }
         }
 
+        // The finished document is part of the output, so it's scanned too
+        let document = workflow.document.as_ref();
+        if let Some(document) = document {
+            let key = document.output_key();
+            if !current_data.is_object() {
+                current_data = serde_json::json!({ "output": current_data });
+            }
+            current_data[key] = document.to_markdown().into();
+        }
+
+        // Scan the final output before it leaves the runtime
+        if let (Some(scanner), Some(report)) = (&self.pii_scanner, pii_findings.as_mut()) {
+            let findings = scanner.apply(&mut current_data, None).await;
+            let blocked = scanner.action() == PiiAction::Fail && !findings.is_empty();
+            report.extend(findings);
+
+            if blocked {
+                self.finish_pii(&mut run, pii_findings);
+                self.event_stream.workflow_failed(
+                    &workflow_id,
+                    "PII detected in final output",
+                    serde_json::json!({
+                        "steps_completed": run.steps.len(),
+                    }),
+                );
+                workflow.state = WorkflowState::Failed;
+                run.state = WorkflowState::Failed;
+                return run;
+            }
+        }
+
+        if let Some(document) = document {
+            // Export what was scanned (and possibly redacted)
+            let key = document.output_key();
+            let markdown = current_data[key].as_str().unwrap_or_default();
+            let reference = run_artifacts.put(
+                NewArtifact::new(
+                    format!("{}.md", key),
+                    "text/markdown",
+                    markdown.as_bytes().to_vec(),
+                )
+                .with_description(document.title()),
+            );
+            run_artifacts.export(&reference.id);
+        }
+
         // Workflow completed successfully
         run.final_output = Some(current_data);
         run.state = WorkflowState::Completed;
         workflow.state = WorkflowState::Completed;
+        self.finish_pii(&mut run, pii_findings);
 
         self.event_stream.workflow_completed(
             &workflow_id,
@@ -169,10 +1274,219 @@ impl Runtime {
                 "steps_completed": run.steps.len(),
             }),
         );
+        if let Some(store) = checkpoints {
+            if let Err(e) = store.remove(&run.run_id).await {
+                self.checkpoint_failed(&workflow_id, &e.to_string());
+            }
+        }
 
         run
     }
 
+    /// Run one step; a sub-workflow runs in this runtime, sharing its event
+    /// stream
+    async fn execute_step(
+        &self,
+        step: &dyn crate::workflow::Step,
+        input: StepInput,
+        ctx: ExecutionContext<'_>,
+    ) -> StepResult {
+        if step.step_type() == StepType::SubWorkflow {
+            let sub_step = unsafe {
+                // SAFETY: We just checked step_type is SubWorkflow
+                let ptr = step as *const dyn crate::workflow::Step as *const SubWorkflowStep;
+                &*ptr
+            };
+            sub_step.execute_with_runtime(input, self).await
+        } else {
+            step.execute_with_context(input, ctx).await
+        }
+    }
+
+    /// Save where `run` stands, with `data` going to the next step
+    async fn save_checkpoint(
+        &self,
+        store: &dyn CheckpointStore,
+        workflow: &Workflow,
+        run: &WorkflowRun,
+        data: &serde_json::Value,
+    ) {
+        let checkpoint = RunCheckpoint {
+            version: CHECKPOINT_VERSION,
+            workflow_id: run.workflow_id.clone(),
+            run_id: run.run_id.clone(),
+            parent_workflow_id: run.parent_workflow_id.clone(),
+            next_step: run.steps.len(),
+            steps: run.steps.clone(),
+            data: data.clone(),
+            context: workflow.checkpoint_context(),
+            saved_at: chrono::Utc::now(),
+        };
+        if let Err(e) = store.save(&checkpoint).await {
+            self.checkpoint_failed(&run.workflow_id, &e.to_string());
+        }
+    }
+
+    /// Save the context to the workflow's context store over
+    /// `version`. After a conflict nothing more is saved, so the other
+    /// writer's context survives the run.
+    async fn save_context(&self, workflow: &Workflow, version: &mut Option<u64>) {
+        let (Some((store, key)), Some(expected), Some(context)) = (
+            &workflow.context_store,
+            *version,
+            workflow.checkpoint_context(),
+        ) else {
+            return;
+        };
+        match store.save_versioned(key, &context, Some(expected)).await {
+            Ok(saved) => *version = Some(saved),
+            Err(e) => {
+                let conflict = matches!(e, ContextStoreError::VersionConflict { .. });
+                if conflict {
+                    *version = None;
+                }
+                self.event_stream.append(
+                    EventScope::System,
+                    EventType::Progress,
+                    "system:context_store_failed".to_string(),
+                    ComponentStatus::Running,
+                    workflow.id.clone(),
+                    Some(format!("Context '{}' not saved: {}", key, e)),
+                    serde_json::json!({
+                        "context_key": key,
+                        "error": e.to_string(),
+                        "conflict": conflict,
+                    }),
+                );
+            }
+        }
+    }
+
+    fn checkpoint_failed(&self, workflow_id: &str, error: &str) {
+        self.event_stream.append(
+            EventScope::System,
+            EventType::Progress,
+            "system:checkpoint_failed".to_string(),
+            ComponentStatus::Running,
+            workflow_id.to_string(),
+            Some(format!("Checkpoint not saved: {}", error)),
+            serde_json::json!({ "error": error }),
+        );
+    }
+
+    /// Keep a copy of the context as it is after `step_index`
+    fn record_context(workflow: &Workflow, step_index: usize, artifacts: &ArtifactStore) {
+        let Some(context) = workflow.checkpoint_context() else {
+            return;
+        };
+        let Ok(data) = serde_json::to_vec(&context) else {
+            return;
+        };
+        let reference = artifacts.put(
+            NewArtifact::new(
+                rerun::context_snapshot_name(step_index),
+                "application/json",
+                data,
+            )
+            .with_description(format!("Workflow context after step {}", step_index)),
+        );
+        artifacts.export(&reference.id);
+    }
+
+    /// Record the run's artifacts, then hand them on to the parent run,
+    /// whose later steps may still use them, or drop the ones that weren't
+    /// exported
+    fn finish_artifacts(&self, run: &mut WorkflowRun, parent_run_id: Option<&str>) {
+        run.artifacts = self.artifacts.list_scope(&run.run_id);
+        match parent_run_id {
+            Some(parent) => self.artifacts.rescope(&run.run_id, parent),
+            None => self.artifacts.collect_scope(&run.run_id),
+        };
+    }
+
+    /// The artifacts `run_artifacts` got since `before` was listed
+    fn new_artifacts(run_artifacts: &ArtifactStore, before: &[ArtifactRef]) -> Vec<ArtifactRef> {
+        let scope = run_artifacts.scope().unwrap_or_default();
+        run_artifacts
+            .list_scope(scope)
+            .into_iter()
+            .filter(|artifact| !before.contains(artifact))
+            .collect()
+    }
+
+    /// Attach the PII report to the run and emit it when anything was found.
+    /// The event carries types, pointers and spans - never the matched text.
+    fn finish_pii(&self, run: &mut WorkflowRun, findings: Option<PiiFindings>) {
+        let Some(findings) = findings else {
+            return;
+        };
+
+        if !findings.is_empty() || !findings.errors.is_empty() {
+            self.event_stream.append(
+                EventScope::System,
+                EventType::Progress,
+                "system:pii_scan".to_string(),
+                ComponentStatus::Running,
+                run.workflow_id.clone(),
+                Some(findings.summary()),
+                serde_json::json!({
+                    "action": findings.action,
+                    "counts": findings.counts(),
+                    "findings": &findings.findings,
+                    "errors": &findings.errors,
+                }),
+            );
+        }
+        run.pii_findings = Some(findings);
+    }
+
+    /// Emit a context analysis event when utilization crosses the configured
+    /// threshold. `over_threshold` tracks the previous state so the event only
+    /// fires on the upward crossing.
+    fn check_context_utilization(&self, workflow: &Workflow, over_threshold: &mut bool) {
+        let (Some(diagnostics), Some(context)) = (&workflow.context_diagnostics, &workflow.context)
+        else {
+            return;
+        };
+
+        let (report, max_input_tokens) = {
+            let ctx = context.read().unwrap();
+            (
+                ctx.analyze(diagnostics.estimator.as_ref()),
+                ctx.max_input_tokens(),
+            )
+        };
+        let utilization = report.utilization(max_input_tokens);
+
+        if utilization < diagnostics.threshold {
+            *over_threshold = false;
+            return;
+        }
+        if *over_threshold {
+            return;
+        }
+        *over_threshold = true;
+
+        self.event_stream.append(
+            EventScope::System,
+            EventType::Progress,
+            "system:context_analysis".to_string(),
+            ComponentStatus::Running,
+            workflow.id.clone(),
+            Some(format!(
+                "Context utilization {:.1}% crossed threshold {:.1}%",
+                utilization * 100.0,
+                diagnostics.threshold * 100.0
+            )),
+            serde_json::json!({
+                "utilization": utilization,
+                "threshold": diagnostics.threshold,
+                "max_input_tokens": max_input_tokens,
+                "report": report,
+            }),
+        );
+    }
+
     /// Get events from a specific offset (for replay)
     pub fn events_from_offset(&self, offset: u64) -> Vec<Event> {
         self.event_stream.get_from_offset(offset)
@@ -184,3 +1498,37 @@ impl Default for Runtime {
         Self::new()
     }
 }
+
+/// Cancels the run started by [`Runtime::execute_cancellable`]
+#[derive(Debug, Clone)]
+pub struct CancellationHandle {
+    workflow_id: String,
+    run_id: String,
+    token: CancellationToken,
+}
+
+impl CancellationHandle {
+    /// Cancel the run; later calls do nothing
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_canceled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// ID of the workflow whose run this handle cancels
+    pub fn workflow_id(&self) -> &str {
+        &self.workflow_id
+    }
+
+    /// ID of the run this handle cancels, for `Runtime::cancel`
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// The run's token, for work outside the runtime that should stop with it
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
@@ -0,0 +1,687 @@
+//! Plain-language summaries of finished runs.
+//!
+//! [`Runtime::explain_run`](super::Runtime::explain_run) condenses a
+//! [`WorkflowRun`] and its events into a [`RunDigest`], then either renders a
+//! deterministic narrative from it or hands it to an LLM to write the prose.
+//! The digest keeps only the top-N tool calls, steps and failures, so its size
+//! stays bounded however large the run was.
+
+use crate::event::{Event, EventScope, EventType};
+use crate::llm::{ChatMessage, ChatRequest, LlmClient};
+use crate::workflow::{StepStatus, WorkflowRun, WorkflowState};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// Who the narrative is written for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Audience {
+    /// Names tools, arguments and token counts
+    #[default]
+    Technical,
+    /// Describes what happened without JSON or jargon
+    NonTechnical,
+}
+
+/// How to explain a run
+#[derive(Clone)]
+pub struct ExplainOptions {
+    /// Write the narrative with this model; the template is used otherwise
+    pub llm: Option<LlmClient>,
+    pub audience: Audience,
+    /// Cap on the LLM's answer
+    pub max_tokens: u32,
+    /// Optional price used to estimate LLM cost
+    pub usd_per_1k_tokens: Option<f64>,
+    /// Most notable tool calls kept in the digest
+    pub max_tool_calls: usize,
+    /// Most steps kept in the digest
+    pub max_steps: usize,
+    /// Most failures kept in the digest
+    pub max_failures: usize,
+    /// Longest argument, error or output excerpt, in characters
+    pub max_excerpt_chars: usize,
+}
+
+impl Default for ExplainOptions {
+    fn default() -> Self {
+        Self {
+            llm: None,
+            audience: Audience::Technical,
+            max_tokens: 400,
+            usd_per_1k_tokens: None,
+            max_tool_calls: 8,
+            max_steps: 12,
+            max_failures: 5,
+            max_excerpt_chars: 120,
+        }
+    }
+}
+
+impl ExplainOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_llm(mut self, llm: LlmClient) -> Self {
+        self.llm = Some(llm);
+        self
+    }
+
+    pub fn audience(mut self, audience: Audience) -> Self {
+        self.audience = audience;
+        self
+    }
+
+    pub fn max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    pub fn with_price(mut self, usd_per_1k_tokens: f64) -> Self {
+        self.usd_per_1k_tokens = Some(usd_per_1k_tokens);
+        self
+    }
+}
+
+/// A compact, size-bounded description of a run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunDigest {
+    pub workflow_id: String,
+    pub state: WorkflowState,
+    pub duration_ms: u64,
+    pub step_count: usize,
+    /// The longest steps first, at most `max_steps`
+    pub steps: Vec<StepDigest>,
+    pub tools: ToolDigest,
+    pub llm: LlmDigest,
+    /// At most `max_failures`, in the order they happened
+    pub failures: Vec<FailureDigest>,
+    pub omitted_failures: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub final_output: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepDigest {
+    pub index: usize,
+    pub name: String,
+    pub step_type: String,
+    pub duration_ms: u64,
+    pub failed: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolDigest {
+    pub total_calls: usize,
+    pub failed_calls: usize,
+    pub total_duration_ms: f64,
+    /// Per-tool totals, most-used first, at most `max_tool_calls`
+    pub by_tool: Vec<ToolTotals>,
+    /// Failures first, then the slowest calls
+    pub notable_calls: Vec<ToolCallDigest>,
+    pub omitted_calls: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolTotals {
+    pub tool: String,
+    pub calls: usize,
+    pub failures: usize,
+    pub duration_ms: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCallDigest {
+    pub tool: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+    /// Truncated argument JSON
+    pub arguments: String,
+    pub duration_ms: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LlmDigest {
+    pub requests: usize,
+    pub failed_requests: usize,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_cost_usd: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FailureDigest {
+    pub scope: EventScope,
+    pub component: String,
+    pub message: String,
+}
+
+/// Where the narrative came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum NarrativeSource {
+    Template,
+    Llm {
+        model: String,
+    },
+    /// The LLM failed; the template narrative was used instead
+    TemplateFallback {
+        error: String,
+    },
+}
+
+/// The digest and the prose written from it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunExplanation {
+    pub digest: RunDigest,
+    pub narrative: String,
+    pub source: NarrativeSource,
+}
+
+pub(crate) async fn explain_run(
+    run: &WorkflowRun,
+    events: &[Event],
+    options: &ExplainOptions,
+) -> RunExplanation {
+    let digest = build_digest(run, events, options);
+    let template = render_template(&digest, options.audience);
+
+    let Some(llm) = &options.llm else {
+        return RunExplanation {
+            digest,
+            narrative: template,
+            source: NarrativeSource::Template,
+        };
+    };
+
+    let request = ChatRequest::new(vec![
+        ChatMessage::system(system_prompt(options.audience)),
+        ChatMessage::user(serde_json::to_string_pretty(&digest).unwrap_or_default()),
+    ])
+    .with_max_tokens(options.max_tokens);
+    match llm.chat(request).await {
+        Ok(response) if !response.content.trim().is_empty() => RunExplanation {
+            digest,
+            narrative: response.content.trim().to_string(),
+            source: NarrativeSource::Llm {
+                model: response.model,
+            },
+        },
+        Ok(_) => RunExplanation {
+            digest,
+            narrative: template,
+            source: NarrativeSource::TemplateFallback {
+                error: "empty response".to_string(),
+            },
+        },
+        Err(e) => RunExplanation {
+            digest,
+            narrative: template,
+            source: NarrativeSource::TemplateFallback {
+                error: e.to_string(),
+            },
+        },
+    }
+}
+
+fn system_prompt(audience: Audience) -> &'static str {
+    match audience {
+        Audience::Technical => {
+            "You summarize workflow runs for engineers. Using only the JSON digest \
+             provided, write three short paragraphs: what the workflow did, which tools \
+             it used and why, and where time and tokens went including any failures. \
+             Name steps, tools and errors exactly. Do not invent details."
+        }
+        Audience::NonTechnical => {
+            "You summarize automated workflow runs for people who are not engineers. \
+             Using only the JSON digest provided, write three short paragraphs in plain \
+             language: what the workflow did, what it looked up or acted on and why, and \
+             how long it took, what it cost and whether anything went wrong. Avoid JSON, \
+             code and jargon. Do not invent details."
+        }
+    }
+}
+
+/// Build the size-bounded digest
+pub fn build_digest(run: &WorkflowRun, events: &[Event], options: &ExplainOptions) -> RunDigest {
+    let failed_step = events
+        .iter()
+        .find(|e| e.scope == EventScope::WorkflowStep && e.event_type == EventType::Failed)
+        .map(|e| {
+            let index = e
+                .component_id
+                .rsplit(':')
+                .next()
+                .and_then(|i| i.parse::<usize>().ok())
+                .unwrap_or(run.steps.len());
+            let name = e.data["step_name"]
+                .as_str()
+                .unwrap_or("unknown")
+                .to_string();
+            (index, name)
+        });
+
+    let mut steps: Vec<StepDigest> = run
+        .steps
+        .iter()
+        .map(|s| StepDigest {
+            index: s.step_index,
+            name: s.step_name.clone(),
+            step_type: s.step_type.clone(),
+            duration_ms: s.execution_time_ms.unwrap_or(0),
+            failed: matches!(s.status, StepStatus::Failed | StepStatus::Canceled),
+        })
+        .collect();
+    // Runs recorded before failed steps were kept lack the failed one
+    if let Some((index, name)) = failed_step
+        .as_ref()
+        .filter(|(index, _)| !steps.iter().any(|s| s.index == *index))
+    {
+        steps.push(StepDigest {
+            index: *index,
+            name: name.clone(),
+            step_type: "unknown".to_string(),
+            duration_ms: 0,
+            failed: true,
+        });
+    }
+    let step_count = steps.len();
+    let steps_duration: u64 = steps.iter().map(|s| s.duration_ms).sum();
+    steps.sort_by(|a, b| {
+        b.failed
+            .cmp(&a.failed)
+            .then(b.duration_ms.cmp(&a.duration_ms))
+            .then(a.index.cmp(&b.index))
+    });
+    steps.truncate(options.max_steps);
+
+    let duration_ms = run_duration(events).unwrap_or(steps_duration);
+    let tools = tool_digest(events, options);
+    let llm = llm_digest(events, options);
+
+    let all_failures: Vec<FailureDigest> = events
+        .iter()
+        .filter(|e| e.event_type == EventType::Failed)
+        .map(|e| FailureDigest {
+            scope: e.scope.clone(),
+            component: e.component_id.clone(),
+            message: excerpt(
+                e.message.as_deref().unwrap_or("failed"),
+                options.max_excerpt_chars,
+            ),
+        })
+        .collect();
+    let omitted_failures = all_failures.len().saturating_sub(options.max_failures);
+    let failures = all_failures
+        .into_iter()
+        .take(options.max_failures)
+        .collect();
+
+    RunDigest {
+        workflow_id: run.workflow_id.clone(),
+        state: run.state.clone(),
+        duration_ms,
+        step_count,
+        steps,
+        tools,
+        llm,
+        failures,
+        omitted_failures,
+        final_output: run.final_output.as_ref().map(|output| {
+            let text = output
+                .get("response")
+                .and_then(|r| r.as_str())
+                .map(str::to_string)
+                .unwrap_or_else(|| output.to_string());
+            excerpt(&text, options.max_excerpt_chars)
+        }),
+    }
+}
+
+fn run_duration(events: &[Event]) -> Option<u64> {
+    let started = events
+        .iter()
+        .find(|e| e.scope == EventScope::Workflow && e.event_type == EventType::Started)?;
+    let finished = events.iter().rev().find(|e| {
+        e.scope == EventScope::Workflow
+            && matches!(e.event_type, EventType::Completed | EventType::Failed)
+    })?;
+    Some(
+        (finished.timestamp - started.timestamp)
+            .num_milliseconds()
+            .max(0) as u64,
+    )
+}
+
+/// Agent and arguments of a tool call awaiting its outcome
+type StartedCall = (Option<String>, String);
+
+fn tool_digest(events: &[Event], options: &ExplainOptions) -> ToolDigest {
+    // Pair each Started with the next outcome for the same tool and call id
+    // (ids can repeat across iterations, so pairing is first-in first-out)
+    let mut pending: HashMap<(String, String), VecDeque<StartedCall>> = HashMap::new();
+    let mut calls = Vec::new();
+    for event in events.iter().filter(|e| e.scope == EventScope::Tool) {
+        let key = (
+            event.component_id.clone(),
+            event.data["tool_call_id"]
+                .as_str()
+                .unwrap_or("")
+                .to_string(),
+        );
+        match event.event_type {
+            EventType::Started => {
+                let agent = event.data["agent"].as_str().map(str::to_string);
+                let arguments = match &event.data["arguments"] {
+                    serde_json::Value::String(s) => s.clone(),
+                    serde_json::Value::Null => String::new(),
+                    other => other.to_string(),
+                };
+                pending
+                    .entry(key)
+                    .or_default()
+                    .push_back((agent, arguments));
+            }
+            EventType::Completed | EventType::Failed => {
+                let (agent, arguments) = pending
+                    .get_mut(&key)
+                    .and_then(|queue| queue.pop_front())
+                    .unwrap_or((
+                        event.data["agent"].as_str().map(str::to_string),
+                        String::new(),
+                    ));
+                let failed = event.event_type == EventType::Failed;
+                calls.push(ToolCallDigest {
+                    tool: event.component_id.clone(),
+                    agent,
+                    arguments: excerpt(&arguments, options.max_excerpt_chars),
+                    duration_ms: event.data["duration_ms"].as_f64().unwrap_or(0.0),
+                    error: failed.then(|| {
+                        excerpt(
+                            event.message.as_deref().unwrap_or("failed"),
+                            options.max_excerpt_chars,
+                        )
+                    }),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    let mut totals: BTreeMap<&str, ToolTotals> = BTreeMap::new();
+    for call in &calls {
+        let entry = totals.entry(&call.tool).or_insert_with(|| ToolTotals {
+            tool: call.tool.clone(),
+            calls: 0,
+            failures: 0,
+            duration_ms: 0.0,
+        });
+        entry.calls += 1;
+        entry.failures += usize::from(call.error.is_some());
+        entry.duration_ms += call.duration_ms;
+    }
+    let mut by_tool: Vec<ToolTotals> = totals.into_values().collect();
+    by_tool.sort_by(|a, b| b.calls.cmp(&a.calls).then(a.tool.cmp(&b.tool)));
+    by_tool.truncate(options.max_tool_calls);
+
+    let total_calls = calls.len();
+    let failed_calls = calls.iter().filter(|c| c.error.is_some()).count();
+    let total_duration_ms = calls.iter().map(|c| c.duration_ms).sum();
+    let mut notable = calls;
+    notable.sort_by(|a, b| {
+        b.error
+            .is_some()
+            .cmp(&a.error.is_some())
+            .then(b.duration_ms.total_cmp(&a.duration_ms))
+    });
+    notable.truncate(options.max_tool_calls);
+
+    ToolDigest {
+        total_calls,
+        failed_calls,
+        total_duration_ms,
+        by_tool,
+        omitted_calls: total_calls - notable.len(),
+        notable_calls: notable,
+    }
+}
+
+fn llm_digest(events: &[Event], options: &ExplainOptions) -> LlmDigest {
+    let mut digest = LlmDigest::default();
+    for event in events.iter().filter(|e| e.scope == EventScope::LlmRequest) {
+        match event.event_type {
+            EventType::Completed => {
+                digest.requests += 1;
+                let usage = &event.data["usage"];
+                digest.prompt_tokens += usage["prompt_tokens"].as_u64().unwrap_or(0);
+                digest.completion_tokens += usage["completion_tokens"].as_u64().unwrap_or(0);
+                digest.total_tokens += usage["total_tokens"].as_u64().unwrap_or(0);
+            }
+            EventType::Failed => {
+                digest.requests += 1;
+                digest.failed_requests += 1;
+            }
+            _ => {}
+        }
+    }
+    digest.estimated_cost_usd = options
+        .usd_per_1k_tokens
+        .map(|price| digest.total_tokens as f64 / 1000.0 * price);
+    digest
+}
+
+/// Truncate to `max_chars`, marking the cut
+fn excerpt(text: &str, max_chars: usize) -> String {
+    let text = text.trim();
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+fn seconds(ms: u64) -> String {
+    if ms < 1000 {
+        format!("{}ms", ms)
+    } else {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    }
+}
+
+fn plural(n: usize, word: &str) -> String {
+    if n == 1 {
+        format!("1 {}", word)
+    } else {
+        format!("{} {}s", n, word)
+    }
+}
+
+/// Render the deterministic narrative: what happened, tools, then time,
+/// cost and failures
+pub fn render_template(digest: &RunDigest, audience: Audience) -> String {
+    let technical = audience == Audience::Technical;
+    let mut paragraphs = Vec::new();
+
+    // What the workflow did
+    let outcome = match digest.state {
+        WorkflowState::Completed => "completed successfully",
+        WorkflowState::Failed => "failed",
+        WorkflowState::Canceled => "was canceled",
+        WorkflowState::BudgetExceeded => "ran out of budget",
+        WorkflowState::Running => "was still running",
+        WorkflowState::Pending => "never started",
+    };
+    let mut what = if technical {
+        format!(
+            "Workflow `{}` {} after {} in {}.",
+            digest.workflow_id,
+            outcome,
+            plural(digest.step_count, "step"),
+            seconds(digest.duration_ms)
+        )
+    } else {
+        format!(
+            "The workflow went through {} and {}.",
+            plural(digest.step_count, "stage"),
+            outcome
+        )
+    };
+    let mut ordered: Vec<&StepDigest> = digest.steps.iter().collect();
+    ordered.sort_by_key(|s| s.index);
+    if !ordered.is_empty() {
+        let names: Vec<String> = ordered
+            .iter()
+            .map(|s| {
+                if technical {
+                    format!("{} ({})", s.name, s.step_type)
+                } else {
+                    s.name.clone()
+                }
+            })
+            .collect();
+        what.push_str(&format!(" It ran {}.", names.join(", then ")));
+    }
+    if let Some(output) = &digest.final_output {
+        what.push_str(&format!(" The final answer was: \"{}\"", output));
+    }
+    paragraphs.push(what);
+
+    // Tools
+    let tools = &digest.tools;
+    paragraphs.push(if tools.total_calls == 0 {
+        if technical {
+            "No tools were called.".to_string()
+        } else {
+            "It answered without looking anything up.".to_string()
+        }
+    } else {
+        let usage: Vec<String> = tools
+            .by_tool
+            .iter()
+            .map(|t| {
+                let mut s = format!("{} ({}×", t.tool, t.calls);
+                if t.failures > 0 {
+                    s.push_str(&format!(", {} failed", t.failures));
+                }
+                s.push(')');
+                s
+            })
+            .collect();
+        if technical {
+            let mut text = format!(
+                "Agents made {} across {}: {}.",
+                plural(tools.total_calls, "tool call"),
+                plural(tools.by_tool.len(), "tool"),
+                usage.join(", ")
+            );
+            if let Some(call) = tools.notable_calls.first() {
+                text.push_str(&format!(
+                    " The most notable was {}({}) taking {:.0}ms",
+                    call.tool, call.arguments, call.duration_ms
+                ));
+                match &call.error {
+                    Some(error) => text.push_str(&format!(", which failed: {}.", error)),
+                    None => text.push('.'),
+                }
+            }
+            if tools.omitted_calls > 0 {
+                text.push_str(&format!(
+                    " {} more calls are not shown.",
+                    tools.omitted_calls
+                ));
+            }
+            text
+        } else {
+            let names: Vec<&str> = tools.by_tool.iter().map(|t| t.tool.as_str()).collect();
+            format!(
+                "To get there it used {} {} time{} in total ({}).",
+                if names.len() == 1 {
+                    "one tool"
+                } else {
+                    "several tools"
+                },
+                tools.total_calls,
+                if tools.total_calls == 1 { "" } else { "s" },
+                names.join(", ")
+            )
+        }
+    });
+
+    // Time, money and failures
+    let mut cost = String::new();
+    if let Some(slowest) = digest.steps.iter().find(|s| !s.failed && s.duration_ms > 0) {
+        let share = if digest.duration_ms > 0 {
+            (slowest.duration_ms as f64 / digest.duration_ms as f64 * 100.0).min(100.0)
+        } else {
+            0.0
+        };
+        cost.push_str(&format!(
+            "Most time went to {} ({}, {:.0}% of the run). ",
+            slowest.name,
+            seconds(slowest.duration_ms),
+            share
+        ));
+    }
+    let llm = &digest.llm;
+    if technical {
+        cost.push_str(&format!(
+            "{} used {} tokens ({} prompt, {} completion)",
+            plural(llm.requests, "LLM request"),
+            llm.total_tokens,
+            llm.prompt_tokens,
+            llm.completion_tokens
+        ));
+    } else {
+        cost.push_str(&format!(
+            "The AI model was consulted {} time{}",
+            llm.requests,
+            if llm.requests == 1 { "" } else { "s" }
+        ));
+    }
+    match llm.estimated_cost_usd {
+        Some(usd) => cost.push_str(&format!(", costing about ${:.4}.", usd)),
+        None => cost.push('.'),
+    }
+    if digest.failures.is_empty() {
+        cost.push_str(" Nothing failed.");
+    } else {
+        let first = &digest.failures[0];
+        let total = digest.failures.len() + digest.omitted_failures;
+        if technical {
+            cost.push_str(&format!(
+                " {} recorded; the first was {:?} `{}`: {}.",
+                plural(total, "failure"),
+                first.scope,
+                first.component,
+                first.message
+            ));
+        } else {
+            cost.push_str(&format!(
+                " Something went wrong along the way ({}): {}.",
+                plural(total, "problem"),
+                first.message
+            ));
+        }
+    }
+    paragraphs.push(cost);
+
+    paragraphs.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_excerpt_marks_truncation() {
+        assert_eq!(excerpt("short", 10), "short");
+        assert_eq!(excerpt("abcdefghijk", 5), "abcd…");
+        assert_eq!(excerpt("ééééé", 3).chars().count(), 3);
+    }
+}
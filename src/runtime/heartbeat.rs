@@ -0,0 +1,351 @@
+//! Heartbeats and stall detection for running workflows
+//!
+//! With [`Runtime::with_heartbeat`](crate::Runtime::with_heartbeat), each
+//! run gets a background task that emits a `system:heartbeat:<workflow_id>`
+//! Progress event every interval. The event says which step is running,
+//! the agent's tool-loop iteration, and how long ago the last LLM chunk and
+//! the last event of the run were.
+//!
+//! Progress means an LLM chunk, a finished tool call or a step starting.
+//! When there has been none for the stall threshold, a
+//! `system:stall:<workflow_id>` event with `stalled: true` follows the
+//! heartbeat and the `on_stall` callback runs, e.g. to cancel the run. It
+//! fires once per stall: progress re-arms it.
+//!
+//! Steps record progress through [`RunActivity`], handed to them in
+//! `ExecutionContext::activity`. Agents pick it up from the step that runs
+//! them.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "workflow")]
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+
+#[cfg(feature = "workflow")]
+use crate::event::{ComponentStatus, Event, EventScope, EventStream, EventType};
+
+/// Stored for timestamps that haven't happened yet
+const NEVER: u64 = u64::MAX;
+
+/// Stored for no step or no iteration
+const NONE: usize = usize::MAX;
+
+#[derive(Debug)]
+struct Activity {
+    started: Instant,
+    step_index: AtomicUsize,
+    iteration: AtomicUsize,
+    /// Milliseconds since `started`
+    last_chunk_ms: AtomicU64,
+    last_progress_ms: AtomicU64,
+    last_event_ms: AtomicU64,
+}
+
+/// When a run last made progress; cheap to clone and to update
+#[derive(Debug, Clone)]
+pub struct RunActivity {
+    inner: Arc<Activity>,
+}
+
+impl Default for RunActivity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RunActivity {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Activity {
+                started: Instant::now(),
+                step_index: AtomicUsize::new(NONE),
+                iteration: AtomicUsize::new(NONE),
+                last_chunk_ms: AtomicU64::new(NEVER),
+                last_progress_ms: AtomicU64::new(0),
+                last_event_ms: AtomicU64::new(NEVER),
+            }),
+        }
+    }
+
+    fn now_ms(&self) -> u64 {
+        self.inner.started.elapsed().as_millis() as u64
+    }
+
+    fn since(&self, at: &AtomicU64) -> Option<Duration> {
+        match at.load(Ordering::Relaxed) {
+            NEVER => None,
+            at => Some(Duration::from_millis(self.now_ms().saturating_sub(at))),
+        }
+    }
+
+    /// Something moved forward, e.g. a custom step finished a unit of work
+    pub fn record_progress(&self) {
+        self.inner
+            .last_progress_ms
+            .store(self.now_ms(), Ordering::Relaxed);
+    }
+
+    /// An LLM chunk arrived; counts as progress
+    pub fn record_chunk(&self) {
+        let now = self.now_ms();
+        self.inner.last_chunk_ms.store(now, Ordering::Relaxed);
+        self.inner.last_progress_ms.store(now, Ordering::Relaxed);
+    }
+
+    /// The running agent started tool-loop iteration `iteration`
+    pub fn record_iteration(&self, iteration: usize) {
+        self.inner.iteration.store(iteration, Ordering::Relaxed);
+    }
+
+    /// Step `index` started; counts as progress
+    #[cfg(feature = "workflow")]
+    pub(crate) fn record_step(&self, index: usize) {
+        self.inner.step_index.store(index, Ordering::Relaxed);
+        self.inner.iteration.store(NONE, Ordering::Relaxed);
+        self.record_progress();
+    }
+
+    #[cfg(feature = "workflow")]
+    fn record_event(&self) {
+        self.inner
+            .last_event_ms
+            .store(self.now_ms(), Ordering::Relaxed);
+    }
+
+    /// The index of the step running, if one has started
+    pub fn step_index(&self) -> Option<usize> {
+        Some(self.inner.step_index.load(Ordering::Relaxed)).filter(|&i| i != NONE)
+    }
+
+    /// The running agent's tool-loop iteration, from 1
+    pub fn iteration(&self) -> Option<usize> {
+        Some(self.inner.iteration.load(Ordering::Relaxed)).filter(|&i| i != NONE)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.inner.started.elapsed()
+    }
+
+    /// `None` until the first chunk
+    pub fn since_last_chunk(&self) -> Option<Duration> {
+        self.since(&self.inner.last_chunk_ms)
+    }
+
+    /// `None` until the run's first event after the heartbeat started
+    pub fn since_last_event(&self) -> Option<Duration> {
+        self.since(&self.inner.last_event_ms)
+    }
+
+    /// Since the last chunk, finished tool call or step start
+    pub fn since_progress(&self) -> Duration {
+        self.since(&self.inner.last_progress_ms).unwrap_or_default()
+    }
+
+    /// Where the run stands now
+    #[cfg(feature = "workflow")]
+    fn report(&self, workflow_id: &str, step_names: &[String]) -> HeartbeatReport {
+        let step_index = self.step_index();
+        HeartbeatReport {
+            workflow_id: workflow_id.to_string(),
+            step_index,
+            step_name: step_index.and_then(|i| step_names.get(i).cloned()),
+            iteration: self.iteration(),
+            elapsed_ms: self.elapsed().as_millis() as u64,
+            since_last_chunk_ms: self.since_last_chunk().map(|d| d.as_millis() as u64),
+            since_last_event_ms: self.since_last_event().map(|d| d.as_millis() as u64),
+            since_progress_ms: self.since_progress().as_millis() as u64,
+            stalled: false,
+        }
+    }
+}
+
+tokio::task_local! {
+    static CURRENT_ACTIVITY: RunActivity;
+}
+
+/// Run `fut` with `activity` as the current run's, for the agents it calls
+#[cfg(feature = "workflow")]
+pub(crate) async fn scope<F: std::future::Future>(
+    activity: Option<RunActivity>,
+    fut: F,
+) -> F::Output {
+    match activity {
+        Some(activity) => CURRENT_ACTIVITY.scope(activity, fut).await,
+        None => fut.await,
+    }
+}
+
+/// The activity of the run this task belongs to, if it has a heartbeat
+pub(crate) fn current() -> Option<RunActivity> {
+    CURRENT_ACTIVITY.try_with(RunActivity::clone).ok()
+}
+
+/// What a heartbeat or stall event carries
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeartbeatReport {
+    pub workflow_id: String,
+
+    /// The step running, if one has started
+    pub step_index: Option<usize>,
+    pub step_name: Option<String>,
+
+    /// The running agent's tool-loop iteration, from 1
+    pub iteration: Option<usize>,
+
+    /// Since the run started
+    pub elapsed_ms: u64,
+
+    /// `None` until the first chunk
+    pub since_last_chunk_ms: Option<u64>,
+
+    /// `None` until the run's first event after the heartbeat started
+    pub since_last_event_ms: Option<u64>,
+
+    /// Since the last chunk, finished tool call or step start
+    pub since_progress_ms: u64,
+
+    pub stalled: bool,
+}
+
+type StallCallback = Arc<dyn Fn(&HeartbeatReport) + Send + Sync>;
+
+/// How often a run reports in, and when it counts as stalled
+#[derive(Clone)]
+pub struct HeartbeatConfig {
+    pub interval: Duration,
+
+    /// No progress for this long is a stall (default: never)
+    pub stall_threshold: Option<Duration>,
+
+    on_stall: Option<StallCallback>,
+}
+
+impl HeartbeatConfig {
+    /// A heartbeat every `interval`
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            stall_threshold: None,
+            on_stall: None,
+        }
+    }
+
+    /// Report a stall after `threshold` without progress. Stalls are
+    /// checked at each heartbeat, so one is noticed up to an interval late.
+    pub fn with_stall_threshold(mut self, threshold: Duration) -> Self {
+        self.stall_threshold = Some(threshold);
+        self
+    }
+
+    /// Call `callback` when a stall is reported, e.g. to cancel the run
+    /// through a [`CancellationHandle`](crate::runtime::CancellationHandle)
+    pub fn on_stall<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&HeartbeatReport) + Send + Sync + 'static,
+    {
+        self.on_stall = Some(Arc::new(callback));
+        self
+    }
+}
+
+impl std::fmt::Debug for HeartbeatConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HeartbeatConfig")
+            .field("interval", &self.interval)
+            .field("stall_threshold", &self.stall_threshold)
+            .field("on_stall", &self.on_stall.is_some())
+            .finish()
+    }
+}
+
+/// Stops the heartbeat task when dropped
+#[cfg(feature = "workflow")]
+pub(crate) struct Heartbeat {
+    task: tokio::task::JoinHandle<()>,
+}
+
+#[cfg(feature = "workflow")]
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Start the heartbeat of run `workflow_id`, whose steps are `step_names`
+#[cfg(feature = "workflow")]
+pub(crate) fn start(
+    config: HeartbeatConfig,
+    stream: EventStream,
+    workflow_id: String,
+    step_names: Vec<String>,
+    activity: RunActivity,
+) -> Heartbeat {
+    let mut events = stream.subscribe();
+    let task = tokio::spawn(async move {
+        let mut ticks = tokio::time::interval_at(Instant::now() + config.interval, config.interval);
+        let mut stalled = false;
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {
+                    let mut report = activity.report(&workflow_id, &step_names);
+                    emit(&stream, "heartbeat", &report);
+                    let over = config
+                        .stall_threshold
+                        .is_some_and(|t| report.since_progress_ms >= t.as_millis() as u64);
+                    if over && !stalled {
+                        report.stalled = true;
+                        emit(&stream, "stall", &report);
+                        if let Some(callback) = &config.on_stall {
+                            callback(&report);
+                        }
+                    }
+                    stalled = over;
+                }
+                event = events.recv() => match event {
+                    Ok(event) if belongs_to(&event, &workflow_id) => activity.record_event(),
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Lagged(_)) => activity.record_event(),
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            }
+        }
+    });
+    Heartbeat { task }
+}
+
+/// Whether `event` is part of the run, not counting heartbeats
+#[cfg(feature = "workflow")]
+fn belongs_to(event: &Event, workflow_id: &str) -> bool {
+    let heartbeat = event.scope == EventScope::System
+        && (event.component_id.starts_with("system:heartbeat:")
+            || event.component_id.starts_with("system:stall:"));
+    !heartbeat
+        && (event.workflow_id == workflow_id
+            || event.workflow_path.iter().any(|w| w == workflow_id))
+}
+
+#[cfg(feature = "workflow")]
+fn emit(stream: &EventStream, kind: &str, report: &HeartbeatReport) {
+    let message = match (&report.step_name, report.stalled) {
+        (Some(step), true) => format!(
+            "No progress for {}ms in step '{}'",
+            report.since_progress_ms, step
+        ),
+        (None, true) => format!("No progress for {}ms", report.since_progress_ms),
+        (Some(step), false) => format!("Running step '{}'", step),
+        (None, false) => "Running".to_string(),
+    };
+    stream.append(
+        EventScope::System,
+        EventType::Progress,
+        format!("system:{}:{}", kind, report.workflow_id),
+        ComponentStatus::Running,
+        report.workflow_id.clone(),
+        Some(message),
+        serde_json::to_value(report).unwrap_or_default(),
+    );
+}
@@ -1,12 +1,34 @@
+pub mod heartbeat;
 pub mod retry;
 pub mod timeout;
 
+pub use heartbeat::{HeartbeatConfig, HeartbeatReport, RunActivity};
 pub use retry::RetryPolicy;
 pub use timeout::{with_timeout, TimeoutConfig};
 
 // The workflow executor and everything it touches are only compiled when
 // the `workflow` feature is enabled.
 #[cfg(feature = "workflow")]
+pub mod batch;
+#[cfg(feature = "workflow")]
+pub mod checkpoint;
+#[cfg(feature = "workflow")]
 mod executor;
 #[cfg(feature = "workflow")]
-pub use executor::Runtime;
+pub mod explain;
+#[cfg(feature = "workflow")]
+pub mod rerun;
+#[cfg(feature = "workflow")]
+mod streaming;
+#[cfg(feature = "workflow")]
+pub use batch::{WorkflowBatch, DEFAULT_BATCH_POLL_INTERVAL};
+#[cfg(feature = "workflow")]
+pub use checkpoint::{
+    CheckpointError, CheckpointStore, FileCheckpointStore, InMemoryCheckpointStore, RunCheckpoint,
+};
+#[cfg(feature = "workflow")]
+pub use executor::{CancellationHandle, Runtime};
+#[cfg(feature = "workflow")]
+pub use rerun::{RerunError, RerunOptions, RerunStart};
+#[cfg(feature = "workflow")]
+pub use streaming::{FlushPolicy, WorkflowStream, WorkflowUpdate};
@@ -0,0 +1,238 @@
+//! Re-executing a recorded run from a later step.
+//!
+//! [`Runtime::rerun_from`](crate::Runtime::rerun_from) replays the recorded
+//! outputs of the steps before the starting point instead of running them
+//! again, optionally with some outputs overridden, and executes the rest of
+//! the (possibly edited) workflow for real.
+
+use crate::artifact::ArtifactStore;
+use crate::context::WorkflowContext;
+use crate::types::JsonValue;
+use crate::workflow::{
+    check_run_compatibility, CompatibilityReport, StepStatus, Workflow, WorkflowRun,
+    WorkflowStepRecord,
+};
+use std::collections::HashMap;
+
+/// Where a rerun starts executing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RerunStart {
+    /// Index into the workflow's steps
+    Index(usize),
+    /// Name of a top-level step
+    Name(String),
+}
+
+/// What to replay and where to start executing
+#[derive(Debug, Clone)]
+pub struct RerunOptions {
+    pub start: RerunStart,
+
+    /// Recorded outputs to replace, by step name. Only replayed steps can
+    /// be overridden.
+    pub output_overrides: HashMap<String, JsonValue>,
+
+    /// Restore the context as it was after the last replayed step. Needs a
+    /// snapshot recorded with `Runtime::with_context_recording`.
+    pub reuse_context: bool,
+}
+
+impl RerunOptions {
+    /// Start executing at step `index`; steps before it are replayed
+    pub fn from_step(index: usize) -> Self {
+        Self {
+            start: RerunStart::Index(index),
+            output_overrides: HashMap::new(),
+            reuse_context: false,
+        }
+    }
+
+    /// Start executing at the step named `name`
+    pub fn from_step_named(name: impl Into<String>) -> Self {
+        Self {
+            start: RerunStart::Name(name.into()),
+            ..Self::from_step(0)
+        }
+    }
+
+    /// Replay `output` as the output of step `name`, e.g. to see what would
+    /// have happened had it returned something else
+    pub fn with_output(mut self, name: impl Into<String>, output: JsonValue) -> Self {
+        self.output_overrides.insert(name.into(), output);
+        self
+    }
+
+    pub fn reuse_context(mut self, reuse: bool) -> Self {
+        self.reuse_context = reuse;
+        self
+    }
+}
+
+/// Why a rerun could not start
+#[derive(Debug, thiserror::Error)]
+pub enum RerunError {
+    #[error("no step named '{0}' in the workflow")]
+    UnknownStep(String),
+
+    #[error(
+        "cannot start at step {index}: the workflow has {steps} steps and the run recorded {recorded}"
+    )]
+    StartOutOfRange {
+        index: usize,
+        steps: usize,
+        recorded: usize,
+    },
+
+    #[error("step {index} ('{name}') cannot be replayed: {reason}")]
+    StepMismatch {
+        index: usize,
+        name: String,
+        reason: String,
+    },
+
+    #[error("override for '{0}' does not name a replayed step")]
+    OverrideNotReplayed(String),
+
+    #[error(
+        "no context snapshot was recorded after step {0}; record one with Runtime::with_context_recording"
+    )]
+    MissingContextSnapshot(usize),
+
+    #[error("checkpoint version {found} is newer than the supported version {supported}")]
+    UnsupportedCheckpoint { found: u32, supported: u32 },
+
+    /// The new workflow's replayed steps no longer fit the recorded data
+    #[error("replayed steps are incompatible with the recorded run\n\n{}", .0.render_markdown())]
+    Incompatible(Box<CompatibilityReport>),
+}
+
+/// Name of the artifact holding the context after step `index`
+pub(crate) fn context_snapshot_name(index: usize) -> String {
+    format!("context-step-{}.json", index)
+}
+
+/// A validated rerun or resume, ready for the executor
+pub(crate) struct RerunPlan {
+    /// The run being re-executed; `None` when resuming a checkpoint
+    pub rerun_of: Option<String>,
+    pub start: usize,
+    pub input: JsonValue,
+    pub replayed: Vec<WorkflowStepRecord>,
+    pub context: Option<WorkflowContext>,
+}
+
+/// Check that the first steps of `workflow` can be replayed from `run`
+pub(crate) async fn plan(
+    run: &WorkflowRun,
+    workflow: &Workflow,
+    options: &RerunOptions,
+    artifacts: &ArtifactStore,
+) -> Result<RerunPlan, RerunError> {
+    let start = match &options.start {
+        RerunStart::Index(index) => *index,
+        RerunStart::Name(name) => workflow
+            .steps
+            .iter()
+            .position(|step| step.name() == name)
+            .ok_or_else(|| RerunError::UnknownStep(name.clone()))?,
+    };
+    if start >= workflow.steps.len() || start > run.steps.len() {
+        return Err(RerunError::StartOutOfRange {
+            index: start,
+            steps: workflow.steps.len(),
+            recorded: run.steps.len(),
+        });
+    }
+
+    // Replayed steps must line up with the recording one-to-one
+    let mut replayed = Vec::with_capacity(start);
+    for (index, step) in workflow.steps[..start].iter().enumerate() {
+        let record = &run.steps[index];
+        let mismatch = |reason: String| RerunError::StepMismatch {
+            index,
+            name: step.name().to_string(),
+            reason,
+        };
+        if record.step_name != step.name() {
+            return Err(mismatch(format!(
+                "the run recorded '{}' here",
+                record.step_name
+            )));
+        }
+        let step_type = format!("{:?}", step.step_type());
+        if record.step_type != step_type {
+            return Err(mismatch(format!(
+                "it is now {} but was recorded as {}",
+                step_type, record.step_type
+            )));
+        }
+        // An overridden output stands in for whatever the step did
+        let replayed_record = match options.output_overrides.get(&record.step_name) {
+            Some(output) => WorkflowStepRecord {
+                output: Some(output.clone()),
+                status: StepStatus::Succeeded,
+                error: None,
+                ..record.clone()
+            },
+            None => record.clone(),
+        };
+        if replayed_record.output.is_none() {
+            return Err(mismatch("the run recorded no output for it".to_string()));
+        }
+        replayed.push(WorkflowStepRecord {
+            replayed: true,
+            ..replayed_record
+        });
+    }
+    if let Some(name) = options
+        .output_overrides
+        .keys()
+        .find(|name| !replayed.iter().any(|r| &r.step_name == *name))
+    {
+        return Err(RerunError::OverrideNotReplayed(name.clone()));
+    }
+
+    // Replayed transforms and conditions must still produce data shaped like
+    // the recording; what happens from `start` on is the point of the rerun
+    let report = check_run_compatibility(run, workflow).await;
+    if report
+        .issues
+        .iter()
+        .any(|issue| replayed.iter().any(|r| r.step_name == issue.step()))
+    {
+        return Err(RerunError::Incompatible(Box::new(report)));
+    }
+
+    let context = if options.reuse_context && start > 0 {
+        let name = context_snapshot_name(start - 1);
+        let snapshot = run
+            .artifacts
+            .iter()
+            .filter(|artifact| artifact.name == name)
+            .find_map(|artifact| artifacts.get(&artifact.id))
+            .ok_or(RerunError::MissingContextSnapshot(start - 1))?;
+        Some(
+            crate::persist::decode(&snapshot.data)
+                .map_err(|_| RerunError::MissingContextSnapshot(start - 1))?,
+        )
+    } else {
+        None
+    };
+
+    let input = match replayed.last() {
+        Some(record) => record.output.clone().unwrap_or_default(),
+        None => run
+            .steps
+            .first()
+            .map(|r| r.input.clone())
+            .unwrap_or_else(|| workflow.initial_input.clone()),
+    };
+
+    Ok(RerunPlan {
+        rerun_of: Some(run.run_id.clone()),
+        start,
+        input,
+        replayed,
+        context,
+    })
+}
@@ -79,6 +79,41 @@ impl RetryPolicy {
         }
     }
 
+    /// Short, bounded retry for transient tool failures
+    ///
+    /// Two retries starting at 200ms, never more than 5s in total, so a
+    /// flaky tool costs the agent little more than the failure itself.
+    pub fn transient_tool() -> Self {
+        Self {
+            max_attempts: 2,
+            initial_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(2),
+            backoff_multiplier: 2.0,
+            jitter_factor: 0.1,
+            max_total_duration: Some(Duration::from_secs(5)),
+        }
+    }
+
+    /// Delay before retry number `retry` (0-indexed), or `None` if it
+    /// shouldn't happen: retries are used up, or waiting would overrun
+    /// `max_total_duration` (measured by `elapsed`) or `time_left`
+    pub fn next_delay(
+        &self,
+        retry: u32,
+        elapsed: Duration,
+        time_left: Option<Duration>,
+    ) -> Option<Duration> {
+        if retry >= self.max_attempts {
+            return None;
+        }
+        let delay = self.delay_for_attempt(retry);
+        let over_budget = self
+            .max_total_duration
+            .is_some_and(|max| elapsed + delay > max);
+        let past_deadline = time_left.is_some_and(|left| delay >= left);
+        (!over_budget && !past_deadline).then_some(delay)
+    }
+
     /// Calculate delay for a given attempt number (0-indexed)
     pub fn delay_for_attempt(&self, attempt: u32) -> Duration {
         let base_delay =
@@ -195,6 +230,31 @@ mod tests {
         assert_eq!(policy.delay_for_attempt(2).as_millis(), 400);
     }
 
+    #[test]
+    fn test_next_delay_respects_budgets() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            backoff_multiplier: 2.0,
+            jitter_factor: 0.0,
+            max_total_duration: Some(Duration::from_millis(500)),
+        };
+        let zero = Duration::ZERO;
+
+        assert_eq!(
+            policy.next_delay(1, zero, None),
+            Some(Duration::from_millis(200))
+        );
+        assert_eq!(policy.next_delay(3, zero, None), None);
+        // 400ms more would pass the 500ms total
+        assert_eq!(policy.next_delay(2, Duration::from_millis(150), None), None);
+        assert_eq!(
+            policy.next_delay(0, zero, Some(Duration::from_millis(100))),
+            None
+        );
+    }
+
     #[test]
     fn test_max_delay_clamp() {
         let policy = RetryPolicy {
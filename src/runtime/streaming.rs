@@ -0,0 +1,351 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::channel::mpsc::UnboundedSender;
+use futures::{Stream, StreamExt};
+
+use crate::event::{Event, EventScope, EventSubscription, EventType};
+use crate::types::JsonValue;
+use crate::workflow::{StepType, Workflow, WorkflowRun};
+
+/// What happened in a run started by [`super::Runtime::execute_streaming`]
+#[derive(Debug, Clone)]
+pub enum WorkflowUpdate {
+    StepStarted {
+        step_index: usize,
+        step_name: String,
+    },
+
+    /// Streamed text from an agent's LLM request
+    LlmChunk { agent: String, text: String },
+
+    /// An agent is calling a tool
+    ToolCall { name: String, args: JsonValue },
+
+    /// `output` is the step's output as recorded (after PII scanning), or
+    /// null if it was blocked
+    StepCompleted {
+        step_index: usize,
+        step_name: String,
+        output: JsonValue,
+    },
+
+    /// The last update of a successful run
+    WorkflowCompleted { run: WorkflowRun },
+
+    /// The last update of a run that failed or was canceled; `run.state`
+    /// tells which
+    WorkflowFailed { error: String, run: WorkflowRun },
+}
+
+type RunFuture<'a> = Pin<Box<dyn Future<Output = WorkflowRun> + Send + 'a>>;
+type NextEvent = Pin<Box<dyn Future<Output = (EventSubscription, Event)> + Send>>;
+
+/// Updates from one run, ending with `WorkflowCompleted` or
+/// `WorkflowFailed`
+///
+/// The run starts when the stream is first polled and is driven by
+/// polling it; dropping the stream cancels the run. Events of sub-workflows
+/// count toward the run for LLM chunks and tool calls, while their steps
+/// show up as the one step that started them. Detail held back by trace
+/// sampling until the run ends is not included.
+pub struct WorkflowStream<'a> {
+    workflow_id: String,
+
+    /// This run and the sub-workflows it started
+    runs: HashSet<String>,
+
+    run: Option<RunFuture<'a>>,
+    finished: Option<WorkflowRun>,
+    next_event: NextEvent,
+
+    /// The run's terminal event, once seen
+    outcome: Option<Event>,
+    done: bool,
+}
+
+impl<'a> WorkflowStream<'a> {
+    pub(super) fn new(
+        workflow_id: String,
+        subscription: EventSubscription,
+        run: impl Future<Output = WorkflowRun> + Send + 'a,
+    ) -> Self {
+        Self {
+            runs: HashSet::from([workflow_id.clone()]),
+            workflow_id,
+            run: Some(Box::pin(run)),
+            finished: None,
+            next_event: next_event(subscription),
+            outcome: None,
+            done: false,
+        }
+    }
+
+    /// ID of the run this stream follows
+    pub fn workflow_id(&self) -> &str {
+        &self.workflow_id
+    }
+
+    /// Drain the remaining updates and return the finished run
+    pub async fn into_run(mut self) -> WorkflowRun {
+        while let Some(update) = self.next().await {
+            match update {
+                WorkflowUpdate::WorkflowCompleted { run }
+                | WorkflowUpdate::WorkflowFailed { run, .. } => return run,
+                _ => {}
+            }
+        }
+        unreachable!("a workflow stream always ends with the run")
+    }
+
+    fn translate(&mut self, event: Event) -> Option<WorkflowUpdate> {
+        if event.scope == EventScope::Workflow && event.event_type == EventType::Started {
+            let parent = event.data["parent_workflow_id"].as_str();
+            if parent.is_some_and(|parent| self.runs.contains(parent)) {
+                self.runs.insert(event.workflow_id);
+            }
+            return None;
+        }
+        if !self.runs.contains(&event.workflow_id) {
+            return None;
+        }
+        let own = event.workflow_id == self.workflow_id;
+
+        match (&event.scope, &event.event_type) {
+            (
+                EventScope::Workflow,
+                EventType::Completed | EventType::Failed | EventType::Canceled,
+            ) if own => {
+                self.outcome = Some(event);
+                None
+            }
+            (EventScope::WorkflowStep, EventType::Started) if own => {
+                Some(WorkflowUpdate::StepStarted {
+                    step_index: step_index(&event)?,
+                    step_name: step_name(&event),
+                })
+            }
+            (EventScope::WorkflowStep, EventType::Completed) if own => {
+                Some(WorkflowUpdate::StepCompleted {
+                    step_index: step_index(&event)?,
+                    step_name: step_name(&event),
+                    output: event.data["output"].clone(),
+                })
+            }
+            (EventScope::LlmRequest, EventType::Progress) => {
+                let (agent, _) = event.component_id.rsplit_once(":llm:")?;
+                Some(WorkflowUpdate::LlmChunk {
+                    agent: agent.to_string(),
+                    text: event.data["chunk"].as_str()?.to_string(),
+                })
+            }
+            (EventScope::Tool, EventType::Started) => {
+                let args = match &event.data["arguments"] {
+                    JsonValue::String(raw) => {
+                        serde_json::from_str(raw).unwrap_or_else(|_| raw.clone().into())
+                    }
+                    other => other.clone(),
+                };
+                Some(WorkflowUpdate::ToolCall {
+                    name: event.component_id,
+                    args,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Stream for WorkflowStream<'_> {
+    type Item = WorkflowUpdate;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<WorkflowUpdate>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+        if let Some(run) = &mut this.run {
+            if let Poll::Ready(run) = run.as_mut().poll(cx) {
+                this.finished = Some(run);
+                this.run = None;
+            }
+        }
+
+        loop {
+            // The run returns before its terminal event is published, and
+            // the event can come first too; finish once both are in
+            if this.finished.is_some() {
+                if let Some(outcome) = this.outcome.take() {
+                    this.done = true;
+                    let run = this.finished.take().expect("checked above");
+                    return Poll::Ready(Some(match outcome.event_type {
+                        EventType::Completed => WorkflowUpdate::WorkflowCompleted { run },
+                        _ => WorkflowUpdate::WorkflowFailed {
+                            error: outcome.message.unwrap_or_default(),
+                            run,
+                        },
+                    }));
+                }
+            }
+
+            let (subscription, event) = match this.next_event.as_mut().poll(cx) {
+                Poll::Ready(next) => next,
+                Poll::Pending => return Poll::Pending,
+            };
+            this.next_event = next_event(subscription);
+            if let Some(update) = this.translate(event) {
+                return Poll::Ready(Some(update));
+            }
+        }
+    }
+}
+
+fn next_event(mut subscription: EventSubscription) -> NextEvent {
+    Box::pin(async move {
+        let event = subscription.recv().await;
+        (subscription, event)
+    })
+}
+
+/// Index from a `{workflow}:step:{index}` component; `None` for a parallel
+/// branch (`{index}.{branch}`)
+fn step_index(event: &Event) -> Option<usize> {
+    let (_, index) = event.component_id.rsplit_once(":step:")?;
+    index.parse().ok()
+}
+
+fn step_name(event: &Event) -> String {
+    event.data["step_name"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Which chunks [`super::Runtime::execute_with_output_stream`] passes on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FlushPolicy {
+    /// The answer of the workflow's last agent step: chunks of a response
+    /// that turns out to call tools are dropped
+    #[default]
+    FinalResponse,
+
+    /// Every chunk of every agent in the run and its sub-workflows, as it
+    /// arrives; for debugging
+    AllChunks,
+}
+
+/// Picks the chunks of one run to pass on to the caller
+///
+/// Under `FinalResponse`, a response whose request offered no tools can
+/// only be an answer and is passed on as it streams. Otherwise its chunks
+/// are held until the request completes, then passed on if it called no
+/// tools, and dropped if it did or the attempt failed.
+pub(super) struct OutputForwarder {
+    workflow_id: String,
+    policy: FlushPolicy,
+
+    /// This run and the sub-workflows it started
+    runs: HashSet<String>,
+
+    /// Index of the last agent step, whose answer is the output
+    output_step: Option<usize>,
+    current_step: Option<usize>,
+
+    /// Chunks held back, by `{agent}:llm:{iteration}` component
+    held: HashMap<String, Vec<String>>,
+    tx: UnboundedSender<String>,
+}
+
+impl OutputForwarder {
+    pub(super) fn new(
+        workflow: &Workflow,
+        policy: FlushPolicy,
+        tx: UnboundedSender<String>,
+    ) -> Self {
+        Self {
+            workflow_id: workflow.id.clone(),
+            policy,
+            runs: HashSet::from([workflow.id.clone()]),
+            output_step: workflow
+                .steps
+                .iter()
+                .rposition(|step| step.step_type() == StepType::Agent),
+            current_step: None,
+            held: HashMap::new(),
+            tx,
+        }
+    }
+
+    /// Pass on what `event` completes; true once it ends the run
+    pub(super) fn observe(&mut self, event: Event) -> bool {
+        match (&event.scope, &event.event_type) {
+            (EventScope::Workflow, EventType::Started) => {
+                let parent = event.data["parent_workflow_id"].as_str();
+                if parent.is_some_and(|parent| self.runs.contains(parent)) {
+                    self.runs.insert(event.workflow_id);
+                }
+                return false;
+            }
+            (
+                EventScope::Workflow,
+                EventType::Completed | EventType::Failed | EventType::Canceled,
+            ) if event.workflow_id == self.workflow_id => {
+                return true;
+            }
+            _ => {}
+        }
+        if self.policy == FlushPolicy::AllChunks {
+            if event.scope == EventScope::LlmRequest
+                && event.event_type == EventType::Progress
+                && self.runs.contains(&event.workflow_id)
+            {
+                self.send(&event);
+            }
+            return false;
+        }
+        if event.workflow_id != self.workflow_id {
+            return false;
+        }
+
+        match (&event.scope, &event.event_type) {
+            (EventScope::WorkflowStep, EventType::Started) => {
+                self.current_step = step_index(&event);
+                self.held.clear();
+            }
+            _ if event.scope != EventScope::LlmRequest
+                || self.output_step.is_none()
+                || self.current_step != self.output_step => {}
+            (_, EventType::Progress) if event.data["tools_offered"] == false => self.send(&event),
+            (_, EventType::Progress) => {
+                if let Some(chunk) = event.data["chunk"].as_str() {
+                    self.held
+                        .entry(event.component_id)
+                        .or_default()
+                        .push(chunk.to_string());
+                }
+            }
+            (_, EventType::Completed) => {
+                let held = self.held.remove(&event.component_id);
+                if event.data["has_tool_calls"] == false {
+                    for chunk in held.into_iter().flatten() {
+                        let _ = self.tx.unbounded_send(chunk);
+                    }
+                }
+            }
+            (_, EventType::Failed | EventType::Canceled) => {
+                self.held.remove(&event.component_id);
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn send(&self, event: &Event) {
+        if let Some(chunk) = event.data["chunk"].as_str() {
+            // The caller may have stopped listening; the run goes on
+            let _ = self.tx.unbounded_send(chunk.to_string());
+        }
+    }
+}
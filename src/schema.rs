@@ -0,0 +1,273 @@
+//! JSON Schemas checked before a workflow's first step and before each
+//! tool call.
+//!
+//! Supports the JSON Schema keywords workflow inputs and tool arguments
+//! need: `type`, `enum`, `properties`, `required`, `additionalProperties`,
+//! `items`, `default`, `minimum`/`maximum`, `minLength`/`maxLength`,
+//! `minItems`/`maxItems` and `pattern`. Other keywords are ignored.
+
+use crate::error::InputViolation;
+use crate::types::JsonValue;
+use serde_json::Map;
+
+/// A JSON Schema for a workflow's `initial_input` or a tool's arguments
+#[derive(Debug, Clone)]
+pub struct InputSchema {
+    schema: JsonValue,
+    lenient: bool,
+}
+
+impl InputSchema {
+    /// Input must match exactly; nothing is rewritten
+    pub fn strict(schema: JsonValue) -> Self {
+        Self {
+            schema,
+            lenient: false,
+        }
+    }
+
+    /// Missing properties take their declared `default`, and scalars are
+    /// coerced to the declared type where unambiguous (`"42"` → `42`,
+    /// `"true"` → `true`, `7` → `"7"`)
+    pub fn lenient(schema: JsonValue) -> Self {
+        Self {
+            schema,
+            lenient: true,
+        }
+    }
+
+    /// The schema document, e.g. for rendering an input form
+    pub fn schema(&self) -> &JsonValue {
+        &self.schema
+    }
+
+    pub fn is_lenient(&self) -> bool {
+        self.lenient
+    }
+
+    /// Check `input`, returning it with defaults and coercions applied, or
+    /// every violation found
+    pub fn validate(&self, input: &JsonValue) -> Result<JsonValue, Vec<InputViolation>> {
+        let mut value = input.clone();
+        let mut violations = Vec::new();
+        self.check(
+            &self.schema,
+            &mut value,
+            &mut String::new(),
+            &mut violations,
+        );
+        if violations.is_empty() {
+            Ok(value)
+        } else {
+            Err(violations)
+        }
+    }
+
+    fn check(
+        &self,
+        schema: &JsonValue,
+        value: &mut JsonValue,
+        pointer: &mut String,
+        violations: &mut Vec<InputViolation>,
+    ) {
+        let mut violation = |message: String| {
+            violations.push(InputViolation {
+                pointer: pointer.clone(),
+                message,
+            })
+        };
+
+        if let Some(types) = declared_types(schema) {
+            if !types.iter().any(|t| has_type(value, t)) {
+                match self.lenient.then(|| coerce(value, &types)).flatten() {
+                    Some(coerced) => *value = coerced,
+                    None => {
+                        return violation(format!(
+                            "expected {}, found {}",
+                            types.join(" or "),
+                            type_name(value)
+                        ))
+                    }
+                }
+            }
+        }
+
+        if let Some(allowed) = schema.get("enum").and_then(JsonValue::as_array) {
+            if !allowed.contains(value) {
+                let allowed: Vec<String> = allowed.iter().map(JsonValue::to_string).collect();
+                violation(format!("must be one of {}", allowed.join(", ")));
+            }
+        }
+
+        match value {
+            JsonValue::Number(n) => {
+                let n = n.as_f64().unwrap_or_default();
+                if let Some(min) = schema.get("minimum").and_then(JsonValue::as_f64) {
+                    if n < min {
+                        violation(format!("must be at least {}", min));
+                    }
+                }
+                if let Some(max) = schema.get("maximum").and_then(JsonValue::as_f64) {
+                    if n > max {
+                        violation(format!("must be at most {}", max));
+                    }
+                }
+            }
+            JsonValue::String(s) => {
+                let len = s.chars().count() as u64;
+                if let Some(min) = schema.get("minLength").and_then(JsonValue::as_u64) {
+                    if len < min {
+                        violation(format!("must be at least {} characters", min));
+                    }
+                }
+                if let Some(max) = schema.get("maxLength").and_then(JsonValue::as_u64) {
+                    if len > max {
+                        violation(format!("must be at most {} characters", max));
+                    }
+                }
+                if let Some(pattern) = schema.get("pattern").and_then(JsonValue::as_str) {
+                    match regex::Regex::new(pattern) {
+                        Ok(re) if !re.is_match(s) => {
+                            violation(format!("must match pattern '{}'", pattern))
+                        }
+                        Ok(_) => {}
+                        Err(e) => violation(format!("schema pattern is invalid: {}", e)),
+                    }
+                }
+            }
+            JsonValue::Array(items) => {
+                let len = items.len() as u64;
+                if let Some(min) = schema.get("minItems").and_then(JsonValue::as_u64) {
+                    if len < min {
+                        violation(format!("must have at least {} items", min));
+                    }
+                }
+                if let Some(max) = schema.get("maxItems").and_then(JsonValue::as_u64) {
+                    if len > max {
+                        violation(format!("must have at most {} items", max));
+                    }
+                }
+                if let Some(item_schema) = schema.get("items").filter(|s| s.is_object()) {
+                    for (index, item) in items.iter_mut().enumerate() {
+                        let len = pointer.len();
+                        pointer.push_str(&format!("/{}", index));
+                        self.check(item_schema, item, pointer, violations);
+                        pointer.truncate(len);
+                    }
+                }
+            }
+            JsonValue::Object(object) => self.check_object(schema, object, pointer, violations),
+            _ => {}
+        }
+    }
+
+    fn check_object(
+        &self,
+        schema: &JsonValue,
+        object: &mut Map<String, JsonValue>,
+        pointer: &mut String,
+        violations: &mut Vec<InputViolation>,
+    ) {
+        let empty = Map::new();
+        let properties = schema
+            .get("properties")
+            .and_then(JsonValue::as_object)
+            .unwrap_or(&empty);
+
+        if self.lenient {
+            for (name, property) in properties {
+                if let (false, Some(default)) = (object.contains_key(name), property.get("default"))
+                {
+                    object.insert(name.clone(), default.clone());
+                }
+            }
+        }
+
+        let required = schema.get("required").and_then(JsonValue::as_array);
+        for name in required.into_iter().flatten().filter_map(JsonValue::as_str) {
+            if !object.contains_key(name) {
+                violations.push(InputViolation {
+                    pointer: format!("{}/{}", pointer, escape(name)),
+                    message: "required property is missing".to_string(),
+                });
+            }
+        }
+
+        let additional = schema.get("additionalProperties");
+        for (name, value) in object.iter_mut() {
+            let property = match properties.get(name) {
+                Some(property) => property,
+                None => match additional {
+                    Some(JsonValue::Bool(false)) => {
+                        violations.push(InputViolation {
+                            pointer: format!("{}/{}", pointer, escape(name)),
+                            message: "property is not allowed".to_string(),
+                        });
+                        continue;
+                    }
+                    Some(property) if property.is_object() => property,
+                    _ => continue,
+                },
+            };
+            let len = pointer.len();
+            pointer.push('/');
+            pointer.push_str(&escape(name));
+            self.check(property, value, pointer, violations);
+            pointer.truncate(len);
+        }
+    }
+}
+
+/// RFC 6901 escaping for one pointer segment
+pub(crate) fn escape(segment: &str) -> String {
+    segment.replace('~', "~0").replace('/', "~1")
+}
+
+pub(crate) fn declared_types(schema: &JsonValue) -> Option<Vec<&str>> {
+    match schema.get("type")? {
+        JsonValue::String(t) => Some(vec![t.as_str()]),
+        JsonValue::Array(types) => Some(types.iter().filter_map(JsonValue::as_str).collect()),
+        _ => None,
+    }
+}
+
+fn has_type(value: &JsonValue, declared: &str) -> bool {
+    match declared {
+        "integer" => value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        other => type_name(value) == other,
+    }
+}
+
+fn type_name(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Null => "null",
+        JsonValue::Bool(_) => "boolean",
+        JsonValue::Number(_) => "number",
+        JsonValue::String(_) => "string",
+        JsonValue::Array(_) => "array",
+        JsonValue::Object(_) => "object",
+    }
+}
+
+/// The value converted to the first declared type it fits, if any
+fn coerce(value: &JsonValue, types: &[&str]) -> Option<JsonValue> {
+    types.iter().find_map(|declared| match (*declared, value) {
+        ("integer", JsonValue::String(s)) => s.trim().parse::<i64>().ok().map(Into::into),
+        ("number", JsonValue::String(s)) => match s.trim().parse::<i64>() {
+            Ok(n) => Some(n.into()),
+            Err(_) => s
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .map(Into::into),
+        },
+        ("boolean", JsonValue::String(s)) => match s.trim() {
+            "true" => Some(true.into()),
+            "false" => Some(false.into()),
+            _ => None,
+        },
+        ("string", JsonValue::Number(_) | JsonValue::Bool(_)) => Some(value.to_string().into()),
+        _ => None,
+    })
+}
@@ -0,0 +1,202 @@
+//! Multi-turn conversations with one agent, outside a workflow.
+//!
+//! A [`Session`] keeps the conversation's history between
+//! [`send`](Session::send)s: each user message runs the agent, tools and
+//! all, on the history so far, and the agent's reply and tool exchanges are
+//! appended to it. Before each turn the session's [`ContextManager`] prunes
+//! the history if it asks to, as a workflow's does before an `AgentStep`.
+//! [`checkpoint`](Session::checkpoint) captures the conversation so a later
+//! request can [`restore`](Session::restore) it.
+//!
+//! ```no_run
+//! use agent_runtime::context_strategies::TokenBudgetManager;
+//! use agent_runtime::{Agent, AgentConfig, Session};
+//! use std::sync::Arc;
+//!
+//! # async fn example(agent: Agent) -> Result<(), Box<dyn std::error::Error>> {
+//! let mut session = Session::new(agent, Arc::new(TokenBudgetManager::new(8_000, 3.0)));
+//! session.send("My order number is 4417").await?;
+//! let reply = session.send("When will it arrive?").await?;
+//!
+//! let saved = serde_json::to_string(&session.checkpoint())?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::agent::Agent;
+use crate::context::{prune_messages, ContextManager, NoOpManager};
+use crate::event::{ComponentStatus, EventScope, EventStream, EventType};
+use crate::llm::types::{ChatMessage, Role};
+use crate::types::{AgentError, AgentInput, AgentResult};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// A conversation with one agent
+pub struct Session {
+    id: String,
+    agent: Agent,
+    context_manager: Arc<dyn ContextManager>,
+
+    /// Every turn so far, without the system prompt
+    history: Vec<ChatMessage>,
+    turns: usize,
+    event_stream: Option<EventStream>,
+}
+
+/// A session's conversation, for storing between requests
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionSnapshot {
+    pub id: String,
+    /// Name of the agent it was taken from
+    pub agent: String,
+    pub history: Vec<ChatMessage>,
+    /// Messages sent so far
+    pub turns: usize,
+}
+
+impl Session {
+    /// Start a conversation with `agent`, its history pruned by
+    /// `context_manager`
+    pub fn new(agent: Agent, context_manager: Arc<dyn ContextManager>) -> Self {
+        Self {
+            id: format!("session_{}", uuid::Uuid::new_v4()),
+            agent,
+            context_manager,
+            history: Vec::new(),
+            turns: 0,
+            event_stream: None,
+        }
+    }
+
+    /// Continue a checkpointed conversation with `agent`
+    ///
+    /// Snapshots don't hold the context manager; the history isn't pruned
+    /// until one is set with [`with_context_manager`](Self::with_context_manager).
+    pub fn restore(agent: Agent, snapshot: SessionSnapshot) -> Self {
+        Self {
+            id: snapshot.id,
+            agent,
+            context_manager: Arc::new(NoOpManager::new()),
+            history: snapshot.history,
+            turns: snapshot.turns,
+            event_stream: None,
+        }
+    }
+
+    pub fn with_context_manager(mut self, context_manager: Arc<dyn ContextManager>) -> Self {
+        self.context_manager = context_manager;
+        self
+    }
+
+    /// Emit the agent's events, and pruning events, to `stream`; the
+    /// session id is their `workflow_id`
+    pub fn with_event_stream(mut self, stream: EventStream) -> Self {
+        self.event_stream = Some(stream);
+        self
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn agent(&self) -> &Agent {
+        &self.agent
+    }
+
+    /// The conversation so far, without the system prompt
+    pub fn history(&self) -> &[ChatMessage] {
+        &self.history
+    }
+
+    /// Messages sent so far
+    pub fn turns(&self) -> usize {
+        self.turns
+    }
+
+    /// Send the user's next message and return the agent's answer
+    ///
+    /// A turn that fails leaves the history as it was, so the message can
+    /// be sent again.
+    pub async fn send(&mut self, user_text: impl Into<String>) -> AgentResult {
+        let text = user_text.into();
+        let mut history = self.history.clone();
+        history.push(ChatMessage::user(text.clone()));
+        let history = self.prune(history).await?;
+
+        let mut input = AgentInput::from_text(text);
+        input.metadata.workflow_id = Some(self.id.clone());
+        input.metadata.step_index = self.turns;
+        input.chat_history = Some(history.clone());
+        let output = self
+            .agent
+            .execute_with_events(input, self.event_stream.as_ref())
+            .await?;
+
+        // The system prompt is the agent's, not the conversation's
+        self.history = match &output.chat_history {
+            Some(messages) => messages
+                .iter()
+                .filter(|m| m.role != Role::System)
+                .cloned()
+                .collect(),
+            None => {
+                let mut history = history;
+                history.push(ChatMessage::assistant(response_text(&output.data)));
+                history
+            }
+        };
+        self.turns += 1;
+        Ok(output)
+    }
+
+    /// Forget the conversation, keeping the session's id
+    pub fn reset(&mut self) {
+        self.history.clear();
+        self.turns = 0;
+    }
+
+    pub fn checkpoint(&self) -> SessionSnapshot {
+        SessionSnapshot {
+            id: self.id.clone(),
+            agent: self.agent.name().to_string(),
+            history: self.history.clone(),
+            turns: self.turns,
+        }
+    }
+
+    /// `history` as the context manager leaves it
+    async fn prune(&self, history: Vec<ChatMessage>) -> Result<Vec<ChatMessage>, AgentError> {
+        let pruned = prune_messages(self.context_manager.as_ref(), &history)
+            .await
+            .map_err(|e| AgentError::ExecutionError(format!("context pruning failed: {}", e)))?;
+        let Some((pruned, outcome)) = pruned else {
+            return Ok(history);
+        };
+        if let Some(stream) = &self.event_stream {
+            stream.append(
+                EventScope::System,
+                EventType::Progress,
+                "system:context_pruning".to_string(),
+                ComponentStatus::Running,
+                self.id.clone(),
+                Some(format!(
+                    "{} pruned the history from {} to {} messages before '{}'",
+                    outcome.strategy,
+                    outcome.messages_before,
+                    outcome.messages_after,
+                    self.agent.name()
+                )),
+                serde_json::to_value(&outcome).unwrap_or_default(),
+            );
+        }
+        Ok(pruned)
+    }
+}
+
+fn response_text(data: &serde_json::Value) -> String {
+    match data.get("response") {
+        Some(serde_json::Value::String(text)) => text.clone(),
+        Some(other) => other.to_string(),
+        None => data.to_string(),
+    }
+}
@@ -0,0 +1,99 @@
+//! `tracing` spans for workflows, steps, LLM requests and tool calls.
+//!
+//! Spans nest as `workflow.execute` > `workflow.step` > `llm.request` /
+//! `tool.call`; a sub-workflow's `workflow.execute` sits inside the step that
+//! started it. Each records `duration_ms` before it closes and, on failure,
+//! `error` plus `otel.status_code = "ERROR"` for OpenTelemetry exporters.
+//! Without a subscriber the spans are disabled and cost next to nothing.
+//!
+//! [`logging::init_tracing`](crate::logging::init_tracing) installs a simple
+//! subscriber; any other `tracing` subscriber works too.
+
+use crate::llm::types::Usage;
+use std::time::Duration;
+use tracing::field::Empty;
+use tracing::Span;
+
+#[cfg(feature = "workflow")]
+pub(crate) fn workflow_span(workflow_id: &str, parent_workflow_id: Option<&str>) -> Span {
+    tracing::info_span!(
+        "workflow.execute",
+        workflow_id,
+        parent_workflow_id,
+        state = Empty,
+        prompt_tokens = Empty,
+        completion_tokens = Empty,
+        total_tokens = Empty,
+        duration_ms = Empty,
+        error = Empty,
+        otel.status_code = Empty,
+    )
+}
+
+#[cfg(feature = "workflow")]
+pub(crate) fn step_span(step: &str, step_type: &str, step_index: usize) -> Span {
+    tracing::info_span!(
+        "workflow.step",
+        step,
+        step_type,
+        step_index,
+        duration_ms = Empty,
+        error = Empty,
+        otel.status_code = Empty,
+    )
+}
+
+/// `model` is filled in from the response, and `provider` corrected if a
+/// fallback chain answered from another provider
+pub(crate) fn llm_span(agent: &str, provider: &str, iteration: usize) -> Span {
+    tracing::info_span!(
+        "llm.request",
+        agent,
+        provider,
+        model = Empty,
+        iteration,
+        attempts = Empty,
+        finish_reason = Empty,
+        prompt_tokens = Empty,
+        completion_tokens = Empty,
+        total_tokens = Empty,
+        duration_ms = Empty,
+        error = Empty,
+        otel.status_code = Empty,
+    )
+}
+
+pub(crate) fn tool_span(agent: &str, tool: &str, tool_call_id: &str) -> Span {
+    tracing::info_span!(
+        "tool.call",
+        agent,
+        tool,
+        tool_call_id,
+        duration_ms = Empty,
+        error = Empty,
+        otel.status_code = Empty,
+    )
+}
+
+/// Record how long `span` took and, if it failed, why
+pub(crate) fn finish(span: &Span, elapsed: Duration, error: Option<&str>) {
+    span.record("duration_ms", millis(elapsed));
+    if let Some(error) = error {
+        span.record("error", error);
+        span.record("otel.status_code", "ERROR");
+    }
+}
+
+/// Record an LLM response's token counts on `span`
+pub(crate) fn record_usage(span: &Span, usage: Option<&Usage>) {
+    if let Some(usage) = usage {
+        span.record("prompt_tokens", usage.prompt_tokens);
+        span.record("completion_tokens", usage.completion_tokens);
+        span.record("total_tokens", usage.total_tokens);
+    }
+}
+
+/// `elapsed` in milliseconds, to the microsecond
+pub(crate) fn millis(elapsed: Duration) -> f64 {
+    elapsed.as_micros() as f64 / 1000.0
+}
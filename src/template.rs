@@ -0,0 +1,730 @@
+//! Sandboxed `{{var}}` templates for user-supplied prompts and transforms.
+//!
+//! Templates may come from tenants, so rendering is bounded: output size,
+//! variable lookups and partial nesting are capped by [`TemplateLimits`],
+//! and a template can only read the variable roots it was allowed. Regex
+//! filters are compiled with size limits and only run on bounded input; the
+//! `regex` engine matches in linear time, so that also bounds their runtime.
+//!
+//! Syntax:
+//! - `{{ input.user.name }}` inserts a value (strings as-is, anything else as
+//!   JSON); numeric segments index arrays
+//! - `{{ input.text | replace "\s+" " " }}` rewrites the value with a regex
+//! - `{{> footer }}` renders a partial added with [`Template::with_partial`]
+//! - `\{{` is a literal `{{`
+//!
+//! Run uploaded templates through [`validate_template`] to report problems
+//! before they are stored.
+
+use crate::types::JsonValue;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Bounds applied when parsing and rendering a template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateLimits {
+    /// Largest rendered output, in bytes
+    pub max_output_bytes: usize,
+
+    /// Variable lookups per render, across partials
+    pub max_lookups: usize,
+
+    /// How deeply partials may include each other
+    pub max_depth: usize,
+
+    /// Compiled size limit for each regex, in bytes
+    pub max_regex_size: usize,
+
+    /// Longest value a regex filter will run on, in bytes
+    pub max_regex_input: usize,
+}
+
+impl Default for TemplateLimits {
+    fn default() -> Self {
+        Self {
+            max_output_bytes: 64 * 1024,
+            max_lookups: 1_000,
+            max_depth: 8,
+            max_regex_size: 256 * 1024,
+            max_regex_input: 64 * 1024,
+        }
+    }
+}
+
+/// What rendering does with a variable that isn't set
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingVariables {
+    /// Fail with [`TemplateErrorKind::MissingVariable`]
+    #[default]
+    Error,
+
+    /// Leave the tag in the output as written
+    Literal,
+}
+
+/// What went wrong with a template
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TemplateErrorKind {
+    #[error("syntax error at byte {offset}: {message}")]
+    Syntax { offset: usize, message: String },
+
+    #[error("'{0}' is outside the variables this template may use")]
+    ForbiddenVariable(String),
+
+    #[error("variable '{0}' is not set")]
+    MissingVariable(String),
+
+    #[error("no partial named '{0}'")]
+    UnknownPartial(String),
+
+    #[error("output exceeds {limit} bytes")]
+    OutputTooLarge { limit: usize },
+
+    #[error("more than {limit} variable lookups")]
+    TooManyLookups { limit: usize },
+
+    #[error("partials nested more than {limit} deep")]
+    TooDeep { limit: usize },
+
+    #[error("invalid regex '{pattern}': {message}")]
+    Regex { pattern: String, message: String },
+
+    #[error("regex input of {len} bytes exceeds {limit}")]
+    RegexInputTooLarge { len: usize, limit: usize },
+}
+
+/// A template failed to parse or render
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("template '{template}': {kind}")]
+pub struct TemplateError {
+    /// Name of the template (or partial) at fault
+    pub template: String,
+    pub kind: TemplateErrorKind,
+}
+
+/// A problem found by [`validate_template`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateIssue {
+    /// Byte offset of the offending tag
+    pub offset: usize,
+    pub kind: TemplateErrorKind,
+}
+
+impl std::fmt::Display for TemplateIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at byte {}: {}", self.offset, self.kind)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone)]
+struct Replace {
+    regex: Regex,
+    replacement: String,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Var {
+        offset: usize,
+        raw: String,
+        path: String,
+        segments: Vec<Segment>,
+        filters: Vec<Replace>,
+    },
+    Partial(String),
+}
+
+/// A parsed template with its sandbox
+#[derive(Debug, Clone)]
+pub struct Template {
+    name: String,
+    nodes: Vec<Node>,
+    limits: TemplateLimits,
+    allowed_roots: Option<Vec<String>>,
+    partials: HashMap<String, Arc<Template>>,
+    missing: MissingVariables,
+}
+
+impl Template {
+    /// Parse `text` with the default limits
+    pub fn new(name: impl Into<String>, text: &str) -> Result<Self, TemplateError> {
+        Self::with_limits(name, text, TemplateLimits::default())
+    }
+
+    /// Parse `text`, compiling its regexes within `limits`
+    pub fn with_limits(
+        name: impl Into<String>,
+        text: &str,
+        limits: TemplateLimits,
+    ) -> Result<Self, TemplateError> {
+        let name = name.into();
+        let (nodes, mut issues) = parse(text, &limits);
+        if !issues.is_empty() {
+            return Err(TemplateError {
+                template: name,
+                kind: issues.remove(0).kind,
+            });
+        }
+        Ok(Self {
+            name,
+            nodes,
+            limits,
+            allowed_roots: None,
+            partials: HashMap::new(),
+            missing: MissingVariables::default(),
+        })
+    }
+
+    /// Only let the template read these top-level variables. Without an
+    /// allowlist every variable passed to `render` is readable.
+    pub fn allow_roots<I, S>(mut self, roots: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allowed_roots = Some(roots.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Make `partial` available to `{{> name}}` tags. Partials render under
+    /// this template's allowlist and limits, not their own.
+    pub fn with_partial(mut self, partial: Template) -> Self {
+        self.partials
+            .insert(partial.name.clone(), Arc::new(partial));
+        self
+    }
+
+    /// What to do with variables `render` isn't given (builder-style)
+    pub fn on_missing(mut self, missing: MissingVariables) -> Self {
+        self.missing = missing;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The text, when the template has no tags: rendering it always
+    /// returns this
+    pub fn static_text(&self) -> Option<&str> {
+        match self.nodes.as_slice() {
+            [] => Some(""),
+            [Node::Text(text)] => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Render against `vars`, a JSON object of variable roots
+    pub fn render(&self, vars: &JsonValue) -> Result<String, TemplateError> {
+        let mut render = Render {
+            root: self,
+            vars,
+            out: String::new(),
+            lookups: 0,
+        };
+        render.nodes(self, 0)?;
+        Ok(render.out)
+    }
+}
+
+/// Check `text` without rendering it: syntax, regexes, and that every
+/// variable's root is in `allowed_vars`
+pub fn validate_template(text: &str, allowed_vars: &[&str]) -> Vec<TemplateIssue> {
+    let (nodes, mut issues) = parse(text, &TemplateLimits::default());
+    for node in &nodes {
+        if let Node::Var { offset, path, .. } = node {
+            if !allowed_vars.contains(&root_of(path)) {
+                issues.push(TemplateIssue {
+                    offset: *offset,
+                    kind: TemplateErrorKind::ForbiddenVariable(path.clone()),
+                });
+            }
+        }
+    }
+    issues.sort_by_key(|issue| issue.offset);
+    issues
+}
+
+fn root_of(path: &str) -> &str {
+    path.split('.').next().unwrap_or(path)
+}
+
+struct Render<'a> {
+    root: &'a Template,
+    vars: &'a JsonValue,
+    out: String,
+    lookups: usize,
+}
+
+impl Render<'_> {
+    fn nodes(&mut self, template: &Template, depth: usize) -> Result<(), TemplateError> {
+        let limits = &self.root.limits;
+        let fail = |kind| TemplateError {
+            template: template.name.clone(),
+            kind,
+        };
+        for node in &template.nodes {
+            match node {
+                Node::Text(text) => self.push(text).map_err(fail)?,
+                Node::Var {
+                    raw,
+                    path,
+                    segments,
+                    filters,
+                    ..
+                } => {
+                    let mut value = match self.lookup(path, segments) {
+                        Err(TemplateErrorKind::MissingVariable(_))
+                            if self.root.missing == MissingVariables::Literal =>
+                        {
+                            self.push(raw).map_err(fail)?;
+                            continue;
+                        }
+                        looked_up => looked_up.map_err(fail)?,
+                    };
+                    for filter in filters {
+                        if value.len() > limits.max_regex_input {
+                            return Err(fail(TemplateErrorKind::RegexInputTooLarge {
+                                len: value.len(),
+                                limit: limits.max_regex_input,
+                            }));
+                        }
+                        value = filter
+                            .regex
+                            .replace_all(&value, filter.replacement.as_str())
+                            .into_owned();
+                    }
+                    self.push(&value).map_err(fail)?;
+                }
+                Node::Partial(name) => {
+                    let partial = self
+                        .root
+                        .partials
+                        .get(name)
+                        .ok_or_else(|| fail(TemplateErrorKind::UnknownPartial(name.clone())))?;
+                    if depth >= limits.max_depth {
+                        return Err(fail(TemplateErrorKind::TooDeep {
+                            limit: limits.max_depth,
+                        }));
+                    }
+                    self.nodes(partial, depth + 1)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn lookup(&mut self, path: &str, segments: &[Segment]) -> Result<String, TemplateErrorKind> {
+        self.lookups += 1;
+        if self.lookups > self.root.limits.max_lookups {
+            return Err(TemplateErrorKind::TooManyLookups {
+                limit: self.root.limits.max_lookups,
+            });
+        }
+        if let Some(allowed) = &self.root.allowed_roots {
+            if !allowed.iter().any(|root| root == root_of(path)) {
+                return Err(TemplateErrorKind::ForbiddenVariable(path.to_string()));
+            }
+        }
+
+        let mut value = self.vars;
+        for segment in segments {
+            let next = match segment {
+                Segment::Key(key) => value.get(key),
+                Segment::Index(index) => value.get(*index),
+            };
+            value = next.ok_or_else(|| TemplateErrorKind::MissingVariable(path.to_string()))?;
+        }
+        Ok(match value {
+            JsonValue::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+    }
+
+    fn push(&mut self, text: &str) -> Result<(), TemplateErrorKind> {
+        let limit = self.root.limits.max_output_bytes;
+        if self.out.len() + text.len() > limit {
+            return Err(TemplateErrorKind::OutputTooLarge { limit });
+        }
+        self.out.push_str(text);
+        Ok(())
+    }
+}
+
+/// Parse into nodes, collecting every problem instead of stopping at the
+/// first regex error. A syntax error ends parsing.
+fn parse(text: &str, limits: &TemplateLimits) -> (Vec<Node>, Vec<TemplateIssue>) {
+    let mut nodes = Vec::new();
+    let mut issues = Vec::new();
+    let mut rest = 0;
+
+    let mut literal = String::new();
+    while let Some(found) = text[rest..].find("{{") {
+        let open = rest + found;
+        if open > rest && text.as_bytes()[open - 1] == b'\\' {
+            literal.push_str(&text[rest..open - 1]);
+            literal.push_str("{{");
+            rest = open + 2;
+            continue;
+        }
+        literal.push_str(&text[rest..open]);
+        if !literal.is_empty() {
+            nodes.push(Node::Text(std::mem::take(&mut literal)));
+        }
+        let body_start = open + 2;
+        let Some(len) = find_close(&text[body_start..]) else {
+            issues.push(syntax(open, "'{{' is never closed"));
+            return (nodes, issues);
+        };
+        let body = &text[body_start..body_start + len];
+        match parse_tag(body, body_start, limits, &mut issues) {
+            Ok(Some(node)) => nodes.push(node),
+            Ok(None) => {}
+            Err(issue) => {
+                issues.push(issue);
+                return (nodes, issues);
+            }
+        }
+        rest = body_start + len + 2;
+    }
+    literal.push_str(&text[rest..]);
+    if !literal.is_empty() {
+        nodes.push(Node::Text(literal));
+    }
+    (nodes, issues)
+}
+
+/// Length of a tag body, skipping `}}` inside quoted filter arguments
+fn find_close(body: &str) -> Option<usize> {
+    let bytes = body.as_bytes();
+    let mut in_string = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if in_string => i += 1,
+            b'"' => in_string = !in_string,
+            b'}' if !in_string && bytes.get(i + 1) == Some(&b'}') => return Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+fn syntax(offset: usize, message: impl Into<String>) -> TemplateIssue {
+    TemplateIssue {
+        offset,
+        kind: TemplateErrorKind::Syntax {
+            offset,
+            message: message.into(),
+        },
+    }
+}
+
+#[derive(Debug)]
+enum Token {
+    Word(String),
+    Str(String),
+    Pipe,
+}
+
+fn tokenize(body: &str, offset: usize) -> Result<Vec<Token>, TemplateIssue> {
+    let mut tokens = Vec::new();
+    let mut chars = body.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '|' => tokens.push(Token::Pipe),
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, '"')) => s.push('"'),
+                            Some((_, other)) => {
+                                // Keep other escapes for the regex, e.g. \s
+                                s.push('\\');
+                                s.push(other);
+                            }
+                            None => return Err(syntax(offset + i, "unterminated string")),
+                        },
+                        Some((_, other)) => s.push(other),
+                        None => return Err(syntax(offset + i, "unterminated string")),
+                    }
+                }
+                tokens.push(Token::Str(s));
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '.' || c == '>' => {
+                let mut word = c.to_string();
+                while let Some(&(_, next)) = chars.peek() {
+                    if next.is_alphanumeric() || next == '_' || next == '.' {
+                        word.push(next);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Word(word));
+            }
+            other => {
+                return Err(syntax(
+                    offset + i,
+                    format!("unexpected character '{}'", other),
+                ))
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_tag(
+    body: &str,
+    offset: usize,
+    limits: &TemplateLimits,
+    issues: &mut Vec<TemplateIssue>,
+) -> Result<Option<Node>, TemplateIssue> {
+    let tokens = tokenize(body, offset)?;
+    let mut tokens = tokens.into_iter();
+    let path = match tokens.next() {
+        Some(Token::Word(word)) => word,
+        _ => return Err(syntax(offset, "expected a variable name")),
+    };
+
+    if let Some(name) = path.strip_prefix('>') {
+        let name = match (name.is_empty(), tokens.next()) {
+            (false, None) => name.to_string(),
+            (true, Some(Token::Word(name))) if tokens.len() == 0 => name,
+            _ => return Err(syntax(offset, "expected '{{> name}}'")),
+        };
+        return Ok(Some(Node::Partial(name)));
+    }
+
+    let mut segments = Vec::new();
+    for (i, part) in path.split('.').enumerate() {
+        if part.is_empty() {
+            return Err(syntax(offset, format!("empty segment in '{}'", path)));
+        }
+        match part.parse::<usize>() {
+            Ok(index) if i > 0 => segments.push(Segment::Index(index)),
+            Ok(_) => return Err(syntax(offset, "a variable cannot start with a number")),
+            Err(_) => segments.push(Segment::Key(part.to_string())),
+        }
+    }
+
+    let mut filters = Vec::new();
+    while let Some(token) = tokens.next() {
+        let args = (token, tokens.next(), tokens.next(), tokens.next());
+        let (pattern, replacement) = match args {
+            (Token::Pipe, Some(Token::Word(name)), Some(Token::Str(p)), Some(Token::Str(r)))
+                if name == "replace" =>
+            {
+                (p, r)
+            }
+            (Token::Pipe, Some(Token::Word(name)), ..) if name != "replace" => {
+                return Err(syntax(offset, format!("unknown filter '{}'", name)))
+            }
+            _ => {
+                return Err(syntax(
+                    offset,
+                    "expected '| replace \"pattern\" \"replacement\"'",
+                ))
+            }
+        };
+        match RegexBuilder::new(&pattern)
+            .size_limit(limits.max_regex_size)
+            .dfa_size_limit(limits.max_regex_size)
+            .build()
+        {
+            Ok(regex) => filters.push(Replace { regex, replacement }),
+            Err(e) => issues.push(TemplateIssue {
+                offset: offset - 2,
+                kind: TemplateErrorKind::Regex {
+                    pattern,
+                    message: e.to_string(),
+                },
+            }),
+        }
+    }
+
+    Ok(Some(Node::Var {
+        offset: offset - 2,
+        raw: format!("{{{{{}}}}}", body),
+        path,
+        segments,
+        filters,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_renders_paths_filters_and_partials() {
+        let footer = Template::new("footer", "-- {{ input.team }}").unwrap();
+        let template = Template::new(
+            "greeting",
+            r#"Hi {{input.users.0.name}}, {{ input.note | replace "\s+" " " }} {{input.count}}
+{{> footer}}"#,
+        )
+        .unwrap()
+        .with_partial(footer);
+
+        let vars = json!({ "input": {
+            "users": [{ "name": "Ada" }],
+            "note": "see    you\n soon",
+            "count": 3,
+            "team": "ops"
+        }});
+        assert_eq!(
+            template.render(&vars).unwrap(),
+            "Hi Ada, see you soon 3\n-- ops"
+        );
+    }
+
+    #[test]
+    fn test_escapes_and_missing_variables() {
+        let template = Template::new("prompt", r"Hi {{name}}, type \{{name}}: {{age}}").unwrap();
+        let vars = json!({ "name": "Ada" });
+        assert_eq!(
+            template.render(&vars).unwrap_err().kind,
+            TemplateErrorKind::MissingVariable("age".into())
+        );
+        let template = template.on_missing(MissingVariables::Literal);
+        assert_eq!(
+            template.render(&vars).unwrap(),
+            "Hi Ada, type {{name}}: {{age}}"
+        );
+        assert_eq!(template.static_text(), None);
+
+        let plain = Template::new("plain", r"No tags, just \{{braces}}").unwrap();
+        assert_eq!(plain.static_text(), Some("No tags, just {{braces}}"));
+        assert_eq!(Template::new("empty", "").unwrap().static_text(), Some(""));
+    }
+
+    #[test]
+    fn test_limits_stop_rendering_cleanly() {
+        let vars = json!({ "big": "x".repeat(1000), "user": { "name": "Ada" } });
+        let tight = TemplateLimits {
+            max_output_bytes: 2_500,
+            ..TemplateLimits::default()
+        };
+
+        let expansion = Template::with_limits("blowup", &"{{big}}".repeat(10), tight).unwrap();
+        let err = expansion.render(&vars).unwrap_err();
+        assert_eq!(err.template, "blowup");
+        assert_eq!(err.kind, TemplateErrorKind::OutputTooLarge { limit: 2_500 });
+
+        let lookups = Template::new("chatty", &"{{user.name}}".repeat(1_001)).unwrap();
+        assert_eq!(
+            lookups.render(&vars).unwrap_err().kind,
+            TemplateErrorKind::TooManyLookups { limit: 1_000 }
+        );
+
+        // A partial that includes itself
+        let looping = Template::new("page", "{{> loop}}")
+            .unwrap()
+            .with_partial(Template::new("loop", "a{{> loop}}").unwrap());
+        let err = looping.render(&vars).unwrap_err();
+        assert_eq!(err.template, "loop");
+        assert_eq!(err.kind, TemplateErrorKind::TooDeep { limit: 8 });
+    }
+
+    #[test]
+    fn test_allowlist_is_enforced_at_render() {
+        let template = Template::new("tenant", "{{user.name}} {{secrets.api_key}}")
+            .unwrap()
+            .allow_roots(["user"]);
+        let vars = json!({ "user": { "name": "Ada" }, "secrets": { "api_key": "sk-1" } });
+        let err = template.render(&vars).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "template 'tenant': 'secrets.api_key' is outside the variables this template may use"
+        );
+
+        let err = Template::new("tenant", "{{user.email}}")
+            .unwrap()
+            .render(&vars)
+            .unwrap_err();
+        assert_eq!(
+            err.kind,
+            TemplateErrorKind::MissingVariable("user.email".into())
+        );
+    }
+
+    #[test]
+    fn test_validate_reports_every_issue() {
+        let issues = validate_template(
+            r#"{{user.name}} {{env.HOME}} {{ user.bio | replace "(" "" }}"#,
+            &["user"],
+        );
+        assert_eq!(issues.len(), 2, "{issues:?}");
+        assert_eq!(
+            issues[0].kind,
+            TemplateErrorKind::ForbiddenVariable("env.HOME".into())
+        );
+        assert_eq!(issues[0].offset, 14);
+        assert!(
+            matches!(&issues[1].kind, TemplateErrorKind::Regex { pattern, .. } if pattern == "(")
+        );
+
+        let issues = validate_template("Hello {{user.name", &["user"]);
+        assert_eq!(issues.len(), 1);
+        assert!(matches!(
+            issues[0].kind,
+            TemplateErrorKind::Syntax { offset: 6, .. }
+        ));
+        assert!(matches!(
+            validate_template("{{ user | upper }}", &["user"])[0].kind,
+            TemplateErrorKind::Syntax { ref message, .. } if message == "unknown filter 'upper'"
+        ));
+        assert!(validate_template("{{user.name}} plain {{> footer}}", &["user"]).is_empty());
+    }
+
+    #[test]
+    fn test_pathological_regex_is_bounded() {
+        // Compiles to far more than the size limit
+        let err = Template::new("huge", r#"{{ text | replace "(\w{100}){100}" "" }}"#).unwrap_err();
+        assert!(matches!(err.kind, TemplateErrorKind::Regex { .. }), "{err}");
+
+        // Classic backtracking bombs run in linear time, on bounded input
+        let template = Template::with_limits(
+            "bomb",
+            r#"{{ text | replace "(a+)+$" "" }}"#,
+            TemplateLimits {
+                max_regex_input: 10_000,
+                ..TemplateLimits::default()
+            },
+        )
+        .unwrap();
+        let started = std::time::Instant::now();
+        let out = template
+            .render(&json!({ "text": format!("{}!", "a".repeat(5_000)) }))
+            .unwrap();
+        assert_eq!(out.len(), 5_001);
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+
+        let err = template
+            .render(&json!({ "text": "a".repeat(20_000) }))
+            .unwrap_err();
+        assert_eq!(
+            err.kind,
+            TemplateErrorKind::RegexInputTooLarge {
+                len: 20_000,
+                limit: 10_000
+            }
+        );
+    }
+}
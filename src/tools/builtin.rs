@@ -1,7 +1,7 @@
 //! Built-in example tools (Echo, Calculator) useful for demos and tests.
 
 use crate::tools::registry::Tool;
-use crate::types::{ToolError, ToolExecutionResult, ToolResult};
+use crate::types::{ToolError, ToolErrorDetail, ToolExecutionResult, ToolResult};
 use async_trait::async_trait;
 use serde_json::Value as JsonValue;
 use std::collections::HashMap;
@@ -49,6 +49,10 @@ impl Tool for EchoTool {
             start.elapsed().as_secs_f64() * 1000.0,
         ))
     }
+
+    fn side_effecting(&self) -> bool {
+        false
+    }
 }
 
 /// Example: Calculator tool for simple math
@@ -103,7 +107,10 @@ impl Tool for CalculatorTool {
             "multiply" => a * b,
             "divide" => {
                 if b == 0.0 {
-                    return Err(ToolError::ExecutionFailed("division by zero".into()));
+                    return Ok(ToolResult::failed(
+                        ToolErrorDetail::new("division_by_zero", "division by zero"),
+                        start.elapsed().as_secs_f64() * 1000.0,
+                    ));
                 }
                 a / b
             }
@@ -120,4 +127,8 @@ impl Tool for CalculatorTool {
             start.elapsed().as_secs_f64() * 1000.0,
         ))
     }
+
+    fn side_effecting(&self) -> bool {
+        false
+    }
 }
@@ -0,0 +1,202 @@
+//! Memoized tool results, shared by every agent calling through a registry.
+//!
+//! [`ToolRegistry::with_cache`](crate::tools::ToolRegistry::with_cache)
+//! keeps each successful [`ToolResult`] under the tool's name and its
+//! arguments with object keys sorted, so a call repeated by another
+//! iteration, agent or step is answered without running the tool. A hit
+//! comes back with `cached` set and the lookup's duration. Error results
+//! and results carrying artifacts are never kept, and tools opt out with
+//! [`Tool::cacheable`](crate::tools::Tool::cacheable).
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde_json::Value as JsonValue;
+use tokio::time::Instant;
+
+use crate::types::{ToolResult, ToolStatus};
+
+/// How long results are kept, and how many
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolCacheConfig {
+    /// Age at which a result is treated as missing
+    pub ttl: Duration,
+
+    /// Results kept at most; the least recently used goes first
+    pub max_entries: usize,
+
+    /// TTLs of particular tools, by name, in place of `ttl`
+    pub per_tool_overrides: HashMap<String, Duration>,
+}
+
+impl Default for ToolCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(300),
+            max_entries: 1_000,
+            per_tool_overrides: HashMap::new(),
+        }
+    }
+}
+
+impl ToolCacheConfig {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Keep results of tool `name` for `ttl` instead
+    pub fn with_tool_ttl(mut self, name: impl Into<String>, ttl: Duration) -> Self {
+        self.per_tool_overrides.insert(name.into(), ttl);
+        self
+    }
+}
+
+struct Entry {
+    tool: String,
+    result: ToolResult,
+    stored: Instant,
+    /// Position in `State::recency`
+    used: u64,
+}
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<String, Entry>,
+    /// Keys from least to most recently used
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+/// Results kept by a registry, by [`call_key`]
+pub(crate) struct ToolCache {
+    config: ToolCacheConfig,
+    state: Mutex<State>,
+}
+
+impl ToolCache {
+    pub(crate) fn new(config: ToolCacheConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::default(),
+        }
+    }
+
+    fn ttl(&self, tool: &str) -> Duration {
+        self.config
+            .per_tool_overrides
+            .get(tool)
+            .copied()
+            .unwrap_or(self.config.ttl)
+    }
+
+    /// The result kept for `key`, marked as cached, unless it is missing
+    /// or expired
+    pub(crate) fn get(&self, key: &str) -> Option<ToolResult> {
+        let started = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        let entry = state.entries.get_mut(key)?;
+        if entry.stored.elapsed() >= self.ttl(&entry.tool) {
+            state.recency.remove(&entry.used);
+            state.entries.remove(key);
+            return None;
+        }
+        state.recency.remove(&entry.used);
+        state.tick += 1;
+        entry.used = state.tick;
+        state.recency.insert(entry.used, key.to_string());
+
+        let mut result = entry.result.clone();
+        result.cached = true;
+        result.duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+        Some(result)
+    }
+
+    /// Keep `result` of a call to `tool` under `key`, unless it failed or
+    /// carries artifacts
+    pub(crate) fn put(&self, tool: &str, key: &str, result: &ToolResult) {
+        if result.status == ToolStatus::Error
+            || !result.artifacts.is_empty()
+            || self.config.max_entries == 0
+            || self.ttl(tool).is_zero()
+        {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let used = state.tick;
+        let entry = Entry {
+            tool: tool.to_string(),
+            result: result.clone(),
+            stored: Instant::now(),
+            used,
+        };
+        if let Some(replaced) = state.entries.insert(key.to_string(), entry) {
+            state.recency.remove(&replaced.used);
+        }
+        state.recency.insert(used, key.to_string());
+        while state.entries.len() > self.config.max_entries {
+            let Some((_, oldest)) = state.recency.pop_first() else {
+                break;
+            };
+            state.entries.remove(&oldest);
+        }
+    }
+
+    /// Drop every result of `tool`, returning how many there were
+    pub(crate) fn invalidate_tool(&self, tool: &str) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        let before = state.entries.len();
+        state.entries.retain(|_, entry| entry.tool != tool);
+        let entries = &state.entries;
+        state.recency.retain(|_, key| entries.contains_key(key));
+        before - state.entries.len()
+    }
+}
+
+/// Tool name plus arguments with object keys sorted at every level
+pub(crate) fn call_key(tool: &str, arguments: &JsonValue) -> String {
+    fn canonical(value: &JsonValue, out: &mut String) {
+        match value {
+            JsonValue::Object(fields) => {
+                let mut names: Vec<&String> = fields.keys().collect();
+                names.sort();
+                out.push('{');
+                for (i, name) in names.into_iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    out.push_str(&JsonValue::from(name.as_str()).to_string());
+                    out.push(':');
+                    canonical(&fields[name], out);
+                }
+                out.push('}');
+            }
+            JsonValue::Array(items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    canonical(item, out);
+                }
+                out.push(']');
+            }
+            other => out.push_str(&other.to_string()),
+        }
+    }
+
+    let mut key = format!("{}:", tool);
+    canonical(arguments, &mut key);
+    key
+}
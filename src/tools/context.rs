@@ -0,0 +1,78 @@
+use crate::artifact::{Artifact, ArtifactRef, ArtifactStore, NewArtifact};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// Per-call context handed to tools by the agent
+///
+/// Gives tools access to run-scoped services such as the artifact store.
+/// Tools executed outside a workflow run (or via `Tool::execute`) get an
+/// empty context whose cancellation token never fires.
+#[derive(Debug, Clone, Default)]
+pub struct ToolRunContext {
+    /// Name of the agent invoking the tool
+    pub agent_name: Option<String>,
+
+    /// ID of the tool call being executed
+    pub tool_call_id: Option<String>,
+
+    /// Artifact store for the current run, if any
+    pub artifacts: Option<ArtifactStore>,
+
+    /// Fires when the run is canceled, the runtime shuts down, or the
+    /// call is abandoned (e.g. by a timeout). Long-running tools should
+    /// watch it, clean up, and return `ToolError::Canceled`.
+    pub cancellation: CancellationToken,
+
+    /// When the agent's deadline passes, if it has one. Retries of
+    /// transient failures are not scheduled past it.
+    pub deadline: Option<Instant>,
+}
+
+impl ToolRunContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_artifacts(mut self, store: ArtifactStore) -> Self {
+        self.artifacts = Some(store);
+        self
+    }
+
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Time left before the deadline; `None` without one
+    pub fn time_left(&self) -> Option<std::time::Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Whether the tool should stop what it is doing
+    pub fn is_canceled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Resolve an artifact by handle (`artifact://N`) or ID
+    pub fn resolve_artifact(&self, handle: &str) -> Option<Artifact> {
+        self.artifacts.as_ref()?.get(handle)
+    }
+
+    /// Store an artifact directly (instead of returning it on the `ToolResult`)
+    pub fn store_artifact(&self, artifact: NewArtifact) -> Option<ArtifactRef> {
+        self.artifacts.as_ref().map(|store| store.put(artifact))
+    }
+
+    /// Keep an artifact after the run completes
+    pub fn export_artifact(&self, handle: &str) -> bool {
+        self.artifacts
+            .as_ref()
+            .is_some_and(|store| store.export(handle))
+    }
+}
@@ -0,0 +1,617 @@
+//! Built-in filesystem tools confined to a sandbox directory.
+//!
+//! [`FsTools`] provides `read_file`, `write_file`, `list_dir` and
+//! `search_files` as [`NativeTool`]s. Every path they are given is resolved
+//! against the sandbox root by [`Sandbox`], symlinks included, and refused
+//! with `ToolError::InvalidParameters` if it lands outside or isn't a valid
+//! path on every platform. Paths in results are
+//! relative to the root, with `/` separators.
+
+use crate::paths::{PathError, Sandbox};
+use crate::tools::native::NativeTool;
+use crate::tools::registry::ToolRegistry;
+use crate::tools::truncation::DEFAULT_MAX_TOOL_RESULT_BYTES;
+use crate::types::{ToolError, ToolExecutionResult, ToolResult};
+use serde_json::Value as JsonValue;
+use std::collections::{HashMap, VecDeque};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+/// Default for [`FsTools::with_max_file_bytes`]: 1 MiB
+pub const DEFAULT_MAX_FILE_BYTES: usize = 1024 * 1024;
+
+/// Default for [`FsTools::with_max_results`]
+pub const DEFAULT_MAX_RESULTS: usize = 200;
+
+/// Longest line of a search match kept in the result
+const MAX_MATCH_LINE_CHARS: usize = 500;
+
+/// The filesystem tools, all confined to one root directory
+///
+/// ```rust,ignore
+/// let mut registry = ToolRegistry::new();
+/// FsTools::new("./workspace").register(&mut registry);
+/// ```
+///
+/// `read_file`, `list_dir` and `search_files` are read-only, so they may be
+/// run speculatively; `write_file` is not.
+#[derive(Debug, Clone)]
+pub struct FsTools {
+    root: PathBuf,
+    max_file_bytes: usize,
+    max_results: usize,
+}
+
+/// A path resolved inside the sandbox; it may not exist yet
+struct Resolved {
+    root: PathBuf,
+    path: PathBuf,
+    exists: bool,
+}
+
+impl FsTools {
+    /// Tools working inside `root`, which must exist when they are called
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            max_file_bytes: DEFAULT_MAX_FILE_BYTES,
+            max_results: DEFAULT_MAX_RESULTS,
+        }
+    }
+
+    /// Refuse writes that would leave a file larger than `bytes`; searches
+    /// skip files larger than this
+    pub fn with_max_file_bytes(mut self, bytes: usize) -> Self {
+        self.max_file_bytes = bytes;
+        self
+    }
+
+    /// Most entries `list_dir` and `search_files` return
+    pub fn with_max_results(mut self, max: usize) -> Self {
+        self.max_results = max;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The four tools, sharing this configuration
+    pub fn tools(&self) -> Vec<NativeTool> {
+        let fs = Arc::new(self.clone());
+        vec![
+            NativeTool::new(
+                "read_file",
+                "Reads a text file. Use offset and limit (in bytes) to page through large files.",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "path": { "type": "string", "description": "File path relative to the workspace" },
+                        "offset": { "type": "integer", "minimum": 0, "description": "Byte to start at" },
+                        "limit": { "type": "integer", "minimum": 1, "description": "Most bytes to return" }
+                    },
+                    "required": ["path"]
+                }),
+                {
+                    let fs = fs.clone();
+                    move |params| {
+                        let fs = fs.clone();
+                        async move { fs.read_file(params).await }
+                    }
+                },
+            )
+            .read_only(),
+            NativeTool::new(
+                "write_file",
+                "Writes a text file, creating parent directories as needed",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "path": { "type": "string", "description": "File path relative to the workspace" },
+                        "content": { "type": "string" },
+                        "mode": {
+                            "type": "string",
+                            "enum": ["create", "overwrite", "append"],
+                            "description": "create fails if the file exists; defaults to overwrite"
+                        }
+                    },
+                    "required": ["path", "content"]
+                }),
+                {
+                    let fs = fs.clone();
+                    move |params| {
+                        let fs = fs.clone();
+                        async move { fs.write_file(params).await }
+                    }
+                },
+            ),
+            NativeTool::new(
+                "list_dir",
+                "Lists a directory's entries with their type, size and modification time",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "path": { "type": "string", "description": "Directory relative to the workspace; defaults to the workspace itself" }
+                    }
+                }),
+                {
+                    let fs = fs.clone();
+                    move |params| {
+                        let fs = fs.clone();
+                        async move { fs.list_dir(params).await }
+                    }
+                },
+            )
+            .read_only(),
+            NativeTool::new(
+                "search_files",
+                "Finds files matching a glob (e.g. **/*.rs), optionally only lines containing a substring",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "pattern": { "type": "string", "description": "Glob; without a '/', matched against file names at any depth" },
+                        "query": { "type": "string", "description": "Return the lines containing this text" },
+                        "path": { "type": "string", "description": "Directory to search; defaults to the workspace" },
+                        "max_results": { "type": "integer", "minimum": 1 }
+                    },
+                    "required": ["pattern"]
+                }),
+                move |params| {
+                    let fs = fs.clone();
+                    async move { fs.search_files(params).await }
+                },
+            )
+            .read_only(),
+        ]
+    }
+
+    /// Register the four tools in `registry`
+    pub fn register(&self, registry: &mut ToolRegistry) {
+        for tool in self.tools() {
+            registry.register(tool);
+        }
+    }
+
+    async fn read_file(&self, params: HashMap<String, JsonValue>) -> ToolExecutionResult {
+        let start = Instant::now();
+        let path = required_str(&params, "path")?;
+        let offset = params
+            .get("offset")
+            .and_then(JsonValue::as_u64)
+            .unwrap_or(0);
+        let limit = params
+            .get("limit")
+            .and_then(JsonValue::as_u64)
+            .map_or(DEFAULT_MAX_TOOL_RESULT_BYTES, |limit| limit as usize)
+            .min(self.max_file_bytes);
+
+        let resolved = self.resolve(path).await?;
+        let file = resolved.existing(path)?;
+        let metadata = tokio::fs::metadata(file)
+            .await
+            .map_err(|e| io_error(path, e))?;
+        if metadata.is_dir() {
+            return Err(ToolError::InvalidParameters(format!(
+                "'{}' is a directory; use list_dir",
+                path
+            )));
+        }
+
+        let mut handle = tokio::fs::File::open(file)
+            .await
+            .map_err(|e| io_error(path, e))?;
+        handle
+            .seek(SeekFrom::Start(offset))
+            .await
+            .map_err(|e| io_error(path, e))?;
+        let mut content = Vec::with_capacity(limit.min(metadata.len() as usize));
+        handle
+            .take(limit as u64)
+            .read_to_end(&mut content)
+            .await
+            .map_err(|e| io_error(path, e))?;
+
+        let size = metadata.len();
+        Ok(ToolResult::success(
+            serde_json::json!({
+                "path": resolved.relative(file),
+                "content": String::from_utf8_lossy(&content),
+                "offset": offset,
+                "bytes": content.len(),
+                "size": size,
+                "has_more": offset + (content.len() as u64) < size,
+            }),
+            start.elapsed().as_secs_f64() * 1000.0,
+        ))
+    }
+
+    async fn write_file(&self, params: HashMap<String, JsonValue>) -> ToolExecutionResult {
+        let start = Instant::now();
+        let path = required_str(&params, "path")?;
+        let content = required_str(&params, "content")?;
+        let mode = params
+            .get("mode")
+            .and_then(JsonValue::as_str)
+            .unwrap_or("overwrite");
+
+        let resolved = self.resolve(path).await?;
+        let file = &resolved.path;
+        let existing_size = match resolved.exists {
+            true => {
+                let metadata = tokio::fs::metadata(file)
+                    .await
+                    .map_err(|e| io_error(path, e))?;
+                if metadata.is_dir() {
+                    return Err(ToolError::InvalidParameters(format!(
+                        "'{}' is a directory",
+                        path
+                    )));
+                }
+                metadata.len() as usize
+            }
+            false => 0,
+        };
+
+        let size = match mode {
+            "create" if resolved.exists => {
+                return Err(ToolError::InvalidParameters(format!(
+                    "'{}' already exists; use mode overwrite or append",
+                    path
+                )))
+            }
+            "create" | "overwrite" => content.len(),
+            "append" => existing_size + content.len(),
+            other => {
+                return Err(ToolError::InvalidParameters(format!(
+                    "unknown mode '{}'",
+                    other
+                )))
+            }
+        };
+        if size > self.max_file_bytes {
+            return Err(ToolError::InvalidParameters(format!(
+                "'{}' would be {} bytes, over the {} byte limit",
+                path, size, self.max_file_bytes
+            )));
+        }
+
+        if let Some(parent) = file.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| io_error(path, e))?;
+        }
+        let mut options = tokio::fs::OpenOptions::new();
+        match mode {
+            "append" => options.append(true).create(true),
+            "create" => options.write(true).create_new(true),
+            _ => options.write(true).create(true).truncate(true),
+        };
+        let mut handle = options.open(file).await.map_err(|e| io_error(path, e))?;
+        handle
+            .write_all(content.as_bytes())
+            .await
+            .map_err(|e| io_error(path, e))?;
+        handle.flush().await.map_err(|e| io_error(path, e))?;
+
+        Ok(ToolResult::success(
+            serde_json::json!({
+                "path": resolved.relative(file),
+                "mode": mode,
+                "bytes_written": content.len(),
+                "size": size,
+                "created": !resolved.exists,
+            }),
+            start.elapsed().as_secs_f64() * 1000.0,
+        ))
+    }
+
+    async fn list_dir(&self, params: HashMap<String, JsonValue>) -> ToolExecutionResult {
+        let start = Instant::now();
+        let path = params
+            .get("path")
+            .and_then(JsonValue::as_str)
+            .unwrap_or(".");
+        let resolved = self.resolve(path).await?;
+        let dir = resolved.existing(path)?;
+
+        let mut entries = Vec::new();
+        let mut reader = tokio::fs::read_dir(dir)
+            .await
+            .map_err(|e| io_error(path, e))?;
+        while let Some(entry) = reader.next_entry().await.map_err(|e| io_error(path, e))? {
+            // Symlinks are described, not followed
+            let metadata = match tokio::fs::symlink_metadata(entry.path()).await {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            let kind = if metadata.is_symlink() {
+                "symlink"
+            } else if metadata.is_dir() {
+                "dir"
+            } else if metadata.is_file() {
+                "file"
+            } else {
+                "other"
+            };
+            let modified = metadata
+                .modified()
+                .ok()
+                .map(|time| chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339());
+            entries.push(serde_json::json!({
+                "name": entry.file_name().to_string_lossy(),
+                "type": kind,
+                "size": metadata.len(),
+                "modified": modified,
+            }));
+        }
+        entries.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+        let total = entries.len();
+        entries.truncate(self.max_results);
+
+        Ok(ToolResult::success(
+            serde_json::json!({
+                "path": resolved.relative(dir),
+                "entries": entries,
+                "total": total,
+                "truncated": total > self.max_results,
+            }),
+            start.elapsed().as_secs_f64() * 1000.0,
+        ))
+    }
+
+    async fn search_files(&self, params: HashMap<String, JsonValue>) -> ToolExecutionResult {
+        let start = Instant::now();
+        let pattern = required_str(&params, "pattern")?;
+        let query = params.get("query").and_then(JsonValue::as_str);
+        let path = params
+            .get("path")
+            .and_then(JsonValue::as_str)
+            .unwrap_or(".");
+        let max_results = params
+            .get("max_results")
+            .and_then(JsonValue::as_u64)
+            .map_or(self.max_results, |max| max as usize)
+            .min(self.max_results);
+
+        let resolved = self.resolve(path).await?;
+        let base = resolved.existing(path)?.clone();
+
+        let mut matches = Vec::new();
+        let mut files_scanned = 0;
+        let mut truncated = false;
+        let mut pending = VecDeque::from([base.clone()]);
+        'walk: while let Some(dir) = pending.pop_front() {
+            let mut reader = match tokio::fs::read_dir(&dir).await {
+                Ok(reader) => reader,
+                Err(_) => continue,
+            };
+            let mut children = Vec::new();
+            while let Ok(Some(entry)) = reader.next_entry().await {
+                children.push(entry);
+            }
+            children.sort_by_key(|entry| entry.file_name());
+
+            for entry in children {
+                // Symlinks are never followed, so the walk stays in the sandbox
+                let kind = match entry.file_type().await {
+                    Ok(kind) if !kind.is_symlink() => kind,
+                    _ => continue,
+                };
+                let child = entry.path();
+                if kind.is_dir() {
+                    pending.push_back(child);
+                    continue;
+                }
+                let within = relative_path(&base, &child);
+                if !kind.is_file() || !glob_match(pattern, &within) {
+                    continue;
+                }
+
+                let size = entry.metadata().await.map(|m| m.len()).unwrap_or(0);
+                let Some(query) = query else {
+                    if matches.len() == max_results {
+                        truncated = true;
+                        break 'walk;
+                    }
+                    matches.push(serde_json::json!({
+                        "path": resolved.relative(&child),
+                        "size": size,
+                    }));
+                    continue;
+                };
+
+                if size as usize > self.max_file_bytes {
+                    continue;
+                }
+                let Ok(text) = tokio::fs::read_to_string(&child).await else {
+                    continue;
+                };
+                files_scanned += 1;
+                for (index, line) in text.lines().enumerate() {
+                    if !line.contains(query) {
+                        continue;
+                    }
+                    if matches.len() == max_results {
+                        truncated = true;
+                        break 'walk;
+                    }
+                    matches.push(serde_json::json!({
+                        "path": resolved.relative(&child),
+                        "line": index + 1,
+                        "text": line.chars().take(MAX_MATCH_LINE_CHARS).collect::<String>(),
+                    }));
+                }
+            }
+        }
+
+        let mut output = serde_json::json!({
+            "matches": matches,
+            "truncated": truncated,
+        });
+        if query.is_some() {
+            output["files_scanned"] = files_scanned.into();
+        }
+        Ok(ToolResult::success(
+            output,
+            start.elapsed().as_secs_f64() * 1000.0,
+        ))
+    }
+
+    /// `path` inside the sandbox, as [`Sandbox::resolve`] finds it; `.`
+    /// and the empty path name the root itself
+    async fn resolve(&self, path: &str) -> Result<Resolved, ToolError> {
+        let root = self.root.clone();
+        let given = path.to_string();
+        let resolved = tokio::task::spawn_blocking(move || {
+            let sandbox = Sandbox::new(&root)
+                .map_err(|e| ToolError::ExecutionFailed(format!("sandbox root: {}", e)))?;
+            let path = match sandbox.resolve(&given) {
+                Ok(path) => path,
+                Err(PathError::Empty) => sandbox.root().to_path_buf(),
+                Err(PathError::Absolute { .. } | PathError::Escapes { .. }) => {
+                    return Err(outside(&given))
+                }
+                Err(e @ PathError::Io { .. }) => {
+                    return Err(ToolError::ExecutionFailed(e.to_string()))
+                }
+                Err(e) => return Err(ToolError::InvalidParameters(e.to_string())),
+            };
+            let exists = path.try_exists().map_err(|e| io_error(&given, e))?;
+            Ok(Resolved {
+                root: sandbox.root().to_path_buf(),
+                path,
+                exists,
+            })
+        })
+        .await
+        .map_err(|e| ToolError::ExecutionFailed(e.to_string()))??;
+        Ok(resolved)
+    }
+}
+
+impl Resolved {
+    /// The path, if it exists
+    fn existing(&self, given: &str) -> Result<&PathBuf, ToolError> {
+        match self.exists {
+            true => Ok(&self.path),
+            false => Err(ToolError::ExecutionFailed(format!(
+                "'{}' does not exist",
+                given
+            ))),
+        }
+    }
+
+    fn relative(&self, path: &Path) -> String {
+        relative_path(&self.root, path)
+    }
+}
+
+fn relative_path(base: &Path, path: &Path) -> String {
+    let relative = path
+        .strip_prefix(base)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    match relative.is_empty() {
+        true => ".".to_string(),
+        false => relative,
+    }
+}
+
+fn required_str<'a>(
+    params: &'a HashMap<String, JsonValue>,
+    name: &str,
+) -> Result<&'a str, ToolError> {
+    params
+        .get(name)
+        .and_then(JsonValue::as_str)
+        .ok_or_else(|| ToolError::InvalidParameters(format!("missing '{}' parameter", name)))
+}
+
+fn outside(path: &str) -> ToolError {
+    ToolError::InvalidParameters(format!("'{}' is outside the sandbox", path))
+}
+
+fn io_error(path: &str, error: std::io::Error) -> ToolError {
+    ToolError::ExecutionFailed(format!("{}: {}", path, error))
+}
+
+/// Whether `path` (relative, `/`-separated) matches `pattern`
+///
+/// `*` and `?` match within one segment, `**` any number of segments. A
+/// pattern without `/` is matched against the file name alone.
+fn glob_match(pattern: &str, path: &str) -> bool {
+    let segments: Vec<&str> = path.split('/').collect();
+    if !pattern.contains('/') {
+        return segment_match(pattern, segments.last().copied().unwrap_or(""));
+    }
+    let pattern: Vec<&str> = pattern.split('/').filter(|s| !s.is_empty()).collect();
+    segments_match(&pattern, &segments)
+}
+
+fn segments_match(pattern: &[&str], segments: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => segments.is_empty(),
+        Some((&"**", rest)) => {
+            (0..=segments.len()).any(|skip| segments_match(rest, &segments[skip..]))
+        }
+        Some((first, rest)) => match segments.split_first() {
+            Some((segment, others)) => {
+                segment_match(first, segment) && segments_match(rest, others)
+            }
+            None => false,
+        },
+    }
+}
+
+fn segment_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where the last `*` was, and how much text it has taken so far
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.rs", "src/tools/fs.rs"));
+        assert!(glob_match("**/*.rs", "src/tools/fs.rs"));
+        assert!(glob_match("**/*.rs", "main.rs"));
+        assert!(glob_match("src/*/fs.rs", "src/tools/fs.rs"));
+        assert!(!glob_match("src/*.rs", "src/tools/fs.rs"));
+        assert!(glob_match("docs/**", "docs/a/b.md"));
+        assert!(glob_match("f?.r*", "fs.rs"));
+        assert!(!glob_match("*.md", "README.txt"));
+        assert!(glob_match("*", "anything"));
+    }
+}
@@ -0,0 +1,703 @@
+//! Built-in `http_request` tool with host and private-network controls.
+//!
+//! [`HttpTool`] lets an agent call HTTP APIs without every project writing
+//! its own fetch tool. What it may reach is fixed when it is built:
+//!
+//! - Hosts must match the allowlist (when there is one) and must not match
+//!   the denylist. Patterns are exact names, `*.example.com` for any
+//!   subdomain, or `*` for every host.
+//! - Hosts are resolved before connecting. Loopback, private, link-local and
+//!   other non-public addresses are refused unless
+//!   [`allow_private_networks`](HttpToolBuilder::allow_private_networks) is
+//!   set, and the connection goes to the checked address, so a second lookup
+//!   can't swap it. Redirects are followed by hand and every hop is checked
+//!   again.
+//! - Response bodies are read up to `max_response_bytes` and cut there.
+//! - Every call has a timeout; callers may shorten it, up to `max_timeout`.
+//!
+//! Sensitive request headers (`Authorization`, cookies, API keys) are shown
+//! as `[REDACTED]` in `Tool` `Started` events.
+
+use crate::event::RedactionRules;
+use crate::tools::context::ToolRunContext;
+use crate::tools::registry::Tool;
+use crate::tools::truncation::DEFAULT_MAX_TOOL_RESULT_BYTES;
+use crate::types::{ToolError, ToolExecutionResult, ToolResult};
+use async_trait::async_trait;
+use base64::Engine;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, LOCATION};
+use reqwest::{Method, StatusCode, Url};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+/// Timeout of a call that doesn't set `timeout_ms`
+pub const DEFAULT_HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest `timeout_ms` a call may ask for, unless the builder changes it
+pub const MAX_HTTP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Redirects followed before the call fails
+const MAX_REDIRECTS: usize = 5;
+
+const METHODS: &[&str] = &["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"];
+
+/// Response headers left out of the result
+const HIDDEN_RESPONSE_HEADERS: &[&str] = &[
+    "set-cookie",
+    "set-cookie2",
+    "authorization",
+    "proxy-authorization",
+];
+
+/// Request headers not carried over a redirect to another host
+const CREDENTIAL_HEADERS: &[&str] = &["authorization", "proxy-authorization", "cookie"];
+
+/// The `http_request` tool
+///
+/// ```rust,ignore
+/// let mut registry = ToolRegistry::new();
+/// registry.register(HttpTool::builder().allow_host("api.github.com").build());
+/// ```
+///
+/// A completed exchange succeeds whatever its status code; the result is
+/// `{status, url, headers, content_type, body, encoding, bytes, truncated}`,
+/// where `encoding` is `"text"` for textual bodies and `"base64"` otherwise.
+/// Refused hosts and addresses fail with `ToolError::NotPermitted`, network
+/// errors are transient, and running out of time is `ToolError::TimedOut`.
+pub struct HttpTool {
+    allow: Vec<HostPattern>,
+    deny: Vec<HostPattern>,
+    allow_private_networks: bool,
+    max_response_bytes: usize,
+    default_timeout: Duration,
+    max_timeout: Duration,
+    redaction: RedactionRules,
+}
+
+impl HttpTool {
+    pub fn builder() -> HttpToolBuilder {
+        HttpToolBuilder::default()
+    }
+
+    /// Whether `host` passes the allowlist and denylist
+    pub fn host_allowed(&self, host: &str) -> bool {
+        let host = normalize_host(host);
+        !self.deny.iter().any(|p| p.matches(&host))
+            && (self.allow.is_empty() || self.allow.iter().any(|p| p.matches(&host)))
+    }
+
+    async fn call(&self, params: HashMap<String, JsonValue>) -> ToolExecutionResult {
+        let start = std::time::Instant::now();
+        let request = self.parse(params)?;
+        let timeout = request.timeout;
+        let output = tokio::time::timeout(timeout, self.send(request))
+            .await
+            .map_err(|_| ToolError::TimedOut(timeout.as_millis() as u64))??;
+        Ok(ToolResult::success(
+            output,
+            start.elapsed().as_secs_f64() * 1000.0,
+        ))
+    }
+
+    fn parse(&self, params: HashMap<String, JsonValue>) -> Result<HttpRequest, ToolError> {
+        let method = match params.get("method").and_then(JsonValue::as_str) {
+            Some(method) if METHODS.contains(&method) => Method::from_bytes(method.as_bytes())
+                .map_err(|e| ToolError::InvalidParameters(e.to_string()))?,
+            Some(method) => {
+                return Err(ToolError::InvalidParameters(format!(
+                    "unsupported method '{}'",
+                    method
+                )))
+            }
+            None => Method::GET,
+        };
+
+        let url = params
+            .get("url")
+            .and_then(JsonValue::as_str)
+            .ok_or_else(|| ToolError::InvalidParameters("missing 'url' parameter".into()))?;
+        let url = Url::parse(url)
+            .map_err(|e| ToolError::InvalidParameters(format!("invalid url '{}': {}", url, e)))?;
+
+        let mut headers = HeaderMap::new();
+        if let Some(given) = params.get("headers").and_then(JsonValue::as_object) {
+            for (name, value) in given {
+                let value = value.as_str().ok_or_else(|| {
+                    ToolError::InvalidParameters(format!("header '{}' must be a string", name))
+                })?;
+                let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| {
+                    ToolError::InvalidParameters(format!("invalid header name '{}'", name))
+                })?;
+                let value = HeaderValue::from_str(value).map_err(|_| {
+                    ToolError::InvalidParameters(format!("invalid value for header '{}'", name))
+                })?;
+                headers.insert(name, value);
+            }
+        }
+
+        let body = params
+            .get("body")
+            .and_then(JsonValue::as_str)
+            .map(str::to_string);
+
+        let timeout = params
+            .get("timeout_ms")
+            .and_then(JsonValue::as_u64)
+            .map(Duration::from_millis)
+            .unwrap_or(self.default_timeout)
+            .min(self.max_timeout);
+
+        Ok(HttpRequest {
+            method,
+            url,
+            headers,
+            body,
+            timeout,
+        })
+    }
+
+    async fn send(&self, request: HttpRequest) -> Result<JsonValue, ToolError> {
+        let HttpRequest {
+            mut method,
+            mut url,
+            mut headers,
+            mut body,
+            ..
+        } = request;
+
+        for _ in 0..=MAX_REDIRECTS {
+            let client = self.client_for(&url).await?;
+            let mut builder = client
+                .request(method.clone(), url.clone())
+                .headers(headers.clone());
+            if let Some(body) = &body {
+                builder = builder.body(body.clone());
+            }
+            let response = builder
+                .send()
+                .await
+                .map_err(|e| ToolError::Transient(format!("{} {}: {}", method, url, e)))?;
+
+            let status = response.status();
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|v| v.to_str().ok());
+            let next = match (status.is_redirection(), location) {
+                (true, Some(location)) => url.join(location).map_err(|e| {
+                    ToolError::ExecutionFailed(format!("bad redirect to '{}': {}", location, e))
+                })?,
+                _ => return self.read(url, response).await,
+            };
+
+            if status == StatusCode::SEE_OTHER
+                || (method == Method::POST
+                    && matches!(status, StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND))
+            {
+                method = Method::GET;
+                body = None;
+            }
+            if next.host_str() != url.host_str() {
+                for name in CREDENTIAL_HEADERS {
+                    headers.remove(*name);
+                }
+            }
+            url = next;
+        }
+
+        Err(ToolError::ExecutionFailed(format!(
+            "stopped after {} redirects at {}",
+            MAX_REDIRECTS, url
+        )))
+    }
+
+    /// A client that may only reach `url`'s host, at the addresses checked
+    /// here
+    async fn client_for(&self, url: &Url) -> Result<reqwest::Client, ToolError> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(ToolError::InvalidParameters(format!(
+                "unsupported scheme '{}'",
+                url.scheme()
+            )));
+        }
+        let host = url
+            .host_str()
+            .ok_or_else(|| ToolError::InvalidParameters(format!("{} has no host", url)))?;
+        if !self.host_allowed(host) {
+            return Err(ToolError::NotPermitted(format!(
+                "host '{}' is not allowed",
+                host
+            )));
+        }
+
+        let mut builder = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .no_proxy();
+        if !self.allow_private_networks {
+            let addresses = match ip_literal(host) {
+                Some(ip) => vec![SocketAddr::new(ip, 0)],
+                None => {
+                    let port = url.port_or_known_default().unwrap_or(80);
+                    let resolved: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+                        .await
+                        .map_err(|e| {
+                            ToolError::Transient(format!("could not resolve '{}': {}", host, e))
+                        })?
+                        .collect();
+                    if resolved.is_empty() {
+                        return Err(ToolError::Transient(format!(
+                            "'{}' resolved to no addresses",
+                            host
+                        )));
+                    }
+                    builder = builder.resolve_to_addrs(host, &resolved);
+                    resolved
+                }
+            };
+            if let Some(blocked) = addresses.iter().find(|a| is_private_address(a.ip())) {
+                return Err(ToolError::NotPermitted(format!(
+                    "'{}' resolves to the private address {}",
+                    host,
+                    blocked.ip()
+                )));
+            }
+        }
+
+        builder
+            .build()
+            .map_err(|e| ToolError::ExecutionFailed(format!("HTTP client: {}", e)))
+    }
+
+    async fn read(
+        &self,
+        url: Url,
+        mut response: reqwest::Response,
+    ) -> Result<JsonValue, ToolError> {
+        let status = response.status();
+        let declared = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| {
+                v.split(';')
+                    .next()
+                    .unwrap_or("")
+                    .trim()
+                    .to_ascii_lowercase()
+            })
+            .filter(|v| !v.is_empty());
+
+        let mut headers = serde_json::Map::new();
+        for (name, value) in response.headers() {
+            if HIDDEN_RESPONSE_HEADERS.contains(&name.as_str()) {
+                continue;
+            }
+            let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+            match headers.get_mut(name.as_str()) {
+                Some(JsonValue::String(existing)) => {
+                    existing.push_str(", ");
+                    existing.push_str(&value);
+                }
+                _ => {
+                    headers.insert(name.to_string(), JsonValue::String(value));
+                }
+            }
+        }
+
+        let mut bytes = Vec::new();
+        let mut truncated = false;
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| ToolError::Transient(format!("reading {}: {}", url, e)))?
+        {
+            let room = self.max_response_bytes - bytes.len();
+            if chunk.len() > room {
+                bytes.extend_from_slice(&chunk[..room]);
+                truncated = true;
+                break;
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        let content_type = match declared {
+            Some(declared) if declared != "application/octet-stream" => declared,
+            declared => sniff(&bytes)
+                .map(str::to_string)
+                .or(declared)
+                .unwrap_or_else(|| match std::str::from_utf8(&bytes) {
+                    Ok(_) => "text/plain".to_string(),
+                    Err(_) => "application/octet-stream".to_string(),
+                }),
+        };
+        let (body, encoding) = match textual(&content_type).then(|| decode_text(&bytes, truncated))
+        {
+            Some(Some(text)) => (text, "text"),
+            _ => (
+                base64::engine::general_purpose::STANDARD.encode(&bytes),
+                "base64",
+            ),
+        };
+
+        Ok(serde_json::json!({
+            "status": status.as_u16(),
+            "url": url.as_str(),
+            "headers": headers,
+            "content_type": content_type,
+            "body": body,
+            "encoding": encoding,
+            "bytes": bytes.len(),
+            "truncated": truncated,
+        }))
+    }
+}
+
+#[async_trait]
+impl Tool for HttpTool {
+    fn name(&self) -> &str {
+        "http_request"
+    }
+
+    fn description(&self) -> &str {
+        "Sends an HTTP request and returns the response status, headers and body"
+    }
+
+    fn input_schema(&self) -> JsonValue {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "method": {
+                    "type": "string",
+                    "enum": METHODS,
+                    "description": "HTTP method; defaults to GET"
+                },
+                "url": {
+                    "type": "string",
+                    "description": "Absolute http:// or https:// URL"
+                },
+                "headers": {
+                    "type": "object",
+                    "additionalProperties": { "type": "string" },
+                    "description": "Request headers"
+                },
+                "body": {
+                    "type": "string",
+                    "description": "Request body, sent as-is; set Content-Type to match"
+                },
+                "timeout_ms": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "description": "Give up after this many milliseconds"
+                }
+            },
+            "required": ["url"]
+        })
+    }
+
+    async fn execute(&self, params: HashMap<String, JsonValue>) -> ToolExecutionResult {
+        self.call(params).await
+    }
+
+    async fn execute_with_context(
+        &self,
+        params: HashMap<String, JsonValue>,
+        ctx: &ToolRunContext,
+    ) -> ToolExecutionResult {
+        tokio::select! {
+            result = self.call(params) => result,
+            _ = ctx.cancellation.cancelled() => Err(ToolError::Canceled(
+                "HTTP request abandoned".to_string()
+            )),
+        }
+    }
+
+    fn redact_arguments(&self, arguments: &JsonValue) -> Option<JsonValue> {
+        let mut redacted = arguments.clone();
+        let headers = redacted.get_mut("headers")?;
+        (self.redaction.redact_value(headers) > 0).then_some(redacted)
+    }
+}
+
+/// Builder for [`HttpTool`]
+///
+/// With no `allow_host`, every public host is allowed.
+#[derive(Debug, Clone)]
+pub struct HttpToolBuilder {
+    allow: Vec<HostPattern>,
+    deny: Vec<HostPattern>,
+    allow_private_networks: bool,
+    max_response_bytes: usize,
+    default_timeout: Duration,
+    max_timeout: Duration,
+}
+
+impl Default for HttpToolBuilder {
+    fn default() -> Self {
+        Self {
+            allow: Vec::new(),
+            deny: Vec::new(),
+            allow_private_networks: false,
+            max_response_bytes: DEFAULT_MAX_TOOL_RESULT_BYTES,
+            default_timeout: DEFAULT_HTTP_TIMEOUT,
+            max_timeout: MAX_HTTP_TIMEOUT,
+        }
+    }
+}
+
+impl HttpToolBuilder {
+    /// Allow hosts matching `pattern`; once any is given, other hosts are
+    /// refused
+    pub fn allow_host(mut self, pattern: impl AsRef<str>) -> Self {
+        self.allow.push(HostPattern::new(pattern.as_ref()));
+        self
+    }
+
+    /// Refuse hosts matching `pattern`, even if they are allowed
+    pub fn deny_host(mut self, pattern: impl AsRef<str>) -> Self {
+        self.deny.push(HostPattern::new(pattern.as_ref()));
+        self
+    }
+
+    /// Let requests reach loopback, private and link-local addresses
+    /// (default false)
+    pub fn allow_private_networks(mut self, allow: bool) -> Self {
+        self.allow_private_networks = allow;
+        self
+    }
+
+    /// Read at most this much of a response body (default 32 KiB, the
+    /// agent's default tool-result limit)
+    pub fn max_response_bytes(mut self, bytes: usize) -> Self {
+        self.max_response_bytes = bytes;
+        self
+    }
+
+    /// Timeout of calls that don't set `timeout_ms` (default 10s)
+    pub fn default_timeout(mut self, timeout: Duration) -> Self {
+        self.default_timeout = timeout;
+        self
+    }
+
+    /// Cap on `timeout_ms` (default 60s)
+    pub fn max_timeout(mut self, timeout: Duration) -> Self {
+        self.max_timeout = timeout;
+        self
+    }
+
+    pub fn build(self) -> HttpTool {
+        HttpTool {
+            allow: self.allow,
+            deny: self.deny,
+            allow_private_networks: self.allow_private_networks,
+            max_response_bytes: self.max_response_bytes,
+            default_timeout: self.default_timeout,
+            max_timeout: self.max_timeout,
+            redaction: RedactionRules::default(),
+        }
+    }
+}
+
+struct HttpRequest {
+    method: Method,
+    url: Url,
+    headers: HeaderMap,
+    body: Option<String>,
+    timeout: Duration,
+}
+
+/// An exact host, `*.domain` for its subdomains, or `*`
+#[derive(Debug, Clone, PartialEq)]
+enum HostPattern {
+    Any,
+    Exact(String),
+    /// Subdomains of the domain, stored with its leading dot
+    Subdomains(String),
+}
+
+impl HostPattern {
+    fn new(pattern: &str) -> Self {
+        let pattern = normalize_host(pattern);
+        match pattern.strip_prefix('*') {
+            Some("") => Self::Any,
+            Some(domain) if domain.starts_with('.') => Self::Subdomains(domain.to_string()),
+            _ => Self::Exact(pattern),
+        }
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Exact(name) => host == name,
+            Self::Subdomains(suffix) => host.ends_with(suffix.as_str()),
+        }
+    }
+}
+
+fn normalize_host(host: &str) -> String {
+    host.trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .trim_end_matches('.')
+        .to_ascii_lowercase()
+}
+
+fn ip_literal(host: &str) -> Option<IpAddr> {
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+/// Whether `ip` is anything but a public unicast address
+fn is_private_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                || v4.is_documentation()
+                || a == 0
+                || a >= 240
+                // Carrier-grade NAT
+                || (a == 100 && (64..128).contains(&b))
+                // Benchmarking
+                || (a == 198 && (b == 18 || b == 19))
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_private_address(IpAddr::V4(v4));
+            }
+            let first = v6.segments()[0];
+            v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                // Unique local
+                || (first & 0xfe00) == 0xfc00
+                // Link-local
+                || (first & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+/// The type of a body recognized from its first bytes
+fn sniff(bytes: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+    ];
+    if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        return Some("image/webp");
+    }
+    SIGNATURES
+        .iter()
+        .find(|(signature, _)| bytes.starts_with(signature))
+        .map(|(_, mime)| *mime)
+}
+
+fn textual(mime: &str) -> bool {
+    mime.starts_with("text/")
+        || mime == "application/x-www-form-urlencoded"
+        || ["json", "xml", "javascript", "yaml", "csv"]
+            .iter()
+            .any(|kind| mime.contains(kind))
+}
+
+/// `bytes` as text, dropping a character the truncation split
+fn decode_text(bytes: &[u8], truncated: bool) -> Option<String> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Some(text.to_string()),
+        Err(e) if truncated && e.error_len().is_none() => {
+            Some(String::from_utf8_lossy(&bytes[..e.valid_up_to()]).into_owned())
+        }
+        Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_host_patterns() {
+        let tool = HttpTool::builder()
+            .allow_host("api.github.com")
+            .allow_host("*.example.com")
+            .deny_host("admin.example.com")
+            .build();
+        assert!(tool.host_allowed("api.github.com"));
+        assert!(tool.host_allowed("API.GitHub.com."));
+        assert!(!tool.host_allowed("github.com"));
+        assert!(tool.host_allowed("docs.example.com"));
+        assert!(!tool.host_allowed("example.com"));
+        assert!(!tool.host_allowed("admin.example.com"));
+        assert!(!tool.host_allowed("evilexample.com"));
+
+        let open = HttpTool::builder().deny_host("*").build();
+        assert!(!open.host_allowed("api.github.com"));
+        assert!(HttpTool::builder().build().host_allowed("anything.test"));
+    }
+
+    #[test]
+    fn test_private_addresses() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(is_private_address(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["93.184.216.34", "140.82.112.3", "2606:4700::1111"] {
+            assert!(!is_private_address(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn test_body_encoding() {
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n...."), Some("image/png"));
+        assert_eq!(sniff(b"RIFF....WEBPVP8 "), Some("image/webp"));
+        assert_eq!(sniff(b"hello"), None);
+        assert!(textual("application/vnd.api+json"));
+        assert!(!textual("image/png"));
+
+        // A multi-byte character cut by the truncation is dropped
+        let bytes = "héllo".as_bytes();
+        assert_eq!(decode_text(&bytes[..2], true).as_deref(), Some("h"));
+        assert_eq!(decode_text(&bytes[..2], false), None);
+    }
+
+    #[test]
+    fn test_authorization_is_redacted() {
+        let tool = HttpTool::builder().build();
+        let arguments = json!({
+            "url": "https://api.github.com/user",
+            "headers": { "Authorization": "token ghp_secret", "Accept": "application/json" }
+        });
+        let redacted = tool.redact_arguments(&arguments).unwrap();
+        assert_eq!(redacted["headers"]["Authorization"], "[REDACTED]");
+        assert_eq!(redacted["headers"]["Accept"], "application/json");
+        assert_eq!(
+            tool.redact_arguments(&json!({ "url": "https://api.github.com" })),
+            None
+        );
+    }
+}
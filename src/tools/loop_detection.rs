@@ -1,5 +1,7 @@
-use serde_json::Value as JsonValue;
-use std::collections::HashMap;
+use crate::types::{ToolResult, ToolStatus};
+use serde_json::{Map, Value as JsonValue};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 
 /// Configuration for detecting and preventing tool call loops
 #[derive(Debug, Clone)]
@@ -10,6 +12,14 @@ pub struct ToolLoopDetectionConfig {
     /// Custom message to inject when a loop is detected
     /// If None, uses a default message
     pub custom_message: Option<String>,
+
+    /// Also treat near-identical arguments as a repeat
+    /// If None, only arguments equal up to key order match
+    pub similarity: Option<SimilarityConfig>,
+
+    /// Treat any call to a tool that already ran this many times in the
+    /// agent run as a loop, whatever its arguments
+    pub max_repeats_per_tool: Option<usize>,
 }
 
 impl Default for ToolLoopDetectionConfig {
@@ -17,60 +27,208 @@ impl Default for ToolLoopDetectionConfig {
         Self {
             enabled: true,
             custom_message: None,
+            similarity: None,
+            max_repeats_per_tool: None,
         }
     }
 }
 
+/// How loosely arguments are compared in similarity mode
+///
+/// String values are always trimmed with inner whitespace collapsed, and
+/// numbers are compared by value (`1.0` matches `1`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SimilarityConfig {
+    /// Compare strings case-insensitively
+    pub ignore_case: bool,
+
+    /// Match when every string argument is at least this similar (0.0 to
+    /// 1.0, normalized Levenshtein) to the earlier call's and all other
+    /// arguments are equal
+    pub fuzzy_threshold: Option<f64>,
+}
+
+impl SimilarityConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compare strings case-insensitively (builder-style)
+    pub fn ignore_case(mut self) -> Self {
+        self.ignore_case = true;
+        self
+    }
+
+    /// Match string arguments at least `threshold` similar (builder-style)
+    pub fn with_fuzzy_threshold(mut self, threshold: f64) -> Self {
+        self.fuzzy_threshold = Some(threshold.clamp(0.0, 1.0));
+        self
+    }
+}
+
 impl ToolLoopDetectionConfig {
     /// Create with loop detection enabled and default message
     pub fn enabled() -> Self {
-        Self {
-            enabled: true,
-            custom_message: None,
-        }
+        Self::default()
     }
 
     /// Create with loop detection disabled
     pub fn disabled() -> Self {
         Self {
             enabled: false,
-            custom_message: None,
+            ..Self::default()
         }
     }
 
     /// Create with a custom message
     pub fn with_message(message: impl Into<String>) -> Self {
         Self {
-            enabled: true,
             custom_message: Some(message.into()),
+            ..Self::default()
         }
     }
 
+    /// Match near-identical arguments (builder-style)
+    pub fn with_similarity(mut self, similarity: SimilarityConfig) -> Self {
+        self.similarity = Some(similarity);
+        self
+    }
+
+    /// Cap the calls to any one tool per agent run (builder-style)
+    pub fn with_max_repeats_per_tool(mut self, max: usize) -> Self {
+        self.max_repeats_per_tool = Some(max);
+        self
+    }
+
     /// Get the message to use when a loop is detected
     pub fn get_message(&self, tool_name: &str, previous_result: &JsonValue) -> String {
+        self.loop_message(
+            tool_name,
+            &LoopMatch {
+                rule: LoopRule::Identical,
+                previous_result: previous_result.clone(),
+            },
+        )
+    }
+
+    /// Get the message for a detected loop, naming the rule that matched
+    ///
+    /// Custom messages can use `{tool_name}`, `{previous_result}` and
+    /// `{rule}`.
+    pub fn loop_message(&self, tool_name: &str, detected: &LoopMatch) -> String {
         if let Some(custom) = &self.custom_message {
             // Replace placeholders in custom message
-            custom
+            return custom
                 .replace("{tool_name}", tool_name)
-                .replace("{previous_result}", &previous_result.to_string())
-        } else {
-            // Default message
-            format!(
+                .replace("{previous_result}", &detected.previous_result.to_string())
+                .replace("{rule}", &detected.rule.to_string());
+        }
+        match detected.rule {
+            LoopRule::Identical => format!(
                 "You already called the tool '{}' with these exact parameters and received a response: {}. \
                 Please use the previous result instead of calling it again. \
                 If you need different information, try calling with different parameters.",
-                tool_name,
-                previous_result
-            )
+                tool_name, detected.previous_result
+            ),
+            LoopRule::MaxRepeats { limit } => format!(
+                "You have already called the tool '{}' {} times in this run, which is the limit. \
+                Its last response was: {}. \
+                Please work with the results you have instead of calling it again.",
+                tool_name, limit, detected.previous_result
+            ),
+            ref rule => format!(
+                "You already called the tool '{}' with equivalent parameters ({}) and received a response: {}. \
+                Please use the previous result instead of calling it again. \
+                If you need different information, try calling with meaningfully different parameters.",
+                tool_name, rule, detected.previous_result
+            ),
+        }
+    }
+}
+
+/// Which comparison found a repeated tool call
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoopRule {
+    /// Exactly the same arguments
+    Identical,
+
+    /// The same arguments with keys in another order
+    KeyOrder,
+
+    /// Strings equal once trimmed and inner whitespace collapsed
+    Whitespace,
+
+    /// Numbers equal by value, e.g. `1.0` and `1`
+    NumberFormat,
+
+    /// Strings equal ignoring case
+    Case,
+
+    /// String arguments at least this similar
+    Fuzzy { similarity: f64 },
+
+    /// The tool already ran `limit` times
+    MaxRepeats { limit: usize },
+}
+
+impl LoopRule {
+    /// Short machine-readable name, as used in loop detection events
+    pub fn name(&self) -> &'static str {
+        match self {
+            LoopRule::Identical => "identical",
+            LoopRule::KeyOrder => "key_order",
+            LoopRule::Whitespace => "whitespace",
+            LoopRule::NumberFormat => "number_format",
+            LoopRule::Case => "case",
+            LoopRule::Fuzzy { .. } => "fuzzy",
+            LoopRule::MaxRepeats { .. } => "max_repeats",
+        }
+    }
+}
+
+impl fmt::Display for LoopRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoopRule::Identical => write!(f, "identical arguments"),
+            LoopRule::KeyOrder => write!(f, "same arguments in a different key order"),
+            LoopRule::Whitespace => write!(f, "arguments match after trimming whitespace"),
+            LoopRule::NumberFormat => write!(f, "arguments match after normalizing numbers"),
+            LoopRule::Case => write!(f, "arguments match ignoring case"),
+            LoopRule::Fuzzy { similarity } => {
+                write!(f, "string arguments {:.0}% similar", similarity * 100.0)
+            }
+            LoopRule::MaxRepeats { limit } => write!(f, "called {} times already", limit),
         }
     }
 }
 
+/// A repeated tool call found by [`ToolCallTracker::detect`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopMatch {
+    pub rule: LoopRule,
+
+    /// Result of the earlier call that matched (the tool's latest, for
+    /// [`LoopRule::MaxRepeats`]): its output, or for a no-data or failed
+    /// call, its status with the message or error
+    pub previous_result: JsonValue,
+}
+
+#[derive(Debug, Clone)]
+struct RecordedCall {
+    tool_name: String,
+    /// Arguments as the model sent them, minus insignificant whitespace
+    raw: String,
+    /// Keys sorted, otherwise untouched
+    canonical: JsonValue,
+    result: JsonValue,
+    /// Failed in a way worth retrying, so a repeat isn't a loop
+    retryable: bool,
+}
+
 /// Tracks tool calls to detect loops
 #[derive(Debug, Clone, Default)]
 pub struct ToolCallTracker {
-    /// History of (tool_name, args_hash, result) tuples
-    history: Vec<(String, String, JsonValue)>,
+    history: Vec<RecordedCall>,
 }
 
 impl ToolCallTracker {
@@ -81,15 +239,36 @@ impl ToolCallTracker {
     }
 
     /// Record a tool call and its result
+    ///
+    /// A call that failed with a retryable error is remembered but never
+    /// reported as a loop: calling it again is a legitimate retry.
     pub fn record_call(
         &mut self,
         tool_name: &str,
         args: &HashMap<String, JsonValue>,
-        result: &JsonValue,
+        result: &ToolResult,
     ) {
-        let args_hash = Self::hash_args(args);
-        self.history
-            .push((tool_name.to_string(), args_hash, result.clone()));
+        let canonical = canonicalize(&JsonValue::Object(
+            args.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        ));
+        self.history.push(RecordedCall {
+            tool_name: tool_name.to_string(),
+            raw: canonical.to_string(),
+            canonical,
+            result: recorded_result(result),
+            retryable: retryable(result),
+        });
+    }
+
+    /// Record a tool call by its raw JSON arguments, as sent by the model
+    pub fn record_raw_call(&mut self, tool_name: &str, arguments: &str, result: &ToolResult) {
+        self.history.push(RecordedCall {
+            tool_name: tool_name.to_string(),
+            raw: compact(arguments),
+            canonical: canonicalize(&parse_arguments(arguments)),
+            result: recorded_result(result),
+            retryable: retryable(result),
+        });
     }
 
     /// Check if this exact tool call (name + args) was made before
@@ -99,13 +278,57 @@ impl ToolCallTracker {
         tool_name: &str,
         args: &HashMap<String, JsonValue>,
     ) -> Option<JsonValue> {
-        let args_hash = Self::hash_args(args);
+        let canonical = canonicalize(&JsonValue::Object(
+            args.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        ));
 
         // Look for previous call with same tool + args
-        self.history
-            .iter()
-            .find(|(name, hash, _)| name == tool_name && hash == &args_hash)
-            .map(|(_, _, result)| result.clone())
+        self.calls_to(tool_name)
+            .find(|call| call.canonical == canonical)
+            .map(|call| call.result.clone())
+    }
+
+    /// Check a call, given its raw JSON arguments, against the history
+    /// using every rule `config` enables
+    ///
+    /// The earliest matching call wins; among the argument rules, the
+    /// strictest one that matches is reported. The per-tool cap is checked
+    /// after them.
+    pub fn detect(
+        &self,
+        config: &ToolLoopDetectionConfig,
+        tool_name: &str,
+        arguments: &str,
+    ) -> Option<LoopMatch> {
+        let args = canonicalize(&parse_arguments(arguments));
+        let raw = compact(arguments);
+        let found = self.calls_to(tool_name).find_map(|call| {
+            let rule = if call.canonical == args {
+                if call.raw == raw {
+                    LoopRule::Identical
+                } else {
+                    LoopRule::KeyOrder
+                }
+            } else {
+                similar(config.similarity.as_ref()?, &call.canonical, &args)?
+            };
+            Some(LoopMatch {
+                rule,
+                previous_result: call.result.clone(),
+            })
+        });
+        if found.is_some() {
+            return found;
+        }
+
+        let limit = config.max_repeats_per_tool?;
+        if self.calls_to(tool_name).count() < limit {
+            return None;
+        }
+        Some(LoopMatch {
+            rule: LoopRule::MaxRepeats { limit },
+            previous_result: self.calls_to(tool_name).next_back()?.result.clone(),
+        })
     }
 
     /// Clear the history (e.g., at start of new agent execution)
@@ -113,18 +336,199 @@ impl ToolCallTracker {
         self.history.clear();
     }
 
-    /// Create a simple hash of arguments for comparison
-    fn hash_args(args: &HashMap<String, JsonValue>) -> String {
-        // Serialize to JSON for consistent comparison
-        // Sort keys to ensure deterministic ordering
-        let json = serde_json::to_string(args).unwrap_or_default();
-        format!("{:x}", md5::compute(json.as_bytes()))
+    fn calls_to<'a>(
+        &'a self,
+        tool_name: &'a str,
+    ) -> impl DoubleEndedIterator<Item = &'a RecordedCall> + 'a {
+        self.history
+            .iter()
+            .filter(move |call| call.tool_name == tool_name && !call.retryable)
+    }
+}
+
+/// What a loop message quotes back: the output of a success, or the status
+/// with the message or error otherwise, so an earlier failure is never
+/// mistaken for an earlier answer
+fn recorded_result(result: &ToolResult) -> JsonValue {
+    match result.status {
+        ToolStatus::Success => result.output.clone(),
+        ToolStatus::SuccessNoData => serde_json::json!({
+            "status": result.status,
+            "message": result.message,
+        }),
+        ToolStatus::Error => serde_json::json!({
+            "status": result.status,
+            "error": result.error,
+            "message": result.message,
+        }),
     }
 }
 
+fn retryable(result: &ToolResult) -> bool {
+    result.status == ToolStatus::Error && result.error.as_ref().is_some_and(|error| error.retryable)
+}
+
+/// Arguments that aren't valid JSON are compared as a plain string
+fn parse_arguments(arguments: &str) -> JsonValue {
+    serde_json::from_str(arguments).unwrap_or_else(|_| JsonValue::String(arguments.to_string()))
+}
+
+/// Drop whitespace outside string literals, so only key order and
+/// spelling tell two otherwise equal argument strings apart
+fn compact(arguments: &str) -> String {
+    let mut out = String::with_capacity(arguments.len());
+    let (mut in_string, mut escaped) = (false, false);
+    for c in arguments.chars() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c.is_whitespace() {
+            continue;
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Sort object keys, recursively
+fn canonicalize(value: &JsonValue) -> JsonValue {
+    map_json(value, &|v| v.clone())
+}
+
+/// Rebuild `value` with sorted keys, applying `leaf` to every scalar
+fn map_json(value: &JsonValue, leaf: &dyn Fn(&JsonValue) -> JsonValue) -> JsonValue {
+    match value {
+        JsonValue::Object(obj) => {
+            let sorted: BTreeMap<&String, JsonValue> =
+                obj.iter().map(|(k, v)| (k, map_json(v, leaf))).collect();
+            JsonValue::Object(
+                sorted
+                    .into_iter()
+                    .map(|(k, v)| (k.clone(), v))
+                    .collect::<Map<_, _>>(),
+            )
+        }
+        JsonValue::Array(items) => {
+            JsonValue::Array(items.iter().map(|v| map_json(v, leaf)).collect())
+        }
+        scalar => leaf(scalar),
+    }
+}
+
+fn collapse_whitespace(value: &JsonValue) -> JsonValue {
+    match value {
+        JsonValue::String(s) => {
+            JsonValue::String(s.split_whitespace().collect::<Vec<_>>().join(" "))
+        }
+        other => other.clone(),
+    }
+}
+
+fn normalize_number(value: &JsonValue) -> JsonValue {
+    match value.as_f64() {
+        Some(n) if value.is_number() && n.fract() == 0.0 && n.abs() < i64::MAX as f64 => {
+            JsonValue::from(n as i64)
+        }
+        _ => value.clone(),
+    }
+}
+
+fn lowercase(value: &JsonValue) -> JsonValue {
+    match value {
+        JsonValue::String(s) => JsonValue::String(s.to_lowercase()),
+        other => other.clone(),
+    }
+}
+
+/// Try the similarity rules in order, each on top of the ones before it
+fn similar(config: &SimilarityConfig, previous: &JsonValue, args: &JsonValue) -> Option<LoopRule> {
+    let whitespace = |v: &JsonValue| collapse_whitespace(v);
+    let numbers = |v: &JsonValue| normalize_number(&collapse_whitespace(v));
+    let case = |v: &JsonValue| lowercase(&normalize_number(&collapse_whitespace(v)));
+
+    let (prev, next) = (map_json(previous, &whitespace), map_json(args, &whitespace));
+    if prev == next {
+        return Some(LoopRule::Whitespace);
+    }
+    let (prev, next) = (map_json(previous, &numbers), map_json(args, &numbers));
+    if prev == next {
+        return Some(LoopRule::NumberFormat);
+    }
+    let (prev, next) = if config.ignore_case {
+        let (prev, next) = (map_json(previous, &case), map_json(args, &case));
+        if prev == next {
+            return Some(LoopRule::Case);
+        }
+        (prev, next)
+    } else {
+        (prev, next)
+    };
+
+    let threshold = config.fuzzy_threshold?;
+    let similarity = string_similarity(&prev, &next)?;
+    (similarity >= threshold).then_some(LoopRule::Fuzzy { similarity })
+}
+
+/// The lowest similarity over all string values, if the two values have
+/// the same shape and agree everywhere else
+fn string_similarity(a: &JsonValue, b: &JsonValue) -> Option<f64> {
+    match (a, b) {
+        (JsonValue::String(a), JsonValue::String(b)) => Some(levenshtein_similarity(a, b)),
+        (JsonValue::Object(a), JsonValue::Object(b)) => {
+            if a.len() != b.len() {
+                return None;
+            }
+            a.iter().try_fold(1.0_f64, |lowest, (key, a)| {
+                Some(lowest.min(string_similarity(a, b.get(key)?)?))
+            })
+        }
+        (JsonValue::Array(a), JsonValue::Array(b)) => {
+            if a.len() != b.len() {
+                return None;
+            }
+            a.iter().zip(b).try_fold(1.0_f64, |lowest, (a, b)| {
+                Some(lowest.min(string_similarity(a, b)?))
+            })
+        }
+        (a, b) => (a == b).then_some(1.0),
+    }
+}
+
+/// 1.0 for equal strings, down to 0.0 for strings with nothing in common
+fn levenshtein_similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    1.0 - row[b.len()] as f64 / longest as f64
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::ToolErrorDetail;
     use serde_json::json;
 
     #[test]
@@ -150,6 +554,10 @@ mod tests {
         assert!(message.contains("test"));
     }
 
+    fn found_nothing() -> ToolResult {
+        ToolResult::success(json!({"found": false}), 1.0)
+    }
+
     #[test]
     fn test_tracker_detects_loop() {
         let mut tracker = ToolCallTracker::new();
@@ -157,7 +565,7 @@ mod tests {
         let mut args = HashMap::new();
         args.insert("query".to_string(), json!("test"));
 
-        let result = json!({"found": false});
+        let result = ToolResult::success(json!({"found": false}), 1.0);
 
         // First call - no loop
         assert!(tracker.check_for_loop("search", &args).is_none());
@@ -168,7 +576,7 @@ mod tests {
         // Second call with same args - loop detected!
         let previous = tracker.check_for_loop("search", &args);
         assert!(previous.is_some());
-        assert_eq!(previous.unwrap(), result);
+        assert_eq!(previous.unwrap(), json!({"found": false}));
     }
 
     #[test]
@@ -181,7 +589,7 @@ mod tests {
         let mut args2 = HashMap::new();
         args2.insert("query".to_string(), json!("test2"));
 
-        tracker.record_call("search", &args1, &json!({}));
+        tracker.record_call("search", &args1, &found_nothing());
 
         // Different args - no loop
         assert!(tracker.check_for_loop("search", &args2).is_none());
@@ -194,10 +602,217 @@ mod tests {
         let mut args = HashMap::new();
         args.insert("query".to_string(), json!("test"));
 
-        tracker.record_call("search", &args, &json!({}));
+        tracker.record_call("search", &args, &found_nothing());
         tracker.clear();
 
         // After clear, no loop detected
         assert!(tracker.check_for_loop("search", &args).is_none());
     }
+
+    fn tracker_with(calls: &[(&str, &str)]) -> ToolCallTracker {
+        let mut tracker = ToolCallTracker::new();
+        for (tool, arguments) in calls {
+            tracker.record_raw_call(tool, arguments, &found_nothing());
+        }
+        tracker
+    }
+
+    fn similarity() -> ToolLoopDetectionConfig {
+        ToolLoopDetectionConfig::default().with_similarity(SimilarityConfig::new())
+    }
+
+    #[test]
+    fn test_detect_identical_and_key_order() {
+        let tracker = tracker_with(&[("search", r#"{"query": "rust", "limit": 5}"#)]);
+        let config = ToolLoopDetectionConfig::default();
+
+        let same = tracker
+            .detect(&config, "search", r#"{"query":"rust","limit":5}"#)
+            .unwrap();
+        assert_eq!(same.rule, LoopRule::Identical);
+        assert_eq!(same.previous_result, json!({"found": false}));
+
+        // Key order never mattered, even without similarity mode
+        let reordered = tracker
+            .detect(&config, "search", r#"{"limit": 5, "query": "rust"}"#)
+            .unwrap();
+        assert_eq!(reordered.rule, LoopRule::KeyOrder);
+
+        assert!(tracker
+            .detect(&config, "fetch", r#"{"query": "rust", "limit": 5}"#)
+            .is_none());
+    }
+
+    #[test]
+    fn test_detect_whitespace_only_in_similarity_mode() {
+        let tracker = tracker_with(&[("search", r#"{"query": "rust agents"}"#)]);
+        let padded = r#"{"query": "  rust   agents "}"#;
+
+        assert!(tracker
+            .detect(&ToolLoopDetectionConfig::default(), "search", padded)
+            .is_none());
+        let detected = tracker.detect(&similarity(), "search", padded).unwrap();
+        assert_eq!(detected.rule, LoopRule::Whitespace);
+    }
+
+    #[test]
+    fn test_detect_number_format() {
+        let tracker = tracker_with(&[("page", r#"{"n": 2, "nested": {"size": [10]}}"#)]);
+        let detected = tracker
+            .detect(
+                &similarity(),
+                "page",
+                r#"{"nested": {"size": [1e1]}, "n": 2.0}"#,
+            )
+            .unwrap();
+        assert_eq!(detected.rule, LoopRule::NumberFormat);
+    }
+
+    #[test]
+    fn test_detect_case_when_enabled() {
+        let tracker = tracker_with(&[("search", r#"{"query": "Rust Agents"}"#)]);
+        let lower = r#"{"query": "rust agents"}"#;
+
+        assert!(tracker.detect(&similarity(), "search", lower).is_none());
+        let config = ToolLoopDetectionConfig::default()
+            .with_similarity(SimilarityConfig::new().ignore_case());
+        let detected = tracker.detect(&config, "search", lower).unwrap();
+        assert_eq!(detected.rule, LoopRule::Case);
+    }
+
+    #[test]
+    fn test_detect_fuzzy_threshold() {
+        let tracker = tracker_with(&[("search", r#"{"query": "rust agent runtime", "limit": 5}"#)]);
+        let config = ToolLoopDetectionConfig::default()
+            .with_similarity(SimilarityConfig::new().with_fuzzy_threshold(0.9));
+
+        let typo = tracker
+            .detect(
+                &config,
+                "search",
+                r#"{"query": "rust agent runtim", "limit": 5}"#,
+            )
+            .unwrap();
+        match typo.rule {
+            LoopRule::Fuzzy { similarity } => assert!((0.9..1.0).contains(&similarity)),
+            other => panic!("expected fuzzy match, got {:?}", other),
+        }
+
+        // Different enough, or a non-string argument differs
+        assert!(tracker
+            .detect(
+                &config,
+                "search",
+                r#"{"query": "python agents", "limit": 5}"#
+            )
+            .is_none());
+        assert!(tracker
+            .detect(
+                &config,
+                "search",
+                r#"{"query": "rust agent runtim", "limit": 6}"#
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn test_max_repeats_per_tool() {
+        let mut tracker = tracker_with(&[("search", r#"{"query": "a"}"#)]);
+        let config = ToolLoopDetectionConfig::default().with_max_repeats_per_tool(2);
+
+        assert!(tracker
+            .detect(&config, "search", r#"{"query": "b"}"#)
+            .is_none());
+        tracker.record_raw_call(
+            "search",
+            r#"{"query": "b"}"#,
+            &ToolResult::success(json!({"hits": 1}), 1.0),
+        );
+
+        let capped = tracker
+            .detect(&config, "search", r#"{"query": "c"}"#)
+            .unwrap();
+        assert_eq!(capped.rule, LoopRule::MaxRepeats { limit: 2 });
+        assert_eq!(capped.previous_result, json!({"hits": 1}));
+
+        // Other tools have their own count
+        assert!(tracker
+            .detect(&config, "fetch", r#"{"query": "c"}"#)
+            .is_none());
+    }
+
+    #[test]
+    fn test_loop_message_names_rule() {
+        let config = ToolLoopDetectionConfig::default();
+        let detected = LoopMatch {
+            rule: LoopRule::Whitespace,
+            previous_result: json!({"data": "test"}),
+        };
+        let message = config.loop_message("search", &detected);
+        assert!(message.contains("search"));
+        assert!(message.contains("after trimming whitespace"));
+
+        let capped = config.loop_message(
+            "search",
+            &LoopMatch {
+                rule: LoopRule::MaxRepeats { limit: 3 },
+                previous_result: json!(null),
+            },
+        );
+        assert!(capped.contains("3 times"));
+
+        let custom = ToolLoopDetectionConfig::with_message("{tool_name} repeated ({rule})");
+        assert_eq!(
+            custom.loop_message("search", &detected),
+            "search repeated (arguments match after trimming whitespace)"
+        );
+    }
+
+    #[test]
+    fn test_check_for_loop_ignores_key_order() {
+        let mut tracker = ToolCallTracker::new();
+        let args: HashMap<String, JsonValue> =
+            (0..8).map(|i| (format!("key{}", i), json!(i))).collect();
+        tracker.record_call("search", &args, &found_nothing());
+
+        // A fresh map iterates in its own order
+        let reordered: HashMap<String, JsonValue> = (0..8)
+            .rev()
+            .map(|i| (format!("key{}", i), json!(i)))
+            .collect();
+        assert!(tracker.check_for_loop("search", &reordered).is_some());
+    }
+
+    #[test]
+    fn test_failures_are_recorded_as_failures() {
+        let config = ToolLoopDetectionConfig::default();
+        let mut tracker = ToolCallTracker::new();
+        tracker.record_raw_call(
+            "search",
+            r#"{"q":"mars"}"#,
+            &ToolResult::failed(ToolErrorDetail::new("not_found", "no such index"), 1.0),
+        );
+
+        let detected = tracker
+            .detect(&config, "search", r#"{"q":"mars"}"#)
+            .unwrap();
+        assert_eq!(detected.previous_result["status"], "error");
+        assert_eq!(detected.previous_result["error"]["code"], "not_found");
+    }
+
+    #[test]
+    fn test_repeat_after_retryable_failure_is_not_a_loop() {
+        let config = ToolLoopDetectionConfig::default().with_max_repeats_per_tool(1);
+        let mut tracker = ToolCallTracker::new();
+        let busy = ToolErrorDetail::new("rate_limited", "slow down").retryable();
+        tracker.record_raw_call("search", r#"{"q":"mars"}"#, &ToolResult::failed(busy, 1.0));
+        assert!(tracker
+            .detect(&config, "search", r#"{"q":"mars"}"#)
+            .is_none());
+
+        tracker.record_raw_call("search", r#"{"q":"mars"}"#, &found_nothing());
+        assert!(tracker
+            .detect(&config, "search", r#"{"q":"mars"}"#)
+            .is_some());
+    }
 }
@@ -2,50 +2,121 @@
 //
 // The Model Context Protocol (MCP) is a protocol for AI assistants to interact
 // with external tools and data sources. This module provides integration with
-// MCP servers via the rust-mcp-sdk.
-//
-// Current Status: PLACEHOLDER IMPLEMENTATION
-// The rust-mcp-sdk API structure is not yet fully understood. This module provides
-// a complete API surface that compiles, but the actual MCP protocol communication
-// needs to be implemented.
-//
-// To complete this implementation, we need to:
-// 1. Study rust-mcp-sdk documentation and examples
-// 2. Understand how to create clients and transports
-// 3. Implement the MCP protocol request/response cycle
-// 4. Handle tool discovery and execution
-//
-// For now, this provides a working skeleton that integrates with our tool system.
+// MCP servers via the rust-mcp-sdk, over either transport the spec defines:
+// a stdio subprocess, or streamable HTTP (JSON-RPC POSTs whose replies come
+// back as JSON or an SSE stream, plus an optional SSE stream for
+// server-initiated messages). The SDK handles the handshake, JSON-RPC id
+// correlation and SSE parsing; this module picks the transport, reconnects
+// when the connection drops, and adapts the server's tools to our `Tool`
+// trait. Resources (documents the server offers) are read through one
+// `read_resource` tool per server, and prompts render into text for an
+// agent's system prompt.
 
-use crate::tools::registry::Tool;
-use crate::types::{JsonValue, ToolError, ToolResult};
+use crate::llm::ChatMessage;
+use crate::runtime::retry::RetryPolicy;
+use crate::tools::context::ToolRunContext;
+use crate::tools::registry::{Tool, ToolRegistry};
+use crate::tools::truncation::{cut_text, DEFAULT_MAX_TOOL_RESULT_BYTES};
+use crate::types::{JsonValue, ToolError, ToolErrorDetail, ToolResult};
 use async_trait::async_trait;
+use base64::Engine;
 use rust_mcp_sdk::{
+    error::McpSdkError,
     mcp_client::{
         client_runtime_core, ClientHandlerCore, McpClientOptions, ToMcpClientHandlerCore,
     },
     schema::{
-        CallToolRequestParams, ClientCapabilities, Implementation, InitializeRequestParams,
-        LATEST_PROTOCOL_VERSION,
+        CallToolRequestParams, CallToolResult, ClientCapabilities, ContentBlock,
+        EmbeddedResourceResource, GetPromptRequestParams, Implementation, InitializeRequestParams,
+        ReadResourceContent, ReadResourceRequestParams, Role as McpRole, LATEST_PROTOCOL_VERSION,
     },
-    McpClient as SdkMcpClient, StdioTransport, TransportOptions,
+    McpClient as SdkMcpClient, RequestOptions, StdioTransport, StreamableTransportOptions,
+    TransportOptions,
 };
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::watch;
+
+/// JSON-RPC "internal error", the MCP equivalent of an HTTP 5xx
+const JSON_RPC_INTERNAL_ERROR: i64 = -32603;
+
+/// Resources listed in a `read_resource` tool's description; the tool can
+/// still read the rest
+const MAX_LISTED_RESOURCES: usize = 50;
+
+/// How to reach an MCP server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum McpTransport {
+    /// Launch the server as a subprocess and talk over its stdin/stdout
+    Stdio { command: String, args: Vec<String> },
+
+    /// Streamable HTTP: POST requests to `url`, sending `headers` (e.g.
+    /// `Authorization`) with every request
+    Http {
+        url: String,
+        headers: HashMap<String, String>,
+    },
+}
+
+impl McpTransport {
+    pub fn stdio(command: impl Into<String>, args: &[&str]) -> Self {
+        Self::Stdio {
+            command: command.into(),
+            args: args.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    pub fn http(url: impl Into<String>) -> Self {
+        Self::Http {
+            url: url.into(),
+            headers: HashMap::new(),
+        }
+    }
+
+    /// Send `name: value` with every HTTP request; ignored for stdio
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        if let Self::Http { headers, .. } = &mut self {
+            headers.insert(name.into(), value.into());
+        }
+        self
+    }
+
+    /// Authenticate HTTP requests with `Authorization: Bearer <token>`
+    pub fn with_bearer_token(self, token: impl AsRef<str>) -> Self {
+        self.with_header("Authorization", format!("Bearer {}", token.as_ref()))
+    }
+}
 
 /// MCP Client wrapper for connecting to MCP servers
 ///
 /// Manages a connection to an MCP server and provides methods to:
 /// - Discover available tools
 /// - Execute tools remotely
+/// - List and read resources
+/// - List and render prompts
 ///
-/// # Example (when implemented)
+/// When the connection drops (the subprocess exits, the HTTP server goes
+/// away or forgets the session) the client reconnects, redoing the
+/// handshake, with the backoff of its reconnect `RetryPolicy`. `list_tools`
+/// is then retried; a tool call is not, since it may have reached the
+/// server, but fails as `ToolError::Transient` so the agent's tool retry
+/// sends it again over the new connection.
+///
+/// # Example
 /// ```no_run
-/// # use agent_runtime::McpClient;
+/// # use agent_runtime::{McpClient, McpTransport};
 /// # async fn example() -> Result<(), String> {
 /// // Connect to an MCP server via stdio
 /// let client = McpClient::new_stdio("npx", &["-y", "@modelcontextprotocol/server-filesystem", "/tmp"]).await?;
 ///
+/// // ...or to a remote one over streamable HTTP
+/// let remote = McpClient::connect(
+///     McpTransport::http("https://tools.example.com/mcp").with_bearer_token("secret"),
+/// )
+/// .await?;
+///
 /// // Discover tools
 /// let tools = client.list_tools().await?;
 /// println!("Found {} tools", tools.len());
@@ -53,7 +124,13 @@ use std::sync::Arc;
 /// # }
 /// ```
 pub struct McpClient {
-    inner: Arc<dyn SdkMcpClient>,
+    transport: McpTransport,
+    reconnect: RetryPolicy,
+    inner: Mutex<Arc<dyn SdkMcpClient>>,
+    // Held while reconnecting, so concurrent failures reconnect once
+    reconnecting: tokio::sync::Mutex<()>,
+    // Counts the server's resources/list_changed notifications
+    resources_changed: Arc<watch::Sender<u64>>,
 }
 
 impl McpClient {
@@ -68,6 +145,55 @@ impl McpClient {
     /// - SQLite: `npx -y @modelcontextprotocol/server-sqlite --db-path ./data.db`
     /// - Web: `npx -y @modelcontextprotocol/server-fetch`
     pub async fn new_stdio(command: &str, args: &[&str]) -> Result<Arc<Self>, String> {
+        Self::connect(McpTransport::stdio(command, args)).await
+    }
+
+    /// Connect to a server's streamable HTTP endpoint, e.g.
+    /// `http://localhost:8080/mcp`
+    pub async fn connect_http(url: &str) -> Result<Arc<Self>, String> {
+        Self::connect(McpTransport::http(url)).await
+    }
+
+    /// Connect over `transport`, reconnecting with the default `RetryPolicy`
+    pub async fn connect(transport: McpTransport) -> Result<Arc<Self>, String> {
+        Self::connect_with_reconnect(transport, RetryPolicy::default()).await
+    }
+
+    /// Connect over `transport`, reconnecting after a dropped connection
+    /// with `reconnect`'s backoff (`RetryPolicy::no_retry()` tries once)
+    pub async fn connect_with_reconnect(
+        transport: McpTransport,
+        reconnect: RetryPolicy,
+    ) -> Result<Arc<Self>, String> {
+        let resources_changed = Arc::new(watch::Sender::new(0));
+        let inner = Self::open(&transport, &resources_changed).await?;
+        Ok(Arc::new(Self {
+            transport,
+            reconnect,
+            inner: Mutex::new(inner),
+            reconnecting: tokio::sync::Mutex::new(()),
+            resources_changed,
+        }))
+    }
+
+    pub fn transport(&self) -> &McpTransport {
+        &self.transport
+    }
+
+    /// Counts the server's `notifications/resources/list_changed`;
+    /// `changed().await` on the receiver wakes on the next one
+    ///
+    /// Over HTTP the server can only notify while the client holds its
+    /// stream for server-initiated messages open, which servers may decline.
+    pub fn resources_changed(&self) -> watch::Receiver<u64> {
+        self.resources_changed.subscribe()
+    }
+
+    /// Start a session: launch or reach the server and do the handshake
+    async fn open(
+        transport: &McpTransport,
+        resources_changed: &Arc<watch::Sender<u64>>,
+    ) -> Result<Arc<dyn SdkMcpClient>, String> {
         // Create client details
         let client_details = InitializeRequestParams {
             capabilities: ClientCapabilities::default(),
@@ -83,35 +209,109 @@ impl McpClient {
             meta: None,
         };
 
-        // Create transport that launches the MCP server
-        let transport = StdioTransport::create_with_server_launch(
-            command,
-            args.iter().map(|s| s.to_string()).collect(),
-            None,
-            TransportOptions::default(),
-        )
-        .map_err(|e| format!("Failed to create transport: {}", e))?;
-
-        // Create a minimal handler
-        let handler = MinimalClientHandler {};
-
-        // Create and start the MCP client
-        let client = client_runtime_core::create_client(McpClientOptions {
-            client_details,
-            transport,
-            handler: handler.to_mcp_client_handler(),
-            task_store: None,
-            server_task_store: None,
-            message_observer: None,
-        });
+        let handler = ClientHandler {
+            resources_changed: resources_changed.clone(),
+        };
+        let client: Arc<dyn SdkMcpClient> = match transport {
+            McpTransport::Stdio { command, args } => {
+                // Create transport that launches the MCP server
+                let transport = StdioTransport::create_with_server_launch(
+                    command,
+                    args.clone(),
+                    None,
+                    TransportOptions::default(),
+                )
+                .map_err(|e| format!("Failed to create transport: {}", e))?;
+
+                client_runtime_core::create_client(McpClientOptions {
+                    client_details,
+                    transport,
+                    handler: handler.to_mcp_client_handler(),
+                    task_store: None,
+                    server_task_store: None,
+                    message_observer: None,
+                })
+            }
+            McpTransport::Http { url, headers } => client_runtime_core::with_transport_options(
+                client_details,
+                StreamableTransportOptions {
+                    mcp_url: url.clone(),
+                    request_options: RequestOptions {
+                        custom_headers: (!headers.is_empty()).then(|| headers.clone()),
+                        ..Default::default()
+                    },
+                },
+                handler,
+                None,
+                None,
+                None,
+            ),
+        };
 
         client
             .clone()
             .start()
             .await
             .map_err(|e| format!("Failed to start MCP client: {}", e))?;
+        Ok(client)
+    }
+
+    fn current(&self) -> Arc<dyn SdkMcpClient> {
+        self.inner.lock().unwrap().clone()
+    }
+
+    /// Replace the session `failed` with a new one, unless another caller
+    /// already has
+    async fn reconnect(&self, failed: &Arc<dyn SdkMcpClient>) -> Result<(), String> {
+        let _guard = self.reconnecting.lock().await;
+        if !Arc::ptr_eq(&self.current(), failed) {
+            return Ok(());
+        }
+
+        let started = Instant::now();
+        let mut retry = 0;
+        let client = loop {
+            match Self::open(&self.transport, &self.resources_changed).await {
+                Ok(client) => break client,
+                Err(e) => match self.reconnect.next_delay(retry, started.elapsed(), None) {
+                    Some(delay) => {
+                        tokio::time::sleep(delay).await;
+                        retry += 1;
+                    }
+                    None => {
+                        return Err(format!(
+                            "Failed to reconnect after {} attempts: {}",
+                            retry + 1,
+                            e
+                        ))
+                    }
+                },
+            }
+        };
+
+        *self.inner.lock().unwrap() = client;
+        let _ = failed.shut_down().await;
+        Ok(())
+    }
 
-        Ok(Arc::new(Self { inner: client }))
+    /// Send a request that's safe to repeat, sending it again over a new
+    /// session if the connection was lost
+    async fn request<T, F, Fut>(&self, what: &str, send: F) -> Result<T, String>
+    where
+        F: Fn(Arc<dyn SdkMcpClient>) -> Fut,
+        Fut: Future<Output = Result<T, McpSdkError>>,
+    {
+        let client = self.current();
+        match send(client.clone()).await {
+            Err(e) if Self::connection_lost(&e) => {
+                self.reconnect(&client)
+                    .await
+                    .map_err(|r| format!("Failed to {}: {} ({})", what, e, r))?;
+                send(self.current()).await
+            }
+            response => response,
+        }
+        .map_err(|e| format!("Failed to {}: {}", what, e))
     }
 
     /// Discover all tools available on the connected MCP server
@@ -119,10 +319,10 @@ impl McpClient {
     /// Sends a `tools/list` request to the MCP server and parses the response.
     pub async fn list_tools(&self) -> Result<Vec<McpToolInfo>, String> {
         let response = self
-            .inner
-            .request_tool_list(None)
-            .await
-            .map_err(|e| format!("Failed to list tools: {}", e))?;
+            .request("list tools", |client| async move {
+                client.request_tool_list(None).await
+            })
+            .await?;
 
         Ok(response
             .tools
@@ -135,6 +335,228 @@ impl McpClient {
             .collect())
     }
 
+    /// Register every tool on the server in `registry` as
+    /// `namespace.tool`, e.g. `github.create_issue`, returning the names
+    /// registered
+    ///
+    /// Nothing is registered if any of the names is already taken, so two
+    /// servers with the same namespace can't shadow each other's tools.
+    pub async fn register_tools(
+        self: &Arc<Self>,
+        namespace: &str,
+        registry: &mut ToolRegistry,
+    ) -> Result<Vec<String>, String> {
+        let tools: Vec<McpTool> = self
+            .list_tools()
+            .await?
+            .into_iter()
+            .map(|info| McpTool::from_info(info, self.clone()).in_namespace(namespace))
+            .collect();
+
+        let mut names: Vec<String> = Vec::new();
+        for tool in &tools {
+            if registry.has_tool(&tool.name) || names.contains(&tool.name) {
+                return Err(format!(
+                    "Tool '{}' from MCP server '{}' is already registered",
+                    tool.name, namespace
+                ));
+            }
+            names.push(tool.name.clone());
+        }
+        for tool in tools {
+            registry.register(tool);
+        }
+        Ok(names)
+    }
+
+    /// The resources the server offers, from `resources/list`
+    pub async fn list_resources(&self) -> Result<Vec<McpResourceInfo>, String> {
+        let response = self
+            .request("list resources", |client| async move {
+                client.request_resource_list(None).await
+            })
+            .await?;
+
+        Ok(response
+            .resources
+            .into_iter()
+            .map(|resource| McpResourceInfo {
+                uri: resource.uri,
+                name: resource.name,
+                description: resource.description,
+                mime_type: resource.mime_type,
+            })
+            .collect())
+    }
+
+    /// Read resource `uri`, with `resources/read`; binary contents are
+    /// decoded from the server's base64
+    pub async fn read_resource(&self, uri: &str) -> Result<Vec<McpResourceContents>, String> {
+        let response = self
+            .request("read resource", |client| async move {
+                client
+                    .request_resource_read(ReadResourceRequestParams {
+                        uri: uri.to_string(),
+                        meta: None,
+                    })
+                    .await
+            })
+            .await?;
+
+        response
+            .contents
+            .into_iter()
+            .map(|contents| match contents {
+                ReadResourceContent::TextResourceContents(text) => Ok(McpResourceContents::Text {
+                    uri: text.uri,
+                    mime_type: text.mime_type,
+                    text: text.text,
+                }),
+                ReadResourceContent::BlobResourceContents(blob) => {
+                    let data = base64::engine::general_purpose::STANDARD
+                        .decode(&blob.blob)
+                        .map_err(|e| {
+                            format!("Resource '{}' has invalid base64 contents: {}", blob.uri, e)
+                        })?;
+                    Ok(McpResourceContents::Blob {
+                        uri: blob.uri,
+                        mime_type: blob.mime_type,
+                        data,
+                    })
+                }
+            })
+            .collect()
+    }
+
+    /// Register `namespace.read_resource`, which reads the server's
+    /// resources, in `registry`, returning its name
+    ///
+    /// The tool's description lists the resources. Each time the server
+    /// says its resource list changed, the tool is registered again with
+    /// the new list, so agents using the registry see it from their next
+    /// LLM request; this stops once the tool is unregistered or the
+    /// registry dropped.
+    pub async fn register_resources(
+        self: &Arc<Self>,
+        namespace: &str,
+        registry: &Arc<ToolRegistry>,
+    ) -> Result<String, String> {
+        let tool = McpResourceTool::new(namespace, self.list_resources().await?, self.clone());
+        let name = tool.name.clone();
+        if registry.has_tool(&name) {
+            return Err(format!(
+                "Tool '{}' from MCP server '{}' is already registered",
+                name, namespace
+            ));
+        }
+        registry.add(tool);
+
+        let mut changes = self.resources_changed();
+        let client = Arc::downgrade(self);
+        let registry = Arc::downgrade(registry);
+        let namespace = namespace.to_string();
+        let registered = name.clone();
+        tokio::spawn(async move {
+            while changes.changed().await.is_ok() {
+                let (Some(client), Some(registry)) = (client.upgrade(), registry.upgrade()) else {
+                    break;
+                };
+                if !registry.has_tool(&registered) {
+                    break;
+                }
+                match client.list_resources().await {
+                    Ok(resources) => {
+                        registry.add(McpResourceTool::new(&namespace, resources, client.clone()));
+                    }
+                    Err(e) => tracing::warn!(
+                        namespace = %namespace,
+                        "keeping the old resource list: {}",
+                        e
+                    ),
+                }
+            }
+        });
+        Ok(name)
+    }
+
+    /// The prompts the server offers, from `prompts/list`
+    pub async fn list_prompts(&self) -> Result<Vec<McpPromptInfo>, String> {
+        let response = self
+            .request("list prompts", |client| async move {
+                client.request_prompt_list(None).await
+            })
+            .await?;
+
+        Ok(response
+            .prompts
+            .into_iter()
+            .map(|prompt| McpPromptInfo {
+                name: prompt.name,
+                description: prompt.description,
+                arguments: prompt
+                    .arguments
+                    .into_iter()
+                    .map(|argument| McpPromptArgument {
+                        name: argument.name,
+                        description: argument.description,
+                        required: argument.required.unwrap_or(false),
+                    })
+                    .collect(),
+            })
+            .collect())
+    }
+
+    /// Server prompt `name` filled in with `arguments`, from
+    /// `prompts/get`
+    ///
+    /// Text content and embedded text resources become the messages' text;
+    /// images, audio and links are left out.
+    pub async fn get_prompt(
+        &self,
+        name: &str,
+        arguments: HashMap<String, String>,
+    ) -> Result<Vec<ChatMessage>, String> {
+        let arguments: BTreeMap<String, String> = arguments.into_iter().collect();
+        let response = self
+            .request("get prompt", |client| {
+                let params = GetPromptRequestParams {
+                    name: name.to_string(),
+                    arguments: (!arguments.is_empty()).then(|| arguments.clone()),
+                    meta: None,
+                };
+                async move { client.request_prompt(params).await }
+            })
+            .await?;
+
+        Ok(response
+            .messages
+            .into_iter()
+            .filter_map(|message| {
+                let text = match message.content {
+                    ContentBlock::TextContent(content) => content.text,
+                    ContentBlock::EmbeddedResource(embedded) => match embedded.resource {
+                        EmbeddedResourceResource::TextResourceContents(contents) => contents.text,
+                        EmbeddedResourceResource::BlobResourceContents(_) => return None,
+                    },
+                    _ => return None,
+                };
+                Some(match message.role {
+                    McpRole::Assistant => ChatMessage::assistant(text),
+                    McpRole::User => ChatMessage::user(text),
+                })
+            })
+            .collect())
+    }
+
+    /// Server prompt `name`, to render with
+    /// [`McpPrompt::into_system_prompt`]
+    pub fn prompt(self: &Arc<Self>, name: impl Into<String>) -> McpPrompt {
+        McpPrompt {
+            name: name.into(),
+            client: self.clone(),
+        }
+    }
+
     /// Call a tool on the MCP server
     ///
     /// Sends a `tools/call` request with the given arguments and waits for the result.
@@ -143,23 +565,66 @@ impl McpClient {
         name: &str,
         arguments: HashMap<String, JsonValue>,
     ) -> Result<JsonValue, String> {
+        self.call_tool_classified(name, arguments)
+            .await
+            .map(|(output, _)| output)
+            .map_err(|e| match e {
+                ToolError::Transient(message) | ToolError::ExecutionFailed(message) => message
+                    .strip_prefix("MCP error: ")
+                    .unwrap_or(&message)
+                    .to_string(),
+                other => other.to_string(),
+            })
+    }
+
+    /// Like `call_tool`, but classifies failures: transport and I/O errors
+    /// and server-internal errors are transient, the rest permanent. The
+    /// flag is the server's `isError`: the tool ran and reported a failure.
+    pub(crate) async fn call_tool_classified(
+        &self,
+        name: &str,
+        arguments: HashMap<String, JsonValue>,
+    ) -> Result<(JsonValue, bool), ToolError> {
         let params = CallToolRequestParams {
             name: name.to_string(),
-            arguments: Some(
-                arguments
-                    .into_iter()
-                    .collect::<serde_json::Map<String, JsonValue>>(),
-            ),
+            arguments: Some(arguments.into_iter().collect()),
             meta: None,
             task: None,
         };
 
-        let result = self
-            .inner
-            .request_tool_call(params)
-            .await
-            .map_err(|e| format!("MCP tool call failed: {}", e))?;
+        let client = self.current();
+        match client.request_tool_call(params).await {
+            Ok(result) => {
+                let is_error = result.is_error.unwrap_or(false);
+                let output = Self::tool_output(result).map_err(ToolError::ExecutionFailed)?;
+                Ok((output, is_error))
+            }
+            Err(e) => {
+                let mut message = format!("MCP error: MCP tool call failed: {}", e);
+                if Self::connection_lost(&e) {
+                    // Reconnect now so a retry of the call finds a live session
+                    if let Err(reconnect) = self.reconnect(&client).await {
+                        message = format!("{} ({})", message, reconnect);
+                    }
+                }
+                Err(match e {
+                    McpSdkError::Transport(_) | McpSdkError::Io(_) => ToolError::Transient(message),
+                    McpSdkError::RpcError(rpc) if rpc.code == JSON_RPC_INTERNAL_ERROR => {
+                        ToolError::Transient(message)
+                    }
+                    _ => ToolError::ExecutionFailed(message),
+                })
+            }
+        }
+    }
+
+    /// Whether `error` means the session is gone rather than the request
+    /// being refused
+    fn connection_lost(error: &McpSdkError) -> bool {
+        matches!(error, McpSdkError::Transport(_) | McpSdkError::Io(_))
+    }
 
+    fn tool_output(result: CallToolResult) -> Result<JsonValue, String> {
         // Convert the result content to a JSON value
         if let Some(content) = result.content.first() {
             if let Ok(text_content) = content.as_text_content() {
@@ -182,15 +647,96 @@ pub struct McpToolInfo {
     pub input_schema: JsonValue,
 }
 
-/// Minimal handler for MCP client (we don't need custom message handling)
-struct MinimalClientHandler;
+/// A resource discovered from an MCP server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct McpResourceInfo {
+    pub uri: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub mime_type: Option<String>,
+}
+
+/// Part of a resource read from an MCP server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum McpResourceContents {
+    Text {
+        uri: String,
+        mime_type: Option<String>,
+        text: String,
+    },
+    Blob {
+        uri: String,
+        mime_type: Option<String>,
+        data: Vec<u8>,
+    },
+}
+
+/// A prompt template discovered from an MCP server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct McpPromptInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub arguments: Vec<McpPromptArgument>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct McpPromptArgument {
+    pub name: String,
+    pub description: Option<String>,
+    pub required: bool,
+}
+
+/// A server's prompt template, for an agent's system prompt
+///
+/// ```no_run
+/// # use agent_runtime::{AgentConfig, McpClient};
+/// # use std::collections::HashMap;
+/// # async fn example(client: std::sync::Arc<McpClient>) -> Result<(), String> {
+/// let prompt = client
+///     .prompt("code_review")
+///     .into_system_prompt(HashMap::from([("language".to_string(), "Rust".to_string())]))
+///     .await?;
+/// let config = AgentConfig::builder("reviewer").system_prompt(prompt).build();
+/// # Ok(())
+/// # }
+/// ```
+pub struct McpPrompt {
+    name: String,
+    client: Arc<McpClient>,
+}
+
+impl McpPrompt {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The prompt filled in with `arguments`, its messages' text joined by
+    /// blank lines
+    pub async fn into_system_prompt(
+        self,
+        arguments: HashMap<String, String>,
+    ) -> Result<String, String> {
+        let messages = self.client.get_prompt(&self.name, arguments).await?;
+        Ok(messages
+            .iter()
+            .map(|message| message.content.text())
+            .collect::<Vec<_>>()
+            .join("\n\n"))
+    }
+}
+
+/// Handles the server's messages: resource list changes are counted, the
+/// rest ignored
+struct ClientHandler {
+    resources_changed: Arc<watch::Sender<u64>>,
+}
 
 use rust_mcp_sdk::schema::{
     NotificationFromServer, ResultFromClient, RpcError, ServerJsonrpcRequest,
 };
 
 #[async_trait]
-impl ClientHandlerCore for MinimalClientHandler {
+impl ClientHandlerCore for ClientHandler {
     async fn handle_request(
         &self,
         _request: ServerJsonrpcRequest,
@@ -201,9 +747,12 @@ impl ClientHandlerCore for MinimalClientHandler {
 
     async fn handle_notification(
         &self,
-        _notification: NotificationFromServer,
+        notification: NotificationFromServer,
         _runtime: &dyn SdkMcpClient,
     ) -> Result<(), RpcError> {
+        if let NotificationFromServer::ResourceListChangedNotification(_) = notification {
+            self.resources_changed.send_modify(|count| *count += 1);
+        }
         Ok(())
     }
 
@@ -224,8 +773,11 @@ pub struct McpTool {
     name: String,
     description: String,
     input_schema: JsonValue,
+    // Name of the tool on the server, when it differs from `name`
+    remote_name: Option<String>,
     // Reference to the MCP client for making calls
     client: Arc<McpClient>,
+    strict: Option<bool>,
 }
 
 impl McpTool {
@@ -240,10 +792,33 @@ impl McpTool {
             name,
             description,
             input_schema,
+            remote_name: None,
             client,
+            strict: None,
         }
     }
 
+    /// Call the server's tool `remote_name` while registering as `name`
+    pub fn with_remote_name(mut self, remote_name: impl Into<String>) -> Self {
+        self.remote_name = Some(remote_name.into());
+        self
+    }
+
+    /// Set the definition's `strict` flag; see [`Tool::strict`]
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = Some(strict);
+        self
+    }
+
+    /// Register as `namespace.name`, still calling the server's tool by its
+    /// own name (builder-style)
+    pub fn in_namespace(mut self, namespace: &str) -> Self {
+        let remote_name = self.remote_name.take().unwrap_or_else(|| self.name.clone());
+        self.name = format!("{}.{}", namespace, self.name);
+        self.remote_name = Some(remote_name);
+        self
+    }
+
     /// Create from McpToolInfo (convenience method)
     pub fn from_info(info: McpToolInfo, client: Arc<McpClient>) -> Self {
         Self::new(info.name, info.description, info.input_schema, client)
@@ -268,12 +843,194 @@ impl Tool for McpTool {
         let start = std::time::Instant::now();
 
         // Call through to MCP server
-        match self.client.call_tool(&self.name, params).await {
-            Ok(output) => Ok(ToolResult::success(
-                output,
-                start.elapsed().as_secs_f64() * 1000.0,
-            )),
-            Err(e) => Err(ToolError::ExecutionFailed(format!("MCP error: {}", e))),
+        let remote_name = self.remote_name.as_deref().unwrap_or(&self.name);
+        let (output, is_error) = self
+            .client
+            .call_tool_classified(remote_name, params)
+            .await?;
+        let duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+        if is_error {
+            let message = match &output {
+                JsonValue::String(text) => text.clone(),
+                other => other.to_string(),
+            };
+            return Ok(ToolResult::failed(
+                ToolErrorDetail::new("mcp_tool_error", message),
+                duration_ms,
+            ));
+        }
+        Ok(ToolResult::success(output, duration_ms))
+    }
+
+    async fn execute_with_context(
+        &self,
+        params: HashMap<String, JsonValue>,
+        ctx: &ToolRunContext,
+    ) -> Result<ToolResult, ToolError> {
+        // Stop waiting on the server once the run is canceled
+        tokio::select! {
+            result = self.execute(params) => result,
+            _ = ctx.cancellation.cancelled() => Err(ToolError::Canceled(format!(
+                "MCP call to '{}' abandoned",
+                self.name
+            ))),
+        }
+    }
+
+    fn strict(&self) -> Option<bool> {
+        self.strict
+    }
+}
+
+/// Reads an MCP server's resources, registered as `namespace.read_resource`
+/// by [`McpClient::register_resources`]
+///
+/// Its description lists the server's resources. The result is
+/// `{uri, contents: [{uri, mime_type, text | blob, encoding, bytes, truncated}]}`,
+/// where `encoding` is `"text"` or `"base64"`. Text longer than the size
+/// limit is cut in the middle; binary contents over it are left out, as
+/// part of a base64 string is no use to the LLM.
+pub struct McpResourceTool {
+    name: String,
+    description: String,
+    client: Arc<McpClient>,
+    max_bytes: usize,
+}
+
+impl McpResourceTool {
+    pub fn new(namespace: &str, resources: Vec<McpResourceInfo>, client: Arc<McpClient>) -> Self {
+        let mut description = format!(
+            "Read a resource from the '{}' MCP server by its URI.",
+            namespace
+        );
+        if resources.is_empty() {
+            description.push_str(" It lists no resources.");
+        } else {
+            description.push_str(" Resources:");
+            for resource in resources.iter().take(MAX_LISTED_RESOURCES) {
+                description.push_str(&format!("\n- {} ({}", resource.uri, resource.name));
+                if let Some(mime_type) = &resource.mime_type {
+                    description.push_str(&format!(", {}", mime_type));
+                }
+                description.push(')');
+                if let Some(about) = &resource.description {
+                    description.push_str(&format!(": {}", about));
+                }
+            }
+            if resources.len() > MAX_LISTED_RESOURCES {
+                description.push_str(&format!(
+                    "\n...and {} more",
+                    resources.len() - MAX_LISTED_RESOURCES
+                ));
+            }
+        }
+        Self {
+            name: format!("{}.read_resource", namespace),
+            description,
+            client,
+            max_bytes: DEFAULT_MAX_TOOL_RESULT_BYTES,
+        }
+    }
+
+    /// Largest contents returned whole (default: the agent's default
+    /// `max_tool_result_bytes`)
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    fn output(&self, contents: McpResourceContents) -> JsonValue {
+        match contents {
+            McpResourceContents::Text {
+                uri,
+                mime_type,
+                text,
+            } => serde_json::json!({
+                "uri": uri,
+                "mime_type": mime_type,
+                "text": cut_text(&text, self.max_bytes),
+                "encoding": "text",
+                "bytes": text.len(),
+                "truncated": text.len() > self.max_bytes,
+            }),
+            McpResourceContents::Blob {
+                uri,
+                mime_type,
+                data,
+            } => {
+                let blob = base64::engine::general_purpose::STANDARD.encode(&data);
+                let truncated = blob.len() > self.max_bytes;
+                let mut output = serde_json::json!({
+                    "uri": uri,
+                    "mime_type": mime_type,
+                    "encoding": "base64",
+                    "bytes": data.len(),
+                    "truncated": truncated,
+                });
+                if !truncated {
+                    output["blob"] = blob.into();
+                }
+                output
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for McpResourceTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn input_schema(&self) -> JsonValue {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "uri": { "type": "string", "description": "URI of the resource to read" }
+            },
+            "required": ["uri"]
+        })
+    }
+
+    async fn execute(&self, params: HashMap<String, JsonValue>) -> Result<ToolResult, ToolError> {
+        let start = std::time::Instant::now();
+        let uri = params
+            .get("uri")
+            .and_then(JsonValue::as_str)
+            .ok_or_else(|| ToolError::InvalidParameters("'uri' must be a string".to_string()))?;
+        let contents = self
+            .client
+            .read_resource(uri)
+            .await
+            .map_err(ToolError::ExecutionFailed)?;
+        let output = serde_json::json!({
+            "uri": uri,
+            "contents": contents
+                .into_iter()
+                .map(|contents| self.output(contents))
+                .collect::<Vec<_>>(),
+        });
+        Ok(ToolResult::success(
+            output,
+            start.elapsed().as_secs_f64() * 1000.0,
+        ))
+    }
+
+    async fn execute_with_context(
+        &self,
+        params: HashMap<String, JsonValue>,
+        ctx: &ToolRunContext,
+    ) -> Result<ToolResult, ToolError> {
+        tokio::select! {
+            result = self.execute(params) => result,
+            _ = ctx.cancellation.cancelled() => Err(ToolError::Canceled(format!(
+                "MCP read by '{}' abandoned",
+                self.name
+            ))),
         }
     }
 }
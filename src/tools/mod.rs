@@ -1,13 +1,37 @@
-//! Tool system: registry, native tools, MCP integration, and loop detection.
+//! Tool system: registry, native tools, MCP integration, OpenAI tool specs,
+//! built-in HTTP, filesystem and retrieval tools, loop detection, result
+//! caching, and result truncation.
 
 pub mod builtin;
+pub mod cache;
+pub mod context;
+pub mod fs;
+pub mod http;
 pub mod loop_detection;
 pub mod mcp;
 pub mod native;
+pub mod openai_spec;
 pub mod registry;
+pub mod retrieval;
+pub mod truncation;
 
 pub use builtin::{CalculatorTool, EchoTool};
-pub use loop_detection::{ToolCallTracker, ToolLoopDetectionConfig};
-pub use mcp::{McpClient, McpTool, McpToolInfo};
+pub use cache::ToolCacheConfig;
+pub use context::ToolRunContext;
+pub use fs::FsTools;
+pub use http::{HttpTool, HttpToolBuilder};
+pub use loop_detection::{
+    LoopMatch, LoopRule, SimilarityConfig, ToolCallTracker, ToolLoopDetectionConfig,
+};
+pub use mcp::{
+    McpClient, McpPrompt, McpPromptArgument, McpPromptInfo, McpResourceContents, McpResourceInfo,
+    McpResourceTool, McpTool, McpToolInfo, McpTransport,
+};
 pub use native::NativeTool;
+pub use openai_spec::{
+    HttpEndpoint, SpecImport, ToolBinder, ToolSpec, ToolSpecError, UnboundPolicy,
+};
 pub use registry::{Tool, ToolRegistry};
+pub use retrieval::{SearchHit, VectorSearchTool, VectorStore};
+pub use tokio_util::sync::CancellationToken;
+pub use truncation::{truncate_tool_result, ToolResultTransformer};
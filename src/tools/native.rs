@@ -1,3 +1,5 @@
+use crate::runtime::retry::RetryPolicy;
+use crate::tools::context::ToolRunContext;
 use crate::tools::registry::Tool;
 use crate::types::ToolExecutionResult;
 use async_trait::async_trait;
@@ -5,9 +7,13 @@ use futures::future::BoxFuture;
 use serde_json::Value as JsonValue;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 type ToolExecutor = Arc<
-    dyn Fn(HashMap<String, JsonValue>) -> BoxFuture<'static, ToolExecutionResult> + Send + Sync,
+    dyn Fn(HashMap<String, JsonValue>, ToolRunContext) -> BoxFuture<'static, ToolExecutionResult>
+        + Send
+        + Sync,
 >;
 
 /// A native (in-memory) tool implemented as a Rust async function
@@ -19,6 +25,12 @@ pub struct NativeTool {
     description: String,
     input_schema: JsonValue,
     executor: ToolExecutor,
+    retry_policy: Option<RetryPolicy>,
+    timeout: Option<Duration>,
+    side_effecting: bool,
+    validate_arguments: bool,
+    cacheable: bool,
+    strict: Option<bool>,
 }
 
 impl NativeTool {
@@ -43,9 +55,112 @@ impl NativeTool {
             name: name.into(),
             description: description.into(),
             input_schema,
-            executor: Arc::new(move |params| Box::pin(executor(params))),
+            executor: Arc::new(move |params, _ctx| Box::pin(executor(params))),
+            retry_policy: None,
+            timeout: None,
+            side_effecting: true,
+            validate_arguments: true,
+            cacheable: true,
+            strict: None,
         }
     }
+
+    /// Create a native tool that receives the per-call `ToolRunContext`
+    ///
+    /// Use this when the tool needs run-scoped services, e.g. resolving
+    /// artifact handles produced by earlier tools.
+    pub fn with_context<F, Fut>(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        input_schema: JsonValue,
+        executor: F,
+    ) -> Self
+    where
+        F: Fn(HashMap<String, JsonValue>, ToolRunContext) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ToolExecutionResult> + Send + 'static,
+    {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            input_schema,
+            executor: Arc::new(move |params, ctx| Box::pin(executor(params, ctx))),
+            retry_policy: None,
+            timeout: None,
+            side_effecting: true,
+            validate_arguments: true,
+            cacheable: true,
+            strict: None,
+        }
+    }
+
+    /// Create a native tool that receives the call's cancellation token
+    ///
+    /// Use this for long-running work (polling, downloads) that should stop
+    /// early when the run is canceled. Return `ToolError::Canceled` once the
+    /// token fires and any partial work is cleaned up.
+    pub fn cancellable<F, Fut>(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        input_schema: JsonValue,
+        executor: F,
+    ) -> Self
+    where
+        F: Fn(HashMap<String, JsonValue>, CancellationToken) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ToolExecutionResult> + Send + 'static,
+    {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            input_schema,
+            executor: Arc::new(move |params, ctx| Box::pin(executor(params, ctx.cancellation))),
+            retry_policy: None,
+            timeout: None,
+            side_effecting: true,
+            validate_arguments: true,
+            cacheable: true,
+            strict: None,
+        }
+    }
+
+    /// Retry this tool's `ToolError::Transient` failures with `policy`
+    /// instead of the agent's default
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Abandon calls that run longer than `timeout`, instead of the agent's
+    /// default
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Declare that calls only read, so they may be run speculatively
+    pub fn read_only(mut self) -> Self {
+        self.side_effecting = false;
+        self
+    }
+
+    /// Don't check arguments against the input schema, e.g. when the schema
+    /// describes arguments only loosely or changes at run time
+    pub fn skip_validation(mut self) -> Self {
+        self.validate_arguments = false;
+        self
+    }
+
+    /// Always run calls, even when the registry has a cached result for
+    /// the same arguments
+    pub fn no_cache(mut self) -> Self {
+        self.cacheable = false;
+        self
+    }
+
+    /// Set the definition's `strict` flag; see [`Tool::strict`]
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = Some(strict);
+        self
+    }
 }
 
 #[async_trait]
@@ -63,7 +178,39 @@ impl Tool for NativeTool {
     }
 
     async fn execute(&self, params: HashMap<String, JsonValue>) -> ToolExecutionResult {
-        (self.executor)(params).await
+        (self.executor)(params, ToolRunContext::default()).await
+    }
+
+    async fn execute_with_context(
+        &self,
+        params: HashMap<String, JsonValue>,
+        ctx: &ToolRunContext,
+    ) -> ToolExecutionResult {
+        (self.executor)(params, ctx.clone()).await
+    }
+
+    fn retry_policy(&self) -> Option<RetryPolicy> {
+        self.retry_policy.clone()
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    fn side_effecting(&self) -> bool {
+        self.side_effecting
+    }
+
+    fn validates_arguments(&self) -> bool {
+        self.validate_arguments
+    }
+
+    fn cacheable(&self) -> bool {
+        self.cacheable
+    }
+
+    fn strict(&self) -> Option<bool> {
+        self.strict
     }
 }
 
@@ -73,6 +220,12 @@ impl std::fmt::Debug for NativeTool {
             .field("name", &self.name)
             .field("description", &self.description)
             .field("input_schema", &self.input_schema)
+            .field("retry_policy", &self.retry_policy)
+            .field("timeout", &self.timeout)
+            .field("side_effecting", &self.side_effecting)
+            .field("validate_arguments", &self.validate_arguments)
+            .field("cacheable", &self.cacheable)
+            .field("strict", &self.strict)
             .finish()
     }
 }
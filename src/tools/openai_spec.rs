@@ -0,0 +1,550 @@
+//! Tools declared in the OpenAI function-calling format.
+//!
+//! [`ToolRegistry::register_from_openai_spec`] parses one or many function
+//! definitions, either wrapped (`{"type": "function", "function": {...}}`) or
+//! bare (`{"name", "description", "parameters"}`), and registers a tool for
+//! each, with its handler taken from a [`ToolBinder`]: a native closure, a
+//! route to an MCP server, or an HTTP endpoint. Names the binder doesn't
+//! know fail the import or, with [`UnboundPolicy::Stub`], become stubs that
+//! answer with a not-implemented error, so an agent's tool use can be tried
+//! out before the handlers exist.
+//!
+//! The parsed `parameters` and `strict` flag are kept as given and sent to
+//! providers as-is. [`ToolRegistry::to_openai_spec`] exports the registry's
+//! catalog in the wrapped format, sorted by name, for syncing back to the
+//! shared catalog.
+
+use crate::tools::context::ToolRunContext;
+use crate::tools::mcp::{McpClient, McpTool};
+use crate::tools::native::NativeTool;
+use crate::tools::registry::{Tool, ToolRegistry};
+use crate::types::{ToolError, ToolExecutionResult, ToolResult};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// One function definition
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolSpec {
+    pub name: String,
+
+    #[serde(default)]
+    pub description: String,
+
+    /// JSON Schema of the arguments
+    #[serde(default = "empty_parameters")]
+    pub parameters: JsonValue,
+
+    /// Whether the LLM must keep to `parameters` exactly
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+}
+
+fn empty_parameters() -> JsonValue {
+    serde_json::json!({ "type": "object", "properties": {} })
+}
+
+impl ToolSpec {
+    /// Parse a spec document: one definition or an array of them, each
+    /// wrapped or bare
+    pub fn parse_all(spec: &JsonValue) -> Result<Vec<ToolSpec>, ToolSpecError> {
+        let items = match spec {
+            JsonValue::Array(items) => items.as_slice(),
+            other => std::slice::from_ref(other),
+        };
+
+        let mut seen = HashSet::new();
+        let mut specs = Vec::with_capacity(items.len());
+        for (index, item) in items.iter().enumerate() {
+            let definition = match item.get("type") {
+                Some(JsonValue::String(kind)) if kind == "function" => {
+                    item.get("function").ok_or_else(|| ToolSpecError::Invalid {
+                        index,
+                        message: "missing 'function'".to_string(),
+                    })?
+                }
+                Some(kind) => {
+                    return Err(ToolSpecError::Invalid {
+                        index,
+                        message: format!("unsupported tool type {}", kind),
+                    })
+                }
+                None => item,
+            };
+            let spec: ToolSpec =
+                serde_json::from_value(definition.clone()).map_err(|e| ToolSpecError::Invalid {
+                    index,
+                    message: e.to_string(),
+                })?;
+
+            let valid_name = (1..=64).contains(&spec.name.len())
+                && spec
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if !valid_name {
+                return Err(ToolSpecError::Invalid {
+                    index,
+                    message: format!(
+                        "'{}' is not a valid tool name (1-64 of a-z, A-Z, 0-9, _ and -)",
+                        spec.name
+                    ),
+                });
+            }
+            if !spec.parameters.is_object() {
+                return Err(ToolSpecError::Invalid {
+                    index,
+                    message: format!("parameters of '{}' must be an object", spec.name),
+                });
+            }
+            if !seen.insert(spec.name.clone()) {
+                return Err(ToolSpecError::Duplicate(spec.name));
+            }
+            specs.push(spec);
+        }
+        Ok(specs)
+    }
+
+    /// The definition in the wrapped format providers receive
+    pub fn to_openai(&self) -> JsonValue {
+        serde_json::json!({
+            "type": "function",
+            "function": self,
+        })
+    }
+}
+
+/// Why a spec couldn't be imported
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ToolSpecError {
+    #[error("tool definition {index} is invalid: {message}")]
+    Invalid { index: usize, message: String },
+
+    #[error("tool '{0}' is defined more than once")]
+    Duplicate(String),
+
+    #[error("no handler bound for: {}", .0.join(", "))]
+    Unbound(Vec<String>),
+}
+
+/// What happens to spec entries the binder has no handler for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnboundPolicy {
+    /// Fail the import, registering nothing
+    #[default]
+    Error,
+
+    /// Register a stub that fails every call with a not-implemented error
+    Stub,
+}
+
+/// Names registered by an import, in spec order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpecImport {
+    pub bound: Vec<String>,
+    pub stubbed: Vec<String>,
+}
+
+type Handler = Arc<
+    dyn Fn(HashMap<String, JsonValue>, ToolRunContext) -> BoxFuture<'static, ToolExecutionResult>
+        + Send
+        + Sync,
+>;
+
+enum Binding {
+    Native(Handler),
+    Mcp {
+        client: Arc<McpClient>,
+        remote_name: Option<String>,
+    },
+    Http(HttpEndpoint),
+}
+
+/// Handlers for tools declared in a spec, by tool name
+#[derive(Default)]
+pub struct ToolBinder {
+    bindings: HashMap<String, Binding>,
+    unbound: UnboundPolicy,
+}
+
+impl ToolBinder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle `name` with an async closure, as in [`NativeTool::new`]
+    pub fn native<F, Fut>(self, name: impl Into<String>, handler: F) -> Self
+    where
+        F: Fn(HashMap<String, JsonValue>) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ToolExecutionResult> + Send + 'static,
+    {
+        self.native_with_context(name, move |params, _ctx| handler(params))
+    }
+
+    /// Handle `name` with an async closure that also receives the call's
+    /// context, as in [`NativeTool::with_context`]
+    pub fn native_with_context<F, Fut>(mut self, name: impl Into<String>, handler: F) -> Self
+    where
+        F: Fn(HashMap<String, JsonValue>, ToolRunContext) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ToolExecutionResult> + Send + 'static,
+    {
+        let handler: Handler = Arc::new(move |params, ctx| Box::pin(handler(params, ctx)));
+        self.bindings.insert(name.into(), Binding::Native(handler));
+        self
+    }
+
+    /// Route `name` to the tool of the same name on an MCP server
+    pub fn mcp(mut self, name: impl Into<String>, client: Arc<McpClient>) -> Self {
+        self.bindings.insert(
+            name.into(),
+            Binding::Mcp {
+                client,
+                remote_name: None,
+            },
+        );
+        self
+    }
+
+    /// Route `name` to the server's tool `remote_name`
+    pub fn mcp_as(
+        mut self,
+        name: impl Into<String>,
+        client: Arc<McpClient>,
+        remote_name: impl Into<String>,
+    ) -> Self {
+        self.bindings.insert(
+            name.into(),
+            Binding::Mcp {
+                client,
+                remote_name: Some(remote_name.into()),
+            },
+        );
+        self
+    }
+
+    /// Handle `name` by calling an HTTP endpoint
+    pub fn http(mut self, name: impl Into<String>, endpoint: HttpEndpoint) -> Self {
+        self.bindings.insert(name.into(), Binding::Http(endpoint));
+        self
+    }
+
+    /// Set what happens to names without a handler (default: error)
+    pub fn unbound(mut self, policy: UnboundPolicy) -> Self {
+        self.unbound = policy;
+        self
+    }
+}
+
+impl ToolRegistry {
+    /// Register a tool for every definition in `spec`, with handlers from
+    /// `binder`. Nothing is registered if the spec is invalid or, under
+    /// [`UnboundPolicy::Error`], if any name has no handler.
+    pub fn register_from_openai_spec(
+        &mut self,
+        spec: &JsonValue,
+        mut binder: ToolBinder,
+    ) -> Result<SpecImport, ToolSpecError> {
+        let specs = ToolSpec::parse_all(spec)?;
+        if binder.unbound == UnboundPolicy::Error {
+            let missing: Vec<String> = specs
+                .iter()
+                .filter(|s| !binder.bindings.contains_key(&s.name))
+                .map(|s| s.name.clone())
+                .collect();
+            if !missing.is_empty() {
+                return Err(ToolSpecError::Unbound(missing));
+            }
+        }
+
+        let mut import = SpecImport::default();
+        for spec in specs {
+            match binder.bindings.remove(&spec.name) {
+                Some(Binding::Native(handler)) => {
+                    import.bound.push(spec.name.clone());
+                    let mut tool = NativeTool::with_context(
+                        spec.name,
+                        spec.description,
+                        spec.parameters,
+                        move |params, ctx| handler(params, ctx),
+                    );
+                    if let Some(strict) = spec.strict {
+                        tool = tool.with_strict(strict);
+                    }
+                    self.register(tool);
+                }
+                Some(Binding::Mcp {
+                    client,
+                    remote_name,
+                }) => {
+                    import.bound.push(spec.name.clone());
+                    let remote_name = remote_name.unwrap_or_else(|| spec.name.clone());
+                    let mut tool =
+                        McpTool::new(spec.name, spec.description, spec.parameters, client)
+                            .with_remote_name(remote_name);
+                    if let Some(strict) = spec.strict {
+                        tool = tool.with_strict(strict);
+                    }
+                    self.register(tool);
+                }
+                Some(Binding::Http(endpoint)) => {
+                    import.bound.push(spec.name.clone());
+                    self.register(HttpTool {
+                        spec,
+                        endpoint,
+                        http: reqwest::Client::new(),
+                    });
+                }
+                None => {
+                    import.stubbed.push(spec.name.clone());
+                    self.register(StubTool { spec });
+                }
+            }
+        }
+        Ok(import)
+    }
+
+    /// The registry's tools as an OpenAI `tools` array, sorted by name
+    pub fn to_openai_spec(&self) -> JsonValue {
+        JsonValue::Array(self.list_tools())
+    }
+}
+
+/// A spec entry without a handler
+struct StubTool {
+    spec: ToolSpec,
+}
+
+#[async_trait]
+impl Tool for StubTool {
+    fn name(&self) -> &str {
+        &self.spec.name
+    }
+
+    fn description(&self) -> &str {
+        &self.spec.description
+    }
+
+    fn input_schema(&self) -> JsonValue {
+        self.spec.parameters.clone()
+    }
+
+    async fn execute(&self, _params: HashMap<String, JsonValue>) -> ToolExecutionResult {
+        Err(ToolError::ExecutionFailed(format!(
+            "'{}' is not implemented yet",
+            self.spec.name
+        )))
+    }
+
+    fn strict(&self) -> Option<bool> {
+        self.spec.strict
+    }
+}
+
+/// An HTTP endpoint a tool call is forwarded to
+///
+/// `{arg}` placeholders in the URL are replaced with the percent-encoded
+/// argument. Arguments not used in the URL go into the query string for
+/// `GET` and `DELETE`, and into a JSON body otherwise. The response body is
+/// the tool's output, parsed as JSON when it is JSON.
+#[derive(Debug, Clone)]
+pub struct HttpEndpoint {
+    pub method: reqwest::Method,
+    pub url_template: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl HttpEndpoint {
+    pub fn new(method: reqwest::Method, url_template: impl Into<String>) -> Self {
+        Self {
+            method,
+            url_template: url_template.into(),
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn get(url_template: impl Into<String>) -> Self {
+        Self::new(reqwest::Method::GET, url_template)
+    }
+
+    pub fn post(url_template: impl Into<String>) -> Self {
+        Self::new(reqwest::Method::POST, url_template)
+    }
+
+    /// Send a header with every request (builder-style)
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// The URL with placeholders filled, and the arguments left over
+    fn render(
+        &self,
+        mut params: HashMap<String, JsonValue>,
+    ) -> Result<(String, HashMap<String, JsonValue>), ToolError> {
+        let mut url = String::with_capacity(self.url_template.len());
+        let mut rest = self.url_template.as_str();
+        while let Some(open) = rest.find('{') {
+            let close = rest[open..].find('}').map(|i| open + i).ok_or_else(|| {
+                ToolError::ExecutionFailed(format!(
+                    "unclosed placeholder in '{}'",
+                    self.url_template
+                ))
+            })?;
+            let name = &rest[open + 1..close];
+            let value = params.remove(name).ok_or_else(|| {
+                ToolError::InvalidParameters(format!("missing '{}' parameter", name))
+            })?;
+            url.push_str(&rest[..open]);
+            url.push_str(&percent_encode(&argument_text(&value)));
+            rest = &rest[close + 1..];
+        }
+        url.push_str(rest);
+        Ok((url, params))
+    }
+}
+
+/// An argument as it appears in a URL: strings bare, the rest as JSON
+fn argument_text(value: &JsonValue) -> String {
+    match value {
+        JsonValue::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// A spec entry forwarded to an [`HttpEndpoint`]
+struct HttpTool {
+    spec: ToolSpec,
+    endpoint: HttpEndpoint,
+    http: reqwest::Client,
+}
+
+impl HttpTool {
+    async fn call(&self, params: HashMap<String, JsonValue>) -> ToolExecutionResult {
+        let start = std::time::Instant::now();
+        let (url, rest) = self.endpoint.render(params)?;
+
+        let mut request = self.http.request(self.endpoint.method.clone(), &url);
+        for (name, value) in &self.endpoint.headers {
+            request = request.header(name, value);
+        }
+        if !rest.is_empty() {
+            request = match self.endpoint.method {
+                reqwest::Method::GET | reqwest::Method::DELETE => {
+                    let mut query: Vec<(String, String)> = rest
+                        .iter()
+                        .map(|(k, v)| (k.clone(), argument_text(v)))
+                        .collect();
+                    query.sort();
+                    request.query(&query)
+                }
+                _ => request.json(&rest),
+            };
+        }
+
+        let response = request.send().await.map_err(|e| {
+            ToolError::Transient(format!("{} {}: {}", self.endpoint.method, url, e))
+        })?;
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| ToolError::Transient(e.to_string()))?;
+        if !status.is_success() {
+            let message = format!(
+                "{} {} returned {}: {}",
+                self.endpoint.method, url, status, body
+            );
+            return Err(if status.is_server_error() {
+                ToolError::Transient(message)
+            } else {
+                ToolError::ExecutionFailed(message)
+            });
+        }
+
+        let output = serde_json::from_str(&body).unwrap_or(JsonValue::String(body));
+        Ok(ToolResult::success(
+            output,
+            start.elapsed().as_secs_f64() * 1000.0,
+        ))
+    }
+}
+
+#[async_trait]
+impl Tool for HttpTool {
+    fn name(&self) -> &str {
+        &self.spec.name
+    }
+
+    fn description(&self) -> &str {
+        &self.spec.description
+    }
+
+    fn input_schema(&self) -> JsonValue {
+        self.spec.parameters.clone()
+    }
+
+    async fn execute(&self, params: HashMap<String, JsonValue>) -> ToolExecutionResult {
+        self.call(params).await
+    }
+
+    async fn execute_with_context(
+        &self,
+        params: HashMap<String, JsonValue>,
+        ctx: &ToolRunContext,
+    ) -> ToolExecutionResult {
+        tokio::select! {
+            result = self.call(params) => result,
+            _ = ctx.cancellation.cancelled() => Err(ToolError::Canceled(format!(
+                "HTTP call for '{}' abandoned",
+                self.spec.name
+            ))),
+        }
+    }
+
+    fn strict(&self) -> Option<bool> {
+        self.spec.strict
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_url_placeholders() {
+        let endpoint = HttpEndpoint::get("https://api.example.com/cities/{city}/weather");
+        let params: HashMap<String, JsonValue> = [
+            ("city".to_string(), json!("São Paulo")),
+            ("units".to_string(), json!("metric")),
+        ]
+        .into();
+
+        let (url, rest) = endpoint.render(params).unwrap();
+        assert_eq!(
+            url,
+            "https://api.example.com/cities/S%C3%A3o%20Paulo/weather"
+        );
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest["units"], "metric");
+
+        assert!(matches!(
+            endpoint.render(HashMap::new()),
+            Err(ToolError::InvalidParameters(m)) if m == "missing 'city' parameter"
+        ));
+    }
+}
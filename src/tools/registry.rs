@@ -1,8 +1,15 @@
+use crate::error::InputViolation;
+use crate::runtime::retry::RetryPolicy;
+use crate::schema::InputSchema;
+use crate::tools::cache::{call_key, ToolCache, ToolCacheConfig};
+use crate::tools::context::ToolRunContext;
 use crate::types::{ToolError, ToolExecutionResult};
 use async_trait::async_trait;
 use serde_json::Value as JsonValue;
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 /// Tool trait that all tools must implement
 #[async_trait]
@@ -18,24 +25,168 @@ pub trait Tool: Send + Sync {
 
     /// Execute the tool with given parameters
     async fn execute(&self, params: HashMap<String, JsonValue>) -> ToolExecutionResult;
+
+    /// Execute with access to run-scoped services (artifacts, etc.)
+    ///
+    /// Defaults to `execute`; override when the tool needs the context.
+    async fn execute_with_context(
+        &self,
+        params: HashMap<String, JsonValue>,
+        _ctx: &ToolRunContext,
+    ) -> ToolExecutionResult {
+        self.execute(params).await
+    }
+
+    /// How to retry this tool's transient failures
+    ///
+    /// `None` (the default) uses the agent's `tool_retry` policy.
+    fn retry_policy(&self) -> Option<RetryPolicy> {
+        None
+    }
+
+    /// Longest one call may run before it is abandoned
+    ///
+    /// `None` (the default) uses the agent's `default_tool_timeout`.
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Whether a call can change anything outside the agent (write files,
+    /// send messages, charge cards)
+    ///
+    /// Defaults to true. Only tools returning false are run speculatively;
+    /// see [`SpeculativePrefetcher`](crate::agent::SpeculativePrefetcher).
+    fn side_effecting(&self) -> bool {
+        true
+    }
+
+    /// Whether the registry checks arguments against `input_schema` before
+    /// calling the tool
+    ///
+    /// Defaults to true. Violations fail the call with
+    /// `ToolError::InvalidParameters` listing each one, without running it.
+    fn validates_arguments(&self) -> bool {
+        true
+    }
+
+    /// The call's arguments as shown in its `Tool` `Started` event, with
+    /// secrets withheld
+    ///
+    /// `None` (the default) shows them unchanged.
+    fn redact_arguments(&self, _arguments: &JsonValue) -> Option<JsonValue> {
+        None
+    }
+
+    /// Whether the registry's [tool cache](ToolRegistry::with_cache) may
+    /// answer a call with an earlier result for the same arguments
+    ///
+    /// Defaults to true; tools whose results change between calls (clocks,
+    /// random numbers, counters) return false.
+    fn cacheable(&self) -> bool {
+        true
+    }
+
+    /// Whether the LLM must keep to `input_schema` exactly, sent as the
+    /// definition's `strict` flag
+    ///
+    /// `None` (the default) leaves the flag out. Providers without it
+    /// ignore it.
+    fn strict(&self) -> Option<bool> {
+        None
+    }
 }
 
 /// Registry for managing tools
 ///
 /// The registry stores all available tools and provides methods to
 /// list, query, and execute them.
+///
+/// Tools can be added and removed while agents are running with it (e.g.
+/// as MCP servers connect and disconnect): [`add`](Self::add),
+/// [`merge`](Self::merge) and [`unregister`](Self::unregister) take `&self`,
+/// and each change bumps [`generation`](Self::generation), which agents
+/// check before every LLM request.
+///
+/// [`with_cache`](Self::with_cache) keeps successful results so calls
+/// repeated with the same arguments, by any agent using the registry, don't
+/// run the tool again.
 pub struct ToolRegistry {
-    tools: HashMap<String, Arc<dyn Tool>>,
+    entries: RwLock<Entries>,
+    /// Bumped on every change
+    generation: AtomicU64,
+    /// A registry whose tools are offered ahead of this one's, changes
+    /// included
+    shared: Option<Arc<ToolRegistry>>,
+    /// Results of earlier calls to this registry's own tools
+    cache: Option<Arc<ToolCache>>,
+}
+
+#[derive(Default)]
+struct Entries {
+    tools: HashMap<String, Entry>,
+    /// Names unregistered since they were last registered
+    removed: HashSet<String>,
+}
+
+#[derive(Clone)]
+struct Entry {
+    tool: Arc<dyn Tool>,
+    /// Argument schema, prepared at registration, if the tool validates
+    schema: Option<Arc<InputSchema>>,
+}
+
+impl Entry {
+    fn new(tool: Arc<dyn Tool>) -> Self {
+        let schema = tool
+            .validates_arguments()
+            .then(|| Arc::new(InputSchema::strict(tool.input_schema())));
+        Self { tool, schema }
+    }
 }
 
 impl ToolRegistry {
     /// Create a new empty tool registry
     pub fn new() -> Self {
         Self {
-            tools: HashMap::new(),
+            entries: RwLock::new(Entries::default()),
+            generation: AtomicU64::new(0),
+            shared: None,
+            cache: None,
         }
     }
 
+    /// Keep successful results of calls to this registry's tools, as
+    /// `config` says, and answer repeated calls with them
+    ///
+    /// Keys are the tool name and the arguments with object keys sorted. A
+    /// hit has [`ToolResult::cached`](crate::types::ToolResult::cached) set
+    /// and the lookup's duration. Errors aren't kept, nor results of tools
+    /// whose [`Tool::cacheable`] is false. Subsets share the cache.
+    pub fn with_cache(mut self, config: ToolCacheConfig) -> Self {
+        self.cache = Some(Arc::new(ToolCache::new(config)));
+        self
+    }
+
+    /// Drop every cached result of tool `name`, returning how many there
+    /// were
+    pub fn invalidate_tool(&self, name: &str) -> usize {
+        self.cache
+            .as_ref()
+            .map_or(0, |cache| cache.invalidate_tool(name))
+            + self
+                .shared
+                .as_ref()
+                .map_or(0, |shared| shared.invalidate_tool(name))
+    }
+
+    /// This registry with every tool of `shared` on top, kept up to date
+    /// as `shared` changes; its tools replace this one's of the same name
+    #[cfg(feature = "workflow")]
+    pub(crate) fn beneath(mut self, shared: Arc<ToolRegistry>) -> Self {
+        self.shared = Some(shared);
+        self
+    }
+
     /// Register a tool
     ///
     /// # Arguments
@@ -44,46 +195,193 @@ impl ToolRegistry {
     /// # Returns
     /// * `&mut Self` - For method chaining
     pub fn register(&mut self, tool: impl Tool + 'static) -> &mut Self {
-        let name = tool.name().to_string();
-        self.tools.insert(name, Arc::new(tool));
+        self.add(tool);
+        self
+    }
+
+    /// Register a tool in a registry that may already be in use, replacing
+    /// any tool of the same name
+    ///
+    /// Agents running with the registry offer it from their next LLM
+    /// request.
+    pub fn add(&self, tool: impl Tool + 'static) -> &Self {
+        let tool: Arc<dyn Tool> = Arc::new(tool);
+        self.insert(vec![(tool.name().to_string(), Entry::new(tool))]);
         self
     }
 
+    /// Share every tool of `other` in one change, replacing tools of the
+    /// same name
+    ///
+    /// Agents running with this registry see all of them from the same LLM
+    /// request, or none.
+    pub fn merge(&self, other: &ToolRegistry) -> &Self {
+        self.insert(other.snapshot().into_iter().collect());
+        self
+    }
+
+    fn insert(&self, added: Vec<(String, Entry)>) {
+        if added.is_empty() {
+            return;
+        }
+        let mut entries = self.entries.write().unwrap();
+        for (name, entry) in added {
+            entries.removed.remove(&name);
+            entries.tools.insert(name, entry);
+        }
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Remove tool `name`, returning whether it was registered
+    ///
+    /// Agents running with the registry stop offering it from their next
+    /// LLM request; a call the LLM already made fails with
+    /// [`ToolError::Unavailable`], which it is told about.
+    pub fn unregister(&self, name: &str) -> bool {
+        let mut entries = self.entries.write().unwrap();
+        if entries.tools.remove(name).is_none() {
+            return false;
+        }
+        entries.removed.insert(name.to_string());
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        true
+    }
+
+    /// Counts changes to the registry: it differs from an earlier reading
+    /// iff tools were registered or removed since
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+            + self.shared.as_ref().map_or(0, |shared| shared.generation())
+    }
+
+    fn entry(&self, name: &str) -> Option<Entry> {
+        if let Some(entry) = self.shared.as_ref().and_then(|shared| shared.entry(name)) {
+            return Some(entry);
+        }
+        self.entries.read().unwrap().tools.get(name).cloned()
+    }
+
+    /// The cache of the registry tool `name` belongs to
+    fn cache_for(&self, name: &str) -> Option<Arc<ToolCache>> {
+        match &self.shared {
+            Some(shared) if shared.entry(name).is_some() => shared.cache_for(name),
+            _ => self.cache.clone(),
+        }
+    }
+
+    /// Tool `tool` called with `params` through the registry's cache, by
+    /// `execute`
+    async fn call_cached<F>(
+        &self,
+        tool: Arc<dyn Tool>,
+        params: HashMap<String, JsonValue>,
+        execute: impl FnOnce(Arc<dyn Tool>, HashMap<String, JsonValue>) -> F,
+    ) -> ToolExecutionResult
+    where
+        F: std::future::Future<Output = ToolExecutionResult>,
+    {
+        let cache = match self.cache_for(tool.name()) {
+            Some(cache) if tool.cacheable() => cache,
+            _ => return execute(tool, params).await,
+        };
+        let key = call_key(
+            tool.name(),
+            &JsonValue::Object(params.clone().into_iter().collect()),
+        );
+        if let Some(hit) = cache.get(&key) {
+            return Ok(hit);
+        }
+        let name = tool.name().to_string();
+        let result = execute(tool, params).await?;
+        cache.put(&name, &key, &result);
+        Ok(result)
+    }
+
+    fn was_removed(&self, name: &str) -> bool {
+        self.entries.read().unwrap().removed.contains(name)
+            || self
+                .shared
+                .as_ref()
+                .is_some_and(|shared| shared.was_removed(name))
+    }
+
+    /// Every tool, by name
+    fn snapshot(&self) -> HashMap<String, Entry> {
+        let mut tools = self.entries.read().unwrap().tools.clone();
+        if let Some(shared) = &self.shared {
+            tools.extend(shared.snapshot());
+        }
+        tools
+    }
+
     /// Get a tool by name
-    pub fn get(&self, name: &str) -> Option<&Arc<dyn Tool>> {
-        self.tools.get(name)
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.entry(name).map(|entry| entry.tool)
     }
 
     /// List all tool names
     pub fn list_names(&self) -> Vec<String> {
-        self.tools.keys().cloned().collect()
+        self.snapshot().into_keys().collect()
     }
 
-    /// List all tools with their schemas (for LLM function calling)
+    /// List all tools with their schemas (for LLM function calling), sorted
+    /// by name so the list is stable between calls
     pub fn list_tools(&self) -> Vec<JsonValue> {
-        self.tools
-            .values()
+        let mut tools: Vec<Arc<dyn Tool>> = self
+            .snapshot()
+            .into_values()
+            .map(|entry| entry.tool)
+            .collect();
+        tools.sort_by(|a, b| a.name().cmp(b.name()));
+        tools
+            .into_iter()
             .map(|tool| {
-                serde_json::json!({
+                let mut definition = serde_json::json!({
                     "type": "function",
                     "function": {
                         "name": tool.name(),
                         "description": tool.description(),
                         "parameters": tool.input_schema(),
                     }
-                })
+                });
+                if let Some(strict) = tool.strict() {
+                    definition["function"]["strict"] = strict.into();
+                }
+                definition
             })
             .collect()
     }
 
-    /// Call a tool by name with the given parameters
-    pub async fn call_tool(
+    /// Check `params` against the input schema of tool `name`, returning
+    /// every violation
+    ///
+    /// Passes for unknown tools and tools that skip validation.
+    pub fn validate_arguments(
         &self,
         name: &str,
-        params: HashMap<String, JsonValue>,
-    ) -> ToolExecutionResult {
-        match self.tools.get(name) {
-            Some(tool) => tool.execute(params).await,
+        params: &HashMap<String, JsonValue>,
+    ) -> Result<(), Vec<InputViolation>> {
+        let Some(schema) = self.entry(name).and_then(|entry| entry.schema) else {
+            return Ok(());
+        };
+        let arguments = JsonValue::Object(params.clone().into_iter().collect());
+        schema.validate(&arguments).map(|_| ())
+    }
+
+    /// Tool `name`, with its arguments checked
+    fn resolve(
+        &self,
+        name: &str,
+        params: &HashMap<String, JsonValue>,
+    ) -> Result<Arc<dyn Tool>, ToolError> {
+        self.validate_arguments(name, params)
+            .map_err(|violations| invalid_arguments(&violations))?;
+        match self.get(name) {
+            Some(tool) => Ok(tool),
+            None if self.was_removed(name) => Err(ToolError::Unavailable(format!(
+                "'{}' was unregistered",
+                name
+            ))),
             None => Err(ToolError::InvalidParameters(format!(
                 "Tool not found: {}",
                 name
@@ -91,20 +389,86 @@ impl ToolRegistry {
         }
     }
 
+    /// Call a tool by name with the given parameters
+    pub async fn call_tool(
+        &self,
+        name: &str,
+        params: HashMap<String, JsonValue>,
+    ) -> ToolExecutionResult {
+        let tool = self.resolve(name, &params)?;
+        self.call_cached(tool, params, |tool, params| async move {
+            tool.execute(params).await
+        })
+        .await
+    }
+
+    /// Call a tool by name with a run context
+    pub async fn call_tool_with_context(
+        &self,
+        name: &str,
+        params: HashMap<String, JsonValue>,
+        ctx: &ToolRunContext,
+    ) -> ToolExecutionResult {
+        let tool = self.resolve(name, &params)?;
+        self.call_cached(tool, params, |tool, params| async move {
+            tool.execute_with_context(params, ctx).await
+        })
+        .await
+    }
+
     /// Check if a tool exists
     pub fn has_tool(&self, name: &str) -> bool {
-        self.tools.contains_key(name)
+        self.entry(name).is_some()
     }
 
     /// Get the number of registered tools
     pub fn len(&self) -> usize {
-        self.tools.len()
+        match &self.shared {
+            Some(_) => self.snapshot().len(),
+            None => self.entries.read().unwrap().tools.len(),
+        }
     }
 
     /// Check if the registry is empty
     pub fn is_empty(&self) -> bool {
-        self.tools.is_empty()
+        self.len() == 0
     }
+
+    /// A registry sharing just the named tools; names this registry
+    /// doesn't have are skipped
+    pub fn subset<S: AsRef<str>>(&self, names: &[S]) -> ToolRegistry {
+        let subset = ToolRegistry {
+            cache: self.cache.clone(),
+            ..ToolRegistry::new()
+        };
+        subset.insert(
+            names
+                .iter()
+                .filter_map(|name| {
+                    let name = name.as_ref();
+                    Some((name.to_string(), self.entry(name)?))
+                })
+                .collect(),
+        );
+        subset
+    }
+}
+
+/// `ToolError::InvalidParameters` listing `violations`, e.g.
+/// "/a: expected number, found string; /b: required property is missing"
+pub(crate) fn invalid_arguments(violations: &[InputViolation]) -> ToolError {
+    let listed: Vec<String> = violations
+        .iter()
+        .map(|v| {
+            let pointer = if v.pointer.is_empty() {
+                "/"
+            } else {
+                &v.pointer
+            };
+            format!("{}: {}", pointer, v.message)
+        })
+        .collect();
+    ToolError::InvalidParameters(listed.join("; "))
 }
 
 impl Default for ToolRegistry {
@@ -115,9 +479,13 @@ impl Default for ToolRegistry {
 
 impl std::fmt::Debug for ToolRegistry {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut names = self.list_names();
+        names.sort_unstable();
         f.debug_struct("ToolRegistry")
-            .field("tool_count", &self.tools.len())
-            .field("tools", &self.tools.keys().collect::<Vec<_>>())
+            .field("tool_count", &names.len())
+            .field("tools", &names)
+            .field("generation", &self.generation())
+            .field("cached", &self.cache.is_some())
             .finish()
     }
 }
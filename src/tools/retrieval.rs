@@ -0,0 +1,225 @@
+//! Retrieval over an in-memory vector store.
+//!
+//! [`VectorStore`] keeps (text, embedding) pairs and ranks them by cosine
+//! similarity. [`VectorSearchTool`] embeds a query with an
+//! [`EmbeddingClient`] and returns the closest texts, as the read-only
+//! `vector_search` [`NativeTool`].
+
+use crate::llm::embeddings::EmbeddingClient;
+use crate::llm::LlmResult;
+use crate::tools::native::NativeTool;
+use crate::tools::registry::ToolRegistry;
+use crate::types::{ToolError, ToolExecutionResult, ToolResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+/// Default for [`VectorSearchTool::with_top_k`]
+pub const DEFAULT_TOP_K: usize = 4;
+
+/// Cosine similarity of `a` and `b`, in `[-1, 1]`; 0 if either is all
+/// zeros or their lengths differ
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let (mut dot, mut norm_a, mut norm_b) = (0.0f32, 0.0f32, 0.0f32);
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+/// A stored text found by a search
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub text: String,
+    pub score: f32,
+}
+
+/// (text, embedding) pairs, searched by cosine similarity
+#[derive(Debug, Default)]
+pub struct VectorStore {
+    entries: RwLock<Vec<(String, Vec<f32>)>>,
+}
+
+impl VectorStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&self, text: impl Into<String>, embedding: Vec<f32>) {
+        self.entries.write().unwrap().push((text.into(), embedding));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The `k` texts closest to `query`, best first
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<SearchHit> {
+        let entries = self.entries.read().unwrap();
+        let mut hits: Vec<SearchHit> = entries
+            .iter()
+            .map(|(text, embedding)| SearchHit {
+                text: text.clone(),
+                score: cosine_similarity(query, embedding),
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(k);
+        hits
+    }
+}
+
+/// Searches a [`VectorStore`] by meaning, as the `vector_search` tool
+///
+/// ```rust,ignore
+/// let search = VectorSearchTool::new(embeddings, Arc::new(VectorStore::new()));
+/// search.add_texts(documents).await?;
+/// search.register(&mut registry);
+/// ```
+#[derive(Clone)]
+pub struct VectorSearchTool {
+    client: Arc<dyn EmbeddingClient>,
+    store: Arc<VectorStore>,
+    top_k: usize,
+}
+
+impl VectorSearchTool {
+    pub const NAME: &'static str = "vector_search";
+
+    pub fn new(client: Arc<dyn EmbeddingClient>, store: Arc<VectorStore>) -> Self {
+        Self {
+            client,
+            store,
+            top_k: DEFAULT_TOP_K,
+        }
+    }
+
+    /// Most results a call returns, unless it asks for fewer with `k`
+    pub fn with_top_k(mut self, k: usize) -> Self {
+        self.top_k = k;
+        self
+    }
+
+    pub fn store(&self) -> &Arc<VectorStore> {
+        &self.store
+    }
+
+    /// Embed `texts` and add them to the store
+    pub async fn add_texts(&self, texts: Vec<String>) -> LlmResult<()> {
+        let embeddings = self.client.embed(texts.clone()).await?;
+        for (text, embedding) in texts.into_iter().zip(embeddings) {
+            self.store.insert(text, embedding);
+        }
+        Ok(())
+    }
+
+    /// The stored texts closest to `query`, best first
+    pub async fn search(&self, query: &str, k: usize) -> Result<Vec<SearchHit>, ToolError> {
+        let embedding = self
+            .client
+            .embed(vec![query.to_string()])
+            .await
+            .map_err(|e| {
+                if e.is_retryable() {
+                    ToolError::transient(format!("embedding failed: {}", e))
+                } else {
+                    ToolError::ExecutionFailed(format!("embedding failed: {}", e))
+                }
+            })?
+            .pop()
+            .ok_or_else(|| ToolError::ExecutionFailed("no embedding for the query".into()))?;
+        Ok(self.store.search(&embedding, k))
+    }
+
+    async fn call(&self, params: HashMap<String, JsonValue>) -> ToolExecutionResult {
+        let start = Instant::now();
+        let query = params
+            .get("query")
+            .and_then(JsonValue::as_str)
+            .ok_or_else(|| ToolError::InvalidParameters("missing 'query' parameter".into()))?;
+        let k = params
+            .get("k")
+            .and_then(JsonValue::as_u64)
+            .map_or(self.top_k, |k| (k as usize).min(self.top_k));
+
+        let hits = self.search(query, k).await?;
+        Ok(ToolResult::success(
+            serde_json::json!({ "results": hits }),
+            start.elapsed().as_secs_f64() * 1000.0,
+        ))
+    }
+
+    /// The `vector_search` tool
+    pub fn tool(&self) -> NativeTool {
+        let search = self.clone();
+        NativeTool::new(
+            Self::NAME,
+            "Finds the stored passages most similar in meaning to a query",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "What to look for" },
+                    "k": { "type": "integer", "minimum": 1, "description": "Most passages to return" }
+                },
+                "required": ["query"]
+            }),
+            move |params| {
+                let search = search.clone();
+                async move { search.call(params).await }
+            },
+        )
+        .read_only()
+    }
+
+    /// Register the tool in `registry`
+    pub fn register(&self, registry: &mut ToolRegistry) {
+        registry.register(self.tool());
+    }
+}
+
+impl std::fmt::Debug for VectorSearchTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VectorSearchTool")
+            .field("model", &self.client.model())
+            .field("entries", &self.store.len())
+            .field("top_k", &self.top_k)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_ranks_by_cosine_similarity() {
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]), 1.0);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[-1.0, 0.0]), -1.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+
+        let store = VectorStore::new();
+        store.insert("east", vec![1.0, 0.0]);
+        store.insert("north", vec![0.0, 1.0]);
+        store.insert("north-east", vec![1.0, 1.0]);
+        let hits = store.search(&[1.0, 0.2], 2);
+        let texts: Vec<&str> = hits.iter().map(|hit| hit.text.as_str()).collect();
+        assert_eq!(texts, vec!["east", "north-east"]);
+        assert!(hits[0].score > hits[1].score);
+    }
+}
@@ -0,0 +1,316 @@
+//! Keeping oversized tool results out of the conversation
+//!
+//! Before a tool's output goes back to the LLM, agents cap it at
+//! `AgentConfig::max_tool_result_bytes`. By default the output is cut down
+//! with [`truncate_tool_result`], which keeps its shape: arrays keep their
+//! first items and say how many were dropped, objects keep their first keys,
+//! and long strings keep their head and tail. A [`ToolResultTransformer`]
+//! replaces that, e.g. to summarize the output with an LLM.
+
+use async_trait::async_trait;
+
+use crate::types::JsonValue;
+
+/// Default for `AgentConfig::max_tool_result_bytes`: 32 KiB, roughly 8k
+/// tokens
+pub const DEFAULT_MAX_TOOL_RESULT_BYTES: usize = 32 * 1024;
+
+/// Largest result a `Tool` `Completed` event carries; larger ones are
+/// referenced by `result_sha256` and `result_bytes` instead
+pub const MAX_EVENT_RESULT_BYTES: usize = 1024 * 1024;
+
+/// Rounds of shrinking before falling back to cutting the serialized text
+const MAX_PASSES: usize = 16;
+
+/// Compacts tool outputs too large to send to the LLM
+///
+/// Called only for outputs that serialize to more than `max_bytes`. The
+/// returned text is what the LLM sees; it is cut to `max_bytes` if it is
+/// still longer.
+#[async_trait]
+pub trait ToolResultTransformer: Send + Sync {
+    async fn compact(&self, tool_name: &str, output: &JsonValue, max_bytes: usize) -> String;
+}
+
+/// `output` serialized, cut down to at most `max_bytes` while keeping its
+/// structure, followed by a line giving the original size
+///
+/// Outputs that already fit are returned serialized as they are.
+pub fn truncate_tool_result(output: &JsonValue, max_bytes: usize) -> String {
+    let full = serialize(output);
+    if full.len() <= max_bytes {
+        return full;
+    }
+    let note = format!("\n[truncated; the full result was {} bytes]", full.len());
+    let budget = max_bytes.saturating_sub(note.len());
+
+    let mut value = output.clone();
+    for _ in 0..MAX_PASSES {
+        let size = json_len(&value);
+        if size <= budget {
+            return serialize(&value) + &note;
+        }
+        if !shrink(&mut value, size - budget) {
+            break;
+        }
+    }
+    cut_text(&serialize(&value), budget) + &note
+}
+
+/// `text` with its middle replaced by a marker so it fits in `max_bytes`
+pub(crate) fn cut_text(text: &str, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+    // Sized for the longest count the marker can show
+    let marker_len = marker(text.len()).len();
+    let keep = max_bytes.saturating_sub(marker_len);
+    let head = floor_char_boundary(text, keep * 2 / 3);
+    let tail = ceil_char_boundary(text, text.len() - (keep - head));
+    format!("{}{}{}", &text[..head], marker(tail - head), &text[tail..])
+}
+
+fn marker(removed: usize) -> String {
+    format!("...[{} bytes truncated]...", removed)
+}
+
+const ITEMS_MARKER: &str = "...truncated, ";
+
+/// Make `value` about `excess` bytes smaller, working on its largest part;
+/// false if it can't get any smaller
+fn shrink(value: &mut JsonValue, excess: usize) -> bool {
+    match value {
+        JsonValue::String(text) => {
+            let target = text.len().saturating_sub(excess);
+            let cut = cut_text(text, target);
+            if cut.len() >= text.len() {
+                return false;
+            }
+            *text = cut;
+            true
+        }
+        JsonValue::Array(items) => shrink_array(items, excess),
+        JsonValue::Object(map) => {
+            let size = 2
+                + map.iter().map(|(k, v)| entry_len(k, v)).sum::<usize>()
+                + map.len().saturating_sub(1);
+            let largest = map
+                .iter()
+                .map(|(key, value)| (key.clone(), json_len(value)))
+                .max_by_key(|(_, len)| *len);
+            let Some((key, len)) = largest else {
+                return false;
+            };
+            // One big field (e.g. an HTTP body) gets cut; otherwise drop
+            // keys from the end
+            if (len * 2 > size || map.len() == 1)
+                && shrink(map.get_mut(&key).expect("just found"), excess)
+            {
+                return true;
+            }
+            shrink_object(map, size.saturating_sub(excess))
+        }
+        _ => false,
+    }
+}
+
+fn shrink_array(items: &mut Vec<JsonValue>, excess: usize) -> bool {
+    // Items dropped by an earlier pass
+    let dropped = match items.last() {
+        Some(JsonValue::String(last)) => last
+            .strip_prefix(ITEMS_MARKER)
+            .and_then(|rest| rest.strip_suffix(" more items"))
+            .and_then(|count| count.parse::<usize>().ok()),
+        _ => None,
+    };
+    if dropped.is_some() {
+        items.pop();
+    }
+    let sizes: Vec<usize> = items.iter().map(json_len).collect();
+    let size = 2 + sizes.iter().sum::<usize>() + sizes.len().saturating_sub(1);
+
+    // One item dominating the array gets cut instead
+    if let Some((index, &largest)) = sizes.iter().enumerate().max_by_key(|(_, len)| **len) {
+        if largest * 2 > size && shrink(&mut items[index], excess) {
+            if let Some(dropped) = dropped {
+                items.push(items_marker(dropped));
+            }
+            return true;
+        }
+    }
+
+    let target = size.saturating_sub(excess);
+    let removed_total = |keep: usize| items.len() - keep + dropped.unwrap_or(0);
+    let mut keep = 0;
+    let mut used = 2 + json_len(&items_marker(removed_total(0)));
+    while keep < items.len() && used + sizes[keep] < target {
+        used += sizes[keep] + 1;
+        keep += 1;
+    }
+    if keep == items.len() {
+        if let Some(dropped) = dropped {
+            items.push(items_marker(dropped));
+        }
+        return false;
+    }
+    let total = removed_total(keep);
+    items.truncate(keep);
+    items.push(items_marker(total));
+    true
+}
+
+fn items_marker(count: usize) -> JsonValue {
+    JsonValue::String(format!("{}{} more items", ITEMS_MARKER, count))
+}
+
+fn shrink_object(map: &mut serde_json::Map<String, JsonValue>, target: usize) -> bool {
+    const KEYS_MARKER: &str = "...";
+    let previously = map
+        .remove(KEYS_MARKER)
+        .and_then(|note| {
+            note.as_str()?
+                .strip_prefix("truncated, ")?
+                .strip_suffix(" more keys")?
+                .parse::<usize>()
+                .ok()
+        })
+        .unwrap_or(0);
+    let note = |count: usize| JsonValue::String(format!("truncated, {} more keys", count));
+
+    let mut used = 2 + entry_len(KEYS_MARKER, &note(map.len() + previously));
+    let mut keep = 0;
+    for (key, value) in map.iter() {
+        let len = entry_len(key, value) + 1;
+        if used + len > target {
+            break;
+        }
+        used += len;
+        keep += 1;
+    }
+    if keep == map.len() {
+        if previously > 0 {
+            map.insert(KEYS_MARKER.to_string(), note(previously));
+        }
+        return false;
+    }
+    let removed = map.len() - keep + previously;
+    let kept: serde_json::Map<String, JsonValue> =
+        std::mem::take(map).into_iter().take(keep).collect();
+    *map = kept;
+    map.insert(KEYS_MARKER.to_string(), note(removed));
+    true
+}
+
+/// `"key":value`
+fn entry_len(key: &str, value: &JsonValue) -> usize {
+    json_len(&JsonValue::String(key.to_string())) + 1 + json_len(value)
+}
+
+fn serialize(value: &JsonValue) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| value.to_string())
+}
+
+fn json_len(value: &JsonValue) -> usize {
+    serde_json::to_vec(value)
+        .map(|bytes| bytes.len())
+        .unwrap_or(0)
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn ceil_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_small_results_pass_through() {
+        let output = json!({ "ok": true });
+        assert_eq!(truncate_tool_result(&output, 100), r#"{"ok":true}"#);
+    }
+
+    #[test]
+    fn test_arrays_keep_their_first_items() {
+        let output: JsonValue = (0..1000).map(|i| json!({ "id": i })).collect();
+        let text = truncate_tool_result(&output, 1000);
+        assert!(text.len() <= 1000);
+
+        let (json_part, note) = text.split_once('\n').unwrap();
+        let items: Vec<JsonValue> = serde_json::from_str(json_part).unwrap();
+        assert_eq!(items[0], json!({ "id": 0 }));
+        let kept = items.len() - 1;
+        assert_eq!(
+            items[kept],
+            json!(format!("...truncated, {} more items", 1000 - kept))
+        );
+        assert_eq!(
+            note,
+            format!(
+                "[truncated; the full result was {} bytes]",
+                json_len(&output)
+            )
+        );
+    }
+
+    #[test]
+    fn test_strings_keep_head_and_tail() {
+        let body = format!("BEGIN{}END", "x".repeat(10_000));
+        let text = truncate_tool_result(&json!(body), 500);
+        assert!(text.len() <= 500);
+        let (json_part, _) = text.split_once('\n').unwrap();
+        let kept: String = serde_json::from_str(json_part).unwrap();
+        assert!(kept.starts_with("BEGINxxx"));
+        assert!(kept.ends_with("xxxEND"));
+        assert!(kept.contains("bytes truncated]..."));
+    }
+
+    #[test]
+    fn test_large_fields_are_cut_in_place() {
+        let output = json!({
+            "status": 200,
+            "headers": { "content-type": "text/html" },
+            "body": "é".repeat(50_000),
+        });
+        let text = truncate_tool_result(&output, 2000);
+        assert!(text.len() <= 2000);
+        let (json_part, _) = text.split_once('\n').unwrap();
+        let kept: JsonValue = serde_json::from_str(json_part).unwrap();
+        assert_eq!(kept["status"], 200);
+        assert_eq!(kept["headers"]["content-type"], "text/html");
+        assert!(kept["body"].as_str().unwrap().contains("bytes truncated"));
+    }
+
+    #[test]
+    fn test_wide_objects_drop_keys() {
+        let output: serde_json::Map<String, JsonValue> = (0..500)
+            .map(|i| (format!("key{:03}", i), json!(i)))
+            .collect();
+        let text = truncate_tool_result(&JsonValue::Object(output), 600);
+        assert!(text.len() <= 600);
+        let (json_part, _) = text.split_once('\n').unwrap();
+        let kept: serde_json::Map<String, JsonValue> = serde_json::from_str(json_part).unwrap();
+        assert_eq!(kept["key000"], 0);
+        let dropped = 500 - (kept.len() - 1);
+        assert_eq!(kept["..."], format!("truncated, {} more keys", dropped));
+    }
+
+    #[test]
+    fn test_cut_text_respects_char_boundaries() {
+        let text = "日本語".repeat(100);
+        let cut = cut_text(&text, 100);
+        assert!(cut.len() <= 100);
+        assert!(cut.contains("bytes truncated"));
+    }
+}
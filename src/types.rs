@@ -1,3 +1,4 @@
+use crate::artifact::NewArtifact;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -28,6 +29,15 @@ pub struct AgentInput {
     /// conversation context across multiple agent calls.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub chat_history: Option<Vec<crate::llm::types::ChatMessage>>,
+
+    /// Caps enforced on the conversation during this execution
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limits: Option<crate::limits::ConversationLimits>,
+
+    /// Values for the agent's prompt templates (see
+    /// [`crate::agent::prompt`])
+    #[serde(default, skip_serializing_if = "crate::agent::PromptVars::is_empty")]
+    pub vars: crate::agent::PromptVars,
 }
 
 impl AgentInput {
@@ -38,8 +48,11 @@ impl AgentInput {
             metadata: AgentInputMetadata {
                 step_index: 0,
                 previous_agent: None,
+                workflow_id: None,
             },
             chat_history: None,
+            limits: None,
+            vars: Default::default(),
         }
     }
 
@@ -50,17 +63,45 @@ impl AgentInput {
             metadata: AgentInputMetadata {
                 step_index: 0,
                 previous_agent: None,
+                workflow_id: None,
             },
             chat_history: None,
+            limits: None,
+            vars: Default::default(),
         }
     }
 
+    /// Create a new AgentInput asking about `text` and `images`
+    ///
+    /// `data` holds the serialized content parts, so in a workflow the same
+    /// value works as `initial_input` or as a step's output:
+    ///
+    /// ```
+    /// use agent_runtime::llm::ContentPart;
+    /// use agent_runtime::AgentInput;
+    ///
+    /// let input = AgentInput::from_text_and_images(
+    ///     "What is in this picture?",
+    ///     [ContentPart::image_url("https://example.com/otter.jpg")],
+    /// );
+    /// assert_eq!(input.data[1]["type"], "image_url");
+    /// ```
+    pub fn from_text_and_images(
+        text: impl Into<String>,
+        images: impl IntoIterator<Item = crate::llm::ContentPart>,
+    ) -> Self {
+        let message = crate::llm::ChatMessage::user_with_images(text, images);
+        Self::from_value(serde_json::to_value(&message.content).unwrap_or_default())
+    }
+
     /// Create a new AgentInput with metadata
     pub fn with_metadata(data: JsonValue, metadata: AgentInputMetadata) -> Self {
         Self {
             data,
             metadata,
             chat_history: None,
+            limits: None,
+            vars: Default::default(),
         }
     }
 
@@ -87,8 +128,11 @@ impl AgentInput {
             metadata: AgentInputMetadata {
                 step_index: 0,
                 previous_agent: None,
+                workflow_id: None,
             },
             chat_history: Some(messages),
+            limits: None,
+            vars: Default::default(),
         }
     }
 
@@ -101,14 +145,40 @@ impl AgentInput {
             data: serde_json::Value::Null,
             metadata,
             chat_history: Some(messages),
+            limits: None,
+            vars: Default::default(),
         }
     }
+
+    /// Enforce conversation caps while executing
+    pub fn with_limits(mut self, limits: crate::limits::ConversationLimits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Fill the agent's prompt placeholders with these values, ahead of
+    /// workflow memory and the built-ins
+    pub fn with_vars<I, K, V>(mut self, vars: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<JsonValue>,
+    {
+        self.vars
+            .input
+            .extend(vars.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentInputMetadata {
     pub step_index: usize,
     pub previous_agent: Option<String>,
+
+    /// Run the agent executes in; its events are tagged with it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workflow_id: Option<String>,
 }
 
 /// Output data produced by an agent
@@ -132,6 +202,49 @@ pub struct AgentOutputMetadata {
     pub agent_name: String,
     pub execution_time_ms: u64,
     pub tool_calls_count: usize,
+
+    /// How the configured effort level was applied, if one was set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effort: Option<crate::llm::AppliedEffort>,
+
+    /// Where the time in this turn went
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<crate::agent::TurnLatency>,
+
+    /// Conversation caps hit, and handled, during this execution
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub limit_events: Vec<crate::limits::LimitEvent>,
+
+    /// Budget notices delivered to the agent, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub budget_signals: Vec<crate::agent::BudgetSignal>,
+
+    /// Speculative tool executions, when a prefetcher is configured and
+    /// predicted anything
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speculation: Option<crate::agent::SpeculationStats>,
+
+    /// This turn's LLM calls; priced only when run in a workflow, whose
+    /// runtime holds the prices
+    #[serde(default)]
+    pub usage: crate::usage::UsageTotals,
+
+    /// The agent hit `max_tool_iterations` and answered without tools, see
+    /// [`MaxIterationsBehavior::FinalAnswer`](crate::MaxIterationsBehavior::FinalAnswer)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub iterations_exhausted: bool,
+
+    /// Critiques of the answer, when reflection is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reflection: Option<crate::agent::ReflectionReport>,
+
+    /// Guardrail checks run on the input and output, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub guardrails: Vec<crate::agent::GuardrailCheck>,
+    /// The agent ended its turn by handing the conversation to another,
+    /// see [`crate::agent::handoff`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handoff: Option<crate::agent::Handoff>,
 }
 
 /// Result type for agent execution
@@ -148,6 +261,30 @@ pub enum AgentError {
 
     #[error("Execution failed: {0}")]
     ExecutionError(String),
+
+    /// A conversation cap rejected the turn; see
+    /// [`LimitExceeded::user_message`](crate::limits::LimitExceeded::user_message)
+    #[error("{0}")]
+    LimitReached(crate::limits::LimitExceeded),
+
+    /// The run was canceled while a tool was executing
+    #[error("Canceled: {0}")]
+    Canceled(String),
+
+    /// The agent isn't allowed to call this tool (see
+    /// `AgentConfig::allowed_tools`). Fed back to the LLM; not retried.
+    #[error("Not permitted: {0}")]
+    NotPermitted(String),
+
+    /// A guardrail refused the input or output; see
+    /// `AgentConfig::guardrails`
+    #[error("Blocked by guardrail '{guardrail}': {reason}")]
+    Blocked { guardrail: String, reason: String },
+
+    /// The run's budget was spent before the next LLM call; see
+    /// `WorkflowBuilder::with_budget`
+    #[error("{0}")]
+    BudgetExceeded(crate::usage::BudgetExceeded),
 }
 
 /// Tool invocation parameters
@@ -173,6 +310,43 @@ pub enum ToolStatus {
     Error,
 }
 
+/// Why a tool call failed, in a form code can act on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolErrorDetail {
+    /// Short machine-readable code, e.g. `not_found` or `rate_limited`
+    pub code: String,
+    pub message: String,
+    /// Whether running the same call again might succeed
+    #[serde(default)]
+    pub retryable: bool,
+}
+
+impl ToolErrorDetail {
+    pub fn new(code: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            code: code.into(),
+            message: message.into(),
+            retryable: false,
+        }
+    }
+
+    /// Mark the failure as worth retrying (builder-style)
+    pub fn retryable(mut self) -> Self {
+        self.retryable = true;
+        self
+    }
+}
+
+impl From<&ToolError> for ToolErrorDetail {
+    fn from(error: &ToolError) -> Self {
+        Self {
+            code: error.code().to_string(),
+            message: error.to_string(),
+            retryable: error.is_retryable(),
+        }
+    }
+}
+
 /// Tool execution result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolResult {
@@ -184,6 +358,17 @@ pub struct ToolResult {
     pub status: ToolStatus,
     /// Optional message explaining the result
     pub message: Option<String>,
+    /// Why the call failed, when `status` is [`ToolStatus::Error`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ToolErrorDetail>,
+    /// Binary artifacts produced by the tool. The runtime moves these into
+    /// the artifact store; only their handles reach the LLM.
+    #[serde(skip)]
+    pub artifacts: Vec<NewArtifact>,
+    /// Whether this is a copy kept by the registry's tool cache rather
+    /// than the result of running the tool
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cached: bool,
 }
 
 impl ToolResult {
@@ -194,6 +379,9 @@ impl ToolResult {
             duration_ms,
             status: ToolStatus::Success,
             message: None,
+            error: None,
+            artifacts: Vec::new(),
+            cached: false,
         }
     }
 
@@ -204,16 +392,28 @@ impl ToolResult {
             duration_ms,
             status: ToolStatus::SuccessNoData,
             message: Some(message.into()),
+            error: None,
+            artifacts: Vec::new(),
+            cached: false,
         }
     }
 
-    /// Create an error result
+    /// Create an error result with the generic code `tool_error`
     pub fn error(message: impl Into<String>, duration_ms: f64) -> Self {
+        let message = message.into();
+        Self::failed(ToolErrorDetail::new("tool_error", message), duration_ms)
+    }
+
+    /// Create an error result from `detail`
+    pub fn failed(detail: ToolErrorDetail, duration_ms: f64) -> Self {
         Self {
             output: JsonValue::Null,
             duration_ms,
             status: ToolStatus::Error,
-            message: Some(message.into()),
+            message: Some(detail.message.clone()),
+            error: Some(detail),
+            artifacts: Vec::new(),
+            cached: false,
         }
     }
 
@@ -222,6 +422,43 @@ impl ToolResult {
         self.message = Some(message.into());
         self
     }
+
+    /// Attach a binary artifact to this result
+    pub fn with_artifact(mut self, artifact: NewArtifact) -> Self {
+        self.artifacts.push(artifact);
+        self
+    }
+
+    /// The text the LLM sees for this result, before truncation
+    ///
+    /// Successes are the output as JSON; a no-data success is its message;
+    /// an error is `TOOL ERROR (code): message`, followed by any output.
+    pub fn to_llm_content(&self) -> String {
+        match self.status {
+            ToolStatus::Success => {
+                serde_json::to_string(&self.output).unwrap_or_else(|_| self.output.to_string())
+            }
+            ToolStatus::SuccessNoData => match &self.message {
+                Some(message) => format!("No data: {}", message),
+                None => "No data".to_string(),
+            },
+            ToolStatus::Error => {
+                let (code, message) = match &self.error {
+                    Some(detail) => (detail.code.as_str(), detail.message.as_str()),
+                    None => ("tool_error", self.message.as_deref().unwrap_or("failed")),
+                };
+                let mut content = format!("TOOL ERROR ({}): {}", code, message);
+                if self.error.as_ref().is_some_and(|detail| detail.retryable) {
+                    content.push_str(" (retryable)");
+                }
+                if !self.output.is_null() {
+                    content.push('\n');
+                    content.push_str(&self.output.to_string());
+                }
+                content
+            }
+        }
+    }
 }
 
 /// Result type for tool execution
@@ -235,4 +472,55 @@ pub enum ToolError {
 
     #[error("Execution failed: {0}")]
     ExecutionFailed(String),
+
+    /// A failure worth retrying as-is: a timeout, dropped connection or
+    /// server-side (5xx) error. The agent retries these automatically.
+    #[error("Transient failure: {0}")]
+    Transient(String),
+
+    /// The call ran past its timeout and was abandoned. Not retried: a
+    /// hung tool would most likely hang again.
+    #[error("timed out after {0}ms")]
+    TimedOut(u64),
+
+    /// The tool stopped because its cancellation token fired. Not fed back
+    /// to the LLM: the run is ending.
+    #[error("Canceled: {0}")]
+    Canceled(String),
+
+    /// The agent isn't allowed to call this tool (see
+    /// `AgentConfig::allowed_tools`). Fed back to the LLM; not retried.
+    #[error("Not permitted: {0}")]
+    NotPermitted(String),
+
+    /// The tool was unregistered after the LLM was offered it. Fed back to
+    /// the LLM, whose next request lists the tools that remain; not
+    /// retried.
+    #[error("No longer available: {0}")]
+    Unavailable(String),
+}
+
+impl ToolError {
+    /// A failure the agent should retry (see [`ToolError::Transient`])
+    pub fn transient(message: impl Into<String>) -> Self {
+        ToolError::Transient(message.into())
+    }
+
+    /// Whether running the same call again might succeed
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, ToolError::Transient(_))
+    }
+
+    /// Short machine-readable code for the variant, as in [`ToolErrorDetail`]
+    pub fn code(&self) -> &'static str {
+        match self {
+            ToolError::InvalidParameters(_) => "invalid_parameters",
+            ToolError::ExecutionFailed(_) => "execution_failed",
+            ToolError::Transient(_) => "transient",
+            ToolError::TimedOut(_) => "timed_out",
+            ToolError::Canceled(_) => "canceled",
+            ToolError::NotPermitted(_) => "not_permitted",
+            ToolError::Unavailable(_) => "unavailable",
+        }
+    }
 }
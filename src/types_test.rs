@@ -15,8 +15,11 @@ mod tests {
             metadata: AgentInputMetadata {
                 step_index: 0,
                 previous_agent: None,
+                workflow_id: None,
             },
             chat_history: None,
+            limits: None,
+            vars: Default::default(),
         };
 
         assert_eq!(input.metadata.step_index, 0);
@@ -32,6 +35,16 @@ mod tests {
                 agent_name: "test_agent".to_string(),
                 execution_time_ms: 100,
                 tool_calls_count: 2,
+                effort: None,
+                latency: None,
+                limit_events: Vec::new(),
+                budget_signals: Vec::new(),
+                speculation: None,
+                usage: Default::default(),
+                iterations_exhausted: false,
+                reflection: None,
+                guardrails: Vec::new(),
+                handoff: None,
             },
             chat_history: None,
         };
@@ -63,6 +76,8 @@ mod tests {
                 step_index: 1,
                 previous_step: Some("previous".to_string()),
                 workflow_id: "wf_123".to_string(),
+                run_id: "run_123".to_string(),
+                item_index: None,
             },
             workflow_context: None,
         };
@@ -81,6 +96,11 @@ mod tests {
                 step_name: "step1".to_string(),
                 step_type: StepType::Agent,
                 execution_time_ms: 500,
+                critic: None,
+                iterations_run: None,
+                policy: None,
+                item_errors: None,
+                handoffs: None,
             },
         };
 
@@ -0,0 +1,1015 @@
+//! Token and cost accounting for dashboards and finance.
+//!
+//! The runtime writes a [`UsageRecord`] to its [`UsageLedger`] at every LLM
+//! call completion inside a workflow run. Appends go through a channel, so
+//! the hot path never waits on a reader; a writer commits pending records
+//! when no reader holds the log, and queries commit whatever is left before
+//! reading the committed log, which only ever grows at the end. Every
+//! [`UsageSnapshot`] therefore covers a prefix of the ledger (`entries`
+//! `first_seq..=as_of_seq`) and later snapshots extend earlier ones.
+//!
+//! Retention trims the front of the log by age and/or entry count as
+//! records are committed, so a ledger nobody reads stays bounded too. A new
+//! ledger keeps the latest [`DEFAULT_MAX_ENTRIES`] records. Evicted records
+//! can be handed to a spill callback (e.g. to write them to durable
+//! storage) before they are dropped.
+//!
+//! Each run's own calls are also summed into `WorkflowRun::usage`, in the
+//! same order and with the same cost arithmetic, so a ledger aggregate
+//! filtered to one run reconciles exactly with the run record.
+//!
+//! `WorkflowRun::usage_breakdown` is the billing view: a [`WorkflowUsage`]
+//! with LLM and tool calls per step and per agent, and sub-workflow runs
+//! rolled up into the step that ran them.
+//!
+//! A [`Budget`] caps a run's LLM use as it goes. Every call made in the run
+//! or its sub-workflows counts toward it, priced by the ledger. Agents check
+//! it before each call: the last call the budget allows is made without
+//! tools, so it gets an answer, and a call past the budget is not made.
+
+use crate::llm::types::Usage;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Price of a model in USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CostPerMToken {
+    pub prompt: f64,
+    pub completion: f64,
+}
+
+impl CostPerMToken {
+    pub fn new(prompt: f64, completion: f64) -> Self {
+        Self { prompt, completion }
+    }
+
+    /// Cost of one call; reasoning tokens are part of `completion_tokens`
+    pub fn cost(&self, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        (prompt_tokens as f64 * self.prompt + completion_tokens as f64 * self.completion)
+            / 1_000_000.0
+    }
+}
+
+/// One LLM call as recorded in the ledger
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    /// Position in the ledger, assigned on commit
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    pub workflow_id: String,
+    pub agent: String,
+
+    /// What the call was for: `agent` for an agent's own turns, `critic`
+    /// for reviews of its output
+    #[serde(default = "default_role")]
+    pub role: String,
+
+    pub model: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub reasoning_tokens: u64,
+    pub total_tokens: u64,
+
+    /// `None` when the ledger has no price for the model
+    pub estimated_cost_usd: Option<f64>,
+
+    /// Labels of the run, e.g. `tenant:acme`
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+impl UsageRecord {
+    /// A record for a call that just completed
+    pub fn new(
+        workflow_id: impl Into<String>,
+        agent: impl Into<String>,
+        model: impl Into<String>,
+        usage: Option<&Usage>,
+    ) -> Self {
+        let (prompt, completion, reasoning, total) = usage.map_or((0, 0, 0, 0), |u| {
+            (
+                u.prompt_tokens as u64,
+                u.completion_tokens as u64,
+                u.reasoning_tokens.unwrap_or(0) as u64,
+                u.total_tokens as u64,
+            )
+        });
+        Self {
+            seq: 0,
+            timestamp: Utc::now(),
+            workflow_id: workflow_id.into(),
+            agent: agent.into(),
+            role: default_role(),
+            model: model.into(),
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+            reasoning_tokens: reasoning,
+            total_tokens: total,
+            estimated_cost_usd: None,
+            labels: Vec::new(),
+        }
+    }
+
+    pub fn at(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    pub fn with_labels(mut self, labels: Vec<String>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    pub fn with_role(mut self, role: impl Into<String>) -> Self {
+        self.role = role.into();
+        self
+    }
+
+    /// Value of a `key:value` (or `key=value`) label
+    pub fn label(&self, key: &str) -> Option<&str> {
+        self.labels.iter().find_map(|label| {
+            label
+                .strip_prefix(key)
+                .and_then(|rest| rest.strip_prefix(':').or_else(|| rest.strip_prefix('=')))
+        })
+    }
+}
+
+fn default_role() -> String {
+    "agent".to_string()
+}
+
+/// Summed usage over a set of LLM calls
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageTotals {
+    pub llm_calls: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub reasoning_tokens: u64,
+    pub total_tokens: u64,
+    pub estimated_cost_usd: f64,
+
+    /// Calls whose model had no price, so are missing from the cost
+    pub unpriced_calls: u64,
+}
+
+impl UsageTotals {
+    pub fn add(&mut self, record: &UsageRecord) {
+        self.llm_calls += 1;
+        self.prompt_tokens += record.prompt_tokens;
+        self.completion_tokens += record.completion_tokens;
+        self.reasoning_tokens += record.reasoning_tokens;
+        self.total_tokens += record.total_tokens;
+        match record.estimated_cost_usd {
+            Some(cost) => self.estimated_cost_usd += cost,
+            None => self.unpriced_calls += 1,
+        }
+    }
+
+    /// Add `other`'s sums to these
+    pub fn merge(&mut self, other: &UsageTotals) {
+        self.llm_calls += other.llm_calls;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.reasoning_tokens += other.reasoning_tokens;
+        self.total_tokens += other.total_tokens;
+        self.estimated_cost_usd += other.estimated_cost_usd;
+        self.unpriced_calls += other.unpriced_calls;
+    }
+}
+
+/// LLM usage plus the tool calls the LLM asked for
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageSummary {
+    #[serde(flatten)]
+    pub llm: UsageTotals,
+
+    #[serde(default)]
+    pub tool_calls: u64,
+}
+
+impl UsageSummary {
+    pub fn merge(&mut self, other: &UsageSummary) {
+        self.llm.merge(&other.llm);
+        self.tool_calls += other.tool_calls;
+    }
+}
+
+/// One step's share of a run's usage
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StepUsage {
+    pub step_index: usize,
+    pub step_name: String,
+
+    #[serde(flatten)]
+    pub usage: UsageSummary,
+}
+
+/// Usage of a run, including its sub-workflows, by step and by agent
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorkflowUsage {
+    pub total: UsageSummary,
+
+    /// Every step the run started, in order; a sub-workflow step includes
+    /// the sub-workflow's usage
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<StepUsage>,
+
+    /// By agent name, including agents of sub-workflows
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub agents: BTreeMap<String, UsageSummary>,
+}
+
+impl WorkflowUsage {
+    pub fn step(&self, step_index: usize) -> Option<&StepUsage> {
+        self.steps.iter().find(|s| s.step_index == step_index)
+    }
+
+    pub fn agent(&self, name: &str) -> Option<&UsageSummary> {
+        self.agents.get(name)
+    }
+
+    #[cfg(feature = "workflow")]
+    fn enter_step(&mut self, step_index: usize, step_name: &str) -> usize {
+        match self.steps.iter().position(|s| s.step_index == step_index) {
+            Some(position) => position,
+            None => {
+                self.steps.push(StepUsage {
+                    step_index,
+                    step_name: step_name.to_string(),
+                    usage: UsageSummary::default(),
+                });
+                self.steps.len() - 1
+            }
+        }
+    }
+
+    /// Add `delta` to the total, the step at `step` (a position in
+    /// `steps`) and `agent`
+    fn add(&mut self, step: Option<usize>, agent: Option<&str>, delta: &UsageSummary) {
+        self.total.merge(delta);
+        if let Some(step) = step.and_then(|position| self.steps.get_mut(position)) {
+            step.usage.merge(delta);
+        }
+        if let Some(agent) = agent {
+            self.agents
+                .entry(agent.to_string())
+                .or_default()
+                .merge(delta);
+        }
+    }
+
+    /// Roll a sub-workflow's usage into this run, under step `step`
+    #[cfg(feature = "workflow")]
+    fn absorb(&mut self, step: Option<usize>, child: &WorkflowUsage) {
+        self.add(step, None, &child.total);
+        for (agent, usage) in &child.agents {
+            self.agents.entry(agent.clone()).or_default().merge(usage);
+        }
+    }
+}
+
+/// Hard caps on a workflow run's LLM use; `None` means unlimited
+///
+/// ```rust,ignore
+/// let workflow = Workflow::builder()
+///     .step(Box::new(AgentStep::from_agent(researcher, "research".into())))
+///     .with_budget(Budget::new().with_max_cost_usd(0.50).with_max_wall_clock(Duration::from_secs(60)))
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Budget {
+    /// Prompt plus completion tokens
+    pub max_total_tokens: Option<u64>,
+
+    /// Estimated with the ledger's prices; calls to unpriced models cost
+    /// nothing
+    pub max_cost_usd: Option<f64>,
+
+    pub max_llm_calls: Option<u64>,
+
+    /// Time since the run started
+    pub max_wall_clock: Option<Duration>,
+}
+
+impl Budget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_total_tokens(mut self, tokens: u64) -> Self {
+        self.max_total_tokens = Some(tokens);
+        self
+    }
+
+    pub fn with_max_cost_usd(mut self, usd: f64) -> Self {
+        self.max_cost_usd = Some(usd);
+        self
+    }
+
+    pub fn with_max_llm_calls(mut self, calls: u64) -> Self {
+        self.max_llm_calls = Some(calls);
+        self
+    }
+
+    pub fn with_max_wall_clock(mut self, time: Duration) -> Self {
+        self.max_wall_clock = Some(time);
+        self
+    }
+}
+
+/// Which cap of a [`Budget`] was reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetDimension {
+    TotalTokens,
+    CostUsd,
+    LlmCalls,
+    WallClock,
+}
+
+/// A run that stopped because its [`Budget`] ran out
+///
+/// `limit` and `used` are in the dimension's unit: tokens, USD, calls or
+/// milliseconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, thiserror::Error)]
+#[error("Budget exceeded: {}", self.describe())]
+pub struct BudgetExceeded {
+    pub dimension: BudgetDimension,
+    pub limit: f64,
+    pub used: f64,
+
+    /// The run whose budget it is, which may enclose the run that stopped
+    pub workflow_id: String,
+}
+
+impl BudgetExceeded {
+    fn describe(&self) -> String {
+        match self.dimension {
+            BudgetDimension::TotalTokens => {
+                format!("{} of {} tokens used", self.used, self.limit)
+            }
+            BudgetDimension::CostUsd => {
+                format!("${:.4} of ${:.4} spent", self.used, self.limit)
+            }
+            BudgetDimension::LlmCalls => {
+                format!("{} of {} LLM calls made", self.used, self.limit)
+            }
+            BudgetDimension::WallClock => {
+                format!("{}ms of {}ms elapsed", self.used, self.limit)
+            }
+        }
+    }
+}
+
+/// Whether the current run's budget allows another LLM call
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum BudgetCheck {
+    Within,
+    /// The call allowed is the last one; it should get an answer
+    LastCall,
+    Exceeded(BudgetExceeded),
+}
+
+/// A run's budget and what has been spent against it
+struct BudgetTracker {
+    budget: Budget,
+    workflow_id: String,
+    started: Instant,
+    used: Mutex<UsageTotals>,
+    /// Set by the first check that found the budget spent
+    exceeded: Mutex<Option<BudgetExceeded>>,
+}
+
+impl BudgetTracker {
+    fn check(&self) -> BudgetCheck {
+        let used = self.used.lock().unwrap().clone();
+        let elapsed = self.started.elapsed();
+        let budget = &self.budget;
+        let spent = [
+            budget.max_llm_calls.and_then(|max| {
+                (used.llm_calls >= max).then_some((
+                    BudgetDimension::LlmCalls,
+                    max as f64,
+                    used.llm_calls as f64,
+                ))
+            }),
+            budget.max_total_tokens.and_then(|max| {
+                (used.total_tokens >= max).then_some((
+                    BudgetDimension::TotalTokens,
+                    max as f64,
+                    used.total_tokens as f64,
+                ))
+            }),
+            budget.max_cost_usd.and_then(|max| {
+                (used.estimated_cost_usd >= max).then_some((
+                    BudgetDimension::CostUsd,
+                    max,
+                    used.estimated_cost_usd,
+                ))
+            }),
+            budget.max_wall_clock.and_then(|max| {
+                (elapsed >= max).then_some((
+                    BudgetDimension::WallClock,
+                    max.as_millis() as f64,
+                    elapsed.as_millis() as f64,
+                ))
+            }),
+        ];
+        if let Some((dimension, limit, used)) = spent.into_iter().flatten().next() {
+            let exceeded = BudgetExceeded {
+                dimension,
+                limit,
+                used,
+                workflow_id: self.workflow_id.clone(),
+            };
+            return BudgetCheck::Exceeded(
+                self.exceeded
+                    .lock()
+                    .unwrap()
+                    .get_or_insert(exceeded)
+                    .clone(),
+            );
+        }
+        match budget.max_llm_calls {
+            Some(max) if used.llm_calls + 1 == max => BudgetCheck::LastCall,
+            _ => BudgetCheck::Within,
+        }
+    }
+}
+
+/// Which records a query covers; unset fields match everything
+#[derive(Debug, Clone, Default)]
+pub struct UsageFilter {
+    pub workflow_id: Option<String>,
+    pub agent: Option<String>,
+    pub role: Option<String>,
+    pub model: Option<String>,
+    /// Exact label, e.g. `tenant:acme`
+    pub label: Option<String>,
+    /// Inclusive start of the time range
+    pub since: Option<DateTime<Utc>>,
+    /// Exclusive end of the time range
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl UsageFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn workflow(mut self, workflow_id: impl Into<String>) -> Self {
+        self.workflow_id = Some(workflow_id.into());
+        self
+    }
+
+    pub fn agent(mut self, agent: impl Into<String>) -> Self {
+        self.agent = Some(agent.into());
+        self
+    }
+
+    pub fn role(mut self, role: impl Into<String>) -> Self {
+        self.role = Some(role.into());
+        self
+    }
+
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn between(mut self, since: DateTime<Utc>, until: DateTime<Utc>) -> Self {
+        self.since = Some(since);
+        self.until = Some(until);
+        self
+    }
+
+    pub fn since(mut self, since: DateTime<Utc>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    pub fn matches(&self, record: &UsageRecord) -> bool {
+        self.workflow_id
+            .as_ref()
+            .is_none_or(|id| *id == record.workflow_id)
+            && self.agent.as_ref().is_none_or(|a| *a == record.agent)
+            && self.role.as_ref().is_none_or(|r| *r == record.role)
+            && self.model.as_ref().is_none_or(|m| *m == record.model)
+            && self
+                .label
+                .as_ref()
+                .is_none_or(|l| record.labels.contains(l))
+            && self.since.is_none_or(|since| record.timestamp >= since)
+            && self.until.is_none_or(|until| record.timestamp < until)
+    }
+}
+
+/// Dimension to group an aggregate by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GroupBy {
+    Workflow,
+    Agent,
+    Role,
+    Model,
+    /// Value of a `key:value` label, e.g. `Label("tenant")`
+    Label(String),
+    /// UTC calendar day (`YYYY-MM-DD`)
+    Day,
+}
+
+impl GroupBy {
+    fn key(&self, record: &UsageRecord) -> String {
+        match self {
+            GroupBy::Workflow => record.workflow_id.clone(),
+            GroupBy::Agent => record.agent.clone(),
+            GroupBy::Role => record.role.clone(),
+            GroupBy::Model => record.model.clone(),
+            GroupBy::Label(key) => record.label(key).unwrap_or(NO_LABEL).to_string(),
+            GroupBy::Day => record.timestamp.format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+/// Group key for records without the requested label
+pub const NO_LABEL: &str = "(none)";
+
+/// Totals for one combination of group keys
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageGroup {
+    /// One value per `GroupBy`, in the order requested
+    pub key: Vec<String>,
+    pub totals: UsageTotals,
+}
+
+/// A consistent aggregate over the committed ledger
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageSnapshot {
+    /// Oldest retained record; earlier ones were evicted
+    pub first_seq: u64,
+    /// Last record covered; `0` for an empty ledger
+    pub as_of_seq: u64,
+    pub taken_at: DateTime<Utc>,
+    /// Records evicted by retention so far
+    pub evicted: u64,
+    /// Sorted by key
+    pub groups: Vec<UsageGroup>,
+    pub totals: UsageTotals,
+}
+
+/// Records a ledger keeps unless [`UsageLedger::with_max_entries`] says
+/// otherwise
+pub const DEFAULT_MAX_ENTRIES: usize = 100_000;
+
+type SpillFn = Arc<dyn Fn(&[UsageRecord]) + Send + Sync>;
+
+struct Log {
+    pending: mpsc::Receiver<UsageRecord>,
+    entries: VecDeque<UsageRecord>,
+    next_seq: u64,
+    evicted: u64,
+    max_entries: Option<usize>,
+    max_age: Option<Duration>,
+    spill: Option<SpillFn>,
+}
+
+impl Log {
+    /// Commit pending records in arrival order, then apply retention
+    fn commit(&mut self) {
+        while let Ok(mut record) = self.pending.try_recv() {
+            self.next_seq += 1;
+            record.seq = self.next_seq;
+            self.entries.push_back(record);
+        }
+
+        let cutoff = self
+            .max_age
+            .and_then(|age| chrono::Duration::from_std(age).ok())
+            .map(|age| Utc::now() - age);
+        let mut evict = self
+            .max_entries
+            .map_or(0, |max| self.entries.len().saturating_sub(max));
+        if let Some(cutoff) = cutoff {
+            evict = evict.max(
+                self.entries
+                    .iter()
+                    .take_while(|r| r.timestamp < cutoff)
+                    .count(),
+            );
+        }
+        if evict > 0 {
+            let evicted: Vec<UsageRecord> = self.entries.drain(..evict).collect();
+            self.evicted += evicted.len() as u64;
+            if let Some(spill) = &self.spill {
+                spill(&evicted);
+            }
+        }
+    }
+}
+
+/// Append-only record of LLM usage, shared by clones
+#[derive(Clone)]
+pub struct UsageLedger {
+    tx: mpsc::Sender<UsageRecord>,
+    log: Arc<Mutex<Log>>,
+    prices: Arc<HashMap<String, CostPerMToken>>,
+}
+
+impl UsageLedger {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            tx,
+            log: Arc::new(Mutex::new(Log {
+                pending: rx,
+                entries: VecDeque::new(),
+                next_seq: 0,
+                evicted: 0,
+                max_entries: Some(DEFAULT_MAX_ENTRIES),
+                max_age: None,
+                spill: None,
+            })),
+            prices: Arc::new(HashMap::new()),
+        }
+    }
+
+    /// A ledger with the prices in `config`
+    pub fn from_config(config: &crate::config::UsageConfig) -> Self {
+        config
+            .prices
+            .iter()
+            .fold(Self::new(), |ledger, (model, price)| {
+                ledger.with_price(model.clone(), *price)
+            })
+    }
+
+    /// Price calls to `model`; configure before sharing the ledger
+    pub fn with_price(mut self, model: impl Into<String>, price: CostPerMToken) -> Self {
+        Arc::make_mut(&mut self.prices).insert(model.into(), price);
+        self
+    }
+
+    /// Keep at most `max` records (default [`DEFAULT_MAX_ENTRIES`])
+    pub fn with_max_entries(self, max: usize) -> Self {
+        self.log.lock().unwrap().max_entries = Some(max);
+        self
+    }
+
+    /// Drop records older than `age`
+    pub fn with_max_age(self, age: Duration) -> Self {
+        self.log.lock().unwrap().max_age = Some(age);
+        self
+    }
+
+    /// Hand evicted records to `spill` before they are dropped
+    pub fn with_spill(self, spill: impl Fn(&[UsageRecord]) + Send + Sync + 'static) -> Self {
+        self.log.lock().unwrap().spill = Some(Arc::new(spill));
+        self
+    }
+
+    /// Price `record` and append it. Never blocks on readers: the record is
+    /// committed, and retention applied, now if no reader holds the log,
+    /// otherwise by whoever takes it next. Returns the record as it will be
+    /// committed (its `seq` is assigned on commit).
+    pub fn record(&self, mut record: UsageRecord) -> UsageRecord {
+        if record.estimated_cost_usd.is_none() {
+            record.estimated_cost_usd = self
+                .prices
+                .get(&record.model)
+                .map(|price| price.cost(record.prompt_tokens, record.completion_tokens));
+        }
+        // The receiver lives as long as any clone of the ledger
+        let _ = self.tx.send(record.clone());
+        if let Ok(mut log) = self.log.try_lock() {
+            log.commit();
+        }
+        record
+    }
+
+    /// Aggregate the records matching `filter`, grouped by `group_by`
+    pub fn aggregate(&self, filter: &UsageFilter, group_by: &[GroupBy]) -> UsageSnapshot {
+        let mut log = self.log.lock().unwrap();
+        log.commit();
+
+        let mut groups: BTreeMap<Vec<String>, UsageTotals> = BTreeMap::new();
+        let mut totals = UsageTotals::default();
+        for record in log.entries.iter().filter(|r| filter.matches(r)) {
+            totals.add(record);
+            if !group_by.is_empty() {
+                let key = group_by.iter().map(|g| g.key(record)).collect();
+                groups.entry(key).or_default().add(record);
+            }
+        }
+
+        UsageSnapshot {
+            first_seq: log.entries.front().map_or(log.next_seq + 1, |r| r.seq),
+            as_of_seq: log.next_seq,
+            taken_at: Utc::now(),
+            evicted: log.evicted,
+            groups: groups
+                .into_iter()
+                .map(|(key, totals)| UsageGroup { key, totals })
+                .collect(),
+            totals,
+        }
+    }
+
+    /// Committed records matching `filter`, oldest first
+    pub fn records(&self, filter: &UsageFilter) -> Vec<UsageRecord> {
+        let mut log = self.log.lock().unwrap();
+        log.commit();
+        log.entries
+            .iter()
+            .filter(|r| filter.matches(r))
+            .cloned()
+            .collect()
+    }
+
+    /// Records matching `filter` as a JSON array
+    pub fn to_json(&self, filter: &UsageFilter) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(&self.records(filter))
+    }
+
+    /// Records matching `filter` as CSV with a header row; labels are
+    /// joined with `;`
+    pub fn to_csv(&self, filter: &UsageFilter) -> String {
+        let mut csv = String::from(
+            "seq,timestamp,workflow_id,agent,model,prompt_tokens,completion_tokens,\
+             reasoning_tokens,total_tokens,estimated_cost_usd,labels\n",
+        );
+        for r in self.records(filter) {
+            let fields = [
+                r.seq.to_string(),
+                r.timestamp.to_rfc3339(),
+                csv_field(&r.workflow_id),
+                csv_field(&r.agent),
+                csv_field(&r.model),
+                r.prompt_tokens.to_string(),
+                r.completion_tokens.to_string(),
+                r.reasoning_tokens.to_string(),
+                r.total_tokens.to_string(),
+                r.estimated_cost_usd
+                    .map(|c| c.to_string())
+                    .unwrap_or_default(),
+                csv_field(&r.labels.join(";")),
+            ];
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+impl Default for UsageLedger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for UsageLedger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UsageLedger")
+            .field("prices", &self.prices)
+            .finish_non_exhaustive()
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Where the current run's LLM calls are accounted
+#[derive(Clone)]
+pub(crate) struct RunMeter {
+    ledger: UsageLedger,
+    workflow_id: String,
+    labels: Vec<String>,
+    totals: Arc<Mutex<UsageTotals>>,
+    breakdown: Arc<Mutex<Breakdown>>,
+    /// This run's budget and those of the runs enclosing it, outermost
+    /// first
+    budgets: Vec<Arc<BudgetTracker>>,
+}
+
+#[derive(Default)]
+struct Breakdown {
+    usage: WorkflowUsage,
+    /// Position in `usage.steps` of the step running now
+    current_step: Option<usize>,
+}
+
+#[cfg(feature = "workflow")]
+impl RunMeter {
+    /// A meter for a run starting now; inside another run's meter, the
+    /// enclosing budgets count this run's calls too
+    pub(crate) fn new(
+        ledger: UsageLedger,
+        workflow_id: String,
+        labels: Vec<String>,
+        budget: Option<Budget>,
+    ) -> Self {
+        let mut budgets = CURRENT_METER
+            .try_with(|meter| meter.budgets.clone())
+            .unwrap_or_default();
+        if let Some(budget) = budget {
+            budgets.push(Arc::new(BudgetTracker {
+                budget,
+                workflow_id: workflow_id.clone(),
+                started: Instant::now(),
+                used: Mutex::default(),
+                exceeded: Mutex::default(),
+            }));
+        }
+        Self {
+            ledger,
+            workflow_id,
+            labels,
+            totals: Arc::default(),
+            breakdown: Arc::default(),
+            budgets,
+        }
+    }
+
+    /// The budget that stopped an LLM call of this run, if one did
+    pub(crate) fn budget_exceeded(&self) -> Option<BudgetExceeded> {
+        self.budgets
+            .iter()
+            .find_map(|budget| budget.exceeded.lock().unwrap().clone())
+    }
+
+    pub(crate) fn totals(&self) -> UsageTotals {
+        self.totals.lock().unwrap().clone()
+    }
+
+    pub(crate) fn breakdown(&self) -> WorkflowUsage {
+        self.breakdown.lock().unwrap().usage.clone()
+    }
+
+    /// Account what follows to step `step_index`
+    pub(crate) fn enter_step(&self, step_index: usize, step_name: &str) {
+        let mut breakdown = self.breakdown.lock().unwrap();
+        breakdown.current_step = Some(breakdown.usage.enter_step(step_index, step_name));
+    }
+}
+
+tokio::task_local! {
+    static CURRENT_METER: RunMeter;
+}
+
+/// Account LLM calls made by `fut` to `meter`
+#[cfg(feature = "workflow")]
+pub(crate) async fn metered<F: std::future::Future>(meter: RunMeter, fut: F) -> F::Output {
+    CURRENT_METER.scope(meter, fut).await
+}
+
+/// Check the current run's budgets before an LLM call; always within
+/// outside a run
+pub(crate) fn check_budget() -> BudgetCheck {
+    CURRENT_METER
+        .try_with(|meter| {
+            let mut check = BudgetCheck::Within;
+            for budget in &meter.budgets {
+                match budget.check() {
+                    exceeded @ BudgetCheck::Exceeded(_) => return exceeded,
+                    BudgetCheck::LastCall => check = BudgetCheck::LastCall,
+                    BudgetCheck::Within => {}
+                }
+            }
+            check
+        })
+        .unwrap_or(BudgetCheck::Within)
+}
+
+/// The budget that stopped an LLM call of the current run, if one did
+#[cfg(feature = "workflow")]
+pub(crate) fn budget_exceeded() -> Option<BudgetExceeded> {
+    CURRENT_METER
+        .try_with(RunMeter::budget_exceeded)
+        .ok()
+        .flatten()
+}
+
+/// Record a completed LLM call against the current run, if any,
+/// returning the record (priced only inside a run)
+pub(crate) fn record_llm_call(agent: &str, model: &str, usage: Option<&Usage>) -> UsageRecord {
+    record_llm_call_as("agent", agent, model, usage)
+}
+
+/// Record a completed LLM call made in `role` on behalf of `agent`
+pub(crate) fn record_llm_call_as(
+    role: &str,
+    agent: &str,
+    model: &str,
+    usage: Option<&Usage>,
+) -> UsageRecord {
+    CURRENT_METER
+        .try_with(|meter| {
+            let record = meter.ledger.record(
+                UsageRecord::new(meter.workflow_id.clone(), agent, model, usage)
+                    .with_role(role)
+                    .with_labels(meter.labels.clone()),
+            );
+            meter.totals.lock().unwrap().add(&record);
+            for budget in &meter.budgets {
+                budget.used.lock().unwrap().add(&record);
+            }
+
+            let mut delta = UsageSummary::default();
+            delta.llm.add(&record);
+            let mut breakdown = meter.breakdown.lock().unwrap();
+            let step = breakdown.current_step;
+            breakdown.usage.add(step, Some(agent), &delta);
+            record
+        })
+        .unwrap_or_else(|_| UsageRecord::new("", agent, model, usage).with_role(role))
+}
+
+/// Record `count` tool calls requested by `agent` against the current run
+pub(crate) fn record_tool_calls(agent: &str, count: usize) {
+    let _ = CURRENT_METER.try_with(|meter| {
+        let delta = UsageSummary {
+            tool_calls: count as u64,
+            ..Default::default()
+        };
+        let mut breakdown = meter.breakdown.lock().unwrap();
+        let step = breakdown.current_step;
+        breakdown.usage.add(step, Some(agent), &delta);
+    });
+}
+
+/// Account the next step of the current run to `step_index`
+#[cfg(feature = "workflow")]
+pub(crate) fn enter_step(step_index: usize, step_name: &str) {
+    let _ = CURRENT_METER.try_with(|meter| meter.enter_step(step_index, step_name));
+}
+
+/// Roll a finished sub-workflow's usage into the run that started it, if
+/// it was started inside one
+#[cfg(feature = "workflow")]
+pub(crate) fn roll_up(child: &WorkflowUsage) {
+    let _ = CURRENT_METER.try_with(|meter| {
+        let mut breakdown = meter.breakdown.lock().unwrap();
+        let step = breakdown.current_step;
+        breakdown.usage.absorb(step, child);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(agent: &str, model: &str, tenant: &str) -> UsageRecord {
+        let usage = Usage {
+            prompt_tokens: 100,
+            completion_tokens: 50,
+            total_tokens: 150,
+            reasoning_tokens: None,
+        };
+        UsageRecord::new("run", agent, model, Some(&usage))
+            .with_labels(vec![format!("tenant:{}", tenant)])
+    }
+
+    #[test]
+    fn test_pricing_and_csv_escaping() {
+        let ledger = UsageLedger::new().with_price("gpt", CostPerMToken::new(2.0, 8.0));
+        let priced = ledger.record(call("a", "gpt", "acme"));
+        assert_eq!(priced.estimated_cost_usd, Some(0.0006));
+        assert_eq!(
+            ledger.record(call("a", "other", "acme")).estimated_cost_usd,
+            None
+        );
+        ledger.record(call("b,c", "gpt", "acme"));
+
+        let csv = ledger.to_csv(&UsageFilter::new());
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("1,"));
+        assert!(lines[1].ends_with(",run,a,gpt,100,50,0,150,0.0006,tenant:acme"));
+        assert!(lines[2].contains(",150,,tenant:acme"));
+        assert!(lines[3].contains(",\"b,c\","));
+    }
+
+    #[test]
+    fn test_label_lookup() {
+        let record = call("a", "m", "acme").with_labels(vec![
+            "canary".into(),
+            "tenant=acme".into(),
+            "tenantx:no".into(),
+        ]);
+        assert_eq!(record.label("tenant"), Some("acme"));
+        assert_eq!(record.label("canary"), None);
+        assert_eq!(GroupBy::Label("region".into()).key(&record), NO_LABEL);
+    }
+}
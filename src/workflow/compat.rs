@@ -0,0 +1,644 @@
+//! Compatibility checking between a recorded `WorkflowRun` and an edited `Workflow`.
+//!
+//! `check_run_compatibility` aligns the recorded steps with the new workflow by
+//! name, then replays the recorded data through the new workflow in dry-run
+//! fashion: transforms and conditions are executed for real and the JSON
+//! pointers steps read from their input are resolved, while agents,
+//! sub-workflows and custom steps are stubbed with their recorded outputs.
+
+use crate::types::JsonValue;
+use crate::workflow::step::{Step, StepInput, StepInputMetadata, StepType};
+use crate::workflow::{Workflow, WorkflowRun, WorkflowStepRecord};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// A step that exists in both versions under different names
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepRename {
+    pub index: usize,
+    pub from: String,
+    pub to: String,
+}
+
+/// A divergence found while replaying recorded data through the new workflow
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CompatibilityIssue {
+    /// The step now receives data with a different shape than was recorded
+    InputShapeChanged {
+        step: String,
+        recorded: String,
+        replayed: String,
+    },
+    /// A JSON pointer the step reads from its input no longer resolves
+    UnresolvedMapping { step: String, pointer: String },
+    /// A re-executed step now produces data with a different shape
+    OutputShapeChanged {
+        step: String,
+        recorded: String,
+        replayed: String,
+    },
+    /// A conditional now takes a different branch on the replayed data
+    RouteChanged {
+        step: String,
+        recorded: String,
+        replayed: String,
+    },
+    /// A step that can't be re-executed has no recorded output to stand in
+    /// for it, so downstream steps can't be replayed
+    MissingRecordedOutput { step: String },
+    /// A re-executed step failed on the replayed data
+    StepFailed { step: String, error: String },
+}
+
+impl CompatibilityIssue {
+    /// Name of the step the issue was found at
+    pub fn step(&self) -> &str {
+        match self {
+            Self::InputShapeChanged { step, .. }
+            | Self::UnresolvedMapping { step, .. }
+            | Self::OutputShapeChanged { step, .. }
+            | Self::RouteChanged { step, .. }
+            | Self::MissingRecordedOutput { step }
+            | Self::StepFailed { step, .. } => step,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::InputShapeChanged {
+                recorded, replayed, ..
+            } => format!("input shape `{}` → `{}`", recorded, replayed),
+            Self::UnresolvedMapping { pointer, .. } => {
+                format!("mapping `{}` no longer resolves", pointer)
+            }
+            Self::OutputShapeChanged {
+                recorded, replayed, ..
+            } => format!("output shape `{}` → `{}`", recorded, replayed),
+            Self::RouteChanged {
+                recorded, replayed, ..
+            } => format!("route `{}` → `{}`", recorded, replayed),
+            Self::MissingRecordedOutput { .. } => {
+                "no recorded output; downstream steps not replayed".to_string()
+            }
+            Self::StepFailed { error, .. } => format!("failed: {}", error),
+        }
+    }
+}
+
+/// Result of [`check_run_compatibility`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompatibilityReport {
+    /// ID of the recorded run
+    pub run_workflow_id: String,
+    /// ID of the new workflow
+    pub workflow_id: String,
+    /// Steps only present in the new workflow
+    pub added_steps: Vec<String>,
+    /// Steps only present in the recorded run
+    pub removed_steps: Vec<String>,
+    /// Steps matched by position and type but renamed
+    pub renamed_steps: Vec<StepRename>,
+    /// Divergences found during replay, in step order
+    pub issues: Vec<CompatibilityIssue>,
+    /// Number of new-workflow steps the replay reached
+    pub replayed_steps: usize,
+}
+
+impl CompatibilityReport {
+    /// True when the step layout is unchanged and replay found no divergence
+    pub fn is_compatible(&self) -> bool {
+        self.added_steps.is_empty()
+            && self.removed_steps.is_empty()
+            && self.renamed_steps.is_empty()
+            && self.issues.is_empty()
+    }
+
+    /// Render the report for PR review
+    pub fn render_markdown(&self) -> String {
+        let mut out = format!(
+            "## Run compatibility: `{}` → `{}`\n\n",
+            self.run_workflow_id, self.workflow_id
+        );
+
+        if self.is_compatible() {
+            out.push_str(&format!(
+                "✅ Compatible — {} steps replayed with no divergence.\n",
+                self.replayed_steps
+            ));
+            return out;
+        }
+
+        if !self.added_steps.is_empty()
+            || !self.removed_steps.is_empty()
+            || !self.renamed_steps.is_empty()
+        {
+            out.push_str("### Step changes\n\n");
+            for step in &self.added_steps {
+                out.push_str(&format!("- ➕ added `{}`\n", step));
+            }
+            for step in &self.removed_steps {
+                out.push_str(&format!("- ➖ removed `{}`\n", step));
+            }
+            for rename in &self.renamed_steps {
+                out.push_str(&format!(
+                    "- ✏️ renamed `{}` → `{}` (step {})\n",
+                    rename.from, rename.to, rename.index
+                ));
+            }
+            out.push('\n');
+        }
+
+        if !self.issues.is_empty() {
+            out.push_str("### Replay divergences\n\n| Step | Issue |\n|---|---|\n");
+            for issue in &self.issues {
+                out.push_str(&format!("| `{}` | {} |\n", issue.step(), issue.describe()));
+            }
+            out.push('\n');
+        }
+
+        out.push_str(&format!("{} steps replayed.\n", self.replayed_steps));
+        out
+    }
+}
+
+/// Check whether a recorded run's data still flows through an edited workflow
+///
+/// Steps are aligned by name; unmatched steps at the same position with the
+/// same type are reported as renames. Replay starts from the recorded input of
+/// the first step and stops at the first step that can neither be re-executed
+/// nor stubbed with a recorded output, or whose input pointers don't resolve.
+pub async fn check_run_compatibility(
+    old_run: &WorkflowRun,
+    new_workflow: &Workflow,
+) -> CompatibilityReport {
+    let mut report = CompatibilityReport {
+        run_workflow_id: old_run.workflow_id.clone(),
+        workflow_id: new_workflow.id.clone(),
+        ..Default::default()
+    };
+
+    let new_names: Vec<&str> = new_workflow.steps.iter().map(|s| s.name()).collect();
+    let old_names: HashSet<&str> = old_run.steps.iter().map(|r| r.step_name.as_str()).collect();
+
+    // Unmatched on either side
+    let mut removed: Vec<&WorkflowStepRecord> = old_run
+        .steps
+        .iter()
+        .filter(|r| !new_names.contains(&r.step_name.as_str()))
+        .collect();
+    let mut added: Vec<(usize, &dyn Step)> = new_workflow
+        .steps
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| !old_names.contains(s.name()))
+        .map(|(i, s)| (i, s.as_ref()))
+        .collect();
+
+    // Pair same-position, same-type leftovers as renames
+    added.retain(|(index, step)| {
+        let step_type = format!("{:?}", step.step_type());
+        match removed
+            .iter()
+            .position(|r| r.step_index == *index && r.step_type == step_type)
+        {
+            Some(pos) => {
+                let record = removed.remove(pos);
+                report.renamed_steps.push(StepRename {
+                    index: *index,
+                    from: record.step_name.clone(),
+                    to: step.name().to_string(),
+                });
+                false
+            }
+            None => true,
+        }
+    });
+    report.added_steps = added.iter().map(|(_, s)| s.name().to_string()).collect();
+    report.removed_steps = removed.iter().map(|r| r.step_name.clone()).collect();
+
+    let recorded_for = |name: &str| -> Option<&WorkflowStepRecord> {
+        let old_name = report
+            .renamed_steps
+            .iter()
+            .find(|r| r.to == name)
+            .map(|r| r.from.as_str())
+            .unwrap_or(name);
+        old_run.steps.iter().find(|r| r.step_name == old_name)
+    };
+
+    let mut issues = Vec::new();
+    let mut replayed_steps = 0;
+    let mut current = old_run
+        .steps
+        .first()
+        .map(|r| r.input.clone())
+        .unwrap_or_else(|| new_workflow.initial_input.clone());
+
+    for (index, step) in new_workflow.steps.iter().enumerate() {
+        replayed_steps += 1;
+        let name = step.name().to_string();
+        let recorded = recorded_for(&name);
+
+        if let Some(record) = recorded {
+            let (recorded_shape, replayed_shape) = (shape_of(&record.input), shape_of(&current));
+            if recorded_shape != replayed_shape {
+                issues.push(CompatibilityIssue::InputShapeChanged {
+                    step: name.clone(),
+                    recorded: recorded_shape,
+                    replayed: replayed_shape,
+                });
+            }
+        }
+
+        let unresolved: Vec<CompatibilityIssue> = step
+            .input_pointers()
+            .into_iter()
+            .filter(|pointer| current.pointer(pointer).is_none())
+            .map(|pointer| CompatibilityIssue::UnresolvedMapping {
+                step: name.clone(),
+                pointer: pointer.to_string(),
+            })
+            .collect();
+        if !unresolved.is_empty() {
+            issues.extend(unresolved);
+            break;
+        }
+
+        let output = match step.step_type() {
+            StepType::Transform => {
+                match dry_run(step.as_ref(), &current, index, &new_workflow.id).await {
+                    Ok(output) => {
+                        compare_output(&name, recorded, &output, &mut issues);
+                        output
+                    }
+                    Err(error) => {
+                        issues.push(CompatibilityIssue::StepFailed { step: name, error });
+                        break;
+                    }
+                }
+            }
+            StepType::Conditional => {
+                let replay =
+                    replay_conditional(step.as_ref(), &current, recorded, index, &new_workflow.id)
+                        .await;
+                issues.extend(replay.issues);
+                match replay.output {
+                    Some(output) => output,
+                    None => break,
+                }
+            }
+            _ => match recorded.and_then(|r| r.output.clone()) {
+                Some(output) => output,
+                None => {
+                    issues.push(CompatibilityIssue::MissingRecordedOutput { step: name });
+                    break;
+                }
+            },
+        };
+
+        current = output;
+    }
+
+    report.issues = issues;
+    report.replayed_steps = replayed_steps;
+    report
+}
+
+struct ConditionalReplay {
+    output: Option<JsonValue>,
+    issues: Vec<CompatibilityIssue>,
+}
+
+async fn replay_conditional(
+    step: &dyn Step,
+    current: &JsonValue,
+    recorded: Option<&WorkflowStepRecord>,
+    index: usize,
+    workflow_id: &str,
+) -> ConditionalReplay {
+    let name = step.name().to_string();
+    let mut issues = Vec::new();
+
+    let (Some(taken), Some((then_step, else_step))) =
+        (step.evaluate_condition(current), step.get_branches())
+    else {
+        // Opaque conditional - fall back to the recorded output
+        let output = recorded.and_then(|r| r.output.clone());
+        if output.is_none() {
+            issues.push(CompatibilityIssue::MissingRecordedOutput { step: name });
+        }
+        return ConditionalReplay { output, issues };
+    };
+
+    let route_label = |branch: bool| {
+        let target = if branch { then_step } else { else_step };
+        format!("{}:{}", if branch { "then" } else { "else" }, target.name())
+    };
+
+    // Evaluate the same condition on the data the old run actually saw
+    let recorded_route = recorded.and_then(|r| step.evaluate_condition(&r.input));
+    let route_changed = recorded_route.is_some_and(|was| was != taken);
+    if let Some(was) = recorded_route.filter(|was| *was != taken) {
+        issues.push(CompatibilityIssue::RouteChanged {
+            step: name.clone(),
+            recorded: route_label(was),
+            replayed: route_label(taken),
+        });
+    }
+
+    let branch = if taken { then_step } else { else_step };
+    let output = if branch.step_type() == StepType::Transform {
+        match dry_run(branch, current, index, workflow_id).await {
+            Ok(output) => {
+                // A different branch is expected to produce different data
+                if !route_changed {
+                    compare_output(&name, recorded, &output, &mut issues);
+                }
+                Some(output)
+            }
+            Err(error) => {
+                issues.push(CompatibilityIssue::StepFailed { step: name, error });
+                None
+            }
+        }
+    } else {
+        let output = recorded.and_then(|r| r.output.clone());
+        if output.is_none() {
+            issues.push(CompatibilityIssue::MissingRecordedOutput { step: name });
+        }
+        output
+    };
+
+    ConditionalReplay { output, issues }
+}
+
+async fn dry_run(
+    step: &dyn Step,
+    data: &JsonValue,
+    index: usize,
+    workflow_id: &str,
+) -> Result<JsonValue, String> {
+    let input = StepInput {
+        data: data.clone(),
+        metadata: StepInputMetadata {
+            step_index: index,
+            previous_step: None,
+            workflow_id: workflow_id.to_string(),
+            run_id: String::new(),
+            item_index: None,
+        },
+        workflow_context: None,
+    };
+    step.execute(input)
+        .await
+        .map(|output| output.data)
+        .map_err(|e| e.to_string())
+}
+
+fn compare_output(
+    name: &str,
+    recorded: Option<&WorkflowStepRecord>,
+    output: &JsonValue,
+    issues: &mut Vec<CompatibilityIssue>,
+) {
+    let Some(recorded_output) = recorded.and_then(|r| r.output.as_ref()) else {
+        return;
+    };
+    let (recorded_shape, replayed_shape) = (shape_of(recorded_output), shape_of(output));
+    if recorded_shape != replayed_shape {
+        issues.push(CompatibilityIssue::OutputShapeChanged {
+            step: name.to_string(),
+            recorded: recorded_shape,
+            replayed: replayed_shape,
+        });
+    }
+}
+
+/// Structural signature of a JSON value: keys and value types, not values
+fn shape_of(value: &JsonValue) -> String {
+    match value {
+        JsonValue::Null => "null".to_string(),
+        JsonValue::Bool(_) => "bool".to_string(),
+        JsonValue::Number(_) => "number".to_string(),
+        JsonValue::String(_) => "string".to_string(),
+        JsonValue::Array(items) => match items.first() {
+            Some(first) => format!("[{}]", shape_of(first)),
+            None => "[]".to_string(),
+        },
+        JsonValue::Object(map) => {
+            let mut keys: Vec<_> = map.iter().collect();
+            keys.sort_by(|a, b| a.0.cmp(b.0));
+            let fields: Vec<String> = keys
+                .into_iter()
+                .map(|(k, v)| format!("{}: {}", k, shape_of(v)))
+                .collect();
+            format!("{{{}}}", fields.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow::steps::{AgentStep, ConditionalStep, StepStatus, TransformStep};
+    use crate::workflow::WorkflowState;
+    use crate::{Agent, AgentConfig};
+    use serde_json::json;
+
+    fn agent_step(name: &str) -> Box<dyn Step> {
+        let agent = Agent::new(AgentConfig::builder(name).build());
+        Box::new(AgentStep::from_agent(agent, name.to_string()))
+    }
+
+    fn record(
+        index: usize,
+        name: &str,
+        step_type: &str,
+        input: JsonValue,
+        output: JsonValue,
+    ) -> WorkflowStepRecord {
+        WorkflowStepRecord {
+            step_index: index,
+            step_name: name.to_string(),
+            step_type: step_type.to_string(),
+            input,
+            output: Some(output),
+            execution_time_ms: Some(1),
+            replayed: false,
+            critic: None,
+            status: StepStatus::Succeeded,
+            error: None,
+            handoffs: None,
+            started_at: None,
+            finished_at: None,
+            artifacts: Vec::new(),
+        }
+    }
+
+    fn routing_conditional() -> Box<dyn Step> {
+        Box::new(ConditionalStep::new(
+            "route".to_string(),
+            |v| v["response"].as_str().unwrap_or("").contains("urgent"),
+            Box::new(TransformStep::new(
+                "escalate".to_string(),
+                |v| json!({"queue": "urgent", "text": v["response"]}),
+            )),
+            Box::new(TransformStep::new(
+                "file".to_string(),
+                |v| json!({"queue": "normal", "text": v["response"]}),
+            )),
+        ))
+    }
+
+    fn recorded_run() -> WorkflowRun {
+        let classified = json!({"response": "urgent: server down"});
+        WorkflowRun {
+            workflow_id: "triage_v1".to_string(),
+            run_id: "run_1".to_string(),
+            state: WorkflowState::Completed,
+            steps: vec![
+                record(
+                    0,
+                    "classifier",
+                    "Agent",
+                    json!("server down"),
+                    classified.clone(),
+                ),
+                record(
+                    1,
+                    "route",
+                    "Conditional",
+                    classified,
+                    json!({"queue": "urgent", "text": "urgent: server down"}),
+                ),
+            ],
+            final_output: None,
+            parent_workflow_id: None,
+            artifacts: Vec::new(),
+            pii_findings: None,
+            trace: None,
+            usage: Default::default(),
+            usage_breakdown: Default::default(),
+            rerun_of: None,
+            failure: None,
+            approvals: Vec::new(),
+            sub_workflows: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compatible_edit_produces_empty_report() {
+        let workflow = Workflow::builder()
+            .name("triage_v2".to_string())
+            .step(agent_step("classifier"))
+            .step(routing_conditional())
+            .build();
+
+        let report = check_run_compatibility(&recorded_run(), &workflow).await;
+        assert!(report.is_compatible(), "{:?}", report);
+        assert_eq!(report.replayed_steps, 2);
+        assert!(report.render_markdown().contains("Compatible"));
+    }
+
+    #[tokio::test]
+    async fn test_renamed_step_detected() {
+        let workflow = Workflow::builder()
+            .step(agent_step("triage_agent"))
+            .step(routing_conditional())
+            .build();
+
+        let report = check_run_compatibility(&recorded_run(), &workflow).await;
+        assert_eq!(
+            report.renamed_steps,
+            vec![StepRename {
+                index: 0,
+                from: "classifier".to_string(),
+                to: "triage_agent".to_string(),
+            }]
+        );
+        assert!(report.added_steps.is_empty());
+        assert!(report.removed_steps.is_empty());
+        // The recorded output still stands in for the renamed agent
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+    }
+
+    #[tokio::test]
+    async fn test_added_transform_changes_downstream_route() {
+        let workflow = Workflow::builder()
+            .step(agent_step("classifier"))
+            .step(Box::new(TransformStep::new("redact".to_string(), |v| {
+                json!({"response": v["response"].as_str().unwrap_or("").replace("urgent", "***")})
+            })))
+            .step(routing_conditional())
+            .build();
+
+        let report = check_run_compatibility(&recorded_run(), &workflow).await;
+        assert_eq!(report.added_steps, vec!["redact".to_string()]);
+        assert_eq!(
+            report.issues,
+            vec![CompatibilityIssue::RouteChanged {
+                step: "route".to_string(),
+                recorded: "then:escalate".to_string(),
+                replayed: "else:file".to_string(),
+            }]
+        );
+
+        let md = report.render_markdown();
+        assert!(md.contains("added `redact`"));
+        assert!(md.contains("route `then:escalate` → `else:file`"));
+    }
+
+    #[tokio::test]
+    async fn test_unresolved_mapping_stops_replay() {
+        let workflow = Workflow::builder()
+            .step(agent_step("classifier"))
+            .step(Box::new(TransformStep::pick(
+                "summary".to_string(),
+                "/body/summary",
+            )))
+            .step(routing_conditional())
+            .build();
+
+        let report = check_run_compatibility(&recorded_run(), &workflow).await;
+        assert_eq!(
+            report.issues,
+            vec![CompatibilityIssue::UnresolvedMapping {
+                step: "summary".to_string(),
+                pointer: "/body/summary".to_string(),
+            }]
+        );
+        assert_eq!(report.replayed_steps, 2);
+        assert!(report
+            .render_markdown()
+            .contains("mapping `/body/summary` no longer resolves"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_recorded_output_stops_replay() {
+        let mut run = recorded_run();
+        run.steps[0].output = None;
+        let workflow = Workflow::builder()
+            .step(agent_step("classifier"))
+            .step(routing_conditional())
+            .build();
+
+        let report = check_run_compatibility(&run, &workflow).await;
+        assert_eq!(
+            report.issues,
+            vec![CompatibilityIssue::MissingRecordedOutput {
+                step: "classifier".to_string()
+            }]
+        );
+        assert_eq!(report.replayed_steps, 1);
+    }
+
+    #[test]
+    fn test_shape_ignores_values() {
+        assert_eq!(
+            shape_of(&json!({"b": 1, "a": ["x"]})),
+            shape_of(&json!({"a": ["y"], "b": 2}))
+        );
+        assert_ne!(shape_of(&json!({"a": 1})), shape_of(&json!({"a": "1"})));
+    }
+}
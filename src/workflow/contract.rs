@@ -0,0 +1,171 @@
+//! Input and output contracts between adjacent workflow steps
+//!
+//! A step may declare JSON Schemas for the data it takes and the data it
+//! produces ([`Step::input_schema`], [`Step::output_schema`]). The runtime
+//! checks each step's output against its own output schema and against
+//! the next step's input schema before the next step runs, so a changed
+//! output shape fails at the step that produced it instead of three steps
+//! later. Schemas are checked strictly, with [`InputSchema`].
+//!
+//! `WorkflowBuilder::try_build` also compares adjacent declared schemas
+//! and rejects pairs that can never match, e.g. a required property the
+//! producer doesn't declare.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::InputViolation;
+use crate::schema::{declared_types, escape, InputSchema};
+use crate::types::JsonValue;
+use crate::workflow::Step;
+
+/// Which schema a step's output failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ContractBoundary {
+    /// The producing step's own output schema
+    Output,
+
+    /// The input schema of the step that would receive the output
+    NextInput { step: String },
+}
+
+/// A step's output that failed a declared schema
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractViolation {
+    /// The step that produced the output
+    pub step: String,
+    pub boundary: ContractBoundary,
+    pub violations: Vec<InputViolation>,
+}
+
+impl fmt::Display for ContractViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.boundary {
+            ContractBoundary::Output => write!(
+                f,
+                "output of step '{}' does not match its output schema",
+                self.step
+            )?,
+            ContractBoundary::NextInput { step } => write!(
+                f,
+                "output of step '{}' does not match the input schema of step '{}'",
+                self.step, step
+            )?,
+        }
+        for (i, violation) in self.violations.iter().enumerate() {
+            f.write_str(if i == 0 { ": " } else { "; " })?;
+            write!(f, "{}", violation)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check `output` of `step` against its output schema, then against the
+/// input schema of `next`, if there is a next step
+pub(crate) fn check_output(
+    step: &dyn Step,
+    next: Option<&dyn Step>,
+    output: &JsonValue,
+) -> Result<(), ContractViolation> {
+    let boundaries = [
+        step.output_schema().map(|s| (s, ContractBoundary::Output)),
+        next.and_then(|next| {
+            next.input_schema().map(|s| {
+                (
+                    s,
+                    ContractBoundary::NextInput {
+                        step: next.name().to_string(),
+                    },
+                )
+            })
+        }),
+    ];
+    for (schema, boundary) in boundaries.into_iter().flatten() {
+        if let Err(violations) = InputSchema::strict(schema.clone()).validate(output) {
+            return Err(ContractViolation {
+                step: step.name().to_string(),
+                boundary,
+                violations,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Ways data matching `output` can never match `input`, e.g. a `/title`
+/// declared a string on one side and a number on the other
+///
+/// Only what both schemas declare is compared: a property or type left
+/// open on either side is assumed to fit.
+pub fn incompatibilities(output: &JsonValue, input: &JsonValue) -> Vec<InputViolation> {
+    let mut found = Vec::new();
+    compare(output, input, &mut String::new(), &mut found);
+    found
+}
+
+fn compare(
+    output: &JsonValue,
+    input: &JsonValue,
+    pointer: &mut String,
+    found: &mut Vec<InputViolation>,
+) {
+    if let (Some(produced), Some(accepted)) = (declared_types(output), declared_types(input)) {
+        if !produced
+            .iter()
+            .any(|p| accepted.iter().any(|a| overlap(p, a)))
+        {
+            found.push(InputViolation {
+                pointer: pointer.clone(),
+                message: format!(
+                    "step outputs {}, next step expects {}",
+                    produced.join(" or "),
+                    accepted.join(" or ")
+                ),
+            });
+            return;
+        }
+    }
+
+    let output_properties = output.get("properties").and_then(JsonValue::as_object);
+    let input_properties = input.get("properties").and_then(JsonValue::as_object);
+    let required = input.get("required").and_then(JsonValue::as_array);
+    let output_required = output.get("required").and_then(JsonValue::as_array);
+    for name in required.into_iter().flatten().filter_map(JsonValue::as_str) {
+        let declared = output_properties.is_some_and(|p| p.contains_key(name))
+            || output_required.is_some_and(|r| r.iter().any(|n| n == name));
+        // A producer without declared properties may still output anything
+        if output_properties.is_some() && !declared {
+            found.push(InputViolation {
+                pointer: format!("{}/{}", pointer, escape(name)),
+                message: "required by the next step, but not in the step's output schema"
+                    .to_string(),
+            });
+        }
+    }
+    if let (Some(output_properties), Some(input_properties)) = (output_properties, input_properties)
+    {
+        for (name, produced) in output_properties {
+            if let Some(accepted) = input_properties.get(name) {
+                let len = pointer.len();
+                pointer.push('/');
+                pointer.push_str(&escape(name));
+                compare(produced, accepted, pointer, found);
+                pointer.truncate(len);
+            }
+        }
+    }
+
+    if let (Some(produced), Some(accepted)) = (output.get("items"), input.get("items")) {
+        let len = pointer.len();
+        pointer.push_str("/*");
+        compare(produced, accepted, pointer, found);
+        pointer.truncate(len);
+    }
+}
+
+/// Whether some value has both declared types
+fn overlap(a: &str, b: &str) -> bool {
+    a == b || matches!((a, b), ("integer", "number") | ("number", "integer"))
+}
@@ -0,0 +1,297 @@
+//! Critic review of agent step outputs.
+//!
+//! A [`CriticConfig`] attached to an [`AgentStep`](super::AgentStep) has a
+//! second, usually cheaper, model judge the step's output against the
+//! step's instructions. A failing verdict goes back to the same agent as a
+//! correction, for at most `max_rounds` revisions. Critic calls are recorded
+//! in usage with role `critic` and emit `agent:critic` events. Verdicts are
+//! memoized by prompt, so an unchanged candidate is never judged twice.
+
+use crate::event::EventStream;
+use crate::llm::{ChatMessage, ChatRequest, LlmClient};
+use crate::template::Template;
+use crate::types::JsonValue;
+use crate::workflow::step::StepError;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Prompt used unless the config sets its own; variables are
+/// `instructions`, `input` and `output`
+pub const DEFAULT_CRITIC_PROMPT: &str = "You review another assistant's work.
+
+Instructions the assistant was given:
+{{instructions}}
+
+Input:
+{{input}}
+
+Candidate output:
+{{output}}
+
+Reply with only a JSON object: {\"pass\": true or false, \"score\": a number from 0 to 1, \"critique\": \"what to fix, or empty if it passes\"}";
+
+/// Most memoized verdicts one config keeps before starting over
+const MAX_CACHED_VERDICTS: usize = 256;
+
+/// A critic's judgement of one candidate output
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CriticVerdict {
+    pub pass: bool,
+
+    /// Quality from 0 to 1
+    pub score: f64,
+
+    pub critique: String,
+
+    /// Reused from an earlier review of the same prompt
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cached: bool,
+}
+
+#[derive(Deserialize)]
+struct RawVerdict {
+    pass: bool,
+    #[serde(default)]
+    score: Option<f64>,
+    #[serde(default)]
+    critique: String,
+}
+
+impl CriticVerdict {
+    /// Parse a critic's reply, tolerating prose or code fences around the
+    /// JSON object. A verdict without a score counts as 1 if it passes and
+    /// 0 if it doesn't.
+    pub fn parse(reply: &str) -> Option<Self> {
+        let start = reply.find('{')?;
+        let end = reply.rfind('}')?;
+        let raw: RawVerdict = serde_json::from_str(reply.get(start..=end)?).ok()?;
+        Some(Self {
+            pass: raw.pass,
+            score: raw
+                .score
+                .unwrap_or(if raw.pass { 1.0 } else { 0.0 })
+                .clamp(0.0, 1.0),
+            critique: raw.critique,
+            cached: false,
+        })
+    }
+}
+
+/// Every review of one step execution
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CriticReport {
+    /// Revisions the agent made in response to critiques
+    pub rounds: u32,
+
+    /// The final output was accepted
+    pub passed: bool,
+
+    /// Sampling skipped the review; the output went through unreviewed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub skipped: bool,
+
+    /// One per review, in order
+    pub verdicts: Vec<CriticVerdict>,
+}
+
+impl CriticReport {
+    /// Critique of the last review, if there was one
+    pub fn last_critique(&self) -> Option<&str> {
+        self.verdicts.last().map(|v| v.critique.as_str())
+    }
+}
+
+/// A reviewer for an agent step's output
+#[derive(Clone)]
+pub struct CriticConfig {
+    pub client: LlmClient,
+
+    /// Rendered with `instructions`, `input` and `output`
+    pub prompt_template: Template,
+
+    /// Revisions allowed after failing verdicts; `0` only reviews
+    pub max_rounds: u32,
+
+    /// Lowest score a passing verdict may have
+    pub pass_threshold: f64,
+
+    /// Fraction of executions that are reviewed at all
+    pub sample_rate: f64,
+
+    cache: Arc<Mutex<HashMap<String, CriticVerdict>>>,
+}
+
+impl CriticConfig {
+    /// A critic with the default prompt, one revision round, a 0.7 pass
+    /// threshold and every execution reviewed
+    pub fn new(client: LlmClient) -> Self {
+        Self {
+            client,
+            prompt_template: Template::new("critic", DEFAULT_CRITIC_PROMPT)
+                .expect("default critic prompt parses"),
+            max_rounds: 1,
+            pass_threshold: 0.7,
+            sample_rate: 1.0,
+            cache: Arc::default(),
+        }
+    }
+
+    pub fn with_prompt_template(mut self, template: Template) -> Self {
+        self.prompt_template = template;
+        self
+    }
+
+    pub fn with_max_rounds(mut self, max_rounds: u32) -> Self {
+        self.max_rounds = max_rounds;
+        self
+    }
+
+    pub fn with_pass_threshold(mut self, threshold: f64) -> Self {
+        self.pass_threshold = threshold;
+        self
+    }
+
+    /// Review only this fraction of executions (0.0 to 1.0)
+    pub fn with_sample_rate(mut self, rate: f64) -> Self {
+        self.sample_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Whether this execution should be reviewed
+    pub(crate) fn sampled(&self) -> bool {
+        self.sample_rate >= 1.0 || rand::random::<f64>() < self.sample_rate
+    }
+
+    pub(crate) fn accepts(&self, verdict: &CriticVerdict) -> bool {
+        verdict.pass && verdict.score >= self.pass_threshold
+    }
+
+    /// Judge `output`, reusing the verdict for an identical prompt
+    pub(crate) async fn review(
+        &self,
+        target: &CriticTarget<'_>,
+        output: &JsonValue,
+        round: u32,
+    ) -> Result<CriticVerdict, StepError> {
+        let fail = |message: String| {
+            if let Some(stream) = target.event_stream {
+                stream.critic_failed(
+                    target.agent,
+                    target.workflow_id.to_string(),
+                    &message,
+                    serde_json::json!({ "role": "critic", "round": round }),
+                );
+            }
+            StepError::AgentError(message)
+        };
+
+        let prompt = self
+            .prompt_template
+            .render(&serde_json::json!({
+                "instructions": target.instructions,
+                "input": target.input,
+                "output": output,
+            }))
+            .map_err(|e| fail(format!("critic prompt failed to render: {}", e)))?;
+        let key = format!("{:x}", Sha256::digest(prompt.as_bytes()));
+
+        if let Some(stream) = target.event_stream {
+            stream.critic_started(
+                target.agent,
+                target.workflow_id.to_string(),
+                serde_json::json!({ "role": "critic", "round": round }),
+            );
+        }
+
+        let cached = self.cache.lock().unwrap().get(&key).cloned();
+        let verdict = match cached {
+            Some(verdict) => CriticVerdict {
+                cached: true,
+                ..verdict
+            },
+            None => {
+                if let crate::usage::BudgetCheck::Exceeded(exceeded) = crate::usage::check_budget()
+                {
+                    return Err(StepError::BudgetExceeded(exceeded));
+                }
+                let response = self
+                    .client
+                    .chat(ChatRequest::new(vec![ChatMessage::user(prompt)]))
+                    .await
+                    .map_err(|e| fail(format!("critic request failed: {}", e)))?;
+                crate::usage::record_llm_call_as(
+                    "critic",
+                    target.agent,
+                    &response.model,
+                    response.usage.as_ref(),
+                );
+                let verdict = CriticVerdict::parse(&response.content).ok_or_else(|| {
+                    fail(format!(
+                        "critic reply is not a verdict: {}",
+                        response.content
+                    ))
+                })?;
+
+                let mut cache = self.cache.lock().unwrap();
+                if cache.len() >= MAX_CACHED_VERDICTS {
+                    cache.clear();
+                }
+                cache.insert(key, verdict.clone());
+                verdict
+            }
+        };
+
+        if let Some(stream) = target.event_stream {
+            stream.critic_completed(
+                target.agent,
+                target.workflow_id.to_string(),
+                serde_json::json!({
+                    "role": "critic",
+                    "round": round,
+                    "accepted": self.accepts(&verdict),
+                    "verdict": &verdict,
+                }),
+            );
+        }
+        Ok(verdict)
+    }
+}
+
+/// What a critic reviews against
+pub(crate) struct CriticTarget<'a> {
+    pub agent: &'a str,
+    pub workflow_id: &'a str,
+    pub instructions: &'a str,
+    pub input: &'a JsonValue,
+    pub event_stream: Option<&'a EventStream>,
+}
+
+/// The message that sends a critique back to the agent
+pub(crate) fn correction(critique: &str) -> String {
+    format!(
+        "A reviewer found problems with your answer:\n{}\n\nRevise your answer to address them.",
+        critique
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_verdict() {
+        let verdict = CriticVerdict::parse(
+            "Here you go:\n```json\n{\"pass\": false, \"score\": 0.4, \"critique\": \"cite sources\"}\n```",
+        )
+        .unwrap();
+        assert!(!verdict.pass);
+        assert_eq!(verdict.score, 0.4);
+        assert_eq!(verdict.critique, "cite sources");
+
+        assert_eq!(CriticVerdict::parse("{\"pass\": true}").unwrap().score, 1.0);
+        assert!(CriticVerdict::parse("looks good to me").is_none());
+        assert!(CriticVerdict::parse("{\"score\": 0.9}").is_none());
+    }
+}
@@ -0,0 +1,482 @@
+//! Linear workflows declared in YAML or TOML
+//!
+//! A [`WorkflowDefinition`] names its steps and what they use; a
+//! [`WorkflowFactory`] supplies the LLM client, the tools and the named
+//! transforms and conditions, and [`WorkflowDefinition::into_workflow`]
+//! puts the two together:
+//!
+//! ```yaml
+//! name: triage
+//! steps:
+//!   - type: agent
+//!     name: scorer
+//!     system_prompt: Rate the ticket's urgency from 0 to 1
+//!     tools: [lookup_customer]
+//!   - type: transform
+//!     name: extract
+//!     transform: parse_score
+//!   - type: conditional
+//!     name: route
+//!     condition: /score > 0.5
+//!     then: { type: agent, name: escalate, system_prompt: Draft a page }
+//!     else: { type: transform, name: archive, transform: archive }
+//! ```
+//!
+//! A condition is either the name of a predicate registered with the
+//! factory or a JSON pointer into the step's input, optionally compared
+//! with a JSON literal: `/score > 0.5`, `/status == "open"`, `/flags/vip`.
+//! Comparisons are `==`, `!=`, `>`, `>=`, `<` and `<=`; ordering applies to
+//! two numbers or two strings. A bare pointer tests truthiness: present and
+//! not `null`, `false`, `0`, or empty. A value that is missing or of another
+//! type never matches, not even with `!=`. A literal that isn't valid JSON
+//! is taken as a string, so `/status == open` works too.
+
+use crate::agent::{Agent, AgentConfig};
+use crate::error::{ConfigError, ConfigErrorCode};
+use crate::llm::LlmClient;
+use crate::tools::ToolRegistry;
+use crate::types::JsonValue;
+use crate::workflow::steps::{AgentStep, ConditionalStep, SubWorkflowStep, TransformStep};
+use crate::workflow::{Step, Workflow};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// A workflow as declared in a file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkflowDefinition {
+    pub name: String,
+
+    /// Input the run starts with, unless the caller sets its own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initial_input: Option<JsonValue>,
+
+    pub steps: Vec<StepDefinition>,
+}
+
+/// One step of a [`WorkflowDefinition`], tagged by `type`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StepDefinition {
+    Agent {
+        name: String,
+
+        #[serde(default)]
+        system_prompt: String,
+
+        /// Names of the factory's tools the agent gets; none by default
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        tools: Vec<String>,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_tool_iterations: Option<usize>,
+    },
+
+    /// Runs the factory's transform of that name
+    Transform { name: String, transform: String },
+
+    Conditional {
+        name: String,
+
+        /// A registered predicate's name or a pointer expression; see the
+        /// [module docs](self)
+        condition: String,
+
+        #[serde(rename = "then")]
+        then_step: Box<StepDefinition>,
+
+        #[serde(rename = "else")]
+        else_step: Box<StepDefinition>,
+    },
+
+    SubWorkflow {
+        name: String,
+        workflow: WorkflowDefinition,
+    },
+}
+
+impl StepDefinition {
+    pub fn name(&self) -> &str {
+        match self {
+            StepDefinition::Agent { name, .. }
+            | StepDefinition::Transform { name, .. }
+            | StepDefinition::Conditional { name, .. }
+            | StepDefinition::SubWorkflow { name, .. } => name,
+        }
+    }
+}
+
+type TransformFn = Arc<dyn Fn(JsonValue) -> JsonValue + Send + Sync>;
+type ConditionFn = Arc<dyn Fn(&JsonValue) -> bool + Send + Sync>;
+
+/// What definitions refer to by name: the agents' LLM client, the tool
+/// registry they pick tools from, and named transforms and conditions
+#[derive(Clone)]
+pub struct WorkflowFactory {
+    llm: LlmClient,
+    tools: Arc<ToolRegistry>,
+    transforms: HashMap<String, TransformFn>,
+    conditions: HashMap<String, ConditionFn>,
+}
+
+impl WorkflowFactory {
+    pub fn new(llm: LlmClient, tools: Arc<ToolRegistry>) -> Self {
+        Self {
+            llm,
+            tools,
+            transforms: HashMap::new(),
+            conditions: HashMap::new(),
+        }
+    }
+
+    /// Register a transform under `name` (builder-style)
+    pub fn with_transform<F>(mut self, name: impl Into<String>, transform: F) -> Self
+    where
+        F: Fn(JsonValue) -> JsonValue + Send + Sync + 'static,
+    {
+        self.transforms.insert(name.into(), Arc::new(transform));
+        self
+    }
+
+    /// Register a condition predicate under `name` (builder-style)
+    pub fn with_condition<F>(mut self, name: impl Into<String>, condition: F) -> Self
+    where
+        F: Fn(&JsonValue) -> bool + Send + Sync + 'static,
+    {
+        self.conditions.insert(name.into(), Arc::new(condition));
+        self
+    }
+}
+
+impl WorkflowDefinition {
+    pub fn from_yaml_str(yaml: &str) -> Result<Self, ConfigError> {
+        yaml_serde::from_str(yaml).map_err(|e| ConfigError {
+            code: ConfigErrorCode::ParseError,
+            message: format!("Failed to parse YAML: {}", e),
+            field: None,
+        })
+    }
+
+    pub fn from_toml_str(toml: &str) -> Result<Self, ConfigError> {
+        toml::from_str(toml).map_err(|e| ConfigError {
+            code: ConfigErrorCode::ParseError,
+            message: format!("Failed to parse TOML: {}", e),
+            field: None,
+        })
+    }
+
+    /// Load a definition, picking the format by extension (`.toml`,
+    /// `.yaml` or `.yml`)
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| ConfigError {
+            code: ConfigErrorCode::FileNotFound,
+            message: format!("Failed to read workflow file: {}", e),
+            field: Some(path.display().to_string()),
+        })?;
+
+        if crate::paths::has_extension(path, "toml") {
+            Self::from_toml_str(&content)
+        } else if crate::paths::has_extension(path, "yaml")
+            || crate::paths::has_extension(path, "yml")
+        {
+            Self::from_yaml_str(&content)
+        } else {
+            Err(ConfigError {
+                code: ConfigErrorCode::ParseError,
+                message: format!(
+                    "Unsupported file extension '{}'. Use .toml, .yaml, or .yml",
+                    path.extension().and_then(|s| s.to_str()).unwrap_or("")
+                ),
+                field: Some(path.display().to_string()),
+            })
+        }
+    }
+
+    /// Build the workflow, resolving names through `factory`
+    ///
+    /// Unknown tools, transforms and conditions, unparseable condition
+    /// expressions, and anything `WorkflowBuilder::try_build` rejects fail
+    /// with a `ConfigError` whose `field` is the offending path, e.g.
+    /// `steps[2].then.transform`.
+    pub fn into_workflow(self, factory: &WorkflowFactory) -> Result<Workflow, ConfigError> {
+        self.build(factory, "")
+    }
+
+    fn build(&self, factory: &WorkflowFactory, prefix: &str) -> Result<Workflow, ConfigError> {
+        let mut builder = Workflow::builder().name(self.name.clone());
+        for (index, step) in self.steps.iter().enumerate() {
+            builder = builder.step(step.build(factory, &format!("{}steps[{}]", prefix, index))?);
+        }
+        if let Some(input) = &self.initial_input {
+            builder = builder.initial_input(input.clone());
+        }
+        builder.try_build().map_err(|e| ConfigError {
+            code: ConfigErrorCode::ValidationFailed,
+            message: e.to_string(),
+            field: Some(match e.step_index {
+                Some(index) => format!("{}steps[{}]", prefix, index),
+                None => format!("{}steps", prefix),
+            }),
+        })
+    }
+}
+
+impl StepDefinition {
+    fn build(&self, factory: &WorkflowFactory, path: &str) -> Result<Box<dyn Step>, ConfigError> {
+        Ok(match self {
+            StepDefinition::Agent {
+                name,
+                system_prompt,
+                tools,
+                max_tool_iterations,
+            } => {
+                if let Some(index) = tools.iter().position(|tool| !factory.tools.has_tool(tool)) {
+                    return Err(unknown(
+                        "tool",
+                        &tools[index],
+                        format!("{}.tools[{}]", path, index),
+                    ));
+                }
+                let mut config = AgentConfig::builder(name.clone()).system_prompt(system_prompt);
+                if !tools.is_empty() {
+                    config = config.tools(Arc::new(factory.tools.subset(tools)));
+                }
+                if let Some(max) = max_tool_iterations {
+                    config = config.max_tool_iterations(*max);
+                }
+                let agent = Agent::new(config.build()).with_client(factory.llm.clone());
+                Box::new(AgentStep::from_agent(agent, name.clone()))
+            }
+
+            StepDefinition::Transform { name, transform } => {
+                let transform_fn = factory.transforms.get(transform).cloned().ok_or_else(|| {
+                    unknown("transform", transform, format!("{}.transform", path))
+                })?;
+                Box::new(TransformStep::new(name.clone(), move |data| {
+                    transform_fn(data)
+                }))
+            }
+
+            StepDefinition::Conditional {
+                name,
+                condition,
+                then_step,
+                else_step,
+            } => {
+                let condition_fn = resolve_condition(factory, condition, path)?;
+                Box::new(ConditionalStep::new(
+                    name.clone(),
+                    move |data| condition_fn(data),
+                    then_step.build(factory, &format!("{}.then", path))?,
+                    else_step.build(factory, &format!("{}.else", path))?,
+                ))
+            }
+
+            StepDefinition::SubWorkflow { name, workflow } => {
+                // Sub-workflows are rebuilt for every run; building one here
+                // surfaces its errors now
+                let prefix = format!("{}.workflow.", path);
+                workflow.build(factory, &prefix)?;
+                let (workflow, factory) = (workflow.clone(), factory.clone());
+                Box::new(SubWorkflowStep::new(name.clone(), move || {
+                    workflow
+                        .build(&factory, &prefix)
+                        .expect("checked when the parent workflow was built")
+                }))
+            }
+        })
+    }
+}
+
+fn unknown(kind: &str, name: &str, field: String) -> ConfigError {
+    ConfigError {
+        code: ConfigErrorCode::InvalidValue,
+        message: format!("Unknown {} '{}'", kind, name),
+        field: Some(field),
+    }
+}
+
+fn resolve_condition(
+    factory: &WorkflowFactory,
+    condition: &str,
+    path: &str,
+) -> Result<ConditionFn, ConfigError> {
+    let field = format!("{}.condition", path);
+    if !condition.trim_start().starts_with('/') {
+        return factory
+            .conditions
+            .get(condition.trim())
+            .cloned()
+            .ok_or_else(|| unknown("condition", condition, field));
+    }
+    let expression = PointerCondition::parse(condition).map_err(|message| ConfigError {
+        code: ConfigErrorCode::InvalidValue,
+        message: format!("Invalid condition '{}': {}", condition, message),
+        field: Some(field),
+    })?;
+    Ok(Arc::new(move |data| expression.matches(data)))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// `/pointer`, optionally followed by a comparison with a literal
+#[derive(Debug, Clone, PartialEq)]
+struct PointerCondition {
+    pointer: String,
+    comparison: Option<(Comparison, JsonValue)>,
+}
+
+impl PointerCondition {
+    fn parse(expression: &str) -> Result<Self, String> {
+        let expression = expression.trim();
+        let (pointer, rest) = expression
+            .split_once(char::is_whitespace)
+            .unwrap_or((expression, ""));
+        let rest = rest.trim_start();
+        if rest.is_empty() {
+            return Ok(Self {
+                pointer: pointer.to_string(),
+                comparison: None,
+            });
+        }
+
+        // Two-character operators first, so `>=` isn't read as `>`
+        let (comparison, literal) = [
+            ("==", Comparison::Eq),
+            ("!=", Comparison::Ne),
+            (">=", Comparison::Ge),
+            ("<=", Comparison::Le),
+            (">", Comparison::Gt),
+            ("<", Comparison::Lt),
+        ]
+        .into_iter()
+        .find_map(|(op, comparison)| {
+            rest.strip_prefix(op)
+                .map(|literal| (comparison, literal.trim()))
+        })
+        .ok_or_else(|| format!("expected a comparison after '{}'", pointer))?;
+        if literal.is_empty() {
+            return Err("expected a value to compare with".to_string());
+        }
+        let literal = serde_json::from_str(literal)
+            .unwrap_or_else(|_| JsonValue::String(literal.to_string()));
+        Ok(Self {
+            pointer: pointer.to_string(),
+            comparison: Some((comparison, literal)),
+        })
+    }
+
+    fn matches(&self, data: &JsonValue) -> bool {
+        let Some(value) = data.pointer(&self.pointer) else {
+            return false;
+        };
+        let Some((comparison, literal)) = &self.comparison else {
+            return truthy(value);
+        };
+        if let Comparison::Eq | Comparison::Ne = comparison {
+            return equal(value, literal)
+                .is_some_and(|equal| equal == (*comparison == Comparison::Eq));
+        }
+        let Some(ordering) = order(value, literal) else {
+            return false;
+        };
+        match comparison {
+            Comparison::Gt => ordering == Ordering::Greater,
+            Comparison::Ge => ordering != Ordering::Less,
+            Comparison::Lt => ordering == Ordering::Less,
+            _ => ordering != Ordering::Greater,
+        }
+    }
+}
+
+fn truthy(value: &JsonValue) -> bool {
+    match value {
+        JsonValue::Null => false,
+        JsonValue::Bool(b) => *b,
+        JsonValue::Number(n) => n.as_f64() != Some(0.0),
+        JsonValue::String(s) => !s.is_empty(),
+        JsonValue::Array(a) => !a.is_empty(),
+        JsonValue::Object(o) => !o.is_empty(),
+    }
+}
+
+/// Whether two values of the same type are equal; numbers compare by value
+fn equal(value: &JsonValue, literal: &JsonValue) -> Option<bool> {
+    match (value, literal) {
+        (JsonValue::Number(a), JsonValue::Number(b)) => Some(a.as_f64()? == b.as_f64()?),
+        (a, b) if std::mem::discriminant(a) == std::mem::discriminant(b) => Some(a == b),
+        _ => None,
+    }
+}
+
+/// Numbers order by value and strings lexically; nothing else orders
+fn order(value: &JsonValue, literal: &JsonValue) -> Option<Ordering> {
+    match (value, literal) {
+        (JsonValue::Number(a), JsonValue::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (JsonValue::String(a), JsonValue::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn matches(expression: &str, data: JsonValue) -> bool {
+        PointerCondition::parse(expression).unwrap().matches(&data)
+    }
+
+    #[test]
+    fn test_numeric_comparisons() {
+        let data = json!({ "score": 0.7 });
+        assert!(matches("/score > 0.5", data.clone()));
+        assert!(matches("/score >= 0.7", data.clone()));
+        assert!(!matches("/score < 0.5", data.clone()));
+        assert!(matches("/score <= 0.7", data.clone()));
+        assert!(matches("/score == 0.7", data.clone()));
+        assert!(matches("/score != 1", data));
+        assert!(matches("/n == 1.0", json!({ "n": 1 })));
+    }
+
+    #[test]
+    fn test_strings_and_bare_literals() {
+        let data = json!({ "ticket": { "status": "open" } });
+        assert!(matches("/ticket/status == \"open\"", data.clone()));
+        assert!(matches("/ticket/status == open", data.clone()));
+        assert!(!matches("/ticket/status != open", data.clone()));
+        assert!(matches("/ticket/status > closed", data));
+    }
+
+    #[test]
+    fn test_truthiness_and_missing_values() {
+        assert!(matches("/vip", json!({ "vip": true })));
+        assert!(matches("/tags", json!({ "tags": ["a"] })));
+        assert!(!matches("/tags", json!({ "tags": [] })));
+        assert!(!matches("/vip", json!({})));
+
+        // Missing or mismatched values never match
+        assert!(!matches("/score != 1", json!({})));
+        assert!(!matches("/score > 0.5", json!({ "score": "high" })));
+        assert!(!matches("/ok > false", json!({ "ok": true })));
+        assert!(!matches("/ok < false", json!({ "ok": true })));
+        assert!(matches("/ok != false", json!({ "ok": true })));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(PointerCondition::parse("/score ~ 1").is_err());
+        assert!(PointerCondition::parse("/score >").is_err());
+    }
+}
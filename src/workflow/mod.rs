@@ -1,13 +1,40 @@
-use crate::context::{ContextManager, WorkflowContext};
+use crate::artifact::ArtifactRef;
+use crate::context::{
+    ContextDiagnostics, ContextManager, ContextStore, TokenEstimator, WorkflowContext,
+};
+use crate::error::{RuntimeError, WorkflowError, WorkflowErrorCode};
+use crate::event::sampling::TraceDecision;
+use crate::limits::ConversationLimits;
+use crate::persist::{PersistError, PersistFormat};
+use crate::pii::PiiFindings;
 use crate::types::JsonValue;
+use crate::usage::{UsageTotals, WorkflowUsage};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
+pub mod compat;
+pub mod contract;
+pub mod critic;
+pub mod definition;
+pub mod report;
+pub use crate::schema;
 pub mod step;
 pub mod steps;
 
+pub use compat::{check_run_compatibility, CompatibilityIssue, CompatibilityReport, StepRename};
+pub use contract::{ContractBoundary, ContractViolation};
+pub use critic::{CriticConfig, CriticReport, CriticVerdict};
+pub use definition::{StepDefinition, WorkflowDefinition, WorkflowFactory};
+pub use report::{ReportFiles, ReportOptions, REPORT_VERSION};
+pub use schema::InputSchema;
 pub use step::{ExecutionContext, Step, StepError, StepInput, StepOutput, StepResult, StepType};
-pub use steps::{AgentStep, ConditionalStep, SubWorkflowStep, TransformStep};
+pub use steps::{
+    AgentStep, ApprovalQueue, ApprovalRecord, ApprovalStep, ConditionalStep, Decision,
+    ForEachFailureMode, ForEachStep, LoopExhaustedMode, LoopStep, OnError, ParallelFailureMode,
+    ParallelOutput, ParallelStep, PendingApproval, PolicyOutcome, PolicyStep, StepPolicy,
+    StepStatus, SubWorkflowStep, SwitchStep, TransformStep, TryTransformStep, WaitStep,
+};
 
 #[cfg(test)]
 mod tests;
@@ -20,17 +47,49 @@ pub enum WorkflowState {
     Running,
     Completed,
     Failed,
+    Canceled,
+    /// Stopped before an LLM call its [`Budget`](crate::usage::Budget) no
+    /// longer allowed
+    #[serde(rename = "budget_exceeded")]
+    BudgetExceeded,
 }
 
 /// Workflow definition
 pub struct Workflow {
     pub id: String,
+
+    /// Unique ID of this run of the workflow; `id` is shared by every run
+    /// of a named workflow
+    pub run_id: String,
     pub steps: Vec<Box<dyn Step>>,
     pub initial_input: JsonValue,
     pub state: WorkflowState,
 
     /// Optional workflow-managed chat history context
     pub context: Option<Arc<RwLock<WorkflowContext>>>,
+
+    /// Optional context utilization diagnostics
+    pub context_diagnostics: Option<ContextDiagnostics>,
+
+    /// Free-form labels, e.g. for always-sampled traces
+    pub labels: Vec<String>,
+
+    /// Checked against `initial_input` before the first step runs
+    pub input_schema: Option<InputSchema>,
+
+    /// Document the run writes; exported and inlined into the final output
+    pub document: Option<crate::document::LiveDocument>,
+
+    /// Where the context is loaded from when the run starts and saved to
+    /// after each step, under the key
+    pub context_store: Option<(Arc<dyn ContextStore>, String)>,
+
+    /// Check step outputs against declared schemas between steps (see
+    /// [`contract`])
+    pub check_contracts: bool,
+
+    /// Caps on the run's LLM use; see [`crate::usage::Budget`]
+    pub budget: Option<crate::usage::Budget>,
 }
 
 impl Workflow {
@@ -50,6 +109,14 @@ impl Workflow {
         self.context.as_ref()
     }
 
+    /// A lock-free handle on the context's snapshots, for dashboards that
+    /// watch the run while it executes
+    pub fn context_monitor(&self) -> Option<crate::context::ContextMonitor> {
+        self.context
+            .as_ref()
+            .map(|ctx| ctx.read().unwrap().monitor())
+    }
+
     /// Take a snapshot of the current context for checkpointing
     /// Returns a serializable clone of the context
     pub fn checkpoint_context(&self) -> Option<WorkflowContext> {
@@ -58,10 +125,85 @@ impl Workflow {
 
     /// Restore context from a checkpoint
     /// This allows resuming workflows with saved conversation state
-    pub fn restore_context(&mut self, context: WorkflowContext) {
+    pub fn restore_context(&mut self, mut context: WorkflowContext) {
+        // Checkpoints don't carry the context manager; keep the current one
+        let manager = self
+            .context
+            .as_ref()
+            .and_then(|ctx| ctx.read().unwrap().manager().cloned());
+        if let (None, Some(manager)) = (context.manager(), manager) {
+            context.set_manager(manager);
+        }
         self.context = Some(Arc::new(RwLock::new(context)));
     }
 
+    /// A fresh copy of this workflow to run again, under a new id
+    ///
+    /// Steps are copied with [`Step::clone_step`], so agents share their
+    /// LLM clients and tool registries and closures aren't re-created. The
+    /// copy gets its own context, starting from this workflow's history,
+    /// memory and limits; runs of two copies never see each other's
+    /// messages. A live document and a context store key are shared, so
+    /// build workflows that use them per run instead.
+    ///
+    /// Fails with [`WorkflowErrorCode::StepNotCloneable`] naming the first
+    /// step that can't be copied.
+    pub fn instantiate(&self) -> Result<Workflow, WorkflowError> {
+        let id = format!("{}_{}", self.id, uuid::Uuid::new_v4());
+        let steps = self
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(index, step)| {
+                step.clone_step().ok_or_else(|| {
+                    WorkflowError::new(
+                        WorkflowErrorCode::StepNotCloneable,
+                        format!(
+                            "step '{}' (or a step inside it) doesn't implement clone_step",
+                            step.name()
+                        ),
+                    )
+                    .at_step(index, step.name())
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        let context = self.context.as_ref().map(|ctx| {
+            let mut ctx = ctx.read().unwrap().clone();
+            ctx.metadata.workflow_id = id.clone();
+            Arc::new(RwLock::new(ctx))
+        });
+
+        Ok(Workflow {
+            id,
+            run_id: new_run_id(),
+            steps,
+            initial_input: self.initial_input.clone(),
+            state: WorkflowState::Pending,
+            context,
+            context_diagnostics: self.context_diagnostics.clone(),
+            labels: self.labels.clone(),
+            input_schema: self.input_schema.clone(),
+            document: self.document.clone(),
+            context_store: self.context_store.clone(),
+            check_contracts: self.check_contracts,
+            budget: self.budget.clone(),
+        })
+    }
+
+    /// Check `initial_input` against the input schema, returning the input
+    /// the first step will receive
+    pub fn validate_input(&self) -> Result<JsonValue, RuntimeError> {
+        match &self.input_schema {
+            Some(schema) => schema.validate(&self.initial_input).map_err(|violations| {
+                RuntimeError::InvalidInput {
+                    workflow: self.id.clone(),
+                    violations,
+                }
+            }),
+            None => Ok(self.initial_input.clone()),
+        }
+    }
+
     /// Generate a Mermaid flowchart diagram of this workflow with full expansion
     pub fn to_mermaid(&self) -> String {
         let mut diagram = String::from("flowchart TD\n");
@@ -98,8 +240,16 @@ impl Workflow {
         diagram.push_str(
             "    classDef subworkflowStyle fill:#e8f5e9,stroke:#1b5e20,stroke-width:2px\n",
         );
+        diagram
+            .push_str("    classDef parallelStyle fill:#fffde7,stroke:#f57f17,stroke-width:2px\n");
         diagram
             .push_str("    classDef convergeStyle fill:#f5f5f5,stroke:#757575,stroke-width:1px\n");
+        diagram.push_str("    classDef loopStyle fill:#fce4ec,stroke:#880e4f,stroke-width:2px\n");
+        diagram
+            .push_str("    classDef forEachStyle fill:#e0f2f1,stroke:#004d40,stroke-width:2px\n");
+        diagram
+            .push_str("    classDef approvalStyle fill:#fff8e1,stroke:#ff6f00,stroke-width:2px\n");
+        diagram.push_str("    classDef waitStyle fill:#eceff1,stroke:#37474f,stroke-width:2px\n");
 
         diagram
     }
@@ -121,6 +271,42 @@ impl Workflow {
 
         match step_type {
             StepType::Conditional => {
+                if let Some(cases) = step.get_cases() {
+                    let converge_node = self.generate_switch_inline(
+                        diagram,
+                        node_counter,
+                        entry_node,
+                        step.as_ref(),
+                        cases,
+                        false,
+                    );
+
+                    // Continue with next step
+                    if step_index + 1 < self.steps.len() {
+                        let next_step = &self.steps[step_index + 1];
+                        if next_step.step_type() == StepType::SubWorkflow {
+                            return self.generate_mermaid_steps(
+                                diagram,
+                                node_counter,
+                                &converge_node,
+                                step_index + 1,
+                            );
+                        } else {
+                            *node_counter += 1;
+                            let next_node = format!("N{}", node_counter);
+                            diagram.push_str(&format!("    {} --> {}\n", converge_node, next_node));
+                            return self.generate_mermaid_steps(
+                                diagram,
+                                node_counter,
+                                &next_node,
+                                step_index + 1,
+                            );
+                        }
+                    } else {
+                        return converge_node;
+                    }
+                }
+
                 // Get branches
                 if let Some((then_step, else_step)) = step.get_branches() {
                     let conditional_node = entry_node;
@@ -228,6 +414,80 @@ impl Workflow {
                     }
                 }
             }
+            StepType::Parallel => {
+                if let Some(branches) = step.get_parallel_branches() {
+                    let join_node = self.generate_parallel_inline(
+                        diagram,
+                        node_counter,
+                        entry_node,
+                        step.as_ref(),
+                        branches,
+                    );
+
+                    // Continue with next step
+                    if step_index + 1 < self.steps.len() {
+                        let next_step = &self.steps[step_index + 1];
+                        if next_step.step_type() == StepType::SubWorkflow {
+                            return self.generate_mermaid_steps(
+                                diagram,
+                                node_counter,
+                                &join_node,
+                                step_index + 1,
+                            );
+                        } else {
+                            *node_counter += 1;
+                            let next_node = format!("N{}", node_counter);
+                            diagram.push_str(&format!("    {} --> {}\n", join_node, next_node));
+                            return self.generate_mermaid_steps(
+                                diagram,
+                                node_counter,
+                                &next_node,
+                                step_index + 1,
+                            );
+                        }
+                    } else {
+                        return join_node;
+                    }
+                }
+            }
+            StepType::Loop => {
+                if let Some((body, max_iterations)) = step.get_loop_body() {
+                    let check_node = self.generate_loop_inline(
+                        diagram,
+                        node_counter,
+                        entry_node,
+                        step.as_ref(),
+                        body,
+                        max_iterations,
+                        false,
+                    );
+
+                    // Continue with next step
+                    if step_index + 1 < self.steps.len() {
+                        let next_step = &self.steps[step_index + 1];
+                        if next_step.step_type() == StepType::SubWorkflow {
+                            return self.generate_mermaid_steps(
+                                diagram,
+                                node_counter,
+                                &check_node,
+                                step_index + 1,
+                            );
+                        } else {
+                            *node_counter += 1;
+                            let next_node = format!("N{}", node_counter);
+                            diagram.push_str(&format!("    {} --> {}\n", check_node, next_node));
+                            return self.generate_mermaid_steps(
+                                diagram,
+                                node_counter,
+                                &next_node,
+                                step_index + 1,
+                            );
+                        }
+                    } else {
+                        return check_node;
+                    }
+                }
+            }
             StepType::SubWorkflow => {
                 // Get sub-workflow and expand it
                 if let Some(sub_wf) = step.get_sub_workflow() {
@@ -289,6 +549,162 @@ impl Workflow {
         entry_node.to_string()
     }
 
+    /// Generate a parallel step as a fork into its branches and a join
+    /// Returns the join node
+    fn generate_parallel_inline(
+        &self,
+        diagram: &mut String,
+        node_counter: &mut usize,
+        fork_node: &str,
+        step: &dyn Step,
+        branches: Vec<&dyn Step>,
+    ) -> String {
+        diagram.push_str(&format!(
+            "    {}[/\"{}\"\\]:::parallelStyle\n",
+            fork_node,
+            step.name()
+        ));
+
+        let mut exit_nodes = Vec::with_capacity(branches.len());
+        for branch in branches {
+            *node_counter += 1;
+            let branch_node = format!("N{}", node_counter);
+
+            let exit_node = match branch.get_sub_workflow() {
+                Some(sub_wf) if branch.step_type() == StepType::SubWorkflow => {
+                    let (_entry, exit) = self.generate_subworkflow_inline(
+                        diagram,
+                        node_counter,
+                        &branch_node,
+                        sub_wf,
+                        branch.name(),
+                    );
+                    exit
+                }
+                _ => {
+                    self.generate_step_node(diagram, &branch_node, branch);
+                    branch_node.clone()
+                }
+            };
+
+            diagram.push_str(&format!("    {} --> {}\n", fork_node, branch_node));
+            exit_nodes.push(exit_node);
+        }
+
+        *node_counter += 1;
+        let join_node = format!("N{}", node_counter);
+        diagram.push_str(&format!("    {}[\\\"join\"/]:::parallelStyle\n", join_node));
+        for exit_node in exit_nodes {
+            diagram.push_str(&format!("    {} --> {}\n", exit_node, join_node));
+        }
+
+        join_node
+    }
+
+    /// Generate a switch step as a diamond with one labeled edge per case,
+    /// converging after the cases
+    /// Returns the convergence node
+    fn generate_switch_inline(
+        &self,
+        diagram: &mut String,
+        node_counter: &mut usize,
+        switch_node: &str,
+        step: &dyn Step,
+        cases: Vec<(&str, &dyn Step)>,
+        indented: bool,
+    ) -> String {
+        let indent = if indented { "        " } else { "    " };
+        diagram.push_str(&format!(
+            "{}{}{{{{\"{}\"}}}}:::conditionalStyle\n",
+            indent,
+            switch_node,
+            step.name()
+        ));
+
+        let mut exit_nodes = Vec::with_capacity(cases.len());
+        for (label, case) in cases {
+            *node_counter += 1;
+            let case_node = format!("N{}", node_counter);
+
+            let exit_node = match case.get_sub_workflow() {
+                Some(sub_wf) if !indented && case.step_type() == StepType::SubWorkflow => {
+                    let (_entry, exit) = self.generate_subworkflow_inline(
+                        diagram,
+                        node_counter,
+                        &case_node,
+                        sub_wf,
+                        case.name(),
+                    );
+                    exit
+                }
+                _ => {
+                    if indented {
+                        self.generate_step_node_indented(diagram, &case_node, case);
+                    } else {
+                        self.generate_step_node(diagram, &case_node, case);
+                    }
+                    case_node.clone()
+                }
+            };
+
+            diagram.push_str(&format!(
+                "{}{} -->|\"{}\"| {}\n",
+                indent, switch_node, label, case_node
+            ));
+            exit_nodes.push(exit_node);
+        }
+
+        *node_counter += 1;
+        let converge_node = format!("N{}", node_counter);
+        diagram.push_str(&format!(
+            "{}{}(( )):::convergeStyle\n",
+            indent, converge_node
+        ));
+        for exit_node in exit_nodes {
+            diagram.push_str(&format!("{}{} --> {}\n", indent, exit_node, converge_node));
+        }
+
+        converge_node
+    }
+
+    /// Generate a loop step as its body followed by a condition check with
+    /// an edge back to the body
+    /// Returns the check node
+    #[allow(clippy::too_many_arguments)]
+    fn generate_loop_inline(
+        &self,
+        diagram: &mut String,
+        node_counter: &mut usize,
+        body_node: &str,
+        step: &dyn Step,
+        body: &dyn Step,
+        max_iterations: usize,
+        indented: bool,
+    ) -> String {
+        let indent = if indented { "        " } else { "    " };
+        if indented {
+            self.generate_step_node_indented(diagram, body_node, body);
+        } else {
+            self.generate_step_node(diagram, body_node, body);
+        }
+
+        *node_counter += 1;
+        let check_node = format!("N{}", node_counter);
+        diagram.push_str(&format!(
+            "{}{}{{{{\"{}\"}}}}:::loopStyle\n",
+            indent,
+            check_node,
+            step.name()
+        ));
+        diagram.push_str(&format!("{}{} --> {}\n", indent, body_node, check_node));
+        diagram.push_str(&format!(
+            "{}{} -.->|\"↻ repeat, max {}\"| {}\n",
+            indent, check_node, max_iterations, body_node
+        ));
+
+        check_node
+    }
+
     /// Generate a subworkflow inline as a subgraph
     /// Returns (entry_node, exit_node) tuple
     fn generate_subworkflow_inline(
@@ -346,6 +762,28 @@ impl Workflow {
 
         match step_type {
             StepType::Conditional => {
+                if let Some(cases) = step.get_cases() {
+                    let converge_node = self.generate_switch_inline(
+                        diagram,
+                        node_counter,
+                        entry_node,
+                        step.as_ref(),
+                        cases,
+                        true,
+                    );
+
+                    *node_counter += 1;
+                    let next_node = format!("N{}", node_counter);
+                    diagram.push_str(&format!("        {} --> {}\n", converge_node, next_node));
+
+                    return self.generate_mermaid_steps_in_subgraph(
+                        diagram,
+                        node_counter,
+                        &next_node,
+                        step_index + 1,
+                    );
+                }
+
                 if let Some((then_step, else_step)) = step.get_branches() {
                     let conditional_node = entry_node;
 
@@ -389,6 +827,70 @@ impl Workflow {
                     );
                 }
             }
+            StepType::Parallel => {
+                if let Some(branches) = step.get_parallel_branches() {
+                    let fork_node = entry_node;
+                    diagram.push_str(&format!(
+                        "        {}[/\"{}\"\\]:::parallelStyle\n",
+                        fork_node,
+                        step.name()
+                    ));
+
+                    let mut branch_nodes = Vec::with_capacity(branches.len());
+                    for branch in branches {
+                        *node_counter += 1;
+                        let branch_node = format!("N{}", node_counter);
+                        self.generate_step_node_indented(diagram, &branch_node, branch);
+                        diagram.push_str(&format!("        {} --> {}\n", fork_node, branch_node));
+                        branch_nodes.push(branch_node);
+                    }
+
+                    *node_counter += 1;
+                    let join_node = format!("N{}", node_counter);
+                    diagram.push_str(&format!(
+                        "        {}[\\\"join\"/]:::parallelStyle\n",
+                        join_node
+                    ));
+                    for branch_node in branch_nodes {
+                        diagram.push_str(&format!("        {} --> {}\n", branch_node, join_node));
+                    }
+
+                    *node_counter += 1;
+                    let next_node = format!("N{}", node_counter);
+                    diagram.push_str(&format!("        {} --> {}\n", join_node, next_node));
+
+                    return self.generate_mermaid_steps_in_subgraph(
+                        diagram,
+                        node_counter,
+                        &next_node,
+                        step_index + 1,
+                    );
+                }
+            }
+            StepType::Loop => {
+                if let Some((body, max_iterations)) = step.get_loop_body() {
+                    let check_node = self.generate_loop_inline(
+                        diagram,
+                        node_counter,
+                        entry_node,
+                        step.as_ref(),
+                        body,
+                        max_iterations,
+                        true,
+                    );
+
+                    *node_counter += 1;
+                    let next_node = format!("N{}", node_counter);
+                    diagram.push_str(&format!("        {} --> {}\n", check_node, next_node));
+
+                    return self.generate_mermaid_steps_in_subgraph(
+                        diagram,
+                        node_counter,
+                        &next_node,
+                        step_index + 1,
+                    );
+                }
+            }
             StepType::SubWorkflow => {
                 // Nested subworkflow within a subworkflow
                 if let Some(nested_wf) = step.get_sub_workflow() {
@@ -484,6 +986,22 @@ impl Workflow {
                 format!("            {}{{\"{}\"}}", current_node, step_name),
                 ":::conditionalStyle",
             ),
+            StepType::ForEach => (
+                format!(
+                    "            {}[\"{}\"]",
+                    current_node,
+                    for_each_label(step.as_ref())
+                ),
+                ":::forEachStyle",
+            ),
+            StepType::Approval => (
+                format!("            {}([\"{}\"])", current_node, step_name),
+                ":::approvalStyle",
+            ),
+            StepType::Wait => (
+                format!("            {}((\"⏱ {}\"))", current_node, step_name),
+                ":::waitStyle",
+            ),
             _ => (
                 format!("            {}[\"{}\"]", current_node, step_name),
                 "",
@@ -522,6 +1040,18 @@ impl Workflow {
                 format!("    {}[[\"{}\"]", node_id, step_name),
                 ":::subworkflowStyle",
             ),
+            StepType::ForEach => (
+                format!("    {}[\"{}\"]", node_id, for_each_label(step)),
+                ":::forEachStyle",
+            ),
+            StepType::Approval => (
+                format!("    {}([\"{}\"])", node_id, step_name),
+                ":::approvalStyle",
+            ),
+            StepType::Wait => (
+                format!("    {}((\"⏱ {}\"))", node_id, step_name),
+                ":::waitStyle",
+            ),
             _ => (format!("    {}[\"{}\"]", node_id, step_name), ""),
         };
 
@@ -542,6 +1072,18 @@ impl Workflow {
                 format!("        {}[/\"{}\"/]", node_id, step_name),
                 ":::transformStyle",
             ),
+            StepType::ForEach => (
+                format!("        {}[\"{}\"]", node_id, for_each_label(step)),
+                ":::forEachStyle",
+            ),
+            StepType::Approval => (
+                format!("        {}([\"{}\"])", node_id, step_name),
+                ":::approvalStyle",
+            ),
+            StepType::Wait => (
+                format!("        {}((\"⏱ {}\"))", node_id, step_name),
+                ":::waitStyle",
+            ),
             _ => (format!("        {}[\"{}\"]", node_id, step_name), ""),
         };
 
@@ -549,6 +1091,20 @@ impl Workflow {
     }
 }
 
+/// A for-each step's node label: its name, the step it runs on each item
+/// and how many run at once, e.g. `summarize_all ×N<br/>summarize, 3 at a time`
+fn for_each_label(step: &dyn Step) -> String {
+    match step.get_for_each_body() {
+        Some((body, concurrency)) => format!(
+            "{} ×N<br/>{}, {} at a time",
+            step.name(),
+            body.name(),
+            concurrency
+        ),
+        None => format!("{} ×N", step.name()),
+    }
+}
+
 /// Builder for Workflow
 pub struct WorkflowBuilder {
     name: Option<String>,
@@ -558,6 +1114,15 @@ pub struct WorkflowBuilder {
     max_context_tokens: Option<usize>,
     input_output_ratio: Option<f64>,
     restored_context: Option<WorkflowContext>,
+    context_diagnostics: Option<ContextDiagnostics>,
+    conversation_limits: Option<ConversationLimits>,
+    labels: Vec<String>,
+    input_schema: Option<InputSchema>,
+    document: Option<crate::document::LiveDocument>,
+    context_store: Option<(Arc<dyn ContextStore>, String)>,
+    allow_empty: bool,
+    check_contracts: bool,
+    budget: Option<crate::usage::Budget>,
 }
 
 impl WorkflowBuilder {
@@ -570,6 +1135,15 @@ impl WorkflowBuilder {
             max_context_tokens: None,
             input_output_ratio: None,
             restored_context: None,
+            context_diagnostics: None,
+            conversation_limits: None,
+            labels: Vec::new(),
+            input_schema: None,
+            document: None,
+            context_store: None,
+            allow_empty: false,
+            check_contracts: true,
+            budget: None,
         }
     }
 
@@ -591,6 +1165,12 @@ impl WorkflowBuilder {
         self
     }
 
+    /// Add a step that is retried and, once retries run out, failed,
+    /// skipped or replaced by a fallback value as `policy` says
+    pub fn add_step_with_policy(self, step: Box<dyn Step>, policy: StepPolicy) -> Self {
+        self.add_step(Box::new(PolicyStep::new(step, policy)))
+    }
+
     /// Set the initial input
     pub fn initial_input(mut self, input: JsonValue) -> Self {
         self.initial_input = Some(input);
@@ -598,6 +1178,9 @@ impl WorkflowBuilder {
     }
 
     /// Enable chat history management with a context manager strategy
+    ///
+    /// Agent steps see the conversation so far and add their turns to it.
+    /// Before each agent step, `manager` may prune the history.
     pub fn with_chat_history(mut self, manager: Arc<dyn ContextManager>) -> Self {
         self.context_manager = Some(manager);
         self
@@ -622,15 +1205,237 @@ impl WorkflowBuilder {
         self
     }
 
+    /// Keep the context in `store` under `key`: the run starts from the
+    /// context saved there, if any, and saves it after each step completes.
+    /// Enables chat history if no context manager is set. See
+    /// [`crate::context::store`].
+    pub fn with_context_store(
+        mut self,
+        store: Arc<dyn ContextStore>,
+        key: impl Into<String>,
+    ) -> Self {
+        self.context_store = Some((store, key.into()));
+        self
+    }
+
+    /// Emit a context analysis event when history utilization crosses `threshold`
+    /// (fraction of the input token budget, e.g. 0.8)
+    pub fn with_context_diagnostics(
+        mut self,
+        threshold: f64,
+        estimator: Arc<dyn TokenEstimator>,
+    ) -> Self {
+        self.context_diagnostics = Some(ContextDiagnostics::new(threshold, estimator));
+        self
+    }
+
+    /// Cap turns and messages in the shared chat history. Enables chat
+    /// history if no context manager is set. A restored context keeps the
+    /// caps it was checkpointed with.
+    pub fn with_conversation_limits(mut self, limits: ConversationLimits) -> Self {
+        self.conversation_limits = Some(limits);
+        self
+    }
+
+    /// Validate the initial input before any step runs. An invalid input
+    /// fails the run with `RuntimeError::InvalidInput` in the Workflow
+    /// Failed event.
+    pub fn with_input_schema(mut self, schema: InputSchema) -> Self {
+        self.input_schema = Some(schema);
+        self
+    }
+
+    /// Have the run write `document`: its edits are emitted as events, and
+    /// when the run completes the markdown is exported as an artifact and
+    /// inlined into the final output under the document's output key
+    pub fn with_live_document(mut self, document: crate::document::LiveDocument) -> Self {
+        self.document = Some(document);
+        self
+    }
+
+    /// Stop the run before an LLM call that `budget` no longer allows. The
+    /// run ends as `WorkflowState::BudgetExceeded`, keeping the steps it
+    /// completed.
+    pub fn with_budget(mut self, budget: crate::usage::Budget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Attach a label to the workflow's runs
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.labels.push(label.into());
+        self
+    }
+
+    /// Don't check step outputs against declared input and output schemas
+    /// while running, e.g. in production once the contracts are tested.
+    /// `try_build` still compares adjacent schemas.
+    pub fn skip_contract_checks(mut self) -> Self {
+        self.check_contracts = false;
+        self
+    }
+
+    /// Let [`try_build`](Self::try_build) accept a workflow without steps
+    pub fn allow_empty(mut self) -> Self {
+        self.allow_empty = true;
+        self
+    }
+
+    /// Build the workflow after checking its configuration
+    ///
+    /// Fails with the first problem found: no steps (unless
+    /// [`allow_empty`](Self::allow_empty)), two steps with the same name,
+    /// context budget options without chat history, an unusable budget, or
+    /// adjacent steps whose declared schemas can never match (see
+    /// [`contract`]). Errors about a step carry its index and name.
+    pub fn try_build(self) -> Result<Workflow, WorkflowError> {
+        match self.problems().into_iter().next() {
+            Some(problem) => Err(problem),
+            None => Ok(self.build_unchecked()),
+        }
+    }
+
+    /// Build the workflow without validation, logging what
+    /// [`try_build`](Self::try_build) would reject as `tracing` warnings.
     pub fn build(self) -> Workflow {
+        for problem in self.problems() {
+            tracing::warn!(
+                workflow = self.name.as_deref().unwrap_or("(unnamed)"),
+                "{}",
+                problem
+            );
+        }
+        self.build_unchecked()
+    }
+
+    /// Everything `try_build` rejects, in the order it checks
+    fn problems(&self) -> Vec<WorkflowError> {
+        let mut problems = Vec::new();
+
+        if self.steps.is_empty() && !self.allow_empty {
+            problems.push(WorkflowError::new(
+                WorkflowErrorCode::EmptyWorkflow,
+                "workflow has no steps",
+            ));
+        }
+
+        // Step names identify steps in events and run records
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        for (index, step) in self.steps.iter().enumerate() {
+            if let Some(&first) = seen.get(step.name()) {
+                problems.push(
+                    WorkflowError::new(
+                        WorkflowErrorCode::DuplicateStepName,
+                        format!(
+                            "step name '{}' is already used by step {}",
+                            step.name(),
+                            first
+                        ),
+                    )
+                    .at_step(index, step.name()),
+                );
+            } else {
+                seen.insert(step.name(), index);
+            }
+        }
+
+        for (index, pair) in self.steps.windows(2).enumerate() {
+            let (step, next) = (&pair[0], &pair[1]);
+            let (Some(output), Some(input)) = (step.output_schema(), next.input_schema()) else {
+                continue;
+            };
+            let found = contract::incompatibilities(output, input);
+            if !found.is_empty() {
+                let found: Vec<String> = found.iter().map(ToString::to_string).collect();
+                problems.push(
+                    WorkflowError::new(
+                        WorkflowErrorCode::IncompatibleStepContracts,
+                        format!(
+                            "output schema of step '{}' can't match the input schema of step '{}': {}",
+                            step.name(),
+                            next.name(),
+                            found.join("; ")
+                        ),
+                    )
+                    .at_step(index, step.name()),
+                );
+            }
+        }
+
+        let budget_options: Vec<&str> = [
+            self.max_context_tokens.map(|_| "with_max_context_tokens"),
+            self.input_output_ratio.map(|_| "with_input_output_ratio"),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let has_history = self.context_manager.is_some()
+            || self.conversation_limits.is_some()
+            || self.restored_context.is_some()
+            || self.context_store.is_some();
+        if !budget_options.is_empty() && !has_history {
+            problems.push(WorkflowError::new(
+                WorkflowErrorCode::MissingContextManager,
+                format!(
+                    "{} {} no effect without with_chat_history",
+                    budget_options.join(" and "),
+                    if budget_options.len() == 1 {
+                        "has"
+                    } else {
+                        "have"
+                    }
+                ),
+            ));
+        }
+
+        if self.max_context_tokens == Some(0) {
+            problems.push(WorkflowError::new(
+                WorkflowErrorCode::InvalidContextBudget,
+                "max_context_tokens must be greater than 0",
+            ));
+        }
+        if let Some(ratio) = self.input_output_ratio {
+            if !ratio.is_finite() || ratio <= 0.0 {
+                problems.push(WorkflowError::new(
+                    WorkflowErrorCode::InvalidContextBudget,
+                    format!(
+                        "input_output_ratio must be a positive number, got {}",
+                        ratio
+                    ),
+                ));
+            }
+        }
+        // The budget is only applied when both are given
+        if budget_options.len() == 1 && has_history {
+            let missing = if self.max_context_tokens.is_some() {
+                "with_input_output_ratio"
+            } else {
+                "with_max_context_tokens"
+            };
+            problems.push(WorkflowError::new(
+                WorkflowErrorCode::InvalidContextBudget,
+                format!("{} is ignored without {}", budget_options[0], missing),
+            ));
+        }
+
+        problems
+    }
+
+    fn build_unchecked(self) -> Workflow {
         let workflow_id = self
             .name
             .unwrap_or_else(|| format!("wf_{}", uuid::Uuid::new_v4()));
 
         // Use restored context if provided, otherwise create new
-        let context = if let Some(restored) = self.restored_context {
+        let context = if let Some(mut restored) = self.restored_context {
+            if let Some(manager) = self.context_manager {
+                restored.set_manager(manager);
+            }
             Some(Arc::new(RwLock::new(restored)))
-        } else if self.context_manager.is_some() {
+        } else if self.context_manager.is_some()
+            || self.conversation_limits.is_some()
+            || self.context_store.is_some()
+        {
             // Create context if context manager is provided
             let mut ctx = if let (Some(tokens), Some(ratio)) =
                 (self.max_context_tokens, self.input_output_ratio)
@@ -642,6 +1447,12 @@ impl WorkflowBuilder {
 
             // Set the workflow ID in metadata
             ctx.metadata.workflow_id = workflow_id.clone();
+            if let Some(limits) = self.conversation_limits {
+                ctx.limits = limits;
+            }
+            if let Some(manager) = self.context_manager {
+                ctx.set_manager(manager);
+            }
 
             Some(Arc::new(RwLock::new(ctx)))
         } else {
@@ -650,10 +1461,18 @@ impl WorkflowBuilder {
 
         Workflow {
             id: workflow_id,
+            run_id: new_run_id(),
             steps: self.steps,
             initial_input: self.initial_input.unwrap_or(serde_json::json!({})),
             state: WorkflowState::Pending,
             context,
+            context_diagnostics: self.context_diagnostics,
+            labels: self.labels,
+            input_schema: self.input_schema,
+            document: self.document,
+            context_store: self.context_store,
+            check_contracts: self.check_contracts,
+            budget: self.budget,
         }
     }
 }
@@ -664,10 +1483,19 @@ impl Default for WorkflowBuilder {
     }
 }
 
+/// A fresh [`Workflow::run_id`]
+pub(crate) fn new_run_id() -> String {
+    format!("run_{}", uuid::Uuid::new_v4())
+}
+
 /// A workflow execution run with complete history
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorkflowRun {
     pub workflow_id: String,
+
+    /// Unique ID of the run, see [`Workflow::run_id`]
+    #[serde(default)]
+    pub run_id: String,
     pub state: WorkflowState,
     pub steps: Vec<WorkflowStepRecord>,
     pub final_output: Option<JsonValue>,
@@ -675,19 +1503,70 @@ pub struct WorkflowRun {
     /// Parent workflow ID if this is a sub-workflow
     #[serde(skip_serializing_if = "Option::is_none")]
     pub parent_workflow_id: Option<String>,
+
+    /// Artifacts produced during the run. Unexported artifacts are removed
+    /// from the store when the run finishes; these references remain.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artifacts: Vec<ArtifactRef>,
+
+    /// PII scan results, when the runtime has a scanner configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pii_findings: Option<PiiFindings>,
+
+    /// Trace sampling decision, when the runtime samples events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace: Option<TraceDecision>,
+
+    /// LLM usage of this run's own agents (sub-workflows report their own),
+    /// matching the runtime's `UsageLedger` entries for the run
+    #[serde(default)]
+    pub usage: UsageTotals,
+
+    /// Usage by step and by agent, with sub-workflows rolled up into the
+    /// step that ran them
+    #[serde(default)]
+    pub usage_breakdown: WorkflowUsage,
+
+    /// `run_id` of the run this one re-executed (see `Runtime::rerun_from`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rerun_of: Option<String>,
+
+    /// The step error that failed or canceled the run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<StepFailure>,
+
+    /// Decisions on the run's approval steps, in the order they were made
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub approvals: Vec<ApprovalRecord>,
+
+    /// The sub-workflows the run's steps started, in the order they
+    /// finished, each with its own
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sub_workflows: Vec<SubWorkflowSummary>,
 }
 
 impl WorkflowRun {
+    /// Serialize this run for storage in `format`
+    pub fn export(&self, format: PersistFormat) -> Result<Vec<u8>, PersistError> {
+        format.encode(self)
+    }
+
+    /// Load a run written by [`export`](Self::export) in any format, or as
+    /// plain JSON
+    pub fn import(bytes: &[u8]) -> Result<Self, PersistError> {
+        crate::persist::decode(bytes)
+    }
+
     /// Generate a Mermaid flowchart with execution results
+    ///
+    /// Nodes are colored by step status: green for succeeded, amber for
+    /// skipped or fallback, red for failed and grey for canceled. A failed
+    /// or canceled step's label ends with its error.
     pub fn to_mermaid_with_results(&self) -> String {
         let mut diagram = String::from("flowchart TD\n");
 
         // Start node
-        let start_style = match self.state {
-            WorkflowState::Completed => ":::successStyle",
-            WorkflowState::Failed => ":::failureStyle",
-            _ => "",
-        };
+        let start_style = self.state_style();
         diagram.push_str(&format!("    Start([Start]){}  \n", start_style));
 
         // Connect start to first step
@@ -698,44 +1577,53 @@ impl WorkflowRun {
         // Generate nodes for each step
         for (i, step) in self.steps.iter().enumerate() {
             let node_id = format!("Step{}", i);
-            let step_name = &step.step_name;
             let step_type = &step.step_type;
             let exec_time = step.execution_time_ms.unwrap_or(0);
 
-            // Determine style based on output
-            let has_output = step.output.is_some();
-            let style_class = if has_output {
-                ":::successStyle"
-            } else {
-                ":::failureStyle"
+            // A run recorded before failed steps were kept names the step
+            // it stopped at only in `failure`
+            let stopped_here = self
+                .failure
+                .as_ref()
+                .is_some_and(|failure| failure.step_index == step.step_index);
+            let style_class = match step.status {
+                StepStatus::Succeeded if stopped_here => ":::failureStyle",
+                StepStatus::Succeeded => ":::successStyle",
+                StepStatus::Skipped | StepStatus::Fallback => ":::skippedStyle",
+                StepStatus::Failed => ":::failureStyle",
+                StepStatus::Canceled => ":::canceledStyle",
             };
 
+            let mut label = format!(
+                "{}<br/><i>{}</i><br/>{}ms",
+                mermaid_text(&step.step_name),
+                mermaid_text(step_type),
+                exec_time
+            );
+            if let (StepStatus::Failed | StepStatus::Canceled, Some(error)) =
+                (step.status, &step.error)
+            {
+                let message = error.to_string();
+                let excerpt: String = message.chars().take(MERMAID_ERROR_CHARS).collect();
+                let ellipsis = if excerpt.len() < message.len() {
+                    "…"
+                } else {
+                    ""
+                };
+                label.push_str(&format!("<br/>{}{}", mermaid_text(&excerpt), ellipsis));
+            }
+
             // Choose node shape based on step type
             let node_def = if step_type.contains("Agent") {
-                format!(
-                    "    {}[\"{}<br/><i>{}</i><br/>{}ms\"]{}",
-                    node_id, step_name, step_type, exec_time, style_class
-                )
+                format!("    {}[\"{}\"]{}", node_id, label, style_class)
             } else if step_type.contains("Transform") {
-                format!(
-                    "    {}[/\"{}<br/><i>{}</i><br/>{}ms\"/]{}",
-                    node_id, step_name, step_type, exec_time, style_class
-                )
+                format!("    {}[/\"{}\"/]{}", node_id, label, style_class)
             } else if step_type.contains("Conditional") {
-                format!(
-                    "    {}{{\"{}<br/><i>{}</i><br/>{}ms\"}}{}",
-                    node_id, step_name, step_type, exec_time, style_class
-                )
+                format!("    {}{{\"{}\"}}{}", node_id, label, style_class)
             } else if step_type.contains("SubWorkflow") {
-                format!(
-                    "    {}[[\"{}<br/><i>{}</i><br/>{}ms\"]]{}",
-                    node_id, step_name, step_type, exec_time, style_class
-                )
+                format!("    {}[[\"{}\"]]{}", node_id, label, style_class)
             } else {
-                format!(
-                    "    {}[\"{}<br/><i>{}</i><br/>{}ms\"]{}",
-                    node_id, step_name, step_type, exec_time, style_class
-                )
+                format!("    {}[\"{}\"]{}", node_id, label, style_class)
             };
 
             diagram.push_str(&node_def);
@@ -755,11 +1643,7 @@ impl WorkflowRun {
             diagram.push_str("    Start --> End\n");
         }
 
-        let end_style = match self.state {
-            WorkflowState::Completed => ":::successStyle",
-            WorkflowState::Failed => ":::failureStyle",
-            _ => "",
-        };
+        let end_style = self.state_style();
         diagram.push_str(&format!("    End([End]){}\n", end_style));
 
         // Add styling
@@ -768,9 +1652,33 @@ impl WorkflowRun {
             .push_str("    classDef successStyle fill:#c8e6c9,stroke:#2e7d32,stroke-width:3px\n");
         diagram
             .push_str("    classDef failureStyle fill:#ffcdd2,stroke:#c62828,stroke-width:3px\n");
+        diagram
+            .push_str("    classDef skippedStyle fill:#ffe0b2,stroke:#ef6c00,stroke-width:3px\n");
+        diagram
+            .push_str("    classDef canceledStyle fill:#e0e0e0,stroke:#616161,stroke-width:3px\n");
 
         diagram
     }
+
+    /// Mermaid class of the Start and End nodes
+    fn state_style(&self) -> &'static str {
+        match self.state {
+            WorkflowState::Completed => ":::successStyle",
+            WorkflowState::Failed | WorkflowState::BudgetExceeded => ":::failureStyle",
+            WorkflowState::Canceled => ":::canceledStyle",
+            _ => "",
+        }
+    }
+}
+
+/// Longest error excerpt, in characters, in a Mermaid node label
+const MERMAID_ERROR_CHARS: usize = 80;
+
+/// `text` safe inside a quoted Mermaid label
+fn mermaid_text(text: &str) -> String {
+    text.replace('"', "#quot;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
 }
 
 /// A single step record in workflow execution
@@ -782,4 +1690,70 @@ pub struct WorkflowStepRecord {
     pub input: JsonValue,
     pub output: Option<JsonValue>,
     pub execution_time_ms: Option<u64>,
+
+    /// Copied from the original run by a rerun instead of executed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub replayed: bool,
+    /// Critic reviews of the step's output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub critic: Option<CriticReport>,
+
+    /// Whether the step succeeded, failed and was skipped or replaced by a
+    /// fallback value under its `StepPolicy`, or failed or was canceled and
+    /// ended the run; records from before it was kept read as succeeded
+    #[serde(default)]
+    pub status: StepStatus,
+
+    /// Why the step failed, unless it succeeded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<StepError>,
+
+    /// When the step started and finished; missing on records from before
+    /// they were kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Agents that handed the conversation on, e.g. triage -> billing;
+    /// the output is the last agent's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handoffs: Option<Vec<crate::agent::Handoff>>,
+
+    /// Artifacts the step produced, its sub-workflows' included
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artifacts: Vec<ArtifactRef>,
+}
+
+/// The step a run stopped at and why
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepFailure {
+    pub step_index: usize,
+    pub step_name: String,
+    pub error: StepError,
+}
+
+/// A sub-workflow run, recorded under the run whose step started it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubWorkflowSummary {
+    pub workflow_id: String,
+
+    /// The sub-workflow step in the parent run
+    pub step_index: usize,
+    pub step_name: String,
+
+    pub state: WorkflowState,
+
+    /// Step records in the sub-workflow's run
+    pub steps_run: usize,
+    pub execution_time_ms: u64,
+
+    #[serde(default)]
+    pub usage: UsageTotals,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<StepFailure>,
+
+    /// The sub-workflows this one started
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sub_workflows: Vec<SubWorkflowSummary>,
 }
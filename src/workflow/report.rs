@@ -0,0 +1,420 @@
+//! Shareable reports of a finished [`WorkflowRun`]
+//!
+//! [`WorkflowRun::to_json_report`] is the run's own serialization plus a
+//! `report_version`, so it loads back as a `WorkflowRun`.
+//! [`WorkflowRun::to_html_report`] renders a self-contained page with the
+//! run's Mermaid diagram and a collapsible table of its steps.
+//! `Runtime::execute_and_report` writes both next to each other.
+
+use super::{WorkflowRun, WorkflowState, WorkflowStepRecord};
+use crate::tools::truncation::truncate_tool_result;
+use crate::types::JsonValue;
+use crate::workflow::StepStatus;
+use std::path::{Path, PathBuf};
+
+/// Version of the JSON report layout; bumped when fields change meaning
+pub const REPORT_VERSION: u32 = 1;
+
+/// Mermaid build the HTML report loads
+const MERMAID_SCRIPT: &str = "https://cdn.jsdelivr.net/npm/mermaid@10/dist/mermaid.min.js";
+
+/// How reports render large payloads
+#[derive(Debug, Clone)]
+pub struct ReportOptions {
+    /// Largest step input or output, in bytes of JSON, shown whole; larger
+    /// ones are cut down with [`truncate_tool_result`] and become strings.
+    /// `None` keeps payloads whole. Default: 64 KiB.
+    pub max_payload_bytes: Option<usize>,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        Self {
+            max_payload_bytes: Some(64 * 1024),
+        }
+    }
+}
+
+impl ReportOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cut payloads longer than `bytes` (builder-style)
+    pub fn with_max_payload_bytes(mut self, bytes: usize) -> Self {
+        self.max_payload_bytes = Some(bytes);
+        self
+    }
+
+    /// Keep payloads whole (builder-style)
+    pub fn untruncated(mut self) -> Self {
+        self.max_payload_bytes = None;
+        self
+    }
+
+    fn payload(&self, value: &JsonValue) -> JsonValue {
+        match self.max_payload_bytes {
+            Some(max) if json_len(value) > max => {
+                JsonValue::String(truncate_tool_result(value, max))
+            }
+            _ => value.clone(),
+        }
+    }
+}
+
+/// Where [`WorkflowRun::write_reports`] wrote a run's reports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportFiles {
+    pub json: PathBuf,
+    pub html: PathBuf,
+}
+
+impl WorkflowRun {
+    /// Write the JSON and HTML reports to `out_dir`, creating it if needed
+    ///
+    /// The files are named `<workflow id>-<UTC timestamp>.json` and `.html`,
+    /// with characters unsafe in file names in the id replaced by `_`.
+    pub fn write_reports(&self, out_dir: impl AsRef<Path>) -> std::io::Result<ReportFiles> {
+        self.write_reports_with(out_dir, &ReportOptions::default())
+    }
+
+    /// [`write_reports`](Self::write_reports) with explicit options
+    pub fn write_reports_with(
+        &self,
+        out_dir: impl AsRef<Path>,
+        options: &ReportOptions,
+    ) -> std::io::Result<ReportFiles> {
+        let out_dir = out_dir.as_ref();
+        std::fs::create_dir_all(out_dir)?;
+        let stem = format!(
+            "{}-{}",
+            file_safe(&self.workflow_id),
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ")
+        );
+        let files = ReportFiles {
+            json: out_dir.join(format!("{}.json", stem)),
+            html: out_dir.join(format!("{}.html", stem)),
+        };
+        let json = serde_json::to_string_pretty(&self.to_json_report_with(options))
+            .map_err(std::io::Error::other)?;
+        std::fs::write(&files.json, json)?;
+        std::fs::write(&files.html, self.to_html_report_with(options))?;
+        Ok(files)
+    }
+
+    /// A versioned JSON report of this run with the default [`ReportOptions`]
+    pub fn to_json_report(&self) -> JsonValue {
+        self.to_json_report_with(&ReportOptions::default())
+    }
+
+    /// A versioned JSON report of this run
+    ///
+    /// The run's fields as `serde` writes them, with step inputs and
+    /// outputs and the final output truncated per `options`, plus
+    /// `report_version`, `generated_at` (RFC 3339) and
+    /// `total_execution_time_ms`.
+    pub fn to_json_report_with(&self, options: &ReportOptions) -> JsonValue {
+        let mut report = serde_json::to_value(self).unwrap_or_default();
+        if let Some(steps) = report["steps"].as_array_mut() {
+            for step in steps {
+                step["input"] = options.payload(&step["input"]);
+                if !step["output"].is_null() {
+                    step["output"] = options.payload(&step["output"]);
+                }
+            }
+        }
+        if !report["final_output"].is_null() {
+            report["final_output"] = options.payload(&report["final_output"]);
+        }
+        report["report_version"] = REPORT_VERSION.into();
+        report["generated_at"] = chrono::Utc::now().to_rfc3339().into();
+        report["total_execution_time_ms"] = self.total_execution_time_ms().into();
+        report
+    }
+
+    /// A self-contained HTML report of this run with the default
+    /// [`ReportOptions`]
+    pub fn to_html_report(&self) -> String {
+        self.to_html_report_with(&ReportOptions::default())
+    }
+
+    /// A self-contained HTML report of this run: its Mermaid diagram, usage
+    /// and a collapsible row per step with timing bars and pretty-printed
+    /// input and output
+    ///
+    /// The diagram is drawn by mermaid.js from a CDN; everything else works
+    /// offline.
+    pub fn to_html_report_with(&self, options: &ReportOptions) -> String {
+        let total_ms = self.total_execution_time_ms();
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+        html.push_str(&format!(
+            "<title>Workflow run {}</title>\n",
+            escape(&self.workflow_id)
+        ));
+        html.push_str(STYLE);
+        html.push_str(&format!(
+            "<script src=\"{}\"></script>\n<script>mermaid.initialize({{ startOnLoad: true }});</script>\n",
+            MERMAID_SCRIPT
+        ));
+        html.push_str("</head>\n<body>\n");
+
+        html.push_str(&format!(
+            "<h1>Workflow run <code>{}</code> <span class=\"state {}\">{}</span></h1>\n",
+            escape(&self.workflow_id),
+            state_class(&self.state),
+            state_label(&self.state)
+        ));
+        html.push_str("<dl class=\"summary\">\n");
+        summary_row(&mut html, "Steps", &self.steps.len().to_string());
+        summary_row(&mut html, "Duration", &format!("{} ms", total_ms));
+        if let Some(parent) = &self.parent_workflow_id {
+            summary_row(&mut html, "Parent run", parent);
+        }
+        if let Some(original) = &self.rerun_of {
+            summary_row(&mut html, "Rerun of", original);
+        }
+        if self.usage.llm_calls > 0 {
+            summary_row(
+                &mut html,
+                "LLM usage",
+                &format!(
+                    "{} calls, {} prompt + {} completion = {} tokens, ${:.4}",
+                    self.usage.llm_calls,
+                    self.usage.prompt_tokens,
+                    self.usage.completion_tokens,
+                    self.usage.total_tokens,
+                    self.usage.estimated_cost_usd
+                ),
+            );
+        }
+        html.push_str("</dl>\n");
+
+        if let Some(failure) = &self.failure {
+            html.push_str(&format!(
+                "<div class=\"failure\"><strong>Step {} ({}) failed:</strong> {}</div>\n",
+                failure.step_index,
+                escape(&failure.step_name),
+                escape(&failure.error.to_string())
+            ));
+        }
+
+        html.push_str("<h2>Flow</h2>\n<pre class=\"mermaid\">\n");
+        html.push_str(&escape(&self.to_mermaid_with_results()));
+        html.push_str("</pre>\n");
+
+        html.push_str("<h2>Steps</h2>\n<div class=\"steps\">\n");
+        let mut elapsed = 0;
+        for step in &self.steps {
+            let duration = step.execution_time_ms.unwrap_or(0);
+            html.push_str(&self.step_row(step, elapsed, duration, total_ms, options));
+            elapsed += duration;
+        }
+        // Runs recorded before failed steps were kept have no record of
+        // the step they stopped at
+        if let Some(failure) = &self.failure {
+            if !self
+                .steps
+                .iter()
+                .any(|step| step.step_index == failure.step_index)
+            {
+                html.push_str(&format!(
+                    "<details class=\"step failed\" open>\n<summary><span class=\"index\">{}</span> \
+                     <span class=\"name\">{}</span> <span class=\"type\"></span> \
+                     <span class=\"status\">failed</span></summary>\n<p class=\"error\">{}</p>\n</details>\n",
+                    failure.step_index,
+                    escape(&failure.step_name),
+                    escape(&failure.error.to_string())
+                ));
+            }
+        }
+        html.push_str("</div>\n");
+
+        if let Some(output) = &self.final_output {
+            html.push_str("<h2>Final output</h2>\n");
+            html.push_str(&format!(
+                "<pre class=\"json\">{}</pre>\n",
+                escape(&pretty(&options.payload(output)))
+            ));
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    /// Sum of the steps' execution times
+    fn total_execution_time_ms(&self) -> u64 {
+        self.steps
+            .iter()
+            .filter_map(|step| step.execution_time_ms)
+            .sum()
+    }
+
+    fn step_failed(&self, step: &WorkflowStepRecord) -> bool {
+        step.status != StepStatus::Succeeded
+            || self
+                .failure
+                .as_ref()
+                .is_some_and(|failure| failure.step_index == step.step_index)
+    }
+
+    fn step_row(
+        &self,
+        step: &WorkflowStepRecord,
+        offset_ms: u64,
+        duration_ms: u64,
+        total_ms: u64,
+        options: &ReportOptions,
+    ) -> String {
+        let failed = self.step_failed(step);
+        let percent = |ms: u64| {
+            if total_ms == 0 {
+                0.0
+            } else {
+                ms as f64 * 100.0 / total_ms as f64
+            }
+        };
+        let status = match (step.status, failed) {
+            (StepStatus::Succeeded, true) => "failed",
+            (StepStatus::Succeeded, false) => "succeeded",
+            (StepStatus::Skipped, _) => "skipped",
+            (StepStatus::Fallback, _) => "fallback",
+            (StepStatus::Failed, _) => "failed",
+            (StepStatus::Canceled, _) => "canceled",
+        };
+
+        let mut row = format!(
+            "<details class=\"step{}\"{}>\n<summary><span class=\"index\">{}</span> \
+             <span class=\"name\">{}</span> <span class=\"type\">{}</span> \
+             <span class=\"status\">{}</span> <span class=\"duration\">{} ms</span>\
+             <span class=\"timeline\"><span class=\"bar\" style=\"margin-left: {:.1}%; width: {:.1}%\"></span></span>\
+             </summary>\n",
+            if failed { " failed" } else { "" },
+            // The step the run stopped at starts expanded
+            if matches!(step.status, StepStatus::Failed | StepStatus::Canceled) {
+                " open"
+            } else {
+                ""
+            },
+            step.step_index,
+            escape(&step.step_name),
+            escape(&step.step_type),
+            status,
+            duration_ms,
+            percent(offset_ms),
+            percent(duration_ms).max(0.5),
+        );
+        if step.replayed {
+            row.push_str("<p class=\"note\">Replayed from the original run</p>\n");
+        }
+        if let Some(error) = &step.error {
+            row.push_str(&format!(
+                "<p class=\"error\">{}</p>\n",
+                escape(&error.to_string())
+            ));
+        }
+        row.push_str("<div class=\"payloads\">\n<div><h3>Input</h3>");
+        row.push_str(&format!(
+            "<pre class=\"json\">{}</pre></div>\n",
+            escape(&pretty(&options.payload(&step.input)))
+        ));
+        row.push_str("<div><h3>Output</h3>");
+        match &step.output {
+            Some(output) => row.push_str(&format!(
+                "<pre class=\"json\">{}</pre></div>\n",
+                escape(&pretty(&options.payload(output)))
+            )),
+            None => row.push_str("<p class=\"note\">No output</p></div>\n"),
+        }
+        row.push_str("</div>\n</details>\n");
+        row
+    }
+}
+
+fn summary_row(html: &mut String, term: &str, value: &str) {
+    html.push_str(&format!("<dt>{}</dt><dd>{}</dd>\n", term, escape(value)));
+}
+
+fn state_label(state: &WorkflowState) -> &'static str {
+    match state {
+        WorkflowState::Pending => "pending",
+        WorkflowState::Running => "running",
+        WorkflowState::Completed => "completed",
+        WorkflowState::Failed => "failed",
+        WorkflowState::Canceled => "canceled",
+        WorkflowState::BudgetExceeded => "budget exceeded",
+    }
+}
+
+fn state_class(state: &WorkflowState) -> &'static str {
+    match state {
+        WorkflowState::Completed => "ok",
+        WorkflowState::Failed | WorkflowState::Canceled | WorkflowState::BudgetExceeded => "bad",
+        WorkflowState::Pending | WorkflowState::Running => "pending",
+    }
+}
+
+/// Payloads truncated to a string are shown as the text they became
+fn pretty(value: &JsonValue) -> String {
+    match value {
+        JsonValue::String(text) if text.contains("[truncated;") => text.clone(),
+        other => serde_json::to_string_pretty(other).unwrap_or_else(|_| other.to_string()),
+    }
+}
+
+fn json_len(value: &JsonValue) -> usize {
+    serde_json::to_string(value).map_or(0, |text| text.len())
+}
+
+pub(crate) fn file_safe(id: &str) -> String {
+    id.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const STYLE: &str = r#"<style>
+body { font-family: -apple-system, "Segoe UI", Roboto, sans-serif; margin: 2rem; color: #212121; }
+h1 code { font-size: 0.8em; }
+.state { font-size: 0.6em; padding: 0.2em 0.6em; border-radius: 1em; vertical-align: middle; }
+.state.ok { background: #c8e6c9; color: #1b5e20; }
+.state.bad { background: #ffcdd2; color: #b71c1c; }
+.state.pending { background: #fff9c4; color: #f57f17; }
+.summary { display: grid; grid-template-columns: max-content auto; gap: 0.3rem 1rem; }
+.summary dt { font-weight: 600; }
+.summary dd { margin: 0; }
+.failure { background: #ffebee; border-left: 4px solid #c62828; padding: 0.8rem; margin: 1rem 0; }
+.step { border: 1px solid #e0e0e0; border-radius: 4px; margin: 0.4rem 0; }
+.step.failed { border-color: #c62828; background: #fff5f5; }
+.step summary { display: grid; grid-template-columns: 2rem 12rem 8rem 6rem 5rem auto; gap: 0.5rem; padding: 0.5rem; cursor: pointer; align-items: center; }
+.step .type, .step .duration { color: #616161; }
+.step.failed .status { color: #c62828; font-weight: 600; }
+.timeline { background: #f5f5f5; height: 0.8rem; border-radius: 2px; }
+.bar { display: block; height: 100%; background: #42a5f5; border-radius: 2px; }
+.step.failed .bar { background: #ef5350; }
+.payloads { display: grid; grid-template-columns: 1fr 1fr; gap: 1rem; padding: 0 0.8rem 0.8rem; }
+.payloads h3 { font-size: 0.9rem; margin: 0.5rem 0; }
+pre.json { background: #fafafa; border: 1px solid #eeeeee; padding: 0.6rem; overflow: auto; max-height: 30rem; font-size: 0.8rem; }
+.error { color: #c62828; padding: 0 0.8rem; }
+.note { color: #757575; font-style: italic; padding: 0 0.8rem; }
+</style>
+"#;
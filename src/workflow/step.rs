@@ -1,9 +1,13 @@
+use crate::artifact::ArtifactStore;
 use crate::context::WorkflowContext;
 use crate::event::EventStream;
+use crate::runtime::heartbeat::RunActivity;
 use crate::types::JsonValue;
+use crate::workflow::steps::ApprovalQueue;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, RwLock};
+use tokio_util::sync::CancellationToken;
 
 /// Types of steps that can be in a workflow
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -14,6 +18,10 @@ pub enum StepType {
     Conditional,
     Parallel,
     SubWorkflow,
+    Loop,
+    ForEach,
+    Approval,
+    Wait,
     Custom(String),
 }
 
@@ -28,11 +36,29 @@ pub struct StepInput {
     pub workflow_context: Option<Arc<RwLock<WorkflowContext>>>,
 }
 
+impl StepInput {
+    /// A copy of the workflow memory's value under `key`; `None` without a
+    /// workflow context
+    pub fn memory_get(&self, key: &str) -> Option<JsonValue> {
+        let context = self.workflow_context.as_ref()?;
+        let context = context.read().unwrap();
+        context.memory_get(key).cloned()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StepInputMetadata {
     pub step_index: usize,
     pub previous_step: Option<String>,
     pub workflow_id: String,
+
+    /// The run the step belongs to, see `Workflow::run_id`
+    #[serde(default)]
+    pub run_id: String,
+
+    /// Position of the element a for-each step is running on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item_index: Option<usize>,
 }
 
 /// Output data produced by a step
@@ -47,6 +73,27 @@ pub struct StepOutputMetadata {
     pub step_name: String,
     pub step_type: StepType,
     pub execution_time_ms: u64,
+
+    /// Reviews of an agent step's output, when it has a critic
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub critic: Option<crate::workflow::critic::CriticReport>,
+
+    /// Times a loop step ran its body
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iterations_run: Option<usize>,
+
+    /// Attempts and disposition of a step run under a `StepPolicy`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<crate::workflow::steps::PolicyOutcome>,
+
+    /// Items of a for-each step that failed and were left `null` (see
+    /// `ForEachFailureMode::CollectErrors`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item_errors: Option<crate::error::AggregateError<StepError>>,
+
+    /// Handoffs an agent step followed, in order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handoffs: Option<Vec<crate::agent::Handoff>>,
 }
 
 /// Result type for step execution
@@ -66,11 +113,47 @@ pub enum StepError {
 
     #[error("Step not found: {0}")]
     StepNotFound(String),
+
+    #[error("{0}")]
+    LimitReached(crate::limits::LimitExceeded),
+
+    #[error("Canceled: {0}")]
+    Canceled(String),
+
+    /// An approval step's data was rejected, by a person or its timeout
+    #[error("Rejected: {0}")]
+    Rejected(String),
+
+    /// Several parts of the step failed (see `ParallelFailureMode::CollectErrors`)
+    #[error("Execution failed: {0}")]
+    Aggregate(#[source] crate::error::AggregateError<StepError>),
+
+    /// The step's output failed its own output schema or the next step's
+    /// input schema (see [`crate::workflow::contract`])
+    #[error("Contract violation: {0}")]
+    ContractViolation(crate::workflow::contract::ContractViolation),
+
+    /// The run's budget was spent (see [`crate::usage::Budget`])
+    #[error("{0}")]
+    BudgetExceeded(crate::usage::BudgetExceeded),
 }
 
 /// Execution context passed to steps
+#[derive(Clone, Copy)]
 pub struct ExecutionContext<'a> {
     pub event_stream: Option<&'a EventStream>,
+
+    /// Run-scoped artifact store for tool outputs
+    pub artifacts: Option<&'a ArtifactStore>,
+
+    /// Fires when the run is canceled; handed to tools
+    pub cancellation: Option<&'a CancellationToken>,
+
+    /// Where approval steps wait for decisions
+    pub approvals: Option<&'a ApprovalQueue>,
+
+    /// Where the step records progress when the run has a heartbeat
+    pub activity: Option<&'a RunActivity>,
 }
 
 impl<'a> Default for ExecutionContext<'a> {
@@ -81,14 +164,48 @@ impl<'a> Default for ExecutionContext<'a> {
 
 impl<'a> ExecutionContext<'a> {
     pub fn new() -> Self {
-        Self { event_stream: None }
+        Self {
+            event_stream: None,
+            artifacts: None,
+            cancellation: None,
+            approvals: None,
+            activity: None,
+        }
     }
 
     pub fn with_event_stream(event_stream: &'a EventStream) -> Self {
         Self {
             event_stream: Some(event_stream),
+            artifacts: None,
+            cancellation: None,
+            approvals: None,
+            activity: None,
         }
     }
+
+    /// Attach an artifact store (builder-style)
+    pub fn with_artifacts(mut self, artifacts: &'a ArtifactStore) -> Self {
+        self.artifacts = Some(artifacts);
+        self
+    }
+
+    /// Attach the run's cancellation token (builder-style)
+    pub fn with_cancellation(mut self, token: &'a CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Attach the queue approval steps wait in (builder-style)
+    pub fn with_approvals(mut self, approvals: &'a ApprovalQueue) -> Self {
+        self.approvals = Some(approvals);
+        self
+    }
+
+    /// Attach the run's activity for the heartbeat (builder-style)
+    pub fn with_activity(mut self, activity: &'a RunActivity) -> Self {
+        self.activity = Some(activity);
+        self
+    }
 }
 
 /// Step trait - all workflow steps must implement this
@@ -115,13 +232,81 @@ pub trait Step: Send + Sync {
         None
     }
 
+    /// JSON Schema the step's input must match, checked against the
+    /// previous step's output (see [`crate::workflow::contract`])
+    fn input_schema(&self) -> Option<&JsonValue> {
+        None
+    }
+
+    /// JSON Schema the step's output must match before the next step runs
+    fn output_schema(&self) -> Option<&JsonValue> {
+        None
+    }
+
+    /// JSON pointers the step reads from its input, for checking data
+    /// against a workflow without running it
+    fn input_pointers(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
     /// For conditional steps: get the branches (then, else)
     fn get_branches(&self) -> Option<(&dyn Step, &dyn Step)> {
         None
     }
 
+    /// For conditional steps: evaluate the condition against `data` without
+    /// executing either branch (`true` selects the then-branch)
+    fn evaluate_condition(&self, _data: &JsonValue) -> Option<bool> {
+        None
+    }
+
+    /// For switch steps: get each case's label and step, in declaration
+    /// order; a default step comes last, labeled `default`
+    fn get_cases(&self) -> Option<Vec<(&str, &dyn Step)>> {
+        None
+    }
+
+    /// For parallel steps: get the branches, in declaration order
+    fn get_parallel_branches(&self) -> Option<Vec<&dyn Step>> {
+        None
+    }
+
+    /// For loop steps: get the repeated step and the iteration cap
+    fn get_loop_body(&self) -> Option<(&dyn Step, usize)> {
+        None
+    }
+
+    /// For for-each steps: get the step run on each item and the
+    /// concurrency limit
+    fn get_for_each_body(&self) -> Option<(&dyn Step, usize)> {
+        None
+    }
+
     /// For sub-workflow steps: get the workflow
     fn get_sub_workflow(&self) -> Option<crate::workflow::Workflow> {
         None
     }
+
+    /// For policy steps: get the wrapped step and its policy
+    fn get_policy(&self) -> Option<(&dyn Step, &crate::workflow::steps::StepPolicy)> {
+        None
+    }
+
+    /// For agent steps: get the agent
+    fn get_agent(&self) -> Option<&crate::agent::Agent> {
+        None
+    }
+
+    /// A copy of this step for another run, sharing its clients, tools and
+    /// closures; `None` for steps that can't be copied, which keeps
+    /// [`Workflow::instantiate`](crate::workflow::Workflow::instantiate)
+    /// from using them
+    fn clone_step(&self) -> Option<Box<dyn Step>> {
+        None
+    }
+}
+
+/// Copy each of `steps` with [`Step::clone_step`]
+pub(crate) fn clone_steps(steps: &[Box<dyn Step>]) -> Option<Vec<Box<dyn Step>>> {
+    steps.iter().map(|step| step.clone_step()).collect()
 }
@@ -1,14 +1,29 @@
-use crate::agent::{Agent, AgentConfig};
+use crate::agent::{
+    prompt, Agent, AgentConfig, Handoff, HandoffTargets, PromptTemplate, PromptVars,
+};
+use crate::context::memory::with_memory_tools;
+use crate::event::{ComponentStatus, EventScope, EventType};
+use crate::llm::types::Role;
+use crate::llm::ChatMessage;
+use crate::types::{AgentError, AgentInput, AgentOutput, JsonValue};
+use crate::workflow::critic::{self, CriticConfig, CriticReport, CriticTarget};
 use crate::workflow::step::{
     ExecutionContext, Step, StepError, StepInput, StepOutput, StepOutputMetadata, StepResult,
     StepType,
 };
 use async_trait::async_trait;
+use std::sync::Arc;
 
 /// A step that executes an agent
+#[derive(Clone)]
 pub struct AgentStep {
     agent: Agent,
     name: String,
+    critic: Option<CriticConfig>,
+    input_template: Option<PromptTemplate>,
+    input_schema: Option<JsonValue>,
+    output_schema: Option<JsonValue>,
+    handoffs: Option<HandoffTargets>,
 }
 
 impl AgentStep {
@@ -18,12 +33,236 @@ impl AgentStep {
         Self {
             agent: Agent::new(config),
             name,
+            critic: None,
+            input_template: None,
+            input_schema: None,
+            output_schema: None,
+            handoffs: None,
         }
     }
 
     /// Create from an existing Agent
     pub fn from_agent(agent: Agent, name: String) -> Self {
-        Self { agent, name }
+        Self {
+            agent,
+            name,
+            critic: None,
+            input_template: None,
+            input_schema: None,
+            output_schema: None,
+            handoffs: None,
+        }
+    }
+
+    /// Have a critic review each output; a rejected output is sent back to
+    /// the agent with the critique for up to `max_rounds` revisions
+    pub fn with_critic(mut self, critic: CriticConfig) -> Self {
+        self.critic = Some(critic);
+        self
+    }
+
+    /// Send the agent `template` rendered as its user message instead of the
+    /// step's input. The input is the `input` variable, beside workflow
+    /// memory and the built-ins (see [`crate::agent::prompt`]).
+    pub fn with_input_template(mut self, template: impl Into<PromptTemplate>) -> Self {
+        self.input_template = Some(template.into());
+        self
+    }
+
+    /// Require the step's input to match `schema` (see
+    /// [`crate::workflow::contract`])
+    pub fn with_input_schema(mut self, schema: JsonValue) -> Self {
+        self.input_schema = Some(schema);
+        self
+    }
+
+    /// Require the agent's output to match `schema` before the next step
+    /// runs
+    pub fn with_output_schema(mut self, schema: JsonValue) -> Self {
+        self.output_schema = Some(schema);
+        self
+    }
+
+    /// When an agent hands the conversation off, run the agent it names
+    /// from `targets` on it, until one answers; see
+    /// [`crate::agent::handoff`]
+    pub fn with_handoffs(mut self, targets: HandoffTargets) -> Self {
+        self.handoffs = Some(targets);
+        self
+    }
+
+    /// `agent` with the workflow's memory tools, when there is a context
+    fn scoped(agent: &Agent, input: &StepInput) -> Option<Agent> {
+        input.workflow_context.as_ref().map(|context| {
+            let tools = with_memory_tools(agent.config().tools.as_ref(), context);
+            agent.with_tool_view(Arc::new(tools))
+        })
+    }
+
+    /// Run the agents `result` hands the conversation to in turn, returning
+    /// the answer, the handoffs made and the agent that answered
+    async fn follow_handoffs<'s>(
+        &'s self,
+        targets: &'s HandoffTargets,
+        mut result: AgentOutput,
+        agent_input: &AgentInput,
+        input: &StepInput,
+        ctx: ExecutionContext<'_>,
+    ) -> Result<(AgentOutput, Vec<Handoff>, &'s Agent), StepError> {
+        let mut chain: Vec<Handoff> = Vec::new();
+        let mut answering = &self.agent;
+        while let Some(handoff) = result.metadata.handoff.take() {
+            chain.push(handoff.clone());
+            if chain.len() > targets.max_depth() {
+                let path: Vec<&str> = std::iter::once(chain[0].from.as_str())
+                    .chain(chain.iter().map(|h| h.to.as_str()))
+                    .collect();
+                return Err(StepError::ExecutionFailed(format!(
+                    "handoff limit of {} reached: {}",
+                    targets.max_depth(),
+                    path.join(" -> ")
+                )));
+            }
+            let target = targets.get(&handoff.to).ok_or_else(|| {
+                StepError::ExecutionFailed(format!(
+                    "agent '{}' handed off to '{}', which is not a handoff target",
+                    handoff.from, handoff.to
+                ))
+            })?;
+            if let Some(stream) = ctx.event_stream {
+                stream.agent_handoff(
+                    target.name(),
+                    input.metadata.workflow_id.clone(),
+                    format!("Taking over from {}", handoff.from),
+                    serde_json::json!({
+                        "handoff": handoff,
+                        "depth": chain.len(),
+                    }),
+                );
+            }
+
+            // The same conversation, with the reason as the next user turn
+            let handed = AgentInput {
+                data: handoff.note().into(),
+                metadata: crate::types::AgentInputMetadata {
+                    previous_agent: Some(handoff.from.clone()),
+                    ..agent_input.metadata.clone()
+                },
+                chat_history: result.chat_history.take(),
+                ..agent_input.clone()
+            };
+            let scoped = Self::scoped(target, input);
+            let limit_events = std::mem::take(&mut result.metadata.limit_events);
+            result = self
+                .run_agent(scoped.as_ref().unwrap_or(target), handed, input, ctx)
+                .await?;
+            result.metadata.limit_events.splice(0..0, limit_events);
+            answering = target;
+        }
+        Ok((result, chain, answering))
+    }
+
+    /// Run the agent once, mapping its errors to step errors
+    async fn run_agent(
+        &self,
+        agent: &Agent,
+        agent_input: AgentInput,
+        input: &StepInput,
+        ctx: ExecutionContext<'_>,
+    ) -> Result<AgentOutput, StepError> {
+        // Execute agent with event stream, preferring the run's artifact store
+        let artifacts = ctx.artifacts.or(agent.artifact_store());
+        let execution = agent.execute_with_cancellation(
+            agent_input,
+            ctx.event_stream,
+            artifacts,
+            ctx.cancellation.cloned().unwrap_or_default(),
+        );
+        crate::runtime::heartbeat::scope(ctx.activity.cloned(), execution)
+            .await
+            .map_err(|e| match e {
+                AgentError::Canceled(reason) => StepError::Canceled(reason),
+                AgentError::LimitReached(exceeded) => {
+                    // The agent already emitted the event; keep the context's stats in step
+                    if let Some(context_arc) = &input.workflow_context {
+                        context_arc
+                            .write()
+                            .unwrap()
+                            .limit_stats
+                            .record(exceeded.event());
+                    }
+                    StepError::LimitReached(exceeded)
+                }
+                AgentError::BudgetExceeded(exceeded) => StepError::BudgetExceeded(exceeded),
+                e => StepError::AgentError(e.to_string()),
+            })
+    }
+
+    /// Review `result` and revise it until the critic accepts it or the
+    /// rounds run out
+    async fn review(
+        &self,
+        agent: &Agent,
+        critic: &CriticConfig,
+        agent_input: &AgentInput,
+        mut result: AgentOutput,
+        input: &StepInput,
+        ctx: ExecutionContext<'_>,
+    ) -> Result<(AgentOutput, CriticReport), StepError> {
+        let mut report = CriticReport::default();
+        if !critic.sampled() {
+            report.passed = true;
+            report.skipped = true;
+            return Ok((result, report));
+        }
+
+        let target = CriticTarget {
+            agent: self.agent.name(),
+            workflow_id: &input.metadata.workflow_id,
+            instructions: &self.agent.config().system_prompt,
+            input: &agent_input.data,
+            event_stream: ctx.event_stream,
+        };
+        loop {
+            let verdict = critic.review(&target, &result.data, report.rounds).await?;
+            let accepted = critic.accepts(&verdict);
+            report.verdicts.push(verdict);
+            if accepted {
+                report.passed = true;
+                return Ok((result, report));
+            }
+            if report.rounds >= critic.max_rounds {
+                return Err(StepError::ExecutionFailed(format!(
+                    "critic rejected the output after {} revision round(s): {}",
+                    report.rounds,
+                    report.last_critique().unwrap_or_default()
+                )));
+            }
+
+            // Continue the agent's own conversation with the critique
+            let mut history = result.chat_history.clone().unwrap_or_else(|| {
+                vec![
+                    ChatMessage::user(
+                        serde_json::to_string_pretty(&agent_input.data).unwrap_or_default(),
+                    ),
+                    ChatMessage::assistant(
+                        serde_json::to_string_pretty(&result.data).unwrap_or_default(),
+                    ),
+                ]
+            });
+            history.push(ChatMessage::user(critic::correction(
+                report.last_critique().unwrap_or_default(),
+            )));
+            let revision = AgentInput {
+                chat_history: Some(history),
+                ..agent_input.clone()
+            };
+
+            report.rounds += 1;
+            let limit_events = std::mem::take(&mut result.metadata.limit_events);
+            result = self.run_agent(agent, revision, input, ctx).await?;
+            result.metadata.limit_events.splice(0..0, limit_events);
+        }
     }
 }
 
@@ -36,36 +275,162 @@ impl Step for AgentStep {
     ) -> StepResult {
         let start = std::time::Instant::now();
 
-        // Extract chat history from workflow context if available
-        let chat_history = if let Some(context_arc) = &input.workflow_context {
+        // Let the workflow's context manager shrink the history first
+        if let Some(context_arc) = &input.workflow_context {
+            let pruned = crate::context::prune_history(context_arc)
+                .await
+                .map_err(|e| {
+                    StepError::ExecutionFailed(format!("context pruning failed: {}", e))
+                })?;
+            if let (Some(outcome), Some(stream)) = (pruned, ctx.event_stream) {
+                stream.append(
+                    EventScope::System,
+                    EventType::Progress,
+                    "system:context_pruning".to_string(),
+                    ComponentStatus::Running,
+                    input.metadata.workflow_id.clone(),
+                    Some(format!(
+                        "{} pruned the history from {} to {} messages before '{}'",
+                        outcome.strategy,
+                        outcome.messages_before,
+                        outcome.messages_after,
+                        self.name
+                    )),
+                    serde_json::to_value(&outcome).unwrap_or_default(),
+                );
+            }
+        }
+
+        // Extract chat history and conversation caps from workflow context if
+        // available; earlier agents' system prompts lead the history, for the
+        // agent's SystemPromptPolicy to drop, annotate or keep
+        let (chat_history, limits) = if let Some(context_arc) = &input.workflow_context {
             let context = context_arc.read().unwrap();
-            Some(context.history().to_vec())
+            let limits = Some(context.limits.clone()).filter(|l| !l.is_unlimited());
+            let mut history = context.system_prompts_for(self.agent.name());
+            history.extend_from_slice(context.history());
+            (Some(history), limits)
         } else {
-            None
+            (None, None)
+        };
+
+        // Memory fills prompt placeholders, when there are any to fill
+        let templated = self
+            .input_template
+            .iter()
+            .any(PromptTemplate::has_placeholders)
+            || self
+                .agent
+                .config()
+                .system_prompt_template
+                .as_ref()
+                .is_some_and(PromptTemplate::has_placeholders);
+        let vars = match &input.workflow_context {
+            Some(context_arc) if templated => PromptVars {
+                memory: context_arc
+                    .read()
+                    .unwrap()
+                    .memory
+                    .clone()
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            },
+            _ => PromptVars::default(),
         };
 
         // Convert StepInput to AgentInput
-        let agent_input = crate::types::AgentInput {
-            data: input.data,
+        let mut agent_input = AgentInput {
+            data: input.data.clone(),
             metadata: crate::types::AgentInputMetadata {
                 step_index: input.metadata.step_index,
                 previous_agent: input.metadata.previous_step.clone(),
+                workflow_id: Some(input.metadata.workflow_id.clone()),
             },
             chat_history,
+            limits,
+            vars,
         };
+        if let Some(template) = &self.input_template {
+            let rendered = template
+                .render_with(|| {
+                    let mut vars = agent_input
+                        .vars
+                        .resolve(prompt::builtins(self.agent.name(), &agent_input.metadata));
+                    vars["input"] = input.data.clone();
+                    vars
+                })
+                .map_err(|e| StepError::InvalidInput(e.to_string()))?;
+            agent_input.data = rendered.into_owned().into();
+        }
+
+        // With a context, the agent also gets the memory tools
+        let scoped = Self::scoped(&self.agent, &input);
+        let mut agent = scoped.as_ref().unwrap_or(&self.agent);
 
-        // Execute agent with event stream
         let result = self
-            .agent
-            .execute_with_events(agent_input, ctx.event_stream)
-            .await
-            .map_err(|e| StepError::AgentError(e.to_string()))?;
+            .run_agent(agent, agent_input.clone(), &input, ctx)
+            .await?;
+        let (result, handoffs, answering) = match &self.handoffs {
+            Some(targets) => {
+                self.follow_handoffs(targets, result, &agent_input, &input, ctx)
+                    .await?
+            }
+            None => (result, Vec::new(), &self.agent),
+        };
+        // The critic sends revisions to the agent that answered
+        let answering_scoped;
+        if !handoffs.is_empty() {
+            answering_scoped = Self::scoped(answering, &input);
+            agent = answering_scoped.as_ref().unwrap_or(answering);
+        }
+        let (result, critic) = match &self.critic {
+            Some(critic) => {
+                let (result, report) = self
+                    .review(agent, critic, &agent_input, result, &input, ctx)
+                    .await?;
+                (result, Some(report))
+            }
+            None => (result, None),
+        };
 
         // Update workflow context with new messages if it exists
         if let Some(context_arc) = &input.workflow_context {
+            let mut context = context_arc.write().unwrap();
+            for event in &result.metadata.limit_events {
+                context.limit_stats.record(event.clone());
+            }
             if let Some(new_history) = &result.chat_history {
-                let mut context = context_arc.write().unwrap();
-                context.set_history(new_history.clone());
+                // System prompts stay out of the shared history; the agent's
+                // is kept beside it for later agents, as this execution
+                // rendered it
+                for agent in std::iter::once(&self.agent).chain(
+                    handoffs
+                        .iter()
+                        .filter_map(|h| self.handoffs.as_ref()?.get(&h.to)),
+                ) {
+                    let system_prompt = agent
+                        .system_prompt_for(&agent_input)
+                        .map_err(|e| StepError::AgentError(e.to_string()))?;
+                    if !system_prompt.is_empty() {
+                        context.record_system_prompt(agent.name(), &system_prompt);
+                    }
+                }
+                let turns = new_history
+                    .iter()
+                    .filter(|m| m.role != Role::System)
+                    .cloned()
+                    .collect();
+                // The agent enforced the caps on this history already, so
+                // this only catches caps tightened mid-execution
+                let events = context
+                    .try_set_history(turns)
+                    .map_err(StepError::LimitReached)?;
+                if let Some(stream) = ctx.event_stream {
+                    for event in &events {
+                        event.emit(stream, context.metadata.workflow_id.clone(), None);
+                    }
+                }
             }
         }
 
@@ -75,6 +440,11 @@ impl Step for AgentStep {
                 step_name: self.name.clone(),
                 step_type: StepType::Agent,
                 execution_time_ms: start.elapsed().as_millis() as u64,
+                critic,
+                iterations_run: None,
+                policy: None,
+                item_errors: None,
+                handoffs: Some(handoffs).filter(|h| !h.is_empty()),
             },
         })
     }
@@ -87,7 +457,23 @@ impl Step for AgentStep {
         StepType::Agent
     }
 
+    fn input_schema(&self) -> Option<&JsonValue> {
+        self.input_schema.as_ref()
+    }
+
+    fn output_schema(&self) -> Option<&JsonValue> {
+        self.output_schema.as_ref()
+    }
+
     fn description(&self) -> Option<&str> {
         Some(self.agent.config().system_prompt.as_str())
     }
+
+    fn get_agent(&self) -> Option<&Agent> {
+        Some(&self.agent)
+    }
+
+    fn clone_step(&self) -> Option<Box<dyn Step>> {
+        Some(Box::new(self.clone()))
+    }
 }
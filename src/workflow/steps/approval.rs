@@ -0,0 +1,383 @@
+use crate::workflow::step::{
+    ExecutionContext, Step, StepError, StepInput, StepOutput, StepOutputMetadata, StepResult,
+    StepType,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// A decision on a pending approval
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+    /// Pass the proposed data on unchanged
+    Approve,
+
+    /// Fail the step with `StepError::Rejected`
+    Reject { reason: String },
+
+    /// Pass this data on in place of the proposed data
+    Modify(serde_json::Value),
+}
+
+/// An approval step waiting for a decision
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingApproval {
+    pub approval_id: String,
+    pub workflow_id: String,
+
+    /// The waiting run, e.g. for `Runtime::cancel`
+    #[serde(default)]
+    pub run_id: String,
+
+    pub step_index: usize,
+    pub step_name: String,
+
+    /// What the step will pass on if approved
+    pub data: serde_json::Value,
+
+    /// What the approver is being asked, if the step says
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+
+    pub requested_at: DateTime<Utc>,
+
+    /// When the step rejects on its own, if it has a timeout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Who decided an approval, when and how; kept in `WorkflowRun::approvals`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApprovalRecord {
+    pub approval_id: String,
+    pub step_index: usize,
+    pub step_name: String,
+    pub decision: Decision,
+
+    /// From `Runtime::resolve_approval_as` or the runtime's decider; `timeout`
+    /// when the step's timeout rejected it
+    pub decided_by: String,
+    pub decided_at: DateTime<Utc>,
+}
+
+type Decider = Arc<dyn Fn(&PendingApproval) -> String + Send + Sync>;
+
+struct Waiting {
+    approval: PendingApproval,
+    reply: oneshot::Sender<(Decision, String)>,
+}
+
+#[derive(Default)]
+struct Queue {
+    pending: HashMap<String, Waiting>,
+    /// Decided approvals by run ID, until their run finishes
+    decided: HashMap<String, Vec<ApprovalRecord>>,
+}
+
+/// In-process approvals: the ones waiting and the decisions of runs still in
+/// flight
+///
+/// Pending approvals live only as long as the waiting step; nothing is
+/// persisted.
+#[derive(Clone, Default)]
+pub struct ApprovalQueue {
+    queue: Arc<Mutex<Queue>>,
+    decider: Option<Decider>,
+}
+
+impl ApprovalQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Name whoever resolves an approval without saying who they are, e.g.
+    /// from the request being handled (builder-style). Defaults to
+    /// `unknown`.
+    pub fn with_decider<F>(mut self, decider: F) -> Self
+    where
+        F: Fn(&PendingApproval) -> String + Send + Sync + 'static,
+    {
+        self.decider = Some(Arc::new(decider));
+        self
+    }
+
+    /// Approvals waiting for a decision, oldest first
+    pub fn pending(&self) -> Vec<PendingApproval> {
+        let queue = self.queue.lock().unwrap();
+        let mut pending: Vec<PendingApproval> = queue
+            .pending
+            .values()
+            .map(|waiting| waiting.approval.clone())
+            .collect();
+        pending.sort_by_key(|approval| approval.requested_at);
+        pending
+    }
+
+    /// Decide a pending approval, naming the decider with the queue's
+    /// decider; returns `false` if no such approval is waiting
+    pub fn resolve(&self, approval_id: &str, decision: Decision) -> bool {
+        let Some(waiting) = self.queue.lock().unwrap().pending.remove(approval_id) else {
+            return false;
+        };
+        let decided_by = match &self.decider {
+            Some(decider) => decider(&waiting.approval),
+            None => "unknown".to_string(),
+        };
+        waiting.reply.send((decision, decided_by)).is_ok()
+    }
+
+    /// Decide a pending approval on behalf of `decided_by`; returns `false`
+    /// if no such approval is waiting
+    pub fn resolve_as(
+        &self,
+        approval_id: &str,
+        decision: Decision,
+        decided_by: impl Into<String>,
+    ) -> bool {
+        match self.queue.lock().unwrap().pending.remove(approval_id) {
+            Some(waiting) => waiting.reply.send((decision, decided_by.into())).is_ok(),
+            None => false,
+        }
+    }
+
+    /// List `approval` as pending until it's decided or the guard drops
+    fn request(
+        &self,
+        approval: PendingApproval,
+    ) -> (Withdraw<'_>, oneshot::Receiver<(Decision, String)>) {
+        let (reply, decision) = oneshot::channel();
+        let approval_id = approval.approval_id.clone();
+        self.queue
+            .lock()
+            .unwrap()
+            .pending
+            .insert(approval_id.clone(), Waiting { approval, reply });
+        let guard = Withdraw {
+            queue: self,
+            approval_id,
+        };
+        (guard, decision)
+    }
+
+    fn record(&self, run_id: &str, record: ApprovalRecord) {
+        self.queue
+            .lock()
+            .unwrap()
+            .decided
+            .entry(run_id.to_string())
+            .or_default()
+            .push(record);
+    }
+
+    /// The decisions made in the run `run_id`, in order
+    pub(crate) fn take_records(&self, run_id: &str) -> Vec<ApprovalRecord> {
+        self.queue
+            .lock()
+            .unwrap()
+            .decided
+            .remove(run_id)
+            .unwrap_or_default()
+    }
+}
+
+/// Withdraws a pending approval when the step stops waiting for it, e.g.
+/// on a timeout or when the run is dropped
+struct Withdraw<'a> {
+    queue: &'a ApprovalQueue,
+    approval_id: String,
+}
+
+impl Drop for Withdraw<'_> {
+    fn drop(&mut self) {
+        self.queue
+            .queue
+            .lock()
+            .unwrap()
+            .pending
+            .remove(&self.approval_id);
+    }
+}
+
+/// A step that waits for a person to approve, reject or edit its input
+///
+/// On reaching the step, the runtime lists it in `Runtime::pending_approvals`
+/// and emits a `WorkflowStep` progress event with `Pending` status holding
+/// the data. The step then waits for `Runtime::resolve_approval`: an
+/// approval passes the data (or the modified data) on, and a rejection
+/// fails the step with `StepError::Rejected`.
+#[derive(Clone)]
+pub struct ApprovalStep {
+    name: String,
+    prompt: Option<String>,
+    timeout: Option<Duration>,
+}
+
+impl ApprovalStep {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            prompt: None,
+            timeout: None,
+        }
+    }
+
+    /// Tell the approver what they're deciding (builder-style)
+    pub fn with_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = Some(prompt.into());
+        self
+    }
+
+    /// Reject if nobody decides within `timeout` (builder-style)
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+#[async_trait]
+impl Step for ApprovalStep {
+    async fn execute_with_context(
+        &self,
+        input: StepInput,
+        ctx: ExecutionContext<'_>,
+    ) -> StepResult {
+        let start = std::time::Instant::now();
+        let Some(approvals) = ctx.approvals else {
+            return Err(StepError::ExecutionFailed(format!(
+                "approval step '{}' needs a runtime to wait for a decision",
+                self.name
+            )));
+        };
+        let workflow_id = input.metadata.workflow_id.clone();
+        let step_index = input.metadata.step_index;
+
+        let requested_at = Utc::now();
+        let approval = PendingApproval {
+            approval_id: format!("approval_{}", uuid::Uuid::new_v4()),
+            workflow_id: workflow_id.clone(),
+            run_id: input.metadata.run_id.clone(),
+            step_index,
+            step_name: self.name.clone(),
+            data: input.data.clone(),
+            prompt: self.prompt.clone(),
+            requested_at,
+            expires_at: self
+                .timeout
+                .and_then(|timeout| chrono::Duration::from_std(timeout).ok())
+                .map(|timeout| requested_at + timeout),
+        };
+        let approval_id = approval.approval_id.clone();
+        if let Some(events) = ctx.event_stream {
+            events.step_pending(
+                &workflow_id,
+                step_index,
+                &format!("'{}' is waiting for approval", self.name),
+                serde_json::to_value(&approval).unwrap_or_default(),
+            );
+        }
+
+        let (pending, decision) = approvals.request(approval);
+        let expired = async {
+            match self.timeout {
+                Some(timeout) => tokio::time::sleep(timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+        let canceled = async {
+            match ctx.cancellation {
+                Some(token) => token.cancelled().await,
+                None => std::future::pending().await,
+            }
+        };
+        let (decision, decided_by) = tokio::select! {
+            decided = decision => decided.map_err(|_| {
+                StepError::ExecutionFailed(format!("approval '{}' was dropped", approval_id))
+            })?,
+            _ = expired => (
+                Decision::Reject {
+                    reason: format!(
+                        "no decision within {}s",
+                        self.timeout.unwrap_or_default().as_secs_f64()
+                    ),
+                },
+                "timeout".to_string(),
+            ),
+            _ = canceled => {
+                return Err(StepError::Canceled(format!(
+                    "canceled while '{}' waited for approval",
+                    self.name
+                )));
+            }
+        };
+        drop(pending);
+
+        if let Some(events) = ctx.event_stream {
+            events.step_progress(
+                &workflow_id,
+                step_index,
+                &format!("'{}' decided by {}", self.name, decided_by),
+                serde_json::json!({
+                    "approval_id": &approval_id,
+                    "decision": &decision,
+                    "decided_by": &decided_by,
+                }),
+            );
+        }
+        approvals.record(
+            &input.metadata.run_id,
+            ApprovalRecord {
+                approval_id,
+                step_index,
+                step_name: self.name.clone(),
+                decision: decision.clone(),
+                decided_by: decided_by.clone(),
+                decided_at: Utc::now(),
+            },
+        );
+
+        let data = match decision {
+            Decision::Approve => input.data,
+            Decision::Modify(data) => data,
+            Decision::Reject { reason } => {
+                return Err(StepError::Rejected(format!(
+                    "'{}' rejected by {}: {}",
+                    self.name, decided_by, reason
+                )));
+            }
+        };
+        Ok(StepOutput {
+            data,
+            metadata: StepOutputMetadata {
+                step_name: self.name.clone(),
+                step_type: StepType::Approval,
+                execution_time_ms: start.elapsed().as_millis() as u64,
+                critic: None,
+                iterations_run: None,
+                policy: None,
+                item_errors: None,
+                handoffs: None,
+            },
+        })
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn step_type(&self) -> StepType {
+        StepType::Approval
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.prompt.as_deref()
+    }
+
+    fn clone_step(&self) -> Option<Box<dyn Step>> {
+        Some(Box::new(self.clone()))
+    }
+}
@@ -1,10 +1,11 @@
 use crate::workflow::step::{ExecutionContext, Step, StepInput, StepResult, StepType};
 use async_trait::async_trait;
+use std::sync::Arc;
 
 /// A step that conditionally executes one of two branches
 pub struct ConditionalStep {
     name: String,
-    condition_fn: Box<dyn Fn(&serde_json::Value) -> bool + Send + Sync>,
+    condition_fn: Arc<dyn Fn(&serde_json::Value) -> bool + Send + Sync>,
     true_step: Box<dyn Step>,
     false_step: Box<dyn Step>,
 }
@@ -21,7 +22,7 @@ impl ConditionalStep {
     {
         Self {
             name,
-            condition_fn: Box::new(condition_fn),
+            condition_fn: Arc::new(condition_fn),
             true_step,
             false_step,
         }
@@ -85,4 +86,17 @@ impl Step for ConditionalStep {
     fn get_branches(&self) -> Option<(&dyn Step, &dyn Step)> {
         Some((self.true_step.as_ref(), self.false_step.as_ref()))
     }
+
+    fn evaluate_condition(&self, data: &serde_json::Value) -> Option<bool> {
+        Some((self.condition_fn)(data))
+    }
+
+    fn clone_step(&self) -> Option<Box<dyn Step>> {
+        Some(Box::new(Self {
+            name: self.name.clone(),
+            condition_fn: self.condition_fn.clone(),
+            true_step: self.true_step.clone_step()?,
+            false_step: self.false_step.clone_step()?,
+        }))
+    }
 }
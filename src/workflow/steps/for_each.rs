@@ -0,0 +1,341 @@
+use crate::error::AggregateError;
+use crate::workflow::step::{
+    ExecutionContext, Step, StepError, StepInput, StepInputMetadata, StepOutput,
+    StepOutputMetadata, StepResult, StepType,
+};
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// What a for-each step does when the step fails on an item
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ForEachFailureMode {
+    /// Fail with the first item error; items still running are dropped and
+    /// the rest are never started
+    #[default]
+    FailFast,
+
+    /// Run every item, leaving `null` in place of each failed item's output;
+    /// the failures are in `metadata.item_errors`, labeled by item index
+    CollectErrors,
+}
+
+/// The step run on each item: one shared instance, or a fresh one per item
+enum ItemStep {
+    Shared(Arc<dyn Step>),
+    Factory {
+        make: Arc<dyn Fn() -> Box<dyn Step> + Send + Sync>,
+        /// Built once for `name`, `get_for_each_body` and diagrams
+        template: Box<dyn Step>,
+    },
+}
+
+/// A step that runs another step on each element of an array
+///
+/// The array is the step's input, or the part of it at a JSON pointer
+/// (`with_pointer`). Each element becomes the input data of one run, with
+/// its position in `metadata.item_index`. At most `concurrency` runs are in
+/// flight at once, and the output is the array of their outputs in input
+/// order.
+///
+/// Item events use the component id `workflow:step:N:item:I`.
+pub struct ForEachStep {
+    name: String,
+    step: ItemStep,
+    pointer: Option<String>,
+    concurrency: usize,
+    failure_mode: ForEachFailureMode,
+}
+
+impl ForEachStep {
+    /// Run `step` on every item; `concurrency` below 1 is treated as 1
+    pub fn new(name: String, step: Box<dyn Step>, concurrency: usize) -> Self {
+        Self::with_step(name, ItemStep::Shared(Arc::from(step)), concurrency)
+    }
+
+    /// Run a fresh step from `factory` on every item, for steps that keep
+    /// state between runs
+    pub fn from_factory<F>(name: String, factory: F, concurrency: usize) -> Self
+    where
+        F: Fn() -> Box<dyn Step> + Send + Sync + 'static,
+    {
+        let template = factory();
+        Self::with_step(
+            name,
+            ItemStep::Factory {
+                make: Arc::new(factory),
+                template,
+            },
+            concurrency,
+        )
+    }
+
+    fn with_step(name: String, step: ItemStep, concurrency: usize) -> Self {
+        Self {
+            name,
+            step,
+            pointer: None,
+            concurrency: concurrency.max(1),
+            failure_mode: ForEachFailureMode::default(),
+        }
+    }
+
+    /// Take the array at this JSON pointer within the input, e.g.
+    /// `/documents` (builder-style)
+    pub fn with_pointer(mut self, pointer: impl Into<String>) -> Self {
+        self.pointer = Some(pointer.into());
+        self
+    }
+
+    /// Set what happens when an item fails (builder-style)
+    pub fn with_failure_mode(mut self, mode: ForEachFailureMode) -> Self {
+        self.failure_mode = mode;
+        self
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    fn item_step(&self) -> &dyn Step {
+        match &self.step {
+            ItemStep::Shared(step) => step.as_ref(),
+            ItemStep::Factory { template, .. } => template.as_ref(),
+        }
+    }
+
+    fn items<'a>(
+        &self,
+        data: &'a serde_json::Value,
+    ) -> Result<&'a Vec<serde_json::Value>, StepError> {
+        let target = match &self.pointer {
+            Some(pointer) => data.pointer(pointer).ok_or_else(|| {
+                StepError::InvalidInput(format!(
+                    "for-each '{}': nothing at {} in the input",
+                    self.name, pointer
+                ))
+            })?,
+            None => data,
+        };
+        target.as_array().ok_or_else(|| {
+            StepError::InvalidInput(format!(
+                "for-each '{}' needs an array{}, got {}",
+                self.name,
+                self.pointer
+                    .as_ref()
+                    .map(|p| format!(" at {}", p))
+                    .unwrap_or_default(),
+                json_kind(target)
+            ))
+        })
+    }
+
+    async fn run_item(
+        &self,
+        item_index: usize,
+        item: serde_json::Value,
+        input: &StepInput,
+        ctx: ExecutionContext<'_>,
+    ) -> StepResult {
+        if ctx.cancellation.is_some_and(|token| token.is_cancelled()) {
+            return Err(StepError::Canceled(format!(
+                "for-each '{}' canceled before item {}",
+                self.name, item_index
+            )));
+        }
+        let item_input = StepInput {
+            data: item,
+            metadata: StepInputMetadata {
+                item_index: Some(item_index),
+                ..input.metadata.clone()
+            },
+            workflow_context: input.workflow_context.clone(),
+        };
+        match &self.step {
+            ItemStep::Shared(step) => step.execute_with_context(item_input, ctx).await,
+            ItemStep::Factory { make, .. } => make().execute_with_context(item_input, ctx).await,
+        }
+    }
+}
+
+#[async_trait]
+impl Step for ForEachStep {
+    async fn execute_with_context(
+        &self,
+        input: StepInput,
+        ctx: ExecutionContext<'_>,
+    ) -> StepResult {
+        let start = std::time::Instant::now();
+        let workflow_id = input.metadata.workflow_id.clone();
+        let step_index = input.metadata.step_index;
+        let items = self.items(&input.data)?;
+        let step_name = self.item_step().name();
+        let started: Vec<AtomicBool> = items.iter().map(|_| AtomicBool::new(false)).collect();
+        let finished: Vec<AtomicBool> = items.iter().map(|_| AtomicBool::new(false)).collect();
+
+        let runs = items.iter().cloned().enumerate().map(|(item_index, item)| {
+            let input = &input;
+            let workflow_id = &workflow_id;
+            let (started, finished) = (&started[item_index], &finished[item_index]);
+            async move {
+                started.store(true, Ordering::SeqCst);
+                if let Some(events) = ctx.event_stream {
+                    events.item_started(
+                        workflow_id,
+                        step_index,
+                        item_index,
+                        serde_json::json!({
+                            "step_name": step_name,
+                            "for_each_step": &self.name,
+                            "item_index": item_index,
+                        }),
+                    );
+                }
+
+                let result = self.run_item(item_index, item, input, ctx).await;
+                finished.store(true, Ordering::SeqCst);
+
+                if let Some(events) = ctx.event_stream {
+                    match &result {
+                        Ok(output) => events.item_completed(
+                            workflow_id,
+                            step_index,
+                            item_index,
+                            serde_json::json!({
+                                "step_name": step_name,
+                                "item_index": item_index,
+                                "execution_time_ms": output.metadata.execution_time_ms,
+                            }),
+                        ),
+                        Err(e) => events.item_failed(
+                            workflow_id,
+                            step_index,
+                            item_index,
+                            &e.to_string(),
+                            serde_json::json!({
+                                "step_name": step_name,
+                                "item_index": item_index,
+                            }),
+                        ),
+                    };
+                }
+
+                (item_index, result)
+            }
+        });
+
+        let mut outputs = vec![serde_json::Value::Null; items.len()];
+        let mut errors = Vec::new();
+        let mut results = stream::iter(runs).buffer_unordered(self.concurrency);
+        while let Some((item_index, result)) = results.next().await {
+            match result {
+                Ok(output) => outputs[item_index] = output.data,
+                Err(error) if self.failure_mode == ForEachFailureMode::FailFast => {
+                    // Items in flight are dropped with the stream
+                    drop(results);
+                    if let Some(events) = ctx.event_stream {
+                        let reason = format!("Canceled: item {} failed", item_index);
+                        for (index, done) in finished.iter().enumerate() {
+                            if started[index].load(Ordering::SeqCst) && !done.load(Ordering::SeqCst)
+                            {
+                                events.item_failed(
+                                    &workflow_id,
+                                    step_index,
+                                    index,
+                                    &reason,
+                                    serde_json::json!({
+                                        "step_name": step_name,
+                                        "item_index": index,
+                                        "canceled": true,
+                                    }),
+                                );
+                            }
+                        }
+                    }
+                    return Err(error);
+                }
+                Err(error) => errors.push((item_index, error)),
+            }
+        }
+
+        // A run-wide cancellation stays a cancellation
+        if !errors.is_empty()
+            && errors
+                .iter()
+                .all(|(_, e)| matches!(e, StepError::Canceled(_)))
+        {
+            return Err(errors.swap_remove(0).1);
+        }
+        let item_errors = (!errors.is_empty()).then(|| {
+            errors.sort_by_key(|(index, _)| *index);
+            let mut aggregate = AggregateError::new("items", items.len());
+            for (index, error) in errors {
+                aggregate.push(index.to_string(), error);
+            }
+            aggregate
+        });
+
+        Ok(StepOutput {
+            data: serde_json::Value::Array(outputs),
+            metadata: StepOutputMetadata {
+                step_name: self.name.clone(),
+                step_type: StepType::ForEach,
+                execution_time_ms: start.elapsed().as_millis() as u64,
+                critic: None,
+                iterations_run: None,
+                policy: None,
+                item_errors,
+                handoffs: None,
+            },
+        })
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn step_type(&self) -> StepType {
+        StepType::ForEach
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("Runs a step on each element of an array")
+    }
+
+    fn input_pointers(&self) -> Vec<&str> {
+        self.pointer.iter().map(String::as_str).collect()
+    }
+
+    fn get_for_each_body(&self) -> Option<(&dyn Step, usize)> {
+        Some((self.item_step(), self.concurrency))
+    }
+
+    fn clone_step(&self) -> Option<Box<dyn Step>> {
+        let step = match &self.step {
+            ItemStep::Shared(step) => ItemStep::Shared(Arc::from(step.clone_step()?)),
+            ItemStep::Factory { make, .. } => ItemStep::Factory {
+                make: make.clone(),
+                template: make(),
+            },
+        };
+        Some(Box::new(Self {
+            name: self.name.clone(),
+            step,
+            pointer: self.pointer.clone(),
+            concurrency: self.concurrency,
+            failure_mode: self.failure_mode,
+        }))
+    }
+}
+
+fn json_kind(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "a boolean",
+        serde_json::Value::Number(_) => "a number",
+        serde_json::Value::String(_) => "a string",
+        serde_json::Value::Array(_) => "an array",
+        serde_json::Value::Object(_) => "an object",
+    }
+}
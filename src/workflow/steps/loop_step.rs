@@ -0,0 +1,153 @@
+use crate::workflow::step::{
+    ExecutionContext, Step, StepError, StepInput, StepOutput, StepResult, StepType,
+};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// What a loop step does when its body has run `max_iterations` times
+/// without meeting the condition
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LoopExhaustedMode {
+    /// Fail with `StepError::ExecutionFailed`
+    #[default]
+    Fail,
+
+    /// Succeed with the last iteration's output
+    PassThrough,
+}
+
+/// A step that runs its body repeatedly until a condition holds
+///
+/// Each iteration's output is the next iteration's input. The condition is
+/// evaluated on every output and the loop stops at the first one for which
+/// it returns `true` ("until", not "while"), e.g. once a critic approves a
+/// draft. The body always runs at least once and never more than
+/// `max_iterations` times.
+///
+/// A `WorkflowStep` `Progress` event with the iteration number is emitted
+/// after each iteration. The output is the last iteration's, with
+/// `metadata.iterations_run` set.
+pub struct LoopStep {
+    name: String,
+    body: Box<dyn Step>,
+    condition_fn: Arc<dyn Fn(&serde_json::Value) -> bool + Send + Sync>,
+    max_iterations: usize,
+    exhausted_mode: LoopExhaustedMode,
+}
+
+impl LoopStep {
+    /// `max_iterations` below 1 is treated as 1
+    pub fn new<F>(name: String, body: Box<dyn Step>, condition_fn: F, max_iterations: usize) -> Self
+    where
+        F: Fn(&serde_json::Value) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            name,
+            body,
+            condition_fn: Arc::new(condition_fn),
+            max_iterations: max_iterations.max(1),
+            exhausted_mode: LoopExhaustedMode::default(),
+        }
+    }
+
+    /// Set what happens when the cap is hit (builder-style)
+    pub fn with_exhausted_mode(mut self, mode: LoopExhaustedMode) -> Self {
+        self.exhausted_mode = mode;
+        self
+    }
+
+    pub fn max_iterations(&self) -> usize {
+        self.max_iterations
+    }
+
+    fn finish(
+        &self,
+        mut output: StepOutput,
+        iterations: usize,
+        start: std::time::Instant,
+    ) -> StepOutput {
+        output.metadata.step_name = self.name.clone();
+        output.metadata.step_type = StepType::Loop;
+        output.metadata.execution_time_ms = start.elapsed().as_millis() as u64;
+        output.metadata.iterations_run = Some(iterations);
+        output
+    }
+}
+
+#[async_trait]
+impl Step for LoopStep {
+    async fn execute_with_context(
+        &self,
+        input: StepInput,
+        ctx: ExecutionContext<'_>,
+    ) -> StepResult {
+        let start = std::time::Instant::now();
+        let workflow_id = input.metadata.workflow_id.clone();
+        let step_index = input.metadata.step_index;
+        let mut input = input;
+
+        for iteration in 1..=self.max_iterations {
+            if ctx.cancellation.is_some_and(|token| token.is_cancelled()) {
+                return Err(StepError::Canceled(format!(
+                    "loop '{}' canceled before iteration {}",
+                    self.name, iteration
+                )));
+            }
+
+            let output = self.body.execute_with_context(input.clone(), ctx).await?;
+            let condition_met = (self.condition_fn)(&output.data);
+
+            if let Some(events) = ctx.event_stream {
+                events.step_progress(
+                    &workflow_id,
+                    step_index,
+                    &format!("iteration {} of at most {}", iteration, self.max_iterations),
+                    serde_json::json!({
+                        "step_name": &self.name,
+                        "iteration": iteration,
+                        "max_iterations": self.max_iterations,
+                        "condition_met": condition_met,
+                    }),
+                );
+            }
+
+            if condition_met {
+                return Ok(self.finish(output, iteration, start));
+            }
+            if iteration == self.max_iterations {
+                return match self.exhausted_mode {
+                    LoopExhaustedMode::Fail => Err(StepError::ExecutionFailed(format!(
+                        "loop '{}' ran {} iterations without meeting its condition",
+                        self.name, self.max_iterations
+                    ))),
+                    LoopExhaustedMode::PassThrough => Ok(self.finish(output, iteration, start)),
+                };
+            }
+            input.data = output.data;
+        }
+
+        unreachable!("the final iteration always returns")
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn step_type(&self) -> StepType {
+        StepType::Loop
+    }
+
+    fn get_loop_body(&self) -> Option<(&dyn Step, usize)> {
+        Some((self.body.as_ref(), self.max_iterations))
+    }
+
+    fn clone_step(&self) -> Option<Box<dyn Step>> {
+        Some(Box::new(Self {
+            name: self.name.clone(),
+            body: self.body.clone_step()?,
+            condition_fn: self.condition_fn.clone(),
+            max_iterations: self.max_iterations,
+            exhausted_mode: self.exhausted_mode,
+        }))
+    }
+}
@@ -1,11 +1,26 @@
 //! Concrete workflow step implementations.
 
 mod agent;
+mod approval;
 mod conditional;
+mod for_each;
+mod loop_step;
+mod parallel;
+mod policy;
 mod subworkflow;
+mod switch;
 mod transform;
+mod wait;
 
 pub use agent::AgentStep;
+pub use approval::{ApprovalQueue, ApprovalRecord, ApprovalStep, Decision, PendingApproval};
 pub use conditional::ConditionalStep;
+pub use for_each::{ForEachFailureMode, ForEachStep};
+pub use loop_step::{LoopExhaustedMode, LoopStep};
+pub use parallel::{ParallelFailureMode, ParallelOutput, ParallelStep};
+pub(crate) use policy::execute_with_policy;
+pub use policy::{OnError, PolicyOutcome, PolicyStep, StepPolicy, StepStatus};
 pub use subworkflow::SubWorkflowStep;
-pub use transform::TransformStep;
+pub use switch::SwitchStep;
+pub use transform::{TransformStep, TryTransformStep};
+pub use wait::WaitStep;
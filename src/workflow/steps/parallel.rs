@@ -0,0 +1,278 @@
+use crate::context::WorkflowContext;
+use crate::error::AggregateError;
+use crate::workflow::step::{
+    clone_steps, ExecutionContext, Step, StepError, StepInput, StepOutput, StepOutputMetadata,
+    StepResult, StepType,
+};
+use async_trait::async_trait;
+use futures::future::{join_all, try_join_all};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// What a parallel step does when one of its branches fails
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParallelFailureMode {
+    /// Fail with the first branch error; branches still running are dropped
+    #[default]
+    FailFast,
+
+    /// Let every branch finish, then fail with all branch errors as a
+    /// `StepError::Aggregate` labeled by branch name
+    CollectErrors,
+}
+
+/// How a parallel step shapes its output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParallelOutput {
+    /// An array of branch outputs in declaration order
+    #[default]
+    Array,
+
+    /// An object keyed by branch step name (names should be unique)
+    ByName,
+}
+
+/// A step that runs independent steps concurrently on the same input
+///
+/// Each branch gets a clone of the step's input and the parent's execution
+/// context. Branch events use the component id `workflow:step:N.B`, where
+/// `B` is the branch's position.
+///
+/// With a workflow context, each branch works on its own copy, starting
+/// from the history as it was before the step, so one branch's agents
+/// don't see (or overwrite) another's turns. Once every branch succeeds,
+/// the copies are merged back in branch order: the history gets the first
+/// branch's new turns, then the second's, and so on, whichever finished
+/// first. A failed step leaves the context as it was.
+pub struct ParallelStep {
+    name: String,
+    branches: Vec<Box<dyn Step>>,
+    failure_mode: ParallelFailureMode,
+    output: ParallelOutput,
+}
+
+impl ParallelStep {
+    pub fn new(name: String, branches: Vec<Box<dyn Step>>) -> Self {
+        Self {
+            name,
+            branches,
+            failure_mode: ParallelFailureMode::default(),
+            output: ParallelOutput::default(),
+        }
+    }
+
+    /// Set what happens when a branch fails (builder-style)
+    pub fn with_failure_mode(mut self, mode: ParallelFailureMode) -> Self {
+        self.failure_mode = mode;
+        self
+    }
+
+    /// Set how branch outputs are combined (builder-style)
+    pub fn with_output(mut self, output: ParallelOutput) -> Self {
+        self.output = output;
+        self
+    }
+
+    fn combine(&self, outputs: Vec<StepOutput>) -> serde_json::Value {
+        match self.output {
+            ParallelOutput::Array => outputs.into_iter().map(|o| o.data).collect(),
+            ParallelOutput::ByName => self
+                .branches
+                .iter()
+                .zip(outputs)
+                .map(|(branch, o)| (branch.name().to_string(), o.data))
+                .collect::<serde_json::Map<_, _>>()
+                .into(),
+        }
+    }
+}
+
+#[async_trait]
+impl Step for ParallelStep {
+    async fn execute_with_context(
+        &self,
+        input: StepInput,
+        ctx: ExecutionContext<'_>,
+    ) -> StepResult {
+        let start = std::time::Instant::now();
+        let workflow_id = input.metadata.workflow_id.clone();
+        let step_index = input.metadata.step_index;
+        let finished: Vec<AtomicBool> = self
+            .branches
+            .iter()
+            .map(|_| AtomicBool::new(false))
+            .collect();
+        let contexts: Vec<_> = self
+            .branches
+            .iter()
+            .map(|_| {
+                let context = input.workflow_context.as_ref()?;
+                let branch = context.read().unwrap().branch();
+                Some(Arc::new(RwLock::new(branch)))
+            })
+            .collect();
+
+        let runs = self
+            .branches
+            .iter()
+            .enumerate()
+            .map(|(branch_index, branch)| {
+                let mut input = input.clone();
+                input.workflow_context = contexts[branch_index].clone();
+                let done = &finished[branch_index];
+                let workflow_id = &workflow_id;
+                async move {
+                    let step_name = branch.name();
+                    if let Some(events) = ctx.event_stream {
+                        events.branch_started(
+                            workflow_id,
+                            step_index,
+                            branch_index,
+                            serde_json::json!({
+                                "step_name": step_name,
+                                "step_type": format!("{:?}", branch.step_type()),
+                                "parallel_step": &self.name,
+                            }),
+                        );
+                    }
+
+                    let result = branch.execute_with_context(input, ctx).await;
+                    done.store(true, Ordering::SeqCst);
+
+                    if let Some(events) = ctx.event_stream {
+                        match &result {
+                            Ok(output) => events.branch_completed(
+                                workflow_id,
+                                step_index,
+                                branch_index,
+                                serde_json::json!({
+                                    "step_name": step_name,
+                                    "execution_time_ms": output.metadata.execution_time_ms,
+                                }),
+                            ),
+                            Err(e) => events.branch_failed(
+                                workflow_id,
+                                step_index,
+                                branch_index,
+                                &e.to_string(),
+                                serde_json::json!({ "step_name": step_name }),
+                            ),
+                        };
+                    }
+
+                    result.map_err(|e| (branch_index, e))
+                }
+            });
+
+        let outputs = match self.failure_mode {
+            ParallelFailureMode::FailFast => match try_join_all(runs).await {
+                Ok(outputs) => outputs,
+                Err((failed, error)) => {
+                    // Branches that hadn't finished were dropped with the join
+                    if let Some(events) = ctx.event_stream {
+                        let reason =
+                            format!("Canceled: branch '{}' failed", self.branches[failed].name());
+                        for (branch_index, branch) in self.branches.iter().enumerate() {
+                            if !finished[branch_index].load(Ordering::SeqCst) {
+                                events.branch_failed(
+                                    &workflow_id,
+                                    step_index,
+                                    branch_index,
+                                    &reason,
+                                    serde_json::json!({
+                                        "step_name": branch.name(),
+                                        "canceled": true,
+                                    }),
+                                );
+                            }
+                        }
+                    }
+                    return Err(error);
+                }
+            },
+            ParallelFailureMode::CollectErrors => {
+                let mut outputs = Vec::with_capacity(self.branches.len());
+                let mut errors = Vec::new();
+                for result in join_all(runs).await {
+                    match result {
+                        Ok(output) => outputs.push(output),
+                        Err(failure) => errors.push(failure),
+                    }
+                }
+
+                if !errors.is_empty() {
+                    // A run-wide cancellation stays a cancellation
+                    if errors
+                        .iter()
+                        .all(|(_, e)| matches!(e, StepError::Canceled(_)))
+                    {
+                        return Err(errors.swap_remove(0).1);
+                    }
+                    let mut aggregate =
+                        AggregateError::new("parallel branches", self.branches.len());
+                    for (i, error) in errors {
+                        aggregate.push(self.branches[i].name(), error);
+                    }
+                    return Err(StepError::Aggregate(aggregate));
+                }
+                outputs
+            }
+        };
+
+        if let Some(context) = &input.workflow_context {
+            let branches = contexts
+                .into_iter()
+                .flatten()
+                .map(|branch| WorkflowContext::clone(&branch.read().unwrap()))
+                .collect();
+            let mut context = context.write().unwrap();
+            let events = context
+                .merge_branches(branches)
+                .map_err(StepError::LimitReached)?;
+            if let Some(stream) = ctx.event_stream {
+                for event in &events {
+                    event.emit(stream, context.metadata.workflow_id.clone(), None);
+                }
+            }
+        }
+
+        Ok(StepOutput {
+            data: self.combine(outputs),
+            metadata: StepOutputMetadata {
+                step_name: self.name.clone(),
+                step_type: StepType::Parallel,
+                execution_time_ms: start.elapsed().as_millis() as u64,
+                critic: None,
+                iterations_run: None,
+                policy: None,
+                item_errors: None,
+                handoffs: None,
+            },
+        })
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn step_type(&self) -> StepType {
+        StepType::Parallel
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("Runs independent steps concurrently")
+    }
+
+    fn get_parallel_branches(&self) -> Option<Vec<&dyn Step>> {
+        Some(self.branches.iter().map(|b| b.as_ref()).collect())
+    }
+
+    fn clone_step(&self) -> Option<Box<dyn Step>> {
+        Some(Box::new(Self {
+            name: self.name.clone(),
+            branches: clone_steps(&self.branches)?,
+            failure_mode: self.failure_mode,
+            output: self.output,
+        }))
+    }
+}
@@ -0,0 +1,275 @@
+use crate::runtime::retry::RetryPolicy;
+use crate::types::JsonValue;
+use crate::workflow::step::{
+    ExecutionContext, Step, StepError, StepInput, StepOutput, StepOutputMetadata, StepResult,
+    StepType,
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+
+/// What happens when a step with a [`StepPolicy`] fails for good
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum OnError {
+    /// Fail the run
+    #[default]
+    Fail,
+
+    /// Record the failure and pass the step's input on as its output
+    Skip,
+
+    /// Record the failure and use this value as the step's output
+    UseFallbackValue(JsonValue),
+}
+
+/// How a step ended: with its output, or without one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    #[default]
+    Succeeded,
+
+    /// Failed; the input was passed on (`OnError::Skip`)
+    Skipped,
+
+    /// Failed; the policy's fallback value was used
+    /// (`OnError::UseFallbackValue`)
+    Fallback,
+
+    /// Failed and ended the run
+    Failed,
+
+    /// Canceled, ending the run
+    Canceled,
+}
+
+/// Retry and failure handling for one step
+///
+/// Attach it with [`PolicyStep`] or `WorkflowBuilder::add_step_with_policy`.
+/// Cancellations and conversation limits are never retried or absorbed;
+/// they end the run as they would without a policy.
+#[derive(Debug, Clone, Default)]
+pub struct StepPolicy {
+    /// Retries with this backoff; `None` runs the step once
+    pub retry: Option<RetryPolicy>,
+
+    pub on_error: OnError,
+}
+
+impl StepPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Retry failed executions (builder-style)
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Set what happens once retries are used up (builder-style)
+    pub fn on_error(mut self, on_error: OnError) -> Self {
+        self.on_error = on_error;
+        self
+    }
+}
+
+/// How a step with a policy finished, in `StepOutputMetadata::policy`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyOutcome {
+    pub status: StepStatus,
+
+    /// Executions, including the first
+    pub attempts: u32,
+
+    /// The last failure, when the step was skipped or fell back
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<StepError>,
+}
+
+/// A step run under a [`StepPolicy`]
+///
+/// It reports the wrapped step's name and type. Each retry emits a
+/// `WorkflowStep` `Progress` event with the attempt number and the error.
+pub struct PolicyStep {
+    inner: Box<dyn Step>,
+    policy: StepPolicy,
+}
+
+impl PolicyStep {
+    pub fn new(inner: Box<dyn Step>, policy: StepPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    pub fn policy(&self) -> &StepPolicy {
+        &self.policy
+    }
+}
+
+/// Run `attempt` until it succeeds or `policy` gives up, then apply
+/// `policy.on_error`
+pub(crate) async fn execute_with_policy<F, Fut>(
+    policy: &StepPolicy,
+    step: &dyn Step,
+    input: &StepInput,
+    ctx: ExecutionContext<'_>,
+    mut attempt: F,
+) -> StepResult
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = StepResult>,
+{
+    let start = std::time::Instant::now();
+    let mut attempts = 1;
+    let error = loop {
+        let error = match attempt().await {
+            Ok(mut output) => {
+                output.metadata.policy = Some(PolicyOutcome {
+                    status: StepStatus::Succeeded,
+                    attempts,
+                    error: None,
+                });
+                return Ok(output);
+            }
+            Err(
+                e @ (StepError::Canceled(_)
+                | StepError::LimitReached(_)
+                | StepError::BudgetExceeded(_)),
+            ) => return Err(e),
+            Err(e) => e,
+        };
+        let Some(delay) = policy
+            .retry
+            .as_ref()
+            .and_then(|retry| retry.next_delay(attempts - 1, start.elapsed(), None))
+        else {
+            break error;
+        };
+        attempts += 1;
+        if let Some(events) = ctx.event_stream {
+            events.step_progress(
+                &input.metadata.workflow_id,
+                input.metadata.step_index,
+                &format!("Retrying (attempt {}) after: {}", attempts, error),
+                serde_json::json!({
+                    "step_name": step.name(),
+                    "attempt": attempts,
+                    "error": error.to_string(),
+                    "delay_ms": delay.as_millis() as u64,
+                }),
+            );
+        }
+        match ctx.cancellation {
+            Some(token) => tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = token.cancelled() => {
+                    return Err(StepError::Canceled(format!(
+                        "step '{}' canceled while waiting to retry",
+                        step.name()
+                    )));
+                }
+            },
+            None => tokio::time::sleep(delay).await,
+        }
+    };
+
+    let (status, data) = match &policy.on_error {
+        OnError::Fail => return Err(error),
+        OnError::Skip => (StepStatus::Skipped, input.data.clone()),
+        OnError::UseFallbackValue(value) => (StepStatus::Fallback, value.clone()),
+    };
+    Ok(StepOutput {
+        data,
+        metadata: StepOutputMetadata {
+            step_name: step.name().to_string(),
+            step_type: step.step_type(),
+            execution_time_ms: start.elapsed().as_millis() as u64,
+            critic: None,
+            iterations_run: None,
+            policy: Some(PolicyOutcome {
+                status,
+                attempts,
+                error: Some(error),
+            }),
+            item_errors: None,
+            handoffs: None,
+        },
+    })
+}
+
+#[async_trait]
+impl Step for PolicyStep {
+    async fn execute_with_context(
+        &self,
+        input: StepInput,
+        ctx: ExecutionContext<'_>,
+    ) -> StepResult {
+        execute_with_policy(&self.policy, self.inner.as_ref(), &input, ctx, || {
+            self.inner.execute_with_context(input.clone(), ctx)
+        })
+        .await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn step_type(&self) -> StepType {
+        self.inner.step_type()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.inner.description()
+    }
+
+    fn input_schema(&self) -> Option<&JsonValue> {
+        self.inner.input_schema()
+    }
+
+    fn output_schema(&self) -> Option<&JsonValue> {
+        self.inner.output_schema()
+    }
+
+    fn get_branches(&self) -> Option<(&dyn Step, &dyn Step)> {
+        self.inner.get_branches()
+    }
+
+    fn evaluate_condition(&self, data: &JsonValue) -> Option<bool> {
+        self.inner.evaluate_condition(data)
+    }
+
+    fn get_cases(&self) -> Option<Vec<(&str, &dyn Step)>> {
+        self.inner.get_cases()
+    }
+
+    fn get_parallel_branches(&self) -> Option<Vec<&dyn Step>> {
+        self.inner.get_parallel_branches()
+    }
+
+    fn get_loop_body(&self) -> Option<(&dyn Step, usize)> {
+        self.inner.get_loop_body()
+    }
+
+    fn get_for_each_body(&self) -> Option<(&dyn Step, usize)> {
+        self.inner.get_for_each_body()
+    }
+
+    fn get_sub_workflow(&self) -> Option<crate::workflow::Workflow> {
+        self.inner.get_sub_workflow()
+    }
+
+    fn get_policy(&self) -> Option<(&dyn Step, &StepPolicy)> {
+        Some((self.inner.as_ref(), &self.policy))
+    }
+
+    fn get_agent(&self) -> Option<&crate::agent::Agent> {
+        self.inner.get_agent()
+    }
+
+    fn clone_step(&self) -> Option<Box<dyn Step>> {
+        Some(Box::new(Self {
+            inner: self.inner.clone_step()?,
+            policy: self.policy.clone(),
+        }))
+    }
+}
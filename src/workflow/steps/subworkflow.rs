@@ -1,15 +1,18 @@
+use crate::event::sampling;
 use crate::runtime::Runtime;
 use crate::workflow::step::{
     ExecutionContext, Step, StepError, StepInput, StepOutput, StepOutputMetadata, StepResult,
     StepType,
 };
-use crate::workflow::Workflow;
+use crate::workflow::{SubWorkflowSummary, Workflow};
 use async_trait::async_trait;
+use std::sync::Arc;
 
 /// A step that executes an entire workflow as a sub-workflow
+#[derive(Clone)]
 pub struct SubWorkflowStep {
     name: String,
-    workflow_builder: Box<dyn Fn() -> Workflow + Send + Sync>,
+    workflow_builder: Arc<dyn Fn() -> Workflow + Send + Sync>,
 }
 
 impl SubWorkflowStep {
@@ -19,7 +22,7 @@ impl SubWorkflowStep {
     {
         Self {
             name,
-            workflow_builder: Box::new(workflow_builder),
+            workflow_builder: Arc::new(workflow_builder),
         }
     }
 
@@ -42,11 +45,63 @@ impl SubWorkflowStep {
                 sub_workflow.context = Some(parent_context);
             }
 
-            let parent_workflow_id = Some(input.metadata.workflow_id.clone());
+            // Refuse to nest past the runtime's limit, e.g. for a builder
+            // that (indirectly) builds itself
+            let parent_workflow_id = input.metadata.workflow_id;
+            let parent_run_id = input.metadata.run_id;
+            let events = runtime.event_stream();
+            let depth = events.workflow_path(&parent_run_id).len() + 1;
+            let max_depth = runtime.max_subworkflow_depth();
+            if depth > max_depth {
+                let message = format!("max sub-workflow depth exceeded ({} levels)", max_depth);
+                // Attributed like the events of a sub-workflow that ran
+                let run_id = sub_workflow.run_id.clone();
+                sampling::in_run(run_id.clone(), async {
+                    events.enter_workflow(
+                        &run_id,
+                        &sub_workflow.id,
+                        Some(&parent_workflow_id),
+                        Some(&parent_run_id),
+                    );
+                    events.workflow_failed(
+                        &sub_workflow.id,
+                        &message,
+                        serde_json::json!({
+                            "parent_workflow_id": parent_workflow_id,
+                            "depth": depth,
+                            "max_subworkflow_depth": max_depth,
+                        }),
+                    );
+                    events.leave_workflow(&run_id);
+                })
+                .await;
+                return Err(StepError::ExecutionFailed(message));
+            }
+
             let run = runtime
-                .execute_with_parent(sub_workflow, parent_workflow_id)
+                .execute_sub_workflow(sub_workflow, parent_workflow_id, &parent_run_id)
                 .await;
+            runtime.record_sub_workflow(
+                &parent_run_id,
+                SubWorkflowSummary {
+                    workflow_id: run.workflow_id.clone(),
+                    step_index: input.metadata.step_index,
+                    step_name: self.name.clone(),
+                    state: run.state.clone(),
+                    steps_run: run.steps.len(),
+                    execution_time_ms: start.elapsed().as_millis() as u64,
+                    usage: run.usage.clone(),
+                    failure: run.failure.clone(),
+                    sub_workflows: run.sub_workflows.clone(),
+                },
+            );
 
+            // A spent budget stops the parent too, whichever run it belongs to
+            if let Some(StepError::BudgetExceeded(exceeded)) =
+                run.failure.map(|failure| failure.error)
+            {
+                return Err(StepError::BudgetExceeded(exceeded));
+            }
             if run.state != crate::workflow::WorkflowState::Completed {
                 return Err(StepError::ExecutionFailed(format!(
                     "Sub-workflow failed: {:?}",
@@ -62,6 +117,11 @@ impl SubWorkflowStep {
                     step_name: self.name.clone(),
                     step_type: StepType::SubWorkflow,
                     execution_time_ms: start.elapsed().as_millis() as u64,
+                    critic: None,
+                    iterations_run: None,
+                    policy: None,
+                    item_errors: None,
+                    handoffs: None,
                 },
             })
         })
@@ -99,4 +159,8 @@ impl Step for SubWorkflowStep {
     fn get_sub_workflow(&self) -> Option<Workflow> {
         Some((self.workflow_builder)())
     }
+
+    fn clone_step(&self) -> Option<Box<dyn Step>> {
+        Some(Box::new(self.clone()))
+    }
 }
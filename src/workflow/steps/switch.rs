@@ -0,0 +1,157 @@
+use crate::workflow::step::{ExecutionContext, Step, StepError, StepInput, StepResult, StepType};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// A step that routes its input to one of several named cases
+///
+/// The selector maps the input to a case label and the case with that label
+/// runs on the input. A label with no case runs the default step if there is
+/// one, and fails with `StepError::ExecutionFailed` listing the known cases
+/// otherwise. A `WorkflowStep` `Progress` event records the case taken.
+///
+/// ```rust,ignore
+/// SwitchStep::new("route".to_string(), |data| data["category"].as_str().unwrap_or("").to_string())
+///     .case("billing", billing_step)
+///     .case("technical", technical_step)
+///     .default(fallback_step)
+/// ```
+pub struct SwitchStep {
+    name: String,
+    selector_fn: Arc<dyn Fn(&serde_json::Value) -> String + Send + Sync>,
+    cases: Vec<(String, Box<dyn Step>)>,
+    default_step: Option<Box<dyn Step>>,
+}
+
+impl SwitchStep {
+    pub fn new<F>(name: String, selector_fn: F) -> Self
+    where
+        F: Fn(&serde_json::Value) -> String + Send + Sync + 'static,
+    {
+        Self {
+            name,
+            selector_fn: Arc::new(selector_fn),
+            cases: Vec::new(),
+            default_step: None,
+        }
+    }
+
+    /// Run `step` when the selector returns `label` (builder-style); a
+    /// repeated label replaces the earlier case
+    pub fn case(mut self, label: impl Into<String>, step: Box<dyn Step>) -> Self {
+        let label = label.into();
+        match self
+            .cases
+            .iter_mut()
+            .find(|(existing, _)| *existing == label)
+        {
+            Some(case) => case.1 = step,
+            None => self.cases.push((label, step)),
+        }
+        self
+    }
+
+    /// Run `step` when no case matches (builder-style)
+    pub fn default(mut self, step: Box<dyn Step>) -> Self {
+        self.default_step = Some(step);
+        self
+    }
+
+    fn select(&self, label: &str) -> Result<(&dyn Step, bool), StepError> {
+        if let Some((_, step)) = self.cases.iter().find(|(case, _)| case == label) {
+            return Ok((step.as_ref(), true));
+        }
+        match &self.default_step {
+            Some(step) => Ok((step.as_ref(), false)),
+            None => {
+                let known: Vec<&str> = self.cases.iter().map(|(case, _)| case.as_str()).collect();
+                Err(StepError::ExecutionFailed(format!(
+                    "switch '{}' has no case '{}' and no default (cases: {})",
+                    self.name,
+                    label,
+                    known.join(", ")
+                )))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Step for SwitchStep {
+    async fn execute_with_context(
+        &self,
+        input: StepInput,
+        ctx: ExecutionContext<'_>,
+    ) -> StepResult {
+        let start = std::time::Instant::now();
+
+        let label = (self.selector_fn)(&input.data);
+        let (chosen_step, matched) = self.select(&label)?;
+
+        if let Some(events) = ctx.event_stream {
+            let message = if matched {
+                format!("case '{}'", label)
+            } else {
+                format!("no case '{}', taking the default", label)
+            };
+            events.step_progress(
+                &input.metadata.workflow_id,
+                input.metadata.step_index,
+                &message,
+                serde_json::json!({
+                    "step_name": &self.name,
+                    "selected": &label,
+                    "case": if matched { label.as_str() } else { "default" },
+                    "branch": chosen_step.name(),
+                }),
+            );
+        }
+
+        let mut result = chosen_step.execute_with_context(input, ctx).await?;
+
+        result.metadata.step_name = self.name.clone();
+        result.metadata.step_type = StepType::Conditional;
+        result.metadata.execution_time_ms = start.elapsed().as_millis() as u64;
+
+        Ok(result)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn step_type(&self) -> StepType {
+        StepType::Conditional
+    }
+
+    /// The cases in declaration order, then the default step labeled
+    /// `default`
+    fn get_cases(&self) -> Option<Vec<(&str, &dyn Step)>> {
+        let mut cases: Vec<(&str, &dyn Step)> = self
+            .cases
+            .iter()
+            .map(|(label, step)| (label.as_str(), step.as_ref()))
+            .collect();
+        if let Some(step) = &self.default_step {
+            cases.push(("default", step.as_ref()));
+        }
+        Some(cases)
+    }
+
+    fn clone_step(&self) -> Option<Box<dyn Step>> {
+        let cases = self
+            .cases
+            .iter()
+            .map(|(label, step)| Some((label.clone(), step.clone_step()?)))
+            .collect::<Option<_>>()?;
+        let default_step = match &self.default_step {
+            Some(step) => Some(step.clone_step()?),
+            None => None,
+        };
+        Some(Box::new(Self {
+            name: self.name.clone(),
+            selector_fn: self.selector_fn.clone(),
+            cases,
+            default_step,
+        }))
+    }
+}
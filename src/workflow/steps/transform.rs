@@ -1,28 +1,91 @@
+use crate::template::Template;
 use crate::workflow::step::{
-    ExecutionContext, Step, StepInput, StepOutput, StepOutputMetadata, StepResult, StepType,
+    ExecutionContext, Step, StepError, StepInput, StepOutput, StepOutputMetadata, StepResult,
+    StepType,
 };
 use async_trait::async_trait;
+use futures::future::BoxFuture;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
 
-/// A step that transforms data using a pure function
-pub struct TransformStep {
+type Memory = HashMap<String, Value>;
+
+type TransformFn = Arc<dyn Fn(Value, &Memory) -> Result<Value, StepError> + Send + Sync>;
+
+type AsyncTransformFn =
+    Arc<dyn Fn(Value) -> BoxFuture<'static, Result<Value, StepError>> + Send + Sync>;
+
+#[derive(Clone)]
+enum Transform {
+    Sync(TransformFn),
+    Async(AsyncTransformFn),
+}
+
+/// A step that transforms data with a function that may fail or await
+///
+/// An `Err` fails the step, so a transform can reject malformed input
+/// instead of passing it on. Rendered as a Transform node, like
+/// [`TransformStep`].
+#[derive(Clone)]
+pub struct TryTransformStep {
     name: String,
-    transform_fn: Box<dyn Fn(serde_json::Value) -> serde_json::Value + Send + Sync>,
+    transform: Transform,
+    input_schema: Option<Value>,
+    output_schema: Option<Value>,
 }
 
-impl TransformStep {
+impl TryTransformStep {
+    /// ```rust,ignore
+    /// TryTransformStep::new("lookup".to_string(), move |data| {
+    ///     let db = db.clone();
+    ///     Box::pin(async move {
+    ///         let id = data["id"].as_str().ok_or_else(|| StepError::InvalidInput("no id".into()))?;
+    ///         db.fetch(id).await.map_err(|e| StepError::ExecutionFailed(e.to_string()))
+    ///     })
+    /// })
+    /// ```
     pub fn new<F>(name: String, transform_fn: F) -> Self
     where
-        F: Fn(serde_json::Value) -> serde_json::Value + Send + Sync + 'static,
+        F: Fn(Value) -> BoxFuture<'static, Result<Value, StepError>> + Send + Sync + 'static,
     {
         Self {
             name,
-            transform_fn: Box::new(transform_fn),
+            transform: Transform::Async(Arc::new(transform_fn)),
+            input_schema: None,
+            output_schema: None,
+        }
+    }
+
+    /// Require the step's input to match `schema` (see
+    /// [`crate::workflow::contract`])
+    pub fn with_input_schema(mut self, schema: Value) -> Self {
+        self.input_schema = Some(schema);
+        self
+    }
+
+    /// Require the step's output to match `schema` before the next step
+    /// runs
+    pub fn with_output_schema(mut self, schema: Value) -> Self {
+        self.output_schema = Some(schema);
+        self
+    }
+
+    fn sync<F>(name: String, transform_fn: F) -> Self
+    where
+        F: Fn(Value, &Memory) -> Result<Value, StepError> + Send + Sync + 'static,
+    {
+        Self {
+            name,
+            transform: Transform::Sync(Arc::new(transform_fn)),
+            input_schema: None,
+            output_schema: None,
         }
     }
 }
 
 #[async_trait]
-impl Step for TransformStep {
+impl Step for TryTransformStep {
     async fn execute_with_context(
         &self,
         input: StepInput,
@@ -35,7 +98,13 @@ impl Step for TransformStep {
     async fn execute(&self, input: StepInput) -> StepResult {
         let start = std::time::Instant::now();
 
-        let output_data = (self.transform_fn)(input.data);
+        let output_data = match &self.transform {
+            Transform::Sync(transform_fn) => match &input.workflow_context {
+                Some(context) => transform_fn(input.data, &context.read().unwrap().memory)?,
+                None => transform_fn(input.data, &Memory::new())?,
+            },
+            Transform::Async(transform_fn) => transform_fn(input.data).await?,
+        };
 
         Ok(StepOutput {
             data: output_data,
@@ -43,6 +112,11 @@ impl Step for TransformStep {
                 step_name: self.name.clone(),
                 step_type: StepType::Transform,
                 execution_time_ms: start.elapsed().as_millis() as u64,
+                critic: None,
+                iterations_run: None,
+                policy: None,
+                item_errors: None,
+                handoffs: None,
             },
         })
     }
@@ -54,4 +128,243 @@ impl Step for TransformStep {
     fn step_type(&self) -> StepType {
         StepType::Transform
     }
+
+    fn input_schema(&self) -> Option<&Value> {
+        self.input_schema.as_ref()
+    }
+
+    fn output_schema(&self) -> Option<&Value> {
+        self.output_schema.as_ref()
+    }
+
+    fn clone_step(&self) -> Option<Box<dyn Step>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+/// A step that transforms data using a pure function
+///
+/// Besides closures, it can be declared without code, e.g. for workflows
+/// built from config: [`pick`](Self::pick), [`merge`](Self::merge) and
+/// [`rename_keys`](Self::rename_keys). For transforms that fail or await,
+/// use [`TryTransformStep`].
+#[derive(Clone)]
+pub struct TransformStep {
+    inner: TryTransformStep,
+    /// Pointers into the input of [`pick`](Self::pick) and
+    /// [`rename_keys`](Self::rename_keys)
+    pointers: Vec<String>,
+}
+
+impl TransformStep {
+    pub fn new<F>(name: String, transform_fn: F) -> Self
+    where
+        F: Fn(Value) -> Value + Send + Sync + 'static,
+    {
+        Self::sync(name, move |data, _| Ok(transform_fn(data)))
+    }
+
+    /// A transform that also reads the workflow's memory (see
+    /// [`WorkflowContext::memory`](crate::context::WorkflowContext::memory)),
+    /// which is empty when the workflow has no context
+    pub fn new_with_memory<F>(name: String, transform_fn: F) -> Self
+    where
+        F: Fn(Value, &Memory) -> Value + Send + Sync + 'static,
+    {
+        Self::sync(name, move |data, memory| Ok(transform_fn(data, memory)))
+    }
+
+    /// A transform declared as a template: renders with the step's input
+    /// bound to `input` and outputs the text. Sandbox violations fail the
+    /// step instead of running unbounded.
+    pub fn from_template(name: String, template: Template) -> Self {
+        Self::sync(name, move |data, _| {
+            template
+                .render(&serde_json::json!({ "input": data }))
+                .map(Value::String)
+                .map_err(|e| StepError::ExecutionFailed(e.to_string()))
+        })
+    }
+
+    /// Output the value at `pointer` (RFC 6901, e.g. `/user/address`); fails
+    /// with `StepError::InvalidInput` if there is none
+    pub fn pick(name: String, pointer: impl Into<String>) -> Self {
+        let pointer = pointer.into();
+        let read = pointer.clone();
+        Self::sync(name, move |data, _| {
+            data.pointer(&read)
+                .cloned()
+                .ok_or_else(|| StepError::InvalidInput(format!("no value at '{}'", read)))
+        })
+        .reading(vec![pointer])
+    }
+
+    /// Merge `patch` into the input object: nested objects merge key by key,
+    /// and any other value in `patch` replaces the input's
+    pub fn merge(name: String, patch: Value) -> Self {
+        Self::sync(name, move |mut data, _| {
+            if !data.is_object() {
+                return Err(StepError::InvalidInput(format!(
+                    "merge needs an object at '', got {}",
+                    kind(&data)
+                )));
+            }
+            merge_into(&mut data, &patch);
+            Ok(data)
+        })
+    }
+
+    /// Rename keys of the input object, `from` to `to`
+    ///
+    /// A `from` starting with `/` is a JSON pointer to a key of a nested
+    /// object, renamed within that object; `to` is always a bare key. Fails
+    /// with `StepError::InvalidInput` if a key is missing or its new name is
+    /// taken.
+    pub fn rename_keys<K, V>(name: String, renames: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let renames: Vec<(String, String)> = renames
+            .into_iter()
+            .map(|(from, to)| (from.into(), to.into()))
+            .collect();
+        let pointers = renames
+            .iter()
+            .map(|(from, _)| match from.starts_with('/') {
+                true => from.clone(),
+                false => format!("/{}", from.replace('~', "~0").replace('/', "~1")),
+            })
+            .collect();
+        Self::sync(name, move |mut data, _| {
+            for (from, to) in &renames {
+                rename_key(&mut data, from, to)?;
+            }
+            Ok(data)
+        })
+        .reading(pointers)
+    }
+
+    /// Require the step's input to match `schema` (see
+    /// [`crate::workflow::contract`])
+    pub fn with_input_schema(mut self, schema: Value) -> Self {
+        self.inner = self.inner.with_input_schema(schema);
+        self
+    }
+
+    /// Require the step's output to match `schema` before the next step
+    /// runs
+    pub fn with_output_schema(mut self, schema: Value) -> Self {
+        self.inner = self.inner.with_output_schema(schema);
+        self
+    }
+
+    fn sync<F>(name: String, transform_fn: F) -> Self
+    where
+        F: Fn(Value, &Memory) -> Result<Value, StepError> + Send + Sync + 'static,
+    {
+        Self {
+            inner: TryTransformStep::sync(name, transform_fn),
+            pointers: Vec::new(),
+        }
+    }
+
+    fn reading(mut self, pointers: Vec<String>) -> Self {
+        self.pointers = pointers;
+        self
+    }
+}
+
+#[async_trait]
+impl Step for TransformStep {
+    async fn execute_with_context(
+        &self,
+        input: StepInput,
+        ctx: ExecutionContext<'_>,
+    ) -> StepResult {
+        self.inner.execute_with_context(input, ctx).await
+    }
+
+    async fn execute(&self, input: StepInput) -> StepResult {
+        self.inner.execute(input).await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn step_type(&self) -> StepType {
+        self.inner.step_type()
+    }
+
+    fn input_schema(&self) -> Option<&Value> {
+        self.inner.input_schema()
+    }
+
+    fn output_schema(&self) -> Option<&Value> {
+        self.inner.output_schema()
+    }
+
+    fn input_pointers(&self) -> Vec<&str> {
+        self.pointers.iter().map(String::as_str).collect()
+    }
+
+    fn clone_step(&self) -> Option<Box<dyn Step>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+fn merge_into(target: &mut Value, patch: &Value) {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, value) in patch {
+                match target.get_mut(key) {
+                    Some(existing) if existing.is_object() && value.is_object() => {
+                        merge_into(existing, value)
+                    }
+                    _ => {
+                        target.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (target, patch) => *target = patch.clone(),
+    }
+}
+
+fn rename_key(data: &mut Value, from: &str, to: &str) -> Result<(), StepError> {
+    let (parent, key) = match from.strip_prefix('/') {
+        Some(_) => {
+            let split = from.rfind('/').unwrap_or(0);
+            let key = from[split + 1..].replace("~1", "/").replace("~0", "~");
+            (&from[..split], key)
+        }
+        None => ("", from.to_string()),
+    };
+    let object = data
+        .pointer_mut(parent)
+        .and_then(Value::as_object_mut)
+        .ok_or_else(|| StepError::InvalidInput(format!("no object holding '{}'", from)))?;
+    if object.contains_key(to) {
+        return Err(StepError::InvalidInput(format!(
+            "can't rename '{}' to '{}': the key exists",
+            from, to
+        )));
+    }
+    let value = object
+        .remove(&key)
+        .ok_or_else(|| StepError::InvalidInput(format!("no key at '{}'", from)))?;
+    object.insert(to.to_string(), value);
+    Ok(())
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
 }
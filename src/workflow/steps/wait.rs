@@ -0,0 +1,284 @@
+use crate::workflow::step::{
+    ExecutionContext, Step, StepError, StepInput, StepOutput, StepOutputMetadata, StepResult,
+    StepType,
+};
+use crate::workflow::steps::LoopExhaustedMode;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// How often a wait reports the time left
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+enum WaitMode {
+    Delay(Duration),
+    Until(DateTime<Utc>),
+    Poll {
+        inner: Box<dyn Step>,
+        interval: Duration,
+        max_attempts: usize,
+        done_when: Arc<dyn Fn(&Value) -> bool + Send + Sync>,
+    },
+}
+
+/// A step that waits: for a while, until a time, or until a polled step
+/// reports it's done
+///
+/// Delays pass their input on unchanged. A poll runs its inner step on the
+/// same input every `interval` until `done_when` returns `true` for an
+/// output, which becomes the step's output, with `metadata.iterations_run`
+/// set to the attempts made. Running out of attempts fails the step, or
+/// passes the last output on with `LoopExhaustedMode::PassThrough`.
+///
+/// While waiting, a `WorkflowStep` `Progress` event with the time left (and
+/// the attempt, when polling) is emitted at least every 10 seconds, and the
+/// run's heartbeat counts it as progress. Canceling the run ends the wait
+/// with `StepError::Canceled`. Waits use `tokio::time`, so tests can
+/// fast-forward them with `tokio::time::pause()`.
+pub struct WaitStep {
+    name: String,
+    mode: WaitMode,
+    exhausted_mode: LoopExhaustedMode,
+}
+
+impl WaitStep {
+    /// Wait for `duration`
+    pub fn delay(name: String, duration: Duration) -> Self {
+        Self::with_mode(name, WaitMode::Delay(duration))
+    }
+
+    /// Wait until `time`; a time already past doesn't wait
+    pub fn until(name: String, time: DateTime<Utc>) -> Self {
+        Self::with_mode(name, WaitMode::Until(time))
+    }
+
+    /// Run `inner` every `interval` until `done_when` holds for its output,
+    /// at most `max_attempts` times (below 1 is treated as 1)
+    pub fn poll<F>(
+        name: String,
+        inner: Box<dyn Step>,
+        interval: Duration,
+        max_attempts: usize,
+        done_when: F,
+    ) -> Self
+    where
+        F: Fn(&Value) -> bool + Send + Sync + 'static,
+    {
+        Self::with_mode(
+            name,
+            WaitMode::Poll {
+                inner,
+                interval,
+                max_attempts: max_attempts.max(1),
+                done_when: Arc::new(done_when),
+            },
+        )
+    }
+
+    fn with_mode(name: String, mode: WaitMode) -> Self {
+        Self {
+            name,
+            mode,
+            exhausted_mode: LoopExhaustedMode::default(),
+        }
+    }
+
+    /// Set what a poll does when its attempts run out (builder-style)
+    pub fn with_exhausted_mode(mut self, mode: LoopExhaustedMode) -> Self {
+        self.exhausted_mode = mode;
+        self
+    }
+
+    /// Sleep for `duration`, reporting the time left
+    async fn wait(
+        &self,
+        duration: Duration,
+        attempt: Option<(usize, usize)>,
+        input: &StepInput,
+        ctx: ExecutionContext<'_>,
+    ) -> Result<(), StepError> {
+        let deadline = Instant::now() + duration;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(());
+            }
+            self.report(remaining, attempt, input, ctx);
+
+            let tick = tokio::time::sleep(remaining.min(PROGRESS_INTERVAL));
+            match ctx.cancellation {
+                Some(token) => tokio::select! {
+                    _ = tick => {}
+                    _ = token.cancelled() => {
+                        return Err(StepError::Canceled(format!(
+                            "wait '{}' canceled with {}s left",
+                            self.name,
+                            remaining.as_secs()
+                        )));
+                    }
+                },
+                None => tick.await,
+            }
+        }
+    }
+
+    fn report(
+        &self,
+        remaining: Duration,
+        attempt: Option<(usize, usize)>,
+        input: &StepInput,
+        ctx: ExecutionContext<'_>,
+    ) {
+        if let Some(activity) = ctx.activity {
+            activity.record_progress();
+        }
+        let Some(events) = ctx.event_stream else {
+            return;
+        };
+        let mut message = format!("waiting {}s more", remaining.as_secs_f64().ceil());
+        let mut data = serde_json::json!({
+            "step_name": &self.name,
+            "remaining_ms": remaining.as_millis() as u64,
+        });
+        if let Some((attempt, max_attempts)) = attempt {
+            message = format!(
+                "attempt {} of {} not done; {}",
+                attempt, max_attempts, message
+            );
+            data["attempt"] = attempt.into();
+            data["max_attempts"] = max_attempts.into();
+        }
+        events.step_progress(
+            &input.metadata.workflow_id,
+            input.metadata.step_index,
+            &message,
+            data,
+        );
+    }
+
+    async fn poll_inner(
+        &self,
+        inner: &dyn Step,
+        interval: Duration,
+        max_attempts: usize,
+        done_when: &(dyn Fn(&Value) -> bool + Send + Sync),
+        input: &StepInput,
+        ctx: ExecutionContext<'_>,
+    ) -> Result<(StepOutput, usize), StepError> {
+        for attempt in 1..=max_attempts {
+            if ctx.cancellation.is_some_and(|token| token.is_cancelled()) {
+                return Err(StepError::Canceled(format!(
+                    "wait '{}' canceled before attempt {}",
+                    self.name, attempt
+                )));
+            }
+            let output = inner.execute_with_context(input.clone(), ctx).await?;
+            if done_when(&output.data) {
+                return Ok((output, attempt));
+            }
+            if attempt == max_attempts {
+                return match self.exhausted_mode {
+                    LoopExhaustedMode::Fail => Err(StepError::ExecutionFailed(format!(
+                        "wait '{}' polled '{}' {} times without it being done",
+                        self.name,
+                        inner.name(),
+                        max_attempts
+                    ))),
+                    LoopExhaustedMode::PassThrough => Ok((output, attempt)),
+                };
+            }
+            self.wait(interval, Some((attempt, max_attempts)), input, ctx)
+                .await?;
+        }
+
+        unreachable!("the final attempt always returns")
+    }
+}
+
+#[async_trait]
+impl Step for WaitStep {
+    async fn execute_with_context(
+        &self,
+        input: StepInput,
+        ctx: ExecutionContext<'_>,
+    ) -> StepResult {
+        let start = std::time::Instant::now();
+        let (data, attempts) = match &self.mode {
+            WaitMode::Delay(duration) => {
+                self.wait(*duration, None, &input, ctx).await?;
+                (input.data, None)
+            }
+            WaitMode::Until(time) => {
+                let duration = (*time - Utc::now()).to_std().unwrap_or_default();
+                self.wait(duration, None, &input, ctx).await?;
+                (input.data, None)
+            }
+            WaitMode::Poll {
+                inner,
+                interval,
+                max_attempts,
+                done_when,
+            } => {
+                let (output, attempts) = self
+                    .poll_inner(
+                        inner.as_ref(),
+                        *interval,
+                        *max_attempts,
+                        done_when.as_ref(),
+                        &input,
+                        ctx,
+                    )
+                    .await?;
+                (output.data, Some(attempts))
+            }
+        };
+
+        Ok(StepOutput {
+            data,
+            metadata: StepOutputMetadata {
+                step_name: self.name.clone(),
+                step_type: StepType::Wait,
+                execution_time_ms: start.elapsed().as_millis() as u64,
+                critic: None,
+                iterations_run: attempts,
+                policy: None,
+                item_errors: None,
+                handoffs: None,
+            },
+        })
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn step_type(&self) -> StepType {
+        StepType::Wait
+    }
+
+    fn clone_step(&self) -> Option<Box<dyn Step>> {
+        let mode = match &self.mode {
+            WaitMode::Delay(duration) => WaitMode::Delay(*duration),
+            WaitMode::Until(time) => WaitMode::Until(*time),
+            WaitMode::Poll {
+                inner,
+                interval,
+                max_attempts,
+                done_when,
+            } => WaitMode::Poll {
+                inner: inner.clone_step()?,
+                interval: *interval,
+                max_attempts: *max_attempts,
+                done_when: done_when.clone(),
+            },
+        };
+        Some(Box::new(Self {
+            name: self.name.clone(),
+            mode,
+            exhausted_mode: self.exhausted_mode,
+        }))
+    }
+}
@@ -1,5 +1,6 @@
-use crate::{Agent, AgentConfig, AgentStep, Runtime, Workflow, WorkflowState};
+use crate::{Agent, AgentConfig, AgentStep, Runtime, Workflow, WorkflowErrorCode, WorkflowState};
 use serde_json::json;
+use std::sync::Arc;
 
 #[test]
 fn test_workflow_builder() {
@@ -64,6 +65,171 @@ fn test_workflow_mermaid_generation() {
     assert!(mermaid.contains("test_step"));
 }
 
+#[test]
+fn test_parallel_mermaid_forks_and_joins() {
+    use crate::{ParallelStep, TransformStep};
+
+    let branch = |name: &str| -> Box<dyn crate::workflow::Step> {
+        Box::new(TransformStep::new(name.to_string(), |data| data))
+    };
+    let workflow = Workflow::builder()
+        .step(Box::new(ParallelStep::new(
+            "analyze".to_string(),
+            vec![branch("tone"), branch("facts")],
+        )))
+        .step(branch("merge"))
+        .initial_input(json!({}))
+        .build();
+
+    let mermaid = workflow.to_mermaid();
+    assert!(mermaid.contains("    N0[/\"analyze\"\\]:::parallelStyle\n"));
+    assert!(mermaid.contains("    N0 --> N1\n"));
+    assert!(mermaid.contains("    N0 --> N2\n"));
+    assert!(mermaid.contains("    N3[\\\"join\"/]:::parallelStyle\n"));
+    assert!(mermaid.contains("    N1 --> N3\n"));
+    assert!(mermaid.contains("    N2 --> N3\n"));
+    assert!(mermaid.contains("    N3 --> N4\n"));
+    assert!(mermaid.contains("    N4[/\"merge\"/]:::transformStyle\n"));
+    assert!(mermaid.contains("classDef parallelStyle"));
+}
+
+#[test]
+fn test_fallible_transforms_render_as_transform_nodes() {
+    use crate::{TransformStep, TryTransformStep};
+
+    let workflow = Workflow::builder()
+        .step(Box::new(TryTransformStep::new(
+            "validate".to_string(),
+            |data| Box::pin(async move { Ok(data) }),
+        )))
+        .step(Box::new(TransformStep::pick("user".to_string(), "/user")))
+        .step(Box::new(TransformStep::rename_keys(
+            "rename".to_string(),
+            [("id", "user_id")],
+        )))
+        .initial_input(json!({}))
+        .build();
+
+    let mermaid = workflow.to_mermaid();
+    assert!(mermaid.contains("    N0[/\"validate\"/]:::transformStyle\n"));
+    assert!(mermaid.contains("    N1[/\"user\"/]:::transformStyle\n"));
+    assert!(mermaid.contains("    N2[/\"rename\"/]:::transformStyle\n"));
+}
+
+#[test]
+fn test_loop_mermaid_renders_loop_back_edge() {
+    use crate::{LoopStep, TransformStep};
+
+    let workflow = Workflow::builder()
+        .step(Box::new(LoopStep::new(
+            "refine".to_string(),
+            Box::new(TransformStep::new("revise".to_string(), |data| data)),
+            |_| true,
+            4,
+        )))
+        .step(Box::new(TransformStep::new(
+            "publish".to_string(),
+            |data| data,
+        )))
+        .initial_input(json!({}))
+        .build();
+
+    let mermaid = workflow.to_mermaid();
+    assert!(mermaid.contains("    Start --> N0\n"));
+    assert!(mermaid.contains("    N0[/\"revise\"/]:::transformStyle\n"));
+    assert!(mermaid.contains("    N1{{\"refine\"}}:::loopStyle\n"));
+    assert!(mermaid.contains("    N0 --> N1\n"));
+    assert!(mermaid.contains("    N1 -.->|\"↻ repeat, max 4\"| N0\n"));
+    assert!(mermaid.contains("    N1 --> N2\n"));
+    assert!(mermaid.contains("    N2[/\"publish\"/]:::transformStyle\n"));
+    assert!(mermaid.contains("classDef loopStyle"));
+}
+
+#[test]
+fn test_switch_mermaid_renders_one_edge_per_case() {
+    use crate::{SwitchStep, TransformStep};
+
+    let step = |name: &str| Box::new(TransformStep::new(name.to_string(), |data| data));
+    let workflow = Workflow::builder()
+        .step(step("classify"))
+        .step(Box::new(
+            SwitchStep::new("route".to_string(), |data| data.to_string())
+                .case("billing", step("refund"))
+                .case("technical", step("debug"))
+                .case("other", step("triage"))
+                .default(step("escalate")),
+        ))
+        .step(step("reply"))
+        .initial_input(json!({}))
+        .build();
+
+    let mermaid = workflow.to_mermaid();
+    let (chart, _styles) = mermaid.split_once("\n\n").unwrap();
+    assert_eq!(
+        chart,
+        r#"flowchart TD
+    Start([Start])
+    Start --> N0
+    N0[/"classify"/]:::transformStyle
+    N0 --> N1
+    N1{{"route"}}:::conditionalStyle
+    N2[/"refund"/]:::transformStyle
+    N1 -->|"billing"| N2
+    N3[/"debug"/]:::transformStyle
+    N1 -->|"technical"| N3
+    N4[/"triage"/]:::transformStyle
+    N1 -->|"other"| N4
+    N5[/"escalate"/]:::transformStyle
+    N1 -->|"default"| N5
+    N6(( )):::convergeStyle
+    N2 --> N6
+    N3 --> N6
+    N4 --> N6
+    N5 --> N6
+    N6 --> N7
+    N7[/"reply"/]:::transformStyle
+    N7 --> End
+    End([End])"#
+    );
+}
+
+#[test]
+fn test_for_each_mermaid_is_one_annotated_node() {
+    use crate::{ForEachStep, TransformStep};
+
+    let workflow = Workflow::builder()
+        .step(Box::new(ForEachStep::new(
+            "summarize_all".to_string(),
+            Box::new(TransformStep::new("summarize".to_string(), |data| data)),
+            3,
+        )))
+        .initial_input(json!([]))
+        .build();
+
+    let mermaid = workflow.to_mermaid();
+    assert!(mermaid
+        .contains("    N0[\"summarize_all ×N<br/>summarize, 3 at a time\"]:::forEachStyle\n"));
+    assert!(mermaid.contains("    N0 --> End\n"));
+    assert!(mermaid.contains("classDef forEachStyle"));
+}
+
+#[test]
+fn test_wait_mermaid_is_a_clock_node() {
+    use crate::WaitStep;
+
+    let workflow = Workflow::builder()
+        .step(Box::new(WaitStep::delay(
+            "cool_down".to_string(),
+            std::time::Duration::from_secs(30),
+        )))
+        .initial_input(json!({}))
+        .build();
+
+    let mermaid = workflow.to_mermaid();
+    assert!(mermaid.contains("    N0((\"⏱ cool_down\")):::waitStyle\n"));
+    assert!(mermaid.contains("classDef waitStyle"));
+}
+
 #[tokio::test]
 async fn test_workflow_execution() {
     let agent = Agent::new(
@@ -98,3 +264,131 @@ fn test_workflow_state() {
     let state = WorkflowState::Failed;
     assert_eq!(state, WorkflowState::Failed);
 }
+
+#[tokio::test]
+async fn test_template_transform_is_sandboxed() {
+    use crate::{Template, TransformStep};
+
+    let summary = |input| {
+        let template = Template::new("summary", "{{input.title}} by {{input.author}}")
+            .unwrap()
+            .allow_roots(["input"]);
+        Workflow::builder()
+            .add_step(Box::new(TransformStep::from_template(
+                "summary".to_string(),
+                template,
+            )))
+            .initial_input(input)
+            .build()
+    };
+    let runtime = Runtime::new();
+
+    let run = runtime
+        .execute(summary(json!({"title": "Otters", "author": "Ada"})))
+        .await;
+    assert_eq!(run.final_output, Some(json!("Otters by Ada")));
+
+    let run = runtime.execute(summary(json!({"title": "Otters"}))).await;
+    assert!(matches!(run.state, WorkflowState::Failed));
+}
+
+fn passthrough(name: &str) -> Box<dyn crate::Step> {
+    Box::new(crate::TransformStep::new(name.to_string(), |data| data))
+}
+
+fn rejection(builder: crate::workflow::WorkflowBuilder) -> crate::WorkflowError {
+    match builder.try_build() {
+        Ok(_) => panic!("expected the builder to be rejected"),
+        Err(e) => e,
+    }
+}
+
+#[test]
+fn test_try_build_accepts_valid_workflow() {
+    let workflow = Workflow::builder()
+        .step(passthrough("clean"))
+        .step(passthrough("format"))
+        .with_chat_history(Arc::new(crate::SlidingWindowManager::new(10)))
+        .with_max_context_tokens(8_000)
+        .with_input_output_ratio(3.0)
+        .try_build()
+        .unwrap();
+    assert_eq!(workflow.steps.len(), 2);
+
+    // Empty is fine when asked for
+    assert!(Workflow::builder().allow_empty().try_build().is_ok());
+}
+
+#[test]
+fn test_try_build_rejects_empty_workflow() {
+    let error = rejection(Workflow::builder().name("empty".to_string()));
+    assert_eq!(error.code, WorkflowErrorCode::EmptyWorkflow);
+    assert_eq!(error.step_index, None);
+}
+
+#[test]
+fn test_try_build_rejects_duplicate_step_names() {
+    let error = rejection(
+        Workflow::builder()
+            .step(passthrough("fetch"))
+            .step(passthrough("parse"))
+            .step(passthrough("fetch")),
+    );
+    assert_eq!(error.code, WorkflowErrorCode::DuplicateStepName);
+    assert_eq!(error.step_index, Some(2));
+    assert_eq!(error.step_id.as_deref(), Some("fetch"));
+    assert_eq!(
+        error.to_string(),
+        "[DuplicateStepName] step name 'fetch' is already used by step 0 (step 2: fetch)"
+    );
+}
+
+#[test]
+fn test_try_build_rejects_budget_without_chat_history() {
+    let error = rejection(
+        Workflow::builder()
+            .step(passthrough("only"))
+            .with_max_context_tokens(8_000)
+            .with_input_output_ratio(3.0),
+    );
+    assert_eq!(error.code, WorkflowErrorCode::MissingContextManager);
+    assert_eq!(
+        error.message,
+        "with_max_context_tokens and with_input_output_ratio have no effect without with_chat_history"
+    );
+}
+
+#[test]
+fn test_try_build_rejects_unusable_budgets() {
+    let history = || {
+        Workflow::builder()
+            .step(passthrough("only"))
+            .with_chat_history(Arc::new(crate::SlidingWindowManager::new(10)))
+    };
+
+    let zero = rejection(
+        history()
+            .with_max_context_tokens(0)
+            .with_input_output_ratio(3.0),
+    );
+    assert_eq!(zero.code, WorkflowErrorCode::InvalidContextBudget);
+    assert!(zero.message.contains("max_context_tokens"));
+
+    for ratio in [0.0, -1.0, f64::NAN] {
+        let error = rejection(
+            history()
+                .with_max_context_tokens(8_000)
+                .with_input_output_ratio(ratio),
+        );
+        assert_eq!(error.code, WorkflowErrorCode::InvalidContextBudget);
+        assert!(error.message.contains("input_output_ratio"));
+    }
+
+    // Half a budget is silently ignored by build()
+    let half = rejection(history().with_max_context_tokens(8_000));
+    assert_eq!(half.code, WorkflowErrorCode::InvalidContextBudget);
+    assert_eq!(
+        half.message,
+        "with_max_context_tokens is ignored without with_input_output_ratio"
+    );
+}
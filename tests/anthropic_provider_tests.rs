@@ -0,0 +1,132 @@
+/// Tests for the Anthropic provider against a scripted local HTTP server
+use agent_runtime::llm::{ChatMessage, ChatRequest, ClaudeClient, GenericChatClient};
+use agent_runtime::{Agent, AgentConfig, AgentInput, NativeTool, ToolRegistry, ToolResult};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+mod common;
+use common::{serve, Reply};
+
+fn sse(events: &[Value]) -> Reply {
+    let body: String = events
+        .iter()
+        .map(|e| format!("event: {}\ndata: {}\n\n", e["type"].as_str().unwrap(), e))
+        .collect();
+    Reply::text("text/event-stream", body)
+}
+
+#[tokio::test]
+async fn test_agent_tool_loop_over_streaming_messages_api() {
+    let (base_url, recorded) = serve(vec![
+        sse(&[
+            json!({"type": "message_start", "message": {"model": "claude-test", "usage": {"input_tokens": 30, "output_tokens": 1}}}),
+            json!({"type": "content_block_start", "index": 0, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "calculator", "input": {}}}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "input_json_delta", "partial_json": "{\"a\": 5, "}}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "input_json_delta", "partial_json": "\"b\": 3}"}}),
+            json!({"type": "content_block_stop", "index": 0}),
+            json!({"type": "message_delta", "delta": {"stop_reason": "tool_use"}, "usage": {"output_tokens": 12}}),
+            json!({"type": "message_stop"}),
+        ]),
+        sse(&[
+            json!({"type": "message_start", "message": {"model": "claude-test", "usage": {"input_tokens": 50, "output_tokens": 1}}}),
+            json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "5 + 3 "}}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "is 8"}}),
+            json!({"type": "message_delta", "delta": {"stop_reason": "end_turn"}, "usage": {"output_tokens": 6}}),
+            json!({"type": "message_stop"}),
+        ]),
+    ])
+    .await;
+
+    let mut registry = ToolRegistry::new();
+    registry.register(NativeTool::new(
+        "calculator",
+        "Adds two numbers",
+        json!({
+            "type": "object",
+            "properties": { "a": {"type": "number"}, "b": {"type": "number"} }
+        }),
+        |args| {
+            Box::pin(async move {
+                let sum = args["a"].as_f64().unwrap() + args["b"].as_f64().unwrap();
+                Ok(ToolResult::success(json!({"result": sum}), 0.0))
+            })
+        },
+    ));
+    let config = AgentConfig::builder("calc")
+        .system_prompt("You are a calculator")
+        .tools(Arc::new(registry))
+        .build();
+    let client = ClaudeClient::with_model("test-key", "claude-test")
+        .with_base_url(format!("{}/v1", base_url));
+    let agent = Agent::new(config).with_client(Arc::new(client));
+
+    let output = agent
+        .execute(&AgentInput::from_text("What is 5 + 3?"))
+        .await
+        .unwrap();
+    assert_eq!(output.data["response"], "5 + 3 is 8");
+
+    let recorded = recorded.lock().unwrap();
+    assert_eq!(recorded.len(), 2);
+    assert!(recorded[0].head.starts_with("post /v1/messages "));
+    assert!(recorded[0].head.contains("x-api-key: test-key"));
+    assert!(recorded[0].head.contains("anthropic-version: 2023-06-01"));
+
+    let first = &recorded[0].body;
+    assert_eq!(first["system"], "You are a calculator");
+    assert_eq!(first["stream"], true);
+    assert!(first["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|m| m["role"] != "system"));
+    assert_eq!(first["tools"][0]["name"], "calculator");
+    assert_eq!(first["tools"][0]["input_schema"]["type"], "object");
+
+    // The follow-up replays the tool_use and answers it with a tool_result
+    let messages = recorded[1].body["messages"].as_array().unwrap();
+    let assistant = &messages[messages.len() - 2];
+    assert_eq!(assistant["role"], "assistant");
+    assert_eq!(
+        assistant["content"][0],
+        json!({"type": "tool_use", "id": "toolu_1", "name": "calculator", "input": {"a": 5, "b": 3}})
+    );
+    let result = &messages[messages.len() - 1];
+    assert_eq!(result["role"], "user");
+    assert_eq!(result["content"][0]["type"], "tool_result");
+    assert_eq!(result["content"][0]["tool_use_id"], "toolu_1");
+}
+
+#[tokio::test]
+async fn test_chat_without_streaming() {
+    let body = json!({
+        "id": "msg_1",
+        "type": "message",
+        "role": "assistant",
+        "model": "claude-test",
+        "content": [{ "type": "text", "text": "Hello there" }],
+        "stop_reason": "end_turn",
+        "usage": { "input_tokens": 9, "output_tokens": 3 }
+    });
+    let (base_url, recorded) = serve(vec![Reply::json(body)]).await;
+
+    let client = ClaudeClient::new("test-key")
+        .with_base_url(format!("{}/v1", base_url))
+        .with_max_tokens(256);
+    let response = client
+        .chat(ChatRequest::new(vec![ChatMessage::user("Hi")]).with_temperature(0.5))
+        .await
+        .unwrap();
+
+    assert_eq!(response.content, "Hello there");
+    assert_eq!(response.finish_reason.as_deref(), Some("stop"));
+    assert!(response.tool_calls.is_none());
+    assert_eq!(response.usage.unwrap().total_tokens, 12);
+
+    let sent = &recorded.lock().unwrap()[0].body;
+    assert_eq!(sent["max_tokens"], 256);
+    assert_eq!(sent["temperature"], 0.5);
+    assert!(sent.get("stream").is_none());
+    assert!(sent.get("system").is_none());
+}
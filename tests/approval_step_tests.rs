@@ -0,0 +1,220 @@
+use agent_runtime::runtime::Runtime;
+use agent_runtime::workflow::StepError;
+use agent_runtime::*;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Drafts an email, waits for approval, then "sends" whatever was approved
+fn email_workflow(approval: ApprovalStep, sent: Arc<std::sync::Mutex<Vec<Value>>>) -> Workflow {
+    Workflow::builder()
+        .name("outreach".to_string())
+        .step(Box::new(TransformStep::new(
+            "draft".to_string(),
+            |to| json!({ "to": to, "body": "Hi there" }),
+        )))
+        .step(Box::new(approval))
+        .step(Box::new(TransformStep::new(
+            "send".to_string(),
+            move |email| {
+                sent.lock().unwrap().push(email.clone());
+                json!({ "sent": true })
+            },
+        )))
+        .initial_input(json!("ada@example.com"))
+        .build()
+}
+
+/// Wait for the runtime's first pending approval, then decide it
+fn decide(
+    runtime: Arc<Runtime>,
+    decide: impl FnOnce(&Runtime, PendingApproval) + Send + 'static,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let pending = loop {
+            if let Some(pending) = runtime.pending_approvals().pop() {
+                break pending;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        };
+        decide(&runtime, pending);
+    })
+}
+
+#[tokio::test]
+async fn test_approved_data_flows_to_the_next_step() {
+    let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let runtime = Arc::new(Runtime::new().with_approval_decider(|_| "ops-oncall".to_string()));
+    let approval = ApprovalStep::new("review_email".to_string()).with_prompt("Send this email?");
+
+    let decider = decide(runtime.clone(), |runtime, pending| {
+        assert_eq!(pending.step_name, "review_email");
+        assert_eq!(pending.step_index, 1);
+        assert_eq!(pending.prompt.as_deref(), Some("Send this email?"));
+        assert_eq!(pending.data["to"], "ada@example.com");
+        // Edit the body before approving
+        let mut edited = pending.data.clone();
+        edited["body"] = json!("Hello Ada");
+        assert!(runtime.resolve_approval(&pending.approval_id, Decision::Modify(edited)));
+        // Already decided
+        assert!(!runtime.resolve_approval(&pending.approval_id, Decision::Approve));
+    });
+    let run = runtime
+        .execute(email_workflow(approval, sent.clone()))
+        .await;
+    decider.await.unwrap();
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(
+        *sent.lock().unwrap(),
+        vec![json!({ "to": "ada@example.com", "body": "Hello Ada" })]
+    );
+    assert_eq!(run.steps[1].step_type, "Approval");
+    assert!(runtime.pending_approvals().is_empty());
+
+    assert_eq!(run.approvals.len(), 1);
+    let record = &run.approvals[0];
+    assert_eq!(record.step_name, "review_email");
+    assert_eq!(record.decided_by, "ops-oncall");
+    assert!(matches!(record.decision, Decision::Modify(_)));
+
+    // The step announced that it was waiting, with the proposed data
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let pending_event = runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .find(|e| e.status == ComponentStatus::Pending)
+        .unwrap();
+    assert_eq!(pending_event.scope, EventScope::WorkflowStep);
+    assert_eq!(pending_event.component_id, "outreach:step:1");
+    assert_eq!(pending_event.data["data"]["body"], "Hi there");
+}
+
+#[tokio::test]
+async fn test_rejection_fails_the_step() {
+    let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let runtime = Arc::new(Runtime::new());
+    let approval = ApprovalStep::new("review_email".to_string());
+
+    let decider = decide(runtime.clone(), |runtime, pending| {
+        let decision = Decision::Reject {
+            reason: "wrong recipient".to_string(),
+        };
+        assert!(runtime.resolve_approval_as(&pending.approval_id, decision, "grace"));
+    });
+    let run = runtime
+        .execute(email_workflow(approval, sent.clone()))
+        .await;
+    decider.await.unwrap();
+
+    assert_eq!(run.state, WorkflowState::Failed);
+    assert!(sent.lock().unwrap().is_empty());
+    let failure = run.failure.unwrap();
+    assert_eq!(failure.step_name, "review_email");
+    assert!(matches!(failure.error, StepError::Rejected(_)));
+    assert_eq!(
+        failure.error.to_string(),
+        "Rejected: 'review_email' rejected by grace: wrong recipient"
+    );
+    assert_eq!(run.approvals[0].decided_by, "grace");
+}
+
+#[tokio::test]
+async fn test_timeout_rejects() {
+    let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let runtime = Runtime::new();
+    let approval =
+        ApprovalStep::new("review_email".to_string()).with_timeout(Duration::from_millis(20));
+
+    let run = runtime
+        .execute(email_workflow(approval, sent.clone()))
+        .await;
+
+    assert_eq!(run.state, WorkflowState::Failed);
+    assert!(sent.lock().unwrap().is_empty());
+    assert!(matches!(
+        run.failure.unwrap().error,
+        StepError::Rejected(reason) if reason.contains("no decision within")
+    ));
+    assert_eq!(run.approvals[0].decided_by, "timeout");
+    assert!(runtime.pending_approvals().is_empty());
+}
+
+#[tokio::test]
+async fn test_cancel_withdraws_the_approval() {
+    let runtime = Arc::new(Runtime::new());
+    let approvals_seen = Arc::new(AtomicUsize::new(0));
+    let canceller = {
+        let seen = approvals_seen.clone();
+        decide(runtime.clone(), move |runtime, pending| {
+            seen.fetch_add(1, Ordering::SeqCst);
+            assert!(runtime.cancel(&pending.run_id));
+        })
+    };
+    let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let run = runtime
+        .execute(email_workflow(
+            ApprovalStep::new("review_email".to_string()),
+            sent,
+        ))
+        .await;
+    canceller.await.unwrap();
+
+    assert_eq!(approvals_seen.load(Ordering::SeqCst), 1);
+    assert_eq!(run.state, WorkflowState::Canceled);
+    assert!(runtime.pending_approvals().is_empty());
+    assert!(run.approvals.is_empty());
+}
+
+#[tokio::test]
+async fn test_concurrent_runs_keep_their_own_decisions() {
+    let runtime = Arc::new(Runtime::new());
+    let first = email_workflow(
+        ApprovalStep::new("review_email".to_string()),
+        Arc::new(std::sync::Mutex::new(Vec::new())),
+    );
+    let second = email_workflow(
+        ApprovalStep::new("review_email".to_string()),
+        Arc::new(std::sync::Mutex::new(Vec::new())),
+    );
+    let first_id = first.run_id.clone();
+
+    // Approve the first run's email and reject the second's, once both wait
+    let decider = {
+        let runtime = runtime.clone();
+        tokio::spawn(async move {
+            let pending = loop {
+                let pending = runtime.pending_approvals();
+                if pending.len() == 2 {
+                    break pending;
+                }
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            };
+            for approval in pending {
+                let decision = if approval.run_id == first_id {
+                    Decision::Approve
+                } else {
+                    Decision::Reject {
+                        reason: "not now".to_string(),
+                    }
+                };
+                assert!(runtime.resolve_approval_as(&approval.approval_id, decision, "grace"));
+            }
+        })
+    };
+    let (first, second) = tokio::join!(runtime.execute(first), runtime.execute(second));
+    decider.await.unwrap();
+
+    assert_eq!(first.workflow_id, second.workflow_id);
+    assert_eq!(first.state, WorkflowState::Completed);
+    assert_eq!(second.state, WorkflowState::Failed);
+    assert_eq!(first.approvals.len(), 1);
+    assert_eq!(first.approvals[0].decision, Decision::Approve);
+    assert_eq!(second.approvals.len(), 1);
+    assert!(matches!(
+        second.approvals[0].decision,
+        Decision::Reject { .. }
+    ));
+}
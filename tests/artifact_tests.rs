@@ -0,0 +1,227 @@
+use agent_runtime::artifact::sha256_hex;
+use agent_runtime::*;
+use serde_json::json;
+use std::sync::Arc;
+
+const CHART_BYTES: &[u8] = b"\x89PNG fake chart bytes";
+
+fn artifact_tools() -> Arc<ToolRegistry> {
+    let mut registry = ToolRegistry::new();
+
+    registry.register(NativeTool::new(
+        "render_chart",
+        "Render a chart as PNG",
+        json!({"type": "object", "properties": {}}),
+        |_params| async move {
+            Ok(
+                ToolResult::success(json!({"rendered": true}), 1.0).with_artifact(
+                    NewArtifact::new("chart.png", "image/png", CHART_BYTES.to_vec())
+                        .with_description("Quarterly sales chart"),
+                ),
+            )
+        },
+    ));
+
+    registry.register(NativeTool::with_context(
+        "upload",
+        "Upload an artifact by handle",
+        json!({"type": "object", "properties": {"handle": {"type": "string"}}}),
+        |params, ctx| async move {
+            let handle = params["handle"].as_str().unwrap_or_default().to_string();
+            match ctx.resolve_artifact(&handle) {
+                Some(artifact) => Ok(ToolResult::success(
+                    json!({
+                        "uploaded": artifact.reference.name,
+                        "sha256": sha256_hex(&artifact.data),
+                    }),
+                    1.0,
+                )),
+                None => Ok(ToolResult::error(
+                    format!("unknown artifact {}", handle),
+                    1.0,
+                )),
+            }
+        },
+    ));
+
+    Arc::new(registry)
+}
+
+fn artifact_workflow(mock: Arc<llm::MockLlmClient>) -> Workflow {
+    let config = AgentConfig::builder("reporter")
+        .system_prompt("You make reports")
+        .tools(artifact_tools())
+        .build();
+    let agent = Agent::new(config).with_client(mock);
+
+    Workflow::builder()
+        .name("artifact_run".to_string())
+        .step(Box::new(AgentStep::from_agent(
+            agent,
+            "reporter".to_string(),
+        )))
+        .initial_input(json!("Chart the sales and upload it"))
+        .build()
+}
+
+fn scripted_client() -> Arc<llm::MockLlmClient> {
+    Arc::new(
+        llm::MockLlmClient::new()
+            .with_tool_call("render_chart", json!({}))
+            .with_tool_call("upload", json!({"handle": "artifact://1"}))
+            .with_response("Uploaded the chart."),
+    )
+}
+
+#[tokio::test]
+async fn test_tool_consumes_artifact_by_handle() {
+    let mock = scripted_client();
+    let runtime = Runtime::new();
+    let run = runtime.execute(artifact_workflow(mock.clone())).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+
+    // The run lists what was produced, with size and hash
+    assert_eq!(run.artifacts.len(), 1);
+    let produced = &run.artifacts[0];
+    assert_eq!(produced.handle, "artifact://1");
+    assert_eq!(produced.size, CHART_BYTES.len() as u64);
+    assert_eq!(produced.sha256, sha256_hex(CHART_BYTES));
+
+    // The consuming tool saw the same bytes
+    let calls = mock.get_calls();
+    let upload_result = calls[2]
+        .messages
+        .iter()
+        .rfind(|m| m.role == Role::Tool)
+        .unwrap();
+    assert!(upload_result.content.text().contains(&produced.sha256));
+
+    // Step output carries the reference
+    let output = run.final_output.unwrap();
+    assert_eq!(output["artifacts"][0]["handle"], "artifact://1");
+}
+
+#[tokio::test]
+async fn test_conversation_only_contains_handle() {
+    let mock = scripted_client();
+    let runtime = Runtime::new();
+    runtime.execute(artifact_workflow(mock.clone())).await;
+
+    let calls = mock.get_calls();
+    let chart_result = calls[1]
+        .messages
+        .iter()
+        .find(|m| m.role == Role::Tool)
+        .unwrap();
+    assert!(chart_result.content.text().contains("artifact://1"));
+    assert!(chart_result
+        .content
+        .text()
+        .contains("Quarterly sales chart"));
+
+    for call in &calls {
+        for message in &call.messages {
+            assert!(!message.content.text().contains("fake chart bytes"));
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_run_completion_collects_unexported_artifacts() {
+    let store = ArtifactStore::new();
+    let runtime = Runtime::new().with_artifact_store(store.clone());
+    runtime.execute(artifact_workflow(scripted_client())).await;
+    assert!(store.is_empty());
+
+    // Exported artifacts outlive the run
+    let mut registry = ToolRegistry::new();
+    registry.register(NativeTool::with_context(
+        "export_chart",
+        "Render and keep a chart",
+        json!({"type": "object", "properties": {}}),
+        |_params, ctx| async move {
+            let reference = ctx
+                .store_artifact(NewArtifact::new("kept.png", "image/png", vec![7]))
+                .unwrap();
+            ctx.export_artifact(&reference.handle);
+            Ok(ToolResult::success(
+                json!({"handle": reference.handle}),
+                1.0,
+            ))
+        },
+    ));
+    let agent = Agent::new(
+        AgentConfig::builder("exporter")
+            .tools(Arc::new(registry))
+            .build(),
+    )
+    .with_client(Arc::new(llm::MockLlmClient::with_tool_then_text(
+        "export_chart",
+        json!({}),
+        "Done",
+    )));
+    let workflow = Workflow::builder()
+        .name("export_run".to_string())
+        .step(Box::new(AgentStep::from_agent(
+            agent,
+            "exporter".to_string(),
+        )))
+        .build();
+
+    let run = runtime.execute(workflow).await;
+    assert_eq!(run.artifacts.len(), 1);
+    assert_eq!(store.len(), 1);
+    assert!(store.get(&run.artifacts[0].handle).is_some());
+}
+
+#[tokio::test]
+async fn test_sub_workflow_artifacts_pass_to_the_parent_run() {
+    let store = ArtifactStore::new();
+    let runtime = Runtime::new().with_artifact_store(store.clone());
+    let uploader = Arc::new(llm::MockLlmClient::with_tool_then_text(
+        "upload",
+        json!({"handle": "artifact://1"}),
+        "Uploaded",
+    ));
+    let agent = Agent::new(
+        AgentConfig::builder("uploader")
+            .tools(artifact_tools())
+            .build(),
+    )
+    .with_client(uploader.clone());
+    let workflow = Workflow::builder()
+        .name("publish".to_string())
+        .step(Box::new(SubWorkflowStep::new("chart".to_string(), || {
+            artifact_workflow(Arc::new(llm::MockLlmClient::with_tool_then_text(
+                "render_chart",
+                json!({}),
+                "Rendered",
+            )))
+        })))
+        .step(Box::new(AgentStep::from_agent(
+            agent,
+            "uploader".to_string(),
+        )))
+        .build();
+
+    let run = runtime.execute(workflow).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+
+    // The chart outlived the sub-workflow for the upload
+    let calls = uploader.get_calls();
+    let upload_result = calls[1]
+        .messages
+        .iter()
+        .rfind(|m| m.role == Role::Tool)
+        .unwrap()
+        .content
+        .text();
+    assert!(upload_result.contains(&sha256_hex(CHART_BYTES)));
+
+    // Listed on the step that produced it and on the run, then collected
+    assert_eq!(run.steps[0].artifacts.len(), 1);
+    assert_eq!(run.steps[0].artifacts[0].name, "chart.png");
+    assert!(run.steps[1].artifacts.is_empty());
+    assert_eq!(run.artifacts, run.steps[0].artifacts);
+    assert!(store.is_empty());
+}
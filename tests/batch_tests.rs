@@ -0,0 +1,381 @@
+/// Tests for running agent requests through OpenAI's Batch API, against an
+/// in-process server
+use agent_runtime::llm::{BatchChatClient, BatchState, OpenAIClient};
+use agent_runtime::runtime::Runtime;
+use agent_runtime::*;
+use axum::extract::{Path, State};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// What the fake Batch API has seen
+#[derive(Default)]
+struct Server {
+    /// Uploaded input files, as their JSONL lines
+    uploads: Vec<Vec<Value>>,
+    batches: Vec<Value>,
+    polls: usize,
+    chat_requests: usize,
+}
+
+type Shared = Arc<Mutex<Server>>;
+
+/// `request-N` lines, answered in reverse order; a user turn containing
+/// "fail" goes to the error file
+fn answers(server: &Server) -> (Vec<Value>, Vec<Value>) {
+    let (mut output, mut errors) = (Vec::new(), Vec::new());
+    for line in server.uploads.last().unwrap().iter().rev() {
+        let prompt = line["body"]["messages"].as_array().unwrap().last().unwrap()["content"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        if prompt.contains("fail") {
+            errors.push(json!({
+                "custom_id": line["custom_id"],
+                "response": { "status_code": 400, "body": { "error": { "message": "bad prompt" } } },
+                "error": null,
+            }));
+            continue;
+        }
+        output.push(json!({
+            "custom_id": line["custom_id"],
+            "response": {
+                "status_code": 200,
+                "body": {
+                    "model": "gpt-4o-mini",
+                    "choices": [{
+                        "message": { "role": "assistant", "content": format!("echo: {}", prompt) },
+                        "finish_reason": "stop",
+                    }],
+                    "usage": { "prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15 },
+                },
+            },
+            "error": null,
+        }));
+    }
+    (output, errors)
+}
+
+fn jsonl(lines: Vec<Value>) -> String {
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+/// A batch that is in progress on its first poll and completed after
+async fn start_server() -> (String, Shared) {
+    let shared = Shared::default();
+    let app = Router::new()
+        .route(
+            "/v1/files",
+            post(|State(shared): State<Shared>, body: String| async move {
+                let lines = body
+                    .lines()
+                    .filter(|line| line.starts_with('{'))
+                    .map(|line| serde_json::from_str(line).unwrap())
+                    .collect();
+                assert!(body.contains("name=\"purpose\"\r\n\r\nbatch"));
+                shared.lock().unwrap().uploads.push(lines);
+                Json(json!({ "id": "file-in", "purpose": "batch" }))
+            }),
+        )
+        .route(
+            "/v1/batches",
+            post(
+                |State(shared): State<Shared>, Json(body): Json<Value>| async move {
+                    shared.lock().unwrap().batches.push(body);
+                    Json(json!({ "id": "batch_1", "status": "validating" }))
+                },
+            ),
+        )
+        .route(
+            "/v1/batches/batch_1",
+            get(|State(shared): State<Shared>| async move {
+                let mut server = shared.lock().unwrap();
+                server.polls += 1;
+                let total = server.uploads.last().map_or(0, Vec::len);
+                Json(if server.polls == 1 {
+                    json!({
+                        "id": "batch_1",
+                        "status": "in_progress",
+                        "request_counts": { "total": total, "completed": 0, "failed": 0 },
+                    })
+                } else {
+                    let (output, errors) = answers(&server);
+                    json!({
+                        "id": "batch_1",
+                        "status": "completed",
+                        "output_file_id": "file-out",
+                        "error_file_id": "file-err",
+                        "request_counts": {
+                            "total": total,
+                            "completed": output.len(),
+                            "failed": errors.len(),
+                        },
+                    })
+                })
+            }),
+        )
+        .route(
+            "/v1/files/{id}/content",
+            get(
+                |State(shared): State<Shared>, Path(id): Path<String>| async move {
+                    let (output, errors) = answers(&shared.lock().unwrap());
+                    jsonl(if id == "file-out" { output } else { errors })
+                },
+            ),
+        )
+        .route(
+            "/v1/chat/completions",
+            post(|State(shared): State<Shared>| async move {
+                shared.lock().unwrap().chat_requests += 1;
+                Json(json!({ "model": "gpt-4o-mini", "choices": [] }))
+            }),
+        )
+        .with_state(shared.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}/v1", addr), shared)
+}
+
+fn client(base_url: &str) -> Arc<OpenAIClient> {
+    Arc::new(OpenAIClient::with_model("sk-test", "gpt-4o-mini").with_base_url(base_url))
+}
+
+fn runtime(base_url: &str) -> Runtime {
+    Runtime::new()
+        .with_batch_client(client(base_url))
+        .with_batch_poll_interval(Duration::from_millis(10))
+}
+
+/// A single agent step answering through the batch server's address
+fn template(base_url: &str, config: AgentConfig) -> impl Fn() -> Workflow {
+    let base_url = base_url.to_string();
+    move || {
+        let agent = Agent::new(config.clone()).with_client(client(&base_url));
+        Workflow::builder()
+            .step(Box::new(AgentStep::from_agent(agent, "answer".to_string())))
+            .build()
+    }
+}
+
+fn grader() -> AgentConfig {
+    AgentConfig::builder("grader")
+        .system_prompt("Grade the answer.")
+        .build()
+}
+
+#[tokio::test]
+async fn test_batch_client_submit_poll_and_results() {
+    let (base_url, shared) = start_server().await;
+    let client = client(&base_url);
+    let requests = ["first", "second", "please fail"]
+        .into_iter()
+        .map(|text| ChatRequest::new(vec![ChatMessage::user(text)]).with_temperature(0.2))
+        .collect();
+    let handle = client.submit_batch(requests).await.unwrap();
+    assert_eq!(handle.provider, "openai");
+    assert_eq!(handle.batch_id, "batch_1");
+    assert_eq!(
+        handle.custom_ids,
+        vec!["request-0", "request-1", "request-2"]
+    );
+
+    {
+        let server = shared.lock().unwrap();
+        let line = &server.uploads[0][1];
+        assert_eq!(line["custom_id"], "request-1");
+        assert_eq!(line["method"], "POST");
+        assert_eq!(line["url"], "/v1/chat/completions");
+        assert_eq!(line["body"]["model"], "gpt-4o-mini");
+        assert!(line["body"].get("stream").is_none());
+        assert_eq!(
+            server.batches[0],
+            json!({
+                "input_file_id": "file-in",
+                "endpoint": "/v1/chat/completions",
+                "completion_window": "24h",
+            })
+        );
+    }
+
+    let status = client.poll(&handle).await.unwrap();
+    assert_eq!(status.state, BatchState::InProgress);
+    assert!(!status.is_finished());
+    assert_eq!(status.total, 3);
+    let status = client.poll(&handle).await.unwrap();
+    assert_eq!(
+        (status.state, status.completed, status.failed),
+        (BatchState::Completed, 2, 1)
+    );
+
+    // Answered in reverse, returned in submission order
+    let results = client.results(&handle).await.unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap().content, "echo: first");
+    assert_eq!(results[1].as_ref().unwrap().content, "echo: second");
+    let error = results[2].as_ref().unwrap_err().to_string();
+    assert!(
+        error.contains("400") && error.contains("bad prompt"),
+        "{}",
+        error
+    );
+    assert_eq!(shared.lock().unwrap().chat_requests, 0);
+}
+
+#[tokio::test]
+async fn test_execute_batch_runs_match_their_inputs() {
+    let (base_url, shared) = start_server().await;
+    let runtime = runtime(&base_url);
+    let inputs = vec![json!("alpha"), json!("please fail"), json!("gamma")];
+    let runs = runtime
+        .execute_batch(template(&base_url, grader()), inputs)
+        .await
+        .unwrap();
+
+    assert_eq!(runs.len(), 3);
+    assert_eq!(runs[0].state, WorkflowState::Completed);
+    assert_eq!(
+        runs[0].final_output.as_ref().unwrap()["response"],
+        "echo: alpha"
+    );
+    assert_eq!(
+        runs[2].final_output.as_ref().unwrap()["response"],
+        "echo: gamma"
+    );
+    assert_eq!(runs[0].usage.llm_calls, 1);
+    assert_eq!(runs[0].usage.total_tokens, 15);
+    assert_eq!(runs[1].state, WorkflowState::Failed);
+    let failure = runs[1].failure.as_ref().unwrap().error.to_string();
+    assert!(failure.contains("bad prompt"), "{}", failure);
+
+    // The batch carried each run's request; no request went to the model
+    let server = shared.lock().unwrap();
+    assert_eq!(server.uploads.len(), 1);
+    let system = &server.uploads[0][0]["body"]["messages"][0];
+    assert_eq!(system["content"], "Grade the answer.");
+    assert_eq!(server.chat_requests, 0);
+
+    // Only the real runs reached the runtime's events
+    let started = runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .filter(|e| e.scope == EventScope::Workflow && e.event_type == EventType::Started)
+        .count();
+    assert_eq!(started, 3);
+}
+
+#[tokio::test]
+async fn test_batch_is_collected_by_a_later_runtime() {
+    let (base_url, _shared) = start_server().await;
+    let config = AgentConfig::builder("grader")
+        .system_prompt_template("Grade for {{audience}}.")
+        .build();
+    // An input whose run fails before its request fails the submission
+    let error = runtime(&base_url)
+        .submit_batch(template(&base_url, config), vec![json!("one")])
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(
+        error.contains("input 0 doesn't get to the LLM request") && error.contains("audience"),
+        "{}",
+        error
+    );
+
+    let config = AgentConfig::builder("grader")
+        .system_prompt_template("Grade on {{today}}.")
+        .build();
+    let submitted = runtime(&base_url)
+        .submit_batch(
+            template(&base_url, config.clone()),
+            vec![json!("one"), json!("two")],
+        )
+        .await
+        .unwrap();
+    let saved = serde_json::to_string(&submitted).unwrap();
+
+    // The next invocation picks the batch up from its saved form
+    let batch: WorkflowBatch = serde_json::from_str(&saved).unwrap();
+    assert_eq!(batch, submitted);
+    let later = runtime(&base_url);
+    assert!(later
+        .collect_batch(template(&base_url, config.clone()), &batch)
+        .await
+        .unwrap()
+        .is_none());
+    let runs = later
+        .collect_batch(template(&base_url, config), &batch)
+        .await
+        .unwrap()
+        .unwrap();
+    let responses: Vec<&Value> = runs
+        .iter()
+        .map(|run| &run.final_output.as_ref().unwrap()["response"])
+        .collect();
+    assert_eq!(responses, vec!["echo: one", "echo: two"]);
+}
+
+#[tokio::test]
+async fn test_workflows_needing_more_than_one_response_are_rejected() {
+    let (base_url, shared) = start_server().await;
+    let runtime = runtime(&base_url);
+
+    let mut tools = ToolRegistry::new();
+    tools.register(NativeTool::new(
+        "lookup",
+        "Look something up",
+        json!({ "type": "object" }),
+        |_| async move { Ok(prelude::ToolResult::success(json!({}), 1.0)) },
+    ));
+    let config = AgentConfig::builder("researcher")
+        .system_prompt("Look it up.")
+        .tools(Arc::new(tools))
+        .build();
+    let error = runtime
+        .execute_batch(template(&base_url, config), vec![json!("x")])
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("agent 'researcher' has tools"), "{}", error);
+
+    // A workflow context gives the agent memory tools
+    let agent_url = base_url.clone();
+    let with_context = move || {
+        let agent = Agent::new(grader()).with_client(client(&agent_url));
+        Workflow::builder()
+            .with_restored_context(WorkflowContext::new())
+            .step(Box::new(AgentStep::from_agent(agent, "answer".to_string())))
+            .build()
+    };
+    let error = runtime
+        .submit_batch(with_context, vec![json!("x")])
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("has tools"), "{}", error);
+
+    let two_steps = || {
+        Workflow::builder()
+            .step(Box::new(TransformStep::new("a".to_string(), |v| v)))
+            .step(Box::new(TransformStep::new("b".to_string(), |v| v)))
+            .build()
+    };
+    let error = runtime
+        .submit_batch(two_steps, vec![json!("x")])
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("has 2 steps"), "{}", error);
+    assert!(shared.lock().unwrap().uploads.is_empty());
+
+    // Without a batch client nothing can be submitted
+    let error = Runtime::new()
+        .submit_batch(template(&base_url, grader()), vec![json!("x")])
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("with_batch_client"), "{}", error);
+}
@@ -0,0 +1,202 @@
+/// Tests for run budgets: calls, tokens, cost and the partial run they leave
+use agent_runtime::llm::{MockLlmClient, MockResponse};
+use agent_runtime::usage::CostPerMToken;
+use agent_runtime::workflow::{StepError, StepStatus};
+use agent_runtime::*;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn searcher(name: &str, mock: Arc<MockLlmClient>) -> Box<dyn workflow::Step> {
+    let mut registry = ToolRegistry::new();
+    registry.register(NativeTool::new(
+        "search",
+        "Search the index",
+        json!({"type": "object", "properties": {}}),
+        |_params| async move { Ok(ToolResult::success(json!({"hits": 3}), 1.0)) },
+    ));
+    let agent =
+        Agent::new(AgentConfig::builder(name).tools(Arc::new(registry)).build()).with_client(mock);
+    Box::new(AgentStep::from_agent(agent, name.to_string()))
+}
+
+/// Searches once, then answers
+fn search_then_answer(answer: &str) -> Arc<MockLlmClient> {
+    Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("search", json!({ "q": "rust" }))
+            .with_response(answer),
+    )
+}
+
+async fn workflow_event(runtime: &Runtime, event_type: EventType) -> Event {
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .find(|e| e.scope == EventScope::Workflow && e.event_type == event_type)
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_call_budget_stops_before_the_next_llm_call() {
+    let research = search_then_answer("three crates");
+    let summary = search_then_answer("summary");
+    let workflow = Workflow::builder()
+        .name("report".to_string())
+        .step(searcher("researcher", research.clone()))
+        .step(searcher("summarizer", summary.clone()))
+        .with_budget(Budget::new().with_max_llm_calls(2))
+        .initial_input(json!("find rust crates"))
+        .build();
+
+    let runtime = Runtime::new();
+    let run = runtime.execute(workflow).await;
+
+    assert_eq!(research.call_count() + summary.call_count(), 2);
+    assert_eq!(summary.call_count(), 0);
+    assert_eq!(run.state, WorkflowState::BudgetExceeded);
+    assert_eq!(run.usage.llm_calls, 2);
+
+    // What the run did before the budget ran out is kept
+    assert_eq!(run.steps.len(), 2);
+    assert_eq!(
+        run.steps[0].output.as_ref().unwrap()["response"],
+        "three crates"
+    );
+    assert_eq!(run.steps[1].status, StepStatus::Failed);
+    let failure = run.failure.unwrap();
+    assert_eq!(failure.step_name, "summarizer");
+    let StepError::BudgetExceeded(exceeded) = failure.error else {
+        panic!("expected a budget error, got {:?}", failure.error);
+    };
+    assert_eq!(exceeded.dimension, BudgetDimension::LlmCalls);
+    assert_eq!(
+        exceeded.to_string(),
+        "Budget exceeded: 2 of 2 LLM calls made"
+    );
+
+    let failed = workflow_event(&runtime, EventType::Failed).await;
+    assert_eq!(failed.data["budget"]["dimension"], "llm_calls");
+    assert_eq!(failed.data["budget"]["limit"], 2.0);
+    assert_eq!(failed.data["failed_step_name"], "summarizer");
+}
+
+#[tokio::test]
+async fn test_last_affordable_call_must_answer() {
+    let mock = Arc::new(MockLlmClient::from_mock_responses(vec![
+        MockResponse::with_tool_call("search", json!({ "q": "rust" })),
+        MockResponse {
+            content: "two crates so far".to_string(),
+            ..MockResponse::with_tool_call("search", json!({ "q": "more rust" }))
+        },
+    ]));
+    let workflow = Workflow::builder()
+        .name("research".to_string())
+        .step(searcher("researcher", mock.clone()))
+        .with_budget(Budget::new().with_max_llm_calls(2))
+        .initial_input(json!("find rust crates"))
+        .build();
+
+    let runtime = Runtime::new();
+    let run = runtime.execute(workflow).await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(mock.call_count(), 2);
+    let calls = mock.get_calls();
+    assert!(calls[0].tools.is_some());
+    assert!(calls[1].tools.is_none());
+    assert_eq!(
+        run.final_output.unwrap()["response"],
+        "two crates so far",
+        "the tool call made anyway is ignored"
+    );
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(runtime
+        .event_stream()
+        .all()
+        .iter()
+        .any(|e| e.component_id == "system:run_budget"));
+}
+
+#[tokio::test]
+async fn test_token_budget_stops_mid_tool_loop() {
+    let mock = search_then_answer("three crates");
+    let workflow = Workflow::builder()
+        .name("research".to_string())
+        .step(searcher("researcher", mock.clone()))
+        .with_budget(Budget::new().with_max_total_tokens(15))
+        .initial_input(json!("find rust crates"))
+        .build();
+
+    let runtime = Runtime::new();
+    let run = runtime.execute(workflow).await;
+
+    assert_eq!(mock.call_count(), 1);
+    assert_eq!(run.state, WorkflowState::BudgetExceeded);
+    assert_eq!(run.steps.len(), 1);
+    assert_eq!(run.steps[0].status, StepStatus::Failed);
+
+    let failed = workflow_event(&runtime, EventType::Failed).await;
+    assert_eq!(failed.data["budget"]["dimension"], "total_tokens");
+    assert_eq!(failed.data["budget"]["used"], 15.0);
+}
+
+#[tokio::test]
+async fn test_cost_budget_uses_the_ledger_prices() {
+    // 10 prompt and 5 completion tokens per call: $0.02 each
+    let runtime = Runtime::new().with_usage_ledger(
+        UsageLedger::new().with_price("mock-model", CostPerMToken::new(1000.0, 2000.0)),
+    );
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec!["ok"; 3]));
+    let workflow = Workflow::builder()
+        .name("costly".to_string())
+        .step(searcher("first", mock.clone()))
+        .step(searcher("second", mock.clone()))
+        .step(searcher("third", mock.clone()))
+        .with_budget(Budget::new().with_max_cost_usd(0.03))
+        .initial_input(json!("go"))
+        .build();
+
+    let run = runtime.execute(workflow).await;
+
+    assert_eq!(mock.call_count(), 2);
+    assert_eq!(run.state, WorkflowState::BudgetExceeded);
+    assert_eq!(run.steps.len(), 3);
+    assert_eq!(run.steps[2].status, StepStatus::Failed);
+
+    let failed = workflow_event(&runtime, EventType::Failed).await;
+    assert_eq!(failed.data["budget"]["dimension"], "cost_usd");
+}
+
+#[tokio::test]
+async fn test_parent_budget_counts_sub_workflow_calls() {
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec!["ok"; 3]));
+    let child_mock = mock.clone();
+    let workflow = Workflow::builder()
+        .name("parent".to_string())
+        .step(searcher("planner", mock.clone()))
+        .step(Box::new(SubWorkflowStep::new(
+            "delegate".to_string(),
+            move || {
+                Workflow::builder()
+                    .name("child".to_string())
+                    .step(searcher("worker", child_mock.clone()))
+                    .step(searcher("checker", child_mock.clone()))
+                    .build()
+            },
+        )))
+        .with_budget(Budget::new().with_max_llm_calls(2))
+        .initial_input(json!("go"))
+        .build();
+
+    let run = Runtime::new().execute(workflow).await;
+
+    assert_eq!(mock.call_count(), 2);
+    assert_eq!(run.state, WorkflowState::BudgetExceeded);
+    assert_eq!(run.steps.len(), 2);
+    assert_eq!(run.sub_workflows[0].state, WorkflowState::BudgetExceeded);
+    assert_eq!(run.sub_workflows[0].steps_run, 2);
+}
@@ -168,6 +168,7 @@ async fn test_from_messages_with_metadata() {
     let metadata = agent_runtime::types::AgentInputMetadata {
         step_index: 5,
         previous_agent: Some("previous_agent".to_string()),
+        workflow_id: None,
     };
 
     let input = AgentInput::from_messages_with_metadata(history, metadata);
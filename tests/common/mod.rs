@@ -0,0 +1,186 @@
+//! A scripted local HTTP server, shared by the tests of code that calls out
+//! over HTTP
+#![allow(dead_code)]
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A request as the server saw it
+#[derive(Debug, Clone)]
+pub struct Recorded {
+    /// The request line and headers, lowercased
+    pub head: String,
+    /// The request line as sent, e.g. `POST /v1/chat/completions HTTP/1.1`
+    pub request_line: String,
+    /// Header values by lowercase name
+    pub headers: HashMap<String, String>,
+    pub raw_body: Vec<u8>,
+    /// The body as JSON; `Null` if it isn't JSON
+    pub body: Value,
+}
+
+impl Recorded {
+    /// The path and query of the request line
+    pub fn path(&self) -> &str {
+        self.request_line.split_whitespace().nth(1).unwrap_or("")
+    }
+}
+
+/// A scripted reply
+#[derive(Debug, Clone)]
+pub struct Reply {
+    status: u16,
+    content_type: &'static str,
+    /// Written one at a time, so the body arrives split across reads
+    pieces: Vec<String>,
+    /// Read the request, then never answer
+    stall: bool,
+}
+
+impl Reply {
+    /// A `200` reply with a JSON body
+    pub fn json(body: Value) -> Self {
+        Self::text("application/json", body.to_string())
+    }
+
+    /// A `200` reply of `content_type`
+    pub fn text(content_type: &'static str, body: impl Into<String>) -> Self {
+        Self::pieces(content_type, vec![body.into()])
+    }
+
+    /// A `200` reply of `content_type`, its body written in `pieces` a
+    /// moment apart
+    pub fn pieces(content_type: &'static str, pieces: Vec<String>) -> Self {
+        Self {
+            status: 200,
+            content_type,
+            pieces,
+            stall: false,
+        }
+    }
+
+    /// Read the request, then never answer
+    pub fn stall() -> Self {
+        Self {
+            stall: true,
+            ..Self::text("text/plain", "")
+        }
+    }
+
+    /// Answer with `status` instead
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+}
+
+impl From<Value> for Reply {
+    fn from(body: Value) -> Self {
+        Reply::json(body)
+    }
+}
+
+/// Serve `replies` in order, one per connection, recording each request;
+/// returns the server's `http://` URL
+pub async fn serve<R: Into<Reply>>(replies: Vec<R>) -> (String, Arc<Mutex<Vec<Recorded>>>) {
+    let replies: Vec<Reply> = replies.into_iter().map(Into::into).collect();
+    let (listener, url, recorded) = listen().await;
+
+    let log = recorded.clone();
+    tokio::spawn(async move {
+        for reply in replies {
+            let (socket, _) = listener.accept().await.unwrap();
+            answer(socket, &reply, &log).await;
+        }
+    });
+
+    (url, recorded)
+}
+
+/// Answer every connection with `reply`, recording each request; returns
+/// the server's `http://` URL
+pub async fn serve_forever(reply: Reply) -> (String, Arc<Mutex<Vec<Recorded>>>) {
+    let (listener, url, recorded) = listen().await;
+
+    let log = recorded.clone();
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            answer(socket, &reply, &log).await;
+        }
+    });
+
+    (url, recorded)
+}
+
+async fn listen() -> (TcpListener, String, Arc<Mutex<Vec<Recorded>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    (listener, url, Arc::new(Mutex::new(Vec::new())))
+}
+
+async fn answer(mut socket: TcpStream, reply: &Reply, log: &Mutex<Vec<Recorded>>) {
+    let request = read_request(&mut socket).await;
+    log.lock().unwrap().push(request);
+    if reply.stall {
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        return;
+    }
+
+    let length: usize = reply.pieces.iter().map(String::len).sum();
+    let reason = reqwest::StatusCode::from_u16(reply.status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or("Unknown");
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        reply.status, reason, reply.content_type, length
+    );
+    socket.write_all(head.as_bytes()).await.unwrap();
+    for (i, piece) in reply.pieces.iter().enumerate() {
+        if i > 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        socket.write_all(piece.as_bytes()).await.unwrap();
+        socket.flush().await.unwrap();
+    }
+    socket.shutdown().await.ok();
+}
+
+async fn read_request(socket: &mut TcpStream) -> Recorded {
+    let mut raw = Vec::new();
+    let mut buf = [0u8; 4096];
+    let (head, body_start) = loop {
+        let n = socket.read(&mut buf).await.unwrap();
+        raw.extend_from_slice(&buf[..n]);
+        if let Some(end) = raw.windows(4).position(|w| w == b"\r\n\r\n") {
+            break (String::from_utf8_lossy(&raw[..end]).to_string(), end + 4);
+        }
+    };
+    let headers: HashMap<String, String> = head
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.trim().to_lowercase(), v.trim().to_string()))
+        .collect();
+    let length: usize = headers
+        .get("content-length")
+        .map_or(0, |v| v.parse().unwrap());
+    while raw.len() < body_start + length {
+        let n = socket.read(&mut buf).await.unwrap();
+        raw.extend_from_slice(&buf[..n]);
+    }
+    let raw_body = raw[body_start..body_start + length].to_vec();
+
+    Recorded {
+        request_line: head.lines().next().unwrap_or_default().to_string(),
+        head: head.to_lowercase(),
+        headers,
+        body: serde_json::from_slice(&raw_body).unwrap_or(Value::Null),
+        raw_body,
+    }
+}
@@ -0,0 +1,164 @@
+/// Tests that every context manager keeps tool calls and their results
+/// together, so pruned histories stay valid for providers
+use agent_runtime::context_strategies::{
+    CompositeContextManager, MessageTypeManager, SlidingWindowManager, SummarizationManager,
+    TokenBudgetManager,
+};
+use agent_runtime::llm::types::{FunctionCall, ToolCall};
+use agent_runtime::llm::{validate_history, HistoryError};
+use agent_runtime::*;
+use std::sync::Arc;
+
+fn call(id: String) -> ToolCall {
+    ToolCall {
+        id,
+        r#type: "function".to_string(),
+        function: FunctionCall {
+            name: "search".to_string(),
+            arguments: "{}".to_string(),
+        },
+    }
+}
+
+/// Rounds of a question, one or two searches at a time with their results,
+/// and an answer; odd rounds search twice in parallel
+fn interleaved(rounds: usize) -> Vec<ChatMessage> {
+    let filler = "lorem ipsum ".repeat(20);
+    let mut history = vec![ChatMessage::system("You research questions.")];
+    for round in 0..rounds {
+        history.push(ChatMessage::user(format!("Question {}: {}", round, filler)));
+        let ids: Vec<String> = (0..1 + round % 2)
+            .map(|n| format!("call_{}_{}", round, n))
+            .collect();
+        history.push(ChatMessage::assistant_with_tool_calls(
+            format!("Searching for {}", round),
+            ids.iter().cloned().map(call).collect(),
+        ));
+        for id in &ids {
+            history.push(ChatMessage::tool_result(id, filler.clone()));
+        }
+        history.push(ChatMessage::assistant(format!(
+            "Answer {}: {}",
+            round, filler
+        )));
+    }
+    history
+}
+
+/// `history` cut short inside a tool exchange, as mid-loop
+fn mid_exchange(rounds: usize) -> Vec<ChatMessage> {
+    let mut history = interleaved(rounds);
+    history.pop();
+    history
+}
+
+fn managers() -> Vec<(String, Arc<dyn ContextManager>)> {
+    let mut managers: Vec<(String, Arc<dyn ContextManager>)> = Vec::new();
+    for max in 3..20 {
+        managers.push((
+            format!("SlidingWindow({})", max),
+            Arc::new(SlidingWindowManager::new(max)),
+        ));
+        for pairs in [1, 2, 4] {
+            managers.push((
+                format!("MessageType({}, {})", max, pairs),
+                Arc::new(MessageTypeManager::new(max, pairs)),
+            ));
+        }
+    }
+    for budget in (400..2_400).step_by(150) {
+        managers.push((
+            format!("TokenBudget({})", budget),
+            Arc::new(TokenBudgetManager::new(budget, 1.0).with_min_messages(1)),
+        ));
+    }
+    for keep in 1..12 {
+        managers.push((
+            format!("Summarization({})", keep),
+            Arc::new(SummarizationManager::new(2_000, 500, 100, keep)),
+        ));
+    }
+    managers
+}
+
+#[tokio::test]
+async fn test_every_manager_leaves_a_valid_history() {
+    assert_eq!(validate_history(&interleaved(8)), Ok(()));
+    for history in [interleaved(8), mid_exchange(8), mid_exchange(7)] {
+        for (name, manager) in managers() {
+            let (pruned, _) = manager.prune(history.clone()).await.unwrap();
+            assert_eq!(validate_history(&pruned), Ok(()), "{}", name);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_composite_leaves_a_valid_history() {
+    for max in 4..16 {
+        let composite = CompositeContextManager::new(vec![
+            Arc::new(MessageTypeManager::new(max, 2)),
+            Arc::new(SummarizationManager::new(2_000, 500, 100, max / 2)),
+            Arc::new(SlidingWindowManager::new(max - 1)),
+        ]);
+        let (pruned, _) = composite.prune(interleaved(8)).await.unwrap();
+        assert_eq!(validate_history(&pruned), Ok(()), "max {}", max);
+    }
+}
+
+#[tokio::test]
+async fn test_sliding_window_drops_results_of_removed_calls() {
+    let history = interleaved(2);
+    // [system, q0, call_0_0, result, a0, q1, call_1_0+call_1_1, result, result, a1]
+    let (pruned, _) = SlidingWindowManager::new(4).prune(history).await.unwrap();
+
+    // The window started at the last call's results, which go with it
+    assert_eq!(pruned.len(), 2);
+    assert_eq!(pruned[0].role, Role::System);
+    assert!(pruned[1].content.text().starts_with("Answer 1"));
+}
+
+#[tokio::test]
+async fn test_message_type_keeps_a_call_with_its_results() {
+    // Mid-loop: the latest call's results must stay with it
+    let history = mid_exchange(6);
+    let (pruned, _) = MessageTypeManager::new(6, 1).prune(history).await.unwrap();
+
+    assert_eq!(validate_history(&pruned), Ok(()));
+    let last_call = pruned.iter().rposition(|m| m.tool_calls.is_some()).unwrap();
+    assert_eq!(pruned[last_call].content.text(), "Searching for 5");
+    assert_eq!(pruned[last_call + 1..].len(), 2);
+    assert!(pruned[last_call + 1..].iter().all(|m| m.role == Role::Tool));
+    // Order is kept
+    assert_eq!(pruned[0].role, Role::System);
+    assert!(pruned[last_call - 1]
+        .content
+        .text()
+        .starts_with("Question 5"));
+}
+
+#[tokio::test]
+async fn test_summary_never_separates_results_from_their_call() {
+    let history = mid_exchange(6);
+    // The last two messages are results of one call
+    let (pruned, _) = SummarizationManager::new(20_000, 500, 100, 2)
+        .prune(history)
+        .await
+        .unwrap();
+
+    assert_eq!(validate_history(&pruned), Ok(()));
+    assert!(pruned.iter().any(|m| m.content.text() == "Searching for 5"));
+    assert_eq!(pruned.iter().filter(|m| m.role == Role::Tool).count(), 2);
+}
+
+#[test]
+fn test_validation_reports_where_the_link_breaks() {
+    let mut history = interleaved(2);
+    history.remove(6);
+    assert_eq!(
+        validate_history(&history),
+        Err(HistoryError::OrphanedToolResult {
+            index: 6,
+            tool_call_id: "call_1_0".to_string()
+        })
+    );
+}
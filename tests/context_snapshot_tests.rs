@@ -0,0 +1,266 @@
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::*;
+use async_trait::async_trait;
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn big_context(turns: usize) -> WorkflowContext {
+    let mut ctx = WorkflowContext::new();
+    let filler = "x".repeat(400);
+    for i in 0..turns {
+        ctx.append_messages(vec![
+            ChatMessage::user(format!("question {} {}", i, filler)),
+            ChatMessage::assistant(format!("answer {} {}", i, filler)),
+        ]);
+    }
+    ctx
+}
+
+#[test]
+fn test_snapshot_reads_do_not_need_the_context_lock() {
+    let ctx = Arc::new(RwLock::new(big_context(500)));
+    let monitor = ctx.read().unwrap().monitor();
+
+    // Hold the write lock while another thread reads and serializes
+    let mut guard = ctx.write().unwrap();
+    let (tx, rx) = mpsc::channel();
+    let reader = thread::spawn(move || {
+        let snapshot = monitor.latest();
+        tx.send(serde_json::to_string(&*snapshot).unwrap().len())
+            .unwrap();
+    });
+    let serialized = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(serialized > 400_000);
+    guard.append_messages(vec![ChatMessage::user("more")]);
+    drop(guard);
+    reader.join().unwrap();
+}
+
+#[test]
+fn test_latest_snapshot_reads_only_what_was_published() {
+    // A loaded context publishes its first snapshot as it loads
+    let saved = serde_json::to_string(&big_context(3)).unwrap();
+    let mut ctx: WorkflowContext = serde_json::from_str(&saved).unwrap();
+    let loaded = ctx.latest_snapshot();
+    assert_eq!(loaded.message_count, 6);
+    assert_eq!(loaded.to_vec(), ctx.history());
+
+    // Direct edits aren't seen until they're published
+    ctx.chat_history.push(ChatMessage::user("unpublished"));
+    assert!(Arc::ptr_eq(&ctx.latest_snapshot(), &loaded));
+    ctx.refresh_snapshot();
+    assert_eq!(ctx.latest_snapshot().message_count, 7);
+}
+
+#[test]
+fn test_slow_readers_never_delay_writers() {
+    let ctx = Arc::new(RwLock::new(big_context(200)));
+    let monitor = ctx.read().unwrap().monitor();
+    let stop = Arc::new(AtomicBool::new(false));
+
+    // Readers keep a snapshot alive for a long "HTTP response" each time
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let monitor = monitor.clone();
+            let stop = stop.clone();
+            thread::spawn(move || {
+                let mut reads = 0;
+                while !stop.load(Ordering::Relaxed) {
+                    let snapshot = monitor.latest();
+                    let _ = serde_json::to_string(&*snapshot).unwrap();
+                    thread::sleep(Duration::from_millis(100));
+                    reads += 1;
+                }
+                reads
+            })
+        })
+        .collect();
+
+    let mut slowest = Duration::ZERO;
+    for i in 0..50 {
+        let started = Instant::now();
+        ctx.write().unwrap().append_messages(vec![
+            ChatMessage::user(format!("q{}", i)),
+            ChatMessage::assistant(format!("a{}", i)),
+        ]);
+        slowest = slowest.max(started.elapsed());
+        thread::sleep(Duration::from_millis(2));
+    }
+    stop.store(true, Ordering::Relaxed);
+    let reads: usize = readers.into_iter().map(|r| r.join().unwrap()).sum();
+
+    assert!(reads > 0);
+    // Far below a single reader's hold time
+    assert!(
+        slowest < Duration::from_millis(50),
+        "writer waited {:?}",
+        slowest
+    );
+    assert_eq!(monitor.latest().message_count, 500);
+}
+
+#[test]
+fn test_snapshots_never_show_half_applied_turns() {
+    let ctx = Arc::new(RwLock::new(WorkflowContext::new()));
+    let monitor = ctx.read().unwrap().monitor();
+    let done = Arc::new(AtomicBool::new(false));
+
+    let reader = {
+        let done = done.clone();
+        thread::spawn(move || {
+            let mut last_version = 0;
+            let mut checked = 0;
+            loop {
+                let finished = done.load(Ordering::Relaxed);
+                let snapshot = monitor.latest();
+                assert!(snapshot.version >= last_version);
+                last_version = snapshot.version;
+
+                // Every turn is committed as a user/assistant pair
+                let messages = snapshot.to_vec();
+                assert_eq!(messages.len(), snapshot.message_count);
+                assert_eq!(messages.len() % 2, 0);
+                for pair in messages.chunks(2) {
+                    assert_eq!(pair[0].role, Role::User);
+                    assert_eq!(pair[1].role, Role::Assistant);
+                    assert_eq!(pair[0].content.text()[1..], pair[1].content.text()[1..]);
+                }
+                assert_eq!(
+                    snapshot.utilization.estimated_tokens,
+                    SimpleTokenEstimator.estimate(&messages)
+                );
+                checked += 1;
+                if finished {
+                    return checked;
+                }
+            }
+        })
+    };
+
+    for i in 0..200 {
+        let mut guard = ctx.write().unwrap();
+        if i % 10 == 9 {
+            // Replacing the history is just as atomic
+            let mut history = guard.history().to_vec();
+            history.drain(..2);
+            history.push(ChatMessage::user(format!("q{}", i)));
+            history.push(ChatMessage::assistant(format!("a{}", i)));
+            guard.set_history(history);
+        } else {
+            guard.append_messages(vec![
+                ChatMessage::user(format!("q{}", i)),
+                ChatMessage::assistant(format!("a{}", i)),
+            ]);
+        }
+    }
+    done.store(true, Ordering::Relaxed);
+    assert!(reader.join().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn test_watch_channel_fires_on_each_commit() {
+    let mut ctx = WorkflowContext::new();
+    let mut rx = ctx.monitor().subscribe();
+    let start = rx.borrow_and_update().version;
+
+    ctx.append_messages(vec![ChatMessage::user("a")]);
+    assert!(rx.has_changed().unwrap());
+    assert_eq!(rx.borrow_and_update().version, start + 1);
+
+    ctx.try_append_messages(vec![ChatMessage::assistant("b")])
+        .unwrap();
+    assert!(rx.has_changed().unwrap());
+    assert_eq!(rx.borrow_and_update().message_count, 2);
+
+    ctx.set_history(vec![ChatMessage::user("reset")]);
+    rx.changed().await.unwrap();
+    let snapshot = rx.borrow_and_update().clone();
+    assert_eq!(snapshot.version, start + 3);
+    assert_eq!(snapshot.stats.history_replacements, 2);
+    assert_eq!(snapshot.stats.messages_appended, 3);
+
+    // Rejected appends change nothing, so nothing is published
+    ctx.limits = ConversationLimits::new().with_max_total_messages(1);
+    assert!(ctx
+        .try_append_messages(vec![ChatMessage::user("too many")])
+        .is_err());
+    assert!(!rx.has_changed().unwrap());
+}
+
+struct SlowStep;
+
+#[async_trait]
+impl Step for SlowStep {
+    async fn execute_with_context(
+        &self,
+        input: StepInput,
+        _ctx: ExecutionContext<'_>,
+    ) -> StepResult {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        Ok(StepOutput {
+            data: input.data,
+            metadata: step::StepOutputMetadata {
+                step_name: "slow".to_string(),
+                step_type: StepType::Custom("slow".to_string()),
+                execution_time_ms: 1_000,
+                critic: None,
+                iterations_run: None,
+                policy: None,
+                item_errors: None,
+                handoffs: None,
+            },
+        })
+    }
+
+    fn name(&self) -> &str {
+        "slow"
+    }
+
+    fn step_type(&self) -> StepType {
+        StepType::Custom("slow".to_string())
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_runtime_publishes_in_flight_snapshots() {
+    let agent = Agent::new(
+        AgentConfig::builder("assistant")
+            .system_prompt("You help")
+            .build(),
+    )
+    .with_client(Arc::new(MockLlmClient::with_responses_vec(vec!["hello"])));
+    let workflow = Workflow::builder()
+        .name("watched".to_string())
+        .with_restored_context(WorkflowContext::new())
+        .step(Box::new(SlowStep))
+        .add_step(Box::new(AgentStep::from_agent(
+            agent,
+            "assistant".to_string(),
+        )))
+        .initial_input(json!("hi"))
+        .build();
+    let run_id = workflow.run_id.clone();
+    let runtime = Runtime::new();
+
+    let watch = async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let monitor = runtime.context_monitor(&run_id).unwrap();
+        let mut rx = monitor.subscribe();
+        assert_eq!(rx.borrow_and_update().message_count, 0);
+        rx.changed().await.unwrap();
+        let snapshot = rx.borrow_and_update().clone();
+        snapshot
+    };
+    let (run, snapshot) = tokio::join!(runtime.execute(workflow), watch);
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    let roles: Vec<_> = snapshot.messages().map(|m| m.role.clone()).collect();
+    // The agent's system prompt stays out of the shared history
+    assert_eq!(roles, vec![Role::User, Role::Assistant]);
+    assert!(snapshot.utilization.ratio > 0.0);
+    // The registry only covers in-flight runs
+    assert!(runtime.context_monitor(&run_id).is_none());
+}
@@ -0,0 +1,301 @@
+/// Tests for keeping workflow contexts in a context store between runs
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::runtime::Runtime;
+use agent_runtime::*;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn agent_step(client: Arc<MockLlmClient>) -> Box<dyn workflow::Step> {
+    let agent = Agent::new(
+        AgentConfig::builder("assistant")
+            .system_prompt("You are helpful.")
+            .build(),
+    )
+    .with_client(client);
+    Box::new(AgentStep::from_agent(agent, "reply".to_string()))
+}
+
+/// A step that passes its input on, tagged with its name
+fn note(name: &str) -> Box<dyn workflow::Step> {
+    let name = name.to_string();
+    Box::new(TransformStep::new(
+        name.clone(),
+        move |data| json!({ "last": name, "input": data }),
+    ))
+}
+
+fn store_events(runtime: &Runtime) -> Vec<Value> {
+    runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .filter(|e| e.component_id == "system:context_store_failed")
+        .map(|e| e.data)
+        .collect()
+}
+
+#[tokio::test]
+async fn test_runs_continue_the_stored_conversation() {
+    let store = Arc::new(MemoryContextStore::new());
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_response("Nice to meet you, Ada.")
+            .with_response("Your name is Ada."),
+    );
+    let runtime = Runtime::new();
+
+    let first = Workflow::builder()
+        .with_context_store(store.clone(), "session-1")
+        .step(agent_step(client.clone()))
+        .initial_input(json!("Hi, I'm Ada"))
+        .build();
+    assert_eq!(runtime.execute(first).await.state, WorkflowState::Completed);
+
+    let record = store.load("session-1").await.unwrap().unwrap().record;
+    assert_eq!(record.version, 1);
+    assert_eq!(record.message_count, 2);
+    assert!(record.token_estimate > 0);
+
+    // A new workflow on the same key starts from the saved history
+    let second = Workflow::builder()
+        .with_context_store(store.clone(), "session-1")
+        .step(agent_step(client.clone()))
+        .initial_input(json!("What's my name?"))
+        .build();
+    let run = runtime.execute(second).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+
+    let seen: Vec<String> = client
+        .last_call()
+        .unwrap()
+        .messages
+        .iter()
+        .map(|m| m.content.text().into_owned())
+        .collect();
+    assert_eq!(
+        seen,
+        vec![
+            "You are helpful.",
+            "Hi, I'm Ada",
+            "Nice to meet you, Ada.",
+            "What's my name?"
+        ]
+    );
+    let stored = store.load("session-1").await.unwrap().unwrap();
+    assert_eq!(stored.record.version, 2);
+    assert_eq!(stored.context.chat_history.len(), 4);
+    assert_eq!(store.list().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_context_is_saved_after_each_completed_step() {
+    let store = Arc::new(MemoryContextStore::new());
+    let runtime = Runtime::new();
+
+    let workflow = Workflow::builder()
+        .with_context_store(store.clone(), "steps")
+        .step(note("one"))
+        .step(note("two"))
+        .step(note("three"))
+        .build();
+    runtime.execute(workflow).await;
+    assert_eq!(
+        store.load("steps").await.unwrap().unwrap().record.version,
+        3
+    );
+
+    // A failed step isn't saved
+    let workflow = Workflow::builder()
+        .with_context_store(store.clone(), "steps")
+        .step(note("four"))
+        .step(Box::new(TransformStep::pick(
+            "missing".to_string(),
+            "/nope",
+        )))
+        .step(note("five"))
+        .build();
+    let run = runtime.execute(workflow).await;
+    assert_eq!(run.state, WorkflowState::Failed);
+    assert_eq!(
+        store.load("steps").await.unwrap().unwrap().record.version,
+        4
+    );
+    assert!(store_events(&runtime).is_empty());
+}
+
+#[tokio::test]
+async fn test_concurrent_writer_is_detected() {
+    let store = Arc::new(MemoryContextStore::new());
+    let version = store.save("shared", &WorkflowContext::new()).await.unwrap();
+    assert_eq!(version, 1);
+
+    // Another writer saves the key while the run is between steps
+    let writer = store.clone();
+    let interloper = TryTransformStep::new("interloper".to_string(), move |data| {
+        let writer = writer.clone();
+        Box::pin(async move {
+            let mut theirs = WorkflowContext::new();
+            theirs.memory_set("owner", json!("other run"));
+            writer
+                .save("shared", &theirs)
+                .await
+                .map_err(|e| StepError::ExecutionFailed(e.to_string()))?;
+            Ok(data)
+        })
+    });
+    let runtime = Runtime::new();
+    let workflow = Workflow::builder()
+        .with_context_store(store.clone(), "shared")
+        .step(note("first"))
+        .step(Box::new(interloper))
+        .step(note("last"))
+        .build();
+    let run = runtime.execute(workflow).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+
+    // The run saved once, then found the other writer's version and stopped
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let events = store_events(&runtime);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["conflict"], true);
+    assert_eq!(events[0]["context_key"], "shared");
+    assert_eq!(
+        events[0]["error"],
+        "context 'shared' is at version 3, expected 2"
+    );
+    let stored = store.load("shared").await.unwrap().unwrap();
+    assert_eq!(stored.record.version, 3);
+    assert_eq!(
+        stored.context.memory_get("owner"),
+        Some(&json!("other run"))
+    );
+
+    // Versioned saves report the conflict to the caller
+    let error = store
+        .save_versioned("shared", &WorkflowContext::new(), Some(2))
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        ContextStoreError::VersionConflict {
+            expected: 2,
+            found: 3,
+            ..
+        }
+    ));
+    let error = store
+        .save_versioned("fresh", &WorkflowContext::new(), Some(1))
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        ContextStoreError::VersionConflict { found: 0, .. }
+    ));
+}
+
+#[tokio::test]
+async fn test_file_store_round_trip() {
+    let dir = std::env::temp_dir().join(format!("context-store-{}", uuid::Uuid::new_v4()));
+    let store = FileContextStore::new(&dir);
+    assert!(store.list().await.unwrap().is_empty());
+    assert!(store.load("user/42").await.unwrap().is_none());
+
+    let mut context = WorkflowContext::new();
+    context.append_messages(vec![llm::ChatMessage::user("remember me")]);
+    assert_eq!(
+        store
+            .save_versioned("user/42", &context, Some(0))
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(store.save("user/42", &context).await.unwrap(), 2);
+
+    let stored = store.load("user/42").await.unwrap().unwrap();
+    assert_eq!(stored.record.key, "user/42");
+    assert_eq!(stored.record.version, 2);
+    assert_eq!(stored.record.message_count, 1);
+    assert_eq!(stored.context.chat_history[0].content.text(), "remember me");
+    assert!(matches!(
+        store.save_versioned("user/42", &context, Some(1)).await,
+        Err(ContextStoreError::VersionConflict { found: 2, .. })
+    ));
+
+    let listed = store.list().await.unwrap();
+    assert_eq!(listed, vec![stored.record]);
+    store.delete("user/42").await.unwrap();
+    store.delete("user/42").await.unwrap();
+    assert!(store.load("user/42").await.unwrap().is_none());
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_sqlite_store_round_trip() {
+    let path = std::env::temp_dir().join(format!("context-store-{}.db", uuid::Uuid::new_v4()));
+    let store = SqliteContextStore::open(&path).unwrap();
+    assert!(store.list().await.unwrap().is_empty());
+    assert!(store.load("user/42").await.unwrap().is_none());
+
+    let mut context = WorkflowContext::new();
+    context.append_messages(vec![llm::ChatMessage::user("remember me")]);
+    assert_eq!(
+        store
+            .save_versioned("user/42", &context, Some(0))
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(store.save("user/42", &context).await.unwrap(), 2);
+
+    // Another connection to the file sees the same rows and versions
+    let other = SqliteContextStore::open(&path).unwrap();
+    let stored = other.load("user/42").await.unwrap().unwrap();
+    assert_eq!(stored.record.key, "user/42");
+    assert_eq!(stored.record.version, 2);
+    assert_eq!(stored.record.message_count, 1);
+    assert!(stored.record.token_estimate > 0);
+    assert_eq!(stored.context.chat_history[0].content.text(), "remember me");
+    assert!(matches!(
+        other.save_versioned("user/42", &context, Some(1)).await,
+        Err(ContextStoreError::VersionConflict { found: 2, .. })
+    ));
+
+    let listed = store.list().await.unwrap();
+    assert_eq!(listed, vec![stored.record]);
+    store.delete("user/42").await.unwrap();
+    store.delete("user/42").await.unwrap();
+    assert!(other.load("user/42").await.unwrap().is_none());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_runs_continue_a_conversation_stored_in_sqlite() {
+    let store = Arc::new(SqliteContextStore::open_in_memory().unwrap());
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_response("Nice to meet you, Ada.")
+            .with_response("Your name is Ada."),
+    );
+    let runtime = Runtime::new();
+
+    for input in ["Hi, I'm Ada", "What's my name?"] {
+        let workflow = Workflow::builder()
+            .with_context_store(store.clone(), "session-1")
+            .step(agent_step(client.clone()))
+            .initial_input(json!(input))
+            .build();
+        assert_eq!(
+            runtime.execute(workflow).await.state,
+            WorkflowState::Completed
+        );
+    }
+
+    // The second run started from the history the first one saved
+    assert_eq!(client.last_call().unwrap().messages.len(), 4);
+    let stored = store.load("session-1").await.unwrap().unwrap();
+    assert_eq!(stored.record.version, 2);
+    assert_eq!(stored.context.chat_history.len(), 4);
+    assert!(store_events(&runtime).is_empty());
+}
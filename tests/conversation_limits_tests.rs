@@ -0,0 +1,314 @@
+use agent_runtime::limits::{LimitEvent, LimitKind};
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::types::AgentError;
+use agent_runtime::*;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn turns(n: usize) -> Vec<ChatMessage> {
+    let mut history = Vec::new();
+    for i in 0..n {
+        history.push(ChatMessage::user(format!("question {}", i)));
+        history.push(ChatMessage::assistant(format!("answer {}", i)));
+    }
+    history.push(ChatMessage::user("next question"));
+    history
+}
+
+fn user_turns(history: &[ChatMessage]) -> usize {
+    history.iter().filter(|m| m.role == Role::User).count()
+}
+
+fn agent(mock: MockLlmClient) -> Agent {
+    Agent::new(
+        AgentConfig::builder("assistant")
+            .system_prompt("You help")
+            .build(),
+    )
+    .with_client(Arc::new(mock))
+}
+
+fn tool_agent(mock: MockLlmClient) -> Agent {
+    let mut registry = ToolRegistry::new();
+    registry.register(NativeTool::new(
+        "search",
+        "Search the index",
+        json!({"type": "object", "properties": {}}),
+        |_params| async move { Ok(ToolResult::success(json!({"hits": 3}), 1.0)) },
+    ));
+    Agent::new(
+        AgentConfig::builder("assistant")
+            .system_prompt("You help")
+            .tools(Arc::new(registry))
+            .disable_tool_loop_detection()
+            .build(),
+    )
+    .with_client(Arc::new(mock))
+}
+
+fn limit_events(events: &[Event]) -> Vec<LimitEvent> {
+    events
+        .iter()
+        .filter(|e| e.component_id == "system:conversation_limit")
+        .map(|e| serde_json::from_value(e.data["event"].clone()).unwrap())
+        .collect()
+}
+
+async fn settled(stream: &EventStream) -> Vec<Event> {
+    // Events are appended asynchronously
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    stream.all()
+}
+
+#[tokio::test]
+async fn test_turn_cap_rejects_before_calling_llm() {
+    let mock = MockLlmClient::with_responses_vec(vec!["never sent"]);
+    let agent = agent(mock);
+    let stream = EventStream::new();
+    let input = AgentInput::from_messages(turns(2))
+        .with_limits(ConversationLimits::new().with_max_turns(2));
+
+    let err = agent
+        .execute_with_events(input, Some(&stream))
+        .await
+        .unwrap_err();
+
+    let AgentError::LimitReached(exceeded) = &err else {
+        panic!("expected LimitReached, got {:?}", err);
+    };
+    assert_eq!(exceeded.kind, LimitKind::Turns);
+    assert_eq!(exceeded.limit, 2);
+    assert_eq!(exceeded.attempted, 3);
+    assert!(exceeded.user_message().contains("limit of 2 turns"));
+    assert!(err.to_string().starts_with("Conversation limit reached"));
+
+    let events = settled(&stream).await;
+    assert!(!events.iter().any(|e| e.scope == EventScope::LlmRequest));
+    let limits = limit_events(&events);
+    assert_eq!(limits.len(), 1);
+    assert_eq!(limits[0].action, LimitAction::Reject);
+    let failed = events
+        .iter()
+        .find(|e| e.scope == EventScope::Agent && e.event_type == EventType::Failed)
+        .unwrap();
+    assert_eq!(failed.data["user_message"], exceeded.user_message());
+}
+
+#[tokio::test]
+async fn test_turn_cap_drop_oldest_keeps_newest_turns() {
+    let agent = agent(MockLlmClient::with_responses_vec(vec!["ok"]));
+    let input = AgentInput::from_messages(turns(3)).with_limits(
+        ConversationLimits::new()
+            .with_max_turns(2)
+            .with_action(LimitAction::DropOldest),
+    );
+
+    let output = agent.execute(&input).await.unwrap();
+
+    let history = output.chat_history.unwrap();
+    assert_eq!(user_turns(&history), 2);
+    assert_eq!(history[0].role, Role::System);
+    assert_eq!(history[1].content, "question 2");
+    assert_eq!(output.metadata.limit_events.len(), 1);
+    assert_eq!(output.metadata.limit_events[0].removed, 4);
+}
+
+#[tokio::test]
+async fn test_turn_cap_prune_halves_history() {
+    let agent = agent(MockLlmClient::with_responses_vec(vec!["ok"]));
+    let input = AgentInput::from_messages(turns(4)).with_limits(
+        ConversationLimits::new()
+            .with_max_turns(4)
+            .with_action(LimitAction::Prune),
+    );
+
+    let output = agent.execute(&input).await.unwrap();
+
+    assert_eq!(user_turns(&output.chat_history.unwrap()), 2);
+    let event = &output.metadata.limit_events[0];
+    assert_eq!(event.action, LimitAction::Prune);
+    assert_eq!(event.attempted, 5);
+}
+
+#[tokio::test]
+async fn test_message_cap_on_context() {
+    let mut ctx =
+        WorkflowContext::new().with_limits(ConversationLimits::new().with_max_total_messages(4));
+    ctx.try_append_messages(vec![ChatMessage::system("sys"), ChatMessage::user("a")])
+        .unwrap();
+
+    // Rejected appends leave the history alone
+    let err = ctx
+        .try_append_messages(vec![
+            ChatMessage::assistant("b"),
+            ChatMessage::user("c"),
+            ChatMessage::assistant("d"),
+        ])
+        .unwrap_err();
+    assert_eq!(err.kind, LimitKind::TotalMessages);
+    assert_eq!(err.attempted, 5);
+    assert_eq!(ctx.history().len(), 2);
+
+    // Dropping the oldest keeps the system prompt
+    ctx.limits.action = LimitAction::DropOldest;
+    let events = ctx
+        .try_append_messages(vec![
+            ChatMessage::assistant("b"),
+            ChatMessage::user("c"),
+            ChatMessage::assistant("d"),
+        ])
+        .unwrap();
+    assert_eq!(events[0].removed, 1);
+    let contents: Vec<_> = ctx.history().iter().map(|m| m.content.text()).collect();
+    assert_eq!(contents, vec!["sys", "b", "c", "d"]);
+
+    // Pruning goes down to half the cap
+    ctx.limits.action = LimitAction::Prune;
+    ctx.try_append_messages(vec![ChatMessage::user("e")])
+        .unwrap();
+    let contents: Vec<_> = ctx.history().iter().map(|m| m.content.text()).collect();
+    assert_eq!(contents, vec!["sys", "e"]);
+
+    assert_eq!(ctx.limit_stats.rejected, 1);
+    assert_eq!(ctx.limit_stats.dropped, 1);
+    assert_eq!(ctx.limit_stats.pruned, 1);
+    assert_eq!(ctx.limit_stats.total(), 3);
+}
+
+#[tokio::test]
+async fn test_tool_message_cap_rejects() {
+    let mock = MockLlmClient::new()
+        .with_tool_call("search", json!({}))
+        .with_tool_call("search", json!({}))
+        .with_response("done");
+    let agent = tool_agent(mock);
+    let input = AgentInput::from_value(json!("look it up"))
+        .with_limits(ConversationLimits::new().with_max_tool_messages_per_execution(1));
+
+    let err = agent.execute(&input).await.unwrap_err();
+
+    let AgentError::LimitReached(exceeded) = err else {
+        panic!("expected LimitReached");
+    };
+    assert_eq!(exceeded.kind, LimitKind::ToolMessagesPerExecution);
+    assert_eq!(exceeded.attempted, 2);
+}
+
+#[tokio::test]
+async fn test_tool_message_cap_drops_oldest_exchange() {
+    let mock = MockLlmClient::new()
+        .with_tool_call("search", json!({}))
+        .with_tool_call("search", json!({}))
+        .with_tool_call("search", json!({}))
+        .with_response("done");
+    let agent = tool_agent(mock);
+    let stream = EventStream::new();
+    let input = AgentInput::from_value(json!("look it up")).with_limits(
+        ConversationLimits::new()
+            .with_max_tool_messages_per_execution(2)
+            .with_action(LimitAction::DropOldest),
+    );
+
+    let output = agent
+        .execute_with_events(input, Some(&stream))
+        .await
+        .unwrap();
+
+    let history = output.chat_history.unwrap();
+    assert_eq!(history.iter().filter(|m| m.role == Role::Tool).count(), 2);
+    assert_eq!(history.iter().filter(|m| m.tool_calls.is_some()).count(), 2);
+    assert_eq!(output.metadata.tool_calls_count, 3);
+    assert_eq!(output.metadata.limit_events.len(), 1);
+    assert_eq!(limit_events(&settled(&stream).await).len(), 1);
+}
+
+fn chained_workflow(
+    name: &str,
+    limits: Option<ConversationLimits>,
+    restored: Option<WorkflowContext>,
+) -> Workflow {
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec![
+        "one", "two", "three",
+    ]));
+    let mut builder = Workflow::builder().name(name.to_string());
+    if let Some(limits) = limits {
+        builder = builder.with_conversation_limits(limits);
+    }
+    if let Some(restored) = restored {
+        builder = builder.with_restored_context(restored);
+    }
+    for i in 0..3 {
+        let agent = Agent::new(
+            AgentConfig::builder(format!("agent{}", i))
+                .system_prompt("You help")
+                .build(),
+        )
+        .with_client(mock.clone());
+        builder = builder.add_step(Box::new(AgentStep::from_agent(
+            agent,
+            format!("agent{}", i),
+        )));
+    }
+    builder.initial_input(json!("start")).build()
+}
+
+#[tokio::test]
+async fn test_rejected_turn_fails_workflow_with_user_message() {
+    let workflow = chained_workflow(
+        "limited",
+        Some(ConversationLimits::new().with_max_turns(2)),
+        None,
+    );
+    let context = workflow.context().cloned().unwrap();
+
+    let runtime = Runtime::new();
+    let run = runtime.execute(workflow).await;
+
+    assert_eq!(run.state, WorkflowState::Failed);
+    assert_eq!(run.steps.len(), 3);
+    assert_eq!(run.steps[2].status, StepStatus::Failed);
+    let stats = context.read().unwrap().limit_stats.clone();
+    assert_eq!(stats.rejected, 1);
+    assert_eq!(stats.events[0].kind, LimitKind::Turns);
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let events = runtime.events_from_offset(0);
+    let failed = events
+        .iter()
+        .find(|e| e.scope == EventScope::Workflow && e.event_type == EventType::Failed)
+        .unwrap();
+    assert_eq!(failed.data["limit"]["kind"], "turns");
+    assert!(failed.data["user_message"]
+        .as_str()
+        .unwrap()
+        .contains("limit of 2 turns"));
+    assert_eq!(limit_events(&events).len(), 1);
+}
+
+#[tokio::test]
+async fn test_restored_context_keeps_caps() {
+    let checkpoint =
+        WorkflowContext::new().with_limits(ConversationLimits::new().with_max_turns(1));
+    let serialized = serde_json::to_string(&checkpoint).unwrap();
+    let restored: WorkflowContext = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(restored.limits, checkpoint.limits);
+
+    // The builder's caps only apply to fresh contexts
+    let workflow = chained_workflow(
+        "restored",
+        Some(ConversationLimits::new().with_max_turns(10)),
+        Some(restored),
+    );
+    let context = workflow.context().cloned().unwrap();
+
+    let run = Runtime::new().execute(workflow).await;
+
+    assert_eq!(run.state, WorkflowState::Failed);
+    assert_eq!(run.steps.len(), 2);
+    assert_eq!(run.steps[1].status, StepStatus::Failed);
+    let ctx = context.read().unwrap();
+    assert_eq!(ctx.limits.max_turns, Some(1));
+    assert_eq!(ctx.limit_stats.rejected, 1);
+    assert_eq!(user_turns(ctx.history()), 1);
+}
@@ -0,0 +1,199 @@
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::usage::UsageFilter;
+use agent_runtime::*;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn verdict(pass: bool, score: f64, critique: &str) -> String {
+    json!({ "pass": pass, "score": score, "critique": critique }).to_string()
+}
+
+fn mock(replies: &[&str]) -> Arc<MockLlmClient> {
+    Arc::new(MockLlmClient::with_responses_vec(replies.to_vec()))
+}
+
+fn workflow(writer: Arc<MockLlmClient>, critic: CriticConfig) -> Workflow {
+    let agent = Agent::new(
+        AgentConfig::builder("writer")
+            .system_prompt("Summarize the input in one sentence")
+            .build(),
+    )
+    .with_client(writer);
+    Workflow::builder()
+        .name("summary".to_string())
+        .step(Box::new(
+            AgentStep::from_agent(agent, "summarize".to_string()).with_critic(critic),
+        ))
+        .initial_input(json!("Otters hold hands while sleeping."))
+        .build()
+}
+
+fn runtime() -> Runtime {
+    Runtime::new().with_usage_ledger(UsageLedger::new())
+}
+
+/// Verdicts the critic reported through events, in order
+async fn evented_verdicts(runtime: &Runtime) -> Vec<bool> {
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .filter(|e| e.component_id == "writer:critic" && e.event_type == EventType::Completed)
+        .map(|e| e.data["accepted"].as_bool().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_passing_output_goes_through() {
+    let writer = mock(&["Otters sleep holding hands."]);
+    let critic = mock(&[&verdict(true, 0.9, "")]);
+    let runtime = runtime();
+
+    let run = runtime
+        .execute(workflow(writer.clone(), CriticConfig::new(critic.clone())))
+        .await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(
+        run.final_output.unwrap()["response"],
+        "Otters sleep holding hands."
+    );
+    let report = run.steps[0].critic.as_ref().unwrap();
+    assert!(report.passed);
+    assert_eq!(report.rounds, 0);
+    assert_eq!(report.verdicts.len(), 1);
+    assert_eq!(writer.call_count(), 1);
+
+    // The critic saw the instructions, the input and the candidate
+    let prompt = critic.last_call().unwrap().messages[0].content.to_string();
+    assert!(prompt.contains("Summarize the input in one sentence"));
+    assert!(prompt.contains("Otters hold hands while sleeping."));
+    assert!(prompt.contains("Otters sleep holding hands."));
+
+    assert_eq!(evented_verdicts(&runtime).await, vec![true]);
+}
+
+#[tokio::test]
+async fn test_critique_triggers_one_revision() {
+    let writer = mock(&[
+        "Otters are animals.",
+        "Sea otters hold hands so they don't drift apart while asleep.",
+    ]);
+    let critic = mock(&[
+        &verdict(false, 0.3, "Mention why they hold hands."),
+        &verdict(true, 0.85, ""),
+    ]);
+    let runtime = runtime();
+
+    let run = runtime
+        .execute(workflow(writer.clone(), CriticConfig::new(critic)))
+        .await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(
+        run.final_output.unwrap()["response"],
+        "Sea otters hold hands so they don't drift apart while asleep."
+    );
+    let report = run.steps[0].critic.as_ref().unwrap();
+    assert!(report.passed);
+    assert_eq!(report.rounds, 1);
+    let scores: Vec<f64> = report.verdicts.iter().map(|v| v.score).collect();
+    assert_eq!(scores, vec![0.3, 0.85]);
+
+    // The revision continued the agent's conversation with the critique
+    let revision = writer.last_call().unwrap();
+    let roles: Vec<_> = revision.messages.iter().map(|m| m.role.clone()).collect();
+    assert_eq!(
+        roles,
+        vec![
+            agent_runtime::llm::Role::System,
+            agent_runtime::llm::Role::User,
+            agent_runtime::llm::Role::Assistant,
+            agent_runtime::llm::Role::User,
+        ]
+    );
+    assert!(revision.messages[3]
+        .content
+        .text()
+        .contains("Mention why they hold hands."));
+
+    // Critic calls are accounted separately from the agent's own
+    let ledger = runtime.usage_ledger();
+    let critic_calls = ledger.records(&UsageFilter::new().role("critic"));
+    assert_eq!(critic_calls.len(), 2);
+    assert!(critic_calls.iter().all(|r| r.agent == "writer"));
+    assert_eq!(ledger.records(&UsageFilter::new().role("agent")).len(), 2);
+    assert_eq!(run.usage.llm_calls, 4);
+
+    assert_eq!(evented_verdicts(&runtime).await, vec![false, true]);
+}
+
+#[tokio::test]
+async fn test_exhausted_rounds_fail_with_last_critique() {
+    let writer = mock(&["Otters.", "Otters are cute."]);
+    let critic = mock(&[
+        &verdict(false, 0.2, "Too short."),
+        // Passing but under the threshold still rejects
+        &verdict(true, 0.5, "Still says nothing about sleeping."),
+    ]);
+    let runtime = runtime();
+
+    let run = runtime
+        .execute(workflow(
+            writer.clone(),
+            CriticConfig::new(critic).with_max_rounds(1),
+        ))
+        .await;
+
+    assert_eq!(run.state, WorkflowState::Failed);
+    assert_eq!(writer.call_count(), 2);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let failure = runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .find(|e| e.component_id == "summary:step:0" && e.event_type == EventType::Failed)
+        .unwrap();
+    assert_eq!(
+        failure.message.as_deref(),
+        Some(
+            "Execution failed: critic rejected the output after 1 revision round(s): \
+             Still says nothing about sleeping."
+        )
+    );
+    assert_eq!(evented_verdicts(&runtime).await, vec![false, false]);
+}
+
+#[tokio::test]
+async fn test_unchanged_output_reuses_verdict() {
+    let writer = mock(&["Otters.", "Otters."]);
+    let critic = mock(&[&verdict(false, 0.2, "Too short.")]);
+
+    let run = runtime()
+        .execute(workflow(writer, CriticConfig::new(critic.clone())))
+        .await;
+
+    assert_eq!(run.state, WorkflowState::Failed);
+    assert_eq!(critic.call_count(), 1);
+}
+
+#[tokio::test]
+async fn test_sampled_out_skips_review() {
+    let writer = mock(&["Otters."]);
+    let critic = mock(&[&verdict(false, 0.0, "Too short.")]);
+
+    let run = runtime()
+        .execute(workflow(
+            writer,
+            CriticConfig::new(critic.clone()).with_sample_rate(0.0),
+        ))
+        .await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    let report = run.steps[0].critic.as_ref().unwrap();
+    assert!(report.skipped);
+    assert!(report.verdicts.is_empty());
+    assert_eq!(critic.call_count(), 0);
+}
@@ -0,0 +1,294 @@
+/// Tests for embedding clients and vector search, against an in-process
+/// server
+use agent_runtime::llm::embeddings::{self, LlamaEmbeddingApi};
+use agent_runtime::llm::types::Role;
+use agent_runtime::llm::{
+    EmbeddingClient, LlamaEmbeddings, LlmError, MockLlmClient, OpenAIEmbeddings,
+};
+use agent_runtime::*;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+const TOPICS: [&str; 3] = ["refund", "shipping", "password"];
+
+/// How much `text` is about each topic
+fn vector(text: &str) -> Vec<f32> {
+    let text = text.to_lowercase();
+    TOPICS
+        .iter()
+        .map(|topic| text.matches(topic).count() as f32 + 0.1)
+        .collect()
+}
+
+/// The request bodies and auth headers the fake server has seen
+#[derive(Default)]
+struct Server {
+    requests: Vec<Value>,
+    auth: Vec<Option<String>>,
+}
+
+type Shared = Arc<Mutex<Server>>;
+
+fn record(shared: &Shared, headers: &HeaderMap, body: &Value) {
+    let mut server = shared.lock().unwrap();
+    server.requests.push(body.clone());
+    server.auth.push(
+        headers
+            .get("authorization")
+            .map(|value| value.to_str().unwrap().to_string()),
+    );
+}
+
+/// An OpenAI-style response, with the data in reverse order
+fn openai_style(inputs: &[Value]) -> Value {
+    let data: Vec<Value> = inputs
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(index, text)| json!({ "index": index, "embedding": vector(text.as_str().unwrap()) }))
+        .collect();
+    json!({
+        "object": "list",
+        "data": data,
+        "model": "embedder",
+        "usage": { "prompt_tokens": inputs.len() * 2, "total_tokens": inputs.len() * 2 },
+    })
+}
+
+/// Serves `/v1/embeddings` (OpenAI-style) and llama.cpp's native
+/// `/embedding`; `/v1/denied/embeddings` rejects the key
+async fn start_server() -> (String, Shared) {
+    let shared = Shared::default();
+    let openai = {
+        let shared = shared.clone();
+        move |headers: HeaderMap, Json(body): Json<Value>| async move {
+            record(&shared, &headers, &body);
+            Json(openai_style(body["input"].as_array().unwrap()))
+        }
+    };
+    let native = {
+        let shared = shared.clone();
+        move |headers: HeaderMap, Json(body): Json<Value>| async move {
+            record(&shared, &headers, &body);
+            let items: Vec<Value> = body["content"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .enumerate()
+                .map(|(index, text)| {
+                    json!({ "index": index, "embedding": [vector(text.as_str().unwrap())] })
+                })
+                .collect();
+            Json(Value::Array(items))
+        }
+    };
+    let app = Router::new()
+        .route("/v1/embeddings", post(openai))
+        .route("/embedding", post(native))
+        .route(
+            "/v1/denied/embeddings",
+            post(|| async { (StatusCode::UNAUTHORIZED, "invalid api key") }),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}", addr), shared)
+}
+
+fn texts(texts: &[&str]) -> Vec<String> {
+    texts.iter().map(|text| text.to_string()).collect()
+}
+
+#[tokio::test]
+async fn test_openai_batches_are_chunked_and_reassembled_in_order() {
+    let (base_url, shared) = start_server().await;
+    let client = OpenAIEmbeddings::new("sk-test", "embedder")
+        .with_base_url(format!("{}/v1/", base_url))
+        .with_dimensions(3)
+        .with_max_inputs_per_request(2);
+    assert_eq!(client.model(), "embedder");
+    assert_eq!(client.dimensions(), Some(3));
+
+    let inputs = texts(&["refund", "shipping", "password", "refund refund", "nothing"]);
+    let response = client.embed_with_usage(inputs.clone()).await.unwrap();
+    let expected: Vec<Vec<f32>> = inputs.iter().map(|text| vector(text)).collect();
+    assert_eq!(response.embeddings, expected);
+    assert_eq!(response.usage.prompt_tokens, 10);
+    assert_eq!(response.usage.total_tokens, 10);
+
+    {
+        let server = shared.lock().unwrap();
+        let sizes: Vec<usize> = server
+            .requests
+            .iter()
+            .map(|body| body["input"].as_array().unwrap().len())
+            .collect();
+        assert_eq!(sizes, vec![2, 2, 1]);
+        assert_eq!(server.requests[0]["model"], "embedder");
+        assert_eq!(server.requests[0]["dimensions"], 3);
+        assert!(server
+            .auth
+            .iter()
+            .all(|auth| auth.as_deref() == Some("Bearer sk-test")));
+    }
+
+    assert!(client.embed(Vec::new()).await.unwrap().is_empty());
+    assert_eq!(shared.lock().unwrap().requests.len(), 3);
+
+    let denied = OpenAIEmbeddings::new("sk-wrong", "embedder")
+        .with_base_url(format!("{}/v1/denied", base_url))
+        .embed(texts(&["refund"]))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&denied, LlmError::AuthenticationFailed(body) if body == "invalid api key"),
+        "{:?}",
+        denied
+    );
+}
+
+#[tokio::test]
+async fn test_llama_embeddings_through_both_endpoints() {
+    let (base_url, shared) = start_server().await;
+    let inputs = texts(&["shipping", "password reset", "refund"]);
+    let expected: Vec<Vec<f32>> = inputs.iter().map(|text| vector(text)).collect();
+
+    let compatible = LlamaEmbeddings::new(&base_url, "nomic").with_max_inputs_per_request(2);
+    assert_eq!(compatible.dimensions(), None);
+    let response = compatible.embed_with_usage(inputs.clone()).await.unwrap();
+    assert_eq!(response.embeddings, expected);
+    assert_eq!(response.usage.total_tokens, 6);
+    assert_eq!(compatible.dimensions(), Some(3));
+
+    let native =
+        LlamaEmbeddings::new(format!("{}/", base_url), "nomic").with_api(LlamaEmbeddingApi::Native);
+    let response = native.embed_with_usage(inputs.clone()).await.unwrap();
+    assert_eq!(response.embeddings, expected);
+    assert_eq!(response.usage.total_tokens, 0);
+
+    let server = shared.lock().unwrap();
+    assert_eq!(server.requests.len(), 3);
+    assert_eq!(server.requests[0]["model"], "nomic");
+    assert_eq!(server.requests[2]["content"], json!(inputs));
+    assert!(server.auth.iter().all(Option::is_none));
+}
+
+#[tokio::test]
+async fn test_client_from_config() {
+    let (base_url, shared) = start_server().await;
+    let mut config = LlmConfig {
+        llama: Some(LlamaConfig {
+            base_url: base_url.clone(),
+            insecure: false,
+            rate_limit: None,
+            default_headers: Default::default(),
+            proxy: None,
+            connect_timeout_ms: None,
+            request_timeout_ms: None,
+        }),
+        embedding: Some(EmbeddingConfig {
+            provider: "llama".to_string(),
+            model: "nomic".to_string(),
+            dimensions: None,
+            max_inputs_per_request: Some(1),
+            native_endpoint: true,
+        }),
+        ..Default::default()
+    };
+    let client = embeddings::from_config(&config).unwrap();
+    assert_eq!(client.model(), "nomic");
+    let vectors = client.embed(texts(&["refund", "shipping"])).await.unwrap();
+    assert_eq!(vectors, vec![vector("refund"), vector("shipping")]);
+    assert_eq!(shared.lock().unwrap().requests.len(), 2);
+
+    config.embedding.as_mut().unwrap().provider = "openai".to_string();
+    let error = embeddings::from_config(&config).err().unwrap();
+    assert_eq!(error.field.as_deref(), Some("llm.embedding.provider"));
+    assert_eq!(
+        error.message,
+        "Provider 'openai' has no [llm.openai] section"
+    );
+
+    config.embedding.as_mut().unwrap().provider = "cohere".to_string();
+    let error = embeddings::from_config(&config).err().unwrap();
+    assert_eq!(
+        error.message,
+        "Unknown embedding provider 'cohere', expected one of openai, llama"
+    );
+
+    let parsed: RuntimeConfig = toml::from_str(
+        r#"
+        [llm.llama]
+        base_url = "http://localhost:8080"
+        insecure = false
+
+        [llm.embedding]
+        provider = "llama"
+        model = "nomic-embed-text"
+        max_inputs_per_request = 0
+        "#,
+    )
+    .unwrap();
+    let error = embeddings::from_config(&parsed.llm).err().unwrap();
+    assert_eq!(
+        error.field.as_deref(),
+        Some("llm.embedding.max_inputs_per_request")
+    );
+}
+
+#[tokio::test]
+async fn test_agent_searches_the_store() {
+    let (base_url, _shared) = start_server().await;
+    let search = VectorSearchTool::new(
+        Arc::new(LlamaEmbeddings::new(&base_url, "nomic")),
+        Arc::new(VectorStore::new()),
+    )
+    .with_top_k(2);
+    search
+        .add_texts(texts(&[
+            "Refunds are issued within 5 days of a refund request.",
+            "Shipping takes 3 to 7 business days.",
+            "Reset your password from the login page.",
+        ]))
+        .await
+        .unwrap();
+    assert_eq!(search.store().len(), 3);
+
+    let mut registry = ToolRegistry::new();
+    search.register(&mut registry);
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("vector_search", json!({ "query": "How do refunds work?" }))
+            .with_response("Within 5 days."),
+    );
+    let output = Agent::new(
+        AgentConfig::builder("support")
+            .system_prompt("Answer from the knowledge base.")
+            .tools(Arc::new(registry))
+            .build(),
+    )
+    .with_client(client.clone())
+    .execute(&AgentInput::from_text("How long do refunds take?"))
+    .await
+    .unwrap();
+    assert_eq!(output.data["response"], "Within 5 days.");
+
+    let calls = client.get_calls();
+    assert_eq!(calls.len(), 2);
+    let result = calls[1]
+        .messages
+        .iter()
+        .find(|m| m.role == Role::Tool)
+        .unwrap();
+    let result: Value = serde_json::from_str(&result.content.text()).unwrap();
+    let results = result["results"].as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(
+        results[0]["text"],
+        "Refunds are issued within 5 days of a refund request."
+    );
+    assert!(results[0]["score"].as_f64().unwrap() > results[1]["score"].as_f64().unwrap());
+}
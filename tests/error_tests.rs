@@ -152,17 +152,16 @@ async fn test_tool_timeout() {
     assert!(result.is_ok(), "Should complete despite slow tool");
 }
 
-#[tokio::test]
-#[ignore] // TODO: Fix - currently errors instead of gracefully stopping
-async fn test_max_iterations_exceeded() {
-    // Test that agent stops after max iterations
+/// An agent whose LLM calls `test_tool` until its 5 iterations run out
+fn looping_agent(behavior: agent_runtime::MaxIterationsBehavior) -> (Agent, Arc<MockLlmClient>) {
     let mut mock_client = MockLlmClient::new();
 
-    // Add many tool calls to exceed max iterations
-    for _ in 0..20 {
+    // Tool calls up to the limit, then an answer
+    for _ in 0..5 {
         mock_client = mock_client.with_tool_call("test_tool", json!({}));
     }
     mock_client = mock_client.with_response("Final");
+    let mock_client = Arc::new(mock_client);
 
     let mut registry = ToolRegistry::new();
     registry.register(NativeTool::new("test_tool", "Test", json!({}), |_args| {
@@ -177,14 +176,60 @@ async fn test_max_iterations_exceeded() {
         .system_prompt("Test")
         .tools(Arc::new(registry))
         .max_tool_iterations(5) // Set low limit
+        .disable_tool_loop_detection()
+        .on_max_iterations(behavior)
         .build();
 
-    let agent = Agent::new(config).with_client(Arc::new(mock_client));
-    let input = AgentInput::from_text("test");
+    let agent = Agent::new(config).with_client(mock_client.clone());
+    (agent, mock_client)
+}
 
-    let result = agent.execute(&input).await;
+#[tokio::test]
+async fn test_max_iterations_exceeded() {
+    // Test that agent stops after max iterations, answering without tools
+    let (agent, mock_client) = looping_agent(agent_runtime::MaxIterationsBehavior::FinalAnswer);
+    let events = Arc::new(EventStream::new());
+
+    let result = agent
+        .execute_with_events(AgentInput::from_text("test"), Some(&events))
+        .await;
     // Should stop at max iterations
-    assert!(result.is_ok(), "Should stop at max iterations");
+    let output = result.expect("Should stop at max iterations");
+    assert!(output.metadata.iterations_exhausted);
+    assert_eq!(output.metadata.tool_calls_count, 5);
+
+    // Five calls with tools, then one without, told to wrap up
+    let calls = mock_client.get_calls();
+    assert_eq!(calls.len(), 6);
+    assert!(calls[..5].iter().all(|call| call.tools.is_some()));
+    let last = &calls[5];
+    assert!(last.tools.is_none());
+    let note = last.messages.last().unwrap();
+    assert_eq!(note.role, Role::System);
+    assert!(note.content.text().starts_with("Tool budget exhausted"));
+    assert_eq!(output.data["response"], "Final");
+
+    let exhausted = events
+        .all()
+        .into_iter()
+        .find(|e| e.component_id == "system:iterations_exhausted")
+        .expect("budget exhaustion is announced");
+    assert_eq!(exhausted.data["max_tool_iterations"], 5);
+    assert_eq!(exhausted.data["tool_calls"], 5);
+}
+
+#[tokio::test]
+async fn test_max_iterations_can_still_fail_hard() {
+    let (agent, mock_client) = looping_agent(agent_runtime::MaxIterationsBehavior::Error);
+
+    let error = agent
+        .execute(&AgentInput::from_text("test"))
+        .await
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("Maximum tool iterations (5) exceeded"));
+    assert_eq!(mock_client.call_count(), 5);
 }
 
 #[tokio::test]
@@ -220,6 +265,64 @@ async fn test_tool_returns_error_status() {
     assert!(result.is_ok(), "Should handle error status result");
 }
 
+#[tokio::test]
+async fn test_structured_tool_errors_reach_the_llm_and_events() {
+    let mock_client = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("lookup", json!({"id": 7}))
+            .with_response("No such record"),
+    );
+
+    let mut registry = ToolRegistry::new();
+    registry.register(NativeTool::new(
+        "lookup",
+        "Looks a record up",
+        json!({}),
+        |_args| {
+            Box::pin(async move {
+                Ok(ToolResult::failed(
+                    agent_runtime::ToolErrorDetail::new("not_found", "no record 7"),
+                    0.5,
+                ))
+            })
+        },
+    ));
+    let config = AgentConfig::builder("test_agent")
+        .tools(Arc::new(registry))
+        .build();
+    let agent = Agent::new(config).with_client(mock_client.clone());
+    let events = Arc::new(EventStream::new());
+
+    agent
+        .execute_with_events(AgentInput::from_text("Find 7"), Some(&events))
+        .await
+        .unwrap();
+
+    let sent = mock_client.get_calls()[1]
+        .messages
+        .iter()
+        .rev()
+        .find(|m| m.role == Role::Tool)
+        .unwrap()
+        .content
+        .clone();
+    assert_eq!(sent, "TOOL ERROR (not_found): no record 7");
+
+    let all = events.all();
+    assert!(!all
+        .iter()
+        .any(|e| e.scope == EventScope::Tool && e.event_type == EventType::Completed));
+    let failed = all
+        .iter()
+        .find(|e| e.scope == EventScope::Tool && e.event_type == EventType::Failed)
+        .unwrap();
+    assert_eq!(failed.data["status"], "error");
+    assert_eq!(
+        failed.data["error"],
+        json!({"code": "not_found", "message": "no record 7", "retryable": false})
+    );
+}
+
 #[tokio::test]
 async fn test_tool_not_found() {
     // Test calling a tool that doesn't exist
@@ -0,0 +1,271 @@
+/// Tests for scoring an agent on eval cases and comparing against a baseline
+use agent_runtime::agent::eval::{EvalCase, EvalConfig, EvalReport, EvalSuite, Expectation, Score};
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::*;
+use serde_json::json;
+use std::sync::Arc;
+
+fn agent(responses: Vec<&str>) -> Agent {
+    Agent::new(
+        AgentConfig::builder("support")
+            .system_prompt("You answer support questions.")
+            .build(),
+    )
+    .with_client(Arc::new(MockLlmClient::with_responses_vec(responses)))
+}
+
+fn cases() -> Vec<EvalCase> {
+    vec![
+        EvalCase::new("capital", "Capital of France?", Expectation::exact("Paris")),
+        EvalCase::new(
+            "refund",
+            "Can I get a refund?",
+            Expectation::contains("within 30 days"),
+        ),
+        EvalCase::new(
+            "order",
+            "Look up order 42 as JSON",
+            Expectation::json_subset(json!({"order": 42, "items": [{"sku": "B7"}]})),
+        ),
+        EvalCase::new(
+            "tracking",
+            "Tracking number?",
+            Expectation::regex(r"^1Z[0-9A-Z]{6}$"),
+        ),
+        EvalCase::new(
+            "brief",
+            "Summarize our policy",
+            Expectation::custom(|output| {
+                let words = output.data["response"]
+                    .as_str()
+                    .unwrap()
+                    .split_whitespace()
+                    .count();
+                Score::graded(1.0 - words as f64 / 20.0, 0.5)
+                    .with_reason(format!("{} words", words))
+            }),
+        ),
+    ]
+}
+
+#[tokio::test]
+async fn test_each_expectation_is_scored() {
+    let agent = agent(vec![
+        " Paris\n",
+        "Refunds are accepted within 30 days of delivery.",
+        "```json\n{\"order\": 42, \"status\": \"shipped\", \"items\": [{\"sku\": \"A1\"}, {\"sku\": \"B7\"}]}\n```",
+        "It is 1Z99X.",
+        "Returns are free and refunds take five business days to reach your card.",
+    ]);
+
+    let report = EvalSuite::run(&agent, cases(), EvalConfig::default()).await;
+
+    let passed: Vec<bool> = report.cases.iter().map(|c| c.passed).collect();
+    assert_eq!(passed, [true, true, true, false, false]);
+    assert_eq!(report.runs, 5);
+    assert_eq!(report.passed_runs, 3);
+    assert!((report.accuracy - 0.6).abs() < 1e-9);
+    assert_eq!(report.errors, 0);
+
+    let tracking = &report.case("tracking").unwrap().runs[0];
+    assert_eq!(tracking.response, "It is 1Z99X.");
+    assert_eq!(
+        tracking.score.reason.as_deref(),
+        Some("no match for /^1Z[0-9A-Z]{6}$/")
+    );
+    let brief = &report.case("brief").unwrap().runs[0].score;
+    assert!((brief.value - 0.35).abs() < 1e-9);
+    assert_eq!(brief.reason.as_deref(), Some("13 words"));
+
+    // Each run's tokens, as the mock reports them
+    assert_eq!(report.prompt_tokens, 50);
+    assert_eq!(report.completion_tokens, 25);
+    assert!(report.latency_p50_ms <= report.latency_p99_ms);
+}
+
+#[tokio::test]
+async fn test_repeats_give_a_pass_rate() {
+    let agent = agent(vec!["Paris", "Lyon", "Paris", "Paris"]);
+    let cases = vec![EvalCase::new(
+        "capital",
+        "Capital of France?",
+        Expectation::exact("Paris"),
+    )];
+
+    let report = EvalSuite::run(
+        &agent,
+        cases,
+        EvalConfig {
+            concurrency: 1,
+            repeats: 4,
+        },
+    )
+    .await;
+
+    let capital = report.case("capital").unwrap();
+    assert!(!capital.passed);
+    assert_eq!(capital.pass_rate, 0.75);
+    assert_eq!(capital.mean_score, 0.75);
+    let repeats: Vec<usize> = capital.runs.iter().map(|r| r.repeat).collect();
+    assert_eq!(repeats, [0, 1, 2, 3]);
+    assert_eq!(capital.runs[1].response, "Lyon");
+}
+
+#[tokio::test]
+async fn test_judge_scores_from_zero_to_one() {
+    let judge = Arc::new(MockLlmClient::with_responses_vec(vec![
+        "0.8\nMentions the deadline and the form.",
+        "Score: 0.2 - misses the deadline",
+        "Looks good to me",
+    ]));
+    let rubric = "Mentions the 30 day deadline and the returns form";
+    let agent = agent(vec![
+        "Fill in the returns form within 30 days.",
+        "Just send it back.",
+        "Send it back within 30 days.",
+    ]);
+    let cases = ["good", "poor", "unscored"]
+        .into_iter()
+        .map(|name| {
+            EvalCase::new(
+                name,
+                "How do I return a jacket?",
+                Expectation::judge(judge.clone(), rubric),
+            )
+        })
+        .collect();
+
+    let report = EvalSuite::run(&agent, cases, EvalConfig::default()).await;
+
+    let good = &report.case("good").unwrap().runs[0].score;
+    assert!(good.passed);
+    assert_eq!(good.value, 0.8);
+    assert_eq!(
+        good.reason.as_deref(),
+        Some("Mentions the deadline and the form.")
+    );
+    let poor = &report.case("poor").unwrap().runs[0].score;
+    assert!(!poor.passed);
+    assert_eq!(poor.value, 0.2);
+
+    let unscored = &report.case("unscored").unwrap().runs[0];
+    assert!(!unscored.score.passed);
+    assert!(unscored
+        .error
+        .as_ref()
+        .unwrap()
+        .starts_with("judge gave no score"));
+    assert_eq!(report.errors, 1);
+
+    // The judge sees the task, the rubric and the response
+    let asked = judge.get_calls()[0].messages[1].content.text().into_owned();
+    assert!(asked.contains("How do I return a jacket?"));
+    assert!(asked.contains(rubric));
+    assert!(asked.contains("Fill in the returns form"));
+}
+
+#[tokio::test]
+async fn test_failed_executions_count_as_errors() {
+    let agent = Agent::new(AgentConfig::builder("support").build())
+        .with_client(Arc::new(MockLlmClient::new().error_on_call(0)));
+    let cases = vec![EvalCase::new(
+        "capital",
+        "Capital of France?",
+        Expectation::exact("Paris"),
+    )];
+
+    let report = EvalSuite::run(&agent, cases, EvalConfig::default()).await;
+
+    let run = &report.case("capital").unwrap().runs[0];
+    assert!(!run.score.passed);
+    assert!(run.error.is_some());
+    assert_eq!(report.errors, 1);
+    assert_eq!(report.accuracy, 0.0);
+}
+
+#[tokio::test]
+async fn test_concurrent_runs_keep_case_order() {
+    let agent = agent(vec!["Paris"; 6]);
+    let cases = (0..3)
+        .map(|i| {
+            EvalCase::new(
+                format!("case {}", i),
+                "Capital of France?",
+                Expectation::exact("Paris"),
+            )
+        })
+        .collect();
+
+    let report = EvalSuite::run(
+        &agent,
+        cases,
+        EvalConfig {
+            concurrency: 4,
+            repeats: 2,
+        },
+    )
+    .await;
+
+    let names: Vec<&str> = report.cases.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["case 0", "case 1", "case 2"]);
+    assert!(report.cases.iter().all(|c| c.passed && c.runs.len() == 2));
+    assert_eq!(report.accuracy, 1.0);
+}
+
+#[tokio::test]
+async fn test_compare_with_a_stored_baseline() {
+    let baseline = EvalSuite::run(
+        &agent(vec![
+            "Paris",
+            "Refunds are accepted within 30 days of delivery.",
+            "{\"order\": 42, \"items\": [{\"sku\": \"B7\"}]}",
+            "1Z99X",
+            "Refunds take five days.",
+        ]),
+        cases(),
+        EvalConfig::default(),
+    )
+    .await;
+    // As CI would keep it between runs
+    let stored = serde_json::to_string_pretty(&baseline).unwrap();
+    let baseline: EvalReport = serde_json::from_str(&stored).unwrap();
+
+    // The new prompt fixes tracking but breaks refunds; brief is dropped
+    // and a case added
+    let mut cases = cases();
+    cases.pop();
+    cases.push(EvalCase::new(
+        "greeting",
+        "Hi",
+        Expectation::contains("Hello"),
+    ));
+    let current = EvalSuite::run(
+        &agent(vec![
+            "Paris",
+            "Refunds are accepted.",
+            "{\"order\": 42, \"items\": [{\"sku\": \"B7\"}]}",
+            "1Z99X4YZ",
+            "Hello! How can I help?",
+        ]),
+        cases,
+        EvalConfig::default(),
+    )
+    .await;
+
+    let diff = current.compare(&baseline);
+    assert!(diff.has_regressions());
+    let regressed: Vec<&str> = diff.regressions.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(regressed, ["refund"]);
+    assert!(diff.regressions[0].was_passed && !diff.regressions[0].passed);
+    let improved: Vec<&str> = diff.improvements.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(improved, ["tracking"]);
+    assert_eq!(diff.added, ["greeting"]);
+    assert_eq!(diff.removed, ["brief"]);
+    assert_eq!(diff.tokens_delta, 0);
+
+    let summary = diff.to_markdown();
+    assert!(summary.contains("- Regressed: refund (pass rate 100% -> 0%"));
+    assert!(summary.contains("- Improved: tracking"));
+
+    assert!(!current.compare(&current).has_regressions());
+}
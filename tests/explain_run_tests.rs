@@ -0,0 +1,290 @@
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::runtime::explain::{Audience, ExplainOptions, NarrativeSource, RunDigest};
+use agent_runtime::workflow::{WorkflowRun, WorkflowStepRecord};
+use agent_runtime::*;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn tool_agent(mock: MockLlmClient) -> Agent {
+    let mut registry = ToolRegistry::new();
+    registry.register(NativeTool::new(
+        "search",
+        "Search the index",
+        json!({"type": "object", "properties": {}}),
+        |_params| async move { Ok(ToolResult::success(json!({"hits": 3}), 1.0)) },
+    ));
+    Agent::new(
+        AgentConfig::builder("researcher")
+            .system_prompt("You help")
+            .tools(Arc::new(registry))
+            .build(),
+    )
+    .with_client(Arc::new(mock))
+}
+
+async fn run(mock: MockLlmClient) -> (WorkflowRun, Vec<Event>) {
+    let workflow = Workflow::builder()
+        .name("research".to_string())
+        .add_step(Box::new(AgentStep::from_agent(
+            tool_agent(mock),
+            "researcher".to_string(),
+        )))
+        .initial_input(json!("find rust crates"))
+        .build();
+    let runtime = Runtime::new();
+    let run = runtime.execute(workflow).await;
+    // Events are appended asynchronously
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    (run, runtime.events_from_offset(0))
+}
+
+fn searching_mock() -> MockLlmClient {
+    MockLlmClient::new()
+        .with_tool_call("search", json!({"q": "rust crates"}))
+        .with_response("Found 3 crates")
+}
+
+#[tokio::test]
+async fn test_template_narrative_for_successful_run() {
+    let (run, events) = run(searching_mock()).await;
+
+    let explanation =
+        Runtime::explain_run(&run, &events, ExplainOptions::new().with_price(2.0)).await;
+
+    assert_eq!(explanation.source, NarrativeSource::Template);
+    let digest = &explanation.digest;
+    assert_eq!(digest.state, WorkflowState::Completed);
+    assert_eq!(digest.step_count, 1);
+    assert_eq!(digest.tools.total_calls, 1);
+    assert_eq!(digest.tools.by_tool[0].tool, "search");
+    assert_eq!(
+        digest.tools.notable_calls[0].arguments,
+        "{\"q\":\"rust crates\"}"
+    );
+    assert_eq!(
+        digest.tools.notable_calls[0].agent.as_deref(),
+        Some("researcher")
+    );
+    assert_eq!(digest.llm.requests, 2);
+    assert_eq!(digest.llm.total_tokens, 30);
+    assert_eq!(digest.llm.estimated_cost_usd, Some(0.06));
+    assert!(digest.failures.is_empty());
+    assert_eq!(digest.final_output.as_deref(), Some("Found 3 crates"));
+
+    let paragraphs: Vec<&str> = explanation.narrative.split("\n\n").collect();
+    assert_eq!(paragraphs.len(), 3);
+    assert!(paragraphs[0].starts_with(&format!(
+        "Workflow `{}` completed successfully after 1 step",
+        run.workflow_id
+    )));
+    assert!(paragraphs[0].contains("researcher (Agent)"));
+    assert!(paragraphs[0].ends_with("\"Found 3 crates\""));
+    assert!(paragraphs[1].starts_with("Agents made 1 tool call across 1 tool: search (1×)."));
+    assert!(paragraphs[1].contains("search({\"q\":\"rust crates\"})"));
+    assert!(paragraphs[2].contains("2 LLM requests used 30 tokens (20 prompt, 10 completion)"));
+    assert!(paragraphs[2].contains("$0.0600"));
+    assert!(paragraphs[2].ends_with("Nothing failed."));
+}
+
+#[tokio::test]
+async fn test_non_technical_template_avoids_jargon() {
+    let (run, events) = run(searching_mock()).await;
+
+    let explanation = Runtime::explain_run(
+        &run,
+        &events,
+        ExplainOptions::new().audience(Audience::NonTechnical),
+    )
+    .await;
+
+    let narrative = &explanation.narrative;
+    assert_eq!(narrative.split("\n\n").count(), 3);
+    assert!(narrative.starts_with("The workflow went through 1 stage and completed successfully."));
+    assert!(narrative.contains("used one tool 1 time in total (search)"));
+    for jargon in ["`", "{", "token", "LLM"] {
+        assert!(
+            !narrative.contains(jargon),
+            "found {:?} in {}",
+            jargon,
+            narrative
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_template_narrative_reports_failures() {
+    let (run, events) = run(searching_mock().error_on_call(1)).await;
+
+    let explanation = Runtime::explain_run(&run, &events, ExplainOptions::new()).await;
+
+    let digest = &explanation.digest;
+    assert_eq!(digest.state, WorkflowState::Failed);
+    assert!(digest.steps[0].failed);
+    assert_eq!(digest.steps[0].name, "researcher");
+    assert_eq!(digest.llm.failed_requests, 1);
+    assert!(digest
+        .failures
+        .iter()
+        .any(|f| f.scope == EventScope::Workflow));
+
+    let paragraphs: Vec<&str> = explanation.narrative.split("\n\n").collect();
+    assert!(paragraphs[0].contains(" failed after 1 step"));
+    assert!(
+        paragraphs[2].contains("failures recorded; the first was LlmRequest `researcher:llm:2`")
+    );
+}
+
+#[tokio::test]
+async fn test_llm_mode_is_fed_the_digest() {
+    let (run, events) = run(searching_mock()).await;
+    let writer = Arc::new(MockLlmClient::with_responses_vec(vec![
+        "The run searched for crates.",
+    ]));
+
+    let explanation = Runtime::explain_run(
+        &run,
+        &events,
+        ExplainOptions::new()
+            .with_llm(writer.clone())
+            .audience(Audience::NonTechnical)
+            .max_tokens(250),
+    )
+    .await;
+
+    assert_eq!(explanation.narrative, "The run searched for crates.");
+    assert_eq!(
+        explanation.source,
+        NarrativeSource::Llm {
+            model: "mock-model".to_string()
+        }
+    );
+
+    let request = writer.last_call().unwrap();
+    assert_eq!(writer.call_count(), 1);
+    assert_eq!(request.max_tokens, Some(250));
+    assert_eq!(request.messages[0].role, Role::System);
+    assert!(request.messages[0].content.text().contains("not engineers"));
+    let sent: RunDigest = serde_json::from_str(&request.messages[1].content.text()).unwrap();
+    assert_eq!(sent, explanation.digest);
+}
+
+#[tokio::test]
+async fn test_llm_failure_falls_back_to_template() {
+    let (run, events) = run(searching_mock()).await;
+    let writer = Arc::new(MockLlmClient::with_responses_vec(vec!["unused"]).error_on_call(0));
+
+    let explanation =
+        Runtime::explain_run(&run, &events, ExplainOptions::new().with_llm(writer)).await;
+
+    assert!(matches!(
+        explanation.source,
+        NarrativeSource::TemplateFallback { .. }
+    ));
+    assert!(explanation.narrative.starts_with("Workflow `"));
+}
+
+fn event(
+    scope: EventScope,
+    event_type: EventType,
+    component: &str,
+    data: serde_json::Value,
+) -> Event {
+    let status = match event_type {
+        EventType::Failed => ComponentStatus::Failed,
+        EventType::Completed => ComponentStatus::Completed,
+        _ => ComponentStatus::Running,
+    };
+    let message = (event_type == EventType::Failed).then(|| "x".repeat(2_000));
+    Event::new(
+        0,
+        scope,
+        event_type,
+        component.to_string(),
+        status,
+        "big".to_string(),
+        message,
+        data,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_digest_size_is_bounded() {
+    let mut events = vec![event(
+        EventScope::Workflow,
+        EventType::Started,
+        "big",
+        json!({}),
+    )];
+    for i in 0..500 {
+        let tool = format!("tool_{}", i % 20);
+        let data = json!({"agent": "worker", "tool_call_id": "call_0"});
+        let mut started = data.clone();
+        started["arguments"] = json!(format!("{{\"payload\": \"{}\"}}", "y".repeat(1_000)));
+        events.push(event(EventScope::Tool, EventType::Started, &tool, started));
+        let mut finished = data;
+        finished["duration_ms"] = json!(i as f64);
+        let outcome = if i % 25 == 0 {
+            EventType::Failed
+        } else {
+            EventType::Completed
+        };
+        events.push(event(EventScope::Tool, outcome, &tool, finished));
+    }
+    let run = WorkflowRun {
+        workflow_id: "big".to_string(),
+        run_id: "run_big".to_string(),
+        state: WorkflowState::Completed,
+        steps: (0..100)
+            .map(|i| WorkflowStepRecord {
+                step_index: i,
+                step_name: format!("step_{}", i),
+                step_type: "Agent".to_string(),
+                input: json!({}),
+                output: Some(json!({"response": "z".repeat(10_000)})),
+                execution_time_ms: Some(i as u64),
+                replayed: false,
+                critic: None,
+                status: StepStatus::Succeeded,
+                error: None,
+                handoffs: None,
+                started_at: None,
+                finished_at: None,
+                artifacts: Vec::new(),
+            })
+            .collect(),
+        final_output: Some(json!({"response": "z".repeat(10_000)})),
+        parent_workflow_id: None,
+        artifacts: vec![],
+        pii_findings: None,
+        trace: None,
+        usage: Default::default(),
+        usage_breakdown: Default::default(),
+        rerun_of: None,
+        failure: None,
+        approvals: Vec::new(),
+        sub_workflows: Vec::new(),
+    };
+
+    let options = ExplainOptions::new();
+    let explanation = Runtime::explain_run(&run, &events, options.clone()).await;
+    let digest = &explanation.digest;
+
+    assert_eq!(digest.tools.total_calls, 500);
+    assert_eq!(digest.tools.failed_calls, 20);
+    assert_eq!(digest.tools.notable_calls.len(), options.max_tool_calls);
+    assert_eq!(digest.tools.omitted_calls, 500 - options.max_tool_calls);
+    assert_eq!(digest.tools.by_tool.len(), options.max_tool_calls);
+    // Failures are the most notable calls
+    assert!(digest.tools.notable_calls.iter().all(|c| c.error.is_some()));
+    assert_eq!(digest.steps.len(), options.max_steps);
+    assert_eq!(digest.steps[0].name, "step_99");
+    assert_eq!(digest.step_count, 100);
+    assert_eq!(digest.failures.len(), options.max_failures);
+    assert_eq!(digest.omitted_failures, 20 - options.max_failures);
+
+    let size = serde_json::to_string(digest).unwrap().len();
+    assert!(size < 8_000, "digest is {} bytes", size);
+    assert!(explanation.narrative.len() < 2_000);
+}
@@ -0,0 +1,266 @@
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::runtime::Runtime;
+use agent_runtime::workflow::step::{ExecutionContext, StepInputMetadata, StepOutputMetadata};
+use agent_runtime::workflow::{StepError, StepInput, StepOutput, StepResult, StepType};
+use agent_runtime::*;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Doubles a number after a delay that shrinks as the number grows, so
+/// later items finish first
+struct Doubler {
+    fail_on: Vec<i64>,
+    runs: Arc<AtomicUsize>,
+}
+
+fn doubler(fail_on: &[i64]) -> (Box<dyn Step>, Arc<AtomicUsize>) {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let step = Doubler {
+        fail_on: fail_on.to_vec(),
+        runs: runs.clone(),
+    };
+    (Box::new(step), runs)
+}
+
+#[async_trait]
+impl Step for Doubler {
+    async fn execute_with_context(
+        &self,
+        input: StepInput,
+        _ctx: ExecutionContext<'_>,
+    ) -> StepResult {
+        self.runs.fetch_add(1, Ordering::SeqCst);
+        let n = input.data.as_i64().unwrap_or_default();
+        tokio::time::sleep(Duration::from_millis(10 * (10 - n.min(10)) as u64)).await;
+        if self.fail_on.contains(&n) {
+            return Err(StepError::ExecutionFailed(format!("{} is unlucky", n)));
+        }
+        Ok(StepOutput {
+            data: json!({ "value": n * 2, "item_index": input.metadata.item_index }),
+            metadata: StepOutputMetadata {
+                step_name: "double".to_string(),
+                step_type: StepType::Custom("doubler".into()),
+                execution_time_ms: 0,
+                critic: None,
+                iterations_run: None,
+                policy: None,
+                item_errors: None,
+                handoffs: None,
+            },
+        })
+    }
+
+    fn name(&self) -> &str {
+        "double"
+    }
+
+    fn step_type(&self) -> StepType {
+        StepType::Custom("doubler".into())
+    }
+}
+
+fn workflow(step: ForEachStep, input: Value) -> Workflow {
+    Workflow::builder()
+        .name("batch".to_string())
+        .step(Box::new(step))
+        .initial_input(input)
+        .build()
+}
+
+fn input(data: Value) -> StepInput {
+    StepInput {
+        data,
+        metadata: StepInputMetadata {
+            step_index: 0,
+            previous_step: None,
+            workflow_id: "batch".to_string(),
+            run_id: "run_1".to_string(),
+            item_index: None,
+        },
+        workflow_context: None,
+    }
+}
+
+/// (component_id, event type) of every item event
+async fn item_events(runtime: &Runtime) -> Vec<(String, EventType)> {
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .filter(|e| e.scope == EventScope::WorkflowStep && e.component_id.contains(":item:"))
+        .map(|e| (e.component_id, e.event_type))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_items_run_with_bounded_concurrency() {
+    // Every item gets its own agent, which calls a tool that records how
+    // many calls are in flight
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+    let calls = Arc::new(AtomicUsize::new(0));
+    let mut registry = ToolRegistry::new();
+    {
+        let (in_flight, peak, calls) = (in_flight.clone(), peak.clone(), calls.clone());
+        registry.register(NativeTool::new(
+            "inspect",
+            "Inspect a document",
+            json!({"type": "object", "properties": {}}),
+            move |_params| {
+                let (in_flight, peak, calls) = (in_flight.clone(), peak.clone(), calls.clone());
+                async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    Ok(ToolResult::success(json!({"ok": true}), 50.0))
+                }
+            },
+        ));
+    }
+    let tools = Arc::new(registry);
+
+    let step = ForEachStep::from_factory(
+        "review_all".to_string(),
+        move || {
+            let config = AgentConfig::builder("reviewer")
+                .system_prompt("Review the document")
+                .tools(tools.clone())
+                .build();
+            let mock = MockLlmClient::new()
+                .with_tool_call("inspect", json!({}))
+                .with_response("reviewed");
+            let agent = Agent::new(config).with_client(Arc::new(mock));
+            Box::new(AgentStep::from_agent(agent, "review".to_string()))
+        },
+        3,
+    );
+    let documents: Vec<Value> = (0..10).map(|i| json!(format!("document {}", i))).collect();
+
+    let runtime = Runtime::new();
+    let run = runtime.execute(workflow(step, json!(documents))).await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(calls.load(Ordering::SeqCst), 10);
+    assert_eq!(peak.load(Ordering::SeqCst), 3);
+    let output = run.final_output.unwrap();
+    let outputs = output.as_array().unwrap();
+    assert_eq!(outputs.len(), 10);
+    assert!(outputs.iter().all(|o| o["response"] == "reviewed"));
+    assert_eq!(run.steps[0].step_type, "ForEach");
+}
+
+#[tokio::test]
+async fn test_outputs_keep_input_order() {
+    let (double, _) = doubler(&[]);
+    let step = ForEachStep::new("double_all".to_string(), double, 4).with_pointer("/numbers");
+
+    let runtime = Runtime::new();
+    let run = runtime
+        .execute(workflow(
+            step,
+            json!({ "numbers": [1, 2, 3, 4, 5, 6, 7, 8, 9, 10] }),
+        ))
+        .await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    let expected: Vec<Value> = (0..10)
+        .map(|i| json!({ "value": (i + 1) * 2, "item_index": i }))
+        .collect();
+    assert_eq!(run.final_output.unwrap(), json!(expected));
+
+    // Each item has started and completed events carrying its index
+    let events = item_events(&runtime).await;
+    for i in 0..10 {
+        let id = format!("batch:step:0:item:{}", i);
+        assert!(events.contains(&(id.clone(), EventType::Started)));
+        assert!(events.contains(&(id, EventType::Completed)));
+    }
+}
+
+#[tokio::test]
+async fn test_fail_fast_stops_at_the_first_failure() {
+    let (double, runs) = doubler(&[3]);
+    let step = ForEachStep::new("double_all".to_string(), double, 2);
+
+    let runtime = Runtime::new();
+    let numbers: Vec<i64> = (0..10).collect();
+    let run = runtime.execute(workflow(step, json!(numbers))).await;
+
+    assert_eq!(run.state, WorkflowState::Failed);
+    let failure = run.failure.unwrap();
+    assert_eq!(failure.step_name, "double_all");
+    assert!(failure.error.to_string().contains("3 is unlucky"));
+    // Items after the failure were never started
+    assert!(runs.load(Ordering::SeqCst) < 10);
+
+    let events = item_events(&runtime).await;
+    assert!(events.contains(&("batch:step:0:item:3".to_string(), EventType::Failed)));
+    assert!(!events.iter().any(|(id, _)| id == "batch:step:0:item:9"));
+}
+
+#[tokio::test]
+async fn test_collect_errors_leaves_nulls() {
+    let (double, runs) = doubler(&[2, 5]);
+    let step = ForEachStep::new("double_all".to_string(), double, 3)
+        .with_failure_mode(ForEachFailureMode::CollectErrors);
+
+    let output = step
+        .execute(input(json!([0, 1, 2, 3, 4, 5, 6])))
+        .await
+        .unwrap();
+
+    assert_eq!(runs.load(Ordering::SeqCst), 7);
+    let values: Vec<Value> = output
+        .data
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|o| o["value"].clone())
+        .collect();
+    assert_eq!(
+        values,
+        vec![
+            json!(0),
+            json!(2),
+            Value::Null,
+            json!(6),
+            json!(8),
+            Value::Null,
+            json!(12)
+        ]
+    );
+    assert!(output.data[2].is_null());
+
+    let errors = output.metadata.item_errors.unwrap();
+    assert_eq!(errors.total, 7);
+    let labels: Vec<&str> = errors.failures.iter().map(|f| f.label.as_str()).collect();
+    assert_eq!(labels, vec!["2", "5"]);
+    assert_eq!(
+        errors.to_string(),
+        "2 of 7 items failed: 2: Execution failed: 2 is unlucky; 5: Execution failed: 5 is unlucky"
+    );
+}
+
+#[tokio::test]
+async fn test_input_must_be_an_array() {
+    let (double, _) = doubler(&[]);
+    let step = ForEachStep::new("double_all".to_string(), double, 2).with_pointer("/numbers");
+
+    let error = step
+        .execute(input(json!({ "numbers": "1, 2, 3" })))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, StepError::InvalidInput(_)));
+    assert!(error
+        .to_string()
+        .contains("needs an array at /numbers, got a string"));
+
+    let error = step.execute(input(json!({}))).await.unwrap_err();
+    assert!(error.to_string().contains("nothing at /numbers"));
+}
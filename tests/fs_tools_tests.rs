@@ -0,0 +1,321 @@
+/// Tests for the built-in filesystem tools and their sandbox
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::prelude::TypesToolError as ToolError;
+use agent_runtime::*;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// A fresh sandbox holding `notes/todo.md`, plus a file next to it that the
+/// tools must not reach
+fn sandbox() -> (PathBuf, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("fs-tools-{}", uuid::Uuid::new_v4()));
+    let root = dir.join("root");
+    std::fs::create_dir_all(root.join("notes")).unwrap();
+    std::fs::write(root.join("notes/todo.md"), "- ship it\n- test it\n").unwrap();
+    std::fs::write(root.join("readme.txt"), "hello sandbox").unwrap();
+    std::fs::write(dir.join("secret.txt"), "top secret").unwrap();
+    (root, dir)
+}
+
+fn registry(tools: FsTools) -> ToolRegistry {
+    let mut registry = ToolRegistry::new();
+    tools.register(&mut registry);
+    registry
+}
+
+fn params(value: Value) -> HashMap<String, Value> {
+    serde_json::from_value(value).unwrap()
+}
+
+async fn call(registry: &ToolRegistry, tool: &str, args: Value) -> Result<Value, ToolError> {
+    registry
+        .call_tool(tool, params(args))
+        .await
+        .map(|result| result.output)
+}
+
+#[tokio::test]
+async fn test_paths_outside_the_root_are_refused() {
+    let (root, dir) = sandbox();
+    let registry = registry(FsTools::new(&root));
+    let secret = dir.join("secret.txt").display().to_string();
+
+    for (tool, args) in [
+        ("read_file", json!({ "path": "../secret.txt" })),
+        ("read_file", json!({ "path": "notes/../../secret.txt" })),
+        ("read_file", json!({ "path": secret })),
+        ("read_file", json!({ "path": "../missing.txt" })),
+        (
+            "write_file",
+            json!({ "path": "../escape.txt", "content": "x" }),
+        ),
+        (
+            "write_file",
+            json!({ "path": "new/../../escape.txt", "content": "x" }),
+        ),
+        ("list_dir", json!({ "path": ".." })),
+        ("search_files", json!({ "pattern": "*", "path": "../" })),
+    ] {
+        let error = call(&registry, tool, args.clone()).await.unwrap_err();
+        assert!(
+            matches!(&error, ToolError::InvalidParameters(m) if m.contains("outside the sandbox")),
+            "{} {}: {:?}",
+            tool,
+            args,
+            error
+        );
+    }
+    assert!(!dir.join("escape.txt").exists());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_symlinks_out_of_the_root_are_refused() {
+    let (root, dir) = sandbox();
+    std::os::unix::fs::symlink(dir.join("secret.txt"), root.join("link.txt")).unwrap();
+    std::os::unix::fs::symlink(&dir, root.join("parent")).unwrap();
+    let registry = registry(FsTools::new(&root));
+
+    for (tool, args) in [
+        ("read_file", json!({ "path": "link.txt" })),
+        ("read_file", json!({ "path": "parent/secret.txt" })),
+        (
+            "write_file",
+            json!({ "path": "link.txt", "content": "overwritten" }),
+        ),
+        (
+            "write_file",
+            json!({ "path": "parent/new.txt", "content": "x" }),
+        ),
+    ] {
+        let error = call(&registry, tool, args.clone()).await.unwrap_err();
+        assert!(
+            matches!(error, ToolError::InvalidParameters(_)),
+            "{} {}: {:?}",
+            tool,
+            args,
+            error
+        );
+    }
+    assert_eq!(
+        std::fs::read_to_string(dir.join("secret.txt")).unwrap(),
+        "top secret"
+    );
+
+    // Searches don't follow symlinks at all
+    let found = call(&registry, "search_files", json!({ "pattern": "**" }))
+        .await
+        .unwrap();
+    let paths: Vec<&str> = found["matches"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(paths, vec!["readme.txt", "notes/todo.md"]);
+}
+
+#[tokio::test]
+async fn test_writes_over_the_size_limit_are_refused() {
+    let (root, _dir) = sandbox();
+    let registry = registry(FsTools::new(&root).with_max_file_bytes(16));
+
+    let error = call(
+        &registry,
+        "write_file",
+        json!({ "path": "big.txt", "content": "x".repeat(17) }),
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        matches!(&error, ToolError::InvalidParameters(m) if m.contains("over the 16 byte limit")),
+        "{:?}",
+        error
+    );
+    assert!(!root.join("big.txt").exists());
+
+    let written = call(
+        &registry,
+        "write_file",
+        json!({ "path": "logs/run.log", "content": "0123456789", "mode": "create" }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(written["created"], true);
+    assert_eq!(written["path"], "logs/run.log");
+
+    // Appending may not grow the file past the limit either
+    let error = call(
+        &registry,
+        "write_file",
+        json!({ "path": "logs/run.log", "content": "0123456789", "mode": "append" }),
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        matches!(error, ToolError::InvalidParameters(_)),
+        "{:?}",
+        error
+    );
+    let appended = call(
+        &registry,
+        "write_file",
+        json!({ "path": "logs/run.log", "content": "abc", "mode": "append" }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(appended["size"], 13);
+    assert_eq!(
+        std::fs::read_to_string(root.join("logs/run.log")).unwrap(),
+        "0123456789abc"
+    );
+
+    let error = call(
+        &registry,
+        "write_file",
+        json!({ "path": "logs/run.log", "content": "again", "mode": "create" }),
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        matches!(error, ToolError::InvalidParameters(_)),
+        "{:?}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn test_read_list_and_search() {
+    let (root, _dir) = sandbox();
+    let registry = registry(FsTools::new(&root).with_max_results(1));
+
+    let page = call(
+        &registry,
+        "read_file",
+        json!({ "path": "readme.txt", "offset": 6, "limit": 4 }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(page["content"], "sand");
+    assert_eq!(page["bytes"], 4);
+    assert_eq!(page["size"], 13);
+    assert_eq!(page["has_more"], true);
+
+    let listing = call(&registry, "list_dir", json!({})).await.unwrap();
+    assert_eq!(listing["path"], ".");
+    assert_eq!(listing["total"], 2);
+    assert_eq!(listing["truncated"], true);
+    assert_eq!(listing["entries"][0]["name"], "notes");
+    assert_eq!(listing["entries"][0]["type"], "dir");
+    assert!(listing["entries"][0]["modified"].is_string());
+
+    let found = call(
+        &registry,
+        "search_files",
+        json!({ "pattern": "**/*.md", "query": "it" }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        found["matches"],
+        json!([{ "path": "notes/todo.md", "line": 1, "text": "- ship it" }])
+    );
+    assert_eq!(found["truncated"], true);
+
+    // Arguments are checked against the schemas before anything runs
+    let error = call(
+        &registry,
+        "write_file",
+        json!({ "path": "a.txt", "content": "x", "mode": "replace" }),
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        matches!(error, ToolError::InvalidParameters(_)),
+        "{:?}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn test_agent_reads_a_file_and_answers() {
+    let (root, _dir) = sandbox();
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("read_file", json!({ "path": "notes/todo.md" }))
+            .with_tool_call("read_file", json!({ "path": "notes/todo.md" }))
+            .with_response("Two items: ship it and test it"),
+    );
+    let agent = Agent::new(
+        AgentConfig::builder("assistant")
+            .tools(Arc::new(registry(FsTools::new(&root))))
+            .build(),
+    )
+    .with_client(client.clone());
+
+    let output = agent
+        .execute(&AgentInput::from_text("What's on my todo list?"))
+        .await
+        .unwrap();
+    assert_eq!(output.data["response"], "Two items: ship it and test it");
+
+    let calls = client.get_calls();
+    let first = calls[1]
+        .messages
+        .last()
+        .unwrap()
+        .content
+        .text()
+        .into_owned();
+    assert!(first.contains("- ship it"), "{}", first);
+
+    // Reading the same file again is caught by the loop detector
+    let repeat = calls[2]
+        .messages
+        .last()
+        .unwrap()
+        .content
+        .text()
+        .into_owned();
+    assert!(
+        repeat.contains("You already called the tool 'read_file'"),
+        "{}",
+        repeat
+    );
+}
+
+#[tokio::test]
+async fn test_paths_follow_the_sandbox_rules() {
+    let (root, _) = sandbox();
+    let registry = registry(FsTools::new(&root));
+
+    // `..` may step back out of a directory that doesn't exist yet
+    let written = call(
+        &registry,
+        "write_file",
+        json!({ "path": "drafts/../notes/new.md", "content": "x" }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(written["path"], "notes/new.md");
+    assert!(root.join("notes/new.md").exists());
+
+    // Names some platform can't hold are refused everywhere
+    for path in ["con.txt", "notes/trailing.", "what?.md"] {
+        let error = call(
+            &registry,
+            "write_file",
+            json!({ "path": path, "content": "x" }),
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            matches!(error, ToolError::InvalidParameters(_)),
+            "{}: {:?}",
+            path,
+            error
+        );
+    }
+}
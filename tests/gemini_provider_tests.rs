@@ -0,0 +1,182 @@
+/// Tests for the Gemini provider against a scripted local HTTP server
+use agent_runtime::llm::{ChatMessage, ChatRequest, GeminiClient, GenericChatClient};
+use agent_runtime::{Agent, AgentConfig, AgentInput, NativeTool, ToolRegistry, ToolResult};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+mod common;
+use common::{serve, Reply};
+
+/// One JSON object per line, as `streamGenerateContent` sends them
+fn ndjson(chunks: &[Value]) -> Reply {
+    let body: String = chunks.iter().map(|c| format!("{}\n", c)).collect();
+    Reply::text("application/json", body)
+}
+
+#[tokio::test]
+async fn test_chat_returns_text_and_usage() {
+    let body = json!({
+        "candidates": [{
+            "content": { "role": "model", "parts": [{ "text": "Hello " }, { "text": "there" }] },
+            "finishReason": "STOP"
+        }],
+        "usageMetadata": { "promptTokenCount": 9, "candidatesTokenCount": 3, "totalTokenCount": 12 },
+        "modelVersion": "gemini-test-001"
+    });
+    let (base_url, recorded) = serve(vec![Reply::json(body)]).await;
+
+    let client = GeminiClient::with_model("test-key", "gemini-test")
+        .with_base_url(format!("{}/v1beta", base_url));
+    let response = client
+        .chat(
+            ChatRequest::new(vec![
+                ChatMessage::system("Be kind"),
+                ChatMessage::user("Hi"),
+            ])
+            .with_max_tokens(256),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.content, "Hello there");
+    assert_eq!(response.model, "gemini-test-001");
+    assert_eq!(response.finish_reason.as_deref(), Some("stop"));
+    assert!(response.tool_calls.is_none());
+    let usage = response.usage.unwrap();
+    assert_eq!(
+        (
+            usage.prompt_tokens,
+            usage.completion_tokens,
+            usage.total_tokens
+        ),
+        (9, 3, 12)
+    );
+
+    let recorded = recorded.lock().unwrap();
+    assert!(recorded[0]
+        .head
+        .starts_with("post /v1beta/models/gemini-test:generatecontent "));
+    assert!(recorded[0].head.contains("x-goog-api-key: test-key"));
+    let sent = &recorded[0].body;
+    assert_eq!(sent["systemInstruction"]["parts"][0]["text"], "Be kind");
+    assert_eq!(
+        sent["contents"],
+        json!([{ "role": "user", "parts": [{ "text": "Hi" }] }])
+    );
+    assert_eq!(sent["generationConfig"]["maxOutputTokens"], 256);
+    assert!(sent.get("tools").is_none());
+}
+
+#[tokio::test]
+async fn test_agent_tool_loop_over_streamed_function_calls() {
+    let (base_url, recorded) = serve(vec![
+        ndjson(&[json!({
+            "candidates": [{
+                "content": { "role": "model", "parts": [
+                    { "functionCall": { "name": "calculator", "args": { "a": 5, "b": 3 } } }
+                ]},
+                "finishReason": "STOP"
+            }],
+            "usageMetadata": { "promptTokenCount": 30, "candidatesTokenCount": 8, "totalTokenCount": 38 }
+        })]),
+        ndjson(&[
+            json!({"candidates": [{ "content": { "role": "model", "parts": [{ "text": "5 + 3 " }] } }]}),
+            json!({
+                "candidates": [{
+                    "content": { "role": "model", "parts": [{ "text": "is 8" }] },
+                    "finishReason": "STOP"
+                }],
+                "usageMetadata": { "promptTokenCount": 50, "candidatesTokenCount": 6, "totalTokenCount": 56 }
+            }),
+        ]),
+    ])
+    .await;
+
+    let mut registry = ToolRegistry::new();
+    registry.register(NativeTool::new(
+        "calculator",
+        "Adds two numbers",
+        json!({
+            "type": "object",
+            "properties": { "a": {"type": "number"}, "b": {"type": "number"} }
+        }),
+        |args| {
+            Box::pin(async move {
+                let sum = args["a"].as_f64().unwrap() + args["b"].as_f64().unwrap();
+                Ok(ToolResult::success(json!({"result": sum}), 0.0))
+            })
+        },
+    ));
+    let config = AgentConfig::builder("calc")
+        .system_prompt("You are a calculator")
+        .tools(Arc::new(registry))
+        .build();
+    let client = GeminiClient::with_model("test-key", "gemini-test")
+        .with_base_url(format!("{}/v1beta", base_url));
+    let agent = Agent::new(config).with_client(Arc::new(client));
+
+    let output = agent
+        .execute(&AgentInput::from_text("What is 5 + 3?"))
+        .await
+        .unwrap();
+    assert_eq!(output.data["response"], "5 + 3 is 8");
+
+    let recorded = recorded.lock().unwrap();
+    assert_eq!(recorded.len(), 2);
+    assert!(recorded[0]
+        .head
+        .starts_with("post /v1beta/models/gemini-test:streamgeneratecontent "));
+
+    let first = &recorded[0].body;
+    assert_eq!(
+        first["systemInstruction"]["parts"][0]["text"],
+        "You are a calculator"
+    );
+    let declaration = &first["tools"][0]["functionDeclarations"][0];
+    assert_eq!(declaration["name"], "calculator");
+    assert_eq!(declaration["parameters"]["type"], "object");
+
+    // The follow-up replays the call and answers it by function name
+    let contents = recorded[1].body["contents"].as_array().unwrap();
+    let model_turn = &contents[contents.len() - 2];
+    assert_eq!(model_turn["role"], "model");
+    assert_eq!(
+        model_turn["parts"][0],
+        json!({"functionCall": {"name": "calculator", "args": {"a": 5, "b": 3}}})
+    );
+    let result = &contents[contents.len() - 1];
+    assert_eq!(result["role"], "user");
+    let response = &result["parts"][0]["functionResponse"];
+    assert_eq!(response["name"], "calculator");
+    assert_eq!(response["response"], json!({"result": 8.0}));
+}
+
+#[tokio::test]
+async fn test_safety_block_is_a_content_filter_finish() {
+    let body = json!({
+        "candidates": [{
+            "finishReason": "SAFETY",
+            "safetyRatings": [
+                { "category": "HARM_CATEGORY_HARASSMENT", "probability": "NEGLIGIBLE" },
+                { "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "probability": "HIGH", "blocked": true }
+            ]
+        }],
+        "usageMetadata": { "promptTokenCount": 14, "totalTokenCount": 14 }
+    });
+    let (base_url, _) = serve(vec![Reply::json(body)]).await;
+
+    let client = GeminiClient::new("test-key").with_base_url(format!("{}/v1beta", base_url));
+    let response = client
+        .chat(ChatRequest::new(vec![ChatMessage::user("Something risky")]))
+        .await
+        .unwrap();
+
+    assert_eq!(response.content, "");
+    assert_eq!(response.finish_reason.as_deref(), Some("content_filter"));
+    assert!(response.tool_calls.is_none());
+    assert_eq!(
+        response.warnings,
+        vec!["response blocked by Gemini: SAFETY (HARM_CATEGORY_DANGEROUS_CONTENT)"]
+    );
+    assert_eq!(response.usage.unwrap().completion_tokens, 0);
+}
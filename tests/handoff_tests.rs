@@ -0,0 +1,200 @@
+/// Tests for agents handing the conversation to one another
+use agent_runtime::llm::types::Role;
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::runtime::Runtime;
+use agent_runtime::*;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn triage() -> AgentConfig {
+    AgentConfig::builder("triage")
+        .system_prompt("You route support requests")
+        .handoffs(["billing_agent", "tech_agent"])
+        .build()
+}
+
+fn targets(client: Arc<MockLlmClient>) -> HandoffTargets {
+    HandoffTargets::new()
+        .with_client(client)
+        .with_config(
+            AgentConfig::builder("billing_agent")
+                .system_prompt("You handle billing")
+                .build(),
+        )
+        .with_config(
+            AgentConfig::builder("tech_agent")
+                .system_prompt("You fix technical problems")
+                .handoffs(["triage"])
+                .build(),
+        )
+}
+
+fn support(client: Arc<MockLlmClient>, targets: HandoffTargets) -> Workflow {
+    let triage = Agent::new(triage()).with_client(client);
+    Workflow::builder()
+        .name("support".to_string())
+        .step(Box::new(
+            AgentStep::from_agent(triage, "support".to_string()).with_handoffs(targets),
+        ))
+        .initial_input(json!("I was charged twice"))
+        .build()
+}
+
+#[tokio::test]
+async fn test_triage_hands_off_to_specialist() {
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call(
+                "handoff",
+                json!({ "to": "billing_agent", "reason": "duplicate charge" }),
+            )
+            .with_response("I've refunded the second charge"),
+    );
+    let runtime = Runtime::new();
+    let run = runtime
+        .execute(support(client.clone(), targets(client.clone())))
+        .await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(
+        run.final_output.unwrap()["response"],
+        "I've refunded the second charge"
+    );
+    assert_eq!(
+        run.steps[0].handoffs,
+        Some(vec![Handoff {
+            from: "triage".to_string(),
+            to: "billing_agent".to_string(),
+            reason: Some("duplicate charge".to_string()),
+        }])
+    );
+
+    // Triage was offered the tool; billing got the conversation and the reason
+    let calls = client.get_calls();
+    let tools = calls[0].tools.as_ref().unwrap();
+    let offered = tools
+        .iter()
+        .find(|t| t["function"]["name"] == "handoff")
+        .unwrap();
+    assert_eq!(
+        offered["function"]["parameters"]["properties"]["to"]["enum"],
+        json!(["billing_agent", "tech_agent"])
+    );
+    let handed = &calls[1].messages;
+    assert_eq!(handed[0].content.text(), "You handle billing");
+    assert!(handed
+        .iter()
+        .all(|m| m.content.text() != "You route support requests"));
+    assert!(handed
+        .iter()
+        .any(|m| m.content.text() == "I was charged twice"));
+    assert!(handed
+        .iter()
+        .any(|m| m.role == Role::Tool && m.content.text() == "Handed off to billing_agent"));
+    assert_eq!(
+        handed.last().unwrap().content.text(),
+        "Agent triage handed this conversation to you. Reason: duplicate charge"
+    );
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let handoff_events: Vec<(String, Option<String>)> = runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .filter(|e| e.scope == EventScope::Agent && e.component_id.ends_with(":handoff"))
+        .map(|e| (e.component_id, e.message))
+        .collect();
+    assert_eq!(
+        handoff_events,
+        vec![
+            (
+                "triage:handoff".to_string(),
+                Some("Handing off to billing_agent".to_string())
+            ),
+            (
+                "billing_agent:handoff".to_string(),
+                Some("Taking over from triage".to_string())
+            ),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_disallowed_target_is_refused_to_the_model() {
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("handoff", json!({ "to": "sales_agent" }))
+            .with_response("Let me look into the charge myself"),
+    );
+    let run = Runtime::new()
+        .execute(support(client.clone(), targets(client.clone())))
+        .await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(
+        run.final_output.unwrap()["response"],
+        "Let me look into the charge myself"
+    );
+    assert_eq!(run.steps[0].handoffs, None);
+    let refusal = client.get_calls()[1]
+        .messages
+        .iter()
+        .find(|m| m.role == Role::Tool)
+        .unwrap()
+        .content
+        .text()
+        .into_owned();
+    assert_eq!(
+        refusal,
+        "Cannot hand off to 'sales_agent'. Agents you can hand off to: billing_agent, tech_agent"
+    );
+}
+
+#[tokio::test]
+async fn test_handoff_depth_is_capped() {
+    // Triage and tech pass the conversation back and forth
+    let ping = json!({ "to": "tech_agent", "reason": "sounds technical" });
+    let pong = json!({ "to": "triage", "reason": "sounds like billing" });
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("handoff", ping.clone())
+            .with_tool_call("handoff", pong)
+            .with_tool_call("handoff", ping),
+    );
+    let targets = targets(client.clone())
+        .with_config(triage())
+        .with_max_depth(2);
+    let run = Runtime::new()
+        .execute(support(client.clone(), targets))
+        .await;
+
+    assert_eq!(run.state, WorkflowState::Failed);
+    let failure = run.failure.unwrap();
+    assert_eq!(failure.step_name, "support");
+    assert_eq!(
+        failure.error.to_string(),
+        "Execution failed: handoff limit of 2 reached: \
+         triage -> tech_agent -> triage -> tech_agent"
+    );
+    assert_eq!(client.call_count(), 3);
+}
+
+#[tokio::test]
+async fn test_agent_alone_reports_the_handoff() {
+    let client = Arc::new(MockLlmClient::new().with_tool_call(
+        "handoff",
+        json!({ "to": "tech_agent", "reason": "login fails" }),
+    ));
+    let agent = Agent::new(triage()).with_client(client);
+    let output = agent
+        .execute(&AgentInput::from_text("I can't log in"))
+        .await
+        .unwrap();
+
+    let handoff = output.metadata.handoff.unwrap();
+    assert_eq!(handoff.to, "tech_agent");
+    assert_eq!(handoff.reason.as_deref(), Some("login fails"));
+    assert_eq!(output.data["handoff"]["to"], "tech_agent");
+    assert_eq!(output.metadata.tool_calls_count, 1);
+}
@@ -0,0 +1,155 @@
+/// Tests for run heartbeats and stall detection
+use agent_runtime::llm::{ChatRequest, ChatResponse, GenericChatClient, LlmResult, MockLlmClient};
+use agent_runtime::runtime::{CancellationHandle, HeartbeatConfig, HeartbeatReport, Runtime};
+use agent_runtime::*;
+use async_trait::async_trait;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Answers after `delay`, sending nothing until then
+struct Slow {
+    inner: MockLlmClient,
+    delay: Duration,
+}
+
+#[async_trait]
+impl GenericChatClient for Slow {
+    async fn chat(&self, request: ChatRequest) -> LlmResult<ChatResponse> {
+        tokio::time::sleep(self.delay).await;
+        self.inner.chat(request).await
+    }
+
+    async fn chat_stream(
+        &self,
+        request: ChatRequest,
+        tx: mpsc::Sender<String>,
+    ) -> LlmResult<ChatResponse> {
+        tokio::time::sleep(self.delay).await;
+        self.inner.chat_stream(request, tx).await
+    }
+}
+
+fn workflow(delay: Duration) -> Workflow {
+    let client = Arc::new(Slow {
+        inner: MockLlmClient::with_responses_vec(vec!["Mars has two moons"]),
+        delay,
+    });
+    let agent = Agent::new(AgentConfig::builder("writer").build()).with_client(client);
+    Workflow::builder()
+        .name("slow".to_string())
+        .step(Box::new(TransformStep::new("prepare".to_string(), |d| d)))
+        .step(Box::new(AgentStep::from_agent(agent, "writer".to_string())))
+        .initial_input(json!("How many moons does Mars have?"))
+        .build()
+}
+
+fn events_of(runtime: &Runtime, component_id: &str) -> Vec<Event> {
+    runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .filter(|e| e.component_id == component_id)
+        .collect()
+}
+
+#[tokio::test]
+async fn test_heartbeat_reports_the_running_step() {
+    let runtime = Runtime::new().with_heartbeat(HeartbeatConfig::new(Duration::from_millis(100)));
+    let run = runtime.execute(workflow(Duration::from_millis(300))).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let heartbeats = events_of(&runtime, &format!("system:heartbeat:{}", run.workflow_id));
+    assert!(heartbeats.len() >= 2, "{} heartbeats", heartbeats.len());
+    let first = &heartbeats[0];
+    assert_eq!(first.scope, EventScope::System);
+    assert_eq!(first.event_type, EventType::Progress);
+    assert_eq!(first.workflow_id, run.workflow_id);
+    assert_eq!(first.message.as_deref(), Some("Running step 'writer'"));
+
+    let report: HeartbeatReport = serde_json::from_value(first.data.clone()).unwrap();
+    assert_eq!(report.step_index, Some(1));
+    assert_eq!(report.step_name.as_deref(), Some("writer"));
+    assert_eq!(report.iteration, Some(1));
+    assert!(!report.stalled);
+    // Waiting on the model: no chunk yet, but the step's events were seen
+    assert_eq!(report.since_last_chunk_ms, None);
+    assert!(report.since_last_event_ms.is_some());
+    assert!(report.since_progress_ms >= 50, "{:?}", report);
+
+    // Without a threshold nothing is a stall
+    assert!(events_of(&runtime, &format!("system:stall:{}", run.workflow_id)).is_empty());
+}
+
+#[tokio::test]
+async fn test_stall_is_reported_once_with_diagnostics() {
+    let stalls: Arc<Mutex<Vec<HeartbeatReport>>> = Default::default();
+    let seen = stalls.clone();
+    let runtime = Runtime::new().with_heartbeat(
+        HeartbeatConfig::new(Duration::from_millis(100))
+            .with_stall_threshold(Duration::from_millis(150))
+            .on_stall(move |report| seen.lock().unwrap().push(report.clone())),
+    );
+    let run = runtime.execute(workflow(Duration::from_millis(300))).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let events = events_of(&runtime, &format!("system:stall:{}", run.workflow_id));
+    assert_eq!(events.len(), 1);
+    let stall = &events[0];
+    assert_eq!(stall.scope, EventScope::System);
+    assert_eq!(stall.data["stalled"], true);
+    assert_eq!(stall.data["step_name"], "writer");
+    assert_eq!(stall.data["iteration"], 1);
+    assert!(stall.data["since_progress_ms"].as_u64().unwrap() >= 150);
+    assert!(stall
+        .message
+        .as_deref()
+        .unwrap()
+        .ends_with("in step 'writer'"));
+
+    let stalls = stalls.lock().unwrap();
+    assert_eq!(stalls.len(), 1);
+    assert!(stalls[0].stalled);
+    assert_eq!(stalls[0].workflow_id, run.workflow_id);
+}
+
+#[tokio::test]
+async fn test_stall_callback_can_cancel_the_run() {
+    let slot: Arc<Mutex<Option<CancellationHandle>>> = Default::default();
+    let from_callback = slot.clone();
+    let runtime = Runtime::new().with_heartbeat(
+        HeartbeatConfig::new(Duration::from_millis(100))
+            .with_stall_threshold(Duration::from_millis(150))
+            .on_stall(move |_| from_callback.lock().unwrap().as_ref().unwrap().cancel()),
+    );
+
+    let (handle, run) = runtime.execute_cancellable(workflow(Duration::from_secs(30)));
+    *slot.lock().unwrap() = Some(handle);
+    let run = tokio::time::timeout(Duration::from_secs(5), run)
+        .await
+        .expect("the stall canceled the run");
+    assert_eq!(run.state, WorkflowState::Canceled);
+    assert_eq!(run.steps.len(), 2);
+    assert_eq!(run.steps[1].status, StepStatus::Canceled);
+}
+
+#[tokio::test]
+async fn test_heartbeat_stops_with_the_run() {
+    let runtime = Runtime::new().with_heartbeat(HeartbeatConfig::new(Duration::from_millis(100)));
+    let run = runtime.execute(workflow(Duration::from_millis(300))).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let component = format!("system:heartbeat:{}", run.workflow_id);
+    let beats = events_of(&runtime, &component).len();
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(events_of(&runtime, &component).len(), beats);
+
+    // A runtime without a heartbeat emits none
+    let runtime = Runtime::new();
+    let run = runtime.execute(workflow(Duration::from_millis(150))).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(events_of(&runtime, &format!("system:heartbeat:{}", run.workflow_id)).is_empty());
+}
@@ -0,0 +1,283 @@
+/// Tests for the built-in `http_request` tool, against an in-process server
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::prelude::TypesToolError as ToolError;
+use agent_runtime::*;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Redirect};
+use axum::routing::{get, post};
+use axum::Router;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR";
+
+/// Serves a few fixed routes on 127.0.0.1; returns the base URL
+async fn start_server() -> String {
+    let app = Router::new()
+        .route(
+            "/hello",
+            get(|headers: HeaderMap| async move {
+                let token = headers
+                    .get(header::AUTHORIZATION)
+                    .map(|v| v.to_str().unwrap().to_string());
+                (
+                    [
+                        (header::CONTENT_TYPE, "application/json"),
+                        (header::SET_COOKIE, "session=abc"),
+                    ],
+                    json!({ "hello": "world", "authorization": token }).to_string(),
+                )
+            }),
+        )
+        .route("/echo", post(|body: String| async move { body }))
+        .route("/moved", get(|| async { Redirect::temporary("/hello") }))
+        .route("/big", get(|| async { "x".repeat(100 * 1024) }))
+        .route("/png", get(|| async { PNG.to_vec() }))
+        .route(
+            "/slow",
+            get(|| async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                StatusCode::OK.into_response()
+            }),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+/// A registry holding `tool`
+fn registry(tool: HttpTool) -> ToolRegistry {
+    let mut registry = ToolRegistry::new();
+    registry.register(tool);
+    registry
+}
+
+/// A tool allowed to reach the local test server
+fn local_tool() -> HttpToolBuilder {
+    HttpTool::builder()
+        .allow_host("127.0.0.1")
+        .allow_private_networks(true)
+}
+
+fn params(value: Value) -> HashMap<String, Value> {
+    serde_json::from_value(value).unwrap()
+}
+
+#[tokio::test]
+async fn test_request_succeeds() {
+    let base = start_server().await;
+    let registry = registry(local_tool().build());
+
+    let result = registry
+        .call_tool(
+            "http_request",
+            params(json!({
+                "url": format!("{}/hello", base),
+                "headers": { "Authorization": "Bearer secret" }
+            })),
+        )
+        .await
+        .unwrap();
+    let output = result.output;
+    assert_eq!(output["status"], 200);
+    assert_eq!(output["encoding"], "text");
+    assert_eq!(output["content_type"], "application/json");
+    assert_eq!(output["truncated"], false);
+    let body: Value = serde_json::from_str(output["body"].as_str().unwrap()).unwrap();
+    assert_eq!(
+        body,
+        json!({ "hello": "world", "authorization": "Bearer secret" })
+    );
+    // Cookies the server sets aren't handed to the LLM
+    assert!(output["headers"].get("set-cookie").is_none());
+    assert!(output["headers"].get("content-length").is_some());
+
+    let posted = registry
+        .call_tool(
+            "http_request",
+            params(json!({
+                "method": "POST",
+                "url": format!("{}/echo", base),
+                "headers": { "Content-Type": "application/json" },
+                "body": "{\"name\":\"ada\"}"
+            })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(posted.output["body"], "{\"name\":\"ada\"}");
+
+    let redirected = registry
+        .call_tool(
+            "http_request",
+            params(json!({ "url": format!("{}/moved", base) })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(redirected.output["status"], 200);
+    assert_eq!(redirected.output["url"], format!("{}/hello", base));
+}
+
+#[tokio::test]
+async fn test_denied_hosts_are_refused() {
+    let base = start_server().await;
+    let port = base.rsplit(':').next().unwrap();
+
+    let denied = registry(
+        HttpTool::builder()
+            .allow_private_networks(true)
+            .deny_host("localhost")
+            .build(),
+    );
+    let error = denied
+        .call_tool(
+            "http_request",
+            params(json!({ "url": format!("http://localhost:{}/hello", port) })),
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&error, ToolError::NotPermitted(m) if m.contains("'localhost' is not allowed")),
+        "{:?}",
+        error
+    );
+
+    // Hosts missing from the allowlist are refused too
+    let github_only = registry(
+        HttpTool::builder()
+            .allow_host("api.github.com")
+            .allow_private_networks(true)
+            .build(),
+    );
+    let error = github_only
+        .call_tool(
+            "http_request",
+            params(json!({ "url": format!("{}/hello", base) })),
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(error, ToolError::NotPermitted(_)), "{:?}", error);
+}
+
+#[tokio::test]
+async fn test_private_addresses_are_blocked_by_default() {
+    let base = start_server().await;
+    let port = base.rsplit(':').next().unwrap();
+    let registry = registry(HttpTool::builder().build());
+
+    for url in [
+        format!("{}/hello", base),
+        format!("http://localhost:{}/hello", port),
+        format!("http://[::1]:{}/hello", port),
+    ] {
+        let error = registry
+            .call_tool("http_request", params(json!({ "url": url })))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&error, ToolError::NotPermitted(m) if m.contains("private address")),
+            "{}: {:?}",
+            url,
+            error
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_oversize_bodies_are_truncated() {
+    let base = start_server().await;
+    let registry = registry(local_tool().max_response_bytes(1024).build());
+
+    let big = registry
+        .call_tool(
+            "http_request",
+            params(json!({ "url": format!("{}/big", base) })),
+        )
+        .await
+        .unwrap()
+        .output;
+    assert_eq!(big["truncated"], true);
+    assert_eq!(big["bytes"], 1024);
+    assert_eq!(big["body"].as_str().unwrap().len(), 1024);
+
+    // Binary bodies come back as base64, typed from their first bytes
+    let png = registry
+        .call_tool(
+            "http_request",
+            params(json!({ "url": format!("{}/png", base) })),
+        )
+        .await
+        .unwrap()
+        .output;
+    assert_eq!(png["content_type"], "image/png");
+    assert_eq!(png["encoding"], "base64");
+    assert_eq!(png["body"], "iVBORw0KGgoAAAANSUhEUg==");
+    assert_eq!(png["truncated"], false);
+}
+
+#[tokio::test]
+async fn test_slow_requests_time_out() {
+    let base = start_server().await;
+    let registry = registry(local_tool().build());
+
+    let error = registry
+        .call_tool(
+            "http_request",
+            params(json!({ "url": format!("{}/slow", base), "timeout_ms": 100 })),
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(error, ToolError::TimedOut(100)), "{:?}", error);
+
+    // Without timeout_ms the builder's default applies
+    let registry = self::registry(
+        local_tool()
+            .default_timeout(Duration::from_millis(50))
+            .build(),
+    );
+    let error = registry
+        .call_tool(
+            "http_request",
+            params(json!({ "url": format!("{}/slow", base) })),
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(error, ToolError::TimedOut(50)), "{:?}", error);
+}
+
+#[tokio::test]
+async fn test_authorization_is_redacted_in_events() {
+    let base = start_server().await;
+    let events = Arc::new(EventStream::new());
+    let agent = Agent::new(
+        AgentConfig::builder("fetcher")
+            .tools(Arc::new(registry(local_tool().build())))
+            .build(),
+    )
+    .with_client(Arc::new(MockLlmClient::with_tool_then_text(
+        "http_request",
+        json!({
+            "url": format!("{}/hello", base),
+            "headers": { "Authorization": "Bearer secret" }
+        }),
+        "Fetched",
+    )));
+
+    let output = agent
+        .execute_with_events(AgentInput::from_text("Fetch it"), Some(&events))
+        .await
+        .unwrap();
+    assert_eq!(output.data["response"], "Fetched");
+
+    let started = events
+        .all()
+        .into_iter()
+        .find(|e| e.scope == EventScope::Tool && e.event_type == EventType::Started)
+        .unwrap();
+    let arguments = started.data["arguments"].as_str().unwrap();
+    assert!(!arguments.contains("secret"), "{}", arguments);
+    let arguments: Value = serde_json::from_str(arguments).unwrap();
+    assert_eq!(arguments["headers"]["Authorization"], "[REDACTED]");
+}
@@ -0,0 +1,144 @@
+use agent_runtime::*;
+use serde_json::json;
+use std::sync::Arc;
+
+fn order_schema() -> serde_json::Value {
+    json!({
+        "type": "object",
+        "required": ["customer", "quantity", "items"],
+        "additionalProperties": false,
+        "properties": {
+            "customer": {
+                "type": "object",
+                "required": ["email"],
+                "properties": { "email": { "type": "string", "pattern": "@" } }
+            },
+            "quantity": { "type": "integer", "minimum": 1 },
+            "priority": { "type": "string", "enum": ["low", "high"], "default": "low" },
+            "gift": { "type": "boolean", "default": false },
+            "items": { "type": "array", "items": { "type": "string" }, "minItems": 1 }
+        }
+    })
+}
+
+fn order_workflow(schema: InputSchema, input: serde_json::Value) -> Workflow {
+    Workflow::builder()
+        .name("orders".to_string())
+        .with_input_schema(schema)
+        .add_step(Box::new(TransformStep::new("echo".to_string(), |d| d)))
+        .initial_input(input)
+        .build()
+}
+
+#[tokio::test]
+async fn test_strict_schema_reports_every_violation() {
+    let input = json!({
+        "customer": { "email": 42 },
+        "quantity": "3",
+        "items": ["mug", 7],
+        "coupon": "FREE"
+    });
+    let workflow = order_workflow(InputSchema::strict(order_schema()), input.clone());
+
+    let Err(RuntimeError::InvalidInput {
+        workflow: id,
+        violations,
+    }) = workflow.validate_input()
+    else {
+        panic!("input should be rejected");
+    };
+    assert_eq!(id, "orders");
+    let found: Vec<(&str, &str)> = violations
+        .iter()
+        .map(|v| (v.pointer.as_str(), v.message.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("/coupon", "property is not allowed"),
+            ("/customer/email", "expected string, found number"),
+            ("/items/1", "expected string, found number"),
+            ("/quantity", "expected integer, found string"),
+        ]
+    );
+
+    // Strict mode leaves valid input untouched
+    let valid = json!({ "customer": { "email": "a@b.c" }, "quantity": 2, "items": ["mug"] });
+    let workflow = order_workflow(InputSchema::strict(order_schema()), valid.clone());
+    assert_eq!(workflow.validate_input().unwrap(), valid);
+}
+
+#[tokio::test]
+async fn test_lenient_schema_fills_defaults_and_coerces() {
+    let input = json!({
+        "customer": { "email": "a@b.c" },
+        "quantity": "3",
+        "gift": "true",
+        "items": ["mug"]
+    });
+    let runtime = Runtime::new();
+    let run = runtime
+        .execute(order_workflow(InputSchema::lenient(order_schema()), input))
+        .await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(
+        run.final_output,
+        Some(json!({
+            "customer": { "email": "a@b.c" },
+            "quantity": 3,
+            "priority": "low",
+            "gift": true,
+            "items": ["mug"]
+        }))
+    );
+
+    // Coercion still can't make a bad value valid
+    let workflow = order_workflow(
+        InputSchema::lenient(order_schema()),
+        json!({ "customer": { "email": "a@b.c" }, "quantity": "three", "items": [] }),
+    );
+    let err = workflow.validate_input().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid input for workflow 'orders': /items must have at least 1 items; \
+         /quantity expected integer, found string;"
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_input_fails_before_any_step() {
+    let mock = Arc::new(llm::MockLlmClient::new().with_response("should not run"));
+    let agent = Agent::new(AgentConfig::builder("writer").build()).with_client(mock.clone());
+    let workflow = Workflow::builder()
+        .name("guarded".to_string())
+        .with_input_schema(InputSchema::strict(json!({
+            "type": "object",
+            "required": ["topic"],
+            "properties": { "topic": { "type": "string", "minLength": 3 } }
+        })))
+        .add_step(Box::new(AgentStep::from_agent(agent, "write".to_string())))
+        .initial_input(json!({ "topic": "ai" }))
+        .build();
+
+    let runtime = Runtime::new();
+    let offset = runtime.event_stream().current_offset();
+    let run = runtime.execute(workflow).await;
+
+    assert_eq!(run.state, WorkflowState::Failed);
+    assert!(run.steps.is_empty());
+    assert_eq!(mock.call_count(), 0);
+
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let events = runtime.events_from_offset(offset);
+    assert!(!events.iter().any(|e| e.scope == EventScope::WorkflowStep));
+    let failed = events
+        .iter()
+        .find(|e| e.scope == EventScope::Workflow && e.event_type == EventType::Failed)
+        .unwrap();
+    assert_eq!(failed.data["invalid_input"], true);
+    assert_eq!(
+        failed.data["violations"],
+        json!([{ "pointer": "/topic", "message": "must be at least 3 characters" }])
+    );
+}
@@ -0,0 +1,293 @@
+use agent_runtime::agent::latency::{self, InMemorySlowTurnStore, LatencyComponent, SlowTurnStore};
+use agent_runtime::llm::{GenericChatClient, LlmResult, MockLlmClient};
+use agent_runtime::*;
+use async_trait::async_trait;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Wraps the mock client with scripted delays: time before the first chunk
+/// (per call, the last value repeats), and an optional rate limiter wait
+/// reported to the current turn
+struct ScriptedClient {
+    inner: MockLlmClient,
+    think_times: Mutex<VecDeque<Duration>>,
+    rate_limit_wait: Duration,
+}
+
+impl ScriptedClient {
+    fn next_think_time(&self) -> Duration {
+        let mut times = self.think_times.lock().unwrap();
+        if times.len() > 1 {
+            times.pop_front().unwrap()
+        } else {
+            times.front().copied().unwrap_or_default()
+        }
+    }
+}
+
+#[async_trait]
+impl GenericChatClient for ScriptedClient {
+    async fn chat(&self, request: ChatRequest) -> LlmResult<ChatResponse> {
+        self.inner.chat(request).await
+    }
+
+    async fn chat_stream(
+        &self,
+        request: ChatRequest,
+        tx: mpsc::Sender<String>,
+    ) -> LlmResult<ChatResponse> {
+        if !self.rate_limit_wait.is_zero() {
+            tokio::time::sleep(self.rate_limit_wait).await;
+            latency::record_rate_limit_wait(self.rate_limit_wait);
+            latency::record_retry("llm", "429 Too Many Requests");
+        }
+        tokio::time::sleep(self.next_think_time()).await;
+        let response = self.inner.chat(request).await?;
+        let _ = tx.send(response.content.clone()).await;
+        Ok(response)
+    }
+}
+
+fn scripted(inner: MockLlmClient, think_ms: u64, rate_limit_ms: u64) -> Arc<ScriptedClient> {
+    scripted_calls(inner, &[think_ms], rate_limit_ms)
+}
+
+fn scripted_calls(
+    inner: MockLlmClient,
+    think_ms: &[u64],
+    rate_limit_ms: u64,
+) -> Arc<ScriptedClient> {
+    Arc::new(ScriptedClient {
+        inner,
+        think_times: Mutex::new(
+            think_ms
+                .iter()
+                .map(|ms| Duration::from_millis(*ms))
+                .collect(),
+        ),
+        rate_limit_wait: Duration::from_millis(rate_limit_ms),
+    })
+}
+
+fn slow_tools(delay_ms: u64) -> Arc<ToolRegistry> {
+    let mut registry = ToolRegistry::new();
+    registry.register(NativeTool::new(
+        "search",
+        "Search the index",
+        json!({"type": "object", "properties": {}}),
+        move |_params| async move {
+            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+            Ok(ToolResult::success(json!({"hits": 3}), delay_ms as f64))
+        },
+    ));
+    Arc::new(registry)
+}
+
+fn agent_with(client: Arc<ScriptedClient>, tools: Option<Arc<ToolRegistry>>) -> Agent {
+    let mut config = AgentConfig::builder("assistant").system_prompt("You help");
+    if let Some(tools) = tools {
+        config = config.tools(tools);
+    }
+    Agent::new(config.build())
+        .with_client(client)
+        .with_latency_slo(LatencySlo::new(Duration::from_millis(1_000)).with_window(10))
+}
+
+async fn slow_turn_reports(stream: &EventStream) -> Vec<SlowTurnReport> {
+    // Events are appended asynchronously
+    tokio::task::yield_now().await;
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    stream
+        .all()
+        .into_iter()
+        .filter(|e| e.component_id == "system:slow_turn")
+        .map(|e| serde_json::from_value(e.data["report"].clone()).unwrap())
+        .collect()
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_slow_tool_is_blamed() {
+    let mock = MockLlmClient::new()
+        .with_tool_call("search", json!({}))
+        .with_response("Found it");
+    let agent = agent_with(scripted(mock, 50, 0), Some(slow_tools(2_000)));
+    let stream = EventStream::new();
+
+    let output = agent
+        .execute_with_events(AgentInput::from_value(json!("find it")), Some(&stream))
+        .await
+        .unwrap();
+
+    let latency = output.metadata.latency.unwrap();
+    assert_eq!(latency.llm_calls.len(), 2);
+    assert_eq!(latency.tool_calls.len(), 1);
+    assert!(latency.tool_calls[0].duration_ms >= 2_000.0);
+
+    let reports = slow_turn_reports(&stream).await;
+    assert_eq!(reports.len(), 1);
+    let report = &reports[0];
+    assert_eq!(report.turn_id, latency.turn_id);
+    assert_eq!(
+        report.dominant,
+        LatencyComponent::Tool {
+            name: "search".to_string()
+        }
+    );
+    assert!(report.summary().contains("tool `search`"));
+    assert!(report.context.message_count >= 4);
+    assert!(!report.context.heaviest_messages.is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_slow_llm_is_blamed() {
+    let agent = agent_with(
+        scripted(MockLlmClient::with_responses_vec(vec!["Done"]), 3_000, 0),
+        None,
+    );
+    let stream = EventStream::new();
+
+    let output = agent
+        .execute_with_events(AgentInput::from_value(json!("hi")), Some(&stream))
+        .await
+        .unwrap();
+
+    let latency = output.metadata.latency.unwrap();
+    let call = &latency.llm_calls[0];
+    assert!(call.total_ms >= 3_000.0);
+    assert!(call.time_to_first_chunk_ms.unwrap() >= 3_000.0);
+
+    let reports = slow_turn_reports(&stream).await;
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].dominant, LatencyComponent::Llm { iteration: 1 });
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_rate_limit_wait_is_blamed() {
+    let agent = agent_with(
+        scripted(MockLlmClient::with_responses_vec(vec!["Done"]), 100, 2_500),
+        None,
+    );
+    let stream = EventStream::new();
+
+    let output = agent
+        .execute_with_events(AgentInput::from_value(json!("hi")), Some(&stream))
+        .await
+        .unwrap();
+
+    let latency = output.metadata.latency.unwrap();
+    assert!(latency.rate_limit_wait_ms >= 2_500.0);
+    assert_eq!(latency.retries.len(), 1);
+    assert_eq!(latency.retries[0].component, "llm");
+
+    let reports = slow_turn_reports(&stream).await;
+    assert_eq!(reports[0].dominant, LatencyComponent::RateLimitWait);
+    assert_eq!(reports[0].latency.retries.len(), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_queue_wait_is_blamed() {
+    let agent = Arc::new(
+        agent_with(
+            scripted_calls(
+                MockLlmClient::with_responses_vec(vec!["first", "second"]),
+                &[900, 200],
+                0,
+            ),
+            None,
+        )
+        .with_turn_limit(1),
+    );
+    let stream = EventStream::new();
+
+    let first = {
+        let agent = agent.clone();
+        let stream = stream.clone();
+        tokio::spawn(async move {
+            agent
+                .execute_with_events(AgentInput::from_value(json!("a")), Some(&stream))
+                .await
+        })
+    };
+    tokio::task::yield_now().await;
+    let second = agent
+        .execute_with_events(AgentInput::from_value(json!("b")), Some(&stream))
+        .await
+        .unwrap();
+    let first = first.await.unwrap().unwrap();
+
+    // The first turn fits the SLO; the second waited for it
+    assert!(first.metadata.latency.unwrap().total_ms < 1_000.0);
+    let latency = second.metadata.latency.unwrap();
+    assert!(latency.queue_wait_ms >= 900.0);
+    assert!(latency.total_ms >= 1_100.0);
+
+    let reports = slow_turn_reports(&stream).await;
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].dominant, LatencyComponent::QueueWait);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_fast_turns_update_attainment_without_report() {
+    let store = Arc::new(InMemorySlowTurnStore::new());
+    let slo = LatencySlo::new(Duration::from_millis(1_000))
+        .with_window(4)
+        .with_store(store.clone());
+    let agent = Agent::new(AgentConfig::builder("assistant").build())
+        .with_client(scripted(
+            MockLlmClient::with_responses_vec(vec!["a", "b", "c"]),
+            200,
+            0,
+        ))
+        .with_latency_slo(slo.clone());
+    let stream = EventStream::new();
+
+    for _ in 0..3 {
+        agent
+            .execute_with_events(AgentInput::from_value(json!("hi")), Some(&stream))
+            .await
+            .unwrap();
+    }
+
+    assert!(slow_turn_reports(&stream).await.is_empty());
+    assert!(store.is_empty());
+
+    let stats = agent.latency_slo().unwrap().attainment();
+    assert_eq!(stats.turns, 3);
+    assert_eq!(stats.within_slo, 3);
+    assert_eq!(stats.attainment, 1.0);
+    assert!(stats.p95_ms >= 200.0 && stats.p95_ms < 1_000.0);
+    assert_eq!(slo.tracker().attainment(), stats);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_slow_turn_is_persisted_by_turn_id() {
+    let store = Arc::new(InMemorySlowTurnStore::new());
+    let agent = Agent::new(AgentConfig::builder("assistant").build())
+        .with_client(scripted(
+            MockLlmClient::with_responses_vec(vec!["fast", "slow"]),
+            1_500,
+            0,
+        ))
+        .with_latency_slo(
+            LatencySlo::new(Duration::from_millis(1_000))
+                .with_store(store.clone())
+                .with_max_input_tokens(1_000),
+        );
+
+    let output = agent
+        .execute(&AgentInput::from_value(json!("hi")))
+        .await
+        .unwrap();
+    let turn_id = output.metadata.latency.unwrap().turn_id;
+
+    let report = store.load(&turn_id).await.unwrap().unwrap();
+    assert_eq!(report.agent_name, "assistant");
+    assert!(report.context.utilization.unwrap() > 0.0);
+
+    let stats = agent.latency_slo().unwrap().attainment();
+    assert_eq!(stats.total_slow_turns, 1);
+    assert_eq!(stats.attainment, 0.0);
+}
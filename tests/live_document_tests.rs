@@ -0,0 +1,225 @@
+use agent_runtime::llm::{MockLlmClient, MockResponse};
+use agent_runtime::*;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// An agent that drafts two sections through the document tools
+fn drafter(document: &LiveDocument) -> AgentStep {
+    let mut tools = ToolRegistry::new();
+    document.register_tools(&mut tools);
+    let client = Arc::new(MockLlmClient::from_mock_responses(vec![
+        MockResponse::with_tool_calls(vec![
+            (
+                "write_section",
+                json!({ "name": "Summary", "content": "Revenue grew 8%." }),
+            ),
+            (
+                "write_section",
+                json!({ "name": "Risks", "content": "Supplier delays." }),
+            ),
+        ]),
+        MockResponse::text("Drafted."),
+    ]));
+    let agent = Agent::new(
+        AgentConfig::builder("drafter")
+            .tools(Arc::new(tools))
+            .build(),
+    )
+    .with_client(client);
+    AgentStep::from_agent(agent, "draft".to_string())
+}
+
+/// A transform branch that writes the Outlook section
+fn outlook(document: &LiveDocument, text: &'static str) -> Box<dyn Step> {
+    let document = document.clone();
+    Box::new(TransformStep::new(text.to_string(), move |data| {
+        document.write_section("Outlook", text);
+        data
+    }))
+}
+
+fn report_workflow(document: &LiveDocument) -> Workflow {
+    let reviser = document.clone();
+    Workflow::builder()
+        .name("report".to_string())
+        .step(Box::new(drafter(document)))
+        .step(Box::new(TransformStep::new(
+            "revise".to_string(),
+            move |data| {
+                reviser.append_to_section("Summary", " Costs were flat.");
+                reviser.write_section("Risks", "Supplier delays; FX exposure.");
+                data
+            },
+        )))
+        .step(Box::new(ParallelStep::new(
+            "outlook".to_string(),
+            vec![
+                outlook(document, "Cautious."),
+                outlook(document, "Optimistic."),
+            ],
+        )))
+        .with_live_document(document.clone())
+        .initial_input(json!("Write the Q3 report"))
+        .build()
+}
+
+#[tokio::test]
+async fn test_workflow_builds_and_revises_sections() {
+    let document = LiveDocument::new("Q3 Report").with_output_key("report");
+    let runtime = Runtime::new();
+
+    let run = runtime.execute(report_workflow(&document)).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+
+    let sections: Vec<(String, u32)> = document
+        .sections()
+        .into_iter()
+        .map(|s| (s.name, s.revisions))
+        .collect();
+    assert_eq!(
+        sections,
+        vec![
+            ("Summary".to_string(), 2),
+            ("Risks".to_string(), 2),
+            ("Outlook".to_string(), 2),
+        ]
+    );
+    assert_eq!(
+        document.section("Summary").as_deref(),
+        Some("Revenue grew 8%. Costs were flat.")
+    );
+
+    // Branch writes raced; the history records both and the last one won
+    let outlook = document.section_history("Outlook");
+    assert_eq!(
+        outlook[1].patch.old_hash,
+        Some(outlook[0].patch.new_hash.clone())
+    );
+    assert_eq!(
+        document.section("Outlook").as_deref(),
+        Some(outlook[1].patch.content.as_str())
+    );
+
+    let history = document.history();
+    let revisions: Vec<u64> = history.iter().map(|r| r.patch.revision).collect();
+    assert_eq!(revisions, (1..=6).collect::<Vec<_>>());
+    assert_eq!(history[0].author.as_deref(), Some("drafter"));
+    assert_eq!(history[2].author, None);
+    assert!(history.windows(2).all(|w| w[0].at <= w[1].at));
+
+    let output = run.final_output.unwrap();
+    // The parallel step's array isn't an object, so it moves under "output"
+    assert_eq!(output["output"][0]["response"], "Drafted.");
+    assert_eq!(output["report"], document.to_markdown());
+}
+
+#[tokio::test]
+async fn test_patch_events_match_mutations() {
+    let document = LiveDocument::new("Q3 Report");
+    let runtime = Runtime::new();
+    runtime.execute(report_workflow(&document)).await;
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let patches: Vec<DocumentPatch> = runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .filter(|e| e.component_id == "system:document")
+        .map(|e| {
+            assert_eq!(e.workflow_id, "report");
+            assert_eq!(e.data["document"], "Q3 Report");
+            serde_json::from_value(e.data).unwrap()
+        })
+        .collect();
+    let recorded: Vec<DocumentPatch> = document.history().into_iter().map(|r| r.patch).collect();
+    assert_eq!(patches, recorded);
+
+    let first = &patches[0];
+    assert_eq!(first.section, "Summary");
+    assert_eq!(first.old_hash, None);
+    assert_eq!(first.new_hash, artifact::sha256_hex(b"Revenue grew 8%."));
+    assert_eq!(patches[2].old_hash, Some(first.new_hash.clone()));
+}
+
+#[tokio::test]
+async fn test_document_is_exported_as_artifact() {
+    let document = LiveDocument::new("Q3 Report").with_output_key("report");
+    let runtime = Runtime::new();
+
+    let run = runtime.execute(report_workflow(&document)).await;
+    let reference = run
+        .artifacts
+        .iter()
+        .find(|a| a.name == "report.md")
+        .unwrap();
+    assert_eq!(reference.mime_type, "text/markdown");
+
+    let artifact = runtime.artifact_store().get(&reference.handle).unwrap();
+    let markdown = String::from_utf8(artifact.data.to_vec()).unwrap();
+    assert_eq!(markdown, document.to_markdown());
+    assert!(
+        markdown.starts_with("# Q3 Report\n\n## Summary\n\nRevenue grew 8%. Costs were flat.\n")
+    );
+    assert!(markdown.contains("## Risks\n\nSupplier delays; FX exposure.\n"));
+}
+
+/// A workflow whose sub-workflow shares `document` and writes a section
+fn appendix_workflow(document: &LiveDocument) -> Workflow {
+    let shared = document.clone();
+    Workflow::builder()
+        .name("appendix".to_string())
+        .step(Box::new(SubWorkflowStep::new(
+            "sources".to_string(),
+            move || {
+                let writer = shared.clone();
+                Workflow::builder()
+                    .name("sources".to_string())
+                    .step(Box::new(TransformStep::new(
+                        "cite".to_string(),
+                        move |data| {
+                            writer.write_section("Sources", "Annual filings.");
+                            data
+                        },
+                    )))
+                    .with_live_document(shared.clone())
+                    .build()
+            },
+        )))
+        .with_live_document(document.clone())
+        .initial_input(json!("Add the sources"))
+        .build()
+}
+
+#[tokio::test]
+async fn test_document_belongs_to_its_first_run() {
+    let document = LiveDocument::new("Q3 Report");
+    let runtime = Runtime::new();
+
+    // The sub-workflow's edits are reported under the parent run
+    let first = runtime.execute(appendix_workflow(&document)).await;
+    assert_eq!(first.state, WorkflowState::Completed);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let edits: Vec<String> = runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .filter(|e| e.component_id == "system:document")
+        .map(|e| e.workflow_id)
+        .collect();
+    assert_eq!(edits, vec!["appendix"]);
+
+    // Another run is refused before any step runs
+    let second = runtime.execute(appendix_workflow(&document)).await;
+    assert_eq!(second.state, WorkflowState::Failed);
+    assert!(second.steps.is_empty());
+    assert_eq!(document.history().len(), 1);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let failed = runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .rfind(|e| e.scope == EventScope::Workflow && e.event_type == EventType::Failed)
+        .unwrap();
+    assert_eq!(failed.data["document_run_id"], first.run_id.as_str());
+}
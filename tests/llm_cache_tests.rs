@@ -0,0 +1,194 @@
+/// Tests for answering identical LLM requests from a cache
+use agent_runtime::llm::{
+    CacheStats, CachedChatClient, ChatMessage, ChatRequest, DiskLlmCache, GenericChatClient,
+    LlmCache, MemoryLlmCache, MockLlmClient,
+};
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+fn request(text: &str) -> ChatRequest {
+    ChatRequest::new(vec![ChatMessage::user(text)])
+}
+
+fn cache_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("llm-cache-{}", uuid::Uuid::new_v4()))
+}
+
+async fn stream(client: &dyn GenericChatClient, request: ChatRequest) -> (Vec<String>, String) {
+    let (tx, mut rx) = mpsc::channel(100);
+    let response = client.chat_stream(request, tx).await.unwrap();
+    let mut chunks = Vec::new();
+    while let Some(chunk) = rx.recv().await {
+        chunks.push(chunk);
+    }
+    (chunks, response.content)
+}
+
+#[tokio::test]
+async fn test_identical_request_is_answered_from_cache() {
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec!["Paris", "Lyon"]));
+    let client = CachedChatClient::new(mock.clone(), Arc::new(MemoryLlmCache::new(16)));
+
+    let first = client.chat(request("Capital of France?")).await.unwrap();
+    let second = client.chat(request("Capital of France?")).await.unwrap();
+    assert_eq!(first.content, "Paris");
+    assert_eq!(second.content, "Paris");
+    assert_eq!(mock.call_count(), 1);
+    assert_eq!(
+        client.stats(),
+        CacheStats {
+            hits: 1,
+            misses: 1,
+            bypassed: 0
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_sampling_and_messages_are_part_of_the_key() {
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec!["a", "b", "c"]));
+    let client = CachedChatClient::new(mock.clone(), Arc::new(MemoryLlmCache::new(16)));
+
+    client.chat(request("hello")).await.unwrap();
+    client
+        .chat(request("hello").with_temperature(0.2))
+        .await
+        .unwrap();
+    client.chat(request("hello there")).await.unwrap();
+    assert_eq!(mock.call_count(), 3);
+
+    // Provenance doesn't change what the model is asked
+    let tagged = ChatRequest::new(vec![
+        ChatMessage::user("hello").with_provenance("researcher", "wf-1")
+    ]);
+    assert_eq!(client.key(&tagged), client.key(&request("hello")));
+}
+
+#[tokio::test]
+async fn test_no_cache_bypasses_lookup_and_store() {
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec![
+        "one", "two", "three",
+    ]));
+    let client = CachedChatClient::new(mock.clone(), Arc::new(MemoryLlmCache::new(16)));
+
+    let fresh = client
+        .chat(request("roll a die").with_no_cache())
+        .await
+        .unwrap();
+    assert_eq!(fresh.content, "one");
+    let cached = client.chat(request("roll a die")).await.unwrap();
+    assert_eq!(cached.content, "two");
+    let fresh = client
+        .chat(request("roll a die").with_no_cache())
+        .await
+        .unwrap();
+    assert_eq!(fresh.content, "three");
+    assert_eq!(mock.call_count(), 3);
+    assert_eq!(client.stats().bypassed, 2);
+}
+
+#[tokio::test]
+async fn test_tool_call_responses_are_cached() {
+    let mock = Arc::new(MockLlmClient::from_tool_call(
+        "search",
+        json!({ "query": "moons of Mars" }),
+    ));
+    let client = CachedChatClient::new(mock.clone(), Arc::new(MemoryLlmCache::new(16)));
+
+    let first = client.chat(request("Look it up")).await.unwrap();
+    let second = client.chat(request("Look it up")).await.unwrap();
+    assert_eq!(mock.call_count(), 1);
+    assert_eq!(second.tool_calls, first.tool_calls);
+    assert_eq!(second.tool_calls.unwrap()[0].function.name, "search");
+}
+
+#[tokio::test]
+async fn test_stream_hit_replays_the_content_in_chunks() {
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec![
+        "Mars has two moons",
+    ]));
+    let client = CachedChatClient::new(mock.clone(), Arc::new(MemoryLlmCache::new(16)));
+
+    let (missed, _) = stream(&client, request("Moons of Mars?")).await;
+    assert_eq!(missed.concat(), "Mars has two moons ");
+
+    let (hit, content) = stream(&client, request("Moons of Mars?")).await;
+    assert_eq!(mock.call_count(), 1);
+    assert_eq!(content, "Mars has two moons");
+    assert_eq!(hit, vec!["Mars ", "has ", "two ", "moons"]);
+
+    // A streamed miss also serves later non-streaming requests
+    let response = client.chat(request("Moons of Mars?")).await.unwrap();
+    assert_eq!(response.content, "Mars has two moons");
+    assert_eq!(mock.call_count(), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_memory_entries_expire_after_ttl() {
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec!["old", "new"]));
+    let cache = Arc::new(MemoryLlmCache::new(16).with_ttl(Duration::from_secs(60)));
+    let client = CachedChatClient::new(mock.clone(), cache.clone());
+
+    client.chat(request("news?")).await.unwrap();
+    tokio::time::advance(Duration::from_secs(30)).await;
+    assert_eq!(client.chat(request("news?")).await.unwrap().content, "old");
+
+    tokio::time::advance(Duration::from_secs(31)).await;
+    assert_eq!(client.chat(request("news?")).await.unwrap().content, "new");
+    assert_eq!(mock.call_count(), 2);
+}
+
+#[tokio::test]
+async fn test_memory_cache_evicts_least_recently_used() {
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec!["a", "b", "c", "a2"]));
+    let cache = Arc::new(MemoryLlmCache::new(2));
+    let client = CachedChatClient::new(mock.clone(), cache.clone());
+
+    client.chat(request("a")).await.unwrap();
+    client.chat(request("b")).await.unwrap();
+    client.chat(request("a")).await.unwrap(); // "b" is now least recently used
+    client.chat(request("c")).await.unwrap();
+    assert_eq!(cache.len(), 2);
+    assert_eq!(mock.call_count(), 3);
+
+    assert!(cache.get(&client.key(&request("b"))).is_none());
+    assert_eq!(client.chat(request("a")).await.unwrap().content, "a");
+    assert_eq!(mock.call_count(), 3);
+}
+
+#[tokio::test]
+async fn test_disk_cache_persists_across_clients() {
+    let dir = cache_dir();
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec!["Paris"]));
+    let client = CachedChatClient::new(mock.clone(), Arc::new(DiskLlmCache::new(&dir)));
+    client.chat(request("Capital of France?")).await.unwrap();
+    drop(client);
+
+    let second = Arc::new(MockLlmClient::with_responses_vec(vec!["Marseille"]));
+    let client = CachedChatClient::new(second.clone(), Arc::new(DiskLlmCache::new(&dir)));
+    let response = client.chat(request("Capital of France?")).await.unwrap();
+    assert_eq!(response.content, "Paris");
+    assert_eq!(second.call_count(), 0);
+    assert_eq!(client.stats().hits, 1);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_disk_entries_expire_after_ttl() {
+    let dir = cache_dir();
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec!["old", "new"]));
+    let cache = Arc::new(DiskLlmCache::new(&dir).with_ttl(Duration::from_millis(50)));
+    let client = CachedChatClient::new(mock.clone(), cache);
+
+    client.chat(request("news?")).await.unwrap();
+    assert_eq!(client.chat(request("news?")).await.unwrap().content, "old");
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    assert_eq!(client.chat(request("news?")).await.unwrap().content, "new");
+    assert_eq!(mock.call_count(), 2);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
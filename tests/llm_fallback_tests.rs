@@ -0,0 +1,222 @@
+/// Tests for failing over between LLM providers
+use agent_runtime::llm::{
+    FallbackChatClient, FallbackStats, GenericChatClient, LlmError, LlmResult, MockLlmClient,
+};
+use agent_runtime::*;
+use async_trait::async_trait;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// A provider that always fails, optionally after streaming some text
+struct DownClient {
+    error: fn() -> LlmError,
+    partial: Option<&'static str>,
+    calls: AtomicUsize,
+}
+
+impl DownClient {
+    fn new(error: fn() -> LlmError) -> Arc<Self> {
+        Arc::new(Self {
+            error,
+            partial: None,
+            calls: AtomicUsize::new(0),
+        })
+    }
+
+    fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl GenericChatClient for DownClient {
+    async fn chat(&self, _request: ChatRequest) -> LlmResult<ChatResponse> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Err((self.error)())
+    }
+
+    async fn chat_stream(
+        &self,
+        request: ChatRequest,
+        tx: mpsc::Sender<String>,
+    ) -> LlmResult<ChatResponse> {
+        if let Some(text) = self.partial {
+            let _ = tx.send(text.to_string()).await;
+        }
+        self.chat(request).await
+    }
+}
+
+fn server_error() -> LlmError {
+    LlmError::ApiError("Status 503: llama.cpp is loading the model".to_string())
+}
+
+fn request() -> ChatRequest {
+    ChatRequest::new(vec![ChatMessage::user("Hello")])
+}
+
+#[tokio::test]
+async fn test_fails_over_to_next_provider() {
+    let local = DownClient::new(server_error);
+    let remote = Arc::new(MockLlmClient::new().with_response("Hi from OpenAI"));
+    let client = FallbackChatClient::new()
+        .provider("llama", local.clone())
+        .provider("openai", remote.clone());
+
+    let response = client.chat(request()).await.unwrap();
+    assert_eq!(response.content, "Hi from OpenAI");
+    assert_eq!(response.provider.as_deref(), Some("openai"));
+    assert_eq!(response.failovers.len(), 1);
+    assert_eq!(response.failovers[0].provider, "llama");
+    assert!(response.failovers[0].error.contains("Status 503"));
+
+    assert_eq!(
+        client.stats(),
+        FallbackStats {
+            served: BTreeMap::from([("openai".to_string(), 1)]),
+            failovers: 1,
+            ..Default::default()
+        }
+    );
+
+    // Without a cooldown the primary is tried on every request
+    client.chat(request()).await.unwrap();
+    assert_eq!(local.calls(), 2);
+    assert_eq!(remote.call_count(), 2);
+}
+
+#[tokio::test]
+async fn test_non_retryable_errors_are_not_failed_over() {
+    let remote = Arc::new(MockLlmClient::new().with_response("unused"));
+    let client = FallbackChatClient::new()
+        .provider(
+            "llama",
+            DownClient::new(|| LlmError::InvalidRequest("context too long".to_string())),
+        )
+        .provider("openai", remote.clone());
+
+    let error = client.chat(request()).await.unwrap_err();
+    assert!(matches!(error, LlmError::InvalidRequest(_)));
+    assert_eq!(remote.call_count(), 0);
+
+    // Nor is a stream that already produced text
+    let partial = Arc::new(DownClient {
+        error: server_error,
+        partial: Some("Hel"),
+        calls: AtomicUsize::new(0),
+    });
+    let client = FallbackChatClient::new()
+        .provider("llama", partial)
+        .provider("openai", remote.clone());
+    let (tx, mut rx) = mpsc::channel(16);
+    let error = client.chat_stream(request(), tx).await.unwrap_err();
+    assert!(error.is_retryable());
+    assert_eq!(rx.recv().await.as_deref(), Some("Hel"));
+    assert_eq!(remote.call_count(), 0);
+}
+
+#[tokio::test]
+async fn test_exhausted_chain_returns_last_error() {
+    let client = FallbackChatClient::new()
+        .provider("llama", DownClient::new(server_error))
+        .provider(
+            "openai",
+            DownClient::new(|| LlmError::NetworkError("connection refused".to_string())),
+        );
+
+    let error = client.chat(request()).await.unwrap_err();
+    assert!(matches!(error, LlmError::NetworkError(_)));
+    let stats = client.stats();
+    assert_eq!(stats.failovers, 2);
+    assert_eq!(stats.exhausted, 1);
+    assert!(stats.served.is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_cooldown_skips_failed_provider() {
+    let local = DownClient::new(server_error);
+    let remote = Arc::new(
+        MockLlmClient::new()
+            .with_response("one")
+            .with_response("two")
+            .with_response("three"),
+    );
+    let client = FallbackChatClient::new()
+        .provider("llama", local.clone())
+        .provider("openai", remote)
+        .with_cooldown(Duration::from_secs(30));
+
+    client.chat(request()).await.unwrap();
+    let response = client.chat(request()).await.unwrap();
+    assert_eq!(response.content, "two");
+    assert!(response.failovers.is_empty());
+    assert_eq!(local.calls(), 1);
+    assert_eq!(client.stats().skipped, 1);
+
+    // Once the cooldown is over the primary gets another chance
+    tokio::time::advance(Duration::from_secs(31)).await;
+    client.chat(request()).await.unwrap();
+    assert_eq!(local.calls(), 2);
+}
+
+#[tokio::test]
+async fn test_agent_reports_failover_event() {
+    let client = FallbackChatClient::new()
+        .provider("llama", DownClient::new(server_error))
+        .provider(
+            "openai",
+            Arc::new(MockLlmClient::new().with_response("Served remotely.")),
+        );
+    let agent = Agent::new(AgentConfig::builder("assistant").build()).with_client(Arc::new(client));
+    let stream = EventStream::new();
+
+    let output = agent
+        .execute_with_events(AgentInput::from_value(json!("Hello")), Some(&stream))
+        .await
+        .unwrap();
+    assert_eq!(output.data["response"], "Served remotely.");
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let failover = stream
+        .all()
+        .into_iter()
+        .find(|e| e.component_id == "system:llm_failover")
+        .unwrap();
+    assert_eq!(failover.data["provider"], "openai");
+    assert_eq!(failover.data["failovers"][0]["provider"], "llama");
+}
+
+#[test]
+fn test_chain_from_config() {
+    let config: RuntimeConfig = toml::from_str(
+        r#"
+        [llm]
+        fallback = ["llama", "anthropic", "gemini"]
+        fallback_cooldown_ms = 30000
+
+        [llm.llama]
+        base_url = "http://localhost:8080"
+        insecure = false
+
+        [llm.anthropic]
+        api_key = "sk-ant-test"
+
+        [llm.gemini]
+        api_key = "gemini-test"
+        model = "gemini-2.0-flash"
+        "#,
+    )
+    .unwrap();
+    config.validate().unwrap();
+    let client = FallbackChatClient::from_config(&config.llm).unwrap();
+    assert_eq!(client.providers(), vec!["llama", "anthropic", "gemini"]);
+
+    let mut missing = config.llm.clone();
+    missing.fallback.push("openai".to_string());
+    let error = FallbackChatClient::from_config(&missing).err().unwrap();
+    assert_eq!(error.field.as_deref(), Some("llm.fallback[3]"));
+}
@@ -0,0 +1,161 @@
+/// Tests for logging LLM requests and responses
+use agent_runtime::llm::{
+    ChatMessage, ChatRequest, GenericChatClient, LlmLogConfig, LlmLogEntry, LoggingChatClient,
+    MockLlmClient, LLM_LOG_FILE,
+};
+use agent_runtime::tools::EchoTool;
+use agent_runtime::*;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+fn log_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("llm-log-{}", uuid::Uuid::new_v4()))
+}
+
+fn entries(dir: &Path) -> Vec<LlmLogEntry> {
+    std::fs::read_to_string(dir.join(LLM_LOG_FILE))
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+const SECRET: &str = "sk-proj-4fGh7Jk9Lm2Np5Qr8St1Uv";
+
+#[tokio::test]
+async fn test_entries_are_masked_and_truncated() {
+    let dir = log_dir();
+    let client = LoggingChatClient::new(
+        Arc::new(MockLlmClient::with_responses_vec(vec![
+            "Mailed jo@example.com a receipt",
+        ])),
+        LlmLogConfig::jsonl(&dir)
+            .with_max_content_chars(60)
+            .mask_emails()
+            .mask_card_numbers(),
+    );
+
+    let request = ChatRequest::new(vec![
+        ChatMessage::system(format!("Use api_key={} for billing", SECRET)),
+        ChatMessage::user(format!(
+            "Refund card 4111 1111 1111 1111 for jo@example.com. {}",
+            "Thanks! ".repeat(20)
+        )),
+    ])
+    .with_temperature(0.2);
+    let response = client.chat(request).await.unwrap();
+    assert_eq!(response.content, "Mailed jo@example.com a receipt");
+
+    let text = std::fs::read_to_string(dir.join(LLM_LOG_FILE)).unwrap();
+    assert!(!text.contains(SECRET));
+    assert!(!text.contains("jo@example.com"));
+    assert!(!text.contains("4111"));
+
+    let entries = entries(&dir);
+    assert_eq!(entries.len(), 1);
+    let entry = &entries[0];
+    assert_eq!(entry.provider, "mock");
+    assert_eq!(entry.request.params.temperature, Some(0.2));
+    let messages = entry.request.messages.as_ref().unwrap();
+    assert_eq!(messages[0].content, "Use api_key=[REDACTED] for billing");
+    assert!(messages[1]
+        .content
+        .starts_with("Refund card [REDACTED] for [REDACTED]. Thanks!"));
+    assert!(messages[1].content.ends_with("...[199 chars]"));
+
+    let response = entry.response.as_ref().unwrap();
+    assert_eq!(
+        response.content.as_deref(),
+        Some("Mailed [REDACTED] a receipt")
+    );
+    assert_eq!(response.usage.as_ref().unwrap().total_tokens, 15);
+}
+
+#[tokio::test]
+async fn test_bodies_can_be_left_out() {
+    let dir = log_dir();
+    let client = LoggingChatClient::new(
+        Arc::new(MockLlmClient::new().with_tool_call("lookup", json!({ "order": 42 }))),
+        LlmLogConfig::jsonl(&dir).without_bodies(),
+    );
+
+    let request =
+        ChatRequest::new(vec![ChatMessage::user("Where is order 42?")]).with_tools(vec![json!({
+            "type": "function",
+            "function": { "name": "lookup", "parameters": {} }
+        })]);
+    client.chat(request).await.unwrap();
+
+    let text = std::fs::read_to_string(dir.join(LLM_LOG_FILE)).unwrap();
+    assert!(!text.contains("order 42"));
+    let entry = &entries(&dir)[0];
+    assert_eq!(entry.request.message_count, 1);
+    assert!(entry.request.messages.is_none());
+    assert_eq!(entry.request.tools, vec!["lookup"]);
+    let response = entry.response.as_ref().unwrap();
+    assert!(response.content.is_none());
+    assert_eq!(response.tool_calls[0].name, "lookup");
+    assert!(response.tool_calls[0].arguments.is_none());
+}
+
+#[tokio::test]
+async fn test_agent_streams_are_logged_once_with_correlation() {
+    let dir = log_dir();
+    let client = Arc::new(LoggingChatClient::new(
+        Arc::new(
+            MockLlmClient::new()
+                .with_tool_call("echo", json!({ "message": "hi" }))
+                .with_response("Echoed it back to you"),
+        ),
+        LlmLogConfig::jsonl(&dir),
+    ));
+    let mut registry = ToolRegistry::new();
+    registry.register(EchoTool);
+    let mut input = AgentInput::from_text("Say hi");
+    input.metadata.workflow_id = Some("wf_logged".to_string());
+
+    Agent::new(
+        AgentConfig::builder("greeter")
+            .tools(Arc::new(registry))
+            .build(),
+    )
+    .with_client(client)
+    .execute(&input)
+    .await
+    .unwrap();
+
+    let entries = entries(&dir);
+    assert_eq!(entries.len(), 2);
+    for (i, entry) in entries.iter().enumerate() {
+        assert!(entry.streamed);
+        assert_eq!(entry.metadata["workflow_id"], "wf_logged");
+        assert_eq!(entry.metadata["agent"], "greeter");
+        assert_eq!(entry.metadata["iteration"], (i + 1).to_string());
+    }
+    let answer = &entries[1];
+    assert_eq!(answer.chunks, Some(5));
+    assert!(answer.time_to_first_token_ms.is_some());
+    assert_eq!(
+        answer.response.as_ref().unwrap().content.as_deref(),
+        Some("Echoed it back to you")
+    );
+}
+
+#[tokio::test]
+async fn test_failed_requests_are_logged() {
+    let dir = log_dir();
+    let client = LoggingChatClient::new(
+        Arc::new(MockLlmClient::new().error_on_call(0)),
+        LlmLogConfig::jsonl(&dir),
+    );
+
+    assert!(client
+        .chat(ChatRequest::new(vec![ChatMessage::user("Hi")]))
+        .await
+        .is_err());
+
+    let entry = &entries(&dir)[0];
+    assert!(entry.response.is_none());
+    assert!(entry.error.as_ref().unwrap().contains("Mock network error"));
+}
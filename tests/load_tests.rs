@@ -169,6 +169,7 @@ async fn test_event_broadcast_to_multiple_subscribers() {
             status: agent_runtime::ComponentStatus::Running,
             workflow_id: format!("workflow_{}", i),
             parent_workflow_id: None,
+            workflow_path: Vec::new(),
             message: None,
             data: json!({"step_index": i}),
         };
@@ -384,6 +385,7 @@ async fn test_event_throughput() {
             status: agent_runtime::ComponentStatus::Completed,
             workflow_id: format!("workflow_{}", i),
             parent_workflow_id: None,
+            workflow_path: Vec::new(),
             message: None,
             data: json!({"step_index": i}),
         };
@@ -0,0 +1,148 @@
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::runtime::Runtime;
+use agent_runtime::workflow::step::StepInputMetadata;
+use agent_runtime::*;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A loop around a drafting agent, done once a draft is approved
+fn refine(mock: Arc<MockLlmClient>, max_iterations: usize) -> LoopStep {
+    let agent = Agent::new(
+        AgentConfig::builder("writer")
+            .system_prompt("Improve the draft")
+            .build(),
+    )
+    .with_client(mock);
+    LoopStep::new(
+        "refine".to_string(),
+        Box::new(AgentStep::from_agent(agent, "draft".to_string())),
+        |output| {
+            output["response"]
+                .as_str()
+                .is_some_and(|r| r.starts_with("APPROVED"))
+        },
+        max_iterations,
+    )
+}
+
+fn workflow(step: LoopStep) -> Workflow {
+    Workflow::builder()
+        .name("editor".to_string())
+        .step(Box::new(step))
+        .initial_input(json!("Write a haiku"))
+        .build()
+}
+
+async fn iteration_events(runtime: &Runtime) -> Vec<Value> {
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .filter(|e| e.scope == EventScope::WorkflowStep && e.event_type == EventType::Progress)
+        .map(|e| e.data)
+        .collect()
+}
+
+#[tokio::test]
+async fn test_loop_converges_after_three_iterations() {
+    let runtime = Runtime::new();
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec![
+        "first draft",
+        "second draft",
+        "APPROVED third draft",
+        "never asked for",
+    ]));
+
+    let run = runtime.execute(workflow(refine(mock.clone(), 5))).await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(mock.call_count(), 3);
+    let step = &run.steps[0];
+    assert_eq!(step.step_type, "Loop");
+    let output = step.output.as_ref().unwrap();
+    assert_eq!(output["response"], "APPROVED third draft");
+
+    // Each draft was fed back in as the next iteration's input
+    let last = mock.last_call().unwrap();
+    assert!(last
+        .messages
+        .iter()
+        .any(|m| m.content.text().contains("second draft")));
+
+    let iterations: Vec<(Value, Value)> = iteration_events(&runtime)
+        .await
+        .into_iter()
+        .map(|d| (d["iteration"].clone(), d["condition_met"].clone()))
+        .collect();
+    assert_eq!(
+        iterations,
+        vec![
+            (json!(1), json!(false)),
+            (json!(2), json!(false)),
+            (json!(3), json!(true)),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_loop_reports_iterations_run() {
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec![
+        "draft",
+        "APPROVED draft",
+    ]));
+    let output = refine(mock, 5)
+        .execute(StepInput {
+            data: json!("Write a haiku"),
+            metadata: StepInputMetadata {
+                step_index: 0,
+                previous_step: None,
+                workflow_id: "editor".to_string(),
+                run_id: "run_1".to_string(),
+                item_index: None,
+            },
+            workflow_context: None,
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(output.metadata.step_name, "refine");
+    assert_eq!(output.metadata.step_type, StepType::Loop);
+    assert_eq!(output.metadata.iterations_run, Some(2));
+}
+
+#[tokio::test]
+async fn test_cap_exceeded_fails_by_default() {
+    let runtime = Runtime::new();
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec![
+        "draft 1", "draft 2", "draft 3", "draft 4",
+    ]));
+
+    let run = runtime.execute(workflow(refine(mock.clone(), 3))).await;
+
+    assert_eq!(run.state, WorkflowState::Failed);
+    assert_eq!(mock.call_count(), 3);
+    let failure = run.failure.unwrap();
+    assert_eq!(failure.step_name, "refine");
+    assert_eq!(
+        failure.error.to_string(),
+        "Execution failed: loop 'refine' ran 3 iterations without meeting its condition"
+    );
+    assert_eq!(iteration_events(&runtime).await.len(), 3);
+}
+
+#[tokio::test]
+async fn test_cap_exceeded_can_pass_through_last_output() {
+    let runtime = Runtime::new();
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec![
+        "draft 1", "draft 2", "draft 3", "draft 4",
+    ]));
+    let step = refine(mock.clone(), 3).with_exhausted_mode(LoopExhaustedMode::PassThrough);
+
+    let run = runtime.execute(workflow(step)).await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(mock.call_count(), 3);
+    assert_eq!(run.final_output.unwrap()["response"], "draft 3");
+}
@@ -0,0 +1,330 @@
+/// Tests for the MCP streamable HTTP transport, against an in-process mock
+/// server
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::prelude::TypesToolError as ToolError;
+use agent_runtime::retry::RetryPolicy;
+use agent_runtime::*;
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::Router;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const SESSION_HEADER: &str = "mcp-session-id";
+
+/// A streamable HTTP MCP server with one tool, `echo`, whose replies come
+/// back as SSE events
+#[derive(Default)]
+struct MockServer {
+    /// Required `Authorization` header, if any
+    token: Option<String>,
+    sessions: Mutex<HashSet<String>>,
+    next_session: Mutex<usize>,
+    /// Methods received, in order
+    methods: Mutex<Vec<String>>,
+}
+
+impl MockServer {
+    /// Forget every session, as a restarted server would
+    fn restart(&self) {
+        self.sessions.lock().unwrap().clear();
+    }
+
+    fn methods(&self) -> Vec<String> {
+        self.methods.lock().unwrap().clone()
+    }
+}
+
+async fn handle(
+    State(server): State<Arc<MockServer>>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    if let Some(token) = &server.token {
+        let auth = headers.get("authorization").and_then(|v| v.to_str().ok());
+        if auth != Some(format!("Bearer {}", token).as_str()) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    }
+
+    let message: Value = serde_json::from_str(&body).unwrap();
+    let method = message["method"].as_str().unwrap_or_default().to_string();
+    server.methods.lock().unwrap().push(method.clone());
+
+    if method == "initialize" {
+        let session = {
+            let mut next = server.next_session.lock().unwrap();
+            *next += 1;
+            format!("session-{}", next)
+        };
+        server.sessions.lock().unwrap().insert(session.clone());
+        let result = json!({
+            "protocolVersion": message["params"]["protocolVersion"],
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": "mock", "version": "1.0.0" },
+        });
+        return Response::builder()
+            .header("content-type", "application/json")
+            .header(SESSION_HEADER, session)
+            .body(Body::from(reply(&message, result).to_string()))
+            .unwrap();
+    }
+
+    let session = headers
+        .get(SESSION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if !server.sessions.lock().unwrap().contains(session) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    match method.as_str() {
+        "notifications/initialized" => StatusCode::ACCEPTED.into_response(),
+        "tools/list" => {
+            let result = json!({
+                "tools": [{
+                    "name": "echo",
+                    "description": "Echo the text back",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": { "text": { "type": "string" } },
+                    },
+                }],
+            });
+            Response::builder()
+                .header("content-type", "application/json")
+                .body(Body::from(reply(&message, result).to_string()))
+                .unwrap()
+        }
+        "tools/call" => {
+            let text = message["params"]["arguments"]["text"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            // Longer texts take longer, so concurrent replies arrive out of
+            // order
+            tokio::time::sleep(Duration::from_millis(10 * text.len() as u64)).await;
+            let result = json!({
+                "content": [{ "type": "text", "text": format!("echo: {}", text) }],
+            });
+            Response::builder()
+                .header("content-type", "text/event-stream")
+                .body(Body::from(sse_event(&reply(&message, result))))
+                .unwrap()
+        }
+        _ => StatusCode::BAD_REQUEST.into_response(),
+    }
+}
+
+/// The standalone stream for server-initiated messages: one log
+/// notification, then held open
+async fn open_stream(State(server): State<Arc<MockServer>>) -> Response {
+    server.methods.lock().unwrap().push("GET".to_string());
+    let log = json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/message",
+        "params": { "level": "info", "data": "server started" },
+    });
+    let events =
+        futures::stream::once(async move { Ok::<_, std::convert::Infallible>(sse_event(&log)) })
+            .chain(futures::stream::pending());
+    Response::builder()
+        .header("content-type", "text/event-stream")
+        .body(Body::from_stream(events))
+        .unwrap()
+}
+
+fn sse_event(message: &Value) -> String {
+    format!("event: message\ndata: {}\n\n", message)
+}
+
+fn reply(request: &Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": request["id"], "result": result })
+}
+
+/// Serve `server` on a free local port, returning its MCP endpoint
+async fn serve(server: Arc<MockServer>) -> String {
+    let app = Router::new()
+        .route(
+            "/mcp",
+            post(handle)
+                .get(open_stream)
+                .delete(|| async { StatusCode::OK }),
+        )
+        .with_state(server);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}/mcp", addr)
+}
+
+fn text(params: Value) -> std::collections::HashMap<String, Value> {
+    serde_json::from_value(params).unwrap()
+}
+
+#[tokio::test]
+async fn test_http_client_lists_and_calls_tools() {
+    let server = Arc::new(MockServer::default());
+    let client = McpClient::connect_http(&serve(server.clone()).await)
+        .await
+        .unwrap();
+
+    let tools = client.list_tools().await.unwrap();
+    assert_eq!(tools.len(), 1);
+    assert_eq!(tools[0].name, "echo");
+    assert_eq!(tools[0].description, "Echo the text back");
+    assert_eq!(
+        tools[0].input_schema["properties"]["text"]["type"],
+        "string"
+    );
+
+    let output = client
+        .call_tool("echo", text(json!({ "text": "hi" })))
+        .await
+        .unwrap();
+    assert_eq!(output, json!("echo: hi"));
+
+    // The handshake opened the server's stream before confirming, and its
+    // notification didn't disturb the calls
+    assert_eq!(
+        server.methods(),
+        [
+            "initialize",
+            "GET",
+            "notifications/initialized",
+            "tools/list",
+            "tools/call"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_concurrent_calls_get_their_own_replies() {
+    let server = Arc::new(MockServer::default());
+    let client = McpClient::connect_http(&serve(server).await).await.unwrap();
+
+    // The slower call is sent first and answered last
+    let (slow, fast) = tokio::join!(
+        client.call_tool("echo", text(json!({ "text": "a long message" }))),
+        client.call_tool("echo", text(json!({ "text": "hi" }))),
+    );
+    assert_eq!(slow.unwrap(), json!("echo: a long message"));
+    assert_eq!(fast.unwrap(), json!("echo: hi"));
+}
+
+#[tokio::test]
+async fn test_bearer_token_is_sent() {
+    let server = Arc::new(MockServer {
+        token: Some("secret".to_string()),
+        ..Default::default()
+    });
+    let url = serve(server.clone()).await;
+
+    assert!(McpClient::connect_http(&url).await.is_err());
+
+    let client = McpClient::connect(McpTransport::http(&url).with_bearer_token("secret"))
+        .await
+        .unwrap();
+    assert_eq!(client.list_tools().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_reconnects_after_server_restart() {
+    let server = Arc::new(MockServer::default());
+    let client = McpClient::connect_with_reconnect(
+        McpTransport::http(serve(server.clone()).await),
+        RetryPolicy::new(2, Duration::from_millis(10)),
+    )
+    .await
+    .unwrap();
+    assert_eq!(client.list_tools().await.unwrap().len(), 1);
+
+    // Listing is retried on the new session
+    server.restart();
+    assert_eq!(client.list_tools().await.unwrap().len(), 1);
+
+    // A call is not, but fails as transient and succeeds when sent again
+    server.restart();
+    let tool = McpTool::from_info(client.list_tools().await.unwrap().remove(0), client.clone());
+    server.restart();
+    let failed = tool.execute(text(json!({ "text": "hi" }))).await;
+    assert!(matches!(failed, Err(ToolError::Transient(_))));
+    let output = tool.execute(text(json!({ "text": "hi" }))).await.unwrap();
+    assert_eq!(output.output, json!("echo: hi"));
+
+    let handshakes = server
+        .methods()
+        .iter()
+        .filter(|m| *m == "initialize")
+        .count();
+    assert_eq!(handshakes, 4);
+}
+
+#[tokio::test]
+async fn test_agent_uses_http_tools_like_any_other() {
+    let server = Arc::new(MockServer::default());
+    let client = McpClient::connect_http(&serve(server).await).await.unwrap();
+
+    let mut registry = ToolRegistry::new();
+    for info in client.list_tools().await.unwrap() {
+        registry.register(McpTool::from_info(info, client.clone()));
+    }
+    let mock = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("echo", json!({ "text": "ping" }))
+            .with_response("The server said ping."),
+    );
+    let agent = Agent::new(
+        AgentConfig::builder("remote")
+            .tools(Arc::new(registry))
+            .build(),
+    )
+    .with_client(mock.clone());
+
+    let output = agent
+        .execute(&AgentInput::from_value(json!("Echo ping")))
+        .await
+        .unwrap();
+    assert_eq!(output.data["response"], "The server said ping.");
+    let served = mock.last_call().unwrap();
+    assert!(served
+        .messages
+        .iter()
+        .any(|m| m.content.text().contains("echo: ping")));
+}
+
+#[tokio::test]
+async fn test_server_tools_register_under_a_namespace() {
+    let server = Arc::new(MockServer::default());
+    let client = McpClient::connect_http(&serve(server).await).await.unwrap();
+
+    let mut registry = ToolRegistry::new();
+    let names = client.register_tools("mock", &mut registry).await.unwrap();
+    assert_eq!(names, vec!["mock.echo"]);
+    assert!(registry.has_tool("mock.echo"));
+    assert!(!registry.has_tool("echo"));
+
+    // Called by the server's own name
+    let output = registry
+        .call_tool("mock.echo", text(json!({ "text": "hi" })))
+        .await
+        .unwrap();
+    assert_eq!(output.output, json!("echo: hi"));
+
+    // A second server under the same namespace would shadow it
+    let error = client
+        .register_tools("mock", &mut registry)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error,
+        "Tool 'mock.echo' from MCP server 'mock' is already registered"
+    );
+    assert_eq!(registry.len(), 1);
+}
@@ -0,0 +1,339 @@
+/// Tests for MCP resources and prompts, against an in-process mock server
+/// speaking streamable HTTP
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::*;
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::Router;
+use base64::Engine;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const PNG: &[u8] = &[0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+/// A server with a handbook, a logo and one prompt, `code_review`
+struct MockServer {
+    resources: Mutex<Vec<Value>>,
+    /// Methods received, in order
+    methods: Mutex<Vec<String>>,
+    /// Arguments of each `prompts/get`
+    prompt_arguments: Mutex<Vec<Value>>,
+    /// Messages for the stream of server-initiated messages
+    notifications: tokio::sync::broadcast::Sender<Value>,
+}
+
+impl MockServer {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            resources: Mutex::new(vec![
+                json!({
+                    "uri": "docs://handbook.md",
+                    "name": "handbook",
+                    "description": "Team handbook",
+                    "mimeType": "text/markdown",
+                }),
+                json!({ "uri": "docs://logo.png", "name": "logo", "mimeType": "image/png" }),
+            ]),
+            methods: Mutex::default(),
+            prompt_arguments: Mutex::default(),
+            notifications: tokio::sync::broadcast::channel(16).0,
+        })
+    }
+
+    /// Offer another resource, and say so
+    fn add_resource(&self, resource: Value) {
+        self.resources.lock().unwrap().push(resource);
+        let _ = self.notifications.send(json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/resources/list_changed",
+        }));
+    }
+
+    fn count(&self, method: &str) -> usize {
+        self.methods
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|m| *m == method)
+            .count()
+    }
+}
+
+async fn handle(State(server): State<Arc<MockServer>>, body: String) -> Response {
+    let message: Value = serde_json::from_str(&body).unwrap();
+    let method = message["method"].as_str().unwrap_or_default().to_string();
+    server.methods.lock().unwrap().push(method.clone());
+    let params = &message["params"];
+
+    let result = match method.as_str() {
+        "initialize" => json!({
+            "protocolVersion": params["protocolVersion"],
+            "capabilities": { "resources": { "listChanged": true }, "prompts": {} },
+            "serverInfo": { "name": "mock", "version": "1.0.0" },
+        }),
+        "notifications/initialized" => return StatusCode::ACCEPTED.into_response(),
+        "resources/list" => json!({ "resources": *server.resources.lock().unwrap() }),
+        "resources/read" => match params["uri"].as_str().unwrap() {
+            "docs://handbook.md" => json!({ "contents": [{
+                "uri": "docs://handbook.md",
+                "mimeType": "text/markdown",
+                "text": "# Handbook\nShip on Tuesdays.",
+            }] }),
+            "docs://logo.png" => json!({ "contents": [{
+                "uri": "docs://logo.png",
+                "mimeType": "image/png",
+                "blob": base64::engine::general_purpose::STANDARD.encode(PNG),
+            }] }),
+            uri => {
+                let error = json!({
+                    "jsonrpc": "2.0",
+                    "id": message["id"],
+                    "error": { "code": -32002, "message": format!("Resource not found: {}", uri) },
+                });
+                return json_response(error);
+            }
+        },
+        "prompts/list" => json!({ "prompts": [{
+            "name": "code_review",
+            "description": "Review code in a language",
+            "arguments": [{ "name": "language", "description": "Language of the code", "required": true }],
+        }] }),
+        "prompts/get" => {
+            server
+                .prompt_arguments
+                .lock()
+                .unwrap()
+                .push(params["arguments"].clone());
+            let language = params["arguments"]["language"].as_str().unwrap_or("any");
+            json!({
+                "description": "Code review",
+                "messages": [
+                    { "role": "user", "content": { "type": "text", "text": format!("You review {} code.", language) } },
+                    { "role": "user", "content": { "type": "image", "data": "AAAA", "mimeType": "image/png" } },
+                    { "role": "assistant", "content": {
+                        "type": "resource",
+                        "resource": { "uri": "docs://style.md", "text": "Flag unwrap() calls." },
+                    } },
+                ],
+            })
+        }
+        _ => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    json_response(json!({ "jsonrpc": "2.0", "id": message["id"], "result": result }))
+}
+
+fn json_response(message: Value) -> Response {
+    Response::builder()
+        .header("content-type", "application/json")
+        .header("mcp-session-id", "session-1")
+        .body(Body::from(message.to_string()))
+        .unwrap()
+}
+
+/// The stream for server-initiated messages, held open
+async fn open_stream(State(server): State<Arc<MockServer>>) -> Response {
+    let events = futures::stream::unfold(server.notifications.subscribe(), |mut rx| async {
+        let message = rx.recv().await.ok()?;
+        let event = format!("event: message\ndata: {}\n\n", message);
+        Some((Ok::<_, std::convert::Infallible>(event), rx))
+    });
+    Response::builder()
+        .header("content-type", "text/event-stream")
+        .body(Body::from_stream(events))
+        .unwrap()
+}
+
+async fn connect(server: Arc<MockServer>) -> Arc<McpClient> {
+    let app = Router::new()
+        .route(
+            "/mcp",
+            post(handle)
+                .get(open_stream)
+                .delete(|| async { StatusCode::OK }),
+        )
+        .with_state(server);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    McpClient::connect_http(&format!("http://{}/mcp", addr))
+        .await
+        .unwrap()
+}
+
+fn uri(uri: &str) -> HashMap<String, Value> {
+    HashMap::from([("uri".to_string(), json!(uri))])
+}
+
+#[tokio::test]
+async fn test_text_resources_are_listed_and_read() {
+    let client = connect(MockServer::new()).await;
+
+    let resources = client.list_resources().await.unwrap();
+    assert_eq!(resources.len(), 2);
+    assert_eq!(resources[0].uri, "docs://handbook.md");
+    assert_eq!(resources[0].name, "handbook");
+    assert_eq!(resources[0].description.as_deref(), Some("Team handbook"));
+    assert_eq!(resources[1].mime_type.as_deref(), Some("image/png"));
+
+    let contents = client.read_resource("docs://handbook.md").await.unwrap();
+    assert_eq!(
+        contents,
+        [McpResourceContents::Text {
+            uri: "docs://handbook.md".to_string(),
+            mime_type: Some("text/markdown".to_string()),
+            text: "# Handbook\nShip on Tuesdays.".to_string(),
+        }]
+    );
+
+    let missing = client.read_resource("docs://missing.md").await.unwrap_err();
+    assert!(missing.contains("Resource not found: docs://missing.md"));
+}
+
+#[tokio::test]
+async fn test_binary_resource_is_decoded_from_base64() {
+    let client = connect(MockServer::new()).await;
+
+    let contents = client.read_resource("docs://logo.png").await.unwrap();
+    let McpResourceContents::Blob {
+        data, mime_type, ..
+    } = &contents[0]
+    else {
+        panic!("expected a blob, got {:?}", contents);
+    };
+    assert_eq!(data, PNG);
+    assert_eq!(mime_type.as_deref(), Some("image/png"));
+
+    // The tool sends it on as base64...
+    let tool = McpResourceTool::new("docs", vec![], client.clone());
+    let output = tool.execute(uri("docs://logo.png")).await.unwrap().output;
+    let part = &output["contents"][0];
+    assert_eq!(part["encoding"], "base64");
+    assert_eq!(part["bytes"], 8);
+    assert_eq!(
+        part["blob"],
+        base64::engine::general_purpose::STANDARD.encode(PNG)
+    );
+    assert_eq!(part["truncated"], false);
+
+    // ...unless it's over the size limit, when it's left out
+    let tool = McpResourceTool::new("docs", vec![], client).with_max_bytes(8);
+    let output = tool.execute(uri("docs://logo.png")).await.unwrap().output;
+    let part = &output["contents"][0];
+    assert_eq!(part["truncated"], true);
+    assert!(part.get("blob").is_none());
+}
+
+#[tokio::test]
+async fn test_agent_reads_a_resource_listed_in_the_tool_description() {
+    let client = connect(MockServer::new()).await;
+    let registry = Arc::new(ToolRegistry::new());
+
+    let name = client.register_resources("docs", &registry).await.unwrap();
+    assert_eq!(name, "docs.read_resource");
+    let description = registry.get(&name).unwrap().description().to_string();
+    assert!(description.contains("- docs://handbook.md (handbook, text/markdown): Team handbook"));
+    assert!(description.contains("- docs://logo.png (logo, image/png)"));
+    assert!(client.register_resources("docs", &registry).await.is_err());
+
+    let mock = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("docs.read_resource", json!({ "uri": "docs://handbook.md" }))
+            .with_response("We ship on Tuesdays."),
+    );
+    let agent = Agent::new(AgentConfig::builder("assistant").tools(registry).build())
+        .with_client(mock.clone());
+    let output = agent
+        .execute(&AgentInput::from_text("When do we ship?"))
+        .await
+        .unwrap();
+
+    assert_eq!(output.data["response"], "We ship on Tuesdays.");
+    let last = mock.last_call().unwrap();
+    let result = last.messages.iter().find(|m| m.role == Role::Tool).unwrap();
+    assert!(result.content.text().contains("Ship on Tuesdays."));
+}
+
+#[tokio::test]
+async fn test_prompt_renders_into_a_system_prompt() {
+    let server = MockServer::new();
+    let client = connect(server.clone()).await;
+
+    let prompts = client.list_prompts().await.unwrap();
+    assert_eq!(prompts[0].name, "code_review");
+    assert_eq!(prompts[0].arguments[0].name, "language");
+    assert!(prompts[0].arguments[0].required);
+
+    let arguments = HashMap::from([("language".to_string(), "Rust".to_string())]);
+    let messages = client
+        .get_prompt("code_review", arguments.clone())
+        .await
+        .unwrap();
+    // The image is left out; the embedded resource keeps its text
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].role, Role::User);
+    assert_eq!(messages[1].role, Role::Assistant);
+
+    let prompt = client
+        .prompt("code_review")
+        .into_system_prompt(arguments)
+        .await
+        .unwrap();
+    assert_eq!(prompt, "You review Rust code.\n\nFlag unwrap() calls.");
+    assert_eq!(
+        server.prompt_arguments.lock().unwrap()[1],
+        json!({ "language": "Rust" })
+    );
+
+    let config = AgentConfig::builder("reviewer")
+        .system_prompt(prompt.clone())
+        .build();
+    assert_eq!(config.system_prompt, prompt);
+}
+
+#[tokio::test]
+async fn test_resource_list_changed_refreshes_the_tool() {
+    let server = MockServer::new();
+    let client = connect(server.clone()).await;
+    let registry = Arc::new(ToolRegistry::new());
+    let name = client.register_resources("docs", &registry).await.unwrap();
+    let generation = registry.generation();
+    let mut changes = client.resources_changed();
+
+    server.add_resource(json!({ "uri": "docs://runbook.md", "name": "runbook" }));
+    tokio::time::timeout(Duration::from_secs(5), changes.changed())
+        .await
+        .unwrap()
+        .unwrap();
+
+    // The tool is registered again with the new list
+    let refreshed = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let description = registry.get(&name).unwrap().description().to_string();
+            if description.contains("docs://runbook.md") {
+                break description;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert!(refreshed.contains("docs://handbook.md"));
+    assert!(registry.generation() > generation);
+    assert_eq!(server.count("resources/list"), 2);
+
+    // An unregistered tool stays unregistered
+    registry.unregister(&name);
+    server.add_resource(json!({ "uri": "docs://faq.md", "name": "faq" }));
+    tokio::time::timeout(Duration::from_secs(5), changes.changed())
+        .await
+        .unwrap()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!registry.has_tool(&name));
+    assert_eq!(server.count("resources/list"), 2);
+}
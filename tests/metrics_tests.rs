@@ -0,0 +1,141 @@
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::metrics::render_prometheus;
+use agent_runtime::prelude::TypesToolError as ToolError;
+use agent_runtime::retry::RetryPolicy;
+use agent_runtime::runtime::Runtime;
+use agent_runtime::*;
+use serde_json::json;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// The registry is process-wide, so each test uses its own agent and tool
+// names
+
+fn workflow(name: &str, agent: Agent, step: &str) -> Workflow {
+    Workflow::builder()
+        .name(name.to_string())
+        .step(Box::new(AgentStep::from_agent(agent, step.to_string())))
+        .initial_input(json!("Look it up"))
+        .build()
+}
+
+/// Fails transiently on its first call
+fn flaky_lookup(name: &str) -> NativeTool {
+    let calls = Arc::new(AtomicUsize::new(0));
+    NativeTool::new(
+        name,
+        "Look something up",
+        json!({"type": "object", "properties": {"q": {"type": "string"}}}),
+        move |_params| {
+            let calls = calls.clone();
+            async move {
+                if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return Err(ToolError::transient("connection reset"));
+                }
+                Ok(ToolResult::success(json!({"found": true}), 1.0))
+            }
+        },
+    )
+}
+
+#[tokio::test]
+async fn test_successful_run_renders_every_family() {
+    let mut registry = ToolRegistry::new();
+    registry.register(flaky_lookup("metrics_lookup"));
+    let config = AgentConfig::builder("metrics_researcher")
+        .system_prompt("Research")
+        .tools(Arc::new(registry))
+        .tool_retry(RetryPolicy::new(2, Duration::from_millis(1)))
+        .build();
+    let mock = MockLlmClient::new()
+        .with_tool_call("metrics_lookup", json!({"q": "moons"}))
+        // The same call again is stopped by loop detection
+        .with_tool_call("metrics_lookup", json!({"q": "moons"}))
+        .with_response("Mars has two moons");
+    let agent = Agent::new(config).with_client(Arc::new(mock));
+
+    let run = Runtime::new()
+        .execute(workflow("metrics_ok", agent, "metrics_research"))
+        .await;
+    assert_eq!(run.state, WorkflowState::Completed);
+
+    let text = render_prometheus();
+    for family in [
+        "agent_runtime_workflow_duration_seconds histogram",
+        "agent_runtime_step_duration_seconds histogram",
+        "agent_runtime_agent_duration_seconds histogram",
+        "agent_runtime_llm_request_duration_seconds histogram",
+        "agent_runtime_llm_tokens_total counter",
+        "agent_runtime_llm_errors_total counter",
+        "agent_runtime_llm_cache_lookups_total counter",
+        "agent_runtime_tool_call_duration_seconds histogram",
+        "agent_runtime_tool_errors_total counter",
+        "agent_runtime_retries_total counter",
+        "agent_runtime_tool_loop_detections_total counter",
+    ] {
+        assert!(text.contains(&format!("# TYPE {}\n", family)), "{}", family);
+    }
+
+    assert!(text.contains("agent_runtime_workflow_duration_seconds_count{outcome=\"completed\"}"));
+    assert!(text.contains(
+        "agent_runtime_step_duration_seconds_count{step_type=\"agent\",step=\"metrics_research\",outcome=\"ok\"} 1\n"
+    ));
+    assert!(text.contains(
+        "agent_runtime_agent_duration_seconds_count{agent=\"metrics_researcher\",outcome=\"ok\"} 1\n"
+    ));
+    assert!(text.contains(
+        "agent_runtime_llm_request_duration_seconds_count{provider=\"mock\",model=\"mock-model\"}"
+    ));
+    assert!(text.contains(
+        "agent_runtime_llm_tokens_total{provider=\"mock\",model=\"mock-model\",kind=\"completion\"}"
+    ));
+    // One logical call, retried once; the repeat never ran
+    assert!(text
+        .contains("agent_runtime_tool_call_duration_seconds_count{tool=\"metrics_lookup\"} 1\n"));
+    assert!(text.contains(
+        "agent_runtime_tool_call_duration_seconds_bucket{tool=\"metrics_lookup\",le=\"+Inf\"} 1\n"
+    ));
+    assert!(text.contains("agent_runtime_retries_total{component=\"tool\"}"));
+    assert!(text.contains(
+        "agent_runtime_tool_loop_detections_total{agent=\"metrics_researcher\",tool=\"metrics_lookup\"} 1\n"
+    ));
+    assert!(!text.contains("agent_runtime_tool_errors_total{tool=\"metrics_lookup\"}"));
+}
+
+#[tokio::test]
+async fn test_failures_are_counted() {
+    let mut registry = ToolRegistry::new();
+    registry.register(NativeTool::new(
+        "metrics_broken",
+        "Always fails",
+        json!({"type": "object", "properties": {}}),
+        |_params| async move { Err(ToolError::ExecutionFailed("disk full".into())) },
+    ));
+    let config = AgentConfig::builder("metrics_unlucky")
+        .system_prompt("Try")
+        .tools(Arc::new(registry))
+        .build();
+    // The tool fails, then the LLM request does
+    let mock = MockLlmClient::new()
+        .with_tool_call("metrics_broken", json!({}))
+        .with_response("never sent")
+        .error_on_call(1);
+    let agent = Agent::new(config).with_client(Arc::new(mock));
+
+    let run = Runtime::new()
+        .execute(workflow("metrics_failed", agent, "metrics_attempt"))
+        .await;
+    assert_eq!(run.state, WorkflowState::Failed);
+
+    let text = render_prometheus();
+    assert!(text.contains("agent_runtime_tool_errors_total{tool=\"metrics_broken\"} 1\n"));
+    assert!(text.contains("agent_runtime_llm_errors_total{provider=\"mock\"}"));
+    assert!(text.contains(
+        "agent_runtime_agent_duration_seconds_count{agent=\"metrics_unlucky\",outcome=\"error\"} 1\n"
+    ));
+    assert!(text.contains(
+        "agent_runtime_step_duration_seconds_count{step_type=\"agent\",step=\"metrics_attempt\",outcome=\"error\"} 1\n"
+    ));
+    assert!(text.contains("agent_runtime_workflow_duration_seconds_count{outcome=\"failed\"}"));
+}
@@ -0,0 +1,255 @@
+use agent_runtime::agent::benchmark::{BenchmarkTask, Grader, ModelBenchmark};
+use agent_runtime::llm::types::Usage;
+use agent_runtime::llm::{GenericChatClient, LlmClient, LlmResult, MockLlmClient};
+use agent_runtime::usage::CostPerMToken;
+use agent_runtime::*;
+use async_trait::async_trait;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+/// Answers by looking up the last user message, so results don't depend on
+/// the order concurrent cells run in; also records every seed it was sent
+struct ScriptedModel {
+    answers: HashMap<&'static str, &'static str>,
+    calls: AtomicUsize,
+    seeds: Mutex<Vec<Option<u64>>>,
+}
+
+impl ScriptedModel {
+    fn new(answers: &[(&'static str, &'static str)]) -> Arc<Self> {
+        Arc::new(Self {
+            answers: answers.iter().copied().collect(),
+            calls: AtomicUsize::new(0),
+            seeds: Mutex::new(Vec::new()),
+        })
+    }
+
+    fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl GenericChatClient for ScriptedModel {
+    async fn chat(&self, request: ChatRequest) -> LlmResult<ChatResponse> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        self.seeds.lock().unwrap().push(request.seed);
+        let question = request
+            .messages
+            .iter()
+            .rev()
+            .find(|m| m.role == Role::User)
+            .map(|m| m.content.to_string())
+            .unwrap_or_default();
+        Ok(ChatResponse {
+            content: self
+                .answers
+                .get(question.as_str())
+                .copied()
+                .unwrap_or("no idea")
+                .to_string(),
+            model: "scripted".to_string(),
+            usage: Some(Usage {
+                prompt_tokens: 100,
+                completion_tokens: 20,
+                total_tokens: 120,
+                reasoning_tokens: None,
+            }),
+            finish_reason: Some("stop".to_string()),
+            tool_calls: None,
+            warnings: vec![],
+            provider: None,
+            failovers: Vec::new(),
+        })
+    }
+
+    async fn chat_stream(
+        &self,
+        request: ChatRequest,
+        tx: mpsc::Sender<String>,
+    ) -> LlmResult<ChatResponse> {
+        let response = self.chat(request).await?;
+        let _ = tx.send(response.content.clone()).await;
+        Ok(response)
+    }
+}
+
+fn suite() -> Vec<BenchmarkTask> {
+    vec![
+        BenchmarkTask::new("sum", "What is 2 + 2?", Grader::exact("4")),
+        BenchmarkTask::new(
+            "capital",
+            "Capital of France?",
+            Grader::regex(r"(?i)\bparis\b"),
+        ),
+        BenchmarkTask::new(
+            "extract",
+            "Extract the age from: Ann is 31.",
+            Grader::json_field("/age", json!(31)),
+        ),
+    ]
+}
+
+fn strong() -> Arc<ScriptedModel> {
+    ScriptedModel::new(&[
+        ("What is 2 + 2?", "4"),
+        ("Capital of France?", "It is Paris."),
+        (
+            "Extract the age from: Ann is 31.",
+            "{\"name\": \"Ann\", \"age\": 31}",
+        ),
+    ])
+}
+
+fn weak() -> Arc<ScriptedModel> {
+    ScriptedModel::new(&[
+        ("What is 2 + 2?", "4"),
+        ("Capital of France?", "I'm sorry, I can't answer that."),
+        ("Extract the age from: Ann is 31.", "Ann is thirty-one"),
+    ])
+}
+
+fn template() -> AgentConfig {
+    AgentConfig::builder("candidate")
+        .system_prompt("Answer concisely.")
+        .build()
+}
+
+#[tokio::test]
+async fn test_two_models_produce_expected_scores() {
+    let (strong, weak) = (strong(), weak());
+    let report = ModelBenchmark::new(template(), suite())
+        .model("strong", strong.clone())
+        .model("weak", weak.clone())
+        .price("strong", CostPerMToken::new(10.0, 30.0))
+        .repetitions(2)
+        .concurrency(4)
+        .seed(7)
+        .run()
+        .await
+        .unwrap();
+
+    let s = report.model("strong").unwrap();
+    assert_eq!((s.runs, s.correct, s.accuracy), (6, 6, 1.0));
+    assert_eq!((s.refusals, s.parse_failures, s.errors), (0, 0, 0));
+    assert_eq!((s.prompt_tokens, s.completion_tokens), (600, 120));
+    // 600 × $10/M + 120 × $30/M
+    assert!((s.cost_usd.unwrap() - 0.0096).abs() < 1e-12);
+    assert!(s.latency_p50_ms <= s.latency_p90_ms && s.latency_p90_ms <= s.latency_p99_ms);
+
+    let w = report.model("weak").unwrap();
+    assert_eq!((w.runs, w.correct), (6, 2));
+    assert!((w.accuracy - 1.0 / 3.0).abs() < 1e-12);
+    assert_eq!((w.refusals, w.parse_failures, w.errors), (2, 2, 0));
+    assert_eq!(w.cost_usd, None);
+
+    let scores: Vec<_> = report
+        .tasks
+        .iter()
+        .map(|t| (t.task_id.as_str(), t.scores[0].1, t.scores[1].1))
+        .collect();
+    assert_eq!(
+        scores,
+        vec![("sum", 2, 2), ("capital", 2, 0), ("extract", 2, 0)]
+    );
+
+    // Cells come back in model, task, repetition order whatever ran first
+    let order: Vec<_> = report
+        .cells
+        .iter()
+        .map(|c| (c.model.as_str(), c.task_id.as_str(), c.repetition))
+        .collect();
+    assert_eq!(order[0], ("strong", "sum", 0));
+    assert_eq!(order[1], ("strong", "sum", 1));
+    assert_eq!(order[6], ("weak", "sum", 0));
+
+    // A fresh agent per cell: one call each, and every one carried the seed
+    assert_eq!((strong.calls(), weak.calls()), (6, 6));
+    assert!(strong.seeds.lock().unwrap().iter().all(|s| *s == Some(7)));
+
+    let markdown = report.to_markdown();
+    assert!(markdown.contains("| strong | 100.0% (6/6) |"));
+    assert!(markdown.contains("| weak | 33.3% (2/6) |"));
+    assert!(markdown.contains("| $0.0096 |"));
+    assert!(markdown.contains("| capital | 2/2 | 0/2 |"));
+}
+
+#[tokio::test]
+async fn test_resumes_from_checkpoint() {
+    let path = std::env::temp_dir().join(format!("benchmark-{}.jsonl", uuid::Uuid::new_v4()));
+
+    // First session only covers the first task
+    let first = strong();
+    let partial = ModelBenchmark::new(template(), suite()[..1].to_vec())
+        .model("strong", first.clone())
+        .checkpoint_file(&path)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!((partial.cells.len(), partial.resumed_cells), (1, 0));
+    assert_eq!(first.calls(), 1);
+
+    // Simulate a crash mid-write
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .and_then(|mut f| std::io::Write::write_all(&mut f, b"{\"model\":\"strong\",\"ta"))
+        .unwrap();
+
+    let second = strong();
+    let report = ModelBenchmark::new(template(), suite())
+        .model("strong", second.clone())
+        .checkpoint_file(&path)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(report.resumed_cells, 1);
+    assert_eq!(second.calls(), 2, "the finished cell is not run again");
+    assert_eq!(report.model("strong").unwrap().correct, 3);
+
+    // Everything is recorded now, so a third session runs nothing
+    let third = strong();
+    let report = ModelBenchmark::new(template(), suite())
+        .model("strong", third.clone())
+        .checkpoint_file(&path)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!((report.resumed_cells, third.calls()), (3, 0));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_judge_grader() {
+    let judge = Arc::new(MockLlmClient::with_responses_vec(vec!["PASS", "FAIL"]));
+    let tasks = vec![BenchmarkTask::new(
+        "haiku",
+        "Write a haiku about rust.",
+        Grader::judge(judge.clone(), "Three lines, about the Rust language"),
+    )];
+    let candidate = ScriptedModel::new(&[(
+        "Write a haiku about rust.",
+        "Borrow checker sighs\nlifetimes fold like origami\nthe build is green now",
+    )]);
+
+    let report = ModelBenchmark::new(template(), tasks)
+        .model("poet", candidate as LlmClient)
+        .repetitions(2)
+        .run()
+        .await
+        .unwrap();
+
+    let poet = report.model("poet").unwrap();
+    assert_eq!((poet.runs, poet.correct), (2, 1));
+
+    let calls = judge.get_calls();
+    assert_eq!(calls.len(), 2);
+    let prompt = calls[0].messages[1].content.text();
+    assert!(prompt.contains("Write a haiku about rust."));
+    assert!(prompt.contains("Three lines, about the Rust language"));
+    assert!(prompt.contains("Borrow checker sighs"));
+}
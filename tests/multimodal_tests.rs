@@ -0,0 +1,178 @@
+/// Tests for sending images to vision models, against a scripted local
+/// HTTP server
+use agent_runtime::llm::{
+    ChatMessage, ChatRequest, ClaudeClient, ContentPart, GeminiClient, GenericChatClient,
+    LlamaClient, LlmError, MockLlmClient, OpenAIClient,
+};
+use agent_runtime::{Agent, AgentConfig, AgentInput};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+mod common;
+use common::Recorded;
+
+/// Reply to each connection with `body`, recording the requests
+async fn serve(replies: usize, body: Value) -> (String, Arc<Mutex<Vec<Recorded>>>) {
+    let (url, recorded) = common::serve(vec![body; replies]).await;
+    (format!("{}/v1", url), recorded)
+}
+
+fn completion() -> Value {
+    json!({
+        "model": "vision",
+        "choices": [{ "message": { "content": "An otter" }, "finish_reason": "stop" }]
+    })
+}
+
+fn images() -> [ContentPart; 2] {
+    [
+        ContentPart::image_url("https://example.com/otter.jpg"),
+        ContentPart::image_base64("image/png", "iVBORw0KGgo="),
+    ]
+}
+
+fn request() -> ChatRequest {
+    ChatRequest::new(vec![
+        ChatMessage::system("Describe images"),
+        ChatMessage::user_with_images("What are these?", images()),
+    ])
+}
+
+/// The chat completions form OpenAI and llama.cpp share
+fn chat_completions_parts() -> Value {
+    json!([
+        { "type": "text", "text": "What are these?" },
+        { "type": "image_url", "image_url": { "url": "https://example.com/otter.jpg" } },
+        { "type": "image_url", "image_url": { "url": "data:image/png;base64,iVBORw0KGgo=" } },
+    ])
+}
+
+#[tokio::test]
+async fn test_openai_chat_completions_sends_image_url_blocks() {
+    let (base_url, recorded) = serve(1, completion()).await;
+    let client = OpenAIClient::with_model("key", "gpt-4o").with_base_url(base_url);
+
+    let response = client.chat(request()).await.unwrap();
+    assert_eq!(response.content, "An otter");
+
+    let body = &recorded.lock().unwrap()[0].body;
+    assert_eq!(body["messages"][0]["content"], "Describe images");
+    assert_eq!(body["messages"][1]["content"], chat_completions_parts());
+}
+
+#[tokio::test]
+async fn test_openai_responses_api_sends_input_images() {
+    let (base_url, recorded) = serve(
+        1,
+        json!({
+            "id": "resp_1",
+            "model": "gpt-4o",
+            "status": "completed",
+            "output": [{
+                "type": "message",
+                "role": "assistant",
+                "content": [{ "type": "output_text", "text": "An otter", "annotations": [] }]
+            }]
+        }),
+    )
+    .await;
+    let client = OpenAIClient::responses_api("key", "gpt-4o").with_base_url(base_url);
+
+    client.chat(request()).await.unwrap();
+
+    let body = &recorded.lock().unwrap()[0].body;
+    assert_eq!(body["instructions"], "Describe images");
+    assert_eq!(
+        body["input"][0]["content"],
+        json!([
+            { "type": "input_text", "text": "What are these?" },
+            { "type": "input_image", "image_url": "https://example.com/otter.jpg" },
+            { "type": "input_image", "image_url": "data:image/png;base64,iVBORw0KGgo=" },
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_llama_sends_image_url_blocks() {
+    let (base_url, recorded) = serve(1, completion()).await;
+    let client = LlamaClient::new(base_url, "llava");
+
+    client.chat(request()).await.unwrap();
+
+    let body = &recorded.lock().unwrap()[0].body;
+    assert_eq!(body["messages"][1]["content"], chat_completions_parts());
+}
+
+#[tokio::test]
+async fn test_text_only_messages_keep_their_plain_form() {
+    let (base_url, recorded) = serve(1, completion()).await;
+    let client = LlamaClient::new(base_url, "llama");
+
+    client
+        .chat(ChatRequest::new(vec![ChatMessage::user("Hi")]))
+        .await
+        .unwrap();
+
+    let body = &recorded.lock().unwrap()[0].body;
+    assert_eq!(
+        body["messages"][0],
+        json!({ "role": "user", "content": "Hi" })
+    );
+}
+
+#[tokio::test]
+async fn test_unsupported_images_are_rejected_not_dropped() {
+    // Nothing listens here; a request that got sent would be a network error
+    let nowhere = "http://127.0.0.1:9/v1";
+
+    let gemini = GeminiClient::new("key").with_base_url(nowhere);
+    let err = gemini.chat(request()).await.unwrap_err();
+    assert!(
+        matches!(&err, LlmError::InvalidRequest(message) if message.contains("does not fetch image URLs")),
+        "{:?}",
+        err
+    );
+
+    let system_with_image = ChatRequest::new(vec![ChatMessage::system(vec![
+        ContentPart::text("Be brief"),
+        ContentPart::image_base64("image/png", "iVBORw0KGgo="),
+    ])]);
+    let claude = ClaudeClient::new("key").with_base_url(nowhere);
+    let err = claude.chat(system_with_image.clone()).await.unwrap_err();
+    assert!(
+        matches!(&err, LlmError::InvalidRequest(message) if message == "anthropic does not accept images in system messages"),
+        "{:?}",
+        err
+    );
+    let openai = OpenAIClient::new("key").with_base_url(nowhere);
+    assert!(matches!(
+        openai.chat(system_with_image).await,
+        Err(LlmError::InvalidRequest(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_agent_input_with_images_reaches_the_model() {
+    let mock = Arc::new(MockLlmClient::new().with_response("Two otters"));
+    let agent = Agent::new(
+        AgentConfig::builder("describer")
+            .system_prompt("Describe images")
+            .build(),
+    )
+    .with_client(mock.clone());
+
+    let input = AgentInput::from_text_and_images("What are these?", images());
+    let output = agent.execute(&input).await.unwrap();
+    assert_eq!(output.data["response"], "Two otters");
+
+    let sent = &mock.last_call().unwrap().messages[1];
+    assert_eq!(sent.content.text(), "What are these?");
+    assert_eq!(
+        sent.content.images().collect::<Vec<_>>(),
+        images().iter().collect::<Vec<_>>()
+    );
+
+    // Images stay in the history handed back for the next turn
+    let history = output.chat_history.unwrap();
+    assert_eq!(history[1].content.image_count(), 2);
+}
@@ -0,0 +1,196 @@
+/// Tests for the native Ollama provider against a scripted local HTTP server
+use agent_runtime::llm::{
+    ChatMessage, ChatRequest, ContentPart, FallbackChatClient, GenericChatClient, LlmError,
+    OllamaClient,
+};
+use agent_runtime::RuntimeConfig;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+mod common;
+use common::{serve, Reply};
+
+/// Newline-delimited JSON, written in `pieces` so a stream arrives in
+/// several chunks
+fn ndjson(pieces: Vec<String>) -> Reply {
+    Reply::pieces("application/x-ndjson", pieces)
+}
+
+fn final_chunk(content: &str) -> Value {
+    json!({
+        "model": "llama3.2",
+        "message": { "role": "assistant", "content": content },
+        "done": true,
+        "done_reason": "stop",
+        "prompt_eval_count": 26,
+        "eval_count": 7
+    })
+}
+
+#[tokio::test]
+async fn test_chat_sends_native_request() {
+    let (base_url, recorded) = serve(vec![ndjson(vec![final_chunk("An otter").to_string()])]).await;
+    let client = OllamaClient::new(base_url, "llava")
+        .with_keep_alive("10m")
+        .with_option("num_ctx", 8192);
+
+    let request = ChatRequest::new(vec![
+        ChatMessage::system("Describe images"),
+        ChatMessage::user_with_images(
+            "What is this?",
+            [ContentPart::image_base64("image/png", "iVBORw0KGgo=")],
+        ),
+    ])
+    .with_temperature(0.2)
+    .with_max_tokens(100)
+    .with_top_p(0.9);
+    let response = client.chat(request).await.unwrap();
+
+    assert_eq!(response.content, "An otter");
+    assert_eq!(response.model, "llama3.2");
+    assert_eq!(response.finish_reason.as_deref(), Some("stop"));
+    let usage = response.usage.unwrap();
+    assert_eq!(
+        (
+            usage.prompt_tokens,
+            usage.completion_tokens,
+            usage.total_tokens
+        ),
+        (26, 7, 33)
+    );
+
+    let request = &recorded.lock().unwrap()[0];
+    assert_eq!(request.path(), "/api/chat");
+    let body = &request.body;
+    assert_eq!(body["model"], "llava");
+    assert_eq!(body["stream"], false);
+    assert_eq!(body["keep_alive"], "10m");
+    assert_eq!(body["options"]["num_ctx"], 8192);
+    assert_eq!(body["options"]["num_predict"], 100);
+    assert!((body["options"]["temperature"].as_f64().unwrap() - 0.2).abs() < 1e-6);
+    assert_eq!(
+        body["messages"],
+        json!([
+            { "role": "system", "content": "Describe images" },
+            { "role": "user", "content": "What is this?", "images": ["iVBORw0KGgo="] },
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_stream_forwards_ndjson_chunks() {
+    let chunk = |text: &str| {
+        json!({ "model": "llama3.2", "message": { "role": "assistant", "content": text }, "done": false })
+            .to_string()
+    };
+    let mut last = final_chunk("");
+    last["done_reason"] = json!("length");
+    let body = format!(
+        "{}\n{}\n{}\n",
+        chunk("Otters "),
+        chunk("hold "),
+        chunk("hands")
+    ) + &last.to_string();
+    // Split mid-line, so chunks don't line up with the JSON lines
+    let (first, rest) = body.split_at(20);
+    let (base_url, recorded) = serve(vec![ndjson(vec![first.to_string(), rest.to_string()])]).await;
+    let client = OllamaClient::new(base_url, "llama3.2");
+
+    let (tx, mut rx) = mpsc::channel(16);
+    let response = client
+        .chat_stream(ChatRequest::new(vec![ChatMessage::user("Otters?")]), tx)
+        .await
+        .unwrap();
+
+    let mut streamed = Vec::new();
+    while let Some(text) = rx.recv().await {
+        streamed.push(text);
+    }
+    assert_eq!(streamed, vec!["Otters ", "hold ", "hands"]);
+    assert_eq!(response.content, "Otters hold hands");
+    assert_eq!(response.finish_reason.as_deref(), Some("length"));
+    assert_eq!(response.usage.unwrap().completion_tokens, 7);
+    assert_eq!(recorded.lock().unwrap()[0].body["stream"], true);
+}
+
+#[tokio::test]
+async fn test_tool_calls_are_parsed() {
+    let tools = vec![json!({
+        "type": "function",
+        "function": {
+            "name": "get_weather",
+            "parameters": { "type": "object", "properties": { "city": { "type": "string" } } }
+        }
+    })];
+    let mut body = final_chunk("");
+    body["message"]["tool_calls"] = json!([
+        { "function": { "name": "get_weather", "arguments": { "city": "Oslo" } } },
+        { "function": { "name": "get_weather", "arguments": { "city": "Bergen" } } }
+    ]);
+    let (base_url, recorded) = serve(vec![ndjson(vec![body.to_string()])]).await;
+    let client = OllamaClient::new(base_url, "qwen2.5");
+
+    let response = client
+        .chat(ChatRequest::new(vec![ChatMessage::user("Weather?")]).with_tools(tools.clone()))
+        .await
+        .unwrap();
+
+    assert_eq!(response.finish_reason.as_deref(), Some("tool_calls"));
+    assert!(response.warnings.is_empty(), "{:?}", response.warnings);
+    let calls = response.tool_calls.unwrap();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0].function.name, "get_weather");
+    assert_eq!(
+        serde_json::from_str::<Value>(&calls[1].function.arguments).unwrap(),
+        json!({ "city": "Bergen" })
+    );
+    assert_ne!(calls[0].id, calls[1].id);
+    assert_eq!(recorded.lock().unwrap()[0].body["tools"], json!(tools));
+}
+
+#[tokio::test]
+async fn test_errors_carry_ollamas_message() {
+    let (base_url, _) = serve(vec![ndjson(vec![json!({
+        "error": "model \"nope\" not found, try pulling it first"
+    })
+    .to_string()])
+    .with_status(404)])
+    .await;
+    let client = OllamaClient::new(base_url, "nope");
+
+    let err = client
+        .chat(ChatRequest::new(vec![ChatMessage::user("Hi")]))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, LlmError::ApiError(message) if message.ends_with("model \"nope\" not found, try pulling it first")),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn test_ollama_from_config() {
+    let config: RuntimeConfig = toml::from_str(
+        r#"
+        [llm]
+        fallback = ["ollama"]
+
+        [llm.ollama]
+        model = "qwen2.5:7b"
+        keep_alive = -1
+
+        [llm.ollama.options]
+        num_ctx = 16384
+        "#,
+    )
+    .unwrap();
+    config.validate().unwrap();
+    let ollama = config.llm.ollama.as_ref().unwrap();
+    assert_eq!(ollama.keep_alive, Some(json!(-1)));
+    assert_eq!(ollama.options["num_ctx"], 16384);
+    assert_eq!(OllamaClient::from_config(ollama).model(), "qwen2.5:7b");
+
+    let chain = FallbackChatClient::from_config(&config.llm).unwrap();
+    assert_eq!(chain.providers(), vec!["ollama"]);
+}
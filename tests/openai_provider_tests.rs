@@ -0,0 +1,388 @@
+/// Tests for the OpenAI provider, both chat completions and the Responses
+/// API, against a scripted local HTTP server
+use agent_runtime::llm::types::{FunctionCall, ToolCall};
+use agent_runtime::llm::{ChatMessage, ChatRequest, GenericChatClient, LlmError, OpenAIClient};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+mod common;
+use common::{serve, Reply};
+
+/// Stream `chunks` as chat-completion SSE events, 40 bytes per write
+fn sse(chunks: &[Value]) -> Reply {
+    let mut stream: String = chunks.iter().map(|c| format!("data: {}\n\n", c)).collect();
+    stream.push_str("data: [DONE]\n\n");
+    let pieces = stream
+        .as_bytes()
+        .chunks(40)
+        .map(|piece| String::from_utf8(piece.to_vec()).unwrap())
+        .collect();
+    Reply::pieces("text/event-stream", pieces)
+}
+
+fn calculator() -> Value {
+    json!({
+        "type": "function",
+        "function": {
+            "name": "calculator",
+            "description": "Adds two numbers",
+            "parameters": {
+                "type": "object",
+                "properties": { "a": {"type": "number"}, "b": {"type": "number"} }
+            }
+        }
+    })
+}
+
+fn request() -> ChatRequest {
+    ChatRequest::new(vec![
+        ChatMessage::system("You are a calculator"),
+        ChatMessage::user("What is 5 + 3?"),
+    ])
+    .with_temperature(0.2)
+    .with_max_tokens(500)
+    .with_tools(vec![calculator()])
+}
+
+#[tokio::test]
+async fn test_chat_completions_with_reasoning_model() {
+    let (base_url, recorded) = serve(vec![json!({
+        "model": "o3-mini",
+        "choices": [{
+            "message": {
+                "content": null,
+                "tool_calls": [{
+                    "id": "call_1",
+                    "type": "function",
+                    "function": { "name": "calculator", "arguments": "{\"a\":5,\"b\":3}" }
+                }]
+            },
+            "finish_reason": "tool_calls"
+        }],
+        "usage": {
+            "prompt_tokens": 40,
+            "completion_tokens": 70,
+            "total_tokens": 110,
+            "completion_tokens_details": { "reasoning_tokens": 64 }
+        }
+    })])
+    .await;
+
+    let client =
+        OpenAIClient::with_model("test-key", "o3-mini").with_base_url(format!("{}/v1", base_url));
+    let response = client.chat(request()).await.unwrap();
+
+    let calls = response.tool_calls.unwrap();
+    assert_eq!(calls[0].function.name, "calculator");
+    assert_eq!(calls[0].function.arguments, "{\"a\":5,\"b\":3}");
+    assert_eq!(response.finish_reason.as_deref(), Some("tool_calls"));
+    assert_eq!(response.usage.unwrap().reasoning_tokens, Some(64));
+
+    let recorded = recorded.lock().unwrap();
+    assert!(recorded[0].head.starts_with("post /v1/chat/completions "));
+    assert!(recorded[0].head.contains("authorization: bearer test-key"));
+    let sent = &recorded[0].body;
+    // Sampling parameters would be rejected with a 400
+    assert!(sent.get("temperature").is_none());
+    assert!(sent.get("max_tokens").is_none());
+    assert_eq!(sent["max_completion_tokens"], 500);
+    assert_eq!(sent["messages"][0]["role"], "system");
+    assert_eq!(sent["tools"][0]["function"]["name"], "calculator");
+}
+
+#[tokio::test]
+async fn test_responses_api_tool_round_trip() {
+    let (base_url, recorded) = serve(vec![
+        json!({
+            "id": "resp_1",
+            "model": "o4-mini",
+            "status": "completed",
+            "output": [
+                { "type": "reasoning", "id": "rs_1", "summary": [] },
+                {
+                    "type": "function_call",
+                    "id": "fc_1",
+                    "call_id": "call_1",
+                    "name": "calculator",
+                    "arguments": "{\"a\":5,\"b\":3}",
+                    "status": "completed"
+                }
+            ],
+            "usage": {
+                "input_tokens": 40,
+                "output_tokens": 90,
+                "total_tokens": 130,
+                "output_tokens_details": { "reasoning_tokens": 80 }
+            }
+        }),
+        json!({
+            "id": "resp_2",
+            "model": "o4-mini",
+            "status": "completed",
+            "output": [{
+                "type": "message",
+                "role": "assistant",
+                "content": [{ "type": "output_text", "text": "5 + 3 is 8", "annotations": [] }]
+            }],
+            "usage": { "input_tokens": 60, "output_tokens": 8, "total_tokens": 68 }
+        }),
+    ])
+    .await;
+
+    let client = OpenAIClient::responses_api("test-key", "o4-mini")
+        .with_base_url(format!("{}/v1", base_url));
+    let first = client
+        .chat(request().with_reasoning_effort("low"))
+        .await
+        .unwrap();
+
+    assert_eq!(first.content, "");
+    assert_eq!(first.finish_reason.as_deref(), Some("tool_calls"));
+    let calls = first.tool_calls.unwrap();
+    assert_eq!(calls[0].id, "call_1");
+    assert_eq!(calls[0].function.arguments, "{\"a\":5,\"b\":3}");
+    let usage = first.usage.unwrap();
+    assert_eq!((usage.prompt_tokens, usage.completion_tokens), (40, 90));
+    assert_eq!(usage.reasoning_tokens, Some(80));
+
+    let mut follow_up = request().messages;
+    follow_up.push(ChatMessage::assistant_with_tool_calls(
+        "",
+        vec![ToolCall {
+            id: "call_1".to_string(),
+            r#type: "function".to_string(),
+            function: FunctionCall {
+                name: "calculator".to_string(),
+                arguments: "{\"a\":5,\"b\":3}".to_string(),
+            },
+        }],
+    ));
+    follow_up.push(ChatMessage::tool_result("call_1", "{\"result\":8}"));
+    let second = client
+        .chat(ChatRequest::new(follow_up).with_tools(vec![calculator()]))
+        .await
+        .unwrap();
+    assert_eq!(second.content, "5 + 3 is 8");
+    assert_eq!(second.finish_reason.as_deref(), Some("stop"));
+    assert!(second.tool_calls.is_none());
+    assert_eq!(second.usage.unwrap().reasoning_tokens, None);
+
+    let recorded = recorded.lock().unwrap();
+    assert!(recorded[0].head.starts_with("post /v1/responses "));
+    let sent = &recorded[0].body;
+    assert_eq!(sent["instructions"], "You are a calculator");
+    assert_eq!(
+        sent["input"],
+        json!([{ "role": "user", "content": "What is 5 + 3?" }])
+    );
+    assert_eq!(sent["reasoning"], json!({ "effort": "low" }));
+    assert_eq!(sent["max_output_tokens"], 500);
+    assert!(sent.get("temperature").is_none());
+    assert_eq!(
+        sent["tools"][0],
+        json!({
+            "type": "function",
+            "name": "calculator",
+            "description": "Adds two numbers",
+            "parameters": {
+                "type": "object",
+                "properties": { "a": {"type": "number"}, "b": {"type": "number"} }
+            }
+        })
+    );
+
+    let input = recorded[1].body["input"].as_array().unwrap();
+    assert_eq!(input.len(), 3);
+    assert_eq!(
+        input[1],
+        json!({ "type": "function_call", "call_id": "call_1", "name": "calculator", "arguments": "{\"a\":5,\"b\":3}" })
+    );
+    assert_eq!(
+        input[2],
+        json!({ "type": "function_call_output", "call_id": "call_1", "output": "{\"result\":8}" })
+    );
+}
+
+#[tokio::test]
+async fn test_responses_api_incomplete_and_failed() {
+    let (base_url, _) = serve(vec![
+        json!({
+            "model": "gpt-4o",
+            "status": "incomplete",
+            "incomplete_details": { "reason": "max_output_tokens" },
+            "output": [{
+                "type": "message",
+                "content": [{ "type": "output_text", "text": "The answer is" }]
+            }]
+        }),
+        json!({
+            "model": "gpt-4o",
+            "status": "failed",
+            "error": { "code": "server_error", "message": "try again" },
+            "output": []
+        }),
+    ])
+    .await;
+
+    let client =
+        OpenAIClient::responses_api("test-key", "gpt-4o").with_base_url(format!("{}/v1", base_url));
+    let truncated = client.chat(request()).await.unwrap();
+    assert_eq!(truncated.content, "The answer is");
+    assert_eq!(truncated.finish_reason.as_deref(), Some("length"));
+    assert!(truncated.usage.is_none());
+
+    let failed = client.chat(request()).await.unwrap_err();
+    let LlmError::ApiError(details) = failed else {
+        panic!("expected ApiError");
+    };
+    assert!(details.contains("try again"), "{}", details);
+}
+
+/// Two tool calls whose arguments arrive in pieces, as OpenAI streams them
+fn streamed_tool_calls() -> Reply {
+    let chunk = |delta: Value, finish_reason: Value| {
+        json!({
+            "model": "gpt-4o",
+            "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }],
+        })
+    };
+    let call = |index: u64, id: Option<&str>, name: Option<&str>, arguments: &str| {
+        let mut call = json!({ "index": index, "function": { "arguments": arguments } });
+        if let (Some(id), Some(name)) = (id, name) {
+            call["id"] = json!(id);
+            call["type"] = json!("function");
+            call["function"]["name"] = json!(name);
+        }
+        json!({ "tool_calls": [call] })
+    };
+    sse(&[
+        chunk(json!({ "role": "assistant", "content": "" }), Value::Null),
+        chunk(json!({ "content": "Adding " }), Value::Null),
+        chunk(json!({ "content": "both." }), Value::Null),
+        chunk(call(0, Some("call_a"), Some("calculator"), ""), Value::Null),
+        chunk(call(0, None, None, "{\"a\":"), Value::Null),
+        chunk(call(0, None, None, "5,\"b\":3}"), Value::Null),
+        chunk(
+            call(1, Some("call_b"), Some("calculator"), "{\"a\":1"),
+            Value::Null,
+        ),
+        chunk(call(1, None, None, ",\"b\":2}"), Value::Null),
+        chunk(json!({}), json!("tool_calls")),
+        json!({
+            "model": "gpt-4o",
+            "choices": [],
+            "usage": { "prompt_tokens": 30, "completion_tokens": 20, "total_tokens": 50 },
+        }),
+    ])
+}
+
+#[tokio::test]
+async fn test_streamed_tool_calls_match_non_streamed() {
+    let (base_url, recorded) = serve(vec![
+        streamed_tool_calls(),
+        Reply::json(json!({
+            "model": "gpt-4o",
+            "choices": [{
+                "message": {
+                    "content": "Adding both.",
+                    "tool_calls": [
+                        {
+                            "id": "call_a",
+                            "type": "function",
+                            "function": { "name": "calculator", "arguments": "{\"a\":5,\"b\":3}" }
+                        },
+                        {
+                            "id": "call_b",
+                            "type": "function",
+                            "function": { "name": "calculator", "arguments": "{\"a\":1,\"b\":2}" }
+                        }
+                    ]
+                },
+                "finish_reason": "tool_calls"
+            }],
+            "usage": { "prompt_tokens": 30, "completion_tokens": 20, "total_tokens": 50 }
+        })),
+    ])
+    .await;
+    let client =
+        OpenAIClient::with_model("test-key", "gpt-4o").with_base_url(format!("{}/v1", base_url));
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+    let streamed = client.chat_stream(request(), tx).await.unwrap();
+    let mut text = String::new();
+    while let Some(chunk) = rx.recv().await {
+        text.push_str(&chunk);
+    }
+    assert_eq!(text, "Adding both.");
+
+    let calls = streamed.tool_calls.as_ref().unwrap();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0].id, "call_a");
+    assert_eq!(calls[1].function.arguments, "{\"a\":1,\"b\":2}");
+    assert_eq!(streamed.usage.as_ref().unwrap().total_tokens, 50);
+
+    let whole = client.chat(request()).await.unwrap();
+    assert_eq!(
+        serde_json::to_value(&streamed).unwrap(),
+        serde_json::to_value(&whole).unwrap()
+    );
+
+    let recorded = recorded.lock().unwrap();
+    assert_eq!(recorded[0].body["stream"], true);
+    assert_eq!(recorded[0].body["stream_options"]["include_usage"], true);
+    assert!(recorded[1].body.get("stream").is_none());
+}
+
+#[tokio::test]
+async fn test_agent_tool_loop_over_streaming() {
+    use agent_runtime::{Agent, AgentConfig, AgentInput, NativeTool, ToolRegistry, ToolResult};
+
+    let (base_url, recorded) = serve(vec![
+        streamed_tool_calls(),
+        sse(&[
+            json!({ "model": "gpt-4o", "choices": [{ "index": 0, "delta": { "content": "8 and 3." } }] }),
+            json!({ "model": "gpt-4o", "choices": [{ "index": 0, "delta": {}, "finish_reason": "stop" }] }),
+        ]),
+    ])
+    .await;
+
+    let mut registry = ToolRegistry::new();
+    registry.register(NativeTool::new(
+        "calculator",
+        "Adds two numbers",
+        json!({
+            "type": "object",
+            "properties": { "a": {"type": "number"}, "b": {"type": "number"} }
+        }),
+        |params| async move {
+            let sum = params["a"].as_f64().unwrap() + params["b"].as_f64().unwrap();
+            Ok(ToolResult::success(json!(sum), 0.0))
+        },
+    ));
+    let agent = Agent::new(
+        AgentConfig::builder("adder")
+            .tools(Arc::new(registry))
+            .build(),
+    )
+    .with_client(Arc::new(
+        OpenAIClient::with_model("test-key", "gpt-4o").with_base_url(format!("{}/v1", base_url)),
+    ));
+
+    let output = agent
+        .execute(&AgentInput::from_value(json!("Add 5+3 and 1+2")))
+        .await
+        .unwrap();
+    assert_eq!(output.data["response"], "8 and 3.");
+
+    // One streamed request per LLM turn, and both results went back
+    let recorded = recorded.lock().unwrap();
+    assert_eq!(recorded.len(), 2);
+    let tool_ids: Vec<&Value> = recorded[1].body["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|m| m["role"] == "tool")
+        .map(|m| &m["tool_call_id"])
+        .collect();
+    assert_eq!(tool_ids, vec!["call_a", "call_b"]);
+}
@@ -0,0 +1,279 @@
+/// Tests for importing OpenAI function-calling tool definitions
+use agent_runtime::llm::{MockLlmClient, Role};
+use agent_runtime::types::{ToolError, ToolResult};
+use agent_runtime::*;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+mod common;
+use common::{serve_forever, Recorded, Reply};
+
+/// A catalog as exported from an OpenAI-style tool registry
+fn catalog() -> Value {
+    json!([
+        {
+            "type": "function",
+            "function": {
+                "name": "get_weather",
+                "description": "Current weather for a city",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "city": { "type": "string" },
+                        "units": { "type": "string", "enum": ["metric", "imperial"] }
+                    },
+                    "required": ["city"]
+                },
+                "strict": true
+            }
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "create_ticket",
+                "description": "Open a support ticket",
+                "parameters": {
+                    "type": "object",
+                    "properties": { "title": { "type": "string" } },
+                    "required": ["title"]
+                }
+            }
+        },
+        { "name": "ping" }
+    ])
+}
+
+fn params(value: Value) -> HashMap<String, Value> {
+    serde_json::from_value(value).unwrap()
+}
+
+/// Answer every request with `status` and `body`, recording the requests
+async fn server(status: u16, body: &'static str) -> (String, Arc<Mutex<Vec<Recorded>>>) {
+    serve_forever(Reply::text("text/plain", body).with_status(status)).await
+}
+
+#[test]
+fn test_parses_wrapped_and_bare_definitions() {
+    let specs = ToolSpec::parse_all(&catalog()).unwrap();
+
+    let names: Vec<&str> = specs.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, vec!["get_weather", "create_ticket", "ping"]);
+    assert_eq!(specs[0].parameters["required"], json!(["city"]));
+    assert_eq!(specs[2].description, "");
+    assert_eq!(
+        specs[2].parameters,
+        json!({ "type": "object", "properties": {} })
+    );
+
+    // A single definition is accepted too
+    let single = ToolSpec::parse_all(&catalog()[1]).unwrap();
+    assert_eq!(single[0].name, "create_ticket");
+}
+
+#[test]
+fn test_rejects_invalid_definitions() {
+    let error = ToolSpec::parse_all(&json!([{ "name": "ok" }, { "name": "has space" }]));
+    assert!(matches!(
+        error,
+        Err(ToolSpecError::Invalid { index: 1, .. })
+    ));
+
+    let error = ToolSpec::parse_all(&json!({ "name": "x", "parameters": "nope" }));
+    assert!(matches!(
+        error,
+        Err(ToolSpecError::Invalid { index: 0, .. })
+    ));
+
+    let error = ToolSpec::parse_all(&json!({ "type": "retrieval" }));
+    assert!(matches!(
+        error,
+        Err(ToolSpecError::Invalid { index: 0, .. })
+    ));
+
+    assert_eq!(
+        ToolSpec::parse_all(&json!([{ "name": "x" }, { "name": "x" }])),
+        Err(ToolSpecError::Duplicate("x".to_string()))
+    );
+}
+
+#[test]
+fn test_unbound_names_fail_the_import() {
+    let mut registry = ToolRegistry::new();
+    let binder = ToolBinder::new().native("ping", |_| async {
+        Ok(ToolResult::success(json!("pong"), 0.0))
+    });
+
+    let error = registry
+        .register_from_openai_spec(&catalog(), binder)
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "no handler bound for: get_weather, create_ticket"
+    );
+    assert!(registry.is_empty());
+}
+
+#[tokio::test]
+async fn test_native_binding_and_stubs() {
+    let mut registry = ToolRegistry::new();
+    let binder = ToolBinder::new()
+        .native("get_weather", |params| async move {
+            Ok(ToolResult::success(
+                json!({ "city": params["city"], "temp": 21 }),
+                0.0,
+            ))
+        })
+        .unbound(UnboundPolicy::Stub);
+
+    let import = registry
+        .register_from_openai_spec(&catalog(), binder)
+        .unwrap();
+    assert_eq!(import.bound, vec!["get_weather"]);
+    assert_eq!(import.stubbed, vec!["create_ticket", "ping"]);
+    assert_eq!(registry.len(), 3);
+
+    let weather = registry
+        .call_tool("get_weather", params(json!({ "city": "Oslo" })))
+        .await
+        .unwrap();
+    assert_eq!(weather.output, json!({ "city": "Oslo", "temp": 21 }));
+
+    let stub = registry
+        .call_tool("create_ticket", params(json!({ "title": "Broken" })))
+        .await;
+    assert!(matches!(
+        stub,
+        Err(ToolError::ExecutionFailed(m)) if m == "'create_ticket' is not implemented yet"
+    ));
+}
+
+#[tokio::test]
+async fn test_agent_sees_stub_error_and_continues() {
+    let mut registry = ToolRegistry::new();
+    registry
+        .register_from_openai_spec(&catalog(), ToolBinder::new().unbound(UnboundPolicy::Stub))
+        .unwrap();
+    let llm = Arc::new(MockLlmClient::with_tool_then_text(
+        "create_ticket",
+        json!({ "title": "Printer on fire" }),
+        "Ticket creation isn't available yet.",
+    ));
+    let agent = Agent::new(
+        AgentConfig::builder("support")
+            .tools(Arc::new(registry))
+            .build(),
+    )
+    .with_client(llm.clone());
+
+    let output = agent
+        .execute(&AgentInput::from_value(json!("Open a ticket")))
+        .await
+        .unwrap();
+    assert_eq!(
+        output.data["response"],
+        "Ticket creation isn't available yet."
+    );
+
+    let last = llm.last_call().unwrap();
+    let tool_reply = last.messages.iter().find(|m| m.role == Role::Tool).unwrap();
+    assert!(tool_reply.content.text().contains("not implemented yet"));
+    // The provider saw the schemas from the spec
+    let offered = last.tools.unwrap();
+    assert_eq!(offered.len(), 3);
+    assert_eq!(offered[1], catalog()[0]);
+}
+
+#[tokio::test]
+async fn test_http_binding() {
+    let (url, requests) = server(200, r#"{"temp": 18}"#).await;
+    let (post_url, posts) = server(201, "created").await;
+
+    let mut registry = ToolRegistry::new();
+    let binder = ToolBinder::new()
+        .http(
+            "get_weather",
+            HttpEndpoint::get(format!("{}/weather/{{city}}", url)),
+        )
+        .http(
+            "create_ticket",
+            HttpEndpoint::post(format!("{}/tickets", post_url)).header("x-api-key", "k"),
+        )
+        .unbound(UnboundPolicy::Stub);
+    registry
+        .register_from_openai_spec(&catalog(), binder)
+        .unwrap();
+
+    let weather = registry
+        .call_tool(
+            "get_weather",
+            params(json!({ "city": "New York", "units": "metric" })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(weather.output, json!({ "temp": 18 }));
+    assert_eq!(
+        requests.lock().unwrap()[0].request_line,
+        "GET /weather/New%20York?units=metric HTTP/1.1"
+    );
+
+    let ticket = registry
+        .call_tool("create_ticket", params(json!({ "title": "Broken" })))
+        .await
+        .unwrap();
+    assert_eq!(ticket.output, json!("created"));
+    let post = posts.lock().unwrap()[0].clone();
+    assert_eq!(post.request_line, "POST /tickets HTTP/1.1");
+    assert_eq!(post.raw_body, br#"{"title":"Broken"}"#);
+
+    let missing = registry
+        .call_tool("get_weather", params(json!({ "units": "metric" })))
+        .await;
+    assert!(matches!(missing, Err(ToolError::InvalidParameters(_))));
+}
+
+#[tokio::test]
+async fn test_http_server_errors_are_transient() {
+    let (url, _) = server(503, "busy").await;
+    let (bad_url, _) = server(404, "no such city").await;
+
+    let mut registry = ToolRegistry::new();
+    let spec = json!([{ "name": "flaky" }, { "name": "missing" }]);
+    let binder = ToolBinder::new()
+        .http("flaky", HttpEndpoint::get(url))
+        .http("missing", HttpEndpoint::get(bad_url));
+    registry.register_from_openai_spec(&spec, binder).unwrap();
+
+    let flaky = registry.call_tool("flaky", HashMap::new()).await;
+    assert!(matches!(flaky, Err(ToolError::Transient(m)) if m.contains("503")));
+    let missing = registry.call_tool("missing", HashMap::new()).await;
+    assert!(matches!(missing, Err(ToolError::ExecutionFailed(m)) if m.contains("no such city")));
+}
+
+#[test]
+fn test_export_round_trips() {
+    let mut registry = ToolRegistry::new();
+    registry
+        .register_from_openai_spec(&catalog(), ToolBinder::new().unbound(UnboundPolicy::Stub))
+        .unwrap();
+
+    let exported = registry.to_openai_spec();
+    let names: Vec<&str> = exported
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["function"]["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["create_ticket", "get_weather", "ping"]);
+    assert_eq!(exported[1], catalog()[0]);
+
+    // Re-importing the export yields the same catalog, byte for byte
+    let mut again = ToolRegistry::new();
+    again
+        .register_from_openai_spec(&exported, ToolBinder::new().unbound(UnboundPolicy::Stub))
+        .unwrap();
+    assert_eq!(
+        serde_json::to_string(&again.to_openai_spec()).unwrap(),
+        serde_json::to_string(&exported).unwrap()
+    );
+}
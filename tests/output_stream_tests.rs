@@ -0,0 +1,131 @@
+/// Tests for streaming a workflow's answer to the caller
+use agent_runtime::llm::{MockLlmClient, MockResponse};
+use agent_runtime::runtime::{FlushPolicy, Runtime};
+use agent_runtime::workflow::WorkflowRun;
+use agent_runtime::*;
+use futures::StreamExt;
+use serde_json::json;
+use std::sync::Arc;
+
+/// Says what it is about to do, searches, then answers
+fn searching_mock() -> MockLlmClient {
+    MockLlmClient::from_mock_responses(vec![
+        MockResponse {
+            content: "Let me search the index".to_string(),
+            ..MockResponse::with_tool_call("search", json!({ "q": "rust crates" }))
+        },
+        MockResponse::text("Found three crates"),
+    ])
+}
+
+fn researcher(name: &str, mock: MockLlmClient) -> Box<dyn workflow::Step> {
+    let mut registry = ToolRegistry::new();
+    registry.register(NativeTool::new(
+        "search",
+        "Search the index",
+        json!({"type": "object", "properties": {}}),
+        |_params| async move { Ok(ToolResult::success(json!({"hits": 3}), 1.0)) },
+    ));
+    let agent = Agent::new(AgentConfig::builder(name).tools(Arc::new(registry)).build())
+        .with_client(Arc::new(mock));
+    Box::new(AgentStep::from_agent(agent, name.to_string()))
+}
+
+fn writer(mock: MockLlmClient) -> Box<dyn workflow::Step> {
+    let agent = Agent::new(AgentConfig::builder("writer").build()).with_client(Arc::new(mock));
+    Box::new(AgentStep::from_agent(agent, "writer".to_string()))
+}
+
+async fn stream(
+    runtime: &Runtime,
+    workflow: Workflow,
+    policy: FlushPolicy,
+) -> (String, WorkflowRun) {
+    let (chunks, run) = runtime.execute_with_flush_policy(workflow, policy);
+    let (chunks, run) = tokio::join!(chunks.collect::<Vec<String>>(), run);
+    (chunks.concat(), run)
+}
+
+#[tokio::test]
+async fn test_only_the_final_response_is_streamed() {
+    let workflow = Workflow::builder()
+        .name("research".to_string())
+        .step(researcher("researcher", searching_mock()))
+        // Not an agent: the researcher still gives the answer
+        .step(Box::new(TransformStep::new(
+            "wrap".to_string(),
+            |v| json!({ "answer": v["response"] }),
+        )))
+        .initial_input(json!("find rust crates"))
+        .build();
+
+    let runtime = Runtime::new();
+    let (chunks, run) = runtime.execute_with_output_stream(workflow);
+    let (chunks, run) = tokio::join!(chunks.collect::<Vec<String>>(), run);
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(chunks, vec!["Found ", "three ", "crates "]);
+
+    // The preamble was streamed as events, but not to the caller
+    let preamble = runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .find(|e| e.component_id == "researcher:llm:1" && e.event_type == EventType::Progress)
+        .unwrap();
+    assert_eq!(preamble.data["chunk"], "Let ");
+    assert_eq!(preamble.data["iteration"], 1);
+    assert_eq!(preamble.data["tools_offered"], true);
+}
+
+#[tokio::test]
+async fn test_earlier_agent_steps_are_not_streamed() {
+    let workflow = Workflow::builder()
+        .name("draft".to_string())
+        .step(researcher("researcher", searching_mock()))
+        .step(writer(MockLlmClient::with_responses_vec(vec![
+            "Three crates fit",
+        ])))
+        .initial_input(json!("find rust crates"))
+        .build();
+
+    let (chunks, run) = stream(&Runtime::new(), workflow, FlushPolicy::FinalResponse).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(chunks, "Three crates fit ");
+}
+
+#[tokio::test]
+async fn test_all_chunks_policy_passes_everything_on() {
+    let workflow = Workflow::builder()
+        .name("draft".to_string())
+        .step(researcher("researcher", searching_mock()))
+        .step(writer(MockLlmClient::with_responses_vec(vec![
+            "Three crates fit",
+        ])))
+        .initial_input(json!("find rust crates"))
+        .build();
+
+    let (chunks, _) = stream(&Runtime::new(), workflow, FlushPolicy::AllChunks).await;
+    assert_eq!(
+        chunks,
+        "Let me search the index Found three crates Three crates fit "
+    );
+}
+
+#[tokio::test]
+async fn test_stream_ends_with_a_failed_run() {
+    let workflow = Workflow::builder()
+        .name("research".to_string())
+        .step(researcher(
+            "researcher",
+            MockLlmClient::new()
+                .with_tool_call("search", json!({ "q": "rust crates" }))
+                .error_on_call(1),
+        ))
+        .initial_input(json!("find rust crates"))
+        .build();
+
+    let (chunks, run) = stream(&Runtime::new(), workflow, FlushPolicy::FinalResponse).await;
+    assert_eq!(run.state, WorkflowState::Failed);
+    assert_eq!(chunks, "");
+}
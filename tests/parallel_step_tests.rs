@@ -0,0 +1,311 @@
+use agent_runtime::llm::types::Role;
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::runtime::Runtime;
+use agent_runtime::workflow::step::{ExecutionContext, StepOutputMetadata};
+use agent_runtime::workflow::{
+    StepError, StepInput, StepOutput, StepResult, StepType, WorkflowRun,
+};
+use agent_runtime::*;
+use async_trait::async_trait;
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+/// Sleeps, then returns its name or fails
+struct Sleeper {
+    name: String,
+    delay: Duration,
+    fail: bool,
+}
+
+fn sleeper(name: &str, millis: u64) -> Box<dyn Step> {
+    Box::new(Sleeper {
+        name: name.to_string(),
+        delay: Duration::from_millis(millis),
+        fail: false,
+    })
+}
+
+fn failing(name: &str, millis: u64) -> Box<dyn Step> {
+    Box::new(Sleeper {
+        name: name.to_string(),
+        delay: Duration::from_millis(millis),
+        fail: true,
+    })
+}
+
+#[async_trait]
+impl Step for Sleeper {
+    async fn execute_with_context(
+        &self,
+        input: StepInput,
+        _ctx: ExecutionContext<'_>,
+    ) -> StepResult {
+        tokio::time::sleep(self.delay).await;
+        if self.fail {
+            return Err(StepError::ExecutionFailed(format!("{} broke", self.name)));
+        }
+        Ok(StepOutput {
+            data: json!({ "by": &self.name, "input": input.data }),
+            metadata: StepOutputMetadata {
+                step_name: self.name.clone(),
+                step_type: StepType::Custom("sleeper".into()),
+                execution_time_ms: self.delay.as_millis() as u64,
+                critic: None,
+                iterations_run: None,
+                policy: None,
+                item_errors: None,
+                handoffs: None,
+            },
+        })
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn step_type(&self) -> StepType {
+        StepType::Custom("sleeper".into())
+    }
+}
+
+fn workflow(step: ParallelStep) -> workflow::Workflow {
+    Workflow::builder()
+        .name("fanout".to_string())
+        .step(Box::new(step))
+        .initial_input(json!("report"))
+        .build()
+}
+
+/// (component_id, event type) of every branch event, sorted
+async fn branch_events(runtime: &Runtime) -> Vec<(String, EventType)> {
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let mut events: Vec<(String, EventType)> = runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .filter(|e| e.scope == EventScope::WorkflowStep && e.component_id.contains('.'))
+        .map(|e| (e.component_id, e.event_type))
+        .collect();
+    events.sort_by(|a, b| {
+        a.0.cmp(&b.0)
+            .then(format!("{:?}", a.1).cmp(&format!("{:?}", b.1)))
+    });
+    events
+}
+
+#[tokio::test]
+async fn test_branches_run_concurrently() {
+    let runtime = Runtime::new();
+    let step = ParallelStep::new(
+        "analyze".to_string(),
+        vec![
+            sleeper("tone", 200),
+            sleeper("facts", 200),
+            sleeper("style", 200),
+        ],
+    );
+
+    let started = Instant::now();
+    let run = runtime.execute(workflow(step)).await;
+    let elapsed = started.elapsed();
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert!(elapsed < Duration::from_millis(500), "took {:?}", elapsed);
+    assert_eq!(
+        run.final_output.unwrap(),
+        json!([
+            { "by": "tone", "input": "report" },
+            { "by": "facts", "input": "report" },
+            { "by": "style", "input": "report" },
+        ])
+    );
+
+    let events = branch_events(&runtime).await;
+    let ids: Vec<&str> = events.iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(
+        ids,
+        vec![
+            "fanout:step:0.0",
+            "fanout:step:0.0",
+            "fanout:step:0.1",
+            "fanout:step:0.1",
+            "fanout:step:0.2",
+            "fanout:step:0.2"
+        ]
+    );
+    assert!(events
+        .iter()
+        .all(|(_, t)| matches!(t, EventType::Started | EventType::Completed)));
+}
+
+#[tokio::test]
+async fn test_output_keyed_by_name() {
+    let runtime = Runtime::new();
+    let step = ParallelStep::new(
+        "analyze".to_string(),
+        vec![sleeper("tone", 5), sleeper("facts", 1)],
+    )
+    .with_output(ParallelOutput::ByName);
+
+    let run = runtime.execute(workflow(step)).await;
+    assert_eq!(
+        run.final_output.unwrap(),
+        json!({
+            "tone": { "by": "tone", "input": "report" },
+            "facts": { "by": "facts", "input": "report" },
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_fail_fast_drops_running_branches() {
+    let runtime = Runtime::new();
+    let step = ParallelStep::new(
+        "analyze".to_string(),
+        vec![
+            sleeper("tone", 1000),
+            failing("facts", 10),
+            sleeper("style", 1000),
+        ],
+    );
+
+    let started = Instant::now();
+    let run = runtime.execute(workflow(step)).await;
+
+    assert_eq!(run.state, WorkflowState::Failed);
+    assert!(started.elapsed() < Duration::from_millis(500));
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let events = runtime.event_stream().all();
+    let step_failure = events
+        .iter()
+        .find(|e| e.component_id == "fanout:step:0" && e.event_type == EventType::Failed)
+        .unwrap();
+    assert_eq!(
+        step_failure.message.as_deref(),
+        Some("Execution failed: facts broke")
+    );
+
+    let mut canceled: Vec<&str> = events
+        .iter()
+        .filter(|e| e.data["canceled"] == json!(true))
+        .map(|e| e.component_id.as_str())
+        .collect();
+    canceled.sort();
+    assert_eq!(canceled, vec!["fanout:step:0.0", "fanout:step:0.2"]);
+}
+
+#[tokio::test]
+async fn test_collect_errors_lets_branches_finish() {
+    let runtime = Runtime::new();
+    let step = ParallelStep::new(
+        "analyze".to_string(),
+        vec![
+            sleeper("tone", 100),
+            failing("facts", 10),
+            failing("style", 20),
+        ],
+    )
+    .with_failure_mode(ParallelFailureMode::CollectErrors);
+
+    let run = runtime.execute(workflow(step)).await;
+    assert_eq!(run.state, WorkflowState::Failed);
+
+    let events = branch_events(&runtime).await;
+    assert!(events.contains(&("fanout:step:0.0".to_string(), EventType::Completed)));
+    assert!(events.contains(&("fanout:step:0.1".to_string(), EventType::Failed)));
+    assert!(events.contains(&("fanout:step:0.2".to_string(), EventType::Failed)));
+
+    let step_failure = runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .find(|e| e.component_id == "fanout:step:0" && e.event_type == EventType::Failed)
+        .unwrap();
+    assert_eq!(
+        step_failure.message.as_deref(),
+        Some(
+            "Execution failed: 2 of 3 parallel branches failed: \
+             facts: Execution failed: facts broke; style: Execution failed: style broke"
+        )
+    );
+
+    // The run record keeps each branch failure, labeled by branch
+    let failure = run.failure.as_ref().unwrap();
+    assert_eq!(
+        (failure.step_index, failure.step_name.as_str()),
+        (0, "analyze")
+    );
+    let StepError::Aggregate(errors) = &failure.error else {
+        panic!("expected an aggregate, got {:?}", failure.error);
+    };
+    let labels: Vec<&str> = errors.iter().map(|f| f.label.as_str()).collect();
+    assert_eq!(labels, vec!["facts", "style"]);
+    assert_eq!(errors.total, 3);
+
+    // ... as does the failure event, and both survive a round trip
+    let workflow_failure = runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .find(|e| e.component_id == run.workflow_id && e.event_type == EventType::Failed)
+        .unwrap();
+    assert_eq!(
+        workflow_failure.data["errors"]["failures"][1]["label"],
+        "style"
+    );
+    let restored: WorkflowRun =
+        serde_json::from_value(serde_json::to_value(&run).unwrap()).unwrap();
+    assert!(matches!(
+        restored.failure.unwrap().error,
+        StepError::Aggregate(e) if e.len() == 2
+    ));
+}
+
+fn agent_branch(name: &str, reply: &str) -> (Box<dyn Step>, std::sync::Arc<MockLlmClient>) {
+    let mock = std::sync::Arc::new(MockLlmClient::new().with_response(reply));
+    let agent = Agent::new(AgentConfig::builder(name).system_prompt("Review").build())
+        .with_client(mock.clone());
+    (
+        Box::new(AgentStep::from_agent(agent, name.to_string())),
+        mock,
+    )
+}
+
+#[tokio::test]
+async fn test_agent_branches_merge_history_in_branch_order() {
+    let (tone, tone_mock) = agent_branch("tone", "The tone is upbeat");
+    let (facts, facts_mock) = agent_branch("facts", "The facts check out");
+    let workflow = Workflow::builder()
+        .name("review".to_string())
+        .with_chat_history(std::sync::Arc::new(SlidingWindowManager::new(50)))
+        .step(Box::new(ParallelStep::new(
+            "analyze".to_string(),
+            vec![tone, facts],
+        )))
+        .initial_input(json!("report"))
+        .build();
+    let context = workflow.context().unwrap().clone();
+
+    let run = Runtime::new().execute(workflow).await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    let replies: Vec<String> = context
+        .read()
+        .unwrap()
+        .history()
+        .iter()
+        .filter(|m| m.role == Role::Assistant)
+        .map(|m| m.content.text().into_owned())
+        .collect();
+    assert_eq!(replies, vec!["The tone is upbeat", "The facts check out"]);
+
+    // Neither branch saw the other's turns
+    for (mock, other) in [
+        (&tone_mock, "The facts check out"),
+        (&facts_mock, "The tone is upbeat"),
+    ] {
+        let request = mock.last_call().unwrap();
+        assert!(!request.messages.iter().any(|m| m.content.text() == other));
+    }
+}
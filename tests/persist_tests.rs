@@ -0,0 +1,230 @@
+use agent_runtime::workflow::{WorkflowRun, WorkflowStepRecord};
+use agent_runtime::*;
+use serde_json::{json, Value};
+
+/// Small deterministic generator so failures reproduce
+struct Gen(u64);
+
+impl Gen {
+    fn next(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        self.0 >> 33
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn text(&mut self) -> String {
+        const PIECES: [&str; 8] = ["otter", " ", "日本語", "\"quoted\"", "\n", "", "🦦", "\\"];
+        (0..self.below(6))
+            .map(|_| PIECES[self.below(PIECES.len() as u64) as usize])
+            .collect()
+    }
+
+    fn value(&mut self, depth: u32) -> Value {
+        match self.below(if depth == 0 { 5 } else { 7 }) {
+            0 => Value::Null,
+            1 => json!(self.below(2) == 1),
+            2 => json!(self.next() as i64 - (1 << 30)),
+            // Dyadic fractions survive JSON's decimal text exactly
+            3 => json!(self.below(1 << 20) as f64 / 64.0),
+            4 => json!(self.text()),
+            5 => Value::Array((0..self.below(4)).map(|_| self.value(depth - 1)).collect()),
+            _ => Value::Object(
+                (0..self.below(4))
+                    .map(|i| (format!("k{}{}", i, self.text()), self.value(depth - 1)))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn context(&mut self) -> WorkflowContext {
+        let mut context = WorkflowContext::with_token_budget(1000 + self.below(1000) as usize, 3.0);
+        let history = (0..self.below(40))
+            .map(|i| match self.below(3) {
+                0 => ChatMessage::user(self.text()),
+                1 => ChatMessage::assistant(self.text()).with_provenance("agent", "wf"),
+                _ => ChatMessage::tool_result(format!("call_{}", i), self.value(2).to_string()),
+            })
+            .collect();
+        context.set_history(history);
+        context
+    }
+
+    fn run(&mut self) -> WorkflowRun {
+        let steps = (0..self.below(8) as usize)
+            .map(|i| WorkflowStepRecord {
+                step_index: i,
+                step_name: format!("step{}", i),
+                step_type: "Transform".to_string(),
+                input: self.value(3),
+                output: (self.below(4) > 0).then(|| self.value(3)),
+                execution_time_ms: Some(self.below(500)),
+                replayed: self.below(2) == 1,
+                critic: None,
+                status: StepStatus::Succeeded,
+                error: None,
+                handoffs: None,
+                started_at: None,
+                finished_at: None,
+                artifacts: Vec::new(),
+            })
+            .collect();
+        WorkflowRun {
+            workflow_id: self.text(),
+            run_id: self.text(),
+            state: WorkflowState::Completed,
+            steps,
+            final_output: Some(self.value(3)),
+            parent_workflow_id: None,
+            artifacts: Vec::new(),
+            pii_findings: None,
+            trace: None,
+            usage: Default::default(),
+            usage_breakdown: Default::default(),
+            rerun_of: (self.below(2) == 1).then(|| "earlier".to_string()),
+            failure: None,
+            approvals: Vec::new(),
+            sub_workflows: Vec::new(),
+        }
+    }
+}
+
+fn formats() -> Vec<PersistFormat> {
+    let mut formats = vec![PersistFormat::Json];
+    if cfg!(feature = "gzip") {
+        formats.push(PersistFormat::JsonGzip);
+    }
+    if cfg!(feature = "cbor") {
+        formats.push(PersistFormat::Cbor);
+    }
+    if cfg!(feature = "msgpack") {
+        formats.push(PersistFormat::MessagePack);
+    }
+    formats
+}
+
+#[test]
+fn test_generated_values_round_trip_in_every_format() {
+    let mut gen = Gen(7);
+    for _ in 0..200 {
+        let context = gen.context();
+        let run = gen.run();
+        let expected_context = serde_json::to_value(&context).unwrap();
+        let expected_run = serde_json::to_value(&run).unwrap();
+
+        for format in formats() {
+            let bytes = context.export(format).unwrap();
+            assert_eq!(PersistFormat::detect(&bytes).unwrap(), format);
+            let restored = WorkflowContext::import(&bytes).unwrap();
+            assert_eq!(serde_json::to_value(&restored).unwrap(), expected_context);
+
+            let bytes = run.export(format).unwrap();
+            let restored = WorkflowRun::import(&bytes).unwrap();
+            assert_eq!(serde_json::to_value(&restored).unwrap(), expected_run);
+        }
+    }
+}
+
+#[test]
+fn test_plain_json_checkpoints_still_load() {
+    let mut context = WorkflowContext::new();
+    context.append_messages(vec![
+        ChatMessage::user("hello"),
+        ChatMessage::assistant("hi"),
+    ]);
+
+    // Written with serde_json before formats were pluggable
+    let legacy = serde_json::to_vec_pretty(&context).unwrap();
+    assert_eq!(PersistFormat::detect(&legacy).unwrap(), PersistFormat::Json);
+    let restored = WorkflowContext::import(&legacy).unwrap();
+    assert_eq!(restored.chat_history, context.chat_history);
+    assert_eq!(restored.metadata.workflow_id, context.metadata.workflow_id);
+}
+
+#[test]
+fn test_corrupt_data_is_reported_with_its_format() {
+    let mut context = WorkflowContext::new();
+    context.append_messages(vec![ChatMessage::user("hello")]);
+
+    for format in formats() {
+        let bytes = context.export(format).unwrap();
+        let truncated = &bytes[..bytes.len() - 3];
+        let err = WorkflowContext::import(truncated).unwrap_err();
+        assert!(
+            matches!(&err, PersistError::Corrupt { format: f, .. } if *f == format),
+            "{err}"
+        );
+        assert!(
+            err.to_string()
+                .starts_with(&format!("corrupt {} data", format)),
+            "{err}"
+        );
+    }
+
+    let err = WorkflowContext::import(b"PK\x03\x04 not a checkpoint").unwrap_err();
+    assert!(matches!(err, PersistError::UnknownFormat(_)), "{err}");
+    assert!(WorkflowContext::import(b"").is_err());
+    assert!(WorkflowContext::import(b"ARPF").is_err());
+}
+
+#[cfg(feature = "cbor")]
+#[test]
+fn test_json_checkpoint_migrates_to_cbor() {
+    let mut gen = Gen(42);
+    let context = gen.context();
+    let json_bytes = context.export(PersistFormat::Json).unwrap();
+
+    let migrated = WorkflowContext::import(&json_bytes)
+        .unwrap()
+        .export(PersistFormat::Cbor)
+        .unwrap();
+    assert_eq!(
+        PersistFormat::detect(&migrated).unwrap(),
+        PersistFormat::Cbor
+    );
+    assert!(migrated.len() < json_bytes.len());
+    assert_eq!(
+        serde_json::to_value(WorkflowContext::import(&migrated).unwrap()).unwrap(),
+        serde_json::to_value(&context).unwrap()
+    );
+
+    // A reader that insists on JSON says what it found instead
+    let err = PersistFormat::Json
+        .decode::<WorkflowContext>(&migrated)
+        .unwrap_err();
+    assert_eq!(err.to_string(), "expected JSON data but found CBOR");
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn test_gzip_shrinks_a_long_history() {
+    let mut context = WorkflowContext::new();
+    context.append_messages(
+        (0..300)
+            .map(|i| ChatMessage::user(format!("Summarize topic {} in a paragraph.", i)))
+            .collect(),
+    );
+
+    let json = context.export(PersistFormat::Json).unwrap();
+    let gzipped = context.export(PersistFormat::JsonGzip).unwrap();
+    assert!(
+        gzipped.len() * 5 < json.len(),
+        "{} vs {}",
+        gzipped.len(),
+        json.len()
+    );
+    assert_eq!(
+        WorkflowContext::import(&gzipped).unwrap().chat_history,
+        context.chat_history
+    );
+
+    let err = PersistFormat::Json
+        .decode::<WorkflowContext>(&gzipped)
+        .unwrap_err();
+    assert_eq!(err.to_string(), "expected JSON data but found gzipped JSON");
+}
@@ -0,0 +1,378 @@
+use agent_runtime::pii::{LlmPiiDetector, NationalIdLocale};
+use agent_runtime::runtime::CheckpointError;
+use agent_runtime::*;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Instant;
+
+async fn types_in(scanner: &PiiScanner, text: &str) -> Vec<PiiType> {
+    scanner
+        .scan(&json!(text), None)
+        .await
+        .findings
+        .into_iter()
+        .map(|f| f.pii_type)
+        .collect()
+}
+
+fn all_locales() -> PiiScanner {
+    PiiScanner::new().with_locales(vec![
+        NationalIdLocale::Us,
+        NationalIdLocale::Gb,
+        NationalIdLocale::Ca,
+    ])
+}
+
+/// Keeps every checkpoint saved, including those removed when the run
+/// completes
+#[derive(Default)]
+struct SavedCheckpoints(std::sync::Mutex<Vec<RunCheckpoint>>);
+
+#[async_trait::async_trait]
+impl CheckpointStore for SavedCheckpoints {
+    async fn save(&self, checkpoint: &RunCheckpoint) -> Result<(), CheckpointError> {
+        self.0.lock().unwrap().push(checkpoint.clone());
+        Ok(())
+    }
+
+    async fn load(&self, _run_id: &str) -> Result<Option<RunCheckpoint>, CheckpointError> {
+        Ok(None)
+    }
+
+    async fn remove(&self, _run_id: &str) -> Result<(), CheckpointError> {
+        Ok(())
+    }
+}
+
+fn passthrough_workflow(output: serde_json::Value) -> Workflow {
+    Workflow::builder()
+        .name("pii_run".to_string())
+        .step(Box::new(TransformStep::new(
+            "produce".to_string(),
+            move |_| output.clone(),
+        )))
+        .step(Box::new(TransformStep::new("forward".to_string(), |v| v)))
+        .build()
+}
+
+#[tokio::test]
+async fn test_detects_each_type() {
+    let scanner = all_locales();
+
+    let positives = [
+        ("mail alice.smith+tag@example.co.uk now", PiiType::Email),
+        ("call +1 (555) 123-4567", PiiType::Phone),
+        ("call 555.123.4567", PiiType::Phone),
+        ("card 4111 1111 1111 1111", PiiType::CreditCard),
+        ("card 5500-0055-5555-5559", PiiType::CreditCard),
+        ("card 4111111111111111", PiiType::CreditCard),
+        ("iban DE89 3704 0044 0532 0130 00", PiiType::Iban),
+        ("iban GB82WEST12345698765432", PiiType::Iban),
+        ("ssn 123-45-6789", PiiType::NationalId),
+        ("nino AB 12 34 56 C", PiiType::NationalId),
+        ("sin 046 454 286", PiiType::NationalId),
+    ];
+    for (text, expected) in positives {
+        assert_eq!(types_in(&scanner, text).await, vec![expected], "{}", text);
+    }
+}
+
+#[tokio::test]
+async fn test_rejects_checksum_failures_and_lookalikes() {
+    let scanner = all_locales();
+
+    let negatives = [
+        // Luhn failures
+        "card 4111 1111 1111 1112",
+        "card 4111111111111112",
+        "card 5500-0055-5555-5558",
+        "sin 046 454 287",
+        // mod-97 failure
+        "iban DE88 3704 0044 0532 0130 00",
+        // invalid SSN areas
+        "ssn 000-12-3456",
+        "ssn 666-12-3456",
+        "ssn 912-34-5678",
+        // not PII
+        "order 12345, qty 3",
+        "version 1.2.3",
+        "no at sign here",
+        "2024-01-15",
+    ];
+    for text in negatives {
+        assert!(types_in(&scanner, text).await.is_empty(), "{}", text);
+    }
+}
+
+#[tokio::test]
+async fn test_locales_are_opt_in() {
+    let us_only = PiiScanner::new();
+    assert!(types_in(&us_only, "nino AB 12 34 56 C").await.is_empty());
+    assert_eq!(
+        types_in(&us_only, "ssn 123-45-6789").await,
+        vec![PiiType::NationalId]
+    );
+
+    let gb_only = PiiScanner::new().with_locales(vec![NationalIdLocale::Gb]);
+    assert!(types_in(&gb_only, "ssn 123-45-6789").await.is_empty());
+}
+
+#[tokio::test]
+async fn test_pointer_locations_in_nested_json() {
+    let value = json!({
+        "customer": {
+            "name": "Jane",
+            "contacts": [
+                {"email": "jane@example.com"},
+                {"note": "call 555-123-4567 after 5"}
+            ]
+        },
+        "a/b": ["card: 4111 1111 1111 1111"],
+        "count": 4111111111111111u64
+    });
+
+    let findings = PiiScanner::new().scan(&value, None).await;
+    let mut located: Vec<(String, PiiType, usize, usize)> = findings
+        .findings
+        .iter()
+        .map(|f| (f.pointer.clone(), f.pii_type.clone(), f.start, f.end))
+        .collect();
+    located.sort_by(|a, b| a.0.cmp(&b.0));
+
+    assert_eq!(
+        located,
+        vec![
+            ("/a~1b/0".to_string(), PiiType::CreditCard, 6, 25),
+            (
+                "/customer/contacts/0/email".to_string(),
+                PiiType::Email,
+                0,
+                16
+            ),
+            (
+                "/customer/contacts/1/note".to_string(),
+                PiiType::Phone,
+                5,
+                17
+            ),
+        ]
+    );
+    for finding in &findings.findings {
+        assert!(value.pointer(&finding.pointer).is_some());
+    }
+    assert_eq!(findings.counts()["credit_card"], 1);
+}
+
+#[tokio::test]
+async fn test_redaction_keeps_json_valid_and_other_content_intact() {
+    let original = json!({
+        "summary": "Reach \"Bob\" at bob@example.com or 555-123-4567.",
+        "items": [1, true, null, "nothing here", "ünïcödé 4111 1111 1111 1111 ✓"],
+        "nested": {"iban": "DE89 3704 0044 0532 0130 00"}
+    });
+    let mut value = original.clone();
+
+    let findings = PiiScanner::new().redact(&mut value, None).await;
+
+    assert_eq!(findings.len(), 4);
+    assert_eq!(value["summary"], "Reach \"Bob\" at [EMAIL] or [PHONE].");
+    assert_eq!(value["items"][4], "ünïcödé [CREDIT_CARD] ✓");
+    assert_eq!(value["nested"]["iban"], "[IBAN]");
+    assert_eq!(
+        value["items"].as_array().unwrap()[0..4],
+        original["items"].as_array().unwrap()[0..4]
+    );
+
+    let round_trip: serde_json::Value =
+        serde_json::from_str(&serde_json::to_string(&value).unwrap()).unwrap();
+    assert_eq!(round_trip, value);
+
+    // Spans refer to the original strings
+    let email = &findings.of_type(&PiiType::Email)[0];
+    let summary = original["summary"].as_str().unwrap();
+    assert_eq!(&summary[email.start..email.end], "bob@example.com");
+}
+
+#[tokio::test]
+async fn test_runtime_report_only() {
+    let output = json!({"reply": "Sure, email me at ops@example.com"});
+    let runtime = Runtime::new().with_pii_scanner(PiiScanner::new());
+
+    let run = runtime.execute(passthrough_workflow(output.clone())).await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(run.final_output, Some(output));
+    let findings = run.pii_findings.unwrap();
+    assert_eq!(findings.action, PiiAction::ReportOnly);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings.findings[0].pointer, "/reply");
+    assert_eq!(findings.findings[0].step_index, None);
+
+    let events = runtime.events_from_offset(0);
+    let event = events
+        .iter()
+        .find(|e| e.component_id == "system:pii_scan")
+        .expect("pii event");
+    assert_eq!(event.data["counts"]["email"], 1);
+    assert!(!event.data.to_string().contains("ops@example.com"));
+}
+
+#[tokio::test]
+async fn test_runtime_redact_final_and_step_outputs() {
+    let output = json!({"reply": "Card 4111 1111 1111 1111 on file", "ok": true});
+    let scanner = PiiScanner::new()
+        .with_action(PiiAction::Redact)
+        .with_step_outputs(true);
+    let runtime = Runtime::new().with_pii_scanner(scanner);
+    let checkpoints = SavedCheckpoints::default();
+
+    let run = runtime
+        .execute_resumable(passthrough_workflow(output), &checkpoints)
+        .await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    let expected = json!({"reply": "Card [CREDIT_CARD] on file", "ok": true});
+    assert_eq!(run.final_output, Some(expected.clone()));
+    // Both recorded step outputs are redacted; the second step still saw the
+    // original value, so its output was scanned and redacted too
+    assert_eq!(run.steps[0].output, Some(expected.clone()));
+    assert_eq!(run.steps[1].output, Some(expected.clone()));
+    // The second step's recorded input is the first one's redacted output
+    assert_eq!(run.steps[1].input, expected);
+
+    // Nothing saved for resuming holds the card number either
+    let saved = checkpoints.0.lock().unwrap();
+    assert_eq!(saved.len(), 2);
+    for checkpoint in saved.iter() {
+        assert_eq!(checkpoint.data, expected);
+        let stored = serde_json::to_string(checkpoint).unwrap();
+        assert!(!stored.contains("4111"), "{}", stored);
+    }
+
+    let findings = run.pii_findings.unwrap();
+    let steps: Vec<Option<usize>> = findings.findings.iter().map(|f| f.step_index).collect();
+    assert_eq!(steps, vec![Some(0), Some(1), None]);
+}
+
+#[tokio::test]
+async fn test_runtime_fail_withholds_output() {
+    let output = json!({"reply": "SSN is 123-45-6789"});
+    let runtime = Runtime::new().with_pii_scanner(PiiScanner::new().with_action(PiiAction::Fail));
+
+    let run = runtime.execute(passthrough_workflow(output.clone())).await;
+
+    assert_eq!(run.state, WorkflowState::Failed);
+    assert!(run.final_output.is_none());
+    assert_eq!(run.pii_findings.unwrap().counts()["national_id"], 1);
+    assert!(runtime
+        .events_from_offset(0)
+        .iter()
+        .any(|e| e.scope == EventScope::Workflow && e.event_type == EventType::Failed));
+
+    // Clean output passes
+    let clean = runtime
+        .execute(passthrough_workflow(json!({"reply": "all good"})))
+        .await;
+    assert_eq!(clean.state, WorkflowState::Completed);
+    assert!(clean.pii_findings.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_runtime_fail_on_step_output() {
+    let runtime = Runtime::new().with_pii_scanner(
+        PiiScanner::new()
+            .with_action(PiiAction::Fail)
+            .with_step_outputs(true),
+    );
+
+    let run = runtime
+        .execute(passthrough_workflow(json!("mail a@b.io")))
+        .await;
+
+    assert_eq!(run.state, WorkflowState::Failed);
+    assert_eq!(run.steps.len(), 1);
+    assert!(run.steps[0].output.is_none());
+}
+
+#[tokio::test]
+async fn test_config_enables_scanner() {
+    let config: RuntimeConfig = toml::from_str(
+        r#"
+        [workflow.pii]
+        enabled = true
+        action = "redact"
+        locales = ["us", "gb"]
+        "#,
+    )
+    .unwrap();
+
+    let scanner = PiiScanner::from_config(&config.workflow.pii).unwrap();
+    assert_eq!(scanner.action(), PiiAction::Redact);
+    assert!(PiiScanner::from_config(&PiiConfig::default()).is_none());
+
+    let mut value = json!("nino AB 12 34 56 C");
+    scanner.apply(&mut value, None).await;
+    assert_eq!(value, "nino [NATIONAL_ID]");
+}
+
+#[tokio::test]
+async fn test_llm_detector_finds_free_form_pii() {
+    let mock = Arc::new(llm::MockLlmClient::with_responses_vec(vec![
+        r#"```json
+[{"type": "name", "text": "Jane Doe"}]
+```"#,
+    ]));
+    let scanner = PiiScanner::new().with_detector(Arc::new(LlmPiiDetector::new(mock.clone())));
+
+    let mut value = json!({"note": "Jane Doe called from 555-123-4567"});
+    let findings = scanner.redact(&mut value, None).await;
+
+    assert_eq!(value["note"], "[NAME] called from [PHONE]");
+    assert_eq!(findings.counts()["name"], 1);
+    assert_eq!(mock.call_count(), 1);
+}
+
+#[tokio::test]
+async fn test_detector_errors_are_reported() {
+    let mock = Arc::new(llm::MockLlmClient::with_responses_vec(vec!["not json"]));
+    let scanner = PiiScanner::new().with_detector(Arc::new(LlmPiiDetector::new(mock)));
+
+    let findings = scanner.scan(&json!("hello"), None).await;
+    assert!(findings.is_empty());
+    assert_eq!(findings.errors.len(), 1);
+}
+
+#[tokio::test]
+async fn test_multi_megabyte_output() {
+    // ~4 MB across 40k strings, one email every 100 strings
+    let filler = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, order 12345 shipped. ";
+    let items: Vec<serde_json::Value> = (0..40_000)
+        .map(|i| {
+            if i % 100 == 0 {
+                json!({"text": format!("{} contact user{}@example.com", filler, i)})
+            } else {
+                json!({"text": filler})
+            }
+        })
+        .collect();
+    let mut value = json!({"items": items});
+
+    // plus a single 2 MB string
+    let big = format!("{}4111 1111 1111 1111", filler.repeat(25_000));
+    value["big"] = json!(big);
+
+    assert!(serde_json::to_string(&value).unwrap().len() > 4_000_000);
+
+    let started = Instant::now();
+    let findings = PiiScanner::new()
+        .with_action(PiiAction::Redact)
+        .redact(&mut value, None)
+        .await;
+    let elapsed = started.elapsed();
+
+    assert_eq!(findings.of_type(&PiiType::Email).len(), 400);
+    assert_eq!(findings.of_type(&PiiType::CreditCard).len(), 1);
+    assert_eq!(findings.len(), 401);
+    assert!(value["big"].as_str().unwrap().ends_with("[CREDIT_CARD]"));
+    assert!(elapsed.as_secs() < 20, "scan took {:?}", elapsed);
+}
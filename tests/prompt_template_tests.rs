@@ -0,0 +1,128 @@
+/// Tests for prompt templates resolved from input variables, workflow memory
+/// and built-ins
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::runtime::Runtime;
+use agent_runtime::*;
+use serde_json::json;
+use std::sync::Arc;
+
+fn agent(client: Arc<MockLlmClient>, prompt: impl Into<PromptTemplate>) -> Agent {
+    Agent::new(
+        AgentConfig::builder("helper")
+            .system_prompt_template(prompt)
+            .build(),
+    )
+    .with_client(client)
+}
+
+/// The text of every message of the client's last request
+fn sent(client: &MockLlmClient) -> Vec<String> {
+    client
+        .last_call()
+        .unwrap()
+        .messages
+        .iter()
+        .map(|m| m.content.text().into_owned())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_input_vars_win_over_memory_and_builtins() {
+    let client = Arc::new(MockLlmClient::new().with_response("Hello!"));
+    let agent = agent(
+        client.clone(),
+        "You help {{customer_name}} ({{plan}} plan) as {{agent_name}}, step {{step_index}}. Today is {{today}}.",
+    );
+    let mut input = AgentInput::from_text("Hi").with_vars([
+        ("customer_name", json!("Ada")),
+        ("agent_name", json!("Ada's concierge")),
+    ]);
+    input
+        .vars
+        .memory
+        .insert("customer_name".into(), json!("Grace"));
+    input.vars.memory.insert("plan".into(), json!("gold"));
+
+    agent.execute(&input).await.unwrap();
+
+    let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+    assert_eq!(
+        sent(&client),
+        vec![
+            format!(
+                "You help Ada (gold plan) as Ada's concierge, step 0. Today is {}.",
+                today
+            ),
+            "Hi".to_string(),
+        ]
+    );
+    // The config keeps the template as written
+    assert_eq!(
+        agent.config().system_prompt,
+        "You help {{customer_name}} ({{plan}} plan) as {{agent_name}}, step {{step_index}}. Today is {{today}}."
+    );
+}
+
+#[tokio::test]
+async fn test_missing_variables_fail_or_stay_literal() {
+    let client = Arc::new(MockLlmClient::new().with_response("Hello!"));
+    let error = agent(client.clone(), "You help {{customer_name}}.")
+        .execute(&AgentInput::from_text("Hi"))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Invalid input: template 'prompt': variable 'customer_name' is not set"
+    );
+    assert_eq!(client.call_count(), 0);
+
+    let lenient =
+        PromptTemplate::new("You help {{customer_name}}.").on_missing(MissingVariables::Literal);
+    agent(client.clone(), lenient)
+        .execute(&AgentInput::from_text("Hi"))
+        .await
+        .unwrap();
+    assert_eq!(sent(&client)[0], "You help {{customer_name}}.");
+
+    // Escaped braces are sent as braces
+    let client = Arc::new(MockLlmClient::new().with_response("{}"));
+    agent(client.clone(), r#"Reply with \{{"ok": true}}."#)
+        .execute(&AgentInput::from_text("Hi"))
+        .await
+        .unwrap();
+    assert_eq!(sent(&client)[0], r#"Reply with {{"ok": true}}."#);
+}
+
+#[tokio::test]
+async fn test_workflow_memory_fills_prompt_and_input_templates() {
+    let mut saved = WorkflowContext::new();
+    saved.memory_set("customer_name", json!("Ada"));
+    let store = Arc::new(MemoryContextStore::new());
+    store.save("support", &saved).await.unwrap();
+
+    let client = Arc::new(MockLlmClient::new().with_response("On it."));
+    let step = AgentStep::from_agent(
+        agent(client.clone(), "You help {{customer_name}}."),
+        "reply".to_string(),
+    )
+    .with_input_template("Ticket {{input.id}} from {{customer_name}}: {{input.subject}}");
+    let workflow = Workflow::builder()
+        .name("support".to_string())
+        .with_context_store(store.clone(), "support")
+        .step(Box::new(step))
+        .initial_input(json!({ "id": 7, "subject": "Login fails" }))
+        .build();
+    let run = Runtime::new().execute(workflow).await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(
+        sent(&client),
+        vec!["You help Ada.", "Ticket 7 from Ada: Login fails"]
+    );
+    // Later agents see the prompt as it was rendered
+    let stored = store.load("support").await.unwrap().unwrap().context;
+    assert_eq!(
+        stored.system_prompts_for("next")[0].content.text(),
+        "You help Ada."
+    );
+}
@@ -0,0 +1,256 @@
+/// Tests for tools described in the prompt, for models without function calling
+use agent_runtime::llm::{ChatRequest, ChatResponse, GenericChatClient, LlmError, MockLlmClient};
+use agent_runtime::*;
+use async_trait::async_trait;
+use serde_json::json;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// A weather tool counting how often it runs
+fn weather(runs: Arc<AtomicUsize>) -> Arc<ToolRegistry> {
+    let mut registry = ToolRegistry::new();
+    registry.register(NativeTool::new(
+        "get_weather",
+        "Current weather for a city",
+        json!({"type": "object", "properties": {"city": {"type": "string"}}}),
+        move |params| {
+            let runs = runs.clone();
+            async move {
+                runs.fetch_add(1, Ordering::SeqCst);
+                Ok(ToolResult::success(
+                    json!({ "city": params["city"], "sky": "clear" }),
+                    1.0,
+                ))
+            }
+        },
+    ));
+    Arc::new(registry)
+}
+
+fn forecaster(tools: Arc<ToolRegistry>, mode: ToolCallingMode) -> AgentConfig {
+    AgentConfig::builder("forecaster")
+        .system_prompt("You report the weather.")
+        .tools(tools)
+        .tool_calling(mode)
+        .build()
+}
+
+fn text(request: &ChatRequest, i: usize) -> String {
+    request.messages[i].content.text().into_owned()
+}
+
+async fn system_events(stream: &EventStream, component: &str) -> Vec<Event> {
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    stream
+        .all()
+        .into_iter()
+        .filter(|e| e.component_id == component)
+        .collect()
+}
+
+#[tokio::test]
+async fn test_prompted_calls_run_and_the_answer_follows() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec![
+        "Let me check both cities.\n\n```json\n{\"tool\": \"get_weather\", \"arguments\": {\"city\": \"Oslo\"}}\n```\n\nand\n\n```json\n{\"tool\": \"get_weather\", \"arguments\": {\"city\": \"Bergen\"}}\n```",
+        "Clear skies in Oslo and Bergen.",
+    ]));
+    let stream = EventStream::new();
+
+    let output = Agent::new(forecaster(weather(runs.clone()), ToolCallingMode::Prompted))
+        .with_client(mock.clone())
+        .execute_with_events(AgentInput::from_text("Weather up north?"), Some(&stream))
+        .await
+        .unwrap();
+
+    assert_eq!(output.data["response"], "Clear skies in Oslo and Bergen.");
+    assert_eq!(output.metadata.tool_calls_count, 2);
+    assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+    let calls = mock.get_calls();
+    assert!(calls.iter().all(|request| request.tools.is_none()));
+    let first = &calls[0];
+    assert_eq!(text(first, 0), "You report the weather.");
+    assert!(text(first, 1).contains("- get_weather: Current weather for a city"));
+    assert!(text(first, 1).contains("```json"));
+
+    // Each result comes back as an observation, after the model's own text
+    let second = &calls[1];
+    let n = second.messages.len();
+    assert!(text(second, n - 3).starts_with("Let me check both cities."));
+    assert!(text(second, n - 2).starts_with("Observation from get_weather:"));
+    assert!(text(second, n - 2).contains("Oslo"));
+    assert!(text(second, n - 1).contains("Bergen"));
+    assert!(second
+        .messages
+        .iter()
+        .all(|m| m.tool_call_id.is_none() && m.tool_calls.is_none()));
+
+    // The descriptions don't outlive the execution
+    let history = output.chat_history.unwrap();
+    assert!(history
+        .iter()
+        .all(|m| !m.content.text().contains("You can use these tools")));
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let completed = stream
+        .all()
+        .into_iter()
+        .filter(|e| e.scope == EventScope::Tool && e.event_type == EventType::Completed)
+        .count();
+    assert_eq!(completed, 2);
+}
+
+#[tokio::test]
+async fn test_unreadable_call_gets_a_correction() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec![
+        "```json\n{\"tool\": \"get_weather\", \"arguments\": {\"city\": \"Oslo\"\n```",
+        "Sorry, again:\n```json\n{\"tool\": \"get_weather\", \"arguments\": {\"city\": \"Oslo\"}}\n```",
+        "Clear in Oslo.",
+    ]));
+
+    let output = Agent::new(forecaster(weather(runs.clone()), ToolCallingMode::Prompted))
+        .with_client(mock.clone())
+        .execute(&AgentInput::from_text("Weather in Oslo?"))
+        .await
+        .unwrap();
+
+    assert_eq!(output.data["response"], "Clear in Oslo.");
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+    let second = mock.get_calls()[1].clone();
+    let correction = text(&second, second.messages.len() - 1);
+    assert!(correction.starts_with("Your tool call could not be read: invalid JSON"));
+}
+
+#[tokio::test]
+async fn test_loop_detection_applies_to_prompted_calls() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let call = "```json\n{\"tool\": \"get_weather\", \"arguments\": {\"city\": \"Oslo\"}}\n```";
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec![
+        call,
+        call,
+        "Clear in Oslo.",
+    ]));
+    let stream = EventStream::new();
+
+    Agent::new(forecaster(weather(runs.clone()), ToolCallingMode::Prompted))
+        .with_client(mock.clone())
+        .execute_with_events(AgentInput::from_text("Weather in Oslo?"), Some(&stream))
+        .await
+        .unwrap();
+
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+    assert_eq!(
+        system_events(&stream, "system:tool_loop_detection")
+            .await
+            .len(),
+        1
+    );
+    let third = mock.get_calls()[2].clone();
+    let observation = text(&third, third.messages.len() - 1);
+    assert!(observation.starts_with("Observation from get_weather:"));
+    assert!(observation.contains("You already called the tool 'get_weather'"));
+}
+
+#[tokio::test]
+async fn test_auto_switches_when_the_model_writes_calls() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec![
+        "{\"tool\": \"get_weather\", \"arguments\": {\"city\": \"Oslo\"}}",
+        "Clear in Oslo.",
+    ]));
+    let stream = EventStream::new();
+
+    let output = Agent::new(forecaster(weather(runs.clone()), ToolCallingMode::Auto))
+        .with_client(mock.clone())
+        .execute_with_events(AgentInput::from_text("Weather in Oslo?"), Some(&stream))
+        .await
+        .unwrap();
+
+    assert_eq!(output.data["response"], "Clear in Oslo.");
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+    let calls = mock.get_calls();
+    assert!(calls[0].tools.is_some());
+    assert!(calls[1].tools.is_none());
+    assert!(text(&calls[1], 1).starts_with("You can use these tools"));
+
+    let switched = system_events(&stream, "system:tool_calling").await;
+    assert_eq!(switched.len(), 1);
+    assert_eq!(switched[0].data["mode"], "prompted");
+}
+
+/// A provider refusing any request with tools, as older local servers do
+struct NoToolSupport(MockLlmClient);
+
+#[async_trait]
+impl GenericChatClient for NoToolSupport {
+    async fn chat(&self, request: ChatRequest) -> Result<ChatResponse, LlmError> {
+        if request.tools.is_some() {
+            return Err(LlmError::InvalidRequest(
+                "tools are not supported by this model".to_string(),
+            ));
+        }
+        self.0.chat(request).await
+    }
+
+    async fn chat_stream(
+        &self,
+        request: ChatRequest,
+        tx: mpsc::Sender<String>,
+    ) -> Result<ChatResponse, LlmError> {
+        if request.tools.is_some() {
+            return Err(LlmError::InvalidRequest(
+                "tools are not supported by this model".to_string(),
+            ));
+        }
+        self.0.chat_stream(request, tx).await
+    }
+
+    fn provider_name(&self) -> &str {
+        "local"
+    }
+}
+
+#[tokio::test]
+async fn test_auto_falls_back_when_tools_are_rejected() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let client = Arc::new(NoToolSupport(MockLlmClient::with_responses_vec(vec![
+        "```json\n{\"tool\": \"get_weather\", \"arguments\": {\"city\": \"Oslo\"}}\n```",
+        "Clear in Oslo.",
+    ])));
+    let stream = EventStream::new();
+
+    let output = Agent::new(forecaster(weather(runs.clone()), ToolCallingMode::Auto))
+        .with_client(client)
+        .execute_with_events(AgentInput::from_text("Weather in Oslo?"), Some(&stream))
+        .await
+        .unwrap();
+
+    assert_eq!(output.data["response"], "Clear in Oslo.");
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+    let switched = system_events(&stream, "system:tool_calling").await;
+    assert_eq!(switched.len(), 1);
+    assert!(switched[0].data["reason"]
+        .as_str()
+        .unwrap()
+        .contains("tools are not supported"));
+}
+
+#[tokio::test]
+async fn test_native_mode_leaves_written_calls_alone() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let written = "```json\n{\"tool\": \"get_weather\", \"arguments\": {\"city\": \"Oslo\"}}\n```";
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec![written]));
+
+    let output = Agent::new(forecaster(weather(runs.clone()), ToolCallingMode::Native))
+        .with_client(mock)
+        .execute(&AgentInput::from_text("Weather in Oslo?"))
+        .await
+        .unwrap();
+
+    assert_eq!(output.data["response"], written);
+    assert_eq!(runs.load(Ordering::SeqCst), 0);
+}
@@ -0,0 +1,189 @@
+/// Tests for provider headers, proxies and timeouts, against a scripted
+/// local HTTP server
+use agent_runtime::llm::{
+    ChatMessage, ChatRequest, FallbackChatClient, GenericChatClient, HeaderProvider, LlamaClient,
+    LlmError, OpenAIClient,
+};
+use agent_runtime::{ConfigErrorCode, LlamaConfig, LlmConfig, OpenAIConfig};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+mod common;
+use common::{serve, Reply};
+
+fn answer(text: &str) -> Reply {
+    Reply::json(json!({
+        "id": "chatcmpl-1",
+        "model": "test",
+        "choices": [{
+            "index": 0,
+            "message": { "role": "assistant", "content": text },
+            "finish_reason": "stop"
+        }],
+        "usage": { "prompt_tokens": 9, "completion_tokens": 2, "total_tokens": 11 }
+    }))
+}
+
+fn streamed(words: &[&str]) -> Reply {
+    let mut chunks: Vec<Value> = words
+        .iter()
+        .map(|word| json!({ "model": "test", "choices": [{ "index": 0, "delta": { "content": word } }] }))
+        .collect();
+    chunks.push(json!({ "model": "test", "choices": [{ "index": 0, "delta": {}, "finish_reason": "stop" }] }));
+    let mut stream: String = chunks.iter().map(|c| format!("data: {}\n\n", c)).collect();
+    stream.push_str("data: [DONE]\n\n");
+    Reply::text("text/event-stream", stream)
+}
+
+fn request() -> ChatRequest {
+    ChatRequest::new(vec![ChatMessage::user("Hi")])
+}
+
+/// A gateway token that changes on every request
+struct RotatingToken(AtomicUsize);
+
+impl HeaderProvider for RotatingToken {
+    fn headers(&self) -> HashMap<String, String> {
+        let n = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+        HashMap::from([("X-Gateway-Token".to_string(), format!("token-{}", n))])
+    }
+}
+
+#[tokio::test]
+async fn test_llama_headers_on_chat_and_stream() {
+    let (base_url, recorded) = serve(vec![answer("Hello"), streamed(&["Hel", "lo"])]).await;
+    let token = Arc::new(RotatingToken(AtomicUsize::new(0)));
+    let client = LlamaClient::new(base_url, "llama")
+        .with_header("X-Team", "search")
+        .with_header_provider(token.clone());
+
+    assert_eq!(client.chat(request()).await.unwrap().content, "Hello");
+    let (tx, mut rx) = mpsc::channel(16);
+    let response = client.chat_stream(request(), tx).await.unwrap();
+    assert_eq!(response.content, "Hello");
+    assert_eq!(rx.recv().await.as_deref(), Some("Hel"));
+
+    // The provider is asked again for each request
+    assert_eq!(token.0.load(Ordering::SeqCst), 2);
+    let recorded = recorded.lock().unwrap();
+    for (i, request) in recorded.iter().enumerate() {
+        assert!(request.head.contains("x-team: search"));
+        assert!(request
+            .head
+            .contains(&format!("x-gateway-token: token-{}", i + 1)));
+    }
+    assert!(recorded[1].head.contains("accept: text/event-stream"));
+}
+
+#[tokio::test]
+async fn test_openai_headers_replace_the_bearer() {
+    let (base_url, recorded) = serve(vec![answer("Hello"), streamed(&["Hello"])]).await;
+    let refreshed =
+        || HashMap::from([("Authorization".to_string(), "Bearer refreshed".to_string())]);
+    let client = OpenAIClient::with_model("sk-stale", "gpt-4o")
+        .with_base_url(base_url)
+        .with_header("X-Team", "search")
+        .with_header_provider(Arc::new(refreshed));
+
+    client.chat(request()).await.unwrap();
+    let (tx, _rx) = mpsc::channel(16);
+    client.chat_stream(request(), tx).await.unwrap();
+
+    let recorded = recorded.lock().unwrap();
+    assert_eq!(recorded.len(), 2);
+    for request in recorded.iter() {
+        assert!(request.head.contains("authorization: bearer refreshed"));
+        assert!(!request.head.contains("sk-stale"));
+        assert!(request.head.contains("x-team: search"));
+    }
+}
+
+#[tokio::test]
+async fn test_factory_applies_headers_and_proxy() {
+    let (proxy, recorded) = serve(vec![answer("Hello"), answer("Hello")]).await;
+    let config = LlmConfig {
+        default_model: Some("gpt-4o".to_string()),
+        fallback: vec!["llama".to_string(), "openai".to_string()],
+        llama: Some(LlamaConfig {
+            base_url: "http://llm.internal:8080".to_string(),
+            insecure: false,
+            rate_limit: None,
+            default_headers: HashMap::from([("X-Team".to_string(), "search".to_string())]),
+            proxy: Some(proxy.clone()),
+            connect_timeout_ms: Some(2_000),
+            request_timeout_ms: Some(10_000),
+        }),
+        openai: Some(OpenAIConfig {
+            api_key: Some("sk-test".to_string()),
+            api_base: Some("http://api.internal/v1".to_string()),
+            organization: Some("org-42".to_string()),
+            rate_limit: None,
+            default_headers: HashMap::new(),
+            proxy: Some(proxy),
+            connect_timeout_ms: None,
+            request_timeout_ms: None,
+        }),
+        ..Default::default()
+    };
+    let chain = FallbackChatClient::from_config(&config).unwrap();
+    chain.chat(request()).await.unwrap();
+
+    // Requests go to the proxy, naming the server they're for
+    let llama = recorded.lock().unwrap()[0].head.clone();
+    assert!(llama.starts_with("post http://llm.internal:8080/chat/completions"));
+    assert!(llama.contains("x-team: search"));
+
+    let openai =
+        OpenAIClient::from_config(config.openai.as_ref().unwrap(), Some("gpt-4o")).unwrap();
+    openai.chat(request()).await.unwrap();
+    let openai = recorded.lock().unwrap()[1].head.clone();
+    assert!(openai.starts_with("post http://api.internal/v1/chat/completions"));
+    assert!(openai.contains("openai-organization: org-42"));
+    assert!(openai.contains("authorization: bearer sk-test"));
+}
+
+#[tokio::test]
+async fn test_request_timeout_from_config() {
+    let (base_url, _) = serve(vec![Reply::stall()]).await;
+    let config = LlamaConfig {
+        base_url,
+        insecure: false,
+        rate_limit: None,
+        default_headers: HashMap::new(),
+        proxy: None,
+        connect_timeout_ms: None,
+        request_timeout_ms: Some(200),
+    };
+    let client = LlamaClient::from_config(&config, "llama").unwrap();
+
+    let started = Instant::now();
+    let error = client.chat(request()).await.unwrap_err();
+    assert!(matches!(error, LlmError::NetworkError(_)));
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn test_invalid_settings_are_config_errors() {
+    let mut config = LlamaConfig {
+        base_url: "http://localhost:8080".to_string(),
+        insecure: false,
+        rate_limit: None,
+        default_headers: HashMap::from([("X-Team".to_string(), "line\nbreak".to_string())]),
+        proxy: None,
+        connect_timeout_ms: None,
+        request_timeout_ms: None,
+    };
+    let error = LlamaClient::from_config(&config, "llama").err().unwrap();
+    assert_eq!(error.code, ConfigErrorCode::InvalidValue);
+    assert_eq!(error.field.as_deref(), Some("llm.llama.default_headers"));
+    assert!(!error.message.contains("line"));
+
+    config.default_headers.clear();
+    config.proxy = Some("http://[not a proxy".to_string());
+    let error = LlamaClient::from_config(&config, "llama").err().unwrap();
+    assert_eq!(error.field.as_deref(), Some("llm.llama.proxy"));
+}
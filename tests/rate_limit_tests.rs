@@ -0,0 +1,195 @@
+/// Tests for rate limiting LLM requests shared across agents
+use agent_runtime::llm::{
+    GenericChatClient, LlmClient, LlmResult, MockLlmClient, RateLimitedChatClient, RateLimiter,
+    RateLimits,
+};
+use agent_runtime::*;
+use async_trait::async_trait;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// Records which request reached the provider, and when
+struct Recording {
+    inner: MockLlmClient,
+    started: Instant,
+    arrivals: Mutex<Vec<(String, Duration)>>,
+}
+
+impl Recording {
+    fn new(responses: usize) -> Arc<Self> {
+        Arc::new(Self {
+            inner: MockLlmClient::with_responses_vec(vec!["ok"; responses]),
+            started: Instant::now(),
+            arrivals: Mutex::new(Vec::new()),
+        })
+    }
+
+    fn arrivals(&self) -> Vec<(String, Duration)> {
+        self.arrivals.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl GenericChatClient for Recording {
+    async fn chat(&self, request: ChatRequest) -> LlmResult<ChatResponse> {
+        let text = request.messages.last().unwrap().content.text().into_owned();
+        self.arrivals
+            .lock()
+            .unwrap()
+            .push((text, self.started.elapsed()));
+        self.inner.chat(request).await
+    }
+
+    async fn chat_stream(
+        &self,
+        request: ChatRequest,
+        _tx: mpsc::Sender<String>,
+    ) -> LlmResult<ChatResponse> {
+        self.chat(request).await
+    }
+}
+
+fn request(text: &str) -> ChatRequest {
+    ChatRequest::new(vec![ChatMessage::user(text)])
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_requests_queue_in_arrival_order() {
+    let provider = Recording::new(5);
+    let client = RateLimitedChatClient::new(
+        provider.clone(),
+        RateLimits::new().with_requests_per_minute(2),
+    );
+
+    let started = Instant::now();
+    let names: Vec<String> = (0..5).map(|i| format!("request {}", i)).collect();
+    let responses =
+        futures::future::join_all(names.iter().map(|name| client.chat(request(name)))).await;
+    assert!(responses.iter().all(|r| r.is_ok()));
+
+    // Two fit in the first minute's bucket; the rest get one every 30s
+    assert_eq!(started.elapsed(), Duration::from_secs(90));
+    let arrivals = provider.arrivals();
+    assert_eq!(
+        arrivals.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+        names.iter().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        arrivals
+            .iter()
+            .map(|(_, at)| at.as_secs())
+            .collect::<Vec<_>>(),
+        vec![0, 0, 30, 60, 90]
+    );
+
+    let stats = client.limiter().stats();
+    assert_eq!(stats.requests, 5);
+    assert_eq!(stats.delayed, 3);
+    assert_eq!(stats.wait_ms, 180_000.0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_unused_tokens_are_refunded_from_usage() {
+    let limiter = RateLimiter::new(RateLimits::new().with_tokens_per_minute(1_000));
+    let provider = Recording::new(3);
+    let client = RateLimitedChatClient::with_limiter(provider.clone(), limiter.clone());
+    let big = || request("hi").with_max_tokens(900);
+
+    // Each reserves 901 tokens; the mock reports 15 used, so the rest comes back
+    for _ in 0..3 {
+        client.chat(big()).await.unwrap();
+    }
+    assert!(provider.arrivals().iter().all(|(_, at)| at.is_zero()));
+    assert_eq!(limiter.stats().refunded_tokens, 3 * (901 - 15));
+
+    // A client sharing the limiter draws on the same bucket
+    let other = RateLimitedChatClient::with_limiter(Recording::new(2), limiter.clone());
+    let permit = limiter.acquire(&big()).await;
+    assert!(permit.waited().is_zero());
+    let started = Instant::now();
+    other.chat(big()).await.unwrap();
+    assert!(started.elapsed() >= Duration::from_secs(50));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_runtime_limiter_covers_every_agent() {
+    let limiter = RateLimiter::new(RateLimits::new().with_requests_per_minute(1));
+    let runtime = Runtime::new().with_rate_limiter(limiter.clone());
+    let mut builder = Workflow::builder()
+        .name("team".to_string())
+        .initial_input(json!("go"));
+    for name in ["planner", "writer", "reviewer"] {
+        let client: LlmClient = Arc::new(MockLlmClient::with_responses_vec(vec!["ok"]));
+        builder = builder.add_step(Box::new(AgentStep::from_agent(
+            Agent::new(AgentConfig::builder(name).build()).with_client(client),
+            name.to_string(),
+        )));
+    }
+
+    let started = Instant::now();
+    let run = runtime.execute(builder.build()).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert!(started.elapsed() >= Duration::from_secs(120));
+
+    let stats = limiter.stats();
+    assert_eq!(stats.requests, 3);
+    assert_eq!(stats.delayed, 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_waits_show_in_the_turn_latency() {
+    let client = RateLimitedChatClient::new(
+        Arc::new(MockLlmClient::with_responses_vec(vec!["ok", "ok"])),
+        RateLimits::new().with_requests_per_minute(1),
+    );
+    let agent = Agent::new(AgentConfig::builder("writer").build()).with_client(Arc::new(client));
+    let input = AgentInput::from_text("go");
+
+    let first = agent.execute(&input).await.unwrap();
+    assert_eq!(first.metadata.latency.unwrap().rate_limit_wait_ms, 0.0);
+    let second = agent.execute(&input).await.unwrap();
+    let latency = second.metadata.latency.unwrap();
+    assert_eq!(latency.rate_limit_wait_ms, 60_000.0);
+    assert_eq!(latency.llm_calls[0].rate_limit_wait_ms, 60_000.0);
+}
+
+#[test]
+fn test_limits_from_config() {
+    let config: RuntimeConfig = toml::from_str(
+        r#"
+        [llm]
+        fallback = ["openai"]
+
+        [llm.openai]
+        api_key = "sk-test"
+
+        [llm.openai.rate_limit]
+        requests_per_minute = 60
+        tokens_per_minute = 90000
+        "#,
+    )
+    .unwrap();
+    config.validate().unwrap();
+    assert_eq!(
+        config.llm.rate_limits("openai"),
+        Some(
+            &RateLimits::new()
+                .with_requests_per_minute(60)
+                .with_tokens_per_minute(90_000)
+        )
+    );
+    assert_eq!(config.llm.rate_limits("llama"), None);
+    llm::FallbackChatClient::from_config(&config.llm).unwrap();
+
+    let mut zero = config.clone();
+    zero.llm.openai.as_mut().unwrap().rate_limit =
+        Some(RateLimits::new().with_tokens_per_minute(0));
+    let error = zero.validate().unwrap_err();
+    assert_eq!(
+        error.field.as_deref(),
+        Some("llm.openai.rate_limit.tokens_per_minute")
+    );
+}
@@ -0,0 +1,181 @@
+/// Tests for recording LLM exchanges to a cassette and replaying them
+use agent_runtime::llm::{
+    ChatMessage, ChatRequest, GenericChatClient, LlmError, MockLlmClient, RecordingChatClient,
+    ReplayChatClient, ReplayMatch,
+};
+use agent_runtime::*;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+fn cassette_path() -> PathBuf {
+    std::env::temp_dir().join(format!("cassette-{}.json", uuid::Uuid::new_v4()))
+}
+
+fn calculator(client: Arc<dyn GenericChatClient>, prompt: &str) -> Agent {
+    let mut registry = ToolRegistry::new();
+    registry.register(NativeTool::new(
+        "add",
+        "Add two numbers",
+        json!({
+            "type": "object",
+            "properties": { "a": { "type": "number" }, "b": { "type": "number" } }
+        }),
+        |params| async move {
+            let number = |key: &str| params.get(key).and_then(Value::as_f64).unwrap_or(0.0);
+            Ok(ToolResult::success(json!(number("a") + number("b")), 0.0))
+        },
+    ));
+    Agent::new(
+        AgentConfig::builder("calculator")
+            .system_prompt(prompt)
+            .tools(Arc::new(registry))
+            .build(),
+    )
+    .with_client(client)
+}
+
+async fn chunks(client: &dyn GenericChatClient, request: ChatRequest) -> Vec<String> {
+    let (tx, mut rx) = mpsc::channel(100);
+    client.chat_stream(request, tx).await.unwrap();
+    let mut chunks = Vec::new();
+    while let Some(chunk) = rx.recv().await {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+#[tokio::test]
+async fn test_replay_reproduces_a_recorded_agent_run() {
+    let path = cassette_path();
+    let mock = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("add", json!({ "a": 2, "b": 3 }))
+            .with_response("2 + 3 is 5"),
+    );
+    let recorder = Arc::new(RecordingChatClient::new(mock.clone(), &path));
+    let input = AgentInput::from_value(json!("What is 2 + 3?"));
+    let recorded = calculator(recorder.clone(), "Use the tools.")
+        .execute(&input)
+        .await
+        .unwrap();
+    assert_eq!(mock.call_count(), 2);
+    assert_eq!(recorder.cassette().interactions.len(), 2);
+
+    let replay = Arc::new(ReplayChatClient::from_file(&path).unwrap());
+    let replayed = calculator(replay.clone(), "Use the tools.")
+        .execute(&input)
+        .await
+        .unwrap();
+    assert_eq!(replayed.data, recorded.data);
+    assert_eq!(replay.remaining(), 0);
+    assert_eq!(mock.call_count(), 2);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_mismatch_names_the_first_divergent_message() {
+    let path = cassette_path();
+    let recorder = Arc::new(RecordingChatClient::new(
+        Arc::new(MockLlmClient::new().with_response("5")),
+        &path,
+    ));
+    let request = |question: &str| {
+        ChatRequest::new(vec![
+            ChatMessage::system("Answer briefly."),
+            ChatMessage::user(question),
+        ])
+    };
+    recorder.chat(request("What is 2 + 3?")).await.unwrap();
+
+    let replay = ReplayChatClient::from_file(&path).unwrap();
+    let error = replay.chat(request("What is 2 + 4?")).await.unwrap_err();
+    match error {
+        LlmError::InvalidRequest(message) => assert_eq!(
+            message,
+            "Request matches no recorded interaction; against interaction 0, message 1 \
+             differs: recorded user \"What is 2 + 3?\", got user \"What is 2 + 4?\""
+        ),
+        other => panic!("expected InvalidRequest, got {other:?}"),
+    }
+
+    // Served once; the next identical request finds the cassette used up
+    assert_eq!(
+        replay
+            .chat(request("What is 2 + 3?"))
+            .await
+            .unwrap()
+            .content,
+        "5"
+    );
+    let error = replay.chat(request("What is 2 + 3?")).await.unwrap_err();
+    assert!(error.to_string().contains("no unused interactions left"));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_relaxed_matching_ignores_earlier_messages() {
+    let path = cassette_path();
+    let mock = Arc::new(MockLlmClient::new().with_response("5"));
+    let input = AgentInput::from_value(json!("What is 2 + 3?"));
+    let recorder = Arc::new(RecordingChatClient::new(mock, &path));
+    calculator(recorder, "Use the tools.")
+        .execute(&input)
+        .await
+        .unwrap();
+
+    // A reworded system prompt no longer matches exactly
+    let strict = Arc::new(ReplayChatClient::from_file(&path).unwrap());
+    let error = calculator(strict, "Always use the tools.")
+        .execute(&input)
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("message 0 differs"));
+
+    let relaxed = Arc::new(
+        ReplayChatClient::from_file(&path)
+            .unwrap()
+            .with_matching(ReplayMatch::LastUserMessage),
+    );
+    let output = calculator(relaxed, "Always use the tools.")
+        .execute(&input)
+        .await
+        .unwrap();
+    assert_eq!(output.data["response"], "5");
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn test_streams_replay_their_recorded_chunks() {
+    let path = cassette_path();
+    let recorder = RecordingChatClient::new(
+        Arc::new(
+            MockLlmClient::new()
+                .with_response("Mars has two moons")
+                .with_response("Phobos and Deimos"),
+        ),
+        &path,
+    );
+    let first = ChatRequest::new(vec![ChatMessage::user("How many moons?")]);
+    let second = ChatRequest::new(vec![ChatMessage::user("Which?")]);
+    let recorded = chunks(&recorder, first.clone()).await;
+    assert_eq!(recorded, ["Mars ", "has ", "two ", "moons "]);
+    recorder.chat(second.clone()).await.unwrap();
+
+    let replay = ReplayChatClient::from_file(&path)
+        .unwrap()
+        .with_chunk_delay(Duration::from_millis(10));
+    let started = std::time::Instant::now();
+    assert_eq!(chunks(&replay, first).await, recorded);
+    assert!(started.elapsed() >= Duration::from_millis(40));
+
+    // Recorded without streaming: replayed as a single chunk
+    assert_eq!(chunks(&replay, second).await, ["Phobos and Deimos"]);
+
+    std::fs::remove_file(path).unwrap();
+}
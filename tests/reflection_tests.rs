@@ -0,0 +1,154 @@
+/// Tests for agent self-reflection before the final answer
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::prelude::ToolResult;
+use agent_runtime::*;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+const REJECT: &str = r#"{"acceptable": false, "issues": ["the price is missing"]}"#;
+const ACCEPT: &str = r#"```json
+{"acceptable": true, "issues": []}
+```"#;
+
+fn prices() -> Arc<ToolRegistry> {
+    let mut registry = ToolRegistry::new();
+    registry.register(NativeTool::new(
+        "get_price",
+        "Look up a product's price",
+        json!({ "type": "object", "properties": { "sku": { "type": "string" } } }),
+        |_| async move { Ok(ToolResult::success(json!({ "price": 42 }), 1.0)) },
+    ));
+    Arc::new(registry)
+}
+
+fn agent(client: Arc<MockLlmClient>, reflection: ReflectionConfig) -> Agent {
+    Agent::new(
+        AgentConfig::builder("shopper")
+            .system_prompt("Answer questions about products.")
+            .tools(prices())
+            .reflection(reflection)
+            .build(),
+    )
+    .with_client(client)
+}
+
+#[tokio::test]
+async fn test_rejected_answer_is_revised() {
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("get_price", json!({ "sku": "X1" }))
+            .with_response("The X1 is in stock.")
+            .with_response(REJECT)
+            .with_response("The X1 is in stock and costs $42.")
+            .with_response(ACCEPT),
+    );
+    let stream = EventStream::new();
+    let output = agent(client.clone(), ReflectionConfig::default())
+        .execute_with_events(
+            AgentInput::from_text("Tell me about the X1, with its price"),
+            Some(&stream),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(output.data["response"], "The X1 is in stock and costs $42.");
+    let report = output.metadata.reflection.unwrap();
+    assert_eq!(report.revisions, 1);
+    assert_eq!(report.verdicts.len(), 2);
+    assert_eq!(report.verdicts[0].issues, vec!["the price is missing"]);
+    assert!(report.verdicts[1].acceptable);
+    // Tool call, two answers and two critiques
+    assert_eq!(output.metadata.usage.llm_calls, 5);
+    assert_eq!(output.metadata.usage.total_tokens, 75);
+
+    let calls = client.get_calls();
+    assert_eq!(calls.len(), 5);
+    // The critique sees the tool result and the candidate answer, without tools
+    let critique = &calls[2];
+    assert!(critique.tools.is_none());
+    assert!(critique
+        .messages
+        .iter()
+        .any(|m| m.content.text().contains("\"price\":42")));
+    assert_eq!(
+        critique.messages[critique.messages.len() - 2]
+            .content
+            .text(),
+        "The X1 is in stock."
+    );
+    // The revision request carries the issues
+    let revision = calls[3]
+        .messages
+        .last()
+        .unwrap()
+        .content
+        .text()
+        .into_owned();
+    assert!(revision.contains("- the price is missing"), "{}", revision);
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let verdicts: Vec<bool> = stream
+        .all()
+        .into_iter()
+        .filter(|e| e.component_id == "shopper:reflection" && e.event_type == EventType::Completed)
+        .map(|e| e.data["accepted"].as_bool().unwrap())
+        .collect();
+    assert_eq!(verdicts, vec![false, true]);
+}
+
+#[tokio::test]
+async fn test_revisions_are_capped() {
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_response("First try.")
+            .with_response(REJECT)
+            .with_response("Second try.")
+            .with_response(REJECT),
+    );
+    let config = ReflectionConfig {
+        max_revisions: 1,
+        ..Default::default()
+    };
+    let output = agent(client.clone(), config)
+        .execute(&AgentInput::from_text("How much is the X1?"))
+        .await
+        .unwrap();
+
+    // Out of revisions, the last answer stands
+    assert_eq!(output.data["response"], "Second try.");
+    let report = output.metadata.reflection.unwrap();
+    assert_eq!(report.revisions, 1);
+    assert!(!report.verdicts[1].acceptable);
+    assert_eq!(client.call_count(), 4);
+}
+
+#[tokio::test]
+async fn test_unreadable_critique_accepts() {
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_response("It costs $42.")
+            .with_response("Looks good to me!"),
+    );
+    let output = agent(client.clone(), ReflectionConfig::default())
+        .execute(&AgentInput::from_text("How much is the X1?"))
+        .await
+        .unwrap();
+
+    assert_eq!(output.data["response"], "It costs $42.");
+    assert_eq!(output.metadata.reflection.unwrap().revisions, 0);
+    assert_eq!(client.call_count(), 2);
+
+    // Disabled, there is no critique at all
+    let client = Arc::new(MockLlmClient::new().with_response("It costs $42."));
+    let config = ReflectionConfig {
+        enabled: false,
+        ..Default::default()
+    };
+    let output = agent(client.clone(), config)
+        .execute(&AgentInput::from_text("How much is the X1?"))
+        .await
+        .unwrap();
+    assert!(output.metadata.reflection.is_none());
+    assert_eq!(client.call_count(), 1);
+}
@@ -0,0 +1,207 @@
+use agent_runtime::runtime::{RerunError, RerunOptions};
+use agent_runtime::*;
+use serde_json::json;
+use std::sync::Arc;
+
+fn agent_step(name: &str, client: Arc<llm::MockLlmClient>) -> Box<dyn Step> {
+    let agent = Agent::new(AgentConfig::builder(name).build()).with_client(client);
+    Box::new(AgentStep::from_agent(agent, name.to_string()))
+}
+
+/// research (agent) → outline (transform) → write (agent)
+fn pipeline(
+    research: Arc<llm::MockLlmClient>,
+    write: Arc<llm::MockLlmClient>,
+    outline_prefix: &'static str,
+) -> Workflow {
+    Workflow::builder()
+        .name("report".to_string())
+        .with_chat_history(Arc::new(TokenBudgetManager::new(24_000, 3.0)))
+        .add_step(agent_step("research", research))
+        .add_step(Box::new(TransformStep::new(
+            "outline".to_string(),
+            move |data| json!({ "outline": format!("{}{}", outline_prefix, data["response"].as_str().unwrap_or("")) }),
+        )))
+        .add_step(agent_step("write", write))
+        .initial_input(json!("Write about otters"))
+        .build()
+}
+
+#[tokio::test]
+async fn test_rerun_executes_only_later_steps() {
+    let runtime = Runtime::new().with_context_recording();
+    let research = Arc::new(llm::MockLlmClient::new().with_response("Otters hold hands"));
+    let write = Arc::new(llm::MockLlmClient::new().with_response("Draft one"));
+    let original = runtime.execute(pipeline(research, write, "- ")).await;
+    assert_eq!(original.state, WorkflowState::Completed);
+    assert_eq!(original.steps.len(), 3);
+
+    // Fixed the outline; research must not be paid for again
+    let research = Arc::new(llm::MockLlmClient::new().with_response("unused"));
+    let write = Arc::new(llm::MockLlmClient::new().with_response("Draft two"));
+    let offset = runtime.event_stream().current_offset();
+    let rerun = runtime
+        .rerun_from(
+            &original,
+            pipeline(research.clone(), write.clone(), "* "),
+            RerunOptions::from_step(1).reuse_context(true),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(rerun.state, WorkflowState::Completed);
+    assert_eq!(research.call_count(), 0);
+    assert_eq!(write.call_count(), 1);
+    assert_eq!(
+        rerun.final_output.as_ref().unwrap()["response"],
+        "Draft two"
+    );
+
+    // Linkage: the run points back, and records what was replayed
+    assert_eq!(rerun.rerun_of.as_deref(), Some(original.run_id.as_str()));
+    let replayed: Vec<_> = rerun
+        .steps
+        .iter()
+        .map(|s| (s.step_name.as_str(), s.replayed))
+        .collect();
+    assert_eq!(
+        replayed,
+        vec![("research", true), ("outline", false), ("write", false)]
+    );
+    assert_eq!(rerun.steps[0].output, original.steps[0].output);
+    assert_eq!(
+        rerun.steps[1].output.as_ref().unwrap()["outline"],
+        "* Otters hold hands"
+    );
+
+    // The restored context already held research's turn
+    let history = &write.get_calls()[0].messages;
+    assert!(history.iter().any(|m| m.content == "Otters hold hands"));
+
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let events = runtime.events_from_offset(offset);
+    let started = events
+        .iter()
+        .find(|e| e.scope == EventScope::Workflow && e.event_type == EventType::Started)
+        .unwrap();
+    assert_eq!(started.data["rerun_of"], original.run_id.as_str());
+    assert_ne!(rerun.run_id, original.run_id);
+    assert_eq!(started.data["replayed_steps"], 1);
+    let executed_steps: Vec<_> = events
+        .iter()
+        .filter(|e| e.scope == EventScope::WorkflowStep && e.event_type == EventType::Started)
+        .map(|e| e.component_id.clone())
+        .collect();
+    assert_eq!(executed_steps, vec!["report:step:1", "report:step:2"]);
+}
+
+/// score (transform) → route (conditional) → done (transform)
+fn triage() -> Workflow {
+    Workflow::builder()
+        .name("triage".to_string())
+        .add_step(Box::new(TransformStep::new(
+            "score".to_string(),
+            |data| json!({ "ticket": data, "score": 3 }),
+        )))
+        .add_step(Box::new(ConditionalStep::new(
+            "route".to_string(),
+            |data| data["score"].as_i64().unwrap_or(0) > 5,
+            Box::new(TransformStep::new(
+                "escalate".to_string(),
+                |data| json!({ "queue": "oncall", "ticket": data["ticket"] }),
+            )),
+            Box::new(TransformStep::new(
+                "archive".to_string(),
+                |data| json!({ "queue": "backlog", "ticket": data["ticket"] }),
+            )),
+        )))
+        .add_step(Box::new(TransformStep::new(
+            "done".to_string(),
+            |data| json!({ "routed_to": data["queue"] }),
+        )))
+        .initial_input(json!("printer on fire"))
+        .build()
+}
+
+#[tokio::test]
+async fn test_output_override_changes_routing() {
+    let runtime = Runtime::new();
+    let original = runtime.execute(triage()).await;
+    assert_eq!(
+        original.final_output,
+        Some(json!({ "routed_to": "backlog" }))
+    );
+
+    // What if scoring had rated it urgent?
+    let rerun = runtime
+        .rerun_from(
+            &original,
+            triage(),
+            RerunOptions::from_step_named("route")
+                .with_output("score", json!({ "ticket": "printer on fire", "score": 9 })),
+        )
+        .await
+        .unwrap();
+    assert_eq!(rerun.final_output, Some(json!({ "routed_to": "oncall" })));
+    assert_eq!(rerun.steps[0].output.as_ref().unwrap()["score"], 9);
+    assert!(rerun.steps[0].replayed);
+}
+
+#[tokio::test]
+async fn test_rerun_rejects_incompatible_workflows() {
+    let runtime = Runtime::new();
+    let original = runtime.execute(triage()).await;
+
+    // The replayed transform now emits a different shape
+    let reshaped = Workflow::builder()
+        .name("triage".to_string())
+        .add_step(Box::new(TransformStep::new("score".to_string(), |_| {
+            json!(3)
+        })))
+        .add_step(Box::new(TransformStep::new("next".to_string(), |d| d)))
+        .build();
+    let err = runtime
+        .rerun_from(&original, reshaped, RerunOptions::from_step(1))
+        .await
+        .unwrap_err();
+    let RerunError::Incompatible(report) = &err else {
+        panic!("expected an incompatibility, got {err}");
+    };
+    assert_eq!(report.issues[0].step(), "score");
+    assert!(err.to_string().contains("output shape"));
+
+    // Renamed earlier step
+    let renamed = Workflow::builder()
+        .name("triage".to_string())
+        .add_step(Box::new(TransformStep::new("rate".to_string(), |d| d)))
+        .add_step(Box::new(TransformStep::new("next".to_string(), |d| d)))
+        .build();
+    let err = runtime
+        .rerun_from(&original, renamed, RerunOptions::from_step(1))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, RerunError::StepMismatch { index: 0, name, .. } if name == "rate"),
+        "{err}"
+    );
+
+    // Overrides only apply to replayed steps, and context needs a snapshot
+    let err = runtime
+        .rerun_from(
+            &original,
+            triage(),
+            RerunOptions::from_step(1).with_output("done", json!({})),
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RerunError::OverrideNotReplayed(name) if name == "done"));
+    let err = runtime
+        .rerun_from(
+            &original,
+            triage(),
+            RerunOptions::from_step(1).reuse_context(true),
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RerunError::MissingContextSnapshot(0)));
+}
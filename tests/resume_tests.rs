@@ -0,0 +1,147 @@
+use agent_runtime::runtime::{
+    CheckpointStore, FileCheckpointStore, InMemoryCheckpointStore, RerunError,
+};
+use agent_runtime::*;
+use serde_json::json;
+use std::sync::Arc;
+
+fn agent_step(name: &str, client: Arc<llm::MockLlmClient>) -> Box<dyn Step> {
+    let agent = Agent::new(AgentConfig::builder(name).build()).with_client(client);
+    Box::new(AgentStep::from_agent(agent, name.to_string()))
+}
+
+/// research → outline → write → review, all agents
+fn pipeline(clients: &[Arc<llm::MockLlmClient>; 4]) -> Workflow {
+    let mut builder = Workflow::builder()
+        .name("report".to_string())
+        .with_chat_history(Arc::new(TokenBudgetManager::new(24_000, 3.0)));
+    for (name, client) in ["research", "outline", "write", "review"]
+        .iter()
+        .zip(clients)
+    {
+        builder = builder.add_step(agent_step(name, client.clone()));
+    }
+    builder.initial_input(json!("Write about otters")).build()
+}
+
+fn mock(response: &str) -> Arc<llm::MockLlmClient> {
+    Arc::new(llm::MockLlmClient::new().with_response(response))
+}
+
+#[tokio::test]
+async fn test_resume_skips_checkpointed_steps() {
+    let dir = std::env::temp_dir().join(format!("resume-{}", uuid::Uuid::new_v4()));
+    let store = FileCheckpointStore::new(&dir);
+    let runtime = Runtime::new();
+    let research = mock("Otters hold hands");
+    let outline = mock("1. Hands");
+    let broken = Arc::new(
+        llm::MockLlmClient::new()
+            .with_response("never")
+            .error_on_call(0),
+    );
+    let clients = [research.clone(), outline.clone(), broken, mock("unused")];
+
+    let failed = runtime.execute_resumable(pipeline(&clients), &store).await;
+    assert_eq!(failed.state, WorkflowState::Failed);
+    let checkpoint = store.load(&failed.run_id).await.unwrap().unwrap();
+    assert_eq!(checkpoint.next_step, 2);
+    assert_eq!(checkpoint.steps.len(), 2);
+    assert_eq!(checkpoint.data["response"], "1. Hands");
+
+    // Fix step 3 and pick the run up where it stopped
+    let write = mock("Draft");
+    let review = mock("Looks good");
+    let clients = [research.clone(), outline.clone(), write.clone(), review];
+    let offset = runtime.event_stream().current_offset();
+    let resumed = runtime
+        .resume_resumable(pipeline(&clients), checkpoint, &store)
+        .await
+        .unwrap();
+
+    assert_eq!(resumed.state, WorkflowState::Completed);
+    assert_eq!(resumed.workflow_id, failed.workflow_id);
+    assert_eq!(resumed.run_id, failed.run_id);
+    assert_eq!(research.call_count(), 1);
+    assert_eq!(outline.call_count(), 1);
+    assert_eq!(write.call_count(), 1);
+    assert_eq!(resumed.steps.len(), 4);
+    assert_eq!(resumed.steps[1].output, failed.steps[1].output);
+    assert_eq!(
+        resumed.final_output.as_ref().unwrap()["response"],
+        "Looks good"
+    );
+    assert!(store.load(&failed.run_id).await.unwrap().is_none());
+
+    // The restored context still held the earlier turns
+    let history = &write.get_calls()[0].messages;
+    assert!(history.iter().any(|m| m.content == "Otters hold hands"));
+
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let started = runtime
+        .events_from_offset(offset)
+        .into_iter()
+        .find(|e| e.scope == EventScope::Workflow && e.event_type == EventType::Started)
+        .unwrap();
+    assert_eq!(started.data["resumed"], true);
+    assert_eq!(started.data["resumed_from_step"], 2);
+
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test]
+async fn test_checkpoints_follow_the_run() {
+    let store = InMemoryCheckpointStore::new();
+    let runtime = Runtime::new();
+    let clients = [mock("a"), mock("b"), mock("c"), mock("d")];
+
+    let run = runtime.execute_resumable(pipeline(&clients), &store).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert!(store.is_empty());
+}
+
+#[tokio::test]
+async fn test_runs_of_one_workflow_keep_their_own_checkpoints() {
+    let store = InMemoryCheckpointStore::new();
+    let runtime = Runtime::new();
+    let broken = || Arc::new(llm::MockLlmClient::new().error_on_call(0));
+    let early = [mock("a"), broken(), mock("c"), mock("d")];
+    let late = [mock("a"), mock("b"), broken(), mock("d")];
+
+    let (early, late) = tokio::join!(
+        runtime.execute_resumable(pipeline(&early), &store),
+        runtime.execute_resumable(pipeline(&late), &store)
+    );
+
+    assert_eq!(early.workflow_id, late.workflow_id);
+    assert_eq!(store.len(), 2);
+    let checkpoint = store.load(&early.run_id).await.unwrap().unwrap();
+    assert_eq!(checkpoint.next_step, 1);
+    let checkpoint = store.load(&late.run_id).await.unwrap().unwrap();
+    assert_eq!(checkpoint.next_step, 2);
+}
+
+#[tokio::test]
+async fn test_resume_rejects_a_changed_workflow() {
+    let store = InMemoryCheckpointStore::new();
+    let runtime = Runtime::new();
+    let broken = Arc::new(llm::MockLlmClient::new().error_on_call(0));
+    let clients = [mock("a"), mock("b"), broken, mock("d")];
+    let failed = runtime.execute_resumable(pipeline(&clients), &store).await;
+    assert_eq!(store.len(), 1);
+    let checkpoint = store.load(&failed.run_id).await.unwrap().unwrap();
+
+    let renamed = Workflow::builder()
+        .name("report".to_string())
+        .add_step(agent_step("research", mock("a")))
+        .add_step(agent_step("summarize", mock("b")))
+        .add_step(agent_step("write", mock("c")))
+        .initial_input(json!("Write about otters"))
+        .build();
+    let err = runtime.resume(renamed, checkpoint).await.unwrap_err();
+    assert!(
+        matches!(&err, RerunError::StepMismatch { index: 1, name, .. } if name == "summarize"),
+        "{:?}",
+        err
+    );
+}
@@ -0,0 +1,208 @@
+/// Tests for the JSON and HTML reports of a finished run
+use agent_runtime::workflow::{WorkflowRun, REPORT_VERSION};
+use agent_runtime::*;
+use serde_json::json;
+use std::sync::Arc;
+
+/// fetch (transform) → summarize (agent) → publish (transform)
+fn pipeline(client: Arc<llm::MockLlmClient>) -> Workflow {
+    let agent = Agent::new(AgentConfig::builder("summarize").build()).with_client(client);
+    Workflow::builder()
+        .name("digest".to_string())
+        .add_step(Box::new(TransformStep::new("fetch".to_string(), |data| {
+            json!({ "article": format!("<b>{}</b>", data.as_str().unwrap_or("")), "padding": "x".repeat(2_000) })
+        })))
+        .add_step(Box::new(AgentStep::from_agent(
+            agent,
+            "summarize".to_string(),
+        )))
+        .add_step(Box::new(TransformStep::new("publish".to_string(), |data| {
+            json!({ "published": data["response"] })
+        })))
+        .initial_input(json!("Otters hold hands"))
+        .build()
+}
+
+async fn finished_run() -> WorkflowRun {
+    let client = Arc::new(llm::MockLlmClient::new().with_response("They sleep holding paws"));
+    let run = Runtime::new().execute(pipeline(client)).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+    run
+}
+
+#[tokio::test]
+async fn test_json_report_round_trips_into_a_run() {
+    let run = finished_run().await;
+    let report = run.to_json_report_with(&ReportOptions::new().untruncated());
+
+    assert_eq!(report["report_version"], REPORT_VERSION);
+    assert!(report["generated_at"].is_string());
+    assert_eq!(
+        report["total_execution_time_ms"],
+        run.steps
+            .iter()
+            .filter_map(|s| s.execution_time_ms)
+            .sum::<u64>()
+    );
+
+    let text = serde_json::to_string(&report).unwrap();
+    let loaded: WorkflowRun = serde_json::from_str(&text).unwrap();
+    assert_eq!(loaded.workflow_id, run.workflow_id);
+    assert_eq!(loaded.state, WorkflowState::Completed);
+    assert_eq!(loaded.steps.len(), 3);
+    for (loaded, original) in loaded.steps.iter().zip(&run.steps) {
+        assert_eq!(loaded.step_name, original.step_name);
+        assert_eq!(loaded.input, original.input);
+        assert_eq!(loaded.output, original.output);
+        assert_eq!(loaded.execution_time_ms, original.execution_time_ms);
+    }
+    assert_eq!(loaded.final_output, run.final_output);
+    assert_eq!(loaded.usage.llm_calls, 1);
+}
+
+#[tokio::test]
+async fn test_large_payloads_are_truncated() {
+    let run = finished_run().await;
+    let report = run.to_json_report_with(&ReportOptions::new().with_max_payload_bytes(256));
+
+    // fetch's 2 KB output, and the agent step's input that carries it
+    let output = report["steps"][0]["output"].as_str().unwrap();
+    assert!(output.len() < 512);
+    assert!(output.contains("[truncated;"));
+    assert!(report["steps"][1]["input"].is_string());
+    // Small payloads are left alone
+    assert_eq!(
+        report["steps"][2]["output"],
+        run.steps[2].output.clone().unwrap()
+    );
+
+    let loaded: WorkflowRun = serde_json::from_value(report).unwrap();
+    assert_eq!(loaded.steps.len(), 3);
+
+    let html = run.to_html_report_with(&ReportOptions::new().with_max_payload_bytes(256));
+    assert!(html.contains("[truncated;"));
+    assert!(!html.contains(&"x".repeat(1_000)));
+}
+
+#[tokio::test]
+async fn test_html_report_shows_the_diagram_and_steps() {
+    let run = finished_run().await;
+    let html = run.to_html_report();
+
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<script src=\"https://cdn.jsdelivr.net/npm/mermaid"));
+    assert!(html.contains("<pre class=\"mermaid\">"));
+    assert!(html.contains("flowchart"));
+    for name in ["fetch", "summarize", "publish"] {
+        assert!(html.contains(&format!("<span class=\"name\">{}</span>", name)));
+    }
+    assert_eq!(html.matches("<details class=\"step\">").count(), 3);
+    assert!(html.contains("They sleep holding paws"));
+
+    // Payloads are escaped, not rendered
+    assert!(html.contains("&lt;b&gt;Otters hold hands&lt;/b&gt;"));
+    assert!(!html.contains("<b>Otters"));
+}
+
+#[tokio::test]
+async fn test_html_report_highlights_the_failed_step() {
+    let client = Arc::new(llm::MockLlmClient::new().error_on_call(0));
+    let run = Runtime::new().execute(pipeline(client)).await;
+    assert_eq!(run.state, WorkflowState::Failed);
+
+    let html = run.to_html_report();
+    assert!(html.contains("<span class=\"state bad\">failed</span>"));
+    assert!(html.contains("<div class=\"failure\"><strong>Step 1 (summarize) failed:</strong>"));
+    assert_eq!(html.matches("<details class=\"step\">").count(), 1);
+    assert!(html.contains(
+        "<details class=\"step failed\" open>\n<summary><span class=\"index\">1</span> \
+         <span class=\"name\">summarize</span>"
+    ));
+}
+
+#[tokio::test]
+async fn test_failed_step_is_recorded_with_its_error() {
+    let client = Arc::new(llm::MockLlmClient::new().error_on_call(0));
+    let run = Runtime::new().execute(pipeline(client)).await;
+    assert_eq!(run.state, WorkflowState::Failed);
+
+    assert_eq!(run.steps.len(), 2);
+    let (fetch, summarize) = (&run.steps[0], &run.steps[1]);
+    assert_eq!(fetch.status, StepStatus::Succeeded);
+    assert_eq!(summarize.status, StepStatus::Failed);
+    assert!(summarize.output.is_none());
+    let error = summarize.error.as_ref().unwrap().to_string();
+    assert_eq!(error, run.failure.as_ref().unwrap().error.to_string());
+    assert!(summarize.started_at.unwrap() <= summarize.finished_at.unwrap());
+    assert!(fetch.finished_at.unwrap() <= summarize.started_at.unwrap());
+
+    let diagram = run.to_mermaid_with_results();
+    let node = |id: &str| {
+        diagram
+            .lines()
+            .find(|line| line.trim_start().starts_with(id))
+            .unwrap()
+            .to_string()
+    };
+    assert!(node("Step0").ends_with(":::successStyle"));
+    assert!(node("Step1").ends_with(":::failureStyle"));
+    // The label ends with the error, cut to 80 characters
+    let excerpt: String = error.chars().take(80).collect();
+    assert!(node("Step1").contains(&format!("<br/>{}…\"]", excerpt)));
+
+    let report = run.to_json_report();
+    assert_eq!(report["steps"][1]["status"], "failed");
+    assert_eq!(
+        serde_json::to_string(&report["steps"][1]["error"]).unwrap(),
+        serde_json::to_string(&summarize.error).unwrap()
+    );
+}
+
+#[test]
+fn test_records_without_status_read_as_succeeded() {
+    let record: workflow::WorkflowStepRecord = serde_json::from_value(json!({
+        "step_index": 0,
+        "step_name": "fetch",
+        "step_type": "Transform",
+        "input": "Otters",
+        "output": null,
+        "execution_time_ms": 3,
+    }))
+    .unwrap();
+    assert_eq!(record.status, StepStatus::Succeeded);
+    assert!(record.error.is_none());
+    assert!(record.started_at.is_none());
+
+    let written = serde_json::to_value(&record).unwrap();
+    assert!(written.get("error").is_none());
+    assert!(written.get("started_at").is_none());
+}
+
+#[tokio::test]
+async fn test_execute_and_report_writes_both_files() {
+    let out_dir = std::env::temp_dir().join(format!("run-report-{}", uuid::Uuid::new_v4()));
+    let client = Arc::new(llm::MockLlmClient::new().with_response("They sleep holding paws"));
+
+    let (run, files) = Runtime::new()
+        .execute_and_report(pipeline(client), &out_dir)
+        .await;
+    let files = files.unwrap();
+    assert_eq!(run.state, WorkflowState::Completed);
+
+    for path in [&files.json, &files.html] {
+        assert_eq!(path.parent().unwrap(), out_dir);
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with(&format!("{}-", run.workflow_id)));
+    }
+    assert_eq!(files.json.extension().unwrap(), "json");
+    assert_eq!(files.html.extension().unwrap(), "html");
+
+    let loaded: WorkflowRun =
+        serde_json::from_str(&std::fs::read_to_string(&files.json).unwrap()).unwrap();
+    assert_eq!(loaded.workflow_id, run.workflow_id);
+    assert!(std::fs::read_to_string(&files.html)
+        .unwrap()
+        .contains("<span class=\"name\">summarize</span>"));
+
+    std::fs::remove_dir_all(&out_dir).unwrap();
+}
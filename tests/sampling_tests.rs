@@ -0,0 +1,173 @@
+/// Tests for agents' sampling parameters, as sent to an in-process server
+use agent_runtime::llm::{LlamaClient, OpenAIClient};
+use agent_runtime::*;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::post;
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+type Bodies = Arc<Mutex<Vec<Value>>>;
+
+/// Records each chat request body and streams back "ok"
+async fn start_server() -> (String, Bodies) {
+    let bodies = Bodies::default();
+    let recorded = bodies.clone();
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(move |Json(body): Json<Value>| async move {
+            recorded.lock().unwrap().push(body);
+            let chunk = json!({
+                "model": "test-model",
+                "choices": [{ "index": 0, "delta": { "content": "ok" }, "finish_reason": "stop" }],
+            });
+            (
+                [(header::CONTENT_TYPE, "text/event-stream")],
+                format!("data: {}\n\ndata: [DONE]\n\n", chunk),
+            )
+                .into_response()
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}", addr), bodies)
+}
+
+fn sampling() -> SamplingParams {
+    SamplingParams::new()
+        .temperature(0.25)
+        .top_p(0.75)
+        .max_tokens(300)
+        .seed(42)
+        .stop(["\nObservation:", "END"])
+        .frequency_penalty(0.5)
+        .presence_penalty(-0.5)
+}
+
+#[tokio::test]
+async fn test_configured_params_reach_the_request() {
+    let (base_url, bodies) = start_server().await;
+    let stream = EventStream::new();
+    let agent = Agent::new(
+        AgentConfig::builder("evaluator")
+            .system_prompt("Grade it.")
+            .sampling(sampling())
+            .build(),
+    )
+    .with_client(Arc::new(
+        OpenAIClient::with_model("sk-test", "gpt-4o-mini")
+            .with_base_url(format!("{}/v1", base_url)),
+    ));
+    let output = agent
+        .execute_with_events(AgentInput::from_text("2 + 2 = 4"), Some(&stream))
+        .await
+        .unwrap();
+    assert_eq!(output.data["response"], "ok");
+
+    let body = bodies.lock().unwrap()[0].clone();
+    assert_eq!(body["temperature"], 0.25);
+    assert_eq!(body["top_p"], 0.75);
+    assert_eq!(body["max_tokens"], 300);
+    assert_eq!(body["seed"], 42);
+    assert_eq!(body["stop"], json!(["\nObservation:", "END"]));
+    assert_eq!(body["frequency_penalty"], 0.5);
+    assert_eq!(body["presence_penalty"], -0.5);
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let started = stream
+        .all()
+        .into_iter()
+        .find(|e| e.scope == EventScope::LlmRequest && e.event_type == EventType::Started)
+        .unwrap();
+    assert_eq!(
+        started.data["sampling"],
+        json!({
+            "temperature": 0.25,
+            "top_p": 0.75,
+            "max_tokens": 300,
+            "seed": 42,
+            "stop": ["\nObservation:", "END"],
+            "frequency_penalty": 0.5,
+            "presence_penalty": -0.5,
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_config_defaults_fill_unset_params() {
+    let (base_url, bodies) = start_server().await;
+    let llm = LlmConfig {
+        default_temperature: 0.1,
+        default_max_tokens: Some(512),
+        ..Default::default()
+    };
+    let client = Arc::new(LlamaClient::new(&base_url, "local"));
+    let run = |config: AgentConfig| {
+        let agent = Agent::new(config).with_client(client.clone());
+        async move { agent.execute(&AgentInput::from_text("hi")).await.unwrap() }
+    };
+
+    // The builder's seed is kept; the rest comes from the config
+    run(AgentConfig::builder("a")
+        .sampling(SamplingParams::new().seed(7).stop(["END"]))
+        .llm_defaults(&llm)
+        .build())
+    .await;
+    // The builder's own values win over the config's
+    run(AgentConfig::builder("b")
+        .llm_defaults(&llm)
+        .sampling(SamplingParams::new().temperature(0.9))
+        .build())
+    .await;
+    // Without either, the agent's own defaults
+    run(AgentConfig::builder("c").build()).await;
+
+    let bodies = bodies.lock().unwrap();
+    assert_eq!(bodies[0]["temperature"].as_f64().unwrap() as f32, 0.1);
+    assert_eq!(bodies[0]["max_tokens"], 512);
+    assert_eq!(bodies[0]["seed"], 7);
+    assert_eq!(bodies[0]["stop"], json!(["END"]));
+    assert!(bodies[0].get("top_p").is_none());
+    assert!(bodies[0].get("presence_penalty").is_none());
+    assert_eq!(bodies[0]["stream"], true);
+
+    assert_eq!(bodies[1]["temperature"].as_f64().unwrap() as f32, 0.9);
+    assert_eq!(bodies[1]["max_tokens"], 512);
+    assert!(bodies[1].get("seed").is_none());
+
+    assert_eq!(
+        bodies[2]["temperature"].as_f64().unwrap() as f32,
+        agent::DEFAULT_TEMPERATURE
+    );
+    assert_eq!(bodies[2]["max_tokens"], agent::DEFAULT_MAX_TOKENS);
+}
+
+#[tokio::test]
+async fn test_reasoning_models_skip_params_they_reject() {
+    let (base_url, bodies) = start_server().await;
+    Agent::new(AgentConfig::builder("thinker").sampling(sampling()).build())
+        .with_client(Arc::new(
+            OpenAIClient::with_model("sk-test", "o3-mini")
+                .with_base_url(format!("{}/v1", base_url)),
+        ))
+        .execute(&AgentInput::from_text("hi"))
+        .await
+        .unwrap();
+
+    let body = bodies.lock().unwrap()[0].clone();
+    assert_eq!(body["seed"], 42);
+    assert_eq!(body["max_completion_tokens"], 300);
+    for rejected in [
+        "temperature",
+        "top_p",
+        "max_tokens",
+        "stop",
+        "frequency_penalty",
+        "presence_penalty",
+    ] {
+        assert!(body.get(rejected).is_none(), "{} was sent", rejected);
+    }
+}
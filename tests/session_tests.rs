@@ -0,0 +1,186 @@
+/// Tests for multi-turn sessions with one agent, outside a workflow
+use agent_runtime::context::{NoOpManager, PruneOutcome};
+use agent_runtime::context_strategies::TokenBudgetManager;
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::*;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn concierge(client: Arc<MockLlmClient>) -> Agent {
+    let mut registry = ToolRegistry::new();
+    registry.register(NativeTool::new(
+        "find_booking",
+        "Look up a booking by name",
+        json!({"type": "object", "properties": {"name": {"type": "string"}}}),
+        |params| async move {
+            Ok(ToolResult::success(
+                json!({ "name": params["name"], "room": 214 }),
+                1.0,
+            ))
+        },
+    ));
+    Agent::new(
+        AgentConfig::builder("concierge")
+            .system_prompt("You are a hotel concierge.")
+            .tools(Arc::new(registry))
+            .build(),
+    )
+    .with_client(client)
+}
+
+fn texts(request: &ChatRequest) -> Vec<String> {
+    request
+        .messages
+        .iter()
+        .map(|m| m.content.text().into_owned())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_later_turns_see_earlier_ones() {
+    let mock = Arc::new(
+        MockLlmClient::new()
+            .with_response("Welcome, Ada.")
+            .with_tool_call("find_booking", json!({ "name": "Ada" }))
+            .with_response("You're in room 214.")
+            .with_response("You told me your name is Ada."),
+    );
+    let mut session = Session::new(concierge(mock.clone()), Arc::new(NoOpManager::new()));
+
+    session.send("Hi, my name is Ada.").await.unwrap();
+    let second = session.send("Which room am I in?").await.unwrap();
+    assert_eq!(second.data["response"], "You're in room 214.");
+    assert_eq!(second.metadata.tool_calls_count, 1);
+    let third = session.send("What did I say my name was?").await.unwrap();
+    assert_eq!(third.data["response"], "You told me your name is Ada.");
+
+    // The last request carries the whole conversation, tool exchange included
+    let last = mock.last_call().unwrap();
+    let sent = texts(&last);
+    assert_eq!(sent[0], "You are a hotel concierge.");
+    assert_eq!(sent[1], "Hi, my name is Ada.");
+    assert_eq!(sent[2], "Welcome, Ada.");
+    assert!(last.messages.iter().any(|m| m.role == Role::Tool));
+    assert_eq!(sent.last().unwrap(), "What did I say my name was?");
+
+    // History holds the turns, without the system prompt
+    let history = session.history();
+    assert_eq!(history.len(), 8);
+    assert!(history.iter().all(|m| m.role != Role::System));
+    assert_eq!(history[7].content.text(), "You told me your name is Ada.");
+    assert_eq!(session.turns(), 3);
+
+    session.reset();
+    assert!(session.history().is_empty());
+    assert_eq!(session.turns(), 0);
+}
+
+#[tokio::test]
+async fn test_history_is_pruned_to_the_budget() {
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec![
+        "The pool opens at seven and closes at ten, every day of the week.",
+        "Breakfast is served from half past six until eleven in the main hall.",
+        "Checkout is at noon; ask the front desk if you need a later time.",
+    ]));
+    let stream = EventStream::new();
+    let mut session = Session::new(
+        concierge(mock.clone()),
+        Arc::new(TokenBudgetManager::new(80, 1.0)),
+    )
+    .with_event_stream(stream.clone());
+
+    session.send("When is the pool open?").await.unwrap();
+    session.send("And breakfast?").await.unwrap();
+    session.send("When is checkout?").await.unwrap();
+
+    // The oldest turns were dropped before the last request
+    let sent = texts(&mock.last_call().unwrap());
+    assert!(!sent.iter().any(|m| m.contains("pool")));
+    assert_eq!(sent.last().unwrap(), "When is checkout?");
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let pruning: Vec<Event> = stream
+        .all()
+        .into_iter()
+        .filter(|e| e.component_id == "system:context_pruning")
+        .collect();
+    assert!(!pruning.is_empty());
+    assert_eq!(pruning[0].workflow_id, session.id());
+    let outcome: PruneOutcome = serde_json::from_value(pruning[0].data.clone()).unwrap();
+    assert_eq!(outcome.strategy, "TokenBudget");
+    assert!(outcome.messages_after < outcome.messages_before);
+}
+
+#[tokio::test]
+async fn test_restored_session_continues_the_conversation() {
+    let first = Arc::new(MockLlmClient::with_responses_vec(vec![
+        "Welcome, Ada.",
+        "Noted: late checkout.",
+    ]));
+    let mut session = Session::new(concierge(first), Arc::new(NoOpManager::new()));
+    session.send("Hi, my name is Ada.").await.unwrap();
+    session.send("I'd like a late checkout.").await.unwrap();
+
+    // Stored between requests, as a web handler would
+    let stored = serde_json::to_string(&session.checkpoint()).unwrap();
+    let snapshot: SessionSnapshot = serde_json::from_str(&stored).unwrap();
+    assert_eq!(snapshot.agent, "concierge");
+    assert_eq!(snapshot.history.len(), 4);
+
+    let second = Arc::new(MockLlmClient::with_responses_vec(vec![
+        "Ada, your late checkout is confirmed.",
+    ]));
+    let mut restored = Session::restore(concierge(second.clone()), snapshot)
+        .with_context_manager(Arc::new(NoOpManager::new()));
+    assert_eq!(restored.id(), session.id());
+    let output = restored.send("Is it confirmed?").await.unwrap();
+    assert_eq!(
+        output.data["response"],
+        "Ada, your late checkout is confirmed."
+    );
+
+    let sent = texts(&second.last_call().unwrap());
+    assert_eq!(
+        sent[1..],
+        [
+            "Hi, my name is Ada.",
+            "Welcome, Ada.",
+            "I'd like a late checkout.",
+            "Noted: late checkout.",
+            "Is it confirmed?",
+        ]
+    );
+    assert_eq!(restored.turns(), 3);
+}
+
+#[tokio::test]
+async fn test_failed_turn_leaves_history_alone() {
+    let mock = Arc::new(
+        MockLlmClient::with_responses_vec(vec!["Welcome, Ada.", "Room 214."]).error_on_call(1),
+    );
+    let mut session = Session::new(concierge(mock), Arc::new(NoOpManager::new()));
+    session.send("Hi, my name is Ada.").await.unwrap();
+
+    assert!(session.send("Which room am I in?").await.is_err());
+    assert_eq!(session.history().len(), 2);
+    assert_eq!(session.turns(), 1);
+}
+
+#[tokio::test]
+async fn test_session_can_live_behind_a_mutex() {
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec!["Welcome, Ada."]));
+    let session = Arc::new(tokio::sync::Mutex::new(Session::new(
+        concierge(mock),
+        Arc::new(NoOpManager::new()),
+    )));
+
+    // As in a web handler: the turn runs on another task
+    let handler = session.clone();
+    let output = tokio::spawn(async move { handler.lock().await.send("Hi, I'm Ada.").await })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(output.data["response"], "Welcome, Ada.");
+    assert_eq!(session.lock().await.history().len(), 2);
+}
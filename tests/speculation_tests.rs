@@ -0,0 +1,269 @@
+/// Tests for speculative tool prefetching
+use agent_runtime::llm::{GenericChatClient, LlmResult, MockLlmClient, Role};
+use agent_runtime::*;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+const THINK_MS: u64 = 500;
+const READ_MS: u64 = 300;
+
+/// The mock client, taking `THINK_MS` per call
+struct SlowClient {
+    inner: MockLlmClient,
+}
+
+#[async_trait]
+impl GenericChatClient for SlowClient {
+    async fn chat(&self, request: ChatRequest) -> LlmResult<ChatResponse> {
+        self.inner.chat(request).await
+    }
+
+    async fn chat_stream(
+        &self,
+        request: ChatRequest,
+        _tx: mpsc::Sender<String>,
+    ) -> LlmResult<ChatResponse> {
+        tokio::time::sleep(Duration::from_millis(THINK_MS)).await;
+        self.inner.chat(request).await
+    }
+}
+
+/// `read_file` (read-only) and `delete_file` (side-effecting), both logging
+/// the paths they were called with
+fn tools(calls: Arc<Mutex<Vec<String>>>) -> Arc<ToolRegistry> {
+    let mut registry = ToolRegistry::new();
+    let log = calls.clone();
+    registry.register(
+        NativeTool::new(
+            "read_file",
+            "Read a file",
+            json!({"type": "object", "properties": {"path": {"type": "string"}}}),
+            move |params| {
+                let log = log.clone();
+                async move {
+                    let path = params["path"].as_str().unwrap().to_string();
+                    log.lock().unwrap().push(format!("read {}", path));
+                    tokio::time::sleep(Duration::from_millis(READ_MS)).await;
+                    Ok(ToolResult::success(
+                        json!({ "contents": format!("contents of {}", path) }),
+                        READ_MS as f64,
+                    ))
+                }
+            },
+        )
+        .read_only(),
+    );
+    registry.register(NativeTool::new(
+        "delete_file",
+        "Delete a file",
+        json!({"type": "object", "properties": {"path": {"type": "string"}}}),
+        move |params| {
+            let log = calls.clone();
+            async move {
+                log.lock()
+                    .unwrap()
+                    .push(format!("delete {}", params["path"].as_str().unwrap()));
+                Ok(ToolResult::success(json!("deleted"), 0.0))
+            }
+        },
+    ));
+    Arc::new(registry)
+}
+
+fn agent(
+    mock: MockLlmClient,
+    calls: Arc<Mutex<Vec<String>>>,
+    prefetcher: Option<SpeculativePrefetcher>,
+) -> (Agent, Arc<SlowClient>) {
+    let client = Arc::new(SlowClient { inner: mock });
+    let mut config = AgentConfig::builder("reader").tools(tools(calls));
+    if let Some(prefetcher) = prefetcher {
+        config = config.speculative_prefetch(prefetcher);
+    }
+    (
+        Agent::new(config.build()).with_client(client.clone()),
+        client,
+    )
+}
+
+async fn speculation_events(stream: &EventStream) -> Vec<Value> {
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    stream
+        .all()
+        .into_iter()
+        .filter(|e| e.component_id == "system:speculation")
+        .map(|e| e.data)
+        .collect()
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_hit_overlaps_tool_with_first_llm_call() {
+    let input = AgentInput::from_value(json!("What does src/main.rs do?"));
+    let script = || {
+        MockLlmClient::with_tool_then_text(
+            "read_file",
+            json!({ "path": "src/main.rs" }),
+            "It prints hello.",
+        )
+    };
+
+    // Serially: think, read, think
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let (serial, _) = agent(script(), calls.clone(), None);
+    let started = Instant::now();
+    serial.execute(&input).await.unwrap();
+    assert_eq!(
+        started.elapsed(),
+        Duration::from_millis(2 * THINK_MS + READ_MS)
+    );
+
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let prefetcher =
+        SpeculativePrefetcher::new().rule(PrefetchRule::file_path("read_file", "path"));
+    let (speculative, client) = agent(script(), calls.clone(), Some(prefetcher.clone()));
+    let stream = EventStream::new();
+    let started = Instant::now();
+    let output = speculative
+        .execute_with_events(input, Some(&stream))
+        .await
+        .unwrap();
+
+    // The read finished during the first call
+    assert_eq!(started.elapsed(), Duration::from_millis(2 * THINK_MS));
+    assert_eq!(*calls.lock().unwrap(), vec!["read src/main.rs"]);
+    assert_eq!(output.data["response"], "It prints hello.");
+    let served = client.inner.last_call().unwrap();
+    let tool_reply = served
+        .messages
+        .iter()
+        .find(|m| m.role == Role::Tool)
+        .unwrap();
+    assert!(tool_reply
+        .content
+        .text()
+        .contains("contents of src/main.rs"));
+
+    let stats = output.metadata.speculation.unwrap();
+    assert_eq!(
+        stats,
+        SpeculationStats {
+            started: 1,
+            hits: 1,
+            ..Default::default()
+        }
+    );
+    assert_eq!(prefetcher.stats(), stats);
+
+    let outcomes: Vec<Value> = speculation_events(&stream)
+        .await
+        .into_iter()
+        .map(|d| d["outcome"].clone())
+        .collect();
+    assert_eq!(outcomes, vec!["started", "hit", "summary"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_miss_discards_speculative_result() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let prefetcher =
+        SpeculativePrefetcher::new().rule(PrefetchRule::file_path("read_file", "path"));
+    let (agent, client) = agent(
+        MockLlmClient::with_tool_then_text(
+            "read_file",
+            json!({ "path": "src/lib.rs" }),
+            "They differ.",
+        ),
+        calls.clone(),
+        Some(prefetcher),
+    );
+    let stream = EventStream::new();
+    let output = agent
+        .execute_with_events(
+            AgentInput::from_value(json!("Compare src/main.rs with the library")),
+            Some(&stream),
+        )
+        .await
+        .unwrap();
+
+    // The guess ran, but the model only saw the call it asked for
+    assert_eq!(
+        *calls.lock().unwrap(),
+        vec!["read src/main.rs", "read src/lib.rs"]
+    );
+    let served = client.inner.last_call().unwrap();
+    let replies: Vec<_> = served
+        .messages
+        .iter()
+        .filter(|m| m.role == Role::Tool)
+        .map(|m| m.content.text())
+        .collect();
+    assert_eq!(replies.len(), 1);
+    assert!(replies[0].contains("contents of src/lib.rs"));
+
+    assert_eq!(
+        output.metadata.speculation.unwrap(),
+        SpeculationStats {
+            started: 1,
+            misses: 1,
+            wasted: 1,
+            ..Default::default()
+        }
+    );
+    let wasted = speculation_events(&stream)
+        .await
+        .into_iter()
+        .find(|d| d["outcome"] == "wasted")
+        .unwrap();
+    assert_eq!(wasted["reason"], "not requested");
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_side_effecting_tools_are_never_speculated() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let predictions = Arc::new(AtomicUsize::new(0));
+    let counter = predictions.clone();
+    let prefetcher = SpeculativePrefetcher::new().rule(PrefetchRule::new("cleanup", move |_| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Some(PredictedCall::new(
+            "delete_file",
+            json!({ "path": "tmp.txt" }),
+        ))
+    }));
+    let (agent, _) = agent(
+        MockLlmClient::with_tool_then_text("delete_file", json!({ "path": "tmp.txt" }), "Deleted."),
+        calls.clone(),
+        Some(prefetcher),
+    );
+    let stream = EventStream::new();
+    let output = agent
+        .execute_with_events(
+            AgentInput::from_value(json!("Clean up tmp.txt")),
+            Some(&stream),
+        )
+        .await
+        .unwrap();
+
+    // Predicted, refused, and run once when the model asked
+    assert_eq!(predictions.load(Ordering::SeqCst), 1);
+    assert_eq!(*calls.lock().unwrap(), vec!["delete tmp.txt"]);
+    assert_eq!(
+        output.metadata.speculation.unwrap(),
+        SpeculationStats {
+            refused: 1,
+            misses: 1,
+            ..Default::default()
+        }
+    );
+    let refused = speculation_events(&stream)
+        .await
+        .into_iter()
+        .find(|d| d["outcome"] == "refused")
+        .unwrap();
+    assert_eq!(refused["tool"], "delete_file");
+    assert_eq!(refused["reason"], "tool has side effects");
+}
@@ -0,0 +1,186 @@
+/// Tests for input/output schema contracts between workflow steps
+use agent_runtime::error::WorkflowErrorCode;
+use agent_runtime::runtime::Runtime;
+use agent_runtime::workflow::{ContractBoundary, WorkflowBuilder};
+use agent_runtime::*;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+fn article_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "title": { "type": "string" },
+            "words": { "type": "integer" }
+        },
+        "required": ["title", "words"]
+    })
+}
+
+/// Outputs what `output` returns, declaring `article_schema` as its output
+fn extract(output: fn(Value) -> Value) -> TransformStep {
+    TransformStep::new("extract".to_string(), output).with_output_schema(article_schema())
+}
+
+fn summarize() -> TransformStep {
+    TransformStep::new("summarize".to_string(), |data| {
+        json!(format!(
+            "{} ({} words)",
+            data["title"].as_str().unwrap(),
+            data["words"]
+        ))
+    })
+    .with_input_schema(json!({
+        "type": "object",
+        "properties": { "title": { "type": "string" } },
+        "required": ["title"]
+    }))
+}
+
+fn workflow(first: TransformStep, second: TransformStep) -> WorkflowBuilder {
+    Workflow::builder()
+        .name("contracts".to_string())
+        .step(Box::new(first))
+        .step(Box::new(second))
+        .initial_input(json!({ "text": "Mars has two moons" }))
+}
+
+#[tokio::test]
+async fn test_compatible_chain_runs() {
+    let workflow = workflow(
+        extract(|_| json!({ "title": "Moons", "words": 4 })),
+        summarize(),
+    )
+    .try_build()
+    .unwrap();
+
+    let run = Runtime::new().execute(workflow).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(run.final_output, Some(json!("Moons (4 words)")));
+}
+
+#[tokio::test]
+async fn test_output_failing_its_own_schema_fails_the_producing_step() {
+    let workflow = workflow(
+        extract(|_| json!({ "title": "Moons", "words": "four" })),
+        summarize(),
+    )
+    .build();
+
+    let run = Runtime::new().execute(workflow).await;
+    assert_eq!(run.state, WorkflowState::Failed);
+    assert_eq!(run.steps.len(), 1);
+    assert_eq!(run.steps[0].status, StepStatus::Failed);
+    let failure = run.failure.unwrap();
+    assert_eq!(failure.step_index, 0);
+    assert_eq!(failure.step_name, "extract");
+    let StepError::ContractViolation(violation) = &failure.error else {
+        panic!("expected a contract violation, got {:?}", failure.error);
+    };
+    assert_eq!(violation.boundary, ContractBoundary::Output);
+    assert_eq!(violation.violations[0].pointer, "/words");
+    assert_eq!(
+        failure.error.to_string(),
+        "Contract violation: output of step 'extract' does not match its output schema: \
+         /words expected integer, found string"
+    );
+}
+
+#[tokio::test]
+async fn test_output_failing_next_input_schema_fails_before_next_step() {
+    let renamed = TransformStep::new(
+        "extract".to_string(),
+        |_| json!({ "headline": "Moons", "words": 4 }),
+    );
+    let workflow = workflow(renamed, summarize()).try_build().unwrap();
+
+    let runtime = Runtime::new();
+    let run = runtime.execute(workflow).await;
+    assert_eq!(run.state, WorkflowState::Failed);
+    let failure = run.failure.unwrap();
+    assert_eq!(failure.step_name, "extract");
+    let StepError::ContractViolation(violation) = &failure.error else {
+        panic!("expected a contract violation, got {:?}", failure.error);
+    };
+    assert_eq!(
+        violation.boundary,
+        ContractBoundary::NextInput {
+            step: "summarize".to_string()
+        }
+    );
+    assert_eq!(
+        failure.error.to_string(),
+        "Contract violation: output of step 'extract' does not match the input schema of \
+         step 'summarize': /title required property is missing"
+    );
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let failed = runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .find(|e| e.scope == EventScope::Workflow && e.event_type == EventType::Failed)
+        .unwrap();
+    assert_eq!(failed.data["contract"]["step"], "extract");
+    assert_eq!(failed.data["contract"]["boundary"]["kind"], "next_input");
+}
+
+#[tokio::test]
+async fn test_skipping_contract_checks_lets_bad_output_through() {
+    let workflow = workflow(
+        extract(|_| json!({ "title": "Moons", "words": "four" })),
+        TransformStep::new("pass".to_string(), |data| data),
+    )
+    .skip_contract_checks()
+    .build();
+
+    let run = Runtime::new().execute(workflow).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(
+        run.final_output,
+        Some(json!({ "title": "Moons", "words": "four" }))
+    );
+}
+
+#[test]
+fn test_try_build_rejects_incompatible_adjacent_schemas() {
+    let counter = TransformStep::new("count".to_string(), |data| data).with_input_schema(json!({
+        "type": "object",
+        "properties": {
+            "title": { "type": "number" },
+            "summary": { "type": "string" }
+        },
+        "required": ["summary"]
+    }));
+    let error = workflow(extract(|data| data), counter)
+        .try_build()
+        .err()
+        .unwrap();
+    assert_eq!(error.code, WorkflowErrorCode::IncompatibleStepContracts);
+    assert_eq!(error.step_index, Some(0));
+    assert!(
+        error.message.contains("/summary required by the next step"),
+        "{}",
+        error.message
+    );
+    assert!(
+        error
+            .message
+            .contains("/title step outputs string, next step expects number"),
+        "{}",
+        error.message
+    );
+
+    // Open on either side: assumed to fit
+    let open = TransformStep::new("open".to_string(), |data| data)
+        .with_input_schema(json!({ "type": "object" }));
+    assert!(workflow(extract(|data| data), open).try_build().is_ok());
+    let integer_into_number = TransformStep::new("number".to_string(), |data| data)
+        .with_input_schema(json!({
+            "type": "object",
+            "properties": { "words": { "type": "number" } }
+        }));
+    assert!(workflow(extract(|data| data), integer_into_number)
+        .try_build()
+        .is_ok());
+}
@@ -0,0 +1,183 @@
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::retry::RetryPolicy;
+use agent_runtime::runtime::Runtime;
+use agent_runtime::*;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// An agent step whose LLM fails on the given calls
+fn flaky_agent(mock: Arc<MockLlmClient>) -> Box<dyn workflow::Step> {
+    let agent = Agent::new(AgentConfig::builder("enricher").build()).with_client(mock);
+    Box::new(AgentStep::from_agent(agent, "enrich".to_string()))
+}
+
+fn retry() -> RetryPolicy {
+    RetryPolicy::new(2, Duration::from_millis(5))
+}
+
+/// prepare -> enrich (under `policy`) -> publish, which echoes its input
+fn pipeline(mock: Arc<MockLlmClient>, policy: StepPolicy) -> Workflow {
+    Workflow::builder()
+        .name("pipeline".to_string())
+        .step(Box::new(TransformStep::new(
+            "prepare".to_string(),
+            |_| json!({ "record": 7 }),
+        )))
+        .add_step_with_policy(flaky_agent(mock), policy)
+        .step(Box::new(TransformStep::new(
+            "publish".to_string(),
+            |v| json!({ "published": v }),
+        )))
+        .initial_input(json!("go"))
+        .build()
+}
+
+async fn step_events(runtime: &Runtime, event_type: EventType) -> Vec<Value> {
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .filter(|e| e.scope == EventScope::WorkflowStep && e.event_type == event_type)
+        .filter(|e| e.data["step_name"] == "enrich")
+        .map(|e| e.data)
+        .collect()
+}
+
+#[tokio::test]
+async fn test_retry_recovers_failing_step() {
+    let mock = Arc::new(
+        MockLlmClient::new()
+            .with_response("enriched")
+            .error_on_call(0),
+    );
+    let runtime = Runtime::new();
+    let run = runtime
+        .execute(pipeline(
+            mock.clone(),
+            StepPolicy::new().with_retry(retry()),
+        ))
+        .await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(mock.call_count(), 2);
+    assert_eq!(run.steps[1].status, StepStatus::Succeeded);
+    assert!(run.steps[1].error.is_none());
+
+    let retries = step_events(&runtime, EventType::Progress).await;
+    assert_eq!(retries.len(), 1);
+    assert_eq!(retries[0]["attempt"], 2);
+    assert!(retries[0]["error"].as_str().unwrap().contains("network"));
+    let completed = step_events(&runtime, EventType::Completed).await;
+    assert_eq!(completed[0]["status"], "succeeded");
+    assert_eq!(completed[0]["attempts"], 2);
+}
+
+#[tokio::test]
+async fn test_fail_aborts_once_retries_run_out() {
+    let mock = Arc::new(MockLlmClient::new().error_on_calls([0, 1, 2]));
+    let runtime = Runtime::new();
+    let run = runtime
+        .execute(pipeline(
+            mock.clone(),
+            StepPolicy::new()
+                .with_retry(retry())
+                .on_error(OnError::Fail),
+        ))
+        .await;
+
+    assert_eq!(run.state, WorkflowState::Failed);
+    assert_eq!(mock.call_count(), 3);
+    assert_eq!(run.steps.len(), 2);
+    assert_eq!(run.steps[1].status, StepStatus::Failed);
+    assert_eq!(run.failure.unwrap().step_name, "enrich");
+    assert_eq!(step_events(&runtime, EventType::Progress).await.len(), 2);
+    assert_eq!(step_events(&runtime, EventType::Failed).await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_skip_passes_previous_output_forward() {
+    let mock = Arc::new(MockLlmClient::new().error_on_calls([0, 1, 2]));
+    let runtime = Runtime::new();
+    let run = runtime
+        .execute(pipeline(
+            mock,
+            StepPolicy::new()
+                .with_retry(retry())
+                .on_error(OnError::Skip),
+        ))
+        .await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(
+        run.final_output,
+        Some(json!({ "published": { "record": 7 } }))
+    );
+
+    let skipped = &run.steps[1];
+    assert_eq!(skipped.status, StepStatus::Skipped);
+    assert_eq!(skipped.output, Some(json!({ "record": 7 })));
+    assert!(skipped.error.is_some());
+    assert_eq!(run.steps[2].status, StepStatus::Succeeded);
+
+    // Consumers reading the stored run can tell the steps apart
+    let stored = serde_json::to_value(&run).unwrap();
+    assert_eq!(stored["steps"][1]["status"], "skipped");
+    assert_eq!(stored["steps"][2]["status"], "succeeded");
+
+    let completed = step_events(&runtime, EventType::Completed).await;
+    assert_eq!(completed[0]["status"], "skipped");
+    assert_eq!(completed[0]["attempts"], 3);
+}
+
+#[tokio::test]
+async fn test_fallback_value_replaces_output() {
+    let mock = Arc::new(MockLlmClient::new().error_on_call(0));
+    let runtime = Runtime::new();
+    let run = runtime
+        .execute(pipeline(
+            mock.clone(),
+            StepPolicy::new().on_error(OnError::UseFallbackValue(json!({ "response": "n/a" }))),
+        ))
+        .await;
+
+    // No retry policy: one attempt, then the fallback
+    assert_eq!(mock.call_count(), 1);
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(run.steps[1].status, StepStatus::Fallback);
+    assert_eq!(
+        run.final_output,
+        Some(json!({ "published": { "response": "n/a" } }))
+    );
+    let completed = step_events(&runtime, EventType::Completed).await;
+    assert_eq!(completed[0]["status"], "fallback");
+}
+
+#[tokio::test]
+async fn test_policy_step_applies_outside_the_runtime() {
+    let mock = Arc::new(
+        MockLlmClient::new()
+            .with_response("second time lucky")
+            .error_on_call(0),
+    );
+    let step = PolicyStep::new(flaky_agent(mock), StepPolicy::new().with_retry(retry()));
+    assert_eq!(step.name(), "enrich");
+
+    let output = step
+        .execute(workflow::StepInput {
+            data: json!("go"),
+            metadata: workflow::step::StepInputMetadata {
+                step_index: 0,
+                previous_step: None,
+                workflow_id: "standalone".to_string(),
+                run_id: "run_1".to_string(),
+                item_index: None,
+            },
+            workflow_context: None,
+        })
+        .await
+        .unwrap();
+    assert_eq!(output.data["response"], "second time lucky");
+    assert_eq!(output.metadata.policy.unwrap().attempts, 2);
+}
@@ -0,0 +1,213 @@
+/// Tests for stop sequences and the response length cap, enforced on the
+/// agent's streamed responses
+use agent_runtime::llm::types::{FunctionCall, ToolCall};
+use agent_runtime::llm::{GenericChatClient, LlmResult, MockLlmClient};
+use agent_runtime::*;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Streams each scripted reply a chunk at a time, ignoring stop sequences
+/// as some providers do
+#[derive(Default)]
+struct ChunkedStream {
+    replies: Mutex<VecDeque<(Vec<&'static str>, Option<ToolCall>)>>,
+    sent: AtomicUsize,
+}
+
+impl ChunkedStream {
+    fn reply(self, chunks: Vec<&'static str>) -> Self {
+        self.replies.lock().unwrap().push_back((chunks, None));
+        self
+    }
+
+    fn tool_call(self, chunks: Vec<&'static str>, name: &str) -> Self {
+        let call = ToolCall {
+            id: "call_1".to_string(),
+            r#type: "function".to_string(),
+            function: FunctionCall {
+                name: name.to_string(),
+                arguments: "{}".to_string(),
+            },
+        };
+        self.replies.lock().unwrap().push_back((chunks, Some(call)));
+        self
+    }
+
+    /// Chunks sent so far, across replies
+    fn sent(&self) -> usize {
+        self.sent.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait::async_trait]
+impl GenericChatClient for ChunkedStream {
+    async fn chat(&self, _request: ChatRequest) -> LlmResult<ChatResponse> {
+        unreachable!("agents stream their requests")
+    }
+
+    async fn chat_stream(
+        &self,
+        _request: ChatRequest,
+        tx: tokio::sync::mpsc::Sender<String>,
+    ) -> LlmResult<ChatResponse> {
+        let (chunks, call) = self.replies.lock().unwrap().pop_front().unwrap();
+        for chunk in &chunks {
+            self.sent.fetch_add(1, Ordering::SeqCst);
+            let _ = tx.send(chunk.to_string()).await;
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        Ok(ChatResponse {
+            content: chunks.concat(),
+            model: "chunked".to_string(),
+            usage: None,
+            finish_reason: Some(if call.is_some() { "tool_calls" } else { "stop" }.to_string()),
+            tool_calls: call.map(|call| vec![call]),
+            warnings: Vec::new(),
+            provider: None,
+            failovers: Vec::new(),
+        })
+    }
+}
+
+fn agent(client: Arc<dyn GenericChatClient>, sampling: SamplingParams) -> Agent {
+    Agent::new(
+        AgentConfig::builder("researcher")
+            .system_prompt("Answer, then stop.")
+            .sampling(sampling)
+            .build(),
+    )
+    .with_client(client)
+}
+
+/// The streamed chunks and the completed request's data
+async fn run(agent: &Agent) -> (AgentOutput, String, serde_json::Value) {
+    let stream = EventStream::new();
+    let output = agent
+        .execute_with_events(
+            AgentInput::from_text("What is six times seven?"),
+            Some(&stream),
+        )
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let events = stream.all();
+    let streamed = events
+        .iter()
+        .filter(|e| e.event_type == EventType::Progress && e.scope == EventScope::LlmRequest)
+        .filter_map(|e| e.data["chunk"].as_str())
+        .collect();
+    let completed = events
+        .iter()
+        .find(|e| e.event_type == EventType::Completed && e.scope == EventScope::LlmRequest)
+        .unwrap()
+        .data
+        .clone();
+    (output, streamed, completed)
+}
+
+#[tokio::test]
+async fn test_stop_sequence_split_across_chunks() {
+    let client = Arc::new(ChunkedStream::default().reply(vec![
+        "The answer is 42.",
+        "\nObser",
+        "vation: the user",
+        " asked again",
+        " and again",
+    ]));
+    let agent = agent(
+        client.clone(),
+        SamplingParams::new().stop(["\nObservation:"]),
+    );
+
+    let (output, streamed, completed) = run(&agent).await;
+
+    assert_eq!(output.data["response"], "The answer is 42.");
+    // The start of the stop sequence was held back, never streamed
+    assert_eq!(streamed, "The answer is 42.");
+    assert_eq!(completed["finish_reason"], "stop_sequence");
+    // The stream was dropped at the match
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(client.sent(), 3);
+}
+
+#[tokio::test]
+async fn test_response_capped_at_max_chars() {
+    let client = Arc::new(ChunkedStream::default().reply(vec![
+        "Forty-two, ",
+        "and here is why ",
+        "at great length",
+        " and more",
+    ]));
+    let agent = Agent::new(
+        AgentConfig::builder("researcher")
+            .max_response_chars(20)
+            .build(),
+    )
+    .with_client(client.clone());
+
+    let (output, streamed, completed) = run(&agent).await;
+
+    assert_eq!(output.data["response"], "Forty-two, and here");
+    assert_eq!(streamed, "Forty-two, and here ");
+    assert_eq!(completed["finish_reason"], "length");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(client.sent(), 2);
+}
+
+#[tokio::test]
+async fn test_tool_call_after_a_stop_sequence_still_runs() {
+    let client = Arc::new(
+        ChunkedStream::default()
+            .tool_call(vec!["Checking. END", " of my thoughts"], "lookup")
+            .reply(vec!["It's 42."]),
+    );
+    let mut registry = ToolRegistry::new();
+    registry.register(NativeTool::new(
+        "lookup",
+        "Look up the answer",
+        json!({"type": "object", "properties": {}}),
+        |_| async move { Ok(ToolResult::success(json!(42), 1.0)) },
+    ));
+    let agent = Agent::new(
+        AgentConfig::builder("researcher")
+            .tools(Arc::new(registry))
+            .stop_sequences(["END"])
+            .build(),
+    )
+    .with_client(client.clone());
+
+    let output = agent
+        .execute(&AgentInput::from_text("What is six times seven?"))
+        .await
+        .unwrap();
+
+    // The stream ran to the end, so the call after the text wasn't lost
+    assert_eq!(output.metadata.tool_calls_count, 1);
+    assert_eq!(output.data["response"], "It's 42.");
+    assert_eq!(client.sent(), 3);
+    let history = output.chat_history.unwrap();
+    let call = history.iter().find(|m| m.tool_calls.is_some()).unwrap();
+    assert_eq!(call.content.text(), "Checking. ");
+}
+
+#[tokio::test]
+async fn test_whole_responses_are_cut_too() {
+    // The mock doesn't stop at stop sequences either
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec![
+        "The answer is 42. END Now some notes.",
+    ]));
+    let agent = agent(mock.clone(), SamplingParams::new().stop(["END"]));
+
+    let (output, _, completed) = run(&agent).await;
+
+    assert_eq!(output.data["response"], "The answer is 42.");
+    assert_eq!(completed["finish_reason"], "stop_sequence");
+    // Still sent to the provider
+    assert_eq!(
+        mock.last_call().unwrap().stop,
+        Some(vec!["END".to_string()])
+    );
+}
@@ -109,7 +109,7 @@ async fn test_subworkflow_shares_parent_context() {
     );
 
     // Verify messages accumulated from all agents
-    let history_text: Vec<String> = history.iter().map(|msg| msg.content.clone()).collect();
+    let history_text: Vec<String> = history.iter().map(|msg| msg.content.to_string()).collect();
     let combined = history_text.join(" ");
 
     // Should contain content from all stages
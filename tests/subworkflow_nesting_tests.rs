@@ -0,0 +1,183 @@
+/// Tests for event ancestry and depth limits of nested sub-workflows
+use agent_runtime::*;
+use serde_json::json;
+use std::time::Duration;
+
+fn level(name: &'static str, inner: Option<fn() -> Workflow>) -> Workflow {
+    let builder = Workflow::builder().name(name.to_string());
+    match inner {
+        Some(inner) => builder
+            .add_step(Box::new(SubWorkflowStep::new(
+                format!("{}_child", name),
+                inner,
+            )))
+            .build(),
+        // Without a client the agent answers with a mock response
+        None => builder
+            .add_step(Box::new(AgentStep::from_agent(
+                Agent::new(AgentConfig::builder("leaf").build()),
+                "leaf".to_string(),
+            )))
+            .build(),
+    }
+}
+
+fn third() -> Workflow {
+    level("third", None)
+}
+
+fn second() -> Workflow {
+    level("second", Some(third))
+}
+
+fn first() -> Workflow {
+    level("first", Some(second))
+}
+
+#[tokio::test]
+async fn test_events_carry_their_ancestry() {
+    let runtime = Runtime::new();
+    let run = runtime.execute(level("root", Some(first))).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+
+    // Child runs are summarized under the run that started them
+    let [first_run] = run.sub_workflows.as_slice() else {
+        panic!("expected one sub-workflow, got {:?}", run.sub_workflows);
+    };
+    assert_eq!(first_run.workflow_id, "first");
+    assert_eq!(first_run.step_name, "root_child");
+    assert_eq!(first_run.state, WorkflowState::Completed);
+    let second_run = &first_run.sub_workflows[0];
+    assert_eq!(second_run.workflow_id, "second");
+    assert_eq!(second_run.sub_workflows[0].workflow_id, "third");
+    assert_eq!(second_run.sub_workflows[0].steps_run, 1);
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let events = runtime.event_stream().all();
+    let leaf = events
+        .iter()
+        .find(|e| e.scope == EventScope::Agent && e.component_id == "leaf")
+        .unwrap();
+    assert_eq!(leaf.workflow_id, "third");
+    assert_eq!(leaf.workflow_path, vec!["root", "first", "second"]);
+    assert_eq!(leaf.parent_workflow_id.as_deref(), Some("second"));
+    assert_eq!(leaf.depth(), 3);
+    assert_eq!(leaf.root_workflow_id(), "root");
+
+    // Every event knows its run's place in the tree
+    for event in &events {
+        let expected: &[&str] = match event.workflow_id.as_str() {
+            "root" => &[],
+            "first" => &["root"],
+            "second" => &["root", "first"],
+            "third" => &["root", "first", "second"],
+            other => panic!("unexpected workflow {}", other),
+        };
+        assert_eq!(event.workflow_path, expected, "{:?}", event);
+    }
+    let serialized = serde_json::to_value(leaf).unwrap();
+    assert_eq!(
+        serialized["workflow_path"],
+        json!(["root", "first", "second"])
+    );
+    let root_started = serde_json::to_value(&events[0]).unwrap();
+    assert!(root_started.get("workflow_path").is_none());
+
+    // Finished runs are forgotten
+    assert!(runtime.event_stream().workflow_path(&run.run_id).is_empty());
+}
+
+#[tokio::test]
+async fn test_concurrent_runs_keep_their_own_sub_workflows() {
+    let runtime = Runtime::new();
+    let (one, two) = tokio::join!(
+        runtime.execute(level("root", Some(first))),
+        runtime.execute(level("root", Some(first)))
+    );
+
+    for run in [&one, &two] {
+        assert_eq!(run.state, WorkflowState::Completed);
+        assert_eq!(run.sub_workflows.len(), 1);
+        assert_eq!(run.sub_workflows[0].sub_workflows.len(), 1);
+    }
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let leaves: Vec<Event> = runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .filter(|e| e.scope == EventScope::Agent && e.event_type == EventType::Started)
+        .collect();
+    assert_eq!(leaves.len(), 2);
+    for leaf in leaves {
+        assert_eq!(leaf.workflow_path, vec!["root", "first", "second"]);
+    }
+}
+
+fn recursive() -> Workflow {
+    Workflow::builder()
+        .name("recursive".to_string())
+        .add_step(Box::new(TransformStep::new("tick".to_string(), |v| v)))
+        .add_step(Box::new(SubWorkflowStep::new(
+            "again".to_string(),
+            recursive,
+        )))
+        .build()
+}
+
+#[tokio::test]
+async fn test_recursive_builder_stops_at_the_depth_cap() {
+    let runtime = Runtime::new().with_max_subworkflow_depth(3);
+    let run = tokio::time::timeout(Duration::from_secs(10), runtime.execute(recursive()))
+        .await
+        .expect("recursion should stop at the depth cap");
+    assert_eq!(run.state, WorkflowState::Failed);
+
+    // Three levels ran below the top; the fourth was refused
+    let mut depth = 0;
+    let mut innermost = None;
+    let mut summaries = &run.sub_workflows;
+    while let Some(summary) = summaries.first() {
+        assert_eq!(summary.state, WorkflowState::Failed);
+        depth += 1;
+        innermost = Some(summary);
+        summaries = &summary.sub_workflows;
+    }
+    assert_eq!(depth, 3);
+    assert_eq!(
+        innermost
+            .unwrap()
+            .failure
+            .as_ref()
+            .unwrap()
+            .error
+            .to_string(),
+        "Execution failed: max sub-workflow depth exceeded (3 levels)"
+    );
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let refused: Vec<Event> = runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .filter(|e| {
+            e.scope == EventScope::Workflow
+                && e.event_type == EventType::Failed
+                && e.data["max_subworkflow_depth"] == 3
+        })
+        .collect();
+    assert_eq!(refused.len(), 1);
+    assert_eq!(
+        refused[0].message.as_deref(),
+        Some("max sub-workflow depth exceeded (3 levels)")
+    );
+    assert_eq!(refused[0].depth(), 4);
+    assert_eq!(refused[0].data["depth"], 4);
+
+    let failure = run.failure.unwrap();
+    assert_eq!(failure.step_name, "again");
+    assert_eq!(
+        failure.error.to_string(),
+        "Execution failed: Sub-workflow failed: Failed"
+    );
+}
@@ -0,0 +1,123 @@
+/// Tests for routing a workflow through the cases of a switch step
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::runtime::Runtime;
+use agent_runtime::*;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A step that tags its input with its name
+fn handler(name: &str) -> Box<dyn workflow::Step> {
+    let name = name.to_string();
+    Box::new(TransformStep::new(
+        name.clone(),
+        move |data| json!({ "handled_by": name, "ticket": data }),
+    ))
+}
+
+fn router() -> SwitchStep {
+    SwitchStep::new("route".to_string(), |data| {
+        data["category"].as_str().unwrap_or_default().to_string()
+    })
+    .case("billing", handler("refunds"))
+    .case("technical", handler("support"))
+    .case("other", handler("triage"))
+}
+
+/// The `case` recorded by each of the switch's progress events
+fn cases_taken(runtime: &Runtime) -> Vec<Value> {
+    runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .filter(|e| e.event_type == EventType::Progress && e.data["step_name"] == "route")
+        .map(|e| e.data["case"].clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_each_case_and_the_default() {
+    let runtime = Runtime::new();
+    for (category, handled_by) in [
+        ("billing", "refunds"),
+        ("technical", "support"),
+        ("other", "triage"),
+        ("sales", "escalate"),
+    ] {
+        let workflow = Workflow::builder()
+            .step(Box::new(router().default(handler("escalate"))))
+            .initial_input(json!({ "category": category }))
+            .build();
+        let run = runtime.execute(workflow).await;
+        assert_eq!(run.state, WorkflowState::Completed);
+        let output = run.final_output.unwrap();
+        assert_eq!(output["handled_by"], handled_by);
+        assert_eq!(output["ticket"]["category"], category);
+        assert_eq!(run.steps[0].step_name, "route");
+    }
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(
+        cases_taken(&runtime),
+        vec!["billing", "technical", "other", "default"]
+    );
+}
+
+#[tokio::test]
+async fn test_unmatched_case_without_default_fails() {
+    let workflow = Workflow::builder()
+        .step(Box::new(router()))
+        .initial_input(json!({ "category": "sales" }))
+        .build();
+    let run = Runtime::new().execute(workflow).await;
+
+    assert_eq!(run.state, WorkflowState::Failed);
+    let failure = run.failure.unwrap();
+    assert_eq!(failure.step_name, "route");
+    assert_eq!(
+        failure.error.to_string(),
+        "Execution failed: switch 'route' has no case 'sales' and no default (cases: billing, technical, other)"
+    );
+}
+
+#[tokio::test]
+async fn test_llm_classification_picks_the_case() {
+    let client = Arc::new(MockLlmClient::new().with_response(" Technical\n"));
+    let classifier = Agent::new(
+        AgentConfig::builder("classifier")
+            .system_prompt(
+                "Classify the ticket as billing, technical or other. Reply with the category only.",
+            )
+            .build(),
+    )
+    .with_client(client.clone());
+
+    let runtime = Runtime::new();
+    let workflow = Workflow::builder()
+        .step(Box::new(AgentStep::from_agent(
+            classifier,
+            "classify".to_string(),
+        )))
+        .step(Box::new(
+            SwitchStep::new("route".to_string(), |data| {
+                data["response"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_lowercase()
+            })
+            .case("billing", handler("refunds"))
+            .case("technical", handler("support"))
+            .default(handler("triage")),
+        ))
+        .initial_input(json!("The app crashes when I upload a photo"))
+        .build();
+    let run = runtime.execute(workflow).await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(run.final_output.unwrap()["handled_by"], "support");
+    assert_eq!(client.call_count(), 1);
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(cases_taken(&runtime), vec!["technical"]);
+}
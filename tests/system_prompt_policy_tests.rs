@@ -0,0 +1,164 @@
+/// Tests for how agents sharing a workflow's history treat each other's
+/// system prompts
+use agent_runtime::llm::types::Role;
+use agent_runtime::*;
+use serde_json::json;
+use std::sync::Arc;
+
+const INPUT: &str = "Write about otters";
+
+/// researcher → outliner → writer, all with `policy`; returns what the
+/// writer was sent
+async fn run(policy: SystemPromptPolicy) -> (Vec<(Role, String)>, WorkflowContext) {
+    let agents = [
+        ("researcher", "You research", "Otters hold hands"),
+        ("outliner", "You outline", "1. Hands"),
+        ("writer", "You write", "Otters hold hands while they sleep."),
+    ];
+    let mut builder = Workflow::builder()
+        .name("report".to_string())
+        .with_chat_history(Arc::new(TokenBudgetManager::new(24_000, 3.0)))
+        .initial_input(json!(INPUT));
+    let mut clients = Vec::new();
+    for (name, prompt, response) in agents {
+        let client = Arc::new(llm::MockLlmClient::new().with_response(response));
+        let agent = Agent::new(
+            AgentConfig::builder(name)
+                .system_prompt(prompt)
+                .system_prompt_policy(policy)
+                .build(),
+        )
+        .with_client(client.clone());
+        builder = builder.add_step(Box::new(AgentStep::from_agent(agent, name.to_string())));
+        clients.push(client);
+    }
+    let workflow = builder.build();
+    let context = workflow.context().unwrap().clone();
+
+    let run = Runtime::new().execute(workflow).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+
+    let sent = clients[2].get_calls()[0]
+        .messages
+        .iter()
+        .map(|m| (m.role.clone(), m.content.text().into_owned()))
+        .collect();
+    let context = context.read().unwrap().clone();
+    (sent, context)
+}
+
+/// What every policy sends after the writer's prompt and the earlier
+/// agents' prompts
+fn turns() -> Vec<(Role, String)> {
+    vec![
+        (Role::User, INPUT.to_string()),
+        (Role::Assistant, "Otters hold hands".to_string()),
+        (Role::User, "Otters hold hands".to_string()),
+        (Role::Assistant, "1. Hands".to_string()),
+        (Role::User, "1. Hands".to_string()),
+    ]
+}
+
+/// However the writer saw them, the shared history holds only turns, and
+/// the prompts are kept beside it
+fn assert_shared_history(context: &WorkflowContext) {
+    let roles: Vec<_> = context.history().iter().map(|m| m.role.clone()).collect();
+    assert_eq!(
+        roles,
+        vec![
+            Role::User,
+            Role::Assistant,
+            Role::User,
+            Role::Assistant,
+            Role::User,
+            Role::Assistant
+        ]
+    );
+    let prompts: Vec<_> = context
+        .system_prompts
+        .iter()
+        .map(|p| (p.agent.as_str(), p.prompt.as_str()))
+        .collect();
+    assert_eq!(
+        prompts,
+        vec![
+            ("researcher", "You research"),
+            ("outliner", "You outline"),
+            ("writer", "You write")
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_strip_sends_only_the_current_prompt() {
+    let (sent, context) = run(SystemPromptPolicy::Strip).await;
+
+    let mut expected = vec![(Role::System, "You write".to_string())];
+    expected.extend(turns());
+    assert_eq!(sent, expected);
+    assert_shared_history(&context);
+}
+
+#[tokio::test]
+async fn test_annotate_notes_earlier_instructions() {
+    let (sent, context) = run(SystemPromptPolicy::Annotate).await;
+
+    let mut expected = vec![
+        (Role::System, "You write".to_string()),
+        (
+            Role::User,
+            "Previously, agent researcher was instructed to: You research".to_string(),
+        ),
+        (
+            Role::User,
+            "Previously, agent outliner was instructed to: You outline".to_string(),
+        ),
+    ];
+    expected.extend(turns());
+    assert_eq!(sent, expected);
+    // The notes were for the request only
+    assert_shared_history(&context);
+}
+
+#[tokio::test]
+async fn test_keep_sends_earlier_prompts_after_the_current_one() {
+    let (sent, context) = run(SystemPromptPolicy::Keep).await;
+
+    let mut expected = vec![
+        (Role::System, "You write".to_string()),
+        (Role::System, "You research".to_string()),
+        (Role::System, "You outline".to_string()),
+    ];
+    expected.extend(turns());
+    assert_eq!(sent, expected);
+    assert_shared_history(&context);
+}
+
+#[tokio::test]
+async fn test_annotation_shortens_long_prompts() {
+    let client = Arc::new(llm::MockLlmClient::new().with_response("ok"));
+    let agent = Agent::new(
+        AgentConfig::builder("editor")
+            .system_prompt("You edit")
+            .system_prompt_policy(SystemPromptPolicy::Annotate)
+            .build(),
+    )
+    .with_client(client.clone());
+    let input = AgentInput {
+        chat_history: Some(vec![
+            ChatMessage::system("x".repeat(1_000)),
+            ChatMessage::system("You edit"),
+        ]),
+        ..AgentInput::from_text("Tidy this")
+    };
+
+    agent.execute(&input).await.unwrap();
+
+    let sent = &client.get_calls()[0].messages;
+    assert_eq!(sent.len(), 3);
+    let note = sent[1].content.text();
+    assert!(note.starts_with("Previously, another agent was instructed to: xxx"));
+    assert!(note.ends_with('…'));
+    assert!(note.chars().count() < 500);
+    assert_eq!(sent[2].content.text(), "Tidy this");
+}
@@ -0,0 +1,184 @@
+/// Tests for the registry's tool result cache
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::*;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A weather tool counting how often it really runs
+fn weather(runs: Arc<AtomicUsize>) -> NativeTool {
+    NativeTool::new(
+        "get_weather",
+        "Current weather for a city",
+        json!({"type": "object", "properties": {"city": {"type": "string"}, "units": {"type": "string"}}}),
+        move |params| {
+            let runs = runs.clone();
+            async move {
+                runs.fetch_add(1, Ordering::SeqCst);
+                if params["city"] == "Atlantis" {
+                    return Ok(ToolResult::error("no such city", 1.0));
+                }
+                Ok(ToolResult::success(
+                    json!({ "city": params["city"], "sky": "clear" }),
+                    25.0,
+                ))
+            }
+        },
+    )
+}
+
+async fn ask(registry: Arc<ToolRegistry>, arguments: serde_json::Value, stream: &EventStream) {
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("get_weather", arguments)
+            .with_response("Clear skies."),
+    );
+    Agent::new(AgentConfig::builder("forecaster").tools(registry).build())
+        .with_client(client)
+        .execute_with_events(AgentInput::from_text("Weather in Tokyo?"), Some(stream))
+        .await
+        .unwrap();
+}
+
+fn params(city: &str) -> HashMap<String, serde_json::Value> {
+    HashMap::from([("city".to_string(), json!(city))])
+}
+
+#[tokio::test]
+async fn test_identical_calls_across_agents_run_once() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let mut registry = ToolRegistry::new().with_cache(ToolCacheConfig::default());
+    registry.register(weather(runs.clone()));
+    let registry = Arc::new(registry);
+
+    let stream = EventStream::new();
+    ask(
+        registry.clone(),
+        json!({"city": "Tokyo", "units": "metric"}),
+        &stream,
+    )
+    .await;
+    // The same arguments in another order
+    ask(
+        registry,
+        json!({"units": "metric", "city": "Tokyo"}),
+        &stream,
+    )
+    .await;
+
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let completed: Vec<Event> = stream
+        .all()
+        .into_iter()
+        .filter(|e| e.scope == EventScope::Tool && e.event_type == EventType::Completed)
+        .collect();
+    assert_eq!(completed.len(), 2);
+    assert!(completed[0].data.get("cached").is_none());
+    assert_eq!(completed[1].data["cached"], true);
+    assert_eq!(completed[1].data["result"], completed[0].data["result"]);
+    assert!(completed[1].data["duration_ms"].as_f64().unwrap() < 25.0);
+}
+
+#[tokio::test]
+async fn test_opted_out_tool_runs_every_time() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let mut registry = ToolRegistry::new().with_cache(ToolCacheConfig::default());
+    registry.register(weather(runs.clone()).no_cache());
+
+    for _ in 0..2 {
+        let result = registry
+            .call_tool("get_weather", params("Tokyo"))
+            .await
+            .unwrap();
+        assert!(!result.cached);
+    }
+    assert_eq!(runs.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_expired_and_invalidated_results_rerun() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let mut registry =
+        ToolRegistry::new().with_cache(ToolCacheConfig::new(Duration::from_secs(60)));
+    registry.register(weather(runs.clone()));
+
+    registry
+        .call_tool("get_weather", params("Tokyo"))
+        .await
+        .unwrap();
+    tokio::time::advance(Duration::from_secs(59)).await;
+    let hit = registry
+        .call_tool("get_weather", params("Tokyo"))
+        .await
+        .unwrap();
+    assert!(hit.cached);
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+    tokio::time::advance(Duration::from_secs(2)).await;
+    let fresh = registry
+        .call_tool("get_weather", params("Tokyo"))
+        .await
+        .unwrap();
+    assert!(!fresh.cached);
+    assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+    assert_eq!(registry.invalidate_tool("get_weather"), 1);
+    registry
+        .call_tool("get_weather", params("Tokyo"))
+        .await
+        .unwrap();
+    assert_eq!(runs.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_errors_are_not_cached() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let mut registry = ToolRegistry::new().with_cache(ToolCacheConfig::default());
+    registry.register(weather(runs.clone()));
+
+    for _ in 0..2 {
+        let result = registry
+            .call_tool("get_weather", params("Atlantis"))
+            .await
+            .unwrap();
+        assert_eq!(result.status, ToolStatus::Error);
+    }
+    assert_eq!(runs.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_per_tool_ttl_and_entry_limit() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let mut registry = ToolRegistry::new().with_cache(
+        ToolCacheConfig::new(Duration::from_secs(3600))
+            .with_tool_ttl("get_weather", Duration::from_secs(10))
+            .with_max_entries(1),
+    );
+    registry.register(weather(runs.clone()));
+
+    registry
+        .call_tool("get_weather", params("Tokyo"))
+        .await
+        .unwrap();
+    // Osaka's result pushes Tokyo's out
+    registry
+        .call_tool("get_weather", params("Osaka"))
+        .await
+        .unwrap();
+    registry
+        .call_tool("get_weather", params("Tokyo"))
+        .await
+        .unwrap();
+    assert_eq!(runs.load(Ordering::SeqCst), 3);
+
+    tokio::time::advance(Duration::from_secs(11)).await;
+    registry
+        .call_tool("get_weather", params("Tokyo"))
+        .await
+        .unwrap();
+    assert_eq!(runs.load(Ordering::SeqCst), 4);
+}
@@ -0,0 +1,340 @@
+use agent_runtime::llm::{GenericChatClient, LlmResult, MockLlmClient};
+use agent_runtime::prelude::TypesToolError as ToolError;
+use agent_runtime::types::AgentError;
+use agent_runtime::*;
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Polls an imaginary job until the token fires
+fn polling_tool() -> NativeTool {
+    NativeTool::cancellable(
+        "poll_job",
+        "Poll a long-running job",
+        json!({"type": "object", "properties": {}}),
+        |_params, token| async move {
+            loop {
+                tokio::select! {
+                    _ = token.cancelled() => {
+                        return Err(ToolError::Canceled("stopped polling".into()));
+                    }
+                    _ = tokio::time::sleep(Duration::from_millis(10)) => {}
+                }
+            }
+        },
+    )
+}
+
+/// Writes a partial download to `path`, removing it if canceled
+fn download_tool(path: PathBuf) -> NativeTool {
+    NativeTool::cancellable(
+        "download",
+        "Download a large file",
+        json!({"type": "object", "properties": {}}),
+        move |_params, token| {
+            let path = path.clone();
+            async move {
+                std::fs::write(&path, b"partial").unwrap();
+                token.cancelled().await;
+                std::fs::remove_file(&path).unwrap();
+                Err(ToolError::Canceled("download aborted".into()))
+            }
+        },
+    )
+}
+
+fn agent(tool: NativeTool, mock: Arc<MockLlmClient>) -> Agent {
+    let mut registry = ToolRegistry::new();
+    registry.register(tool);
+    Agent::new(
+        AgentConfig::builder("worker")
+            .system_prompt("You help")
+            .tools(Arc::new(registry))
+            .build(),
+    )
+    .with_client(mock)
+}
+
+fn calling(tool: &str) -> Arc<MockLlmClient> {
+    Arc::new(
+        MockLlmClient::new()
+            .with_tool_call(tool, json!({}))
+            .with_response("unreachable"),
+    )
+}
+
+#[tokio::test]
+async fn test_looping_tool_exits_promptly_on_cancel() {
+    let mock = calling("poll_job");
+    let agent = agent(polling_tool(), mock.clone());
+    let token = CancellationToken::new();
+
+    let canceler = token.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        canceler.cancel();
+    });
+
+    let started = Instant::now();
+    let result = agent
+        .execute_with_cancellation(AgentInput::from_value(json!("go")), None, None, token)
+        .await;
+
+    assert!(started.elapsed() < Duration::from_secs(1));
+    match result {
+        Err(AgentError::Canceled(reason)) => assert_eq!(reason, "poll_job: stopped polling"),
+        other => panic!("expected Canceled, got {:?}", other),
+    }
+    // The canceled result is never sent back to the LLM
+    assert_eq!(mock.call_count(), 1);
+}
+
+#[tokio::test]
+async fn test_canceled_tool_cleans_up_temp_file() {
+    let path = std::env::temp_dir().join(format!("agent-runtime-{}.part", uuid::Uuid::new_v4()));
+    let agent = agent(download_tool(path.clone()), calling("download"));
+    let token = CancellationToken::new();
+
+    let canceler = token.clone();
+    let watched = path.clone();
+    tokio::spawn(async move {
+        while !watched.exists() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        canceler.cancel();
+    });
+
+    let result = agent
+        .execute_with_cancellation(AgentInput::from_value(json!("go")), None, None, token)
+        .await;
+
+    assert!(matches!(result, Err(AgentError::Canceled(_))));
+    assert!(!path.exists());
+}
+
+fn workflow(mock: Arc<MockLlmClient>) -> Workflow {
+    Workflow::builder()
+        .name("long_job".to_string())
+        .add_step(Box::new(AgentStep::from_agent(
+            agent(polling_tool(), mock),
+            "worker".to_string(),
+        )))
+        .add_step(Box::new(TransformStep::new("after".to_string(), |data| {
+            data
+        })))
+        .initial_input(json!("go"))
+        .build()
+}
+
+#[tokio::test]
+async fn test_runtime_cancel_maps_to_canceled_run() {
+    let runtime = Arc::new(Runtime::new());
+    let mock = calling("poll_job");
+    let workflow = workflow(mock.clone());
+    let run_id = workflow.run_id.clone();
+
+    let canceler = runtime.clone();
+    let id = run_id.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(canceler.cancel(&id));
+    });
+
+    let run = runtime.execute(workflow).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert_eq!(run.state, WorkflowState::Canceled);
+    assert_eq!(run.steps.len(), 1);
+    assert_eq!(run.steps[0].status, StepStatus::Canceled);
+    assert!(run.final_output.is_none());
+    assert_eq!(mock.call_count(), 1);
+    assert_eq!(run.run_id, run_id);
+    assert!(!runtime.cancel(&run_id));
+
+    let events = runtime.events_from_offset(0);
+    let canceled: Vec<_> = events
+        .iter()
+        .filter(|e| e.event_type == EventType::Canceled)
+        .map(|e| (e.scope.clone(), e.component_id.as_str()))
+        .collect();
+    assert_eq!(
+        canceled,
+        vec![
+            (EventScope::Tool, "poll_job"),
+            (EventScope::Agent, "worker"),
+            (EventScope::Workflow, run.workflow_id.as_str()),
+        ]
+    );
+    let workflow_event = events
+        .iter()
+        .find(|e| e.scope == EventScope::Workflow && e.event_type == EventType::Canceled)
+        .unwrap();
+    assert_eq!(workflow_event.status, ComponentStatus::Canceled);
+    assert_eq!(workflow_event.data["failed_step_name"], "worker");
+    // Later steps never start
+    assert!(!events
+        .iter()
+        .any(|e| e.scope == EventScope::WorkflowStep && e.data["step_name"] == "after"));
+}
+
+#[tokio::test]
+async fn test_cancel_stops_only_that_run_of_a_workflow() {
+    let runtime = Arc::new(Runtime::new());
+    let first = workflow(calling("poll_job"));
+    let second = workflow(calling("poll_job"));
+    assert_eq!(first.id, second.id);
+    let (first_id, second_id) = (first.run_id.clone(), second.run_id.clone());
+
+    let running = runtime.clone();
+    let runs =
+        tokio::spawn(async move { tokio::join!(running.execute(first), running.execute(second)) });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(runtime.cancel(&first_id));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // The first run is over; the other run of the workflow carries on
+    assert!(!runtime.cancel(&first_id));
+    assert!(!runs.is_finished());
+    assert!(runtime.cancel(&second_id));
+    let (first, second) = runs.await.unwrap();
+    assert_eq!(first.run_id, first_id);
+    assert_eq!(first.state, WorkflowState::Canceled);
+    assert_eq!(second.state, WorkflowState::Canceled);
+}
+
+#[tokio::test]
+async fn test_shutdown_cancels_in_flight_runs() {
+    let runtime = Arc::new(Runtime::new());
+    let stopper = runtime.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        stopper.shutdown();
+    });
+
+    let run = runtime.execute(workflow(calling("poll_job"))).await;
+
+    assert_eq!(run.state, WorkflowState::Canceled);
+}
+
+/// Streams a reply that takes far longer than any test waits
+struct StalledStream;
+
+#[async_trait::async_trait]
+impl GenericChatClient for StalledStream {
+    async fn chat(&self, _request: ChatRequest) -> LlmResult<ChatResponse> {
+        unreachable!("agents stream their requests")
+    }
+
+    async fn chat_stream(
+        &self,
+        _request: ChatRequest,
+        tx: tokio::sync::mpsc::Sender<String>,
+    ) -> LlmResult<ChatResponse> {
+        let _ = tx.send("Thinking".to_string()).await;
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        unreachable!("the request is canceled first")
+    }
+}
+
+fn three_step_workflow(middle: Box<dyn Step>) -> Workflow {
+    Workflow::builder()
+        .name("pipeline".to_string())
+        .add_step(Box::new(TransformStep::new(
+            "prepare".to_string(),
+            |data| data,
+        )))
+        .add_step(middle)
+        .add_step(Box::new(TransformStep::new("after".to_string(), |data| {
+            data
+        })))
+        .initial_input(json!("go"))
+        .build()
+}
+
+fn started_steps(events: &[Event]) -> Vec<String> {
+    events
+        .iter()
+        .filter(|e| e.scope == EventScope::WorkflowStep && e.event_type == EventType::Started)
+        .filter_map(|e| e.data["step_name"].as_str().map(str::to_string))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_cancel_aborts_streaming_llm_request() {
+    let runtime = Runtime::new();
+    let agent =
+        Agent::new(AgentConfig::builder("writer").build()).with_client(Arc::new(StalledStream));
+    let workflow =
+        three_step_workflow(Box::new(AgentStep::from_agent(agent, "writer".to_string())));
+
+    let (handle, run) = runtime.execute_cancellable(workflow);
+    let canceler = handle.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        canceler.cancel();
+    });
+
+    let started = Instant::now();
+    let run = run.await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(handle.is_canceled());
+    assert_eq!(run.state, WorkflowState::Canceled);
+    let completed: Vec<&str> = run
+        .steps
+        .iter()
+        .filter(|s| s.status == StepStatus::Succeeded)
+        .map(|s| s.step_name.as_str())
+        .collect();
+    assert_eq!(completed, vec!["prepare"]);
+
+    let events = runtime.events_from_offset(0);
+    assert_eq!(started_steps(&events), vec!["prepare", "writer"]);
+    let llm = events
+        .iter()
+        .find(|e| e.scope == EventScope::LlmRequest && e.event_type == EventType::Canceled)
+        .unwrap();
+    assert_eq!(llm.component_id, "writer:llm:1");
+    assert!(events
+        .iter()
+        .any(|e| e.scope == EventScope::Agent && e.event_type == EventType::Canceled));
+}
+
+#[tokio::test]
+async fn test_cancel_between_steps_starts_no_more_steps() {
+    let runtime = Runtime::new();
+    let slot: Arc<std::sync::Mutex<Option<CancellationHandle>>> = Default::default();
+    let from_step = slot.clone();
+    let workflow = three_step_workflow(Box::new(TransformStep::new(
+        "cancel".to_string(),
+        move |data| {
+            from_step.lock().unwrap().as_ref().unwrap().cancel();
+            data
+        },
+    )));
+    let workflow_id = workflow.id.clone();
+    let run_id = workflow.run_id.clone();
+
+    let (handle, run) = runtime.execute_cancellable(workflow);
+    assert_eq!(handle.workflow_id(), workflow_id);
+    assert_eq!(handle.run_id(), run_id);
+    *slot.lock().unwrap() = Some(handle);
+    let run = run.await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // The step that canceled finished; the next one never started
+    assert_eq!(run.state, WorkflowState::Canceled);
+    assert_eq!(run.steps.len(), 2);
+    assert!(run.final_output.is_none());
+
+    let events = runtime.events_from_offset(0);
+    assert_eq!(started_steps(&events), vec!["prepare", "cancel"]);
+    let canceled = events
+        .iter()
+        .find(|e| e.scope == EventScope::Workflow && e.event_type == EventType::Canceled)
+        .unwrap();
+    assert_eq!(canceled.data["next_step_name"], "after");
+    assert_eq!(canceled.data["steps_completed"], 2);
+}
@@ -0,0 +1,213 @@
+/// Tests for registering and removing tools while an agent is running
+use agent_runtime::llm::{MockLlmClient, Role};
+use agent_runtime::types::ToolError;
+use agent_runtime::*;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn tool<F, Fut>(name: &str, run: F) -> NativeTool
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ToolExecutionResult> + Send + 'static,
+{
+    NativeTool::new(
+        name,
+        "Does its job",
+        json!({ "type": "object", "properties": {} }),
+        move |_params| run(),
+    )
+}
+
+fn answering(name: &str, answer: &'static str) -> NativeTool {
+    tool(name, move || async move {
+        Ok(ToolResult::success(json!(answer), 0.0))
+    })
+}
+
+fn offered(tools: &Option<Vec<Value>>) -> Vec<&str> {
+    tools
+        .iter()
+        .flatten()
+        .map(|tool| tool["function"]["name"].as_str().unwrap())
+        .collect()
+}
+
+fn tool_message(request: &llm::ChatRequest) -> String {
+    request
+        .messages
+        .iter()
+        .rfind(|m| m.role == Role::Tool)
+        .unwrap()
+        .content
+        .text()
+        .into_owned()
+}
+
+/// (component, message) of the stream's tool Progress events
+async fn tool_changes(stream: &EventStream) -> Vec<(String, String)> {
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    stream
+        .all()
+        .into_iter()
+        .filter(|e| e.scope == EventScope::Tool && e.event_type == EventType::Progress)
+        .map(|e| (e.component_id, e.message.unwrap_or_default()))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_tools_registered_mid_run_are_offered_next_request() {
+    let registry = Arc::new(ToolRegistry::new());
+    let shared = registry.clone();
+    registry.add(tool("connect_github", move || {
+        let shared = shared.clone();
+        async move {
+            // The server's tools arrive together
+            let mut github = ToolRegistry::new();
+            github
+                .register(answering("search_issues", "issue #7"))
+                .register(answering("create_issue", "created"));
+            shared.merge(&github);
+            Ok(ToolResult::success(json!("connected"), 0.0))
+        }
+    }));
+
+    let client = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("connect_github", json!({}))
+            .with_tool_call("search_issues", json!({}))
+            .with_response("Issue #7 covers it."),
+    );
+    let stream = EventStream::new();
+    let output = Agent::new(
+        AgentConfig::builder("assistant")
+            .tools(registry.clone())
+            .build(),
+    )
+    .with_client(client.clone())
+    .execute_with_events(AgentInput::from_text("Find the issue"), Some(&stream))
+    .await
+    .unwrap();
+    assert_eq!(output.data["response"], "Issue #7 covers it.");
+
+    let calls = client.get_calls();
+    assert_eq!(calls.len(), 3);
+    assert_eq!(offered(&calls[0].tools), vec!["connect_github"]);
+    assert_eq!(
+        offered(&calls[1].tools),
+        vec!["connect_github", "create_issue", "search_issues"]
+    );
+    assert_eq!(tool_message(&calls[2]), r#""issue #7""#);
+
+    assert_eq!(
+        tool_changes(&stream).await,
+        vec![
+            (
+                "create_issue".into(),
+                "Tool 'create_issue' registered".into()
+            ),
+            (
+                "search_issues".into(),
+                "Tool 'search_issues' registered".into()
+            ),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_calls_to_a_tool_removed_mid_run_fail_gracefully() {
+    let registry = Arc::new(ToolRegistry::new());
+    let shared = registry.clone();
+    registry
+        .add(answering("lookup", "found"))
+        .add(tool("disconnect", move || {
+            let shared = shared.clone();
+            async move {
+                assert!(shared.unregister("lookup"));
+                Ok(ToolResult::success(json!("disconnected"), 0.0))
+            }
+        }));
+
+    // The LLM calls `lookup` in the same turn it disconnects
+    let client = Arc::new(
+        MockLlmClient::from_mock_responses(vec![llm::MockResponse::with_tool_calls(vec![
+            ("disconnect", json!({})),
+            ("lookup", json!({})),
+        ])])
+        .with_response("Lookup is gone."),
+    );
+    let stream = EventStream::new();
+    let output = Agent::new(
+        AgentConfig::builder("assistant")
+            .tools(registry.clone())
+            .build(),
+    )
+    .with_client(client.clone())
+    .execute_with_events(AgentInput::from_text("Look it up"), Some(&stream))
+    .await
+    .unwrap();
+    assert_eq!(output.data["response"], "Lookup is gone.");
+
+    let calls = client.get_calls();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(offered(&calls[0].tools), vec!["disconnect", "lookup"]);
+    assert_eq!(offered(&calls[1].tools), vec!["disconnect"]);
+    assert_eq!(
+        tool_message(&calls[1]),
+        "Error: Tool execution failed: No longer available: 'lookup' was unregistered"
+    );
+
+    let failed = stream
+        .all()
+        .into_iter()
+        .find(|e| e.scope == EventScope::Tool && e.event_type == EventType::Failed)
+        .unwrap();
+    assert_eq!(failed.component_id, "lookup");
+    assert_eq!(failed.data["error"]["code"], "unavailable");
+    assert_eq!(
+        tool_changes(&stream).await,
+        vec![("lookup".into(), "Tool 'lookup' unregistered".into())]
+    );
+}
+
+#[tokio::test]
+async fn test_registry_changes_bump_the_generation() {
+    let registry = ToolRegistry::new();
+    assert_eq!(registry.generation(), 0);
+    registry.add(answering("a", "a"));
+    let mut bulk = ToolRegistry::new();
+    bulk.register(answering("b", "b"))
+        .register(answering("c", "c"));
+    registry.merge(&bulk);
+    assert_eq!(registry.generation(), 2);
+    assert_eq!(registry.len(), 3);
+
+    // Merging nothing and removing unknown tools change nothing
+    registry.merge(&ToolRegistry::new());
+    assert!(!registry.unregister("missing"));
+    assert_eq!(registry.generation(), 2);
+
+    assert!(registry.unregister("a"));
+    assert_eq!(registry.generation(), 3);
+    assert!(!registry.has_tool("a"));
+    let removed = registry.call_tool("a", HashMap::new()).await.unwrap_err();
+    assert!(
+        matches!(removed, ToolError::Unavailable(_)),
+        "{:?}",
+        removed
+    );
+    assert!(!removed.is_retryable());
+    let unknown = registry.call_tool("z", HashMap::new()).await.unwrap_err();
+    assert!(
+        matches!(unknown, ToolError::InvalidParameters(_)),
+        "{:?}",
+        unknown
+    );
+
+    // Registering it again brings it back
+    registry.add(answering("a", "again"));
+    let result = registry.call_tool("a", HashMap::new()).await.unwrap();
+    assert_eq!(result.output, json!("again"));
+}
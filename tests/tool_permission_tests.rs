@@ -0,0 +1,132 @@
+/// Tests for limiting which of a shared registry's tools each agent gets
+use agent_runtime::llm::{MockLlmClient, Role};
+use agent_runtime::*;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A tool named `name` that counts its executions
+fn counting_tool(name: &str, runs: Arc<AtomicUsize>) -> NativeTool {
+    NativeTool::new(
+        name,
+        "Does its job",
+        json!({ "type": "object", "properties": { "q": { "type": "string" } } }),
+        move |_params| {
+            let runs = runs.clone();
+            async move {
+                runs.fetch_add(1, Ordering::SeqCst);
+                Ok(ToolResult::success(json!("done"), 0.0))
+            }
+        },
+    )
+}
+
+fn offered(tools: &Option<Vec<Value>>) -> Vec<&str> {
+    tools
+        .iter()
+        .flatten()
+        .map(|tool| tool["function"]["name"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_agents_sharing_a_registry_see_only_their_tools() {
+    let searches = Arc::new(AtomicUsize::new(0));
+    let saves = Arc::new(AtomicUsize::new(0));
+    let mut registry = ToolRegistry::new();
+    registry
+        .register(counting_tool("web_search", searches.clone()))
+        .register(counting_tool("save_document", saves.clone()));
+    let registry = Arc::new(registry);
+
+    let researcher_llm = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("web_search", json!({ "q": "otters" }))
+            .with_response("Found otters"),
+    );
+    let researcher = Agent::new(
+        AgentConfig::builder("researcher")
+            .tools(registry.clone())
+            .allowed_tools(["web_search"])
+            .build(),
+    )
+    .with_client(researcher_llm.clone());
+
+    // The writer tries the researcher's tool, then its own
+    let writer_llm = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("web_search", json!({ "q": "more otters" }))
+            .with_tool_call("save_document", json!({ "q": "otters.md" }))
+            .with_response("Saved"),
+    );
+    let writer = Agent::new(
+        AgentConfig::builder("writer")
+            .tools(registry.clone())
+            .allowed_tools(vec!["save_document".to_string()])
+            .build(),
+    )
+    .with_client(writer_llm.clone());
+
+    let input = AgentInput::from_text("Otters");
+    researcher.execute(&input).await.unwrap();
+    let output = writer.execute(&input).await.unwrap();
+    assert_eq!(output.data["response"], "Saved");
+
+    for call in researcher_llm.get_calls() {
+        assert_eq!(offered(&call.tools), vec!["web_search"]);
+    }
+    for call in writer_llm.get_calls() {
+        assert_eq!(offered(&call.tools), vec!["save_document"]);
+    }
+    assert_eq!(searches.load(Ordering::SeqCst), 1);
+    assert_eq!(saves.load(Ordering::SeqCst), 1);
+
+    // The refusal went back to the writer's LLM, which carried on
+    let refusal = writer_llm.get_calls()[1]
+        .messages
+        .iter()
+        .rev()
+        .find(|m| m.role == Role::Tool)
+        .unwrap()
+        .content
+        .clone();
+    assert_eq!(
+        refusal,
+        "Error: Tool execution failed: Not permitted: agent 'writer' may not call \
+         'web_search'; its tools are: save_document"
+    );
+}
+
+#[tokio::test]
+async fn test_refused_calls_are_reported_under_the_tools_name() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let mut registry = ToolRegistry::new();
+    registry.register(counting_tool("github.create_issue", runs.clone()));
+    let events = Arc::new(EventStream::new());
+    let agent = Agent::new(
+        AgentConfig::builder("reader")
+            .tools(Arc::new(registry))
+            .allowed_tools(Vec::<String>::new())
+            .build(),
+    )
+    .with_client(Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("github.create_issue", json!({ "q": "bug" }))
+            .with_response("Couldn't file it"),
+    ));
+
+    let output = agent
+        .execute_with_events(AgentInput::from_text("File a bug"), Some(&events))
+        .await
+        .unwrap();
+    assert_eq!(output.data["response"], "Couldn't file it");
+    assert_eq!(runs.load(Ordering::SeqCst), 0);
+
+    let failed = events
+        .all()
+        .into_iter()
+        .find(|e| e.scope == EventScope::Tool && e.event_type == EventType::Failed)
+        .unwrap();
+    assert_eq!(failed.component_id, "github.create_issue");
+    assert!(failed.message.unwrap().contains("its tools are: none"));
+}
@@ -0,0 +1,149 @@
+/// Tests for checking tool arguments against their declared schemas
+use agent_runtime::llm::{MockLlmClient, Role};
+use agent_runtime::prelude::TypesToolError as ToolError;
+use agent_runtime::*;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// `add(a, b)`, counting its executions
+fn add_tool(runs: Arc<AtomicUsize>) -> NativeTool {
+    NativeTool::new(
+        "add",
+        "Add two numbers",
+        json!({
+            "type": "object",
+            "properties": {
+                "a": { "type": "number" },
+                "b": { "type": "number" }
+            },
+            "required": ["a", "b"]
+        }),
+        move |params| {
+            let runs = runs.clone();
+            async move {
+                runs.fetch_add(1, Ordering::SeqCst);
+                let number = |key: &str| params.get(key).and_then(Value::as_f64);
+                let (a, b) = (
+                    number("a").unwrap_or_default(),
+                    number("b").unwrap_or_default(),
+                );
+                Ok(ToolResult::success(json!(a + b), 0.0))
+            }
+        },
+    )
+}
+
+fn args(value: Value) -> HashMap<String, Value> {
+    serde_json::from_value(value).unwrap()
+}
+
+#[tokio::test]
+async fn test_violations_are_listed_and_the_tool_is_not_run() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let mut registry = ToolRegistry::new();
+    registry.register(add_tool(runs.clone()));
+
+    let missing = registry.call_tool("add", args(json!({ "a": 1 }))).await;
+    let Err(ToolError::InvalidParameters(message)) = missing else {
+        panic!("expected InvalidParameters");
+    };
+    assert_eq!(message, "/b: required property is missing");
+
+    let wrong = registry
+        .call_tool("add", args(json!({ "a": "one", "b": [2] })))
+        .await;
+    let Err(ToolError::InvalidParameters(message)) = wrong else {
+        panic!("expected InvalidParameters");
+    };
+    assert!(
+        message.contains("/a: expected number, found string"),
+        "{}",
+        message
+    );
+    assert!(
+        message.contains("/b: expected number, found array"),
+        "{}",
+        message
+    );
+    assert_eq!(runs.load(Ordering::SeqCst), 0);
+
+    let sum = registry
+        .call_tool("add", args(json!({ "a": 1, "b": 2 })))
+        .await
+        .unwrap();
+    assert_eq!(sum.output, json!(3.0));
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_skip_validation_passes_arguments_through() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let mut registry = ToolRegistry::new();
+    registry.register(add_tool(runs.clone()).skip_validation());
+
+    assert!(registry
+        .validate_arguments("add", &args(json!({ "a": "one" })))
+        .is_ok());
+    let result = registry
+        .call_tool("add", args(json!({ "a": "one" })))
+        .await
+        .unwrap();
+    assert_eq!(result.output, json!(0.0));
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_agent_feeds_violations_back_to_the_llm() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let mut registry = ToolRegistry::new();
+    registry.register(add_tool(runs.clone()));
+    let mock = Arc::new(
+        MockLlmClient::new()
+            .with_tool_call("add", json!({ "a": "2" }))
+            .with_tool_call("add", json!({ "a": 2, "b": 3 }))
+            .with_response("2 + 3 = 5"),
+    );
+    let agent = Agent::new(
+        AgentConfig::builder("adder")
+            .tools(Arc::new(registry))
+            .build(),
+    )
+    .with_client(mock.clone());
+
+    let stream = EventStream::new();
+    let output = agent
+        .execute_with_events(AgentInput::from_value(json!("Add 2 and 3")), Some(&stream))
+        .await
+        .unwrap();
+    assert_eq!(output.data["response"], "2 + 3 = 5");
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+    // The model saw what was wrong with its first call
+    let served = mock.last_call().unwrap();
+    let replies: Vec<_> = served
+        .messages
+        .iter()
+        .filter(|m| m.role == Role::Tool)
+        .map(|m| m.content.text())
+        .collect();
+    assert_eq!(replies.len(), 2);
+    assert!(replies[0].contains("/a: expected number, found string"));
+    assert!(replies[0].contains("/b: required property is missing"));
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let failed = stream
+        .all()
+        .into_iter()
+        .find(|e| e.scope == EventScope::Tool && e.event_type == EventType::Failed)
+        .unwrap();
+    assert_eq!(
+        failed.data["validation_errors"],
+        json!([
+            { "pointer": "/b", "message": "required property is missing" },
+            { "pointer": "/a", "message": "expected number, found string" }
+        ])
+    );
+}
@@ -0,0 +1,318 @@
+use agent_runtime::event::sampling::{SamplingPolicy, SamplingReason, TailSampling};
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::*;
+use async_trait::async_trait;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+async fn settled_events(runtime: &Runtime) -> Vec<Event> {
+    // Events are appended asynchronously
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    runtime.events_from_offset(0)
+}
+
+fn agent_workflow(name: &str, mock: Arc<MockLlmClient>, agents: usize) -> WorkflowBuilder {
+    let mut builder = Workflow::builder().name(name.to_string());
+    for i in 0..agents {
+        let agent = Agent::new(
+            AgentConfig::builder(format!("agent{}", i))
+                .system_prompt("You help")
+                .build(),
+        )
+        .with_client(mock.clone());
+        builder = builder.add_step(Box::new(AgentStep::from_agent(
+            agent,
+            format!("agent{}", i),
+        )));
+    }
+    builder.initial_input(json!("go"))
+}
+
+fn responses(n: usize) -> Arc<MockLlmClient> {
+    Arc::new(MockLlmClient::with_responses_vec(vec!["ok"; n]))
+}
+
+fn is_lifecycle(event: &Event) -> bool {
+    matches!(event.scope, EventScope::Workflow | EventScope::WorkflowStep)
+}
+
+#[tokio::test]
+async fn test_ratio_decision_is_deterministic_by_run_id() {
+    let policy = SamplingPolicy::ratio(0.5).with_seed(42);
+    let runtime = Runtime::new().with_trace_sampling(policy.clone());
+
+    // Runs of one named workflow are sampled at roughly the ratio
+    let mut runs = Vec::new();
+    for _ in 0..200 {
+        let run = runtime
+            .execute(agent_workflow("orders", responses(1), 1).build())
+            .await;
+        let sampled = run.trace.unwrap().sampled;
+        assert_eq!(
+            sampled,
+            policy.decide(&run.run_id, &[]) == SamplingReason::Ratio
+        );
+        runs.push((run.run_id, sampled));
+    }
+    let sampled = runs.iter().filter(|(_, sampled)| *sampled).count();
+    assert!((70..130).contains(&sampled), "sampled {}", sampled);
+
+    // The same run gets the same decision again, e.g. when resumed
+    let runtime = Runtime::new().with_trace_sampling(policy);
+    for (run_id, sampled) in runs.iter().take(20) {
+        let mut workflow = agent_workflow("orders", responses(1), 1).build();
+        workflow.run_id = run_id.clone();
+        let run = runtime.execute(workflow).await;
+        assert_eq!(run.trace.unwrap().sampled, *sampled);
+    }
+}
+
+#[tokio::test]
+async fn test_unsampled_run_keeps_lifecycle_events_only() {
+    let runtime = Runtime::new().with_trace_sampling(SamplingPolicy::ratio(0.0));
+    let run = runtime
+        .execute(agent_workflow("quiet", responses(2), 2).build())
+        .await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    let trace = run.trace.unwrap();
+    assert!(!trace.sampled);
+    assert_eq!(trace.reason, SamplingReason::NotSampled);
+    assert!(trace.dropped_events > 0);
+
+    let events = settled_events(&runtime).await;
+    assert!(events.iter().all(is_lifecycle));
+    let kinds: Vec<_> = events
+        .iter()
+        .map(|e| (e.scope.clone(), e.event_type.clone()))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (EventScope::Workflow, EventType::Started),
+            (EventScope::WorkflowStep, EventType::Started),
+            (EventScope::WorkflowStep, EventType::Completed),
+            (EventScope::WorkflowStep, EventType::Started),
+            (EventScope::WorkflowStep, EventType::Completed),
+            (EventScope::Workflow, EventType::Completed),
+        ]
+    );
+
+    // The decision and policy are on the Started event
+    assert_eq!(events[0].data["trace"]["sampled"], false);
+    assert_eq!(events[0].data["trace"]["policy"]["ratio"], 0.0);
+
+    // Metrics count what was dropped
+    let metrics = runtime.event_stream().metrics().unwrap();
+    assert_eq!(metrics.count(&EventScope::Agent, &EventType::Started), 2);
+    assert_eq!(
+        metrics.count(&EventScope::LlmRequest, &EventType::Started),
+        2
+    );
+    assert_eq!(metrics.total as usize, events.len() + trace.dropped_events);
+}
+
+#[tokio::test]
+async fn test_always_sampled_label() {
+    let runtime = Runtime::new()
+        .with_trace_sampling(SamplingPolicy::ratio(0.0).always_sample_label("canary"));
+    let run = runtime
+        .execute(
+            agent_workflow("canary_run", responses(1), 1)
+                .label("canary")
+                .build(),
+        )
+        .await;
+
+    let trace = run.trace.unwrap();
+    assert!(trace.sampled);
+    assert_eq!(
+        trace.reason,
+        SamplingReason::Label {
+            label: "canary".to_string()
+        }
+    );
+    let events = settled_events(&runtime).await;
+    assert!(events.iter().any(|e| e.scope == EventScope::LlmRequest));
+}
+
+#[tokio::test]
+async fn test_failed_run_is_upgraded_with_buffered_detail() {
+    let runtime = Runtime::new()
+        .with_trace_sampling(SamplingPolicy::ratio(0.0).with_tail(TailSampling::new()));
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec!["ok", "ok"]).error_on_call(1));
+    let run = runtime
+        .execute(agent_workflow("failing", mock, 2).build())
+        .await;
+
+    assert_eq!(run.state, WorkflowState::Failed);
+    let trace = run.trace.unwrap();
+    assert!(trace.sampled);
+    assert_eq!(trace.reason, SamplingReason::Failed);
+    assert!(trace.buffered_events > 0);
+    assert_eq!(trace.evicted_events, 0);
+
+    let events = settled_events(&runtime).await;
+    let failed_at = events
+        .iter()
+        .position(|e| e.scope == EventScope::Workflow && e.event_type == EventType::Failed)
+        .unwrap();
+    let detail: Vec<_> = events.iter().filter(|e| !is_lifecycle(e)).collect();
+    assert_eq!(detail.len(), trace.buffered_events);
+    assert!(events[failed_at + 1..].iter().all(|e| !is_lifecycle(e)));
+    assert!(detail
+        .iter()
+        .any(|e| e.scope == EventScope::Agent && e.event_type == EventType::Failed));
+    assert!(detail
+        .iter()
+        .any(|e| e.component_id == "agent0" && e.event_type == EventType::Started));
+
+    // Offsets stay unique and ordered even for late deliveries
+    let offsets: Vec<_> = events.iter().map(|e| e.offset).collect();
+    assert!(offsets.windows(2).all(|w| w[0] < w[1]));
+}
+
+#[tokio::test]
+async fn test_successful_unsampled_run_drops_buffer() {
+    let runtime = Runtime::new()
+        .with_trace_sampling(SamplingPolicy::ratio(0.0).with_tail(TailSampling::new()));
+    let run = runtime
+        .execute(agent_workflow("fine", responses(1), 1).build())
+        .await;
+
+    let trace = run.trace.unwrap();
+    assert!(!trace.sampled);
+    assert_eq!(trace.buffered_events, 0);
+    assert!(trace.dropped_events > 0);
+    assert!(settled_events(&runtime).await.iter().all(is_lifecycle));
+}
+
+#[tokio::test]
+async fn test_memory_cap_evicts_oldest_detail() {
+    let tail = TailSampling::new().with_max_buffered_bytes(1_200);
+    let runtime = Runtime::new().with_trace_sampling(SamplingPolicy::ratio(0.0).with_tail(tail));
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec!["ok"; 4]).error_on_call(4));
+    let run = runtime
+        .execute(agent_workflow("capped", mock, 5).build())
+        .await;
+
+    let trace = run.trace.unwrap();
+    assert_eq!(trace.reason, SamplingReason::Failed);
+    assert!(trace.evicted_events > 0);
+
+    let events = settled_events(&runtime).await;
+    let detail: Vec<_> = events.iter().filter(|e| !is_lifecycle(e)).collect();
+    assert_eq!(detail.len(), trace.buffered_events);
+    // The newest detail survives, the oldest is gone
+    assert!(!detail.iter().any(|e| e.component_id == "agent0"));
+    assert!(detail
+        .iter()
+        .any(|e| e.component_id == "agent4" && e.event_type == EventType::Failed));
+
+    let metrics = runtime.event_stream().metrics().unwrap();
+    assert_eq!(metrics.evicted as usize, trace.evicted_events);
+    assert_eq!(metrics.upgraded_runs, 1);
+}
+
+struct SlowStep;
+
+#[async_trait]
+impl Step for SlowStep {
+    async fn execute_with_context(
+        &self,
+        input: StepInput,
+        ctx: ExecutionContext<'_>,
+    ) -> StepResult {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        if let Some(stream) = ctx.event_stream {
+            stream.append(
+                EventScope::System,
+                EventType::Progress,
+                "system:slow_step".to_string(),
+                ComponentStatus::Running,
+                input.metadata.workflow_id.clone(),
+                None,
+                json!({}),
+            );
+        }
+        Ok(StepOutput {
+            data: input.data,
+            metadata: step::StepOutputMetadata {
+                step_name: "slow".to_string(),
+                step_type: StepType::Custom("slow".to_string()),
+                execution_time_ms: 5_000,
+                critic: None,
+                iterations_run: None,
+                policy: None,
+                item_errors: None,
+                handoffs: None,
+            },
+        })
+    }
+
+    fn name(&self) -> &str {
+        "slow"
+    }
+
+    fn step_type(&self) -> StepType {
+        StepType::Custom("slow".to_string())
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_slow_run_is_upgraded() {
+    let tail = TailSampling::new().with_slow_run(Duration::from_secs(2));
+    let runtime = Runtime::new().with_trace_sampling(SamplingPolicy::ratio(0.0).with_tail(tail));
+    let run = runtime
+        .execute(
+            Workflow::builder()
+                .name("slow".to_string())
+                .step(Box::new(SlowStep))
+                .build(),
+        )
+        .await;
+
+    let trace = run.trace.unwrap();
+    assert!(trace.sampled);
+    assert!(matches!(
+        trace.reason,
+        SamplingReason::SlowRun {
+            threshold_ms: 2_000,
+            ..
+        }
+    ));
+    let events = settled_events(&runtime).await;
+    assert!(events.iter().any(|e| e.component_id == "system:slow_step"));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_concurrent_runs_of_a_workflow_are_traced_apart() {
+    let runtime = Runtime::new()
+        .with_trace_sampling(SamplingPolicy::ratio(0.0).with_tail(TailSampling::new()));
+    // Both runs are in flight while the first one fails
+    let twin = |mock: Arc<MockLlmClient>| {
+        let agent = Agent::new(AgentConfig::builder("agent0").build()).with_client(mock);
+        Workflow::builder()
+            .name("twins".to_string())
+            .step(Box::new(SlowStep))
+            .add_step(Box::new(AgentStep::from_agent(agent, "agent0".to_string())))
+            .initial_input(json!("go"))
+            .build()
+    };
+    let failing = twin(Arc::new(MockLlmClient::new().error_on_call(0)));
+    let fine = twin(responses(1));
+
+    let (failed, finished) = tokio::join!(runtime.execute(failing), runtime.execute(fine));
+
+    assert_eq!(failed.state, WorkflowState::Failed);
+    let upgraded = failed.trace.unwrap();
+    assert_eq!(upgraded.reason, SamplingReason::Failed);
+    assert!(upgraded.buffered_events > 0);
+    assert_eq!(upgraded.dropped_events, 0);
+
+    assert_eq!(finished.state, WorkflowState::Completed);
+    let dropped = finished.trace.unwrap();
+    assert!(!dropped.sampled);
+    assert!(dropped.dropped_events > 0);
+}
@@ -0,0 +1,213 @@
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::prelude::TypesToolError as ToolError;
+use agent_runtime::runtime::Runtime;
+use agent_runtime::*;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// A writer the test can read back
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Captured {
+    /// The span close lines written so far, as JSON
+    fn closed_spans(&self) -> Vec<Value> {
+        String::from_utf8(self.0.lock().unwrap().clone())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .filter(|line| line["fields"]["message"] == "close")
+            .collect()
+    }
+}
+
+fn closed<'a>(spans: &'a [Value], name: &str) -> Vec<&'a Value> {
+    spans.iter().filter(|s| s["span"]["name"] == name).collect()
+}
+
+fn research_workflow(tool: NativeTool, mock: MockLlmClient) -> Workflow {
+    let mut registry = ToolRegistry::new();
+    registry.register(tool);
+    let agent = Agent::new(
+        AgentConfig::builder("researcher")
+            .tools(Arc::new(registry))
+            .build(),
+    )
+    .with_client(Arc::new(mock));
+    Workflow::builder()
+        .name("research".to_string())
+        .step(Box::new(AgentStep::from_agent(
+            agent,
+            "research".to_string(),
+        )))
+        .initial_input(json!("Moons of Mars"))
+        .build()
+}
+
+#[tokio::test]
+async fn test_workflow_steps_llm_requests_and_tools_get_spans() {
+    let captured = Captured::default();
+    let _guard = tracing::subscriber::set_default(
+        TraceSubscriber::new("info")
+            .with_json(true)
+            .with_writer(captured.clone()),
+    );
+
+    let lookup = NativeTool::new(
+        "lookup",
+        "Look something up",
+        json!({"type": "object", "properties": {"q": {"type": "string"}}}),
+        |_params| async { Ok(ToolResult::success(json!({"moons": 2}), 1.0)) },
+    );
+    let mock = MockLlmClient::new()
+        .with_tool_call("lookup", json!({"q": "mars"}))
+        .with_response("Mars has two moons");
+    let workflow = research_workflow(lookup, mock);
+    let workflow_id = workflow.id.clone();
+    let run = Runtime::new().execute(workflow).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+
+    let spans = captured.closed_spans();
+    let workflow_span = &closed(&spans, "workflow.execute")[0]["span"];
+    assert_eq!(workflow_span["workflow_id"], workflow_id.as_str());
+    assert_eq!(workflow_span["state"], "Completed");
+    assert_eq!(workflow_span["total_tokens"], 30);
+    assert!(workflow_span["duration_ms"].as_f64().is_some());
+    assert!(workflow_span.get("error").is_none());
+
+    let step = closed(&spans, "workflow.step")[0];
+    assert_eq!(step["span"]["step"], "research");
+    assert_eq!(step["span"]["step_type"], "Agent");
+    assert_eq!(step["span"]["step_index"], 0);
+    assert_eq!(step["spans"][0]["name"], "workflow.execute");
+
+    let requests = closed(&spans, "llm.request");
+    assert_eq!(requests.len(), 2);
+    for (iteration, request) in requests.iter().enumerate() {
+        let span = &request["span"];
+        assert_eq!(span["agent"], "researcher");
+        assert_eq!(span["provider"], "mock");
+        assert_eq!(span["model"], "mock-model");
+        assert_eq!(span["iteration"], iteration as u64 + 1);
+        assert_eq!(span["prompt_tokens"], 10);
+        assert_eq!(span["completion_tokens"], 5);
+        assert_eq!(span["attempts"], 1);
+        let names: Vec<&str> = request["spans"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["workflow.execute", "workflow.step", "llm.request"]);
+    }
+    assert_eq!(requests[0]["span"]["finish_reason"], "tool_calls");
+    assert_eq!(requests[1]["span"]["finish_reason"], "stop");
+
+    let tool = &closed(&spans, "tool.call")[0]["span"];
+    assert_eq!(tool["tool"], "lookup");
+    assert_eq!(tool["agent"], "researcher");
+    assert!(tool["tool_call_id"].is_string());
+    assert!(tool.get("otel.status_code").is_none());
+}
+
+#[tokio::test]
+async fn test_failed_tools_and_steps_record_the_error() {
+    let captured = Captured::default();
+    let _guard = tracing::subscriber::set_default(
+        TraceSubscriber::new("info")
+            .with_json(true)
+            .with_writer(captured.clone()),
+    );
+
+    let broken = NativeTool::new(
+        "lookup",
+        "Look something up",
+        json!({"type": "object", "properties": {"q": {"type": "string"}}}),
+        |_params| async { Err(ToolError::ExecutionFailed("index offline".to_string())) },
+    );
+    // The LLM request after the tool call fails, failing the step
+    let mock = MockLlmClient::new()
+        .with_tool_call("lookup", json!({"q": "mars"}))
+        .error_on_call(1);
+    let run = Runtime::new()
+        .execute(research_workflow(broken, mock))
+        .await;
+    assert_eq!(run.state, WorkflowState::Failed);
+
+    let spans = captured.closed_spans();
+    let tool = &closed(&spans, "tool.call")[0]["span"];
+    assert_eq!(tool["otel.status_code"], "ERROR");
+    assert!(tool["error"].as_str().unwrap().contains("index offline"));
+
+    let request = &closed(&spans, "llm.request")[1]["span"];
+    assert_eq!(request["otel.status_code"], "ERROR");
+    assert!(request["error"]
+        .as_str()
+        .unwrap()
+        .contains("Mock network error"));
+
+    let step = &closed(&spans, "workflow.step")[0]["span"];
+    assert_eq!(step["otel.status_code"], "ERROR");
+    let workflow_span = &closed(&spans, "workflow.execute")[0]["span"];
+    assert_eq!(workflow_span["state"], "Failed");
+    assert_eq!(workflow_span["error"], step["error"]);
+}
+
+#[tokio::test]
+async fn test_text_output_and_filtering() {
+    let lookup = || {
+        NativeTool::new(
+            "lookup",
+            "Look something up",
+            json!({"type": "object"}),
+            |_params| async { Ok(ToolResult::success(json!({}), 1.0)) },
+        )
+    };
+
+    let captured = Captured::default();
+    {
+        let _guard = tracing::subscriber::set_default(
+            TraceSubscriber::new("warn,agent_runtime=info").with_writer(captured.clone()),
+        );
+        let mock = MockLlmClient::new().with_response("Two");
+        let run = Runtime::new()
+            .execute(research_workflow(lookup(), mock))
+            .await;
+        assert_eq!(run.state, WorkflowState::Completed);
+    }
+    let text = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+    assert!(text.contains(" workflow.execute{"), "{}", text);
+    assert!(
+        text.contains(": workflow.step{step=research step_index=0 step_type=Agent"),
+        "{}",
+        text
+    );
+    assert!(text.contains("llm.request{agent=researcher"), "{}", text);
+    assert!(text.contains("state=Completed"), "{}", text);
+    assert!(text.contains(" close elapsed_ms="), "{}", text);
+
+    // Spans are info-level, so a warn filter drops them
+    let quiet = Captured::default();
+    {
+        let _guard = tracing::subscriber::set_default(
+            TraceSubscriber::new("info,agent_runtime=warn").with_writer(quiet.clone()),
+        );
+        let mock = MockLlmClient::new().with_response("Two");
+        Runtime::new()
+            .execute(research_workflow(lookup(), mock))
+            .await;
+    }
+    assert!(quiet.0.lock().unwrap().is_empty());
+}
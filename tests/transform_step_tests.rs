@@ -0,0 +1,180 @@
+use agent_runtime::runtime::Runtime;
+use agent_runtime::*;
+use serde_json::{json, Value};
+
+/// Run `step` alone on `input`
+async fn run(step: Box<dyn workflow::Step>, input: Value) -> workflow::WorkflowRun {
+    let workflow = Workflow::builder()
+        .name("transform".to_string())
+        .step(step)
+        .initial_input(input)
+        .build();
+    Runtime::new().execute(workflow).await
+}
+
+fn order() -> Value {
+    json!({
+        "order": {
+            "id": "A-7",
+            "customer": { "name": "Ada", "tier": "gold" },
+            "items": [{ "sku": "X1", "qty": 2 }]
+        },
+        "source": "web"
+    })
+}
+
+fn parse_total() -> TryTransformStep {
+    TryTransformStep::new("parse_total".to_string(), |data| {
+        Box::pin(async move {
+            tokio::task::yield_now().await;
+            let total = data["total"]
+                .as_str()
+                .and_then(|total| total.parse::<f64>().ok())
+                .ok_or_else(|| {
+                    StepError::InvalidInput(format!("total is not a number: {}", data["total"]))
+                })?;
+            Ok(json!({ "total": total }))
+        })
+    })
+}
+
+#[tokio::test]
+async fn test_async_transform_failure_fails_the_step() {
+    let parsed = run(Box::new(parse_total()), json!({ "total": "12.5" })).await;
+    assert_eq!(parsed.state, WorkflowState::Completed);
+    assert_eq!(parsed.final_output, Some(json!({ "total": 12.5 })));
+
+    let run = run(Box::new(parse_total()), json!({ "total": "twelve" })).await;
+    assert_eq!(run.state, WorkflowState::Failed);
+    let failure = run.failure.unwrap();
+    assert_eq!(failure.step_name, "parse_total");
+    assert_eq!(
+        failure.error.to_string(),
+        "Invalid input: total is not a number: \"twelve\""
+    );
+}
+
+#[tokio::test]
+async fn test_pick_nested_values() {
+    let picked = run(
+        Box::new(TransformStep::pick(
+            "customer".to_string(),
+            "/order/customer",
+        )),
+        order(),
+    )
+    .await;
+    assert_eq!(
+        picked.final_output,
+        Some(json!({ "name": "Ada", "tier": "gold" }))
+    );
+
+    let sku = run(
+        Box::new(TransformStep::pick("sku".to_string(), "/order/items/0/sku")),
+        order(),
+    )
+    .await;
+    assert_eq!(sku.final_output, Some(json!("X1")));
+
+    let missing = run(
+        Box::new(TransformStep::pick(
+            "email".to_string(),
+            "/order/customer/email",
+        )),
+        order(),
+    )
+    .await;
+    assert_eq!(missing.state, WorkflowState::Failed);
+    assert_eq!(
+        missing.failure.unwrap().error.to_string(),
+        "Invalid input: no value at '/order/customer/email'"
+    );
+}
+
+#[tokio::test]
+async fn test_merge_deep_merges_objects() {
+    let merged = run(
+        Box::new(TransformStep::merge(
+            "defaults".to_string(),
+            json!({
+                "order": { "customer": { "tier": "platinum", "locale": "en" }, "items": [] },
+                "channel": "email"
+            }),
+        )),
+        order(),
+    )
+    .await;
+    assert_eq!(
+        merged.final_output,
+        Some(json!({
+            "order": {
+                "id": "A-7",
+                "customer": { "name": "Ada", "tier": "platinum", "locale": "en" },
+                "items": []
+            },
+            "source": "web",
+            "channel": "email"
+        }))
+    );
+
+    let not_object = run(
+        Box::new(TransformStep::merge("defaults".to_string(), json!({}))),
+        json!(["a"]),
+    )
+    .await;
+    assert_eq!(
+        not_object.failure.unwrap().error.to_string(),
+        "Invalid input: merge needs an object at '', got an array"
+    );
+}
+
+#[tokio::test]
+async fn test_rename_keys_top_level_and_nested() {
+    let renamed = run(
+        Box::new(TransformStep::rename_keys(
+            "rename".to_string(),
+            [("source", "channel"), ("/order/customer/name", "full_name")],
+        )),
+        order(),
+    )
+    .await;
+    assert_eq!(
+        renamed.final_output,
+        Some(json!({
+            "order": {
+                "id": "A-7",
+                "customer": { "full_name": "Ada", "tier": "gold" },
+                "items": [{ "sku": "X1", "qty": 2 }]
+            },
+            "channel": "web"
+        }))
+    );
+
+    for (from, to, message) in [
+        (
+            "/order/customer/email",
+            "contact",
+            "Invalid input: no key at '/order/customer/email'",
+        ),
+        (
+            "/order/buyer/name",
+            "full_name",
+            "Invalid input: no object holding '/order/buyer/name'",
+        ),
+        (
+            "source",
+            "order",
+            "Invalid input: can't rename 'source' to 'order': the key exists",
+        ),
+    ] {
+        let failed = run(
+            Box::new(TransformStep::rename_keys(
+                "rename".to_string(),
+                [(from, to)],
+            )),
+            order(),
+        )
+        .await;
+        assert_eq!(failed.failure.unwrap().error.to_string(), message);
+    }
+}
@@ -0,0 +1,12 @@
+use agent_runtime::workflow;
+
+fn main() {
+    let _wf = workflow! {
+        steps: [
+            conditional "route" {
+                when: |_| true,
+                then: transform "a" => |data| data,
+            },
+        ],
+    };
+}
@@ -0,0 +1,10 @@
+use agent_runtime::workflow;
+
+fn main() {
+    let _wf = workflow! {
+        steps: [
+            transform "clean" => |data| data,
+            transform "clean" => |data| data,
+        ],
+    };
+}
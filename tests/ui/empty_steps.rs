@@ -0,0 +1,7 @@
+use agent_runtime::workflow;
+
+fn main() {
+    let _wf = workflow! {
+        steps: [],
+    };
+}
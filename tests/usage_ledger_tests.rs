@@ -0,0 +1,362 @@
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::usage::{CostPerMToken, GroupBy, UsageFilter, UsageRecord, DEFAULT_MAX_ENTRIES};
+use agent_runtime::*;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn agent(name: &str, mock: Arc<MockLlmClient>) -> Box<AgentStep> {
+    Box::new(AgentStep::from_agent(
+        Agent::new(AgentConfig::builder(name).system_prompt("You help").build()).with_client(mock),
+        name.to_string(),
+    ))
+}
+
+fn workflow(tenant: &str, agents: &[&str], mock: Arc<MockLlmClient>) -> Workflow {
+    let mut builder = Workflow::builder()
+        .name(format!("{}_job", tenant))
+        .label(format!("tenant:{}", tenant))
+        .initial_input(json!("go"));
+    for name in agents {
+        builder = builder.add_step(agent(name, mock.clone()));
+    }
+    builder.build()
+}
+
+fn answers(n: usize) -> Arc<MockLlmClient> {
+    Arc::new(MockLlmClient::with_responses_vec(vec!["ok"; n]))
+}
+
+fn runtime() -> Runtime {
+    Runtime::new().with_usage_ledger(
+        UsageLedger::new().with_price("mock-model", CostPerMToken::new(1.0, 2.0)),
+    )
+}
+
+#[tokio::test]
+async fn test_grouped_aggregation() {
+    let runtime = runtime();
+    runtime
+        .execute(workflow("acme", &["planner", "writer"], answers(2)))
+        .await;
+    runtime
+        .execute(workflow("globex", &["writer"], answers(1)))
+        .await;
+
+    let snapshot = runtime.usage_ledger().aggregate(
+        &UsageFilter::new(),
+        &[GroupBy::Label("tenant".into()), GroupBy::Agent],
+    );
+
+    assert_eq!(snapshot.as_of_seq, 3);
+    assert_eq!(snapshot.totals.llm_calls, 3);
+    assert_eq!(snapshot.totals.total_tokens, 45);
+    let keys: Vec<Vec<&str>> = snapshot
+        .groups
+        .iter()
+        .map(|g| g.key.iter().map(String::as_str).collect())
+        .collect();
+    assert_eq!(
+        keys,
+        vec![
+            vec!["acme", "planner"],
+            vec!["acme", "writer"],
+            vec!["globex", "writer"]
+        ]
+    );
+    for group in &snapshot.groups {
+        assert_eq!(group.totals.prompt_tokens, 10);
+        assert_eq!(group.totals.completion_tokens, 5);
+        // 10 * $1/M + 5 * $2/M
+        assert_eq!(group.totals.estimated_cost_usd, 0.00002);
+        assert_eq!(group.totals.unpriced_calls, 0);
+    }
+
+    let writers = runtime
+        .usage_ledger()
+        .aggregate(&UsageFilter::new().agent("writer"), &[GroupBy::Model]);
+    assert_eq!(writers.groups.len(), 1);
+    assert_eq!(writers.groups[0].key, vec!["mock-model"]);
+    assert_eq!(writers.groups[0].totals.llm_calls, 2);
+
+    let acme_today = runtime.usage_ledger().aggregate(
+        &UsageFilter::new()
+            .label("tenant:acme")
+            .since(chrono::Utc::now() - chrono::Duration::days(1)),
+        &[],
+    );
+    assert_eq!(acme_today.totals.llm_calls, 2);
+    assert!(acme_today.groups.is_empty());
+
+    let json: Vec<UsageRecord> =
+        serde_json::from_str(&runtime.usage_ledger().to_json(&UsageFilter::new()).unwrap())
+            .unwrap();
+    assert_eq!(json.len(), 3);
+    assert_eq!(json[2].labels, vec!["tenant:globex"]);
+}
+
+#[test]
+fn test_retention_evicts_and_spills() {
+    let spilled: Arc<Mutex<Vec<u64>>> = Arc::default();
+    let sink = spilled.clone();
+    let ledger = UsageLedger::new()
+        .with_max_entries(3)
+        .with_max_age(Duration::from_secs(3600))
+        .with_spill(move |records| sink.lock().unwrap().extend(records.iter().map(|r| r.seq)));
+
+    let old = chrono::Utc::now() - chrono::Duration::hours(2);
+    ledger.record(UsageRecord::new("run", "a", "m", None).at(old));
+    for _ in 0..4 {
+        ledger.record(UsageRecord::new("run", "a", "m", None));
+    }
+
+    let snapshot = ledger.aggregate(&UsageFilter::new(), &[]);
+    assert_eq!(snapshot.totals.llm_calls, 3);
+    assert_eq!(snapshot.evicted, 2);
+    assert_eq!((snapshot.first_seq, snapshot.as_of_seq), (3, 5));
+    assert_eq!(*spilled.lock().unwrap(), vec![1, 2]);
+
+    // Age-based eviction alone
+    let ledger = UsageLedger::new().with_max_age(Duration::from_secs(3600));
+    ledger.record(UsageRecord::new("run", "a", "m", None).at(old));
+    ledger.record(UsageRecord::new("run", "a", "m", None));
+    let records = ledger.records(&UsageFilter::new());
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].seq, 2);
+}
+
+#[test]
+fn test_retention_applies_while_nobody_reads() {
+    let spilled = Arc::new(Mutex::new(0usize));
+    let sink = spilled.clone();
+    let ledger = UsageLedger::new()
+        .with_max_entries(100)
+        .with_spill(move |records| *sink.lock().unwrap() += records.len());
+
+    for _ in 0..10_000 {
+        ledger.record(UsageRecord::new("run", "a", "m", None));
+    }
+    // Evicted as they were recorded, before any query
+    assert_eq!(*spilled.lock().unwrap(), 9_900);
+
+    let snapshot = ledger.aggregate(&UsageFilter::new(), &[]);
+    assert_eq!(snapshot.totals.llm_calls, 100);
+    assert_eq!((snapshot.first_seq, snapshot.as_of_seq), (9_901, 10_000));
+
+    // The runtime's default ledger is bounded as well
+    let runtime = Runtime::new();
+    for _ in 0..DEFAULT_MAX_ENTRIES + 10 {
+        runtime
+            .usage_ledger()
+            .record(UsageRecord::new("run", "a", "m", None));
+    }
+    let snapshot = runtime.usage_ledger().aggregate(&UsageFilter::new(), &[]);
+    assert_eq!(snapshot.totals.llm_calls, DEFAULT_MAX_ENTRIES as u64);
+    assert_eq!(snapshot.evicted, 10);
+}
+
+#[test]
+fn test_concurrent_appends_are_seen_as_a_growing_prefix() {
+    let ledger = UsageLedger::new();
+    let writers: Vec<_> = (0..8)
+        .map(|w| {
+            let ledger = ledger.clone();
+            std::thread::spawn(move || {
+                for _ in 0..250 {
+                    ledger.record(UsageRecord::new("run", format!("agent_{}", w), "m", None));
+                }
+            })
+        })
+        .collect();
+
+    let mut last = 0;
+    while writers.iter().any(|w| !w.is_finished()) {
+        let snapshot = ledger.aggregate(&UsageFilter::new(), &[GroupBy::Agent]);
+        assert!(snapshot.as_of_seq >= last);
+        assert_eq!(snapshot.totals.llm_calls, snapshot.as_of_seq);
+        let grouped: u64 = snapshot.groups.iter().map(|g| g.totals.llm_calls).sum();
+        assert_eq!(grouped, snapshot.totals.llm_calls);
+        last = snapshot.as_of_seq;
+    }
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    let records = ledger.records(&UsageFilter::new());
+    assert_eq!(records.len(), 2_000);
+    assert!(records
+        .windows(2)
+        .all(|pair| pair[1].seq == pair[0].seq + 1));
+}
+
+#[tokio::test]
+async fn test_ledger_reconciles_with_run_records() {
+    let runtime = Arc::new(runtime());
+    let shared = answers(16);
+
+    let mock = shared.clone();
+    let sub = SubWorkflowStep::new("sub".to_string(), move || {
+        workflow("initech", &["sub_agent"], mock.clone())
+    });
+    let nested = Workflow::builder()
+        .name("parent".to_string())
+        .add_step(agent("lead", shared.clone()))
+        .add_step(Box::new(sub))
+        .initial_input(json!("go"))
+        .build();
+    let failing = workflow(
+        "globex",
+        &["first", "second"],
+        Arc::new(MockLlmClient::with_responses_vec(vec!["ok", "ok"]).error_on_call(1)),
+    );
+
+    let runs = futures::future::join_all(vec![
+        runtime.execute(workflow("acme", &["a", "b", "c"], shared.clone())),
+        runtime.execute(nested),
+        runtime.execute(failing),
+    ])
+    .await;
+
+    assert_eq!(runs[2].state, WorkflowState::Failed);
+    assert_eq!(runs[2].usage.llm_calls, 1);
+    // The sub-workflow's call is on its own run, not the parent's
+    assert_eq!(runs[1].usage.llm_calls, 1);
+
+    let ledger = runtime.usage_ledger();
+    let mut sum = 0;
+    for run in &runs {
+        let snapshot = ledger.aggregate(&UsageFilter::new().workflow(&run.workflow_id), &[]);
+        assert_eq!(snapshot.totals, run.usage, "run {}", run.workflow_id);
+        sum += run.usage.total_tokens;
+    }
+    let all = ledger.aggregate(&UsageFilter::new(), &[GroupBy::Workflow]);
+    assert_eq!(all.totals.llm_calls, 6);
+    assert_eq!(all.groups.len(), 4);
+    assert_eq!(sum + 15, all.totals.total_tokens);
+}
+
+#[tokio::test]
+async fn test_breakdown_by_step_and_agent() {
+    let mut tools = ToolRegistry::new();
+    tools.register(NativeTool::new(
+        "lookup",
+        "Look something up",
+        json!({"type": "object"}),
+        |_| async { Ok(ToolResult::success(json!("found"), 0.0)) },
+    ));
+    let researcher = Agent::new(
+        AgentConfig::builder("researcher")
+            .tools(Arc::new(tools))
+            .build(),
+    )
+    .with_client(Arc::new(MockLlmClient::with_tool_then_text(
+        "lookup",
+        json!({}),
+        "done",
+    )));
+    let workflow = Workflow::builder()
+        .name("report".to_string())
+        .add_step(Box::new(AgentStep::from_agent(
+            researcher,
+            "research".to_string(),
+        )))
+        .add_step(agent("writer", answers(1)))
+        .add_step(agent("editor", answers(1)))
+        .initial_input(json!("go"))
+        .build();
+
+    let config: RuntimeConfig = toml::from_str(
+        r#"
+            [usage.prices.mock-model]
+            prompt = 1.0
+            completion = 2.0
+        "#,
+    )
+    .unwrap();
+    let runtime = Runtime::new().with_usage_ledger(UsageLedger::from_config(&config.usage));
+    let run = runtime.execute(workflow).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+
+    let usage = &run.usage_breakdown;
+    assert_eq!(usage.total.llm.llm_calls, 4);
+    assert_eq!(usage.total.llm.total_tokens, 60);
+    assert_eq!(usage.total.tool_calls, 1);
+    assert_eq!(usage.total.llm, run.usage);
+    assert_eq!(usage.total.llm.unpriced_calls, 0);
+
+    let steps: Vec<(&str, u64, u64)> = usage
+        .steps
+        .iter()
+        .map(|s| {
+            (
+                s.step_name.as_str(),
+                s.usage.llm.llm_calls,
+                s.usage.tool_calls,
+            )
+        })
+        .collect();
+    assert_eq!(
+        steps,
+        vec![("research", 2, 1), ("writer", 1, 0), ("editor", 1, 0)]
+    );
+    let step_tokens: u64 = usage.steps.iter().map(|s| s.usage.llm.total_tokens).sum();
+    assert_eq!(step_tokens, usage.total.llm.total_tokens);
+
+    let researcher = usage.agent("researcher").unwrap();
+    assert_eq!(researcher.llm.llm_calls, 2);
+    assert_eq!(researcher.tool_calls, 1);
+    assert_eq!(usage.agent("writer").unwrap().llm.prompt_tokens, 10);
+}
+
+#[tokio::test]
+async fn test_agent_output_reports_its_turn() {
+    let agent = Agent::new(AgentConfig::builder("solo").build())
+        .with_client(Arc::new(MockLlmClient::with_responses_vec(vec!["ok"])));
+    let output = agent
+        .execute(&AgentInput::from_value(json!("go")))
+        .await
+        .unwrap();
+
+    let usage = &output.metadata.usage;
+    assert_eq!(usage.llm_calls, 1);
+    assert_eq!(usage.total_tokens, 15);
+    // Prices live on the runtime; outside a run nothing is priced
+    assert_eq!(usage.unpriced_calls, 1);
+}
+
+#[tokio::test]
+async fn test_sub_workflow_usage_rolls_up_into_parent() {
+    let runtime = runtime();
+    let mock = answers(3);
+    let inner = mock.clone();
+    let sub = SubWorkflowStep::new("delegate".to_string(), move || {
+        workflow("initech", &["helper_a", "helper_b"], inner.clone())
+    });
+    let parent = Workflow::builder()
+        .name("parent".to_string())
+        .add_step(agent("lead", mock.clone()))
+        .add_step(Box::new(sub))
+        .initial_input(json!("go"))
+        .build();
+
+    let run = runtime.execute(parent).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+
+    // The run's own calls stay separate ...
+    assert_eq!(run.usage.llm_calls, 1);
+
+    // ... but the breakdown includes the sub-workflow's
+    let usage = &run.usage_breakdown;
+    assert_eq!(usage.total.llm.llm_calls, 3);
+    assert_eq!(usage.total.llm.total_tokens, 45);
+    assert_eq!(usage.total.llm.estimated_cost_usd, 3.0 * 0.00002);
+    assert_eq!(usage.step(0).unwrap().usage.llm.llm_calls, 1);
+    let delegate = usage.step(1).unwrap();
+    assert_eq!(delegate.step_name, "delegate");
+    assert_eq!(delegate.usage.llm.llm_calls, 2);
+    let agents: Vec<&str> = usage.agents.keys().map(String::as_str).collect();
+    assert_eq!(agents, vec!["helper_a", "helper_b", "lead"]);
+
+    let all = runtime.usage_ledger().aggregate(&UsageFilter::new(), &[]);
+    assert_eq!(all.totals, usage.total.llm);
+}
@@ -0,0 +1,172 @@
+use agent_runtime::runtime::Runtime;
+use agent_runtime::*;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+fn workflow(step: WaitStep) -> Workflow {
+    Workflow::builder()
+        .name("export".to_string())
+        .step(Box::new(step))
+        .initial_input(json!({ "job": "export-17" }))
+        .build()
+}
+
+/// Checks a job that's done on attempt `done_on`, counting the checks
+fn check_job(done_on: usize) -> (Box<dyn workflow::Step>, Arc<AtomicUsize>) {
+    let checks = Arc::new(AtomicUsize::new(0));
+    let counter = checks.clone();
+    let step = TransformStep::new("check_job".to_string(), move |input| {
+        let check = counter.fetch_add(1, Ordering::SeqCst) + 1;
+        let status = if check >= done_on { "done" } else { "running" };
+        json!({ "job": input["job"], "status": status, "check": check })
+    });
+    (Box::new(step), checks)
+}
+
+fn poll(inner: Box<dyn workflow::Step>, max_attempts: usize) -> WaitStep {
+    WaitStep::poll(
+        "await_export".to_string(),
+        inner,
+        Duration::from_secs(30),
+        max_attempts,
+        |output| output["status"] == "done",
+    )
+}
+
+async fn progress(runtime: &Runtime) -> Vec<Value> {
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .filter(|e| e.scope == EventScope::WorkflowStep && e.event_type == EventType::Progress)
+        .map(|e| e.data)
+        .collect()
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_fixed_delay_passes_its_input_on() {
+    let runtime = Runtime::new();
+    let started = Instant::now();
+
+    let run = runtime
+        .execute(workflow(WaitStep::delay(
+            "cool_down".to_string(),
+            Duration::from_secs(60),
+        )))
+        .await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert!(started.elapsed() >= Duration::from_secs(60));
+    assert_eq!(run.steps[0].step_type, "Wait");
+    assert_eq!(run.final_output, Some(json!({ "job": "export-17" })));
+
+    // The time left, every ten seconds
+    let remaining: Vec<Value> = progress(&runtime)
+        .await
+        .into_iter()
+        .map(|d| d["remaining_ms"].clone())
+        .collect();
+    assert_eq!(
+        remaining,
+        [60_000, 50_000, 40_000, 30_000, 20_000, 10_000].map(Value::from)
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_wait_until_a_time() {
+    let runtime = Runtime::new();
+    let started = Instant::now();
+    let at = chrono::Utc::now() + chrono::Duration::seconds(25);
+
+    let run = runtime
+        .execute(workflow(WaitStep::until("open_window".to_string(), at)))
+        .await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    let waited = started.elapsed();
+    assert!(waited > Duration::from_secs(24) && waited <= Duration::from_secs(25));
+
+    // A time already past doesn't wait
+    let started = Instant::now();
+    let past = chrono::Utc::now() - chrono::Duration::seconds(5);
+    let run = runtime
+        .execute(workflow(WaitStep::until("open_window".to_string(), past)))
+        .await;
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_poll_succeeds_on_the_third_attempt() {
+    let runtime = Runtime::new();
+    let (inner, checks) = check_job(3);
+    let started = Instant::now();
+
+    let run = runtime.execute(workflow(poll(inner, 5))).await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(checks.load(Ordering::SeqCst), 3);
+    assert!(started.elapsed() >= Duration::from_secs(60));
+    // The inner step's output is the wait's
+    assert_eq!(
+        run.final_output,
+        Some(json!({ "job": "export-17", "status": "done", "check": 3 }))
+    );
+
+    // Three reports for each wait between checks
+    let events = progress(&runtime).await;
+    assert_eq!(events.len(), 6);
+    assert_eq!(events[0]["attempt"], 1);
+    assert_eq!(events[0]["max_attempts"], 5);
+    assert_eq!(events[0]["remaining_ms"], 30_000);
+    assert_eq!(events[3]["attempt"], 2);
+    assert_eq!(events[5]["remaining_ms"], 10_000);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_poll_exhaustion() {
+    let runtime = Runtime::new();
+    let (inner, checks) = check_job(usize::MAX);
+
+    let run = runtime.execute(workflow(poll(inner, 3))).await;
+
+    assert_eq!(run.state, WorkflowState::Failed);
+    assert_eq!(checks.load(Ordering::SeqCst), 3);
+    let failure = run.failure.unwrap();
+    assert_eq!(failure.step_name, "await_export");
+    assert_eq!(
+        failure.error.to_string(),
+        "Execution failed: wait 'await_export' polled 'check_job' 3 times without it being done"
+    );
+
+    // Or carry on with the last check
+    let (inner, _) = check_job(usize::MAX);
+    let step = poll(inner, 3).with_exhausted_mode(LoopExhaustedMode::PassThrough);
+    let run = runtime.execute(workflow(step)).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(run.final_output.unwrap()["check"], 3);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_cancel_mid_wait() {
+    let runtime = Runtime::new();
+    let started = Instant::now();
+    let (handle, run) = runtime.execute_cancellable(workflow(WaitStep::delay(
+        "cool_down".to_string(),
+        Duration::from_secs(3600),
+    )));
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(95)).await;
+        handle.cancel();
+    });
+
+    let run = run.await;
+
+    assert_eq!(run.state, WorkflowState::Canceled);
+    let waited = started.elapsed();
+    assert!(waited >= Duration::from_secs(95) && waited < Duration::from_secs(96));
+}
@@ -0,0 +1,256 @@
+/// Tests for event webhooks against a local mock receiver
+use agent_runtime::event::webhook::{verify_signature, WebhookStatus};
+use agent_runtime::{
+    EventFilter, EventScope, EventStream, EventType, RetryPolicy, WebhookSubscriber,
+    WebhookSubscription,
+};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+mod common;
+use common::{serve_forever, Recorded, Reply};
+
+/// The `(workflow_id, component_id)` of each event in a delivery
+fn sources(delivery: &Recorded) -> Vec<(String, String)> {
+    delivery.body["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| {
+            (
+                e["workflow_id"].as_str().unwrap().to_string(),
+                e["component_id"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+/// Answer every delivery with `status`, recording what arrived
+async fn receiver(status: u16) -> (String, Arc<Mutex<Vec<Recorded>>>) {
+    let (url, received) = serve_forever(Reply::text("text/plain", "").with_status(status)).await;
+    (format!("{}/hooks", url), received)
+}
+
+/// Wait until `done` holds for the subscription's status
+async fn wait_for(
+    subscriber: &WebhookSubscriber,
+    id: &str,
+    done: impl Fn(&WebhookStatus) -> bool,
+) -> WebhookStatus {
+    for _ in 0..200 {
+        let status = subscriber.status(id).unwrap();
+        if done(&status) {
+            return status;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("webhook never reached the expected state");
+}
+
+#[tokio::test]
+async fn test_filter_selects_events() {
+    let (url, received) = receiver(200).await;
+    let stream = EventStream::new();
+    let subscriber = WebhookSubscriber::new();
+    let id = subscriber.subscribe(
+        WebhookSubscription::new(url)
+            .with_batching(Duration::from_millis(50), 100)
+            .with_filter(
+                EventFilter::new()
+                    .scope(EventScope::Tool)
+                    .event_type(EventType::Failed)
+                    .workflow("prod-*"),
+            ),
+    );
+    subscriber.attach(&stream);
+
+    stream.tool_failed("search", "prod-billing".into(), "timeout", json!({}));
+    stream.tool_failed("search", "dev-billing".into(), "timeout", json!({}));
+    stream.tool_completed("search", "prod-billing".into(), json!({}));
+    stream.agent_failed("writer", "prod-billing".into(), "boom", json!({}));
+    stream.tool_failed("fetch", "prod-search".into(), "404", json!({}));
+
+    wait_for(&subscriber, &id, |s| s.delivered_batches == 1).await;
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    let mut delivered = sources(&received[0]);
+    delivered.sort();
+    assert_eq!(
+        delivered,
+        vec![
+            ("prod-billing".to_string(), "search".to_string()),
+            ("prod-search".to_string(), "fetch".to_string()),
+        ]
+    );
+    assert_eq!(received[0].body["subscription"], id);
+}
+
+#[tokio::test]
+async fn test_batches_close_on_size_or_window() {
+    let (url, received) = receiver(200).await;
+    let stream = EventStream::new();
+    let subscriber = WebhookSubscriber::new();
+    let id = subscriber
+        .subscribe(WebhookSubscription::new(url).with_batching(Duration::from_millis(300), 3));
+    subscriber.attach(&stream);
+
+    for name in ["a", "b", "c", "d", "e"] {
+        stream.tool_started(name, "wf".into(), json!({}));
+    }
+
+    // Three fill the first batch; the other two wait out the window
+    let status = wait_for(&subscriber, &id, |s| s.delivered_batches == 1).await;
+    assert_eq!(status.deliveries[0].events, 3);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(subscriber.status(&id).unwrap().delivered_batches, 1);
+
+    wait_for(&subscriber, &id, |s| s.delivered_batches == 2).await;
+    let received = received.lock().unwrap();
+    let sizes: Vec<usize> = received
+        .iter()
+        .map(|r| r.body["events"].as_array().unwrap().len())
+        .collect();
+    assert_eq!(sizes, vec![3, 2]);
+
+    // Each batch carries its own idempotency key, matching the body id
+    for delivery in received.iter() {
+        assert_eq!(delivery.headers["idempotency-key"], delivery.body["id"]);
+    }
+    assert_ne!(received[0].body["id"], received[1].body["id"]);
+}
+
+#[tokio::test]
+async fn test_signature_verifies() {
+    let (url, received) = receiver(200).await;
+    let stream = EventStream::new();
+    let subscriber = WebhookSubscriber::new();
+    let id = subscriber.subscribe(
+        WebhookSubscription::new(url)
+            .with_secret("whsec_test")
+            .with_batching(Duration::from_millis(20), 100),
+    );
+    subscriber.attach(&stream);
+
+    stream.workflow_completed("prod-billing", json!({"steps": 3}));
+    wait_for(&subscriber, &id, |s| s.delivered_batches == 1).await;
+
+    let received = received.lock().unwrap();
+    let signature = &received[0].headers["x-webhook-signature"];
+    assert!(signature.starts_with("sha256="));
+    assert!(verify_signature(
+        "whsec_test",
+        &received[0].raw_body,
+        signature
+    ));
+    assert!(!verify_signature("wrong", &received[0].raw_body, signature));
+
+    let mut tampered = received[0].raw_body.clone();
+    tampered.pop();
+    assert!(!verify_signature("whsec_test", &tampered, signature));
+}
+
+#[tokio::test]
+async fn test_failing_target_is_retried_then_dead_lettered() {
+    let (url, received) = receiver(503).await;
+    let stream = EventStream::new();
+    let subscriber = WebhookSubscriber::new();
+    let mut retry = RetryPolicy::new(2, Duration::from_millis(10));
+    retry.jitter_factor = 0.0;
+    let id = subscriber.subscribe(
+        WebhookSubscription::new(url)
+            .with_batching(Duration::from_millis(20), 100)
+            .with_retry(retry),
+    );
+    subscriber.attach(&stream);
+
+    stream.tool_failed("search", "prod-billing".into(), "timeout", json!({}));
+    let status = wait_for(&subscriber, &id, |s| !s.dead_letters.is_empty()).await;
+
+    assert_eq!(status.delivered_batches, 0);
+    assert_eq!(status.queued, 0);
+    let attempts: Vec<(u32, Option<u16>, bool)> = status
+        .deliveries
+        .iter()
+        .map(|d| (d.attempt, d.status, d.delivered))
+        .collect();
+    assert_eq!(
+        attempts,
+        vec![
+            (1, Some(503), false),
+            (2, Some(503), false),
+            (3, Some(503), false)
+        ]
+    );
+    let dead = &status.dead_letters[0];
+    assert_eq!(dead.attempts, 3);
+    assert_eq!(dead.batch.events.len(), 1);
+    assert!(dead.reason.contains("gave up after 3 attempts"));
+
+    // Every retry reuses the batch's idempotency key
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 3);
+    assert!(received
+        .iter()
+        .all(|r| r.headers["idempotency-key"] == dead.batch.id));
+}
+
+#[tokio::test]
+async fn test_payloads_are_redacted() {
+    let (url, received) = receiver(200).await;
+    let stream = EventStream::new();
+    let subscriber = WebhookSubscriber::new();
+    let id = subscriber
+        .subscribe(WebhookSubscription::new(url).with_batching(Duration::from_millis(20), 100));
+    subscriber.attach(&stream);
+
+    let secret = "sk-live-4f9a8b7c6d5e4f3a2b1c";
+    stream.tool_failed(
+        "charge",
+        "prod-billing".into(),
+        &format!("card declined using key {}", secret),
+        json!({"request": {"api_key": "plain-value", "amount": 40}, "log": format!("key={}", secret)}),
+    );
+    wait_for(&subscriber, &id, |s| s.delivered_batches == 1).await;
+
+    let received = received.lock().unwrap();
+    let raw = String::from_utf8_lossy(&received[0].raw_body);
+    assert!(!raw.contains(secret));
+    assert!(!raw.contains("plain-value"));
+
+    let event = &received[0].body["events"][0];
+    assert_eq!(event["message"], "card declined using key [REDACTED]");
+    assert_eq!(
+        event["data"],
+        json!({"request": {"api_key": "[REDACTED]", "amount": 40}, "log": "key=[REDACTED]"})
+    );
+
+    // The stream itself is untouched unless it has its own rules
+    assert!(stream.all()[0].data["log"]
+        .as_str()
+        .unwrap()
+        .contains(secret));
+}
+
+#[tokio::test]
+async fn test_rate_limit_spaces_requests() {
+    let (url, received) = receiver(200).await;
+    let stream = EventStream::new();
+    let subscriber = WebhookSubscriber::new();
+    let id = subscriber.subscribe(
+        WebhookSubscription::new(url)
+            .with_batching(Duration::ZERO, 1)
+            .with_rate_limit(10),
+    );
+    subscriber.attach(&stream);
+
+    let started = std::time::Instant::now();
+    for name in ["a", "b", "c", "d"] {
+        stream.tool_started(name, "wf".into(), json!({}));
+    }
+    wait_for(&subscriber, &id, |s| s.delivered_batches == 4).await;
+
+    // At most one request per 100ms after the first
+    assert!(started.elapsed() >= Duration::from_millis(300));
+    assert_eq!(received.lock().unwrap().len(), 4);
+}
@@ -0,0 +1,224 @@
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::runtime::Runtime;
+use agent_runtime::*;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+fn tools() -> Arc<ToolRegistry> {
+    let mut registry = ToolRegistry::new();
+    for name in ["lookup_customer", "send_email"] {
+        registry.register(NativeTool::new(
+            name,
+            "Does nothing",
+            json!({ "type": "object", "properties": {} }),
+            |_| async { Ok(types::ToolResult::success(json!(null), 0.0)) },
+        ));
+    }
+    Arc::new(registry)
+}
+
+fn factory(mock: Arc<MockLlmClient>) -> WorkflowFactory {
+    WorkflowFactory::new(mock, tools())
+        .with_transform("parse_score", |v: Value| {
+            let score = v["response"].as_str().unwrap_or("0").trim().parse::<f64>();
+            json!({ "score": score.unwrap_or(0.0) })
+        })
+        .with_transform("archive", |v| json!({ "archived": v }))
+}
+
+fn tool_names(request: &llm::ChatRequest) -> Vec<String> {
+    request
+        .tools
+        .iter()
+        .flatten()
+        .map(|tool| tool["function"]["name"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_urgent_ticket_takes_the_then_branch() {
+    let mock = Arc::new(
+        MockLlmClient::new()
+            .with_response("0.9")
+            .with_response("Paging on-call: checkout is down"),
+    );
+    let definition = WorkflowDefinition::from_file(fixture("triage_workflow.yaml")).unwrap();
+    let workflow = definition.into_workflow(&factory(mock.clone())).unwrap();
+
+    let run = Runtime::new().execute(workflow).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+    let names: Vec<_> = run.steps.iter().map(|s| s.step_name.as_str()).collect();
+    assert_eq!(names, ["scorer", "extract", "route"]);
+    assert_eq!(run.steps[1].output, Some(json!({ "score": 0.9 })));
+    assert_eq!(
+        run.final_output.unwrap()["response"],
+        "Paging on-call: checkout is down"
+    );
+
+    // The scorer only gets the tools it lists; the escalation agent none
+    let calls = mock.get_calls();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(tool_names(&calls[0]), ["lookup_customer"]);
+    assert!(tool_names(&calls[1]).is_empty());
+    assert!(calls[1].messages[0]
+        .content
+        .text()
+        .contains("Draft a page for the on-call engineer."));
+}
+
+#[tokio::test]
+async fn test_routine_ticket_takes_the_else_branch() {
+    let mock = Arc::new(MockLlmClient::new().with_response("0.2"));
+    let definition = WorkflowDefinition::from_file(fixture("triage_workflow.yaml")).unwrap();
+    let workflow = definition.into_workflow(&factory(mock.clone())).unwrap();
+
+    let run = Runtime::new().execute(workflow).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(
+        run.final_output,
+        Some(json!({ "archived": { "score": 0.2 } }))
+    );
+    assert_eq!(mock.call_count(), 1);
+}
+
+#[tokio::test]
+async fn test_toml_definition_with_a_sub_workflow() {
+    let toml = r#"
+        name = "outer"
+        initial_input = { vip = true }
+
+        [[steps]]
+        type = "conditional"
+        name = "vip_check"
+        condition = "is_vip"
+        then = { type = "transform", name = "tag", transform = "tag" }
+        else = { type = "transform", name = "skip", transform = "archive" }
+
+        [[steps]]
+        type = "sub_workflow"
+        name = "notify"
+
+        [steps.workflow]
+        name = "inner"
+
+        [[steps.workflow.steps]]
+        type = "agent"
+        name = "notifier"
+        tools = ["send_email"]
+    "#;
+    let mock = Arc::new(MockLlmClient::new().with_response("sent"));
+    let factory = factory(mock.clone())
+        .with_transform("tag", |v| json!({ "tagged": v }))
+        .with_condition("is_vip", |v| v["vip"] == true);
+    let workflow = WorkflowDefinition::from_toml_str(toml)
+        .unwrap()
+        .into_workflow(&factory)
+        .unwrap();
+
+    let run = Runtime::new().execute(workflow).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(
+        run.steps[0].output,
+        Some(json!({ "tagged": { "vip": true } }))
+    );
+    assert_eq!(run.final_output.unwrap()["response"], "sent");
+    assert_eq!(tool_names(&mock.get_calls()[0]), ["send_email"]);
+}
+
+fn config_error(yaml: &str) -> error::ConfigError {
+    let factory = factory(Arc::new(MockLlmClient::new()));
+    match WorkflowDefinition::from_yaml_str(yaml)
+        .unwrap()
+        .into_workflow(&factory)
+    {
+        Ok(_) => panic!("expected a config error"),
+        Err(e) => e,
+    }
+}
+
+#[test]
+fn test_unknown_names_report_their_field() {
+    let error = config_error(
+        r#"
+        name: broken
+        steps:
+          - { type: agent, name: a, tools: [lookup_customer, shell] }
+        "#,
+    );
+    assert_eq!(error.code, error::ConfigErrorCode::InvalidValue);
+    assert_eq!(error.field.as_deref(), Some("steps[0].tools[1]"));
+    assert_eq!(error.message, "Unknown tool 'shell'");
+
+    let error = config_error(
+        r#"
+        name: broken
+        steps:
+          - type: conditional
+            name: route
+            condition: /score > 0.5
+            then: { type: transform, name: keep, transform: archive }
+            else: { type: transform, name: drop, transform: shred }
+        "#,
+    );
+    assert_eq!(error.field.as_deref(), Some("steps[0].else.transform"));
+    assert_eq!(error.message, "Unknown transform 'shred'");
+
+    let error = config_error(
+        r#"
+        name: broken
+        steps:
+          - type: sub_workflow
+            name: nested
+            workflow:
+              name: inner
+              steps:
+                - type: conditional
+                  name: route
+                  condition: is_weekend
+                  then: { type: transform, name: a, transform: archive }
+                  else: { type: transform, name: b, transform: archive }
+        "#,
+    );
+    assert_eq!(
+        error.field.as_deref(),
+        Some("steps[0].workflow.steps[0].condition")
+    );
+}
+
+#[test]
+fn test_invalid_definitions_are_config_errors() {
+    let error = config_error(
+        r#"
+        name: broken
+        steps:
+          - type: conditional
+            name: route
+            condition: /score ~ 0.5
+            then: { type: transform, name: a, transform: archive }
+            else: { type: transform, name: b, transform: archive }
+        "#,
+    );
+    assert_eq!(error.field.as_deref(), Some("steps[0].condition"));
+    assert!(error
+        .message
+        .starts_with("Invalid condition '/score ~ 0.5'"));
+
+    let error = config_error(
+        r#"
+        name: broken
+        steps:
+          - { type: transform, name: same, transform: archive }
+          - { type: transform, name: same, transform: archive }
+        "#,
+    );
+    assert_eq!(error.code, error::ConfigErrorCode::ValidationFailed);
+    assert_eq!(error.field.as_deref(), Some("steps[1]"));
+
+    let error = WorkflowDefinition::from_yaml_str("name: broken\nsteps:\n  - type: teleport\n")
+        .unwrap_err();
+    assert_eq!(error.code, error::ConfigErrorCode::ParseError);
+}
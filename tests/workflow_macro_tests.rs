@@ -0,0 +1,87 @@
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::*;
+use serde_json::json;
+use std::sync::Arc;
+
+fn agent(name: &str, mock: &Arc<MockLlmClient>) -> Agent {
+    Agent::new(AgentConfig::builder(name).system_prompt("You help").build())
+        .with_client(mock.clone())
+}
+
+fn is_urgent(data: &serde_json::Value) -> bool {
+    data["response"]
+        .as_str()
+        .unwrap_or_default()
+        .contains("urgent")
+}
+
+fn hand_written(mock: &Arc<MockLlmClient>) -> Workflow {
+    Workflow::builder()
+        .name("triage".to_string())
+        .label("tenant:acme")
+        .add_step(Box::new(AgentStep::from_agent(
+            agent("classifier", mock),
+            "classifier".to_string(),
+        )))
+        .add_step(Box::new(ConditionalStep::new(
+            "route".to_string(),
+            is_urgent,
+            Box::new(AgentStep::from_agent(
+                agent("pager", mock),
+                "pager".to_string(),
+            )),
+            Box::new(TransformStep::new("ticket".to_string(), |data| data)),
+        )))
+        .initial_input(json!("server down"))
+        .build()
+}
+
+fn declared(mock: &Arc<MockLlmClient>) -> Workflow {
+    workflow! {
+        name: "triage",
+        labels: ["tenant:acme"],
+        input: json!("server down"),
+        steps: [
+            agent "classifier" => agent("classifier", mock),
+            conditional "route" {
+                when: is_urgent,
+                then: agent "pager" => agent("pager", mock),
+                else: transform "ticket" => |data| data,
+            },
+        ],
+    }
+}
+
+fn mock() -> Arc<MockLlmClient> {
+    Arc::new(MockLlmClient::with_responses_vec(vec![
+        "urgent: server down",
+        "paged on-call",
+    ]))
+}
+
+#[tokio::test]
+async fn test_macro_matches_hand_written_builder() {
+    let (a, b) = (mock(), mock());
+    let expected = hand_written(&a);
+    let actual = declared(&b);
+
+    assert_eq!(actual.id, expected.id);
+    assert_eq!(actual.labels, expected.labels);
+    assert_eq!(actual.initial_input, expected.initial_input);
+    assert_eq!(actual.to_mermaid(), expected.to_mermaid());
+
+    let expected = Runtime::new().execute(expected).await;
+    let actual = Runtime::new().execute(actual).await;
+    assert_eq!(actual.state, WorkflowState::Completed);
+    assert_eq!(actual.final_output, expected.final_output);
+    let names = |run: &workflow::WorkflowRun| -> Vec<String> {
+        run.steps.iter().map(|s| s.step_name.clone()).collect()
+    };
+    assert_eq!(names(&actual), names(&expected));
+    assert_eq!(b.call_count(), 2);
+}
+
+#[test]
+fn test_invalid_workflows_fail_to_compile() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,182 @@
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::persist::PersistFormat;
+use agent_runtime::runtime::Runtime;
+use agent_runtime::*;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+fn agent_step(name: &str, mock: MockLlmClient) -> (Box<dyn Step>, Arc<MockLlmClient>) {
+    let mock = Arc::new(mock);
+    let agent = Agent::new(AgentConfig::builder(name).system_prompt("Work").build())
+        .with_client(mock.clone());
+    (
+        Box::new(AgentStep::from_agent(agent, name.to_string())),
+        mock,
+    )
+}
+
+/// The result the agent's `call`th LLM request got back from its tool call
+fn tool_result(mock: &MockLlmClient, call: usize) -> Value {
+    let request = &mock.get_calls()[call];
+    let message = request
+        .messages
+        .iter()
+        .rev()
+        .find(|m| m.role == agent_runtime::llm::types::Role::Tool)
+        .expect("a tool result");
+    serde_json::from_str(&message.content.text()).unwrap()
+}
+
+fn with_context() -> WorkflowBuilder {
+    Workflow::builder()
+        .name("memory".to_string())
+        .with_chat_history(Arc::new(SlidingWindowManager::new(50)))
+        .initial_input(json!("France"))
+}
+
+#[tokio::test]
+async fn test_later_agent_reads_what_an_earlier_one_stored() {
+    let (researcher, _) = agent_step(
+        "researcher",
+        MockLlmClient::new()
+            .with_tool_call("memory_set", json!({"key": "capital", "value": "Paris"}))
+            .with_response("Noted"),
+    );
+    let (writer, writer_mock) = agent_step(
+        "writer",
+        MockLlmClient::new()
+            .with_tool_call("memory_get", json!({"key": "capital"}))
+            .with_response("The capital is Paris"),
+    );
+    let workflow = with_context().step(researcher).step(writer).build();
+    let context = workflow.context().unwrap().clone();
+
+    let run = Runtime::new().execute(workflow).await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(
+        tool_result(&writer_mock, 1),
+        json!({"key": "capital", "found": true, "value": "Paris"})
+    );
+    assert_eq!(
+        context.read().unwrap().memory_get("capital"),
+        Some(&json!("Paris"))
+    );
+    // The tools are offered without being registered
+    let offered = writer_mock.get_calls()[0].tools.clone().unwrap();
+    assert!(offered
+        .iter()
+        .any(|tool| tool["function"]["name"] == "memory_get"));
+}
+
+#[tokio::test]
+async fn test_transform_reads_what_a_sub_workflow_stored() {
+    let sub_workflow = SubWorkflowStep::new("lookup".to_string(), || {
+        let (agent, _) = agent_step(
+            "sub_researcher",
+            MockLlmClient::new()
+                .with_tool_call("memory_set", json!({"key": "population", "value": 68}))
+                .with_response("Noted"),
+        );
+        Workflow::builder()
+            .name("lookup".to_string())
+            .step(agent)
+            .build()
+    });
+    let report = TransformStep::new_with_memory(
+        "report".to_string(),
+        |_, memory| json!({ "population": memory.get("population") }),
+    );
+    let workflow = with_context()
+        .step(Box::new(sub_workflow))
+        .step(Box::new(report))
+        .build();
+
+    let run = Runtime::new().execute(workflow).await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(run.final_output, Some(json!({"population": 68})));
+}
+
+#[tokio::test]
+async fn test_parallel_branches_append_without_losing_writes() {
+    let branch = |name: &str, finding: &str| {
+        agent_step(
+            name,
+            MockLlmClient::new()
+                .with_tool_call(
+                    "memory_set",
+                    json!({"key": "findings", "value": finding, "append": true}),
+                )
+                .with_response("Added"),
+        )
+        .0
+    };
+    let parallel = ParallelStep::new(
+        "research".to_string(),
+        (0..8)
+            .map(|i| branch(&format!("branch_{}", i), &format!("finding {}", i)))
+            .collect(),
+    );
+    let workflow = with_context().step(Box::new(parallel)).build();
+    let context = workflow.context().unwrap().clone();
+
+    let run = Runtime::new().execute(workflow).await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    let context = context.read().unwrap();
+    let mut findings: Vec<&str> = context.memory["findings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| f.as_str().unwrap())
+        .collect();
+    findings.sort_unstable();
+    let expected: Vec<String> = (0..8).map(|i| format!("finding {}", i)).collect();
+    assert_eq!(findings, expected);
+}
+
+#[tokio::test]
+async fn test_memory_survives_a_checkpoint() {
+    let (researcher, _) = agent_step(
+        "researcher",
+        MockLlmClient::new()
+            .with_tool_call(
+                "memory_set",
+                json!({"key": "sources", "value": ["atlas", "wiki"]}),
+            )
+            .with_response("Noted"),
+    );
+    let workflow = with_context().step(researcher).build();
+    let context = workflow.context().unwrap().clone();
+    let run = Runtime::new().execute(workflow).await;
+    assert_eq!(run.state, WorkflowState::Completed);
+
+    let bytes = context.read().unwrap().export(PersistFormat::Json).unwrap();
+    let restored = WorkflowContext::import(&bytes).unwrap();
+    assert_eq!(
+        restored.memory_get("sources"),
+        Some(&json!(["atlas", "wiki"]))
+    );
+
+    // A resumed workflow's agents see the restored scratchpad
+    let (writer, writer_mock) = agent_step(
+        "writer",
+        MockLlmClient::new()
+            .with_tool_call("memory_get", json!({"key": "sources"}))
+            .with_response("Cited"),
+    );
+    let resumed = Workflow::builder()
+        .name("memory".to_string())
+        .with_restored_context(restored)
+        .step(writer)
+        .initial_input(json!("France"))
+        .build();
+    let run = Runtime::new().execute(resumed).await;
+
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(
+        tool_result(&writer_mock, 1)["value"],
+        json!(["atlas", "wiki"])
+    );
+}
@@ -0,0 +1,200 @@
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::*;
+use futures::StreamExt;
+use serde_json::json;
+use std::sync::Arc;
+
+fn researcher(mock: MockLlmClient) -> Box<dyn workflow::Step> {
+    let mut registry = ToolRegistry::new();
+    registry.register(NativeTool::new(
+        "search",
+        "Search the index",
+        json!({"type": "object", "properties": {}}),
+        |_params| async move { Ok(ToolResult::success(json!({"hits": 3}), 1.0)) },
+    ));
+    let agent = Agent::new(
+        AgentConfig::builder("researcher")
+            .tools(Arc::new(registry))
+            .build(),
+    )
+    .with_client(Arc::new(mock));
+    Box::new(AgentStep::from_agent(agent, "research".to_string()))
+}
+
+fn research_workflow(mock: MockLlmClient) -> Workflow {
+    Workflow::builder()
+        .name("research".to_string())
+        .step(researcher(mock))
+        .step(Box::new(TransformStep::new("shout".to_string(), |v| {
+            json!(v["response"].as_str().unwrap_or_default().to_uppercase())
+        })))
+        .initial_input(json!("find rust crates"))
+        .build()
+}
+
+fn searching_mock() -> MockLlmClient {
+    MockLlmClient::new()
+        .with_tool_call("search", json!({"q": "rust crates"}))
+        .with_response("Found three crates")
+}
+
+/// Position of the first update matching `pred`
+fn position(updates: &[WorkflowUpdate], pred: impl Fn(&WorkflowUpdate) -> bool) -> usize {
+    updates.iter().position(pred).expect("update not found")
+}
+
+#[tokio::test]
+async fn test_updates_arrive_in_order() {
+    let runtime = Runtime::new();
+    let updates: Vec<WorkflowUpdate> = runtime
+        .execute_streaming(research_workflow(searching_mock()))
+        .collect()
+        .await;
+
+    let started = position(
+        &updates,
+        |u| matches!(u, WorkflowUpdate::StepStarted { step_index: 0, step_name } if step_name == "research"),
+    );
+    let tool = position(
+        &updates,
+        |u| matches!(u, WorkflowUpdate::ToolCall { name, args } if name == "search" && args == &json!({"q": "rust crates"})),
+    );
+    let chunk = position(&updates, |u| matches!(u, WorkflowUpdate::LlmChunk { .. }));
+    let completed = position(&updates, |u| {
+        matches!(u, WorkflowUpdate::StepCompleted { step_index: 0, .. })
+    });
+    let next_started = position(&updates, |u| {
+        matches!(u, WorkflowUpdate::StepStarted { step_index: 1, .. })
+    });
+    assert!(started < tool && tool < chunk && chunk < completed && completed < next_started);
+
+    let text: String = updates
+        .iter()
+        .filter_map(|u| match u {
+            WorkflowUpdate::LlmChunk { agent, text } => {
+                assert_eq!(agent, "researcher");
+                Some(text.as_str())
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(text.trim(), "Found three crates");
+
+    match &updates[updates.len() - 2] {
+        WorkflowUpdate::StepCompleted {
+            step_index: 1,
+            output,
+            ..
+        } => assert_eq!(output, &json!("FOUND THREE CRATES")),
+        other => panic!("expected the last step to complete, got {:?}", other),
+    }
+    match updates.last().unwrap() {
+        WorkflowUpdate::WorkflowCompleted { run } => {
+            assert_eq!(run.state, WorkflowState::Completed);
+            assert_eq!(run.final_output, Some(json!("FOUND THREE CRATES")));
+        }
+        other => panic!("expected the run to complete last, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_concurrent_runs_only_see_their_own_events() {
+    let runtime = Runtime::new();
+    let quiet = Workflow::builder()
+        .name("quiet".to_string())
+        .step(Box::new(TransformStep::new("noop".to_string(), |v| v)))
+        .initial_input(json!(1))
+        .build();
+    let quiet_id = quiet.id.clone();
+
+    let (researching, quiet): (Vec<_>, Vec<_>) = tokio::join!(
+        runtime
+            .execute_streaming(research_workflow(searching_mock()))
+            .collect(),
+        runtime.execute_streaming(quiet).collect(),
+    );
+
+    assert!(researching
+        .iter()
+        .any(|u| matches!(u, WorkflowUpdate::ToolCall { .. })));
+    assert_eq!(quiet.len(), 3, "{:?}", quiet);
+    assert!(matches!(
+        &quiet[0],
+        WorkflowUpdate::StepStarted { step_name, .. } if step_name == "noop"
+    ));
+    assert!(matches!(
+        &quiet[1],
+        WorkflowUpdate::StepCompleted { output, .. } if output == &json!(1)
+    ));
+    match &quiet[2] {
+        WorkflowUpdate::WorkflowCompleted { run } => assert_eq!(run.workflow_id, quiet_id),
+        other => panic!("expected completion, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_failed_run_ends_stream_with_error() {
+    let runtime = Runtime::new();
+    let mock = MockLlmClient::new().error_on_call(0);
+    let mut updates = runtime.execute_streaming(research_workflow(mock));
+
+    let mut last = None;
+    while let Some(update) = updates.next().await {
+        last = Some(update);
+    }
+    // Polling past the end stays finished
+    assert!(updates.next().await.is_none());
+
+    match last.unwrap() {
+        WorkflowUpdate::WorkflowFailed { error, run } => {
+            assert!(error.contains("network"), "{}", error);
+            assert_eq!(run.state, WorkflowState::Failed);
+            assert_eq!(run.failure.unwrap().step_name, "research");
+        }
+        other => panic!("expected failure, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_into_run_returns_finished_run() {
+    let runtime = Runtime::new();
+    let run = runtime
+        .execute_streaming(research_workflow(searching_mock()))
+        .into_run()
+        .await;
+    assert_eq!(run.state, WorkflowState::Completed);
+    assert_eq!(run.steps.len(), 2);
+}
+
+#[tokio::test]
+async fn test_sub_workflow_chunks_count_toward_parent() {
+    let outer = Workflow::builder()
+        .name("outer".to_string())
+        .step(Box::new(SubWorkflowStep::new("nested".to_string(), || {
+            Workflow::builder()
+                .name("inner".to_string())
+                .step(researcher(searching_mock()))
+                .build()
+        })))
+        .initial_input(json!("go"))
+        .build();
+
+    let runtime = Runtime::new();
+    let updates: Vec<WorkflowUpdate> = runtime.execute_streaming(outer).collect().await;
+
+    assert!(updates
+        .iter()
+        .any(|u| matches!(u, WorkflowUpdate::ToolCall { name, .. } if name == "search")));
+    assert!(updates
+        .iter()
+        .any(|u| matches!(u, WorkflowUpdate::LlmChunk { .. })));
+    // The nested run's steps are folded into the step that started it
+    let steps: Vec<&str> = updates
+        .iter()
+        .filter_map(|u| match u {
+            WorkflowUpdate::StepStarted { step_name, .. } => Some(step_name.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(steps, vec!["nested"]);
+}
@@ -0,0 +1,148 @@
+/// Tests for running one workflow definition many times
+use agent_runtime::error::WorkflowErrorCode;
+use agent_runtime::llm::MockLlmClient;
+use agent_runtime::workflow::{ExecutionContext, StepInput, StepResult, StepType};
+use agent_runtime::*;
+use async_trait::async_trait;
+use serde_json::json;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn agent(name: &str, mock: Arc<MockLlmClient>) -> Box<AgentStep> {
+    Box::new(AgentStep::from_agent(
+        Agent::new(AgentConfig::builder(name).system_prompt("You help").build()).with_client(mock),
+        name.to_string(),
+    ))
+}
+
+fn digest(mock: Arc<MockLlmClient>) -> Workflow {
+    Workflow::builder()
+        .name("digest".to_string())
+        .step(agent("reader", mock.clone()))
+        .step(agent("writer", mock))
+        .with_chat_history(Arc::new(TokenBudgetManager::new(24_000, 3.0)))
+        .build()
+}
+
+#[tokio::test]
+async fn test_template_runs_concurrently_in_isolation() {
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec!["noted"; 20]));
+    let template = digest(mock.clone());
+    let inputs: Vec<_> = (0..10).map(|i| json!(format!("topic {}", i))).collect();
+
+    let runtime = Runtime::new();
+    let runs = runtime.execute_many(&template, inputs, 10).await.unwrap();
+
+    assert_eq!(runs.len(), 10);
+    for (i, run) in runs.iter().enumerate() {
+        assert_eq!(run.state, WorkflowState::Completed);
+        assert!(run.workflow_id.starts_with("digest_"));
+        assert_eq!(run.steps[0].input, json!(format!("topic {}", i)));
+    }
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let started: HashSet<String> = runtime
+        .event_stream()
+        .all()
+        .into_iter()
+        .filter(|e| e.scope == EventScope::Workflow && e.event_type == EventType::Started)
+        .map(|e| e.workflow_id)
+        .collect();
+    assert_eq!(started.len(), 10);
+
+    // Every request, including the writer's with the shared history, holds
+    // one run's topic only
+    assert_eq!(mock.call_count(), 20);
+    for request in mock.get_calls() {
+        let topics: HashSet<String> = request
+            .messages
+            .iter()
+            .map(|m| m.content.text().into_owned())
+            .filter(|text| text.contains("topic "))
+            .collect();
+        assert_eq!(topics.len(), 1, "{:?}", request.messages);
+    }
+
+    // The template itself never ran
+    assert!(template
+        .checkpoint_context()
+        .unwrap()
+        .chat_history
+        .is_empty());
+    assert_eq!(template.state, WorkflowState::Pending);
+}
+
+#[tokio::test]
+async fn test_instances_start_from_the_template_context() {
+    let template = digest(Arc::new(MockLlmClient::with_responses_vec(vec!["ok"; 2])));
+    template
+        .context()
+        .unwrap()
+        .write()
+        .unwrap()
+        .memory
+        .insert("tone".to_string(), json!("formal"));
+
+    let first = template.instantiate().unwrap();
+    let second = template.instantiate().unwrap();
+    assert_ne!(first.id, second.id);
+    assert_eq!(first.steps.len(), 2);
+
+    first
+        .context()
+        .unwrap()
+        .write()
+        .unwrap()
+        .memory
+        .insert("tone".to_string(), json!("casual"));
+    let second_context = second.checkpoint_context().unwrap();
+    assert_eq!(second_context.memory["tone"], "formal");
+    assert_eq!(second_context.metadata.workflow_id, second.id);
+}
+
+struct Opaque;
+
+#[async_trait]
+impl workflow::Step for Opaque {
+    async fn execute_with_context(
+        &self,
+        input: StepInput,
+        _ctx: ExecutionContext<'_>,
+    ) -> StepResult {
+        TransformStep::new("opaque".to_string(), |v| v)
+            .execute(input)
+            .await
+    }
+
+    fn name(&self) -> &str {
+        "opaque"
+    }
+
+    fn step_type(&self) -> StepType {
+        StepType::Transform
+    }
+}
+
+#[tokio::test]
+async fn test_template_with_an_uncloneable_step_runs_nothing() {
+    let mock = Arc::new(MockLlmClient::with_responses_vec(vec!["ok"]));
+    let template = Workflow::builder()
+        .name("mixed".to_string())
+        .step(agent("reader", mock.clone()))
+        .step(Box::new(ParallelStep::new(
+            "fan_out".to_string(),
+            vec![Box::new(Opaque)],
+        )))
+        .build();
+
+    let error = Runtime::new()
+        .execute_many(&template, vec![json!("a"), json!("b")], 2)
+        .await
+        .unwrap_err();
+
+    assert_eq!(error.code, WorkflowErrorCode::StepNotCloneable);
+    assert_eq!(error.step_index, Some(1));
+    assert_eq!(error.step_id.as_deref(), Some("fan_out"));
+    assert_eq!(mock.call_count(), 0);
+}